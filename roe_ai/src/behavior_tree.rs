@@ -0,0 +1,504 @@
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum NodeStatus {
+    Success,
+    Failure,
+    Running,
+}
+
+type ConditionFn<C> = Box<dyn Fn(&C) -> bool>;
+type ActionFn<C> = Box<dyn FnMut(&mut C, Duration) -> NodeStatus>;
+
+enum Decorator {
+    Inverter,
+    AlwaysSucceed,
+    AlwaysFail,
+    Repeat { times: u32, count: u32 },
+    Retry { times: u32, count: u32 },
+    Timeout { limit: Duration, elapsed: Duration },
+}
+
+enum NodeKind<C> {
+    Sequence {
+        children: Vec<BehaviorNode<C>>,
+        current: usize,
+    },
+    Selector {
+        children: Vec<BehaviorNode<C>>,
+        current: usize,
+    },
+    Decorator {
+        decorator: Decorator,
+        child: Box<BehaviorNode<C>>,
+    },
+    Condition(ConditionFn<C>),
+    Action(ActionFn<C>),
+}
+
+pub struct BehaviorNode<C> {
+    name: String,
+    kind: NodeKind<C>,
+    status: Option<NodeStatus>,
+}
+
+impl<C> BehaviorNode<C> {
+    // Ticks the children in order until one fails or is running. When a child is running, the
+    // next tick resumes from it.
+    pub fn sequence(children: Vec<BehaviorNode<C>>) -> Self {
+        Self::new(
+            "Sequence",
+            NodeKind::Sequence {
+                children,
+                current: 0,
+            },
+        )
+    }
+
+    // Ticks the children in order until one succeeds or is running. When a child is running,
+    // the next tick resumes from it.
+    pub fn selector(children: Vec<BehaviorNode<C>>) -> Self {
+        Self::new(
+            "Selector",
+            NodeKind::Selector {
+                children,
+                current: 0,
+            },
+        )
+    }
+
+    pub fn condition<F>(condition: F) -> Self
+    where
+        F: Fn(&C) -> bool + 'static,
+    {
+        Self::new("Condition", NodeKind::Condition(Box::new(condition)))
+    }
+
+    pub fn action<F>(action: F) -> Self
+    where
+        F: FnMut(&mut C, Duration) -> NodeStatus + 'static,
+    {
+        Self::new("Action", NodeKind::Action(Box::new(action)))
+    }
+
+    pub fn named<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn invert(self) -> Self {
+        self.decorate("Inverter", Decorator::Inverter)
+    }
+
+    pub fn always_succeed(self) -> Self {
+        self.decorate("AlwaysSucceed", Decorator::AlwaysSucceed)
+    }
+
+    pub fn always_fail(self) -> Self {
+        self.decorate("AlwaysFail", Decorator::AlwaysFail)
+    }
+
+    // Runs the node until it succeeds the given number of times, one run per tick. Fails as soon
+    // as the node fails.
+    pub fn repeat(self, times: u32) -> Self {
+        assert!(times > 0, "The repetition count must be higher than 0");
+        self.decorate("Repeat", Decorator::Repeat { times, count: 0 })
+    }
+
+    // Runs the node until it succeeds, at most the given number of times, one run per tick.
+    pub fn retry(self, times: u32) -> Self {
+        assert!(times > 0, "The retry count must be higher than 0");
+        self.decorate("Retry", Decorator::Retry { times, count: 0 })
+    }
+
+    // Fails and resets the node if it is still running after the time limit.
+    pub fn timeout(self, limit: Duration) -> Self {
+        self.decorate(
+            "Timeout",
+            Decorator::Timeout {
+                limit,
+                elapsed: Duration::ZERO,
+            },
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Status returned by the last tick, or None if the node wasn't ticked since the last reset.
+    pub fn status(&self) -> Option<NodeStatus> {
+        self.status
+    }
+
+    pub fn tick(&mut self, context: &mut C, dt: Duration) -> NodeStatus {
+        let status = match &mut self.kind {
+            NodeKind::Sequence { children, current } => {
+                tick_composite(children, current, context, dt, NodeStatus::Success)
+            }
+            NodeKind::Selector { children, current } => {
+                tick_composite(children, current, context, dt, NodeStatus::Failure)
+            }
+            NodeKind::Decorator { decorator, child } => {
+                tick_decorator(decorator, child, context, dt)
+            }
+            NodeKind::Condition(condition) => {
+                if condition(context) {
+                    NodeStatus::Success
+                } else {
+                    NodeStatus::Failure
+                }
+            }
+            NodeKind::Action(action) => action(context, dt),
+        };
+        self.status = Some(status);
+        status
+    }
+
+    pub fn reset(&mut self) {
+        self.status = None;
+        match &mut self.kind {
+            NodeKind::Sequence { children, current } | NodeKind::Selector { children, current } => {
+                *current = 0;
+                for child in children.iter_mut() {
+                    child.reset();
+                }
+            }
+            NodeKind::Decorator { decorator, child } => {
+                match decorator {
+                    Decorator::Repeat { count, .. } | Decorator::Retry { count, .. } => *count = 0,
+                    Decorator::Timeout { elapsed, .. } => *elapsed = Duration::ZERO,
+                    _ => (),
+                }
+                child.reset();
+            }
+            NodeKind::Condition(_) | NodeKind::Action(_) => (),
+        }
+    }
+
+    pub fn snapshot(&self) -> BehaviorNodeSnapshot {
+        let children = match &self.kind {
+            NodeKind::Sequence { children, .. } | NodeKind::Selector { children, .. } => {
+                children.iter().map(|child| child.snapshot()).collect()
+            }
+            NodeKind::Decorator { child, .. } => vec![child.snapshot()],
+            NodeKind::Condition(_) | NodeKind::Action(_) => Vec::new(),
+        };
+        BehaviorNodeSnapshot {
+            name: self.name.clone(),
+            status: self.status,
+            children,
+        }
+    }
+
+    fn new(name: &str, kind: NodeKind<C>) -> Self {
+        Self {
+            name: String::from(name),
+            kind,
+            status: None,
+        }
+    }
+
+    fn decorate(self, name: &str, decorator: Decorator) -> Self {
+        Self::new(
+            name,
+            NodeKind::Decorator {
+                decorator,
+                child: Box::new(self),
+            },
+        )
+    }
+}
+
+impl<C> std::fmt::Debug for BehaviorNode<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.snapshot())
+    }
+}
+
+// Shared logic of sequences and selectors: the children are ticked in order while they return
+// the continue status.
+fn tick_composite<C>(
+    children: &mut [BehaviorNode<C>],
+    current: &mut usize,
+    context: &mut C,
+    dt: Duration,
+    continue_status: NodeStatus,
+) -> NodeStatus {
+    while *current < children.len() {
+        let status = children[*current].tick(context, dt);
+        if status == NodeStatus::Running {
+            return status;
+        }
+        if status != continue_status {
+            *current = 0;
+            return status;
+        }
+        *current += 1;
+    }
+    *current = 0;
+    continue_status
+}
+
+fn tick_decorator<C>(
+    decorator: &mut Decorator,
+    child: &mut BehaviorNode<C>,
+    context: &mut C,
+    dt: Duration,
+) -> NodeStatus {
+    let status = child.tick(context, dt);
+    match decorator {
+        Decorator::Inverter => match status {
+            NodeStatus::Success => NodeStatus::Failure,
+            NodeStatus::Failure => NodeStatus::Success,
+            NodeStatus::Running => NodeStatus::Running,
+        },
+        Decorator::AlwaysSucceed => match status {
+            NodeStatus::Running => NodeStatus::Running,
+            _ => NodeStatus::Success,
+        },
+        Decorator::AlwaysFail => match status {
+            NodeStatus::Running => NodeStatus::Running,
+            _ => NodeStatus::Failure,
+        },
+        Decorator::Repeat { times, count } => match status {
+            NodeStatus::Success => {
+                *count += 1;
+                if *count >= *times {
+                    *count = 0;
+                    NodeStatus::Success
+                } else {
+                    NodeStatus::Running
+                }
+            }
+            NodeStatus::Failure => {
+                *count = 0;
+                NodeStatus::Failure
+            }
+            NodeStatus::Running => NodeStatus::Running,
+        },
+        Decorator::Retry { times, count } => match status {
+            NodeStatus::Failure => {
+                *count += 1;
+                if *count >= *times {
+                    *count = 0;
+                    NodeStatus::Failure
+                } else {
+                    NodeStatus::Running
+                }
+            }
+            NodeStatus::Success => {
+                *count = 0;
+                NodeStatus::Success
+            }
+            NodeStatus::Running => NodeStatus::Running,
+        },
+        Decorator::Timeout { limit, elapsed } => {
+            if status != NodeStatus::Running {
+                *elapsed = Duration::ZERO;
+                return status;
+            }
+            *elapsed += dt;
+            if *elapsed >= *limit {
+                *elapsed = Duration::ZERO;
+                child.reset();
+                NodeStatus::Failure
+            } else {
+                NodeStatus::Running
+            }
+        }
+    }
+}
+
+// Debug view of a behavior tree, with the status of each node after the last tick.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct BehaviorNodeSnapshot {
+    pub name: String,
+    pub status: Option<NodeStatus>,
+    pub children: Vec<BehaviorNodeSnapshot>,
+}
+
+// Behavior tree operating on a context of type C, meant to be ticked from the fixed update step.
+pub struct BehaviorTree<C> {
+    root: BehaviorNode<C>,
+}
+
+impl<C> BehaviorTree<C> {
+    pub fn new(root: BehaviorNode<C>) -> Self {
+        Self { root }
+    }
+
+    pub fn root(&self) -> &BehaviorNode<C> {
+        &self.root
+    }
+
+    pub fn tick(&mut self, context: &mut C, dt: Duration) -> NodeStatus {
+        self.root.tick(context, dt)
+    }
+
+    pub fn reset(&mut self) {
+        self.root.reset();
+    }
+
+    pub fn snapshot(&self) -> BehaviorNodeSnapshot {
+        self.root.snapshot()
+    }
+}
+
+impl<C> std::fmt::Debug for BehaviorTree<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BehaviorTree")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const DT: Duration = Duration::from_millis(100);
+
+    #[derive(Debug, Default)]
+    struct Agent {
+        enemy_visible: bool,
+        ammo: u32,
+        log: Vec<&'static str>,
+    }
+
+    fn log_action(entry: &'static str, status: NodeStatus) -> BehaviorNode<Agent> {
+        BehaviorNode::action(move |agent: &mut Agent, _| {
+            agent.log.push(entry);
+            status
+        })
+        .named(entry)
+    }
+
+    fn guard_tree() -> BehaviorTree<Agent> {
+        BehaviorTree::new(BehaviorNode::selector(vec![
+            BehaviorNode::sequence(vec![
+                BehaviorNode::condition(|agent: &Agent| agent.enemy_visible).named("enemy"),
+                BehaviorNode::action(|agent: &mut Agent, _| {
+                    if agent.ammo == 0 {
+                        return NodeStatus::Failure;
+                    }
+                    agent.ammo -= 1;
+                    agent.log.push("shoot");
+                    NodeStatus::Success
+                })
+                .named("shoot"),
+            ]),
+            log_action("patrol", NodeStatus::Success),
+        ]))
+    }
+
+    #[test]
+    fn selector_and_sequence() {
+        let mut tree = guard_tree();
+        let mut agent = Agent {
+            enemy_visible: false,
+            ammo: 1,
+            ..Agent::default()
+        };
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Success));
+        agent.enemy_visible = true;
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Success));
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Success));
+        expect_that!(&agent.log, eq(vec!["patrol", "shoot", "patrol"]));
+        expect_that!(&agent.ammo, eq(0));
+    }
+
+    #[test]
+    fn running_child_is_resumed() {
+        let mut remaining = 2;
+        let mut tree = BehaviorTree::new(BehaviorNode::sequence(vec![
+            log_action("first", NodeStatus::Success),
+            BehaviorNode::action(move |agent: &mut Agent, _| {
+                agent.log.push("second");
+                if remaining == 0 {
+                    NodeStatus::Success
+                } else {
+                    remaining -= 1;
+                    NodeStatus::Running
+                }
+            }),
+        ]));
+        let mut agent = Agent::default();
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Success));
+        expect_that!(&tree.tick(&mut agent, DT), eq(NodeStatus::Success));
+        expect_that!(
+            &agent.log,
+            eq(vec![
+                "first", "second", "second", "second", "first", "second"
+            ])
+        );
+    }
+
+    #[test]
+    fn decorators() {
+        let mut agent = Agent::default();
+        let mut inverted = log_action("a", NodeStatus::Success).invert();
+        expect_that!(&inverted.tick(&mut agent, DT), eq(NodeStatus::Failure));
+        let mut succeed = log_action("a", NodeStatus::Failure).always_succeed();
+        expect_that!(&succeed.tick(&mut agent, DT), eq(NodeStatus::Success));
+        let mut fail = log_action("a", NodeStatus::Success).always_fail();
+        expect_that!(&fail.tick(&mut agent, DT), eq(NodeStatus::Failure));
+        let mut running = log_action("a", NodeStatus::Running).invert();
+        expect_that!(&running.tick(&mut agent, DT), eq(NodeStatus::Running));
+    }
+
+    #[test]
+    fn repeat_and_retry() {
+        let mut agent = Agent::default();
+        let mut repeat = log_action("repeat", NodeStatus::Success).repeat(3);
+        expect_that!(&repeat.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&repeat.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&repeat.tick(&mut agent, DT), eq(NodeStatus::Success));
+        expect_that!(&agent.log.len(), eq(3));
+
+        let mut retry = log_action("retry", NodeStatus::Failure).retry(2);
+        expect_that!(&retry.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&retry.tick(&mut agent, DT), eq(NodeStatus::Failure));
+        expect_that!(&retry.tick(&mut agent, DT), eq(NodeStatus::Running));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut agent = Agent::default();
+        let mut node = log_action("wait", NodeStatus::Running).timeout(Duration::from_millis(250));
+        expect_that!(&node.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&node.tick(&mut agent, DT), eq(NodeStatus::Running));
+        expect_that!(&node.tick(&mut agent, DT), eq(NodeStatus::Failure));
+        expect_that!(&node.tick(&mut agent, DT), eq(NodeStatus::Running));
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut tree = guard_tree();
+        let mut agent = Agent::default();
+        tree.tick(&mut agent, DT);
+
+        let snapshot = tree.snapshot();
+        expect_that!(&snapshot.name, eq(String::from("Selector")));
+        expect_that!(&snapshot.status, eq(Some(NodeStatus::Success)));
+        expect_that!(&snapshot.children[0].status, eq(Some(NodeStatus::Failure)));
+        expect_that!(
+            &snapshot.children[0].children[0].name,
+            eq(String::from("enemy"))
+        );
+        expect_that!(&snapshot.children[0].children[1].status, eq(None));
+        expect_that!(&snapshot.children[1].name, eq(String::from("patrol")));
+
+        let serialized = ron::to_string(&snapshot).unwrap();
+        let deserialized: BehaviorNodeSnapshot = ron::from_str(&serialized).unwrap();
+        expect_that!(&deserialized, eq(snapshot));
+
+        tree.reset();
+        expect_that!(&tree.root().status(), eq(None));
+    }
+}
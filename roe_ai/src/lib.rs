@@ -0,0 +1,5 @@
+mod behavior_tree;
+pub use behavior_tree::*;
+
+mod state_machine;
+pub use state_machine::*;
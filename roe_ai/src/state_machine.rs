@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+type HookFn<C> = Box<dyn FnMut(&mut C)>;
+type UpdateFn<S, C> = Box<dyn FnMut(&mut C, Duration) -> Option<S>>;
+
+struct StateHooks<S, C> {
+    enter: Option<HookFn<C>>,
+    exit: Option<HookFn<C>>,
+    update: Option<UpdateFn<S, C>>,
+}
+
+impl<S, C> Default for StateHooks<S, C> {
+    fn default() -> Self {
+        Self {
+            enter: None,
+            exit: None,
+            update: None,
+        }
+    }
+}
+
+// Finite state machine whose states are values of type S, usually a fieldless enum, operating
+// on a context of type C. It is meant to be updated from the fixed update step.
+pub struct StateMachine<S, C> {
+    hooks: HashMap<S, StateHooks<S, C>>,
+    current: S,
+    previous: Option<S>,
+    time_in_state: Duration,
+    transition_count: u64,
+    started: bool,
+}
+
+impl<S, C> StateMachine<S, C>
+where
+    S: Copy + Eq + Hash,
+{
+    pub fn new(initial_state: S) -> Self {
+        Self {
+            hooks: HashMap::new(),
+            current: initial_state,
+            previous: None,
+            time_in_state: Duration::ZERO,
+            transition_count: 0,
+            started: false,
+        }
+    }
+
+    pub fn on_enter<F>(mut self, state: S, hook: F) -> Self
+    where
+        F: FnMut(&mut C) + 'static,
+    {
+        self.hooks.entry(state).or_default().enter = Some(Box::new(hook));
+        self
+    }
+
+    pub fn on_exit<F>(mut self, state: S, hook: F) -> Self
+    where
+        F: FnMut(&mut C) + 'static,
+    {
+        self.hooks.entry(state).or_default().exit = Some(Box::new(hook));
+        self
+    }
+
+    // The update hook returns the state to transition to, if any. Returning the current state
+    // makes the machine exit and enter it again.
+    pub fn on_update<F>(mut self, state: S, hook: F) -> Self
+    where
+        F: FnMut(&mut C, Duration) -> Option<S> + 'static,
+    {
+        self.hooks.entry(state).or_default().update = Some(Box::new(hook));
+        self
+    }
+
+    pub fn current(&self) -> S {
+        self.current
+    }
+
+    pub fn previous(&self) -> Option<S> {
+        self.previous
+    }
+
+    pub fn time_in_state(&self) -> Duration {
+        self.time_in_state
+    }
+
+    // Calls the update hook of the current state and performs the transition it requests. The
+    // enter hook of the initial state is called on the first update.
+    pub fn update(&mut self, context: &mut C, dt: Duration) {
+        self.start(context);
+        self.time_in_state += dt;
+        let next = self
+            .hooks
+            .get_mut(&self.current)
+            .and_then(|hooks| hooks.update.as_mut())
+            .and_then(|update| update(context, dt));
+        if let Some(next) = next {
+            self.transition(context, next);
+        }
+    }
+
+    pub fn transition(&mut self, context: &mut C, next: S) {
+        self.start(context);
+        if let Some(exit) = self
+            .hooks
+            .get_mut(&self.current)
+            .and_then(|hooks| hooks.exit.as_mut())
+        {
+            exit(context);
+        }
+        self.previous = Some(self.current);
+        self.current = next;
+        self.time_in_state = Duration::ZERO;
+        self.transition_count += 1;
+        self.enter_current(context);
+    }
+
+    pub fn snapshot(&self) -> StateMachineSnapshot<S> {
+        StateMachineSnapshot {
+            current: self.current,
+            previous: self.previous,
+            time_in_state: self.time_in_state,
+            transition_count: self.transition_count,
+        }
+    }
+
+    fn start(&mut self, context: &mut C) {
+        if !self.started {
+            self.started = true;
+            self.enter_current(context);
+        }
+    }
+
+    fn enter_current(&mut self, context: &mut C) {
+        if let Some(enter) = self
+            .hooks
+            .get_mut(&self.current)
+            .and_then(|hooks| hooks.enter.as_mut())
+        {
+            enter(context);
+        }
+    }
+}
+
+impl<S, C> std::fmt::Debug for StateMachine<S, C>
+where
+    S: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StateMachine")
+            .field("current", &self.current)
+            .field("previous", &self.previous)
+            .field("time_in_state", &self.time_in_state)
+            .field("transition_count", &self.transition_count)
+            .finish()
+    }
+}
+
+// Debug view of a state machine.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct StateMachineSnapshot<S> {
+    pub current: S,
+    pub previous: Option<S>,
+    pub time_in_state: Duration,
+    pub transition_count: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const DT: Duration = Duration::from_millis(100);
+
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+    enum Guard {
+        Idle,
+        Chase,
+        Attack,
+    }
+
+    #[derive(Debug, Default)]
+    struct Context {
+        distance: f32,
+        log: Vec<String>,
+    }
+
+    fn guard_machine() -> StateMachine<Guard, Context> {
+        StateMachine::new(Guard::Idle)
+            .on_enter(Guard::Idle, |c: &mut Context| {
+                c.log.push("enter idle".into())
+            })
+            .on_exit(Guard::Idle, |c: &mut Context| {
+                c.log.push("exit idle".into())
+            })
+            .on_enter(Guard::Chase, |c: &mut Context| {
+                c.log.push("enter chase".into())
+            })
+            .on_update(Guard::Idle, |c: &mut Context, _| {
+                if c.distance < 10. {
+                    Some(Guard::Chase)
+                } else {
+                    None
+                }
+            })
+            .on_update(Guard::Chase, |c: &mut Context, _| {
+                if c.distance < 1. {
+                    Some(Guard::Attack)
+                } else if c.distance >= 10. {
+                    Some(Guard::Idle)
+                } else {
+                    None
+                }
+            })
+    }
+
+    #[test]
+    fn transitions() {
+        let mut machine = guard_machine();
+        let mut context = Context {
+            distance: 20.,
+            ..Context::default()
+        };
+        machine.update(&mut context, DT);
+        expect_that!(&machine.current(), eq(Guard::Idle));
+
+        context.distance = 5.;
+        machine.update(&mut context, DT);
+        expect_that!(&machine.current(), eq(Guard::Chase));
+        expect_that!(&machine.previous(), eq(Some(Guard::Idle)));
+
+        context.distance = 0.5;
+        machine.update(&mut context, DT);
+        expect_that!(&machine.current(), eq(Guard::Attack));
+
+        // Attack has no hooks: the machine stays there until an explicit transition.
+        machine.update(&mut context, DT);
+        expect_that!(&machine.current(), eq(Guard::Attack));
+        machine.transition(&mut context, Guard::Idle);
+
+        expect_that!(
+            &context.log,
+            eq(vec![
+                String::from("enter idle"),
+                String::from("exit idle"),
+                String::from("enter chase"),
+                String::from("enter idle"),
+            ])
+        );
+    }
+
+    #[test]
+    fn time_in_state() {
+        let mut machine = guard_machine();
+        let mut context = Context {
+            distance: 20.,
+            ..Context::default()
+        };
+        machine.update(&mut context, DT);
+        machine.update(&mut context, DT);
+        expect_that!(&machine.time_in_state(), eq(Duration::from_millis(200)));
+        context.distance = 5.;
+        machine.update(&mut context, DT);
+        expect_that!(&machine.time_in_state(), eq(Duration::ZERO));
+    }
+
+    #[test]
+    fn transition_before_update() {
+        let mut machine = guard_machine();
+        let mut context = Context::default();
+        machine.transition(&mut context, Guard::Chase);
+        expect_that!(
+            &context.log,
+            eq(vec![
+                String::from("enter idle"),
+                String::from("exit idle"),
+                String::from("enter chase"),
+            ])
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut machine = guard_machine();
+        let mut context = Context {
+            distance: 5.,
+            ..Context::default()
+        };
+        machine.update(&mut context, DT);
+        machine.update(&mut context, DT);
+
+        let snapshot = machine.snapshot();
+        expect_that!(
+            &snapshot,
+            eq(StateMachineSnapshot {
+                current: Guard::Chase,
+                previous: Some(Guard::Idle),
+                time_in_state: DT,
+                transition_count: 1,
+            })
+        );
+
+        let serialized = ron::to_string(&snapshot).unwrap();
+        let deserialized: StateMachineSnapshot<Guard> = ron::from_str(&serialized).unwrap();
+        expect_that!(&deserialized, eq(snapshot));
+    }
+}
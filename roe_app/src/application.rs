@@ -1,8 +1,17 @@
-use super::{ApplicationState, ControlFlow};
+use super::{
+    set_crash_frame_stats, ApplicationState, ControlFlow, DeterminismAudit, EventBus, FrameStats,
+    LifecycleEvent, LifecyclePolicy, TimeScale, VirtualGamepad, VirtualGamepadInput,
+};
 
+use roe_jobs as jobs;
 use roe_os as os;
 
-use std::{collections::BTreeMap, ops::DerefMut};
+use std::{cell::RefCell, collections::BTreeMap, ops::DerefMut, rc::Rc};
+
+type ApplicationStateBox<ErrorType, CustomEventType> =
+    Box<dyn ApplicationState<ErrorType, CustomEventType>>;
+
+type LifecycleHookBox<ErrorType> = Box<dyn FnMut(LifecycleEvent) -> Result<(), ErrorType>>;
 
 pub struct Application<ErrorType, CustomEventType>
 where
@@ -10,10 +19,15 @@ where
     CustomEventType: 'static,
 {
     keyboard_state: KeyboardState,
-    fixed_update_period: std::time::Duration,
-    variable_update_min_period: std::time::Duration,
-    last_fixed_update_time: std::time::Instant,
-    last_variable_update_time: std::time::Instant,
+    update_timer: UpdateTimer,
+    lifecycle_policy: LifecyclePolicy,
+    lifecycle_hooks: Vec<LifecycleHookBox<ErrorType>>,
+    suspended: bool,
+    focused: bool,
+    virtual_gamepad: Option<Rc<RefCell<VirtualGamepad>>>,
+    determinism_audit: Option<Rc<RefCell<DeterminismAudit>>>,
+    event_bus: EventBus,
+    time_scale: TimeScale,
     state_stack: Vec<Box<dyn ApplicationState<ErrorType, CustomEventType>>>,
 }
 
@@ -40,18 +54,104 @@ where
             Some(v) => std::time::Duration::from_secs_f64(1. / v as f64),
             None => std::time::Duration::from_secs_f64(0.),
         };
-        let current_time = std::time::Instant::now();
 
         Self {
             keyboard_state: KeyboardState::new(),
-            fixed_update_period,
-            variable_update_min_period,
-            last_fixed_update_time: current_time,
-            last_variable_update_time: current_time,
+            update_timer: UpdateTimer::new(fixed_update_period, variable_update_min_period),
+            lifecycle_policy: LifecyclePolicy::default(),
+            lifecycle_hooks: Vec::new(),
+            suspended: false,
+            focused: true,
+            virtual_gamepad: None,
+            determinism_audit: None,
+            event_bus: EventBus::new(),
+            time_scale: TimeScale::new(),
             state_stack: Vec::new(),
         }
     }
 
+    // Bus shared by the application states, delivered before each fixed update. States can
+    // keep a clone to publish events and create subscribers.
+    pub fn event_bus(&self) -> &EventBus {
+        &self.event_bus
+    }
+
+    // Time scale shared by the application states, advanced after each fixed update. The dt
+    // passed to the fixed updates is not scaled, states scale it for their channel.
+    pub fn time_scale(&self) -> &TimeScale {
+        &self.time_scale
+    }
+
+    pub fn lifecycle_policy(&self) -> &LifecyclePolicy {
+        &self.lifecycle_policy
+    }
+
+    pub fn set_lifecycle_policy(&mut self, lifecycle_policy: LifecyclePolicy) {
+        lifecycle_policy.validate();
+        self.lifecycle_policy = lifecycle_policy;
+    }
+
+    pub fn with_lifecycle_policy(mut self, lifecycle_policy: LifecyclePolicy) -> Self {
+        self.set_lifecycle_policy(lifecycle_policy);
+        self
+    }
+
+    // Registers a hook called on suspension, resumption and focus changes, before the current
+    // state. Used for engine-wide reactions, such as pausing the audio mixer while suspended.
+    pub fn add_lifecycle_hook<F>(&mut self, hook: F)
+    where
+        F: FnMut(LifecycleEvent) -> Result<(), ErrorType> + 'static,
+    {
+        self.lifecycle_hooks.push(Box::new(hook));
+    }
+
+    pub fn with_lifecycle_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(LifecycleEvent) -> Result<(), ErrorType> + 'static,
+    {
+        self.add_lifecycle_hook(hook);
+        self
+    }
+
+    // Touches handled by the virtual gamepad are delivered to the states as key and axis events,
+    // instead of touch events. The states can keep a clone to draw the gamepad.
+    pub fn set_virtual_gamepad(&mut self, virtual_gamepad: Option<Rc<RefCell<VirtualGamepad>>>) {
+        self.virtual_gamepad = virtual_gamepad;
+    }
+
+    pub fn with_virtual_gamepad(mut self, virtual_gamepad: Rc<RefCell<VirtualGamepad>>) -> Self {
+        self.set_virtual_gamepad(Some(virtual_gamepad));
+        self
+    }
+
+    // Each fixed update is recorded by the audit, after the current state has filled the tick
+    // record in ApplicationState::on_determinism_audit. The states can keep a clone to save the
+    // log, e.g. along with a replay.
+    pub fn set_determinism_audit(
+        &mut self,
+        determinism_audit: Option<Rc<RefCell<DeterminismAudit>>>,
+    ) {
+        self.determinism_audit = determinism_audit;
+    }
+
+    pub fn with_determinism_audit(
+        mut self,
+        determinism_audit: Rc<RefCell<DeterminismAudit>>,
+    ) -> Self {
+        self.set_determinism_audit(Some(determinism_audit));
+        self
+    }
+
+    fn run_lifecycle_hooks(
+        hooks: &mut [LifecycleHookBox<ErrorType>],
+        event: LifecycleEvent,
+    ) -> Result<(), ErrorType> {
+        for hook in hooks.iter_mut() {
+            hook(event)?;
+        }
+        Ok(())
+    }
+
     #[cfg(test)]
     fn create_event_loop() -> os::EventLoop<CustomEventType> {
         use os::EventLoopAnyThread;
@@ -63,11 +163,31 @@ where
         os::EventLoop::<CustomEventType>::with_user_event()
     }
 
+    pub(crate) fn state_count(&self) -> usize {
+        self.state_stack.len()
+    }
+
+    // Moves the update timer back, as if the time had passed since the last update.
+    pub(crate) fn advance_time(&mut self, dt: std::time::Duration) {
+        self.update_timer.advance(dt);
+    }
+
+    pub(crate) fn reset_update_timer(&mut self) {
+        self.update_timer.reset();
+    }
+
+    // The update timer time only moves when waiting for the next fixed update, so that headless
+    // runs perform exactly one fixed update per tick without sleeping.
+    #[cfg(test)]
+    pub(crate) fn use_manual_clock(&mut self) {
+        self.update_timer.use_manual_clock();
+    }
+
     fn default_error_handler<E: std::fmt::Display>(error: E) {
         eprintln!("The application shut down due to an error ({})", error);
     }
 
-    fn push_state(
+    pub(crate) fn push_state(
         &mut self,
         mut state: Box<dyn ApplicationState<ErrorType, CustomEventType>>,
     ) -> Result<(), ErrorType> {
@@ -101,15 +221,13 @@ where
         Ok(())
     }
 
-    pub fn run(
-        mut self,
-        initialization_fn: fn(
+    // The initialization function can capture values shared with the lifecycle hooks.
+    pub fn run<F>(mut self, initialization_fn: F)
+    where
+        F: FnOnce(
             &os::EventLoop<CustomEventType>,
-        ) -> Result<
-            Box<dyn ApplicationState<ErrorType, CustomEventType>>,
-            ErrorType,
-        >,
-    ) {
+        ) -> Result<ApplicationStateBox<ErrorType, CustomEventType>, ErrorType>,
+    {
         let event_loop = Self::create_event_loop();
 
         let initial_state = match initialization_fn(&event_loop) {
@@ -125,32 +243,109 @@ where
             return;
         }
 
-        let current_time = std::time::Instant::now();
-        self.last_fixed_update_time = current_time;
-        self.last_variable_update_time = current_time;
+        self.update_timer.reset();
 
         event_loop.run(
             move |event, _, control_flow| match self.handle_event(event) {
                 Ok(flow) => *control_flow = flow,
                 Err(e) => {
-                    match self.state_stack.last_mut() {
-                        Some(state) => {
-                            let state = state.deref_mut();
-                            if !state.handle_error(&e) {
-                                Self::default_error_handler(e);
-                            }
-                        }
-                        None => {
-                            Self::default_error_handler(e);
-                        }
-                    }
+                    self.handle_error(e);
                     *control_flow = os::ControlFlow::Exit;
                 }
             },
         );
     }
 
-    fn handle_event(
+    // Runs the application without an event loop: no window or device events are generated, and
+    // only the update callbacks of the current state are called. The fixed update frequency
+    // determines the tick rate, and the thread sleeps between ticks.
+    pub fn run_headless(
+        mut self,
+        initialization_fn: fn()
+            -> Result<ApplicationStateBox<ErrorType, CustomEventType>, ErrorType>,
+    ) {
+        let initial_state = match initialization_fn() {
+            Ok(s) => s,
+            Err(e) => {
+                Self::default_error_handler(e);
+                return;
+            }
+        };
+
+        if let Err(e) = self.push_state(initial_state) {
+            Self::default_error_handler(e);
+            return;
+        }
+
+        self.update_timer.reset();
+
+        loop {
+            match self.tick_headless() {
+                Ok(os::ControlFlow::Exit) => break,
+                Ok(_) => self.update_timer.wait_for_next_fixed_update(),
+                Err(e) => {
+                    self.handle_error(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn tick_headless(&mut self) -> Result<os::ControlFlow, ErrorType> {
+        Self::pump_job_callbacks();
+        let control_flow = match self.state_stack.last_mut() {
+            Some(state) => self.update_timer.update(
+                state.deref_mut(),
+                &self.event_bus,
+                &self.time_scale,
+                self.determinism_audit.as_deref(),
+            )?,
+            None => ControlFlow::Exit,
+        };
+        self.apply_control_flow(control_flow)
+    }
+
+    fn deliver_virtual_gamepad_input(
+        state: &mut dyn ApplicationState<ErrorType, CustomEventType>,
+        window_id: os::WindowId,
+        device_id: os::DeviceId,
+        input: VirtualGamepadInput,
+    ) -> Result<(), ErrorType> {
+        match input {
+            VirtualGamepadInput::KeyPressed(key_code) => {
+                state.on_key_pressed(window_id, device_id, 0, Some(key_code), false, false)
+            }
+            VirtualGamepadInput::KeyReleased(key_code) => {
+                state.on_key_released(window_id, device_id, 0, Some(key_code), false)
+            }
+            VirtualGamepadInput::AxisMoved(axis, value) => {
+                state.on_axis_moved(window_id, device_id, axis, value)
+            }
+        }
+    }
+
+    // Runs the main thread callbacks of the global job system once per frame, before the update.
+    fn pump_job_callbacks() {
+        if let Some(job_system) = jobs::JobSystem::try_global() {
+            job_system.pump_main_thread();
+        }
+    }
+
+    fn handle_error(&mut self, e: ErrorType) {
+        match self.state_stack.last_mut() {
+            Some(state) => {
+                let state = state.deref_mut();
+                if !state.handle_error(&e) {
+                    Self::default_error_handler(e);
+                }
+            }
+            None => {
+                Self::default_error_handler(e);
+            }
+        }
+    }
+
+    pub(crate) fn handle_event(
         &mut self,
         event: os::Event<CustomEventType>,
     ) -> Result<os::ControlFlow, ErrorType> {
@@ -168,29 +363,37 @@ where
                 }
 
                 os::Event::Suspended => {
+                    self.suspended = true;
+                    Self::run_lifecycle_hooks(
+                        &mut self.lifecycle_hooks,
+                        LifecycleEvent::Suspended,
+                    )?;
                     state.on_suspended()?;
                 }
 
                 os::Event::Resumed => {
+                    // Avoid catching up with the updates skipped while suspended.
+                    if self.suspended && self.lifecycle_policy.pause_when_suspended {
+                        self.update_timer.reset();
+                    }
+                    self.suspended = false;
+                    Self::run_lifecycle_hooks(&mut self.lifecycle_hooks, LifecycleEvent::Resumed)?;
                     state.on_resumed()?;
                 }
 
                 os::Event::MainEventsCleared => {
-                    let current_time = std::time::Instant::now();
-
-                    while current_time - self.last_fixed_update_time >= self.fixed_update_period {
-                        state.on_fixed_update(self.fixed_update_period)?;
-                        control_flow = state.requested_control_flow();
-                        self.last_fixed_update_time += self.fixed_update_period;
-                    }
-
-                    let time_since_last_variable_update =
-                        current_time - self.last_variable_update_time;
-                    if time_since_last_variable_update > self.variable_update_min_period {
-                        state.on_variable_update(time_since_last_variable_update)?;
-                        self.last_variable_update_time = current_time;
+                    Self::pump_job_callbacks();
+                    if !self
+                        .lifecycle_policy
+                        .updates_paused(self.suspended, self.focused)
+                    {
+                        control_flow = self.update_timer.update(
+                            state,
+                            &self.event_bus,
+                            &self.time_scale,
+                            self.determinism_audit.as_deref(),
+                        )?;
                     }
-
                     state.on_main_events_cleared()?;
                 }
 
@@ -218,6 +421,17 @@ where
                     }
 
                     os::WindowEvent::Focused(focused) => {
+                        // Avoid catching up with the updates skipped while unfocused.
+                        if focused && !self.focused && self.lifecycle_policy.pause_when_unfocused {
+                            self.update_timer.reset();
+                        }
+                        self.focused = focused;
+                        let lifecycle_event = if focused {
+                            LifecycleEvent::FocusGained
+                        } else {
+                            LifecycleEvent::FocusLost
+                        };
+                        Self::run_lifecycle_hooks(&mut self.lifecycle_hooks, lifecycle_event)?;
                         if focused {
                             state.on_focus_gained(window_id)?;
                         } else {
@@ -330,14 +544,32 @@ where
                     }
 
                     os::WindowEvent::Touch(touch) => {
-                        state.on_touch(
-                            window_id,
-                            touch.device_id,
-                            touch.phase,
-                            touch.location,
-                            touch.force,
-                            touch.id,
-                        )?;
+                        let gamepad_input = self.virtual_gamepad.as_ref().and_then(|gamepad| {
+                            let mut gamepad = gamepad.borrow_mut();
+                            gamepad
+                                .on_touch(touch.phase, touch.location, touch.id)
+                                .then(|| gamepad.take_input())
+                        });
+                        match gamepad_input {
+                            Some(input) => {
+                                for input in input {
+                                    Self::deliver_virtual_gamepad_input(
+                                        state,
+                                        window_id,
+                                        touch.device_id,
+                                        input,
+                                    )?;
+                                }
+                            }
+                            None => state.on_touch(
+                                window_id,
+                                touch.device_id,
+                                touch.phase,
+                                touch.location,
+                                touch.force,
+                                touch.id,
+                            )?,
+                        }
                     }
 
                     os::WindowEvent::AxisMotion {
@@ -420,6 +652,30 @@ where
             }
         }
 
+        let control_flow = self.apply_control_flow(control_flow)?;
+        if control_flow != os::ControlFlow::Poll {
+            return Ok(control_flow);
+        }
+
+        if self.suspended && self.lifecycle_policy.pause_when_suspended {
+            return Ok(os::ControlFlow::Wait);
+        }
+
+        if !self.focused {
+            if let Some(period) = self.lifecycle_policy.unfocused_min_period() {
+                return Ok(os::ControlFlow::WaitUntil(
+                    std::time::Instant::now() + period,
+                ));
+            }
+        }
+
+        Ok(control_flow)
+    }
+
+    fn apply_control_flow(
+        &mut self,
+        control_flow: ControlFlow<ErrorType, CustomEventType>,
+    ) -> Result<os::ControlFlow, ErrorType> {
         match control_flow {
             ControlFlow::Exit => {
                 while !self.state_stack.is_empty() {
@@ -448,6 +704,130 @@ where
     }
 }
 
+// Time source of the update timer. The manual clock is only available to the tests.
+enum UpdateClock {
+    System,
+    #[cfg(test)]
+    Manual(std::time::Instant),
+}
+
+impl UpdateClock {
+    fn now(&self) -> std::time::Instant {
+        match self {
+            Self::System => std::time::Instant::now(),
+            #[cfg(test)]
+            Self::Manual(time) => *time,
+        }
+    }
+}
+
+struct UpdateTimer {
+    clock: UpdateClock,
+    fixed_update_period: std::time::Duration,
+    variable_update_min_period: std::time::Duration,
+    last_fixed_update_time: std::time::Instant,
+    last_variable_update_time: std::time::Instant,
+    frame_stats: FrameStats,
+}
+
+impl UpdateTimer {
+    fn new(
+        fixed_update_period: std::time::Duration,
+        variable_update_min_period: std::time::Duration,
+    ) -> Self {
+        let current_time = std::time::Instant::now();
+        Self {
+            clock: UpdateClock::System,
+            fixed_update_period,
+            variable_update_min_period,
+            last_fixed_update_time: current_time,
+            last_variable_update_time: current_time,
+            frame_stats: FrameStats::default(),
+        }
+    }
+
+    #[cfg(test)]
+    fn use_manual_clock(&mut self) {
+        self.clock = UpdateClock::Manual(std::time::Instant::now());
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        let current_time = self.clock.now();
+        self.last_fixed_update_time = current_time;
+        self.last_variable_update_time = current_time;
+    }
+
+    fn advance(&mut self, dt: std::time::Duration) {
+        let move_back = |t: std::time::Instant| {
+            t.checked_sub(dt)
+                .expect("The time advance exceeds the range of the system clock")
+        };
+        self.last_fixed_update_time = move_back(self.last_fixed_update_time);
+        self.last_variable_update_time = move_back(self.last_variable_update_time);
+    }
+
+    fn update<ErrorType, CustomEventType>(
+        &mut self,
+        state: &mut dyn ApplicationState<ErrorType, CustomEventType>,
+        event_bus: &EventBus,
+        time_scale: &TimeScale,
+        determinism_audit: Option<&RefCell<DeterminismAudit>>,
+    ) -> Result<ControlFlow<ErrorType, CustomEventType>, ErrorType>
+    where
+        ErrorType: std::fmt::Display + std::error::Error + 'static,
+        CustomEventType: 'static,
+    {
+        let mut control_flow = ControlFlow::Continue;
+        let current_time = self.clock.now();
+
+        while current_time - self.last_fixed_update_time >= self.fixed_update_period {
+            event_bus.deliver();
+            state.on_fixed_update(self.fixed_update_period)?;
+            if let Some(audit) = determinism_audit {
+                let mut record = {
+                    let audit = audit.borrow();
+                    audit.begin_tick(audit.next_tick())
+                };
+                state.on_determinism_audit(&mut record)?;
+                let divergence = audit.borrow_mut().end_tick(record).cloned();
+                if let Some(divergence) = divergence {
+                    state.on_determinism_divergence(&divergence)?;
+                }
+            }
+            time_scale.advance(self.fixed_update_period);
+            control_flow = state.requested_control_flow();
+            self.last_fixed_update_time += self.fixed_update_period;
+            self.frame_stats.fixed_update_count += 1;
+        }
+
+        let time_since_last_variable_update = current_time - self.last_variable_update_time;
+        if time_since_last_variable_update > self.variable_update_min_period {
+            state.on_variable_update(time_since_last_variable_update)?;
+            self.last_variable_update_time = current_time;
+            self.frame_stats.frame_count += 1;
+            self.frame_stats.last_frame_duration = time_since_last_variable_update;
+        }
+        set_crash_frame_stats(&self.frame_stats);
+
+        Ok(control_flow)
+    }
+
+    fn wait_for_next_fixed_update(&mut self) {
+        let next_fixed_update_time = self.last_fixed_update_time + self.fixed_update_period;
+        match &mut self.clock {
+            UpdateClock::System => {
+                let current_time = std::time::Instant::now();
+                if next_fixed_update_time > current_time {
+                    std::thread::sleep(next_fixed_update_time - current_time);
+                }
+            }
+            #[cfg(test)]
+            UpdateClock::Manual(time) => *time = std::cmp::max(*time, next_fixed_update_time),
+        }
+    }
+}
+
 fn get_key_index(key_code: os::KeyCode) -> usize {
     match key_code {
         os::KeyCode::Key0 => 0,
@@ -700,4 +1080,107 @@ mod tests {
     fn run() {
         Application::new(10, Some(10)).run(|_event_queue| Ok(Box::new(MyAppState {})));
     }
+
+    thread_local! {
+        static HEADLESS_LOG: std::cell::RefCell<Vec<String>> =
+            const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    fn headless_log(entry: &str) {
+        HEADLESS_LOG.with(|log| log.borrow_mut().push(String::from(entry)));
+    }
+
+    fn take_headless_log() -> Vec<String> {
+        HEADLESS_LOG.with(|log| log.replace(Vec::new()))
+    }
+
+    #[derive(Debug)]
+    struct HeadlessState {
+        name: &'static str,
+        remaining_updates: u32,
+        push_child: bool,
+    }
+
+    impl ApplicationState<MyError, ()> for HeadlessState {
+        fn on_start(&mut self) -> Result<(), MyError> {
+            headless_log(&format!("{} start", self.name));
+            Ok(())
+        }
+
+        fn on_end(&mut self) -> Result<(), MyError> {
+            headless_log(&format!("{} end", self.name));
+            Ok(())
+        }
+
+        fn on_fixed_update(&mut self, _dt: std::time::Duration) -> Result<(), MyError> {
+            headless_log(&format!("{} update", self.name));
+            self.remaining_updates = self.remaining_updates.saturating_sub(1);
+            Ok(())
+        }
+
+        fn requested_control_flow(&mut self) -> ControlFlow<MyError, ()> {
+            if self.push_child {
+                self.push_child = false;
+                ControlFlow::PushState(Box::new(HeadlessState {
+                    name: "child",
+                    remaining_updates: 1,
+                    push_child: false,
+                }))
+            } else if self.remaining_updates == 0 {
+                ControlFlow::PopState
+            } else {
+                ControlFlow::Continue
+            }
+        }
+    }
+
+    #[test]
+    fn run_headless() {
+        take_headless_log();
+        let mut app = Application::<MyError, ()>::new(1000, None);
+        app.use_manual_clock();
+        app.run_headless(|| {
+            Ok(Box::new(HeadlessState {
+                name: "root",
+                remaining_updates: 3,
+                push_child: false,
+            }))
+        });
+        assert_eq!(
+            take_headless_log(),
+            vec![
+                "root start",
+                "root update",
+                "root update",
+                "root update",
+                "root end"
+            ]
+        );
+    }
+
+    #[test]
+    fn run_headless_state_stack() {
+        take_headless_log();
+        let mut app = Application::<MyError, ()>::new(1000, None);
+        app.use_manual_clock();
+        app.run_headless(|| {
+            Ok(Box::new(HeadlessState {
+                name: "root",
+                remaining_updates: 2,
+                push_child: true,
+            }))
+        });
+        assert_eq!(
+            take_headless_log(),
+            vec![
+                "root start",
+                "root update",
+                "child start",
+                "child update",
+                "child end",
+                "root update",
+                "root end"
+            ]
+        );
+    }
 }
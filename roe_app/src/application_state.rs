@@ -1,3 +1,5 @@
+use super::{Divergence, TickRecord};
+
 use roe_os as os;
 
 pub enum ControlFlow<ErrorType, CustomEventType> {
@@ -312,6 +314,18 @@ where
         Ok(())
     }
 
+    // Adds the simulation state to the record of the fixed update that just ran. Only called if
+    // the application has a determinism audit.
+    fn on_determinism_audit(&mut self, _record: &mut TickRecord) -> Result<(), ErrorType> {
+        Ok(())
+    }
+
+    // Called once, at the first tick differing from the reference log of the audit.
+    fn on_determinism_divergence(&mut self, divergence: &Divergence) -> Result<(), ErrorType> {
+        eprintln!("{}", divergence);
+        Ok(())
+    }
+
     fn on_variable_update(&mut self, _dt: std::time::Duration) -> Result<(), ErrorType> {
         Ok(())
     }
@@ -0,0 +1,323 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct FrameStats {
+    pub frame_count: u64,
+    pub fixed_update_count: u64,
+    pub last_frame_duration: std::time::Duration,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CrashReport {
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+    // Oldest line first.
+    pub log: Vec<String>,
+    pub context: BTreeMap<String, String>,
+    pub frame_stats: FrameStats,
+}
+
+impl CrashReport {
+    // Captures the current crash context. The backtrace is captured even if RUST_BACKTRACE
+    // isn't set.
+    pub fn capture<S: Into<String>>(message: S, location: Option<String>) -> Self {
+        let state = lock_state();
+        Self {
+            message: message.into(),
+            location,
+            thread: std::thread::current().name().map(String::from),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            log: state.log.iter().cloned().collect(),
+            context: state.context.clone(),
+            frame_stats: state.frame_stats,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Crash report\n\nMessage: {}\n", self.message);
+        if let Some(location) = &self.location {
+            text.push_str(&format!("Location: {}\n", location));
+        }
+        text.push_str(&format!(
+            "Thread: {}\n",
+            self.thread.as_deref().unwrap_or("<unnamed>")
+        ));
+        text.push_str(&format!(
+            "\nFrame stats:\n  frame count: {}\n  fixed update count: {}\n  last frame duration: {:?}\n",
+            self.frame_stats.frame_count,
+            self.frame_stats.fixed_update_count,
+            self.frame_stats.last_frame_duration
+        ));
+        text.push_str("\nContext:\n");
+        for (key, value) in self.context.iter() {
+            text.push_str(&format!("  {}: {}\n", key, value));
+        }
+        text.push_str("\nRecent log:\n");
+        for line in self.log.iter() {
+            text.push_str(&format!("  {}\n", line));
+        }
+        text.push_str(&format!("\nBacktrace:\n{}\n", self.backtrace));
+        text
+    }
+
+    // Writes the report to a new file in the directory, creating it if necessary. Returns the
+    // path of the file.
+    pub fn write_to_directory<P: AsRef<Path>>(&self, directory: P) -> std::io::Result<PathBuf> {
+        let directory = directory.as_ref();
+        std::fs::create_dir_all(directory)?;
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|t| t.as_millis())
+            .unwrap_or(0);
+        let path = directory.join(format!("crash_{}_{}.txt", timestamp, std::process::id()));
+        std::fs::write(&path, self.to_text())?;
+        Ok(path)
+    }
+}
+
+// Called after the report has been written, e.g. to upload it. The path is None if the
+// report file couldn't be written.
+pub type CrashReportCallback = Box<dyn Fn(&CrashReport, Option<&Path>) + Send + Sync>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CrashReporterDescriptor {
+    pub directory: PathBuf,
+    // Number of recent log lines included in the reports.
+    pub log_capacity: usize,
+}
+
+impl Default for CrashReporterDescriptor {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("crash_reports"),
+            log_capacity: 256,
+        }
+    }
+}
+
+struct CrashState {
+    log: VecDeque<String>,
+    log_capacity: usize,
+    context: BTreeMap<String, String>,
+    frame_stats: FrameStats,
+}
+
+static CRASH_STATE: Mutex<CrashState> = Mutex::new(CrashState {
+    log: VecDeque::new(),
+    log_capacity: 256,
+    context: BTreeMap::new(),
+    frame_stats: FrameStats {
+        frame_count: 0,
+        fixed_update_count: 0,
+        last_frame_duration: std::time::Duration::ZERO,
+    },
+});
+
+// A panic while the state is locked must not prevent the report from being written.
+fn lock_state() -> MutexGuard<'static, CrashState> {
+    CRASH_STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Installs a panic hook writing a crash report for every panic, before running the previously
+// installed hook.
+pub fn install_crash_reporter(
+    desc: &CrashReporterDescriptor,
+    callback: Option<CrashReportCallback>,
+) {
+    assert!(
+        desc.log_capacity > 0,
+        "The crash log capacity must be higher than 0"
+    );
+    {
+        let mut state = lock_state();
+        state.log_capacity = desc.log_capacity;
+        while state.log.len() > state.log_capacity {
+            state.log.pop_front();
+        }
+    }
+    let directory = desc.directory.clone();
+    let callback = callback.map(Arc::new);
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = if let Some(s) = info.payload().downcast_ref::<&str>() {
+            String::from(*s)
+        } else if let Some(s) = info.payload().downcast_ref::<String>() {
+            s.clone()
+        } else {
+            String::from("Unknown panic payload")
+        };
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+        let report = CrashReport::capture(message, location);
+        let path = match report.write_to_directory(&directory) {
+            Ok(path) => {
+                eprintln!("Crash report written to {}", path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("Failed to write the crash report ({})", e);
+                None
+            }
+        };
+        if let Some(callback) = &callback {
+            callback(&report, path.as_deref());
+        }
+        previous_hook(info);
+    }));
+}
+
+// Adds a line to the recent log included in crash reports. The oldest lines are dropped when
+// the capacity is exceeded.
+pub fn crash_log<S: Into<String>>(line: S) {
+    let mut state = lock_state();
+    if state.log.len() >= state.log_capacity {
+        state.log.pop_front();
+    }
+    state.log.push_back(line.into());
+}
+
+// Sets a key included in crash reports, e.g. the GPU adapter information. A None value removes
+// the key.
+pub fn set_crash_context<K: Into<String>, V: Into<String>>(key: K, value: Option<V>) {
+    let mut state = lock_state();
+    match value {
+        Some(value) => state.context.insert(key.into(), value.into()),
+        None => state.context.remove(&key.into()),
+    };
+}
+
+// Updated by the application every frame.
+pub fn set_crash_frame_stats(stats: &FrameStats) {
+    lock_state().frame_stats = *stats;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use serial_test::serial;
+
+    fn clear_state() {
+        let mut state = lock_state();
+        state.log.clear();
+        state.log_capacity = 256;
+        state.context.clear();
+        state.frame_stats = FrameStats::default();
+    }
+
+    #[test]
+    #[serial]
+    fn capture() {
+        clear_state();
+        install_crash_reporter(
+            &CrashReporterDescriptor {
+                log_capacity: 2,
+                ..CrashReporterDescriptor::default()
+            },
+            None,
+        );
+        let _ = std::panic::take_hook();
+        crash_log("first");
+        crash_log("second");
+        crash_log("third");
+        set_crash_context("gpu_adapter", Some("Test adapter"));
+        set_crash_context("level", Some("forest"));
+        set_crash_context::<_, String>("level", None);
+        let stats = FrameStats {
+            frame_count: 10,
+            fixed_update_count: 20,
+            last_frame_duration: std::time::Duration::from_millis(16),
+        };
+        set_crash_frame_stats(&stats);
+
+        let report = CrashReport::capture("Something broke", None);
+        expect_that!(&report.message, eq(String::from("Something broke")));
+        expect_that!(
+            &report.log,
+            eq(vec![String::from("second"), String::from("third")])
+        );
+        expect_that!(&report.context.len(), eq(1));
+        expect_that!(
+            &report.context["gpu_adapter"],
+            eq(String::from("Test adapter"))
+        );
+        expect_that!(&report.frame_stats, eq(stats));
+        expect_that!(!report.backtrace.is_empty());
+
+        let text = report.to_text();
+        expect_that!(text.contains("Message: Something broke"));
+        expect_that!(text.contains("gpu_adapter: Test adapter"));
+        expect_that!(text.contains("  third"));
+        clear_state();
+    }
+
+    #[test]
+    #[serial]
+    fn panic_hook() {
+        clear_state();
+        let directory = std::env::temp_dir().join(format!(
+            "roe_app_crash_reporter_test_{}",
+            std::process::id()
+        ));
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let callback_reports = reports.clone();
+        let thread = std::thread::current().name().map(String::from);
+        install_crash_reporter(
+            &CrashReporterDescriptor {
+                directory: directory.clone(),
+                ..CrashReporterDescriptor::default()
+            },
+            // Tests running in parallel may panic too.
+            Some(Box::new(move |report, path| {
+                if report.thread != thread {
+                    return;
+                }
+                callback_reports
+                    .lock()
+                    .unwrap()
+                    .push((report.clone(), path.map(PathBuf::from)));
+            })),
+        );
+        crash_log("Loading level");
+        let result = std::panic::catch_unwind(|| panic!("Out of bananas ({})", 3));
+        let _ = std::panic::take_hook();
+        expect_that!(result.is_err());
+
+        let reports = reports.lock().unwrap();
+        expect_that!(&reports.len(), eq(1));
+        let (report, path) = &reports[0];
+        expect_that!(&report.message, eq(String::from("Out of bananas (3)")));
+        expect_that!(report
+            .location
+            .as_ref()
+            .unwrap()
+            .contains("crash_reporter.rs"));
+        expect_that!(&report.log, eq(vec![String::from("Loading level")]));
+        let text = std::fs::read_to_string(path.as_ref().unwrap()).unwrap();
+        expect_that!(text.contains("Out of bananas (3)"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+        clear_state();
+    }
+
+    #[test]
+    #[should_panic(expected = "The crash log capacity must be higher than 0")]
+    fn invalid_log_capacity() {
+        install_crash_reporter(
+            &CrashReporterDescriptor {
+                log_capacity: 0,
+                ..CrashReporterDescriptor::default()
+            },
+            None,
+        );
+    }
+}
@@ -0,0 +1,467 @@
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+#[derive(Debug)]
+pub enum DeterminismError {
+    IoError(std::io::Error),
+    SerializationError(bincode::Error),
+}
+
+impl std::fmt::Display for DeterminismError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::SerializationError(e) => write!(f, "Serialization error ({})", e),
+        }
+    }
+}
+
+impl std::error::Error for DeterminismError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::SerializationError(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for DeterminismError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<bincode::Error> for DeterminismError {
+    fn from(e: bincode::Error) -> Self {
+        Self::SerializationError(e)
+    }
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct FieldRecord {
+    pub name: String,
+    pub hash: u64,
+    // Readable value, only stored in detailed mode.
+    pub value: Option<String>,
+}
+
+// Hashes of the simulation state at a tick, e.g. positions and random generator states. Fields
+// are hashed from their serialized form, so floating point values are compared bitwise.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TickRecord {
+    tick: u32,
+    detailed: bool,
+    fields: Vec<FieldRecord>,
+}
+
+impl TickRecord {
+    pub fn new(tick: u32, detailed: bool) -> Self {
+        Self {
+            tick,
+            detailed,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn fields(&self) -> &[FieldRecord] {
+        &self.fields
+    }
+
+    pub fn is_detailed(&self) -> bool {
+        self.detailed
+    }
+
+    // Fields must be added in the same order in every run.
+    pub fn add<S: Into<String>, T: Serialize + std::fmt::Debug>(
+        &mut self,
+        name: S,
+        value: &T,
+    ) -> Result<(), DeterminismError> {
+        let data = bincode::serialize(value)?;
+        self.add_bytes(name, &data, || format!("{:?}", value));
+        Ok(())
+    }
+
+    // Adds a field hashed from already serialized data. The readable value is only computed in
+    // detailed mode.
+    pub fn add_bytes<S: Into<String>, F: FnOnce() -> String>(
+        &mut self,
+        name: S,
+        data: &[u8],
+        value: F,
+    ) {
+        let value = if self.detailed { Some(value()) } else { None };
+        self.fields.push(FieldRecord {
+            name: name.into(),
+            hash: fnv1a(FNV_OFFSET_BASIS, data),
+            value,
+        });
+    }
+
+    // Hash of the whole state, small enough to be exchanged every tick by lockstep peers.
+    pub fn hash(&self) -> u64 {
+        self.fields.iter().fold(FNV_OFFSET_BASIS, |hash, field| {
+            let hash = fnv1a(hash, field.name.as_bytes());
+            fnv1a(hash, &field.hash.to_le_bytes())
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FieldDivergence {
+    pub name: String,
+    // Value of the field if known, otherwise its hash. None if the field is missing.
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Divergence {
+    pub tick: u32,
+    // Empty if only the tick hashes are known.
+    pub fields: Vec<FieldDivergence>,
+}
+
+impl Divergence {
+    fn new(expected: &TickRecord, actual: &TickRecord) -> Self {
+        let describe = |field: Option<&FieldRecord>| {
+            field.map(|f| match &f.value {
+                Some(value) => value.clone(),
+                None => format!("<hash {:016x}>", f.hash),
+            })
+        };
+        let mut fields = Vec::new();
+        let mut names: Vec<&str> = expected.fields.iter().map(|f| f.name.as_str()).collect();
+        for field in actual.fields.iter() {
+            if !names.contains(&field.name.as_str()) {
+                names.push(&field.name);
+            }
+        }
+        for name in names {
+            let expected_field = expected.fields.iter().find(|f| f.name == name);
+            let actual_field = actual.fields.iter().find(|f| f.name == name);
+            if expected_field.map(|f| f.hash) != actual_field.map(|f| f.hash) {
+                fields.push(FieldDivergence {
+                    name: String::from(name),
+                    expected: describe(expected_field),
+                    actual: describe(actual_field),
+                });
+            }
+        }
+        Self {
+            tick: expected.tick,
+            fields,
+        }
+    }
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Simulation diverged at tick {}", self.tick)?;
+        for field in self.fields.iter() {
+            write!(
+                f,
+                "\n  {}: expected {}, got {}",
+                field.name,
+                field.expected.as_deref().unwrap_or("<missing>"),
+                field.actual.as_deref().unwrap_or("<missing>")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Tick records of a run, in increasing tick order. Can be saved along with a replay to check
+// that playing it back produces the same simulation.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct DeterminismLog {
+    ticks: Vec<TickRecord>,
+}
+
+impl DeterminismLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ticks(&self) -> &[TickRecord] {
+        &self.ticks
+    }
+
+    pub fn tick(&self, tick: u32) -> Option<&TickRecord> {
+        self.ticks
+            .binary_search_by_key(&tick, |record| record.tick)
+            .ok()
+            .map(|i| &self.ticks[i])
+    }
+
+    pub fn push(&mut self, record: TickRecord) {
+        if let Some(last) = self.ticks.last() {
+            assert!(
+                record.tick > last.tick,
+                "The ticks must be recorded in increasing order"
+            );
+        }
+        self.ticks.push(record);
+    }
+
+    // Compares the ticks recorded in both logs, ignoring the ticks recorded in only one of them.
+    pub fn first_divergence(&self, other: &DeterminismLog) -> Option<Divergence> {
+        self.ticks.iter().find_map(|expected| {
+            let actual = other.tick(expected.tick)?;
+            if expected.hash() != actual.hash() {
+                Some(Divergence::new(expected, actual))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DeterminismError> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeterminismError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DeterminismError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DeterminismError> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DeterminismAuditDescriptor {
+    // Stores readable field values, to make divergences easier to investigate, at the cost of
+    // memory and time.
+    pub detailed: bool,
+}
+
+impl Default for DeterminismAuditDescriptor {
+    fn default() -> Self {
+        Self { detailed: true }
+    }
+}
+
+// Records the state of each fixed step of the simulation and, if a reference log is set (e.g.
+// the one of the original run when playing back a replay), stops at the first divergent tick.
+#[derive(Debug)]
+pub struct DeterminismAudit {
+    desc: DeterminismAuditDescriptor,
+    log: DeterminismLog,
+    reference: Option<DeterminismLog>,
+    divergence: Option<Divergence>,
+}
+
+impl DeterminismAudit {
+    pub fn new(desc: &DeterminismAuditDescriptor) -> Self {
+        Self {
+            desc: *desc,
+            log: DeterminismLog::new(),
+            reference: None,
+            divergence: None,
+        }
+    }
+
+    pub fn with_reference(desc: &DeterminismAuditDescriptor, reference: DeterminismLog) -> Self {
+        Self {
+            reference: Some(reference),
+            ..Self::new(desc)
+        }
+    }
+
+    // Tick following the last recorded one.
+    pub fn next_tick(&self) -> u32 {
+        self.log.ticks().last().map_or(0, |record| record.tick + 1)
+    }
+
+    pub fn begin_tick(&self, tick: u32) -> TickRecord {
+        TickRecord::new(tick, self.desc.detailed)
+    }
+
+    // Returns the divergence the first time one is found. Later ticks are still recorded.
+    pub fn end_tick(&mut self, record: TickRecord) -> Option<&Divergence> {
+        let mut diverged = false;
+        if self.divergence.is_none() {
+            if let Some(expected) = self.reference.as_ref().and_then(|r| r.tick(record.tick)) {
+                if expected.hash() != record.hash() {
+                    self.divergence = Some(Divergence::new(expected, &record));
+                    diverged = true;
+                }
+            }
+        }
+        self.log.push(record);
+        if diverged {
+            self.divergence.as_ref()
+        } else {
+            None
+        }
+    }
+
+    // Checks the hash received from a lockstep peer for an already recorded tick.
+    pub fn check_remote_hash(&mut self, tick: u32, hash: u64) -> Option<&Divergence> {
+        if self.divergence.is_some() {
+            return None;
+        }
+        let record = self.log.tick(tick)?;
+        if record.hash() == hash {
+            return None;
+        }
+        self.divergence = Some(Divergence {
+            tick,
+            fields: Vec::new(),
+        });
+        self.divergence.as_ref()
+    }
+
+    pub fn divergence(&self) -> Option<&Divergence> {
+        self.divergence.as_ref()
+    }
+
+    pub fn log(&self) -> &DeterminismLog {
+        &self.log
+    }
+
+    pub fn into_log(self) -> DeterminismLog {
+        self.log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    // Toy simulation with a linear congruential generator, diverging after the given tick if
+    // requested.
+    fn run(
+        audit: &mut DeterminismAudit,
+        ticks: u32,
+        diverge_at: Option<u32>,
+    ) -> Option<Divergence> {
+        let mut position = (0f32, 0f32);
+        let mut rng_state = 12345u32;
+        let mut divergence = None;
+        for tick in 0..ticks {
+            rng_state = rng_state.wrapping_mul(1103515245).wrapping_add(12345);
+            position.0 += (rng_state % 10) as f32 * 0.1;
+            if Some(tick) == diverge_at {
+                position.1 += 0.001;
+            }
+            let mut record = audit.begin_tick(tick);
+            record.add("position", &position).unwrap();
+            record.add("rng", &rng_state).unwrap();
+            if let Some(d) = audit.end_tick(record) {
+                divergence = Some(d.clone());
+            }
+        }
+        divergence
+    }
+
+    #[test]
+    fn identical_runs() {
+        let mut audit = DeterminismAudit::new(&DeterminismAuditDescriptor::default());
+        run(&mut audit, 20, None);
+        let reference = audit.into_log();
+        let mut audit = DeterminismAudit::with_reference(
+            &DeterminismAuditDescriptor::default(),
+            reference.clone(),
+        );
+        expect_that!(&run(&mut audit, 20, None), eq(None));
+        expect_that!(&reference.first_divergence(audit.log()), eq(None));
+    }
+
+    #[test]
+    fn divergent_runs() {
+        let mut audit = DeterminismAudit::new(&DeterminismAuditDescriptor::default());
+        run(&mut audit, 20, None);
+        let reference = audit.into_log();
+        let mut audit = DeterminismAudit::with_reference(
+            &DeterminismAuditDescriptor::default(),
+            reference.clone(),
+        );
+        let divergence = run(&mut audit, 20, Some(7)).unwrap();
+        expect_that!(&divergence.tick, eq(7));
+        expect_that!(&divergence.fields.len(), eq(1));
+        expect_that!(&divergence.fields[0].name, eq(String::from("position")));
+        expect_that!(divergence.fields[0]
+            .actual
+            .as_ref()
+            .unwrap()
+            .contains("0.001"));
+        expect_that!(divergence
+            .to_string()
+            .starts_with("Simulation diverged at tick 7\n  position: expected"));
+        expect_that!(&audit.log().ticks().len(), eq(20));
+        expect_that!(
+            &reference.first_divergence(audit.log()),
+            eq(Some(divergence))
+        );
+    }
+
+    #[test]
+    fn hashes_only() {
+        let desc = DeterminismAuditDescriptor { detailed: false };
+        let mut audit = DeterminismAudit::new(&desc);
+        run(&mut audit, 10, None);
+        let reference = audit.into_log();
+        let mut audit = DeterminismAudit::with_reference(&desc, reference);
+        let divergence = run(&mut audit, 10, Some(3)).unwrap();
+        expect_that!(divergence.fields[0]
+            .expected
+            .as_ref()
+            .unwrap()
+            .starts_with("<hash "));
+
+        let hash = audit.log().tick(5).unwrap().hash();
+        let mut audit = DeterminismAudit::new(&desc);
+        run(&mut audit, 10, None);
+        expect_that!(
+            &audit.check_remote_hash(4, audit.log().tick(4).unwrap().hash()),
+            eq(None)
+        );
+        expect_that!(
+            &audit.check_remote_hash(5, hash).map(|d| d.tick),
+            eq(Some(5))
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let mut audit = DeterminismAudit::new(&DeterminismAuditDescriptor::default());
+        run(&mut audit, 5, None);
+        let log = audit.into_log();
+        expect_that!(
+            &DeterminismLog::from_bytes(&log.to_bytes().unwrap()).unwrap(),
+            eq(log)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The ticks must be recorded in increasing order")]
+    fn ticks_out_of_order() {
+        let mut log = DeterminismLog::new();
+        log.push(TickRecord::new(3, false));
+        log.push(TickRecord::new(3, false));
+    }
+}
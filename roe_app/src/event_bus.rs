@@ -0,0 +1,212 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+trait AnyEventQueue: Send {
+    fn deliver(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Double buffered: events are published to the pending buffer, and read from the delivered one.
+struct EventQueue<T> {
+    pending: Vec<T>,
+    delivered: Vec<T>,
+}
+
+impl<T: Send + 'static> AnyEventQueue for EventQueue<T> {
+    fn deliver(&mut self) {
+        self.delivered.clear();
+        std::mem::swap(&mut self.pending, &mut self.delivered);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Default)]
+struct EventQueues {
+    queues: HashMap<TypeId, Box<dyn AnyEventQueue>>,
+    delivery: u64,
+}
+
+impl EventQueues {
+    fn queue<T: Send + 'static>(&self) -> Option<&EventQueue<T>> {
+        self.queues
+            .get(&TypeId::of::<T>())
+            .map(|queue| queue.as_any().downcast_ref().unwrap())
+    }
+
+    fn queue_mut<T: Send + 'static>(&mut self) -> &mut EventQueue<T> {
+        self.queues
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(EventQueue::<T> {
+                    pending: Vec::new(),
+                    delivered: Vec::new(),
+                })
+            })
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+// Typed publish / subscribe channel between game systems, e.g. for damage events, pickups or
+// ui notifications. Events are queued when published, and delivered to all subscribers of their
+// type at once when deliver is called. The application delivers the events before each fixed
+// update, so the events published in a frame are seen by all systems in the next fixed update,
+// independently of the order of the systems. Delivered events are dropped at the next delivery.
+// The bus can be cloned and moved to other threads, the clones share the same queues.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    queues: Arc<Mutex<EventQueues>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn publish<T: Send + 'static>(&self, event: T) {
+        self.queues.lock().unwrap().queue_mut().pending.push(event);
+    }
+
+    pub fn subscribe<T: Clone + Send + 'static>(&self) -> Subscriber<T> {
+        Subscriber {
+            bus: self.clone(),
+            last_read_delivery: None,
+            event_type: PhantomData,
+        }
+    }
+
+    // Makes the published events available to the subscribers, replacing the ones delivered
+    // previously.
+    pub fn deliver(&self) {
+        let mut queues = self.queues.lock().unwrap();
+        for queue in queues.queues.values_mut() {
+            queue.deliver();
+        }
+        queues.delivery += 1;
+    }
+
+    // Events of the given type published since the last delivery.
+    pub fn pending_count<T: Send + 'static>(&self) -> usize {
+        self.queues
+            .lock()
+            .unwrap()
+            .queue::<T>()
+            .map(|queue| queue.pending.len())
+            .unwrap_or(0)
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let queues = self.queues.lock().unwrap();
+        write!(
+            f,
+            "EventBus {{ event_types: {}, delivery: {} }}",
+            queues.queues.len(),
+            queues.delivery
+        )
+    }
+}
+
+// Receives the events of one type. Each subscriber reads every delivered event once.
+pub struct Subscriber<T> {
+    bus: EventBus,
+    last_read_delivery: Option<u64>,
+    event_type: PhantomData<fn() -> T>,
+}
+
+impl<T: Clone + Send + 'static> Subscriber<T> {
+    // Events of the last delivery, if not read yet. Events of earlier deliveries not read in
+    // time are lost.
+    pub fn read(&mut self) -> Vec<T> {
+        let queues = self.bus.queues.lock().unwrap();
+        if self.last_read_delivery == Some(queues.delivery) {
+            return Vec::new();
+        }
+        self.last_read_delivery = Some(queues.delivery);
+        queues
+            .queue::<T>()
+            .map(|queue| queue.delivered.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<T> std::fmt::Debug for Subscriber<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Subscriber {{ event_type: {}, last_read_delivery: {:?} }}",
+            std::any::type_name::<T>(),
+            self.last_read_delivery
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct Damage(u32);
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct Pickup(&'static str);
+
+    #[test]
+    fn delivery() {
+        let bus = EventBus::new();
+        let mut health = bus.subscribe::<Damage>();
+        let mut hud = bus.subscribe::<Damage>();
+        let mut inventory = bus.subscribe::<Pickup>();
+
+        bus.publish(Damage(3));
+        bus.clone().publish(Damage(5));
+        bus.publish(Pickup("key"));
+        expect_that!(&bus.pending_count::<Damage>(), eq(2));
+        expect_that!(&health.read(), eq(Vec::new()));
+
+        bus.deliver();
+        expect_that!(&bus.pending_count::<Damage>(), eq(0));
+        expect_that!(&health.read(), eq(vec![Damage(3), Damage(5)]));
+        expect_that!(&health.read(), eq(Vec::new()));
+        expect_that!(&inventory.read(), eq(vec![Pickup("key")]));
+
+        // Published while the previous events are being read.
+        bus.publish(Damage(1));
+        expect_that!(&hud.read(), eq(vec![Damage(3), Damage(5)]));
+
+        bus.deliver();
+        expect_that!(&health.read(), eq(vec![Damage(1)]));
+        expect_that!(&inventory.read(), eq(Vec::new()));
+
+        // Missed deliveries are lost.
+        bus.deliver();
+        expect_that!(&hud.read(), eq(Vec::new()));
+    }
+
+    #[test]
+    fn publish_from_other_thread() {
+        let bus = EventBus::new();
+        let mut subscriber = bus.subscribe::<Damage>();
+        let thread_bus = bus.clone();
+        std::thread::spawn(move || thread_bus.publish(Damage(7)))
+            .join()
+            .unwrap();
+        bus.deliver();
+        expect_that!(&subscriber.read(), eq(vec![Damage(7)]));
+    }
+}
@@ -0,0 +1,57 @@
+use roe_os as os;
+
+// Sends custom events to the application event loop. The sender can be cloned and moved to
+// other threads: sending an event wakes up the event loop, and the event is dispatched to the
+// current application state through on_custom_event.
+#[derive(Debug)]
+pub struct EventSender<CustomEventType: 'static> {
+    proxy: os::EventLoopProxy<CustomEventType>,
+}
+
+impl<CustomEventType: 'static> EventSender<CustomEventType> {
+    pub fn new(event_loop: &os::EventLoop<CustomEventType>) -> Self {
+        Self {
+            proxy: event_loop.create_proxy(),
+        }
+    }
+
+    pub fn send(&self, event: CustomEventType) -> Result<(), os::EventLoopClosed<CustomEventType>> {
+        self.proxy.send_event(event)
+    }
+}
+
+impl<CustomEventType: 'static> Clone for EventSender<CustomEventType> {
+    fn clone(&self) -> Self {
+        Self {
+            proxy: self.proxy.clone(),
+        }
+    }
+}
+
+impl<CustomEventType: 'static> From<os::EventLoopProxy<CustomEventType>>
+    for EventSender<CustomEventType>
+{
+    fn from(proxy: os::EventLoopProxy<CustomEventType>) -> Self {
+        Self { proxy }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use os::EventLoopAnyThread;
+
+    #[test]
+    #[serial_test::serial]
+    fn send_from_other_thread() {
+        let event_loop = os::EventLoop::<u32>::new_any_thread();
+        let sender = EventSender::new(&event_loop);
+        let thread_sender = sender.clone();
+        let result = std::thread::spawn(move || thread_sender.send(3).is_ok())
+            .join()
+            .unwrap();
+        expect_that!(&result, eq(true));
+        expect_that!(sender.send(4).is_ok());
+    }
+}
@@ -0,0 +1,477 @@
+use roe_os as os;
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Gesture {
+    Tap {
+        position: os::PhysicalPosition<f64>,
+    },
+    // The first tap of a double tap is reported as a normal tap.
+    DoubleTap {
+        position: os::PhysicalPosition<f64>,
+    },
+    LongPress {
+        position: os::PhysicalPosition<f64>,
+    },
+    DragStarted {
+        position: os::PhysicalPosition<f64>,
+    },
+    Drag {
+        position: os::PhysicalPosition<f64>,
+        delta: os::PhysicalPosition<f64>,
+    },
+    DragEnded {
+        position: os::PhysicalPosition<f64>,
+    },
+    // The scale is relative to the distance between the touches at the previous pinch event.
+    Pinch {
+        center: os::PhysicalPosition<f64>,
+        scale: f64,
+    },
+    // The angle is in radians, relative to the angle between the touches at the previous rotate
+    // event. Positive angles are clockwise in window coordinates.
+    Rotate {
+        center: os::PhysicalPosition<f64>,
+        angle: f64,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GestureRecognizerDescriptor {
+    pub tap_max_duration: std::time::Duration,
+    // Touches moving farther than this distance become drags.
+    pub tap_max_distance: f64,
+    pub double_tap_max_interval: std::time::Duration,
+    pub long_press_min_duration: std::time::Duration,
+}
+
+impl Default for GestureRecognizerDescriptor {
+    fn default() -> Self {
+        Self {
+            tap_max_duration: std::time::Duration::from_millis(300),
+            tap_max_distance: 10.,
+            double_tap_max_interval: std::time::Duration::from_millis(300),
+            long_press_min_duration: std::time::Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TouchState {
+    start_time: std::time::Instant,
+    start_position: os::PhysicalPosition<f64>,
+    position: os::PhysicalPosition<f64>,
+    dragging: bool,
+    long_pressed: bool,
+}
+
+#[derive(Debug)]
+pub struct GestureRecognizer {
+    desc: GestureRecognizerDescriptor,
+    touches: BTreeMap<u64, TouchState>,
+    multi_touch: bool,
+    last_tap: Option<(std::time::Instant, os::PhysicalPosition<f64>)>,
+}
+
+impl GestureRecognizer {
+    pub fn new(desc: GestureRecognizerDescriptor) -> Self {
+        Self {
+            desc,
+            touches: BTreeMap::new(),
+            multi_touch: false,
+            last_tap: None,
+        }
+    }
+
+    pub fn descriptor(&self) -> &GestureRecognizerDescriptor {
+        &self.desc
+    }
+
+    pub fn active_touch_count(&self) -> usize {
+        self.touches.len()
+    }
+
+    // Processes a touch event, as received by ApplicationState::on_touch.
+    pub fn on_touch(
+        &mut self,
+        phase: os::TouchPhase,
+        position: os::PhysicalPosition<f64>,
+        id: u64,
+        time: std::time::Instant,
+    ) -> Vec<Gesture> {
+        match phase {
+            os::TouchPhase::Started => self.on_touch_started(position, id, time),
+            os::TouchPhase::Moved => self.on_touch_moved(position, id),
+            os::TouchPhase::Ended => self.on_touch_ended(position, id, time, false),
+            os::TouchPhase::Cancelled => self.on_touch_ended(position, id, time, true),
+        }
+    }
+
+    // Detects time-based gestures (long press). Should be called regularly, e.g. on each update.
+    pub fn update(&mut self, time: std::time::Instant) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        if self.multi_touch {
+            return gestures;
+        }
+        for touch in self.touches.values_mut() {
+            if !touch.dragging
+                && !touch.long_pressed
+                && time.saturating_duration_since(touch.start_time)
+                    >= self.desc.long_press_min_duration
+            {
+                touch.long_pressed = true;
+                gestures.push(Gesture::LongPress {
+                    position: touch.position,
+                });
+            }
+        }
+        gestures
+    }
+
+    fn on_touch_started(
+        &mut self,
+        position: os::PhysicalPosition<f64>,
+        id: u64,
+        time: std::time::Instant,
+    ) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        if !self.touches.is_empty() {
+            // A drag turns into a multi-touch gesture as soon as a second finger is down.
+            self.multi_touch = true;
+            for touch in self.touches.values_mut() {
+                if touch.dragging {
+                    touch.dragging = false;
+                    gestures.push(Gesture::DragEnded {
+                        position: touch.position,
+                    });
+                }
+            }
+        }
+        self.touches.insert(
+            id,
+            TouchState {
+                start_time: time,
+                start_position: position,
+                position,
+                dragging: false,
+                long_pressed: false,
+            },
+        );
+        gestures
+    }
+
+    fn on_touch_moved(&mut self, position: os::PhysicalPosition<f64>, id: u64) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        let previous_pair = self.touch_pair();
+
+        let touch = match self.touches.get_mut(&id) {
+            Some(touch) => touch,
+            None => return gestures,
+        };
+        let previous_position = touch.position;
+        touch.position = position;
+
+        if self.multi_touch {
+            if let (Some((p0, p1)), Some((c0, c1))) = (previous_pair, self.touch_pair()) {
+                let center = midpoint(c0, c1);
+                let previous_distance = distance(p0, p1);
+                let current_distance = distance(c0, c1);
+                if previous_distance > 0. && current_distance != previous_distance {
+                    gestures.push(Gesture::Pinch {
+                        center,
+                        scale: current_distance / previous_distance,
+                    });
+                }
+                let angle = normalize_angle(angle(c0, c1) - angle(p0, p1));
+                if angle != 0. {
+                    gestures.push(Gesture::Rotate { center, angle });
+                }
+            }
+        } else {
+            if !touch.dragging
+                && !touch.long_pressed
+                && distance(touch.start_position, position) > self.desc.tap_max_distance
+            {
+                touch.dragging = true;
+                gestures.push(Gesture::DragStarted {
+                    position: touch.start_position,
+                });
+            }
+            if touch.dragging {
+                gestures.push(Gesture::Drag {
+                    position,
+                    delta: os::PhysicalPosition::new(
+                        position.x - previous_position.x,
+                        position.y - previous_position.y,
+                    ),
+                });
+            }
+        }
+
+        gestures
+    }
+
+    fn on_touch_ended(
+        &mut self,
+        position: os::PhysicalPosition<f64>,
+        id: u64,
+        time: std::time::Instant,
+        cancelled: bool,
+    ) -> Vec<Gesture> {
+        let mut gestures = Vec::new();
+        let touch = match self.touches.remove(&id) {
+            Some(touch) => touch,
+            None => return gestures,
+        };
+
+        if touch.dragging {
+            gestures.push(Gesture::DragEnded { position });
+        } else if !cancelled
+            && !self.multi_touch
+            && !touch.long_pressed
+            && time.saturating_duration_since(touch.start_time) <= self.desc.tap_max_duration
+            && distance(touch.start_position, position) <= self.desc.tap_max_distance
+        {
+            gestures.push(self.tap(position, time));
+        }
+
+        if self.touches.is_empty() {
+            self.multi_touch = false;
+        }
+
+        gestures
+    }
+
+    fn tap(&mut self, position: os::PhysicalPosition<f64>, time: std::time::Instant) -> Gesture {
+        if let Some((last_time, last_position)) = self.last_tap {
+            if time.saturating_duration_since(last_time) <= self.desc.double_tap_max_interval
+                && distance(last_position, position) <= self.desc.tap_max_distance
+            {
+                self.last_tap = None;
+                return Gesture::DoubleTap { position };
+            }
+        }
+        self.last_tap = Some((time, position));
+        Gesture::Tap { position }
+    }
+
+    fn touch_pair(&self) -> Option<(os::PhysicalPosition<f64>, os::PhysicalPosition<f64>)> {
+        let mut touches = self.touches.values();
+        match (touches.next(), touches.next()) {
+            (Some(t0), Some(t1)) => Some((t0.position, t1.position)),
+            _ => None,
+        }
+    }
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new(GestureRecognizerDescriptor::default())
+    }
+}
+
+pub(crate) fn distance(p0: os::PhysicalPosition<f64>, p1: os::PhysicalPosition<f64>) -> f64 {
+    (p1.x - p0.x).hypot(p1.y - p0.y)
+}
+
+fn midpoint(
+    p0: os::PhysicalPosition<f64>,
+    p1: os::PhysicalPosition<f64>,
+) -> os::PhysicalPosition<f64> {
+    os::PhysicalPosition::new((p0.x + p1.x) / 2., (p0.y + p1.y) / 2.)
+}
+
+fn angle(p0: os::PhysicalPosition<f64>, p1: os::PhysicalPosition<f64>) -> f64 {
+    (p1.y - p0.y).atan2(p1.x - p0.x)
+}
+
+fn normalize_angle(angle: f64) -> f64 {
+    let pi = std::f64::consts::PI;
+    if angle > pi {
+        angle - 2. * pi
+    } else if angle <= -pi {
+        angle + 2. * pi
+    } else {
+        angle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn pos(x: f64, y: f64) -> os::PhysicalPosition<f64> {
+        os::PhysicalPosition::new(x, y)
+    }
+
+    fn ms(t0: std::time::Instant, ms: u64) -> std::time::Instant {
+        t0 + std::time::Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn tap() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Started, pos(10., 10.), 0, t0),
+            eq(vec![])
+        );
+        expect_that!(&gr.active_touch_count(), eq(1));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(12., 11.), 0, ms(t0, 100)),
+            eq(vec![Gesture::Tap {
+                position: pos(12., 11.)
+            }])
+        );
+        expect_that!(&gr.active_touch_count(), eq(0));
+    }
+
+    #[test]
+    fn slow_release_is_not_a_tap() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(10., 10.), 0, t0);
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(10., 10.), 0, ms(t0, 400)),
+            eq(vec![])
+        );
+    }
+
+    #[test]
+    fn double_tap() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(10., 10.), 0, t0);
+        gr.on_touch(os::TouchPhase::Ended, pos(10., 10.), 0, ms(t0, 50));
+        gr.on_touch(os::TouchPhase::Started, pos(12., 10.), 1, ms(t0, 200));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(12., 10.), 1, ms(t0, 250)),
+            eq(vec![Gesture::DoubleTap {
+                position: pos(12., 10.)
+            }])
+        );
+        gr.on_touch(os::TouchPhase::Started, pos(12., 10.), 2, ms(t0, 300));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(12., 10.), 2, ms(t0, 350)),
+            eq(vec![Gesture::Tap {
+                position: pos(12., 10.)
+            }])
+        );
+    }
+
+    #[test]
+    fn long_press() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(10., 10.), 0, t0);
+        expect_that!(&gr.update(ms(t0, 400)), eq(vec![]));
+        expect_that!(
+            &gr.update(ms(t0, 500)),
+            eq(vec![Gesture::LongPress {
+                position: pos(10., 10.)
+            }])
+        );
+        expect_that!(&gr.update(ms(t0, 600)), eq(vec![]));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(10., 10.), 0, ms(t0, 700)),
+            eq(vec![])
+        );
+    }
+
+    #[test]
+    fn drag() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(10., 10.), 0, t0);
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Moved, pos(15., 10.), 0, ms(t0, 10)),
+            eq(vec![])
+        );
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Moved, pos(25., 10.), 0, ms(t0, 20)),
+            eq(vec![
+                Gesture::DragStarted {
+                    position: pos(10., 10.)
+                },
+                Gesture::Drag {
+                    position: pos(25., 10.),
+                    delta: pos(10., 0.)
+                }
+            ])
+        );
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Moved, pos(25., 20.), 0, ms(t0, 30)),
+            eq(vec![Gesture::Drag {
+                position: pos(25., 20.),
+                delta: pos(0., 10.)
+            }])
+        );
+        expect_that!(&gr.update(ms(t0, 1000)), eq(vec![]));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(25., 20.), 0, ms(t0, 1100)),
+            eq(vec![Gesture::DragEnded {
+                position: pos(25., 20.)
+            }])
+        );
+    }
+
+    #[test]
+    fn pinch() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(0., 0.), 0, t0);
+        gr.on_touch(os::TouchPhase::Started, pos(10., 0.), 1, t0);
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Moved, pos(20., 0.), 1, ms(t0, 10)),
+            eq(vec![Gesture::Pinch {
+                center: pos(10., 0.),
+                scale: 2.
+            }])
+        );
+        gr.on_touch(os::TouchPhase::Ended, pos(20., 0.), 1, ms(t0, 20));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Ended, pos(0., 0.), 0, ms(t0, 30)),
+            eq(vec![])
+        );
+    }
+
+    #[test]
+    fn rotate() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(0., 0.), 0, t0);
+        gr.on_touch(os::TouchPhase::Started, pos(10., 0.), 1, t0);
+        let gestures = gr.on_touch(os::TouchPhase::Moved, pos(0., 10.), 1, ms(t0, 10));
+        expect_that!(
+            &gestures,
+            eq(vec![Gesture::Rotate {
+                center: pos(0., 5.),
+                angle: std::f64::consts::FRAC_PI_2
+            }])
+        );
+    }
+
+    #[test]
+    fn second_touch_ends_drag() {
+        let t0 = std::time::Instant::now();
+        let mut gr = GestureRecognizer::default();
+        gr.on_touch(os::TouchPhase::Started, pos(0., 0.), 0, t0);
+        gr.on_touch(os::TouchPhase::Moved, pos(20., 0.), 0, ms(t0, 10));
+        expect_that!(
+            &gr.on_touch(os::TouchPhase::Started, pos(50., 0.), 1, ms(t0, 20)),
+            eq(vec![Gesture::DragEnded {
+                position: pos(20., 0.)
+            }])
+        );
+    }
+
+    #[test]
+    fn normalize_angles() {
+        let pi = std::f64::consts::PI;
+        expect_that!(&normalize_angle(1.5 * pi), close_to(-0.5 * pi, 1e-12));
+        expect_that!(&normalize_angle(-1.5 * pi), close_to(0.5 * pi, 1e-12));
+        expect_that!(&normalize_angle(pi), close_to(pi, 1e-12));
+    }
+}
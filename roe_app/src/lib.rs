@@ -3,3 +3,33 @@ pub use application::*;
 
 mod application_state;
 pub use application_state::*;
+
+mod gesture_recognizer;
+pub use gesture_recognizer::*;
+
+mod lifecycle_policy;
+pub use lifecycle_policy::*;
+
+mod crash_reporter;
+pub use crash_reporter::*;
+
+mod event_sender;
+pub use event_sender::*;
+
+mod event_bus;
+pub use event_bus::*;
+
+mod determinism;
+pub use determinism::*;
+
+mod time_scale;
+pub use time_scale::*;
+
+mod window_descriptor;
+pub use window_descriptor::*;
+
+mod virtual_gamepad;
+pub use virtual_gamepad::*;
+
+mod test_driver;
+pub use test_driver::*;
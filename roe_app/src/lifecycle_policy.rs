@@ -0,0 +1,92 @@
+// Lifecycle changes reported to the hooks registered on the application, before the current
+// state is notified.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LifecycleEvent {
+    Suspended,
+    Resumed,
+    FocusLost,
+    FocusGained,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct LifecyclePolicy {
+    // Skips fixed and variable updates while the application is suspended, and lets the event
+    // loop sleep until the next event.
+    pub pause_when_suspended: bool,
+    // Skips fixed and variable updates while no window has the focus.
+    pub pause_when_unfocused: bool,
+    // Limits the event loop frequency while no window has the focus.
+    pub unfocused_max_frequency_hz: Option<u64>,
+}
+
+impl LifecyclePolicy {
+    pub fn validate(&self) {
+        if let Some(v) = self.unfocused_max_frequency_hz {
+            assert!(
+                v > 0,
+                "The unfocused maximum frequency must be higher than 0"
+            );
+        }
+    }
+
+    pub fn updates_paused(&self, suspended: bool, focused: bool) -> bool {
+        (suspended && self.pause_when_suspended) || (!focused && self.pause_when_unfocused)
+    }
+
+    pub fn unfocused_min_period(&self) -> Option<std::time::Duration> {
+        self.unfocused_max_frequency_hz
+            .map(|v| std::time::Duration::from_secs_f64(1. / v as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn default_policy() {
+        let policy = LifecyclePolicy::default();
+        expect_that!(!policy.updates_paused(true, false));
+        expect_that!(&policy.unfocused_min_period(), eq(None));
+    }
+
+    #[test]
+    fn updates_paused() {
+        let policy = LifecyclePolicy {
+            pause_when_suspended: true,
+            ..LifecyclePolicy::default()
+        };
+        expect_that!(policy.updates_paused(true, true));
+        expect_that!(!policy.updates_paused(false, false));
+
+        let policy = LifecyclePolicy {
+            pause_when_unfocused: true,
+            ..LifecyclePolicy::default()
+        };
+        expect_that!(!policy.updates_paused(true, true));
+        expect_that!(policy.updates_paused(false, false));
+    }
+
+    #[test]
+    #[should_panic(expected = "The unfocused maximum frequency must be higher than 0")]
+    fn validate_zero_unfocused_frequency() {
+        LifecyclePolicy {
+            unfocused_max_frequency_hz: Some(0),
+            ..LifecyclePolicy::default()
+        }
+        .validate();
+    }
+
+    #[test]
+    fn unfocused_min_period() {
+        let policy = LifecyclePolicy {
+            unfocused_max_frequency_hz: Some(10),
+            ..LifecyclePolicy::default()
+        };
+        expect_that!(
+            &policy.unfocused_min_period(),
+            eq(Some(std::time::Duration::from_millis(100)))
+        );
+    }
+}
@@ -0,0 +1,747 @@
+use super::{Application, ApplicationState, EventBus, TimeScale};
+
+use roe_os as os;
+
+// Drives an application without an event loop or an OS window, injecting synthetic events.
+// Meant for integration tests of the application states: every call dispatches the events
+// immediately to the current state, and the update timer only advances through advance_time.
+pub struct TestDriver<ErrorType, CustomEventType>
+where
+    ErrorType: std::fmt::Display + std::error::Error + 'static,
+    CustomEventType: 'static,
+{
+    app: Application<ErrorType, CustomEventType>,
+    window_id: os::WindowId,
+    device_id: os::DeviceId,
+    exited: bool,
+}
+
+impl<ErrorType, CustomEventType> TestDriver<ErrorType, CustomEventType>
+where
+    ErrorType: std::fmt::Display + std::error::Error + 'static,
+    CustomEventType: 'static,
+{
+    pub fn new(
+        mut app: Application<ErrorType, CustomEventType>,
+        initial_state: Box<dyn ApplicationState<ErrorType, CustomEventType>>,
+    ) -> Result<Self, ErrorType> {
+        app.push_state(initial_state)?;
+        app.reset_update_timer();
+        // The dummy ids are only compared against each other, never passed to the OS.
+        Ok(Self {
+            app,
+            window_id: unsafe { os::WindowId::dummy() },
+            device_id: unsafe { os::DeviceId::dummy() },
+            exited: false,
+        })
+    }
+
+    // Id of the simulated window, passed to the window event callbacks.
+    pub fn window_id(&self) -> os::WindowId {
+        self.window_id
+    }
+
+    // Id of the simulated keyboard and mouse.
+    pub fn device_id(&self) -> os::DeviceId {
+        self.device_id
+    }
+
+    pub fn is_exited(&self) -> bool {
+        self.exited
+    }
+
+    // Number of states in the application state stack.
+    pub fn state_count(&self) -> usize {
+        self.app.state_count()
+    }
+
+    pub fn event_bus(&self) -> &EventBus {
+        self.app.event_bus()
+    }
+
+    pub fn time_scale(&self) -> &TimeScale {
+        self.app.time_scale()
+    }
+
+    pub fn send_event(
+        &mut self,
+        event: os::Event<CustomEventType>,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        assert!(!self.exited, "The application has already exited");
+        let control_flow = self.app.handle_event(event)?;
+        if control_flow == os::ControlFlow::Exit {
+            self.exited = true;
+        }
+        Ok(control_flow)
+    }
+
+    pub fn send_window_event(
+        &mut self,
+        event: os::WindowEvent,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        let window_id = self.window_id;
+        self.send_event(os::Event::WindowEvent { window_id, event })
+    }
+
+    pub fn send_custom_event(
+        &mut self,
+        event: CustomEventType,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        self.send_event(os::Event::UserEvent(event))
+    }
+
+    // The next frame runs the updates corresponding to the elapsed time.
+    pub fn advance_time(&mut self, dt: std::time::Duration) {
+        self.app.advance_time(dt);
+    }
+
+    // Sends the events of an event loop iteration, as the event loop would after the input events.
+    pub fn run_frame(&mut self) -> Result<os::ControlFlow, ErrorType> {
+        let window_id = self.window_id;
+        let events = [
+            os::Event::NewEvents(os::EventLoopStartCause::Poll),
+            os::Event::MainEventsCleared,
+            os::Event::RedrawRequested(window_id),
+            os::Event::RedrawEventsCleared,
+        ];
+        let mut control_flow = os::ControlFlow::Poll;
+        for event in events {
+            control_flow = self.send_event(event)?;
+            if self.exited {
+                break;
+            }
+        }
+        Ok(control_flow)
+    }
+
+    // Advances the time by dt before each frame. Stops early if the application exits.
+    pub fn run_frames(
+        &mut self,
+        count: usize,
+        dt: std::time::Duration,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        let mut control_flow = os::ControlFlow::Poll;
+        for _ in 0..count {
+            self.advance_time(dt);
+            control_flow = self.run_frame()?;
+            if self.exited {
+                break;
+            }
+        }
+        Ok(control_flow)
+    }
+
+    #[allow(deprecated)]
+    fn send_key(
+        &mut self,
+        key: os::KeyCode,
+        state: os::ElementState,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        let device_id = self.device_id;
+        self.send_window_event(os::WindowEvent::KeyboardInput {
+            device_id,
+            input: os::KeyboardInput {
+                scancode: 0,
+                state,
+                virtual_keycode: Some(key),
+                modifiers: os::ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        })
+    }
+
+    pub fn press_key(&mut self, key: os::KeyCode) -> Result<os::ControlFlow, ErrorType> {
+        self.send_key(key, os::ElementState::Pressed)
+    }
+
+    pub fn release_key(&mut self, key: os::KeyCode) -> Result<os::ControlFlow, ErrorType> {
+        self.send_key(key, os::ElementState::Released)
+    }
+
+    pub fn tap_key(&mut self, key: os::KeyCode) -> Result<os::ControlFlow, ErrorType> {
+        self.press_key(key)?;
+        self.release_key(key)
+    }
+
+    pub fn set_modifiers(
+        &mut self,
+        modifiers: os::ModifiersState,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        self.send_window_event(os::WindowEvent::ModifiersChanged(modifiers))
+    }
+
+    // Sends a received character event for each character, without the key events.
+    pub fn type_text(&mut self, text: &str) -> Result<os::ControlFlow, ErrorType> {
+        let mut control_flow = os::ControlFlow::Poll;
+        for c in text.chars() {
+            control_flow = self.send_window_event(os::WindowEvent::ReceivedCharacter(c))?;
+        }
+        Ok(control_flow)
+    }
+
+    #[allow(deprecated)]
+    pub fn move_cursor(
+        &mut self,
+        position: os::PhysicalPosition<f64>,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        let device_id = self.device_id;
+        self.send_window_event(os::WindowEvent::CursorMoved {
+            device_id,
+            position,
+            modifiers: os::ModifiersState::empty(),
+        })
+    }
+
+    #[allow(deprecated)]
+    fn send_mouse_button(
+        &mut self,
+        button: os::MouseButton,
+        state: os::ElementState,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        let device_id = self.device_id;
+        self.send_window_event(os::WindowEvent::MouseInput {
+            device_id,
+            state,
+            button,
+            modifiers: os::ModifiersState::empty(),
+        })
+    }
+
+    pub fn press_mouse_button(
+        &mut self,
+        button: os::MouseButton,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        self.send_mouse_button(button, os::ElementState::Pressed)
+    }
+
+    pub fn release_mouse_button(
+        &mut self,
+        button: os::MouseButton,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        self.send_mouse_button(button, os::ElementState::Released)
+    }
+
+    // Moves the cursor to the position, then presses and releases the button.
+    pub fn click(
+        &mut self,
+        position: os::PhysicalPosition<f64>,
+        button: os::MouseButton,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        self.move_cursor(position)?;
+        self.press_mouse_button(button)?;
+        self.release_mouse_button(button)
+    }
+
+    #[allow(deprecated)]
+    pub fn scroll(&mut self, delta: os::MouseScrollDelta) -> Result<os::ControlFlow, ErrorType> {
+        let device_id = self.device_id;
+        self.send_window_event(os::WindowEvent::MouseWheel {
+            device_id,
+            delta,
+            phase: os::TouchPhase::Moved,
+            modifiers: os::ModifiersState::empty(),
+        })
+    }
+
+    pub fn touch(
+        &mut self,
+        id: u64,
+        phase: os::TouchPhase,
+        location: os::PhysicalPosition<f64>,
+    ) -> Result<os::ControlFlow, ErrorType> {
+        let device_id = self.device_id;
+        self.send_window_event(os::WindowEvent::Touch(os::Touch {
+            device_id,
+            phase,
+            location,
+            force: None,
+            id,
+        }))
+    }
+
+    pub fn set_focused(&mut self, focused: bool) -> Result<os::ControlFlow, ErrorType> {
+        self.send_window_event(os::WindowEvent::Focused(focused))
+    }
+
+    pub fn resize(&mut self, size: os::PhysicalSize<u32>) -> Result<os::ControlFlow, ErrorType> {
+        self.send_window_event(os::WindowEvent::Resized(size))
+    }
+
+    pub fn request_close(&mut self) -> Result<os::ControlFlow, ErrorType> {
+        self.send_window_event(os::WindowEvent::CloseRequested)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{
+            ControlFlow, DeterminismAudit, DeterminismAuditDescriptor, Divergence, Subscriber,
+            TickRecord, TimeChannel, VirtualButtonDescriptor, VirtualGamepad,
+        },
+        *,
+    };
+    use galvanic_assert::{matchers::*, *};
+
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Debug, PartialEq, Clone)]
+    enum MyError {}
+
+    impl std::fmt::Display for MyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "MyError")
+        }
+    }
+
+    impl std::error::Error for MyError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            None
+        }
+    }
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    // Opens a child state on Enter, closes it on Escape.
+    struct MenuState {
+        name: &'static str,
+        log: Log,
+        next: Option<ControlFlow<MyError, u32>>,
+    }
+
+    impl MenuState {
+        fn new(name: &'static str, log: &Log) -> Self {
+            Self {
+                name,
+                log: log.clone(),
+                next: None,
+            }
+        }
+
+        fn log(&self, entry: String) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, entry));
+        }
+    }
+
+    impl ApplicationState<MyError, u32> for MenuState {
+        fn on_start(&mut self) -> Result<(), MyError> {
+            self.log(String::from("start"));
+            Ok(())
+        }
+
+        fn on_end(&mut self) -> Result<(), MyError> {
+            self.log(String::from("end"));
+            Ok(())
+        }
+
+        fn on_key_pressed(
+            &mut self,
+            _wid: os::WindowId,
+            _device_id: os::DeviceId,
+            _scan_code: os::ScanCode,
+            key_code: Option<os::KeyCode>,
+            _is_synthetic: bool,
+            is_repeat: bool,
+        ) -> Result<(), MyError> {
+            self.log(format!("pressed {:?} {}", key_code.unwrap(), is_repeat));
+            match key_code {
+                Some(os::KeyCode::Return) => {
+                    self.next = Some(ControlFlow::PushState(Box::new(MenuState::new(
+                        "child", &self.log,
+                    ))))
+                }
+                Some(os::KeyCode::Escape) => self.next = Some(ControlFlow::PopState),
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn on_received_character(&mut self, _wid: os::WindowId, c: char) -> Result<(), MyError> {
+            self.log(format!("char {}", c));
+            Ok(())
+        }
+
+        fn on_mouse_button_pressed(
+            &mut self,
+            _wid: os::WindowId,
+            _device_id: os::DeviceId,
+            button: os::MouseButton,
+        ) -> Result<(), MyError> {
+            self.log(format!("mouse pressed {:?}", button));
+            Ok(())
+        }
+
+        fn on_cursor_moved(
+            &mut self,
+            _wid: os::WindowId,
+            _device_id: os::DeviceId,
+            position: os::PhysicalPosition<f64>,
+        ) -> Result<(), MyError> {
+            self.log(format!("cursor {} {}", position.x, position.y));
+            Ok(())
+        }
+
+        fn on_custom_event(&mut self, event: u32) -> Result<(), MyError> {
+            self.log(format!("custom {}", event));
+            Ok(())
+        }
+
+        fn on_fixed_update(&mut self, _dt: std::time::Duration) -> Result<(), MyError> {
+            self.log(String::from("update"));
+            Ok(())
+        }
+
+        fn requested_control_flow(&mut self) -> ControlFlow<MyError, u32> {
+            self.next.take().unwrap_or(ControlFlow::Continue)
+        }
+    }
+
+    fn take_log(log: &Log) -> Vec<String> {
+        log.replace(Vec::new())
+    }
+
+    #[test]
+    fn input_events() {
+        let log = Log::default();
+        let mut driver = TestDriver::new(
+            Application::new(10, None),
+            Box::new(MenuState::new("root", &log)),
+        )
+        .unwrap();
+        expect_that!(&driver.state_count(), eq(1));
+
+        driver.press_key(os::KeyCode::A).unwrap();
+        driver.press_key(os::KeyCode::A).unwrap();
+        driver.release_key(os::KeyCode::A).unwrap();
+        driver.type_text("hi").unwrap();
+        driver
+            .click(os::PhysicalPosition::new(3., 4.), os::MouseButton::Left)
+            .unwrap();
+        driver.send_custom_event(5).unwrap();
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("root start"),
+                String::from("root pressed A false"),
+                String::from("root pressed A true"),
+                String::from("root char h"),
+                String::from("root char i"),
+                String::from("root cursor 3 4"),
+                String::from("root mouse pressed Left"),
+                String::from("root custom 5"),
+            ])
+        );
+    }
+
+    #[test]
+    fn state_transitions() {
+        let log = Log::default();
+        let mut driver = TestDriver::new(
+            Application::new(10, None),
+            Box::new(MenuState::new("root", &log)),
+        )
+        .unwrap();
+
+        // The control flow is only requested after the fixed updates.
+        driver.tap_key(os::KeyCode::Return).unwrap();
+        driver.run_frame().unwrap();
+        expect_that!(&driver.state_count(), eq(1));
+        driver
+            .run_frames(1, std::time::Duration::from_millis(100))
+            .unwrap();
+        expect_that!(&driver.state_count(), eq(2));
+
+        driver.tap_key(os::KeyCode::Escape).unwrap();
+        driver
+            .run_frames(1, std::time::Duration::from_millis(100))
+            .unwrap();
+        expect_that!(&driver.state_count(), eq(1));
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("root start"),
+                String::from("root pressed Return false"),
+                String::from("root update"),
+                String::from("child start"),
+                String::from("child pressed Escape false"),
+                String::from("child update"),
+                String::from("child end"),
+            ])
+        );
+
+        let control_flow = driver.request_close().unwrap();
+        expect_that!(&control_flow, eq(os::ControlFlow::Exit));
+        expect_that!(driver.is_exited());
+        expect_that!(&driver.state_count(), eq(0));
+        expect_that!(&take_log(&log), eq(vec![String::from("root end")]));
+    }
+
+    #[test]
+    fn fixed_updates_follow_advanced_time() {
+        let log = Log::default();
+        let mut driver = TestDriver::new(
+            Application::new(10, None),
+            Box::new(MenuState::new("root", &log)),
+        )
+        .unwrap();
+        take_log(&log);
+        driver
+            .run_frames(2, std::time::Duration::from_millis(250))
+            .unwrap();
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("root update"),
+                String::from("root update"),
+                String::from("root update"),
+                String::from("root update"),
+                String::from("root update"),
+            ])
+        );
+    }
+
+    // Logs the damage events received in each fixed update.
+    struct DamageState {
+        damage: Subscriber<u32>,
+        log: Log,
+    }
+
+    impl ApplicationState<MyError, u32> for DamageState {
+        fn on_fixed_update(&mut self, _dt: std::time::Duration) -> Result<(), MyError> {
+            self.log
+                .borrow_mut()
+                .push(format!("damage {:?}", self.damage.read()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events_delivered_before_fixed_updates() {
+        let log = Log::default();
+        let app = Application::new(10, None);
+        let state = DamageState {
+            damage: app.event_bus().subscribe(),
+            log: log.clone(),
+        };
+        let mut driver = TestDriver::new(app, Box::new(state)).unwrap();
+        driver.event_bus().publish(3u32);
+        driver.event_bus().publish(4u32);
+        driver
+            .run_frames(1, std::time::Duration::from_millis(200))
+            .unwrap();
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("damage [3, 4]"),
+                String::from("damage []")
+            ])
+        );
+    }
+
+    // Logs the scaled gameplay dt of each fixed update.
+    struct SlowMotionState {
+        time_scale: TimeScale,
+        log: Log,
+    }
+
+    impl ApplicationState<MyError, u32> for SlowMotionState {
+        fn on_fixed_update(&mut self, dt: std::time::Duration) -> Result<(), MyError> {
+            let dt = self.time_scale.scale_delta(TimeChannel::Gameplay, dt);
+            self.log
+                .borrow_mut()
+                .push(format!("gameplay {}", dt.as_millis()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn time_scale_advanced_by_fixed_updates() {
+        let log = Log::default();
+        let app = Application::new(10, None);
+        let state = SlowMotionState {
+            time_scale: app.time_scale().clone(),
+            log: log.clone(),
+        };
+        let mut driver = TestDriver::new(app, Box::new(state)).unwrap();
+        driver
+            .time_scale()
+            .set_channel_scale(TimeChannel::Gameplay, 0.5);
+        driver
+            .time_scale()
+            .hit_stop(std::time::Duration::from_millis(200));
+        driver
+            .run_frames(1, std::time::Duration::from_millis(400))
+            .unwrap();
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("gameplay 0"),
+                String::from("gameplay 0"),
+                String::from("gameplay 50"),
+                String::from("gameplay 50"),
+            ])
+        );
+    }
+
+    #[test]
+    fn virtual_gamepad() {
+        let log = Log::default();
+        let gamepad = Rc::new(RefCell::new(VirtualGamepad::new()));
+        gamepad.borrow_mut().add_button(VirtualButtonDescriptor {
+            center: os::PhysicalPosition::new(10., 10.),
+            radius: 5.,
+            key_code: Some(os::KeyCode::A),
+        });
+        let mut driver = TestDriver::new(
+            Application::new(10, None).with_virtual_gamepad(gamepad.clone()),
+            Box::new(MenuState::new("root", &log)),
+        )
+        .unwrap();
+        driver
+            .touch(
+                0,
+                os::TouchPhase::Started,
+                os::PhysicalPosition::new(12., 10.),
+            )
+            .unwrap();
+        expect_that!(gamepad.borrow().button_pressed(0));
+        driver
+            .touch(
+                1,
+                os::TouchPhase::Started,
+                os::PhysicalPosition::new(50., 10.),
+            )
+            .unwrap();
+        driver
+            .touch(
+                0,
+                os::TouchPhase::Ended,
+                os::PhysicalPosition::new(12., 10.),
+            )
+            .unwrap();
+        expect_that!(!gamepad.borrow().button_pressed(0));
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("root start"),
+                String::from("root pressed A false"),
+            ])
+        );
+    }
+
+    #[test]
+    fn lifecycle_hooks() {
+        let log = Log::default();
+        let hook_log = log.clone();
+        let mut driver = TestDriver::new(
+            Application::new(10, None).with_lifecycle_hook(move |event| {
+                hook_log.borrow_mut().push(format!("hook {:?}", event));
+                Ok(())
+            }),
+            Box::new(MenuState::new("root", &log)),
+        )
+        .unwrap();
+        driver.send_event(os::Event::Suspended).unwrap();
+        driver.send_event(os::Event::Resumed).unwrap();
+        driver.set_focused(false).unwrap();
+        driver.set_focused(true).unwrap();
+        expect_that!(
+            &take_log(&log),
+            eq(vec![
+                String::from("root start"),
+                String::from("hook Suspended"),
+                String::from("hook Resumed"),
+                String::from("hook FocusLost"),
+                String::from("hook FocusGained"),
+            ])
+        );
+    }
+
+    // Counts the fixed updates, skipping one at the given tick if requested.
+    struct CounterState {
+        counter: u32,
+        skip_at: Option<u32>,
+        log: Log,
+    }
+
+    impl ApplicationState<MyError, u32> for CounterState {
+        fn on_fixed_update(&mut self, _dt: std::time::Duration) -> Result<(), MyError> {
+            if self.skip_at != Some(self.counter) {
+                self.counter += 1;
+            } else {
+                self.skip_at = None;
+            }
+            Ok(())
+        }
+
+        fn on_determinism_audit(&mut self, record: &mut TickRecord) -> Result<(), MyError> {
+            record.add("counter", &self.counter).unwrap();
+            Ok(())
+        }
+
+        fn on_determinism_divergence(&mut self, divergence: &Divergence) -> Result<(), MyError> {
+            self.log
+                .borrow_mut()
+                .push(format!("diverged at tick {}", divergence.tick));
+            Ok(())
+        }
+    }
+
+    fn run_counter(audit: &Rc<RefCell<DeterminismAudit>>, skip_at: Option<u32>, log: &Log) {
+        let state = CounterState {
+            counter: 0,
+            skip_at,
+            log: log.clone(),
+        };
+        let mut driver = TestDriver::new(
+            Application::new(10, None).with_determinism_audit(audit.clone()),
+            Box::new(state),
+        )
+        .unwrap();
+        driver
+            .run_frames(5, std::time::Duration::from_millis(100))
+            .unwrap();
+    }
+
+    #[test]
+    fn determinism_audit() {
+        let log = Log::default();
+        let desc = DeterminismAuditDescriptor::default();
+        let audit = Rc::new(RefCell::new(DeterminismAudit::new(&desc)));
+        run_counter(&audit, None, &log);
+        let reference = audit.borrow().log().clone();
+        expect_that!(&reference.ticks().len(), eq(5));
+
+        let audit = Rc::new(RefCell::new(DeterminismAudit::with_reference(
+            &desc,
+            reference.clone(),
+        )));
+        run_counter(&audit, None, &log);
+        expect_that!(&take_log(&log), eq(Vec::<String>::new()));
+
+        let audit = Rc::new(RefCell::new(DeterminismAudit::with_reference(
+            &desc, reference,
+        )));
+        run_counter(&audit, Some(3), &log);
+        expect_that!(
+            &take_log(&log),
+            eq(vec![String::from("diverged at tick 3")])
+        );
+        expect_that!(&audit.borrow().log().ticks().len(), eq(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "The application has already exited")]
+    fn event_after_exit() {
+        let log = Log::default();
+        let mut driver = TestDriver::new(
+            Application::<MyError, u32>::new(10, None),
+            Box::new(MenuState::new("root", &log)),
+        )
+        .unwrap();
+        driver.request_close().unwrap();
+        driver.send_custom_event(1).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// Lowest pitch returned by audio_pitch, since audio sources don't accept a null pitch.
+pub const MIN_AUDIO_PITCH: f32 = 0.01;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum TimeChannel {
+    // Simulation, animations, particles. Affected by hit-stops.
+    Gameplay,
+    // Menus and hud, usually kept at normal speed during slow motion.
+    Ui,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct ScaleRamp {
+    from: f32,
+    to: f32,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct ChannelScale {
+    scale: f32,
+    ramp: Option<ScaleRamp>,
+}
+
+impl ChannelScale {
+    fn new() -> Self {
+        Self {
+            scale: 1.,
+            ramp: None,
+        }
+    }
+
+    fn advance(&mut self, dt: Duration) {
+        if let Some(ramp) = &mut self.ramp {
+            ramp.elapsed += dt;
+            if ramp.elapsed >= ramp.duration {
+                self.scale = ramp.to;
+                self.ramp = None;
+            } else {
+                let t = ramp.elapsed.as_secs_f32() / ramp.duration.as_secs_f32();
+                self.scale = ramp.from + (ramp.to - ramp.from) * t;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TimeScaleState {
+    global: f32,
+    gameplay: ChannelScale,
+    ui: ChannelScale,
+    hit_stop: Duration,
+    audio_follows_gameplay: bool,
+}
+
+impl TimeScaleState {
+    fn channel(&self, channel: TimeChannel) -> &ChannelScale {
+        match channel {
+            TimeChannel::Gameplay => &self.gameplay,
+            TimeChannel::Ui => &self.ui,
+        }
+    }
+
+    fn channel_mut(&mut self, channel: TimeChannel) -> &mut ChannelScale {
+        match channel {
+            TimeChannel::Gameplay => &mut self.gameplay,
+            TimeChannel::Ui => &mut self.ui,
+        }
+    }
+
+    fn scale(&self, channel: TimeChannel) -> f32 {
+        if channel == TimeChannel::Gameplay && self.hit_stop > Duration::ZERO {
+            return 0.;
+        }
+        self.global * self.channel(channel).scale
+    }
+}
+
+// Speed of the game time relative to the real time, e.g. for slow motion or pause menus. The
+// scale of a channel is the global scale multiplied by the channel scale. States scale the dt
+// received in the fixed updates with scale_delta for the channel they simulate.
+// The application advances hit-stops and ramps by the real fixed update period after each fixed
+// update. The time scale can be cloned and moved to other threads, the clones share the same
+// values.
+#[derive(Clone)]
+pub struct TimeScale {
+    state: Arc<Mutex<TimeScaleState>>,
+}
+
+impl TimeScale {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TimeScaleState {
+                global: 1.,
+                gameplay: ChannelScale::new(),
+                ui: ChannelScale::new(),
+                hit_stop: Duration::ZERO,
+                audio_follows_gameplay: false,
+            })),
+        }
+    }
+
+    pub fn global_scale(&self) -> f32 {
+        self.state.lock().unwrap().global
+    }
+
+    pub fn set_global_scale(&self, scale: f32) {
+        assert!(scale >= 0., "The time scale must not be negative");
+        self.state.lock().unwrap().global = scale;
+    }
+
+    // Scale of the channel only, without the global scale and hit-stops.
+    pub fn channel_scale(&self, channel: TimeChannel) -> f32 {
+        self.state.lock().unwrap().channel(channel).scale
+    }
+
+    // Cancels the running ramp of the channel, if any.
+    pub fn set_channel_scale(&self, channel: TimeChannel, scale: f32) {
+        assert!(scale >= 0., "The time scale must not be negative");
+        *self.state.lock().unwrap().channel_mut(channel) = ChannelScale { scale, ramp: None };
+    }
+
+    // Moves the channel scale linearly from its current value to the target over the given real
+    // time, e.g. to ease in and out of slow motion.
+    pub fn ramp_channel_scale(&self, channel: TimeChannel, target: f32, duration: Duration) {
+        assert!(target >= 0., "The time scale must not be negative");
+        let mut state = self.state.lock().unwrap();
+        let channel = state.channel_mut(channel);
+        if duration == Duration::ZERO {
+            *channel = ChannelScale {
+                scale: target,
+                ramp: None,
+            };
+        } else {
+            channel.ramp = Some(ScaleRamp {
+                from: channel.scale,
+                to: target,
+                duration,
+                elapsed: Duration::ZERO,
+            });
+        }
+    }
+
+    pub fn is_ramping(&self, channel: TimeChannel) -> bool {
+        self.state.lock().unwrap().channel(channel).ramp.is_some()
+    }
+
+    // Freezes the gameplay time for the given real time, e.g. for a few frames on heavy hits.
+    // Overlapping hit-stops don't add up, the longest remaining one is kept.
+    pub fn hit_stop(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.hit_stop = state.hit_stop.max(duration);
+    }
+
+    pub fn is_hit_stopped(&self) -> bool {
+        self.state.lock().unwrap().hit_stop > Duration::ZERO
+    }
+
+    pub fn cancel_hit_stop(&self) {
+        self.state.lock().unwrap().hit_stop = Duration::ZERO;
+    }
+
+    // Effective scale of the channel: global scale, channel scale and hit-stops combined.
+    pub fn scale(&self, channel: TimeChannel) -> f32 {
+        self.state.lock().unwrap().scale(channel)
+    }
+
+    pub fn scale_delta(&self, channel: TimeChannel, dt: Duration) -> Duration {
+        dt.mul_f32(self.scale(channel))
+    }
+
+    pub fn audio_follows_gameplay(&self) -> bool {
+        self.state.lock().unwrap().audio_follows_gameplay
+    }
+
+    pub fn set_audio_follows_gameplay(&self, value: bool) {
+        self.state.lock().unwrap().audio_follows_gameplay = value;
+    }
+
+    // Pitch to apply to the gameplay audio sources, if the audio follows the gameplay scale.
+    pub fn audio_pitch(&self) -> Option<f32> {
+        let state = self.state.lock().unwrap();
+        if state.audio_follows_gameplay {
+            Some(state.scale(TimeChannel::Gameplay).max(MIN_AUDIO_PITCH))
+        } else {
+            None
+        }
+    }
+
+    // Advances the hit-stops and ramps by the given real time.
+    pub fn advance(&self, dt: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.hit_stop = state.hit_stop.saturating_sub(dt);
+        state.gameplay.advance(dt);
+        state.ui.advance(dt);
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for TimeScale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+        write!(
+            f,
+            "TimeScale {{ gameplay: {}, ui: {} }}",
+            state.scale(TimeChannel::Gameplay),
+            state.scale(TimeChannel::Ui)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn channel_scales() {
+        let time_scale = TimeScale::new();
+        expect_that!(&time_scale.scale(TimeChannel::Gameplay), eq(1.));
+        time_scale.set_global_scale(0.5);
+        time_scale.set_channel_scale(TimeChannel::Gameplay, 0.5);
+        expect_that!(&time_scale.scale(TimeChannel::Gameplay), eq(0.25));
+        expect_that!(&time_scale.scale(TimeChannel::Ui), eq(0.5));
+        expect_that!(
+            &time_scale.scale_delta(TimeChannel::Gameplay, Duration::from_millis(100)),
+            eq(Duration::from_millis(25))
+        );
+    }
+
+    #[test]
+    fn hit_stop() {
+        let time_scale = TimeScale::new();
+        time_scale.hit_stop(Duration::from_millis(50));
+        time_scale.hit_stop(Duration::from_millis(30));
+        expect_that!(time_scale.is_hit_stopped());
+        expect_that!(&time_scale.scale(TimeChannel::Gameplay), eq(0.));
+        expect_that!(&time_scale.scale(TimeChannel::Ui), eq(1.));
+
+        time_scale.advance(Duration::from_millis(40));
+        expect_that!(time_scale.is_hit_stopped());
+        time_scale.advance(Duration::from_millis(10));
+        expect_that!(!time_scale.is_hit_stopped());
+        expect_that!(&time_scale.scale(TimeChannel::Gameplay), eq(1.));
+    }
+
+    #[test]
+    fn slow_motion_ramp() {
+        let time_scale = TimeScale::new();
+        time_scale.ramp_channel_scale(TimeChannel::Gameplay, 0.2, Duration::from_millis(400));
+        time_scale.advance(Duration::from_millis(100));
+        expect_that!(
+            &time_scale.channel_scale(TimeChannel::Gameplay),
+            close_to(0.8, 1e-6)
+        );
+        time_scale.advance(Duration::from_millis(400));
+        expect_that!(!time_scale.is_ramping(TimeChannel::Gameplay));
+        expect_that!(
+            &time_scale.channel_scale(TimeChannel::Gameplay),
+            close_to(0.2, 1e-6)
+        );
+
+        time_scale.ramp_channel_scale(TimeChannel::Gameplay, 1., Duration::from_millis(100));
+        time_scale.set_channel_scale(TimeChannel::Gameplay, 0.5);
+        time_scale.advance(Duration::from_millis(100));
+        expect_that!(&time_scale.channel_scale(TimeChannel::Gameplay), eq(0.5));
+    }
+
+    #[test]
+    fn audio_pitch() {
+        let time_scale = TimeScale::new();
+        time_scale.set_channel_scale(TimeChannel::Gameplay, 0.5);
+        expect_that!(&time_scale.audio_pitch(), eq(None));
+        time_scale.set_audio_follows_gameplay(true);
+        expect_that!(&time_scale.audio_pitch(), eq(Some(0.5)));
+        time_scale.hit_stop(Duration::from_millis(10));
+        expect_that!(&time_scale.audio_pitch(), eq(Some(MIN_AUDIO_PITCH)));
+    }
+
+    #[test]
+    #[should_panic(expected = "The time scale must not be negative")]
+    fn negative_scale() {
+        TimeScale::new().set_global_scale(-1.);
+    }
+}
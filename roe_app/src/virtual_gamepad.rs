@@ -0,0 +1,391 @@
+use super::gesture_recognizer::distance;
+
+use roe_os as os;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VirtualJoystickDescriptor {
+    pub center: os::PhysicalPosition<f64>,
+    pub radius: f64,
+    // Fraction of the radius where the joystick doesn't report any movement.
+    pub dead_zone: f64,
+    // Axes reported to the application state when the joystick moves.
+    pub axes: Option<(os::AxisId, os::AxisId)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VirtualJoystick {
+    desc: VirtualJoystickDescriptor,
+    touch_id: Option<u64>,
+    knob_position: os::PhysicalPosition<f64>,
+}
+
+impl VirtualJoystick {
+    pub fn new(desc: VirtualJoystickDescriptor) -> Self {
+        assert!(
+            desc.radius > 0.,
+            "The virtual joystick radius must be higher than 0"
+        );
+        assert!(
+            desc.dead_zone >= 0. && desc.dead_zone < 1.,
+            "The virtual joystick dead zone must be in the range [0, 1)"
+        );
+        Self {
+            desc,
+            touch_id: None,
+            knob_position: desc.center,
+        }
+    }
+
+    pub fn descriptor(&self) -> &VirtualJoystickDescriptor {
+        &self.desc
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.touch_id.is_some()
+    }
+
+    // Position of the knob, clamped inside the joystick area.
+    pub fn knob_position(&self) -> os::PhysicalPosition<f64> {
+        self.knob_position
+    }
+
+    // Axis values in the range [-1, 1]. Positive y values point down, as in window coordinates.
+    pub fn axes(&self) -> (f64, f64) {
+        let dx = (self.knob_position.x - self.desc.center.x) / self.desc.radius;
+        let dy = (self.knob_position.y - self.desc.center.y) / self.desc.radius;
+        let magnitude = dx.hypot(dy);
+        if magnitude <= self.desc.dead_zone {
+            return (0., 0.);
+        }
+        let scale = (magnitude - self.desc.dead_zone) / (1. - self.desc.dead_zone) / magnitude;
+        (dx * scale, dy * scale)
+    }
+
+    fn contains(&self, position: os::PhysicalPosition<f64>) -> bool {
+        distance(self.desc.center, position) <= self.desc.radius
+    }
+
+    fn move_knob(&mut self, position: os::PhysicalPosition<f64>) {
+        let center = self.desc.center;
+        let d = distance(center, position);
+        self.knob_position = if d > self.desc.radius {
+            let scale = self.desc.radius / d;
+            os::PhysicalPosition::new(
+                center.x + (position.x - center.x) * scale,
+                center.y + (position.y - center.y) * scale,
+            )
+        } else {
+            position
+        };
+    }
+
+    fn release(&mut self) {
+        self.touch_id = None;
+        self.knob_position = self.desc.center;
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VirtualButtonDescriptor {
+    pub center: os::PhysicalPosition<f64>,
+    pub radius: f64,
+    // Key reported to the application state when the button is pressed and released.
+    pub key_code: Option<os::KeyCode>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VirtualButton {
+    desc: VirtualButtonDescriptor,
+    touch_id: Option<u64>,
+}
+
+impl VirtualButton {
+    pub fn new(desc: VirtualButtonDescriptor) -> Self {
+        assert!(
+            desc.radius > 0.,
+            "The virtual button radius must be higher than 0"
+        );
+        Self {
+            desc,
+            touch_id: None,
+        }
+    }
+
+    pub fn descriptor(&self) -> &VirtualButtonDescriptor {
+        &self.desc
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.touch_id.is_some()
+    }
+
+    fn contains(&self, position: os::PhysicalPosition<f64>) -> bool {
+        distance(self.desc.center, position) <= self.desc.radius
+    }
+}
+
+// Input generated by the virtual gamepad widgets, delivered to the application state as key and
+// axis events.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum VirtualGamepadInput {
+    KeyPressed(os::KeyCode),
+    KeyReleased(os::KeyCode),
+    AxisMoved(os::AxisId, f64),
+}
+
+// On-screen controller driven by touch events. Each widget is captured by the touch starting
+// inside it, and released when that touch ends.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct VirtualGamepad {
+    joysticks: Vec<VirtualJoystick>,
+    buttons: Vec<VirtualButton>,
+    input: Vec<VirtualGamepadInput>,
+}
+
+impl VirtualGamepad {
+    pub fn new() -> Self {
+        Self {
+            joysticks: Vec::new(),
+            buttons: Vec::new(),
+            input: Vec::new(),
+        }
+    }
+
+    pub fn add_joystick(&mut self, desc: VirtualJoystickDescriptor) -> usize {
+        self.joysticks.push(VirtualJoystick::new(desc));
+        self.joysticks.len() - 1
+    }
+
+    pub fn add_button(&mut self, desc: VirtualButtonDescriptor) -> usize {
+        self.buttons.push(VirtualButton::new(desc));
+        self.buttons.len() - 1
+    }
+
+    pub fn joysticks(&self) -> &[VirtualJoystick] {
+        &self.joysticks
+    }
+
+    pub fn buttons(&self) -> &[VirtualButton] {
+        &self.buttons
+    }
+
+    pub fn joystick_axes(&self, index: usize) -> (f64, f64) {
+        self.joysticks[index].axes()
+    }
+
+    pub fn button_pressed(&self, index: usize) -> bool {
+        self.buttons[index].is_pressed()
+    }
+
+    pub fn release_all(&mut self) {
+        for index in 0..self.joysticks.len() {
+            self.update_joystick(index, VirtualJoystick::release);
+        }
+        for index in 0..self.buttons.len() {
+            self.release_button(index);
+        }
+    }
+
+    // Takes the input generated since the last call. The application delivers it to the current
+    // state when the gamepad is registered with Application::with_virtual_gamepad.
+    pub fn take_input(&mut self) -> Vec<VirtualGamepadInput> {
+        std::mem::take(&mut self.input)
+    }
+
+    fn update_joystick<F: FnOnce(&mut VirtualJoystick)>(&mut self, index: usize, f: F) {
+        let joystick = &mut self.joysticks[index];
+        let (x0, y0) = joystick.axes();
+        f(joystick);
+        let (x1, y1) = joystick.axes();
+        if let Some((axis_x, axis_y)) = joystick.desc.axes {
+            if x1 != x0 {
+                self.input.push(VirtualGamepadInput::AxisMoved(axis_x, x1));
+            }
+            if y1 != y0 {
+                self.input.push(VirtualGamepadInput::AxisMoved(axis_y, y1));
+            }
+        }
+    }
+
+    fn press_button(&mut self, index: usize, id: u64) {
+        let button = &mut self.buttons[index];
+        button.touch_id = Some(id);
+        if let Some(key_code) = button.desc.key_code {
+            self.input.push(VirtualGamepadInput::KeyPressed(key_code));
+        }
+    }
+
+    fn release_button(&mut self, index: usize) {
+        let button = &mut self.buttons[index];
+        if button.touch_id.take().is_some() {
+            if let Some(key_code) = button.desc.key_code {
+                self.input.push(VirtualGamepadInput::KeyReleased(key_code));
+            }
+        }
+    }
+
+    // Processes a touch event, as received by ApplicationState::on_touch. Returns true if the
+    // touch is handled by the gamepad, and shouldn't be processed further.
+    pub fn on_touch(
+        &mut self,
+        phase: os::TouchPhase,
+        position: os::PhysicalPosition<f64>,
+        id: u64,
+    ) -> bool {
+        match phase {
+            os::TouchPhase::Started => {
+                if let Some(index) = self
+                    .joysticks
+                    .iter()
+                    .position(|j| !j.is_active() && j.contains(position))
+                {
+                    self.update_joystick(index, |joystick| {
+                        joystick.touch_id = Some(id);
+                        joystick.move_knob(position);
+                    });
+                    return true;
+                }
+                if let Some(index) = self
+                    .buttons
+                    .iter()
+                    .position(|b| !b.is_pressed() && b.contains(position))
+                {
+                    self.press_button(index, id);
+                    return true;
+                }
+                false
+            }
+            os::TouchPhase::Moved => {
+                if let Some(index) = self.joysticks.iter().position(|j| j.touch_id == Some(id)) {
+                    self.update_joystick(index, |joystick| joystick.move_knob(position));
+                    return true;
+                }
+                self.buttons.iter().any(|b| b.touch_id == Some(id))
+            }
+            os::TouchPhase::Ended | os::TouchPhase::Cancelled => {
+                if let Some(index) = self.joysticks.iter().position(|j| j.touch_id == Some(id)) {
+                    self.update_joystick(index, VirtualJoystick::release);
+                    return true;
+                }
+                if let Some(index) = self.buttons.iter().position(|b| b.touch_id == Some(id)) {
+                    self.release_button(index);
+                    return true;
+                }
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn pos(x: f64, y: f64) -> os::PhysicalPosition<f64> {
+        os::PhysicalPosition::new(x, y)
+    }
+
+    fn gamepad() -> VirtualGamepad {
+        let mut gamepad = VirtualGamepad::new();
+        gamepad.add_joystick(VirtualJoystickDescriptor {
+            center: pos(100., 100.),
+            radius: 50.,
+            dead_zone: 0.2,
+            axes: Some((0, 1)),
+        });
+        gamepad.add_button(VirtualButtonDescriptor {
+            center: pos(300., 100.),
+            radius: 20.,
+            key_code: Some(os::KeyCode::Space),
+        });
+        gamepad
+    }
+
+    #[test]
+    fn joystick() {
+        let mut gamepad = gamepad();
+        expect_that!(&gamepad.joystick_axes(0), eq((0., 0.)));
+
+        expect_that!(gamepad.on_touch(os::TouchPhase::Started, pos(105., 100.), 3));
+        expect_that!(gamepad.joysticks()[0].is_active());
+        expect_that!(&gamepad.joystick_axes(0), eq((0., 0.)));
+
+        expect_that!(gamepad.on_touch(os::TouchPhase::Moved, pos(200., 100.), 3));
+        expect_that!(&gamepad.joysticks()[0].knob_position(), eq(pos(150., 100.)));
+        expect_that!(&gamepad.joystick_axes(0), eq((1., 0.)));
+
+        gamepad.on_touch(os::TouchPhase::Moved, pos(100., 70.), 3);
+        let (x, y) = gamepad.joystick_axes(0);
+        expect_that!(&x, close_to(0., 1e-12));
+        expect_that!(&y, close_to(-0.5, 1e-12));
+
+        expect_that!(gamepad.on_touch(os::TouchPhase::Ended, pos(100., 70.), 3));
+        expect_that!(!gamepad.joysticks()[0].is_active());
+        expect_that!(&gamepad.joystick_axes(0), eq((0., 0.)));
+    }
+
+    #[test]
+    fn button() {
+        let mut gamepad = gamepad();
+        expect_that!(!gamepad.button_pressed(0));
+        expect_that!(gamepad.on_touch(os::TouchPhase::Started, pos(310., 100.), 1));
+        expect_that!(gamepad.button_pressed(0));
+        expect_that!(gamepad.on_touch(os::TouchPhase::Moved, pos(400., 100.), 1));
+        expect_that!(gamepad.button_pressed(0));
+        expect_that!(gamepad.on_touch(os::TouchPhase::Cancelled, pos(400., 100.), 1));
+        expect_that!(!gamepad.button_pressed(0));
+    }
+
+    #[test]
+    fn touches_outside_widgets_are_not_handled() {
+        let mut gamepad = gamepad();
+        expect_that!(!gamepad.on_touch(os::TouchPhase::Started, pos(200., 300.), 0));
+        expect_that!(!gamepad.on_touch(os::TouchPhase::Moved, pos(100., 100.), 0));
+        expect_that!(!gamepad.on_touch(os::TouchPhase::Ended, pos(100., 100.), 0));
+        expect_that!(!gamepad.joysticks()[0].is_active());
+    }
+
+    #[test]
+    fn multiple_touches() {
+        let mut gamepad = gamepad();
+        gamepad.on_touch(os::TouchPhase::Started, pos(100., 120.), 0);
+        gamepad.on_touch(os::TouchPhase::Started, pos(300., 100.), 1);
+        expect_that!(gamepad.joysticks()[0].is_active());
+        expect_that!(gamepad.button_pressed(0));
+
+        gamepad.release_all();
+        expect_that!(!gamepad.joysticks()[0].is_active());
+        expect_that!(!gamepad.button_pressed(0));
+    }
+
+    #[test]
+    fn input() {
+        let mut gamepad = gamepad();
+        gamepad.on_touch(os::TouchPhase::Started, pos(100., 100.), 0);
+        gamepad.on_touch(os::TouchPhase::Moved, pos(200., 100.), 0);
+        gamepad.on_touch(os::TouchPhase::Started, pos(300., 100.), 1);
+        gamepad.on_touch(os::TouchPhase::Moved, pos(310., 100.), 1);
+        gamepad.on_touch(os::TouchPhase::Ended, pos(310., 100.), 1);
+        expect_that!(
+            &gamepad.take_input(),
+            eq(vec![
+                VirtualGamepadInput::AxisMoved(0, 1.),
+                VirtualGamepadInput::KeyPressed(os::KeyCode::Space),
+                VirtualGamepadInput::KeyReleased(os::KeyCode::Space),
+            ])
+        );
+
+        gamepad.on_touch(os::TouchPhase::Started, pos(300., 100.), 1);
+        gamepad.release_all();
+        expect_that!(
+            &gamepad.take_input(),
+            eq(vec![
+                VirtualGamepadInput::KeyPressed(os::KeyCode::Space),
+                VirtualGamepadInput::AxisMoved(0, 0.),
+                VirtualGamepadInput::KeyReleased(os::KeyCode::Space),
+            ])
+        );
+        expect_that!(gamepad.take_input().is_empty());
+    }
+}
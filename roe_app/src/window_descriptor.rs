@@ -0,0 +1,93 @@
+use roe_os as os;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WindowDescriptor {
+    pub title: String,
+    pub inner_size: Option<os::Size>,
+    pub visible: bool,
+    pub resizable: bool,
+    pub decorations: bool,
+    // Makes the window background transparent. The rendered content must clear to a color with
+    // alpha lower than 1 for the effect to be visible, and the platform compositor must support
+    // it.
+    pub transparent: bool,
+    pub always_on_top: bool,
+}
+
+impl WindowDescriptor {
+    pub fn overlay() -> Self {
+        Self {
+            decorations: false,
+            transparent: true,
+            always_on_top: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn window_builder(&self) -> os::WindowBuilder {
+        let mut builder = os::WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_visible(self.visible)
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_transparent(self.transparent)
+            .with_always_on_top(self.always_on_top);
+        if let Some(inner_size) = self.inner_size {
+            builder = builder.with_inner_size(inner_size);
+        }
+        builder
+    }
+
+    pub fn build<T: 'static>(
+        &self,
+        event_loop: &os::EventLoopWindowTarget<T>,
+    ) -> Result<os::Window, os::OsError> {
+        self.window_builder().build(event_loop)
+    }
+}
+
+impl Default for WindowDescriptor {
+    fn default() -> Self {
+        Self {
+            title: String::from("Red Orango Engine"),
+            inner_size: None,
+            visible: true,
+            resizable: true,
+            decorations: true,
+            transparent: false,
+            always_on_top: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn overlay_descriptor() {
+        let desc = WindowDescriptor::overlay();
+        expect_that!(&desc.decorations, eq(false));
+        expect_that!(&desc.transparent, eq(true));
+        expect_that!(&desc.always_on_top, eq(true));
+        expect_that!(&desc.visible, eq(true));
+        expect_that!(&desc.resizable, eq(true));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn window_creation() {
+        use os::EventLoopAnyThread;
+        let event_loop = os::EventLoop::<()>::new_any_thread();
+        let window = WindowDescriptor {
+            title: String::from("Overlay"),
+            inner_size: Some(os::Size::Physical(os::PhysicalSize::new(200, 100))),
+            visible: false,
+            ..WindowDescriptor::overlay()
+        }
+        .build(&event_loop)
+        .unwrap();
+        expect_that!(&window.inner_size(), eq(os::PhysicalSize::new(200, 100)));
+    }
+}
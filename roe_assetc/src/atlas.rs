@@ -0,0 +1,244 @@
+use super::Error;
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use std::{collections::BTreeMap, path::Path};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AtlasDescriptor {
+    pub max_size: u32,
+    // Empty pixels around each region, avoids bleeding when sampling with linear filtering.
+    pub padding: u32,
+    pub power_of_two: bool,
+}
+
+impl Default for AtlasDescriptor {
+    fn default() -> Self {
+        Self {
+            max_size: 4096,
+            padding: 1,
+            power_of_two: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl AtlasLayout {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+}
+
+// Places the rectangles in rows sorted by decreasing height, starting from the smallest atlas
+// that could contain them and growing it until they fit. Returns the atlas size and the region
+// of each rectangle, in the same order as the input.
+pub fn pack_rects(
+    sizes: &[(u32, u32)],
+    desc: &AtlasDescriptor,
+) -> Result<(u32, u32, Vec<AtlasRegion>), Error> {
+    assert!(desc.max_size > 0, "The maximum size must be higher than 0");
+    let padded: Vec<(u32, u32)> = sizes
+        .iter()
+        .map(|(w, h)| (w + desc.padding * 2, h + desc.padding * 2))
+        .collect();
+    let area: u64 = padded.iter().map(|(w, h)| *w as u64 * *h as u64).sum();
+    let widest = padded.iter().map(|(w, _)| *w).max().unwrap_or(1);
+    let mut width = std::cmp::max((area as f64).sqrt().ceil() as u32, widest);
+    if desc.power_of_two {
+        width = width.next_power_of_two();
+    }
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| padded[*b].1.cmp(&padded[*a].1).then(a.cmp(b)));
+
+    while width <= desc.max_size {
+        if let Some((height, regions)) = pack_rows(&padded, &order, width, desc) {
+            let height = if desc.power_of_two {
+                height.next_power_of_two()
+            } else {
+                height
+            };
+            if height <= desc.max_size {
+                return Ok((width, height, regions));
+            }
+        }
+        width = if desc.power_of_two {
+            width * 2
+        } else {
+            width + std::cmp::max(width / 4, 1)
+        };
+    }
+    Err(Error::AtlasTooLarge)
+}
+
+fn pack_rows(
+    padded: &[(u32, u32)],
+    order: &[usize],
+    width: u32,
+    desc: &AtlasDescriptor,
+) -> Option<(u32, Vec<AtlasRegion>)> {
+    let mut regions = vec![AtlasRegion::default(); padded.len()];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for &i in order {
+        let (w, h) = padded[i];
+        if w > width {
+            return None;
+        }
+        if x + w > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        regions[i] = AtlasRegion {
+            x: x + desc.padding,
+            y: y + desc.padding,
+            width: w - desc.padding * 2,
+            height: h - desc.padding * 2,
+        };
+        x += w;
+        row_height = std::cmp::max(row_height, h);
+    }
+    Some((std::cmp::max(y + row_height, 1), regions))
+}
+
+// Packs the named images into a single image.
+pub fn pack_atlas(
+    images: &[(String, RgbaImage)],
+    desc: &AtlasDescriptor,
+) -> Result<(RgbaImage, AtlasLayout), Error> {
+    let sizes: Vec<(u32, u32)> = images.iter().map(|(_, i)| i.dimensions()).collect();
+    let (width, height, regions) = pack_rects(&sizes, desc)?;
+    let mut atlas = RgbaImage::new(width, height);
+    let mut layout = AtlasLayout {
+        width,
+        height,
+        regions: BTreeMap::new(),
+    };
+    for ((name, image), region) in images.iter().zip(regions) {
+        image::imageops::replace(&mut atlas, image, region.x, region.y);
+        layout.regions.insert(name.clone(), region);
+    }
+    Ok((atlas, layout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn overlaps(a: &AtlasRegion, b: &AtlasRegion) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn rect_packing() {
+        let sizes = [(30, 10), (10, 30), (16, 16), (16, 16), (5, 40)];
+        let desc = AtlasDescriptor::default();
+        let (width, height, regions) = pack_rects(&sizes, &desc).unwrap();
+        expect_that!(width.is_power_of_two());
+        expect_that!(height.is_power_of_two());
+        for (i, (region, size)) in regions.iter().zip(sizes.iter()).enumerate() {
+            expect_that!(&(region.width, region.height), eq(*size));
+            expect_that!(region.x >= 1 && region.x + region.width < width);
+            expect_that!(region.y >= 1 && region.y + region.height < height);
+            for other in &regions[i + 1..] {
+                expect_that!(!overlaps(region, other));
+            }
+        }
+    }
+
+    #[test]
+    fn rect_packing_without_padding() {
+        let desc = AtlasDescriptor {
+            padding: 0,
+            power_of_two: false,
+            ..AtlasDescriptor::default()
+        };
+        let (width, height, regions) =
+            pack_rects(&[(8, 8), (8, 8), (8, 8), (8, 8)], &desc).unwrap();
+        expect_that!(&(width, height), eq((16, 16)));
+        expect_that!(
+            &regions[3],
+            eq(AtlasRegion {
+                x: 8,
+                y: 8,
+                width: 8,
+                height: 8
+            })
+        );
+    }
+
+    #[test]
+    fn atlas_too_large() {
+        let desc = AtlasDescriptor {
+            max_size: 64,
+            ..AtlasDescriptor::default()
+        };
+        expect_that!(&pack_rects(&[(64, 8)], &desc), is_variant!(Result::Err));
+        expect_that!(
+            &pack_rects(&[(40, 40), (40, 40)], &desc),
+            is_variant!(Result::Err)
+        );
+        expect_that!(&pack_rects(&[(62, 62)], &desc), is_variant!(Result::Ok));
+    }
+
+    #[test]
+    fn image_packing() {
+        let red = RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(3, 3, image::Rgba([0, 0, 255, 255]));
+        let (atlas, layout) = pack_atlas(
+            &[(String::from("red"), red), (String::from("blue"), blue)],
+            &AtlasDescriptor::default(),
+        )
+        .unwrap();
+        expect_that!(&atlas.dimensions(), eq((layout.width, layout.height)));
+        let r = layout.regions["red"];
+        let b = layout.regions["blue"];
+        expect_that!(&(r.width, r.height), eq((4, 2)));
+        expect_that!(
+            atlas.get_pixel(r.x + 3, r.y + 1),
+            eq(image::Rgba([255, 0, 0, 255]))
+        );
+        expect_that!(
+            atlas.get_pixel(b.x, b.y + 2),
+            eq(image::Rgba([0, 0, 255, 255]))
+        );
+        expect_that!(atlas.get_pixel(r.x - 1, r.y), eq(image::Rgba([0, 0, 0, 0])));
+        expect_that!(
+            &AtlasLayout::from_ron_str(&layout.to_ron_string().unwrap()).unwrap(),
+            eq(layout)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use super::Error;
+
+use roe_audio as audio;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioDescriptor {
+    // Keeps the source sample rate if None.
+    pub sample_rate: Option<u32>,
+    // Either 1 or 2. Keeps the source channel count if None.
+    pub channel_count: Option<u32>,
+}
+
+// Interleaved samples in the [-1, 1] range.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PcmData {
+    pub samples: Vec<f32>,
+    pub channel_count: u32,
+    pub sample_rate: u32,
+}
+
+impl PcmData {
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channel_count as usize
+    }
+
+    // Decodes any format supported by roe_audio, chosen based on the extension.
+    pub fn decode(extension: &str, data: Vec<u8>) -> Result<Self, Error> {
+        let mut decoder = audio::create_decoder(extension, std::io::Cursor::new(data))?;
+        let format = decoder.format();
+        let bytes = decoder
+            .read_all()
+            .map_err(|e| Error::AudioError(e.into()))?;
+        let samples = if format.bytes_per_sample() == 1 {
+            bytes.iter().map(|b| (*b as f32 - 128.) / 128.).collect()
+        } else {
+            bytes
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.)
+                .collect()
+        };
+        Ok(Self {
+            samples,
+            channel_count: format.channel_count(),
+            sample_rate: decoder.sample_rate(),
+        })
+    }
+
+    // Mono is duplicated to both channels, stereo is averaged.
+    pub fn with_channel_count(&self, channel_count: u32) -> Self {
+        assert!(
+            channel_count == 1 || channel_count == 2,
+            "Invalid channel count ({})",
+            channel_count
+        );
+        let samples = match (self.channel_count, channel_count) {
+            (1, 2) => self.samples.iter().flat_map(|s| [*s, *s]).collect(),
+            (2, 1) => self
+                .samples
+                .chunks_exact(2)
+                .map(|s| (s[0] + s[1]) * 0.5)
+                .collect(),
+            _ => self.samples.clone(),
+        };
+        Self {
+            samples,
+            channel_count,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    // Linear interpolation between neighbouring frames.
+    pub fn resampled(&self, sample_rate: u32) -> Self {
+        assert!(sample_rate > 0, "The sample rate must be higher than 0");
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return Self {
+                sample_rate,
+                ..self.clone()
+            };
+        }
+        let channels = self.channel_count as usize;
+        let frame_count = self.frame_count();
+        let out_frame_count =
+            (frame_count as u64 * sample_rate as u64).div_ceil(self.sample_rate as u64) as usize;
+        let step = self.sample_rate as f64 / sample_rate as f64;
+        let mut samples = Vec::with_capacity(out_frame_count * channels);
+        for i in 0..out_frame_count {
+            let pos = i as f64 * step;
+            let frame = std::cmp::min(pos as usize, frame_count - 1);
+            let next = std::cmp::min(frame + 1, frame_count - 1);
+            let t = (pos - frame as f64) as f32;
+            for c in 0..channels {
+                let a = self.samples[frame * channels + c];
+                let b = self.samples[next * channels + c];
+                samples.push(a + (b - a) * t);
+            }
+        }
+        Self {
+            samples,
+            channel_count: self.channel_count,
+            sample_rate,
+        }
+    }
+
+    // 16 bit PCM WAV file.
+    pub fn encode_wav(&self) -> Vec<u8> {
+        let data_size = self.samples.len() as u32 * 2;
+        let block_align = self.channel_count * 2;
+        let mut out = Vec::with_capacity(44 + data_size as usize);
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + data_size).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&(self.channel_count as u16).to_le_bytes());
+        out.extend_from_slice(&self.sample_rate.to_le_bytes());
+        out.extend_from_slice(&(self.sample_rate * block_align).to_le_bytes());
+        out.extend_from_slice(&(block_align as u16).to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_size.to_le_bytes());
+        for s in &self.samples {
+            let s = (s.clamp(-1., 1.) * 32767.).round() as i16;
+            out.extend_from_slice(&s.to_le_bytes());
+        }
+        out
+    }
+}
+
+// Converts the audio file to a 16 bit WAV file with the requested sample rate and channel
+// count.
+pub fn convert_audio(
+    extension: &str,
+    data: Vec<u8>,
+    desc: &AudioDescriptor,
+) -> Result<Vec<u8>, Error> {
+    let mut pcm = PcmData::decode(extension, data)?;
+    if let Some(channel_count) = desc.channel_count {
+        pcm = pcm.with_channel_count(channel_count);
+    }
+    if let Some(sample_rate) = desc.sample_rate {
+        pcm = pcm.resampled(sample_rate);
+    }
+    Ok(pcm.encode_wav())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const TEST_AUDIO_PATH: &str = "../roe_audio/data/audio/stereo-16-44100.wav";
+
+    #[test]
+    fn channel_conversion() {
+        let pcm = PcmData {
+            samples: vec![0.5, -0.5, 1., 0.],
+            channel_count: 2,
+            sample_rate: 100,
+        };
+        let mono = pcm.with_channel_count(1);
+        expect_that!(&mono.samples, eq(vec![0., 0.5]));
+        expect_that!(
+            &mono.with_channel_count(2).samples,
+            eq(vec![0., 0., 0.5, 0.5])
+        );
+    }
+
+    #[test]
+    fn resampling() {
+        let pcm = PcmData {
+            samples: vec![0., 1., 0., -1.],
+            channel_count: 1,
+            sample_rate: 100,
+        };
+        let up = pcm.resampled(200);
+        expect_that!(&up.sample_rate, eq(200));
+        expect_that!(&up.samples, eq(vec![0., 0.5, 1., 0.5, 0., -0.5, -1., -1.]));
+        let down = pcm.resampled(50);
+        expect_that!(&down.samples, eq(vec![0., 0.]));
+    }
+
+    #[test]
+    fn wav_round_trip() {
+        let pcm = PcmData {
+            samples: vec![0., 0.5, -0.5, 0.25],
+            channel_count: 2,
+            sample_rate: 8000,
+        };
+        let decoded = PcmData::decode("wav", pcm.encode_wav()).unwrap();
+        expect_that!(&decoded.channel_count, eq(2));
+        expect_that!(&decoded.sample_rate, eq(8000));
+        for (a, b) in decoded.samples.iter().zip(pcm.samples.iter()) {
+            expect_that!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn file_conversion() {
+        let data = std::fs::read(TEST_AUDIO_PATH).unwrap();
+        let source = PcmData::decode("wav", data.clone()).unwrap();
+        let converted = convert_audio(
+            "wav",
+            data,
+            &AudioDescriptor {
+                sample_rate: Some(22050),
+                channel_count: Some(1),
+            },
+        )
+        .unwrap();
+        let converted = PcmData::decode("wav", converted).unwrap();
+        expect_that!(&converted.channel_count, eq(1));
+        expect_that!(&converted.sample_rate, eq(22050));
+        expect_that!(
+            &converted.frame_count(),
+            eq(source.frame_count().div_ceil(2))
+        );
+    }
+}
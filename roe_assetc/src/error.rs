@@ -0,0 +1,81 @@
+use roe_assets as assets;
+use roe_audio as audio;
+use roe_text as text;
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    ImageError(image::ImageError),
+    AssetError(assets::Error),
+    AudioError(audio::Error),
+    FontError(text::FontError),
+    // The images don't fit in an atlas of the maximum size.
+    AtlasTooLarge,
+    UnsupportedFile(std::path::PathBuf),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::ImageError(e) => write!(f, "Image error ({})", e),
+            Self::AssetError(e) => write!(f, "Asset error ({})", e),
+            Self::AudioError(e) => write!(f, "Audio error ({})", e),
+            Self::FontError(e) => write!(f, "Font error ({})", e),
+            Self::AtlasTooLarge => write!(f, "The atlas exceeds the maximum size"),
+            Self::UnsupportedFile(path) => write!(f, "Unsupported file ({})", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            Self::ImageError(e) => Some(e),
+            Self::AssetError(e) => Some(e),
+            Self::AudioError(e) => Some(e),
+            Self::FontError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Self::ImageError(e)
+    }
+}
+
+impl From<assets::Error> for Error {
+    fn from(e: assets::Error) -> Self {
+        Self::AssetError(e)
+    }
+}
+
+impl From<audio::Error> for Error {
+    fn from(e: audio::Error) -> Self {
+        Self::AudioError(e)
+    }
+}
+
+impl From<text::FontError> for Error {
+    fn from(e: text::FontError) -> Self {
+        Self::FontError(e)
+    }
+}
@@ -0,0 +1,170 @@
+use super::{pack_rects, AtlasDescriptor, AtlasRegion, Error};
+
+use image::GrayImage;
+use roe_text as text;
+use serde::{Deserialize, Serialize};
+
+use std::{collections::BTreeMap, path::Path};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontAtlasDescriptor {
+    // In pixels.
+    pub size: f32,
+    pub characters: Vec<char>,
+    pub atlas: AtlasDescriptor,
+}
+
+impl Default for FontAtlasDescriptor {
+    fn default() -> Self {
+        Self {
+            size: 16.,
+            characters: (0x20u8..0x7f).map(char::from).collect(),
+            atlas: AtlasDescriptor::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct GlyphMetrics {
+    pub region: AtlasRegion,
+    // Offset of the top left corner of the bitmap from the pen position, y pointing down.
+    pub bearing: (i32, i32),
+    pub advance: f32,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct FontAtlasLayout {
+    pub size: f32,
+    pub width: u32,
+    pub height: u32,
+    pub glyphs: BTreeMap<char, GlyphMetrics>,
+}
+
+impl FontAtlasLayout {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+}
+
+struct GlyphBitmap {
+    c: char,
+    pixels: Vec<u8>,
+    width: u32,
+    rows: u32,
+    pitch: usize,
+    bearing: (i32, i32),
+    advance: f32,
+}
+
+// Rasterizes the characters and packs them into a single channel coverage image.
+pub fn bake_font_atlas<P: AsRef<Path>>(
+    path: P,
+    desc: &FontAtlasDescriptor,
+) -> Result<(GrayImage, FontAtlasLayout), Error> {
+    assert!(desc.size > 0., "The font size must be higher than 0");
+    let lib = text::FontLibrary::new()?;
+    let face = text::Face::from_file(&lib, path, 0)?;
+    face.set_char_size(desc.size, 72)?;
+
+    let mut glyphs = Vec::with_capacity(desc.characters.len());
+    for c in &desc.characters {
+        let glyph = face.load_char(*c)?;
+        let bitmap = glyph.bitmap();
+        // Empty glyphs, e.g. spaces, have no buffer.
+        let pixels = if bitmap.width() > 0 && bitmap.rows() > 0 {
+            Vec::from(bitmap.buffer())
+        } else {
+            Vec::new()
+        };
+        glyphs.push(GlyphBitmap {
+            c: *c,
+            pixels,
+            width: bitmap.width() as u32,
+            rows: bitmap.rows() as u32,
+            pitch: bitmap.pitch().unsigned_abs() as usize,
+            bearing: (glyph.bitmap_left(), -glyph.bitmap_top()),
+            advance: text::i26dot6_to_fsize(glyph.advance().x as i32),
+        });
+    }
+
+    let sizes: Vec<(u32, u32)> = glyphs.iter().map(|g| (g.width, g.rows)).collect();
+    let (width, height, regions) = pack_rects(&sizes, &desc.atlas)?;
+    let mut image = GrayImage::new(width, height);
+    let mut layout = FontAtlasLayout {
+        size: desc.size,
+        width,
+        height,
+        glyphs: BTreeMap::new(),
+    };
+    for (g, region) in glyphs.iter().zip(regions) {
+        for y in 0..g.rows {
+            for x in 0..g.width {
+                let value = g.pixels[y as usize * g.pitch + x as usize];
+                image.put_pixel(region.x + x, region.y + y, image::Luma([value]));
+            }
+        }
+        layout.glyphs.insert(
+            g.c,
+            GlyphMetrics {
+                region,
+                bearing: g.bearing,
+                advance: g.advance,
+            },
+        );
+    }
+    Ok((image, layout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const TEST_FONT_PATH: &str = "../roe_text/data/fonts/Roboto-Regular.ttf";
+
+    #[test]
+    fn font_atlas_baking() {
+        let desc = FontAtlasDescriptor {
+            size: 20.,
+            characters: vec!['a', 'Z', ' ', '#'],
+            ..FontAtlasDescriptor::default()
+        };
+        let (image, layout) = bake_font_atlas(TEST_FONT_PATH, &desc).unwrap();
+        expect_that!(&image.dimensions(), eq((layout.width, layout.height)));
+        expect_that!(&layout.glyphs.len(), eq(4));
+
+        let space = layout.glyphs[&' '];
+        expect_that!(&(space.region.width, space.region.height), eq((0, 0)));
+        expect_that!(space.advance > 0.);
+
+        let z = layout.glyphs[&'Z'];
+        expect_that!(z.region.height > 10 && z.region.height <= 20);
+        expect_that!(z.bearing.1 < 0);
+        let covered = (0..z.region.height)
+            .flat_map(|y| (0..z.region.width).map(move |x| (x, y)))
+            .any(|(x, y)| image.get_pixel(z.region.x + x, z.region.y + y).0[0] > 0);
+        expect_that!(covered);
+
+        expect_that!(
+            &FontAtlasLayout::from_ron_str(&layout.to_ron_string().unwrap()).unwrap(),
+            eq(layout)
+        );
+    }
+}
@@ -0,0 +1,17 @@
+mod error;
+pub use error::*;
+
+mod atlas;
+pub use atlas::*;
+
+mod texture;
+pub use texture::*;
+
+mod audio;
+pub use audio::*;
+
+mod font_atlas;
+pub use font_atlas::*;
+
+mod pipeline;
+pub use pipeline::*;
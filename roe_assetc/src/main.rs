@@ -0,0 +1,36 @@
+use roe_assetc::{AssetPipeline, AssetPipelineDescriptor};
+
+use std::path::PathBuf;
+
+const USAGE: &str =
+    "Usage: roe_assetc <pipeline.ron> <source dir> <output dir> [--pack <name>] [--no-checksums]";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut positional = Vec::new();
+    let mut desc = AssetPipelineDescriptor::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pack" => match args.next() {
+                Some(pack) => desc.pack = Some(PathBuf::from(pack)),
+                None => exit_with_error(USAGE),
+            },
+            "--no-checksums" => desc.checksums = false,
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() != 3 {
+        exit_with_error(USAGE);
+    }
+
+    let pipeline = AssetPipeline::load(&positional[0]).unwrap_or_else(|e| exit_with_error(e));
+    let manifest = pipeline
+        .run(&positional[1], &positional[2], &desc)
+        .unwrap_or_else(|e| exit_with_error(e));
+    println!("Processed {} assets", manifest.entries.len());
+}
+
+fn exit_with_error<T: std::fmt::Display>(e: T) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1)
+}
@@ -0,0 +1,480 @@
+use super::{
+    bake_font_atlas, convert_audio, encode_ktx2, pack_atlas, texture_variant_path, AtlasDescriptor,
+    AudioDescriptor, Error, FontAtlasDescriptor, TextureCompression, TextureDescriptor,
+};
+
+use roe_assets as assets;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+// Source paths are relative to the source directory, output paths to the output directory.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum AssetJob {
+    Copy {
+        source: PathBuf,
+        output: PathBuf,
+    },
+    // Each variant is written next to the output, see texture_variant_path, and listed in
+    // the manifest entry of the output by decreasing preference. The output itself is the
+    // fallback, usually uncompressed.
+    Texture {
+        source: PathBuf,
+        output: PathBuf,
+        #[serde(default)]
+        options: TextureDescriptor,
+        #[serde(default)]
+        variants: Vec<TextureCompression>,
+    },
+    // Region names are the source paths without extension.
+    Atlas {
+        sources: Vec<PathBuf>,
+        image: PathBuf,
+        layout: PathBuf,
+        #[serde(default)]
+        options: AtlasDescriptor,
+    },
+    Audio {
+        source: PathBuf,
+        output: PathBuf,
+        #[serde(default)]
+        options: AudioDescriptor,
+    },
+    FontAtlas {
+        source: PathBuf,
+        image: PathBuf,
+        layout: PathBuf,
+        #[serde(default)]
+        options: FontAtlasDescriptor,
+    },
+}
+
+impl AssetJob {
+    pub fn sources(&self) -> Vec<&Path> {
+        match self {
+            Self::Copy { source, .. }
+            | Self::Texture { source, .. }
+            | Self::Audio { source, .. }
+            | Self::FontAtlas { source, .. } => vec![source.as_path()],
+            Self::Atlas { sources, .. } => sources.iter().map(|s| s.as_path()).collect(),
+        }
+    }
+
+    pub fn outputs(&self) -> Vec<PathBuf> {
+        match self {
+            Self::Copy { output, .. } | Self::Audio { output, .. } => vec![output.clone()],
+            Self::Texture {
+                output, variants, ..
+            } => std::iter::once(output.clone())
+                .chain(variants.iter().map(|v| texture_variant_path(output, *v)))
+                .collect(),
+            Self::Atlas { image, layout, .. } | Self::FontAtlas { image, layout, .. } => {
+                vec![image.clone(), layout.clone()]
+            }
+        }
+    }
+
+    fn run(
+        &self,
+        source_dir: &Path,
+        outputs: &mut BTreeMap<PathBuf, Vec<u8>>,
+        variants: &mut BTreeMap<PathBuf, Vec<assets::AssetVariant>>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Copy { source, output } => {
+                outputs.insert(output.clone(), std::fs::read(source_dir.join(source))?);
+            }
+            Self::Texture {
+                source,
+                output,
+                options,
+                variants: compressions,
+            } => {
+                let image = image::open(source_dir.join(source))?.to_rgba8();
+                outputs.insert(output.clone(), encode_ktx2(&image, options));
+                let mut output_variants = Vec::with_capacity(compressions.len());
+                for compression in compressions {
+                    let path = texture_variant_path(output, *compression);
+                    let desc = TextureDescriptor {
+                        compression: *compression,
+                        ..*options
+                    };
+                    outputs.insert(path.clone(), encode_ktx2(&image, &desc));
+                    output_variants.push(assets::AssetVariant::new(
+                        assets::normalize_path(path),
+                        compression.encoding(),
+                    ));
+                }
+                if !output_variants.is_empty() {
+                    variants.insert(assets::normalize_path(output), output_variants);
+                }
+            }
+            Self::Atlas {
+                sources,
+                image,
+                layout,
+                options,
+            } => {
+                let mut images = Vec::with_capacity(sources.len());
+                for source in sources {
+                    let name = assets::normalize_path(source.with_extension(""));
+                    let data = image::open(source_dir.join(source))?.to_rgba8();
+                    images.push((name.to_string_lossy().into_owned(), data));
+                }
+                let (atlas, atlas_layout) = pack_atlas(&images, options)?;
+                outputs.insert(image.clone(), encode_png(&atlas)?);
+                outputs.insert(layout.clone(), atlas_layout.to_ron_string()?.into_bytes());
+            }
+            Self::Audio {
+                source,
+                output,
+                options,
+            } => {
+                let extension = source
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .ok_or_else(|| Error::UnsupportedFile(source.clone()))?;
+                let data = std::fs::read(source_dir.join(source))?;
+                outputs.insert(output.clone(), convert_audio(extension, data, options)?);
+            }
+            Self::FontAtlas {
+                source,
+                image,
+                layout,
+                options,
+            } => {
+                let (atlas, atlas_layout) = bake_font_atlas(source_dir.join(source), options)?;
+                outputs.insert(image.clone(), encode_png(&atlas)?);
+                outputs.insert(layout.clone(), atlas_layout.to_ron_string()?.into_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn encode_png<P, C>(image: &image::ImageBuffer<P, C>) -> Result<Vec<u8>, Error>
+where
+    P: image::Pixel<Subpixel = u8> + 'static,
+    C: std::ops::Deref<Target = [u8]>,
+{
+    let mut data = Vec::new();
+    image::png::PngEncoder::new(&mut data).encode(
+        image,
+        image.width(),
+        image.height(),
+        <P as image::Pixel>::COLOR_TYPE,
+    )?;
+    Ok(data)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AssetPipelineDescriptor {
+    // Written next to the outputs, not included in the pack.
+    pub manifest: PathBuf,
+    // Outputs are written into a single pack with this name instead of separate files.
+    pub pack: Option<PathBuf>,
+    pub checksums: bool,
+}
+
+impl Default for AssetPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            manifest: PathBuf::from("manifest.ron"),
+            pack: None,
+            checksums: true,
+        }
+    }
+}
+
+// List of conversions from source assets to the files loaded at runtime. Can be run from a
+// build script or through the roe_assetc executable.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct AssetPipeline {
+    pub jobs: Vec<AssetJob>,
+}
+
+impl AssetPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+
+    pub fn add_job(&mut self, job: AssetJob) -> &mut Self {
+        self.jobs.push(job);
+        self
+    }
+
+    // Useful to emit cargo:rerun-if-changed directives from a build script.
+    pub fn sources(&self) -> Vec<&Path> {
+        self.jobs.iter().flat_map(|j| j.sources()).collect()
+    }
+
+    // Processes all the jobs, then writes the outputs and the manifest listing them. Nothing is
+    // written if a job fails.
+    pub fn run<P, Q>(
+        &self,
+        source_dir: P,
+        output_dir: Q,
+        desc: &AssetPipelineDescriptor,
+    ) -> Result<assets::AssetManifest, Error>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let output_dir = output_dir.as_ref();
+        let mut outputs = BTreeMap::new();
+        let mut variants = BTreeMap::new();
+        for job in &self.jobs {
+            job.run(source_dir.as_ref(), &mut outputs, &mut variants)?;
+        }
+        let outputs: BTreeMap<PathBuf, Vec<u8>> = outputs
+            .into_iter()
+            .map(|(path, data)| (assets::normalize_path(path), data))
+            .collect();
+
+        let mut manifest = assets::AssetManifest::default();
+        for (path, data) in &outputs {
+            let checksum = if desc.checksums {
+                Some(assets::Checksum::compute(data))
+            } else {
+                None
+            };
+            manifest.entries.insert(
+                path.clone(),
+                assets::AssetManifestEntry {
+                    checksum,
+                    variants: variants.remove(path).unwrap_or_default(),
+                },
+            );
+        }
+
+        std::fs::create_dir_all(output_dir)?;
+        match &desc.pack {
+            Some(pack) => assets::write_pack(
+                output_dir.join(pack),
+                outputs,
+                &assets::PackDescriptor {
+                    checksums: desc.checksums,
+                },
+            )?,
+            None => {
+                for (path, data) in outputs {
+                    let path = output_dir.join(path);
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(path, data)?;
+                }
+            }
+        }
+        manifest.save(output_dir.join(&desc.manifest))?;
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use std::sync::Arc;
+
+    fn source_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("roe_assetc_{}_src", name));
+        std::fs::create_dir_all(dir.join("sprites")).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]))
+            .save(dir.join("sprites/red.png"))
+            .unwrap();
+        image::RgbaImage::from_pixel(2, 8, image::Rgba([0, 255, 0, 255]))
+            .save(dir.join("sprites/green.png"))
+            .unwrap();
+        std::fs::write(dir.join("data.txt"), "data").unwrap();
+        dir
+    }
+
+    fn test_pipeline() -> AssetPipeline {
+        let mut pipeline = AssetPipeline::new();
+        pipeline
+            .add_job(AssetJob::Copy {
+                source: PathBuf::from("data.txt"),
+                output: PathBuf::from("data.txt"),
+            })
+            .add_job(AssetJob::Texture {
+                source: PathBuf::from("sprites/red.png"),
+                output: PathBuf::from("textures/red.ktx2"),
+                options: TextureDescriptor::default(),
+                variants: vec![TextureCompression::Bc7, TextureCompression::Etc2],
+            })
+            .add_job(AssetJob::Atlas {
+                sources: vec![
+                    PathBuf::from("sprites/red.png"),
+                    PathBuf::from("sprites/green.png"),
+                ],
+                image: PathBuf::from("atlas.png"),
+                layout: PathBuf::from("atlas.ron"),
+                options: AtlasDescriptor::default(),
+            });
+        pipeline
+    }
+
+    #[test]
+    fn serialization() {
+        let pipeline = test_pipeline();
+        expect_that!(
+            &AssetPipeline::from_ron_str(&pipeline.to_ron_string().unwrap()).unwrap(),
+            eq(pipeline.clone())
+        );
+        expect_that!(
+            &pipeline.sources(),
+            eq(vec![
+                Path::new("data.txt"),
+                Path::new("sprites/red.png"),
+                Path::new("sprites/red.png"),
+                Path::new("sprites/green.png"),
+            ])
+        );
+        let pipeline = AssetPipeline::from_ron_str(
+            r#"(jobs: [Texture(source: "a.png", output: "a.ktx2", options: (mipmaps: false))])"#,
+        )
+        .unwrap();
+        expect_that!(
+            &pipeline.jobs[0],
+            eq(AssetJob::Texture {
+                source: PathBuf::from("a.png"),
+                output: PathBuf::from("a.ktx2"),
+                options: TextureDescriptor {
+                    mipmaps: false,
+                    ..TextureDescriptor::default()
+                },
+                variants: Vec::new(),
+            })
+        );
+        expect_that!(
+            &pipeline.jobs[0].outputs(),
+            eq(vec![PathBuf::from("a.ktx2")])
+        );
+        expect_that!(
+            &test_pipeline().jobs[1].outputs(),
+            eq(vec![
+                PathBuf::from("textures/red.ktx2"),
+                PathBuf::from("textures/red.bc7.ktx2"),
+                PathBuf::from("textures/red.etc2.ktx2"),
+            ])
+        );
+    }
+
+    #[test]
+    fn loose_outputs() {
+        let src = source_dir("loose");
+        let out = std::env::temp_dir().join("roe_assetc_loose_out");
+        let manifest = test_pipeline()
+            .run(&src, &out, &AssetPipelineDescriptor::default())
+            .unwrap();
+        expect_that!(
+            &manifest.entries.keys().cloned().collect::<Vec<_>>(),
+            eq(vec![
+                PathBuf::from("atlas.png"),
+                PathBuf::from("atlas.ron"),
+                PathBuf::from("data.txt"),
+                PathBuf::from("textures/red.bc7.ktx2"),
+                PathBuf::from("textures/red.etc2.ktx2"),
+                PathBuf::from("textures/red.ktx2"),
+            ])
+        );
+        expect_that!(
+            &manifest.resolve_variant(
+                "textures/red.ktx2",
+                &assets::AssetCapabilities {
+                    texture_compression_etc2: true,
+                    ..assets::AssetCapabilities::default()
+                }
+            ),
+            eq(PathBuf::from("textures/red.etc2.ktx2"))
+        );
+        expect_that!(
+            &assets::AssetManifest::load(out.join("manifest.ron")).unwrap(),
+            eq(manifest.clone())
+        );
+
+        let layout = super::super::AtlasLayout::load(out.join("atlas.ron")).unwrap();
+        expect_that!(layout.regions.contains_key("sprites/red"));
+        expect_that!(layout.regions.contains_key("sprites/green"));
+
+        let vfs = assets::Vfs::with_directory(&out);
+        let report = assets::verify_assets(&vfs, Some(&manifest));
+        expect_that!(
+            &report.status("data.txt").cloned(),
+            eq(Some(assets::IntegrityStatus::Valid))
+        );
+        expect_that!(report.is_valid());
+    }
+
+    #[test]
+    fn packed_outputs() {
+        let src = source_dir("packed");
+        let out = std::env::temp_dir().join("roe_assetc_packed_out");
+        let manifest = test_pipeline()
+            .run(
+                &src,
+                &out,
+                &AssetPipelineDescriptor {
+                    pack: Some(PathBuf::from("assets.pack")),
+                    ..AssetPipelineDescriptor::default()
+                },
+            )
+            .unwrap();
+        let vfs = Arc::new(assets::Vfs::new());
+        vfs.mount(
+            "assets",
+            "",
+            0,
+            assets::PackSource::open(out.join("assets.pack")).unwrap(),
+        );
+        expect_that!(
+            &vfs.read_to_string("data.txt").unwrap(),
+            eq(String::from("data"))
+        );
+        expect_that!(
+            &vfs.checksum("textures/red.ktx2"),
+            eq(manifest.checksum("textures/red.ktx2"))
+        );
+        expect_that!(assets::verify_assets(&vfs, Some(&manifest)).is_valid());
+    }
+
+    #[test]
+    fn failing_job() {
+        let src = source_dir("failing");
+        let out = std::env::temp_dir().join("roe_assetc_failing_out");
+        let mut pipeline = test_pipeline();
+        pipeline.add_job(AssetJob::Copy {
+            source: PathBuf::from("missing.txt"),
+            output: PathBuf::from("missing.txt"),
+        });
+        expect_that!(
+            &pipeline.run(&src, &out, &AssetPipelineDescriptor::default()),
+            is_variant!(Result::Err)
+        );
+        expect_that!(!out.join("manifest.ron").exists());
+    }
+}
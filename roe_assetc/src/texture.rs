@@ -0,0 +1,900 @@
+use image::RgbaImage;
+use roe_assets as assets;
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum TextureCompression {
+    #[default]
+    None,
+    // 4 bits per pixel, with 1 bit alpha.
+    Bc1,
+    // 8 bits per pixel, desktop GPUs.
+    Bc7,
+    // 8 bits per pixel, mobile GPUs.
+    Etc2,
+}
+
+impl TextureCompression {
+    pub fn encoding(&self) -> assets::TextureEncoding {
+        match self {
+            Self::None => assets::TextureEncoding::Rgba,
+            Self::Bc1 => assets::TextureEncoding::Bc1,
+            Self::Bc7 => assets::TextureEncoding::Bc7,
+            Self::Etc2 => assets::TextureEncoding::Etc2,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            Self::None => "rgba",
+            Self::Bc1 => "bc1",
+            Self::Bc7 => "bc7",
+            Self::Etc2 => "etc2",
+        }
+    }
+}
+
+// Output of a texture variant, e.g. "textures/red.bc7.ktx2" for "textures/red.ktx2".
+pub fn texture_variant_path<P: AsRef<Path>>(output: P, compression: TextureCompression) -> PathBuf {
+    let output = output.as_ref();
+    let mut name = output.file_stem().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(compression.suffix());
+    if let Some(extension) = output.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    output.with_file_name(name)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextureDescriptor {
+    pub compression: TextureCompression,
+    pub srgb: bool,
+    pub mipmaps: bool,
+}
+
+impl Default for TextureDescriptor {
+    fn default() -> Self {
+        Self {
+            compression: TextureCompression::None,
+            srgb: true,
+            mipmaps: true,
+        }
+    }
+}
+
+// Full mip chain down to 1x1, starting with the image itself. Each level is a box filtered
+// version of the previous one.
+pub fn generate_mipmaps(image: &RgbaImage) -> Vec<RgbaImage> {
+    let mut levels = vec![image.clone()];
+    loop {
+        let prev = levels.last().unwrap();
+        let (width, height) = prev.dimensions();
+        if width == 1 && height == 1 {
+            break;
+        }
+        let (next_width, next_height) = (std::cmp::max(width / 2, 1), std::cmp::max(height / 2, 1));
+        let next = RgbaImage::from_fn(next_width, next_height, |x, y| {
+            let xs = [x * 2, std::cmp::min(x * 2 + 1, width - 1)];
+            let ys = [y * 2, std::cmp::min(y * 2 + 1, height - 1)];
+            let mut sum = [0u32; 4];
+            for &sy in &ys {
+                for &sx in &xs {
+                    for (s, c) in sum.iter_mut().zip(prev.get_pixel(sx, sy).0.iter()) {
+                        *s += *c as u32;
+                    }
+                }
+            }
+            image::Rgba(sum.map(|s| ((s + 2) / 4) as u8))
+        });
+        levels.push(next);
+    }
+    levels
+}
+
+fn to_rgb565(c: [f32; 3]) -> u16 {
+    let r = (c[0] * 31. / 255.).round() as u16;
+    let g = (c[1] * 63. / 255.).round() as u16;
+    let b = (c[2] * 31. / 255.).round() as u16;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_rgb565(c: u16) -> [f32; 3] {
+    let r = ((c >> 11) & 31) as f32;
+    let g = ((c >> 5) & 63) as f32;
+    let b = (c & 31) as f32;
+    [r * 255. / 31., g * 255. / 63., b * 255. / 31.]
+}
+
+fn distance_squared(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+// Endpoints are the corners of the bounding box of the opaque colors. Pixels with alpha lower
+// than 128 switch the block to the 3 color mode, where index 3 is transparent.
+fn compress_bc1_block(pixels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let transparent = pixels.iter().any(|p| p[3] < 128);
+    let opaque: Vec<[f32; 3]> = pixels
+        .iter()
+        .filter(|p| p[3] >= 128)
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect();
+    let (mut min, mut max) = ([255f32; 3], [0f32; 3]);
+    for c in &opaque {
+        for i in 0..3 {
+            min[i] = min[i].min(c[i]);
+            max[i] = max[i].max(c[i]);
+        }
+    }
+    if opaque.is_empty() {
+        min = [0.; 3];
+        max = [0.; 3];
+    }
+    let (mut c0, mut c1) = (to_rgb565(max), to_rgb565(min));
+    // c0 > c1 selects the 4 color mode, c0 <= c1 the 3 color mode.
+    if transparent == (c0 > c1) {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let (e0, e1) = (from_rgb565(c0), from_rgb565(c1));
+    let palette: Vec<[f32; 3]> = if c0 > c1 {
+        vec![e0, e1, lerp(e0, e1, 1. / 3.), lerp(e0, e1, 2. / 3.)]
+    } else {
+        vec![e0, e1, lerp(e0, e1, 0.5)]
+    };
+
+    let mut indices = 0u32;
+    for (i, p) in pixels.iter().enumerate() {
+        let index = if p[3] < 128 {
+            3
+        } else {
+            let c = [p[0] as f32, p[1] as f32, p[2] as f32];
+            (0..palette.len())
+                .min_by(|a, b| {
+                    distance_squared(c, palette[*a]).total_cmp(&distance_squared(c, palette[*b]))
+                })
+                .unwrap() as u32
+        };
+        indices |= index << (i * 2);
+    }
+
+    let mut block = [0; 8];
+    block[0..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+// Blocks are stored row by row. Partial blocks at the border repeat the last row or column.
+pub fn compress_bc1(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut data = Vec::with_capacity((blocks_x * blocks_y * 8) as usize);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let mut pixels = [[0; 4]; 16];
+            for (i, p) in pixels.iter_mut().enumerate() {
+                let x = std::cmp::min(bx * 4 + i as u32 % 4, width - 1);
+                let y = std::cmp::min(by * 4 + i as u32 / 4, height - 1);
+                *p = image.get_pixel(x, y).0;
+            }
+            data.extend_from_slice(&compress_bc1_block(&pixels));
+        }
+    }
+    data
+}
+
+fn distance_squared4(a: [f32; 4], b: [f32; 4]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+// Pixels of the 4x4 block starting at (bx * 4, by * 4), row by row. Partial blocks at the
+// border repeat the last row or column.
+fn block_pixels(image: &RgbaImage, bx: u32, by: u32) -> [[u8; 4]; 16] {
+    let (width, height) = image.dimensions();
+    let mut pixels = [[0; 4]; 16];
+    for (i, p) in pixels.iter_mut().enumerate() {
+        let x = std::cmp::min(bx * 4 + i as u32 % 4, width - 1);
+        let y = std::cmp::min(by * 4 + i as u32 / 4, height - 1);
+        *p = image.get_pixel(x, y).0;
+    }
+    pixels
+}
+
+fn compress_blocks<F: Fn(&[[u8; 4]; 16]) -> [u8; 16]>(image: &RgbaImage, f: F) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    let mut data = Vec::with_capacity((blocks_x * blocks_y * 16) as usize);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            data.extend_from_slice(&f(&block_pixels(image, bx, by)));
+        }
+    }
+    data
+}
+
+// Extremes of the pixels along their principal axis, found by power iteration on the
+// covariance matrix.
+fn principal_endpoints(pixels: &[[f32; 4]; 16]) -> ([f32; 4], [f32; 4]) {
+    let mut mean = [0f32; 4];
+    for p in pixels {
+        for c in 0..4 {
+            mean[c] += p[c] / 16.;
+        }
+    }
+    let mut covariance = [[0f32; 4]; 4];
+    for p in pixels {
+        for i in 0..4 {
+            for j in 0..4 {
+                covariance[i][j] += (p[i] - mean[i]) * (p[j] - mean[j]);
+            }
+        }
+    }
+    let mut axis = [1f32; 4];
+    for _ in 0..8 {
+        let mut next = [0f32; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                next[i] += covariance[i][j] * axis[j];
+            }
+        }
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            return (mean, mean);
+        }
+        axis = next.map(|v| v / length);
+    }
+    let project = |p: &[f32; 4]| (0..4).map(|c| (p[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (mut min, mut max) = (f32::MAX, f32::MIN);
+    for p in pixels {
+        let t = project(p);
+        min = min.min(t);
+        max = max.max(t);
+    }
+    let endpoint = |t: f32| [0, 1, 2, 3].map(|c| (mean[c] + axis[c] * t).clamp(0., 255.));
+    (endpoint(min), endpoint(max))
+}
+
+const BC7_WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// 7 bit components with a shared low bit, picked to minimize the error.
+fn quantize_bc7_endpoint(endpoint: [f32; 4]) -> ([u8; 4], u8) {
+    (0..2)
+        .map(|p| {
+            let q = endpoint.map(|c| ((c - p as f32) / 2.).round().clamp(0., 127.) as u8);
+            let error: f32 = (0..4)
+                .map(|i| {
+                    let d = endpoint[i] - (q[i] * 2 + p) as f32;
+                    d * d
+                })
+                .sum();
+            (q, p, error)
+        })
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(q, p, _)| (q, p))
+        .unwrap()
+}
+
+// Mode 6: a single pair of RGBA endpoints with 4 bit indices.
+fn compress_bc7_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let colors = pixels.map(|p| p.map(|c| c as f32));
+    let (e0, e1) = principal_endpoints(&colors);
+    let (mut q0, mut p0) = quantize_bc7_endpoint(e0);
+    let (mut q1, mut p1) = quantize_bc7_endpoint(e1);
+    let unquantize = |q: [u8; 4], p: u8| q.map(|c| (c * 2 + p) as u32);
+    let (u0, u1) = (unquantize(q0, p0), unquantize(q1, p1));
+    let palette: Vec<[f32; 4]> = BC7_WEIGHTS
+        .iter()
+        .map(|w| [0, 1, 2, 3].map(|c| ((u0[c] * (64 - w) + u1[c] * w + 32) >> 6) as f32))
+        .collect();
+    let mut indices = colors.map(|c| {
+        (0..16)
+            .min_by(|a, b| {
+                distance_squared4(c, palette[*a]).total_cmp(&distance_squared4(c, palette[*b]))
+            })
+            .unwrap() as u32
+    });
+    // The high bit of the first index is implicitly 0.
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        indices = indices.map(|i| 15 - i);
+    }
+
+    let mut bits = 0u128;
+    let mut offset = 0;
+    let mut put = |value: u32, count: u32| {
+        bits |= (value as u128) << offset;
+        offset += count;
+    };
+    put(1 << 6, 7);
+    for c in 0..4 {
+        put(q0[c] as u32, 7);
+        put(q1[c] as u32, 7);
+    }
+    put(p0 as u32, 1);
+    put(p1 as u32, 1);
+    for (i, index) in indices.iter().enumerate() {
+        put(*index, if i == 0 { 3 } else { 4 });
+    }
+    bits.to_le_bytes()
+}
+
+// Blocks are stored row by row.
+pub fn compress_bc7(image: &RgbaImage) -> Vec<u8> {
+    compress_blocks(image, compress_bc7_block)
+}
+
+const ETC1_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// Modifier index order: small positive, large positive, small negative, large negative.
+fn etc1_modifier(table: usize, index: u32) -> i32 {
+    let value = ETC1_MODIFIERS[table][(index & 1) as usize];
+    if index & 2 == 0 {
+        value
+    } else {
+        -value
+    }
+}
+
+// Base color, table, per pixel indices and error of a half block in individual mode.
+fn compress_etc1_subblock(pixels: &[[u8; 4]]) -> ([u8; 3], usize, Vec<u32>, i32) {
+    let mut base = [0u8; 3];
+    for (c, b) in base.iter_mut().enumerate() {
+        let sum: u32 = pixels.iter().map(|p| p[c] as u32).sum();
+        *b = ((sum as f32 / pixels.len() as f32) / 17.).round() as u8;
+    }
+    let color = base.map(|b| b as i32 * 17);
+    (0..8)
+        .map(|table| {
+            let mut error = 0;
+            let indices = pixels
+                .iter()
+                .map(|p| {
+                    let (index, e) = (0..4)
+                        .map(|index| {
+                            let m = etc1_modifier(table, index);
+                            let e: i32 = (0..3)
+                                .map(|c| {
+                                    let d = (color[c] + m).clamp(0, 255) - p[c] as i32;
+                                    d * d
+                                })
+                                .sum();
+                            (index, e)
+                        })
+                        .min_by_key(|(_, e)| *e)
+                        .unwrap();
+                    error += e;
+                    index
+                })
+                .collect();
+            (base, table, indices, error)
+        })
+        .min_by_key(|r| r.3)
+        .unwrap()
+}
+
+// Individual mode, with the best split of the block in halves.
+fn compress_etc2_color(pixels: &[[u8; 4]; 16]) -> u64 {
+    (0..2u64)
+        .map(|flip| {
+            let in_second = |i: usize| {
+                let (x, y) = (i % 4, i / 4);
+                if flip == 0 {
+                    x >= 2
+                } else {
+                    y >= 2
+                }
+            };
+            let halves: Vec<Vec<usize>> = [false, true]
+                .iter()
+                .map(|second| (0..16).filter(|i| in_second(*i) == *second).collect())
+                .collect();
+            let results: Vec<_> = halves
+                .iter()
+                .map(|half| {
+                    let half_pixels: Vec<[u8; 4]> = half.iter().map(|i| pixels[*i]).collect();
+                    compress_etc1_subblock(&half_pixels)
+                })
+                .collect();
+            let (b0, b1) = (results[0].0, results[1].0);
+            let mut block = (b0[0] as u64) << 60
+                | (b1[0] as u64) << 56
+                | (b0[1] as u64) << 52
+                | (b1[1] as u64) << 48
+                | (b0[2] as u64) << 44
+                | (b1[2] as u64) << 40
+                | (results[0].1 as u64) << 37
+                | (results[1].1 as u64) << 34
+                | flip << 32;
+            for (half, result) in halves.iter().zip(results.iter()) {
+                for (i, index) in half.iter().zip(result.2.iter()) {
+                    // Pixels are indexed column by column.
+                    let bit = (i % 4) * 4 + i / 4;
+                    block |= ((*index as u64 >> 1) << (16 + bit)) | ((*index as u64 & 1) << bit);
+                }
+            }
+            (block, results[0].3 + results[1].3)
+        })
+        .min_by_key(|(_, error)| *error)
+        .unwrap()
+        .0
+}
+
+fn compress_eac_alpha(pixels: &[[u8; 4]; 16]) -> u64 {
+    let alpha = pixels.map(|p| p[3] as i32);
+    let (min, max) = (*alpha.iter().min().unwrap(), *alpha.iter().max().unwrap());
+    let mut best = (0, i32::MAX);
+    for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+        let span = modifiers[7] - modifiers[3];
+        let multiplier = ((max - min) as f32 / span as f32).round() as i32;
+        for multiplier in [multiplier - 1, multiplier, multiplier + 1] {
+            let multiplier = multiplier.clamp(1, 15);
+            let base = ((min + max) as f32 / 2.
+                - (modifiers[7] + modifiers[3]) as f32 * multiplier as f32 / 2.)
+                .round()
+                .clamp(0., 255.) as i32;
+            let mut block = (base as u64) << 56 | (multiplier as u64) << 52 | (table as u64) << 48;
+            let mut error = 0;
+            for (i, a) in alpha.iter().enumerate() {
+                let (index, e) = modifiers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, m)| {
+                        let d = (base + m * multiplier).clamp(0, 255) - a;
+                        (index, d * d)
+                    })
+                    .min_by_key(|(_, e)| *e)
+                    .unwrap();
+                error += e;
+                let pixel = (i % 4) * 4 + i / 4;
+                block |= (index as u64) << (45 - 3 * pixel);
+            }
+            if error < best.1 {
+                best = (block, error);
+            }
+        }
+    }
+    best.0
+}
+
+// EAC alpha block followed by the ETC2 color block, both big endian.
+fn compress_etc2_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[0..8].copy_from_slice(&compress_eac_alpha(pixels).to_be_bytes());
+    block[8..16].copy_from_slice(&compress_etc2_color(pixels).to_be_bytes());
+    block
+}
+
+// Blocks are stored row by row.
+pub fn compress_etc2(image: &RgbaImage) -> Vec<u8> {
+    compress_blocks(image, compress_etc2_block)
+}
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+const VK_FORMAT_BC1_RGBA_UNORM_BLOCK: u32 = 133;
+const VK_FORMAT_BC1_RGBA_SRGB_BLOCK: u32 = 134;
+const VK_FORMAT_BC7_UNORM_BLOCK: u32 = 145;
+const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+const VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK: u32 = 151;
+const VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK: u32 = 152;
+
+pub(crate) fn vk_format(desc: &TextureDescriptor) -> u32 {
+    match (desc.compression, desc.srgb) {
+        (TextureCompression::None, false) => VK_FORMAT_R8G8B8A8_UNORM,
+        (TextureCompression::None, true) => VK_FORMAT_R8G8B8A8_SRGB,
+        (TextureCompression::Bc1, false) => VK_FORMAT_BC1_RGBA_UNORM_BLOCK,
+        (TextureCompression::Bc1, true) => VK_FORMAT_BC1_RGBA_SRGB_BLOCK,
+        (TextureCompression::Bc7, false) => VK_FORMAT_BC7_UNORM_BLOCK,
+        (TextureCompression::Bc7, true) => VK_FORMAT_BC7_SRGB_BLOCK,
+        (TextureCompression::Etc2, false) => VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK,
+        (TextureCompression::Etc2, true) => VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK,
+    }
+}
+
+// (channel id, bit offset, bit length, upper value)
+type DfdSample = (u8, u16, u8, u32);
+
+// Basic data format descriptor block, as required by the KTX2 specification.
+fn data_format_descriptor(desc: &TextureDescriptor) -> Vec<u8> {
+    let (color_model, block_size, bytes_per_block) = match desc.compression {
+        TextureCompression::None => (1u8, 1u8, 4u8),
+        TextureCompression::Bc1 => (128, 4, 8),
+        TextureCompression::Bc7 => (134, 4, 16),
+        TextureCompression::Etc2 => (161, 4, 16),
+    };
+    let samples: Vec<DfdSample> = match desc.compression {
+        TextureCompression::None => vec![
+            (0, 0, 8, 255),
+            (1, 8, 8, 255),
+            (2, 16, 8, 255),
+            (15, 24, 8, 255),
+        ],
+        TextureCompression::Bc1 => vec![(1, 0, 64, u32::MAX)],
+        TextureCompression::Bc7 => vec![(0, 0, 128, u32::MAX)],
+        // Alpha block, then color block.
+        TextureCompression::Etc2 => vec![(15, 0, 64, u32::MAX), (2, 64, 64, u32::MAX)],
+    };
+    let block_length = 24 + 16 * samples.len() as u32;
+    let mut dfd = Vec::new();
+    dfd.extend_from_slice(&(block_length + 4).to_le_bytes());
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&(2 | (block_length << 16)).to_le_bytes());
+    let transfer = if desc.srgb { 2 } else { 1 };
+    dfd.extend_from_slice(&[color_model, 1, transfer, 0]);
+    dfd.extend_from_slice(&[block_size - 1, block_size - 1, 0, 0]);
+    dfd.extend_from_slice(&[bytes_per_block, 0, 0, 0, 0, 0, 0, 0]);
+    for (channel, offset, length, upper) in samples {
+        // The alpha channel is always linear.
+        let qualifiers = if desc.srgb && channel == 15 { 0x10 } else { 0 };
+        dfd.extend_from_slice(&offset.to_le_bytes());
+        dfd.extend_from_slice(&[length - 1, channel | qualifiers]);
+        dfd.extend_from_slice(&[0; 4]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&upper.to_le_bytes());
+    }
+    dfd
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+// Encodes the image as a KTX2 file without supercompression. Mip levels are stored from the
+// smallest to the largest, as recommended by the specification.
+pub fn encode_ktx2(image: &RgbaImage, desc: &TextureDescriptor) -> Vec<u8> {
+    let levels = if desc.mipmaps {
+        generate_mipmaps(image)
+    } else {
+        vec![image.clone()]
+    };
+    let level_data: Vec<Vec<u8>> = levels
+        .iter()
+        .map(|level| match desc.compression {
+            TextureCompression::None => level.as_raw().clone(),
+            TextureCompression::Bc1 => compress_bc1(level),
+            TextureCompression::Bc7 => compress_bc7(level),
+            TextureCompression::Etc2 => compress_etc2(level),
+        })
+        .collect();
+    let alignment = match desc.compression {
+        TextureCompression::None => 4,
+        TextureCompression::Bc1 => 8,
+        TextureCompression::Bc7 | TextureCompression::Etc2 => 16,
+    };
+
+    let dfd = data_format_descriptor(desc);
+    let header_size = KTX2_IDENTIFIER.len() + 9 * 4 + 4 * 4 + 2 * 8;
+    let level_index_size = level_data.len() * 3 * 8;
+    let dfd_offset = header_size + level_index_size;
+    let mut level_offsets = vec![0; level_data.len()];
+    let mut offset = dfd_offset + dfd.len();
+    for (i, data) in level_data.iter().enumerate().rev() {
+        offset = align(offset, alignment);
+        level_offsets[i] = offset;
+        offset += data.len();
+    }
+
+    let mut out = Vec::with_capacity(offset);
+    out.extend_from_slice(&KTX2_IDENTIFIER);
+    for value in [
+        vk_format(desc),
+        1,
+        image.width(),
+        image.height(),
+        0,
+        0,
+        1,
+        level_data.len() as u32,
+        0,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [dfd_offset as u32, dfd.len() as u32, 0, 0] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out.extend_from_slice(&0u64.to_le_bytes());
+    out.extend_from_slice(&0u64.to_le_bytes());
+    for (data, offset) in level_data.iter().zip(level_offsets.iter()) {
+        out.extend_from_slice(&(*offset as u64).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    }
+    out.extend_from_slice(&dfd);
+    for (data, offset) in level_data.iter().zip(level_offsets.iter()).rev() {
+        out.resize(*offset, 0);
+        out.extend_from_slice(data);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    fn read_u64(data: &[u8], offset: usize) -> u64 {
+        read_u32(data, offset) as u64 | ((read_u32(data, offset + 4) as u64) << 32)
+    }
+
+    #[test]
+    fn mipmaps() {
+        let mut image = RgbaImage::new(4, 2);
+        image.put_pixel(0, 0, image::Rgba([200, 100, 40, 255]));
+        image.put_pixel(1, 1, image::Rgba([200, 100, 40, 255]));
+        let levels = generate_mipmaps(&image);
+        expect_that!(
+            &levels.iter().map(|l| l.dimensions()).collect::<Vec<_>>(),
+            eq(vec![(4, 2), (2, 1), (1, 1)])
+        );
+        expect_that!(
+            levels[1].get_pixel(0, 0),
+            eq(image::Rgba([100, 50, 20, 128]))
+        );
+        expect_that!(levels[1].get_pixel(1, 0), eq(image::Rgba([0, 0, 0, 0])));
+        expect_that!(levels[2].get_pixel(0, 0), eq(image::Rgba([50, 25, 10, 64])));
+    }
+
+    #[test]
+    fn bc1_compression() {
+        let mut image = RgbaImage::from_pixel(6, 4, image::Rgba([255, 0, 0, 255]));
+        image.put_pixel(0, 0, image::Rgba([0, 0, 0, 255]));
+        image.put_pixel(5, 3, image::Rgba([0, 0, 0, 0]));
+        let data = compress_bc1(&image);
+        expect_that!(&data.len(), eq(16));
+
+        // First block: opaque, 4 color mode.
+        let c0 = u16::from_le_bytes([data[0], data[1]]);
+        let c1 = u16::from_le_bytes([data[2], data[3]]);
+        expect_that!(c0 > c1);
+        let indices = read_u32(&data, 4);
+        let endpoints = [from_rgb565(c0), from_rgb565(c1)];
+        expect_that!(&endpoints[(indices & 3) as usize], eq([0., 0., 0.]));
+        expect_that!(
+            &endpoints[((indices >> 2) & 3) as usize],
+            eq([255., 0., 0.])
+        );
+
+        // Second block: transparent pixel, 3 color mode.
+        let c0 = u16::from_le_bytes([data[8], data[9]]);
+        let c1 = u16::from_le_bytes([data[10], data[11]]);
+        expect_that!(c0 <= c1);
+        let indices = read_u32(&data, 12);
+        // Pixel (5, 3) is at position (1, 3) in the block, and repeated at (2, 3) and (3, 3).
+        expect_that!(&((indices >> 26) & 3), eq(3));
+        expect_that!(&((indices >> 30) & 3), eq(3));
+        expect_that!(&((indices >> 24) & 3), eq(0));
+    }
+
+    // Left half opaque red, right half translucent blue.
+    fn two_color_image() -> RgbaImage {
+        RgbaImage::from_fn(4, 4, |x, _| {
+            if x < 2 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 128])
+            }
+        })
+    }
+
+    fn expect_close(decoded: &[[i32; 4]; 16], image: &RgbaImage, tolerance: i32) {
+        for (i, color) in decoded.iter().enumerate() {
+            let expected = image.get_pixel(i as u32 % 4, i as u32 / 4).0;
+            for c in 0..4 {
+                expect_that!(&(color[c] - expected[c] as i32).abs(), leq(tolerance));
+            }
+        }
+    }
+
+    fn decode_bc7_mode6(block: &[u8]) -> [[i32; 4]; 16] {
+        let bits = u128::from_le_bytes(block.try_into().unwrap());
+        let mut offset = 0;
+        let mut get = |count: u32| {
+            let value = ((bits >> offset) & ((1 << count) - 1)) as i32;
+            offset += count;
+            value
+        };
+        assert_eq!(get(7), 1 << 6);
+        let mut endpoints = [[0; 2]; 4];
+        for channel in endpoints.iter_mut() {
+            *channel = [get(7), get(7)];
+        }
+        let p = [get(1), get(1)];
+        let mut decoded = [[0; 4]; 16];
+        for (i, color) in decoded.iter_mut().enumerate() {
+            let w = BC7_WEIGHTS[get(if i == 0 { 3 } else { 4 }) as usize] as i32;
+            for c in 0..4 {
+                let (e0, e1) = (endpoints[c][0] * 2 + p[0], endpoints[c][1] * 2 + p[1]);
+                color[c] = (e0 * (64 - w) + e1 * w + 32) >> 6;
+            }
+        }
+        decoded
+    }
+
+    fn decode_etc2_rgba(block: &[u8]) -> [[i32; 4]; 16] {
+        let alpha = u64::from_be_bytes(block[0..8].try_into().unwrap());
+        let color = u64::from_be_bytes(block[8..16].try_into().unwrap());
+        // Individual mode only.
+        assert_eq!((color >> 33) & 1, 0);
+        let flip = (color >> 32) & 1 == 1;
+        let base = |shift: u32| ((color >> shift) & 15) as i32 * 17;
+        let bases = [
+            [base(60), base(52), base(44)],
+            [base(56), base(48), base(40)],
+        ];
+        let tables = [((color >> 37) & 7) as usize, ((color >> 34) & 7) as usize];
+        let alpha_base = (alpha >> 56) as i32;
+        let multiplier = ((alpha >> 52) & 15) as i32;
+        let alpha_table = ((alpha >> 48) & 15) as usize;
+        let mut decoded = [[0; 4]; 16];
+        for (i, pixel) in decoded.iter_mut().enumerate() {
+            let (x, y) = (i % 4, i / 4);
+            let half = if flip { y >= 2 } else { x >= 2 } as usize;
+            let bit = x * 4 + y;
+            let index = (((color >> (16 + bit)) & 1) << 1 | ((color >> bit) & 1)) as u32;
+            let m = etc1_modifier(tables[half], index);
+            for c in 0..3 {
+                pixel[c] = (bases[half][c] + m).clamp(0, 255);
+            }
+            let alpha_index = ((alpha >> (45 - 3 * bit)) & 7) as usize;
+            pixel[3] =
+                (alpha_base + EAC_MODIFIERS[alpha_table][alpha_index] * multiplier).clamp(0, 255);
+        }
+        decoded
+    }
+
+    #[test]
+    fn bc7_compression() {
+        let image = two_color_image();
+        let data = compress_bc7(&image);
+        expect_that!(&data.len(), eq(16));
+        expect_close(&decode_bc7_mode6(&data), &image, 1);
+    }
+
+    #[test]
+    fn etc2_compression() {
+        let image = two_color_image();
+        let data = compress_etc2(&image);
+        expect_that!(&data.len(), eq(16));
+        expect_close(&decode_etc2_rgba(&data), &image, 2);
+    }
+
+    #[test]
+    fn variant_paths() {
+        expect_that!(
+            &texture_variant_path("textures/red.ktx2", TextureCompression::Bc7),
+            eq(PathBuf::from("textures/red.bc7.ktx2"))
+        );
+        expect_that!(
+            &texture_variant_path("red", TextureCompression::Etc2),
+            eq(PathBuf::from("red.etc2"))
+        );
+        expect_that!(
+            &TextureCompression::Etc2.encoding(),
+            eq(assets::TextureEncoding::Etc2)
+        );
+    }
+
+    #[test]
+    fn ktx2_encoding() {
+        let image = RgbaImage::from_pixel(8, 4, image::Rgba([10, 20, 30, 40]));
+        let data = encode_ktx2(&image, &TextureDescriptor::default());
+        expect_that!(&data[0..12].to_vec(), eq(KTX2_IDENTIFIER.to_vec()));
+        expect_that!(&read_u32(&data, 12), eq(VK_FORMAT_R8G8B8A8_SRGB));
+        expect_that!(&read_u32(&data, 20), eq(8));
+        expect_that!(&read_u32(&data, 24), eq(4));
+        expect_that!(&read_u32(&data, 40), eq(4));
+
+        let dfd_offset = read_u32(&data, 48) as usize;
+        let dfd_length = read_u32(&data, 52) as usize;
+        expect_that!(&read_u32(&data, dfd_offset), eq(dfd_length as u32));
+        expect_that!(&dfd_length, eq(92));
+
+        let expected_lengths = [128, 32, 8, 4];
+        for (i, expected_length) in expected_lengths.iter().enumerate() {
+            let entry = 80 + i * 24;
+            let offset = read_u64(&data, entry) as usize;
+            let length = read_u64(&data, entry + 8) as usize;
+            expect_that!(&length, eq(*expected_length));
+            expect_that!(offset.is_multiple_of(4));
+            expect_that!(&data[offset..offset + 4].to_vec(), eq(vec![10, 20, 30, 40]));
+        }
+        // Level 0 is stored last.
+        expect_that!(&(read_u64(&data, 80) as usize + 128), eq(data.len()));
+    }
+
+    #[test]
+    fn ktx2_compressed_encoding() {
+        let image = RgbaImage::from_pixel(8, 8, image::Rgba([10, 20, 30, 255]));
+        let data = encode_ktx2(
+            &image,
+            &TextureDescriptor {
+                compression: TextureCompression::Bc1,
+                srgb: false,
+                mipmaps: false,
+            },
+        );
+        expect_that!(&read_u32(&data, 12), eq(VK_FORMAT_BC1_RGBA_UNORM_BLOCK));
+        expect_that!(&read_u32(&data, 40), eq(1));
+        expect_that!(&read_u32(&data, 52), eq(44));
+        let offset = read_u64(&data, 80) as usize;
+        expect_that!(offset.is_multiple_of(8));
+        expect_that!(&read_u64(&data, 88), eq(32));
+        expect_that!(&(offset + 32), eq(data.len()));
+    }
+    #[test]
+    fn ktx2_block_compressed_variants() {
+        let image = RgbaImage::from_pixel(8, 4, image::Rgba([10, 20, 30, 255]));
+        for (compression, format) in [
+            (TextureCompression::Bc7, VK_FORMAT_BC7_SRGB_BLOCK),
+            (TextureCompression::Etc2, VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK),
+        ] {
+            let data = encode_ktx2(
+                &image,
+                &TextureDescriptor {
+                    compression,
+                    srgb: true,
+                    mipmaps: false,
+                },
+            );
+            expect_that!(&read_u32(&data, 12), eq(format));
+            let offset = read_u64(&data, 80) as usize;
+            expect_that!(offset.is_multiple_of(16));
+            expect_that!(&read_u64(&data, 88), eq(32));
+            expect_that!(&(offset + 32), eq(data.len()));
+        }
+    }
+}
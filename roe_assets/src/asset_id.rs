@@ -0,0 +1,243 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+// Kind of asset identified by an AssetId. Ids of different kinds can't be mixed up, even if they
+// were created from the same name.
+pub trait AssetKind: 'static {
+    // Used in the Debug output, e.g. "TextureId".
+    const ID_NAME: &'static str;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TextureAsset {}
+
+impl AssetKind for TextureAsset {
+    const ID_NAME: &'static str = "TextureId";
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FontAsset {}
+
+impl AssetKind for FontAsset {
+    const ID_NAME: &'static str = "FontId";
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SoundAsset {}
+
+impl AssetKind for SoundAsset {
+    const ID_NAME: &'static str = "SoundId";
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MeshAsset {}
+
+impl AssetKind for MeshAsset {
+    const ID_NAME: &'static str = "MeshId";
+}
+
+pub type TextureId = AssetId<TextureAsset>;
+pub type FontId = AssetId<FontAsset>;
+pub type SoundId = AssetId<SoundAsset>;
+pub type MeshId = AssetId<MeshAsset>;
+
+#[derive(Debug, Default)]
+struct AssetNames {
+    names: Vec<Arc<str>>,
+    indices: HashMap<Arc<str>, u32>,
+}
+
+// Names of all ids created so far, grouped by asset kind.
+fn registry() -> &'static Mutex<HashMap<&'static str, AssetNames>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<&'static str, AssetNames>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Typed identifier of an asset, usually its path in the asset file system. The names are
+// interned in a process wide registry: ids are cheap to copy, hash and compare, and the name is
+// still available for lookups, logging and serialization, where ids are stored as their name.
+// The same name always maps to the same id within a process, but the numeric value isn't stable
+// across runs.
+pub struct AssetId<K: AssetKind> {
+    index: u32,
+    kind: PhantomData<fn() -> K>,
+}
+
+impl<K: AssetKind> AssetId<K> {
+    // Registers the name if needed.
+    pub fn new(name: &str) -> Self {
+        let mut registry = registry().lock().unwrap();
+        let names = registry.entry(K::ID_NAME).or_default();
+        let index = match names.indices.get(name) {
+            Some(index) => *index,
+            None => {
+                let index = names.names.len() as u32;
+                let name: Arc<str> = Arc::from(name);
+                names.names.push(name.clone());
+                names.indices.insert(name, index);
+                index
+            }
+        };
+        Self::from_index(index)
+    }
+
+    // Id of an already registered name.
+    pub fn find(name: &str) -> Option<Self> {
+        let registry = registry().lock().unwrap();
+        registry
+            .get(K::ID_NAME)
+            .and_then(|names| names.indices.get(name))
+            .map(|index| Self::from_index(*index))
+    }
+
+    // All ids of this kind registered so far, in registration order.
+    pub fn registered() -> Vec<Self> {
+        let registry = registry().lock().unwrap();
+        let count = registry
+            .get(K::ID_NAME)
+            .map(|names| names.names.len())
+            .unwrap_or(0);
+        (0..count as u32).map(Self::from_index).collect()
+    }
+
+    pub fn name(&self) -> Arc<str> {
+        let registry = registry().lock().unwrap();
+        registry[K::ID_NAME].names[self.index as usize].clone()
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    fn from_index(index: u32) -> Self {
+        Self {
+            index,
+            kind: PhantomData,
+        }
+    }
+}
+
+// Implemented manually, since deriving would require the same traits on the kind.
+impl<K: AssetKind> Clone for AssetId<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K: AssetKind> Copy for AssetId<K> {}
+
+impl<K: AssetKind> PartialEq for AssetId<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<K: AssetKind> Eq for AssetId<K> {}
+
+// Registration order, not alphabetical order.
+impl<K: AssetKind> PartialOrd for AssetId<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: AssetKind> Ord for AssetId<K> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<K: AssetKind> std::hash::Hash for AssetId<K> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<K: AssetKind> std::fmt::Debug for AssetId<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({:?})", K::ID_NAME, self.name())
+    }
+}
+
+impl<K: AssetKind> std::fmt::Display for AssetId<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl<K: AssetKind> From<&str> for AssetId<K> {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl<K: AssetKind> From<&String> for AssetId<K> {
+    fn from(name: &String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl<K: AssetKind> From<String> for AssetId<K> {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl<K: AssetKind> Serialize for AssetId<K> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+impl<'de, K: AssetKind> Deserialize<'de> for AssetId<K> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Self::new(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn registration() {
+        let a = TextureId::new("asset_id_test/a.png");
+        let b = TextureId::new("asset_id_test/b.png");
+        expect_that!(&a, not(eq(b)));
+        expect_that!(&TextureId::new("asset_id_test/a.png"), eq(a));
+        expect_that!(&TextureId::find("asset_id_test/b.png"), eq(Some(b)));
+        expect_that!(&TextureId::find("asset_id_test/c.png"), eq(None));
+        expect_that!(&SoundId::find("asset_id_test/a.png"), eq(None));
+        expect_that!(TextureId::registered().contains(&b));
+
+        expect_that!(&&*a.name(), eq("asset_id_test/a.png"));
+        expect_that!(&format!("{}", a), eq(String::from("asset_id_test/a.png")));
+        expect_that!(
+            &format!("{:?}", a),
+            eq(String::from("TextureId(\"asset_id_test/a.png\")"))
+        );
+
+        let sound = SoundId::from("asset_id_test/a.png");
+        expect_that!(
+            &format!("{:?}", sound),
+            eq(String::from("SoundId(\"asset_id_test/a.png\")"))
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let font = FontId::new("asset_id_test/font.ttf");
+        let s = ron::ser::to_string(&font).unwrap();
+        expect_that!(&s, eq(String::from("\"asset_id_test/font.ttf\"")));
+        expect_that!(&ron::de::from_str::<FontId>(&s).unwrap(), eq(font));
+        let mesh: MeshId = ron::de::from_str("\"asset_id_test/new_mesh\"").unwrap();
+        expect_that!(&MeshId::find("asset_id_test/new_mesh"), eq(Some(mesh)));
+    }
+}
@@ -0,0 +1,721 @@
+use super::{
+    expected_checksum, normalize_path, verify_assets, AssetCapabilities, AssetManifest, Error,
+    IntegrityReport, IntegrityStatus, Vfs,
+};
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicBool},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub enum LoadPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+// Shared flag used to cancel a group of loads, e.g. all the loads of a level.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, atomic::Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(atomic::Ordering::Acquire)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct LoadId(u64);
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LoadState {
+    Queued,
+    Loading,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoadInfo {
+    pub id: LoadId,
+    pub path: PathBuf,
+    pub priority: LoadPriority,
+    pub state: LoadState,
+}
+
+#[derive(Debug)]
+pub struct LoadResult {
+    pub id: LoadId,
+    pub path: PathBuf,
+    // File actually read: a variant of the asset picked from the manifest, or the path itself.
+    pub source: PathBuf,
+    // Cancelled loads return Error::Cancelled.
+    pub data: Result<Vec<u8>, Error>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AssetLoaderDescriptor {
+    // Loading is IO bound, a few threads are usually enough.
+    pub thread_count: usize,
+    // Check loaded data against the checksums from the manifest or the pack. Corrupted assets
+    // return Error::ChecksumMismatch.
+    pub verify_checksums: bool,
+    // Selects the asset variants listed in the manifest. Without capabilities, the fallback
+    // encodings are loaded.
+    pub capabilities: AssetCapabilities,
+}
+
+impl Default for AssetLoaderDescriptor {
+    fn default() -> Self {
+        Self {
+            thread_count: 2,
+            verify_checksums: true,
+            capabilities: AssetCapabilities::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueuedLoad {
+    id: LoadId,
+    path: PathBuf,
+    source: PathBuf,
+    priority: LoadPriority,
+    tokens: Vec<CancellationToken>,
+}
+
+impl QueuedLoad {
+    fn is_cancelled(&self) -> bool {
+        self.tokens.iter().any(|t| t.is_cancelled())
+    }
+
+    fn info(&self, state: LoadState) -> LoadInfo {
+        LoadInfo {
+            id: self.id,
+            path: self.path.clone(),
+            priority: self.priority,
+            state,
+        }
+    }
+}
+
+// Higher priorities first, then first come first served.
+impl Ord for QueuedLoad {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for QueuedLoad {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedLoad {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for QueuedLoad {}
+
+#[derive(Debug, Default)]
+struct LoadQueue {
+    queued: BinaryHeap<QueuedLoad>,
+    loading: BTreeMap<LoadId, LoadInfo>,
+    // Tokens of the queued and loading requests, used to cancel them by id.
+    tokens: BTreeMap<LoadId, CancellationToken>,
+    // Integrity of the assets verified so far.
+    integrity: BTreeMap<PathBuf, IntegrityStatus>,
+    paused: bool,
+    closed: bool,
+}
+
+#[derive(Debug, Default)]
+struct SharedQueue {
+    queue: Mutex<LoadQueue>,
+    condvar: Condvar,
+}
+
+// Reads asset files from the file system on worker threads. Queued loads are processed by priority, and can be
+// cancelled individually or in groups through cancellation tokens, in which case they are
+// skipped without reading the file. Results are returned in completion order.
+pub struct AssetLoader {
+    vfs: Arc<Vfs>,
+    manifest: Option<Arc<AssetManifest>>,
+    shared: Arc<SharedQueue>,
+    next_id: u64,
+    capabilities: AssetCapabilities,
+    pending: usize,
+    result_receiver: mpsc::Receiver<LoadResult>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl AssetLoader {
+    pub fn new(vfs: Arc<Vfs>, desc: &AssetLoaderDescriptor) -> Self {
+        Self::create(vfs, None, desc)
+    }
+
+    // Checksums listed in the manifest take precedence over the ones stored in packs.
+    pub fn with_manifest(
+        vfs: Arc<Vfs>,
+        manifest: Arc<AssetManifest>,
+        desc: &AssetLoaderDescriptor,
+    ) -> Self {
+        Self::create(vfs, Some(manifest), desc)
+    }
+
+    fn create(
+        vfs: Arc<Vfs>,
+        manifest: Option<Arc<AssetManifest>>,
+        desc: &AssetLoaderDescriptor,
+    ) -> Self {
+        assert!(
+            desc.thread_count > 0,
+            "The thread count must be higher than 0"
+        );
+        let shared = Arc::new(SharedQueue::default());
+        let (result_sender, result_receiver) = mpsc::channel();
+        let workers = (0..desc.thread_count)
+            .map(|_| {
+                let vfs = Arc::clone(&vfs);
+                let manifest = manifest.clone();
+                let verify_checksums = desc.verify_checksums;
+                let shared = Arc::clone(&shared);
+                let result_sender = result_sender.clone();
+                thread::spawn(move || {
+                    while let Some(load) = next_load(&shared) {
+                        let mut status = None;
+                        let data = if load.is_cancelled() {
+                            Err(Error::Cancelled)
+                        } else {
+                            match vfs.read(&load.source) {
+                                Ok(_) if load.is_cancelled() => Err(Error::Cancelled),
+                                Ok(data) if verify_checksums => {
+                                    let expected =
+                                        expected_checksum(&vfs, manifest.as_deref(), &load.source);
+                                    let s = IntegrityStatus::check(expected, &data);
+                                    let result = if s.is_failure() {
+                                        Err(Error::ChecksumMismatch(load.source.clone()))
+                                    } else {
+                                        Ok(data)
+                                    };
+                                    status = Some(s);
+                                    result
+                                }
+                                result => result,
+                            }
+                        };
+                        {
+                            let mut queue = shared.queue.lock().unwrap();
+                            queue.loading.remove(&load.id);
+                            queue.tokens.remove(&load.id);
+                            if let Some(status) = status {
+                                queue.integrity.insert(normalize_path(&load.source), status);
+                            }
+                        }
+                        let result = LoadResult {
+                            id: load.id,
+                            path: load.path,
+                            source: load.source,
+                            data,
+                        };
+                        if result_sender.send(result).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self {
+            vfs,
+            manifest,
+            shared,
+            next_id: 0,
+            capabilities: desc.capabilities,
+            pending: 0,
+            result_receiver,
+            workers,
+        }
+    }
+
+    pub fn vfs(&self) -> &Arc<Vfs> {
+        &self.vfs
+    }
+
+    pub fn manifest(&self) -> Option<&Arc<AssetManifest>> {
+        self.manifest.as_ref()
+    }
+
+    pub fn capabilities(&self) -> AssetCapabilities {
+        self.capabilities
+    }
+
+    // File read when loading the asset, see AssetManifest::resolve_variant.
+    pub fn resolve_variant<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        match &self.manifest {
+            Some(manifest) => manifest.resolve_variant(path, &self.capabilities),
+            None => path.as_ref().to_path_buf(),
+        }
+    }
+
+    // Reads and verifies all the assets on the calling thread. Meant to be called at startup,
+    // the results are stored in the integrity report.
+    pub fn verify_assets(&self) -> IntegrityReport {
+        let report = verify_assets(&self.vfs, self.manifest.as_deref());
+        self.shared
+            .queue
+            .lock()
+            .unwrap()
+            .integrity
+            .extend(report.results.clone());
+        report
+    }
+
+    // Integrity of the asset as of the last time it was loaded or verified.
+    pub fn integrity_status<P: AsRef<Path>>(&self, path: P) -> Option<IntegrityStatus> {
+        self.shared
+            .queue
+            .lock()
+            .unwrap()
+            .integrity
+            .get(&normalize_path(path))
+            .cloned()
+    }
+
+    pub fn integrity_report(&self) -> IntegrityReport {
+        IntegrityReport {
+            results: self.shared.queue.lock().unwrap().integrity.clone(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P, priority: LoadPriority) -> LoadId {
+        self.enqueue(path.as_ref(), priority, None)
+    }
+
+    // The load is cancelled when the token is.
+    pub fn load_with_token<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        priority: LoadPriority,
+        token: &CancellationToken,
+    ) -> LoadId {
+        self.enqueue(path.as_ref(), priority, Some(token.clone()))
+    }
+
+    // Does nothing if the load already completed.
+    pub fn cancel(&self, id: LoadId) {
+        if let Some(token) = self.shared.queue.lock().unwrap().tokens.get(&id) {
+            token.cancel();
+        }
+    }
+
+    // While paused, loads that already started complete but no new load is started.
+    pub fn pause(&self) {
+        self.shared.queue.lock().unwrap().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.shared.queue.lock().unwrap().paused = false;
+        self.shared.condvar.notify_all();
+    }
+
+    // Loads being read and queued loads, by decreasing priority.
+    pub fn in_flight_loads(&self) -> Vec<LoadInfo> {
+        let queue = self.shared.queue.lock().unwrap();
+        let mut loads: Vec<LoadInfo> = queue.loading.values().cloned().collect();
+        loads.extend(queue.queued.iter().map(|load| load.info(LoadState::Queued)));
+        loads.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.id.cmp(&b.id)));
+        loads
+    }
+
+    // Number of loads whose result hasn't been returned yet.
+    pub fn pending_count(&self) -> usize {
+        self.pending
+    }
+
+    // Returns a completed result if available, without blocking.
+    pub fn poll_result(&mut self) -> Option<LoadResult> {
+        let result = self.result_receiver.try_recv().ok()?;
+        self.pending -= 1;
+        Some(result)
+    }
+
+    // Blocks until a result is available. Returns None if there are no pending loads.
+    pub fn wait_result(&mut self) -> Option<LoadResult> {
+        if self.pending == 0 {
+            return None;
+        }
+        let result = self
+            .result_receiver
+            .recv()
+            .expect("The asset loader threads terminated unexpectedly");
+        self.pending -= 1;
+        Some(result)
+    }
+
+    fn enqueue(
+        &mut self,
+        path: &Path,
+        priority: LoadPriority,
+        token: Option<CancellationToken>,
+    ) -> LoadId {
+        let id = LoadId(self.next_id);
+        self.next_id += 1;
+        self.pending += 1;
+        let own_token = CancellationToken::new();
+        let mut tokens = vec![own_token.clone()];
+        tokens.extend(token);
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.tokens.insert(id, own_token);
+            queue.queued.push(QueuedLoad {
+                id,
+                path: path.to_path_buf(),
+                source: self.resolve_variant(path),
+                priority,
+                tokens,
+            });
+        }
+        self.shared.condvar.notify_one();
+        id
+    }
+}
+
+impl std::fmt::Debug for AssetLoader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "AssetLoader {{ vfs: {:?}, pending: {} }}",
+            self.vfs, self.pending
+        )
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.closed = true;
+            queue.queued.clear();
+        }
+        self.shared.condvar.notify_all();
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+// Blocks until a load is available. Returns None when the loader is dropped.
+fn next_load(shared: &SharedQueue) -> Option<QueuedLoad> {
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        if queue.closed {
+            return None;
+        }
+        if !queue.paused {
+            if let Some(load) = queue.queued.pop() {
+                queue.loading.insert(load.id, load.info(LoadState::Loading));
+                return Some(load);
+            }
+        }
+        queue = shared.condvar.wait(queue).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn test_dir(name: &str, file_count: usize) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("roe_assets_{}", name));
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..file_count {
+            std::fs::write(dir.join(format!("{}.bin", i)), vec![i as u8; i + 1]).unwrap();
+        }
+        dir
+    }
+
+    fn queued_load(id: u64, priority: LoadPriority) -> QueuedLoad {
+        QueuedLoad {
+            id: LoadId(id),
+            path: PathBuf::new(),
+            source: PathBuf::new(),
+            priority,
+            tokens: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn queue_order() {
+        let mut heap = BinaryHeap::new();
+        heap.push(queued_load(0, LoadPriority::Low));
+        heap.push(queued_load(1, LoadPriority::High));
+        heap.push(queued_load(2, LoadPriority::Normal));
+        heap.push(queued_load(3, LoadPriority::High));
+        heap.push(queued_load(4, LoadPriority::Critical));
+        let order: Vec<u64> = std::iter::from_fn(|| heap.pop().map(|l| l.id.0)).collect();
+        expect_that!(&order, eq(vec![4, 1, 3, 2, 0]));
+    }
+
+    #[test]
+    fn loading() {
+        let dir = test_dir("loading", 4);
+        let mut loader = AssetLoader::new(
+            Arc::new(Vfs::with_directory(&dir)),
+            &AssetLoaderDescriptor::default(),
+        );
+        let ids: Vec<LoadId> = (0..4)
+            .map(|i| loader.load(format!("{}.bin", i), LoadPriority::Normal))
+            .collect();
+        let missing = loader.load("missing.bin", LoadPriority::Low);
+        expect_that!(&loader.pending_count(), eq(5));
+
+        let mut results = BTreeMap::new();
+        while let Some(result) = loader.wait_result() {
+            results.insert(result.id, result);
+        }
+        expect_that!(&loader.pending_count(), eq(0));
+        expect_that!(loader.poll_result().is_none());
+        for (i, id) in ids.iter().enumerate() {
+            expect_that!(
+                &results[id].data.as_ref().unwrap().clone(),
+                eq(vec![i as u8; i + 1])
+            );
+        }
+        expect_that!(&results[&missing].data, is_variant!(Result::Err));
+        expect_that!(loader.in_flight_loads().is_empty());
+    }
+
+    #[test]
+    fn priorities_and_inspection() {
+        let dir = test_dir("priorities", 3);
+        let mut loader = AssetLoader::new(
+            Arc::new(Vfs::with_directory(&dir)),
+            &AssetLoaderDescriptor {
+                thread_count: 1,
+                ..AssetLoaderDescriptor::default()
+            },
+        );
+        loader.pause();
+        let low = loader.load("0.bin", LoadPriority::Low);
+        let high = loader.load("1.bin", LoadPriority::High);
+        let normal = loader.load("2.bin", LoadPriority::Normal);
+
+        let in_flight = loader.in_flight_loads();
+        expect_that!(
+            &in_flight.iter().map(|l| l.id).collect::<Vec<_>>(),
+            eq(vec![high, normal, low])
+        );
+        expect_that!(&in_flight[0].path, eq(PathBuf::from("1.bin")));
+        expect_that!(&in_flight[0].state, eq(LoadState::Queued));
+
+        loader.resume();
+        let order: Vec<LoadId> =
+            std::iter::from_fn(|| loader.wait_result().map(|r| r.id)).collect();
+        expect_that!(&order, eq(vec![high, normal, low]));
+    }
+
+    #[test]
+    fn cancellation() {
+        let dir = test_dir("cancellation", 4);
+        let mut loader = AssetLoader::new(
+            Arc::new(Vfs::with_directory(&dir)),
+            &AssetLoaderDescriptor::default(),
+        );
+        loader.pause();
+        let level = CancellationToken::new();
+        let a = loader.load_with_token("0.bin", LoadPriority::Normal, &level);
+        let b = loader.load_with_token("1.bin", LoadPriority::High, &level);
+        let c = loader.load("2.bin", LoadPriority::Normal);
+        let d = loader.load("3.bin", LoadPriority::Normal);
+        level.cancel();
+        loader.cancel(d);
+        loader.resume();
+
+        let mut results = BTreeMap::new();
+        while let Some(result) = loader.wait_result() {
+            results.insert(result.id, result);
+        }
+        expect_that!(&results[&a].data, is_variant!(Result::Err));
+        expect_that!(&results[&b].data, is_variant!(Result::Err));
+        expect_that!(&results[&d].data, is_variant!(Result::Err));
+        expect_that!(
+            &results[&c].data.as_ref().unwrap().clone(),
+            eq(vec![2u8; 3])
+        );
+
+        // Cancelling a completed load has no effect.
+        loader.cancel(c);
+    }
+
+    #[test]
+    fn checksum_verification() {
+        let pack_path = std::env::temp_dir().join("roe_assets_loader_verification.pack");
+        crate::write_pack(
+            &pack_path,
+            vec![("packed.bin", b"packed".to_vec())],
+            &crate::PackDescriptor::default(),
+        )
+        .unwrap();
+        let vfs = Arc::new(Vfs::new());
+        vfs.mount("pack", "", 0, crate::PackSource::open(&pack_path).unwrap());
+        let loose = crate::MemorySource::new();
+        loose.insert("loose.bin", b"loose".to_vec());
+        loose.insert("tampered.bin", b"tampered".to_vec());
+        vfs.mount("loose", "", 0, loose);
+        let mut manifest = AssetManifest::default();
+        manifest.entries.insert(
+            PathBuf::from("tampered.bin"),
+            crate::AssetManifestEntry {
+                checksum: Some(crate::Checksum::compute(b"original")),
+                ..crate::AssetManifestEntry::default()
+            },
+        );
+
+        let mut loader =
+            AssetLoader::with_manifest(vfs, Arc::new(manifest), &AssetLoaderDescriptor::default());
+        let packed = loader.load("packed.bin", LoadPriority::Normal);
+        let loose = loader.load("./loose.bin", LoadPriority::Normal);
+        let tampered = loader.load("tampered.bin", LoadPriority::Normal);
+        let mut results = BTreeMap::new();
+        while let Some(result) = loader.wait_result() {
+            results.insert(result.id, result);
+        }
+        expect_that!(
+            &results[&packed].data.as_ref().unwrap().clone(),
+            eq(b"packed".to_vec())
+        );
+        expect_that!(
+            &results[&loose].data.as_ref().unwrap().clone(),
+            eq(b"loose".to_vec())
+        );
+        expect_that!(&results[&tampered].data, is_variant!(Result::Err));
+        expect_that!(
+            &loader.integrity_status("packed.bin"),
+            eq(Some(IntegrityStatus::Valid))
+        );
+        expect_that!(
+            &loader.integrity_status("loose.bin"),
+            eq(Some(IntegrityStatus::Unverified))
+        );
+        expect_that!(
+            &loader
+                .integrity_status("tampered.bin")
+                .unwrap()
+                .is_failure(),
+            eq(true)
+        );
+        expect_that!(!loader.integrity_report().is_valid());
+
+        let report = loader.verify_assets();
+        expect_that!(&report.failures().count(), eq(1));
+        expect_that!(&loader.integrity_report(), eq(report));
+    }
+
+    #[test]
+    fn variants() {
+        let source = crate::MemorySource::new();
+        source.insert("sprite.ktx2", b"rgba".to_vec());
+        source.insert("sprite.bc7.ktx2", b"bc7".to_vec());
+        source.insert("sprite.etc2.ktx2", b"etc2".to_vec());
+        let vfs = Arc::new(Vfs::new());
+        vfs.mount("assets", "", 0, source);
+        let mut manifest = AssetManifest::default();
+        manifest.entries.insert(
+            PathBuf::from("sprite.ktx2"),
+            crate::AssetManifestEntry {
+                variants: vec![
+                    crate::AssetVariant::new("sprite.bc7.ktx2", crate::TextureEncoding::Bc7),
+                    crate::AssetVariant::new("sprite.etc2.ktx2", crate::TextureEncoding::Etc2),
+                ],
+                ..crate::AssetManifestEntry::default()
+            },
+        );
+        manifest.entries.insert(
+            PathBuf::from("sprite.etc2.ktx2"),
+            crate::AssetManifestEntry {
+                checksum: Some(crate::Checksum::compute(b"etc2")),
+                ..crate::AssetManifestEntry::default()
+            },
+        );
+        let manifest = Arc::new(manifest);
+
+        let load = |capabilities| {
+            let mut loader = AssetLoader::with_manifest(
+                Arc::clone(&vfs),
+                Arc::clone(&manifest),
+                &AssetLoaderDescriptor {
+                    capabilities,
+                    ..AssetLoaderDescriptor::default()
+                },
+            );
+            loader.load("sprite.ktx2", LoadPriority::Normal);
+            let result = loader.wait_result().unwrap();
+            expect_that!(&result.path, eq(PathBuf::from("sprite.ktx2")));
+            expect_that!(
+                &loader.resolve_variant("sprite.ktx2"),
+                eq(result.source.clone())
+            );
+            (
+                result.source,
+                result.data.unwrap(),
+                loader.integrity_report(),
+            )
+        };
+
+        let (source, data, _) = load(AssetCapabilities {
+            texture_compression_bc: true,
+            ..AssetCapabilities::default()
+        });
+        expect_that!(&source, eq(PathBuf::from("sprite.bc7.ktx2")));
+        expect_that!(&data, eq(b"bc7".to_vec()));
+
+        // The checksum of the variant is verified.
+        let (source, data, report) = load(AssetCapabilities {
+            texture_compression_etc2: true,
+            ..AssetCapabilities::default()
+        });
+        expect_that!(&source, eq(PathBuf::from("sprite.etc2.ktx2")));
+        expect_that!(&data, eq(b"etc2".to_vec()));
+        expect_that!(
+            &report.status("sprite.etc2.ktx2").cloned(),
+            eq(Some(IntegrityStatus::Valid))
+        );
+
+        let (source, data, _) = load(AssetCapabilities::default());
+        expect_that!(&source, eq(PathBuf::from("sprite.ktx2")));
+        expect_that!(&data, eq(b"rgba".to_vec()));
+    }
+
+    #[test]
+    #[should_panic(expected = "The thread count must be higher than 0")]
+    fn no_threads() {
+        AssetLoader::new(
+            Arc::new(Vfs::new()),
+            &AssetLoaderDescriptor {
+                thread_count: 0,
+                ..AssetLoaderDescriptor::default()
+            },
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use std::path::PathBuf;
+
+// GPU encoding of the pixels of a texture variant.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum TextureEncoding {
+    // Uncompressed, supported everywhere.
+    #[default]
+    Rgba,
+    Bc1,
+    Bc7,
+    Etc2,
+}
+
+impl TextureEncoding {
+    pub fn is_supported(&self, capabilities: &AssetCapabilities) -> bool {
+        match self {
+            Self::Rgba => true,
+            Self::Bc1 | Self::Bc7 => capabilities.texture_compression_bc,
+            Self::Etc2 => capabilities.texture_compression_etc2,
+        }
+    }
+}
+
+// What the device can consume, used to pick between asset variants. BC formats are usually
+// available on desktop GPUs, ETC2 on mobile GPUs.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct AssetCapabilities {
+    pub texture_compression_bc: bool,
+    pub texture_compression_etc2: bool,
+}
+
+impl AssetCapabilities {
+    pub fn all() -> Self {
+        Self {
+            texture_compression_bc: true,
+            texture_compression_etc2: true,
+        }
+    }
+}
+
+// Alternative file for an asset, loaded instead of it when the device supports its encoding.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct AssetVariant {
+    pub path: PathBuf,
+    pub encoding: TextureEncoding,
+}
+
+impl AssetVariant {
+    pub fn new<P: Into<PathBuf>>(path: P, encoding: TextureEncoding) -> Self {
+        Self {
+            path: path.into(),
+            encoding,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn support() {
+        let desktop = AssetCapabilities {
+            texture_compression_bc: true,
+            ..AssetCapabilities::default()
+        };
+        expect_that!(TextureEncoding::Rgba.is_supported(&AssetCapabilities::default()));
+        expect_that!(TextureEncoding::Bc7.is_supported(&desktop));
+        expect_that!(!TextureEncoding::Etc2.is_supported(&desktop));
+        expect_that!(TextureEncoding::Etc2.is_supported(&AssetCapabilities::all()));
+        expect_that!(
+            &AssetVariant::new("a.bc7.ktx2", TextureEncoding::Bc7).path,
+            eq(PathBuf::from("a.bc7.ktx2"))
+        );
+    }
+}
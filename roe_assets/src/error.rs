@@ -0,0 +1,44 @@
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    FileNotFound(std::path::PathBuf),
+    InvalidPack(String),
+    ChecksumMismatch(std::path::PathBuf),
+    Cancelled,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::FileNotFound(path) => write!(f, "File not found ({})", path.display()),
+            Self::InvalidPack(e) => write!(f, "Invalid pack ({})", e),
+            Self::ChecksumMismatch(path) => write!(f, "Checksum mismatch ({})", path.display()),
+            Self::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
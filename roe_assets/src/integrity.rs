@@ -0,0 +1,365 @@
+use super::{normalize_path, AssetCapabilities, AssetVariant, Error, Vfs};
+
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+// SHA-1 of the contents of an asset. Detects corrupted or modified files, but isn't meant to
+// protect against deliberate attacks.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct Checksum(pub [u8; 20]);
+
+impl Checksum {
+    pub fn compute(data: &[u8]) -> Self {
+        Self(Sha1::digest(data).into())
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn from_hex(s: &str) -> Option<Self> {
+        if s.len() != 40 || !s.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0; 20];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Self(bytes))
+    }
+}
+
+impl std::fmt::Display for Checksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+impl std::fmt::Debug for Checksum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Checksum({})", self.to_hex())
+    }
+}
+
+impl Serialize for Checksum {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Checksum {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::from_hex(&s).ok_or_else(|| serde::de::Error::custom("Invalid checksum"))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct AssetManifestEntry {
+    #[serde(default)]
+    pub checksum: Option<Checksum>,
+    // Alternative encodings of the asset, by decreasing preference. The entry itself is the
+    // fallback when the device supports none of them. Variants are listed as separate entries
+    // as well.
+    #[serde(default)]
+    pub variants: Vec<AssetVariant>,
+}
+
+// List of the assets expected in the file system.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct AssetManifest {
+    pub entries: BTreeMap<PathBuf, AssetManifestEntry>,
+}
+
+impl AssetManifest {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+
+    pub fn load_from_vfs<P: AsRef<Path>>(vfs: &Vfs, path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&vfs.read_to_string(path)?)
+    }
+
+    pub fn checksum<P: AsRef<Path>>(&self, path: P) -> Option<Checksum> {
+        self.entries
+            .get(path.as_ref())
+            .and_then(|entry| entry.checksum)
+    }
+
+    // File to load for the asset on a device with the given capabilities: the first supported
+    // variant, or the asset itself.
+    pub fn resolve_variant<P: AsRef<Path>>(
+        &self,
+        path: P,
+        capabilities: &AssetCapabilities,
+    ) -> PathBuf {
+        let path = normalize_path(path);
+        self.entries
+            .get(&path)
+            .and_then(|entry| {
+                entry
+                    .variants
+                    .iter()
+                    .find(|v| v.encoding.is_supported(capabilities))
+            })
+            .map(|v| normalize_path(&v.path))
+            .unwrap_or(path)
+    }
+}
+
+// Checksum from the manifest if available, otherwise from the source of the file.
+pub fn expected_checksum(
+    vfs: &Vfs,
+    manifest: Option<&AssetManifest>,
+    path: &Path,
+) -> Option<Checksum> {
+    manifest
+        .and_then(|m| m.checksum(path))
+        .or_else(|| vfs.checksum(path))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum IntegrityStatus {
+    Valid,
+    // No checksum available.
+    Unverified,
+    Corrupted {
+        expected: Checksum,
+        actual: Checksum,
+    },
+    Missing,
+}
+
+impl IntegrityStatus {
+    pub fn check(expected: Option<Checksum>, data: &[u8]) -> Self {
+        match expected {
+            Some(expected) => {
+                let actual = Checksum::compute(data);
+                if actual == expected {
+                    Self::Valid
+                } else {
+                    Self::Corrupted { expected, actual }
+                }
+            }
+            None => Self::Unverified,
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Corrupted { .. } | Self::Missing)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IntegrityReport {
+    pub results: BTreeMap<PathBuf, IntegrityStatus>,
+}
+
+impl IntegrityReport {
+    pub fn status<P: AsRef<Path>>(&self, path: P) -> Option<&IntegrityStatus> {
+        self.results.get(path.as_ref())
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.results.values().any(IntegrityStatus::is_failure)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (&PathBuf, &IntegrityStatus)> {
+        self.results
+            .iter()
+            .filter(|(_, status)| status.is_failure())
+    }
+}
+
+// Reads all the files visible in the file system and the ones listed in the manifest, and
+// checks them against their expected checksum. Meant to be run at startup.
+pub fn verify_assets(vfs: &Vfs, manifest: Option<&AssetManifest>) -> IntegrityReport {
+    let mut paths = vfs.paths();
+    if let Some(manifest) = manifest {
+        paths.extend(manifest.entries.keys().cloned());
+    }
+    let results = paths
+        .into_iter()
+        .map(|path| {
+            let status = match vfs.read(&path) {
+                Ok(data) => IntegrityStatus::check(expected_checksum(vfs, manifest, &path), &data),
+                Err(_) => IntegrityStatus::Missing,
+            };
+            (path, status)
+        })
+        .collect();
+    IntegrityReport { results }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{write_pack, MemorySource, PackDescriptor, PackSource};
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn checksum() {
+        let checksum = Checksum::compute(b"abc");
+        expect_that!(
+            &checksum.to_hex(),
+            eq(String::from("a9993e364706816aba3e25717850c26c9cd0d89d"))
+        );
+        expect_that!(&Checksum::from_hex(&checksum.to_hex()), eq(Some(checksum)));
+        expect_that!(&Checksum::from_hex("a9993e"), eq(None));
+        expect_that!(
+            &Checksum::from_hex("z9993e364706816aba3e25717850c26c9cd0d89d"),
+            eq(None)
+        );
+    }
+
+    #[test]
+    fn manifest_serialization() {
+        let mut manifest = AssetManifest::default();
+        manifest.entries.insert(
+            PathBuf::from("a.png"),
+            AssetManifestEntry {
+                checksum: Some(Checksum::compute(b"a")),
+                variants: vec![AssetVariant::new("a.bc7.ktx2", crate::TextureEncoding::Bc7)],
+            },
+        );
+        manifest
+            .entries
+            .insert(PathBuf::from("b.png"), AssetManifestEntry::default());
+        let s = manifest.to_ron_string().unwrap();
+        expect_that!(&AssetManifest::from_ron_str(&s).unwrap(), eq(manifest));
+        expect_that!(
+            &AssetManifest::from_ron_str(r#"(entries: {"a": (checksum: Some("00"))})"#),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn variant_resolution() {
+        use crate::TextureEncoding;
+        let manifest = AssetManifest::from_ron_str(
+            r#"(entries: {
+                "sprite.ktx2": (variants: [
+                    (path: "sprite.bc7.ktx2", encoding: Bc7),
+                    (path: "./sprite.etc2.ktx2", encoding: Etc2),
+                ]),
+                "sprite.bc7.ktx2": (),
+                "sprite.etc2.ktx2": (),
+            })"#,
+        )
+        .unwrap();
+        expect_that!(
+            &manifest.entries[Path::new("sprite.ktx2")].variants[1].encoding,
+            eq(TextureEncoding::Etc2)
+        );
+
+        let desktop = AssetCapabilities {
+            texture_compression_bc: true,
+            ..AssetCapabilities::default()
+        };
+        let mobile = AssetCapabilities {
+            texture_compression_etc2: true,
+            ..AssetCapabilities::default()
+        };
+        expect_that!(
+            &manifest.resolve_variant("sprite.ktx2", &desktop),
+            eq(PathBuf::from("sprite.bc7.ktx2"))
+        );
+        expect_that!(
+            &manifest.resolve_variant("./sprite.ktx2", &mobile),
+            eq(PathBuf::from("sprite.etc2.ktx2"))
+        );
+        expect_that!(
+            &manifest.resolve_variant("sprite.ktx2", &AssetCapabilities::all()),
+            eq(PathBuf::from("sprite.bc7.ktx2"))
+        );
+        expect_that!(
+            &manifest.resolve_variant("sprite.ktx2", &AssetCapabilities::default()),
+            eq(PathBuf::from("sprite.ktx2"))
+        );
+        expect_that!(
+            &manifest.resolve_variant("unlisted.png", &desktop),
+            eq(PathBuf::from("unlisted.png"))
+        );
+    }
+
+    #[test]
+    fn verification() {
+        let pack_path = std::env::temp_dir().join("roe_assets_integrity.pack");
+        write_pack(
+            &pack_path,
+            vec![
+                ("packed.bin", b"packed".to_vec()),
+                ("listed.bin", b"listed".to_vec()),
+            ],
+            &PackDescriptor::default(),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new();
+        vfs.mount("pack", "", 0, PackSource::open(&pack_path).unwrap());
+        let loose = MemorySource::new();
+        loose.insert("loose.bin", b"loose".to_vec());
+        loose.insert("tampered.bin", b"tampered".to_vec());
+        vfs.mount("loose", "", 0, loose);
+
+        let mut manifest = AssetManifest::default();
+        for (path, data) in [
+            ("tampered.bin", &b"original"[..]),
+            ("missing.bin", &b"missing"[..]),
+        ] {
+            manifest.entries.insert(
+                PathBuf::from(path),
+                AssetManifestEntry {
+                    checksum: Some(Checksum::compute(data)),
+                    ..AssetManifestEntry::default()
+                },
+            );
+        }
+
+        let report = verify_assets(&vfs, Some(&manifest));
+        expect_that!(&report.results.len(), eq(5));
+        expect_that!(
+            &report.status("packed.bin").cloned(),
+            eq(Some(IntegrityStatus::Valid))
+        );
+        expect_that!(
+            &report.status("loose.bin").cloned(),
+            eq(Some(IntegrityStatus::Unverified))
+        );
+        expect_that!(
+            &report.status("missing.bin").cloned(),
+            eq(Some(IntegrityStatus::Missing))
+        );
+        expect_that!(
+            &report.status("tampered.bin").cloned(),
+            eq(Some(IntegrityStatus::Corrupted {
+                expected: Checksum::compute(b"original"),
+                actual: Checksum::compute(b"tampered"),
+            }))
+        );
+        expect_that!(!report.is_valid());
+        expect_that!(&report.failures().count(), eq(2));
+        expect_that!(verify_assets(&vfs, None).is_valid());
+    }
+}
@@ -0,0 +1,17 @@
+mod error;
+pub use error::*;
+
+mod vfs;
+pub use vfs::*;
+
+mod integrity;
+pub use integrity::*;
+
+mod asset_variant;
+pub use asset_variant::*;
+
+mod asset_loader;
+pub use asset_loader::*;
+
+mod asset_id;
+pub use asset_id::*;
@@ -0,0 +1,599 @@
+use super::{Checksum, Error};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+// Removes "." components and resolves ".." components, so that the same file always has the
+// same path. Paths are relative to the mount point.
+pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::Normal(c) => normalized.push(c),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => (),
+        }
+    }
+    normalized
+}
+
+pub trait VfsSource: std::fmt::Debug + Send + Sync {
+    fn contains(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error>;
+    fn paths(&self) -> Vec<PathBuf>;
+
+    // Checksum stored alongside the file, if any.
+    fn checksum(&self, _path: &Path) -> Option<Checksum> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn collect_paths(&self, dir: &Path, paths: &mut Vec<PathBuf>) {
+        let entries = match std::fs::read_dir(self.root.join(dir)) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let path = dir.join(entry.file_name());
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => self.collect_paths(&path, paths),
+                Ok(_) => paths.push(path),
+                Err(_) => (),
+            }
+        }
+    }
+}
+
+impl VfsSource for DirectorySource {
+    fn contains(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        Ok(std::fs::read(self.root.join(path))?)
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        self.collect_paths(Path::new(""), &mut paths);
+        paths
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemorySource {
+    files: RwLock<BTreeMap<PathBuf, Arc<[u8]>>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<P: AsRef<Path>>(&self, path: P, data: Vec<u8>) {
+        self.files
+            .write()
+            .unwrap()
+            .insert(normalize_path(path), Arc::from(data));
+    }
+
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.files
+            .write()
+            .unwrap()
+            .remove(&normalize_path(path))
+            .is_some()
+    }
+}
+
+impl VfsSource for MemorySource {
+    fn contains(&self, path: &Path) -> bool {
+        self.files.read().unwrap().contains_key(path)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.files.read().unwrap().get(path) {
+            Some(data) => Ok(data.to_vec()),
+            None => Err(Error::FileNotFound(path.to_path_buf())),
+        }
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        self.files.read().unwrap().keys().cloned().collect()
+    }
+}
+
+const PACK_MAGIC: &[u8; 8] = b"ROEPACK\0";
+const PACK_VERSION: u32 = 2;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    size: u64,
+    checksum: Option<Checksum>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PackDescriptor {
+    // Store the checksum of each file, verified when reading it.
+    pub checksums: bool,
+}
+
+impl Default for PackDescriptor {
+    fn default() -> Self {
+        Self { checksums: true }
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, Error> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// Writes the files into a single pack file. Layout: magic, version, entry count, entries (path
+// length, UTF-8 path, data offset, data size, checksum flag, optional checksum), file data.
+// Numbers are little endian. Version 1 packs have no checksum fields.
+pub fn write_pack<P, I, Q>(path: P, files: I, desc: &PackDescriptor) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (Q, Vec<u8>)>,
+    Q: AsRef<Path>,
+{
+    let files: BTreeMap<String, Vec<u8>> = files
+        .into_iter()
+        .map(|(path, data)| {
+            let path = normalize_path(path);
+            match path.to_str() {
+                Some(s) => Ok((s.replace('\\', "/"), data)),
+                None => Err(Error::InvalidPack(format!(
+                    "Non UTF-8 path {}",
+                    path.display()
+                ))),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    let checksum_size = if desc.checksums { 21 } else { 1 };
+    let header_size = PACK_MAGIC.len() as u64
+        + 8
+        + files
+            .keys()
+            .map(|p| 4 + p.len() as u64 + 16 + checksum_size)
+            .sum::<u64>();
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    out.write_all(PACK_MAGIC)?;
+    out.write_all(&PACK_VERSION.to_le_bytes())?;
+    out.write_all(&(files.len() as u32).to_le_bytes())?;
+    let mut offset = header_size;
+    for (path, data) in files.iter() {
+        out.write_all(&(path.len() as u32).to_le_bytes())?;
+        out.write_all(path.as_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&(data.len() as u64).to_le_bytes())?;
+        if desc.checksums {
+            out.write_all(&[1])?;
+            out.write_all(&Checksum::compute(data).0)?;
+        } else {
+            out.write_all(&[0])?;
+        }
+        offset += data.len() as u64;
+    }
+    for data in files.values() {
+        out.write_all(data)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Files stored in a pack written by write_pack. Only the index is kept in memory.
+#[derive(Debug)]
+pub struct PackSource {
+    path: PathBuf,
+    entries: BTreeMap<PathBuf, PackEntry>,
+}
+
+impl PackSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let mut file = std::io::BufReader::new(std::fs::File::open(path.as_ref())?);
+        let file_size = file.get_ref().metadata()?.len();
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != PACK_MAGIC {
+            return Err(Error::InvalidPack(String::from("Invalid signature")));
+        }
+        let version = read_u32(&mut file)?;
+        if version == 0 || version > PACK_VERSION {
+            return Err(Error::InvalidPack(format!(
+                "Unsupported version {}",
+                version
+            )));
+        }
+        let count = read_u32(&mut file)?;
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let len = read_u32(&mut file)? as usize;
+            let mut path = vec![0; len];
+            file.read_exact(&mut path)?;
+            let path = String::from_utf8(path)
+                .map_err(|_| Error::InvalidPack(String::from("Non UTF-8 path")))?;
+            let offset = read_u64(&mut file)?;
+            let size = read_u64(&mut file)?;
+            let mut checksum = None;
+            if version >= 2 {
+                let mut flag = [0];
+                file.read_exact(&mut flag)?;
+                if flag[0] != 0 {
+                    let mut bytes = [0; 20];
+                    file.read_exact(&mut bytes)?;
+                    checksum = Some(Checksum(bytes));
+                }
+            }
+            let entry = PackEntry {
+                offset,
+                size,
+                checksum,
+            };
+            if entry
+                .offset
+                .checked_add(entry.size)
+                .is_none_or(|end| end > file_size)
+            {
+                return Err(Error::InvalidPack(format!("Truncated file {}", path)));
+            }
+            entries.insert(normalize_path(path), entry);
+        }
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl VfsSource for PackSource {
+    fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let entry = self
+            .entries
+            .get(path)
+            .ok_or_else(|| Error::FileNotFound(path.to_path_buf()))?;
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.offset))?;
+        let mut data = vec![0; entry.size as usize];
+        file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        self.entries.keys().cloned().collect()
+    }
+
+    fn checksum(&self, path: &Path) -> Option<Checksum> {
+        self.entries.get(path).and_then(|entry| entry.checksum)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Mount {
+    name: String,
+    mount_point: PathBuf,
+    priority: i32,
+    source: Arc<dyn VfsSource>,
+}
+
+impl Mount {
+    fn source_path(&self, path: &Path) -> Option<PathBuf> {
+        path.strip_prefix(&self.mount_point)
+            .ok()
+            .map(Path::to_path_buf)
+    }
+}
+
+// Combines directories, packs and in-memory files into a single file tree. When several mounts
+// contain the same path, the one with the highest priority wins, and among the ones with the
+// same priority the last mounted, so that mods and DLC packs can override the base files.
+// Mounts can be changed while the file system is shared between threads.
+#[derive(Debug, Default)]
+pub struct Vfs {
+    mounts: RwLock<Vec<Mount>>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Convenience for a single directory mounted at the root.
+    pub fn with_directory<P: AsRef<Path>>(root: P) -> Self {
+        let vfs = Self::new();
+        vfs.mount("root", "", 0, DirectorySource::new(root));
+        vfs
+    }
+
+    // Replaces the mount with the same name, if present.
+    pub fn mount<S, P, T>(&self, name: S, mount_point: P, priority: i32, source: T)
+    where
+        S: Into<String>,
+        P: AsRef<Path>,
+        T: VfsSource + 'static,
+    {
+        self.mount_shared(name, mount_point, priority, Arc::new(source));
+    }
+
+    pub fn mount_shared<S, P>(
+        &self,
+        name: S,
+        mount_point: P,
+        priority: i32,
+        source: Arc<dyn VfsSource>,
+    ) where
+        S: Into<String>,
+        P: AsRef<Path>,
+    {
+        let name = name.into();
+        let mut mounts = self.mounts.write().unwrap();
+        mounts.retain(|m| m.name != name);
+        // Mounts are sorted by precedence.
+        let index = mounts
+            .iter()
+            .position(|m| m.priority <= priority)
+            .unwrap_or(mounts.len());
+        mounts.insert(
+            index,
+            Mount {
+                name,
+                mount_point: normalize_path(mount_point),
+                priority,
+                source,
+            },
+        );
+    }
+
+    pub fn unmount(&self, name: &str) -> bool {
+        let mut mounts = self.mounts.write().unwrap();
+        let count = mounts.len();
+        mounts.retain(|m| m.name != name);
+        mounts.len() != count
+    }
+
+    // Names of the mounts, from the highest to the lowest precedence.
+    pub fn mount_names(&self) -> Vec<String> {
+        self.mounts
+            .read()
+            .unwrap()
+            .iter()
+            .map(|m| m.name.clone())
+            .collect()
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    // Name of the mount providing the file.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        let path = normalize_path(path);
+        self.mounts
+            .read()
+            .unwrap()
+            .iter()
+            .find(|m| m.source_path(&path).is_some_and(|p| m.source.contains(&p)))
+            .map(|m| m.name.clone())
+    }
+
+    // Checksum stored by the source providing the file.
+    pub fn checksum<P: AsRef<Path>>(&self, path: P) -> Option<Checksum> {
+        let path = normalize_path(path);
+        self.mounts.read().unwrap().iter().find_map(|m| {
+            m.source_path(&path)
+                .filter(|p| m.source.contains(p))
+                .map(|p| m.source.checksum(&p))
+        })?
+    }
+
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>, Error> {
+        let path = normalize_path(path);
+        let source = self.mounts.read().unwrap().iter().find_map(|m| {
+            m.source_path(&path)
+                .filter(|p| m.source.contains(p))
+                .map(|p| (Arc::clone(&m.source), p))
+        });
+        match source {
+            // The lock isn't held while reading.
+            Some((source, source_path)) => source.read(&source_path),
+            None => Err(Error::FileNotFound(path)),
+        }
+    }
+
+    pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| Error::IoError(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    }
+
+    // All the visible files.
+    pub fn paths(&self) -> BTreeSet<PathBuf> {
+        self.mounts
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|m| {
+                m.source
+                    .paths()
+                    .into_iter()
+                    .map(|p| m.mount_point.join(p))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("roe_assets_vfs_{}", name));
+        std::fs::create_dir_all(dir.join("textures")).unwrap();
+        std::fs::write(dir.join("config.ron"), "base").unwrap();
+        std::fs::write(dir.join("textures/hero.png"), "hero").unwrap();
+        dir
+    }
+
+    #[test]
+    fn path_normalization() {
+        expect_that!(
+            &normalize_path("./a/b/../c.png"),
+            eq(PathBuf::from("a/c.png"))
+        );
+        expect_that!(&normalize_path("/a/./b"), eq(PathBuf::from("a/b")));
+        expect_that!(&normalize_path("../a"), eq(PathBuf::from("a")));
+    }
+
+    #[test]
+    fn directory_source() {
+        let source = DirectorySource::new(test_dir("directory"));
+        expect_that!(source.contains(Path::new("textures/hero.png")));
+        expect_that!(!source.contains(Path::new("textures")));
+        let mut paths = source.paths();
+        paths.sort();
+        expect_that!(
+            &paths,
+            eq(vec![
+                PathBuf::from("config.ron"),
+                PathBuf::from("textures/hero.png")
+            ])
+        );
+    }
+
+    #[test]
+    fn pack_source() {
+        let path = std::env::temp_dir().join("roe_assets_vfs_pack.pack");
+        write_pack(
+            &path,
+            vec![
+                ("a.txt", b"first".to_vec()),
+                ("dir/b.txt", b"second".to_vec()),
+                ("./empty", Vec::new()),
+            ],
+            &PackDescriptor::default(),
+        )
+        .unwrap();
+        let pack = PackSource::open(&path).unwrap();
+        expect_that!(&pack.paths().len(), eq(3));
+        expect_that!(
+            &pack.read(Path::new("dir/b.txt")).unwrap(),
+            eq(b"second".to_vec())
+        );
+        expect_that!(&pack.read(Path::new("empty")).unwrap(), eq(Vec::new()));
+        expect_that!(
+            &pack.checksum(Path::new("a.txt")),
+            eq(Some(Checksum::compute(b"first")))
+        );
+
+        write_pack(
+            &path,
+            vec![("a.txt", b"first".to_vec())],
+            &PackDescriptor { checksums: false },
+        )
+        .unwrap();
+        let pack = PackSource::open(&path).unwrap();
+        expect_that!(
+            &pack.read(Path::new("a.txt")).unwrap(),
+            eq(b"first".to_vec())
+        );
+        expect_that!(&pack.checksum(Path::new("a.txt")), eq(None));
+        expect_that!(&pack.read(Path::new("missing")), is_variant!(Result::Err));
+
+        std::fs::write(&path, b"NOTAPACK").unwrap();
+        expect_that!(&PackSource::open(&path), is_variant!(Result::Err));
+    }
+
+    #[test]
+    fn overlays() {
+        let vfs = Vfs::with_directory(test_dir("overlays"));
+        let dlc = MemorySource::new();
+        dlc.insert("config.ron", b"dlc".to_vec());
+        dlc.insert("levels/1.ron", b"level".to_vec());
+        vfs.mount("dlc", "", 10, dlc);
+        let patch = MemorySource::new();
+        patch.insert("config.ron", b"patch".to_vec());
+        vfs.mount("patch", "", 10, patch);
+        let sounds = MemorySource::new();
+        sounds.insert("jump.wav", b"jump".to_vec());
+        vfs.mount("sounds", "audio", 0, sounds);
+
+        expect_that!(
+            &vfs.mount_names(),
+            eq(vec![
+                String::from("patch"),
+                String::from("dlc"),
+                String::from("sounds"),
+                String::from("root")
+            ])
+        );
+        expect_that!(
+            &vfs.read_to_string("config.ron").unwrap(),
+            eq(String::from("patch"))
+        );
+        expect_that!(
+            &vfs.resolve("./levels/1.ron"),
+            eq(Some(String::from("dlc")))
+        );
+        expect_that!(&vfs.read("audio/jump.wav").unwrap(), eq(b"jump".to_vec()));
+        expect_that!(!vfs.contains("jump.wav"));
+        expect_that!(
+            &vfs.read("textures/hero.png").unwrap(),
+            eq(b"hero".to_vec())
+        );
+        expect_that!(&vfs.paths().len(), eq(4));
+
+        expect_that!(vfs.unmount("patch"));
+        expect_that!(!vfs.unmount("patch"));
+        expect_that!(
+            &vfs.read_to_string("config.ron").unwrap(),
+            eq(String::from("dlc"))
+        );
+        vfs.unmount("dlc");
+        expect_that!(
+            &vfs.read_to_string("config.ron").unwrap(),
+            eq(String::from("base"))
+        );
+        expect_that!(&vfs.read("levels/1.ron"), is_variant!(Result::Err));
+    }
+}
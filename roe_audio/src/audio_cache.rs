@@ -0,0 +1,395 @@
+use super::{
+    Buffer, Context, Decoder, DecoderError, Error, FlacDecoder, Format, OggDecoder, WavDecoder,
+};
+
+use roe_assets as assets;
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum AudioStoragePolicy {
+    // Decoded when the cache is created, the samples are kept in memory.
+    #[default]
+    Preload,
+    // Only the encoded bytes are kept in memory, samples are decoded on demand. Suited for
+    // long tracks played through a streaming source.
+    Stream,
+    // Decoded the first time it is requested, the samples are kept in memory until unloaded.
+    Lazy,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct AudioManifestEntry {
+    // Path in the file system of the cache. The decoder is chosen based on the extension.
+    pub path: PathBuf,
+    #[serde(default)]
+    pub policy: AudioStoragePolicy,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct AudioManifest {
+    pub entries: BTreeMap<String, AudioManifestEntry>,
+}
+
+impl AudioManifest {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+
+    pub fn load_from_vfs<P: AsRef<Path>>(vfs: &assets::Vfs, path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&vfs.read_to_string(path)?)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AudioSamples {
+    pub data: Vec<u8>,
+    pub format: Format,
+    pub sample_rate: u32,
+}
+
+impl AudioSamples {
+    pub fn from_decoder<D: Decoder + ?Sized>(decoder: &mut D) -> Result<Self, Error> {
+        Ok(Self {
+            data: decoder.read_all()?,
+            format: decoder.format(),
+            sample_rate: decoder.sample_rate(),
+        })
+    }
+}
+
+pub fn create_decoder<T>(extension: &str, input: T) -> Result<Box<dyn Decoder>, Error>
+where
+    T: std::io::Read + std::io::Seek + 'static,
+{
+    match extension.to_lowercase().as_str() {
+        "wav" => Ok(Box::new(WavDecoder::new(input)?)),
+        "ogg" => Ok(Box::new(OggDecoder::new(input)?)),
+        "flac" => Ok(Box::new(FlacDecoder::new(input)?)),
+        _ => Err(Error::DecoderError(DecoderError::InvalidEncoding(format!(
+            "Unsupported file extension \"{}\"",
+            extension
+        )))),
+    }
+}
+
+#[derive(Debug)]
+enum AudioStorage {
+    Unloaded,
+    Encoded(Arc<[u8]>),
+    Decoded(Arc<AudioSamples>),
+}
+
+#[derive(Debug)]
+struct AudioEntry {
+    path: PathBuf,
+    policy: AudioStoragePolicy,
+    storage: AudioStorage,
+}
+
+impl AudioEntry {
+    fn extension(&self) -> String {
+        self.path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(String::from)
+            .unwrap_or_default()
+    }
+}
+
+// Keeps the audio assets listed in a manifest in memory, reading them through a virtual file
+// system, either as decoded samples or as the
+// encoded file contents, according to the storage policy of each asset.
+#[derive(Debug)]
+pub struct AudioCache {
+    vfs: Arc<assets::Vfs>,
+    entries: BTreeMap<assets::SoundId, AudioEntry>,
+}
+
+impl AudioCache {
+    pub fn new(vfs: Arc<assets::Vfs>) -> Self {
+        Self {
+            vfs,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_manifest(vfs: Arc<assets::Vfs>, manifest: &AudioManifest) -> Result<Self, Error> {
+        let mut cache = Self::new(vfs);
+        for (name, entry) in manifest.entries.iter() {
+            cache.insert(name, entry)?;
+        }
+        Ok(cache)
+    }
+
+    // Replaces the asset with the same id, if present.
+    pub fn insert<I: Into<assets::SoundId>>(
+        &mut self,
+        id: I,
+        manifest_entry: &AudioManifestEntry,
+    ) -> Result<(), Error> {
+        let mut entry = AudioEntry {
+            path: manifest_entry.path.clone(),
+            policy: manifest_entry.policy,
+            storage: AudioStorage::Unloaded,
+        };
+        match entry.policy {
+            AudioStoragePolicy::Preload => {
+                entry.storage = AudioStorage::Decoded(Arc::new(self.decode_file(&entry)?));
+            }
+            AudioStoragePolicy::Stream => {
+                let bytes = self.vfs.read(&entry.path)?;
+                entry.storage = AudioStorage::Encoded(Arc::from(bytes));
+            }
+            AudioStoragePolicy::Lazy => (),
+        }
+        self.entries.insert(id.into(), entry);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: assets::SoundId) -> bool {
+        self.entries.remove(&id).is_some()
+    }
+
+    pub fn contains(&self, id: assets::SoundId) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    pub fn policy(&self, id: assets::SoundId) -> Option<AudioStoragePolicy> {
+        self.entries.get(&id).map(|e| e.policy)
+    }
+
+    pub fn is_decoded(&self, id: assets::SoundId) -> bool {
+        matches!(
+            self.entries.get(&id).map(|e| &e.storage),
+            Some(AudioStorage::Decoded(_))
+        )
+    }
+
+    // Decoded samples of the asset. The samples of streamed assets are decoded each time and
+    // not kept in memory.
+    pub fn samples(&mut self, id: assets::SoundId) -> Result<Arc<AudioSamples>, Error> {
+        let entry = self
+            .entries
+            .get(&id)
+            .ok_or_else(|| Error::UnknownAudio(id.to_string()))?;
+        match &entry.storage {
+            AudioStorage::Decoded(samples) => Ok(samples.clone()),
+            AudioStorage::Encoded(bytes) => {
+                let mut decoder =
+                    create_decoder(&entry.extension(), std::io::Cursor::new(bytes.clone()))?;
+                Ok(Arc::new(AudioSamples::from_decoder(decoder.as_mut())?))
+            }
+            AudioStorage::Unloaded => {
+                let samples = Arc::new(self.decode_file(entry)?);
+                self.entries.get_mut(&id).unwrap().storage = AudioStorage::Decoded(samples.clone());
+                Ok(samples)
+            }
+        }
+    }
+
+    pub fn buffer(&mut self, context: &Context, id: assets::SoundId) -> Result<Buffer, Error> {
+        let samples = self.samples(id)?;
+        Buffer::new(context, &samples.data, samples.format, samples.sample_rate)
+    }
+
+    // Decoder to be used with a streaming source. Streamed assets are decoded from memory,
+    // the others are read again from the file system.
+    pub fn decoder(&self, id: assets::SoundId) -> Result<Box<dyn Decoder>, Error> {
+        let entry = self
+            .entries
+            .get(&id)
+            .ok_or_else(|| Error::UnknownAudio(id.to_string()))?;
+        match &entry.storage {
+            AudioStorage::Encoded(bytes) => {
+                create_decoder(&entry.extension(), std::io::Cursor::new(bytes.clone()))
+            }
+            _ => create_decoder(
+                &entry.extension(),
+                std::io::Cursor::new(self.vfs.read(&entry.path)?),
+            ),
+        }
+    }
+
+    // Releases the samples of a lazily loaded asset, which will be decoded again on the next
+    // request.
+    pub fn unload(&mut self, id: assets::SoundId) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            if entry.policy == AudioStoragePolicy::Lazy {
+                entry.storage = AudioStorage::Unloaded;
+            }
+        }
+    }
+
+    // Bytes used by samples and encoded data.
+    pub fn memory_usage(&self) -> usize {
+        self.entries
+            .values()
+            .map(|e| match &e.storage {
+                AudioStorage::Unloaded => 0,
+                AudioStorage::Encoded(bytes) => bytes.len(),
+                AudioStorage::Decoded(samples) => samples.data.len(),
+            })
+            .sum()
+    }
+
+    fn decode_file(&self, entry: &AudioEntry) -> Result<AudioSamples, Error> {
+        let mut decoder = create_decoder(
+            &entry.extension(),
+            std::io::Cursor::new(self.vfs.read(&entry.path)?),
+        )?;
+        AudioSamples::from_decoder(decoder.as_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assets::SoundId;
+    use galvanic_assert::{matchers::*, *};
+
+    const MANIFEST: &str = r#"(
+        entries: {
+            "effect": (path: "mono-16-44100.wav"),
+            "music": (path: "stereo-16-44100.ogg", policy: Stream),
+            "voice": (path: "mono-16-22050.ogg", policy: Lazy),
+        },
+    )"#;
+
+    fn create_cache() -> AudioCache {
+        AudioCache::with_manifest(
+            Arc::new(assets::Vfs::with_directory("data/audio")),
+            &AudioManifest::from_ron_str(MANIFEST).unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn manifest_serialization() {
+        let manifest = AudioManifest::from_ron_str(MANIFEST).unwrap();
+        expect_that!(
+            &manifest.entries["effect"].policy,
+            eq(AudioStoragePolicy::Preload)
+        );
+        expect_that!(
+            &manifest.entries["music"].policy,
+            eq(AudioStoragePolicy::Stream)
+        );
+        expect_that!(
+            &AudioManifest::from_ron_str(&manifest.to_ron_string().unwrap()).unwrap(),
+            eq(manifest)
+        );
+    }
+
+    #[test]
+    fn storage_policies() {
+        let mut cache = create_cache();
+        expect_that!(cache.is_decoded(SoundId::new("effect")));
+        expect_that!(!cache.is_decoded(SoundId::new("music")));
+        expect_that!(!cache.is_decoded(SoundId::new("voice")));
+        let initial_memory_usage = cache.memory_usage();
+
+        let music_size = std::fs::metadata("data/audio/stereo-16-44100.ogg")
+            .unwrap()
+            .len() as usize;
+        let effect = cache.samples(SoundId::new("effect")).unwrap();
+        expect_that!(&effect.format, eq(Format::Mono16));
+        expect_that!(&effect.sample_rate, eq(44100));
+        expect_that!(&initial_memory_usage, eq(effect.data.len() + music_size));
+
+        // Streamed samples aren't kept.
+        let music = cache.samples(SoundId::new("music")).unwrap();
+        expect_that!(&music.format, eq(Format::Stereo16));
+        expect_that!(!cache.is_decoded(SoundId::new("music")));
+        expect_that!(&cache.memory_usage(), eq(initial_memory_usage));
+
+        let voice = cache.samples(SoundId::new("voice")).unwrap();
+        expect_that!(&voice.sample_rate, eq(22050));
+        expect_that!(cache.is_decoded(SoundId::new("voice")));
+        expect_that!(
+            &cache.memory_usage(),
+            eq(initial_memory_usage + voice.data.len())
+        );
+        cache.unload(SoundId::new("voice"));
+        expect_that!(!cache.is_decoded(SoundId::new("voice")));
+        expect_that!(&cache.memory_usage(), eq(initial_memory_usage));
+    }
+
+    #[test]
+    fn decoders() {
+        let cache = create_cache();
+        let mut decoder = cache.decoder(SoundId::new("music")).unwrap();
+        expect_that!(&decoder.format(), eq(Format::Stereo16));
+        let streamed = decoder.read_all().unwrap();
+        let mut decoder = cache.decoder(SoundId::new("effect")).unwrap();
+        expect_that!(&decoder.sample_rate(), eq(44100));
+
+        let mut file_decoder = OggDecoder::new(std::io::BufReader::new(
+            std::fs::File::open("data/audio/stereo-16-44100.ogg").unwrap(),
+        ))
+        .unwrap();
+        expect_that!(&streamed, eq(file_decoder.read_all().unwrap()));
+        expect_that!(&decoder.read_all().unwrap().is_empty(), eq(false));
+    }
+
+    #[test]
+    fn errors() {
+        let mut cache = create_cache();
+        expect_that!(
+            &cache.samples(SoundId::new("missing")),
+            is_variant!(Result::Err)
+        );
+        expect_that!(
+            &cache.insert(
+                "text",
+                &AudioManifestEntry {
+                    path: PathBuf::from("not-an-audio-file.txt"),
+                    policy: AudioStoragePolicy::Preload,
+                },
+            ),
+            is_variant!(Result::Err)
+        );
+        expect_that!(!cache.contains(SoundId::new("text")));
+    }
+
+    #[test]
+    fn overlays() {
+        let vfs = Arc::new(assets::Vfs::with_directory("data/audio"));
+        let overlay = assets::MemorySource::new();
+        overlay.insert(
+            "mono-16-44100.wav",
+            std::fs::read("data/audio/mono-16-22050.wav").unwrap(),
+        );
+        overlay.insert("audio.ron", MANIFEST.as_bytes().to_vec());
+        vfs.mount("overlay", "", 1, overlay);
+
+        let manifest = AudioManifest::load_from_vfs(&vfs, "audio.ron").unwrap();
+        let mut cache = AudioCache::with_manifest(vfs, &manifest).unwrap();
+        expect_that!(
+            &cache.samples(SoundId::new("effect")).unwrap().sample_rate,
+            eq(22050)
+        );
+    }
+}
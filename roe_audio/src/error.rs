@@ -1,4 +1,6 @@
-use super::DecoderError;
+use super::{DecoderError, MixerSourceId};
+
+use roe_assets as assets;
 
 pub use alto::AltoError as BackendError;
 
@@ -6,6 +8,12 @@ pub use alto::AltoError as BackendError;
 pub enum Error {
     BackendError(BackendError),
     DecoderError(DecoderError),
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    AssetError(assets::Error),
+    UnknownAudio(String),
+    UnknownMixerSource(MixerSourceId),
+    SelfCrossfade(MixerSourceId),
 }
 
 impl std::fmt::Display for Error {
@@ -13,6 +21,14 @@ impl std::fmt::Display for Error {
         match self {
             Self::BackendError(e) => write!(f, "Backend error ({})", e),
             Self::DecoderError(e) => write!(f, "Decoder error ({})", e),
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::AssetError(e) => write!(f, "Asset error ({})", e),
+            Self::UnknownAudio(name) => write!(f, "Unknown audio ({})", name),
+            Self::UnknownMixerSource(id) => write!(f, "Unknown mixer source ({})", id),
+            Self::SelfCrossfade(id) => {
+                write!(f, "Crossfade of a mixer source with itself ({})", id)
+            }
         }
     }
 }
@@ -22,6 +38,10 @@ impl std::error::Error for Error {
         match self {
             Self::BackendError(e) => Some(e),
             Self::DecoderError(e) => Some(e),
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            Self::AssetError(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -37,3 +57,21 @@ impl From<DecoderError> for Error {
         Self::DecoderError(e)
     }
 }
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
+
+impl From<assets::Error> for Error {
+    fn from(e: assets::Error) -> Self {
+        Self::AssetError(e)
+    }
+}
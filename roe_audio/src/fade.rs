@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    // Keeps the perceived loudness constant when two sources are crossfaded.
+    EqualPower,
+}
+
+impl FadeCurve {
+    // Fraction of the gain change applied at the normalized time t.
+    fn progress(&self, t: f32, rising: bool) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::EqualPower => {
+                let angle = t * std::f32::consts::FRAC_PI_2;
+                if rising {
+                    angle.sin()
+                } else {
+                    1. - angle.cos()
+                }
+            }
+        }
+    }
+}
+
+// Gain envelope moving from a start gain to a target gain over a duration.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GainFade {
+    start_gain: f32,
+    target_gain: f32,
+    duration: Duration,
+    elapsed: Duration,
+    curve: FadeCurve,
+    stop: bool,
+    restore_gain: f32,
+}
+
+impl GainFade {
+    pub fn new(start_gain: f32, target_gain: f32, duration: Duration) -> Self {
+        Self {
+            start_gain,
+            target_gain,
+            duration,
+            elapsed: Duration::ZERO,
+            curve: FadeCurve::Linear,
+            stop: false,
+            restore_gain: start_gain,
+        }
+    }
+
+    // Fade to silence, after which the source is stopped and its start gain restored.
+    pub fn fade_out(start_gain: f32, duration: Duration) -> Self {
+        Self {
+            stop: true,
+            ..Self::new(start_gain, 0., duration)
+        }
+    }
+
+    pub fn with_curve(self, curve: FadeCurve) -> Self {
+        Self { curve, ..self }
+    }
+
+    // Gain restored after a fade out instead of the start gain, e.g. when the source was still
+    // fading when the fade out began.
+    pub fn with_restore_gain(self, restore_gain: f32) -> Self {
+        Self {
+            restore_gain,
+            ..self
+        }
+    }
+
+    pub fn start_gain(&self) -> f32 {
+        self.start_gain
+    }
+
+    pub fn target_gain(&self) -> f32 {
+        self.target_gain
+    }
+
+    pub fn curve(&self) -> FadeCurve {
+        self.curve
+    }
+
+    pub fn stops(&self) -> bool {
+        self.stop
+    }
+
+    // Gain the source settles on once the fade is over.
+    pub fn final_gain(&self) -> f32 {
+        if self.stop {
+            self.restore_gain
+        } else {
+            self.target_gain
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    pub fn gain(&self) -> f32 {
+        if self.is_finished() {
+            return self.target_gain;
+        }
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        let progress = self.curve.progress(t, self.target_gain >= self.start_gain);
+        self.start_gain + (self.target_gain - self.start_gain) * progress
+    }
+
+    pub fn advance(&mut self, dt: Duration) -> f32 {
+        self.elapsed = std::cmp::min(self.elapsed + dt, self.duration);
+        self.gain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn linear_fade() {
+        let mut fade = GainFade::new(1., 0.2, Duration::from_secs(2));
+        expect_that!(&fade.gain(), close_to(1., 1e-6));
+        expect_that!(
+            &fade.advance(Duration::from_millis(500)),
+            close_to(0.8, 1e-6)
+        );
+        expect_that!(!fade.is_finished());
+        expect_that!(&fade.advance(Duration::from_secs(5)), close_to(0.2, 1e-6));
+        expect_that!(fade.is_finished());
+        expect_that!(&fade.final_gain(), close_to(0.2, 1e-6));
+    }
+
+    #[test]
+    fn instant_fade() {
+        let fade = GainFade::new(1., 0.5, Duration::ZERO);
+        expect_that!(fade.is_finished());
+        expect_that!(&fade.gain(), close_to(0.5, 1e-6));
+    }
+
+    #[test]
+    fn fade_out() {
+        let mut fade = GainFade::fade_out(0.8, Duration::from_secs(1));
+        expect_that!(fade.stops());
+        expect_that!(
+            &fade.advance(Duration::from_millis(250)),
+            close_to(0.6, 1e-6)
+        );
+        fade.advance(Duration::from_secs(1));
+        expect_that!(&fade.gain(), close_to(0., 1e-6));
+        expect_that!(&fade.final_gain(), close_to(0.8, 1e-6));
+
+        let fade = GainFade::fade_out(0.4, Duration::from_secs(1)).with_restore_gain(1.);
+        expect_that!(&fade.gain(), close_to(0.4, 1e-6));
+        expect_that!(&fade.final_gain(), close_to(1., 1e-6));
+    }
+
+    #[test]
+    fn equal_power_crossfade() {
+        let duration = Duration::from_secs(1);
+        let mut fade_out = GainFade::fade_out(1., duration).with_curve(FadeCurve::EqualPower);
+        let mut fade_in = GainFade::new(0., 1., duration).with_curve(FadeCurve::EqualPower);
+        for _ in 0..10 {
+            let dt = Duration::from_millis(100);
+            let (a, b) = (fade_out.advance(dt), fade_in.advance(dt));
+            expect_that!(&(a * a + b * b), close_to(1., 1e-5));
+        }
+        expect_that!(&fade_in.gain(), close_to(1., 1e-6));
+        expect_that!(&fade_out.gain(), close_to(0., 1e-6));
+    }
+}
@@ -0,0 +1,984 @@
+use super::{Decoder, DecoderError, Format};
+
+const fn crc8_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC8_TABLE: [u8; 256] = crc8_table();
+const CRC16_TABLE: [u16; 256] = crc16_table();
+
+// Reads the frames bit by bit, most significant bit first, keeping track of the checksums of
+// the bytes read so far.
+struct BitReader<'a, T: std::io::Read> {
+    input: &'a mut T,
+    byte: u8,
+    bits_left: u32,
+    byte_count: u64,
+    crc8: u8,
+    crc16: u16,
+}
+
+impl<'a, T> BitReader<'a, T>
+where
+    T: std::io::Read,
+{
+    fn new(input: &'a mut T) -> Self {
+        Self {
+            input,
+            byte: 0,
+            bits_left: 0,
+            byte_count: 0,
+            crc8: 0,
+            crc16: 0,
+        }
+    }
+
+    fn load_byte(&mut self) -> Result<(), DecoderError> {
+        let mut byte = [0; 1];
+        self.input
+            .read_exact(&mut byte)
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => {
+                    DecoderError::InvalidData(String::from("Truncated frame"))
+                }
+                _ => DecoderError::IoError(e),
+            })?;
+        self.byte = byte[0];
+        self.bits_left = 8;
+        self.byte_count += 1;
+        self.crc8 = CRC8_TABLE[(self.crc8 ^ self.byte) as usize];
+        self.crc16 =
+            (self.crc16 << 8) ^ CRC16_TABLE[((self.crc16 >> 8) as u8 ^ self.byte) as usize];
+        Ok(())
+    }
+
+    fn read_bits(&mut self, count: u32) -> Result<u64, DecoderError> {
+        assert!(count <= 64);
+        let mut value = 0;
+        let mut remaining = count;
+        while remaining > 0 {
+            if self.bits_left == 0 {
+                self.load_byte()?;
+            }
+            let n = std::cmp::min(remaining, self.bits_left);
+            let bits = (self.byte >> (self.bits_left - n)) & ((1u32 << n) - 1) as u8;
+            value = (value << n) | bits as u64;
+            self.bits_left -= n;
+            remaining -= n;
+        }
+        Ok(value)
+    }
+
+    fn read_signed_bits(&mut self, count: u32) -> Result<i64, DecoderError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let value = self.read_bits(count)?;
+        Ok(((value << (64 - count)) as i64) >> (64 - count))
+    }
+
+    // Number of zeros before the next one.
+    fn read_unary(&mut self) -> Result<u32, DecoderError> {
+        let mut count = 0;
+        loop {
+            if self.bits_left == 0 {
+                self.load_byte()?;
+            }
+            let bits = self.byte << (8 - self.bits_left);
+            if bits == 0 {
+                count += self.bits_left;
+                self.bits_left = 0;
+            } else {
+                let zeros = bits.leading_zeros();
+                count += zeros;
+                self.bits_left -= zeros + 1;
+                return Ok(count);
+            }
+        }
+    }
+
+    fn read_rice(&mut self, parameter: u32) -> Result<i64, DecoderError> {
+        let quotient = self.read_unary()? as u64;
+        let value = (quotient << parameter) | self.read_bits(parameter)?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    // Frame and sample numbers are coded like UTF-8 characters, up to 36 bits.
+    fn read_utf8(&mut self) -> Result<u64, DecoderError> {
+        let first = self.read_bits(8)? as u8;
+        let length = first.leading_ones();
+        if length == 0 {
+            return Ok(first as u64);
+        }
+        if length == 1 || length > 7 {
+            return Err(DecoderError::InvalidData(String::from(
+                "Invalid frame number",
+            )));
+        }
+        let mut value = (first & (0x7f >> length)) as u64;
+        for _ in 1..length {
+            let byte = self.read_bits(8)?;
+            if byte & 0xc0 != 0x80 {
+                return Err(DecoderError::InvalidData(String::from(
+                    "Invalid frame number",
+                )));
+            }
+            value = (value << 6) | (byte & 0x3f);
+        }
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bits_left = 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FlacStreamInfo {
+    sample_rate: u32,
+    channels: u32,
+    bits_per_sample: u32,
+    // Zero if unknown.
+    sample_count: u64,
+}
+
+impl FlacStreamInfo {
+    const SIZE: usize = 34;
+
+    fn parse(data: &[u8; Self::SIZE]) -> Result<Self, DecoderError> {
+        let mut data = &data[..];
+        let mut reader = BitReader::new(&mut data);
+        let min_block_size = reader.read_bits(16)? as u32;
+        let max_block_size = reader.read_bits(16)? as u32;
+        reader.read_bits(48)?;
+        let sample_rate = reader.read_bits(20)? as u32;
+        let channels = reader.read_bits(3)? as u32 + 1;
+        let bits_per_sample = reader.read_bits(5)? as u32 + 1;
+        let sample_count = reader.read_bits(36)?;
+
+        if min_block_size < 16 || max_block_size < min_block_size {
+            return Err(DecoderError::InvalidHeader(format!(
+                "Invalid block size range ({} - {})",
+                min_block_size, max_block_size
+            )));
+        }
+        if sample_rate == 0 {
+            return Err(DecoderError::InvalidHeader(String::from(
+                "Invalid sample rate (0)",
+            )));
+        }
+        if channels != 1 && channels != 2 {
+            return Err(DecoderError::InvalidHeader(format!(
+                "Invalid channel count ({})",
+                channels
+            )));
+        }
+        if bits_per_sample < 4 {
+            return Err(DecoderError::InvalidHeader(format!(
+                "Invalid bits per sample ({})",
+                bits_per_sample
+            )));
+        }
+        Ok(Self {
+            sample_rate,
+            channels,
+            bits_per_sample,
+            sample_count,
+        })
+    }
+
+    // Samples with up to 8 bits are stored on 8 bits, the others on 16 bits.
+    fn format(&self) -> Format {
+        let bytes_per_sample = if self.bits_per_sample <= 8 { 1 } else { 2 };
+        Format::new(self.channels, bytes_per_sample)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlacChannelAssignment {
+    Independent,
+    LeftSide,
+    SideRight,
+    MidSide,
+}
+
+fn decode_residual<T: std::io::Read>(
+    reader: &mut BitReader<T>,
+    order: usize,
+    samples: &mut [i64],
+) -> Result<(), DecoderError> {
+    let (parameter_bits, escape_code) = match reader.read_bits(2)? {
+        0 => (4, 0xf),
+        1 => (5, 0x1f),
+        _ => {
+            return Err(DecoderError::InvalidData(String::from(
+                "Invalid residual coding method",
+            )))
+        }
+    };
+    let partition_order = reader.read_bits(4)? as u32;
+    let block_size = samples.len();
+    let partition_size = block_size >> partition_order;
+    if partition_size << partition_order != block_size || partition_size < order {
+        return Err(DecoderError::InvalidData(format!(
+            "Invalid residual partition order ({})",
+            partition_order
+        )));
+    }
+
+    let mut i = order;
+    for partition in 0..1 << partition_order {
+        let end = (partition + 1) * partition_size;
+        let parameter = reader.read_bits(parameter_bits)? as u32;
+        if parameter == escape_code {
+            let bits = reader.read_bits(5)? as u32;
+            for sample in samples[i..end].iter_mut() {
+                *sample = reader.read_signed_bits(bits)?;
+            }
+        } else {
+            for sample in samples[i..end].iter_mut() {
+                *sample = reader.read_rice(parameter)?;
+            }
+        }
+        i = end;
+    }
+    Ok(())
+}
+
+// Adds the prediction from the previous samples to the residuals, after the warm-up samples.
+fn restore_lpc(samples: &mut [i64], coefficients: &[i64], shift: u32) {
+    let order = coefficients.len();
+    for i in order..samples.len() {
+        let prediction: i64 = coefficients
+            .iter()
+            .zip(samples[i - order..i].iter().rev())
+            .map(|(c, s)| c * s)
+            .sum();
+        samples[i] += prediction >> shift;
+    }
+}
+
+fn decode_subframe<T: std::io::Read>(
+    reader: &mut BitReader<T>,
+    bits_per_sample: u32,
+    samples: &mut [i64],
+) -> Result<(), DecoderError> {
+    if reader.read_bits(1)? != 0 {
+        return Err(DecoderError::InvalidData(String::from(
+            "Invalid subframe padding",
+        )));
+    }
+    let subframe_type = reader.read_bits(6)? as usize;
+    let wasted_bits = if reader.read_bits(1)? != 0 {
+        reader.read_unary()? + 1
+    } else {
+        0
+    };
+    if wasted_bits >= bits_per_sample {
+        return Err(DecoderError::InvalidData(format!(
+            "Invalid wasted bits per sample ({})",
+            wasted_bits
+        )));
+    }
+    let bits_per_sample = bits_per_sample - wasted_bits;
+
+    match subframe_type {
+        0b000000 => {
+            let value = reader.read_signed_bits(bits_per_sample)?;
+            samples.iter_mut().for_each(|s| *s = value);
+        }
+        0b000001 => {
+            for sample in samples.iter_mut() {
+                *sample = reader.read_signed_bits(bits_per_sample)?;
+            }
+        }
+        0b001000..=0b001100 => {
+            const FIXED_COEFFICIENTS: [&[i64]; 5] =
+                [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+            let order = subframe_type & 0b111;
+            if order > samples.len() {
+                return Err(DecoderError::InvalidData(format!(
+                    "Invalid predictor order ({})",
+                    order
+                )));
+            }
+            for sample in samples[..order].iter_mut() {
+                *sample = reader.read_signed_bits(bits_per_sample)?;
+            }
+            decode_residual(reader, order, samples)?;
+            restore_lpc(samples, FIXED_COEFFICIENTS[order], 0);
+        }
+        0b100000..=0b111111 => {
+            let order = (subframe_type & 0b11111) + 1;
+            if order > samples.len() {
+                return Err(DecoderError::InvalidData(format!(
+                    "Invalid predictor order ({})",
+                    order
+                )));
+            }
+            for sample in samples[..order].iter_mut() {
+                *sample = reader.read_signed_bits(bits_per_sample)?;
+            }
+            let precision = reader.read_bits(4)? as u32;
+            if precision == 0b1111 {
+                return Err(DecoderError::InvalidData(String::from(
+                    "Invalid predictor coefficient precision",
+                )));
+            }
+            let shift = reader.read_signed_bits(5)?;
+            if shift < 0 {
+                return Err(DecoderError::InvalidData(format!(
+                    "Invalid predictor shift ({})",
+                    shift
+                )));
+            }
+            let coefficients = (0..order)
+                .map(|_| reader.read_signed_bits(precision + 1))
+                .collect::<Result<Vec<_>, _>>()?;
+            decode_residual(reader, order, samples)?;
+            restore_lpc(samples, &coefficients, shift as u32);
+        }
+        _ => {
+            return Err(DecoderError::InvalidData(format!(
+                "Invalid subframe type ({})",
+                subframe_type
+            )))
+        }
+    }
+
+    if wasted_bits > 0 {
+        samples.iter_mut().for_each(|s| *s <<= wasted_bits);
+    }
+    Ok(())
+}
+
+// Decodes the frame at the current input position. Returns the samples of each channel and
+// the size of the frame in bytes.
+fn decode_frame<T: std::io::Read>(
+    input: &mut T,
+    info: &FlacStreamInfo,
+) -> Result<(Vec<Vec<i64>>, u64), DecoderError> {
+    let mut reader = BitReader::new(input);
+
+    if reader.read_bits(14)? != 0b11111111111110 || reader.read_bits(1)? != 0 {
+        return Err(DecoderError::InvalidData(String::from(
+            "Invalid frame sync code",
+        )));
+    }
+    reader.read_bits(1)?;
+    let block_size_code = reader.read_bits(4)?;
+    let sample_rate_code = reader.read_bits(4)?;
+    let channel_code = reader.read_bits(4)? as u32;
+    let sample_size_code = reader.read_bits(3)?;
+    if reader.read_bits(1)? != 0 {
+        return Err(DecoderError::InvalidData(String::from(
+            "Invalid frame header",
+        )));
+    }
+    // The frame or sample number. The position in the stream is tracked by the decoder.
+    reader.read_utf8()?;
+
+    let block_size = match block_size_code {
+        1 => 192,
+        2..=5 => 576 << (block_size_code - 2),
+        6 => reader.read_bits(8)? as usize + 1,
+        7 => reader.read_bits(16)? as usize + 1,
+        8..=15 => 256 << (block_size_code - 8),
+        _ => {
+            return Err(DecoderError::InvalidData(String::from(
+                "Invalid block size",
+            )))
+        }
+    };
+    match sample_rate_code {
+        12 => {
+            reader.read_bits(8)?;
+        }
+        13 | 14 => {
+            reader.read_bits(16)?;
+        }
+        15 => {
+            return Err(DecoderError::InvalidData(String::from(
+                "Invalid sample rate",
+            )))
+        }
+        _ => (),
+    }
+    let bits_per_sample = match sample_size_code {
+        0 => info.bits_per_sample,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        7 => 32,
+        _ => 0,
+    };
+    if bits_per_sample != info.bits_per_sample {
+        return Err(DecoderError::InvalidData(format!(
+            "Invalid frame bits per sample ({})",
+            bits_per_sample
+        )));
+    }
+    let (channels, assignment) = match channel_code {
+        0..=7 => (channel_code + 1, FlacChannelAssignment::Independent),
+        8 => (2, FlacChannelAssignment::LeftSide),
+        9 => (2, FlacChannelAssignment::SideRight),
+        10 => (2, FlacChannelAssignment::MidSide),
+        _ => (0, FlacChannelAssignment::Independent),
+    };
+    if channels != info.channels {
+        return Err(DecoderError::InvalidData(format!(
+            "Invalid frame channel assignment ({})",
+            channel_code
+        )));
+    }
+
+    let header_crc = reader.crc8;
+    if reader.read_bits(8)? as u8 != header_crc {
+        return Err(DecoderError::InvalidData(String::from(
+            "Frame header checksum mismatch",
+        )));
+    }
+
+    let mut samples = vec![vec![0; block_size]; channels as usize];
+    for (i, channel) in samples.iter_mut().enumerate() {
+        // The side channel has an extra bit.
+        let side = match assignment {
+            FlacChannelAssignment::Independent => false,
+            FlacChannelAssignment::SideRight => i == 0,
+            _ => i == 1,
+        };
+        decode_subframe(&mut reader, bits_per_sample + side as u32, channel)?;
+    }
+    reader.align_to_byte();
+
+    let frame_crc = reader.crc16;
+    if reader.read_bits(16)? as u16 != frame_crc {
+        return Err(DecoderError::InvalidData(String::from(
+            "Frame checksum mismatch",
+        )));
+    }
+
+    if assignment != FlacChannelAssignment::Independent {
+        let (first, second) = samples.split_at_mut(1);
+        for (a, b) in first[0].iter_mut().zip(second[0].iter_mut()) {
+            let (left, right) = match assignment {
+                FlacChannelAssignment::LeftSide => (*a, *a - *b),
+                FlacChannelAssignment::SideRight => (*a + *b, *b),
+                _ => {
+                    let mid = (*a << 1) | (*b & 1);
+                    ((mid + *b) >> 1, (mid - *b) >> 1)
+                }
+            };
+            *a = left;
+            *b = right;
+        }
+    }
+
+    Ok((samples, reader.byte_count))
+}
+
+pub struct FlacDecoder<T>
+where
+    T: std::io::Read + std::io::Seek,
+{
+    input: T,
+    info: FlacStreamInfo,
+    format: Format,
+    sample_length: u64,
+    first_frame_offset: u64,
+    stream_length: u64,
+    // Sample index and byte offset from the first frame of frame starts, sorted. Filled from
+    // the seek table and while decoding, so that seeking doesn't need to decode the stream
+    // from the beginning.
+    seek_points: Vec<(u64, u64)>,
+    next_frame_offset: u64,
+    frame: Vec<u8>,
+    frame_start_sample: u64,
+    frame_current_byte_pos: u64,
+}
+
+impl<T> FlacDecoder<T>
+where
+    T: std::io::Read + std::io::Seek,
+{
+    pub fn new(mut input: T) -> Result<Self, DecoderError> {
+        let stream_length = input.seek(std::io::SeekFrom::End(0))?;
+        input.seek(std::io::SeekFrom::Start(0))?;
+
+        let mut signature = [0; 4];
+        if stream_length < 4 || {
+            input.read_exact(&mut signature)?;
+            &signature != b"fLaC"
+        } {
+            return Err(DecoderError::InvalidEncoding(String::from(
+                "Not a flac file",
+            )));
+        }
+
+        let mut info = None;
+        let mut seek_points = vec![(0, 0)];
+        loop {
+            let mut block_header = [0; 4];
+            input.read_exact(&mut block_header)?;
+            let last = block_header[0] & 0x80 != 0;
+            let block_type = block_header[0] & 0x7f;
+            let block_length =
+                u32::from_be_bytes([0, block_header[1], block_header[2], block_header[3]]);
+            match block_type {
+                0 => {
+                    if block_length as usize != FlacStreamInfo::SIZE {
+                        return Err(DecoderError::InvalidHeader(format!(
+                            "Invalid stream info length ({})",
+                            block_length
+                        )));
+                    }
+                    let mut data = [0; FlacStreamInfo::SIZE];
+                    input.read_exact(&mut data)?;
+                    info = Some(FlacStreamInfo::parse(&data)?);
+                }
+                3 => {
+                    for _ in 0..block_length / 18 {
+                        let mut point = [0; 18];
+                        input.read_exact(&mut point)?;
+                        let sample = u64::from_be_bytes(point[0..8].try_into().unwrap());
+                        let offset = u64::from_be_bytes(point[8..16].try_into().unwrap());
+                        // Placeholder points have all the sample bits set.
+                        if sample != u64::MAX {
+                            seek_points.push((sample, offset));
+                        }
+                    }
+                    input.seek(std::io::SeekFrom::Current((block_length % 18) as i64))?;
+                }
+                127 => {
+                    return Err(DecoderError::InvalidHeader(String::from(
+                        "Invalid metadata block type (127)",
+                    )))
+                }
+                _ => {
+                    input.seek(std::io::SeekFrom::Current(block_length as i64))?;
+                }
+            }
+            if info.is_none() {
+                return Err(DecoderError::InvalidHeader(String::from(
+                    "Missing stream info",
+                )));
+            }
+            if last {
+                break;
+            }
+        }
+        let info = info.unwrap();
+        seek_points.sort_unstable();
+        seek_points.dedup_by_key(|p| p.0);
+
+        let first_frame_offset = input.stream_position()?;
+        let mut decoder = Self {
+            input,
+            info,
+            format: info.format(),
+            sample_length: info.sample_count,
+            first_frame_offset,
+            stream_length,
+            seek_points,
+            next_frame_offset: first_frame_offset,
+            frame: Vec::new(),
+            frame_start_sample: 0,
+            frame_current_byte_pos: 0,
+        };
+        if decoder.sample_length == 0 {
+            decoder.sample_length = decoder.compute_sample_count()?;
+        }
+        decoder
+            .seek_points
+            .retain(|p| p.0 == 0 || (p.0 < decoder.sample_length && p.1 < stream_length));
+        Ok(decoder)
+    }
+
+    fn compute_sample_count(&mut self) -> Result<u64, DecoderError> {
+        let mut sample_length = 0;
+        let mut offset = self.first_frame_offset;
+        self.input.seek(std::io::SeekFrom::Start(offset))?;
+        while offset < self.stream_length {
+            let (samples, size) = decode_frame(&mut self.input, &self.info)?;
+            sample_length += samples[0].len() as u64;
+            offset += size;
+        }
+        Ok(sample_length)
+    }
+
+    fn frame_sample_count(&self) -> u64 {
+        (self.frame.len() / self.format.total_bytes_per_sample() as usize) as u64
+    }
+
+    fn jump_to_seek_point(&mut self, sample: u64, offset: u64) {
+        self.frame.clear();
+        self.frame_start_sample = sample;
+        self.frame_current_byte_pos = 0;
+        self.next_frame_offset = self.first_frame_offset + offset;
+    }
+
+    // Returns false at the end of the stream.
+    fn read_next_frame(&mut self) -> Result<bool, DecoderError> {
+        self.frame_start_sample += self.frame_sample_count();
+        self.frame_current_byte_pos = 0;
+        self.frame.clear();
+        if self.frame_start_sample >= self.sample_length
+            || self.next_frame_offset >= self.stream_length
+        {
+            return Ok(false);
+        }
+
+        let offset = self.next_frame_offset - self.first_frame_offset;
+        if let Err(i) = self
+            .seek_points
+            .binary_search_by_key(&self.frame_start_sample, |p| p.0)
+        {
+            self.seek_points
+                .insert(i, (self.frame_start_sample, offset));
+        }
+
+        self.input
+            .seek(std::io::SeekFrom::Start(self.next_frame_offset))?;
+        let (samples, size) = decode_frame(&mut self.input, &self.info)?;
+        self.next_frame_offset += size;
+
+        // The last frame may be longer than the stream.
+        let sample_count = std::cmp::min(
+            samples[0].len() as u64,
+            self.sample_length - self.frame_start_sample,
+        ) as usize;
+        let bits_per_sample = self.info.bits_per_sample as i32;
+        let bytes_per_sample = self.format.bytes_per_sample() as usize;
+        self.frame
+            .reserve(sample_count * samples.len() * bytes_per_sample);
+        for i in 0..sample_count {
+            for channel in samples.iter() {
+                let value = channel[i];
+                if bytes_per_sample == 1 {
+                    // 8 bit samples are unsigned.
+                    let value = (value << (8 - bits_per_sample)) + 128;
+                    self.frame.push(value as u8);
+                } else {
+                    let value = if bits_per_sample > 16 {
+                        value >> (bits_per_sample - 16)
+                    } else {
+                        value << (16 - bits_per_sample)
+                    };
+                    self.frame.extend_from_slice(&(value as i16).to_le_bytes());
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<T> Decoder for FlacDecoder<T>
+where
+    T: std::io::Read + std::io::Seek,
+{
+    fn format(&self) -> Format {
+        self.format
+    }
+
+    fn byte_seek(&mut self, pos: std::io::SeekFrom) -> Result<u64, DecoderError> {
+        let byte_length = self.byte_length() as i64;
+        let target_pos = match pos {
+            std::io::SeekFrom::Start(v) => v as i64,
+            std::io::SeekFrom::End(v) => byte_length + v,
+            std::io::SeekFrom::Current(v) => self.byte_stream_position()? as i64 + v,
+        };
+        let target_pos = std::cmp::max(0, std::cmp::min(target_pos, byte_length)) as u64;
+
+        let tbps = self.format().total_bytes_per_sample() as u64;
+        assert!(
+            target_pos.is_multiple_of(tbps),
+            "Invalid seek offset ({})",
+            target_pos
+        );
+        let target_sample = target_pos / tbps;
+
+        // Jump to the closest known frame start, unless the current frame is closer.
+        let i = self.seek_points.partition_point(|p| p.0 <= target_sample);
+        let (point_sample, point_offset) = self.seek_points[i - 1];
+        let frame_end_sample = self.frame_start_sample + self.frame_sample_count();
+        if target_sample < self.frame_start_sample || point_sample > frame_end_sample {
+            self.jump_to_seek_point(point_sample, point_offset);
+        }
+
+        while target_sample >= self.frame_start_sample + self.frame_sample_count() {
+            if !self.read_next_frame()? {
+                break;
+            }
+        }
+        self.frame_current_byte_pos = target_pos - self.frame_start_sample * tbps;
+
+        Ok(target_pos)
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.info.sample_rate
+    }
+
+    fn sample_length(&self) -> u64 {
+        self.sample_length
+    }
+
+    fn byte_stream_position(&mut self) -> Result<u64, DecoderError> {
+        let tbps = self.format().total_bytes_per_sample() as u64;
+        Ok(self.frame_start_sample * tbps + self.frame_current_byte_pos)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, DecoderError> {
+        let tbps = self.format().total_bytes_per_sample() as usize;
+        assert!(
+            buf.len().is_multiple_of(tbps),
+            "Invalid buffer length ({})",
+            buf.len()
+        );
+
+        let mut read_byte_count = 0;
+        while read_byte_count < buf.len() {
+            if self.frame_current_byte_pos == self.frame.len() as u64 && !self.read_next_frame()? {
+                break;
+            }
+            let byte_to_read_count = std::cmp::min(
+                self.frame.len() - self.frame_current_byte_pos as usize,
+                buf.len() - read_byte_count,
+            );
+            let in_range = self.frame_current_byte_pos as usize
+                ..self.frame_current_byte_pos as usize + byte_to_read_count;
+            let out_range = read_byte_count..read_byte_count + byte_to_read_count;
+            buf[out_range].clone_from_slice(&self.frame[in_range]);
+            read_byte_count += byte_to_read_count;
+            self.frame_current_byte_pos += byte_to_read_count as u64;
+        }
+
+        Ok(read_byte_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WavDecoder;
+    use galvanic_assert::{matchers::*, *};
+
+    fn open(name: &str) -> FlacDecoder<std::io::BufReader<std::fs::File>> {
+        let file = std::fs::File::open(format!("data/audio/{}.flac", name)).unwrap();
+        FlacDecoder::new(std::io::BufReader::new(file)).unwrap()
+    }
+
+    fn wav_content(name: &str) -> Vec<u8> {
+        let file = std::fs::File::open(format!("data/audio/{}.wav", name)).unwrap();
+        let mut decoder = WavDecoder::new(std::io::BufReader::new(file)).unwrap();
+        decoder.read_all().unwrap()
+    }
+
+    #[test]
+    fn invalid_input_file() {
+        let file = std::fs::File::open("data/audio/not-an-audio-file.txt").unwrap();
+        let buf = std::io::BufReader::new(file);
+        let result = FlacDecoder::new(buf);
+        expect_that!(&result, is_variant!(Result::Err));
+        if let Err(e) = result {
+            expect_that!(&e, is_variant!(DecoderError::InvalidEncoding));
+        }
+    }
+
+    #[test]
+    fn corrupted_frame() {
+        let mut data = std::fs::read("data/audio/mono-16-44100.flac").unwrap();
+        let last = data.len() - 100;
+        data[last] ^= 0x10;
+        let mut decoder = FlacDecoder::new(std::io::Cursor::new(data)).unwrap();
+        let result = decoder.read_all();
+        expect_that!(&result, is_variant!(Result::Err));
+        if let Err(e) = result {
+            expect_that!(&e, is_variant!(DecoderError::InvalidData));
+        }
+    }
+
+    #[test]
+    fn loading() {
+        let decoder = open("mono-8-44100");
+        expect_that!(&decoder.format(), eq(Format::Mono8));
+        expect_that!(&decoder.byte_length(), eq(21231));
+        expect_that!(&decoder.sample_length(), eq(21231));
+        expect_that!(&decoder.byte_rate(), eq(44100));
+        expect_that!(&decoder.sample_rate(), eq(44100));
+
+        let decoder = open("stereo-8-44100");
+        expect_that!(&decoder.format(), eq(Format::Stereo8));
+        expect_that!(&decoder.sample_length(), eq(21231));
+        expect_that!(&decoder.byte_rate(), eq(44100 * 2));
+
+        let decoder = open("mono-16-44100");
+        expect_that!(&decoder.format(), eq(Format::Mono16));
+        expect_that!(&decoder.byte_length(), eq(21231 * 2));
+        expect_that!(&decoder.byte_rate(), eq(44100 * 2));
+
+        let decoder = open("stereo-16-44100");
+        expect_that!(&decoder.format(), eq(Format::Stereo16));
+        expect_that!(&decoder.byte_length(), eq(21231 * 4));
+        expect_that!(&decoder.byte_rate(), eq(44100 * 4));
+
+        // Converted to 16 bits.
+        let decoder = open("mono-24-22050");
+        expect_that!(&decoder.format(), eq(Format::Mono16));
+        expect_that!(&decoder.sample_rate(), eq(22050));
+    }
+
+    #[test]
+    fn read_all() {
+        // The flac files are lossless encodings of the wav files.
+        for name in [
+            "mono-8-44100",
+            "stereo-8-44100",
+            "mono-16-44100",
+            "stereo-16-44100",
+        ] {
+            let mut decoder = open(name);
+            expect_that!(&decoder.read_all().unwrap(), eq(wav_content(name)));
+        }
+        // The lower 8 bits are dropped.
+        let mut decoder = open("mono-24-22050");
+        expect_that!(
+            &decoder.read_all().unwrap(),
+            eq(wav_content("mono-16-22050"))
+        );
+    }
+
+    #[test]
+    fn byte_seek() {
+        let mut decoder = open("stereo-16-44100");
+
+        expect_that!(&decoder.byte_stream_position().unwrap(), eq(0));
+        expect_that!(&decoder.sample_stream_position().unwrap(), eq(0));
+
+        // From start.
+        expect_that!(
+            &decoder.byte_seek(std::io::SeekFrom::Start(24)).unwrap(),
+            eq(24)
+        );
+        expect_that!(&decoder.sample_stream_position().unwrap(), eq(6));
+
+        // From current.
+        expect_that!(
+            &decoder.byte_seek(std::io::SeekFrom::Current(-8)).unwrap(),
+            eq(16)
+        );
+        expect_that!(&decoder.sample_stream_position().unwrap(), eq(4));
+
+        // From end.
+        expect_that!(
+            &decoder.byte_seek(std::io::SeekFrom::End(-12)).unwrap(),
+            eq(21228 * 4)
+        );
+        expect_that!(&decoder.sample_stream_position().unwrap(), eq(21228));
+
+        // Beyond end.
+        expect_that!(
+            &decoder.byte_seek(std::io::SeekFrom::End(40)).unwrap(),
+            eq(21231 * 4)
+        );
+        expect_that!(&decoder.sample_stream_position().unwrap(), eq(21231));
+        let mut buf = vec![0; 8];
+        expect_that!(&decoder.read(&mut buf).unwrap(), eq(0));
+
+        // Before start.
+        expect_that!(
+            &decoder
+                .byte_seek(std::io::SeekFrom::Current(-100000))
+                .unwrap(),
+            eq(0)
+        );
+        expect_that!(&decoder.sample_stream_position().unwrap(), eq(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid seek offset (6)")]
+    fn byte_seek_invalid_offset() {
+        let mut decoder = open("stereo-16-44100");
+        decoder.byte_seek(std::io::SeekFrom::Start(6)).unwrap();
+    }
+
+    #[test]
+    fn sample_accurate_seek() {
+        for name in ["stereo-8-44100", "mono-16-44100", "stereo-16-44100"] {
+            let content = wav_content(name);
+            let mut decoder = open(name);
+            let tbps = decoder.format().total_bytes_per_sample() as u64;
+            // Within frames, at frame boundaries, forward and backward.
+            for sample in [4095, 4096, 10000, 17, 12288, 8191, 21230, 0] {
+                expect_that!(
+                    &decoder
+                        .sample_seek(std::io::SeekFrom::Start(sample))
+                        .unwrap(),
+                    eq(sample)
+                );
+                let mut buf = vec![0; 64 * tbps as usize];
+                let count = decoder.read(&mut buf).unwrap();
+                let begin = (sample * tbps) as usize;
+                let end = std::cmp::min(begin + buf.len(), content.len());
+                expect_that!(&count, eq(end - begin));
+                expect_that!(&&buf[..count], eq(&content[begin..end]));
+                expect_that!(&decoder.byte_stream_position().unwrap(), eq(end as u64));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid buffer length (7)")]
+    fn read_invalid_buffer_length() {
+        let mut decoder = open("mono-16-44100");
+        let mut buf = vec![0; 7];
+        decoder.read(&mut buf).unwrap();
+    }
+
+    #[test]
+    fn read_to_end() {
+        let mut decoder = open("stereo-8-44100");
+        decoder.byte_seek(std::io::SeekFrom::Start(572)).unwrap();
+        let content = decoder.read_to_end().unwrap();
+        expect_that!(&content.len(), eq(decoder.byte_length() as usize - 572));
+        expect_that!(&content, eq(wav_content("stereo-8-44100")[572..].to_vec()));
+    }
+}
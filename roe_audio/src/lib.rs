@@ -16,6 +16,9 @@ pub use wav_decoder::*;
 mod ogg_decoder;
 pub use ogg_decoder::*;
 
+mod flac_decoder;
+pub use flac_decoder::*;
+
 mod alto_lib;
 pub use alto_lib::*;
 
@@ -28,6 +31,9 @@ pub use context::*;
 mod buffer;
 pub use buffer::*;
 
+mod fade;
+pub use fade::*;
+
 mod source;
 pub use source::*;
 
@@ -36,3 +42,9 @@ pub use static_source::*;
 
 mod streaming_source;
 pub use streaming_source::*;
+
+mod mixer;
+pub use mixer::*;
+
+mod audio_cache;
+pub use audio_cache::*;
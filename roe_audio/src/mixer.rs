@@ -0,0 +1,314 @@
+use super::{Error, FadeCurve, GainFade, Source, StaticSource, StreamingSource};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+#[derive(Debug)]
+pub enum MixerSource {
+    Static(StaticSource),
+    Streaming(StreamingSource),
+}
+
+impl MixerSource {
+    pub fn source(&self) -> &dyn Source {
+        match self {
+            Self::Static(source) => source,
+            Self::Streaming(source) => source,
+        }
+    }
+
+    pub fn source_mut(&mut self) -> &mut dyn Source {
+        match self {
+            Self::Static(source) => source,
+            Self::Streaming(source) => source,
+        }
+    }
+
+    fn update(&mut self, dt: Duration) -> Result<(), Error> {
+        self.source_mut().update_fade(dt);
+        if let Self::Streaming(source) = self {
+            source.update_buffers()?;
+        }
+        Ok(())
+    }
+}
+
+impl From<StaticSource> for MixerSource {
+    fn from(source: StaticSource) -> Self {
+        Self::Static(source)
+    }
+}
+
+impl From<StreamingSource> for MixerSource {
+    fn from(source: StreamingSource) -> Self {
+        Self::Streaming(source)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct MixerSourceId(u64);
+
+impl std::fmt::Display for MixerSourceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Owns a set of sources and updates them every frame: fades are applied and streaming
+// sources refill their buffers.
+#[derive(Debug, Default)]
+pub struct Mixer {
+    sources: BTreeMap<MixerSourceId, MixerSource>,
+    paused_sources: BTreeSet<MixerSourceId>,
+    next_id: u64,
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<S: Into<MixerSource>>(&mut self, source: S) -> MixerSourceId {
+        let id = MixerSourceId(self.next_id);
+        self.next_id += 1;
+        self.sources.insert(id, source.into());
+        id
+    }
+
+    pub fn remove(&mut self, id: MixerSourceId) -> Option<MixerSource> {
+        self.paused_sources.remove(&id);
+        self.sources.remove(&id)
+    }
+
+    pub fn contains(&self, id: MixerSourceId) -> bool {
+        self.sources.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    pub fn get(&self, id: MixerSourceId) -> Option<&MixerSource> {
+        self.sources.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: MixerSourceId) -> Option<&mut MixerSource> {
+        self.sources.get_mut(&id)
+    }
+
+    pub fn source(&self, id: MixerSourceId) -> Option<&dyn Source> {
+        self.get(id).map(MixerSource::source)
+    }
+
+    pub fn source_mut(&mut self, id: MixerSourceId) -> Option<&mut dyn Source> {
+        self.get_mut(id).map(MixerSource::source_mut)
+    }
+
+    // Fades the first source out and stops it, while the second source fades in to the gain it
+    // is set to, or the gain its current fade settles on. Both fades use an equal power curve.
+    // The first source gets back the gain it would have settled on once stopped.
+    pub fn crossfade(
+        &mut self,
+        from: MixerSourceId,
+        to: MixerSourceId,
+        duration: Duration,
+    ) -> Result<(), Error> {
+        if from == to {
+            return Err(Error::SelfCrossfade(from));
+        }
+        if let Some(id) = [from, to].into_iter().find(|id| !self.contains(*id)) {
+            return Err(Error::UnknownMixerSource(id));
+        }
+
+        let from = self.source_mut(from).unwrap();
+        let restore_gain = from.fade().map_or(from.gain(), GainFade::final_gain);
+        from.set_fade(Some(
+            GainFade::fade_out(from.gain(), duration)
+                .with_curve(FadeCurve::EqualPower)
+                .with_restore_gain(restore_gain),
+        ));
+
+        let to = self.source_mut(to).unwrap();
+        let target_gain = to.fade().map_or(to.gain(), GainFade::final_gain);
+        if !to.playing() {
+            to.set_gain(0.);
+        }
+        to.set_fade(Some(
+            GainFade::new(to.gain(), target_gain, duration).with_curve(FadeCurve::EqualPower),
+        ));
+        to.play()
+    }
+
+    // Pauses the playing sources until resume_all is called, for example while the application
+    // is suspended. Sources already paused or stopped are not resumed afterwards.
+    pub fn pause_all(&mut self) {
+        for (id, source) in self.sources.iter_mut() {
+            let source = source.source_mut();
+            if source.playing() {
+                source.pause();
+                self.paused_sources.insert(*id);
+            }
+        }
+    }
+
+    pub fn resume_all(&mut self) -> Result<(), Error> {
+        for id in std::mem::take(&mut self.paused_sources) {
+            if let Some(source) = self.source_mut(id) {
+                source.play()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, dt: Duration) -> Result<(), Error> {
+        for source in self.sources.values_mut() {
+            source.update(dt)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        super::{Buffer, Context, Device, Format},
+        *,
+    };
+    use galvanic_assert::{matchers::*, *};
+
+    fn create_source(context: &Context) -> StaticSource {
+        let format = Format::Stereo16;
+        let buf = Buffer::new(
+            context,
+            vec![0; 64 * format.total_bytes_per_sample() as usize].as_ref(),
+            format,
+            64,
+        )
+        .unwrap();
+        let mut source = StaticSource::with_buffer(context, &buf).unwrap();
+        source.set_looping(true);
+        source
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn crossfade() {
+        let device = Device::default().unwrap();
+        let context = Context::default(&device).unwrap();
+        let mut mixer = Mixer::new();
+        let a = mixer.insert(create_source(&context));
+        let b = mixer.insert(create_source(&context));
+        expect_that!(&mixer.len(), eq(2));
+        mixer.source_mut(b).unwrap().set_gain(0.5);
+        mixer.source_mut(a).unwrap().play().unwrap();
+
+        mixer.crossfade(a, b, Duration::from_secs(1)).unwrap();
+        expect_that!(mixer.source(b).unwrap().playing());
+        expect_that!(&mixer.source(b).unwrap().gain(), close_to(0., 1e-6));
+
+        mixer.update(Duration::from_millis(500)).unwrap();
+        let gain_a = mixer.source(a).unwrap().gain();
+        let gain_b = mixer.source(b).unwrap().gain();
+        expect_that!(&gain_a, close_to(std::f32::consts::FRAC_1_SQRT_2, 1e-5));
+        expect_that!(
+            &gain_b,
+            close_to(0.5 * std::f32::consts::FRAC_1_SQRT_2, 1e-5)
+        );
+
+        mixer.update(Duration::from_millis(500)).unwrap();
+        expect_that!(!mixer.source(a).unwrap().playing());
+        expect_that!(&mixer.source(a).unwrap().gain(), close_to(1., 1e-6));
+        expect_that!(&mixer.source(b).unwrap().gain(), close_to(0.5, 1e-6));
+        expect_that!(mixer.source(b).unwrap().fade().is_none());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn crossfade_while_fading() {
+        let device = Device::default().unwrap();
+        let context = Context::default(&device).unwrap();
+        let mut mixer = Mixer::new();
+        let a = mixer.insert(create_source(&context));
+        let b = mixer.insert(create_source(&context));
+        let c = mixer.insert(create_source(&context));
+        mixer.source_mut(b).unwrap().set_gain(0.5);
+        mixer.source_mut(a).unwrap().play().unwrap();
+
+        mixer.crossfade(a, b, Duration::from_secs(1)).unwrap();
+        mixer.update(Duration::from_millis(500)).unwrap();
+        // Back to the first source while it is still fading out.
+        mixer.crossfade(b, a, Duration::from_secs(1)).unwrap();
+        expect_that!(
+            &mixer.source(a).unwrap().fade().unwrap().target_gain(),
+            close_to(1., 1e-6)
+        );
+        mixer.update(Duration::from_millis(500)).unwrap();
+        mixer.crossfade(a, c, Duration::from_secs(1)).unwrap();
+        mixer.update(Duration::from_secs(1)).unwrap();
+
+        expect_that!(!mixer.source(a).unwrap().playing());
+        expect_that!(&mixer.source(a).unwrap().gain(), close_to(1., 1e-6));
+        expect_that!(!mixer.source(b).unwrap().playing());
+        expect_that!(&mixer.source(b).unwrap().gain(), close_to(0.5, 1e-6));
+        expect_that!(mixer.source(c).unwrap().playing());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn pause_all() {
+        let device = Device::default().unwrap();
+        let context = Context::default(&device).unwrap();
+        let mut mixer = Mixer::new();
+        let a = mixer.insert(create_source(&context));
+        let b = mixer.insert(create_source(&context));
+        let c = mixer.insert(create_source(&context));
+        mixer.source_mut(a).unwrap().play().unwrap();
+        mixer.source_mut(c).unwrap().play().unwrap();
+
+        mixer.pause_all();
+        expect_that!(!mixer.source(a).unwrap().playing());
+        expect_that!(!mixer.source(b).unwrap().playing());
+        expect_that!(!mixer.source(c).unwrap().playing());
+
+        mixer.remove(c);
+        mixer.resume_all().unwrap();
+        expect_that!(mixer.source(a).unwrap().playing());
+        expect_that!(!mixer.source(b).unwrap().playing());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn crossfade_unknown_source() {
+        let device = Device::default().unwrap();
+        let context = Context::default(&device).unwrap();
+        let mut mixer = Mixer::new();
+        let a = mixer.insert(create_source(&context));
+        let b = mixer.insert(create_source(&context));
+        mixer.remove(b);
+        expect_that!(
+            &mixer.crossfade(a, b, Duration::from_secs(1)),
+            is_variant!(Result::Err)
+        );
+        expect_that!(mixer.source(a).unwrap().fade().is_none());
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn crossfade_with_itself() {
+        let device = Device::default().unwrap();
+        let context = Context::default(&device).unwrap();
+        let mut mixer = Mixer::new();
+        let a = mixer.insert(create_source(&context));
+        expect_that!(
+            &mixer.crossfade(a, a, Duration::from_secs(1)),
+            is_variant!(Result::Err)
+        );
+        expect_that!(mixer.source(a).unwrap().fade().is_none());
+    }
+}
@@ -1,7 +1,9 @@
-use super::{Error, Format};
+use super::{Error, Format, GainFade};
 
 pub use alto::DistanceModel;
 
+use std::time::Duration;
+
 pub trait Source {
     fn format(&self) -> Format;
     fn sample_rate(&self) -> u32;
@@ -60,6 +62,51 @@ pub trait Source {
     fn gain(&self) -> f32;
     fn set_gain(&mut self, value: f32);
 
+    // Gain envelope applied by update_fade.
+    fn fade(&self) -> Option<&GainFade>;
+    fn set_fade(&mut self, value: Option<GainFade>);
+
+    fn fade_to(&mut self, gain: f32, duration: Duration) {
+        self.set_fade(Some(GainFade::new(self.gain(), gain, duration)));
+    }
+
+    // Starts the source from silence if it isn't playing.
+    fn fade_in(&mut self, gain: f32, duration: Duration) -> Result<(), Error> {
+        if !self.playing() {
+            self.set_gain(0.);
+        }
+        self.fade_to(gain, duration);
+        self.play()
+    }
+
+    // The gain the source settles on, i.e. the current gain or the one a fade in progress
+    // settles on, is restored once the source is stopped.
+    fn fade_out_and_stop(&mut self, duration: Duration) {
+        let restore_gain = self.fade().map_or(self.gain(), GainFade::final_gain);
+        self.set_fade(Some(
+            GainFade::fade_out(self.gain(), duration).with_restore_gain(restore_gain),
+        ));
+    }
+
+    // Advances the fade and applies its gain, to be called every frame.
+    fn update_fade(&mut self, dt: Duration) {
+        let mut fade = match self.fade() {
+            Some(fade) => fade.clone(),
+            None => return,
+        };
+        let gain = fade.advance(dt);
+        if fade.is_finished() {
+            self.set_fade(None);
+            if fade.stops() {
+                self.stop();
+            }
+            self.set_gain(fade.final_gain());
+        } else {
+            self.set_gain(gain);
+            self.set_fade(Some(fade));
+        }
+    }
+
     fn min_gain(&self) -> f32;
     fn set_min_gain(&mut self, value: f32);
 
@@ -93,14 +140,26 @@ pub trait Source {
     fn distance_model(&self) -> DistanceModel;
     fn set_distance_model(&mut self, value: DistanceModel);
 
-    fn position<V: From<[f32; 3]>>(&self) -> V;
-    fn set_position<V: Into<[f32; 3]>>(&mut self, value: V);
-
-    fn velocity<V: From<[f32; 3]>>(&self) -> V;
-    fn set_velocity<V: Into<[f32; 3]>>(&mut self, value: V);
-
-    fn direction<V: From<[f32; 3]>>(&self) -> V;
-    fn set_direction<V: Into<[f32; 3]>>(&mut self, value: V);
+    fn position<V: From<[f32; 3]>>(&self) -> V
+    where
+        Self: Sized;
+    fn set_position<V: Into<[f32; 3]>>(&mut self, value: V)
+    where
+        Self: Sized;
+
+    fn velocity<V: From<[f32; 3]>>(&self) -> V
+    where
+        Self: Sized;
+    fn set_velocity<V: Into<[f32; 3]>>(&mut self, value: V)
+    where
+        Self: Sized;
+
+    fn direction<V: From<[f32; 3]>>(&self) -> V
+    where
+        Self: Sized;
+    fn set_direction<V: Into<[f32; 3]>>(&mut self, value: V)
+    where
+        Self: Sized;
 }
 
 #[macro_export]
@@ -253,6 +312,32 @@ macro_rules! generate_source_tests {
             expect_that!(&source.gain(), close_to(0.5, 1e-6));
         }
 
+        #[test]
+        #[serial_test::serial]
+        fn fades() {
+            let context = create_context();
+            let mut source = <$TestFixture>::create_with_data(&context, Format::Stereo16, 64, 64);
+            source.set_looping(true);
+            source
+                .fade_in(0.8, std::time::Duration::from_secs(1))
+                .unwrap();
+            expect_that!(&source.playing(), eq(true));
+            expect_that!(&source.gain(), close_to(0., 1e-6));
+            source.update_fade(std::time::Duration::from_millis(500));
+            expect_that!(&source.gain(), close_to(0.4, 1e-6));
+            source.update_fade(std::time::Duration::from_millis(500));
+            expect_that!(&source.gain(), close_to(0.8, 1e-6));
+            expect_that!(source.fade().is_none());
+
+            source.fade_out_and_stop(std::time::Duration::from_secs(2));
+            source.update_fade(std::time::Duration::from_secs(1));
+            expect_that!(&source.gain(), close_to(0.4, 1e-6));
+            expect_that!(&source.playing(), eq(true));
+            source.update_fade(std::time::Duration::from_secs(1));
+            expect_that!(&source.playing(), eq(false));
+            expect_that!(&source.gain(), close_to(0.8, 1e-6));
+        }
+
         #[test]
         #[serial_test::serial]
         #[should_panic(expected = "InvalidValue")]
@@ -1,4 +1,4 @@
-use super::{Buffer, Context, DistanceModel, Error, Format, Source};
+use super::{Buffer, Context, DistanceModel, Error, Format, GainFade, Source};
 
 use alto::Source as AltoSource;
 
@@ -7,6 +7,7 @@ use std::sync::Arc;
 pub struct StaticSource {
     value: alto::StaticSource,
     paused_sample_offset: u64,
+    fade: Option<GainFade>,
 }
 
 impl StaticSource {
@@ -15,6 +16,7 @@ impl StaticSource {
         Ok(Self {
             value: static_source,
             paused_sample_offset: 0,
+            fade: None,
         })
     }
     pub fn with_buffer(context: &Context, buf: &Buffer) -> Result<Self, Error> {
@@ -150,6 +152,14 @@ impl Source for StaticSource {
         self.value.set_gain(value).unwrap()
     }
 
+    fn fade(&self) -> Option<&GainFade> {
+        self.fade.as_ref()
+    }
+
+    fn set_fade(&mut self, value: Option<GainFade>) {
+        self.fade = value;
+    }
+
     fn min_gain(&self) -> f32 {
         self.value.min_gain()
     }
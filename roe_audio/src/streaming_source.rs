@@ -1,4 +1,4 @@
-use super::{Context, Decoder, DistanceModel, Error, Format, Source};
+use super::{Context, Decoder, DistanceModel, Error, Format, GainFade, Source};
 
 use alto::{Mono, Source as AltoSource, SourceState, Stereo};
 
@@ -65,6 +65,7 @@ pub struct StreamingSource {
     processed_sample_count: u64,
     paused_sample_offset: u64,
     processing_buffer_queue: bool,
+    fade: Option<GainFade>,
 }
 
 impl StreamingSource {
@@ -90,6 +91,7 @@ impl StreamingSource {
             processed_sample_count: 0,
             paused_sample_offset: 0,
             processing_buffer_queue: false,
+            fade: None,
         })
     }
 
@@ -335,6 +337,14 @@ impl Source for StreamingSource {
         self.value.set_gain(value).unwrap()
     }
 
+    fn fade(&self) -> Option<&GainFade> {
+        self.fade.as_ref()
+    }
+
+    fn set_fade(&mut self, value: Option<GainFade>) {
+        self.fade = value;
+    }
+
     fn min_gain(&self) -> f32 {
         self.value.min_gain()
     }
@@ -1,4 +1,4 @@
-use roe_app::{Application, ApplicationState};
+use roe_app::{Application, ApplicationState, EventSender};
 use roe_examples::*;
 use roe_os as os;
 
@@ -6,12 +6,13 @@ use roe_os as os;
 enum CustomEvent {
     SomeTimePassed,
     LongTimePassed,
+    WorkerTick(u64),
 }
 
 #[derive(Debug)]
 struct ApplicationImpl {
     _window: os::Window,
-    event_loop_proxy: os::EventLoopProxy<CustomEvent>,
+    event_sender: EventSender<CustomEvent>,
     processed_fixed_frames: u64,
     processed_variable_frames: u64,
     processed_cursor_moved_events: u64,
@@ -31,9 +32,26 @@ impl ApplicationImpl {
                 height: 600,
             }))
             .build(event_loop)?;
+
+        let event_sender = EventSender::new(event_loop);
+        let worker_event_sender = event_sender.clone();
+        std::thread::spawn(move || {
+            let mut tick = 0;
+            loop {
+                std::thread::sleep(std::time::Duration::from_secs(5));
+                if worker_event_sender
+                    .send(CustomEvent::WorkerTick(tick))
+                    .is_err()
+                {
+                    break;
+                }
+                tick += 1;
+            }
+        });
+
         Ok(Self {
             _window: window,
-            event_loop_proxy: event_loop.create_proxy(),
+            event_sender,
             processed_fixed_frames: 0,
             processed_variable_frames: 0,
             processed_cursor_moved_events: 0,
@@ -53,13 +71,11 @@ impl ApplicationState<ApplicationError, CustomEvent> for ApplicationImpl {
         }
 
         if self.processed_fixed_frames % 120 == 0 {
-            self.event_loop_proxy
-                .send_event(CustomEvent::SomeTimePassed)?;
+            self.event_sender.send(CustomEvent::SomeTimePassed)?;
         }
 
         if self.processed_fixed_frames % 240 == 0 {
-            self.event_loop_proxy
-                .send_event(CustomEvent::LongTimePassed)?;
+            self.event_sender.send(CustomEvent::LongTimePassed)?;
         }
 
         self.processed_fixed_frames = self.processed_fixed_frames + 1;
@@ -451,7 +467,12 @@ impl ApplicationState<ApplicationError, CustomEvent> for ApplicationImpl {
     }
 
     fn on_custom_event(&mut self, event: CustomEvent) -> Result<(), ApplicationError> {
-        println!("Processed 'custom' event, {:?}", event);
+        match event {
+            CustomEvent::WorkerTick(tick) => {
+                println!("Processed 'custom' event from worker thread, tick {}", tick)
+            }
+            _ => println!("Processed 'custom' event, {:?}", event),
+        }
         Ok(())
     }
 
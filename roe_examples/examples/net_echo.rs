@@ -0,0 +1,138 @@
+use roe_examples::*;
+
+use roe_app::{Application, ApplicationState, ControlFlow};
+
+use roe_net::{Channel, Endpoint, EndpointDescriptor, NetEvent, UdpTransport};
+
+use serde::{Deserialize, Serialize};
+
+use std::net::SocketAddr;
+
+const PROTOCOL_ID: u64 = 0x524f45;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Message {
+    Ping(u32),
+    Pong(u32),
+}
+
+// Runs a server and a client in the same process. The client sends a ping every second, and
+// the server replies with a pong. The networking is pumped once per fixed update.
+struct EchoState {
+    server: Endpoint<UdpTransport>,
+    client: Endpoint<UdpTransport>,
+    server_address: SocketAddr,
+    next_ping: u32,
+    time_to_next_ping: std::time::Duration,
+    control_flow: ControlFlow<ApplicationError, ApplicationEvent>,
+}
+
+impl EchoState {
+    const PING_COUNT: u32 = 5;
+
+    fn new() -> Result<Self, ApplicationError> {
+        let server = Endpoint::new(
+            UdpTransport::bind("127.0.0.1:0")?,
+            EndpointDescriptor {
+                protocol_id: PROTOCOL_ID,
+                accept_connections: true,
+                ..EndpointDescriptor::default()
+            },
+        );
+        let server_address = server.local_address()?;
+        let mut client = Endpoint::new(
+            UdpTransport::bind("127.0.0.1:0")?,
+            EndpointDescriptor {
+                protocol_id: PROTOCOL_ID,
+                ..EndpointDescriptor::default()
+            },
+        );
+        client.connect(server_address);
+        Ok(Self {
+            server,
+            client,
+            server_address,
+            next_ping: 0,
+            time_to_next_ping: std::time::Duration::from_secs(0),
+            control_flow: ControlFlow::Continue,
+        })
+    }
+
+    fn process_server_events(&mut self) -> Result<(), ApplicationError> {
+        while let Some(event) = self.server.poll_event() {
+            match event {
+                NetEvent::Connected(address) => println!("Server - Client {} connected.", address),
+                NetEvent::Disconnected(address, reason) => {
+                    println!("Server - Client {} disconnected ({:?}).", address, reason)
+                }
+                NetEvent::Message { address, data, .. } => {
+                    if let Message::Ping(id) = roe_net::deserialize_message(&data)? {
+                        println!("Server - Received ping {}.", id);
+                        self.server
+                            .send_message(address, Channel::Reliable, &Message::Pong(id))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn process_client_events(&mut self) -> Result<(), ApplicationError> {
+        while let Some(event) = self.client.poll_event() {
+            match event {
+                NetEvent::Connected(address) => println!("Client - Connected to {}.", address),
+                NetEvent::Disconnected(address, reason) => {
+                    println!("Client - Disconnected from {} ({:?}).", address, reason);
+                    self.control_flow = ControlFlow::Exit;
+                }
+                NetEvent::Message { data, .. } => {
+                    if let Message::Pong(id) = roe_net::deserialize_message(&data)? {
+                        println!("Client - Received pong {}.", id);
+                        if id + 1 == Self::PING_COUNT {
+                            self.client.disconnect(self.server_address)?;
+                            self.control_flow = ControlFlow::Exit;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationState<ApplicationError, ApplicationEvent> for EchoState {
+    fn on_fixed_update(&mut self, dt: std::time::Duration) -> Result<(), ApplicationError> {
+        let time = std::time::Instant::now();
+        self.server.pump(time)?;
+        self.client.pump(time)?;
+        self.process_server_events()?;
+        self.process_client_events()?;
+
+        if self.client.is_connected(self.server_address) && self.next_ping < Self::PING_COUNT {
+            self.time_to_next_ping = self.time_to_next_ping.saturating_sub(dt);
+            if self.time_to_next_ping.is_zero() {
+                println!("Client - Sending ping {}.", self.next_ping);
+                self.client.send_message(
+                    self.server_address,
+                    Channel::Reliable,
+                    &Message::Ping(self.next_ping),
+                )?;
+                self.next_ping += 1;
+                self.time_to_next_ping = std::time::Duration::from_secs(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn requested_control_flow(&mut self) -> ControlFlow<ApplicationError, ApplicationEvent> {
+        let mut control_flow = ControlFlow::Continue;
+        std::mem::swap(&mut control_flow, &mut self.control_flow);
+        control_flow
+    }
+}
+
+fn main() {
+    const FIXED_FRAMERATE: u64 = 30;
+    let app = Application::new(FIXED_FRAMERATE, None);
+    app.run_headless(|| Ok(Box::new(EchoState::new()?)));
+}
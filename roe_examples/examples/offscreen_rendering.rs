@@ -52,7 +52,7 @@ impl ApplicationImpl {
                 window,
                 surface,
                 &CanvasWindowDescriptor::default(),
-            );
+            )?;
             (window, instance)
         };
 
@@ -65,10 +65,11 @@ impl ApplicationImpl {
                     format: CanvasColorBufferFormat::Rgba8UnormSrgb,
                     usage: CanvasColorBufferUsage::TEXTURE_BINDING
                         | CanvasColorBufferUsage::COPY_SRC,
+                    sample_count: None,
                 }),
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )?;
 
         let shape2_pipeline = roe_shape::RenderPipeline::new(
             &instance,
@@ -193,7 +194,7 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
         _size: os::PhysicalSize<u32>,
     ) -> Result<(), ApplicationError> {
         if wid == self.window.id() {
-            self.window.update_buffer(&self.instance);
+            self.window.update_buffer(&self.instance)?;
         }
         Ok(())
     }
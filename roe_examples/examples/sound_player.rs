@@ -1,22 +1,26 @@
-use roe_app::{Application, ApplicationState};
+use roe_app::{Application, ApplicationState, LifecycleEvent};
 
 use roe_os as os;
 
-use roe_audio::{Buffer, Decoder, Source};
+use roe_audio::{Buffer, Mixer, MixerSourceId};
 
-use std::{borrow::BorrowMut, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
 use roe_examples::*;
 
 #[derive(Debug)]
 struct ApplicationImpl {
     window: os::Window,
-    static_source: roe_audio::StaticSource,
-    streaming_source: roe_audio::StreamingSource,
+    mixer: Rc<RefCell<Mixer>>,
+    static_source: MixerSourceId,
+    streaming_source: MixerSourceId,
 }
 
 impl ApplicationImpl {
-    fn new(event_loop: &os::EventLoop<ApplicationEvent>) -> Result<Self, ApplicationError> {
+    fn new(
+        event_loop: &os::EventLoop<ApplicationEvent>,
+        mixer: Rc<RefCell<Mixer>>,
+    ) -> Result<Self, ApplicationError> {
         let window = os::WindowBuilder::new()
             .with_title("Sound Player")
             .with_inner_size(os::Size::Physical(os::PhysicalSize {
@@ -29,8 +33,7 @@ impl ApplicationImpl {
 
         let audio_buffer = Buffer::from_decoder(
             &audio_context,
-            load_decoder("roe_examples/data/audio/stereo-16-44100.wav")?.borrow_mut()
-                as &mut dyn Decoder,
+            load_decoder("roe_examples/data/audio/stereo-16-44100.wav")?.as_mut(),
         )?;
         let static_source = roe_audio::StaticSource::with_buffer(&audio_context, &audio_buffer)?;
 
@@ -41,8 +44,12 @@ impl ApplicationImpl {
             &roe_audio::StreamingSourceDescriptor::default(),
         )?;
 
+        let static_source = mixer.borrow_mut().insert(static_source);
+        let streaming_source = mixer.borrow_mut().insert(streaming_source);
+
         Ok(Self {
             window,
+            mixer,
             static_source,
             streaming_source,
         })
@@ -61,60 +68,63 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
     ) -> Result<(), ApplicationError> {
         if !is_repeat && wid == self.window.id() {
             if let Some(key_code) = key_code {
+                let mut mixer = self.mixer.borrow_mut();
+
+                let static_source = mixer.source_mut(self.static_source).unwrap();
                 if key_code == os::KeyCode::Q {
-                    self.static_source.play()?;
+                    static_source.play()?;
                 }
                 if key_code == os::KeyCode::W {
-                    self.static_source.replay()?;
+                    static_source.replay()?;
                 }
                 if key_code == os::KeyCode::E {
-                    self.static_source.pause();
+                    static_source.pause();
                 }
                 if key_code == os::KeyCode::R {
-                    self.static_source.stop();
+                    static_source.stop();
                 }
                 if key_code == os::KeyCode::T {
-                    let cur_time = self.static_source.time_offset();
+                    let cur_time = static_source.time_offset();
                     let time_step = std::time::Duration::from_secs_f64(0.1);
                     let new_time = if time_step > cur_time {
                         std::time::Duration::from_millis(0)
                     } else {
                         cur_time - time_step
                     };
-                    self.static_source.set_time_offset(new_time)?;
+                    static_source.set_time_offset(new_time)?;
                 }
                 if key_code == os::KeyCode::Y {
-                    self.static_source.set_time_offset(
-                        self.static_source.time_offset() + std::time::Duration::from_secs_f64(0.1),
+                    static_source.set_time_offset(
+                        static_source.time_offset() + std::time::Duration::from_secs_f64(0.1),
                     )?;
                 }
 
+                let streaming_source = mixer.source_mut(self.streaming_source).unwrap();
                 if key_code == os::KeyCode::A {
-                    self.streaming_source.play()?;
+                    streaming_source.play()?;
                 }
                 if key_code == os::KeyCode::S {
-                    self.streaming_source.replay()?;
+                    streaming_source.replay()?;
                 }
                 if key_code == os::KeyCode::D {
-                    self.streaming_source.pause();
+                    streaming_source.pause();
                 }
                 if key_code == os::KeyCode::F {
-                    self.streaming_source.stop();
+                    streaming_source.stop();
                 }
                 if key_code == os::KeyCode::G {
-                    let cur_time = self.static_source.time_offset();
+                    let cur_time = streaming_source.time_offset();
                     let time_step = std::time::Duration::from_secs_f64(0.1);
                     let new_time = if time_step > cur_time {
                         std::time::Duration::from_millis(0)
                     } else {
                         cur_time - time_step
                     };
-                    self.streaming_source.set_time_offset(new_time)?;
+                    streaming_source.set_time_offset(new_time)?;
                 }
                 if key_code == os::KeyCode::H {
-                    self.streaming_source.set_time_offset(
-                        self.streaming_source.time_offset()
-                            + std::time::Duration::from_secs_f64(1.),
+                    streaming_source.set_time_offset(
+                        streaming_source.time_offset() + std::time::Duration::from_secs_f64(1.),
                     )?;
                 }
             }
@@ -122,8 +132,8 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
         Ok(())
     }
 
-    fn on_fixed_update(&mut self, _: std::time::Duration) -> Result<(), ApplicationError> {
-        self.streaming_source.update_buffers()?;
+    fn on_fixed_update(&mut self, dt: std::time::Duration) -> Result<(), ApplicationError> {
+        self.mixer.borrow_mut().update(dt)?;
         Ok(())
     }
 }
@@ -131,6 +141,17 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
 fn main() {
     const FIXED_FRAMERATE: u64 = 30;
     const VARIABLE_FRAMERATE_CAP: u64 = 60;
+    let mixer = Rc::new(RefCell::new(Mixer::new()));
+    let hook_mixer = mixer.clone();
     Application::new(FIXED_FRAMERATE, Some(VARIABLE_FRAMERATE_CAP))
-        .run(|event_queue| Ok(Box::new(ApplicationImpl::new(event_queue)?)));
+        // The audio is paused while the application is suspended.
+        .with_lifecycle_hook(move |event| {
+            match event {
+                LifecycleEvent::Suspended => hook_mixer.borrow_mut().pause_all(),
+                LifecycleEvent::Resumed => hook_mixer.borrow_mut().resume_all()?,
+                _ => (),
+            }
+            Ok(())
+        })
+        .run(move |event_queue| Ok(Box::new(ApplicationImpl::new(event_queue, mixer)?)));
 }
@@ -47,7 +47,7 @@ impl ApplicationImpl {
                     sample_count: Self::SAMPLE_COUNT,
                     ..CanvasWindowDescriptor::default()
                 },
-            );
+            )?;
             (window, Rc::new(instance))
         };
 
@@ -89,7 +89,7 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
         size: os::PhysicalSize<u32>,
     ) -> Result<(), ApplicationError> {
         if wid == self.window.id() {
-            self.window.update_buffer(&self.instance);
+            self.window.update_buffer(&self.instance)?;
             self.projection_transform = roe_math::ortographic_projection2(
                 0.,
                 1f32.max(size.width as f32),
@@ -2,7 +2,7 @@ use std::iter::once;
 
 use rand::Rng;
 
-use roe_app::{Application, ApplicationState};
+use roe_app::{Application, ApplicationState, CrashReporterDescriptor};
 
 use roe_os as os;
 
@@ -54,9 +54,10 @@ impl ApplicationImpl {
                     sample_count: Self::SAMPLE_COUNT,
                     ..CanvasWindowDescriptor::default()
                 },
-            );
+            )?;
             (window, instance)
         };
+        roe_app::set_crash_context("gpu_adapter", Some(format!("{:?}", instance.info())));
 
         let pipeline = roe_shape::RenderPipeline::new(
             &instance,
@@ -137,7 +138,7 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
         size: os::PhysicalSize<u32>,
     ) -> Result<(), ApplicationError> {
         if wid == self.window.id() {
-            self.window.update_buffer(&self.instance);
+            self.window.update_buffer(&self.instance)?;
             self.projection_transform = roe_math::ortographic_projection2(
                 0.,
                 1f32.max(size.width as f32),
@@ -155,7 +156,7 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
         size: &'a mut os::PhysicalSize<u32>,
     ) -> Result<(), ApplicationError> {
         if wid == self.window.id() {
-            self.window.update_buffer(&self.instance);
+            self.window.update_buffer(&self.instance)?;
             self.projection_transform = roe_math::ortographic_projection2(
                 0.,
                 1f32.max(size.width as f32),
@@ -260,6 +261,7 @@ impl ApplicationState<ApplicationError, ApplicationEvent> for ApplicationImpl {
 fn main() {
     const FIXED_FRAMERATE: u64 = 30;
     const VARIABLE_FRAMERATE_CAP: u64 = 60;
+    roe_app::install_crash_reporter(&CrashReporterDescriptor::default(), None);
     Application::new(FIXED_FRAMERATE, Some(VARIABLE_FRAMERATE_CAP))
         .run(|event_queue| Ok(Box::new(ApplicationImpl::new(event_queue)?)));
 }
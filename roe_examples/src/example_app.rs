@@ -15,8 +15,10 @@ pub enum ApplicationError {
     WindowCreationFailed(roe_os::OsError),
     InstanceCreationFailed(roe_graphics::InstanceCreationError),
     RenderFrameCreationFailed(roe_graphics::SurfaceError),
+    CanvasCreationFailed(roe_graphics::CanvasBufferError),
     FontCreationFailed(roe_text::FontError),
     AudioError(roe_audio::Error),
+    NetError(roe_net::Error),
     IoError(std::io::Error),
     ImageError(image::error::ImageError),
     CustomEventSendingError,
@@ -28,8 +30,10 @@ impl std::fmt::Display for ApplicationError {
             Self::WindowCreationFailed(e) => write!(f, "Window creation failed ({})", e),
             Self::InstanceCreationFailed(e) => write!(f, "Instance creation failed ({})", e),
             Self::RenderFrameCreationFailed(e) => write!(f, "Render frame creation failed ({})", e),
+            Self::CanvasCreationFailed(e) => write!(f, "Canvas creation failed ({})", e),
             Self::FontCreationFailed(e) => write!(f, "Font creation failed ({})", e),
             Self::AudioError(e) => write!(f, "Audio error ({})", e),
+            Self::NetError(e) => write!(f, "Network error ({})", e),
             Self::IoError(e) => write!(f, "I/O error ({})", e),
             Self::ImageError(e) => write!(f, "Image load error ({})", e),
             Self::CustomEventSendingError => write!(f, "Failed to send custom event"),
@@ -43,8 +47,10 @@ impl std::error::Error for ApplicationError {
             Self::WindowCreationFailed(e) => Some(e),
             Self::InstanceCreationFailed(e) => Some(e),
             Self::RenderFrameCreationFailed(e) => Some(e),
+            Self::CanvasCreationFailed(e) => Some(e),
             Self::FontCreationFailed(e) => Some(e),
             Self::AudioError(e) => Some(e),
+            Self::NetError(e) => Some(e),
             Self::IoError(e) => Some(e),
             Self::ImageError(e) => Some(e),
             Self::CustomEventSendingError => None,
@@ -70,6 +76,12 @@ impl From<roe_graphics::SurfaceError> for ApplicationError {
     }
 }
 
+impl From<roe_graphics::CanvasBufferError> for ApplicationError {
+    fn from(e: roe_graphics::CanvasBufferError) -> Self {
+        ApplicationError::CanvasCreationFailed(e)
+    }
+}
+
 impl From<roe_text::FontError> for ApplicationError {
     fn from(e: roe_text::FontError) -> Self {
         ApplicationError::FontCreationFailed(e)
@@ -88,6 +100,12 @@ impl From<roe_audio::DecoderError> for ApplicationError {
     }
 }
 
+impl From<roe_net::Error> for ApplicationError {
+    fn from(e: roe_net::Error) -> Self {
+        ApplicationError::NetError(e)
+    }
+}
+
 impl From<std::io::Error> for ApplicationError {
     fn from(e: std::io::Error) -> Self {
         ApplicationError::IoError(e)
@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use roe_graphics::FrameArena;
+
+// Number of batches built in a frame, each with a few quads and a label, roughly as in a ui
+// draw list.
+const BATCH_COUNTS: [usize; 3] = [100, 1000, 10000];
+const QUADS_PER_BATCH: usize = 9;
+
+type Vertex = [f32; 4];
+
+fn quad(i: usize) -> [Vertex; 4] {
+    let x = i as f32;
+    [
+        [x, 0., 0., 0.],
+        [x, 1., 0., 1.],
+        [x + 1., 1., 1., 1.],
+        [x + 1., 0., 1., 0.],
+    ]
+}
+
+fn heap_frame(batch_count: usize) -> usize {
+    let mut batches = Vec::new();
+    for i in 0..batch_count {
+        let mut vertices = Vec::new();
+        for j in 0..QUADS_PER_BATCH {
+            vertices.extend_from_slice(&quad(j));
+        }
+        let label = format!("batch {}", i);
+        batches.push((vertices, label));
+    }
+    batches.len()
+}
+
+fn arena_frame(batch_count: usize, arena: &mut FrameArena) -> usize {
+    let len = {
+        let mut batches = arena.vec();
+        for i in 0..batch_count {
+            let mut vertices = arena.vec();
+            for j in 0..QUADS_PER_BATCH {
+                vertices.extend_from_slice(&quad(j));
+            }
+            let mut label = arena.string();
+            std::fmt::Write::write_fmt(&mut label, format_args!("batch {}", i)).unwrap();
+            batches.push((vertices, label));
+        }
+        batches.len()
+    };
+    arena.finish_frame();
+    len
+}
+
+fn frame_scratch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame_scratch");
+    for count in BATCH_COUNTS {
+        group.bench_with_input(BenchmarkId::new("heap", count), &count, |b, count| {
+            b.iter(|| heap_frame(*count))
+        });
+        let mut arena = FrameArena::new();
+        group.bench_with_input(BenchmarkId::new("arena", count), &count, |b, count| {
+            b.iter(|| arena_frame(*count, &mut arena))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, frame_scratch);
+criterion_main!(benches);
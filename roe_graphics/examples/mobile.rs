@@ -0,0 +1,214 @@
+// Minimal application for Android and iOS: clears the screen with a color picked by touching it.
+// Build it with `cargo apk run -p roe_graphics --example mobile` on Android, or link the static
+// library built by `cargo lipo -p roe_graphics --example mobile` in an Xcode project on iOS, calling
+// `roe_mobile_main`.
+
+use roe_app::{Application, ApplicationState, LifecyclePolicy};
+
+use roe_os as os;
+
+use roe_graphics::{
+    Canvas, CanvasBufferError, CanvasWindow, CanvasWindowDescriptor, ColorF64, ColorOperations,
+    CommandSequence, Instance, InstanceCreationError, InstanceDescriptor, LoadOp,
+    RenderPassOperations, RenderPassRequirements, SurfaceError,
+};
+
+#[derive(Debug)]
+enum ApplicationError {
+    Os(os::OsError),
+    Instance(InstanceCreationError),
+    Surface(SurfaceError),
+    Canvas(CanvasBufferError),
+}
+
+impl std::fmt::Display for ApplicationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Os(e) => write!(f, "Window creation failed ({})", e),
+            Self::Instance(e) => write!(f, "Instance creation failed ({})", e),
+            Self::Surface(e) => write!(f, "Render frame creation failed ({})", e),
+            Self::Canvas(e) => write!(f, "Canvas creation failed ({})", e),
+        }
+    }
+}
+
+impl std::error::Error for ApplicationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Os(e) => Some(e),
+            Self::Instance(e) => Some(e),
+            Self::Surface(e) => Some(e),
+            Self::Canvas(e) => Some(e),
+        }
+    }
+}
+
+impl From<os::OsError> for ApplicationError {
+    fn from(e: os::OsError) -> Self {
+        ApplicationError::Os(e)
+    }
+}
+
+impl From<InstanceCreationError> for ApplicationError {
+    fn from(e: InstanceCreationError) -> Self {
+        ApplicationError::Instance(e)
+    }
+}
+
+impl From<SurfaceError> for ApplicationError {
+    fn from(e: SurfaceError) -> Self {
+        ApplicationError::Surface(e)
+    }
+}
+
+impl From<CanvasBufferError> for ApplicationError {
+    fn from(e: CanvasBufferError) -> Self {
+        ApplicationError::Canvas(e)
+    }
+}
+
+#[derive(Debug)]
+struct Graphics {
+    window: CanvasWindow,
+    instance: Instance,
+}
+
+#[derive(Debug)]
+struct ApplicationImpl {
+    // On Android the native window only exists after the application is resumed, so the surface
+    // is created when the first resumed event arrives.
+    pending_window: Option<os::Window>,
+    graphics: Option<Graphics>,
+    color: ColorF64,
+}
+
+impl ApplicationImpl {
+    fn new(event_loop: &os::EventLoop<()>) -> Result<Self, ApplicationError> {
+        let window = os::WindowBuilder::new()
+            .with_title("Mobile")
+            .build(event_loop)?;
+        let mut app = Self {
+            pending_window: Some(window),
+            graphics: None,
+            color: ColorF64::BLACK,
+        };
+        if !cfg!(target_os = "android") {
+            app.create_graphics()?;
+        }
+        Ok(app)
+    }
+
+    fn create_graphics(&mut self) -> Result<(), ApplicationError> {
+        if let Some(window) = self.pending_window.take() {
+            let graphics = unsafe {
+                let (instance, surface) =
+                    Instance::new_with_compatible_window(&InstanceDescriptor::default(), &window)?;
+                let window = CanvasWindow::from_window_and_surface(
+                    &instance,
+                    window,
+                    surface,
+                    &CanvasWindowDescriptor::default(),
+                )?;
+                Graphics { window, instance }
+            };
+            self.graphics = Some(graphics);
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationState<ApplicationError, ()> for ApplicationImpl {
+    fn on_resumed(&mut self) -> Result<(), ApplicationError> {
+        // The previous surface was destroyed together with the native window.
+        match &mut self.graphics {
+            Some(graphics) => unsafe { graphics.window.recreate_surface(&graphics.instance)? },
+            None => self.create_graphics()?,
+        }
+        Ok(())
+    }
+
+    fn on_resized(
+        &mut self,
+        wid: os::WindowId,
+        _size: os::PhysicalSize<u32>,
+    ) -> Result<(), ApplicationError> {
+        // Orientation changes are reported as resize events.
+        if let Some(graphics) = &mut self.graphics {
+            if wid == graphics.window.id() {
+                graphics.window.update_buffer(&graphics.instance)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn on_touch(
+        &mut self,
+        wid: os::WindowId,
+        _device_id: os::DeviceId,
+        phase: os::TouchPhase,
+        location: os::PhysicalPosition<f64>,
+        _force: Option<os::TouchForce>,
+        _id: u64,
+    ) -> Result<(), ApplicationError> {
+        if let Some(graphics) = &self.graphics {
+            if wid == graphics.window.id() && phase != os::TouchPhase::Cancelled {
+                let size = graphics.window.inner_size();
+                self.color = ColorF64 {
+                    r: location.x / f64::max(1., size.width as f64),
+                    g: location.y / f64::max(1., size.height as f64),
+                    b: 0.5,
+                    a: 1.,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn on_variable_update(&mut self, _dt: std::time::Duration) -> Result<(), ApplicationError> {
+        if let Some(graphics) = &mut self.graphics {
+            let requirements = RenderPassRequirements {
+                sample_count: graphics.window.sample_count(),
+                color_buffer_formats: vec![graphics.window.color_buffer_format()],
+                depth_stencil_buffer_format: None,
+            };
+            if let Some(frame) = graphics.window.current_frame()? {
+                let mut cmd_sequence = CommandSequence::new(&graphics.instance);
+                cmd_sequence.begin_render_pass(
+                    &frame,
+                    &requirements,
+                    &RenderPassOperations {
+                        color_operations: vec![ColorOperations {
+                            load: LoadOp::Clear(self.color),
+                            store: true,
+                        }],
+                        ..RenderPassOperations::default()
+                    },
+                );
+                cmd_sequence.submit(&graphics.instance);
+                frame.present();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(
+    target_os = "android",
+    ndk_glue::main(backtrace = "on", ndk_glue = "ndk_glue")
+)]
+pub fn main() {
+    const FIXED_FRAMERATE: u64 = 30;
+    const VARIABLE_FRAMERATE_CAP: u64 = 60;
+    Application::new(FIXED_FRAMERATE, Some(VARIABLE_FRAMERATE_CAP))
+        .with_lifecycle_policy(LifecyclePolicy {
+            pause_when_suspended: true,
+            ..LifecyclePolicy::default()
+        })
+        .run(|event_queue| Ok(Box::new(ApplicationImpl::new(event_queue)?)));
+}
+
+#[cfg(target_os = "ios")]
+#[no_mangle]
+pub extern "C" fn roe_mobile_main() {
+    main();
+}
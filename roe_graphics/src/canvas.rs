@@ -5,13 +5,14 @@ use super::{
 };
 
 fn canvas_texture_descriptor<'a>(
+    label: Option<&'a str>,
     size: CanvasSize,
     sample_count: SampleCount,
     format: TextureFormat,
     usage: TextureUsage,
 ) -> TextureDescriptor<'a> {
     TextureDescriptor {
-        label: None,
+        label,
         size: Extent3d {
             width: size.width(),
             height: size.height(),
@@ -25,9 +26,12 @@ fn canvas_texture_descriptor<'a>(
     }
 }
 
-fn canvas_texture_view_descriptor<'a>(format: TextureFormat) -> TextureViewDescriptor<'a> {
+fn canvas_texture_view_descriptor<'a>(
+    label: Option<&'a str>,
+    format: TextureFormat,
+) -> TextureViewDescriptor<'a> {
     TextureViewDescriptor {
-        label: None,
+        label,
         format: Some(format),
         dimension: Some(TextureViewDimension::D2),
         aspect: TextureAspect::All,
@@ -44,6 +48,9 @@ pub enum CanvasColorBufferFormat {
     Rgba8UnormSrgb,
     Bgra8Unorm,
     Bgra8UnormSrgb,
+    // Linear hdr values, e.g. the scene color buffer read by post processing passes. Not
+    // supported by window surfaces.
+    Rgba16Float,
 }
 
 impl Default for CanvasColorBufferFormat {
@@ -52,7 +59,13 @@ impl Default for CanvasColorBufferFormat {
         Self::Bgra8Unorm
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
+    // Android surfaces generally don't support BGRA formats.
+    #[cfg(target_os = "android")]
+    fn default() -> Self {
+        Self::Rgba8UnormSrgb
+    }
+
+    #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
     fn default() -> Self {
         Self::Bgra8UnormSrgb
     }
@@ -65,6 +78,7 @@ impl From<CanvasColorBufferFormat> for TextureFormat {
             CanvasColorBufferFormat::Rgba8UnormSrgb => TextureFormat::Rgba8UnormSrgb,
             CanvasColorBufferFormat::Bgra8Unorm => TextureFormat::Bgra8Unorm,
             CanvasColorBufferFormat::Bgra8UnormSrgb => TextureFormat::Bgra8UnormSrgb,
+            CanvasColorBufferFormat::Rgba16Float => TextureFormat::Rgba16Float,
         }
     }
 }
@@ -129,6 +143,7 @@ impl<'a> CanvasSurfaceRef<'a> {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CanvasSurfaceDescriptor {
+    pub label: Option<String>,
     pub size: CanvasSize,
     pub sample_count: SampleCount,
     pub format: CanvasColorBufferFormat,
@@ -170,13 +185,19 @@ impl CanvasSurface {
             let multisampling_buffer_texture = Texture::new(
                 instance,
                 &canvas_texture_descriptor(
+                    desc.label.as_deref(),
                     desc.size,
                     desc.sample_count,
                     format,
                     TextureUsage::empty(),
                 ),
             );
-            Some(multisampling_buffer_texture.create_view(&canvas_texture_view_descriptor(format)))
+            Some(
+                multisampling_buffer_texture.create_view(&canvas_texture_view_descriptor(
+                    desc.label.as_deref(),
+                    format,
+                )),
+            )
         } else {
             None
         };
@@ -201,9 +222,10 @@ impl CanvasSurface {
         let surface_texture = self.surface.get_current_texture()?;
         let surface_view = surface_texture
             .texture
-            .create_view(&canvas_texture_view_descriptor(TextureFormat::from(
-                self.format,
-            )));
+            .create_view(&canvas_texture_view_descriptor(
+                None,
+                TextureFormat::from(self.format),
+            ));
         let multisampled_buffer = match self.multisampled_buffer {
             Some(ref v) => Some(v),
             None => None,
@@ -266,6 +288,7 @@ impl From<CanvasColorBufferUsage> for TextureUsage {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CanvasColorBufferDescriptor {
+    pub label: Option<String>,
     pub size: CanvasSize,
     pub sample_count: SampleCount,
     pub format: CanvasColorBufferFormat,
@@ -285,9 +308,10 @@ pub struct CanvasColorBuffer {
 impl CanvasColorBuffer {
     pub fn new(instance: &Instance, desc: &CanvasColorBufferDescriptor) -> Self {
         let format = TextureFormat::from(desc.format);
+        let label = desc.label.as_deref();
         let mut tex_desc =
-            canvas_texture_descriptor(desc.size, 1, format, TextureUsage::from(desc.usage));
-        let tex_view_desc = canvas_texture_view_descriptor(format);
+            canvas_texture_descriptor(label, desc.size, 1, format, TextureUsage::from(desc.usage));
+        let tex_view_desc = canvas_texture_view_descriptor(label, format);
 
         let main_buffer_texture = Texture::new(instance, &tex_desc);
         let main_buffer_view = main_buffer_texture.create_view(&tex_view_desc);
@@ -366,6 +390,7 @@ impl<'a> CanvasDepthStencilBufferRef<'a> {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CanvasDepthStencilBufferDescriptor {
+    pub label: Option<String>,
     pub size: CanvasSize,
     pub sample_count: SampleCount,
     pub format: CanvasDepthStencilBufferFormat,
@@ -383,11 +408,21 @@ pub struct CanvasDepthStencilBuffer {
 impl CanvasDepthStencilBuffer {
     pub fn new(instance: &Instance, desc: &CanvasDepthStencilBufferDescriptor) -> Self {
         let format = TextureFormat::from(desc.format);
+        // Sampled e.g. by shadow map lookups.
         let buffer_texture = Texture::new(
             instance,
-            &canvas_texture_descriptor(desc.size, desc.sample_count, format, TextureUsage::empty()),
+            &canvas_texture_descriptor(
+                desc.label.as_deref(),
+                desc.size,
+                desc.sample_count,
+                format,
+                TextureUsage::TEXTURE_BINDING,
+            ),
         );
-        let buffer_view = buffer_texture.create_view(&canvas_texture_view_descriptor(format));
+        let buffer_view = buffer_texture.create_view(&canvas_texture_view_descriptor(
+            desc.label.as_deref(),
+            format,
+        ));
         Self {
             size: desc.size,
             sample_count: desc.sample_count,
@@ -462,6 +497,8 @@ pub struct CanvasBufferSurfaceDescriptor {
 pub struct CanvasBufferColorBufferDescriptor {
     pub format: CanvasColorBufferFormat,
     pub usage: CanvasColorBufferUsage,
+    // If not specified, the sample count of the canvas buffer is used.
+    pub sample_count: Option<SampleCount>,
 }
 
 impl Default for CanvasBufferColorBufferDescriptor {
@@ -469,19 +506,153 @@ impl Default for CanvasBufferColorBufferDescriptor {
         Self {
             format: CanvasColorBufferFormat::default(),
             usage: CanvasColorBufferUsage::empty(),
+            sample_count: None,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct CanvasBufferDescriptor {
+    // The buffers are labeled after the canvas buffer, e.g. "scene (color buffer 0)".
+    pub label: Option<String>,
     pub size: CanvasSize,
     pub sample_count: SampleCount,
     pub surface_descriptor: Option<CanvasBufferSurfaceDescriptor>,
     pub color_buffer_descriptors: Vec<CanvasBufferColorBufferDescriptor>,
     pub depth_stencil_buffer_format: Option<CanvasDepthStencilBufferFormat>,
+    // If not specified, the sample count of the canvas buffer is used.
+    pub depth_stencil_buffer_sample_count: Option<SampleCount>,
+}
+
+impl CanvasBufferDescriptor {
+    pub fn attachment_label(&self, attachment: CanvasAttachment) -> Option<String> {
+        self.label
+            .as_ref()
+            .map(|label| format!("{} ({})", label, attachment))
+    }
+
+    pub fn attachment_sample_counts(&self) -> Vec<(CanvasAttachment, SampleCount)> {
+        let mut sample_counts = Vec::with_capacity(self.color_buffer_descriptors.len() + 2);
+        if self.surface_descriptor.is_some() {
+            sample_counts.push((CanvasAttachment::Surface, self.sample_count));
+        }
+        for (i, cbd) in self.color_buffer_descriptors.iter().enumerate() {
+            sample_counts.push((
+                CanvasAttachment::ColorBuffer(i),
+                cbd.sample_count.unwrap_or(self.sample_count),
+            ));
+        }
+        if self.depth_stencil_buffer_format.is_some() {
+            sample_counts.push((
+                CanvasAttachment::DepthStencilBuffer,
+                self.depth_stencil_buffer_sample_count
+                    .unwrap_or(self.sample_count),
+            ));
+        }
+        sample_counts
+    }
+
+    pub fn validate(&self) -> Result<(), CanvasBufferError> {
+        let sample_counts = self.attachment_sample_counts();
+
+        let invalid_sample_counts: Vec<_> = sample_counts
+            .iter()
+            .filter(|(_, sample_count)| !is_valid_sample_count(*sample_count))
+            .copied()
+            .collect();
+        if !invalid_sample_counts.is_empty() {
+            return Err(CanvasBufferError::InvalidSampleCounts(
+                invalid_sample_counts,
+            ));
+        }
+
+        // A depth stencil buffer can only be used in a render pass together with color
+        // attachments having the same sample count. If no such attachment exists, the buffer is
+        // unusable.
+        if let Some((_, ds_sample_count)) = sample_counts
+            .iter()
+            .find(|(attachment, _)| *attachment == CanvasAttachment::DepthStencilBuffer)
+        {
+            let has_color_attachments = sample_counts.len() > 1;
+            let has_matching_color_attachment = sample_counts.iter().any(|(attachment, sc)| {
+                *attachment != CanvasAttachment::DepthStencilBuffer && sc == ds_sample_count
+            });
+            if has_color_attachments && !has_matching_color_attachment {
+                // Only the attachments overriding the canvas buffer sample count are reported.
+                let incompatible_sample_counts = sample_counts
+                    .iter()
+                    .filter(|(_, sample_count)| *sample_count != self.sample_count)
+                    .copied()
+                    .collect();
+                return Err(CanvasBufferError::IncompatibleSampleCounts(
+                    incompatible_sample_counts,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_valid_sample_count(sample_count: SampleCount) -> bool {
+    (1..=32).contains(&sample_count) && sample_count.is_power_of_two()
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CanvasAttachment {
+    Surface,
+    ColorBuffer(usize),
+    DepthStencilBuffer,
+}
+
+impl std::fmt::Display for CanvasAttachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanvasAttachment::Surface => write!(f, "surface"),
+            CanvasAttachment::ColorBuffer(i) => write!(f, "color buffer {}", i),
+            CanvasAttachment::DepthStencilBuffer => write!(f, "depth stencil buffer"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CanvasBufferError {
+    InvalidSampleCounts(Vec<(CanvasAttachment, SampleCount)>),
+    IncompatibleSampleCounts(Vec<(CanvasAttachment, SampleCount)>),
+}
+
+fn format_attachment_sample_counts(
+    f: &mut std::fmt::Formatter<'_>,
+    sample_counts: &[(CanvasAttachment, SampleCount)],
+) -> std::fmt::Result {
+    for (i, (attachment, sample_count)) in sample_counts.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: {}", attachment, sample_count)?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for CanvasBufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanvasBufferError::InvalidSampleCounts(sample_counts) => {
+                write!(f, "Invalid sample counts (")?;
+                format_attachment_sample_counts(f, sample_counts)?;
+                write!(f, ")")
+            }
+            CanvasBufferError::IncompatibleSampleCounts(sample_counts) => {
+                write!(f, "Incompatible sample counts (")?;
+                format_attachment_sample_counts(f, sample_counts)?;
+                write!(f, ")")
+            }
+        }
+    }
 }
 
+impl std::error::Error for CanvasBufferError {}
+
 #[derive(Debug)]
 pub struct CanvasBuffer {
     size: CanvasSize,
@@ -497,7 +668,7 @@ impl CanvasBuffer {
         instance: &Instance,
         surface: Option<Surface>,
         desc: &CanvasBufferDescriptor,
-    ) -> Self {
+    ) -> Result<Self, CanvasBufferError> {
         let canvas_surface = match surface {
             Some(surface) => Some(CanvasSurface::new(surface)),
             None => None,
@@ -509,11 +680,17 @@ impl CanvasBuffer {
             canvas_color_buffers: Vec::new(),
             canvas_depth_stencil_buffer: None,
         };
-        canvas_buffer.configure(instance, desc);
-        canvas_buffer
+        canvas_buffer.configure(instance, desc)?;
+        Ok(canvas_buffer)
     }
 
-    pub fn configure(&mut self, instance: &Instance, desc: &CanvasBufferDescriptor) {
+    pub fn configure(
+        &mut self,
+        instance: &Instance,
+        desc: &CanvasBufferDescriptor,
+    ) -> Result<(), CanvasBufferError> {
+        desc.validate()?;
+
         self.size = desc.size;
         self.sample_count = desc.sample_count;
 
@@ -522,13 +699,14 @@ impl CanvasBuffer {
         // flagged as "invalid", no frames are returned, and the surface will be recreated when the
         // size changes again to a valid value.
         if desc.size.width() == 0 || desc.size.height() == 0 {
-            return;
+            return Ok(());
         }
 
         self.configure_canvas_surface(instance, desc);
         self.canvas_color_buffers = Self::create_color_buffers(instance, desc);
         self.canvas_depth_stencil_buffer = Self::create_depth_stencil_buffer(instance, desc);
         self.assert_has_buffer();
+        Ok(())
     }
 
     fn configure_canvas_surface(&mut self, instance: &Instance, desc: &CanvasBufferDescriptor) {
@@ -543,6 +721,7 @@ impl CanvasBuffer {
                 canvas_surface.configure(
                     instance,
                     &CanvasSurfaceDescriptor {
+                        label: desc.attachment_label(CanvasAttachment::Surface),
                         size: desc.size,
                         sample_count: desc.sample_count,
                         format,
@@ -562,12 +741,13 @@ impl CanvasBuffer {
         desc: &CanvasBufferDescriptor,
     ) -> Vec<CanvasColorBuffer> {
         let mut canvas_color_buffers = Vec::with_capacity(desc.color_buffer_descriptors.len());
-        for cbd in desc.color_buffer_descriptors.iter() {
+        for (i, cbd) in desc.color_buffer_descriptors.iter().enumerate() {
             canvas_color_buffers.push(CanvasColorBuffer::new(
                 instance,
                 &CanvasColorBufferDescriptor {
+                    label: desc.attachment_label(CanvasAttachment::ColorBuffer(i)),
                     size: desc.size,
-                    sample_count: desc.sample_count,
+                    sample_count: cbd.sample_count.unwrap_or(desc.sample_count),
                     format: cbd.format,
                     usage: cbd.usage,
                 },
@@ -584,8 +764,11 @@ impl CanvasBuffer {
             Some(format) => Some(CanvasDepthStencilBuffer::new(
                 instance,
                 &CanvasDepthStencilBufferDescriptor {
+                    label: desc.attachment_label(CanvasAttachment::DepthStencilBuffer),
                     size: desc.size,
-                    sample_count: desc.sample_count,
+                    sample_count: desc
+                        .depth_stencil_buffer_sample_count
+                        .unwrap_or(desc.sample_count),
                     format: *format,
                 },
             )),
@@ -602,6 +785,17 @@ impl CanvasBuffer {
         );
     }
 
+    // Replaces the surface of the canvas buffer. The old surface is released before the new one
+    // is set. The canvas buffer must be configured again before using the new surface.
+    pub fn replace_surface(&mut self, surface: Surface) {
+        assert!(
+            self.canvas_surface.is_some(),
+            "Canvas buffer created without a surface"
+        );
+        self.canvas_surface = None;
+        self.canvas_surface = Some(CanvasSurface::new(surface));
+    }
+
     pub fn is_valid(&self) -> bool {
         self.size.width() != 0 && self.size.height() != 0
     }
@@ -680,6 +874,29 @@ mod tests {
     use os::EventLoopAnyThread;
     use roe_os as os;
 
+    #[test]
+    fn attachment_labels() {
+        let mut desc = CanvasBufferDescriptor {
+            label: Some(String::from("scene")),
+            size: CanvasSize::new(8, 8),
+            sample_count: 1,
+            surface_descriptor: None,
+            color_buffer_descriptors: Vec::new(),
+            depth_stencil_buffer_format: None,
+            depth_stencil_buffer_sample_count: None,
+        };
+        expect_that!(
+            &desc.attachment_label(CanvasAttachment::ColorBuffer(1)),
+            eq(Some(String::from("scene (color buffer 1)")))
+        );
+        expect_that!(
+            &desc.attachment_label(CanvasAttachment::DepthStencilBuffer),
+            eq(Some(String::from("scene (depth stencil buffer)")))
+        );
+        desc.label = None;
+        expect_that!(&desc.attachment_label(CanvasAttachment::Surface), eq(None));
+    }
+
     #[test]
     #[serial_test::serial]
     fn canvas_surface() {
@@ -732,6 +949,7 @@ mod tests {
         surface.configure(
             &instance,
             &CanvasSurfaceDescriptor {
+                label: None,
                 sample_count: 2,
                 format: CanvasColorBufferFormat::Bgra8Unorm,
                 size: CanvasSize::new(12, 20),
@@ -755,6 +973,7 @@ mod tests {
         let buffer = CanvasColorBuffer::new(
             &instance,
             &CanvasColorBufferDescriptor {
+                label: None,
                 sample_count: 2,
                 format: CanvasColorBufferFormat::Bgra8Unorm,
                 size: CanvasSize::new(12, 20),
@@ -779,6 +998,7 @@ mod tests {
         let buffer = CanvasDepthStencilBuffer::new(
             &instance,
             &CanvasDepthStencilBufferDescriptor {
+                label: None,
                 sample_count: 2,
                 format: CanvasDepthStencilBufferFormat::Depth32Float,
                 size: CanvasSize::new(12, 20),
@@ -816,6 +1036,7 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -825,15 +1046,19 @@ mod tests {
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::default(),
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::Bgra8Unorm,
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                 ],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(2));
@@ -897,6 +1122,7 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 1,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -904,8 +1130,10 @@ mod tests {
                 }),
                 color_buffer_descriptors: vec![],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(1));
@@ -945,6 +1173,7 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -952,8 +1181,10 @@ mod tests {
                 }),
                 color_buffer_descriptors: vec![],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(2));
@@ -985,16 +1216,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 1,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::default(),
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(1));
@@ -1026,16 +1261,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::default(),
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(2));
@@ -1067,13 +1306,16 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 1,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(1));
@@ -1108,13 +1350,16 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
         expect_that!(&buffer.sample_count(), eq(2));
@@ -1150,13 +1395,16 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: None,
                 color_buffer_descriptors: Vec::new(),
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
     }
 
     #[test]
@@ -1175,13 +1423,16 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
     }
 
     #[test]
@@ -1193,6 +1444,7 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 2,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -1200,8 +1452,10 @@ mod tests {
                 }),
                 color_buffer_descriptors: vec![],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
     }
 
     #[test]
@@ -1220,6 +1474,7 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(0, 0),
                 sample_count: 1,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -1229,15 +1484,19 @@ mod tests {
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::default(),
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::Bgra8Unorm,
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                 ],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(0, 0)));
         expect_that!(&buffer.sample_count(), eq(1));
@@ -1265,6 +1524,7 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(10, 20),
                 sample_count: 1,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -1274,28 +1534,36 @@ mod tests {
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::default(),
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::Bgra8Unorm,
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                 ],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
-
-        buffer.configure(
-            &instance,
-            &CanvasBufferDescriptor {
-                size: CanvasSize::new(30, 10),
-                sample_count: 2,
-                surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
-                    format: CanvasColorBufferFormat::default(),
-                }),
-                color_buffer_descriptors: vec![],
-                depth_stencil_buffer_format: None,
-            },
-        );
+        )
+        .unwrap();
+
+        buffer
+            .configure(
+                &instance,
+                &CanvasBufferDescriptor {
+                    label: None,
+                    size: CanvasSize::new(30, 10),
+                    sample_count: 2,
+                    surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
+                        format: CanvasColorBufferFormat::default(),
+                    }),
+                    color_buffer_descriptors: vec![],
+                    depth_stencil_buffer_format: None,
+                    depth_stencil_buffer_sample_count: None,
+                },
+            )
+            .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(30, 10)));
         expect_that!(&buffer.sample_count(), eq(2));
@@ -1328,6 +1596,7 @@ mod tests {
             &instance,
             Some(surface),
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(10, 20),
                 sample_count: 1,
                 surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
@@ -1337,28 +1606,36 @@ mod tests {
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::default(),
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                     CanvasBufferColorBufferDescriptor {
                         format: CanvasColorBufferFormat::Bgra8Unorm,
                         usage: CanvasColorBufferUsage::empty(),
+                        sample_count: None,
                     },
                 ],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
-
-        buffer.configure(
-            &instance,
-            &CanvasBufferDescriptor {
-                size: CanvasSize::new(0, 0),
-                sample_count: 2,
-                surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
-                    format: CanvasColorBufferFormat::default(),
-                }),
-                color_buffer_descriptors: vec![],
-                depth_stencil_buffer_format: None,
-            },
-        );
+        )
+        .unwrap();
+
+        buffer
+            .configure(
+                &instance,
+                &CanvasBufferDescriptor {
+                    label: None,
+                    size: CanvasSize::new(0, 0),
+                    sample_count: 2,
+                    surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
+                        format: CanvasColorBufferFormat::default(),
+                    }),
+                    color_buffer_descriptors: vec![],
+                    depth_stencil_buffer_format: None,
+                    depth_stencil_buffer_sample_count: None,
+                },
+            )
+            .unwrap();
 
         expect_that!(buffer.size(), eq(CanvasSize::new(0, 0)));
         expect_that!(&buffer.sample_count(), eq(2));
@@ -1369,4 +1646,214 @@ mod tests {
 
         expect_that!(buffer.current_frame().unwrap().is_none());
     }
+
+    #[test]
+    fn canvas_buffer_descriptor_attachment_sample_counts() {
+        let desc = CanvasBufferDescriptor {
+            label: None,
+            size: CanvasSize::new(12, 20),
+            sample_count: 4,
+            surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
+                format: CanvasColorBufferFormat::default(),
+            }),
+            color_buffer_descriptors: vec![
+                CanvasBufferColorBufferDescriptor::default(),
+                CanvasBufferColorBufferDescriptor {
+                    sample_count: Some(1),
+                    ..CanvasBufferColorBufferDescriptor::default()
+                },
+            ],
+            depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+            depth_stencil_buffer_sample_count: Some(1),
+        };
+        expect_that!(
+            &desc.attachment_sample_counts(),
+            eq(vec![
+                (CanvasAttachment::Surface, 4),
+                (CanvasAttachment::ColorBuffer(0), 4),
+                (CanvasAttachment::ColorBuffer(1), 1),
+                (CanvasAttachment::DepthStencilBuffer, 1),
+            ])
+        );
+        expect_that!(&desc.validate(), eq(Ok(())));
+    }
+
+    #[test]
+    fn canvas_buffer_descriptor_invalid_sample_counts() {
+        let desc = CanvasBufferDescriptor {
+            label: None,
+            size: CanvasSize::new(12, 20),
+            sample_count: 2,
+            surface_descriptor: None,
+            color_buffer_descriptors: vec![
+                CanvasBufferColorBufferDescriptor {
+                    sample_count: Some(3),
+                    ..CanvasBufferColorBufferDescriptor::default()
+                },
+                CanvasBufferColorBufferDescriptor::default(),
+            ],
+            depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+            depth_stencil_buffer_sample_count: Some(0),
+        };
+        let error = desc.validate().unwrap_err();
+        expect_that!(
+            &error,
+            eq(CanvasBufferError::InvalidSampleCounts(vec![
+                (CanvasAttachment::ColorBuffer(0), 3),
+                (CanvasAttachment::DepthStencilBuffer, 0)
+            ]))
+        );
+        expect_that!(
+            &format!("{}", error),
+            eq(String::from(
+                "Invalid sample counts (color buffer 0: 3, depth stencil buffer: 0)"
+            ))
+        );
+    }
+
+    #[test]
+    fn canvas_buffer_descriptor_incompatible_sample_counts() {
+        let desc = CanvasBufferDescriptor {
+            label: None,
+            size: CanvasSize::new(12, 20),
+            sample_count: 4,
+            surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
+                format: CanvasColorBufferFormat::default(),
+            }),
+            color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
+                sample_count: Some(2),
+                ..CanvasBufferColorBufferDescriptor::default()
+            }],
+            depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+            depth_stencil_buffer_sample_count: Some(1),
+        };
+        let error = desc.validate().unwrap_err();
+        expect_that!(
+            &error,
+            eq(CanvasBufferError::IncompatibleSampleCounts(vec![
+                (CanvasAttachment::ColorBuffer(0), 2),
+                (CanvasAttachment::DepthStencilBuffer, 1)
+            ]))
+        );
+        expect_that!(
+            &format!("{}", error),
+            eq(String::from(
+                "Incompatible sample counts (color buffer 0: 2, depth stencil buffer: 1)"
+            ))
+        );
+
+        let desc = CanvasBufferDescriptor {
+            surface_descriptor: None,
+            color_buffer_descriptors: vec![
+                CanvasBufferColorBufferDescriptor {
+                    sample_count: Some(2),
+                    ..CanvasBufferColorBufferDescriptor::default()
+                },
+                CanvasBufferColorBufferDescriptor {
+                    sample_count: Some(1),
+                    ..CanvasBufferColorBufferDescriptor::default()
+                },
+            ],
+            depth_stencil_buffer_sample_count: None,
+            ..desc
+        };
+        expect_that!(
+            &desc.validate(),
+            eq(Err(CanvasBufferError::IncompatibleSampleCounts(vec![
+                (CanvasAttachment::ColorBuffer(0), 2),
+                (CanvasAttachment::ColorBuffer(1), 1)
+            ])))
+        );
+    }
+
+    #[test]
+    fn canvas_buffer_descriptor_only_depth_stencil_sample_count() {
+        let desc = CanvasBufferDescriptor {
+            label: None,
+            size: CanvasSize::new(12, 20),
+            sample_count: 1,
+            surface_descriptor: None,
+            color_buffer_descriptors: vec![],
+            depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+            depth_stencil_buffer_sample_count: Some(8),
+        };
+        expect_that!(&desc.validate(), eq(Ok(())));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn canvas_buffer_independent_sample_counts() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let buffer = CanvasBuffer::new(
+            &instance,
+            None,
+            &CanvasBufferDescriptor {
+                label: None,
+                size: CanvasSize::new(12, 20),
+                sample_count: 1,
+                surface_descriptor: None,
+                color_buffer_descriptors: vec![
+                    CanvasBufferColorBufferDescriptor::default(),
+                    CanvasBufferColorBufferDescriptor {
+                        sample_count: Some(4),
+                        ..CanvasBufferColorBufferDescriptor::default()
+                    },
+                ],
+                depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: Some(4),
+            },
+        )
+        .unwrap();
+
+        expect_that!(&buffer.sample_count(), eq(1));
+        expect_that!(&buffer.color_buffers()[0].sample_count(), eq(1));
+        expect_that!(&buffer.color_buffers()[1].sample_count(), eq(4));
+        expect_that!(
+            &buffer.depth_stencil_buffer().unwrap().sample_count(),
+            eq(4)
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn canvas_buffer_configure_invalid_sample_count() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let mut buffer = CanvasBuffer::new(
+            &instance,
+            None,
+            &CanvasBufferDescriptor {
+                label: None,
+                size: CanvasSize::new(12, 20),
+                sample_count: 1,
+                surface_descriptor: None,
+                color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor::default()],
+                depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
+            },
+        )
+        .unwrap();
+
+        let result = buffer.configure(
+            &instance,
+            &CanvasBufferDescriptor {
+                label: None,
+                size: CanvasSize::new(30, 10),
+                sample_count: 6,
+                surface_descriptor: None,
+                color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor::default()],
+                depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
+            },
+        );
+
+        expect_that!(
+            &result,
+            eq(Err(CanvasBufferError::InvalidSampleCounts(vec![(
+                CanvasAttachment::ColorBuffer(0),
+                6
+            )])))
+        );
+        expect_that!(buffer.size(), eq(CanvasSize::new(12, 20)));
+        expect_that!(&buffer.sample_count(), eq(1));
+    }
 }
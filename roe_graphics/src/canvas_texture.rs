@@ -2,14 +2,15 @@ use std::default::Default;
 
 use super::{
     Canvas, CanvasBuffer, CanvasBufferColorBufferDescriptor, CanvasBufferDescriptor,
-    CanvasColorBufferFormat, CanvasDepthStencilBufferFormat, CanvasFrame, CanvasSize, Instance,
-    SampleCount, Size, SurfaceError, Texture, TextureView,
+    CanvasBufferError, CanvasColorBufferFormat, CanvasDepthStencilBufferFormat, CanvasFrame,
+    CanvasSize, Instance, SampleCount, Size, SurfaceError, Texture, TextureView,
 };
 
 pub type CanvasTextureColorBufferDescriptor = CanvasBufferColorBufferDescriptor;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CanvasTextureDescriptor {
+    pub label: Option<String>,
     pub size: Size<u32>,
     pub sample_count: SampleCount,
     pub color_buffer_descriptor: Option<CanvasTextureColorBufferDescriptor>,
@@ -19,6 +20,7 @@ pub struct CanvasTextureDescriptor {
 impl Default for CanvasTextureDescriptor {
     fn default() -> Self {
         Self {
+            label: None,
             size: Size::new(1, 1),
             sample_count: 1,
             color_buffer_descriptor: Some(CanvasTextureColorBufferDescriptor::default()),
@@ -33,11 +35,15 @@ pub struct CanvasTexture {
 }
 
 impl CanvasTexture {
-    pub fn new(instance: &Instance, desc: &CanvasTextureDescriptor) -> Self {
+    pub fn new(
+        instance: &Instance,
+        desc: &CanvasTextureDescriptor,
+    ) -> Result<Self, CanvasBufferError> {
         let canvas_buffer = CanvasBuffer::new(
             instance,
             None,
             &CanvasBufferDescriptor {
+                label: desc.label.clone(),
                 size: desc.size,
                 sample_count: desc.sample_count,
                 surface_descriptor: None,
@@ -46,9 +52,10 @@ impl CanvasTexture {
                     None => Vec::new(),
                 },
                 depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
-        Self { canvas_buffer }
+        )?;
+        Ok(Self { canvas_buffer })
     }
 
     pub fn color_buffer_format(&self) -> Option<CanvasColorBufferFormat> {
@@ -125,7 +132,8 @@ mod tests {
     #[serial_test::serial]
     fn default_parameters() {
         let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
-        let mut texture = CanvasTexture::new(&instance, &CanvasTextureDescriptor::default());
+        let mut texture =
+            CanvasTexture::new(&instance, &CanvasTextureDescriptor::default()).unwrap();
 
         expect_that!(texture.canvas_size(), eq(CanvasSize::new(1, 1)));
         expect_that!(&texture.sample_count(), eq(1));
@@ -158,7 +166,8 @@ mod tests {
                 size: CanvasSize::new(20, 30),
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(texture.canvas_size(), eq(CanvasSize::new(20, 30)));
         expect_that!(&texture.sample_count(), eq(1));
@@ -191,7 +200,8 @@ mod tests {
                 sample_count: 2,
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(texture.canvas_size(), eq(CanvasSize::new(1, 1)));
         expect_that!(&texture.sample_count(), eq(2));
@@ -224,7 +234,8 @@ mod tests {
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth24Plus),
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(texture.canvas_size(), eq(CanvasSize::new(1, 1)));
         expect_that!(&texture.sample_count(), eq(1));
@@ -268,7 +279,8 @@ mod tests {
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth24Plus),
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(texture.canvas_size(), eq(CanvasSize::new(1, 1)));
         expect_that!(&texture.sample_count(), eq(1));
@@ -302,7 +314,8 @@ mod tests {
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth24Plus),
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )
+        .unwrap();
 
         expect_that!(texture.canvas_size(), eq(CanvasSize::new(1, 1)));
         expect_that!(&texture.sample_count(), eq(2));
@@ -347,6 +360,7 @@ mod tests {
                 depth_stencil_buffer_format: None,
                 ..CanvasTextureDescriptor::default()
             },
-        );
+        )
+        .unwrap();
     }
 }
@@ -1,7 +1,8 @@
 use super::{
-    Canvas, CanvasBuffer, CanvasBufferDescriptor, CanvasBufferSurfaceDescriptor,
-    CanvasColorBufferFormat, CanvasDepthStencilBufferFormat, CanvasFrame, CanvasSize, Instance,
-    SampleCount, Surface, SurfaceError,
+    Canvas, CanvasBuffer, CanvasBufferDescriptor, CanvasBufferError, CanvasBufferSurfaceDescriptor,
+    CanvasColorBufferFormat, CanvasDepthStencilBufferFormat, CanvasFrame, CanvasSize, FrameArena,
+    GraphicsSettings, GraphicsSettingsChanges, GraphicsSettingsSubscriber, Instance,
+    RuntimeGraphicsSettings, SampleCount, Surface, SurfaceError,
 };
 
 use roe_os as os;
@@ -10,26 +11,75 @@ use std::default::Default;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct CanvasWindowDescriptor {
+    pub label: Option<String>,
     pub sample_count: SampleCount,
     pub color_buffer_format: CanvasColorBufferFormat,
     pub depth_stencil_buffer_format: Option<CanvasDepthStencilBufferFormat>,
+    // Bytes preallocated by the frame arena.
+    pub frame_arena_capacity: usize,
 }
 
 impl Default for CanvasWindowDescriptor {
     fn default() -> Self {
         Self {
+            label: None,
             sample_count: 1,
             color_buffer_format: CanvasColorBufferFormat::default(),
             depth_stencil_buffer_format: None,
+            frame_arena_capacity: 0,
         }
     }
 }
 
+#[derive(Debug)]
+pub enum CanvasWindowError {
+    WindowCreationFailed(os::OsError),
+    CanvasBufferCreationFailed(CanvasBufferError),
+}
+
+impl std::fmt::Display for CanvasWindowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanvasWindowError::WindowCreationFailed(e) => {
+                write!(f, "Window creation failed ({})", e)
+            }
+            CanvasWindowError::CanvasBufferCreationFailed(e) => {
+                write!(f, "Canvas buffer creation failed ({})", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CanvasWindowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CanvasWindowError::WindowCreationFailed(e) => Some(e),
+            CanvasWindowError::CanvasBufferCreationFailed(e) => Some(e),
+        }
+    }
+}
+
+impl From<os::OsError> for CanvasWindowError {
+    fn from(e: os::OsError) -> Self {
+        CanvasWindowError::WindowCreationFailed(e)
+    }
+}
+
+impl From<CanvasBufferError> for CanvasWindowError {
+    fn from(e: CanvasBufferError) -> Self {
+        CanvasWindowError::CanvasBufferCreationFailed(e)
+    }
+}
+
 #[derive(Debug)]
 pub struct CanvasWindow {
     canvas_buffer: CanvasBuffer,
     window: os::Window,
+    label: Option<String>,
     color_buffer_format: CanvasColorBufferFormat,
+    depth_stencil_buffer_format: Option<CanvasDepthStencilBufferFormat>,
+    frame_arena: FrameArena,
+    graphics_settings: Option<GraphicsSettingsSubscriber>,
 }
 
 impl CanvasWindow {
@@ -38,9 +88,9 @@ impl CanvasWindow {
         instance: &Instance,
         event_loop: &os::EventLoop<T>,
         desc: &CanvasWindowDescriptor,
-    ) -> Result<Self, os::OsError> {
+    ) -> Result<Self, CanvasWindowError> {
         let window = os::Window::new(event_loop)?;
-        Ok(Self::from_window(instance, window, desc))
+        Ok(Self::from_window(instance, window, desc)?)
     }
 
     // Unsafe: surface creation.
@@ -48,7 +98,7 @@ impl CanvasWindow {
         instance: &Instance,
         window: os::Window,
         desc: &CanvasWindowDescriptor,
-    ) -> Self {
+    ) -> Result<Self, CanvasBufferError> {
         let surface = Surface::new(&instance, &window);
         Self::from_window_and_surface(instance, window, surface, desc)
     }
@@ -59,56 +109,175 @@ impl CanvasWindow {
         window: os::Window,
         surface: Surface,
         desc: &CanvasWindowDescriptor,
-    ) -> Self {
+    ) -> Result<Self, CanvasBufferError> {
         let surface_size = window.inner_size();
         let canvas_buffer = CanvasBuffer::new(
             instance,
             Some(surface),
-            &CanvasBufferDescriptor {
-                size: CanvasSize::new(surface_size.width, surface_size.height),
-                sample_count: desc.sample_count,
-                surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
-                    format: desc.color_buffer_format,
-                }),
-                color_buffer_descriptors: Vec::new(),
-                depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
-            },
-        );
-        Self {
+            &Self::canvas_buffer_descriptor(
+                desc.label.clone(),
+                CanvasSize::new(surface_size.width, surface_size.height),
+                desc.sample_count,
+                desc.color_buffer_format,
+                desc.depth_stencil_buffer_format,
+            ),
+        )?;
+        Ok(Self {
             canvas_buffer,
             window,
+            label: desc.label.clone(),
             color_buffer_format: desc.color_buffer_format,
+            depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+            frame_arena: FrameArena::with_capacity(desc.frame_arena_capacity),
+            graphics_settings: None,
+        })
+    }
+
+    fn canvas_buffer_descriptor(
+        label: Option<String>,
+        size: CanvasSize,
+        sample_count: SampleCount,
+        color_buffer_format: CanvasColorBufferFormat,
+        depth_stencil_buffer_format: Option<CanvasDepthStencilBufferFormat>,
+    ) -> CanvasBufferDescriptor {
+        CanvasBufferDescriptor {
+            label,
+            size,
+            sample_count,
+            surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
+                format: color_buffer_format,
+            }),
+            color_buffer_descriptors: Vec::new(),
+            depth_stencil_buffer_format,
+            depth_stencil_buffer_sample_count: None,
         }
     }
 
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     pub fn color_buffer_format(&self) -> CanvasColorBufferFormat {
         self.color_buffer_format
     }
 
     pub fn depth_stencil_buffer_format(&self) -> Option<CanvasDepthStencilBufferFormat> {
-        match &self.canvas_buffer.depth_stencil_buffer() {
-            Some(v) => Some(v.format()),
-            None => None,
+        self.depth_stencil_buffer_format
+    }
+
+    // Pipelines rendering to the window must be recreated with the new sample count.
+    pub fn set_sample_count(
+        &mut self,
+        instance: &Instance,
+        sample_count: SampleCount,
+    ) -> Result<(), CanvasBufferError> {
+        if sample_count == self.sample_count() {
+            return Ok(());
         }
+        let size = *self.canvas_size();
+        self.canvas_buffer.configure(
+            instance,
+            &Self::canvas_buffer_descriptor(
+                self.label.clone(),
+                size,
+                sample_count,
+                self.color_buffer_format(),
+                self.depth_stencil_buffer_format(),
+            ),
+        )
+    }
+
+    // The sample count follows the MSAA level of the settings, starting from the current one.
+    // Returns true if the sample count changed.
+    pub fn subscribe_graphics_settings(
+        &mut self,
+        instance: &Instance,
+        settings: &RuntimeGraphicsSettings,
+    ) -> Result<bool, CanvasBufferError> {
+        let subscriber = settings.subscribe(GraphicsSettingsChanges::MSAA);
+        let current = *subscriber.settings();
+        self.graphics_settings = Some(subscriber);
+        self.apply_graphics_settings(instance, &current)
+    }
+
+    // Applies the settings changed since the last update, e.g. at the start of a frame. Returns
+    // true if the sample count changed.
+    pub fn update_graphics_settings(
+        &mut self,
+        instance: &Instance,
+    ) -> Result<bool, CanvasBufferError> {
+        match self.graphics_settings.as_mut().and_then(|s| s.poll()) {
+            Some(update) => self.apply_graphics_settings(instance, &update.settings),
+            None => Ok(false),
+        }
+    }
+
+    pub fn apply_graphics_settings(
+        &mut self,
+        instance: &Instance,
+        settings: &GraphicsSettings,
+    ) -> Result<bool, CanvasBufferError> {
+        let sample_count = settings.msaa.sample_count();
+        let changed = sample_count != self.sample_count();
+        self.set_sample_count(instance, sample_count)?;
+        Ok(changed)
     }
 
-    pub fn update_buffer(&mut self, instance: &Instance) {
+    pub fn update_buffer(&mut self, instance: &Instance) -> Result<(), CanvasBufferError> {
         let current_size = self.inner_size();
         let current_size = CanvasSize::new(current_size.width, current_size.height);
         if *self.canvas_size() != current_size {
             self.canvas_buffer.configure(
                 instance,
-                &CanvasBufferDescriptor {
-                    size: current_size,
-                    sample_count: self.sample_count(),
-                    surface_descriptor: Some(CanvasBufferSurfaceDescriptor {
-                        format: self.color_buffer_format(),
-                    }),
-                    color_buffer_descriptors: Vec::new(),
-                    depth_stencil_buffer_format: self.depth_stencil_buffer_format(),
-                },
-            );
+                &Self::canvas_buffer_descriptor(
+                    self.label.clone(),
+                    current_size,
+                    self.sample_count(),
+                    self.color_buffer_format(),
+                    self.depth_stencil_buffer_format(),
+                ),
+            )?;
         }
+        Ok(())
+    }
+
+    // Creates a new surface for the window, replacing the current one. On some platforms (e.g.
+    // Android) the native window is destroyed when the application is suspended, and the surface
+    // must be recreated when the application is resumed.
+    // Unsafe: surface creation.
+    pub unsafe fn recreate_surface(
+        &mut self,
+        instance: &Instance,
+    ) -> Result<(), CanvasBufferError> {
+        let surface_size = self.window.inner_size();
+        let desc = Self::canvas_buffer_descriptor(
+            self.label.clone(),
+            CanvasSize::new(surface_size.width, surface_size.height),
+            self.sample_count(),
+            self.color_buffer_format(),
+            self.depth_stencil_buffer_format(),
+        );
+        self.canvas_buffer
+            .replace_surface(Surface::new(instance, &self.window));
+        self.canvas_buffer.configure(instance, &desc)
+    }
+
+    // Arena for the temporary CPU data of a frame. Use current_frame_with_arena to allocate from
+    // it while rendering.
+    pub fn frame_arena(&self) -> &FrameArena {
+        &self.frame_arena
+    }
+
+    // Same as current_frame, also returning the frame arena. The arena is reset each time a new
+    // frame is requested, after the previous one has been presented.
+    pub fn current_frame_with_arena(
+        &mut self,
+    ) -> Result<Option<(CanvasFrame<'_>, &FrameArena)>, SurfaceError> {
+        self.frame_arena.finish_frame();
+        Ok(self
+            .canvas_buffer
+            .current_frame()?
+            .map(|frame| (frame, &self.frame_arena)))
     }
 
     pub fn id(&self) -> os::WindowId {
@@ -146,12 +315,16 @@ impl CanvasWindow {
         self.window.outer_size()
     }
 
-    pub fn set_inner_size<S>(&mut self, instance: &Instance, size: S)
+    pub fn set_inner_size<S>(
+        &mut self,
+        instance: &Instance,
+        size: S,
+    ) -> Result<(), CanvasBufferError>
     where
         S: Into<os::Size>,
     {
         self.window.set_inner_size(size);
-        self.update_buffer(instance);
+        self.update_buffer(instance)
     }
 
     pub fn set_min_inner_size<S>(&mut self, min_size: Option<S>)
@@ -237,6 +410,7 @@ impl CanvasWindow {
 
 impl Canvas for CanvasWindow {
     fn current_frame(&mut self) -> Result<Option<CanvasFrame>, SurfaceError> {
+        self.frame_arena.finish_frame();
         self.canvas_buffer.current_frame()
     }
 
@@ -251,7 +425,7 @@ impl Canvas for CanvasWindow {
 
 #[cfg(test)]
 mod tests {
-    use super::super::InstanceDescriptor;
+    use super::super::{InstanceDescriptor, MsaaLevel};
     use super::*;
     use galvanic_assert::{matchers::*, *};
     use os::EventLoopAnyThread;
@@ -267,7 +441,7 @@ mod tests {
             .with_visible(false)
             .build(&event_loop)
             .unwrap();
-        let window = unsafe { CanvasWindow::from_window(&instance, window, desc) };
+        let window = unsafe { CanvasWindow::from_window(&instance, window, desc).unwrap() };
         (window, instance)
     }
 
@@ -282,6 +456,7 @@ mod tests {
             .unwrap();
         let _canvas_window = unsafe {
             CanvasWindow::from_window(&instance, window, &CanvasWindowDescriptor::default())
+                .unwrap()
         };
     }
 
@@ -303,6 +478,7 @@ mod tests {
                 surface,
                 &CanvasWindowDescriptor::default(),
             )
+            .unwrap()
         };
     }
 
@@ -320,6 +496,7 @@ mod tests {
                     .unwrap(),
                 &CanvasWindowDescriptor::default(),
             )
+            .unwrap()
         };
         let window2 = unsafe {
             CanvasWindow::from_window(
@@ -330,6 +507,7 @@ mod tests {
                     .unwrap(),
                 &CanvasWindowDescriptor::default(),
             )
+            .unwrap()
         };
         expect_that!(&window1.id(), not(eq(window2.id())));
     }
@@ -352,6 +530,7 @@ mod tests {
                 surface,
                 &CanvasWindowDescriptor::default(),
             )
+            .unwrap()
         };
         let window2 = unsafe {
             CanvasWindow::from_window(
@@ -362,6 +541,7 @@ mod tests {
                     .unwrap(),
                 &CanvasWindowDescriptor::default(),
             )
+            .unwrap()
         };
         expect_that!(&window1.id(), not(eq(window2.id())));
     }
@@ -390,13 +570,15 @@ mod tests {
             &CanvasWindowDescriptor::default(),
         );
 
-        window.set_inner_size(
-            &instance,
-            os::PhysicalSize {
-                width: 200,
-                height: 60,
-            },
-        );
+        window
+            .set_inner_size(
+                &instance,
+                os::PhysicalSize {
+                    width: 200,
+                    height: 60,
+                },
+            )
+            .unwrap();
         expect_that!(window.canvas_size(), eq(CanvasSize::new(200, 60)));
 
         // Changing the min or max size doesn't directly influence the window size.
@@ -429,7 +611,7 @@ mod tests {
             height: 100,
         });
         expect_that!(window.canvas_size(), eq(CanvasSize::new(150, 30)));
-        window.update_buffer(&instance);
+        window.update_buffer(&instance).unwrap();
         expect_that!(window.canvas_size(), eq(CanvasSize::new(200, 100)));
     }
 
@@ -542,6 +724,35 @@ mod tests {
         );
     }
 
+    #[test]
+    #[serial_test::serial]
+    fn recreate_surface() {
+        let (mut window, instance) = create_window(
+            os::PhysicalSize {
+                width: 20,
+                height: 30,
+            },
+            &CanvasWindowDescriptor {
+                sample_count: 2,
+                depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                ..CanvasWindowDescriptor::default()
+            },
+        );
+
+        unsafe { window.recreate_surface(&instance).unwrap() };
+
+        expect_that!(window.canvas_size(), eq(CanvasSize::new(20, 30)));
+        expect_that!(&window.sample_count(), eq(2));
+        expect_that!(
+            &window.depth_stencil_buffer_format(),
+            eq(Some(CanvasDepthStencilBufferFormat::Depth32Float))
+        );
+
+        let frame = window.current_frame().unwrap().unwrap();
+        expect_that!(frame.surface().is_some());
+        expect_that!(frame.depth_stencil_buffer().is_some());
+    }
+
     #[test]
     #[serial_test::serial]
     fn multisampled_with_depth_stencil_buffer() {
@@ -620,4 +831,86 @@ mod tests {
             eq(CanvasColorBufferFormat::Bgra8Unorm)
         );
     }
+
+    #[test]
+    #[serial_test::serial]
+    fn frame_arena() {
+        let (mut window, _) = create_window(
+            os::PhysicalSize {
+                width: 20,
+                height: 30,
+            },
+            &CanvasWindowDescriptor {
+                frame_arena_capacity: 256,
+                ..CanvasWindowDescriptor::default()
+            },
+        );
+        expect_that!(window.frame_arena().capacity() >= 256);
+
+        {
+            let (frame, arena) = window.current_frame_with_arena().unwrap().unwrap();
+            arena.alloc_slice_copy(&[0u8; 100]);
+            frame.present();
+        }
+        expect_that!(&window.frame_arena().last_frame_size(), eq(0));
+
+        // The arena is reset when the next frame is requested.
+        let _frame = window.current_frame().unwrap().unwrap();
+        expect_that!(&window.frame_arena().last_frame_size(), eq(100));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn graphics_settings() {
+        let (mut window, instance) = create_window(
+            os::PhysicalSize {
+                width: 20,
+                height: 30,
+            },
+            &CanvasWindowDescriptor::default(),
+        );
+        let settings = RuntimeGraphicsSettings::new(GraphicsSettings {
+            msaa: MsaaLevel::X4,
+            ..GraphicsSettings::default()
+        });
+        expect_that!(window
+            .subscribe_graphics_settings(&instance, &settings)
+            .unwrap());
+        expect_that!(&window.sample_count(), eq(4));
+
+        settings.modify(|s| s.resolution_scale = 0.5);
+        expect_that!(!window.update_graphics_settings(&instance).unwrap());
+        settings.modify(|s| s.msaa = MsaaLevel::Off);
+        expect_that!(window.update_graphics_settings(&instance).unwrap());
+        expect_that!(&window.sample_count(), eq(1));
+        expect_that!(window.canvas_size(), eq(CanvasSize::new(20, 30)));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn invalid_sample_count() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let event_loop = os::EventLoop::<()>::new_any_thread();
+        let window = os::WindowBuilder::new()
+            .with_visible(false)
+            .build(&event_loop)
+            .unwrap();
+        let result = unsafe {
+            CanvasWindow::from_window(
+                &instance,
+                window,
+                &CanvasWindowDescriptor {
+                    sample_count: 3,
+                    ..CanvasWindowDescriptor::default()
+                },
+            )
+        };
+        expect_that!(
+            &result.unwrap_err(),
+            eq(CanvasBufferError::InvalidSampleCounts(vec![(
+                crate::CanvasAttachment::Surface,
+                3
+            )]))
+        );
+    }
 }
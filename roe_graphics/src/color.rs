@@ -5,6 +5,10 @@ use super::utility;
 pub use wgpu::Color as ColorF64;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -83,6 +87,10 @@ impl as_slice::AsSlice for Color {
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct ColorF32 {
     pub r: f32,
     pub g: f32,
@@ -342,4 +350,26 @@ mod tests {
         expect_that!(&color.b, close_to(0.3, 1e-16));
         expect_that!(&color.a, close_to(0.45, 1e-16));
     }
+
+    #[test]
+    #[cfg(feature = "serde-serialize")]
+    fn serialization() {
+        let color = ColorF32 {
+            r: 1.,
+            g: 0.5,
+            b: 0.,
+            a: 0.25,
+        };
+        let s = ron::ser::to_string(&color).unwrap();
+        expect_that!(&ron::de::from_str::<ColorF32>(&s).unwrap(), eq(color));
+        expect_that!(
+            &ron::de::from_str::<Color>("(r: 1, g: 2, b: 3, a: 255)").unwrap(),
+            eq(Color {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 255
+            })
+        );
+    }
 }
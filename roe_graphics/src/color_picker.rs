@@ -0,0 +1,329 @@
+use super::{
+    linear_to_srgb, srgb_to_linear, Buffer, BufferDescriptor, BufferUsage, Color, ColorF32,
+    CommandEncoder, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, Instance, Maintain, MapMode, Origin3d, Texture, TextureAspect, TextureFormat,
+    TextureUsage,
+};
+
+use futures::FutureExt;
+
+use std::{future::Future, pin::Pin};
+
+// Color of a picked pixel.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PickedColor {
+    // As displayed, sRGB encoded.
+    pub srgb: Color,
+    // Linear values, unclamped for float formats.
+    pub linear: ColorF32,
+}
+
+pub fn color_picker_supports_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb
+            | TextureFormat::Rgba16Float
+    )
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1. } else { 1. };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * (-24f32).exp2(),
+        31 if mantissa == 0. => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1. + mantissa / 1024.) * ((exponent - 15) as f32).exp2(),
+    }
+}
+
+// Converts the texel to the displayed color. 8 bit formats store the displayed values whatever
+// their encoding, either through the hardware conversion or the pipeline color conversion,
+// float formats store linear values.
+fn decode_texel(format: TextureFormat, texel: &[u8]) -> PickedColor {
+    let from_srgb_bytes = |bytes: [u8; 4]| {
+        let srgb = Color {
+            r: bytes[0],
+            g: bytes[1],
+            b: bytes[2],
+            a: bytes[3],
+        };
+        let linear = |c: u8| srgb_to_linear(c as f32 / 255.);
+        PickedColor {
+            srgb,
+            linear: ColorF32 {
+                r: linear(srgb.r),
+                g: linear(srgb.g),
+                b: linear(srgb.b),
+                a: srgb.a as f32 / 255.,
+            },
+        }
+    };
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+            from_srgb_bytes([texel[0], texel[1], texel[2], texel[3]])
+        }
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+            from_srgb_bytes([texel[2], texel[1], texel[0], texel[3]])
+        }
+        TextureFormat::Rgba16Float => {
+            let channel =
+                |i: usize| f16_to_f32(u16::from_le_bytes([texel[2 * i], texel[2 * i + 1]]));
+            let linear = ColorF32 {
+                r: channel(0),
+                g: channel(1),
+                b: channel(2),
+                a: channel(3),
+            };
+            let encode = |c: f32| (linear_to_srgb(c.clamp(0., 1.)) * 255.).round() as u8;
+            let srgb = Color {
+                r: encode(linear.r),
+                g: encode(linear.g),
+                b: encode(linear.b),
+                a: (linear.a.clamp(0., 1.) * 255.).round() as u8,
+            };
+            PickedColor { srgb, linear }
+        }
+        _ => panic!("Unsupported color picker format"),
+    }
+}
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+struct PendingPick {
+    format: TextureFormat,
+    future: MapFuture,
+}
+
+impl std::fmt::Debug for PendingPick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PendingPick {{ format: {:?} }}", self.format)
+    }
+}
+
+// Reads back single pixels of a texture without stalling, e.g. for an eyedropper reading the
+// final frame before it is presented. The texture must have the COPY_SRC usage. Results arrive
+// once the GPU is done with the copy, usually a frame or two later.
+#[derive(Debug)]
+pub struct ColorPicker {
+    buffer: Buffer,
+    pending: Option<PendingPick>,
+    last_color: Option<PickedColor>,
+}
+
+impl ColorPicker {
+    pub fn new(instance: &Instance) -> Self {
+        let buffer = Buffer::new(
+            instance,
+            &BufferDescriptor {
+                label: Some("color_picker"),
+                size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+                usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        Self {
+            buffer,
+            pending: None,
+            last_color: None,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    // Last color received.
+    pub fn last_color(&self) -> Option<PickedColor> {
+        self.last_color
+    }
+
+    // Copies the pixel and starts reading it back. Returns false if a request is already
+    // pending or the position is outside of the texture, e.g. when the cursor left the window.
+    pub fn request(&mut self, instance: &Instance, texture: &Texture, x: u32, y: u32) -> bool {
+        assert!(
+            texture.usage().contains(TextureUsage::COPY_SRC),
+            "The texture must have the COPY_SRC usage"
+        );
+        assert!(
+            texture.sample_count() == 1,
+            "Multisampled textures can't be picked"
+        );
+        assert!(
+            color_picker_supports_format(texture.format()),
+            "Unsupported color picker format"
+        );
+        if self.is_pending() || x >= texture.size().width || y >= texture.size().height {
+            return false;
+        }
+        let mut encoder = CommandEncoder::new(instance, &CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: core::num::NonZeroU32::new(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        instance.submit(Some(encoder.finish()));
+        self.pending = Some(PendingPick {
+            format: texture.format(),
+            future: Box::pin(self.buffer.slice(..).map_async(MapMode::Read)),
+        });
+        true
+    }
+
+    // Returns the color of the pending request if it is available, without blocking.
+    pub fn poll(&mut self, instance: &Instance) -> Option<PickedColor> {
+        instance.poll(Maintain::Poll);
+        let pending = self.pending.as_mut()?;
+        let result = (&mut pending.future).now_or_never()?;
+        self.finish(result)
+    }
+
+    // Blocks until the pending request completes.
+    pub fn wait(&mut self, instance: &Instance) -> Option<PickedColor> {
+        let pending = self.pending.as_mut()?;
+        instance.poll(Maintain::Wait);
+        let result = futures::executor::block_on(&mut pending.future);
+        self.finish(result)
+    }
+
+    // Single call per frame: collects the previous request if done, requests the pixel
+    // again when idle, and returns the most recent color.
+    pub fn pick(
+        &mut self,
+        instance: &Instance,
+        texture: &Texture,
+        x: u32,
+        y: u32,
+    ) -> Option<PickedColor> {
+        self.poll(instance);
+        self.request(instance, texture, x, y);
+        self.last_color
+    }
+
+    fn finish(&mut self, result: Result<(), wgpu::BufferAsyncError>) -> Option<PickedColor> {
+        let pending = self.pending.take()?;
+        if result.is_err() {
+            return None;
+        }
+        let slice = self.buffer.slice(..);
+        let color = decode_texel(pending.format, &slice.get_mapped_range());
+        self.buffer.unmap();
+        self.last_color = Some(color);
+        Some(color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use crate::{InstanceDescriptor, TextureDescriptor, TextureDimension};
+
+    #[test]
+    fn half_floats() {
+        expect_that!(&f16_to_f32(0x3c00), eq(1.));
+        expect_that!(&f16_to_f32(0xc000), eq(-2.));
+        expect_that!(&f16_to_f32(0x3800), eq(0.5));
+        expect_that!(&f16_to_f32(0x0001), eq(5.960_464_5e-8));
+        expect_that!(&f16_to_f32(0x7c00), eq(f32::INFINITY));
+        expect_that!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn texel_decoding() {
+        let rgba = decode_texel(TextureFormat::Rgba8UnormSrgb, &[255, 188, 0, 128]);
+        expect_that!(
+            &rgba.srgb,
+            eq(Color {
+                r: 255,
+                g: 188,
+                b: 0,
+                a: 128
+            })
+        );
+        expect_that!(&rgba.linear.r, close_to(1., 1e-6));
+        expect_that!(&rgba.linear.g, close_to(0.5029, 1e-3));
+        expect_that!(&rgba.linear.a, close_to(128. / 255., 1e-6));
+
+        let bgra = decode_texel(TextureFormat::Bgra8Unorm, &[0, 188, 255, 128]);
+        expect_that!(&bgra, eq(rgba));
+
+        // 0.5 linear, 2 and 1 as half floats.
+        let texel = [0x00, 0x38, 0x00, 0x40, 0x00, 0x00, 0x00, 0x3c];
+        let float = decode_texel(TextureFormat::Rgba16Float, &texel);
+        expect_that!(&float.linear.r, eq(0.5));
+        expect_that!(&float.linear.g, eq(2.));
+        expect_that!(
+            &float.srgb,
+            eq(Color {
+                r: 188,
+                g: 255,
+                b: 0,
+                a: 255
+            })
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn pick() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let texture = Texture::new(
+            &instance,
+            &TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                usage: TextureUsage::COPY_SRC | TextureUsage::COPY_DST,
+            },
+        );
+        let mut pixels = [0u8; 64];
+        pixels[(2 * 4 + 1) * 4..(2 * 4 + 2) * 4].copy_from_slice(&[10, 20, 30, 255]);
+        texture.write_region(&instance, &crate::TextureRegion::whole(&texture), &pixels);
+
+        let mut picker = ColorPicker::new(&instance);
+        expect_that!(!picker.request(&instance, &texture, 4, 0));
+        expect_that!(picker.request(&instance, &texture, 1, 2));
+        expect_that!(!picker.request(&instance, &texture, 0, 0));
+        let color = picker.wait(&instance).unwrap();
+        expect_that!(
+            &color.srgb,
+            eq(Color {
+                r: 10,
+                g: 20,
+                b: 30,
+                a: 255
+            })
+        );
+        expect_that!(!picker.is_pending());
+        expect_that!(&picker.last_color(), eq(Some(color)));
+    }
+}
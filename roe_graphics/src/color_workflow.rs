@@ -0,0 +1,226 @@
+use super::{CanvasColorBufferFormat, ColorF32, TextureFormat};
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+impl CanvasColorBufferFormat {
+    pub fn is_srgb(self) -> bool {
+        matches!(self, Self::Rgba8UnormSrgb | Self::Bgra8UnormSrgb)
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::Rgba16Float)
+    }
+
+    // The format with the same channel order and the requested encoding. Float formats are
+    // returned unchanged.
+    pub fn with_srgb(self, srgb: bool) -> Self {
+        match (self, srgb) {
+            (Self::Rgba16Float, _) => Self::Rgba16Float,
+            (Self::Rgba8Unorm, true) | (Self::Rgba8UnormSrgb, true) => Self::Rgba8UnormSrgb,
+            (Self::Rgba8Unorm, false) | (Self::Rgba8UnormSrgb, false) => Self::Rgba8Unorm,
+            (Self::Bgra8Unorm, true) | (Self::Bgra8UnormSrgb, true) => Self::Bgra8UnormSrgb,
+            (Self::Bgra8Unorm, false) | (Self::Bgra8UnormSrgb, false) => Self::Bgra8Unorm,
+        }
+    }
+}
+
+// Conversion applied by a fragment shader before writing to the color buffer. The values match
+// the ones expected by the shaders.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[repr(u32)]
+pub enum ColorConversion {
+    #[default]
+    None = 0,
+    LinearToSrgb = 1,
+    SrgbToLinear = 2,
+}
+
+impl ColorConversion {
+    // Converts a color on the cpu, e.g. the clear color of a render pass.
+    pub fn apply(self, color: ColorF32) -> ColorF32 {
+        let f = match self {
+            Self::None => return color,
+            Self::LinearToSrgb => linear_to_srgb,
+            Self::SrgbToLinear => srgb_to_linear,
+        };
+        ColorF32 {
+            r: f(color.r),
+            g: f(color.g),
+            b: f(color.b),
+            a: color.a,
+        }
+    }
+}
+
+// How colors are stored, blended and written. The engine pipelines read the workflow from the
+// instance so that, with Linear or Gamma, the result looks the same on Unorm and Srgb color
+// buffers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ColorWorkflow {
+    // Textures are decoded to linear values when sampled, and colors are written without any
+    // conversion, so that Unorm color buffers store linear values. Output conversions are opt-in
+    // through the other workflows.
+    #[default]
+    Unconverted,
+    // Textures are decoded to linear values when sampled, and colors are gamma encoded when
+    // written. Blending is only physically correct on Srgb color buffers: when targeting a Unorm
+    // color buffer, render to an intermediate Srgb canvas and blit it with the output conversion.
+    Linear,
+    // Colors are used as stored and blended in gamma space, matching most image editors.
+    Gamma,
+}
+
+impl ColorWorkflow {
+    // Format of color textures created from images.
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            Self::Unconverted | Self::Linear => TextureFormat::Rgba8UnormSrgb,
+            Self::Gamma => TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    // Format of intermediate canvases, blending in the workflow color space.
+    pub fn canvas_color_buffer_format(
+        self,
+        format: CanvasColorBufferFormat,
+    ) -> CanvasColorBufferFormat {
+        match self {
+            Self::Unconverted => format,
+            Self::Linear => format.with_srgb(true),
+            Self::Gamma => format.with_srgb(false),
+        }
+    }
+
+    // Conversion required when rendering to a color buffer with the given format. Float
+    // formats store linear values, like Srgb formats.
+    pub fn output_conversion(self, format: CanvasColorBufferFormat) -> ColorConversion {
+        match (self, format.is_srgb() || format.is_float()) {
+            (Self::Linear, false) => ColorConversion::LinearToSrgb,
+            (Self::Gamma, true) => ColorConversion::SrgbToLinear,
+            _ => ColorConversion::None,
+        }
+    }
+
+    // Converts a color authored in sRGB, e.g. picked in an image editor, to the workflow color
+    // space. Push constant colors are expected in the workflow color space.
+    pub fn color(self, color: ColorF32) -> ColorF32 {
+        match self {
+            Self::Linear => ColorConversion::SrgbToLinear.apply(color),
+            Self::Unconverted | Self::Gamma => color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn srgb_conversion() {
+        expect_that!(&srgb_to_linear(0.), eq(0.));
+        expect_that!(&linear_to_srgb(1.), close_to(1., 1e-6));
+        expect_that!(&srgb_to_linear(0.5), close_to(0.214_041, 1e-5));
+        for i in 0..=10 {
+            let v = i as f32 / 10.;
+            expect_that!(&linear_to_srgb(srgb_to_linear(v)), close_to(v, 1e-5));
+        }
+
+        let c = ColorF32 {
+            r: 0.5,
+            g: 0.,
+            b: 1.,
+            a: 0.5,
+        };
+        let linear = ColorConversion::SrgbToLinear.apply(c);
+        expect_that!(&linear.r, close_to(0.214_041, 1e-5));
+        expect_that!(&linear.a, eq(0.5));
+        expect_that!(&ColorConversion::None.apply(c), eq(c));
+    }
+
+    #[test]
+    fn workflow_formats() {
+        let unorm = CanvasColorBufferFormat::Bgra8Unorm;
+        let srgb = CanvasColorBufferFormat::Bgra8UnormSrgb;
+        expect_that!(&unorm.with_srgb(true), eq(srgb));
+        expect_that!(&srgb.with_srgb(false), eq(unorm));
+        expect_that!(
+            &ColorWorkflow::Gamma
+                .canvas_color_buffer_format(CanvasColorBufferFormat::Rgba8UnormSrgb),
+            eq(CanvasColorBufferFormat::Rgba8Unorm)
+        );
+
+        expect_that!(
+            &ColorWorkflow::Linear.output_conversion(srgb),
+            eq(ColorConversion::None)
+        );
+        expect_that!(
+            &ColorWorkflow::Linear.output_conversion(unorm),
+            eq(ColorConversion::LinearToSrgb)
+        );
+        expect_that!(
+            &ColorWorkflow::Gamma.output_conversion(srgb),
+            eq(ColorConversion::SrgbToLinear)
+        );
+        expect_that!(
+            &ColorWorkflow::Gamma.output_conversion(unorm),
+            eq(ColorConversion::None)
+        );
+        let float = CanvasColorBufferFormat::Rgba16Float;
+        expect_that!(&float.with_srgb(false), eq(float));
+        expect_that!(
+            &ColorWorkflow::Linear.output_conversion(float),
+            eq(ColorConversion::None)
+        );
+        expect_that!(
+            &ColorWorkflow::Gamma.output_conversion(float),
+            eq(ColorConversion::SrgbToLinear)
+        );
+        expect_that!(
+            &ColorWorkflow::Linear.texture_format(),
+            eq(TextureFormat::Rgba8UnormSrgb)
+        );
+    }
+
+    #[test]
+    fn unconverted_workflow() {
+        let workflow = ColorWorkflow::default();
+        expect_that!(&workflow, eq(ColorWorkflow::Unconverted));
+        for format in [
+            CanvasColorBufferFormat::Rgba8Unorm,
+            CanvasColorBufferFormat::Bgra8UnormSrgb,
+            CanvasColorBufferFormat::Rgba16Float,
+        ] {
+            expect_that!(
+                &workflow.output_conversion(format),
+                eq(ColorConversion::None)
+            );
+            expect_that!(&workflow.canvas_color_buffer_format(format), eq(format));
+        }
+        expect_that!(
+            &workflow.texture_format(),
+            eq(TextureFormat::Rgba8UnormSrgb)
+        );
+        let c = ColorF32 {
+            r: 0.5,
+            g: 0.2,
+            b: 1.,
+            a: 1.,
+        };
+        expect_that!(&workflow.color(c), eq(c));
+    }
+}
@@ -1,10 +1,12 @@
 use std::{default::Default, iter};
 
 use super::{
-    CanvasColorBufferFormat, CanvasDepthStencilBufferFormat, CanvasFrame, ColorOperations,
-    CommandEncoder, CommandEncoderDescriptor, DepthOperations, Instance, Operations, RenderPass,
-    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor, SampleCount,
-    StencilOperations,
+    region_aligned, region_fits, Buffer, BufferAddress, CanvasColorBufferFormat,
+    CanvasDepthStencilBufferFormat, CanvasFrame, ColorConversion, ColorOperations, CommandEncoder,
+    CommandEncoderDescriptor, ComputePass, ComputePassDescriptor, DepthOperations,
+    ImageCopyTexture, Instance, Operations, RenderPass, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, SampleCount, StencilOperations,
+    Texture, TextureAspect, TextureBlitter, TextureRegion, TextureUsage,
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -34,12 +36,37 @@ impl Default for RenderPassOperations {
 #[derive(Debug)]
 pub struct CommandSequence {
     encoder: CommandEncoder,
+    debug_group_depth: u32,
 }
 
 impl CommandSequence {
     pub fn new(instance: &Instance) -> Self {
         let encoder = CommandEncoder::new(&instance, &CommandEncoderDescriptor::default());
-        Self { encoder }
+        Self {
+            encoder,
+            debug_group_depth: 0,
+        }
+    }
+
+    // Groups the following commands under the label in graphics debuggers, until the matching
+    // call to pop_debug_group. Groups can be nested.
+    pub fn push_debug_group(&mut self, label: &str) {
+        self.encoder.push_debug_group(label);
+        self.debug_group_depth += 1;
+    }
+
+    pub fn pop_debug_group(&mut self) {
+        assert!(self.debug_group_depth > 0, "No debug group to pop");
+        self.encoder.pop_debug_group();
+        self.debug_group_depth -= 1;
+    }
+
+    pub fn insert_debug_marker(&mut self, label: &str) {
+        self.encoder.insert_debug_marker(label);
+    }
+
+    pub fn debug_group_depth(&self) -> u32 {
+        self.debug_group_depth
     }
 
     pub fn begin_render_pass<'a>(
@@ -118,20 +145,199 @@ impl CommandSequence {
         self.encoder.begin_render_pass(&render_pass_desc)
     }
 
+    pub fn begin_compute_pass(&mut self, label: Option<&str>) -> ComputePass<'_> {
+        self.encoder
+            .begin_compute_pass(&ComputePassDescriptor { label })
+    }
+
+    // Offsets and size must be multiples of COPY_BUFFER_ALIGNMENT.
+    pub fn copy_buffer(
+        &mut self,
+        src: &Buffer,
+        src_offset: BufferAddress,
+        dst: &Buffer,
+        dst_offset: BufferAddress,
+        size: BufferAddress,
+    ) {
+        self.encoder
+            .copy_buffer_to_buffer(src, src_offset, dst, dst_offset, size);
+    }
+
+    // Copies a region of a texture into a texture with the same format, e.g. to compose an
+    // atlas. The regions must have the same size.
+    pub fn copy_texture_region(
+        &mut self,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: &Texture,
+        dst_region: &TextureRegion,
+    ) {
+        assert!(
+            src.usage().contains(TextureUsage::COPY_SRC),
+            "The source texture must have the COPY_SRC usage"
+        );
+        assert!(
+            dst.usage().contains(TextureUsage::COPY_DST),
+            "The destination texture must have the COPY_DST usage"
+        );
+        assert!(src.format() == dst.format(), "Incompatible texture formats");
+        assert!(
+            src.sample_count() == 1 && dst.sample_count() == 1,
+            "Multisampled textures can't be copied"
+        );
+        assert!(
+            src_region.size == dst_region.size,
+            "The source and destination regions must have the same size"
+        );
+        Self::check_regions(src, src_region, dst, dst_region);
+        assert!(
+            region_aligned(src.format(), src_region) && region_aligned(dst.format(), dst_region),
+            "The regions must be aligned to the texture format blocks"
+        );
+        self.encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: src,
+                mip_level: src_region.mip_level,
+                origin: src_region.origin,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: dst,
+                mip_level: dst_region.mip_level,
+                origin: dst_region.origin,
+                aspect: TextureAspect::All,
+            },
+            src_region.size,
+        );
+    }
+
+    // Copies a region of a texture into a region of another texture, converting the format and
+    // scaling with the blitter if needed. A plain copy is used when possible.
+    pub fn blit_texture_region(
+        &mut self,
+        instance: &Instance,
+        blitter: &TextureBlitter,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: &Texture,
+        dst_region: &TextureRegion,
+    ) {
+        let can_copy = src.format() == dst.format()
+            && blitter.conversion() == ColorConversion::None
+            && src_region.size == dst_region.size
+            && src.sample_count() == 1
+            && dst.sample_count() == 1
+            && src.usage().contains(TextureUsage::COPY_SRC)
+            && dst.usage().contains(TextureUsage::COPY_DST);
+        if can_copy {
+            self.copy_texture_region(src, src_region, dst, dst_region);
+            return;
+        }
+
+        assert!(
+            src.usage().contains(TextureUsage::TEXTURE_BINDING),
+            "The source texture must have the TEXTURE_BINDING usage"
+        );
+        assert!(
+            dst.usage().contains(TextureUsage::RENDER_ATTACHMENT),
+            "The destination texture must have the RENDER_ATTACHMENT usage"
+        );
+        assert!(
+            blitter.format() == dst.format(),
+            "Incompatible texture blitter"
+        );
+        assert!(
+            src.sample_count() == 1 && dst.sample_count() == 1,
+            "Multisampled textures can't be blitted"
+        );
+        assert!(
+            src_region.size.depth_or_array_layers == 1
+                && dst_region.size.depth_or_array_layers == 1,
+            "Only single layer regions can be blitted"
+        );
+        Self::check_regions(src, src_region, dst, dst_region);
+        blitter.blit(
+            instance,
+            &mut self.encoder,
+            src,
+            src_region,
+            dst,
+            dst_region,
+        );
+    }
+
     pub fn submit(self, instance: &Instance) {
+        assert!(
+            self.debug_group_depth == 0,
+            "Debug groups must be popped before submitting"
+        );
         instance.submit(iter::once(self.encoder.finish()))
     }
+
+    fn check_regions(
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: &Texture,
+        dst_region: &TextureRegion,
+    ) {
+        assert!(
+            region_fits(src.size(), src.mip_level_count(), src_region),
+            "The source region is out of bounds"
+        );
+        assert!(
+            region_fits(dst.size(), dst.mip_level_count(), dst_region),
+            "The destination region is out of bounds"
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use galvanic_assert::{matchers::*, *};
+
     use crate::{
         CanvasBuffer, CanvasBufferColorBufferDescriptor, CanvasBufferDescriptor,
-        CanvasColorBufferUsage, CanvasSize, InstanceDescriptor,
+        CanvasColorBufferUsage, CanvasSize, Extent3d, InstanceDescriptor, TextureBlitterDescriptor,
+        TextureDescriptor, TextureDimension, TextureFormat,
     };
 
+    fn create_texture(
+        instance: &Instance,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        usage: TextureUsage,
+    ) -> Texture {
+        Texture::new(
+            instance,
+            &TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+            },
+        )
+    }
+
+    fn checkerboard() -> image::RgbaImage {
+        image::RgbaImage::from_fn(4, 4, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 255])
+            }
+        })
+    }
+
     #[test]
     #[serial_test::serial]
     fn creation() {
@@ -139,6 +345,30 @@ mod tests {
         let _cmd_seq = CommandSequence::new(&instance);
     }
 
+    #[test]
+    #[serial_test::serial]
+    fn debug_groups() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.push_debug_group("frame");
+        cmd_seq.push_debug_group("scene");
+        cmd_seq.insert_debug_marker("marker");
+        expect_that!(&cmd_seq.debug_group_depth(), eq(2));
+        cmd_seq.pop_debug_group();
+        cmd_seq.pop_debug_group();
+        expect_that!(&cmd_seq.debug_group_depth(), eq(0));
+        cmd_seq.submit(&instance);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    #[should_panic(expected = "No debug group to pop")]
+    fn debug_groups_error_unbalanced() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.pop_debug_group();
+    }
+
     #[test]
     #[serial_test::serial]
     fn render_pass() {
@@ -148,16 +378,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 1,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::default(),
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         let frame = buffer.current_frame().unwrap().unwrap();
         {
@@ -196,16 +430,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 4,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::default(),
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         let frame = buffer.current_frame().unwrap().unwrap();
         {
@@ -245,16 +483,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 1,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::default(),
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         {
             let frame = buffer.current_frame().unwrap().unwrap();
@@ -280,13 +522,16 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 4,
                 surface_descriptor: None,
                 color_buffer_descriptors: Vec::new(),
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth32Float),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         let frame = buffer.current_frame().unwrap().unwrap();
         let _rpass = cmd_seq.begin_render_pass(
@@ -310,16 +555,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 4,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::Bgra8Unorm,
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         let frame = buffer.current_frame().unwrap().unwrap();
         let _rpass = cmd_seq.begin_render_pass(
@@ -343,16 +592,20 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 4,
                 surface_descriptor: None,
                 color_buffer_descriptors: vec![CanvasBufferColorBufferDescriptor {
                     format: CanvasColorBufferFormat::default(),
                     usage: CanvasColorBufferUsage::empty(),
+                    sample_count: None,
                 }],
                 depth_stencil_buffer_format: None,
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         let frame = buffer.current_frame().unwrap().unwrap();
         let _rpass = cmd_seq.begin_render_pass(
@@ -376,13 +629,16 @@ mod tests {
             &instance,
             None,
             &CanvasBufferDescriptor {
+                label: None,
                 size: CanvasSize::new(12, 20),
                 sample_count: 4,
                 surface_descriptor: None,
                 color_buffer_descriptors: Vec::new(),
                 depth_stencil_buffer_format: Some(CanvasDepthStencilBufferFormat::Depth24Plus),
+                depth_stencil_buffer_sample_count: None,
             },
-        );
+        )
+        .unwrap();
 
         let frame = buffer.current_frame().unwrap().unwrap();
         let _rpass = cmd_seq.begin_render_pass(
@@ -395,4 +651,137 @@ mod tests {
             &RenderPassOperations::default(),
         );
     }
+
+    #[test]
+    #[serial_test::serial]
+    fn copy_texture_region() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let src = Texture::from_image(&instance, &checkerboard(), TextureUsage::COPY_SRC);
+        let dst = create_texture(
+            &instance,
+            8,
+            8,
+            TextureFormat::Rgba8UnormSrgb,
+            TextureUsage::COPY_SRC | TextureUsage::COPY_DST,
+        );
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.copy_texture_region(
+            &src,
+            &TextureRegion::new(1, 0, 2, 2),
+            &dst,
+            &TextureRegion::new(6, 6, 2, 2),
+        );
+        cmd_seq.submit(&instance);
+
+        let image = dst.to_image(&instance);
+        expect_that!(image.get_pixel(6, 6), eq(image::Rgba([0, 0, 255, 255])));
+        expect_that!(image.get_pixel(7, 6), eq(image::Rgba([255, 0, 0, 255])));
+        expect_that!(image.get_pixel(5, 5), eq(image::Rgba([0, 0, 0, 0])));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    #[should_panic(expected = "The destination region is out of bounds")]
+    fn copy_texture_region_error_out_of_bounds() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let src = Texture::from_image(&instance, &checkerboard(), TextureUsage::COPY_SRC);
+        let dst = create_texture(
+            &instance,
+            8,
+            8,
+            TextureFormat::Rgba8UnormSrgb,
+            TextureUsage::COPY_DST,
+        );
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.copy_texture_region(
+            &src,
+            &TextureRegion::whole(&src),
+            &dst,
+            &TextureRegion::new(6, 6, 4, 4),
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    #[should_panic(expected = "Incompatible texture formats")]
+    fn copy_texture_region_error_different_formats() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let src = Texture::from_image(&instance, &checkerboard(), TextureUsage::COPY_SRC);
+        let dst = create_texture(
+            &instance,
+            8,
+            8,
+            TextureFormat::Bgra8Unorm,
+            TextureUsage::COPY_DST,
+        );
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.copy_texture_region(
+            &src,
+            &TextureRegion::whole(&src),
+            &dst,
+            &TextureRegion::new(0, 0, 4, 4),
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn blit_texture_region() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let src = Texture::from_image(&instance, &checkerboard(), TextureUsage::TEXTURE_BINDING);
+        let dst = create_texture(
+            &instance,
+            8,
+            8,
+            TextureFormat::Rgba8Unorm,
+            TextureUsage::COPY_SRC | TextureUsage::RENDER_ATTACHMENT,
+        );
+        let blitter = TextureBlitter::new(
+            &instance,
+            &TextureBlitterDescriptor {
+                format: TextureFormat::Rgba8Unorm,
+                filter: crate::FilterMode::Nearest,
+                ..TextureBlitterDescriptor::default()
+            },
+        );
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.blit_texture_region(
+            &instance,
+            &blitter,
+            &src,
+            &TextureRegion::new(0, 0, 2, 2),
+            &dst,
+            &TextureRegion::new(0, 0, 8, 8),
+        );
+        cmd_seq.submit(&instance);
+
+        let image = dst.to_image(&instance);
+        expect_that!(image.get_pixel(1, 1), eq(image::Rgba([255, 0, 0, 255])));
+        expect_that!(image.get_pixel(5, 1), eq(image::Rgba([0, 0, 255, 255])));
+        expect_that!(image.get_pixel(6, 6), eq(image::Rgba([255, 0, 0, 255])));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    #[should_panic(expected = "Incompatible texture blitter")]
+    fn blit_texture_region_error_incompatible_blitter() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let src = Texture::from_image(&instance, &checkerboard(), TextureUsage::TEXTURE_BINDING);
+        let dst = create_texture(
+            &instance,
+            8,
+            8,
+            TextureFormat::Bgra8Unorm,
+            TextureUsage::RENDER_ATTACHMENT,
+        );
+        let blitter = TextureBlitter::new(&instance, &TextureBlitterDescriptor::default());
+        let mut cmd_seq = CommandSequence::new(&instance);
+        cmd_seq.blit_texture_region(
+            &instance,
+            &blitter,
+            &src,
+            &TextureRegion::whole(&src),
+            &dst,
+            &TextureRegion::new(0, 0, 8, 8),
+        );
+    }
 }
@@ -0,0 +1,242 @@
+use super::{Instance, Texture, TextureRegion, TextureUsage};
+
+// Past this number of separate dirty regions they are merged into their bounding box, trading
+// upload bandwidth for fewer copies.
+const MAX_DIRTY_REGIONS: usize = 16;
+
+fn region_right(region: &TextureRegion) -> u32 {
+    region.origin.x + region.size.width
+}
+
+fn region_bottom(region: &TextureRegion) -> u32 {
+    region.origin.y + region.size.height
+}
+
+// Overlapping or sharing an edge.
+fn regions_touch(a: &TextureRegion, b: &TextureRegion) -> bool {
+    a.origin.x <= region_right(b)
+        && b.origin.x <= region_right(a)
+        && a.origin.y <= region_bottom(b)
+        && b.origin.y <= region_bottom(a)
+}
+
+fn region_union(a: &TextureRegion, b: &TextureRegion) -> TextureRegion {
+    let x = std::cmp::min(a.origin.x, b.origin.x);
+    let y = std::cmp::min(a.origin.y, b.origin.y);
+    TextureRegion::new(
+        x,
+        y,
+        std::cmp::max(region_right(a), region_right(b)) - x,
+        std::cmp::max(region_bottom(a), region_bottom(b)) - y,
+    )
+}
+
+// Adds a region to a set of disjoint regions, merging it with the regions it touches.
+fn add_dirty_region(regions: &mut Vec<TextureRegion>, region: TextureRegion) {
+    if region.size.width == 0 || region.size.height == 0 {
+        return;
+    }
+    let mut merged = region;
+    while let Some(i) = regions.iter().position(|r| regions_touch(r, &merged)) {
+        merged = region_union(&regions.swap_remove(i), &merged);
+    }
+    regions.push(merged);
+    if regions.len() > MAX_DIRTY_REGIONS {
+        let bounds = regions
+            .iter()
+            .skip(1)
+            .fold(regions[0], |acc, r| region_union(&acc, r));
+        regions.clear();
+        regions.push(bounds);
+    }
+}
+
+// Pixels of a region of the image, tightly packed.
+fn region_pixels(image: &image::RgbaImage, region: &TextureRegion) -> Vec<u8> {
+    let row_start = region.origin.x as usize * 4;
+    let row_end = region_right(region) as usize * 4;
+    let stride = image.width() as usize * 4;
+    let mut pixels =
+        Vec::with_capacity(region.size.width as usize * region.size.height as usize * 4);
+    for y in region.origin.y..region_bottom(region) {
+        let row = y as usize * stride;
+        pixels.extend_from_slice(&image.as_raw()[row + row_start..row + row_end]);
+    }
+    pixels
+}
+
+// Texture with a CPU copy of its pixels. Edits modify the copy and mark the regions they touch,
+// upload sends the modified regions to the GPU, typically once per frame.
+#[derive(Debug)]
+pub struct EditableTexture {
+    texture: Texture,
+    image: image::RgbaImage,
+    dirty_regions: Vec<TextureRegion>,
+}
+
+impl EditableTexture {
+    pub fn new(instance: &Instance, image: image::RgbaImage, usage: TextureUsage) -> Self {
+        let texture = Texture::from_image(instance, &image, usage);
+        Self {
+            texture,
+            image,
+            dirty_regions: Vec::new(),
+        }
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn image(&self) -> &image::RgbaImage {
+        &self.image
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> image::Rgba<u8> {
+        *self.image.get_pixel(x, y)
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: image::Rgba<u8>) {
+        self.image.put_pixel(x, y, color);
+        self.mark_dirty(TextureRegion::new(x, y, 1, 1));
+    }
+
+    pub fn fill_region(&mut self, region: &TextureRegion, color: image::Rgba<u8>) {
+        self.edit(region, |image| {
+            for y in region.origin.y..region_bottom(region) {
+                for x in region.origin.x..region_right(region) {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        });
+    }
+
+    // Copies an image with its top left corner at (x, y), e.g. a brush stamp or a decal.
+    pub fn draw_image(&mut self, x: u32, y: u32, source: &image::RgbaImage) {
+        let region = TextureRegion::new(x, y, source.width(), source.height());
+        self.edit(&region, |image| {
+            for (sx, sy, color) in source.enumerate_pixels() {
+                image.put_pixel(x + sx, y + sy, *color);
+            }
+        });
+    }
+
+    // Gives access to the whole image, but only changes inside the region are guaranteed to be
+    // uploaded.
+    pub fn edit<F: FnOnce(&mut image::RgbaImage)>(&mut self, region: &TextureRegion, f: F) {
+        assert!(
+            region.mip_level == 0
+                && region.origin.z == 0
+                && region_right(region) <= self.width()
+                && region_bottom(region) <= self.height(),
+            "The region is out of bounds"
+        );
+        f(&mut self.image);
+        self.mark_dirty(*region);
+    }
+
+    pub fn mark_dirty(&mut self, region: TextureRegion) {
+        add_dirty_region(&mut self.dirty_regions, region);
+    }
+
+    pub fn dirty_regions(&self) -> &[TextureRegion] {
+        &self.dirty_regions
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty_regions.is_empty()
+    }
+
+    pub fn upload(&mut self, instance: &Instance) {
+        for region in self.dirty_regions.drain(..) {
+            self.texture
+                .write_region(instance, &region, &region_pixels(&self.image, &region));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use crate::InstanceDescriptor;
+
+    #[test]
+    fn dirty_region_merging() {
+        let mut regions = Vec::new();
+        add_dirty_region(&mut regions, TextureRegion::new(0, 0, 2, 2));
+        add_dirty_region(&mut regions, TextureRegion::new(10, 10, 2, 2));
+        add_dirty_region(&mut regions, TextureRegion::new(4, 4, 0, 3));
+        expect_that!(&regions.len(), eq(2));
+
+        // Touching the first region.
+        add_dirty_region(&mut regions, TextureRegion::new(2, 1, 3, 1));
+        expect_that!(&regions.len(), eq(2));
+        expect_that!(regions.contains(&TextureRegion::new(0, 0, 5, 2)));
+
+        // Bridging both regions.
+        add_dirty_region(&mut regions, TextureRegion::new(4, 2, 6, 8));
+        expect_that!(&regions, eq(vec![TextureRegion::new(0, 0, 12, 12)]));
+    }
+
+    #[test]
+    fn dirty_region_limit() {
+        let mut regions = Vec::new();
+        for i in 0..MAX_DIRTY_REGIONS as u32 {
+            add_dirty_region(&mut regions, TextureRegion::new(i * 4, 0, 1, 1));
+        }
+        expect_that!(&regions.len(), eq(MAX_DIRTY_REGIONS));
+        add_dirty_region(&mut regions, TextureRegion::new(0, 8, 1, 1));
+        expect_that!(
+            &regions,
+            eq(vec![TextureRegion::new(
+                0,
+                0,
+                MAX_DIRTY_REGIONS as u32 * 4 - 3,
+                9
+            )])
+        );
+    }
+
+    #[test]
+    fn pixel_packing() {
+        let image = image::RgbaImage::from_fn(4, 3, |x, y| image::Rgba([x as u8, y as u8, 0, 1]));
+        let pixels = region_pixels(&image, &TextureRegion::new(1, 1, 2, 2));
+        expect_that!(
+            &pixels,
+            eq(vec![1, 1, 0, 1, 2, 1, 0, 1, 1, 2, 0, 1, 2, 2, 0, 1])
+        );
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn upload() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let mut texture = EditableTexture::new(
+            &instance,
+            image::RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 0, 255])),
+            TextureUsage::TEXTURE_BINDING | TextureUsage::COPY_SRC,
+        );
+        texture.set_pixel(1, 1, image::Rgba([255, 0, 0, 255]));
+        texture.fill_region(
+            &TextureRegion::new(4, 4, 2, 3),
+            image::Rgba([0, 255, 0, 255]),
+        );
+        expect_that!(texture.is_dirty());
+        texture.upload(&instance);
+        expect_that!(!texture.is_dirty());
+
+        let output = texture.texture().to_image(&instance);
+        expect_that!(&output, eq(texture.image().clone()));
+        expect_that!(output.get_pixel(5, 6), eq(image::Rgba([0, 255, 0, 255])));
+    }
+}
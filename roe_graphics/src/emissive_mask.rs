@@ -0,0 +1,18 @@
+use super::{BlendComponent, BlendFactor, BlendOperation};
+
+// Convention restricting bloom to the emissive surfaces: the alpha channel of the scene color
+// buffer holds the fraction of the color emitted by the surface. The scene is cleared with a
+// transparent color, opaque pipelines write the fraction directly, and blended pipelines, e.g.
+// sprites, use this alpha blend component: emissive ones set the mask to their coverage, the
+// others remove the mask beneath them.
+pub fn emissive_mask_alpha_blend(emissive: bool) -> BlendComponent {
+    BlendComponent {
+        src_factor: if emissive {
+            BlendFactor::One
+        } else {
+            BlendFactor::Zero
+        },
+        dst_factor: BlendFactor::OneMinusSrcAlpha,
+        operation: BlendOperation::Add,
+    }
+}
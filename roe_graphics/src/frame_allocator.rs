@@ -0,0 +1,275 @@
+use super::{
+    BindingResource, Buffer, BufferAddress, BufferBinding, BufferDescriptor, BufferSize,
+    BufferSlice, BufferUsage, Instance, COPY_BUFFER_ALIGNMENT,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FrameAllocatorDescriptor {
+    // Size of the buffers allocations are taken from. Larger allocations get a dedicated
+    // buffer.
+    pub page_size: BufferAddress,
+    // Buffers unused for this number of frames are released.
+    pub max_idle_frames: u64,
+}
+
+impl Default for FrameAllocatorDescriptor {
+    fn default() -> Self {
+        Self {
+            page_size: 1 << 20,
+            max_idle_frames: 60,
+        }
+    }
+}
+
+// Region of a buffer of a frame allocator, valid until the end of the frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameAllocation {
+    page: usize,
+    offset: BufferAddress,
+    size: BufferAddress,
+}
+
+impl FrameAllocation {
+    pub fn offset(&self) -> BufferAddress {
+        self.offset
+    }
+
+    pub fn size(&self) -> BufferAddress {
+        self.size
+    }
+}
+
+#[derive(Debug)]
+struct Page {
+    buffer: Buffer,
+    size: BufferAddress,
+    offset: BufferAddress,
+    last_used_frame: u64,
+}
+
+fn align(value: BufferAddress, alignment: BufferAddress) -> BufferAddress {
+    value.div_ceil(alignment) * alignment
+}
+
+// Offset of an allocation in a page, if it fits.
+fn fit(
+    page_offset: BufferAddress,
+    page_size: BufferAddress,
+    size: BufferAddress,
+    alignment: BufferAddress,
+) -> Option<BufferAddress> {
+    let offset = align(page_offset, alignment);
+    if offset + size <= page_size {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+// Allocates short-lived vertex, index and uniform data from a pool of buffers, instead of
+// creating a buffer for each of them every frame. Allocations are valid until finish_frame is
+// called, which should happen after submitting the commands using them: the data written in
+// the next frame is uploaded only after the previously submitted commands.
+#[derive(Debug)]
+pub struct FrameAllocator {
+    desc: FrameAllocatorDescriptor,
+    uniform_alignment: BufferAddress,
+    pages: Vec<Page>,
+    frame: u64,
+}
+
+impl FrameAllocator {
+    pub fn new(instance: &Instance, desc: &FrameAllocatorDescriptor) -> Self {
+        assert!(
+            desc.page_size > 0 && desc.page_size.is_multiple_of(COPY_BUFFER_ALIGNMENT),
+            "The page size must be a positive multiple of {}",
+            COPY_BUFFER_ALIGNMENT
+        );
+        let uniform_alignment = std::cmp::max(
+            instance.limits().min_uniform_buffer_offset_alignment as BufferAddress,
+            COPY_BUFFER_ALIGNMENT,
+        );
+        Self {
+            desc: desc.clone(),
+            uniform_alignment,
+            pages: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    // Writes vertex or index data.
+    pub fn write<T: bytemuck::Pod>(&mut self, instance: &Instance, data: &[T]) -> FrameAllocation {
+        self.write_aligned(instance, bytemuck::cast_slice(data), COPY_BUFFER_ALIGNMENT)
+    }
+
+    // Writes uniform data, aligned so that it can be bound with binding.
+    pub fn write_uniform<T: bytemuck::Pod>(
+        &mut self,
+        instance: &Instance,
+        data: &T,
+    ) -> FrameAllocation {
+        let alignment = self.uniform_alignment;
+        self.write_aligned(instance, bytemuck::bytes_of(data), alignment)
+    }
+
+    pub fn buffer(&self, allocation: &FrameAllocation) -> &Buffer {
+        &self.pages[allocation.page].buffer
+    }
+
+    pub fn slice(&self, allocation: &FrameAllocation) -> BufferSlice<'_> {
+        self.buffer(allocation)
+            .slice(allocation.offset..allocation.offset + allocation.size)
+    }
+
+    pub fn binding(&self, allocation: &FrameAllocation) -> BindingResource<'_> {
+        BindingResource::Buffer(BufferBinding {
+            buffer: self.buffer(allocation),
+            offset: allocation.offset,
+            size: BufferSize::new(allocation.size),
+        })
+    }
+
+    // Invalidates the allocations of the frame and releases the buffers that have been idle
+    // for too long.
+    pub fn finish_frame(&mut self) {
+        for page in self.pages.iter_mut() {
+            if page.offset > 0 {
+                page.last_used_frame = self.frame;
+                page.offset = 0;
+            }
+        }
+        let frame = self.frame;
+        let max_idle_frames = self.desc.max_idle_frames;
+        self.pages
+            .retain(|page| frame - page.last_used_frame <= max_idle_frames);
+        self.frame += 1;
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    // Bytes allocated in the current frame, including padding.
+    pub fn allocated_size(&self) -> BufferAddress {
+        self.pages.iter().map(|page| page.offset).sum()
+    }
+
+    fn write_aligned(
+        &mut self,
+        instance: &Instance,
+        bytes: &[u8],
+        alignment: BufferAddress,
+    ) -> FrameAllocation {
+        // Buffer writes must have a size multiple of the copy alignment.
+        let size = align(bytes.len() as BufferAddress, COPY_BUFFER_ALIGNMENT);
+        let allocation = self.allocate(instance, size, alignment);
+        if size == bytes.len() as BufferAddress {
+            instance.write_buffer(self.buffer(&allocation), allocation.offset, bytes);
+        } else {
+            let mut padded = bytes.to_vec();
+            padded.resize(size as usize, 0);
+            instance.write_buffer(self.buffer(&allocation), allocation.offset, &padded);
+        }
+        FrameAllocation {
+            size: bytes.len() as BufferAddress,
+            ..allocation
+        }
+    }
+
+    fn allocate(
+        &mut self,
+        instance: &Instance,
+        size: BufferAddress,
+        alignment: BufferAddress,
+    ) -> FrameAllocation {
+        // Zero sized bindings aren't allowed.
+        let size = std::cmp::max(size, COPY_BUFFER_ALIGNMENT);
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            if let Some(offset) = fit(page.offset, page.size, size, alignment) {
+                page.offset = offset + size;
+                return FrameAllocation {
+                    page: i,
+                    offset,
+                    size,
+                };
+            }
+        }
+        let page_size = std::cmp::max(self.desc.page_size, size);
+        let buffer = Buffer::new(
+            instance,
+            &BufferDescriptor {
+                label: Some("frame_allocator_page"),
+                size: page_size,
+                usage: BufferUsage::VERTEX
+                    | BufferUsage::INDEX
+                    | BufferUsage::UNIFORM
+                    | BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        self.pages.push(Page {
+            buffer,
+            size: page_size,
+            offset: size,
+            last_used_frame: self.frame,
+        });
+        FrameAllocation {
+            page: self.pages.len() - 1,
+            offset: 0,
+            size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use crate::InstanceDescriptor;
+
+    #[test]
+    fn fitting() {
+        expect_that!(&align(0, 4), eq(0));
+        expect_that!(&align(5, 4), eq(8));
+        expect_that!(&align(256, 256), eq(256));
+        expect_that!(&fit(0, 64, 64, 4), eq(Some(0)));
+        expect_that!(&fit(6, 64, 16, 4), eq(Some(8)));
+        expect_that!(&fit(8, 64, 60, 4), eq(None));
+        expect_that!(&fit(4, 512, 16, 256), eq(Some(256)));
+        expect_that!(&fit(260, 512, 16, 256), eq(None));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn allocation_and_recycling() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let mut allocator = FrameAllocator::new(
+            &instance,
+            &FrameAllocatorDescriptor {
+                page_size: 1024,
+                max_idle_frames: 2,
+            },
+        );
+        let a = allocator.write(&instance, &[1u16, 2, 3]);
+        expect_that!(&a.offset(), eq(0));
+        expect_that!(&a.size(), eq(6));
+        let b = allocator.write(&instance, &[1f32, 2.]);
+        expect_that!(&b.offset(), eq(8));
+        let c = allocator.write(&instance, &[0u8; 2048]);
+        expect_that!(&c.size(), eq(2048));
+        expect_that!(&allocator.page_count(), eq(2));
+
+        allocator.finish_frame();
+        expect_that!(&allocator.allocated_size(), eq(0));
+        let d = allocator.write(&instance, &[1u32]);
+        expect_that!(&d.offset(), eq(0));
+
+        // The large page stays idle and is eventually released.
+        for _ in 0..4 {
+            allocator.write(&instance, &[1u32]);
+            allocator.finish_frame();
+        }
+        expect_that!(&allocator.page_count(), eq(1));
+    }
+}
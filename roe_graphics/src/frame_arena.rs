@@ -0,0 +1,136 @@
+use bumpalo::Bump;
+
+pub type ArenaVec<'a, T> = bumpalo::collections::Vec<'a, T>;
+pub type ArenaString<'a> = bumpalo::collections::String<'a>;
+
+// Bump allocator for temporary CPU data built every frame, e.g. draw lists, text layouts and
+// particle scratch buffers. Allocations only move a pointer, and the memory is reused in the
+// next frame after finish_frame is called, usually after submitting the frame. Once the arena
+// has grown to the size of a typical frame, no more heap allocations happen.
+// Each CanvasWindow owns an arena, finished when the next frame is requested.
+// The destructors of values allocated with alloc aren't run, arena vectors and strings drop
+// their content as usual.
+#[derive(Debug, Default)]
+pub struct FrameArena {
+    bump: Bump,
+    last_frame_size: usize,
+    peak_frame_size: usize,
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Preallocates the given number of bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bump: Bump::with_capacity(capacity),
+            ..Self::default()
+        }
+    }
+
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        self.bump.alloc(value)
+    }
+
+    pub fn alloc_slice_copy<T: Copy>(&self, values: &[T]) -> &mut [T] {
+        self.bump.alloc_slice_copy(values)
+    }
+
+    pub fn alloc_str(&self, value: &str) -> &mut str {
+        self.bump.alloc_str(value)
+    }
+
+    pub fn vec<T>(&self) -> ArenaVec<'_, T> {
+        ArenaVec::new_in(&self.bump)
+    }
+
+    pub fn vec_with_capacity<T>(&self, capacity: usize) -> ArenaVec<'_, T> {
+        ArenaVec::with_capacity_in(capacity, &self.bump)
+    }
+
+    pub fn vec_from_iter<T, I: IntoIterator<Item = T>>(&self, iter: I) -> ArenaVec<'_, T> {
+        ArenaVec::from_iter_in(iter, &self.bump)
+    }
+
+    pub fn string(&self) -> ArenaString<'_> {
+        ArenaString::new_in(&self.bump)
+    }
+
+    // Bytes reserved from the heap, used or not.
+    pub fn capacity(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    // Bytes used in the last finished frame, including padding.
+    pub fn last_frame_size(&self) -> usize {
+        self.last_frame_size
+    }
+
+    // Largest number of bytes used in a frame so far.
+    pub fn peak_frame_size(&self) -> usize {
+        self.peak_frame_size
+    }
+
+    // Frees all allocations of the frame. The borrow checker ensures that none of them is still
+    // in use. Only the largest block of memory is kept.
+    pub fn finish_frame(&mut self) {
+        self.last_frame_size = self
+            .bump
+            .iter_allocated_chunks()
+            .map(|chunk| chunk.len())
+            .sum();
+        self.peak_frame_size = std::cmp::max(self.peak_frame_size, self.last_frame_size);
+        self.bump.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn allocation() {
+        let arena = FrameArena::new();
+        let value = arena.alloc(3u32);
+        *value += 1;
+        let slice = arena.alloc_slice_copy(&[1., 2., 3.]);
+        let s = arena.alloc_str("text");
+        let mut v = arena.vec_with_capacity(4);
+        v.extend_from_slice(&[1u16, 2]);
+        let mut string = arena.string();
+        string.push_str("frame");
+        expect_that!(&*value, eq(4));
+        expect_that!(&slice.len(), eq(3));
+        expect_that!(&&*s, eq("text"));
+        expect_that!(&&v[..], eq(&[1u16, 2][..]));
+        expect_that!(&string.as_str(), eq("frame"));
+        expect_that!(&&arena.vec_from_iter(0..3)[..], eq(&[0, 1, 2][..]));
+    }
+
+    #[test]
+    fn frames() {
+        let mut arena = FrameArena::with_capacity(64);
+        expect_that!(arena.capacity() >= 64);
+        expect_that!(&arena.last_frame_size(), eq(0));
+
+        arena.alloc_slice_copy(&[0u8; 1000]);
+        arena.finish_frame();
+        expect_that!(&arena.last_frame_size(), eq(1000));
+        expect_that!(&arena.peak_frame_size(), eq(1000));
+
+        // Frames of the same size reuse the memory.
+        arena.alloc_slice_copy(&[0u8; 100]);
+        arena.finish_frame();
+        let capacity = arena.capacity();
+        for _ in 0..4 {
+            arena.alloc_slice_copy(&[0u8; 100]);
+            arena.finish_frame();
+        }
+        expect_that!(&arena.capacity(), eq(capacity));
+        expect_that!(&arena.last_frame_size(), eq(100));
+        expect_that!(&arena.peak_frame_size(), eq(1000));
+    }
+}
@@ -0,0 +1,145 @@
+use roe_os as os;
+
+use std::path::PathBuf;
+
+// Triggers captures in RenderDoc. Only available when the application is launched from
+// RenderDoc, or when the RenderDoc library is injected into the process.
+#[derive(Debug)]
+pub struct FrameCapture {
+    api: renderdoc::RenderDoc<renderdoc::V110>,
+}
+
+impl FrameCapture {
+    pub fn new() -> Result<Self, FrameCaptureError> {
+        Ok(Self {
+            api: renderdoc::RenderDoc::new()?,
+        })
+    }
+
+    // Captures the next presented frame.
+    pub fn trigger_capture(&mut self) {
+        self.api.trigger_capture();
+    }
+
+    pub fn trigger_multi_frame_capture(&mut self, frame_count: u32) {
+        self.api.trigger_multi_frame_capture(frame_count);
+    }
+
+    pub fn capture_count(&self) -> u32 {
+        self.api.get_num_captures()
+    }
+
+    pub fn capture_path(&self, index: u32) -> Option<PathBuf> {
+        self.api.get_capture(index).map(|(path, _)| path)
+    }
+
+    // Opens the RenderDoc replay UI connected to the application. Returns the process id.
+    pub fn launch_replay_ui(&self) -> Result<u32, FrameCaptureError> {
+        Ok(self.api.launch_replay_ui(true, None)?)
+    }
+}
+
+// Key combination triggering a capture, e.g. from a debug overlay forwarding the key events.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameCaptureKeyBinding {
+    pub key: os::KeyCode,
+    pub modifiers: os::ModifiersState,
+}
+
+impl Default for FrameCaptureKeyBinding {
+    // RenderDoc uses F12 and PrintScreen by default, which would trigger a second capture.
+    fn default() -> Self {
+        Self {
+            key: os::KeyCode::F11,
+            modifiers: os::ModifiersState::empty(),
+        }
+    }
+}
+
+impl FrameCaptureKeyBinding {
+    pub fn matches(&self, input: &os::KeyboardInput, modifiers: os::ModifiersState) -> bool {
+        input.state == os::ElementState::Pressed
+            && input.virtual_keycode == Some(self.key)
+            && modifiers == self.modifiers
+    }
+
+    // Triggers a capture if the input matches the binding. Returns true if it did.
+    pub fn handle_input(
+        &self,
+        capture: &mut FrameCapture,
+        input: &os::KeyboardInput,
+        modifiers: os::ModifiersState,
+    ) -> bool {
+        let matches = self.matches(input, modifiers);
+        if matches {
+            capture.trigger_capture();
+        }
+        matches
+    }
+}
+
+#[derive(Debug)]
+pub enum FrameCaptureError {
+    RenderDocError(renderdoc::Error),
+}
+
+impl std::fmt::Display for FrameCaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameCaptureError::RenderDocError(e) => write!(f, "RenderDoc error ({})", e),
+        }
+    }
+}
+
+impl std::error::Error for FrameCaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FrameCaptureError::RenderDocError(e) => Some(e),
+        }
+    }
+}
+
+impl From<renderdoc::Error> for FrameCaptureError {
+    fn from(e: renderdoc::Error) -> Self {
+        FrameCaptureError::RenderDocError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::*;
+
+    #[allow(deprecated)]
+    fn key_input(key: os::KeyCode, state: os::ElementState) -> os::KeyboardInput {
+        os::KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: os::ModifiersState::empty(),
+        }
+    }
+
+    #[test]
+    fn key_binding() {
+        let binding = FrameCaptureKeyBinding {
+            key: os::KeyCode::C,
+            modifiers: os::ModifiersState::CTRL,
+        };
+        let pressed = key_input(os::KeyCode::C, os::ElementState::Pressed);
+        expect_that!(binding.matches(&pressed, os::ModifiersState::CTRL));
+        expect_that!(!binding.matches(&pressed, os::ModifiersState::empty()));
+        expect_that!(!binding.matches(
+            &pressed,
+            os::ModifiersState::CTRL | os::ModifiersState::SHIFT
+        ));
+        expect_that!(!binding.matches(
+            &key_input(os::KeyCode::C, os::ElementState::Released),
+            os::ModifiersState::CTRL
+        ));
+        expect_that!(!binding.matches(
+            &key_input(os::KeyCode::V, os::ElementState::Pressed),
+            os::ModifiersState::CTRL
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+use image::RgbaImage;
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GoldenImageDescriptor {
+    // Maximum difference of each channel for two pixels to be considered equal.
+    pub channel_tolerance: u8,
+    // Maximum fraction of differing pixels, between 0 and 1.
+    pub max_differing_pixels: f32,
+    // When the comparison fails, the result, expected and diff images are saved here.
+    pub diff_dir: Option<PathBuf>,
+}
+
+impl Default for GoldenImageDescriptor {
+    fn default() -> Self {
+        Self {
+            channel_tolerance: 2,
+            max_differing_pixels: 0.001,
+            diff_dir: Some(std::env::temp_dir().join("roe_golden_images")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ImageComparison {
+    pub pixel_count: u32,
+    pub differing_pixel_count: u32,
+    pub max_channel_difference: u8,
+}
+
+impl ImageComparison {
+    pub fn differing_pixel_ratio(&self) -> f32 {
+        if self.pixel_count == 0 {
+            0.
+        } else {
+            self.differing_pixel_count as f32 / self.pixel_count as f32
+        }
+    }
+
+    pub fn passes(&self, desc: &GoldenImageDescriptor) -> bool {
+        self.differing_pixel_ratio() <= desc.max_differing_pixels
+    }
+}
+
+fn channel_difference(a: &image::Rgba<u8>, b: &image::Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or(0)
+}
+
+// The images must have the same size.
+pub fn compare_images(result: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> ImageComparison {
+    assert!(
+        result.dimensions() == expected.dimensions(),
+        "The images must have the same size"
+    );
+    let mut comparison = ImageComparison {
+        pixel_count: result.width() * result.height(),
+        differing_pixel_count: 0,
+        max_channel_difference: 0,
+    };
+    for (a, b) in result.pixels().zip(expected.pixels()) {
+        let difference = channel_difference(a, b);
+        comparison.max_channel_difference =
+            std::cmp::max(comparison.max_channel_difference, difference);
+        if difference > tolerance {
+            comparison.differing_pixel_count += 1;
+        }
+    }
+    comparison
+}
+
+// Differing pixels are red, the others are a faded grayscale version of the expected image.
+pub fn diff_image(result: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> RgbaImage {
+    assert!(
+        result.dimensions() == expected.dimensions(),
+        "The images must have the same size"
+    );
+    RgbaImage::from_fn(result.width(), result.height(), |x, y| {
+        let a = result.get_pixel(x, y);
+        let b = expected.get_pixel(x, y);
+        if channel_difference(a, b) > tolerance {
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (b.0[0] as u32 * 3 + b.0[1] as u32 * 6 + b.0[2] as u32) / 10;
+            let faded = (luma / 4 + 192) as u8;
+            image::Rgba([faded, faded, faded, 255])
+        }
+    })
+}
+
+// Compares the result against the image stored at the path, saving the diff images to the diff
+// directory on failure. Meant for rendering tests, where results vary slightly across drivers.
+pub fn check_golden_image<P: AsRef<Path>>(
+    result: &RgbaImage,
+    expected_path: P,
+    desc: &GoldenImageDescriptor,
+) -> Result<ImageComparison, GoldenImageError> {
+    let expected_path = expected_path.as_ref();
+    let expected = image::open(expected_path)?.into_rgba8();
+    if result.dimensions() != expected.dimensions() {
+        save_failure_images(result, &expected, expected_path, desc)?;
+        return Err(GoldenImageError::SizeMismatch(
+            result.dimensions(),
+            expected.dimensions(),
+        ));
+    }
+    let comparison = compare_images(result, &expected, desc.channel_tolerance);
+    if comparison.passes(desc) {
+        Ok(comparison)
+    } else {
+        save_failure_images(result, &expected, expected_path, desc)?;
+        Err(GoldenImageError::Mismatch(comparison))
+    }
+}
+
+fn save_failure_images(
+    result: &RgbaImage,
+    expected: &RgbaImage,
+    expected_path: &Path,
+    desc: &GoldenImageDescriptor,
+) -> Result<(), GoldenImageError> {
+    let dir = match &desc.diff_dir {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    std::fs::create_dir_all(dir)?;
+    let name = expected_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    result.save(dir.join(format!("{}.result.png", name)))?;
+    expected.save(dir.join(format!("{}.expected.png", name)))?;
+    if result.dimensions() == expected.dimensions() {
+        diff_image(result, expected, desc.channel_tolerance)
+            .save(dir.join(format!("{}.diff.png", name)))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum GoldenImageError {
+    IoError(std::io::Error),
+    ImageError(image::ImageError),
+    // Result and expected size.
+    SizeMismatch((u32, u32), (u32, u32)),
+    Mismatch(ImageComparison),
+}
+
+impl std::fmt::Display for GoldenImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GoldenImageError::IoError(e) => write!(f, "I/O error ({})", e),
+            GoldenImageError::ImageError(e) => write!(f, "Image error ({})", e),
+            GoldenImageError::SizeMismatch(result, expected) => write!(
+                f,
+                "Image size mismatch (result: {:?}, expected: {:?})",
+                result, expected
+            ),
+            GoldenImageError::Mismatch(c) => write!(
+                f,
+                "Image mismatch ({} of {} pixels differ, max channel difference {})",
+                c.differing_pixel_count, c.pixel_count, c.max_channel_difference
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GoldenImageError::IoError(e) => Some(e),
+            GoldenImageError::ImageError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GoldenImageError {
+    fn from(e: std::io::Error) -> Self {
+        GoldenImageError::IoError(e)
+    }
+}
+
+impl From<image::ImageError> for GoldenImageError {
+    fn from(e: image::ImageError) -> Self {
+        GoldenImageError::ImageError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(10, 10, |x, y| {
+            image::Rgba([(x * 20) as u8, (y * 20) as u8, 100, 255])
+        })
+    }
+
+    #[test]
+    fn comparison() {
+        let expected = gradient();
+        let mut result = expected.clone();
+        result.put_pixel(1, 1, image::Rgba([22, 20, 100, 255]));
+        result.put_pixel(2, 2, image::Rgba([40, 40, 110, 255]));
+        let comparison = compare_images(&result, &expected, 2);
+        expect_that!(&comparison.pixel_count, eq(100));
+        expect_that!(&comparison.differing_pixel_count, eq(1));
+        expect_that!(&comparison.max_channel_difference, eq(10));
+        expect_that!(&comparison.differing_pixel_ratio(), eq(0.01));
+
+        let diff = diff_image(&result, &expected, 2);
+        expect_that!(diff.get_pixel(2, 2), eq(image::Rgba([255, 0, 0, 255])));
+        expect_that!(diff.get_pixel(1, 1) != &image::Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn golden_image_check() {
+        let dir = std::env::temp_dir().join("roe_graphics_golden_image_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let expected_path = dir.join("gradient.png");
+        gradient().save(&expected_path).unwrap();
+        let desc = GoldenImageDescriptor {
+            max_differing_pixels: 0.02,
+            diff_dir: Some(dir.join("diff")),
+            ..GoldenImageDescriptor::default()
+        };
+
+        let mut result = gradient();
+        result.put_pixel(0, 0, image::Rgba([255, 255, 255, 255]));
+        expect_that!(
+            &check_golden_image(&result, &expected_path, &desc),
+            is_variant!(Result::Ok)
+        );
+        expect_that!(!dir.join("diff").exists());
+
+        result.put_pixel(0, 1, image::Rgba([255, 255, 255, 255]));
+        result.put_pixel(0, 2, image::Rgba([255, 255, 255, 255]));
+        expect_that!(
+            &check_golden_image(&result, &expected_path, &desc),
+            is_variant!(Result::Err)
+        );
+        expect_that!(dir.join("diff/gradient.diff.png").exists());
+        expect_that!(dir.join("diff/gradient.result.png").exists());
+
+        let small = RgbaImage::new(4, 4);
+        match check_golden_image(&small, &expected_path, &desc) {
+            Err(GoldenImageError::SizeMismatch(result, expected)) => {
+                expect_that!(&result, eq((4, 4)));
+                expect_that!(&expected, eq((10, 10)));
+            }
+            _ => panic!("Expected a size mismatch"),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,463 @@
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "serde-serialize")]
+use roe_platform as platform;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum MsaaLevel {
+    #[default]
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl MsaaLevel {
+    pub fn sample_count(&self) -> u32 {
+        match self {
+            Self::Off => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+            Self::X8 => 8,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    #[default]
+    Medium,
+    High,
+    Ultra,
+}
+
+// Post process anti-aliasing, independent of the MSAA level.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum AntiAliasingMode {
+    #[default]
+    Off,
+    // Fast approximate anti-aliasing, smoothing the edges detected in the final image.
+    Fxaa,
+    // Temporal anti-aliasing, accumulating jittered frames into a history buffer.
+    Taa,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct PostEffectSettings {
+    pub bloom: bool,
+    pub anti_aliasing: AntiAliasingMode,
+}
+
+impl PostEffectSettings {
+    pub const NONE: Self = Self {
+        bloom: false,
+        anti_aliasing: AntiAliasingMode::Off,
+    };
+    pub const ALL: Self = Self {
+        bloom: true,
+        anti_aliasing: AntiAliasingMode::Taa,
+    };
+}
+
+impl Default for PostEffectSettings {
+    fn default() -> Self {
+        GraphicsSettings::from_preset(QualityPreset::default()).post_effects
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Ultra,
+}
+
+impl QualityPreset {
+    pub const ALL: [Self; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+}
+
+bitflags::bitflags! {
+    // Groups of settings, each one usually handled by a different subsystem.
+    pub struct GraphicsSettingsChanges : u32 {
+        const MSAA = 0x01;
+        const SHADOWS = 0x02;
+        const POST_EFFECTS = 0x04;
+        const RESOLUTION_SCALE = 0x08;
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct GraphicsSettings {
+    pub msaa: MsaaLevel,
+    pub shadow_quality: ShadowQuality,
+    pub post_effects: PostEffectSettings,
+    // Size of the 3D render targets relative to the window, upscaled when presenting.
+    pub resolution_scale: f32,
+}
+
+impl GraphicsSettings {
+    pub const MIN_RESOLUTION_SCALE: f32 = 0.25;
+    pub const MAX_RESOLUTION_SCALE: f32 = 2.;
+
+    // Section of the settings store the graphics settings are saved to.
+    pub const SETTINGS_SECTION: &'static str = "graphics";
+
+    // Every setting takes the value of the preset.
+    pub fn from_preset(preset: QualityPreset) -> Self {
+        match preset {
+            QualityPreset::Low => Self {
+                msaa: MsaaLevel::Off,
+                shadow_quality: ShadowQuality::Low,
+                post_effects: PostEffectSettings::NONE,
+                resolution_scale: 0.75,
+            },
+            QualityPreset::Medium => Self {
+                msaa: MsaaLevel::X2,
+                shadow_quality: ShadowQuality::Medium,
+                post_effects: PostEffectSettings {
+                    anti_aliasing: AntiAliasingMode::Fxaa,
+                    ..PostEffectSettings::NONE
+                },
+                resolution_scale: 1.,
+            },
+            QualityPreset::High => Self {
+                msaa: MsaaLevel::X4,
+                shadow_quality: ShadowQuality::High,
+                post_effects: PostEffectSettings {
+                    anti_aliasing: AntiAliasingMode::Fxaa,
+                    ..PostEffectSettings::ALL
+                },
+                resolution_scale: 1.,
+            },
+            QualityPreset::Ultra => Self {
+                msaa: MsaaLevel::X8,
+                shadow_quality: ShadowQuality::Ultra,
+                post_effects: PostEffectSettings::ALL,
+                resolution_scale: 1.,
+            },
+        }
+    }
+
+    // None if some setting was customized.
+    pub fn matching_preset(&self) -> Option<QualityPreset> {
+        QualityPreset::ALL
+            .iter()
+            .copied()
+            .find(|preset| Self::from_preset(*preset) == *self)
+    }
+
+    // Values out of range are clamped.
+    pub fn sanitized(mut self) -> Self {
+        self.resolution_scale = if self.resolution_scale.is_finite() {
+            self.resolution_scale
+                .clamp(Self::MIN_RESOLUTION_SCALE, Self::MAX_RESOLUTION_SCALE)
+        } else {
+            1.
+        };
+        self
+    }
+
+    // Groups of settings differing between the two.
+    pub fn changes(&self, other: &Self) -> GraphicsSettingsChanges {
+        let mut changes = GraphicsSettingsChanges::empty();
+        changes.set(GraphicsSettingsChanges::MSAA, self.msaa != other.msaa);
+        changes.set(
+            GraphicsSettingsChanges::SHADOWS,
+            self.shadow_quality != other.shadow_quality,
+        );
+        changes.set(
+            GraphicsSettingsChanges::POST_EFFECTS,
+            self.post_effects != other.post_effects,
+        );
+        changes.set(
+            GraphicsSettingsChanges::RESOLUTION_SCALE,
+            self.resolution_scale != other.resolution_scale,
+        );
+        changes
+    }
+
+    // Default settings if the store has no graphics section.
+    #[cfg(feature = "serde-serialize")]
+    pub fn load_from(store: &platform::SettingsStore) -> Result<Self, platform::Error> {
+        Ok(store
+            .section::<Self>(Self::SETTINGS_SECTION)?
+            .unwrap_or_default()
+            .sanitized())
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    pub fn store_to(&self, store: &mut platform::SettingsStore) -> Result<(), platform::Error> {
+        store.set_section(Self::SETTINGS_SECTION, self)
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self::from_preset(QualityPreset::default())
+    }
+}
+
+#[derive(Debug)]
+struct SettingsState {
+    settings: GraphicsSettings,
+    revision: u64,
+}
+
+// Graphics settings changed at runtime, e.g. from an options menu. The subsystems subscribe to
+// the groups of settings they depend on, and poll for changes when they can apply them, e.g. at
+// the start of a frame. The settings can be cloned and moved to other threads, the clones share
+// the same values.
+#[derive(Debug, Clone)]
+pub struct RuntimeGraphicsSettings {
+    state: Arc<Mutex<SettingsState>>,
+}
+
+impl RuntimeGraphicsSettings {
+    pub fn new(settings: GraphicsSettings) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SettingsState {
+                settings: settings.sanitized(),
+                revision: 0,
+            })),
+        }
+    }
+
+    pub fn settings(&self) -> GraphicsSettings {
+        self.state.lock().unwrap().settings
+    }
+
+    // Increased every time the settings change.
+    pub fn revision(&self) -> u64 {
+        self.state.lock().unwrap().revision
+    }
+
+    // Returns the changed groups of settings.
+    pub fn set(&self, settings: GraphicsSettings) -> GraphicsSettingsChanges {
+        let settings = settings.sanitized();
+        let mut state = self.state.lock().unwrap();
+        let changes = state.settings.changes(&settings);
+        if !changes.is_empty() {
+            state.settings = settings;
+            state.revision += 1;
+        }
+        changes
+    }
+
+    pub fn modify<F: FnOnce(&mut GraphicsSettings)>(&self, f: F) -> GraphicsSettingsChanges {
+        let mut settings = self.settings();
+        f(&mut settings);
+        self.set(settings)
+    }
+
+    pub fn apply_preset(&self, preset: QualityPreset) -> GraphicsSettingsChanges {
+        self.set(GraphicsSettings::from_preset(preset))
+    }
+
+    // The subscriber starts from the current settings.
+    pub fn subscribe(&self, interests: GraphicsSettingsChanges) -> GraphicsSettingsSubscriber {
+        GraphicsSettingsSubscriber {
+            source: self.clone(),
+            interests,
+            applied: self.settings(),
+        }
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    pub fn load_from(store: &platform::SettingsStore) -> Result<Self, platform::Error> {
+        Ok(Self::new(GraphicsSettings::load_from(store)?))
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    pub fn store_to(&self, store: &mut platform::SettingsStore) -> Result<(), platform::Error> {
+        self.settings().store_to(store)
+    }
+}
+
+impl Default for RuntimeGraphicsSettings {
+    fn default() -> Self {
+        Self::new(GraphicsSettings::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GraphicsSettingsUpdate {
+    pub settings: GraphicsSettings,
+    // Only the groups the subscriber is interested in.
+    pub changes: GraphicsSettingsChanges,
+}
+
+#[derive(Debug)]
+pub struct GraphicsSettingsSubscriber {
+    source: RuntimeGraphicsSettings,
+    interests: GraphicsSettingsChanges,
+    applied: GraphicsSettings,
+}
+
+impl GraphicsSettingsSubscriber {
+    pub fn interests(&self) -> GraphicsSettingsChanges {
+        self.interests
+    }
+
+    // Settings as of the last poll.
+    pub fn settings(&self) -> &GraphicsSettings {
+        &self.applied
+    }
+
+    // Settings changed since the last poll, if the change concerns the subscriber. Changes to
+    // other groups of settings are ignored.
+    pub fn poll(&mut self) -> Option<GraphicsSettingsUpdate> {
+        let settings = self.source.settings();
+        let changes = self.applied.changes(&settings) & self.interests;
+        self.applied = settings;
+        if changes.is_empty() {
+            None
+        } else {
+            Some(GraphicsSettingsUpdate { settings, changes })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn presets() {
+        for preset in QualityPreset::ALL {
+            expect_that!(
+                &GraphicsSettings::from_preset(preset).matching_preset(),
+                eq(Some(preset))
+            );
+        }
+        let low = GraphicsSettings::from_preset(QualityPreset::Low);
+        let ultra = GraphicsSettings::from_preset(QualityPreset::Ultra);
+        expect_that!(&low.changes(&ultra), eq(GraphicsSettingsChanges::all()));
+        expect_that!(&ultra.msaa.sample_count(), eq(8));
+        expect_that!(&ultra.shadow_quality, eq(ShadowQuality::Ultra));
+        expect_that!(&ultra.post_effects.anti_aliasing, eq(AntiAliasingMode::Taa));
+
+        let custom = GraphicsSettings {
+            resolution_scale: 0.5,
+            ..GraphicsSettings::default()
+        };
+        expect_that!(&custom.matching_preset(), eq(None));
+        expect_that!(
+            &custom.changes(&GraphicsSettings::default()),
+            eq(GraphicsSettingsChanges::RESOLUTION_SCALE)
+        );
+        let clamped = GraphicsSettings {
+            resolution_scale: 10.,
+            ..GraphicsSettings::default()
+        }
+        .sanitized();
+        expect_that!(&clamped.resolution_scale, eq(2.));
+    }
+
+    #[test]
+    fn subscription() {
+        let settings = RuntimeGraphicsSettings::default();
+        let mut shadows = settings.subscribe(GraphicsSettingsChanges::SHADOWS);
+        let mut targets = settings
+            .subscribe(GraphicsSettingsChanges::MSAA | GraphicsSettingsChanges::RESOLUTION_SCALE);
+        expect_that!(&shadows.poll(), eq(None));
+
+        let revision = settings.revision();
+        let changes = settings.modify(|s| s.resolution_scale = 0.5);
+        expect_that!(&changes, eq(GraphicsSettingsChanges::RESOLUTION_SCALE));
+        expect_that!(settings.revision() > revision);
+        expect_that!(&shadows.poll(), eq(None));
+        let update = targets.poll().unwrap();
+        expect_that!(
+            &update.changes,
+            eq(GraphicsSettingsChanges::RESOLUTION_SCALE)
+        );
+        expect_that!(&update.settings.resolution_scale, eq(0.5));
+        expect_that!(&targets.poll(), eq(None));
+
+        // Setting the same values doesn't notify.
+        let revision = settings.revision();
+        expect_that!(settings.set(settings.settings()).is_empty());
+        expect_that!(&settings.revision(), eq(revision));
+
+        settings.clone().apply_preset(QualityPreset::Low);
+        expect_that!(
+            &shadows.poll().unwrap().changes,
+            eq(GraphicsSettingsChanges::SHADOWS)
+        );
+        expect_that!(
+            &targets.poll().unwrap().changes,
+            eq(GraphicsSettingsChanges::MSAA | GraphicsSettingsChanges::RESOLUTION_SCALE)
+        );
+        expect_that!(
+            &targets.settings().matching_preset(),
+            eq(Some(QualityPreset::Low))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde-serialize")]
+    fn serialization() {
+        let mut store = platform::SettingsStore::new();
+        expect_that!(
+            &GraphicsSettings::load_from(&store).unwrap(),
+            eq(GraphicsSettings::default())
+        );
+
+        let settings = RuntimeGraphicsSettings::new(GraphicsSettings {
+            resolution_scale: 0.8,
+            ..GraphicsSettings::from_preset(QualityPreset::Medium)
+        });
+        settings.store_to(&mut store).unwrap();
+        let store = platform::SettingsStore::from_ron_str(&store.to_ron_string().unwrap()).unwrap();
+        expect_that!(
+            &RuntimeGraphicsSettings::load_from(&store)
+                .unwrap()
+                .settings(),
+            eq(settings.settings())
+        );
+
+        // Missing settings take the default values, values out of range are clamped.
+        let store = platform::SettingsStore::from_ron_str(
+            r#"(sections: {"graphics": "(msaa: X2, resolution_scale: 5.)"})"#,
+        )
+        .unwrap();
+        let partial = GraphicsSettings::load_from(&store).unwrap();
+        expect_that!(&partial.msaa, eq(MsaaLevel::X2));
+        expect_that!(
+            &partial.shadow_quality,
+            eq(GraphicsSettings::default().shadow_quality)
+        );
+        expect_that!(&partial.resolution_scale, eq(2.));
+    }
+}
@@ -2,19 +2,21 @@ pub use wgpu::{
     include_spirv, util::BufferInitDescriptor, AdapterInfo, AddressMode, Backends as Backend,
     BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
     BindingResource, BindingType, BlendComponent, BlendFactor, BlendOperation, BlendState,
-    BufferAddress, BufferDescriptor, BufferSlice, BufferUsages as BufferUsage, ColorTargetState,
-    ColorWrites as ColorWrite, CommandBuffer, CommandEncoderDescriptor, CompareFunction,
-    DepthStencilState, Extent3d, Face, Features, FilterMode, FragmentState, FrontFace,
-    ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, IndexFormat, Limits, LoadOp, Maintain,
-    MapMode, MultisampleState, Operations, Origin3d, PipelineLayoutDescriptor, PolygonMode,
-    PowerPreference, PresentMode, PrimitiveState, PrimitiveTopology, PushConstantRange,
-    RenderBundleEncoderDescriptor, RenderPass, RenderPassColorAttachment,
-    RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipelineDescriptor,
-    SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages as ShaderStage,
-    SurfaceConfiguration, SurfaceError, SurfaceTexture, TextureAspect, TextureDescriptor,
-    TextureDimension, TextureFormat, TextureSampleType, TextureUsages as TextureUsage, TextureView,
-    TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexFormat,
-    VertexState, VertexStepMode,
+    BufferAddress, BufferBinding, BufferBindingType, BufferDescriptor, BufferSize, BufferSlice,
+    BufferUsages as BufferUsage, ColorTargetState, ColorWrites as ColorWrite, CommandBuffer,
+    CommandEncoderDescriptor, CompareFunction, ComputePass, ComputePassDescriptor,
+    ComputePipelineDescriptor, DepthBiasState, DepthStencilState, Extent3d, Face, Features,
+    FilterMode, FragmentState, FrontFace, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    IndexFormat, Limits, LoadOp, Maintain, MapMode, MultisampleState, Operations, Origin3d,
+    PipelineLayoutDescriptor, PolygonMode, PowerPreference, PresentMode, PrimitiveState,
+    PrimitiveTopology, PushConstantRange, RenderBundleEncoderDescriptor, RenderPass,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPassDescriptor,
+    RenderPipelineDescriptor, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages as ShaderStage, StencilState, SurfaceConfiguration, SurfaceError, SurfaceTexture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsages as TextureUsage, TextureView, TextureViewDescriptor, TextureViewDimension,
+    VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+    COPY_BUFFER_ALIGNMENT,
 };
 
 pub mod utility;
@@ -42,3 +44,41 @@ pub use command_sequence::*;
 
 mod mesh;
 pub use mesh::*;
+
+mod frame_allocator;
+pub use frame_allocator::*;
+
+mod frame_arena;
+pub use frame_arena::*;
+
+mod mesh_builder;
+pub use mesh_builder::*;
+
+mod texture_blitter;
+pub use texture_blitter::*;
+
+mod editable_texture;
+pub use editable_texture::*;
+
+mod pipeline_cache;
+pub use pipeline_cache::*;
+
+mod color_workflow;
+pub use color_workflow::*;
+
+mod color_picker;
+pub use color_picker::*;
+
+mod golden_image;
+pub use golden_image::*;
+
+mod graphics_settings;
+pub use graphics_settings::*;
+
+mod emissive_mask;
+pub use emissive_mask::*;
+
+#[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+mod frame_capture;
+#[cfg(all(feature = "renderdoc", not(target_arch = "wasm32")))]
+pub use frame_capture::*;
@@ -1,13 +1,15 @@
 use super::{
     AdapterInfo, Backend, BindGroupDescriptor, BindGroupLayoutDescriptor, BufferAddress,
-    BufferDescriptor, BufferInitDescriptor, BufferUsage, ColorF64, CommandBuffer,
-    CommandEncoderDescriptor, Extent3d, Features, ImageCopyBuffer, ImageCopyTexture,
-    ImageDataLayout, Limits, Maintain, MapMode, Operations, Origin3d, PipelineLayoutDescriptor,
-    PowerPreference, RenderBundleEncoderDescriptor, RenderPipelineDescriptor, SamplerDescriptor,
-    ShaderModuleDescriptor, SurfaceConfiguration, SurfaceError, SurfaceTexture, TextureAspect,
-    TextureDescriptor, TextureDimension, TextureFormat, TextureUsage,
+    BufferDescriptor, BufferInitDescriptor, BufferUsage, ColorF64, ColorWorkflow, CommandBuffer,
+    CommandEncoderDescriptor, ComputePipelineDescriptor, Extent3d, Features, ImageCopyBuffer,
+    ImageCopyTexture, ImageDataLayout, Limits, Maintain, MapMode, Operations, Origin3d,
+    PipelineLayoutDescriptor, PowerPreference, RenderBundleEncoderDescriptor,
+    RenderPipelineDescriptor, SamplerDescriptor, ShaderModuleDescriptor, SurfaceConfiguration,
+    SurfaceError, SurfaceTexture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureRegion, TextureUsage,
 };
 
+use roe_assets as assets;
 use roe_os as os;
 
 use wgpu::util::DeviceExt;
@@ -31,6 +33,7 @@ pub struct InstanceDescriptor {
     pub required_features: Features,
     pub optional_features: Features,
     pub required_limits: Limits,
+    pub color_workflow: ColorWorkflow,
 }
 
 impl InstanceDescriptor {
@@ -41,8 +44,10 @@ impl InstanceDescriptor {
             backend: Backend::PRIMARY,
             power_preference: PowerPreference::HighPerformance,
             required_features: Features::default() | Features::PUSH_CONSTANTS,
-            optional_features: Features::empty(),
+            optional_features: Features::TEXTURE_COMPRESSION_BC
+                | Features::TEXTURE_COMPRESSION_ETC2,
             required_limits,
+            color_workflow: ColorWorkflow::default(),
         }
     }
 }
@@ -55,18 +60,29 @@ impl Default for InstanceDescriptor {
             backend: Backend::PRIMARY,
             power_preference: PowerPreference::HighPerformance,
             required_features: Features::default() | Features::PUSH_CONSTANTS,
-            optional_features: Features::empty(),
+            optional_features: Features::TEXTURE_COMPRESSION_BC
+                | Features::TEXTURE_COMPRESSION_ETC2,
             required_limits,
+            color_workflow: ColorWorkflow::default(),
         }
     }
 }
 
+// Texture compression formats enabled in the features.
+pub fn asset_capabilities(features: Features) -> assets::AssetCapabilities {
+    assets::AssetCapabilities {
+        texture_compression_bc: features.contains(Features::TEXTURE_COMPRESSION_BC),
+        texture_compression_etc2: features.contains(Features::TEXTURE_COMPRESSION_ETC2),
+    }
+}
+
 #[derive(Debug)]
 pub struct Instance {
     queue: wgpu::Queue,
     device: wgpu::Device,
     adapter: wgpu::Adapter,
     instance: wgpu::Instance,
+    color_workflow: ColorWorkflow,
 }
 
 impl Instance {
@@ -79,6 +95,7 @@ impl Instance {
             adapter,
             device,
             instance,
+            color_workflow: desc.color_workflow,
         })
     }
 
@@ -97,6 +114,7 @@ impl Instance {
                 adapter,
                 device,
                 instance,
+                color_workflow: desc.color_workflow,
             },
             Surface { value: surface },
         ))
@@ -106,6 +124,10 @@ impl Instance {
         self.adapter.get_info()
     }
 
+    pub fn color_workflow(&self) -> ColorWorkflow {
+        self.color_workflow
+    }
+
     pub fn poll(&self, maintain: Maintain) {
         self.device.poll(maintain)
     }
@@ -114,6 +136,11 @@ impl Instance {
         self.device.features()
     }
 
+    // Selects the texture variants supported by the device when loading assets.
+    pub fn asset_capabilities(&self) -> assets::AssetCapabilities {
+        asset_capabilities(self.features())
+    }
+
     pub fn limits(&self) -> Limits {
         self.device.limits()
     }
@@ -287,6 +314,32 @@ impl DerefMut for RenderPipeline {
     }
 }
 
+#[derive(Debug)]
+pub struct ComputePipeline {
+    value: wgpu::ComputePipeline,
+}
+
+impl ComputePipeline {
+    pub fn new(instance: &Instance, desc: &ComputePipelineDescriptor) -> Self {
+        Self {
+            value: instance.device.create_compute_pipeline(desc),
+        }
+    }
+}
+
+impl Deref for ComputePipeline {
+    type Target = wgpu::ComputePipeline;
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl DerefMut for ComputePipeline {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.value
+    }
+}
+
 #[derive(Debug)]
 pub struct Buffer {
     value: wgpu::Buffer,
@@ -459,6 +512,10 @@ impl TextureBufferSize {
 pub struct Texture {
     value: wgpu::Texture,
     size: Extent3d,
+    mip_level_count: u32,
+    sample_count: SampleCount,
+    format: TextureFormat,
+    usage: TextureUsage,
 }
 
 impl Texture {
@@ -466,6 +523,10 @@ impl Texture {
         Self {
             value: instance.device.create_texture(desc),
             size: desc.size,
+            mip_level_count: desc.mip_level_count,
+            sample_count: desc.sample_count,
+            format: desc.format,
+            usage: desc.usage,
         }
     }
 
@@ -473,6 +534,22 @@ impl Texture {
         &self.size
     }
 
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
+    pub fn sample_count(&self) -> SampleCount {
+        self.sample_count
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn usage(&self) -> TextureUsage {
+        self.usage
+    }
+
     pub fn from_image(instance: &Instance, img: &image::RgbaImage, usage: TextureUsage) -> Self {
         let img_dimensions = img.dimensions();
         let size = Extent3d {
@@ -488,7 +565,7 @@ impl Texture {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
-                format: TextureFormat::Rgba8UnormSrgb,
+                format: instance.color_workflow().texture_format(),
                 usage: usage | TextureUsage::COPY_DST,
             },
         );
@@ -589,6 +666,61 @@ impl Texture {
             size,
         );
     }
+
+    // Replaces the content of a region with tightly packed pixels, row by row and layer by
+    // layer. For compressed formats the pixels are whole blocks.
+    pub fn write_region(&self, instance: &Instance, region: &TextureRegion, pixels: &[u8]) {
+        assert!(
+            self.usage.contains(TextureUsage::COPY_DST),
+            "The texture must have the COPY_DST usage"
+        );
+        assert!(
+            self.sample_count == 1,
+            "Multisampled textures can't be written"
+        );
+        assert!(
+            super::region_fits(&self.size, self.mip_level_count, region),
+            "The region is out of bounds"
+        );
+        assert!(
+            super::region_aligned(self.format, region),
+            "The region must be aligned to the texture format blocks"
+        );
+        let layout = region_data_layout(self.format, region);
+        assert!(
+            pixels.len() as u64 == region_byte_count(&layout, region),
+            "The pixel data doesn't match the region size"
+        );
+        self.write(
+            instance,
+            region.mip_level,
+            region.origin,
+            pixels,
+            layout,
+            region.size,
+        );
+    }
+}
+
+// Layout of tightly packed pixel data for a region.
+pub(crate) fn region_data_layout(format: TextureFormat, region: &TextureRegion) -> ImageDataLayout {
+    let info = format.describe();
+    let (block_width, block_height) = (
+        info.block_dimensions.0 as u32,
+        info.block_dimensions.1 as u32,
+    );
+    let bytes_per_row = region.size.width / block_width * info.block_size as u32;
+    ImageDataLayout {
+        offset: 0,
+        bytes_per_row: core::num::NonZeroU32::new(bytes_per_row),
+        rows_per_image: core::num::NonZeroU32::new(region.size.height / block_height),
+    }
+}
+
+pub(crate) fn region_byte_count(layout: &ImageDataLayout, region: &TextureRegion) -> u64 {
+    let bytes_per_row = layout.bytes_per_row.map_or(0, |b| b.get()) as u64;
+    let rows_per_image = layout.rows_per_image.map_or(0, |r| r.get()) as u64;
+    bytes_per_row * rows_per_image * region.size.depth_or_array_layers as u64
 }
 
 impl Deref for Texture {
@@ -687,10 +819,22 @@ mod tests {
             required_features: Features::default(),
             optional_features: Features::empty(),
             required_limits: Limits::default(),
+            color_workflow: ColorWorkflow::Gamma,
         })
         .unwrap();
     }
 
+    #[test]
+    fn texture_compression_capabilities() {
+        let capabilities = asset_capabilities(Features::TEXTURE_COMPRESSION_ETC2);
+        expect_that!(!capabilities.texture_compression_bc);
+        expect_that!(capabilities.texture_compression_etc2);
+        expect_that!(
+            &asset_capabilities(InstanceDescriptor::default().optional_features),
+            eq(assets::AssetCapabilities::all())
+        );
+    }
+
     #[test]
     #[serial_test::serial]
     fn new_with_compatible_window() {
@@ -704,6 +848,42 @@ mod tests {
         };
     }
 
+    #[test]
+    fn region_data_size() {
+        let region = TextureRegion::new(4, 8, 12, 4);
+        let layout = region_data_layout(TextureFormat::Rgba8Unorm, &region);
+        expect_that!(&layout.bytes_per_row.unwrap().get(), eq(48));
+        expect_that!(&region_byte_count(&layout, &region), eq(192));
+
+        let layout = region_data_layout(TextureFormat::Bc1RgbaUnorm, &region);
+        expect_that!(&layout.bytes_per_row.unwrap().get(), eq(24));
+        expect_that!(&region_byte_count(&layout, &region), eq(24));
+
+        let mut region = TextureRegion::new(0, 0, 2, 2);
+        region.size.depth_or_array_layers = 3;
+        let layout = region_data_layout(TextureFormat::R8Unorm, &region);
+        expect_that!(&region_byte_count(&layout, &region), eq(12));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn write_texture_region() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let image = image::RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 0, 255]));
+        let texture = Texture::from_image(
+            &instance,
+            &image,
+            TextureUsage::TEXTURE_BINDING | TextureUsage::COPY_SRC,
+        );
+        let pixels = [255u8, 0, 0, 255].repeat(6);
+        texture.write_region(&instance, &TextureRegion::new(2, 4, 3, 2), &pixels);
+        let output = texture.to_image(&instance);
+        expect_that!(output.get_pixel(2, 4), eq(image::Rgba([255, 0, 0, 255])));
+        expect_that!(output.get_pixel(4, 5), eq(image::Rgba([255, 0, 0, 255])));
+        expect_that!(output.get_pixel(5, 5), eq(image::Rgba([0, 0, 0, 255])));
+        expect_that!(output.get_pixel(2, 6), eq(image::Rgba([0, 0, 0, 255])));
+    }
+
     #[test]
     #[serial_test::serial]
     fn load_texture_from_image() {
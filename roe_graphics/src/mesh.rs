@@ -1,12 +1,40 @@
 use ::core::ops::Range;
 use std::marker::PhantomData;
 
-use super::{Buffer, BufferInitDescriptor, BufferUsage, Instance};
+use super::{Buffer, BufferInitDescriptor, BufferUsage, IndexFormat, Instance};
 
 pub type MeshVertexRange = Range<u32>;
 pub type MeshIndexRange = Range<u32>;
 pub type MeshIndex = u16;
 
+// Types usable as mesh indices. 16 bit indices save memory, 32 bit indices are needed for
+// meshes with more than 65536 vertices.
+pub trait MeshIndexType: bytemuck::Pod {
+    const INDEX_FORMAT: IndexFormat;
+    const MAX: u32;
+
+    // The index must not be higher than MAX.
+    fn from_u32(index: u32) -> Self;
+}
+
+impl MeshIndexType for u16 {
+    const INDEX_FORMAT: IndexFormat = IndexFormat::Uint16;
+    const MAX: u32 = u16::MAX as u32;
+
+    fn from_u32(index: u32) -> Self {
+        index as u16
+    }
+}
+
+impl MeshIndexType for u32 {
+    const INDEX_FORMAT: IndexFormat = IndexFormat::Uint32;
+    const MAX: u32 = u32::MAX;
+
+    fn from_u32(index: u32) -> Self {
+        index
+    }
+}
+
 #[derive(Debug)]
 struct TypedBuffer<T: bytemuck::Pod> {
     buffer: Buffer,
@@ -15,11 +43,16 @@ struct TypedBuffer<T: bytemuck::Pod> {
 }
 
 impl<T: bytemuck::Pod> TypedBuffer<T> {
-    pub fn new(instance: &Instance, vertex_list: &[T], usage: BufferUsage) -> Self {
+    pub fn new(
+        instance: &Instance,
+        label: Option<&str>,
+        vertex_list: &[T],
+        usage: BufferUsage,
+    ) -> Self {
         let buffer = Buffer::init(
             &instance,
             &BufferInitDescriptor {
-                label: None,
+                label,
                 contents: bytemuck::cast_slice(vertex_list),
                 usage,
             },
@@ -40,7 +73,12 @@ pub struct Mesh<V: bytemuck::Pod> {
 
 impl<V: bytemuck::Pod> Mesh<V> {
     pub fn new(instance: &Instance, vertex_list: &[V]) -> Self {
-        let vertex_buffer = TypedBuffer::new(instance, vertex_list, BufferUsage::VERTEX);
+        Self::new_with_label(instance, None, vertex_list)
+    }
+
+    // The label is shown by graphics debuggers.
+    pub fn new_with_label(instance: &Instance, label: Option<&str>, vertex_list: &[V]) -> Self {
+        let vertex_buffer = TypedBuffer::new(instance, label, vertex_list, BufferUsage::VERTEX);
         Self { vertex_buffer }
     }
 
@@ -54,15 +92,25 @@ impl<V: bytemuck::Pod> Mesh<V> {
 }
 
 #[derive(Debug)]
-pub struct IndexedMesh<V: bytemuck::Pod> {
+pub struct IndexedMesh<V: bytemuck::Pod, I: MeshIndexType = MeshIndex> {
     vertex_buffer: TypedBuffer<V>,
-    index_buffer: TypedBuffer<MeshIndex>,
+    index_buffer: TypedBuffer<I>,
 }
 
-impl<V: bytemuck::Pod> IndexedMesh<V> {
-    pub fn new(instance: &Instance, vertex_list: &[V], index_list: &[MeshIndex]) -> Self {
-        let vertex_buffer = TypedBuffer::new(instance, vertex_list, BufferUsage::VERTEX);
-        let index_buffer = TypedBuffer::new(instance, index_list, BufferUsage::INDEX);
+impl<V: bytemuck::Pod, I: MeshIndexType> IndexedMesh<V, I> {
+    pub fn new(instance: &Instance, vertex_list: &[V], index_list: &[I]) -> Self {
+        Self::new_with_label(instance, None, vertex_list, index_list)
+    }
+
+    // The label is shown by graphics debuggers.
+    pub fn new_with_label(
+        instance: &Instance,
+        label: Option<&str>,
+        vertex_list: &[V],
+        index_list: &[I],
+    ) -> Self {
+        let vertex_buffer = TypedBuffer::new(instance, label, vertex_list, BufferUsage::VERTEX);
+        let index_buffer = TypedBuffer::new(instance, label, index_list, BufferUsage::INDEX);
         Self {
             vertex_buffer,
             index_buffer,
@@ -84,6 +132,71 @@ impl<V: bytemuck::Pod> IndexedMesh<V> {
     pub fn index_count(&self) -> u32 {
         self.index_buffer.element_count
     }
+
+    pub fn index_format(&self) -> IndexFormat {
+        I::INDEX_FORMAT
+    }
+}
+
+// Indexed mesh whose vertices can be rewritten after creation, e.g. every frame for deformed
+// meshes. The vertex and index counts are fixed.
+#[derive(Debug)]
+pub struct DynamicMesh<V: bytemuck::Pod, I: MeshIndexType = MeshIndex> {
+    mesh: IndexedMesh<V, I>,
+}
+
+impl<V: bytemuck::Pod, I: MeshIndexType> DynamicMesh<V, I> {
+    pub fn new(instance: &Instance, vertex_list: &[V], index_list: &[I]) -> Self {
+        Self::new_with_label(instance, None, vertex_list, index_list)
+    }
+
+    // The label is shown by graphics debuggers.
+    pub fn new_with_label(
+        instance: &Instance,
+        label: Option<&str>,
+        vertex_list: &[V],
+        index_list: &[I],
+    ) -> Self {
+        let vertex_buffer = TypedBuffer::new(
+            instance,
+            label,
+            vertex_list,
+            BufferUsage::VERTEX | BufferUsage::COPY_DST,
+        );
+        let index_buffer = TypedBuffer::new(instance, label, index_list, BufferUsage::INDEX);
+        Self {
+            mesh: IndexedMesh {
+                vertex_buffer,
+                index_buffer,
+            },
+        }
+    }
+
+    // The write is queued and applied before the next submitted commands.
+    pub fn update_vertices(&mut self, instance: &Instance, vertex_list: &[V]) {
+        assert!(
+            vertex_list.len() as u32 == self.mesh.vertex_count(),
+            "The vertex count of a dynamic mesh can't change"
+        );
+        instance.write_buffer(
+            self.mesh.vertex_buffer(),
+            0,
+            bytemuck::cast_slice(vertex_list),
+        );
+    }
+
+    // For drawing with the same functions as static meshes.
+    pub fn mesh(&self) -> &IndexedMesh<V, I> {
+        &self.mesh
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.mesh.vertex_count()
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.mesh.index_count()
+    }
 }
 
 #[cfg(test)]
@@ -137,5 +250,35 @@ mod tests {
         );
         expect_that!(&mesh.vertex_count(), eq(3));
         expect_that!(&mesh.index_count(), eq(4));
+        expect_that!(&mesh.index_format(), eq(IndexFormat::Uint16));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn indexed_mesh_creation_32_bit() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let vertices = vec![Vertex { pos: [0., 0.] }; 70000];
+        let indices: Vec<u32> = (0..70000).collect();
+        let mesh = IndexedMesh::<Vertex, u32>::new(&instance, &vertices, &indices);
+        expect_that!(&mesh.vertex_count(), eq(70000));
+        expect_that!(&mesh.index_count(), eq(70000));
+        expect_that!(&mesh.index_format(), eq(IndexFormat::Uint32));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn dynamic_mesh_update() {
+        let instance = Instance::new(&InstanceDescriptor::default()).unwrap();
+        let mut mesh = DynamicMesh::<Vertex>::new(
+            &instance,
+            &[Vertex { pos: [1., 2.] }, Vertex { pos: [3., 4.] }],
+            &[0, 1],
+        );
+        mesh.update_vertices(
+            &instance,
+            &[Vertex { pos: [5., 6.] }, Vertex { pos: [7., 8.] }],
+        );
+        expect_that!(&mesh.vertex_count(), eq(2));
+        expect_that!(&mesh.mesh().index_count(), eq(2));
     }
 }
@@ -0,0 +1,372 @@
+use super::{IndexedMesh, Instance, MeshIndexType};
+
+use roe_math::{Vector2, Vector3, Vector4};
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MeshBuilderVertex {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    // The w component is the handedness of the bitangent (1 or -1).
+    pub tangent: Vector4<f32>,
+    pub texture_coordinates: Vector2<f32>,
+    pub color: Vector4<f32>,
+}
+
+impl MeshBuilderVertex {
+    pub fn new(position: Vector3<f32>, texture_coordinates: Vector2<f32>) -> Self {
+        Self {
+            position,
+            texture_coordinates,
+            ..Self::default()
+        }
+    }
+
+    fn key(&self, quantization_step: f32) -> [i64; 16] {
+        let mut key = [0; 16];
+        let values = self
+            .position
+            .iter()
+            .chain(self.normal.iter())
+            .chain(self.tangent.iter())
+            .chain(self.texture_coordinates.iter())
+            .chain(self.color.iter());
+        for (k, v) in key.iter_mut().zip(values) {
+            *k = (v / quantization_step).round() as i64;
+        }
+        key
+    }
+}
+
+impl Default for MeshBuilderVertex {
+    fn default() -> Self {
+        Self {
+            position: Vector3::zeros(),
+            normal: Vector3::zeros(),
+            tangent: Vector4::new(1., 0., 0., 1.),
+            texture_coordinates: Vector2::zeros(),
+            color: Vector4::new(1., 1., 1., 1.),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeshBuilderDescriptor {
+    // Merges vertices whose attributes are all equal after rounding to the quantization step.
+    pub deduplicate: bool,
+    pub quantization_step: f32,
+}
+
+impl Default for MeshBuilderDescriptor {
+    fn default() -> Self {
+        Self {
+            deduplicate: true,
+            quantization_step: 1e-5,
+        }
+    }
+}
+
+// Accumulates triangles into an indexed vertex list, e.g. for procedural geometry. The
+// vertices are converted to the vertex type of the pipeline when building the mesh.
+#[derive(Debug, Clone)]
+pub struct MeshBuilder {
+    desc: MeshBuilderDescriptor,
+    vertices: Vec<MeshBuilderVertex>,
+    indices: Vec<u32>,
+    vertex_map: HashMap<[i64; 16], u32>,
+}
+
+impl MeshBuilder {
+    pub fn new(desc: &MeshBuilderDescriptor) -> Self {
+        assert!(
+            desc.quantization_step > 0.,
+            "The quantization step must be higher than 0"
+        );
+        Self {
+            desc: desc.clone(),
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            vertex_map: HashMap::new(),
+        }
+    }
+
+    pub fn vertices(&self) -> &[MeshBuilderVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    // Counter-clockwise triangles are front facing.
+    pub fn push_triangle(
+        &mut self,
+        a: &MeshBuilderVertex,
+        b: &MeshBuilderVertex,
+        c: &MeshBuilderVertex,
+    ) {
+        for v in [a, b, c] {
+            let index = self.push_vertex(v);
+            self.indices.push(index);
+        }
+    }
+
+    // Vertices a, b, c and d in counter-clockwise order.
+    pub fn push_quad(
+        &mut self,
+        a: &MeshBuilderVertex,
+        b: &MeshBuilderVertex,
+        c: &MeshBuilderVertex,
+        d: &MeshBuilderVertex,
+    ) {
+        self.push_triangle(a, b, c);
+        self.push_triangle(a, c, d);
+    }
+
+    // Computes smooth normals, weighting the normals of the adjacent triangles by their area.
+    // Vertices sharing the same position get the same normal, even if other attributes differ.
+    pub fn generate_normals(&mut self) {
+        let step = self.desc.quantization_step;
+        let position_key = |p: &Vector3<f32>| {
+            [
+                (p.x / step).round() as i64,
+                (p.y / step).round() as i64,
+                (p.z / step).round() as i64,
+            ]
+        };
+        let mut normals: HashMap<[i64; 3], Vector3<f32>> = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let p: Vec<Vector3<f32>> = triangle
+                .iter()
+                .map(|i| self.vertices[*i as usize].position)
+                .collect();
+            // The cross product length is twice the triangle area.
+            let face_normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+            for position in p.iter() {
+                *normals
+                    .entry(position_key(position))
+                    .or_insert_with(Vector3::zeros) += face_normal;
+            }
+        }
+        for v in self.vertices.iter_mut() {
+            let normal = normals[&position_key(&v.position)];
+            v.normal = normal
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::zeros);
+        }
+        self.rebuild_vertex_map();
+    }
+
+    // Computes the tangents from the texture coordinates and the normals, which must be already
+    // set.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vector3::<f32>::zeros(); self.vertices.len()];
+        let mut bitangents = vec![Vector3::<f32>::zeros(); self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let v: Vec<&MeshBuilderVertex> = triangle
+                .iter()
+                .map(|i| &self.vertices[*i as usize])
+                .collect();
+            let e1 = v[1].position - v[0].position;
+            let e2 = v[2].position - v[0].position;
+            let d1 = v[1].texture_coordinates - v[0].texture_coordinates;
+            let d2 = v[2].texture_coordinates - v[0].texture_coordinates;
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) / det;
+            let bitangent = (e2 * d1.x - e1 * d2.x) / det;
+            for i in triangle {
+                tangents[*i as usize] += tangent;
+                bitangents[*i as usize] += bitangent;
+            }
+        }
+        for (i, v) in self.vertices.iter_mut().enumerate() {
+            let n = v.normal;
+            // Gram-Schmidt orthogonalization against the normal.
+            let t = match (tangents[i] - n * n.dot(&tangents[i])).try_normalize(f32::EPSILON) {
+                Some(t) => t,
+                None => continue,
+            };
+            let handedness = if n.cross(&t).dot(&bitangents[i]) < 0. {
+                -1.
+            } else {
+                1.
+            };
+            v.tangent = Vector4::new(t.x, t.y, t.z, handedness);
+        }
+        self.rebuild_vertex_map();
+    }
+
+    // Indices converted to the given index type. Panics if there are too many vertices for it.
+    pub fn converted_indices<I: MeshIndexType>(&self) -> Vec<I> {
+        assert!(
+            self.vertices.len() as u64 <= I::MAX as u64 + 1,
+            "Too many vertices for the index type"
+        );
+        self.indices.iter().map(|i| I::from_u32(*i)).collect()
+    }
+
+    pub fn build<V, I, F>(&self, instance: &Instance, convert: F) -> IndexedMesh<V, I>
+    where
+        V: bytemuck::Pod,
+        I: MeshIndexType,
+        F: Fn(&MeshBuilderVertex) -> V,
+    {
+        let vertices: Vec<V> = self.vertices.iter().map(convert).collect();
+        IndexedMesh::new(instance, &vertices, &self.converted_indices::<I>())
+    }
+
+    fn push_vertex(&mut self, v: &MeshBuilderVertex) -> u32 {
+        if !self.desc.deduplicate {
+            self.vertices.push(*v);
+            return self.vertices.len() as u32 - 1;
+        }
+        let key = v.key(self.desc.quantization_step);
+        if let Some(index) = self.vertex_map.get(&key) {
+            return *index;
+        }
+        let index = self.vertices.len() as u32;
+        self.vertices.push(*v);
+        self.vertex_map.insert(key, index);
+        index
+    }
+
+    // Keeps deduplicating new vertices against the existing ones after changing their
+    // attributes.
+    fn rebuild_vertex_map(&mut self) {
+        if !self.desc.deduplicate {
+            return;
+        }
+        self.vertex_map.clear();
+        for (i, v) in self.vertices.iter().enumerate() {
+            self.vertex_map
+                .entry(v.key(self.desc.quantization_step))
+                .or_insert(i as u32);
+        }
+    }
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self::new(&MeshBuilderDescriptor::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn vertex(x: f32, y: f32, z: f32, u: f32, v: f32) -> MeshBuilderVertex {
+        MeshBuilderVertex::new(Vector3::new(x, y, z), Vector2::new(u, v))
+    }
+
+    fn square(builder: &mut MeshBuilder) {
+        builder.push_quad(
+            &vertex(0., 0., 0., 0., 0.),
+            &vertex(1., 0., 0., 1., 0.),
+            &vertex(1., 1., 0., 1., 1.),
+            &vertex(0., 1., 0., 0., 1.),
+        );
+    }
+
+    #[test]
+    fn deduplication() {
+        let mut builder = MeshBuilder::default();
+        square(&mut builder);
+        expect_that!(&builder.vertices().len(), eq(4));
+        expect_that!(&builder.indices().to_vec(), eq(vec![0, 1, 2, 0, 2, 3]));
+
+        // Nearly identical vertices are merged.
+        builder.push_triangle(
+            &vertex(1.000001, 0., 0., 1., 0.),
+            &vertex(2., 0., 0., 0., 0.),
+            &vertex(1., 1., 0., 1., 1.),
+        );
+        expect_that!(&builder.vertices().len(), eq(5));
+        expect_that!(&builder.indices()[6..].to_vec(), eq(vec![1, 4, 2]));
+
+        let mut builder = MeshBuilder::new(&MeshBuilderDescriptor {
+            deduplicate: false,
+            ..MeshBuilderDescriptor::default()
+        });
+        square(&mut builder);
+        expect_that!(&builder.vertices().len(), eq(6));
+    }
+
+    #[test]
+    fn normals() {
+        let mut builder = MeshBuilder::default();
+        square(&mut builder);
+        // A second face, folded along the y axis and facing +x.
+        builder.push_quad(
+            &vertex(0., 0., 0., 0., 0.),
+            &vertex(0., 1., 0., 0., 1.),
+            &vertex(0., 1., -1., 1., 1.),
+            &vertex(0., 0., -1., 1., 0.),
+        );
+        builder.generate_normals();
+        let vertices = builder.vertices();
+        expect_that!(&vertices[1].normal, eq(Vector3::new(0., 0., 1.)));
+        // Shared positions get the average of the adjacent faces, even if the texture
+        // coordinates differ.
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        expect_that!(&vertices[0].normal.x, close_to(-s, 1e-6));
+        expect_that!(&vertices[0].normal.z, close_to(s, 1e-6));
+        let last = vertices.last().unwrap();
+        expect_that!(&last.normal, eq(Vector3::new(-1., 0., 0.)));
+    }
+
+    #[test]
+    fn tangents() {
+        let mut builder = MeshBuilder::default();
+        square(&mut builder);
+        builder.generate_normals();
+        builder.generate_tangents();
+        for v in builder.vertices() {
+            expect_that!(&v.tangent, eq(Vector4::new(1., 0., 0., 1.)));
+        }
+
+        // Mirrored texture coordinates flip the handedness.
+        let mut builder = MeshBuilder::default();
+        builder.push_quad(
+            &vertex(0., 0., 0., 1., 0.),
+            &vertex(1., 0., 0., 0., 0.),
+            &vertex(1., 1., 0., 0., 1.),
+            &vertex(0., 1., 0., 1., 1.),
+        );
+        builder.generate_normals();
+        builder.generate_tangents();
+        for v in builder.vertices() {
+            expect_that!(&v.tangent, eq(Vector4::new(-1., 0., 0., -1.)));
+        }
+    }
+
+    #[test]
+    fn index_conversion() {
+        let mut builder = MeshBuilder::default();
+        square(&mut builder);
+        expect_that!(
+            &builder.converted_indices::<u16>(),
+            eq(vec![0u16, 1, 2, 0, 2, 3])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many vertices for the index type")]
+    fn too_many_vertices() {
+        let mut builder = MeshBuilder::default();
+        for i in 0..21846 {
+            let x = i as f32;
+            builder.push_triangle(
+                &vertex(x, 0., 0., 0., 0.),
+                &vertex(x, 1., 0., 0., 0.),
+                &vertex(x, 2., 0., 0., 0.),
+            );
+        }
+        builder.converted_indices::<u16>();
+    }
+}
@@ -0,0 +1,76 @@
+// Stores the pipelines created for each descriptor, so that pipeline variants are only created
+// the first time they are requested. Lookup is linear, the number of variants is expected to be
+// small.
+#[derive(Debug)]
+pub struct PipelineCache<D, P> {
+    pipelines: Vec<(D, P)>,
+}
+
+impl<D: PartialEq + Clone, P> PipelineCache<D, P> {
+    pub fn new() -> Self {
+        Self {
+            pipelines: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, desc: &D) -> Option<&P> {
+        self.pipelines
+            .iter()
+            .find(|(d, _)| d == desc)
+            .map(|(_, p)| p)
+    }
+
+    pub fn get_or_insert_with<F: FnOnce(&D) -> P>(&mut self, desc: &D, create: F) -> &P {
+        let index = match self.pipelines.iter().position(|(d, _)| d == desc) {
+            Some(index) => index,
+            None => {
+                self.pipelines.push((desc.clone(), create(desc)));
+                self.pipelines.len() - 1
+            }
+        };
+        &self.pipelines[index].1
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+    }
+}
+
+impl<D: PartialEq + Clone, P> Default for PipelineCache<D, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn caching() {
+        let mut cache = PipelineCache::new();
+        let mut created = 0;
+        for desc in [1, 2, 1, 3, 2] {
+            let pipeline = *cache.get_or_insert_with(&desc, |d| {
+                created += 1;
+                d * 10
+            });
+            expect_that!(&pipeline, eq(desc * 10));
+        }
+        expect_that!(&created, eq(3));
+        expect_that!(&cache.len(), eq(3));
+        expect_that!(&cache.get(&2).cloned(), eq(Some(20)));
+        expect_that!(&cache.get(&4).cloned(), eq(None));
+        cache.clear();
+        expect_that!(cache.is_empty());
+    }
+}
@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 outTexCoords;
+layout(push_constant) uniform PushConstant {
+    // Offset and size of the source region in texture coordinates.
+    vec4 texRegion;
+} pushConstant;
+
+void main() {
+    vec2 corner = vec2(float(gl_VertexIndex & 1), float((gl_VertexIndex >> 1) & 1));
+    gl_Position = vec4(corner.x * 2. - 1., 1. - corner.y * 2., 0., 1.);
+    outTexCoords = pushConstant.texRegion.xy + corner * pushConstant.texRegion.zw;
+}
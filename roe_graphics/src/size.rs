@@ -2,6 +2,11 @@ use num_traits::identities::Zero;
 use std::cmp::PartialOrd;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SizeData<T>")
+)]
 pub struct Size<T: Copy + Zero + PartialOrd> {
     width: T,
     height: T,
@@ -35,6 +40,30 @@ impl<T: Copy + Zero + PartialOrd> Size<T> {
     }
 }
 
+// Checks the deserialized values, which could be negative.
+#[cfg(feature = "serde-serialize")]
+#[derive(serde::Deserialize)]
+struct SizeData<T> {
+    width: T,
+    height: T,
+}
+
+#[cfg(feature = "serde-serialize")]
+impl<T: Copy + Zero + PartialOrd> TryFrom<SizeData<T>> for Size<T> {
+    type Error = &'static str;
+
+    fn try_from(data: SizeData<T>) -> Result<Self, Self::Error> {
+        if data.width >= T::zero() && data.height >= T::zero() {
+            Ok(Self {
+                width: data.width,
+                height: data.height,
+            })
+        } else {
+            Err("A negative size is invalid")
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +143,14 @@ mod tests {
         let mut size = Size::<f32>::new(0., 0.);
         size.set_height(-2.);
     }
+
+    #[test]
+    #[cfg(feature = "serde-serialize")]
+    fn serialization() {
+        let size = Size::<u32>::new(640, 480);
+        let s = ron::ser::to_string(&size).unwrap();
+        expect_that!(&s.as_str(), eq("(width:640,height:480)"));
+        expect_that!(&ron::de::from_str::<Size<u32>>(&s).unwrap(), eq(size));
+        expect_that!(ron::de::from_str::<Size<f32>>("(width: -1, height: 2)").is_err());
+    }
 }
@@ -0,0 +1,351 @@
+use super::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, ColorConversion, ColorTargetState,
+    ColorWrite, CommandEncoder, Extent3d, FilterMode, FragmentState, Instance, LoadOp,
+    MultisampleState, Operations, Origin3d, PipelineLayout, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, PrimitiveTopology, PushConstantRange, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerDescriptor,
+    ShaderModule, ShaderStage, Texture, TextureFormat, TextureSampleType, TextureViewDescriptor,
+    TextureViewDimension, VertexState,
+};
+
+// Rectangular region of a mip level of a texture. The z coordinate of the origin is the array
+// layer.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TextureRegion {
+    pub mip_level: u32,
+    pub origin: Origin3d,
+    pub size: Extent3d,
+}
+
+impl TextureRegion {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            mip_level: 0,
+            origin: Origin3d { x, y, z: 0 },
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        }
+    }
+
+    // The first layer of the base mip level.
+    pub fn whole(texture: &Texture) -> Self {
+        Self::new(0, 0, texture.size().width, texture.size().height)
+    }
+}
+
+pub(crate) fn mip_level_size(size: &Extent3d, mip_level: u32) -> Extent3d {
+    Extent3d {
+        width: std::cmp::max(size.width >> mip_level, 1),
+        height: std::cmp::max(size.height >> mip_level, 1),
+        depth_or_array_layers: size.depth_or_array_layers,
+    }
+}
+
+pub(crate) fn region_fits(size: &Extent3d, mip_level_count: u32, region: &TextureRegion) -> bool {
+    if region.mip_level >= mip_level_count {
+        return false;
+    }
+    let size = mip_level_size(size, region.mip_level);
+    region.origin.x + region.size.width <= size.width
+        && region.origin.y + region.size.height <= size.height
+        && region.origin.z + region.size.depth_or_array_layers <= size.depth_or_array_layers
+}
+
+// Compressed formats can only be copied in whole blocks.
+pub(crate) fn region_aligned(format: TextureFormat, region: &TextureRegion) -> bool {
+    let (block_width, block_height) = format.describe().block_dimensions;
+    let (block_width, block_height) = (block_width as u32, block_height as u32);
+    region.origin.x.is_multiple_of(block_width)
+        && region.origin.y.is_multiple_of(block_height)
+        && region.size.width.is_multiple_of(block_width)
+        && region.size.height.is_multiple_of(block_height)
+}
+
+const CONVERSION_PC_OFFSET: u32 = std::mem::size_of::<[f32; 4]>() as u32;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextureBlitterDescriptor {
+    // Format of the destination textures.
+    pub format: TextureFormat,
+    // Linear filtering requires filterable source formats.
+    pub filter: FilterMode,
+    // Applied to the sampled colors, e.g. for the final gamma pass from an intermediate canvas
+    // to a color buffer not matching the color workflow.
+    pub conversion: ColorConversion,
+}
+
+impl Default for TextureBlitterDescriptor {
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::Rgba8UnormSrgb,
+            filter: FilterMode::Linear,
+            conversion: ColorConversion::None,
+        }
+    }
+}
+
+// Draws a region of a texture into a region of a texture with a different format or size. The
+// source region is stretched to fill the destination region.
+#[derive(Debug)]
+pub struct TextureBlitter {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    format: TextureFormat,
+    conversion: ColorConversion,
+}
+
+impl TextureBlitter {
+    pub fn new(instance: &Instance, desc: &TextureBlitterDescriptor) -> Self {
+        let filtering = desc.filter == FilterMode::Linear;
+        let bind_group_layout = BindGroupLayout::new(
+            instance,
+            &BindGroupLayoutDescriptor {
+                label: Some("texture_blitter"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStage::FRAGMENT,
+                        ty: BindingType::Texture {
+                            multisampled: false,
+                            sample_type: TextureSampleType::Float {
+                                filterable: filtering,
+                            },
+                            view_dimension: TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStage::FRAGMENT,
+                        ty: BindingType::Sampler {
+                            filtering,
+                            comparison: false,
+                        },
+                        count: None,
+                    },
+                ],
+            },
+        );
+        let pipeline_layout = PipelineLayout::new(
+            instance,
+            &PipelineLayoutDescriptor {
+                label: Some("texture_blitter"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[
+                    PushConstantRange {
+                        stages: ShaderStage::VERTEX,
+                        range: 0..CONVERSION_PC_OFFSET,
+                    },
+                    PushConstantRange {
+                        stages: ShaderStage::FRAGMENT,
+                        range: CONVERSION_PC_OFFSET
+                            ..CONVERSION_PC_OFFSET + std::mem::size_of::<u32>() as u32,
+                    },
+                ],
+            },
+        );
+        let vs_module = ShaderModule::new(
+            instance,
+            &wgpu::include_spirv!("shaders/gen/spirv/blit.vert.spv"),
+        );
+        let fs_module = ShaderModule::new(
+            instance,
+            &wgpu::include_spirv!("shaders/gen/spirv/blit.frag.spv"),
+        );
+        let pipeline = RenderPipeline::new(
+            instance,
+            &RenderPipelineDescriptor {
+                label: Some("texture_blitter"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleStrip,
+                    strip_index_format: None,
+                    front_face: Default::default(),
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[ColorTargetState {
+                        format: desc.format,
+                        blend: None,
+                        write_mask: ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        let sampler = Sampler::new(
+            instance,
+            &SamplerDescriptor {
+                mag_filter: desc.filter,
+                min_filter: desc.filter,
+                ..SamplerDescriptor::default()
+            },
+        );
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            format: desc.format,
+            conversion: desc.conversion,
+        }
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn conversion(&self) -> ColorConversion {
+        self.conversion
+    }
+
+    // Regions are expected to be validated.
+    pub(crate) fn blit(
+        &self,
+        instance: &Instance,
+        encoder: &mut CommandEncoder,
+        src: &Texture,
+        src_region: &TextureRegion,
+        dst: &Texture,
+        dst_region: &TextureRegion,
+    ) {
+        let src_view = src.create_view(&layer_view_descriptor(src_region));
+        let dst_view = dst.create_view(&layer_view_descriptor(dst_region));
+        let bind_group = BindGroup::new(
+            instance,
+            &BindGroupDescriptor {
+                label: Some("texture_blitter"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&src_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            },
+        );
+        let src_size = mip_level_size(src.size(), src_region.mip_level);
+        let (width, height) = (src_size.width as f32, src_size.height as f32);
+        let tex_region = [
+            src_region.origin.x as f32 / width,
+            src_region.origin.y as f32 / height,
+            src_region.size.width as f32 / width,
+            src_region.size.height as f32 / height,
+        ];
+
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("texture_blitter"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: &dst_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_viewport(
+            dst_region.origin.x as f32,
+            dst_region.origin.y as f32,
+            dst_region.size.width as f32,
+            dst_region.size.height as f32,
+            0.,
+            1.,
+        );
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.set_push_constants(
+            ShaderStage::VERTEX,
+            0,
+            super::utility::as_slice(&tex_region),
+        );
+        rpass.set_push_constants(
+            ShaderStage::FRAGMENT,
+            CONVERSION_PC_OFFSET,
+            super::utility::as_slice(&(self.conversion as u32)),
+        );
+        rpass.draw(0..4, 0..1);
+    }
+}
+
+fn layer_view_descriptor(region: &TextureRegion) -> TextureViewDescriptor<'static> {
+    TextureViewDescriptor {
+        label: None,
+        dimension: Some(TextureViewDimension::D2),
+        base_mip_level: region.mip_level,
+        mip_level_count: std::num::NonZeroU32::new(1),
+        base_array_layer: region.origin.z,
+        array_layer_count: std::num::NonZeroU32::new(1),
+        ..TextureViewDescriptor::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn region_validation() {
+        let size = Extent3d {
+            width: 64,
+            height: 32,
+            depth_or_array_layers: 2,
+        };
+        expect_that!(region_fits(&size, 1, &TextureRegion::new(0, 0, 64, 32)));
+        expect_that!(region_fits(&size, 1, &TextureRegion::new(60, 30, 4, 2)));
+        expect_that!(!region_fits(&size, 1, &TextureRegion::new(60, 30, 5, 2)));
+
+        let mut region = TextureRegion::new(0, 0, 32, 16);
+        region.mip_level = 1;
+        expect_that!(!region_fits(&size, 1, &region));
+        expect_that!(region_fits(&size, 2, &region));
+        region.size.width = 33;
+        expect_that!(!region_fits(&size, 2, &region));
+
+        let mut region = TextureRegion::new(0, 0, 8, 8);
+        region.origin.z = 1;
+        expect_that!(region_fits(&size, 1, &region));
+        region.origin.z = 2;
+        expect_that!(!region_fits(&size, 1, &region));
+
+        expect_that!(&mip_level_size(&size, 6).width, eq(1));
+        expect_that!(&mip_level_size(&size, 6).height, eq(1));
+    }
+
+    #[test]
+    fn block_alignment() {
+        expect_that!(region_aligned(
+            TextureFormat::Rgba8Unorm,
+            &TextureRegion::new(1, 3, 5, 7)
+        ));
+        expect_that!(region_aligned(
+            TextureFormat::Bc1RgbaUnorm,
+            &TextureRegion::new(4, 8, 16, 4)
+        ));
+        expect_that!(!region_aligned(
+            TextureFormat::Bc1RgbaUnorm,
+            &TextureRegion::new(4, 8, 15, 4)
+        ));
+    }
+}
@@ -0,0 +1,24 @@
+mod error;
+pub use error::*;
+
+mod plural;
+pub use plural::*;
+
+mod message;
+pub use message::*;
+
+mod localization;
+pub use localization::*;
+
+// Translates a key with the given localization, with optional named arguments:
+// tr!(localization, "apples", count = 3, owner = "Anna").
+// The "count" argument selects the plural form of the message.
+#[macro_export]
+macro_rules! tr {
+    ($localization:expr, $key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $localization.translate(
+            $key,
+            &[$((stringify!($name), $crate::Arg::from($value))),*],
+        )
+    };
+}
@@ -0,0 +1,269 @@
+use super::{Arg, Error, Message};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+// Messages of a locale by key.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct TranslationTable {
+    pub locale: String,
+    pub messages: BTreeMap<String, Message>,
+}
+
+impl TranslationTable {
+    pub fn new<S: Into<String>>(locale: S) -> Self {
+        Self {
+            locale: locale.into(),
+            messages: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+}
+
+// Translates messages to the current locale. If a root directory is set, the tables of the
+// locales that weren't inserted are loaded from the "<locale>.ron" files in it.
+#[derive(Debug)]
+pub struct Localization {
+    root: Option<PathBuf>,
+    tables: HashMap<String, TranslationTable>,
+    locale: String,
+    fallback_locale: Option<String>,
+    revision: u64,
+}
+
+impl Localization {
+    pub fn new<S: Into<String>>(locale: S) -> Self {
+        Self {
+            root: None,
+            tables: HashMap::new(),
+            locale: locale.into(),
+            fallback_locale: None,
+            revision: 0,
+        }
+    }
+
+    pub fn with_root<P: Into<PathBuf>, S: Into<String>>(root: P, locale: S) -> Result<Self, Error> {
+        let mut localization = Self::new(locale);
+        localization.root = Some(root.into());
+        let locale = localization.locale.clone();
+        localization.load_table(&locale)?;
+        Ok(localization)
+    }
+
+    // Replaces the table of the same locale, if any.
+    pub fn insert_table(&mut self, table: TranslationTable) {
+        if table.locale == self.locale {
+            self.revision += 1;
+        }
+        self.tables.insert(table.locale.clone(), table);
+    }
+
+    pub fn contains_locale(&self, locale: &str) -> bool {
+        self.tables.contains_key(locale)
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    // Switches locale, loading its table from the root directory if necessary. On failure the
+    // current locale is kept.
+    pub fn set_locale<S: Into<String>>(&mut self, locale: S) -> Result<(), Error> {
+        let locale = locale.into();
+        if locale == self.locale {
+            return Ok(());
+        }
+        self.load_table(&locale)?;
+        self.locale = locale;
+        self.revision += 1;
+        Ok(())
+    }
+
+    pub fn fallback_locale(&self) -> Option<&str> {
+        self.fallback_locale.as_deref()
+    }
+
+    // Messages missing from the current locale are taken from the fallback locale.
+    pub fn set_fallback_locale<S: Into<String>>(&mut self, locale: Option<S>) -> Result<(), Error> {
+        let locale = locale.map(|l| l.into());
+        if let Some(locale) = &locale {
+            self.load_table(locale)?;
+        }
+        self.fallback_locale = locale;
+        self.revision += 1;
+        Ok(())
+    }
+
+    // Increased every time the translations may change, e.g. when switching locale. Texts and
+    // layouts depending on the translations must be updated when it changes.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.message(key).is_some()
+    }
+
+    // Missing messages are replaced by the key itself, to make them easy to spot.
+    pub fn translate(&self, key: &str, args: &[(&str, Arg)]) -> String {
+        match self.message(key) {
+            Some((locale, message)) => message.format(locale, args),
+            None => String::from(key),
+        }
+    }
+
+    fn message(&self, key: &str) -> Option<(&str, &Message)> {
+        std::iter::once(self.locale.as_str())
+            .chain(self.fallback_locale.as_deref())
+            .find_map(|locale| {
+                self.tables
+                    .get(locale)
+                    .and_then(|table| table.messages.get(key))
+                    .map(|message| (locale, message))
+            })
+    }
+
+    fn load_table(&mut self, locale: &str) -> Result<(), Error> {
+        if self.tables.contains_key(locale) {
+            return Ok(());
+        }
+        let root = match &self.root {
+            Some(root) => root,
+            None => return Err(Error::UnknownLocale(String::from(locale))),
+        };
+        let mut table = TranslationTable::load(root.join(format!("{}.ron", locale)))?;
+        table.locale = String::from(locale);
+        self.tables.insert(String::from(locale), table);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tr;
+    use galvanic_assert::{matchers::*, *};
+
+    const EN: &str = r#"(
+        locale: "en",
+        messages: {
+            "title": "Red Orange",
+            "greeting": "Hello, {name}!",
+            "apples": (zero: "No apples", one: "One apple", other: "{count} apples"),
+        },
+    )"#;
+
+    const IT: &str = r#"(
+        locale: "it",
+        messages: {
+            "greeting": "Ciao, {name}!",
+            "apples": (one: "Una mela", other: "{count} mele"),
+        },
+    )"#;
+
+    #[test]
+    fn translate() {
+        let mut localization = Localization::new("en");
+        localization.insert_table(TranslationTable::from_ron_str(EN).unwrap());
+        expect_that!(&tr!(localization, "title"), eq(String::from("Red Orange")));
+        expect_that!(
+            &tr!(localization, "greeting", name = "Anna"),
+            eq(String::from("Hello, Anna!"))
+        );
+        expect_that!(
+            &tr!(localization, "apples", count = 0),
+            eq(String::from("No apples"))
+        );
+        expect_that!(
+            &tr!(localization, "apples", count = 1),
+            eq(String::from("One apple"))
+        );
+        expect_that!(
+            &tr!(localization, "apples", count = 12u64),
+            eq(String::from("12 apples"))
+        );
+        expect_that!(!localization.contains("missing"));
+        expect_that!(&tr!(localization, "missing"), eq(String::from("missing")));
+    }
+
+    #[test]
+    fn locale_switching() {
+        let mut localization = Localization::new("en");
+        localization.insert_table(TranslationTable::from_ron_str(EN).unwrap());
+        localization.insert_table(TranslationTable::from_ron_str(IT).unwrap());
+        let revision = localization.revision();
+
+        localization.set_locale("it").unwrap();
+        expect_that!(&localization.locale(), eq("it"));
+        expect_that!(localization.revision() > revision);
+        expect_that!(
+            &tr!(localization, "apples", count = 3),
+            eq(String::from("3 mele"))
+        );
+        expect_that!(&tr!(localization, "title"), eq(String::from("title")));
+
+        localization.set_fallback_locale(Some("en")).unwrap();
+        expect_that!(&tr!(localization, "title"), eq(String::from("Red Orange")));
+
+        let revision = localization.revision();
+        expect_that!(&localization.set_locale("de"), is_variant!(Result::Err));
+        expect_that!(&localization.locale(), eq("it"));
+        expect_that!(&localization.revision(), eq(revision));
+    }
+
+    #[test]
+    fn serialization() {
+        let table = TranslationTable::from_ron_str(IT).unwrap();
+        let table_again = TranslationTable::from_ron_str(&table.to_ron_string().unwrap()).unwrap();
+        expect_that!(&table_again, eq(table));
+    }
+
+    #[test]
+    fn load_from_root() {
+        let root = std::env::temp_dir().join("roe_i18n_localization_test");
+        std::fs::create_dir_all(&root).unwrap();
+        TranslationTable::from_ron_str(EN)
+            .unwrap()
+            .save(root.join("en.ron"))
+            .unwrap();
+        TranslationTable::from_ron_str(IT)
+            .unwrap()
+            .save(root.join("it.ron"))
+            .unwrap();
+
+        let mut localization = Localization::with_root(&root, "en").unwrap();
+        expect_that!(!localization.contains_locale("it"));
+        localization.set_locale("it").unwrap();
+        expect_that!(localization.contains_locale("it"));
+        expect_that!(
+            &tr!(localization, "greeting", name = "Anna"),
+            eq(String::from("Ciao, Anna!"))
+        );
+        expect_that!(&localization.set_locale("fr"), is_variant!(Result::Err));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use super::{plural_category, PluralCategory};
+
+use serde::{Deserialize, Serialize};
+
+// Value of a named message argument.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Arg {
+    Text(String),
+    Number(f64),
+}
+
+impl std::fmt::Display for Arg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(s) => write!(f, "{}", s),
+            Self::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(s: &str) -> Self {
+        Self::Text(String::from(s))
+    }
+}
+
+impl From<String> for Arg {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<&String> for Arg {
+    fn from(s: &String) -> Self {
+        Self::Text(s.clone())
+    }
+}
+
+macro_rules! impl_number_arg {
+    ($($type:ty),*) => {
+        $(
+            impl From<$type> for Arg {
+                fn from(n: $type) -> Self {
+                    Self::Number(n as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_number_arg!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct PluralForms {
+    // Used for a count of exactly 0 in any language, if set.
+    #[serde(default)]
+    pub zero: Option<String>,
+    #[serde(default)]
+    pub one: Option<String>,
+    #[serde(default)]
+    pub two: Option<String>,
+    #[serde(default)]
+    pub few: Option<String>,
+    #[serde(default)]
+    pub many: Option<String>,
+    pub other: String,
+}
+
+impl PluralForms {
+    // Missing forms fall back to the other form.
+    pub fn select(&self, locale: &str, count: f64) -> &str {
+        if count == 0. {
+            if let Some(zero) = &self.zero {
+                return zero;
+            }
+        }
+        let form = match plural_category(locale, count) {
+            PluralCategory::Zero => &self.zero,
+            PluralCategory::One => &self.one,
+            PluralCategory::Two => &self.two,
+            PluralCategory::Few => &self.few,
+            PluralCategory::Many => &self.many,
+            PluralCategory::Other => return &self.other,
+        };
+        form.as_deref().unwrap_or(&self.other)
+    }
+}
+
+// A translated message. Written in translation files either as a string or as a structure
+// with the plural forms, e.g. (one: "{count} apple", other: "{count} apples").
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Message {
+    Text(String),
+    Plural(PluralForms),
+}
+
+impl Message {
+    // Selects the plural form through the "count" argument, then replaces the "{name}"
+    // placeholders with the arguments. "{{" and "}}" stand for literal braces.
+    pub fn format(&self, locale: &str, args: &[(&str, Arg)]) -> String {
+        let template = match self {
+            Self::Text(text) => text.as_str(),
+            Self::Plural(forms) => {
+                let count = args.iter().find_map(|(name, value)| match value {
+                    Arg::Number(n) if *name == "count" => Some(*n),
+                    _ => None,
+                });
+                forms.select(locale, count.unwrap_or(0.))
+            }
+        };
+        format_template(template, args)
+    }
+}
+
+pub fn format_template(template: &str, args: &[(&str, Arg)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let end = match tail.find('}') {
+            Some(end) if tail.starts_with('{') => end,
+            _ => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+                continue;
+            }
+        };
+        let name = tail[1..end].trim();
+        match args.iter().find(|(arg_name, _)| *arg_name == name) {
+            Some((_, value)) => out.push_str(&value.to_string()),
+            // Unknown placeholders are kept, to make missing arguments visible.
+            None => out.push_str(&tail[..=end]),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn template() {
+        let args = [("name", Arg::from("Anna")), ("count", Arg::from(3))];
+        expect_that!(
+            &format_template("{name} has {count} apples", &args),
+            eq(String::from("Anna has 3 apples"))
+        );
+        expect_that!(
+            &format_template("{{name}} {missing} {count", &args),
+            eq(String::from("{name} {missing} {count"))
+        );
+        expect_that!(
+            &format_template("{ name }: {count}}}", &args),
+            eq(String::from("Anna: 3}"))
+        );
+        expect_that!(
+            &format_template("{count}", &[("count", Arg::from(1.5f32))]),
+            eq(String::from("1.5"))
+        );
+    }
+
+    #[test]
+    fn plural() {
+        let message: Message = ron::de::from_str(
+            r#"(zero: "No apples", one: "{count} apple", few: "{count} apples (few)", other: "{count} apples")"#,
+        )
+        .unwrap();
+        let format = |locale, count: u32| message.format(locale, &[("count", Arg::from(count))]);
+        expect_that!(&format("en", 0), eq(String::from("No apples")));
+        expect_that!(&format("en", 1), eq(String::from("1 apple")));
+        expect_that!(&format("en", 3), eq(String::from("3 apples")));
+        expect_that!(&format("ru", 3), eq(String::from("3 apples (few)")));
+        expect_that!(&format("ru", 21), eq(String::from("21 apple")));
+        // Missing forms fall back to the other form.
+        expect_that!(&format("ru", 5), eq(String::from("5 apples")));
+
+        let message: Message = ron::de::from_str(r#""Hello {name}""#).unwrap();
+        expect_that!(
+            &message.format("en", &[("name", Arg::from("Bob"))]),
+            eq(String::from("Hello Bob"))
+        );
+    }
+}
@@ -0,0 +1,127 @@
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+// Language part of a locale tag, e.g. "pt" for "pt-BR".
+pub fn locale_language(locale: &str) -> &str {
+    locale.split(['-', '_']).next().unwrap_or("")
+}
+
+// Plural category of a number in the language of the locale, following a simplified version
+// of the CLDR rules. Languages without specific rules use the English ones.
+pub fn plural_category(locale: &str, n: f64) -> PluralCategory {
+    let n = n.abs();
+    let integer = n.fract() == 0.;
+    let i = n as u64;
+    match locale_language(locale) {
+        "ja" | "zh" | "ko" | "vi" | "th" | "id" | "ms" => PluralCategory::Other,
+        "fr" | "pt" => {
+            if i <= 1 {
+                PluralCategory::One
+            } else {
+                PluralCategory::Other
+            }
+        }
+        "ru" | "uk" | "be" => {
+            if !integer {
+                PluralCategory::Other
+            } else if i % 10 == 1 && i % 100 != 11 {
+                PluralCategory::One
+            } else if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) {
+                PluralCategory::Few
+            } else {
+                PluralCategory::Many
+            }
+        }
+        "pl" => {
+            if !integer {
+                PluralCategory::Other
+            } else if i == 1 {
+                PluralCategory::One
+            } else if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) {
+                PluralCategory::Few
+            } else {
+                PluralCategory::Many
+            }
+        }
+        "cs" | "sk" => {
+            if !integer {
+                PluralCategory::Many
+            } else if i == 1 {
+                PluralCategory::One
+            } else if (2..=4).contains(&i) {
+                PluralCategory::Few
+            } else {
+                PluralCategory::Other
+            }
+        }
+        "ar" => {
+            if !integer {
+                PluralCategory::Other
+            } else if i == 0 {
+                PluralCategory::Zero
+            } else if i == 1 {
+                PluralCategory::One
+            } else if i == 2 {
+                PluralCategory::Two
+            } else if (3..=10).contains(&(i % 100)) {
+                PluralCategory::Few
+            } else if (11..=99).contains(&(i % 100)) {
+                PluralCategory::Many
+            } else {
+                PluralCategory::Other
+            }
+        }
+        _ => {
+            if integer && i == 1 {
+                PluralCategory::One
+            } else {
+                PluralCategory::Other
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn categories() {
+        let categories = |locale, numbers: &[f64]| -> Vec<PluralCategory> {
+            numbers
+                .iter()
+                .map(|n| plural_category(locale, *n))
+                .collect()
+        };
+        use PluralCategory::*;
+        expect_that!(
+            &categories("en-US", &[0., 1., 2., 1.5]),
+            eq(vec![Other, One, Other, Other])
+        );
+        expect_that!(
+            &categories("fr", &[0., 1., 1.5, 2.]),
+            eq(vec![One, One, One, Other])
+        );
+        expect_that!(
+            &categories("ru_RU", &[1., 3., 5., 11., 21., 22., 112.]),
+            eq(vec![One, Few, Many, Many, One, Few, Many])
+        );
+        expect_that!(
+            &categories("pl", &[1., 4., 21., 22.]),
+            eq(vec![One, Few, Many, Few])
+        );
+        expect_that!(&categories("ja", &[1.]), eq(vec![Other]));
+        expect_that!(
+            &categories("ar", &[0., 1., 2., 5., 50., 100.]),
+            eq(vec![Zero, One, Two, Few, Many, Other])
+        );
+    }
+}
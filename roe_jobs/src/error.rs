@@ -0,0 +1,21 @@
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JobError {
+    Cancelled,
+    // Contains the panic message, if it was a string.
+    Panicked(String),
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cancelled => write!(f, "Cancelled"),
+            Self::Panicked(message) => write!(f, "Job panicked ({})", message),
+        }
+    }
+}
+
+impl std::error::Error for JobError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
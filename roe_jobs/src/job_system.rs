@@ -0,0 +1,498 @@
+use super::{JobError, MainThreadQueue, MainThreadSender};
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+    thread,
+};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Default)]
+pub enum JobPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JobSystemDescriptor {
+    pub thread_count: usize,
+    // Prefix of the worker thread names, shown by debuggers and profilers.
+    pub thread_name: String,
+}
+
+impl Default for JobSystemDescriptor {
+    // Leaves a core to the main thread.
+    fn default() -> Self {
+        let cores = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2);
+        Self {
+            thread_count: std::cmp::max(cores - 1, 1),
+            thread_name: String::from("roe_jobs"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum JobState<T> {
+    Queued,
+    Running,
+    Finished(Result<T, JobError>),
+    Taken,
+}
+
+#[derive(Debug)]
+struct JobSlot<T> {
+    state: Mutex<JobState<T>>,
+    condvar: Condvar,
+}
+
+impl<T> JobSlot<T> {
+    // Returns false if the job was cancelled before starting.
+    fn start(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            JobState::Queued => {
+                *state = JobState::Running;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn cancel(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            JobState::Queued => {
+                *state = JobState::Finished(Err(JobError::Cancelled));
+                self.condvar.notify_all();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn finish(&self, result: Result<T, JobError>) {
+        *self.state.lock().unwrap() = JobState::Finished(result);
+        self.condvar.notify_all();
+    }
+}
+
+// Result of a spawned job. Dropping the handle doesn't cancel the job.
+#[derive(Debug)]
+pub struct JobHandle<T> {
+    slot: Arc<JobSlot<T>>,
+}
+
+impl<T> JobHandle<T> {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            *self.slot.state.lock().unwrap(),
+            JobState::Finished(_) | JobState::Taken
+        )
+    }
+
+    // Cancels the job if it didn't start yet. Returns true if it was cancelled.
+    pub fn cancel(&self) -> bool {
+        self.slot.cancel()
+    }
+
+    // Returns the result if the job finished, without blocking. The result can only be taken
+    // once.
+    pub fn try_take(&mut self) -> Option<Result<T, JobError>> {
+        let mut state = self.slot.state.lock().unwrap();
+        match *state {
+            JobState::Finished(_) => match std::mem::replace(&mut *state, JobState::Taken) {
+                JobState::Finished(result) => Some(result),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
+    // Blocks until the job finishes.
+    pub fn wait(mut self) -> Result<T, JobError> {
+        {
+            let mut state = self.slot.state.lock().unwrap();
+            while matches!(*state, JobState::Queued | JobState::Running) {
+                state = self.slot.condvar.wait(state).unwrap();
+            }
+        }
+        self.try_take().expect("The job result was already taken")
+    }
+}
+
+// The argument tells whether the job must be cancelled instead of run.
+type Job = Box<dyn FnOnce(bool) + Send>;
+
+struct QueuedJob {
+    priority: JobPriority,
+    sequence: u64,
+    run: Job,
+}
+
+// Higher priorities first, then first come first served.
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.sequence == other.sequence
+    }
+}
+
+impl Eq for QueuedJob {}
+
+#[derive(Default)]
+struct JobQueue {
+    queued: BinaryHeap<QueuedJob>,
+    closed: bool,
+}
+
+#[derive(Default)]
+struct SharedQueue {
+    queue: Mutex<JobQueue>,
+    condvar: Condvar,
+}
+
+// Thread pool shared by the engine subsystems, e.g. for asset decoding, audio streaming or path
+// finding. Jobs are run by priority. Results are retrieved through the job handles, or delivered
+// to callbacks on the main thread when the main thread queue is pumped.
+pub struct JobSystem {
+    shared: Arc<SharedQueue>,
+    next_sequence: AtomicU64,
+    main_thread_queue: MainThreadQueue,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+static GLOBAL_JOB_SYSTEM: OnceLock<JobSystem> = OnceLock::new();
+
+impl JobSystem {
+    pub fn new(desc: &JobSystemDescriptor) -> Self {
+        assert!(
+            desc.thread_count > 0,
+            "The thread count must be higher than 0"
+        );
+        let shared = Arc::new(SharedQueue::default());
+        let workers = (0..desc.thread_count)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("{} {}", desc.thread_name, i))
+                    .spawn(move || {
+                        while let Some(job) = next_job(&shared) {
+                            (job.run)(false);
+                        }
+                    })
+                    .expect("Failed to spawn a job thread")
+            })
+            .collect();
+        Self {
+            shared,
+            next_sequence: AtomicU64::new(0),
+            main_thread_queue: MainThreadQueue::new(),
+            workers,
+        }
+    }
+
+    // Initializes the global job system. Returns false if it was already initialized.
+    pub fn init_global(desc: &JobSystemDescriptor) -> bool {
+        let mut initialized = false;
+        GLOBAL_JOB_SYSTEM.get_or_init(|| {
+            initialized = true;
+            Self::new(desc)
+        });
+        initialized
+    }
+
+    // The global job system, initialized with the default descriptor on first use. Its threads
+    // live until the end of the process.
+    pub fn global() -> &'static Self {
+        GLOBAL_JOB_SYSTEM.get_or_init(|| Self::new(&JobSystemDescriptor::default()))
+    }
+
+    // The global job system, if initialized. Doesn't spawn any thread.
+    pub fn try_global() -> Option<&'static Self> {
+        GLOBAL_JOB_SYSTEM.get()
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    // Jobs waiting for a free thread.
+    pub fn queued_count(&self) -> usize {
+        self.shared.queue.lock().unwrap().queued.len()
+    }
+
+    pub fn spawn<T, F>(&self, priority: JobPriority, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let slot = Arc::new(JobSlot {
+            state: Mutex::new(JobState::Queued),
+            condvar: Condvar::new(),
+        });
+        let job_slot = Arc::clone(&slot);
+        self.enqueue(
+            priority,
+            Box::new(move |cancelled| {
+                if cancelled {
+                    job_slot.cancel();
+                } else if job_slot.start() {
+                    job_slot.finish(run_job(job));
+                }
+            }),
+        );
+        JobHandle { slot }
+    }
+
+    // The callback receives the result on the main thread, when the main thread queue is
+    // pumped.
+    pub fn spawn_with_callback<T, F, C>(&self, priority: JobPriority, job: F, callback: C)
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+        C: FnOnce(Result<T, JobError>) + Send + 'static,
+    {
+        let sender = self.main_thread_sender();
+        self.enqueue(
+            priority,
+            Box::new(move |cancelled| {
+                let result = if cancelled {
+                    Err(JobError::Cancelled)
+                } else {
+                    run_job(job)
+                };
+                sender.post(move || callback(result));
+            }),
+        );
+    }
+
+    pub fn main_thread_sender(&self) -> MainThreadSender {
+        self.main_thread_queue.sender()
+    }
+
+    pub fn main_thread_queue(&self) -> &MainThreadQueue {
+        &self.main_thread_queue
+    }
+
+    // Runs the callbacks posted to the main thread. To be called once per frame by the main
+    // thread.
+    pub fn pump_main_thread(&self) -> usize {
+        self.main_thread_queue.pump()
+    }
+
+    fn enqueue(&self, priority: JobPriority, run: Job) {
+        let sequence = self.next_sequence.fetch_add(1, atomic::Ordering::Relaxed);
+        self.shared.queue.lock().unwrap().queued.push(QueuedJob {
+            priority,
+            sequence,
+            run,
+        });
+        self.shared.condvar.notify_one();
+    }
+}
+
+impl std::fmt::Debug for JobSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "JobSystem {{ thread_count: {}, queued_count: {} }}",
+            self.thread_count(),
+            self.queued_count()
+        )
+    }
+}
+
+// Jobs still queued are cancelled and running jobs are waited for. Callbacks not pumped yet are
+// never run.
+impl Drop for JobSystem {
+    fn drop(&mut self) {
+        let queued = {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.closed = true;
+            std::mem::take(&mut queue.queued)
+        };
+        self.shared.condvar.notify_all();
+        for job in queued.into_sorted_vec() {
+            (job.run)(true);
+        }
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+fn run_job<T, F: FnOnce() -> T>(job: F) -> Result<T, JobError> {
+    std::panic::catch_unwind(AssertUnwindSafe(job)).map_err(|e| {
+        let message = if let Some(s) = e.downcast_ref::<&str>() {
+            String::from(*s)
+        } else if let Some(s) = e.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            String::new()
+        };
+        JobError::Panicked(message)
+    })
+}
+
+// Blocks until a job is available. Returns None when the job system is dropped.
+fn next_job(shared: &SharedQueue) -> Option<QueuedJob> {
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        if queue.closed {
+            return None;
+        }
+        if let Some(job) = queue.queued.pop() {
+            return Some(job);
+        }
+        queue = shared.condvar.wait(queue).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use std::sync::mpsc;
+
+    fn single_thread() -> JobSystem {
+        JobSystem::new(&JobSystemDescriptor {
+            thread_count: 1,
+            ..JobSystemDescriptor::default()
+        })
+    }
+
+    // Keeps the single worker busy until the returned sender is used.
+    fn block_worker(jobs: &JobSystem) -> (mpsc::Sender<()>, JobHandle<()>) {
+        let (sender, receiver) = mpsc::channel();
+        let (started_sender, started_receiver) = mpsc::channel();
+        let handle = jobs.spawn(JobPriority::High, move || {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+        });
+        started_receiver.recv().unwrap();
+        (sender, handle)
+    }
+
+    #[test]
+    fn results() {
+        let jobs = single_thread();
+        let handle = jobs.spawn(JobPriority::Normal, || 2 + 3);
+        expect_that!(&handle.wait(), eq(Ok(5)));
+
+        let mut handle = jobs.spawn(JobPriority::Normal, || -> u32 { panic!("Job failure") });
+        while !handle.is_finished() {
+            thread::yield_now();
+        }
+        expect_that!(
+            &handle.try_take(),
+            eq(Some(Err(JobError::Panicked(String::from("Job failure")))))
+        );
+        expect_that!(&handle.try_take(), eq(None));
+    }
+
+    #[test]
+    fn priorities() {
+        let jobs = single_thread();
+        let (unblock, blocker) = block_worker(&jobs);
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let handles: Vec<_> = [
+            (JobPriority::Low, 0),
+            (JobPriority::Normal, 1),
+            (JobPriority::High, 2),
+            (JobPriority::Normal, 3),
+        ]
+        .iter()
+        .map(|(priority, i)| {
+            let order = order.clone();
+            let i = *i;
+            jobs.spawn(*priority, move || order.lock().unwrap().push(i))
+        })
+        .collect();
+        expect_that!(&jobs.queued_count(), eq(4));
+        unblock.send(()).unwrap();
+        blocker.wait().unwrap();
+        for handle in handles {
+            handle.wait().unwrap();
+        }
+        expect_that!(&*order.lock().unwrap(), eq(vec![2, 1, 3, 0]));
+    }
+
+    #[test]
+    fn cancellation() {
+        let jobs = single_thread();
+        let (unblock, blocker) = block_worker(&jobs);
+        let handle = jobs.spawn(JobPriority::Normal, || 1);
+        expect_that!(handle.cancel());
+        expect_that!(handle.is_finished());
+        expect_that!(!blocker.cancel());
+        unblock.send(()).unwrap();
+        expect_that!(&blocker.wait(), eq(Ok(())));
+        expect_that!(&handle.wait(), eq(Err(JobError::Cancelled)));
+    }
+
+    #[test]
+    fn drop_cancels_queued_jobs() {
+        let jobs = single_thread();
+        let (unblock, blocker) = block_worker(&jobs);
+        let handle = jobs.spawn(JobPriority::Normal, || 1);
+        let dropper = thread::spawn(move || drop(jobs));
+        // Queued jobs are cancelled before waiting for the running ones.
+        while !handle.is_finished() {
+            thread::yield_now();
+        }
+        unblock.send(()).unwrap();
+        dropper.join().unwrap();
+        expect_that!(&blocker.wait(), eq(Ok(())));
+        expect_that!(&handle.wait(), eq(Err(JobError::Cancelled)));
+    }
+
+    #[test]
+    fn main_thread_callbacks() {
+        let jobs = single_thread();
+        let (sender, receiver) = mpsc::channel();
+        jobs.spawn_with_callback(
+            JobPriority::Normal,
+            || thread::current().name().map(String::from),
+            move |result| sender.send(result).unwrap(),
+        );
+        while jobs.main_thread_queue().is_empty() {
+            thread::yield_now();
+        }
+        expect_that!(receiver.try_recv().is_err());
+        expect_that!(&jobs.pump_main_thread(), eq(1));
+        expect_that!(
+            &receiver.try_recv().unwrap(),
+            eq(Ok(Some(String::from("roe_jobs 0"))))
+        );
+    }
+
+    #[test]
+    fn global() {
+        let system = JobSystem::global();
+        expect_that!(JobSystem::try_global().is_some());
+        expect_that!(!JobSystem::init_global(&JobSystemDescriptor::default()));
+        expect_that!(&system.spawn(JobPriority::Low, || 7).wait(), eq(Ok(7)));
+    }
+}
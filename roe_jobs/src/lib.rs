@@ -0,0 +1,10 @@
+mod error;
+pub use error::*;
+
+mod main_thread_queue;
+pub use main_thread_queue::*;
+
+mod job_system;
+pub use job_system::*;
+
+mod parallel;
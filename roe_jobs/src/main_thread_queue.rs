@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+// Posts callbacks to be run on the main thread, e.g. to upload to the gpu the data prepared by a
+// job. Can be cloned and moved to other threads.
+#[derive(Clone, Default)]
+pub struct MainThreadSender {
+    callbacks: Arc<Mutex<Vec<Callback>>>,
+}
+
+impl MainThreadSender {
+    pub fn post<F: FnOnce() + Send + 'static>(&self, callback: F) {
+        self.callbacks.lock().unwrap().push(Box::new(callback));
+    }
+}
+
+impl std::fmt::Debug for MainThreadSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MainThreadSender")
+    }
+}
+
+// Callbacks posted from any thread, run when the owner pumps the queue, typically once per frame.
+#[derive(Default)]
+pub struct MainThreadQueue {
+    sender: MainThreadSender,
+}
+
+impl MainThreadQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sender(&self) -> MainThreadSender {
+        self.sender.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sender.callbacks.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Runs the callbacks in the order they were posted, returning how many were run. Callbacks
+    // posted while pumping are run on the next call.
+    pub fn pump(&self) -> usize {
+        let callbacks = std::mem::take(&mut *self.sender.callbacks.lock().unwrap());
+        let count = callbacks.len();
+        for callback in callbacks {
+            callback();
+        }
+        count
+    }
+}
+
+impl std::fmt::Debug for MainThreadQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MainThreadQueue {{ len: {} }}", self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn pump() {
+        let queue = MainThreadQueue::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let sender = queue.sender();
+        let thread_log = log.clone();
+        std::thread::spawn(move || {
+            for i in 0..3 {
+                let log = thread_log.clone();
+                sender.post(move || log.lock().unwrap().push(i));
+            }
+        })
+        .join()
+        .unwrap();
+        expect_that!(&queue.len(), eq(3));
+        expect_that!(log.lock().unwrap().is_empty());
+
+        expect_that!(&queue.pump(), eq(3));
+        expect_that!(&*log.lock().unwrap(), eq(vec![0, 1, 2]));
+        expect_that!(queue.is_empty());
+
+        // Posted while pumping.
+        let sender = queue.sender();
+        let inner_log = log.clone();
+        queue.sender().post(move || {
+            sender.post(move || inner_log.lock().unwrap().push(4));
+        });
+        expect_that!(&queue.pump(), eq(1));
+        expect_that!(&queue.pump(), eq(1));
+        expect_that!(&*log.lock().unwrap(), eq(vec![0, 1, 2, 4]));
+    }
+}
@@ -0,0 +1,190 @@
+use super::{JobError, JobPriority, JobSystem};
+
+use std::{panic::AssertUnwindSafe, sync::Mutex};
+
+impl JobSystem {
+    // Calls f on every item with its index, in parallel on the job threads and the calling
+    // thread. Each call gets exclusive access to its own item only.
+    pub fn par_for_each_mut<T, F>(&self, items: &mut [T], f: F)
+    where
+        T: Send,
+        F: Fn(usize, &mut T) + Sync,
+    {
+        let chunk_size = self.chunk_size(items.len());
+        self.par_chunks_mut(items, chunk_size, |offset, chunk| {
+            for (i, item) in chunk.iter_mut().enumerate() {
+                f(offset + i, item);
+            }
+        });
+    }
+
+    pub fn par_for_each<T, F>(&self, items: &[T], f: F)
+    where
+        T: Sync,
+        F: Fn(usize, &T) + Sync,
+    {
+        let chunk_size = self.chunk_size(items.len());
+        self.par_chunks(items, chunk_size, |offset, chunk| {
+            for (i, item) in chunk.iter().enumerate() {
+                f(offset + i, item);
+            }
+        });
+    }
+
+    // Calls f on chunks of at most chunk_size items, together with the index of their first
+    // item. Returns when all chunks have been processed. If f panics, the panic is propagated
+    // after all chunks have been processed.
+    pub fn par_chunks_mut<T, F>(&self, items: &mut [T], chunk_size: usize, f: F)
+    where
+        T: Send,
+        F: Fn(usize, &mut [T]) + Sync,
+    {
+        assert!(chunk_size > 0, "The chunk size must be higher than 0");
+        let chunk_count = items.len().div_ceil(chunk_size);
+        self.run_scoped(
+            items.chunks_mut(chunk_size).enumerate(),
+            chunk_count,
+            &|(i, chunk)| f(i * chunk_size, chunk),
+        );
+    }
+
+    pub fn par_chunks<T, F>(&self, items: &[T], chunk_size: usize, f: F)
+    where
+        T: Sync,
+        F: Fn(usize, &[T]) + Sync,
+    {
+        assert!(chunk_size > 0, "The chunk size must be higher than 0");
+        let chunk_count = items.len().div_ceil(chunk_size);
+        self.run_scoped(
+            items.chunks(chunk_size).enumerate(),
+            chunk_count,
+            &|(i, chunk)| f(i * chunk_size, chunk),
+        );
+    }
+
+    // A few chunks per thread, so that threads finishing early can help the others.
+    fn chunk_size(&self, item_count: usize) -> usize {
+        std::cmp::max(item_count / ((self.thread_count() + 1) * 4), 1)
+    }
+
+    // Processes the work items on the calling thread and on helper jobs. The calling thread
+    // never waits for a job that didn't start: the helpers still queued when all the work is
+    // done are cancelled, so nested calls from a job don't deadlock.
+    fn run_scoped<W, I>(&self, work: I, work_count: usize, f: &(dyn Fn(W) + Sync))
+    where
+        W: Send,
+        I: Iterator<Item = W> + Send,
+    {
+        let work = Mutex::new(work);
+        let process = || loop {
+            let next = work.lock().unwrap().next();
+            match next {
+                Some(item) => f(item),
+                None => break,
+            }
+        };
+
+        let helper_count = std::cmp::min(self.thread_count(), work_count.saturating_sub(1));
+        let helpers: Vec<_> = (0..helper_count)
+            .map(|_| {
+                let job: Box<dyn FnOnce() + Send + '_> = Box::new(process);
+                // The job borrows data of this stack frame. This is safe because every helper
+                // is either cancelled before starting or waited for before returning, also if
+                // the calling thread panics. A cancelled job is dropped without running, and
+                // dropping it doesn't access the borrowed data.
+                let job: Box<dyn FnOnce() + Send + 'static> = unsafe { std::mem::transmute(job) };
+                self.spawn(JobPriority::High, job)
+            })
+            .collect();
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(process));
+        let mut helper_panic = None;
+        for helper in helpers {
+            if !helper.cancel() {
+                if let Err(JobError::Panicked(message)) = helper.wait() {
+                    helper_panic = Some(message);
+                }
+            }
+        }
+        if let Err(e) = result {
+            std::panic::resume_unwind(e);
+        }
+        if let Some(message) = helper_panic {
+            panic!("A parallel job panicked ({})", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobSystemDescriptor;
+    use galvanic_assert::{matchers::*, *};
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn job_system() -> JobSystem {
+        JobSystem::new(&JobSystemDescriptor {
+            thread_count: 3,
+            ..JobSystemDescriptor::default()
+        })
+    }
+
+    #[test]
+    fn for_each() {
+        let jobs = job_system();
+        let mut items: Vec<usize> = vec![0; 1000];
+        jobs.par_for_each_mut(&mut items, |i, item| *item = i * 2);
+        expect_that!(items.iter().enumerate().all(|(i, item)| *item == i * 2));
+
+        let sum = AtomicUsize::new(0);
+        jobs.par_for_each(&items, |_, item| {
+            sum.fetch_add(*item, Ordering::Relaxed);
+        });
+        expect_that!(&sum.load(Ordering::Relaxed), eq(999 * 1000));
+
+        jobs.par_for_each_mut(&mut [] as &mut [u32], |_, _| panic!("No items"));
+    }
+
+    #[test]
+    fn chunks() {
+        let jobs = job_system();
+        let mut items = [0u32; 10];
+        let chunk_sizes = Mutex::new(Vec::new());
+        jobs.par_chunks_mut(&mut items, 4, |offset, chunk| {
+            chunk_sizes.lock().unwrap().push((offset, chunk.len()));
+            for item in chunk.iter_mut() {
+                *item += 1;
+            }
+        });
+        let mut chunk_sizes = chunk_sizes.into_inner().unwrap();
+        chunk_sizes.sort();
+        expect_that!(&chunk_sizes, eq(vec![(0, 4), (4, 4), (8, 2)]));
+        expect_that!(&items, eq([1; 10]));
+    }
+
+    #[test]
+    fn nested() {
+        let jobs = JobSystem::new(&JobSystemDescriptor {
+            thread_count: 1,
+            ..JobSystemDescriptor::default()
+        });
+        let mut rows = vec![vec![0u32; 16]; 16];
+        jobs.par_for_each_mut(&mut rows, |i, row| {
+            jobs.par_for_each_mut(row, |j, item| *item = (i * 16 + j) as u32);
+        });
+        expect_that!(&rows[15][15], eq(255));
+    }
+
+    #[test]
+    #[should_panic(expected = "Item failure")]
+    fn panic_propagation() {
+        let jobs = job_system();
+        let mut items = [0u32; 64];
+        jobs.par_chunks_mut(&mut items, 1, |offset, _| {
+            if offset == 32 {
+                panic!("Item failure");
+            }
+        });
+    }
+}
@@ -0,0 +1,211 @@
+use super::{HomogeneousMatrix3, Point3, RealField, Vector2, Vector3};
+
+// Axis aligned bounding box in 2D.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Aabb2<N: RealField + Copy> {
+    pub min: Vector2<N>,
+    pub max: Vector2<N>,
+}
+
+impl<N: RealField + Copy> Aabb2<N> {
+    pub fn new(min: Vector2<N>, max: Vector2<N>) -> Self {
+        Self { min, max }
+    }
+
+    // Returns None if there are no points.
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector2<N>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut aabb = Self::new(*first, *first);
+        for p in points {
+            aabb.min = aabb.min.inf(p);
+            aabb.max = aabb.max.sup(p);
+        }
+        Some(aabb)
+    }
+
+    pub fn size(&self) -> Vector2<N> {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vector2<N> {
+        (self.min + self.max) * N::from_subset(&0.5)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    // Boxes touching on an edge intersect.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    pub fn contains_point(&self, p: &Vector2<N>) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+    }
+}
+
+// Axis aligned bounding box in 3D.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Aabb3<N: RealField + Copy> {
+    pub min: Vector3<N>,
+    pub max: Vector3<N>,
+}
+
+impl<N: RealField + Copy> Aabb3<N> {
+    pub fn new(min: Vector3<N>, max: Vector3<N>) -> Self {
+        Self { min, max }
+    }
+
+    // Returns None if there are no points.
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector3<N>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut aabb = Self::new(*first, *first);
+        for p in points {
+            aabb.min = aabb.min.inf(p);
+            aabb.max = aabb.max.sup(p);
+        }
+        Some(aabb)
+    }
+
+    pub fn size(&self) -> Vector3<N> {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vector3<N> {
+        (self.min + self.max) * N::from_subset(&0.5)
+    }
+
+    // Ordered by the bits of the index: x for bit 0, y for bit 1, z for bit 2.
+    pub fn corners(&self) -> [Vector3<N>; 8] {
+        let mut corners = [self.min; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            for axis in 0..3 {
+                if i & (1 << axis) != 0 {
+                    corner[axis] = self.max[axis];
+                }
+            }
+        }
+        corners
+    }
+
+    // Box containing the transformed box.
+    pub fn transformed(&self, transform: &HomogeneousMatrix3<N>) -> Self {
+        let corners: Vec<Vector3<N>> = self
+            .corners()
+            .iter()
+            .map(|c| transform.transform_point(&Point3::from(*c)).coords)
+            .collect();
+        Self::from_points(&corners).unwrap()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    // Boxes touching on a face intersect.
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    pub fn contains_point(&self, p: &Vector3<N>) -> bool {
+        (0..3).all(|i| p[i] >= self.min[i] && p[i] <= self.max[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn construction() {
+        let aabb = Aabb2::from_points(&[
+            Vector2::new(1., 2.),
+            Vector2::new(-3., 5.),
+            Vector2::new(0., -1.),
+        ])
+        .unwrap();
+        expect_that!(
+            &aabb,
+            eq(Aabb2::new(Vector2::new(-3., -1.), Vector2::new(1., 5.)))
+        );
+        expect_that!(&aabb.size(), eq(Vector2::new(4., 6.)));
+        expect_that!(&aabb.center(), eq(Vector2::new(-1., 2.)));
+        expect_that!(Aabb2::<f32>::from_points(&[]).is_none());
+    }
+
+    #[test]
+    fn queries() {
+        let a = Aabb2::new(Vector2::new(0., 0.), Vector2::new(10., 10.));
+        let b = Aabb2::new(Vector2::new(10., 5.), Vector2::new(20., 6.));
+        let c = Aabb2::new(Vector2::new(11., 0.), Vector2::new(20., 6.));
+        expect_that!(a.intersects(&b));
+        expect_that!(!a.intersects(&c));
+        expect_that!(
+            &a.union(&c),
+            eq(Aabb2::new(Vector2::new(0., 0.), Vector2::new(20., 10.)))
+        );
+        expect_that!(a.contains_point(&Vector2::new(5., 10.)));
+        expect_that!(!a.contains_point(&Vector2::new(5., 10.5)));
+    }
+
+    #[test]
+    fn aabb3() {
+        let aabb =
+            Aabb3::from_points(&[Vector3::new(1., 2., 0.), Vector3::new(-3., 5., 2.)]).unwrap();
+        expect_that!(&aabb.center(), eq(Vector3::new(-1., 3.5, 1.)));
+        expect_that!(&aabb.corners()[5], eq(Vector3::new(1., 2., 2.)));
+        expect_that!(aabb.contains_point(&Vector3::new(0., 5., 1.)));
+        expect_that!(!aabb.contains_point(&Vector3::new(0., 5., 3.)));
+        let other = Aabb3::new(Vector3::new(1., 5., 2.), Vector3::new(4., 6., 3.));
+        expect_that!(aabb.intersects(&other));
+        expect_that!(!aabb.intersects(&Aabb3::new(
+            Vector3::new(1.5, 0., 0.),
+            Vector3::new(2., 1., 1.)
+        )));
+
+        let transform = HomogeneousMatrix3::new_translation(&Vector3::new(1., 0., 0.))
+            * HomogeneousMatrix3::new_nonuniform_scaling(&Vector3::new(2., 1., 1.));
+        expect_that!(
+            &aabb.transformed(&transform),
+            eq(Aabb3::new(
+                Vector3::new(-5., 2., 0.),
+                Vector3::new(3., 5., 2.)
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde-serialize")]
+    fn serialization() {
+        let aabb = Aabb2::new(Vector2::new(-1., 2.), Vector2::new(3., 4.5));
+        let s = ron::ser::to_string(&aabb).unwrap();
+        expect_that!(&s.as_str(), eq("(min:[-1,2],max:[3,4.5])"));
+        expect_that!(&ron::de::from_str::<Aabb2<f32>>(&s).unwrap(), eq(aabb));
+
+        let transform = crate::translation2(&Vector2::new(1., 2.));
+        let s = ron::ser::to_string(&transform).unwrap();
+        expect_that!(
+            &ron::de::from_str::<crate::HomogeneousMatrix2<f32>>(&s).unwrap(),
+            eq(transform)
+        );
+        expect_that!(
+            &ron::de::from_str::<crate::Point2<f32>>("[3, 4]").unwrap(),
+            eq(crate::Point2::new(3., 4.))
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use super::{convert, RealField};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadraticIn,
+    QuadraticOut,
+    QuadraticInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+}
+
+impl Easing {
+    // Maps t in [0, 1] to the eased progress. Values outside of the range are clamped.
+    pub fn apply<N: RealField + Copy>(&self, t: N) -> N {
+        let zero = N::zero();
+        let one = N::one();
+        let two = convert::<_, N>(2.);
+        let half = convert::<_, N>(0.5);
+        let t = t.clamp(zero, one);
+        match self {
+            Self::Linear => t,
+            Self::QuadraticIn => t * t,
+            Self::QuadraticOut => t * (two - t),
+            Self::QuadraticInOut => {
+                if t < half {
+                    two * t * t
+                } else {
+                    one - (two - two * t).powi(2) / two
+                }
+            }
+            Self::CubicIn => t * t * t,
+            Self::CubicOut => one - (one - t).powi(3),
+            Self::CubicInOut => {
+                if t < half {
+                    convert::<_, N>(4.) * t * t * t
+                } else {
+                    one - (two - two * t).powi(3) / two
+                }
+            }
+            Self::SineIn => one - (t * N::frac_pi_2()).cos(),
+            Self::SineOut => (t * N::frac_pi_2()).sin(),
+            Self::SineInOut => (one - (t * N::pi()).cos()) / two,
+        }
+    }
+}
+
+// Interpolates between a and b, using the easing function to compute the progress.
+pub fn ease<N: RealField + Copy>(easing: Easing, a: N, b: N, t: N) -> N {
+    a + (b - a) * easing.apply(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const ALL: [Easing; 10] = [
+        Easing::Linear,
+        Easing::QuadraticIn,
+        Easing::QuadraticOut,
+        Easing::QuadraticInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+    ];
+
+    #[test]
+    fn end_points() {
+        for easing in ALL {
+            expect_that!(&easing.apply(0f32), close_to(0., 1e-6));
+            expect_that!(&easing.apply(1f32), close_to(1., 1e-6));
+            expect_that!(&easing.apply(-1f32), close_to(0., 1e-6));
+            expect_that!(&easing.apply(2f32), close_to(1., 1e-6));
+        }
+    }
+
+    #[test]
+    fn monotonic() {
+        for easing in ALL {
+            let mut previous = 0f64;
+            for i in 1..=100 {
+                let value = easing.apply(i as f64 / 100.);
+                expect_that!(value >= previous);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_symmetry() {
+        for easing in [
+            Easing::QuadraticInOut,
+            Easing::CubicInOut,
+            Easing::SineInOut,
+        ] {
+            expect_that!(&easing.apply(0.5f32), close_to(0.5, 1e-6));
+            expect_that!(
+                &(easing.apply(0.25f32) + easing.apply(0.75f32)),
+                close_to(1., 1e-6)
+            );
+        }
+    }
+
+    #[test]
+    fn values() {
+        expect_that!(&Easing::QuadraticIn.apply(0.5f32), close_to(0.25, 1e-6));
+        expect_that!(&Easing::QuadraticOut.apply(0.5f32), close_to(0.75, 1e-6));
+        expect_that!(&Easing::CubicIn.apply(0.5f32), close_to(0.125, 1e-6));
+        expect_that!(&ease(Easing::Linear, 2f32, 4., 0.25), close_to(2.5, 1e-6));
+    }
+}
@@ -1,7 +1,9 @@
 pub use nalgebra::{
     base::*,
     convert, convert_ref, convert_ref_unchecked, convert_unchecked,
-    geometry::{DualQuaternion, Quaternion, UnitComplex, UnitDualQuaternion, UnitQuaternion},
+    geometry::{
+        DualQuaternion, Point2, Point3, Quaternion, UnitComplex, UnitDualQuaternion, UnitQuaternion,
+    },
     try_convert, try_convert_ref, ComplexField, Field, RealField,
 };
 
@@ -15,3 +17,9 @@ pub use Vector4 as HomogeneousVector3;
 
 mod transform;
 pub use transform::*;
+
+mod easing;
+pub use easing::*;
+
+mod aabb;
+pub use aabb::*;
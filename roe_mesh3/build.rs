@@ -0,0 +1,26 @@
+extern crate roe_shader;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let shader_folder = "src/shaders";
+    let in_dir: std::path::PathBuf = [shader_folder, "glsl"].iter().collect();
+    println!(
+        "cargo:rerun-if-changed={}/**",
+        in_dir.to_str().unwrap_or("")
+    );
+    let in_dir: std::path::PathBuf = [shader_folder, "glsl"].iter().collect();
+    let out_dir: std::path::PathBuf = [shader_folder, "gen", "spirv"].iter().collect();
+    roe_shader::compile_shaders_into_spirv(in_dir.clone(), out_dir.clone())?;
+
+    // The terrain shader is the PBR shader with splatted layers instead of the material. The
+    // instance fade is used by the scattered instances.
+    let mut compiler = roe_shader::ShaderCompiler::new(&roe_shader::ShaderCompilerDescriptor {
+        cache_dir: Some(std::path::PathBuf::from(std::env::var("OUT_DIR")?).join("shader_cache")),
+    });
+    roe_shader::compile_shader_permutations(
+        &mut compiler,
+        in_dir.join("pbr.frag"),
+        out_dir,
+        &["TERRAIN", "INSTANCE_FADE"],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,546 @@
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix3, Vector3};
+
+// Camera facing quad drawn by the billboard pipeline, one instance per billboard.
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BillboardInstance {
+    pub position: [f32; 3],
+    // Rotation around the view direction in radians, ignored by axis locked billboards.
+    pub rotation: f32,
+    pub size: [f32; 2],
+    // Point of the quad placed at the position, from (0, 0) at the bottom left corner to (1, 1)
+    // at the top right corner.
+    pub pivot: [f32; 2],
+    // Zero for billboards fully facing the camera. Otherwise the quad only rotates around this
+    // axis, e.g. for trees.
+    pub axis: [f32; 3],
+    // Texture coordinates of the top left corner, width and height.
+    pub texture_rect: [f32; 4],
+    // In the workflow color space.
+    pub color: [f32; 4],
+}
+
+impl BillboardInstance {
+    pub fn new(position: Vector3<f32>, width: f32, height: f32) -> Self {
+        Self {
+            position: position.into(),
+            rotation: 0.,
+            size: [width, height],
+            pivot: [0.5, 0.5],
+            axis: [0., 0., 0.],
+            texture_rect: [0., 0., 1., 1.],
+            color: [1., 1., 1., 1.],
+        }
+    }
+
+    pub fn with_axis(mut self, axis: Vector3<f32>) -> Self {
+        self.axis = axis.into();
+        self
+    }
+
+    pub fn with_pivot(mut self, x: f32, y: f32) -> Self {
+        self.pivot = [x, y];
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_texture_rect(mut self, texture_rect: [f32; 4]) -> Self {
+        self.texture_rect = texture_rect;
+        self
+    }
+
+    pub fn with_color(mut self, color: gfx::ColorF32) -> Self {
+        self.color = [color.r, color.g, color.b, color.a];
+        self
+    }
+}
+
+unsafe impl bytemuck::Zeroable for BillboardInstance {
+    fn zeroed() -> Self {
+        Self {
+            position: [0.; 3],
+            rotation: 0.,
+            size: [0.; 2],
+            pivot: [0.; 2],
+            axis: [0.; 3],
+            texture_rect: [0.; 4],
+            color: [0.; 4],
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for BillboardInstance {}
+
+// Texture rect of the impostor frame to show for an object seen from the camera. The atlas has
+// frame_count frames side by side, rendered every 360 / frame_count degrees around the y axis,
+// starting with the object seen from the positive z axis.
+pub fn impostor_texture_rect(
+    position: &Vector3<f32>,
+    camera_position: &Vector3<f32>,
+    frame_count: u32,
+) -> [f32; 4] {
+    assert!(frame_count > 0, "An impostor needs at least one frame");
+    let to_camera = camera_position - position;
+    let angle = to_camera
+        .x
+        .atan2(to_camera.z)
+        .rem_euclid(2. * std::f32::consts::PI);
+    let frame_angle = 2. * std::f32::consts::PI / frame_count as f32;
+    let frame = ((angle / frame_angle).round() as u32) % frame_count;
+    let width = 1. / frame_count as f32;
+    [frame as f32 * width, 0., width, 1.]
+}
+
+// Blended billboards must be drawn after the opaque geometry, from the farthest.
+pub fn sort_billboards_back_to_front(
+    billboards: &mut [BillboardInstance],
+    camera_position: &Vector3<f32>,
+) {
+    let distance2 = |b: &BillboardInstance| {
+        let position = b.position;
+        (Vector3::from(position) - camera_position).norm_squared()
+    };
+    billboards.sort_by(|a, b| distance2(b).total_cmp(&distance2(a)));
+}
+
+// Instance buffer, reallocated when it gets too small.
+#[derive(Debug)]
+pub struct BillboardInstanceBuffer {
+    buffer: gfx::Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl BillboardInstanceBuffer {
+    pub fn new(instance: &gfx::Instance, billboards: &[BillboardInstance]) -> Self {
+        let mut buffer = Self {
+            buffer: Self::create_buffer(instance, 1),
+            capacity: 1,
+            len: 0,
+        };
+        buffer.update(instance, billboards);
+        buffer
+    }
+
+    // The write is queued and applied before the next submitted commands.
+    pub fn update(&mut self, instance: &gfx::Instance, billboards: &[BillboardInstance]) {
+        if billboards.len() > self.capacity {
+            self.capacity = billboards.len().next_power_of_two();
+            self.buffer = Self::create_buffer(instance, self.capacity);
+        }
+        self.len = billboards.len();
+        if !billboards.is_empty() {
+            instance.write_buffer(&self.buffer, 0, bytemuck::cast_slice(billboards));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn create_buffer(instance: &gfx::Instance, capacity: usize) -> gfx::Buffer {
+        gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (capacity * std::mem::size_of::<BillboardInstance>()) as gfx::BufferAddress,
+                usage: gfx::BufferUsage::VERTEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Float { filterable: true },
+                        view_dimension: gfx::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+#[derive(Debug)]
+pub struct BillboardUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl BillboardUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &bind_group_layout(instance),
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(texture),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BillboardPushConstants {
+    view_projection: HomogeneousMatrix3<f32>,
+    camera_right: [f32; 4],
+    camera_up: [f32; 4],
+    camera_position: [f32; 4],
+    alpha_cutoff: f32,
+}
+
+impl BillboardPushConstants {
+    // Fragments with a lower alpha than the cutoff are discarded.
+    pub fn new(
+        view: &HomogeneousMatrix3<f32>,
+        projection: &HomogeneousMatrix3<f32>,
+        alpha_cutoff: f32,
+    ) -> Self {
+        let camera_position = view
+            .try_inverse()
+            .map(|inverse| inverse.fixed_slice::<3, 1>(0, 3).into_owned())
+            .unwrap_or_else(Vector3::zeros);
+        let row = |i: usize| [view[(i, 0)], view[(i, 1)], view[(i, 2)], 0.];
+        Self {
+            view_projection: projection * view,
+            camera_right: row(0),
+            camera_up: row(1),
+            camera_position: [camera_position.x, camera_position.y, camera_position.z, 0.],
+            alpha_cutoff,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for BillboardPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            view_projection: HomogeneousMatrix3::zeros(),
+            camera_right: [0.; 4],
+            camera_up: [0.; 4],
+            camera_position: [0.; 4],
+            alpha_cutoff: 0.,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for BillboardPushConstants {}
+
+const PC_VERTEX_SIZE: u32 = std::mem::size_of::<HomogeneousMatrix3<f32>>() as u32 + 48;
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<BillboardPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BillboardBlendMode {
+    // Opaque, writes the depth buffer. Requires an alpha cutoff to cut the texture shape.
+    Cutout,
+    // Tested against the depth buffer without writing it, e.g. for smoke particles.
+    #[default]
+    AlphaBlend,
+    // Adds the color to the background without writing the depth buffer, e.g. for fire.
+    Additive,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BillboardPipelineDescriptor {
+    pub label: Option<String>,
+    pub blend_mode: BillboardBlendMode,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for BillboardPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            blend_mode: BillboardBlendMode::default(),
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat::Depth32Float,
+            sample_count: 1,
+        }
+    }
+}
+
+// Instanced, unlit billboards sharing the depth buffer of the 3D meshes.
+#[derive(Debug)]
+pub struct BillboardPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl BillboardPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &BillboardPipelineDescriptor) -> Self {
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout(instance)],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_VERTEX_SIZE,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_VERTEX_SIZE..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/billboard.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/billboard.frag.spv"),
+        );
+        let blend = match desc.blend_mode {
+            BillboardBlendMode::Cutout => None,
+            BillboardBlendMode::AlphaBlend => Some(gfx::BlendState::ALPHA_BLENDING),
+            BillboardBlendMode::Additive => Some(gfx::BlendState {
+                color: gfx::BlendComponent {
+                    src_factor: gfx::BlendFactor::SrcAlpha,
+                    dst_factor: gfx::BlendFactor::One,
+                    operation: gfx::BlendOperation::Add,
+                },
+                alpha: gfx::BlendComponent {
+                    src_factor: gfx::BlendFactor::Zero,
+                    dst_factor: gfx::BlendFactor::One,
+                    operation: gfx::BlendOperation::Add,
+                },
+            }),
+        };
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[gfx::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<BillboardInstance>()
+                            as gfx::BufferAddress,
+                        step_mode: gfx::VertexStepMode::Instance,
+                        attributes: &[
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x4,
+                                offset: 0,
+                                shader_location: 0,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x4,
+                                offset: 16,
+                                shader_location: 1,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x3,
+                                offset: 32,
+                                shader_location: 2,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x4,
+                                offset: 44,
+                                shader_location: 3,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x4,
+                                offset: 60,
+                                shader_location: 4,
+                            },
+                        ],
+                    }],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(gfx::DepthStencilState {
+                    format: gfx::TextureFormat::from(desc.depth_stencil_buffer_format),
+                    depth_write_enabled: desc.blend_mode == BillboardBlendMode::Cutout,
+                    depth_compare: gfx::CompareFunction::Less,
+                    stencil: gfx::StencilState::default(),
+                    bias: gfx::DepthBiasState::default(),
+                }),
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend,
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: Some(self.depth_stencil_buffer_format),
+        }
+    }
+}
+
+pub trait BillboardRenderer<'a> {
+    fn draw_billboards(
+        &mut self,
+        pipeline: &'a BillboardPipeline,
+        uniform_constants: &'a BillboardUniformConstants,
+        instances: &'a BillboardInstanceBuffer,
+        push_constants: &'a BillboardPushConstants,
+    );
+}
+
+impl<'a> BillboardRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_billboards(
+        &mut self,
+        pipeline: &'a BillboardPipeline,
+        uniform_constants: &'a BillboardUniformConstants,
+        instances: &'a BillboardInstanceBuffer,
+        push_constants: &'a BillboardPushConstants,
+    ) {
+        if instances.is_empty() {
+            return;
+        }
+        let bytes = bytemuck::bytes_of(push_constants);
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_vertex_buffer(0, instances.buffer.slice(..));
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            &bytes[..PC_VERTEX_SIZE as usize],
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_VERTEX_SIZE,
+            &bytes[PC_VERTEX_SIZE as usize..],
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..6, 0..instances.len() as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn instance_layout() {
+        expect_that!(&std::mem::size_of::<BillboardInstance>(), eq(76));
+        expect_that!(&PC_VERTEX_SIZE, eq(112));
+        expect_that!(&PC_CONVERSION_MEM_OFFSET, eq(116));
+    }
+
+    #[test]
+    fn camera_basis() {
+        // Camera at (0, 0, 5) looking towards the origin.
+        let view = HomogeneousMatrix3::new_translation(&Vector3::new(0., 0., -5.));
+        let pc = BillboardPushConstants::new(&view, &HomogeneousMatrix3::identity(), 0.5);
+        let (right, up, position) = (pc.camera_right, pc.camera_up, pc.camera_position);
+        expect_that!(&right, eq([1., 0., 0., 0.]));
+        expect_that!(&up, eq([0., 1., 0., 0.]));
+        expect_that!(&position, eq([0., 0., 5., 0.]));
+    }
+
+    #[test]
+    fn impostor_frames() {
+        let position = Vector3::zeros();
+        let rect = impostor_texture_rect(&position, &Vector3::new(0., 1., 10.), 4);
+        expect_that!(&rect, eq([0., 0., 0.25, 1.]));
+        let rect = impostor_texture_rect(&position, &Vector3::new(10., 1., 0.), 4);
+        expect_that!(&rect, eq([0.25, 0., 0.25, 1.]));
+        let rect = impostor_texture_rect(&position, &Vector3::new(-10., 0., 0.1), 4);
+        expect_that!(&rect, eq([0.75, 0., 0.25, 1.]));
+    }
+
+    #[test]
+    fn back_to_front_sorting() {
+        let mut billboards = vec![
+            BillboardInstance::new(Vector3::new(0., 0., 1.), 1., 1.),
+            BillboardInstance::new(Vector3::new(0., 0., -5.), 1., 1.),
+            BillboardInstance::new(Vector3::new(0., 0., -2.), 1., 1.),
+        ];
+        sort_billboards_back_to_front(&mut billboards, &Vector3::new(0., 0., 2.));
+        let depths: Vec<f32> = billboards.iter().map(|b| b.position[2]).collect();
+        expect_that!(&depths, eq(vec![-5., -2., 1.]));
+    }
+}
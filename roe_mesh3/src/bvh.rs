@@ -0,0 +1,355 @@
+use roe_math::{Aabb3, HomogeneousMatrix3, Vector3, Vector4};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    // Not necessarily normalized: hit distances are measured in multiples of the direction.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Self { origin, direction }
+    }
+
+    // Ray through a point of the screen, e.g. under the mouse cursor, in normalized device
+    // coordinates. The direction is normalized.
+    pub fn from_ndc(view_projection: &HomogeneousMatrix3<f32>, x: f32, y: f32) -> Option<Self> {
+        let inverse = view_projection.try_inverse()?;
+        let unproject = |z: f32| {
+            let p = inverse * Vector4::new(x, y, z, 1.);
+            p.xyz() / p.w
+        };
+        let near = unproject(0.);
+        let direction = (unproject(1.) - near).try_normalize(f32::EPSILON)?;
+        Some(Self::new(near, direction))
+    }
+
+    pub fn at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    pub fn transformed(&self, transform: &HomogeneousMatrix3<f32>) -> Self {
+        let origin = transform * self.origin.push(1.);
+        Self::new(
+            origin.xyz() / origin.w,
+            (transform * self.direction.push(0.)).xyz(),
+        )
+    }
+}
+
+// Distance at which the ray enters the box, 0 if the origin is inside.
+pub fn ray_aabb_intersection(ray: &Ray, aabb: &Aabb3<f32>, max_distance: f32) -> Option<f32> {
+    let mut t_min = 0f32;
+    let mut t_max = max_distance;
+    for axis in 0..3 {
+        let inverse = 1. / ray.direction[axis];
+        let mut t0 = (aabb.min[axis] - ray.origin[axis]) * inverse;
+        let mut t1 = (aabb.max[axis] - ray.origin[axis]) * inverse;
+        if inverse < 0. {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        // NaN, from a zero direction component on the box plane, keeps the current bounds.
+        t_min = if t0 > t_min { t0 } else { t_min };
+        t_max = if t1 < t_max { t1 } else { t_max };
+        if t_max < t_min {
+            return None;
+        }
+    }
+    Some(t_min)
+}
+
+// Distance and barycentric coordinates of the second and third vertex. Both faces are hit.
+pub fn ray_triangle_intersection(
+    ray: &Ray,
+    triangle: &[Vector3<f32>; 3],
+    max_distance: f32,
+) -> Option<(f32, [f32; 2])> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < f32::EPSILON * edge1.norm() * edge2.norm() * ray.direction.norm() {
+        return None;
+    }
+    let inverse = 1. / determinant;
+    let s = ray.origin - triangle[0];
+    let u = s.dot(&p) * inverse;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = ray.direction.dot(&q) * inverse;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let distance = edge2.dot(&q) * inverse;
+    if distance < 0. || distance > max_distance {
+        return None;
+    }
+    Some((distance, [u, v]))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct BvhNode {
+    bounds: Aabb3<f32>,
+    // Index of the first child for internal nodes, the second child follows. First index in the
+    // primitive list for leaves.
+    first: usize,
+    // Zero for internal nodes.
+    count: usize,
+}
+
+const MAX_LEAF_SIZE: usize = 4;
+
+// Bounding volume hierarchy over primitives identified by their index, e.g. mesh triangles or
+// scene objects. Rebuild it when the primitives move.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    primitives: Vec<usize>,
+    primitive_bounds: Vec<Aabb3<f32>>,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb3<f32>]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            primitives: (0..bounds.len()).collect(),
+            primitive_bounds: bounds.to_vec(),
+        };
+        if !bounds.is_empty() {
+            bvh.nodes.push(BvhNode {
+                bounds: bounds[0],
+                first: 0,
+                count: bounds.len(),
+            });
+            bvh.subdivide(0, bounds);
+        }
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn bounds(&self) -> Option<&Aabb3<f32>> {
+        self.nodes.first().map(|n| &n.bounds)
+    }
+
+    // Calls intersect for the primitives whose bounds are hit closer than the closest hit so far,
+    // with that distance as limit. Intersect returns the hit distance and any data to return.
+    pub fn closest_hit<T, F>(&self, ray: &Ray, max_distance: f32, mut intersect: F) -> Option<T>
+    where
+        F: FnMut(usize, f32) -> Option<(f32, T)>,
+    {
+        let mut closest = None;
+        let mut max_distance = max_distance;
+        self.traverse(ray, &mut max_distance, |primitive, max_distance| {
+            if let Some((distance, hit)) = intersect(primitive, *max_distance) {
+                if distance <= *max_distance {
+                    *max_distance = distance;
+                    closest = Some(hit);
+                }
+            }
+            false
+        });
+        closest
+    }
+
+    // Stops at the first primitive for which intersect returns true, e.g. for line of sight.
+    pub fn any_hit<F>(&self, ray: &Ray, max_distance: f32, mut intersect: F) -> bool
+    where
+        F: FnMut(usize, f32) -> bool,
+    {
+        let mut max_distance = max_distance;
+        self.traverse(ray, &mut max_distance, |primitive, max_distance| {
+            intersect(primitive, *max_distance)
+        })
+    }
+
+    // Primitives whose bounds intersect the box.
+    pub fn overlapping(&self, aabb: &Aabb3<f32>) -> Vec<usize> {
+        let mut result = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds.intersects(aabb) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            } else {
+                result.extend(
+                    self.primitives[node.first..node.first + node.count]
+                        .iter()
+                        .filter(|p| self.primitive_bounds[**p].intersects(aabb)),
+                );
+            }
+        }
+        result
+    }
+
+    fn traverse<F>(&self, ray: &Ray, max_distance: &mut f32, mut visit: F) -> bool
+    where
+        F: FnMut(usize, &mut f32) -> bool,
+    {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if ray_aabb_intersection(ray, &node.bounds, *max_distance).is_none() {
+                continue;
+            }
+            if node.count == 0 {
+                // Visits the nearest child first, so that farther nodes are more likely culled.
+                let (near, far) = (node.first, node.first + 1);
+                let distance = |i: usize| {
+                    ray_aabb_intersection(ray, &self.nodes[i].bounds, f32::INFINITY)
+                        .unwrap_or(f32::INFINITY)
+                };
+                if distance(near) <= distance(far) {
+                    stack.push(far);
+                    stack.push(near);
+                } else {
+                    stack.push(near);
+                    stack.push(far);
+                }
+            } else {
+                for primitive in &self.primitives[node.first..node.first + node.count] {
+                    if visit(*primitive, max_distance) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    // Splits at the median of the primitive centers along the longest axis.
+    fn subdivide(&mut self, index: usize, bounds: &[Aabb3<f32>]) {
+        let (first, count) = (self.nodes[index].first, self.nodes[index].count);
+        let primitives = &mut self.primitives[first..first + count];
+        let mut node_bounds = bounds[primitives[0]];
+        for p in primitives.iter() {
+            node_bounds = node_bounds.union(&bounds[*p]);
+        }
+        self.nodes[index].bounds = node_bounds;
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let centers: Vec<Vector3<f32>> = primitives.iter().map(|p| bounds[*p].center()).collect();
+        let extent = Aabb3::from_points(&centers).unwrap().size();
+        let axis = extent.imax();
+        if extent[axis] <= 0. {
+            return;
+        }
+        let middle = count / 2;
+        primitives.select_nth_unstable_by(middle, |a, b| {
+            bounds[*a].center()[axis].total_cmp(&bounds[*b].center()[axis])
+        });
+
+        let children = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            first,
+            count: middle,
+        });
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            first: first + middle,
+            count: count - middle,
+        });
+        self.nodes[index].first = children;
+        self.nodes[index].count = 0;
+        self.subdivide(children, bounds);
+        self.subdivide(children + 1, bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn unit_box(x: f32) -> Aabb3<f32> {
+        Aabb3::new(Vector3::new(x, 0., 0.), Vector3::new(x + 1., 1., 1.))
+    }
+
+    #[test]
+    fn ray_box() {
+        let aabb = unit_box(2.);
+        let ray = Ray::new(Vector3::new(0., 0.5, 0.5), Vector3::x());
+        expect_that!(&ray_aabb_intersection(&ray, &aabb, 10.), eq(Some(2.)));
+        expect_that!(&ray_aabb_intersection(&ray, &aabb, 1.), eq(None));
+        let inside = Ray::new(Vector3::new(2.5, 0.5, 0.5), -Vector3::x());
+        expect_that!(&ray_aabb_intersection(&inside, &aabb, 10.), eq(Some(0.)));
+        let miss = Ray::new(Vector3::new(0., 2., 0.5), Vector3::x());
+        expect_that!(&ray_aabb_intersection(&miss, &aabb, 10.), eq(None));
+    }
+
+    #[test]
+    fn ray_triangle() {
+        let triangle = [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(1., 0., 0.),
+            Vector3::new(0., 1., 0.),
+        ];
+        let ray = Ray::new(Vector3::new(0.25, 0.5, 2.), Vector3::new(0., 0., -2.));
+        let (distance, barycentric) = ray_triangle_intersection(&ray, &triangle, 10.).unwrap();
+        expect_that!(&distance, close_to(1., 1e-6));
+        expect_that!(&barycentric, eq([0.25, 0.5]));
+        let miss = Ray::new(Vector3::new(0.75, 0.5, 2.), Vector3::new(0., 0., -1.));
+        expect_that!(ray_triangle_intersection(&miss, &triangle, 10.).is_none());
+        let behind = Ray::new(Vector3::new(0.25, 0.25, 2.), Vector3::new(0., 0., 1.));
+        expect_that!(ray_triangle_intersection(&behind, &triangle, 10.).is_none());
+    }
+
+    #[test]
+    fn queries() {
+        let bounds: Vec<Aabb3<f32>> = (0..20).map(|i| unit_box(i as f32 * 2.)).collect();
+        let bvh = Bvh::new(&bounds);
+        expect_that!(&bvh.bounds().unwrap().max.x, eq(39.));
+
+        let ray = Ray::new(Vector3::new(-10., 0.5, 0.5), Vector3::x());
+        let mut tested = 0;
+        let closest = bvh.closest_hit(&ray, f32::INFINITY, |i, max_distance| {
+            tested += 1;
+            ray_aabb_intersection(&ray, &bounds[i], max_distance).map(|d| (d, i))
+        });
+        expect_that!(&closest, eq(Some(0)));
+        expect_that!(tested < 20);
+
+        let backwards = Ray::new(Vector3::new(50., 0.5, 0.5), -Vector3::x());
+        let closest = bvh.closest_hit(&backwards, f32::INFINITY, |i, max_distance| {
+            ray_aabb_intersection(&backwards, &bounds[i], max_distance).map(|d| (d, i))
+        });
+        expect_that!(&closest, eq(Some(19)));
+        expect_that!(!bvh.any_hit(&backwards, 5., |i, d| ray_aabb_intersection(
+            &backwards, &bounds[i], d
+        )
+        .is_some()));
+
+        let mut overlapping = bvh.overlapping(&Aabb3::new(
+            Vector3::new(3.5, 0., 0.),
+            Vector3::new(6.5, 1., 1.),
+        ));
+        overlapping.sort_unstable();
+        expect_that!(&overlapping, eq(vec![2, 3]));
+        expect_that!(Bvh::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn screen_ray() {
+        let projection = HomogeneousMatrix3::new_perspective(1., 1., 0.1, 100.);
+        let ray = Ray::from_ndc(&projection, 0., 0.).unwrap();
+        expect_that!((ray.direction - Vector3::new(0., 0., -1.)).norm() < 1e-4);
+    }
+}
@@ -0,0 +1,377 @@
+use roe_graphics as gfx;
+use roe_math::Vector3;
+
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum CubemapError {
+    ImageError(image::ImageError),
+    // Faces must be 6 square images with the same size.
+    InvalidFaces(String),
+}
+
+impl std::fmt::Display for CubemapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ImageError(e) => write!(f, "Image error ({})", e),
+            Self::InvalidFaces(description) => write!(f, "Invalid cubemap faces ({})", description),
+        }
+    }
+}
+
+impl std::error::Error for CubemapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ImageError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for CubemapError {
+    fn from(e: image::ImageError) -> Self {
+        Self::ImageError(e)
+    }
+}
+
+pub const CUBEMAP_FACE_COUNT: usize = 6;
+
+// Faces in texture layer order: +X, -X, +Y, -Y, +Z, -Z.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CubemapFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+impl CubemapFace {
+    pub const ALL: [CubemapFace; CUBEMAP_FACE_COUNT] = [
+        Self::PositiveX,
+        Self::NegativeX,
+        Self::PositiveY,
+        Self::NegativeY,
+        Self::PositiveZ,
+        Self::NegativeZ,
+    ];
+
+    // Direction through a point of the face, with coordinates from 0 to 1 starting from the top
+    // left corner of the face image. Not normalized.
+    pub fn direction(self, s: f32, t: f32) -> Vector3<f32> {
+        let (u, v) = (2. * s - 1., 2. * t - 1.);
+        match self {
+            Self::PositiveX => Vector3::new(1., -v, -u),
+            Self::NegativeX => Vector3::new(-1., -v, u),
+            Self::PositiveY => Vector3::new(u, 1., v),
+            Self::NegativeY => Vector3::new(u, -1., -v),
+            Self::PositiveZ => Vector3::new(u, -v, 1.),
+            Self::NegativeZ => Vector3::new(-u, -v, -1.),
+        }
+    }
+
+    // Face and face coordinates hit by a direction, as when sampling a cube texture.
+    pub fn from_direction(direction: &Vector3<f32>) -> (Self, f32, f32) {
+        let a = direction.abs();
+        let (face, sc, tc, ma) = if a.x >= a.y && a.x >= a.z {
+            if direction.x > 0. {
+                (Self::PositiveX, -direction.z, -direction.y, a.x)
+            } else {
+                (Self::NegativeX, direction.z, -direction.y, a.x)
+            }
+        } else if a.y >= a.z {
+            if direction.y > 0. {
+                (Self::PositiveY, direction.x, direction.z, a.y)
+            } else {
+                (Self::NegativeY, direction.x, -direction.z, a.y)
+            }
+        } else if direction.z > 0. {
+            (Self::PositiveZ, direction.x, -direction.y, a.z)
+        } else {
+            (Self::NegativeZ, -direction.x, -direction.y, a.z)
+        };
+        (face, (sc / ma + 1.) / 2., (tc / ma + 1.) / 2.)
+    }
+}
+
+// Cubemap with linear rgb values, e.g. for computing environment lighting.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CubemapImage {
+    size: u32,
+    faces: Vec<Vec<Vector3<f32>>>,
+}
+
+impl CubemapImage {
+    // Texels of each face in row order.
+    pub fn new(size: u32, faces: Vec<Vec<Vector3<f32>>>) -> Self {
+        assert!(size > 0, "The cubemap size must be positive");
+        assert!(
+            faces.len() == CUBEMAP_FACE_COUNT
+                && faces.iter().all(|f| f.len() == (size * size) as usize),
+            "Invalid cubemap faces"
+        );
+        Self { size, faces }
+    }
+
+    pub fn from_fn<F: FnMut(&Vector3<f32>) -> Vector3<f32>>(size: u32, mut f: F) -> Self {
+        let faces = CubemapFace::ALL
+            .iter()
+            .map(|face| {
+                (0..size * size)
+                    .map(|i| f(&texel_direction(*face, size, i % size, i / size)))
+                    .collect()
+            })
+            .collect();
+        Self::new(size, faces)
+    }
+
+    // Face images are sRGB encoded, in CubemapFace order.
+    pub fn from_images(images: &[image::RgbaImage]) -> Result<Self, CubemapError> {
+        if images.len() != CUBEMAP_FACE_COUNT {
+            return Err(CubemapError::InvalidFaces(format!(
+                "{} faces",
+                images.len()
+            )));
+        }
+        let size = images[0].width();
+        if size == 0 || images.iter().any(|i| i.dimensions() != (size, size)) {
+            return Err(CubemapError::InvalidFaces(String::from(
+                "the faces must be square and have the same size",
+            )));
+        }
+        let faces = images
+            .iter()
+            .map(|image| {
+                image
+                    .pixels()
+                    .map(|p| Vector3::from_fn(|i, _| gfx::srgb_to_linear(p[i] as f32 / 255.)))
+                    .collect()
+            })
+            .collect();
+        Ok(Self::new(size, faces))
+    }
+
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Self, CubemapError> {
+        let images = paths
+            .iter()
+            .map(|path| Ok(image::open(path)?.to_rgba8()))
+            .collect::<Result<Vec<_>, CubemapError>>()?;
+        Self::from_images(&images)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn map<F: FnMut(&Vector3<f32>) -> Vector3<f32>>(&self, mut f: F) -> Self {
+        let faces = self
+            .faces
+            .iter()
+            .map(|face| face.iter().map(&mut f).collect())
+            .collect();
+        Self::new(self.size, faces)
+    }
+
+    pub fn texel(&self, face: CubemapFace, x: u32, y: u32) -> &Vector3<f32> {
+        &self.faces[face as usize][(y * self.size + x) as usize]
+    }
+
+    // Normalized direction through the center of a texel.
+    pub fn texel_direction(&self, face: CubemapFace, x: u32, y: u32) -> Vector3<f32> {
+        texel_direction(face, self.size, x, y)
+    }
+
+    // Bilinear sampling inside the face hit by the direction.
+    pub fn sample(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let (face, s, t) = CubemapFace::from_direction(direction);
+        let max = (self.size - 1) as f32;
+        let x = (s * self.size as f32 - 0.5).clamp(0., max);
+        let y = (t * self.size as f32 - 0.5).clamp(0., max);
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let top = self.texel(face, x0, y0).lerp(self.texel(face, x1, y0), fx);
+        let bottom = self.texel(face, x0, y1).lerp(self.texel(face, x1, y1), fx);
+        top.lerp(&bottom, fy)
+    }
+
+    // Half size cubemap, averaging 2x2 texels.
+    pub fn downsample(&self) -> Self {
+        let size = (self.size / 2).max(1);
+        let step = self.size / size;
+        let faces = CubemapFace::ALL
+            .iter()
+            .map(|face| {
+                (0..size * size)
+                    .map(|i| {
+                        let (x, y) = (i % size * step, i / size * step);
+                        let mut sum = Vector3::zeros();
+                        for dy in 0..step {
+                            for dx in 0..step {
+                                sum += self.texel(*face, x + dx, y + dy);
+                            }
+                        }
+                        sum / (step * step) as f32
+                    })
+                    .collect()
+            })
+            .collect();
+        Self::new(size, faces)
+    }
+
+    // sRGB encoded rgba texels of a face, clamped to 1.
+    pub fn face_bytes(&self, face: CubemapFace) -> Vec<u8> {
+        self.faces[face as usize]
+            .iter()
+            .flat_map(|c| {
+                let channel = |v: f32| (gfx::linear_to_srgb(v.clamp(0., 1.)) * 255. + 0.5) as u8;
+                [channel(c.x), channel(c.y), channel(c.z), 255]
+            })
+            .collect()
+    }
+}
+
+fn texel_direction(face: CubemapFace, size: u32, x: u32, y: u32) -> Vector3<f32> {
+    face.direction(
+        (x as f32 + 0.5) / size as f32,
+        (y as f32 + 0.5) / size as f32,
+    )
+    .normalize()
+}
+
+// Cube texture, created with the workflow texture format like the other color textures.
+#[derive(Debug)]
+pub struct Cubemap {
+    texture: gfx::Texture,
+    view: gfx::TextureView,
+}
+
+impl Cubemap {
+    pub fn new(instance: &gfx::Instance, image: &CubemapImage) -> Self {
+        Self::with_mip_levels(instance, std::slice::from_ref(image))
+    }
+
+    // Each level must be half the size of the previous one.
+    pub fn with_mip_levels(instance: &gfx::Instance, levels: &[CubemapImage]) -> Self {
+        assert!(!levels.is_empty(), "A cubemap needs at least one level");
+        for (i, level) in levels.iter().enumerate() {
+            assert!(
+                level.size() == (levels[0].size() >> i).max(1),
+                "Invalid cubemap mip level size"
+            );
+        }
+        let size = levels[0].size();
+        let texture = gfx::Texture::new(
+            instance,
+            &gfx::TextureDescriptor {
+                label: None,
+                size: gfx::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: CUBEMAP_FACE_COUNT as u32,
+                },
+                mip_level_count: levels.len() as u32,
+                sample_count: 1,
+                dimension: gfx::TextureDimension::D2,
+                format: instance.color_workflow().texture_format(),
+                usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+            },
+        );
+        for (mip_level, level) in levels.iter().enumerate() {
+            for face in CubemapFace::ALL.iter() {
+                texture.write(
+                    instance,
+                    mip_level as u32,
+                    gfx::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: *face as u32,
+                    },
+                    &level.face_bytes(*face),
+                    gfx::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: core::num::NonZeroU32::new(4 * level.size()),
+                        rows_per_image: None,
+                    },
+                    gfx::Extent3d {
+                        width: level.size(),
+                        height: level.size(),
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+        }
+        let view = texture.create_view(&gfx::TextureViewDescriptor {
+            dimension: Some(gfx::TextureViewDimension::Cube),
+            ..gfx::TextureViewDescriptor::default()
+        });
+        Self { texture, view }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.texture.size().width
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.texture.mip_level_count()
+    }
+
+    pub fn view(&self) -> &gfx::TextureView {
+        &self.view
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn face_directions() {
+        for face in CubemapFace::ALL.iter() {
+            for (s, t) in [(0.5, 0.5), (0.1, 0.8), (0.9, 0.3)] {
+                let (hit, hs, ht) = CubemapFace::from_direction(&face.direction(s, t));
+                expect_that!(&hit, eq(*face));
+                expect_that!(&hs, close_to(s, 1e-6));
+                expect_that!(&ht, close_to(t, 1e-6));
+            }
+        }
+        expect_that!(
+            &CubemapFace::PositiveY.direction(0.5, 0.5),
+            eq(Vector3::new(0., 1., 0.))
+        );
+    }
+
+    #[test]
+    fn sampling() {
+        let cubemap = CubemapImage::from_fn(8, |d| d.map(|c| c.max(0.)));
+        let up = cubemap.sample(&Vector3::new(0., 1., 0.));
+        expect_that!(&up.y, close_to(1., 0.05));
+        expect_that!(&up.x, close_to(0., 0.1));
+
+        let small = cubemap.downsample();
+        expect_that!(&small.size(), eq(4));
+        expect_that!(&small.sample(&Vector3::new(0., 0., -1.)).z, eq(0.));
+
+        let bytes = cubemap.face_bytes(CubemapFace::NegativeX);
+        expect_that!(&bytes.len(), eq(8 * 8 * 4));
+        expect_that!(&bytes[3], eq(255));
+    }
+
+    #[test]
+    fn invalid_images() {
+        let face = image::RgbaImage::new(4, 4);
+        let faces = vec![face.clone(); 5];
+        expect_that!(matches!(
+            CubemapImage::from_images(&faces),
+            Err(CubemapError::InvalidFaces(_))
+        ));
+        let mut faces = vec![face; 6];
+        faces[2] = image::RgbaImage::new(4, 2);
+        expect_that!(matches!(
+            CubemapImage::from_images(&faces),
+            Err(CubemapError::InvalidFaces(_))
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+use super::{
+    Mesh, MeshIndexRange, PbrLighting, PbrMaterialUniforms, PbrPipeline, PbrPushConstants,
+    PbrRenderer,
+};
+
+use roe_graphics as gfx;
+use roe_math::{Aabb3, HomogeneousMatrix3, Point3, Vector3, Vector4};
+use roe_sprite::{DrawQueue, DrawQueueItem};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vector3<f32>, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn from_aabb(aabb: &Aabb3<f32>) -> Self {
+        Self {
+            center: aabb.center(),
+            radius: aabb.size().norm() / 2.,
+        }
+    }
+
+    // Non uniform scaling grows the radius by the largest scale factor.
+    pub fn transformed(&self, transform: &HomogeneousMatrix3<f32>) -> Self {
+        let scale = (0..3)
+            .map(|i| transform.fixed_slice::<3, 1>(0, i).norm())
+            .fold(0., f32::max);
+        Self {
+            center: transform.transform_point(&Point3::from(self.center)).coords,
+            radius: self.radius * scale,
+        }
+    }
+}
+
+// World space bounds of a mesh instance.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct InstanceBounds {
+    pub aabb: Aabb3<f32>,
+    pub sphere: BoundingSphere,
+}
+
+impl InstanceBounds {
+    pub fn new(local_bounds: &Aabb3<f32>, transform: &HomogeneousMatrix3<f32>) -> Self {
+        Self {
+            aabb: local_bounds.transformed(transform),
+            sphere: BoundingSphere::from_aabb(local_bounds).transformed(transform),
+        }
+    }
+}
+
+// The 6 clipping planes of a view projection matrix, with the clip space depth in [0, 1]. The
+// plane normals point inside.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    pub fn new(view_projection: &HomogeneousMatrix3<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = plane.xyz().norm();
+            if length > 0. {
+                *plane /= length;
+            }
+        }
+        Self { planes }
+    }
+
+    pub fn planes(&self) -> &[Vector4<f32>; 6] {
+        &self.planes
+    }
+
+    fn distance(plane: &Vector4<f32>, p: &Vector3<f32>) -> f32 {
+        plane.xyz().dot(p) + plane.w
+    }
+
+    pub fn contains_point(&self, p: &Vector3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Self::distance(plane, p) >= 0.)
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Self::distance(plane, &sphere.center) >= -sphere.radius)
+    }
+
+    // Conservative: boxes near the frustum corners can be reported as visible.
+    pub fn intersects_aabb(&self, aabb: &Aabb3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            let farthest = Vector3::from_fn(|i, _| {
+                if plane[i] >= 0. {
+                    aabb.max[i]
+                } else {
+                    aabb.min[i]
+                }
+            });
+            Self::distance(plane, &farthest) >= 0.
+        })
+    }
+
+    pub fn intersects(&self, bounds: &InstanceBounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+
+// Culls instances against the camera frustum and pushes the visible ones in a draw queue, with
+// their view depth as sorting key. Use FrontToBack layers for opaque meshes and BackToFront
+// layers for blended ones; 2D layers can share the same queue.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FrustumCuller {
+    view: HomogeneousMatrix3<f32>,
+    frustum: Frustum,
+}
+
+impl FrustumCuller {
+    pub fn new(view: &HomogeneousMatrix3<f32>, projection: &HomogeneousMatrix3<f32>) -> Self {
+        Self {
+            view: *view,
+            frustum: Frustum::new(&(projection * view)),
+        }
+    }
+
+    pub fn frustum(&self) -> &Frustum {
+        &self.frustum
+    }
+
+    pub fn is_visible(&self, bounds: &InstanceBounds) -> bool {
+        self.frustum.intersects(bounds)
+    }
+
+    // Distance in front of the camera, along the view direction.
+    pub fn view_depth(&self, p: &Vector3<f32>) -> f32 {
+        -self.view.transform_point(&Point3::from(*p)).z
+    }
+
+    // Returns false if the instance was culled.
+    pub fn push<T>(
+        &self,
+        queue: &mut DrawQueue<T>,
+        layer: i32,
+        bounds: &InstanceBounds,
+        value: T,
+    ) -> bool {
+        if !self.is_visible(bounds) {
+            return false;
+        }
+        queue.push_with_depth(layer, self.view_depth(&bounds.sphere.center), value);
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct PbrDrawCommand<'a> {
+    pub material: &'a PbrMaterialUniforms,
+    pub mesh: &'a Mesh,
+    pub push_constants: &'a PbrPushConstants,
+    pub index_range: MeshIndexRange,
+}
+
+pub trait PbrDrawQueueRenderer<'a> {
+    // Draws sorted queue items with a single pipeline, e.g. the items of a layer, see
+    // DrawQueue::layer_items.
+    fn draw_pbr_items(
+        &mut self,
+        pipeline: &'a PbrPipeline,
+        lighting: PbrLighting<'a>,
+        items: &'a [DrawQueueItem<PbrDrawCommand<'a>>],
+    );
+}
+
+impl<'a> PbrDrawQueueRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_pbr_items(
+        &mut self,
+        pipeline: &'a PbrPipeline,
+        lighting: PbrLighting<'a>,
+        items: &'a [DrawQueueItem<PbrDrawCommand<'a>>],
+    ) {
+        for item in items {
+            let command = &item.value;
+            self.draw_pbr_mesh(
+                pipeline,
+                lighting,
+                command.material,
+                command.mesh,
+                command.push_constants,
+                command.index_range.clone(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use roe_sprite::DrawSortMode;
+
+    fn unit_box() -> Aabb3<f32> {
+        Aabb3::new(Vector3::new(-1., -1., -1.), Vector3::new(1., 1., 1.))
+    }
+
+    fn culler() -> FrustumCuller {
+        // Camera at z = 10, looking towards -z.
+        let view = HomogeneousMatrix3::look_at_rh(
+            &Point3::new(0., 0., 10.),
+            &Point3::origin(),
+            &Vector3::y(),
+        );
+        let projection = HomogeneousMatrix3::new_perspective(1., 1., 0.1, 100.);
+        FrustumCuller::new(&view, &projection)
+    }
+
+    #[test]
+    fn bounding_sphere_transformed() {
+        let sphere = BoundingSphere::from_aabb(&unit_box());
+        expect_that!(&sphere.radius, close_to(3f32.sqrt(), 1e-6));
+        let transform = HomogeneousMatrix3::new_translation(&Vector3::new(1., 2., 3.))
+            * HomogeneousMatrix3::new_nonuniform_scaling(&Vector3::new(1., 4., 2.));
+        let sphere = sphere.transformed(&transform);
+        expect_that!(&sphere.center, eq(Vector3::new(1., 2., 3.)));
+        expect_that!(&sphere.radius, close_to(4. * 3f32.sqrt(), 1e-5));
+    }
+
+    #[test]
+    fn frustum_tests() {
+        let frustum = *culler().frustum();
+        expect_that!(frustum.contains_point(&Vector3::zeros()));
+        expect_that!(!frustum.contains_point(&Vector3::new(0., 0., 11.)));
+        expect_that!(!frustum.contains_point(&Vector3::new(0., 0., -100.)));
+        expect_that!(!frustum.contains_point(&Vector3::new(20., 0., 0.)));
+
+        let at = |x: f32, z: f32| {
+            InstanceBounds::new(
+                &unit_box(),
+                &HomogeneousMatrix3::new_translation(&Vector3::new(x, 0., z)),
+            )
+        };
+        expect_that!(frustum.intersects(&at(0., 0.)));
+        // Partially inside the left plane.
+        expect_that!(frustum.intersects(&at(-5.9, 0.)));
+        expect_that!(!frustum.intersects(&at(-8., 0.)));
+        expect_that!(!frustum.intersects(&at(0., 12.)));
+        expect_that!(!frustum.intersects(&at(0., -95.)));
+    }
+
+    #[test]
+    fn culled_draw_sorting() {
+        let culler = culler();
+        let mut queue = DrawQueue::new(DrawSortMode::Submission);
+        queue.set_layer_sort_mode(0, Some(DrawSortMode::FrontToBack));
+        queue.set_layer_sort_mode(1, Some(DrawSortMode::BackToFront));
+        let mut push = |layer, z: f32, value| {
+            let bounds = InstanceBounds::new(
+                &unit_box(),
+                &HomogeneousMatrix3::new_translation(&Vector3::new(0., 0., z)),
+            );
+            culler.push(&mut queue, layer, &bounds, value)
+        };
+        expect_that!(push(0, -20., "opaque_far"));
+        expect_that!(push(0, 5., "opaque_near"));
+        expect_that!(!push(0, 20., "opaque_behind"));
+        expect_that!(push(1, 5., "blended_near"));
+        expect_that!(push(1, -20., "blended_far"));
+        queue.sort();
+        let order: Vec<&str> = queue.items().map(|item| item.value).collect();
+        expect_that!(
+            &order,
+            eq(vec![
+                "opaque_near",
+                "opaque_far",
+                "blended_far",
+                "blended_near"
+            ])
+        );
+        expect_that!(&queue.items().next().unwrap().depth, close_to(5., 1e-5));
+        expect_that!(&queue.layer_items(1).len(), eq(2));
+    }
+}
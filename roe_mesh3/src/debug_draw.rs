@@ -0,0 +1,541 @@
+use roe_graphics as gfx;
+use roe_math::{Aabb3, HomogeneousMatrix3, Point3, Vector3, Vector4};
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DebugLineVertex {
+    pub position: [f32; 3],
+    // In the workflow color space.
+    pub color: [f32; 4],
+}
+
+impl DebugLineVertex {
+    pub fn new(position: &Vector3<f32>, color: gfx::ColorF32) -> Self {
+        Self {
+            position: [position.x, position.y, position.z],
+            color: [color.r, color.g, color.b, color.a],
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for DebugLineVertex {
+    fn zeroed() -> Self {
+        Self {
+            position: [0.; 3],
+            color: [0.; 4],
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for DebugLineVertex {}
+
+// Lines collected during a frame to visualize e.g. colliders, cameras and light volumes.
+// Primitives are depth tested against the scene unless added while always on top is enabled.
+#[derive(Debug, Default, Clone)]
+pub struct DebugDraw3 {
+    depth_tested: Vec<DebugLineVertex>,
+    on_top: Vec<DebugLineVertex>,
+    always_on_top: bool,
+}
+
+impl DebugDraw3 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Applies to the primitives added afterwards.
+    pub fn set_always_on_top(&mut self, value: bool) {
+        self.always_on_top = value;
+    }
+
+    pub fn always_on_top(&self) -> bool {
+        self.always_on_top
+    }
+
+    pub fn clear(&mut self) {
+        self.depth_tested.clear();
+        self.on_top.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depth_tested.is_empty() && self.on_top.is_empty()
+    }
+
+    // Line list vertices.
+    pub fn depth_tested_vertices(&self) -> &[DebugLineVertex] {
+        &self.depth_tested
+    }
+
+    pub fn on_top_vertices(&self) -> &[DebugLineVertex] {
+        &self.on_top
+    }
+
+    pub fn line(&mut self, from: &Vector3<f32>, to: &Vector3<f32>, color: gfx::ColorF32) {
+        let vertices = if self.always_on_top {
+            &mut self.on_top
+        } else {
+            &mut self.depth_tested
+        };
+        vertices.push(DebugLineVertex::new(from, color));
+        vertices.push(DebugLineVertex::new(to, color));
+    }
+
+    // Red x, green y and blue z axes of a transform.
+    pub fn axes(&mut self, transform: &HomogeneousMatrix3<f32>, size: f32) {
+        let origin = transform_point(transform, &Vector3::zeros());
+        let colors = [
+            gfx::ColorF32::RED,
+            gfx::ColorF32::GREEN,
+            gfx::ColorF32::BLUE,
+        ];
+        for (axis, color) in colors.iter().enumerate() {
+            let tip = transform_point(transform, &(Vector3::ith(axis, size)));
+            self.line(&origin, &tip, *color);
+        }
+    }
+
+    // Grid on the xz plane, with the given number of cells per side.
+    pub fn grid(&mut self, center: &Vector3<f32>, size: f32, divisions: u32, color: gfx::ColorF32) {
+        let divisions = divisions.max(1);
+        let half = size / 2.;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            self.line(
+                &(center + Vector3::new(offset, 0., -half)),
+                &(center + Vector3::new(offset, 0., half)),
+                color,
+            );
+            self.line(
+                &(center + Vector3::new(-half, 0., offset)),
+                &(center + Vector3::new(half, 0., offset)),
+                color,
+            );
+        }
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb3<f32>, color: gfx::ColorF32) {
+        self.box_edges(&aabb.corners(), color);
+    }
+
+    // Box in the local space of a transform.
+    pub fn oriented_box(
+        &mut self,
+        transform: &HomogeneousMatrix3<f32>,
+        aabb: &Aabb3<f32>,
+        color: gfx::ColorF32,
+    ) {
+        let mut corners = aabb.corners();
+        for corner in corners.iter_mut() {
+            *corner = transform_point(transform, corner);
+        }
+        self.box_edges(&corners, color);
+    }
+
+    // Volume visible through a view projection matrix, e.g. of a camera or a shadow cascade.
+    pub fn frustum(&mut self, view_projection: &HomogeneousMatrix3<f32>, color: gfx::ColorF32) {
+        let inverse = match view_projection.try_inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let ndc = Aabb3::new(Vector3::new(-1., -1., 0.), Vector3::new(1., 1., 1.));
+        let mut corners = ndc.corners();
+        for corner in corners.iter_mut() {
+            let p = inverse * Vector4::new(corner.x, corner.y, corner.z, 1.);
+            *corner = p.xyz() / p.w;
+        }
+        self.box_edges(&corners, color);
+    }
+
+    pub fn circle(
+        &mut self,
+        center: &Vector3<f32>,
+        normal: &Vector3<f32>,
+        radius: f32,
+        segments: u32,
+        color: gfx::ColorF32,
+    ) {
+        let normal = normal.normalize();
+        let reference = if normal.y.abs() < 0.99 {
+            Vector3::y()
+        } else {
+            Vector3::x()
+        };
+        let u = normal.cross(&reference).normalize() * radius;
+        let v = normal.cross(&u);
+        let segments = segments.max(3);
+        let point = |i: u32| {
+            let angle = 2. * std::f32::consts::PI * i as f32 / segments as f32;
+            center + u * angle.cos() + v * angle.sin()
+        };
+        for i in 0..segments {
+            self.line(&point(i), &point(i + 1), color);
+        }
+    }
+
+    // Three circles around the coordinate axes.
+    pub fn sphere(
+        &mut self,
+        center: &Vector3<f32>,
+        radius: f32,
+        segments: u32,
+        color: gfx::ColorF32,
+    ) {
+        for axis in 0..3 {
+            self.circle(center, &Vector3::ith(axis, 1.), radius, segments, color);
+        }
+    }
+
+    // Corners ordered as in Aabb3::corners.
+    fn box_edges(&mut self, corners: &[Vector3<f32>; 8], color: gfx::ColorF32) {
+        for i in 0..8 {
+            for axis in 0..3 {
+                if i & (1 << axis) == 0 {
+                    self.line(&corners[i], &corners[i | (1 << axis)], color);
+                }
+            }
+        }
+    }
+}
+
+fn transform_point(transform: &HomogeneousMatrix3<f32>, p: &Vector3<f32>) -> Vector3<f32> {
+    transform.transform_point(&Point3::from(*p)).coords
+}
+
+#[derive(Debug)]
+struct LineBuffer {
+    buffer: gfx::Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl LineBuffer {
+    fn new(instance: &gfx::Instance) -> Self {
+        Self {
+            buffer: Self::create_buffer(instance, 2),
+            capacity: 2,
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, instance: &gfx::Instance, vertices: &[DebugLineVertex]) {
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.buffer = Self::create_buffer(instance, self.capacity);
+        }
+        self.len = vertices.len();
+        if !vertices.is_empty() {
+            instance.write_buffer(&self.buffer, 0, bytemuck::cast_slice(vertices));
+        }
+    }
+
+    fn create_buffer(instance: &gfx::Instance, capacity: usize) -> gfx::Buffer {
+        gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (capacity * std::mem::size_of::<DebugLineVertex>()) as gfx::BufferAddress,
+                usage: gfx::BufferUsage::VERTEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+}
+
+// Gpu copy of a DebugDraw3, reallocated when it gets too small.
+#[derive(Debug)]
+pub struct DebugDrawBuffer {
+    depth_tested: LineBuffer,
+    on_top: LineBuffer,
+}
+
+impl DebugDrawBuffer {
+    pub fn new(instance: &gfx::Instance, debug_draw: &DebugDraw3) -> Self {
+        let mut buffer = Self {
+            depth_tested: LineBuffer::new(instance),
+            on_top: LineBuffer::new(instance),
+        };
+        buffer.update(instance, debug_draw);
+        buffer
+    }
+
+    // The write is queued and applied before the next submitted commands.
+    pub fn update(&mut self, instance: &gfx::Instance, debug_draw: &DebugDraw3) {
+        self.depth_tested
+            .update(instance, debug_draw.depth_tested_vertices());
+        self.on_top.update(instance, debug_draw.on_top_vertices());
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DebugDrawPushConstants {
+    view_projection: HomogeneousMatrix3<f32>,
+}
+
+impl DebugDrawPushConstants {
+    pub fn new(view_projection: &HomogeneousMatrix3<f32>) -> Self {
+        Self {
+            view_projection: *view_projection,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for DebugDrawPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            view_projection: HomogeneousMatrix3::zeros(),
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for DebugDrawPushConstants {}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<DebugDrawPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DebugDrawPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for DebugDrawPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat::Depth32Float,
+            sample_count: 1,
+        }
+    }
+}
+
+// Alpha blended lines, drawn after the scene. Lines never write the depth buffer.
+#[derive(Debug)]
+pub struct DebugDrawPipeline {
+    depth_tested: gfx::RenderPipeline,
+    on_top: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl DebugDrawPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &DebugDrawPipelineDescriptor) -> Self {
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_CONVERSION_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_CONVERSION_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/debug_line.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/debug_line.frag.spv"),
+        );
+        let create_pipeline = |depth_compare| {
+            gfx::RenderPipeline::new(
+                instance,
+                &gfx::RenderPipelineDescriptor {
+                    label: desc.label.as_deref(),
+                    layout: Some(&pipeline_layout),
+                    vertex: gfx::VertexState {
+                        module: &vs_module,
+                        entry_point: "main",
+                        buffers: &[gfx::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<DebugLineVertex>()
+                                as gfx::BufferAddress,
+                            step_mode: gfx::VertexStepMode::Vertex,
+                            attributes: &[
+                                gfx::VertexAttribute {
+                                    format: gfx::VertexFormat::Float32x3,
+                                    offset: 0,
+                                    shader_location: 0,
+                                },
+                                gfx::VertexAttribute {
+                                    format: gfx::VertexFormat::Float32x4,
+                                    offset: 12,
+                                    shader_location: 1,
+                                },
+                            ],
+                        }],
+                    },
+                    primitive: gfx::PrimitiveState {
+                        topology: gfx::PrimitiveTopology::LineList,
+                        strip_index_format: None,
+                        front_face: gfx::FrontFace::Ccw,
+                        cull_mode: None,
+                        clamp_depth: false,
+                        polygon_mode: gfx::PolygonMode::Fill,
+                        conservative: false,
+                    },
+                    depth_stencil: Some(gfx::DepthStencilState {
+                        format: gfx::TextureFormat::from(desc.depth_stencil_buffer_format),
+                        depth_write_enabled: false,
+                        depth_compare,
+                        stencil: gfx::StencilState::default(),
+                        bias: gfx::DepthBiasState::default(),
+                    }),
+                    multisample: gfx::MultisampleState {
+                        count: desc.sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    fragment: Some(gfx::FragmentState {
+                        module: &fs_module,
+                        entry_point: "main",
+                        targets: &[gfx::ColorTargetState {
+                            format: gfx::TextureFormat::from(desc.color_buffer_format),
+                            blend: Some(gfx::BlendState::ALPHA_BLENDING),
+                            write_mask: gfx::ColorWrite::ALL,
+                        }],
+                    }),
+                },
+            )
+        };
+        Self {
+            depth_tested: create_pipeline(gfx::CompareFunction::LessEqual),
+            on_top: create_pipeline(gfx::CompareFunction::Always),
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: Some(self.depth_stencil_buffer_format),
+        }
+    }
+}
+
+pub trait DebugDrawRenderer<'a> {
+    fn draw_debug_lines(
+        &mut self,
+        pipeline: &'a DebugDrawPipeline,
+        buffer: &'a DebugDrawBuffer,
+        push_constants: &'a DebugDrawPushConstants,
+    );
+}
+
+impl<'a> DebugDrawRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_debug_lines(
+        &mut self,
+        pipeline: &'a DebugDrawPipeline,
+        buffer: &'a DebugDrawBuffer,
+        push_constants: &'a DebugDrawPushConstants,
+    ) {
+        // The lines on top are drawn last.
+        for (render_pipeline, lines) in [
+            (&pipeline.depth_tested, &buffer.depth_tested),
+            (&pipeline.on_top, &buffer.on_top),
+        ] {
+            if lines.len == 0 {
+                continue;
+            }
+            self.set_pipeline(render_pipeline);
+            self.set_push_constants(
+                gfx::ShaderStage::VERTEX,
+                0,
+                gfx::utility::as_slice(push_constants),
+            );
+            self.set_push_constants(
+                gfx::ShaderStage::FRAGMENT,
+                PC_CONVERSION_MEM_OFFSET,
+                gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+            );
+            self.set_vertex_buffer(0, lines.buffer.slice(..));
+            self.draw(0..lines.len as u32, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn positions(vertices: &[DebugLineVertex]) -> Vec<[f32; 3]> {
+        vertices.iter().map(|v| v.position).collect()
+    }
+
+    #[test]
+    fn depth_test_toggle() {
+        let mut debug_draw = DebugDraw3::new();
+        debug_draw.line(&Vector3::zeros(), &Vector3::x(), gfx::ColorF32::WHITE);
+        debug_draw.set_always_on_top(true);
+        debug_draw.axes(&HomogeneousMatrix3::identity(), 2.);
+        expect_that!(&debug_draw.depth_tested_vertices().len(), eq(2));
+        expect_that!(
+            &positions(debug_draw.on_top_vertices()),
+            eq(vec![
+                [0., 0., 0.],
+                [2., 0., 0.],
+                [0., 0., 0.],
+                [0., 2., 0.],
+                [0., 0., 0.],
+                [0., 0., 2.]
+            ])
+        );
+        debug_draw.clear();
+        expect_that!(debug_draw.is_empty());
+    }
+
+    #[test]
+    fn primitive_line_counts() {
+        let mut debug_draw = DebugDraw3::new();
+        let color = gfx::ColorF32::WHITE;
+        debug_draw.aabb(&Aabb3::new(Vector3::zeros(), Vector3::repeat(1.)), color);
+        expect_that!(&debug_draw.depth_tested_vertices().len(), eq(24));
+        debug_draw.clear();
+        debug_draw.grid(&Vector3::zeros(), 10., 4, color);
+        expect_that!(&debug_draw.depth_tested_vertices().len(), eq(20));
+        debug_draw.clear();
+        debug_draw.sphere(&Vector3::zeros(), 1., 16, color);
+        expect_that!(&debug_draw.depth_tested_vertices().len(), eq(96));
+        for v in debug_draw.depth_tested_vertices() {
+            let position = v.position;
+            expect_that!(&Vector3::from(position).norm(), close_to(1., 1e-5));
+        }
+    }
+
+    #[test]
+    fn frustum_corners() {
+        let mut debug_draw = DebugDraw3::new();
+        let projection = HomogeneousMatrix3::new_orthographic(-1., 1., -2., 2., 1., 5.);
+        debug_draw.frustum(&projection, gfx::ColorF32::WHITE);
+        let points = positions(debug_draw.depth_tested_vertices());
+        expect_that!(&points.len(), eq(24));
+        for p in points.iter() {
+            expect_that!(&p[0].abs(), close_to(1., 1e-5));
+            expect_that!(&p[1].abs(), close_to(2., 1e-5));
+            expect_that!(p[2] < -0.9 && p[2] > -5.1);
+        }
+    }
+}
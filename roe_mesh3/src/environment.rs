@@ -0,0 +1,497 @@
+use super::{Cubemap, CubemapFace, CubemapImage, CUBEMAP_FACE_COUNT};
+
+use roe_graphics as gfx;
+use roe_math::Vector3;
+
+// Spherical harmonics coefficients (3 bands) of the irradiance of an environment, already
+// convolved with the cosine lobe and divided by pi: evaluating them in a normal direction gives
+// the diffuse light reflected by a white lambertian surface.
+pub type IrradianceSh = [Vector3<f32>; 9];
+
+fn sh_basis(d: &Vector3<f32>) -> [f32; 9] {
+    [
+        0.282_095,
+        0.488_603 * d.y,
+        0.488_603 * d.z,
+        0.488_603 * d.x,
+        1.092_548 * d.x * d.y,
+        1.092_548 * d.y * d.z,
+        0.315_392 * (3. * d.z * d.z - 1.),
+        1.092_548 * d.x * d.z,
+        0.546_274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+// Cosine lobe convolution factors per band, divided by pi.
+const SH_BAND_FACTORS: [f32; 9] = [1., 2. / 3., 2. / 3., 2. / 3., 0.25, 0.25, 0.25, 0.25, 0.25];
+
+pub fn irradiance_sh(environment: &CubemapImage) -> IrradianceSh {
+    let size = environment.size();
+    let mut coefficients = [Vector3::zeros(); 9];
+    let mut total_weight = 0.;
+    for face in CubemapFace::ALL.iter() {
+        for y in 0..size {
+            for x in 0..size {
+                // Solid angle of the texel.
+                let u = 2. * (x as f32 + 0.5) / size as f32 - 1.;
+                let v = 2. * (y as f32 + 0.5) / size as f32 - 1.;
+                let weight = 1. / (1. + u * u + v * v).powf(1.5);
+                let direction = environment.texel_direction(*face, x, y);
+                let radiance = environment.texel(*face, x, y);
+                for (c, basis) in coefficients.iter_mut().zip(sh_basis(&direction)) {
+                    *c += radiance * (basis * weight);
+                }
+                total_weight += weight;
+            }
+        }
+    }
+    // The weights sum to 4 pi, normalizing removes the discretization error.
+    let normalization = 4. * std::f32::consts::PI / total_weight;
+    for (c, factor) in coefficients.iter_mut().zip(SH_BAND_FACTORS) {
+        *c *= normalization * factor;
+    }
+    coefficients
+}
+
+pub fn evaluate_sh(coefficients: &IrradianceSh, normal: &Vector3<f32>) -> Vector3<f32> {
+    coefficients
+        .iter()
+        .zip(sh_basis(normal))
+        .fold(Vector3::zeros(), |sum, (c, basis)| sum + c * basis)
+}
+
+fn hammersley(i: u32, count: u32) -> (f32, f32) {
+    (
+        i as f32 / count as f32,
+        i.reverse_bits() as f32 * 2.328_306_4e-10,
+    )
+}
+
+// Half vector around the z axis, distributed according to the GGX distribution.
+fn importance_sample_ggx(xi: (f32, f32), roughness: f32) -> Vector3<f32> {
+    let alpha = roughness * roughness;
+    let phi = 2. * std::f32::consts::PI * xi.0;
+    let cos_theta = ((1. - xi.1) / (1. + (alpha * alpha - 1.) * xi.1)).sqrt();
+    let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+    Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+fn tangent_to_world(v: &Vector3<f32>, n: &Vector3<f32>) -> Vector3<f32> {
+    let up = if n.z.abs() < 0.999 {
+        Vector3::z()
+    } else {
+        Vector3::x()
+    };
+    let tangent = up.cross(n).normalize();
+    let bitangent = n.cross(&tangent);
+    tangent * v.x + bitangent * v.y + n * v.z
+}
+
+// Mip chain of the environment convolved with the GGX lobe, with roughness going linearly from 0
+// to 1. Samples are read from a lower resolution level of the source when their lobe covers many
+// texels, removing most of the noise.
+pub fn prefilter_environment(
+    source: &CubemapImage,
+    mip_level_count: u32,
+    sample_count: u32,
+) -> Vec<CubemapImage> {
+    assert!(mip_level_count > 0, "There must be at least one mip level");
+    assert!(sample_count > 0, "There must be at least one sample");
+    let mut source_levels = vec![source.clone()];
+    while source_levels.last().unwrap().size() > 1 {
+        let next = source_levels.last().unwrap().downsample();
+        source_levels.push(next);
+    }
+    let texel_solid_angle =
+        4. * std::f32::consts::PI / (CUBEMAP_FACE_COUNT as f32 * (source.size() as f32).powi(2));
+
+    let mut levels = vec![source.clone()];
+    for mip_level in 1..mip_level_count {
+        let size = (source.size() >> mip_level).max(1);
+        let roughness = mip_level as f32 / (mip_level_count - 1) as f32;
+        let alpha2 = roughness.powi(4);
+        let level = CubemapImage::from_fn(size, |n| {
+            let mut sum = Vector3::zeros();
+            let mut total_weight = 0.;
+            for i in 0..sample_count {
+                let h = tangent_to_world(
+                    &importance_sample_ggx(hammersley(i, sample_count), roughness),
+                    n,
+                );
+                let n_dot_h = n.dot(&h).max(0.);
+                // The view direction is assumed equal to the normal.
+                let l = h * (2. * n_dot_h) - n;
+                let n_dot_l = n.dot(&l);
+                if n_dot_l <= 0. {
+                    continue;
+                }
+                let d = n_dot_h * n_dot_h * (alpha2 - 1.) + 1.;
+                let pdf = alpha2 / (std::f32::consts::PI * d * d) / 4.;
+                let sample_solid_angle = 1. / (sample_count as f32 * pdf + 1e-4);
+                let source_level = (0.5 * (sample_solid_angle / texel_solid_angle).log2() + 1.)
+                    .clamp(0., (source_levels.len() - 1) as f32);
+                sum += source_levels[source_level.round() as usize].sample(&l) * n_dot_l;
+                total_weight += n_dot_l;
+            }
+            sum / total_weight.max(1e-4)
+        });
+        levels.push(level);
+    }
+    levels
+}
+
+// Scale (first value) and bias (second value) applied to the Fresnel reflectance at normal
+// incidence by the split sum approximation. Rows go from roughness 0 to 1, columns from n dot v
+// 0 to 1.
+pub fn brdf_lut(size: u32, sample_count: u32) -> Vec<[f32; 2]> {
+    let mut lut = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        let roughness = (y as f32 + 0.5) / size as f32;
+        let k = roughness * roughness / 2.;
+        let geometry = |n_dot: f32| n_dot / (n_dot * (1. - k) + k);
+        for x in 0..size {
+            let n_dot_v = (x as f32 + 0.5) / size as f32;
+            let v = Vector3::new((1. - n_dot_v * n_dot_v).sqrt(), 0., n_dot_v);
+            let (mut a, mut b) = (0., 0.);
+            for i in 0..sample_count {
+                let h = importance_sample_ggx(hammersley(i, sample_count), roughness);
+                let v_dot_h = v.dot(&h).max(0.);
+                let l = h * (2. * v_dot_h) - v;
+                if l.z <= 0. {
+                    continue;
+                }
+                let visibility =
+                    geometry(n_dot_v) * geometry(l.z) * v_dot_h / (h.z * n_dot_v).max(1e-4);
+                let fresnel = (1. - v_dot_h).powi(5);
+                a += (1. - fresnel) * visibility;
+                b += fresnel * visibility;
+            }
+            lut.push([a / sample_count as f32, b / sample_count as f32]);
+        }
+    }
+    lut
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnvironmentLightingDescriptor {
+    // Size of the prefiltered environment faces.
+    pub size: u32,
+    pub mip_level_count: u32,
+    // Samples per texel used when prefiltering the environment and computing the BRDF lookup
+    // table.
+    pub sample_count: u32,
+    pub brdf_lut_size: u32,
+    pub intensity: f32,
+}
+
+impl Default for EnvironmentLightingDescriptor {
+    fn default() -> Self {
+        Self {
+            size: 128,
+            mip_level_count: 5,
+            sample_count: 64,
+            brdf_lut_size: 32,
+            intensity: 1.,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct EnvironmentBlock {
+    irradiance: [[f32; 4]; 9],
+    // Intensity, highest mip level and enabled flag.
+    parameters: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for EnvironmentBlock {}
+
+unsafe impl bytemuck::Pod for EnvironmentBlock {}
+
+pub(crate) fn environment_bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    let texture_entry = |binding, view_dimension| gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: true },
+            view_dimension,
+        },
+        count: None,
+    };
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Buffer {
+                        ty: gfx::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1, gfx::TextureViewDimension::Cube),
+                texture_entry(2, gfx::TextureViewDimension::D2),
+                gfx::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+// Image based lighting for the PBR pipeline: irradiance for the diffuse term, prefiltered
+// environment and BRDF lookup table for the specular term. Replaces the scene ambient color when
+// enabled.
+#[derive(Debug)]
+pub struct EnvironmentLighting {
+    buffer: gfx::Buffer,
+    bind_group: gfx::BindGroup,
+    block: EnvironmentBlock,
+    environment: Cubemap,
+}
+
+impl EnvironmentLighting {
+    // The prefiltering runs on the cpu, and can take a while for large sizes.
+    pub fn new(
+        instance: &gfx::Instance,
+        environment: &CubemapImage,
+        desc: &EnvironmentLightingDescriptor,
+    ) -> Self {
+        let mut source = environment.clone();
+        while source.size() > desc.size {
+            source = source.downsample();
+        }
+        let mip_level_count = desc
+            .mip_level_count
+            .clamp(1, 32 - source.size().leading_zeros());
+        let levels = prefilter_environment(&source, mip_level_count, desc.sample_count);
+        // The lighting is computed in the workflow color space.
+        let sh = match instance.color_workflow() {
+            gfx::ColorWorkflow::Unconverted | gfx::ColorWorkflow::Linear => {
+                irradiance_sh(environment)
+            }
+            gfx::ColorWorkflow::Gamma => {
+                irradiance_sh(&environment.map(|c| c.map(gfx::linear_to_srgb)))
+            }
+        };
+        let mut irradiance = [[0.; 4]; 9];
+        for (dst, src) in irradiance.iter_mut().zip(sh) {
+            *dst = [src.x, src.y, src.z, 0.];
+        }
+        let block = EnvironmentBlock {
+            irradiance,
+            parameters: [desc.intensity, (mip_level_count - 1) as f32, 1., 0.],
+        };
+        Self::with_block(
+            instance,
+            block,
+            Cubemap::with_mip_levels(instance, &levels),
+            desc.brdf_lut_size,
+            &brdf_lut(desc.brdf_lut_size, desc.sample_count),
+        )
+    }
+
+    // The scene ambient color is used instead.
+    pub fn disabled(instance: &gfx::Instance) -> Self {
+        let black = CubemapImage::from_fn(1, |_| Vector3::zeros());
+        Self::with_block(
+            instance,
+            EnvironmentBlock {
+                irradiance: [[0.; 4]; 9],
+                parameters: [0.; 4],
+            },
+            Cubemap::new(instance, &black),
+            1,
+            &[[0., 0.]],
+        )
+    }
+
+    fn with_block(
+        instance: &gfx::Instance,
+        block: EnvironmentBlock,
+        environment: Cubemap,
+        brdf_lut_size: u32,
+        brdf_lut: &[[f32; 2]],
+    ) -> Self {
+        let buffer = gfx::Buffer::init(
+            instance,
+            &gfx::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::bytes_of(&block),
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+            },
+        );
+        let lut_size = gfx::Extent3d {
+            width: brdf_lut_size,
+            height: brdf_lut_size,
+            depth_or_array_layers: 1,
+        };
+        let lut_texture = gfx::Texture::new(
+            instance,
+            &gfx::TextureDescriptor {
+                label: None,
+                size: lut_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: gfx::TextureDimension::D2,
+                format: gfx::TextureFormat::Rgba8Unorm,
+                usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+            },
+        );
+        let lut_bytes: Vec<u8> = brdf_lut
+            .iter()
+            .flat_map(|[a, b]| {
+                let channel = |v: f32| (v.clamp(0., 1.) * 255. + 0.5) as u8;
+                [channel(*a), channel(*b), 0, 255]
+            })
+            .collect();
+        lut_texture.write(
+            instance,
+            0,
+            gfx::Origin3d::ZERO,
+            &lut_bytes,
+            gfx::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: core::num::NonZeroU32::new(4 * brdf_lut_size),
+                rows_per_image: None,
+            },
+            lut_size,
+        );
+        let lut_view = lut_texture.create_view(&gfx::TextureViewDescriptor::default());
+        let sampler = gfx::Sampler::new(
+            instance,
+            &gfx::SamplerDescriptor {
+                mag_filter: gfx::FilterMode::Linear,
+                min_filter: gfx::FilterMode::Linear,
+                mipmap_filter: gfx::FilterMode::Linear,
+                ..gfx::SamplerDescriptor::default()
+            },
+        );
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &environment_bind_group_layout(instance),
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::TextureView(environment.view()),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 2,
+                        resource: gfx::BindingResource::TextureView(&lut_view),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 3,
+                        resource: gfx::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            },
+        );
+        Self {
+            buffer,
+            bind_group,
+            block,
+            environment,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.block.parameters[2] != 0.
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.block.parameters[0]
+    }
+
+    pub fn set_intensity(&mut self, instance: &gfx::Instance, intensity: f32) {
+        self.block.parameters[0] = intensity;
+        instance.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.block));
+    }
+
+    // Prefiltered environment, the first level is the unfiltered environment and can be drawn as
+    // a skybox.
+    pub fn environment(&self) -> &Cubemap {
+        &self.environment
+    }
+
+    pub(crate) fn bind_group(&self) -> &gfx::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn constant_environment_irradiance() {
+        let environment = CubemapImage::from_fn(8, |_| Vector3::new(0.5, 1., 2.));
+        let sh = irradiance_sh(&environment);
+        for n in [
+            Vector3::x(),
+            -Vector3::y(),
+            Vector3::new(1., 1., -1.).normalize(),
+        ] {
+            let irradiance = evaluate_sh(&sh, &n);
+            expect_that!(&irradiance.x, close_to(0.5, 1e-3));
+            expect_that!(&irradiance.y, close_to(1., 1e-3));
+            expect_that!(&irradiance.z, close_to(2., 1e-3));
+        }
+    }
+
+    #[test]
+    fn directional_environment_irradiance() {
+        // Light only from above.
+        let environment = CubemapImage::from_fn(16, |d| Vector3::repeat(d.y.max(0.)));
+        let sh = irradiance_sh(&environment);
+        let up = evaluate_sh(&sh, &Vector3::y()).x;
+        let side = evaluate_sh(&sh, &Vector3::x()).x;
+        let down = evaluate_sh(&sh, &-Vector3::y()).x;
+        expect_that!(up > side);
+        expect_that!(side > down);
+        expect_that!(&down, close_to(0., 0.05));
+    }
+
+    #[test]
+    fn brdf_lookup_table() {
+        let size = 8;
+        let lut = brdf_lut(size, 256);
+        expect_that!(&lut.len(), eq(64));
+        for [a, b] in lut.iter() {
+            expect_that!(*a >= 0. && *b >= 0.);
+            expect_that!(a + b <= 1.001);
+        }
+        // Smooth surfaces seen from above reflect everything.
+        let [a, b] = lut[(size - 1) as usize];
+        expect_that!(&(a + b), close_to(1., 0.05));
+    }
+
+    #[test]
+    fn prefiltered_levels() {
+        let environment = CubemapImage::from_fn(16, |d| Vector3::repeat(d.y.max(0.)));
+        let levels = prefilter_environment(&environment, 4, 32);
+        let sizes: Vec<u32> = levels.iter().map(|l| l.size()).collect();
+        expect_that!(&sizes, eq(vec![16, 8, 4, 2]));
+        expect_that!(&levels[0], eq(environment));
+        // Rough reflections blur the light towards the horizon.
+        let horizon = Vector3::new(1., 0.05, 0.).normalize();
+        expect_that!(levels[3].sample(&horizon).x > levels[0].sample(&horizon).x);
+    }
+}
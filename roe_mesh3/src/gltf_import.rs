@@ -0,0 +1,437 @@
+use super::{LodGroup, LodLevel, LodMetric, MeshData, MorphTarget, MorphedMesh, Vertex};
+
+use roe_math::Vector3;
+
+use serde::Deserialize;
+
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum GltfError {
+    GltfError(gltf::Error),
+    // Mesh name and description.
+    InvalidMesh(String, String),
+}
+
+impl std::fmt::Display for GltfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GltfError(e) => write!(f, "glTF error ({})", e),
+            Self::InvalidMesh(mesh, description) => {
+                write!(f, "Invalid mesh {} ({})", mesh, description)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::GltfError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(e: gltf::Error) -> Self {
+        Self::GltfError(e)
+    }
+}
+
+// Morph target names aren't part of the glTF specification, exporters store them in the mesh
+// extras.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMeshExtras {
+    #[serde(default)]
+    target_names: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GltfMesh {
+    pub name: String,
+    // One per glTF primitive, sharing the morph target names and default weights.
+    pub primitives: Vec<MorphedMesh>,
+}
+
+impl GltfMesh {
+    // Morph weights are shared by all primitives.
+    pub fn default_weights(&self) -> &[f32] {
+        self.primitives
+            .first()
+            .map(|p| p.default_weights())
+            .unwrap_or(&[])
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GltfLodDescriptor {
+    // Meshes named <name><suffix><level>, e.g. rock_LOD1, form a LOD group named <name>.
+    pub suffix: String,
+    pub metric: LodMetric,
+    // Threshold of each level, from the most detailed. Missing thresholds halve the last screen
+    // size, or double the last distance.
+    pub thresholds: Vec<f32>,
+    pub cross_fade: f32,
+}
+
+impl Default for GltfLodDescriptor {
+    fn default() -> Self {
+        Self {
+            suffix: String::from("_LOD"),
+            metric: LodMetric::ScreenSize,
+            thresholds: vec![0.5, 0.25, 0.1, 0.02],
+            cross_fade: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GltfImportDescriptor {
+    // None disables LOD grouping.
+    pub lod: Option<GltfLodDescriptor>,
+}
+
+impl Default for GltfImportDescriptor {
+    fn default() -> Self {
+        Self {
+            lod: Some(GltfLodDescriptor::default()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GltfLodGroup {
+    pub name: String,
+    // Levels are indices in GltfModel::meshes.
+    pub group: LodGroup<usize>,
+}
+
+// Meshes of a glTF or GLB file. Materials, nodes and animations are ignored.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GltfModel {
+    pub meshes: Vec<GltfMesh>,
+    pub lod_groups: Vec<GltfLodGroup>,
+}
+
+impl GltfModel {
+    pub fn from_slice(data: &[u8]) -> Result<Self, GltfError> {
+        Self::from_slice_with_descriptor(data, &GltfImportDescriptor::default())
+    }
+
+    pub fn from_slice_with_descriptor(
+        data: &[u8],
+        desc: &GltfImportDescriptor,
+    ) -> Result<Self, GltfError> {
+        let (document, buffers, _) = gltf::import_slice(data)?;
+        Self::from_document(&document, &buffers, desc)
+    }
+
+    // External buffers are resolved relatively to the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GltfError> {
+        Self::load_with_descriptor(path, &GltfImportDescriptor::default())
+    }
+
+    pub fn load_with_descriptor<P: AsRef<Path>>(
+        path: P,
+        desc: &GltfImportDescriptor,
+    ) -> Result<Self, GltfError> {
+        let (document, buffers, _) = gltf::import(path)?;
+        Self::from_document(&document, &buffers, desc)
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<&GltfMesh> {
+        self.meshes.iter().find(|m| m.name == name)
+    }
+
+    pub fn lod_group(&self, name: &str) -> Option<&GltfLodGroup> {
+        self.lod_groups.iter().find(|g| g.name == name)
+    }
+
+    fn from_document(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        desc: &GltfImportDescriptor,
+    ) -> Result<Self, GltfError> {
+        let meshes = document
+            .meshes()
+            .map(|mesh| import_mesh(&mesh, buffers))
+            .collect::<Result<Vec<_>, _>>()?;
+        let lod_groups = match &desc.lod {
+            Some(lod) => group_lods(&meshes, lod),
+            None => Vec::new(),
+        };
+        Ok(Self { meshes, lod_groups })
+    }
+}
+
+fn group_lods(meshes: &[GltfMesh], desc: &GltfLodDescriptor) -> Vec<GltfLodGroup> {
+    // Group name and (level, mesh index) pairs, in order of appearance.
+    let mut groups: Vec<(String, Vec<(usize, usize)>)> = Vec::new();
+    for (mesh_index, mesh) in meshes.iter().enumerate() {
+        let split = match mesh.name.rfind(&desc.suffix) {
+            Some(split) if split > 0 => split,
+            _ => continue,
+        };
+        let level = match mesh.name[split + desc.suffix.len()..].parse::<usize>() {
+            Ok(level) => level,
+            Err(_) => continue,
+        };
+        let name = &mesh.name[..split];
+        match groups.iter_mut().find(|(n, _)| n == name) {
+            Some((_, levels)) => levels.push((level, mesh_index)),
+            None => groups.push((String::from(name), vec![(level, mesh_index)])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(name, mut levels)| {
+            levels.sort_by_key(|(level, _)| *level);
+            let mut threshold = 1.;
+            let levels = levels
+                .into_iter()
+                .enumerate()
+                .map(|(i, (_, mesh_index))| {
+                    threshold = match desc.thresholds.get(i) {
+                        Some(t) => *t,
+                        None => match desc.metric {
+                            LodMetric::Distance => threshold * 2.,
+                            LodMetric::ScreenSize => threshold / 2.,
+                        },
+                    };
+                    LodLevel::new(mesh_index, threshold)
+                })
+                .collect();
+            GltfLodGroup {
+                name,
+                group: LodGroup::new(desc.metric, levels).with_cross_fade(desc.cross_fade),
+            }
+        })
+        .collect()
+}
+
+fn import_mesh(mesh: &gltf::Mesh, buffers: &[gltf::buffer::Data]) -> Result<GltfMesh, GltfError> {
+    let name = mesh
+        .name()
+        .map(String::from)
+        .unwrap_or_else(|| format!("mesh_{}", mesh.index()));
+    let invalid = |description: &str| GltfError::InvalidMesh(name.clone(), description.into());
+    let target_names = match mesh.extras() {
+        Some(extras) => serde_json::from_str::<RawMeshExtras>(extras.get())
+            .map(|e| e.target_names)
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let mut primitives = Vec::new();
+    for primitive in mesh.primitives() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            return Err(invalid("only triangle lists are supported"));
+        }
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| &b.0[..]));
+        let positions: Vec<[f32; 3]> = reader
+            .read_positions()
+            .ok_or_else(|| invalid("missing vertex positions"))?
+            .collect();
+        let vertex_count = positions.len();
+        let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+        let texture_coordinates: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+            Some(tc) => tc.into_f32().collect(),
+            None => vec![[0., 0.]; vertex_count],
+        };
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..vertex_count as u32).collect(),
+        };
+        if !indices.len().is_multiple_of(3) || indices.iter().any(|i| *i as usize >= vertex_count) {
+            return Err(invalid("invalid vertex indices"));
+        }
+
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| {
+                let normal = normals.as_ref().map(|n| n[i]).unwrap_or([0., 0., 0.]);
+                Vertex::new(*position, normal, texture_coordinates[i])
+            })
+            .collect();
+        let mut base = MeshData::new(vertices, indices);
+        if normals.is_none() {
+            base.compute_normals();
+        }
+
+        let to_vectors = |offsets: Vec<[f32; 3]>| -> Vec<Vector3<f32>> {
+            offsets.into_iter().map(Vector3::from).collect()
+        };
+        let mut targets = Vec::new();
+        for (i, (position_offsets, normal_offsets, _)) in reader.read_morph_targets().enumerate() {
+            let position_offsets = match position_offsets {
+                Some(p) => to_vectors(p.collect()),
+                None => vec![Vector3::zeros(); vertex_count],
+            };
+            let normal_offsets = normal_offsets
+                .map(|n| to_vectors(n.collect()))
+                .unwrap_or_default();
+            targets.push(MorphTarget {
+                name: target_names
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| format!("target_{}", i)),
+                position_offsets,
+                normal_offsets,
+            });
+        }
+        let default_weights = match mesh.weights() {
+            Some(weights) if weights.len() == targets.len() => weights.to_vec(),
+            Some(_) => return Err(invalid("wrong morph target weight count")),
+            None => vec![0.; targets.len()],
+        };
+        primitives.push(MorphedMesh::new(base, targets).with_default_weights(default_weights));
+    }
+    Ok(GltfMesh { name, primitives })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut bin = bin.to_vec();
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut out = Vec::new();
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(length as u32).to_le_bytes());
+        out.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.extend_from_slice(&json);
+        out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"BIN\0");
+        out.extend_from_slice(&bin);
+        out
+    }
+
+    // A triangle with a "smile" target moving the last vertex up, and no normals.
+    fn smile_triangle() -> Vec<u8> {
+        let mut bin = f32_bytes(&[0., 0., 0., 1., 0., 0., 0., 1., 0.]);
+        bin.extend(f32_bytes(&[0., 0., 0., 0., 0., 0., 0., 0.5, 0.]));
+        bin.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 78}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 36, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 72, "byteLength": 6}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                 "min": [0, 0, 0], "max": [1, 1, 0]},
+                {"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3",
+                 "min": [0, 0, 0], "max": [0, 0.5, 0]},
+                {"bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "meshes": [{
+                "name": "face",
+                "primitives": [{
+                    "attributes": {"POSITION": 0},
+                    "indices": 2,
+                    "targets": [{"POSITION": 1}]
+                }],
+                "weights": [0.25],
+                "extras": {"targetNames": ["smile"]}
+            }]
+        }"#;
+        glb(json, &bin)
+    }
+
+    #[test]
+    fn morph_targets_import() {
+        let model = GltfModel::from_slice(&smile_triangle()).unwrap();
+        let mesh = model.mesh("face").unwrap();
+        expect_that!(&mesh.primitives.len(), eq(1));
+        expect_that!(&mesh.default_weights(), eq(&[0.25][..]));
+
+        let primitive = &mesh.primitives[0];
+        expect_that!(&primitive.target_index("smile"), eq(Some(0)));
+        expect_that!(&primitive.base().indices, eq(vec![0, 1, 2]));
+        let normal = primitive.base().vertices[0].normal;
+        expect_that!(&normal, eq([0., 0., 1.]));
+
+        let vertices = primitive.blend(&[1.]);
+        let position = vertices[2].position;
+        expect_that!(&position, eq([0., 1.5, 0.]));
+    }
+
+    // Triangles named rock_LOD1, rock_LOD0 and tree, sharing the same data.
+    fn lod_meshes() -> Vec<u8> {
+        let mut bin = f32_bytes(&[0., 0., 0., 1., 0., 0., 0., 1., 0.]);
+        bin.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 42}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                 "min": [0, 0, 0], "max": [1, 1, 0]},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "meshes": [
+                {"name": "rock_LOD1", "primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]},
+                {"name": "rock_LOD0", "primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]},
+                {"name": "tree", "primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}
+            ]
+        }"#;
+        glb(json, &bin)
+    }
+
+    #[test]
+    fn lod_groups_import() {
+        let desc = GltfImportDescriptor {
+            lod: Some(GltfLodDescriptor {
+                metric: LodMetric::Distance,
+                thresholds: vec![20.],
+                cross_fade: 0.,
+                ..GltfLodDescriptor::default()
+            }),
+        };
+        let model = GltfModel::from_slice_with_descriptor(&lod_meshes(), &desc).unwrap();
+        expect_that!(&model.meshes.len(), eq(3));
+        expect_that!(&model.lod_groups.len(), eq(1));
+        let group = &model.lod_group("rock").unwrap().group;
+        expect_that!(
+            &group.levels().to_vec(),
+            eq(vec![LodLevel::new(1, 20.), LodLevel::new(0, 40.)])
+        );
+        expect_that!(&group.select(30.).unwrap().level, eq(1));
+
+        let desc = GltfImportDescriptor { lod: None };
+        let model = GltfModel::from_slice_with_descriptor(&lod_meshes(), &desc).unwrap();
+        expect_that!(model.lod_groups.is_empty());
+    }
+
+    #[test]
+    fn invalid_data() {
+        expect_that!(matches!(
+            GltfModel::from_slice(b"not a model"),
+            Err(GltfError::GltfError(_))
+        ));
+    }
+}
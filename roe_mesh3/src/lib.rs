@@ -0,0 +1,56 @@
+mod billboard;
+pub use billboard::*;
+
+mod bvh;
+pub use bvh::*;
+
+mod cubemap;
+pub use cubemap::*;
+
+mod culling;
+pub use culling::*;
+
+mod debug_draw;
+pub use debug_draw::*;
+
+mod environment;
+pub use environment::*;
+
+mod gltf_import;
+pub use gltf_import::*;
+
+mod light_probes;
+pub use light_probes::*;
+
+mod lod;
+pub use lod::*;
+
+mod mesh;
+pub use mesh::*;
+
+mod morph_target;
+pub use morph_target::*;
+
+mod occlusion;
+pub use occlusion::*;
+
+mod pbr;
+pub use pbr::*;
+
+mod raycast;
+pub use raycast::*;
+
+mod scatter;
+pub use scatter::*;
+
+mod shadow;
+pub use shadow::*;
+
+mod skybox;
+pub use skybox::*;
+
+mod terrain;
+pub use terrain::*;
+
+mod vat;
+pub use vat::*;
@@ -0,0 +1,200 @@
+use super::{evaluate_sh, IrradianceSh};
+
+use roe_math::Vector3;
+
+// Irradiance spherical harmonics of a constant radiance coming from every direction.
+pub fn uniform_irradiance_sh(radiance: &Vector3<f32>) -> IrradianceSh {
+    let mut coefficients = [Vector3::zeros(); 9];
+    coefficients[0] = radiance / 0.282_095;
+    coefficients
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LightProbeGrid3Descriptor {
+    // Position of the first probe, in world units.
+    pub origin: Vector3<f32>,
+    // Distance between neighboring probes.
+    pub spacing: Vector3<f32>,
+    // Number of probes along each axis.
+    pub size: Vector3<u32>,
+}
+
+impl Default for LightProbeGrid3Descriptor {
+    fn default() -> Self {
+        Self {
+            origin: Vector3::zeros(),
+            spacing: Vector3::new(4., 4., 4.),
+            size: Vector3::new(8, 4, 8),
+        }
+    }
+}
+
+// Grid of irradiance probes, sampled by position so that dynamic meshes pick up the local
+// indirect lighting without evaluating every light per mesh. The probes can be baked, e.g. with
+// irradiance_sh on cubemaps rendered at the probe positions, or updated at runtime. Positions
+// outside the grid take the value of the closest face. All probes start black.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LightProbeGrid3 {
+    origin: Vector3<f32>,
+    spacing: Vector3<f32>,
+    size: Vector3<u32>,
+    // Along x first, then y, then z.
+    probes: Vec<IrradianceSh>,
+}
+
+impl LightProbeGrid3 {
+    pub fn new(desc: &LightProbeGrid3Descriptor) -> Self {
+        let size = desc.size.map(|s| s.max(1));
+        Self {
+            origin: desc.origin,
+            spacing: desc.spacing,
+            size,
+            probes: vec![[Vector3::zeros(); 9]; (size.x * size.y * size.z) as usize],
+        }
+    }
+
+    pub fn size(&self) -> Vector3<u32> {
+        self.size
+    }
+
+    pub fn probe_position(&self, x: u32, y: u32, z: u32) -> Vector3<f32> {
+        self.origin
+            + self
+                .spacing
+                .component_mul(&Vector3::new(x as f32, y as f32, z as f32))
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        ((z * self.size.y + y) * self.size.x + x) as usize
+    }
+
+    pub fn probe(&self, x: u32, y: u32, z: u32) -> Option<&IrradianceSh> {
+        if x < self.size.x && y < self.size.y && z < self.size.z {
+            Some(&self.probes[self.index(x, y, z)])
+        } else {
+            None
+        }
+    }
+
+    // Returns false if the probe is outside the grid.
+    pub fn set_probe(&mut self, x: u32, y: u32, z: u32, coefficients: IrradianceSh) -> bool {
+        if x < self.size.x && y < self.size.y && z < self.size.z {
+            let i = self.index(x, y, z);
+            self.probes[i] = coefficients;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Sets every probe from its position.
+    pub fn bake<F: FnMut(&Vector3<f32>) -> IrradianceSh>(&mut self, mut f: F) {
+        for z in 0..self.size.z {
+            for y in 0..self.size.y {
+                for x in 0..self.size.x {
+                    let i = self.index(x, y, z);
+                    self.probes[i] = f(&self.probe_position(x, y, z));
+                }
+            }
+        }
+    }
+
+    // Trilinear interpolation of the 8 probes around the position.
+    pub fn sample(&self, position: &Vector3<f32>) -> IrradianceSh {
+        let cell = |axis: usize| {
+            let size = self.size[axis];
+            let t = if self.spacing[axis] > 0. {
+                ((position[axis] - self.origin[axis]) / self.spacing[axis])
+                    .clamp(0., (size - 1) as f32)
+            } else {
+                0.
+            };
+            let i = (t.floor() as u32).min(size.saturating_sub(2));
+            (
+                [i, (i + 1).min(size - 1)],
+                [1. - (t - i as f32), t - i as f32],
+            )
+        };
+        let (xs, wx) = cell(0);
+        let (ys, wy) = cell(1);
+        let (zs, wz) = cell(2);
+        let mut coefficients = [Vector3::zeros(); 9];
+        for corner in 0..8 {
+            let (a, b, c) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = wx[a] * wy[b] * wz[c];
+            let probe = &self.probes[self.index(xs[a], ys[b], zs[c])];
+            for (sum, value) in coefficients.iter_mut().zip(probe.iter()) {
+                *sum += value * weight;
+            }
+        }
+        coefficients
+    }
+
+    // Diffuse light reflected by a white lambertian surface with the given normal.
+    pub fn irradiance(&self, position: &Vector3<f32>, normal: &Vector3<f32>) -> Vector3<f32> {
+        evaluate_sh(&self.sample(position), normal)
+    }
+
+    // Irradiance averaged over all the directions, to replace the scene ambient radiance of a
+    // mesh, see PbrPushConstants::with_ambient.
+    pub fn ambient_radiance(&self, position: &Vector3<f32>) -> Vector3<f32> {
+        self.sample(position)[0] * 0.282_095
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn grid() -> LightProbeGrid3 {
+        let mut grid = LightProbeGrid3::new(&LightProbeGrid3Descriptor {
+            origin: Vector3::new(0., 0., -2.),
+            spacing: Vector3::new(1., 2., 2.),
+            size: Vector3::new(2, 2, 3),
+        });
+        grid.bake(|p| uniform_irradiance_sh(&Vector3::new(p.x, p.y, p.z)));
+        grid
+    }
+
+    #[test]
+    fn probes() {
+        let mut grid = grid();
+        expect_that!(&grid.size(), eq(Vector3::new(2, 2, 3)));
+        expect_that!(&grid.probe_position(1, 1, 2), eq(Vector3::new(1., 2., 2.)));
+        expect_that!(grid.probe(2, 0, 0).is_none());
+        expect_that!(grid.set_probe(1, 0, 0, [Vector3::zeros(); 9]));
+        expect_that!(!grid.set_probe(0, 0, 3, [Vector3::zeros(); 9]));
+        expect_that!(&grid.probe(1, 0, 0).unwrap()[0], eq(Vector3::zeros()));
+    }
+
+    #[test]
+    fn sampling() {
+        let grid = grid();
+        // The radiance is linear in the position, so it is interpolated exactly.
+        for p in [
+            Vector3::new(0.25, 1.5, -1.),
+            Vector3::new(1., 0., 2.),
+            Vector3::new(0.5, 1., 0.5),
+        ] {
+            expect_that!((grid.ambient_radiance(&p) - p).norm() < 1e-4);
+            let irradiance = grid.irradiance(&p, &Vector3::y());
+            expect_that!((irradiance - p).norm() < 1e-4);
+        }
+        // Clamped outside the grid.
+        let radiance = grid.ambient_radiance(&Vector3::new(-5., 10., 7.));
+        expect_that!((radiance - Vector3::new(0., 2., 2.)).norm() < 1e-4);
+    }
+
+    #[test]
+    fn single_probe() {
+        let mut grid = LightProbeGrid3::new(&LightProbeGrid3Descriptor {
+            size: Vector3::new(1, 0, 1),
+            ..LightProbeGrid3Descriptor::default()
+        });
+        expect_that!(&grid.size(), eq(Vector3::new(1, 1, 1)));
+        grid.set_probe(0, 0, 0, uniform_irradiance_sh(&Vector3::new(1., 2., 3.)));
+        let radiance = grid.ambient_radiance(&Vector3::new(10., -4., 3.));
+        expect_that!((radiance - Vector3::new(1., 2., 3.)).norm() < 1e-4);
+    }
+}
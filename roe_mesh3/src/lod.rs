@@ -0,0 +1,222 @@
+use super::BoundingSphere;
+
+use roe_math::{HomogeneousMatrix3, Vector3};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LodMetric {
+    // Distance between the camera and the bounding sphere center. A level is used while the
+    // distance is below its threshold.
+    Distance,
+    // Fraction of the screen height covered by the bounding sphere, independent of the field of
+    // view and resolution. A level is used while the screen size is above its threshold.
+    #[default]
+    ScreenSize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LodLevel<T> {
+    pub value: T,
+    pub threshold: f32,
+}
+
+impl<T> LodLevel<T> {
+    pub fn new(value: T, threshold: f32) -> Self {
+        Self { value, threshold }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LodTransition {
+    // None when the model fades out past the last level.
+    pub next: Option<usize>,
+    // From 0 when the transition starts to 1 when the next level is fully visible.
+    pub progress: f32,
+}
+
+impl LodTransition {
+    // See PbrPushConstants::with_lod_fade.
+    pub fn outgoing_fade(&self) -> f32 {
+        self.progress
+    }
+
+    pub fn incoming_fade(&self) -> f32 {
+        self.progress - 1.
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LodSelection {
+    pub level: usize,
+    // Set while cross-fading to the next level.
+    pub transition: Option<LodTransition>,
+}
+
+// Levels of detail of a model, from the most detailed. Past the last level the model isn't
+// drawn at all.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LodGroup<T> {
+    metric: LodMetric,
+    levels: Vec<LodLevel<T>>,
+    cross_fade: f32,
+}
+
+impl<T> LodGroup<T> {
+    // Levels are sorted from the most detailed according to the metric.
+    pub fn new(metric: LodMetric, mut levels: Vec<LodLevel<T>>) -> Self {
+        let key = |a: f32, b: f32| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal);
+        match metric {
+            LodMetric::Distance => levels.sort_by(|a, b| key(a.threshold, b.threshold)),
+            LodMetric::ScreenSize => levels.sort_by(|a, b| key(b.threshold, a.threshold)),
+        }
+        Self {
+            metric,
+            levels,
+            cross_fade: 0.,
+        }
+    }
+
+    // Width of the cross-fade band before each threshold, as a fraction of the threshold. 0
+    // switches levels instantly.
+    pub fn with_cross_fade(mut self, cross_fade: f32) -> Self {
+        self.cross_fade = cross_fade.clamp(0., 1.);
+        self
+    }
+
+    pub fn metric(&self) -> LodMetric {
+        self.metric
+    }
+
+    pub fn cross_fade(&self) -> f32 {
+        self.cross_fade
+    }
+
+    pub fn levels(&self) -> &[LodLevel<T>] {
+        &self.levels
+    }
+
+    pub fn level(&self, index: usize) -> &T {
+        &self.levels[index].value
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    // None if the model is past the last level.
+    pub fn select(&self, value: f32) -> Option<LodSelection> {
+        let index = self.levels.iter().position(|level| match self.metric {
+            LodMetric::Distance => value < level.threshold,
+            LodMetric::ScreenSize => value >= level.threshold,
+        })?;
+        let threshold = self.levels[index].threshold;
+        let band = threshold * self.cross_fade;
+        let progress = match self.metric {
+            LodMetric::Distance => (value - (threshold - band)) / band,
+            LodMetric::ScreenSize => (threshold + band - value) / band,
+        };
+        let transition = if band > 0. && progress > 0. {
+            let next = index + 1;
+            Some(LodTransition {
+                next: (next < self.levels.len()).then_some(next),
+                progress: progress.min(1.),
+            })
+        } else {
+            None
+        };
+        Some(LodSelection {
+            level: index,
+            transition,
+        })
+    }
+
+    // The camera position is in world space, the bounds should be in world space too.
+    pub fn select_for_camera(
+        &self,
+        bounds: &BoundingSphere,
+        camera_position: &Vector3<f32>,
+        projection: &HomogeneousMatrix3<f32>,
+    ) -> Option<LodSelection> {
+        let distance = (bounds.center - camera_position).norm();
+        match self.metric {
+            LodMetric::Distance => self.select(distance),
+            LodMetric::ScreenSize => self.select(screen_size(bounds.radius, distance, projection)),
+        }
+    }
+}
+
+// Fraction of the screen height covered by a sphere, for perspective projections. Spheres
+// containing the camera cover the whole screen.
+pub fn screen_size(radius: f32, distance: f32, projection: &HomogeneousMatrix3<f32>) -> f32 {
+    if distance <= radius {
+        return f32::INFINITY;
+    }
+    radius * projection[(1, 1)] / distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn group() -> LodGroup<&'static str> {
+        LodGroup::new(
+            LodMetric::Distance,
+            vec![
+                LodLevel::new("low", 100.),
+                LodLevel::new("high", 10.),
+                LodLevel::new("medium", 40.),
+            ],
+        )
+    }
+
+    #[test]
+    fn distance_selection() {
+        let group = group();
+        let level = |d| group.select(d).map(|s| *group.level(s.level));
+        expect_that!(&level(0.), eq(Some("high")));
+        expect_that!(&level(10.), eq(Some("medium")));
+        expect_that!(&level(99.), eq(Some("low")));
+        expect_that!(&level(100.), eq(None));
+        expect_that!(&group.select(5.).unwrap().transition, eq(None));
+    }
+
+    #[test]
+    fn cross_fade() {
+        let group = group().with_cross_fade(0.5);
+        let selection = group.select(7.5).unwrap();
+        expect_that!(&selection.level, eq(0));
+        let transition = selection.transition.unwrap();
+        expect_that!(&transition.next, eq(Some(1)));
+        expect_that!(&transition.progress, close_to(0.5, 1e-6));
+        expect_that!(&transition.incoming_fade(), close_to(-0.5, 1e-6));
+        expect_that!(&group.select(4.).unwrap().transition, eq(None));
+
+        let transition = group.select(75.).unwrap().transition.unwrap();
+        expect_that!(&transition.next, eq(None));
+        expect_that!(&transition.outgoing_fade(), close_to(0.5, 1e-6));
+    }
+
+    #[test]
+    fn screen_size_selection() {
+        let projection = HomogeneousMatrix3::new_perspective(1., 1., 0.1, 1000.);
+        let group = LodGroup::new(
+            LodMetric::ScreenSize,
+            vec![LodLevel::new("low", 0.1), LodLevel::new("high", 0.5)],
+        );
+        let sphere = BoundingSphere::new(Vector3::zeros(), 1.);
+        let select = |z: f32| {
+            group
+                .select_for_camera(&sphere, &Vector3::new(0., 0., z), &projection)
+                .map(|s| *group.level(s.level))
+        };
+        let scale = projection[(1, 1)];
+        expect_that!(&select(0.5), eq(Some("high")));
+        expect_that!(&select(scale / 0.6), eq(Some("high")));
+        expect_that!(&select(scale / 0.4), eq(Some("low")));
+        expect_that!(&select(scale / 0.05), eq(None));
+    }
+}
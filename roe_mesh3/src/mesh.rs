@@ -0,0 +1,101 @@
+use roe_graphics as gfx;
+use roe_math::Vector3;
+
+pub type MeshIndexRange = gfx::MeshIndexRange;
+// 3D models easily exceed 65536 vertices.
+pub type MeshIndex = u32;
+pub type Mesh<I = MeshIndex> = gfx::IndexedMesh<Vertex, I>;
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub texture_coordinates: [f32; 2],
+}
+
+impl Vertex {
+    pub fn new(position: [f32; 3], normal: [f32; 3], texture_coordinates: [f32; 2]) -> Self {
+        Self {
+            position,
+            normal,
+            texture_coordinates,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for Vertex {
+    fn zeroed() -> Self {
+        Self::new([0., 0., 0.], [0., 0., 0.], [0., 0.])
+    }
+}
+
+unsafe impl bytemuck::Pod for Vertex {}
+
+// CPU copy of a triangle list mesh.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<MeshIndex>,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<MeshIndex>) -> Self {
+        assert!(
+            indices.len().is_multiple_of(3),
+            "The mesh isn't a triangle list"
+        );
+        assert!(
+            indices.iter().all(|i| (*i as usize) < vertices.len()),
+            "Mesh index out of range"
+        );
+        Self { vertices, indices }
+    }
+
+    // Smooth normals, averaged over the adjacent triangles weighted by area.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::<f32>::zeros(); self.vertices.len()];
+        for triangle in self.indices.chunks(3) {
+            let p: Vec<Vector3<f32>> = triangle
+                .iter()
+                .map(|i| Vector3::from(self.vertices[*i as usize].position))
+                .collect();
+            let face_normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+            for i in triangle {
+                normals[*i as usize] += face_normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.try_normalize(f32::EPSILON).unwrap_or(normal).into();
+        }
+    }
+
+    pub fn create_mesh(&self, instance: &gfx::Instance) -> Mesh {
+        Mesh::new(instance, &self.vertices, &self.indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn smooth_normals() {
+        let mut mesh = MeshData::new(
+            vec![
+                Vertex::new([0., 0., 0.], [0., 0., 0.], [0., 0.]),
+                Vertex::new([1., 0., 0.], [0., 0., 0.], [0., 0.]),
+                Vertex::new([0., 1., 0.], [0., 0., 0.], [0., 0.]),
+                Vertex::new([0., 0., -1.], [0., 0., 0.], [0., 0.]),
+            ],
+            vec![0, 1, 2, 0, 1, 3],
+        );
+        mesh.compute_normals();
+        let normals: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.normal).collect();
+        expect_that!(&normals[2], eq([0., 0., 1.]));
+        expect_that!(&normals[3], eq([0., 1., 0.]));
+        let shared = Vector3::from(normals[0]);
+        expect_that!((shared - Vector3::new(0., 1., 1.).normalize()).norm() < 1e-6);
+    }
+}
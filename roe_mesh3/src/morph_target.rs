@@ -0,0 +1,179 @@
+use super::{MeshData, MeshIndex, Vertex};
+
+use roe_graphics as gfx;
+use roe_math::Vector3;
+
+pub type MorphedMeshBuffer<I = MeshIndex> = gfx::DynamicMesh<Vertex, I>;
+
+// Per-vertex offsets from the base mesh, also known as a blend shape.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MorphTarget {
+    pub name: String,
+    pub position_offsets: Vec<Vector3<f32>>,
+    // Empty if the target doesn't change the normals.
+    pub normal_offsets: Vec<Vector3<f32>>,
+}
+
+// Mesh whose shape is a weighted sum of morph targets, e.g. facial expressions.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MorphedMesh {
+    base: MeshData,
+    targets: Vec<MorphTarget>,
+    default_weights: Vec<f32>,
+}
+
+impl MorphedMesh {
+    pub fn new(base: MeshData, targets: Vec<MorphTarget>) -> Self {
+        for target in targets.iter() {
+            assert!(
+                target.position_offsets.len() == base.vertices.len(),
+                "Morph target {} doesn't match the base mesh",
+                target.name
+            );
+            assert!(
+                target.normal_offsets.is_empty()
+                    || target.normal_offsets.len() == base.vertices.len(),
+                "Morph target {} doesn't match the base mesh",
+                target.name
+            );
+        }
+        let default_weights = vec![0.; targets.len()];
+        Self {
+            base,
+            targets,
+            default_weights,
+        }
+    }
+
+    pub fn with_default_weights(mut self, weights: Vec<f32>) -> Self {
+        assert!(
+            weights.len() == self.targets.len(),
+            "There must be a weight per morph target"
+        );
+        self.default_weights = weights;
+        self
+    }
+
+    pub fn base(&self) -> &MeshData {
+        &self.base
+    }
+
+    pub fn targets(&self) -> &[MorphTarget] {
+        &self.targets
+    }
+
+    pub fn target_index(&self, name: &str) -> Option<usize> {
+        self.targets.iter().position(|t| t.name == name)
+    }
+
+    pub fn default_weights(&self) -> &[f32] {
+        &self.default_weights
+    }
+
+    // Weights aren't clamped, so that shapes can be exaggerated.
+    pub fn blend(&self, weights: &[f32]) -> Vec<Vertex> {
+        assert!(
+            weights.len() == self.targets.len(),
+            "There must be a weight per morph target"
+        );
+        let mut vertices = self.base.vertices.clone();
+        let mut normals_changed = false;
+        for (target, weight) in self.targets.iter().zip(weights) {
+            if *weight == 0. {
+                continue;
+            }
+            for (vertex, offset) in vertices.iter_mut().zip(target.position_offsets.iter()) {
+                vertex.position = (Vector3::from(vertex.position) + offset * *weight).into();
+            }
+            for (vertex, offset) in vertices.iter_mut().zip(target.normal_offsets.iter()) {
+                vertex.normal = (Vector3::from(vertex.normal) + offset * *weight).into();
+                normals_changed = true;
+            }
+        }
+        if normals_changed {
+            for vertex in vertices.iter_mut() {
+                let normal = Vector3::from(vertex.normal);
+                vertex.normal = normal.try_normalize(f32::EPSILON).unwrap_or(normal).into();
+            }
+        }
+        vertices
+    }
+
+    pub fn create_buffer(&self, instance: &gfx::Instance, weights: &[f32]) -> MorphedMeshBuffer {
+        MorphedMeshBuffer::new(instance, &self.blend(weights), &self.base.indices)
+    }
+
+    pub fn update_buffer(
+        &self,
+        instance: &gfx::Instance,
+        buffer: &mut MorphedMeshBuffer,
+        weights: &[f32],
+    ) {
+        buffer.update_vertices(instance, &self.blend(weights));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn quad() -> MorphedMesh {
+        let base = MeshData::new(
+            vec![
+                Vertex::new([0., 0., 0.], [0., 0., 1.], [0., 0.]),
+                Vertex::new([1., 0., 0.], [0., 0., 1.], [1., 0.]),
+                Vertex::new([1., 1., 0.], [0., 0., 1.], [1., 1.]),
+                Vertex::new([0., 1., 0.], [0., 0., 1.], [0., 1.]),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+        let raise = MorphTarget {
+            name: String::from("raise"),
+            position_offsets: vec![
+                Vector3::zeros(),
+                Vector3::zeros(),
+                Vector3::new(0., 0., 2.),
+                Vector3::new(0., 0., 2.),
+            ],
+            normal_offsets: vec![Vector3::new(0., -1., 0.); 4],
+        };
+        let widen = MorphTarget {
+            name: String::from("widen"),
+            position_offsets: vec![
+                Vector3::new(-1., 0., 0.),
+                Vector3::new(1., 0., 0.),
+                Vector3::new(1., 0., 0.),
+                Vector3::new(-1., 0., 0.),
+            ],
+            normal_offsets: Vec::new(),
+        };
+        MorphedMesh::new(base, vec![raise, widen])
+    }
+
+    #[test]
+    fn weighted_blending() {
+        let mesh = quad();
+        expect_that!(&mesh.target_index("widen"), eq(Some(1)));
+        expect_that!(
+            &mesh.blend(mesh.default_weights()),
+            eq(mesh.base().vertices.clone())
+        );
+
+        let vertices = mesh.blend(&[0.5, 0.25]);
+        let position = vertices[2].position;
+        expect_that!(&position, eq([1.25, 1., 1.]));
+        let position = vertices[0].position;
+        expect_that!(&position, eq([-0.25, 0., 0.]));
+        let normal = Vector3::from(vertices[1].normal);
+        expect_that!((normal - Vector3::new(0., -0.5, 1.).normalize()).norm() < 1e-6);
+        let texture_coordinates = vertices[3].texture_coordinates;
+        expect_that!(&texture_coordinates, eq([0., 1.]));
+    }
+
+    #[test]
+    #[should_panic(expected = "There must be a weight per morph target")]
+    fn wrong_weight_count() {
+        quad().blend(&[1.]);
+    }
+}
@@ -0,0 +1,330 @@
+use super::{FrustumCuller, InstanceBounds};
+
+use roe_math::{Aabb3, HomogeneousMatrix3, Vector3, Vector4};
+use roe_sprite::DrawQueue;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct OcclusionBufferDescriptor {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for OcclusionBufferDescriptor {
+    fn default() -> Self {
+        Self {
+            width: 256,
+            height: 128,
+        }
+    }
+}
+
+// Points closer to the camera plane than this are treated as behind the camera.
+const MIN_CLIP_W: f32 = 1e-5;
+
+fn edge(a: &Vector3<f32>, b: &Vector3<f32>, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+// Low resolution depth buffer, rasterized on the CPU from a few large occluders (walls,
+// terrain chunks, buildings), against which the bounds of the instances are tested before
+// drawing them. The test is conservative: instances are culled only if their bounds are
+// entirely behind the occluders.
+//
+// Occluders must be opaque and fully contained in the geometry they stand for, e.g. simplified
+// meshes or boxes inside the rendered ones, otherwise visible instances can be culled.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OcclusionBuffer {
+    width: u32,
+    height: u32,
+    view_projection: HomogeneousMatrix3<f32>,
+    // Clip space depth, row by row from the top. 1 where nothing was rasterized.
+    depth: Vec<f32>,
+}
+
+impl OcclusionBuffer {
+    pub fn new(desc: &OcclusionBufferDescriptor) -> Self {
+        let (width, height) = (desc.width.max(1), desc.height.max(1));
+        Self {
+            width,
+            height,
+            view_projection: HomogeneousMatrix3::identity(),
+            depth: vec![1.; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> &[f32] {
+        &self.depth
+    }
+
+    // Must be called every frame, before rasterizing the occluders.
+    pub fn clear(&mut self, view_projection: &HomogeneousMatrix3<f32>) {
+        self.view_projection = *view_projection;
+        self.depth.iter_mut().for_each(|d| *d = 1.);
+    }
+
+    // Screen position in pixels and clip space depth, None if behind the camera.
+    fn project(&self, p: &Vector3<f32>) -> Option<Vector3<f32>> {
+        let clip = self.view_projection * Vector4::new(p.x, p.y, p.z, 1.);
+        if clip.w <= MIN_CLIP_W {
+            return None;
+        }
+        let ndc = clip.xyz() / clip.w;
+        Some(Vector3::new(
+            (ndc.x * 0.5 + 0.5) * self.width as f32,
+            (0.5 - ndc.y * 0.5) * self.height as f32,
+            ndc.z,
+        ))
+    }
+
+    // Pixel ranges covered by the bounding rectangle, None if empty.
+    fn pixel_range(&self, min: (f32, f32), max: (f32, f32), round_out: bool) -> Option<[u32; 4]> {
+        let (x0, y0, x1, y1) = if round_out {
+            (min.0.floor(), min.1.floor(), max.0.ceil(), max.1.ceil())
+        } else {
+            (
+                (min.0 - 0.5).ceil(),
+                (min.1 - 0.5).ceil(),
+                (max.0 - 0.5).floor() + 1.,
+                (max.1 - 0.5).floor() + 1.,
+            )
+        };
+        let x0 = x0.max(0.) as u32;
+        let y0 = y0.max(0.) as u32;
+        let x1 = x1.min(self.width as f32).max(0.) as u32;
+        let y1 = y1.min(self.height as f32).max(0.) as u32;
+        if x0 >= x1 || y0 >= y1 {
+            None
+        } else {
+            Some([x0, y0, x1, y1])
+        }
+    }
+
+    fn rasterize_triangle(&mut self, a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) {
+        let area = edge(a, b, c.x, c.y);
+        if area == 0. {
+            return;
+        }
+        let min = (a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y));
+        let max = (a.x.max(b.x).max(c.x), a.y.max(b.y).max(c.y));
+        let [x0, y0, x1, y1] = match self.pixel_range(min, max, false) {
+            Some(range) => range,
+            None => return,
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Only pixels whose center is covered, so that occluders never grow.
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(b, c, px, py) / area;
+                let w1 = edge(c, a, px, py) / area;
+                let w2 = edge(a, b, px, py) / area;
+                if w0 < 0. || w1 < 0. || w2 < 0. {
+                    continue;
+                }
+                let depth = w0 * a.z + w1 * b.z + w2 * c.z;
+                let texel = &mut self.depth[(y * self.width + x) as usize];
+                if depth < *texel {
+                    *texel = depth;
+                }
+            }
+        }
+    }
+
+    // Rasterizes an indexed triangle list, e.g. a simplified occluder mesh. Triangles with a
+    // vertex behind the camera are skipped.
+    pub fn rasterize_triangles(
+        &mut self,
+        transform: &HomogeneousMatrix3<f32>,
+        positions: &[Vector3<f32>],
+        indices: &[u32],
+    ) {
+        let projected: Vec<_> = positions
+            .iter()
+            .map(|p| {
+                let world = transform * Vector4::new(p.x, p.y, p.z, 1.);
+                self.project(&world.xyz())
+            })
+            .collect();
+        for triangle in indices.chunks_exact(3) {
+            let a = projected[triangle[0] as usize];
+            let b = projected[triangle[1] as usize];
+            let c = projected[triangle[2] as usize];
+            if let (Some(a), Some(b), Some(c)) = (a, b, c) {
+                self.rasterize_triangle(&a, &b, &c);
+            }
+        }
+    }
+
+    // Rasterizes a solid world space box.
+    pub fn rasterize_aabb(&mut self, aabb: &Aabb3<f32>) {
+        const BOX_INDICES: [u32; 36] = [
+            0, 1, 3, 0, 3, 2, 4, 6, 7, 4, 7, 5, 0, 4, 5, 0, 5, 1, 2, 3, 7, 2, 7, 6, 0, 2, 6, 0, 6,
+            4, 1, 5, 7, 1, 7, 3,
+        ];
+        let corners: Vec<_> = (0..8)
+            .map(|i| {
+                Vector3::new(
+                    if i & 4 == 0 { aabb.min.x } else { aabb.max.x },
+                    if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                    if i & 1 == 0 { aabb.min.z } else { aabb.max.z },
+                )
+            })
+            .collect();
+        self.rasterize_triangles(&HomogeneousMatrix3::identity(), &corners, &BOX_INDICES);
+    }
+
+    // False if the box is entirely hidden by the rasterized occluders, or outside the screen.
+    // Boxes crossing the camera plane are always visible.
+    pub fn is_visible(&self, aabb: &Aabb3<f32>) -> bool {
+        let mut min = (f32::MAX, f32::MAX, f32::MAX);
+        let mut max = (f32::MIN, f32::MIN);
+        for corner in aabb.corners().iter() {
+            let p = match self.project(corner) {
+                Some(p) => p,
+                None => return true,
+            };
+            min = (min.0.min(p.x), min.1.min(p.y), min.2.min(p.z));
+            max = (max.0.max(p.x), max.1.max(p.y));
+        }
+        let [x0, y0, x1, y1] = match self.pixel_range((min.0, min.1), max, true) {
+            Some(range) => range,
+            None => return false,
+        };
+        (y0..y1).any(|y| {
+            let row = &self.depth[(y * self.width) as usize..((y + 1) * self.width) as usize];
+            row[x0 as usize..x1 as usize]
+                .iter()
+                .any(|depth| *depth >= min.2)
+        })
+    }
+}
+
+// Frustum culling followed by the occlusion test, for the culling pass.
+#[derive(Debug, Clone, Copy)]
+pub struct OcclusionCuller<'a> {
+    frustum_culler: &'a FrustumCuller,
+    occlusion_buffer: &'a OcclusionBuffer,
+}
+
+impl<'a> OcclusionCuller<'a> {
+    // The occlusion buffer must have been cleared with the view projection of the culler.
+    pub fn new(frustum_culler: &'a FrustumCuller, occlusion_buffer: &'a OcclusionBuffer) -> Self {
+        Self {
+            frustum_culler,
+            occlusion_buffer,
+        }
+    }
+
+    pub fn is_visible(&self, bounds: &InstanceBounds) -> bool {
+        self.frustum_culler.is_visible(bounds) && self.occlusion_buffer.is_visible(&bounds.aabb)
+    }
+
+    // Returns false if the instance was culled.
+    pub fn push<T>(
+        &self,
+        queue: &mut DrawQueue<T>,
+        layer: i32,
+        bounds: &InstanceBounds,
+        value: T,
+    ) -> bool {
+        if !self.is_visible(bounds) {
+            return false;
+        }
+        queue.push_with_depth(
+            layer,
+            self.frustum_culler.view_depth(&bounds.sphere.center),
+            value,
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use roe_math::Point3;
+    use roe_sprite::DrawSortMode;
+
+    fn camera() -> (HomogeneousMatrix3<f32>, HomogeneousMatrix3<f32>) {
+        // Camera at z = 10, looking towards -z.
+        let view = HomogeneousMatrix3::look_at_rh(
+            &Point3::new(0., 0., 10.),
+            &Point3::origin(),
+            &Vector3::y(),
+        );
+        let projection = HomogeneousMatrix3::new_perspective(1., 1., 0.1, 100.);
+        (view, projection)
+    }
+
+    fn unit_box_at(x: f32, z: f32) -> Aabb3<f32> {
+        Aabb3::new(
+            Vector3::new(x - 1., -1., z - 1.),
+            Vector3::new(x + 1., 1., z + 1.),
+        )
+    }
+
+    fn buffer_with_wall() -> OcclusionBuffer {
+        let (view, projection) = camera();
+        let mut buffer = OcclusionBuffer::new(&OcclusionBufferDescriptor {
+            width: 64,
+            height: 64,
+        });
+        buffer.clear(&(projection * view));
+        buffer.rasterize_aabb(&Aabb3::new(
+            Vector3::new(-2., -2., -0.5),
+            Vector3::new(2., 2., 0.5),
+        ));
+        buffer
+    }
+
+    #[test]
+    fn occluded_bounds() {
+        let buffer = buffer_with_wall();
+        let covered = buffer.depth().iter().filter(|d| **d < 1.).count();
+        expect_that!(covered > 0 && covered < 64 * 64 / 4);
+        // Behind the wall.
+        expect_that!(!buffer.is_visible(&unit_box_at(0., -10.)));
+        // In front of the wall, beside it, crossing the camera plane.
+        expect_that!(buffer.is_visible(&unit_box_at(0., 5.)));
+        expect_that!(buffer.is_visible(&unit_box_at(6., -10.)));
+        expect_that!(buffer.is_visible(&unit_box_at(0., 10.)));
+        // Outside the screen.
+        expect_that!(!buffer.is_visible(&unit_box_at(40., -10.)));
+
+        let mut cleared = buffer.clone();
+        let (view, projection) = camera();
+        cleared.clear(&(projection * view));
+        expect_that!(cleared.is_visible(&unit_box_at(0., -10.)));
+    }
+
+    #[test]
+    fn culling_pass() {
+        let (view, projection) = camera();
+        let frustum_culler = FrustumCuller::new(&view, &projection);
+        let buffer = buffer_with_wall();
+        let culler = OcclusionCuller::new(&frustum_culler, &buffer);
+        let mut queue = DrawQueue::new(DrawSortMode::FrontToBack);
+        let mut push = |x: f32, z: f32, value| {
+            let bounds = InstanceBounds::new(
+                &Aabb3::new(Vector3::new(-1., -1., -1.), Vector3::new(1., 1., 1.)),
+                &HomogeneousMatrix3::new_translation(&Vector3::new(x, 0., z)),
+            );
+            culler.push(&mut queue, 0, &bounds, value)
+        };
+        expect_that!(!push(0., -10., "occluded"));
+        expect_that!(!push(0., 20., "behind_camera"));
+        expect_that!(push(6., -10., "beside"));
+        expect_that!(push(0., 5., "front"));
+        queue.sort();
+        let order: Vec<&str> = queue.items().map(|item| item.value).collect();
+        expect_that!(&order, eq(vec!["front", "beside"]));
+    }
+}
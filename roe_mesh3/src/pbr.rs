@@ -0,0 +1,856 @@
+use super::{
+    environment_bind_group_layout, shadow_bind_group_layout, EnvironmentLighting, Mesh,
+    MeshIndexRange, ShadowMap, Vertex,
+};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix3, Vector3};
+
+// Must match the values in pbr.frag.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 16;
+
+// Operator compressing the hdr lighting result into the displayable range. None keeps the hdr
+// values, for Rgba16Float canvases processed by the post processing passes (auto exposure,
+// color grading) before being displayed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[repr(u32)]
+pub enum ToneMapping {
+    None = 0,
+    Reinhard = 1,
+    // Filmic curve fitted to the ACES reference transform.
+    #[default]
+    Aces = 2,
+}
+
+impl ToneMapping {
+    // Same as the shader, e.g. for debugging.
+    pub fn apply(self, color: Vector3<f32>) -> Vector3<f32> {
+        match self {
+            Self::None => color,
+            Self::Reinhard => color.map(|c| c / (1. + c)),
+            Self::Aces => color
+                .map(|c| ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0., 1.)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DirectionalLight {
+    // Direction the light travels in, e.g. downwards for the sun at noon.
+    pub direction: Vector3<f32>,
+    pub color: gfx::ColorF32,
+    pub intensity: f32,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    pub color: gfx::ColorF32,
+    pub intensity: f32,
+    // The light fades out smoothly before reaching the range.
+    pub range: f32,
+}
+
+// Camera and lights shared by all the meshes drawn in a pass. Colors are in the workflow color
+// space. Lights beyond MAX_DIRECTIONAL_LIGHTS and MAX_POINT_LIGHTS are ignored.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PbrScene {
+    pub view_projection: HomogeneousMatrix3<f32>,
+    pub camera_position: Vector3<f32>,
+    // Ignored when the environment lighting is enabled.
+    pub ambient_color: gfx::ColorF32,
+    pub ambient_intensity: f32,
+    pub directional_lights: Vec<DirectionalLight>,
+    pub point_lights: Vec<PointLight>,
+    pub exposure: f32,
+    pub tone_mapping: ToneMapping,
+}
+
+impl Default for PbrScene {
+    fn default() -> Self {
+        Self {
+            view_projection: HomogeneousMatrix3::identity(),
+            camera_position: Vector3::zeros(),
+            ambient_color: gfx::ColorF32::WHITE,
+            ambient_intensity: 0.03,
+            directional_lights: Vec::new(),
+            point_lights: Vec::new(),
+            exposure: 1.,
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+}
+
+fn radiance(color: &gfx::ColorF32, intensity: f32) -> [f32; 4] {
+    [
+        color.r * intensity,
+        color.g * intensity,
+        color.b * intensity,
+        0.,
+    ]
+}
+
+// Layout of the scene uniform block in the shaders.
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct SceneBlock {
+    view_projection: [[f32; 4]; 4],
+    camera_position: [f32; 4],
+    ambient_radiance: [f32; 4],
+    settings: [u32; 4],
+    exposure: [f32; 4],
+    directional_lights: [[f32; 4]; 2 * MAX_DIRECTIONAL_LIGHTS],
+    point_lights: [[f32; 4]; 2 * MAX_POINT_LIGHTS],
+}
+
+unsafe impl bytemuck::Zeroable for SceneBlock {}
+
+unsafe impl bytemuck::Pod for SceneBlock {}
+
+impl SceneBlock {
+    fn new(scene: &PbrScene) -> Self {
+        let directional_count = scene.directional_lights.len().min(MAX_DIRECTIONAL_LIGHTS);
+        let point_count = scene.point_lights.len().min(MAX_POINT_LIGHTS);
+        let mut block = Self {
+            view_projection: scene.view_projection.into(),
+            camera_position: scene.camera_position.push(1.).into(),
+            ambient_radiance: radiance(&scene.ambient_color, scene.ambient_intensity),
+            settings: [
+                directional_count as u32,
+                point_count as u32,
+                scene.tone_mapping as u32,
+                0,
+            ],
+            exposure: [scene.exposure, 0., 0., 0.],
+            directional_lights: [[0.; 4]; 2 * MAX_DIRECTIONAL_LIGHTS],
+            point_lights: [[0.; 4]; 2 * MAX_POINT_LIGHTS],
+        };
+        for (i, light) in scene.directional_lights[..directional_count]
+            .iter()
+            .enumerate()
+        {
+            block.directional_lights[2 * i] = light.direction.push(0.).into();
+            block.directional_lights[2 * i + 1] = radiance(&light.color, light.intensity);
+        }
+        for (i, light) in scene.point_lights[..point_count].iter().enumerate() {
+            block.point_lights[2 * i] = light.position.push(light.range).into();
+            block.point_lights[2 * i + 1] = radiance(&light.color, light.intensity);
+        }
+        block
+    }
+}
+
+fn uniform_buffer_entry(binding: u32, visibility: gfx::ShaderStage) -> gfx::BindGroupLayoutEntry {
+    gfx::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: gfx::BindingType::Buffer {
+            ty: gfx::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn texture_entry(binding: u32) -> gfx::BindGroupLayoutEntry {
+    gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: true },
+            view_dimension: gfx::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+fn scene_bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[uniform_buffer_entry(
+                0,
+                gfx::ShaderStage::VERTEX | gfx::ShaderStage::FRAGMENT,
+            )],
+        },
+    )
+}
+
+// Pipeline variants can append entries after these, e.g. for vertex animation textures.
+pub(crate) const MATERIAL_BINDING_COUNT: u32 = 7;
+
+pub(crate) fn material_bind_group_layout_entries() -> Vec<gfx::BindGroupLayoutEntry> {
+    vec![
+        uniform_buffer_entry(0, gfx::ShaderStage::FRAGMENT),
+        texture_entry(1),
+        texture_entry(2),
+        texture_entry(3),
+        texture_entry(4),
+        texture_entry(5),
+        gfx::BindGroupLayoutEntry {
+            binding: 6,
+            visibility: gfx::ShaderStage::FRAGMENT,
+            ty: gfx::BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            },
+            count: None,
+        },
+    ]
+}
+
+pub(crate) fn material_bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &material_bind_group_layout_entries(),
+        },
+    )
+}
+
+// Camera and lights uniform buffer, rewritten when the scene changes.
+#[derive(Debug)]
+pub struct PbrSceneUniforms {
+    buffer: gfx::Buffer,
+    bind_group: gfx::BindGroup,
+}
+
+impl PbrSceneUniforms {
+    pub fn new(instance: &gfx::Instance, scene: &PbrScene) -> Self {
+        let buffer = gfx::Buffer::init(
+            instance,
+            &gfx::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::bytes_of(&SceneBlock::new(scene)),
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+            },
+        );
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &scene_bind_group_layout(instance),
+                entries: &[gfx::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            },
+        );
+        Self { buffer, bind_group }
+    }
+
+    pub fn update(&self, instance: &gfx::Instance, scene: &PbrScene) {
+        instance.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&SceneBlock::new(scene)));
+    }
+}
+
+// glTF metallic-roughness material. The factors multiply the texture values.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PbrMaterial {
+    // In the workflow color space.
+    pub base_color_factor: gfx::ColorF32,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: Vector3<f32>,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            base_color_factor: gfx::ColorF32::WHITE,
+            metallic_factor: 1.,
+            roughness_factor: 1.,
+            emissive_factor: Vector3::zeros(),
+            normal_scale: 1.,
+            occlusion_strength: 1.,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct MaterialBlock {
+    base_color_factor: [f32; 4],
+    emissive_factor: [f32; 4],
+    parameters: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for MaterialBlock {}
+
+unsafe impl bytemuck::Pod for MaterialBlock {}
+
+impl MaterialBlock {
+    fn new(material: &PbrMaterial) -> Self {
+        let c = &material.base_color_factor;
+        Self {
+            base_color_factor: [c.r, c.g, c.b, c.a],
+            emissive_factor: material.emissive_factor.push(0.).into(),
+            parameters: [
+                material.metallic_factor,
+                material.roughness_factor,
+                material.normal_scale,
+                material.occlusion_strength,
+            ],
+        }
+    }
+}
+
+// Single texel textures used in place of the textures a material doesn't have.
+#[derive(Debug)]
+pub struct PbrDefaultTextures {
+    pub(crate) white: gfx::TextureView,
+    pub(crate) white_linear: gfx::TextureView,
+    pub(crate) red_linear: gfx::TextureView,
+    pub(crate) flat_normal: gfx::TextureView,
+}
+
+impl PbrDefaultTextures {
+    pub fn new(instance: &gfx::Instance) -> Self {
+        let texel = |format, value: [u8; 4]| {
+            let texture = gfx::Texture::new(
+                instance,
+                &gfx::TextureDescriptor {
+                    label: None,
+                    size: gfx::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: gfx::TextureDimension::D2,
+                    format,
+                    usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+                },
+            );
+            texture.write(
+                instance,
+                0,
+                gfx::Origin3d::ZERO,
+                &value,
+                gfx::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: core::num::NonZeroU32::new(4),
+                    rows_per_image: None,
+                },
+                gfx::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+            texture.create_view(&gfx::TextureViewDescriptor::default())
+        };
+        Self {
+            white: texel(instance.color_workflow().texture_format(), [255; 4]),
+            white_linear: texel(gfx::TextureFormat::Rgba8Unorm, [255; 4]),
+            red_linear: texel(gfx::TextureFormat::Rgba8Unorm, [255, 0, 0, 0]),
+            flat_normal: texel(gfx::TextureFormat::Rgba8Unorm, [128, 128, 255, 255]),
+        }
+    }
+}
+
+// Material textures. Base color and emissive textures store colors, and are created with the
+// workflow texture format. The other textures store data and must have a Unorm format.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PbrMaterialTextures<'a> {
+    pub base_color: Option<&'a gfx::TextureView>,
+    // Tangent space normals.
+    pub normal: Option<&'a gfx::TextureView>,
+    // Roughness in the green channel and metalness in the blue channel.
+    pub metallic_roughness: Option<&'a gfx::TextureView>,
+    pub emissive: Option<&'a gfx::TextureView>,
+    // Ambient occlusion in the red channel.
+    pub occlusion: Option<&'a gfx::TextureView>,
+}
+
+pub(crate) fn texture_binding<'a>(
+    binding: u32,
+    texture: Option<&'a gfx::TextureView>,
+    default: &'a gfx::TextureView,
+) -> gfx::BindGroupEntry<'a> {
+    gfx::BindGroupEntry {
+        binding,
+        resource: gfx::BindingResource::TextureView(texture.unwrap_or(default)),
+    }
+}
+
+#[derive(Debug)]
+pub struct PbrMaterialUniforms {
+    buffer: gfx::Buffer,
+    bind_group: gfx::BindGroup,
+}
+
+impl PbrMaterialUniforms {
+    pub fn new(
+        instance: &gfx::Instance,
+        material: &PbrMaterial,
+        textures: &PbrMaterialTextures,
+        defaults: &PbrDefaultTextures,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        Self::with_layout(
+            instance,
+            material,
+            textures,
+            defaults,
+            sampler,
+            &material_bind_group_layout(instance),
+            &[],
+        )
+    }
+
+    // For layouts appending entries to the material layout.
+    pub(crate) fn with_layout(
+        instance: &gfx::Instance,
+        material: &PbrMaterial,
+        textures: &PbrMaterialTextures,
+        defaults: &PbrDefaultTextures,
+        sampler: &gfx::Sampler,
+        layout: &gfx::BindGroupLayout,
+        extra_entries: &[gfx::BindGroupEntry],
+    ) -> Self {
+        let buffer = gfx::Buffer::init(
+            instance,
+            &gfx::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::bytes_of(&MaterialBlock::new(material)),
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+            },
+        );
+        let mut entries = vec![
+            gfx::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            texture_binding(1, textures.base_color, &defaults.white),
+            texture_binding(2, textures.normal, &defaults.flat_normal),
+            texture_binding(3, textures.metallic_roughness, &defaults.white_linear),
+            texture_binding(4, textures.emissive, &defaults.white),
+            texture_binding(5, textures.occlusion, &defaults.white_linear),
+            gfx::BindGroupEntry {
+                binding: 6,
+                resource: gfx::BindingResource::Sampler(sampler),
+            },
+        ];
+        entries.extend_from_slice(extra_entries);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout,
+                entries: &entries,
+            },
+        );
+        Self { buffer, bind_group }
+    }
+
+    pub(crate) fn bind_group(&self) -> &gfx::BindGroup {
+        &self.bind_group
+    }
+
+    // The textures can't be changed, only the factors.
+    pub fn update(&self, instance: &gfx::Instance, material: &PbrMaterial) {
+        instance.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::bytes_of(&MaterialBlock::new(material)),
+        );
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PbrPushConstants {
+    model: HomogeneousMatrix3<f32>,
+    lod_fade: f32,
+    _padding: [f32; 3],
+    // Replaces the scene ambient radiance when the last component is not 0.
+    ambient: [f32; 4],
+}
+
+impl PbrPushConstants {
+    pub fn new(model: &HomogeneousMatrix3<f32>) -> Self {
+        Self {
+            model: *model,
+            lod_fade: 0.,
+            _padding: [0.; 3],
+            ambient: [0.; 4],
+        }
+    }
+
+    // Dithers the mesh out during a LOD cross-fade, see LodTransition.
+    pub fn with_lod_fade(mut self, lod_fade: f32) -> Self {
+        self.lod_fade = lod_fade.clamp(-1., 1.);
+        self
+    }
+
+    // Local ambient radiance, e.g. sampled from a LightProbeGrid3, replacing the scene ambient
+    // radiance and the diffuse environment lighting.
+    pub fn with_ambient(mut self, radiance: &Vector3<f32>) -> Self {
+        self.ambient = [radiance.x, radiance.y, radiance.z, 1.];
+        self
+    }
+}
+
+unsafe impl bytemuck::Zeroable for PbrPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            model: HomogeneousMatrix3::zeros(),
+            lod_fade: 0.,
+            _padding: [0.; 3],
+            ambient: [0.; 4],
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for PbrPushConstants {}
+
+const PC_VERTEX_SIZE: u32 = std::mem::size_of::<HomogeneousMatrix3<f32>>() as u32;
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<PbrPushConstants>() as u32;
+// Followed by the emissive mask flag.
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + 2 * std::mem::size_of::<u32>() as u32;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum PbrBlendMode {
+    #[default]
+    Opaque,
+    // Blended with the base color alpha, tested against the depth buffer without writing it.
+    // Draw these meshes after the opaque ones, back to front.
+    AlphaBlend,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PbrPipelineDescriptor {
+    pub label: Option<String>,
+    pub blend_mode: PbrBlendMode,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    pub sample_count: gfx::SampleCount,
+    // None for double sided materials.
+    pub cull_mode: Option<gfx::Face>,
+    // Follows the emissive mask convention, see gfx::emissive_mask_alpha_blend: opaque meshes
+    // write the emitted fraction of their color to the alpha, blended meshes remove the mask
+    // beneath them.
+    pub emissive_mask: bool,
+}
+
+impl Default for PbrPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            blend_mode: PbrBlendMode::default(),
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat::Depth32Float,
+            sample_count: 1,
+            cull_mode: Some(gfx::Face::Back),
+            emissive_mask: false,
+        }
+    }
+}
+
+// Metallic-roughness meshes, with depth testing.
+#[derive(Debug)]
+pub struct PbrPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    color_conversion: gfx::ColorConversion,
+    emissive_mask: bool,
+}
+
+// Shaders and layouts of the variants of the PBR pipeline, e.g. the terrain pipeline. The
+// other bind groups and the push constants are shared.
+pub(crate) struct PbrPipelineShaders<'a> {
+    pub vertex_shader: gfx::ShaderModuleDescriptor<'a>,
+    pub vertex_buffers: &'a [gfx::VertexBufferLayout<'a>],
+    pub material_bind_group_layout: gfx::BindGroupLayout,
+    pub fragment_shader: gfx::ShaderModuleDescriptor<'a>,
+}
+
+pub(crate) const VERTEX_ATTRIBUTES: [gfx::VertexAttribute; 3] = [
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 0,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x3,
+        offset: 12,
+        shader_location: 1,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x2,
+        offset: 24,
+        shader_location: 2,
+    },
+];
+
+pub(crate) fn vertex_buffer_layout() -> gfx::VertexBufferLayout<'static> {
+    gfx::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Vertex>() as gfx::BufferAddress,
+        step_mode: gfx::VertexStepMode::Vertex,
+        attributes: &VERTEX_ATTRIBUTES,
+    }
+}
+
+impl PbrPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &PbrPipelineDescriptor) -> Self {
+        Self::with_shaders(
+            instance,
+            desc,
+            PbrPipelineShaders {
+                vertex_shader: gfx::include_spirv!("shaders/gen/spirv/mesh.vert.spv"),
+                vertex_buffers: &[vertex_buffer_layout()],
+                material_bind_group_layout: material_bind_group_layout(instance),
+                fragment_shader: gfx::include_spirv!("shaders/gen/spirv/pbr.frag.spv"),
+            },
+        )
+    }
+
+    pub(crate) fn with_shaders(
+        instance: &gfx::Instance,
+        desc: &PbrPipelineDescriptor,
+        shaders: PbrPipelineShaders,
+    ) -> Self {
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[
+                    &scene_bind_group_layout(instance),
+                    &shaders.material_bind_group_layout,
+                    &shadow_bind_group_layout(instance),
+                    &environment_bind_group_layout(instance),
+                ],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_VERTEX_SIZE,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_VERTEX_SIZE..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(instance, &shaders.vertex_shader);
+        let fs_module = gfx::ShaderModule::new(instance, &shaders.fragment_shader);
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: shaders.vertex_buffers,
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: desc.cull_mode,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(gfx::DepthStencilState {
+                    format: gfx::TextureFormat::from(desc.depth_stencil_buffer_format),
+                    depth_write_enabled: desc.blend_mode == PbrBlendMode::Opaque,
+                    depth_compare: gfx::CompareFunction::Less,
+                    stencil: gfx::StencilState::default(),
+                    bias: gfx::DepthBiasState::default(),
+                }),
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: match desc.blend_mode {
+                            PbrBlendMode::Opaque => None,
+                            PbrBlendMode::AlphaBlend if desc.emissive_mask => {
+                                Some(gfx::BlendState {
+                                    color: gfx::BlendState::ALPHA_BLENDING.color,
+                                    alpha: gfx::emissive_mask_alpha_blend(false),
+                                })
+                            }
+                            PbrBlendMode::AlphaBlend => Some(gfx::BlendState::ALPHA_BLENDING),
+                        },
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+            emissive_mask: desc.emissive_mask && desc.blend_mode == PbrBlendMode::Opaque,
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: Some(self.depth_stencil_buffer_format),
+        }
+    }
+}
+
+// Lighting resources shared by all the meshes drawn in a pass.
+#[derive(Debug, Clone, Copy)]
+pub struct PbrLighting<'a> {
+    pub scene: &'a PbrSceneUniforms,
+    pub shadow_map: &'a ShadowMap,
+    pub environment: &'a EnvironmentLighting,
+}
+
+pub trait PbrRenderer<'a> {
+    fn draw_pbr_mesh(
+        &mut self,
+        pipeline: &'a PbrPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a PbrMaterialUniforms,
+        mesh: &'a Mesh,
+        push_constants: &'a PbrPushConstants,
+        index_range: MeshIndexRange,
+    );
+}
+
+impl<'a> PbrRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_pbr_mesh(
+        &mut self,
+        pipeline: &'a PbrPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a PbrMaterialUniforms,
+        mesh: &'a Mesh,
+        push_constants: &'a PbrPushConstants,
+        index_range: MeshIndexRange,
+    ) {
+        bind_lit_mesh(
+            self,
+            pipeline,
+            lighting,
+            &material.bind_group,
+            mesh,
+            push_constants,
+        );
+        self.draw_indexed(index_range, 0, 0..1);
+    }
+}
+
+// Shared by the pipelines using the PbrPipeline bind group layouts, binds everything but the
+// instance buffers.
+pub(crate) fn bind_lit_mesh<'a>(
+    pass: &mut gfx::RenderPass<'a>,
+    pipeline: &'a PbrPipeline,
+    lighting: PbrLighting<'a>,
+    material_bind_group: &'a gfx::BindGroup,
+    mesh: &'a Mesh,
+    push_constants: &'a PbrPushConstants,
+) {
+    pass.set_pipeline(&pipeline.pipeline);
+    pass.set_push_constants(
+        gfx::ShaderStage::FRAGMENT,
+        PC_CONVERSION_MEM_OFFSET,
+        gfx::utility::as_slice(&[
+            pipeline.color_conversion as u32,
+            pipeline.emissive_mask as u32,
+        ]),
+    );
+    pass.set_bind_group(0, &lighting.scene.bind_group, &[]);
+    pass.set_bind_group(1, material_bind_group, &[]);
+    pass.set_bind_group(2, lighting.shadow_map.bind_group(), &[]);
+    pass.set_bind_group(3, lighting.environment.bind_group(), &[]);
+    pass.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
+    pass.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+    let bytes = bytemuck::bytes_of(push_constants);
+    pass.set_push_constants(
+        gfx::ShaderStage::VERTEX,
+        0,
+        &bytes[..PC_VERTEX_SIZE as usize],
+    );
+    pass.set_push_constants(
+        gfx::ShaderStage::FRAGMENT,
+        PC_VERTEX_SIZE,
+        &bytes[PC_VERTEX_SIZE as usize..],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn scene_block_layout() {
+        expect_that!(&std::mem::size_of::<SceneBlock>(), eq(768));
+        expect_that!(&std::mem::size_of::<MaterialBlock>(), eq(48));
+
+        let light = PointLight {
+            position: Vector3::new(1., 2., 3.),
+            color: gfx::ColorF32::WHITE,
+            intensity: 2.,
+            range: 10.,
+        };
+        let scene = PbrScene {
+            directional_lights: vec![DirectionalLight {
+                direction: Vector3::new(0., -1., 0.),
+                color: gfx::ColorF32 {
+                    r: 1.,
+                    g: 0.5,
+                    b: 0.,
+                    a: 1.,
+                },
+                intensity: 4.,
+            }],
+            point_lights: vec![light; MAX_POINT_LIGHTS + 3],
+            tone_mapping: ToneMapping::Reinhard,
+            ..PbrScene::default()
+        };
+        let block = SceneBlock::new(&scene);
+        expect_that!(&block.settings, eq([1, MAX_POINT_LIGHTS as u32, 1, 0]));
+        expect_that!(&block.directional_lights[0], eq([0., -1., 0., 0.]));
+        expect_that!(&block.directional_lights[1], eq([4., 2., 0., 0.]));
+        expect_that!(&block.point_lights[30], eq([1., 2., 3., 10.]));
+        expect_that!(&block.point_lights[31], eq([2., 2., 2., 0.]));
+    }
+
+    #[test]
+    fn tone_mapping() {
+        let hdr = Vector3::new(0., 1., 100.);
+        expect_that!(&ToneMapping::None.apply(hdr), eq(hdr));
+        expect_that!(
+            &ToneMapping::Reinhard.apply(hdr),
+            eq(Vector3::new(0., 0.5, 100. / 101.))
+        );
+        let aces = ToneMapping::Aces.apply(hdr);
+        expect_that!(&aces.x, close_to(0., 1e-6));
+        expect_that!(&aces.y, close_to(0.8038, 1e-3));
+        expect_that!(&aces.z, eq(1.));
+    }
+}
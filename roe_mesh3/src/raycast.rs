@@ -0,0 +1,220 @@
+use super::{ray_triangle_intersection, Bvh, MeshData, Ray};
+
+use roe_math::{Aabb3, HomogeneousMatrix3, Vector3};
+
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RayHit {
+    // In multiples of the ray direction.
+    pub distance: f32,
+    pub triangle: usize,
+    // Weights of the second and third triangle vertex, e.g. to interpolate texture coordinates.
+    pub barycentric: [f32; 2],
+}
+
+// Triangles of a mesh, in the mesh local space, for ray queries.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeshBvh {
+    triangles: Vec<[Vector3<f32>; 3]>,
+    bvh: Bvh,
+}
+
+impl MeshBvh {
+    pub fn new(mesh: &MeshData) -> Self {
+        let triangles: Vec<[Vector3<f32>; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                let position = |i: u32| Vector3::from(mesh.vertices[i as usize].position);
+                [position(t[0]), position(t[1]), position(t[2])]
+            })
+            .collect();
+        let bounds: Vec<Aabb3<f32>> = triangles
+            .iter()
+            .map(|t| Aabb3::from_points(t).unwrap())
+            .collect();
+        Self {
+            bvh: Bvh::new(&bounds),
+            triangles,
+        }
+    }
+
+    pub fn triangle(&self, index: usize) -> &[Vector3<f32>; 3] {
+        &self.triangles[index]
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn bounds(&self) -> Option<&Aabb3<f32>> {
+        self.bvh.bounds()
+    }
+
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        self.bvh
+            .closest_hit(ray, max_distance, |triangle, max_distance| {
+                ray_triangle_intersection(ray, &self.triangles[triangle], max_distance).map(
+                    |(distance, barycentric)| {
+                        (
+                            distance,
+                            RayHit {
+                                distance,
+                                triangle,
+                                barycentric,
+                            },
+                        )
+                    },
+                )
+            })
+    }
+
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.bvh
+            .any_hit(ray, max_distance, |triangle, max_distance| {
+                ray_triangle_intersection(ray, &self.triangles[triangle], max_distance).is_some()
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SceneBvhInstance {
+    pub mesh: Arc<MeshBvh>,
+    pub transform: HomogeneousMatrix3<f32>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SceneRayHit {
+    pub instance: usize,
+    pub hit: RayHit,
+}
+
+// Mesh instances placed in a scene, for picking and line of sight queries. Meshes can be shared
+// by many instances. Rebuild it when instances move.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SceneBvh {
+    instances: Vec<SceneBvhInstance>,
+    inverse_transforms: Vec<HomogeneousMatrix3<f32>>,
+    bvh: Bvh,
+}
+
+impl SceneBvh {
+    pub fn new(instances: Vec<SceneBvhInstance>) -> Self {
+        let mut inverse_transforms = Vec::with_capacity(instances.len());
+        let mut bounds = Vec::with_capacity(instances.len());
+        for instance in instances.iter() {
+            // Degenerate instances, e.g. scaled to zero, are never hit: the zero inverse maps
+            // rays to a zero direction.
+            let inverse = instance.transform.try_inverse();
+            let origin = instance.transform.fixed_slice::<3, 1>(0, 3).into_owned();
+            bounds.push(match (instance.mesh.bounds(), inverse.is_some()) {
+                (Some(b), true) => b.transformed(&instance.transform),
+                _ => Aabb3::new(origin, origin),
+            });
+            inverse_transforms.push(inverse.unwrap_or_else(HomogeneousMatrix3::zeros));
+        }
+        Self {
+            bvh: Bvh::new(&bounds),
+            instances,
+            inverse_transforms,
+        }
+    }
+
+    pub fn instances(&self) -> &[SceneBvhInstance] {
+        &self.instances
+    }
+
+    // The ray is transformed in the instance local space without normalizing the direction, so
+    // distances remain in multiples of the world space direction.
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f32) -> Option<SceneRayHit> {
+        self.bvh
+            .closest_hit(ray, max_distance, |instance, max_distance| {
+                let local_ray = ray.transformed(&self.inverse_transforms[instance]);
+                self.instances[instance]
+                    .mesh
+                    .closest_hit(&local_ray, max_distance)
+                    .map(|hit| (hit.distance, SceneRayHit { instance, hit }))
+            })
+    }
+
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.bvh
+            .any_hit(ray, max_distance, |instance, max_distance| {
+                let local_ray = ray.transformed(&self.inverse_transforms[instance]);
+                self.instances[instance]
+                    .mesh
+                    .any_hit(&local_ray, max_distance)
+            })
+    }
+
+    // True if nothing is hit on the segment between the two points.
+    pub fn line_of_sight(&self, from: &Vector3<f32>, to: &Vector3<f32>) -> bool {
+        !self.any_hit(&Ray::new(*from, to - from), 1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vertex;
+    use galvanic_assert::{matchers::*, *};
+
+    // Unit quad on the xy plane, facing z.
+    fn quad() -> MeshData {
+        let vertex = |x, y| Vertex::new([x, y, 0.], [0., 0., 1.], [x, y]);
+        MeshData::new(
+            vec![
+                vertex(0., 0.),
+                vertex(1., 0.),
+                vertex(1., 1.),
+                vertex(0., 1.),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        )
+    }
+
+    #[test]
+    fn mesh_hits() {
+        let bvh = MeshBvh::new(&quad());
+        expect_that!(&bvh.triangle_count(), eq(2));
+        let ray = Ray::new(Vector3::new(0.25, 0.75, 3.), -Vector3::z());
+        let hit = bvh.closest_hit(&ray, f32::INFINITY).unwrap();
+        expect_that!(&hit.distance, close_to(3., 1e-6));
+        expect_that!(&hit.triangle, eq(1));
+        expect_that!(bvh.any_hit(&ray, 4.));
+        expect_that!(!bvh.any_hit(&ray, 2.));
+    }
+
+    #[test]
+    fn scene_hits() {
+        let mesh = Arc::new(MeshBvh::new(&quad()));
+        let instance = |z: f32, scale: f32| SceneBvhInstance {
+            mesh: mesh.clone(),
+            transform: HomogeneousMatrix3::new_translation(&Vector3::new(0., 0., z))
+                * HomogeneousMatrix3::new_scaling(scale),
+        };
+        let scene = SceneBvh::new(vec![
+            instance(-5., 1.),
+            instance(-2., 4.),
+            instance(-1., 0.),
+        ]);
+        let ray = Ray::new(Vector3::new(2., 2., 0.), -Vector3::z());
+        let hit = scene.closest_hit(&ray, f32::INFINITY).unwrap();
+        expect_that!(&hit.instance, eq(1));
+        expect_that!(&hit.hit.distance, close_to(2., 1e-5));
+
+        let ray = Ray::new(Vector3::new(0.5, 0.5, 0.), -Vector3::z());
+        expect_that!(
+            &scene.closest_hit(&ray, f32::INFINITY).unwrap().instance,
+            eq(1)
+        );
+        expect_that!(
+            !scene.line_of_sight(&Vector3::new(0.5, 0.5, 0.), &Vector3::new(0.5, 0.5, -10.))
+        );
+        expect_that!(
+            scene.line_of_sight(&Vector3::new(0.5, 0.5, 0.), &Vector3::new(0.5, 0.5, -1.5))
+        );
+        expect_that!(scene.line_of_sight(&Vector3::new(5., 5., 0.), &Vector3::new(5., 5., -10.)));
+    }
+}
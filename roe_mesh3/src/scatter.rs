@@ -0,0 +1,589 @@
+use super::{
+    bind_lit_mesh, material_bind_group_layout, vertex_buffer_layout, Frustum, Heightfield, Mesh,
+    PbrLighting, PbrMaterialUniforms, PbrPipeline, PbrPipelineDescriptor, PbrPipelineShaders,
+    PbrPushConstants, Terrain,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use roe_graphics as gfx;
+use roe_math::{Aabb3, HomogeneousMatrix3, Rotation3, Vector2, Vector3};
+
+use std::{collections::BTreeMap, ops::Range};
+
+// Values from 0 to 1, stretched over the scattered area. Loaded like heightfields.
+pub type DensityMap = Heightfield;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScatterDescriptor {
+    // Corner and extent of the scattered area on the xz plane.
+    pub origin: Vector2<f32>,
+    pub size: Vector2<f32>,
+    // Instances per square unit where the density map is 1.
+    pub density: f32,
+    pub seed: u64,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // Random rotation around the up axis.
+    pub random_yaw: bool,
+    // Maximum random tilt away from the up axis, in radians.
+    pub max_tilt: f32,
+    // Up axis along the ground normal instead of the y axis.
+    pub align_to_ground: bool,
+    // Instances aren't placed on steeper ground, in radians.
+    pub max_slope: f32,
+}
+
+impl Default for ScatterDescriptor {
+    fn default() -> Self {
+        Self {
+            origin: Vector2::zeros(),
+            size: Vector2::new(64., 64.),
+            density: 1.,
+            seed: 0,
+            min_scale: 0.8,
+            max_scale: 1.2,
+            random_yaw: true,
+            max_tilt: 0.,
+            align_to_ground: false,
+            max_slope: std::f32::consts::FRAC_PI_2,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ScatterInstance {
+    pub position: Vector3<f32>,
+    pub rotation: Rotation3<f32>,
+    pub scale: f32,
+}
+
+impl ScatterInstance {
+    pub fn transform(&self) -> HomogeneousMatrix3<f32> {
+        HomogeneousMatrix3::new_translation(&self.position)
+            * self.rotation.to_homogeneous()
+            * HomogeneousMatrix3::new_scaling(self.scale)
+    }
+}
+
+fn random_range(rng: &mut StdRng, min: f32, max: f32) -> f32 {
+    if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+// Bilinear sample, u and v from 0 to 1.
+fn density_at(map: &DensityMap, u: f32, v: f32) -> f32 {
+    let x = u * (map.width() - 1) as f32;
+    let z = v * (map.depth() - 1) as f32;
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (x - x0, z - z0);
+    let (x0, z0) = (x0 as i64, z0 as i64);
+    let s = |dx, dz| map.sample(x0 + dx, z0 + dz);
+    let top = s(0, 0) + (s(1, 0) - s(0, 0)) * fx;
+    let bottom = s(0, 1) + (s(1, 1) - s(0, 1)) * fx;
+    top + (bottom - top) * fz
+}
+
+// Places instances on a jittered grid, keeping each one with the probability given by the density
+// map. When a terrain is given, the instances are placed on it and the area outside of it is
+// skipped, otherwise they are placed at y = 0. The result only depends on the inputs and seed.
+pub fn scatter(
+    density_map: &DensityMap,
+    desc: &ScatterDescriptor,
+    ground: Option<&Terrain>,
+) -> Vec<ScatterInstance> {
+    let mut instances = Vec::new();
+    if desc.density <= 0. || desc.size.x <= 0. || desc.size.y <= 0. {
+        return instances;
+    }
+    let spacing = 1. / desc.density.sqrt();
+    let columns = (desc.size.x / spacing).ceil() as u32;
+    let rows = (desc.size.y / spacing).ceil() as u32;
+    let mut rng = StdRng::seed_from_u64(desc.seed);
+    for row in 0..rows {
+        for column in 0..columns {
+            // Always drawn, so that the sequence doesn't depend on the rejected instances.
+            let jitter = Vector2::new(rng.gen::<f32>(), rng.gen::<f32>());
+            let keep = rng.gen::<f32>();
+            let yaw = random_range(&mut rng, 0., std::f32::consts::TAU);
+            let tilt = random_range(&mut rng, 0., desc.max_tilt);
+            let tilt_direction = random_range(&mut rng, 0., std::f32::consts::TAU);
+            let scale = random_range(&mut rng, desc.min_scale, desc.max_scale);
+
+            let cell = Vector2::new(column as f32, row as f32);
+            let local = (cell + jitter) * spacing;
+            if local.x >= desc.size.x || local.y >= desc.size.y {
+                continue;
+            }
+            let uv = local.component_div(&desc.size);
+            if keep >= density_at(density_map, uv.x, uv.y) {
+                continue;
+            }
+            let xz = desc.origin + local;
+            let (height, up) = match ground {
+                Some(terrain) => {
+                    let height = match terrain.height_at(xz.x, xz.y) {
+                        Some(height) => height,
+                        None => continue,
+                    };
+                    let normal = terrain.normal_at(xz.x, xz.y).unwrap_or_else(Vector3::y);
+                    if normal.y.clamp(-1., 1.).acos() > desc.max_slope {
+                        continue;
+                    }
+                    (height, normal)
+                }
+                None => (0., Vector3::y()),
+            };
+
+            let mut rotation = Rotation3::from_axis_angle(&Vector3::y_axis(), yaw);
+            if tilt > 0. {
+                let axis = Vector3::new(tilt_direction.cos(), 0., tilt_direction.sin());
+                rotation = Rotation3::new(axis * tilt) * rotation;
+            }
+            if desc.align_to_ground {
+                if let Some(align) = Rotation3::rotation_between(&Vector3::y(), &up) {
+                    rotation = align * rotation;
+                }
+            }
+            if !desc.random_yaw {
+                rotation *= Rotation3::from_axis_angle(&Vector3::y_axis(), -yaw);
+            }
+            instances.push(ScatterInstance {
+                position: Vector3::new(xz.x, height, xz.y),
+                rotation,
+                scale,
+            });
+        }
+    }
+    instances
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScatterFieldDescriptor {
+    // Size of the culling cells on the xz plane.
+    pub cell_size: f32,
+    // Instances fade out between these distances from the camera, and aren't drawn beyond.
+    pub fade_start: f32,
+    pub fade_end: f32,
+}
+
+impl Default for ScatterFieldDescriptor {
+    fn default() -> Self {
+        Self {
+            cell_size: 16.,
+            fade_start: 40.,
+            fade_end: 50.,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScatterCell {
+    // Bounds of the instances in the cell, in world space.
+    pub bounds: Aabb3<f32>,
+    pub instances: Range<usize>,
+}
+
+// Scattered instances of a mesh, grouped in cells to cull them in bulk.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScatterField {
+    instances: Vec<ScatterInstance>,
+    cells: Vec<ScatterCell>,
+    fade_start: f32,
+    fade_end: f32,
+}
+
+impl ScatterField {
+    // The bounds are the ones of the mesh, in local space.
+    pub fn new(
+        instances: Vec<ScatterInstance>,
+        mesh_bounds: &Aabb3<f32>,
+        desc: &ScatterFieldDescriptor,
+    ) -> Self {
+        let cell_size = desc.cell_size.max(f32::EPSILON);
+        let mut grid: BTreeMap<(i64, i64), Vec<ScatterInstance>> = BTreeMap::new();
+        for instance in instances {
+            let key = (
+                (instance.position.x / cell_size).floor() as i64,
+                (instance.position.z / cell_size).floor() as i64,
+            );
+            grid.entry(key).or_default().push(instance);
+        }
+
+        let mut instances = Vec::new();
+        let mut cells = Vec::with_capacity(grid.len());
+        for cell_instances in grid.into_values() {
+            let start = instances.len();
+            let bounds = cell_instances
+                .iter()
+                .map(|i| mesh_bounds.transformed(&i.transform()))
+                .reduce(|a, b| a.union(&b))
+                .unwrap();
+            instances.extend(cell_instances);
+            cells.push(ScatterCell {
+                bounds,
+                instances: start..instances.len(),
+            });
+        }
+        Self {
+            instances,
+            cells,
+            fade_start: desc.fade_start,
+            fade_end: desc.fade_end,
+        }
+    }
+
+    // Ordered by cell.
+    pub fn instances(&self) -> &[ScatterInstance] {
+        &self.instances
+    }
+
+    pub fn cells(&self) -> &[ScatterCell] {
+        &self.cells
+    }
+
+    // Fade from 0 to 1, or None beyond the fade end.
+    pub fn fade(&self, distance: f32) -> Option<f32> {
+        if distance >= self.fade_end {
+            None
+        } else if self.fade_end > self.fade_start {
+            Some(((distance - self.fade_start) / (self.fade_end - self.fade_start)).max(0.))
+        } else {
+            Some(0.)
+        }
+    }
+
+    // Replaces the content of the output with the instances to draw. Returns the number of visible
+    // cells.
+    pub fn visible_instances(
+        &self,
+        frustum: &Frustum,
+        camera_position: &Vector3<f32>,
+        out: &mut Vec<ScatterInstanceData>,
+    ) -> usize {
+        out.clear();
+        let mut visible_cells = 0;
+        for cell in &self.cells {
+            let closest = camera_position.sup(&cell.bounds.min).inf(&cell.bounds.max);
+            if (closest - camera_position).norm() >= self.fade_end
+                || !frustum.intersects_aabb(&cell.bounds)
+            {
+                continue;
+            }
+            visible_cells += 1;
+            for instance in &self.instances[cell.instances.clone()] {
+                if let Some(fade) = self.fade((instance.position - camera_position).norm()) {
+                    out.push(ScatterInstanceData::new(&instance.transform(), fade));
+                }
+            }
+        }
+        visible_cells
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ScatterInstanceData {
+    pub model: [[f32; 4]; 4],
+    // Fraction of the fragments discarded, see PbrPushConstants::with_lod_fade.
+    pub fade: f32,
+}
+
+impl ScatterInstanceData {
+    pub fn new(model: &HomogeneousMatrix3<f32>, fade: f32) -> Self {
+        Self {
+            model: (*model).into(),
+            fade,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for ScatterInstanceData {}
+
+unsafe impl bytemuck::Pod for ScatterInstanceData {}
+
+#[derive(Debug)]
+pub struct ScatterInstanceBuffer {
+    buffer: gfx::Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl ScatterInstanceBuffer {
+    pub fn new(instance: &gfx::Instance, instances: &[ScatterInstanceData]) -> Self {
+        let mut buffer = Self {
+            buffer: Self::create_buffer(instance, 1),
+            capacity: 1,
+            len: 0,
+        };
+        buffer.update(instance, instances);
+        buffer
+    }
+
+    // The write is queued and applied before the next submitted commands.
+    pub fn update(&mut self, instance: &gfx::Instance, instances: &[ScatterInstanceData]) {
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.buffer = Self::create_buffer(instance, self.capacity);
+        }
+        self.len = instances.len();
+        if !instances.is_empty() {
+            instance.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn create_buffer(instance: &gfx::Instance, capacity: usize) -> gfx::Buffer {
+        gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (capacity * std::mem::size_of::<ScatterInstanceData>()) as gfx::BufferAddress,
+                usage: gfx::BufferUsage::VERTEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+}
+
+const INSTANCE_ATTRIBUTES: [gfx::VertexAttribute; 5] = [
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 0,
+        shader_location: 3,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 16,
+        shader_location: 4,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 32,
+        shader_location: 5,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 48,
+        shader_location: 6,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32,
+        offset: 64,
+        shader_location: 7,
+    },
+];
+
+// Instanced PBR meshes with a dithered distance fade. The push constants model matrix is applied
+// after the instance ones.
+#[derive(Debug)]
+pub struct ScatterPipeline {
+    pipeline: PbrPipeline,
+}
+
+impl ScatterPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &PbrPipelineDescriptor) -> Self {
+        Self {
+            pipeline: PbrPipeline::with_shaders(
+                instance,
+                desc,
+                PbrPipelineShaders {
+                    vertex_shader: gfx::include_spirv!("shaders/gen/spirv/scatter.vert.spv"),
+                    vertex_buffers: &[
+                        vertex_buffer_layout(),
+                        gfx::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<ScatterInstanceData>()
+                                as gfx::BufferAddress,
+                            step_mode: gfx::VertexStepMode::Instance,
+                            attributes: &INSTANCE_ATTRIBUTES,
+                        },
+                    ],
+                    material_bind_group_layout: material_bind_group_layout(instance),
+                    fragment_shader: gfx::include_spirv!("shaders/gen/spirv/pbr.frag.2.spv"),
+                },
+            ),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.pipeline.color_conversion()
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        self.pipeline.render_pass_requirements()
+    }
+}
+
+pub trait ScatterRenderer<'a> {
+    fn draw_scatter(
+        &mut self,
+        pipeline: &'a ScatterPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a PbrMaterialUniforms,
+        mesh: &'a Mesh,
+        instances: &'a ScatterInstanceBuffer,
+        push_constants: &'a PbrPushConstants,
+    );
+}
+
+impl<'a> ScatterRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_scatter(
+        &mut self,
+        pipeline: &'a ScatterPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a PbrMaterialUniforms,
+        mesh: &'a Mesh,
+        instances: &'a ScatterInstanceBuffer,
+        push_constants: &'a PbrPushConstants,
+    ) {
+        if instances.is_empty() {
+            return;
+        }
+        bind_lit_mesh(
+            self,
+            &pipeline.pipeline,
+            lighting,
+            material.bind_group(),
+            mesh,
+            push_constants,
+        );
+        self.set_vertex_buffer(1, instances.buffer.slice(..));
+        self.draw_indexed(0..mesh.index_count(), 0, 0..instances.len() as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TerrainDescriptor;
+    use galvanic_assert::{matchers::*, *};
+
+    fn uniform_map(value: f32) -> DensityMap {
+        DensityMap::from_fn(2, 2, |_, _| value).unwrap()
+    }
+
+    fn unit_bounds() -> Aabb3<f32> {
+        Aabb3::new(Vector3::repeat(-0.5), Vector3::repeat(0.5))
+    }
+
+    #[test]
+    fn placement() {
+        let desc = ScatterDescriptor {
+            size: Vector2::new(10., 10.),
+            density: 4.,
+            ..ScatterDescriptor::default()
+        };
+        let instances = scatter(&uniform_map(1.), &desc, None);
+        expect_that!(&instances.len(), eq(400));
+        expect_that!(&instances, eq(scatter(&uniform_map(1.), &desc, None)));
+        for instance in &instances {
+            let p = instance.position;
+            expect_that!(p.x >= 0. && p.x < 10. && p.z >= 0. && p.z < 10.);
+            expect_that!(&p.y, eq(0.));
+            expect_that!(instance.scale >= 0.8 && instance.scale < 1.2);
+        }
+        expect_that!(scatter(&uniform_map(0.), &desc, None).is_empty());
+
+        let other_seed = ScatterDescriptor { seed: 1, ..desc };
+        expect_that!(
+            &scatter(&uniform_map(1.), &other_seed, None),
+            not(eq(instances))
+        );
+    }
+
+    #[test]
+    fn density_map() {
+        // Empty on the left half, full on the right half.
+        let map = DensityMap::from_fn(3, 2, |x, _| if x == 2 { 1. } else { 0. }).unwrap();
+        let desc = ScatterDescriptor {
+            size: Vector2::new(20., 20.),
+            ..ScatterDescriptor::default()
+        };
+        let instances = scatter(&map, &desc, None);
+        expect_that!(!instances.is_empty());
+        expect_that!(instances.iter().all(|i| i.position.x > 10.));
+    }
+
+    #[test]
+    fn ground_placement() {
+        let heightfield = Heightfield::from_fn(5, 5, |x, _| x as f32).unwrap();
+        let terrain = Terrain::new(heightfield, &TerrainDescriptor::default());
+        let desc = ScatterDescriptor {
+            origin: Vector2::new(-2., 0.),
+            size: Vector2::new(8., 4.),
+            random_yaw: false,
+            align_to_ground: true,
+            ..ScatterDescriptor::default()
+        };
+        let instances = scatter(&uniform_map(1.), &desc, Some(&terrain));
+        expect_that!(!instances.is_empty());
+        for instance in &instances {
+            let p = instance.position;
+            expect_that!(p.x >= 0. && p.x <= 4.);
+            expect_that!(&p.y, close_to(p.x, 1e-4));
+            let up = instance.rotation * Vector3::y();
+            expect_that!(
+                &(up - terrain.normal_at(p.x, p.z).unwrap()).norm(),
+                close_to(0., 1e-4)
+            );
+        }
+
+        let steep = ScatterDescriptor {
+            max_slope: 0.5,
+            ..desc
+        };
+        expect_that!(scatter(&uniform_map(1.), &steep, Some(&terrain)).is_empty());
+    }
+
+    #[test]
+    fn culling_and_fade() {
+        let instances = (0..10)
+            .map(|i| ScatterInstance {
+                position: Vector3::new(0., 0., -5. - i as f32 * 10.),
+                rotation: Rotation3::identity(),
+                scale: 1.,
+            })
+            .collect();
+        let field = ScatterField::new(
+            instances,
+            &unit_bounds(),
+            &ScatterFieldDescriptor {
+                cell_size: 20.,
+                fade_start: 30.,
+                fade_end: 50.,
+            },
+        );
+        expect_that!(&field.cells().len(), eq(5));
+        expect_that!(&field.cells()[4].instances, eq(8..10));
+        expect_that!(&field.fade(10.), eq(Some(0.)));
+        expect_that!(&field.fade(40.), eq(Some(0.5)));
+        expect_that!(&field.fade(50.), eq(None));
+
+        // Looking down the negative z axis.
+        let projection = HomogeneousMatrix3::new_perspective(1., 1., 0.1, 1000.);
+        let frustum = Frustum::new(&projection);
+        let mut visible = Vec::new();
+        let cells = field.visible_instances(&frustum, &Vector3::zeros(), &mut visible);
+        expect_that!(&cells, eq(3));
+        expect_that!(&visible.len(), eq(5));
+        let fades: Vec<f32> = visible.iter().map(|v| v.fade).collect();
+        expect_that!(&fades, eq(vec![0.75, 0., 0.25, 0., 0.]));
+
+        // Looking away.
+        let behind = Frustum::new(&(projection * HomogeneousMatrix3::new_scaling(-1.)));
+        expect_that!(
+            &field.visible_instances(&behind, &Vector3::zeros(), &mut visible),
+            eq(0)
+        );
+        expect_that!(visible.is_empty());
+    }
+}
@@ -0,0 +1,22 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 1) in vec4 inColor;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uTexture;
+layout(set = 0, binding = 1) uniform sampler uSampler;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 112) float alphaCutoff;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+    vec4 color = inColor * texture(sampler2D(uTexture, uSampler), inTexCoords);
+    if (color.a < pushConstant.alphaCutoff) {
+        discard;
+    }
+    outColor = vec4(convertColor(color.rgb, pushConstant.colorConversion), color.a);
+}
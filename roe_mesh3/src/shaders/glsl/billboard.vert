@@ -0,0 +1,43 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 inPositionRotation;
+layout(location = 1) in vec4 inSizePivot;
+layout(location = 2) in vec3 inAxis;
+layout(location = 3) in vec4 inTextureRect;
+layout(location = 4) in vec4 inColor;
+layout(location = 0) out vec2 outTexCoords;
+layout(location = 1) out vec4 outColor;
+layout(push_constant) uniform PushConstant {
+    mat4 viewProjection;
+    vec4 cameraRight;
+    vec4 cameraUp;
+    vec4 cameraPosition;
+} pushConstant;
+
+// Two triangles, drawn without a vertex buffer.
+const vec2 CORNERS[6] = vec2[](
+    vec2(0., 0.), vec2(1., 0.), vec2(1., 1.), vec2(0., 0.), vec2(1., 1.), vec2(0., 1.));
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec2 offset = (corner - inSizePivot.zw) * inSizePivot.xy;
+    vec3 position = inPositionRotation.xyz;
+    vec3 right;
+    vec3 up;
+    if (dot(inAxis, inAxis) > 0.) {
+        // Rotates only around the axis, e.g. trees or light beams.
+        up = normalize(inAxis);
+        right = cross(up, pushConstant.cameraPosition.xyz - position);
+        right = dot(right, right) > 1e-8 ? normalize(right) : pushConstant.cameraRight.xyz;
+    } else {
+        float s = sin(inPositionRotation.w);
+        float c = cos(inPositionRotation.w);
+        right = c * pushConstant.cameraRight.xyz + s * pushConstant.cameraUp.xyz;
+        up = c * pushConstant.cameraUp.xyz - s * pushConstant.cameraRight.xyz;
+    }
+    vec3 worldPosition = position + right * offset.x + up * offset.y;
+    gl_Position = pushConstant.viewProjection * vec4(worldPosition, 1.);
+    outTexCoords = inTextureRect.xy + vec2(corner.x, 1. - corner.y) * inTextureRect.zw;
+    outColor = inColor;
+}
@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 inColor;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 64) uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+    outColor = vec4(convertColor(inColor.rgb, pushConstant.colorConversion), inColor.a);
+}
@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec4 inColor;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstant {
+    mat4 viewProjection;
+} pushConstant;
+
+void main() {
+    gl_Position = pushConstant.viewProjection * vec4(inPosition, 1.);
+    outColor = inColor;
+}
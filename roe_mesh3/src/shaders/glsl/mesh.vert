@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inTexCoords;
+layout(location = 0) out vec3 outWorldPosition;
+layout(location = 1) out vec3 outNormal;
+layout(location = 2) out vec2 outTexCoords;
+layout(set = 0, binding = 0) uniform Scene {
+    mat4 viewProjection;
+} uScene;
+layout(push_constant) uniform PushConstant {
+    mat4 model;
+} pushConstant;
+
+void main() {
+    vec4 worldPosition = pushConstant.model * vec4(inPosition, 1.);
+    gl_Position = uScene.viewProjection * worldPosition;
+    outWorldPosition = worldPosition.xyz;
+    outNormal = transpose(inverse(mat3(pushConstant.model))) * inNormal;
+    outTexCoords = inTexCoords;
+}
@@ -0,0 +1,322 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Must match the values in pbr.rs.
+#define MAX_DIRECTIONAL_LIGHTS 4
+#define MAX_POINT_LIGHTS 16
+// Must match the value in shadow.rs.
+#define MAX_SHADOW_CASCADES 4
+
+const float PI = 3.14159265359;
+
+struct DirectionalLight {
+    // Direction the light travels in.
+    vec4 direction;
+    // Color multiplied by the intensity.
+    vec4 radiance;
+};
+
+struct PointLight {
+    // Range in w.
+    vec4 positionRange;
+    vec4 radiance;
+};
+
+layout(location = 0) in vec3 inWorldPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inTexCoords;
+#ifdef INSTANCE_FADE
+// Distance fade of the instance, like the LOD fade.
+layout(location = 3) in float inFade;
+#endif
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform Scene {
+    mat4 viewProjection;
+    vec4 cameraPosition;
+    vec4 ambientRadiance;
+    // Directional light count, point light count, tone mapping operator.
+    uvec4 settings;
+    // Exposure in x.
+    vec4 exposure;
+    DirectionalLight directionalLights[MAX_DIRECTIONAL_LIGHTS];
+    PointLight pointLights[MAX_POINT_LIGHTS];
+} uScene;
+#ifdef TERRAIN
+// Splatted layers, weighted by the blend map channels. Must match the layout in terrain.rs.
+layout(set = 1, binding = 0) uniform TerrainMaterial {
+    // Layer texture repetitions per world unit.
+    vec4 layerTiling;
+    vec4 layerRoughness;
+} uTerrain;
+layout(set = 1, binding = 1) uniform texture2D uBlendMap;
+layout(set = 1, binding = 2) uniform texture2D uLayerTex0;
+layout(set = 1, binding = 3) uniform texture2D uLayerTex1;
+layout(set = 1, binding = 4) uniform texture2D uLayerTex2;
+layout(set = 1, binding = 5) uniform texture2D uLayerTex3;
+layout(set = 1, binding = 6) uniform sampler uSampler;
+#else
+layout(set = 1, binding = 0) uniform Material {
+    vec4 baseColorFactor;
+    vec4 emissiveFactor;
+    // Metallic factor, roughness factor, normal scale, occlusion strength.
+    vec4 parameters;
+} uMaterial;
+layout(set = 1, binding = 1) uniform texture2D uBaseColorTex;
+layout(set = 1, binding = 2) uniform texture2D uNormalTex;
+layout(set = 1, binding = 3) uniform texture2D uMetallicRoughnessTex;
+layout(set = 1, binding = 4) uniform texture2D uEmissiveTex;
+layout(set = 1, binding = 5) uniform texture2D uOcclusionTex;
+layout(set = 1, binding = 6) uniform sampler uSampler;
+#endif
+// The first directional light casts shadows.
+layout(set = 2, binding = 0) uniform Shadows {
+    mat4 cascadeViewProjections[MAX_SHADOW_CASCADES];
+    // Cascade count, 0 if shadows are disabled, and PCF radius in texels.
+    uvec4 settings;
+    // Depth bias and texel size of the shadow map.
+    vec4 parameters;
+} uShadows;
+// Cascades side by side, from the nearest one.
+layout(set = 2, binding = 1) uniform texture2D uShadowMap;
+layout(set = 2, binding = 2) uniform samplerShadow uShadowSampler;
+// Image based lighting, replacing the ambient color when enabled.
+layout(set = 3, binding = 0) uniform Environment {
+    // Irradiance spherical harmonics.
+    vec4 irradiance[9];
+    // Intensity, highest mip level of the prefiltered environment, enabled flag.
+    vec4 parameters;
+} uEnvironment;
+layout(set = 3, binding = 1) uniform textureCube uEnvironmentMap;
+layout(set = 3, binding = 2) uniform texture2D uBrdfLut;
+layout(set = 3, binding = 3) uniform sampler uEnvironmentSampler;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 64) float lodFade;
+    // Local ambient radiance in rgb, used instead of the scene one when a is not 0.
+    layout(offset = 80) vec4 ambient;
+    layout(offset = 96) uint colorConversion;
+    // Writes the emitted fraction of the color to the alpha, see gfx::emissive_mask_alpha_blend.
+    layout(offset = 100) uint emissiveMask;
+} pushConstant;
+
+// Threshold in [0, 1) of a 4x4 ordered dithering pattern.
+float ditherThreshold(vec2 fragCoord) {
+    const float bayer[16] = float[16](
+        0., 8., 2., 10.,
+        12., 4., 14., 6.,
+        3., 11., 1., 9.,
+        15., 7., 13., 5.);
+    ivec2 p = ivec2(fragCoord) & 3;
+    return bayer[p.y * 4 + p.x] / 16.;
+}
+
+// A positive fade discards that fraction of the fragments, a negative fade discards the
+// complementary pattern, so that two LOD levels cross-fade without overlapping.
+void applyLodFade() {
+    float fade = pushConstant.lodFade;
+#ifdef INSTANCE_FADE
+    if (inFade > 0.) {
+        fade = inFade;
+    }
+#endif
+    float threshold = ditherThreshold(gl_FragCoord.xy);
+    if ((fade > 0. && threshold < fade) || (fade < 0. && threshold >= 1. + fade)) {
+        discard;
+    }
+}
+
+#include <roe/color_conversion.glsl>
+
+// Values of ToneMapping.
+vec3 toneMap(vec3 color, uint toneMapping) {
+    if (toneMapping == 1u) {
+        return color / (1. + color);
+    } else if (toneMapping == 2u) {
+        return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14),
+            0., 1.);
+    }
+    return color;
+}
+
+// Normal mapping without vertex tangents, from the screen space derivatives.
+vec3 perturbNormal(vec3 n, vec3 tangentNormal) {
+    vec3 dp1 = dFdx(inWorldPosition);
+    vec3 dp2 = dFdy(inWorldPosition);
+    vec2 duv1 = dFdx(inTexCoords);
+    vec2 duv2 = dFdy(inTexCoords);
+    vec3 dp2perp = cross(dp2, n);
+    vec3 dp1perp = cross(n, dp1);
+    vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+    float scale = max(dot(t, t), dot(b, b));
+    if (scale <= 0.) {
+        return n;
+    }
+    float invMax = inversesqrt(scale);
+    return normalize(mat3(t * invMax, b * invMax, n) * tangentNormal);
+}
+
+// Fraction of the first directional light reaching the position. Uses the first cascade
+// containing the position.
+float shadowFactor(vec3 worldPosition) {
+    uint cascadeCount = min(uShadows.settings.x, uint(MAX_SHADOW_CASCADES));
+    for (uint i = 0u; i < cascadeCount; ++i) {
+        vec4 p = uShadows.cascadeViewProjections[i] * vec4(worldPosition, 1.);
+        p.xyz /= p.w;
+        if (any(greaterThan(abs(p.xy), vec2(1.))) || p.z < 0. || p.z > 1.) {
+            continue;
+        }
+        float tileWidth = 1. / float(cascadeCount);
+        vec2 texel = uShadows.parameters.yz;
+        vec2 uv = vec2((p.x * 0.5 + 0.5 + float(i)) * tileWidth, 0.5 - p.y * 0.5);
+        // Taps must not read the neighboring cascades.
+        vec2 minUv = vec2(tileWidth * float(i), 0.) + texel * 0.5;
+        vec2 maxUv = vec2(tileWidth * float(i + 1u), 1.) - texel * 0.5;
+        float depth = p.z - uShadows.parameters.x;
+        int radius = int(uShadows.settings.y);
+        float lit = 0.;
+        for (int x = -radius; x <= radius; ++x) {
+            for (int y = -radius; y <= radius; ++y) {
+                vec2 tap = clamp(uv + vec2(x, y) * texel, minUv, maxUv);
+                lit += texture(sampler2DShadow(uShadowMap, uShadowSampler), vec3(tap, depth));
+            }
+        }
+        float tapCount = float((2 * radius + 1) * (2 * radius + 1));
+        return lit / tapCount;
+    }
+    return 1.;
+}
+
+vec3 irradiance(vec3 n) {
+    return uEnvironment.irradiance[0].rgb * 0.282095
+        + uEnvironment.irradiance[1].rgb * 0.488603 * n.y
+        + uEnvironment.irradiance[2].rgb * 0.488603 * n.z
+        + uEnvironment.irradiance[3].rgb * 0.488603 * n.x
+        + uEnvironment.irradiance[4].rgb * 1.092548 * n.x * n.y
+        + uEnvironment.irradiance[5].rgb * 1.092548 * n.y * n.z
+        + uEnvironment.irradiance[6].rgb * 0.315392 * (3. * n.z * n.z - 1.)
+        + uEnvironment.irradiance[7].rgb * 1.092548 * n.x * n.z
+        + uEnvironment.irradiance[8].rgb * 0.546274 * (n.x * n.x - n.y * n.y);
+}
+
+// Split sum approximation of the environment lighting.
+vec3 shadeEnvironment(vec3 n, vec3 v, vec3 albedo, float metallic, float roughness) {
+    float nDotV = max(dot(n, v), 1e-4);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 f = f0 + (max(vec3(1. - roughness), f0) - f0) * pow(1. - nDotV, 5.);
+    vec3 ambient = pushConstant.ambient.a != 0. ? pushConstant.ambient.rgb : irradiance(n);
+    vec3 diffuse = (1. - f) * (1. - metallic) * albedo * ambient;
+    vec3 prefiltered = textureLod(samplerCube(uEnvironmentMap, uEnvironmentSampler),
+        reflect(-v, n), roughness * uEnvironment.parameters.y).rgb;
+    vec2 brdf = texture(sampler2D(uBrdfLut, uEnvironmentSampler), vec2(nDotV, roughness)).rg;
+    vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+    return (diffuse + specular) * uEnvironment.parameters.x;
+}
+
+float distributionGgx(float nDotH, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = nDotH * nDotH * (alpha2 - 1.) + 1.;
+    return alpha2 / (PI * d * d);
+}
+
+float geometrySmith(float nDotV, float nDotL, float roughness) {
+    float k = (roughness + 1.) * (roughness + 1.) / 8.;
+    return nDotV / (nDotV * (1. - k) + k) * nDotL / (nDotL * (1. - k) + k);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {
+    return f0 + (1. - f0) * pow(1. - cosTheta, 5.);
+}
+
+// Radiance reflected towards the viewer by a light coming from direction l.
+vec3 shade(vec3 n, vec3 v, vec3 l, vec3 radiance, vec3 albedo, float metallic,
+    float roughness) {
+    vec3 h = normalize(v + l);
+    float nDotL = max(dot(n, l), 0.);
+    float nDotV = max(dot(n, v), 1e-4);
+    float nDotH = max(dot(n, h), 0.);
+    vec3 f0 = mix(vec3(0.04), albedo, metallic);
+    vec3 f = fresnelSchlick(max(dot(h, v), 0.), f0);
+    float d = distributionGgx(nDotH, roughness * roughness);
+    float g = geometrySmith(nDotV, nDotL, roughness);
+    vec3 specular = d * g * f / (4. * nDotV * max(nDotL, 1e-4));
+    vec3 diffuse = (1. - f) * (1. - metallic) * albedo / PI;
+    return (diffuse + specular) * radiance * nDotL;
+}
+
+void main() {
+    applyLodFade();
+#ifdef TERRAIN
+    vec4 weights = texture(sampler2D(uBlendMap, uSampler), inTexCoords);
+    weights /= max(weights.r + weights.g + weights.b + weights.a, 1e-4);
+    vec2 worldCoords = inWorldPosition.xz;
+    vec4 baseColor = vec4(0.);
+    baseColor += weights.r
+        * texture(sampler2D(uLayerTex0, uSampler), worldCoords * uTerrain.layerTiling.x);
+    baseColor += weights.g
+        * texture(sampler2D(uLayerTex1, uSampler), worldCoords * uTerrain.layerTiling.y);
+    baseColor += weights.b
+        * texture(sampler2D(uLayerTex2, uSampler), worldCoords * uTerrain.layerTiling.z);
+    baseColor += weights.a
+        * texture(sampler2D(uLayerTex3, uSampler), worldCoords * uTerrain.layerTiling.w);
+    baseColor.a = 1.;
+    float metallic = 0.;
+    float roughness = clamp(dot(weights, uTerrain.layerRoughness), 0.04, 1.);
+    vec3 tangentNormal = vec3(0., 0., 1.);
+    float occlusion = 1.;
+    vec3 emissive = vec3(0.);
+#else
+    vec4 baseColor = uMaterial.baseColorFactor
+        * texture(sampler2D(uBaseColorTex, uSampler), inTexCoords);
+    vec4 metallicRoughness = texture(sampler2D(uMetallicRoughnessTex, uSampler), inTexCoords);
+    float metallic = clamp(uMaterial.parameters.x * metallicRoughness.b, 0., 1.);
+    float roughness = clamp(uMaterial.parameters.y * metallicRoughness.g, 0.04, 1.);
+    vec3 tangentNormal = texture(sampler2D(uNormalTex, uSampler), inTexCoords).xyz * 2. - 1.;
+    tangentNormal.xy *= uMaterial.parameters.z;
+    float occlusion = mix(1., texture(sampler2D(uOcclusionTex, uSampler), inTexCoords).r,
+        uMaterial.parameters.w);
+    vec3 emissive = uMaterial.emissiveFactor.rgb
+        * texture(sampler2D(uEmissiveTex, uSampler), inTexCoords).rgb;
+#endif
+
+    vec3 n = normalize(inNormal);
+    if (!gl_FrontFacing) {
+        n = -n;
+    }
+    n = perturbNormal(n, normalize(tangentNormal));
+    vec3 v = normalize(uScene.cameraPosition.xyz - inWorldPosition);
+
+    vec3 color = vec3(0.);
+    for (uint i = 0u; i < min(uScene.settings.x, uint(MAX_DIRECTIONAL_LIGHTS)); ++i) {
+        DirectionalLight light = uScene.directionalLights[i];
+        vec3 radiance = light.radiance.rgb;
+        if (i == 0u) {
+            radiance *= shadowFactor(inWorldPosition);
+        }
+        color += shade(n, v, -normalize(light.direction.xyz), radiance, baseColor.rgb, metallic,
+            roughness);
+    }
+    for (uint i = 0u; i < min(uScene.settings.y, uint(MAX_POINT_LIGHTS)); ++i) {
+        PointLight light = uScene.pointLights[i];
+        vec3 toLight = light.positionRange.xyz - inWorldPosition;
+        float distance2 = max(dot(toLight, toLight), 1e-4);
+        float range = light.positionRange.w;
+        float window = clamp(1. - pow(distance2 / (range * range), 2.), 0., 1.);
+        vec3 radiance = light.radiance.rgb * window / distance2;
+        color += shade(n, v, normalize(toLight), radiance, baseColor.rgb, metallic, roughness);
+    }
+    if (uEnvironment.parameters.z != 0.) {
+        color += shadeEnvironment(n, v, baseColor.rgb, metallic, roughness) * occlusion;
+    } else {
+        vec3 ambient = pushConstant.ambient.a != 0. ? pushConstant.ambient.rgb
+            : uScene.ambientRadiance.rgb;
+        color += ambient * baseColor.rgb * occlusion;
+    }
+    color += emissive;
+    const vec3 luminanceWeights = vec3(0.2126, 0.7152, 0.0722);
+    float emissiveFraction = clamp(dot(emissive, luminanceWeights)
+        / max(dot(color, luminanceWeights), 1e-4), 0., 1.);
+
+    color = toneMap(color * uScene.exposure.x, uScene.settings.z);
+    float alpha = pushConstant.emissiveMask != 0u ? emissiveFraction : baseColor.a;
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), alpha);
+}
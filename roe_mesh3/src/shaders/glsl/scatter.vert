@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inTexCoords;
+// Columns of the instance model matrix.
+layout(location = 3) in vec4 inModel0;
+layout(location = 4) in vec4 inModel1;
+layout(location = 5) in vec4 inModel2;
+layout(location = 6) in vec4 inModel3;
+layout(location = 7) in float inFade;
+layout(location = 0) out vec3 outWorldPosition;
+layout(location = 1) out vec3 outNormal;
+layout(location = 2) out vec2 outTexCoords;
+layout(location = 3) out float outFade;
+layout(set = 0, binding = 0) uniform Scene {
+    mat4 viewProjection;
+} uScene;
+layout(push_constant) uniform PushConstant {
+    mat4 model;
+} pushConstant;
+
+void main() {
+    mat4 model = pushConstant.model * mat4(inModel0, inModel1, inModel2, inModel3);
+    vec4 worldPosition = model * vec4(inPosition, 1.);
+    gl_Position = uScene.viewProjection * worldPosition;
+    outWorldPosition = worldPosition.xyz;
+    outNormal = transpose(inverse(mat3(model))) * inNormal;
+    outTexCoords = inTexCoords;
+    outFade = inFade;
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(push_constant) uniform PushConstant {
+    // Cascade view projection multiplied by the model transform.
+    mat4 transform;
+} pushConstant;
+
+void main() {
+    gl_Position = pushConstant.transform * vec4(inPosition, 1.);
+}
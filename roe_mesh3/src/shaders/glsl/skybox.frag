@@ -0,0 +1,33 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inDirection;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform textureCube uTexture;
+layout(set = 0, binding = 1) uniform sampler uSampler;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 64) float intensity;
+    float exposure;
+    uint toneMapping;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+// Values of ToneMapping.
+vec3 toneMap(vec3 color, uint toneMapping) {
+    if (toneMapping == 1u) {
+        return color / (1. + color);
+    } else if (toneMapping == 2u) {
+        return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14),
+            0., 1.);
+    }
+    return color;
+}
+
+void main() {
+    vec3 color = textureLod(samplerCube(uTexture, uSampler), inDirection, 0.).rgb;
+    color = toneMap(color * pushConstant.intensity * pushConstant.exposure,
+        pushConstant.toneMapping);
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), 1.);
+}
@@ -0,0 +1,16 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec3 outDirection;
+layout(push_constant) uniform PushConstant {
+    mat4 inverseViewProjection;
+} pushConstant;
+
+// Fullscreen triangle on the far plane.
+void main() {
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2. - 1.;
+    gl_Position = vec4(position, 1., 1.);
+    vec4 near = pushConstant.inverseViewProjection * vec4(position, 0., 1.);
+    vec4 far = pushConstant.inverseViewProjection * vec4(position, 1., 1.);
+    outDirection = far.xyz / far.w - near.xyz / near.w;
+}
@@ -0,0 +1,71 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec2 inTexCoords;
+// Columns of the instance model matrix.
+layout(location = 3) in vec4 inModel0;
+layout(location = 4) in vec4 inModel1;
+layout(location = 5) in vec4 inModel2;
+layout(location = 6) in vec4 inModel3;
+// Time offset, playback speed, first frame and frame count of the played clip.
+layout(location = 7) in vec4 inPlayback;
+layout(location = 0) out vec3 outWorldPosition;
+layout(location = 1) out vec3 outNormal;
+layout(location = 2) out vec2 outTexCoords;
+layout(set = 0, binding = 0) uniform Scene {
+    mat4 viewProjection;
+} uScene;
+// Must match the layout in vat.rs.
+layout(set = 1, binding = 7) uniform VertexAnimation {
+    // Vertex count, frame count, texture width, normals flag.
+    uvec4 settings;
+    // Frame rate, time.
+    vec4 parameters;
+} uAnimation;
+// Frames one after the other, vertex by vertex, wrapping at the texture width.
+layout(set = 1, binding = 8) uniform texture2D uAnimationPositions;
+layout(set = 1, binding = 9) uniform texture2D uAnimationNormals;
+layout(set = 1, binding = 10) uniform sampler uAnimationSampler;
+layout(push_constant) uniform PushConstant {
+    mat4 model;
+} pushConstant;
+
+ivec2 texelCoords(uint frame, uint vertex) {
+    uint i = frame * uAnimation.settings.x + vertex;
+    uint width = uAnimation.settings.z;
+    return ivec2(i % width, i / width);
+}
+
+void main() {
+    uint vertex = uint(gl_VertexIndex);
+    vec3 position = inPosition;
+    vec3 normal = inNormal;
+    uint frameCount = uAnimation.settings.y;
+    if (vertex < uAnimation.settings.x && frameCount > 0u) {
+        uint firstFrame = min(uint(inPlayback.z), frameCount - 1u);
+        uint clipFrames = inPlayback.w > 0. ? uint(inPlayback.w) : frameCount;
+        clipFrames = min(clipFrames, frameCount - firstFrame);
+        float time = uAnimation.parameters.y * inPlayback.y + inPlayback.x;
+        float frame = mod(time * uAnimation.parameters.x, float(clipFrames));
+        uint frame0 = min(uint(frame), clipFrames - 1u);
+        uint frame1 = (frame0 + 1u) % clipFrames;
+        float t = fract(frame);
+        ivec2 c0 = texelCoords(firstFrame + frame0, vertex);
+        ivec2 c1 = texelCoords(firstFrame + frame1, vertex);
+        position = mix(texelFetch(sampler2D(uAnimationPositions, uAnimationSampler), c0, 0),
+            texelFetch(sampler2D(uAnimationPositions, uAnimationSampler), c1, 0), t).xyz;
+        if (uAnimation.settings.w != 0u) {
+            normal = mix(texelFetch(sampler2D(uAnimationNormals, uAnimationSampler), c0, 0),
+                texelFetch(sampler2D(uAnimationNormals, uAnimationSampler), c1, 0), t).xyz;
+        }
+    }
+
+    mat4 model = pushConstant.model * mat4(inModel0, inModel1, inModel2, inModel3);
+    vec4 worldPosition = model * vec4(position, 1.);
+    gl_Position = uScene.viewProjection * worldPosition;
+    outWorldPosition = worldPosition.xyz;
+    outNormal = transpose(inverse(mat3(model))) * normal;
+    outTexCoords = inTexCoords;
+}
@@ -0,0 +1,680 @@
+use super::{Mesh, MeshIndexRange, Vertex};
+
+use roe_graphics::{self as gfx, Canvas};
+use roe_math::{HomogeneousMatrix3, Point3, Vector3, Vector4};
+
+// Must match the value in pbr.frag.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+pub const SHADOW_MAP_FORMAT: gfx::CanvasDepthStencilBufferFormat =
+    gfx::CanvasDepthStencilBufferFormat::Depth32Float;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    #[default]
+    Medium,
+    High,
+    Ultra,
+}
+
+impl ShadowQuality {
+    pub fn settings(self) -> Option<ShadowSettings> {
+        let (resolution, cascade_count, pcf_radius, max_distance) = match self {
+            Self::Off => return None,
+            Self::Low => (1024, 1, 0, 50.),
+            Self::Medium => (1024, 2, 1, 100.),
+            Self::High => (2048, 3, 1, 150.),
+            Self::Ultra => (2048, 4, 2, 200.),
+        };
+        Some(ShadowSettings {
+            resolution,
+            cascade_count,
+            pcf_radius,
+            max_distance,
+            ..ShadowSettings::default()
+        })
+    }
+}
+
+impl From<gfx::ShadowQuality> for ShadowQuality {
+    fn from(quality: gfx::ShadowQuality) -> Self {
+        match quality {
+            gfx::ShadowQuality::Off => Self::Off,
+            gfx::ShadowQuality::Low => Self::Low,
+            gfx::ShadowQuality::Medium => Self::Medium,
+            gfx::ShadowQuality::High => Self::High,
+            gfx::ShadowQuality::Ultra => Self::Ultra,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ShadowSettings {
+    // Size of each cascade, in texels.
+    pub resolution: u32,
+    pub cascade_count: usize,
+    // The shadow map is sampled (2 * radius + 1)^2 times per fragment.
+    pub pcf_radius: u32,
+    // Distance from the camera beyond which nothing is shadowed.
+    pub max_distance: f32,
+    // Blend between uniform (0) and logarithmic (1) cascade splits.
+    pub split_lambda: f32,
+    // Distance behind each cascade where shadow casters are still rendered, e.g. for tall
+    // buildings outside of the view.
+    pub caster_margin: f32,
+    // Subtracted from the depth of the shaded fragments, against shadow acne.
+    pub depth_bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            cascade_count: 2,
+            pcf_radius: 1,
+            max_distance: 100.,
+            split_lambda: 0.75,
+            caster_margin: 50.,
+            depth_bias: 0.0005,
+        }
+    }
+}
+
+// Far distance of each cascade. The first cascade starts at the near distance.
+pub fn cascade_splits(near: f32, far: f32, cascade_count: usize, lambda: f32) -> Vec<f32> {
+    assert!(near > 0. && near < far, "Invalid depth range");
+    (1..=cascade_count)
+        .map(|i| {
+            let t = i as f32 / cascade_count as f32;
+            let uniform = near + (far - near) * t;
+            let logarithmic = near * (far / near).powf(t);
+            uniform + (logarithmic - uniform) * lambda
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ShadowCascade {
+    // Maps world positions to the cascade clip space.
+    pub view_projection: HomogeneousMatrix3<f32>,
+    // Distance from the camera covered by the cascade.
+    pub near: f32,
+    pub far: f32,
+}
+
+// Cascades covering the camera view up to the shadow distance. Each cascade is fitted to a
+// bounding sphere of its slice of the view frustum, and snapped to the shadow map texels, so
+// that the shadows don't shimmer when the camera moves or rotates.
+pub fn shadow_cascades(
+    camera_view_projection: &HomogeneousMatrix3<f32>,
+    camera_near: f32,
+    camera_far: f32,
+    light_direction: &Vector3<f32>,
+    settings: &ShadowSettings,
+) -> Vec<ShadowCascade> {
+    let inverse = camera_view_projection
+        .try_inverse()
+        .expect("The camera view projection must be invertible");
+    let unproject = |x: f32, y: f32, z: f32| {
+        let p = inverse * Vector4::new(x, y, z, 1.);
+        p.xyz() / p.w
+    };
+    let corners: Vec<(Vector3<f32>, Vector3<f32>)> = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+        .iter()
+        .map(|(x, y)| (unproject(*x, *y, 0.), unproject(*x, *y, 1.)))
+        .collect();
+
+    let direction = light_direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    let light_view =
+        HomogeneousMatrix3::look_at_rh(&Point3::origin(), &Point3::from(direction), &up);
+
+    let far = camera_far.min(settings.max_distance);
+    let splits = cascade_splits(
+        camera_near,
+        far,
+        settings.cascade_count,
+        settings.split_lambda,
+    );
+    let mut near = camera_near;
+    let mut cascades = Vec::with_capacity(splits.len());
+    for split in splits {
+        let point_at = |(n, f): &(Vector3<f32>, Vector3<f32>), d: f32| {
+            n + (f - n) * ((d - camera_near) / (camera_far - camera_near))
+        };
+        let slice: Vec<Vector3<f32>> = corners
+            .iter()
+            .flat_map(|c| [point_at(c, near), point_at(c, split)])
+            .collect();
+        let center = slice.iter().sum::<Vector3<f32>>() / slice.len() as f32;
+        let radius = slice
+            .iter()
+            .map(|p| (p - center).norm())
+            .fold(0., f32::max)
+            .max(f32::EPSILON);
+
+        let texel_size = 2. * radius / settings.resolution as f32;
+        let light_center = (light_view * center.push(1.)).xyz();
+        let cx = (light_center.x / texel_size).round() * texel_size;
+        let cy = (light_center.y / texel_size).round() * texel_size;
+        // The light view looks towards negative z.
+        let near_plane = light_center.z + radius + settings.caster_margin;
+        let depth = 2. * radius + settings.caster_margin;
+        #[rustfmt::skip]
+        let projection = HomogeneousMatrix3::new(
+            1. / radius, 0., 0., -cx / radius,
+            0., 1. / radius, 0., -cy / radius,
+            0., 0., -1. / depth, near_plane / depth,
+            0., 0., 0., 1.,
+        );
+        cascades.push(ShadowCascade {
+            view_projection: projection * light_view,
+            near,
+            far: split,
+        });
+        near = split;
+    }
+    cascades
+}
+
+// Layout of the shadow uniform block in pbr.frag.
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct ShadowBlock {
+    cascade_view_projections: [[[f32; 4]; 4]; MAX_SHADOW_CASCADES],
+    settings: [u32; 4],
+    parameters: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for ShadowBlock {}
+
+unsafe impl bytemuck::Pod for ShadowBlock {}
+
+pub(crate) fn shadow_bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Buffer {
+                        ty: gfx::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Depth,
+                        view_dimension: gfx::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: true,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+// Cascaded shadow map of the first directional light of a PbrScene. The cascades are rendered
+// side by side into a single depth buffer, each with its own viewport.
+#[derive(Debug)]
+pub struct ShadowMap {
+    settings: Option<ShadowSettings>,
+    canvas: gfx::CanvasTexture,
+    cascades: Vec<ShadowCascade>,
+    buffer: gfx::Buffer,
+    bind_group: gfx::BindGroup,
+    graphics_settings: Option<gfx::GraphicsSettingsSubscriber>,
+}
+
+impl ShadowMap {
+    // With ShadowQuality::Off, the shadow map can still be bound but nothing is shadowed.
+    pub fn new(instance: &gfx::Instance, quality: ShadowQuality) -> Self {
+        Self::with_settings(instance, quality.settings())
+    }
+
+    pub fn with_settings(instance: &gfx::Instance, settings: Option<ShadowSettings>) -> Self {
+        let size = match &settings {
+            Some(settings) => {
+                assert!(
+                    settings.cascade_count > 0 && settings.cascade_count <= MAX_SHADOW_CASCADES,
+                    "Invalid shadow cascade count"
+                );
+                let width = settings.resolution * settings.cascade_count as u32;
+                assert!(
+                    width <= instance.limits().max_texture_dimension_2d,
+                    "The shadow map is too large"
+                );
+                gfx::CanvasSize::new(width, settings.resolution)
+            }
+            None => gfx::CanvasSize::new(1, 1),
+        };
+        let canvas = gfx::CanvasTexture::new(
+            instance,
+            &gfx::CanvasTextureDescriptor {
+                label: Some(String::from("shadow map")),
+                size,
+                sample_count: 1,
+                color_buffer_descriptor: None,
+                depth_stencil_buffer_format: Some(SHADOW_MAP_FORMAT),
+            },
+        )
+        .expect("Failed to create the shadow map");
+        let buffer = gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: std::mem::size_of::<ShadowBlock>() as gfx::BufferAddress,
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let sampler = gfx::Sampler::new(
+            instance,
+            &gfx::SamplerDescriptor {
+                mag_filter: gfx::FilterMode::Linear,
+                min_filter: gfx::FilterMode::Linear,
+                compare: Some(gfx::CompareFunction::LessEqual),
+                ..gfx::SamplerDescriptor::default()
+            },
+        );
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &shadow_bind_group_layout(instance),
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::TextureView(
+                            canvas.depth_stencil_texture_view().unwrap(),
+                        ),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 2,
+                        resource: gfx::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            },
+        );
+        let shadow_map = Self {
+            settings,
+            canvas,
+            cascades: Vec::new(),
+            buffer,
+            bind_group,
+            graphics_settings: None,
+        };
+        shadow_map.write_uniforms(instance);
+        shadow_map
+    }
+
+    // The quality follows the shadow quality of the settings, starting from the current one.
+    // Returns true if the shadow map was recreated.
+    pub fn subscribe_graphics_settings(
+        &mut self,
+        instance: &gfx::Instance,
+        settings: &gfx::RuntimeGraphicsSettings,
+    ) -> bool {
+        let subscriber = settings.subscribe(gfx::GraphicsSettingsChanges::SHADOWS);
+        let current = *subscriber.settings();
+        self.graphics_settings = Some(subscriber);
+        self.apply_graphics_settings(instance, &current)
+    }
+
+    // Applies the settings changed since the last update, e.g. at the start of a frame. Returns
+    // true if the shadow map was recreated.
+    pub fn update_graphics_settings(&mut self, instance: &gfx::Instance) -> bool {
+        match self.graphics_settings.as_mut().and_then(|s| s.poll()) {
+            Some(update) => self.apply_graphics_settings(instance, &update.settings),
+            None => false,
+        }
+    }
+
+    // Custom shadow settings are replaced by the ones of the quality level.
+    pub fn apply_graphics_settings(
+        &mut self,
+        instance: &gfx::Instance,
+        settings: &gfx::GraphicsSettings,
+    ) -> bool {
+        let shadow_settings = ShadowQuality::from(settings.shadow_quality).settings();
+        if shadow_settings == self.settings {
+            return false;
+        }
+        let graphics_settings = self.graphics_settings.take();
+        *self = Self::with_settings(instance, shadow_settings);
+        self.graphics_settings = graphics_settings;
+        true
+    }
+
+    pub fn settings(&self) -> Option<&ShadowSettings> {
+        self.settings.as_ref()
+    }
+
+    pub fn cascades(&self) -> &[ShadowCascade] {
+        &self.cascades
+    }
+
+    // Fits the cascades to the camera. Must be called before rendering the shadow casters.
+    pub fn update(
+        &mut self,
+        instance: &gfx::Instance,
+        camera_view_projection: &HomogeneousMatrix3<f32>,
+        camera_near: f32,
+        camera_far: f32,
+        light_direction: &Vector3<f32>,
+    ) {
+        if let Some(settings) = &self.settings {
+            self.cascades = shadow_cascades(
+                camera_view_projection,
+                camera_near,
+                camera_far,
+                light_direction,
+                settings,
+            );
+            self.write_uniforms(instance);
+        }
+    }
+
+    // The depth buffer must be cleared to 1 before rendering the casters.
+    pub fn canvas(&mut self) -> &mut gfx::CanvasTexture {
+        &mut self.canvas
+    }
+
+    // Region of the shadow map rendered by a cascade: x, y, width and height.
+    pub fn cascade_viewport(&self, cascade: usize) -> [f32; 4] {
+        let resolution = self.settings.map(|s| s.resolution).unwrap_or(1) as f32;
+        [resolution * cascade as f32, 0., resolution, resolution]
+    }
+
+    fn write_uniforms(&self, instance: &gfx::Instance) {
+        let mut block = ShadowBlock {
+            cascade_view_projections: [[[0.; 4]; 4]; MAX_SHADOW_CASCADES],
+            settings: [0; 4],
+            parameters: [0.; 4],
+        };
+        if let Some(settings) = &self.settings {
+            for (i, cascade) in self.cascades.iter().enumerate() {
+                block.cascade_view_projections[i] = cascade.view_projection.into();
+            }
+            let size = self.canvas.canvas_size();
+            block.settings = [self.cascades.len() as u32, settings.pcf_radius, 0, 0];
+            block.parameters = [
+                settings.depth_bias,
+                1. / size.width() as f32,
+                1. / size.height() as f32,
+                0.,
+            ];
+        }
+        instance.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&block));
+    }
+
+    pub(crate) fn bind_group(&self) -> &gfx::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ShadowCasterPushConstants {
+    transform: HomogeneousMatrix3<f32>,
+}
+
+impl ShadowCasterPushConstants {
+    pub fn new(cascade: &ShadowCascade, model: &HomogeneousMatrix3<f32>) -> Self {
+        Self {
+            transform: cascade.view_projection * model,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for ShadowCasterPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            transform: HomogeneousMatrix3::zeros(),
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for ShadowCasterPushConstants {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShadowCasterPipelineDescriptor {
+    pub label: Option<String>,
+    // Depth bias proportional to the slope of the casters, against shadow acne.
+    pub constant_depth_bias: i32,
+    pub slope_depth_bias: f32,
+    // None for casters with open or double sided geometry.
+    pub cull_mode: Option<gfx::Face>,
+}
+
+impl Default for ShadowCasterPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            constant_depth_bias: 2,
+            slope_depth_bias: 2.,
+            cull_mode: Some(gfx::Face::Back),
+        }
+    }
+}
+
+// Depth only pipeline rendering meshes into a shadow map.
+#[derive(Debug)]
+pub struct ShadowCasterPipeline {
+    pipeline: gfx::RenderPipeline,
+}
+
+impl ShadowCasterPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &ShadowCasterPipelineDescriptor) -> Self {
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::VERTEX,
+                    range: 0..std::mem::size_of::<ShadowCasterPushConstants>() as u32,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/shadow_caster.vert.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[gfx::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as gfx::BufferAddress,
+                        step_mode: gfx::VertexStepMode::Vertex,
+                        attributes: &[gfx::VertexAttribute {
+                            format: gfx::VertexFormat::Float32x3,
+                            offset: 0,
+                            shader_location: 0,
+                        }],
+                    }],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: desc.cull_mode,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(gfx::DepthStencilState {
+                    format: gfx::TextureFormat::from(SHADOW_MAP_FORMAT),
+                    depth_write_enabled: true,
+                    depth_compare: gfx::CompareFunction::Less,
+                    stencil: gfx::StencilState::default(),
+                    bias: gfx::DepthBiasState {
+                        constant: desc.constant_depth_bias,
+                        slope_scale: desc.slope_depth_bias,
+                        clamp: 0.,
+                    },
+                }),
+                multisample: gfx::MultisampleState::default(),
+                fragment: None,
+            },
+        );
+        Self { pipeline }
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: 1,
+            color_buffer_formats: Vec::new(),
+            depth_stencil_buffer_format: Some(SHADOW_MAP_FORMAT),
+        }
+    }
+}
+
+pub trait ShadowCasterRenderer<'a> {
+    fn draw_shadow_caster(
+        &mut self,
+        pipeline: &'a ShadowCasterPipeline,
+        mesh: &'a Mesh,
+        push_constants: &'a ShadowCasterPushConstants,
+        index_range: MeshIndexRange,
+    );
+}
+
+impl<'a> ShadowCasterRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_shadow_caster(
+        &mut self,
+        pipeline: &'a ShadowCasterPipeline,
+        mesh: &'a Mesh,
+        push_constants: &'a ShadowCasterPushConstants,
+        index_range: MeshIndexRange,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
+        self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.draw_indexed(index_range, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn camera(position: Vector3<f32>) -> HomogeneousMatrix3<f32> {
+        let projection = HomogeneousMatrix3::new_perspective(16. / 9., 1., 0.1, 500.);
+        let view = HomogeneousMatrix3::look_at_rh(
+            &Point3::from(position),
+            &Point3::from(position + Vector3::new(0., -0.2, -1.)),
+            &Vector3::y(),
+        );
+        projection * view
+    }
+
+    #[test]
+    fn splits() {
+        let splits = cascade_splits(0.1, 100., 4, 0.75);
+        expect_that!(&splits.len(), eq(4));
+        expect_that!(&splits[3], close_to(100., 1e-3));
+        expect_that!(splits.windows(2).all(|w| w[0] < w[1]));
+        expect_that!(&cascade_splits(1., 9., 2, 0.)[0], close_to(5., 1e-5));
+        expect_that!(&cascade_splits(1., 9., 2, 1.)[0], close_to(3., 1e-5));
+    }
+
+    #[test]
+    fn graphics_settings_quality() {
+        expect_that!(
+            &ShadowQuality::from(gfx::ShadowQuality::Off).settings(),
+            eq(None)
+        );
+        let high = gfx::GraphicsSettings::from_preset(gfx::QualityPreset::High);
+        expect_that!(
+            &ShadowQuality::from(high.shadow_quality),
+            eq(ShadowQuality::High)
+        );
+    }
+
+    #[test]
+    fn cascades_contain_their_slice() {
+        let settings = ShadowQuality::Ultra.settings().unwrap();
+        let view_projection = camera(Vector3::new(3., 2., 1.));
+        let light = Vector3::new(0.3, -1., 0.2);
+        let cascades = shadow_cascades(&view_projection, 0.1, 500., &light, &settings);
+        expect_that!(&cascades.len(), eq(4));
+        expect_that!(&cascades[3].far, close_to(settings.max_distance, 1e-3));
+
+        // A point on the view axis at the middle of each slice.
+        let forward = Vector3::new(0., -0.2, -1.).normalize();
+        for cascade in cascades.iter() {
+            let distance = (cascade.near + cascade.far) / 2.;
+            let p = Vector3::new(3., 2., 1.) + forward * distance;
+            let clip = cascade.view_projection * p.push(1.);
+            expect_that!(clip.x.abs() <= 1. && clip.y.abs() <= 1.);
+            expect_that!(clip.z >= 0. && clip.z <= 1.);
+            // Casters towards the light are rendered.
+            let caster = cascade.view_projection * (p - light.normalize() * 20.).push(1.);
+            expect_that!(caster.z >= 0.);
+        }
+    }
+
+    #[test]
+    fn cascades_are_snapped_to_texels() {
+        let settings = ShadowQuality::Low.settings().unwrap();
+        let light = Vector3::new(0., -1., -1.);
+        let a = shadow_cascades(
+            &camera(Vector3::new(0., 0., 0.)),
+            0.1,
+            500.,
+            &light,
+            &settings,
+        );
+        let b = shadow_cascades(
+            &camera(Vector3::new(0.013, 0., 0.)),
+            0.1,
+            500.,
+            &light,
+            &settings,
+        );
+        // The same world point moves by a whole number of texels.
+        let p = Vector3::new(1., 0., -10.).push(1.);
+        let texels = (b[0].view_projection * p - a[0].view_projection * p).x
+            * settings.resolution as f32
+            / 2.;
+        expect_that!(&texels, close_to(texels.round(), 1e-2));
+    }
+}
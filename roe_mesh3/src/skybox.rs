@@ -0,0 +1,288 @@
+use super::{Cubemap, PbrScene};
+
+use roe_graphics as gfx;
+use roe_math::HomogeneousMatrix3;
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Float { filterable: true },
+                        view_dimension: gfx::TextureViewDimension::Cube,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+#[derive(Debug)]
+pub struct SkyboxUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl SkyboxUniformConstants {
+    pub fn new(instance: &gfx::Instance, cubemap: &Cubemap, sampler: &gfx::Sampler) -> Self {
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &bind_group_layout(instance),
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(cubemap.view()),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SkyboxPushConstants {
+    inverse_view_projection: HomogeneousMatrix3<f32>,
+    intensity: f32,
+    exposure: f32,
+    tone_mapping: u32,
+}
+
+impl SkyboxPushConstants {
+    // Uses the camera, exposure and tone mapping of the scene, so that the sky matches the
+    // meshes lit by the same environment.
+    pub fn new(scene: &PbrScene, intensity: f32) -> Self {
+        Self {
+            inverse_view_projection: scene
+                .view_projection
+                .try_inverse()
+                .unwrap_or_else(HomogeneousMatrix3::identity),
+            intensity,
+            exposure: scene.exposure,
+            tone_mapping: scene.tone_mapping as u32,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for SkyboxPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            inverse_view_projection: HomogeneousMatrix3::zeros(),
+            intensity: 0.,
+            exposure: 0.,
+            tone_mapping: 0,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for SkyboxPushConstants {}
+
+const PC_MATRIX_SIZE: u32 = std::mem::size_of::<HomogeneousMatrix3<f32>>() as u32;
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<SkyboxPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkyboxPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for SkyboxPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat::Depth32Float,
+            sample_count: 1,
+        }
+    }
+}
+
+// Draws a cubemap on the far plane, behind everything else. Meant to be drawn after the opaque
+// meshes, in the same pass, so that only the uncovered pixels are shaded.
+#[derive(Debug)]
+pub struct SkyboxPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    depth_stencil_buffer_format: gfx::CanvasDepthStencilBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl SkyboxPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &SkyboxPipelineDescriptor) -> Self {
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout(instance)],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_MATRIX_SIZE,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_MATRIX_SIZE..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/skybox.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/skybox.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                // Passes only where the depth buffer still has the clear value.
+                depth_stencil: Some(gfx::DepthStencilState {
+                    format: gfx::TextureFormat::from(desc.depth_stencil_buffer_format),
+                    depth_write_enabled: false,
+                    depth_compare: gfx::CompareFunction::LessEqual,
+                    stencil: gfx::StencilState::default(),
+                    bias: gfx::DepthBiasState::default(),
+                }),
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: None,
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: Some(self.depth_stencil_buffer_format),
+        }
+    }
+}
+
+pub trait SkyboxRenderer<'a> {
+    fn draw_skybox(
+        &mut self,
+        pipeline: &'a SkyboxPipeline,
+        uniform_constants: &'a SkyboxUniformConstants,
+        push_constants: &'a SkyboxPushConstants,
+    );
+}
+
+impl<'a> SkyboxRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_skybox(
+        &mut self,
+        pipeline: &'a SkyboxPipeline,
+        uniform_constants: &'a SkyboxUniformConstants,
+        push_constants: &'a SkyboxPushConstants,
+    ) {
+        let bytes = bytemuck::bytes_of(push_constants);
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            &bytes[..PC_MATRIX_SIZE as usize],
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_MATRIX_SIZE,
+            &bytes[PC_MATRIX_SIZE as usize..],
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToneMapping;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn push_constants_layout() {
+        expect_that!(&PC_CONVERSION_MEM_OFFSET, eq(76));
+        let scene = PbrScene {
+            exposure: 2.,
+            tone_mapping: ToneMapping::Reinhard,
+            ..PbrScene::default()
+        };
+        let pc = SkyboxPushConstants::new(&scene, 0.5);
+        let bytes = bytemuck::bytes_of(&pc);
+        expect_that!(bytes[64..68] == 0.5f32.to_le_bytes());
+        expect_that!(bytes[68..72] == 2f32.to_le_bytes());
+        expect_that!(bytes[72..76] == 1u32.to_le_bytes());
+    }
+}
@@ -0,0 +1,651 @@
+use super::{
+    bind_lit_mesh, material_bind_group_layout, texture_binding, vertex_buffer_layout, Mesh,
+    MeshData, MeshIndex, PbrDefaultTextures, PbrLighting, PbrPipeline, PbrPipelineDescriptor,
+    PbrPipelineShaders, PbrPushConstants, Vertex,
+};
+
+use roe_graphics as gfx;
+use roe_math::{Aabb3, Vector2, Vector3};
+
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum TerrainError {
+    ImageError(image::ImageError),
+    // Heightfields need at least 2x2 samples.
+    InvalidHeightfield(String),
+}
+
+impl std::fmt::Display for TerrainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ImageError(e) => write!(f, "Image error ({})", e),
+            Self::InvalidHeightfield(description) => {
+                write!(f, "Invalid heightfield ({})", description)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TerrainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ImageError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for TerrainError {
+    fn from(e: image::ImageError) -> Self {
+        Self::ImageError(e)
+    }
+}
+
+// Grid of height samples, row by row. Columns map to the x axis and rows to the z axis.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Heightfield {
+    width: u32,
+    depth: u32,
+    heights: Vec<f32>,
+}
+
+impl Heightfield {
+    pub fn new(width: u32, depth: u32, heights: Vec<f32>) -> Result<Self, TerrainError> {
+        if width < 2 || depth < 2 {
+            return Err(TerrainError::InvalidHeightfield(format!(
+                "{}x{} samples",
+                width, depth
+            )));
+        }
+        if heights.len() != (width * depth) as usize {
+            return Err(TerrainError::InvalidHeightfield(format!(
+                "{} heights for {}x{} samples",
+                heights.len(),
+                width,
+                depth
+            )));
+        }
+        Ok(Self {
+            width,
+            depth,
+            heights,
+        })
+    }
+
+    pub fn from_fn<F: FnMut(u32, u32) -> f32>(
+        width: u32,
+        depth: u32,
+        mut f: F,
+    ) -> Result<Self, TerrainError> {
+        let heights = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| (x, z)))
+            .map(|(x, z)| f(x, z))
+            .collect();
+        Self::new(width, depth, heights)
+    }
+
+    // Grayscale heights from 0 to 1. 16 bit images keep their precision.
+    pub fn from_image(image: &image::DynamicImage) -> Result<Self, TerrainError> {
+        let color = image.color();
+        if color.bytes_per_pixel() > color.channel_count() {
+            let luma = image.to_luma16();
+            let heights = luma
+                .pixels()
+                .map(|p| p.0[0] as f32 / u16::MAX as f32)
+                .collect();
+            Self::new(luma.width(), luma.height(), heights)
+        } else {
+            let luma = image.to_luma8();
+            let heights = luma
+                .pixels()
+                .map(|p| p.0[0] as f32 / u8::MAX as f32)
+                .collect();
+            Self::new(luma.width(), luma.height(), heights)
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TerrainError> {
+        Self::from_image(&image::open(path)?)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    // Coordinates are clamped to the grid.
+    pub fn sample(&self, x: i64, z: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let z = z.clamp(0, self.depth as i64 - 1) as usize;
+        self.heights[z * self.width as usize + x]
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TerrainDescriptor {
+    // Horizontal distance between samples.
+    pub cell_size: f32,
+    // Height of a sample with value 1.
+    pub height_scale: f32,
+    // Cells per chunk side.
+    pub chunk_cells: u32,
+}
+
+impl Default for TerrainDescriptor {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.,
+            height_scale: 1.,
+            chunk_cells: 32,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TerrainContact {
+    // Surface point below the sphere center.
+    pub point: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    // Distance the sphere must be moved along the normal to stop intersecting.
+    pub depth: f32,
+}
+
+// Heightfield terrain in local space, with the first sample at the origin and extending towards
+// positive x and z. The queries match the triangles of the generated meshes, and return None
+// outside of the terrain.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Terrain {
+    heightfield: Heightfield,
+    cell_size: f32,
+    height_scale: f32,
+    chunk_cells: u32,
+}
+
+impl Terrain {
+    pub fn new(heightfield: Heightfield, desc: &TerrainDescriptor) -> Self {
+        Self {
+            heightfield,
+            cell_size: desc.cell_size,
+            height_scale: desc.height_scale,
+            chunk_cells: desc.chunk_cells.max(1),
+        }
+    }
+
+    pub fn heightfield(&self) -> &Heightfield {
+        &self.heightfield
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn height_scale(&self) -> f32 {
+        self.height_scale
+    }
+
+    // Extent along x and z.
+    pub fn size(&self) -> Vector2<f32> {
+        Vector2::new(
+            (self.heightfield.width - 1) as f32,
+            (self.heightfield.depth - 1) as f32,
+        ) * self.cell_size
+    }
+
+    fn position(&self, x: u32, z: u32) -> Vector3<f32> {
+        Vector3::new(
+            x as f32 * self.cell_size,
+            self.heightfield.sample(x as i64, z as i64) * self.height_scale,
+            z as f32 * self.cell_size,
+        )
+    }
+
+    // Smooth vertex normal, from the neighboring samples.
+    pub fn sample_normal(&self, x: u32, z: u32) -> Vector3<f32> {
+        let (x, z) = (x as i64, z as i64);
+        let h = |x, z| self.heightfield.sample(x, z) * self.height_scale;
+        Vector3::new(
+            h(x - 1, z) - h(x + 1, z),
+            2. * self.cell_size,
+            h(x, z - 1) - h(x, z + 1),
+        )
+        .normalize()
+    }
+
+    // Cell coordinates and position in the cell, from 0 to 1.
+    fn cell(&self, x: f32, z: f32) -> Option<(u32, u32, f32, f32)> {
+        let (gx, gz) = (x / self.cell_size, z / self.cell_size);
+        let (max_x, max_z) = (
+            (self.heightfield.width - 1) as f32,
+            (self.heightfield.depth - 1) as f32,
+        );
+        if !(0. ..=max_x).contains(&gx) || !(0. ..=max_z).contains(&gz) {
+            return None;
+        }
+        let cx = (gx.floor() as u32).min(self.heightfield.width - 2);
+        let cz = (gz.floor() as u32).min(self.heightfield.depth - 2);
+        Some((cx, cz, gx - cx as f32, gz - cz as f32))
+    }
+
+    // Vertices of the triangle containing the point. Cells are split along the diagonal from
+    // their first to their last corner.
+    fn triangle(&self, x: f32, z: f32) -> Option<[Vector3<f32>; 3]> {
+        let (cx, cz, fx, fz) = self.cell(x, z)?;
+        let p = |dx, dz| self.position(cx + dx, cz + dz);
+        Some(if fx >= fz {
+            [p(0, 0), p(1, 1), p(1, 0)]
+        } else {
+            [p(0, 0), p(0, 1), p(1, 1)]
+        })
+    }
+
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let [a, b, c] = self.triangle(x, z)?;
+        let normal = (b - a).cross(&(c - a));
+        // Plane through the triangle, solved for y.
+        Some(a.y - (normal.x * (x - a.x) + normal.z * (z - a.z)) / normal.y)
+    }
+
+    // Normal of the triangle containing the point.
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<Vector3<f32>> {
+        let [a, b, c] = self.triangle(x, z)?;
+        Some((b - a).cross(&(c - a)).normalize())
+    }
+
+    // Angle between the surface and the horizontal plane, in radians.
+    pub fn slope_at(&self, x: f32, z: f32) -> Option<f32> {
+        self.normal_at(x, z).map(|n| n.y.clamp(-1., 1.).acos())
+    }
+
+    // E.g. to keep characters off steep cliffs.
+    pub fn is_walkable(&self, x: f32, z: f32, max_slope: f32) -> bool {
+        self.slope_at(x, z).is_some_and(|slope| slope <= max_slope)
+    }
+
+    // Approximates the contact with the plane of the triangle below the sphere center.
+    pub fn sphere_contact(&self, center: &Vector3<f32>, radius: f32) -> Option<TerrainContact> {
+        let height = self.height_at(center.x, center.z)?;
+        let normal = self.normal_at(center.x, center.z)?;
+        let point = Vector3::new(center.x, height, center.z);
+        let depth = radius - (center - point).dot(&normal);
+        if depth > 0. {
+            Some(TerrainContact {
+                point,
+                normal,
+                depth,
+            })
+        } else {
+            None
+        }
+    }
+
+    // Along x and z.
+    pub fn chunk_counts(&self) -> (u32, u32) {
+        let count = |samples: u32| (samples - 1).div_ceil(self.chunk_cells);
+        (count(self.heightfield.width), count(self.heightfield.depth))
+    }
+
+    // Sample ranges covered by a chunk, including the shared border samples.
+    fn chunk_samples(&self, x: u32, z: u32) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let range = |chunk: u32, samples: u32| {
+            let begin = chunk * self.chunk_cells;
+            begin..(begin + self.chunk_cells + 1).min(samples)
+        };
+        (
+            range(x, self.heightfield.width),
+            range(z, self.heightfield.depth),
+        )
+    }
+
+    pub fn chunk_bounds(&self, x: u32, z: u32) -> Aabb3<f32> {
+        let (xs, zs) = self.chunk_samples(x, z);
+        let mut min_height = f32::INFINITY;
+        let mut max_height = f32::NEG_INFINITY;
+        for sz in zs.clone() {
+            for sx in xs.clone() {
+                let h = self.position(sx, sz).y;
+                min_height = min_height.min(h);
+                max_height = max_height.max(h);
+            }
+        }
+        Aabb3::new(
+            Vector3::new(
+                xs.start as f32 * self.cell_size,
+                min_height,
+                zs.start as f32 * self.cell_size,
+            ),
+            Vector3::new(
+                (xs.end - 1) as f32 * self.cell_size,
+                max_height,
+                (zs.end - 1) as f32 * self.cell_size,
+            ),
+        )
+    }
+
+    // Texture coordinates span the whole terrain, to sample the blend map.
+    pub fn chunk_mesh_data(&self, x: u32, z: u32) -> MeshData {
+        let (xs, zs) = self.chunk_samples(x, z);
+        let uv_scale = Vector2::new(
+            1. / (self.heightfield.width - 1) as f32,
+            1. / (self.heightfield.depth - 1) as f32,
+        );
+        let mut vertices = Vec::with_capacity(xs.len() * zs.len());
+        for sz in zs.clone() {
+            for sx in xs.clone() {
+                vertices.push(Vertex::new(
+                    self.position(sx, sz).into(),
+                    self.sample_normal(sx, sz).into(),
+                    [sx as f32 * uv_scale.x, sz as f32 * uv_scale.y],
+                ));
+            }
+        }
+        let row = xs.len() as MeshIndex;
+        let mut indices = Vec::with_capacity((xs.len() - 1) * (zs.len() - 1) * 6);
+        for cz in 0..zs.len() as MeshIndex - 1 {
+            for cx in 0..row - 1 {
+                let i = cz * row + cx;
+                indices.extend_from_slice(&[i, i + row + 1, i + 1, i, i + row, i + row + 1]);
+            }
+        }
+        MeshData::new(vertices, indices)
+    }
+
+    pub fn create_chunks(&self, instance: &gfx::Instance) -> Vec<TerrainChunk> {
+        let (count_x, count_z) = self.chunk_counts();
+        (0..count_z)
+            .flat_map(|z| (0..count_x).map(move |x| (x, z)))
+            .map(|(x, z)| TerrainChunk {
+                coordinates: (x, z),
+                bounds: self.chunk_bounds(x, z),
+                mesh: self.chunk_mesh_data(x, z).create_mesh(instance),
+            })
+            .collect()
+    }
+}
+
+// Bounds are in the terrain local space, transform them by the terrain model matrix to cull
+// chunks, see InstanceBounds.
+#[derive(Debug)]
+pub struct TerrainChunk {
+    pub coordinates: (u32, u32),
+    pub bounds: Aabb3<f32>,
+    pub mesh: Mesh,
+}
+
+pub const TERRAIN_LAYER_COUNT: usize = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TerrainLayer {
+    // Texture repetitions per world unit.
+    pub tiling: f32,
+    pub roughness: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            tiling: 0.25,
+            roughness: 0.9,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct TerrainMaterial {
+    pub layers: [TerrainLayer; TERRAIN_LAYER_COUNT],
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct TerrainMaterialBlock {
+    layer_tiling: [f32; 4],
+    layer_roughness: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for TerrainMaterialBlock {}
+
+unsafe impl bytemuck::Pod for TerrainMaterialBlock {}
+
+impl TerrainMaterialBlock {
+    fn new(material: &TerrainMaterial) -> Self {
+        Self {
+            layer_tiling: material.layers.map(|l| l.tiling),
+            layer_roughness: material.layers.map(|l| l.roughness),
+        }
+    }
+}
+
+// The blend map covers the whole terrain, its red, green, blue and alpha channels weight the
+// layers, and must have a Unorm format. Layer textures store colors, with the workflow texture
+// format. The sampler should repeat, for the tiling.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TerrainMaterialTextures<'a> {
+    pub blend_map: Option<&'a gfx::TextureView>,
+    pub layers: [Option<&'a gfx::TextureView>; TERRAIN_LAYER_COUNT],
+}
+
+#[derive(Debug)]
+pub struct TerrainMaterialUniforms {
+    buffer: gfx::Buffer,
+    bind_group: gfx::BindGroup,
+}
+
+impl TerrainMaterialUniforms {
+    pub fn new(
+        instance: &gfx::Instance,
+        material: &TerrainMaterial,
+        textures: &TerrainMaterialTextures,
+        defaults: &PbrDefaultTextures,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        let buffer = gfx::Buffer::init(
+            instance,
+            &gfx::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::bytes_of(&TerrainMaterialBlock::new(material)),
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+            },
+        );
+        // Without a blend map only the first layer is visible.
+        let blend_map = texture_binding(1, textures.blend_map, &defaults.red_linear);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &material_bind_group_layout(instance),
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    blend_map,
+                    texture_binding(2, textures.layers[0], &defaults.white),
+                    texture_binding(3, textures.layers[1], &defaults.white),
+                    texture_binding(4, textures.layers[2], &defaults.white),
+                    texture_binding(5, textures.layers[3], &defaults.white),
+                    gfx::BindGroupEntry {
+                        binding: 6,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                ],
+            },
+        );
+        Self { buffer, bind_group }
+    }
+
+    // The textures can't be changed, only the layer parameters.
+    pub fn update(&self, instance: &gfx::Instance, material: &TerrainMaterial) {
+        instance.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::bytes_of(&TerrainMaterialBlock::new(material)),
+        );
+    }
+}
+
+// Lit like the PBR meshes, with the same scene, shadow and environment bindings.
+#[derive(Debug)]
+pub struct TerrainPipeline {
+    pipeline: PbrPipeline,
+}
+
+impl TerrainPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &PbrPipelineDescriptor) -> Self {
+        Self {
+            pipeline: PbrPipeline::with_shaders(
+                instance,
+                desc,
+                PbrPipelineShaders {
+                    vertex_shader: gfx::include_spirv!("shaders/gen/spirv/mesh.vert.spv"),
+                    vertex_buffers: &[vertex_buffer_layout()],
+                    material_bind_group_layout: material_bind_group_layout(instance),
+                    fragment_shader: gfx::include_spirv!("shaders/gen/spirv/pbr.frag.1.spv"),
+                },
+            ),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.pipeline.color_conversion()
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        self.pipeline.render_pass_requirements()
+    }
+}
+
+pub trait TerrainRenderer<'a> {
+    fn draw_terrain_chunk(
+        &mut self,
+        pipeline: &'a TerrainPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a TerrainMaterialUniforms,
+        chunk: &'a TerrainChunk,
+        push_constants: &'a PbrPushConstants,
+    );
+}
+
+impl<'a> TerrainRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_terrain_chunk(
+        &mut self,
+        pipeline: &'a TerrainPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a TerrainMaterialUniforms,
+        chunk: &'a TerrainChunk,
+        push_constants: &'a PbrPushConstants,
+    ) {
+        bind_lit_mesh(
+            self,
+            &pipeline.pipeline,
+            lighting,
+            &material.bind_group,
+            &chunk.mesh,
+            push_constants,
+        );
+        self.draw_indexed(0..chunk.mesh.index_count(), 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    // A ramp rising by 1 every 2 cells along x.
+    fn ramp() -> Terrain {
+        Terrain::new(
+            Heightfield::from_fn(5, 3, |x, _| x as f32 / 4.).unwrap(),
+            &TerrainDescriptor {
+                cell_size: 2.,
+                height_scale: 4.,
+                chunk_cells: 3,
+            },
+        )
+    }
+
+    #[test]
+    fn invalid_heightfields() {
+        expect_that!(matches!(
+            Heightfield::new(1, 4, vec![0.; 4]),
+            Err(TerrainError::InvalidHeightfield(_))
+        ));
+        expect_that!(matches!(
+            Heightfield::new(2, 2, vec![0.; 3]),
+            Err(TerrainError::InvalidHeightfield(_))
+        ));
+    }
+
+    #[test]
+    fn image_heightfield() {
+        let image = image::GrayImage::from_raw(2, 2, vec![0, 255, 51, 0]).unwrap();
+        let heightfield = Heightfield::from_image(&image::DynamicImage::ImageLuma8(image)).unwrap();
+        expect_that!(&heightfield.sample(1, 0), close_to(1., 1e-6));
+        expect_that!(&heightfield.sample(0, 1), close_to(0.2, 1e-6));
+        expect_that!(&heightfield.sample(5, -3), close_to(1., 1e-6));
+    }
+
+    #[test]
+    fn queries() {
+        let terrain = ramp();
+        expect_that!(&terrain.size(), eq(Vector2::new(8., 4.)));
+        expect_that!(&terrain.height_at(3., 1.).unwrap(), close_to(1.5, 1e-5));
+        expect_that!(&terrain.height_at(8., 4.).unwrap(), close_to(4., 1e-5));
+        expect_that!(&terrain.height_at(-0.1, 1.), eq(None));
+        expect_that!(&terrain.height_at(1., 4.1), eq(None));
+
+        let normal = terrain.normal_at(5., 3.).unwrap();
+        let expected = Vector3::new(-1., 2., 0.).normalize();
+        expect_that!((normal - expected).norm() < 1e-5);
+        expect_that!(
+            &terrain.slope_at(5., 3.).unwrap(),
+            close_to(0.5f32.atan(), 1e-5)
+        );
+        expect_that!(terrain.is_walkable(5., 3., 0.5));
+        expect_that!(!terrain.is_walkable(5., 3., 0.4));
+
+        let contact = terrain
+            .sphere_contact(&Vector3::new(4., 2.5, 2.), 1.)
+            .unwrap();
+        expect_that!(&contact.point, eq(Vector3::new(4., 2., 2.)));
+        expect_that!(&contact.depth, close_to(1. - 0.5 * expected.y, 1e-5));
+        expect_that!(
+            &terrain.sphere_contact(&Vector3::new(4., 4., 2.), 1.),
+            eq(None)
+        );
+    }
+
+    #[test]
+    fn chunks() {
+        let terrain = ramp();
+        expect_that!(&terrain.chunk_counts(), eq((2, 1)));
+
+        let data = terrain.chunk_mesh_data(1, 0);
+        expect_that!(&data.vertices.len(), eq(6));
+        expect_that!(&data.indices.len(), eq(12));
+        let (position, normal, texture_coordinates) = (
+            data.vertices[0].position,
+            data.vertices[0].normal,
+            data.vertices[0].texture_coordinates,
+        );
+        expect_that!(&position, eq([6., 3., 0.]));
+        expect_that!(&texture_coordinates, eq([0.75, 0.]));
+        let normal = Vector3::from(normal);
+        expect_that!((normal - Vector3::new(-1., 2., 0.).normalize()).norm() < 1e-5);
+        // Triangles face up.
+        for t in data.indices.chunks(3) {
+            let p = |i: MeshIndex| Vector3::from(data.vertices[i as usize].position);
+            expect_that!((p(t[1]) - p(t[0])).cross(&(p(t[2]) - p(t[0]))).y > 0.);
+        }
+
+        let bounds = terrain.chunk_bounds(0, 0);
+        expect_that!(&bounds.min, eq(Vector3::new(0., 0., 0.)));
+        expect_that!(&bounds.max, eq(Vector3::new(6., 3., 4.)));
+    }
+}
@@ -0,0 +1,727 @@
+use super::{
+    bind_lit_mesh, material_bind_group_layout_entries, vertex_buffer_layout, Mesh,
+    PbrDefaultTextures, PbrLighting, PbrMaterial, PbrMaterialTextures, PbrMaterialUniforms,
+    PbrPipeline, PbrPipelineDescriptor, PbrPipelineShaders, PbrPushConstants,
+    MATERIAL_BINDING_COUNT,
+};
+
+use roe_graphics as gfx;
+use roe_math::{Aabb3, HomogeneousMatrix3, Vector3};
+
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum VatError {
+    ImageError(image::ImageError),
+    InvalidData(String),
+}
+
+impl std::fmt::Display for VatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ImageError(e) => write!(f, "Image error ({})", e),
+            Self::InvalidData(description) => {
+                write!(f, "Invalid vertex animation ({})", description)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ImageError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for VatError {
+    fn from(e: image::ImageError) -> Self {
+        Self::ImageError(e)
+    }
+}
+
+// Wider animations wrap to the next texture rows. Supported by all adapters.
+pub const VAT_TEXTURE_WIDTH: u32 = 2048;
+
+// Baked vertex positions and optionally normals, frame by frame, in the mesh local space. The
+// vertices match the mesh vertices by index. Playback always loops.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VertexAnimation {
+    vertex_count: u32,
+    frame_count: u32,
+    frame_rate: f32,
+    positions: Vec<Vector3<f32>>,
+    normals: Option<Vec<Vector3<f32>>>,
+}
+
+impl VertexAnimation {
+    // Positions and normals are stored frame after frame.
+    pub fn new(
+        vertex_count: u32,
+        frame_rate: f32,
+        positions: Vec<Vector3<f32>>,
+        normals: Option<Vec<Vector3<f32>>>,
+    ) -> Result<Self, VatError> {
+        if vertex_count == 0 || positions.is_empty() {
+            return Err(VatError::InvalidData(String::from("no frames")));
+        }
+        if !positions.len().is_multiple_of(vertex_count as usize) {
+            return Err(VatError::InvalidData(format!(
+                "{} positions for {} vertices",
+                positions.len(),
+                vertex_count
+            )));
+        }
+        if normals.as_ref().is_some_and(|n| n.len() != positions.len()) {
+            return Err(VatError::InvalidData(String::from(
+                "normal and position count mismatch",
+            )));
+        }
+        if frame_rate <= 0. {
+            return Err(VatError::InvalidData(format!("frame rate {}", frame_rate)));
+        }
+        Ok(Self {
+            vertex_count,
+            frame_count: (positions.len() / vertex_count as usize) as u32,
+            frame_rate,
+            positions,
+            normals,
+        })
+    }
+
+    // Textures exported by DCC tools, one column per vertex and one row per frame. Positions are
+    // remapped from [0, 1] to the bounds, normals from [0, 1] to [-1, 1].
+    pub fn from_images(
+        positions: &image::DynamicImage,
+        normals: Option<&image::DynamicImage>,
+        bounds: &Aabb3<f32>,
+        frame_rate: f32,
+    ) -> Result<Self, VatError> {
+        let size = bounds.size();
+        let vertex_count = image_width(positions);
+        let positions = image_texels(positions)
+            .into_iter()
+            .map(|c| bounds.min + c.component_mul(&size))
+            .collect();
+        let normals = match normals {
+            Some(normals) => {
+                if image_width(normals) != vertex_count {
+                    return Err(VatError::InvalidData(String::from(
+                        "normal and position texture size mismatch",
+                    )));
+                }
+                Some(
+                    image_texels(normals)
+                        .into_iter()
+                        .map(|c| c * 2. - Vector3::repeat(1.))
+                        .collect(),
+                )
+            }
+            None => None,
+        };
+        Self::new(vertex_count, frame_rate, positions, normals)
+    }
+
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(
+        positions: P,
+        normals: Option<Q>,
+        bounds: &Aabb3<f32>,
+        frame_rate: f32,
+    ) -> Result<Self, VatError> {
+        let positions = image::open(positions)?;
+        let normals = match normals {
+            Some(path) => Some(image::open(path)?),
+            None => None,
+        };
+        Self::from_images(&positions, normals.as_ref(), bounds, frame_rate)
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    // In seconds.
+    pub fn duration(&self) -> f32 {
+        self.frame_count as f32 / self.frame_rate
+    }
+
+    pub fn has_normals(&self) -> bool {
+        self.normals.is_some()
+    }
+
+    pub fn position(&self, frame: u32, vertex: u32) -> Vector3<f32> {
+        self.positions[(frame * self.vertex_count + vertex) as usize]
+    }
+
+    pub fn normal(&self, frame: u32, vertex: u32) -> Option<Vector3<f32>> {
+        self.normals
+            .as_ref()
+            .map(|n| n[(frame * self.vertex_count + vertex) as usize])
+    }
+
+    // Frames around the time, looping, and the interpolation factor between them.
+    pub fn frames_at(&self, time: f32) -> (u32, u32, f32) {
+        let frame = (time * self.frame_rate).rem_euclid(self.frame_count as f32);
+        let frame0 = (frame as u32).min(self.frame_count - 1);
+        (frame0, (frame0 + 1) % self.frame_count, frame.fract())
+    }
+
+    // Same as the shader, e.g. for picking.
+    pub fn sample_position(&self, time: f32, vertex: u32) -> Vector3<f32> {
+        let (frame0, frame1, t) = self.frames_at(time);
+        self.position(frame0, vertex)
+            .lerp(&self.position(frame1, vertex), t)
+    }
+}
+
+fn image_width(image: &image::DynamicImage) -> u32 {
+    use image::GenericImageView;
+    image.width()
+}
+
+// RGB from 0 to 1. 16 bit images keep their precision.
+fn image_texels(image: &image::DynamicImage) -> Vec<Vector3<f32>> {
+    let color = image.color();
+    if color.bytes_per_pixel() > color.channel_count() {
+        image
+            .to_rgb16()
+            .pixels()
+            .map(|p| Vector3::from(p.0.map(|c| c as f32 / u16::MAX as f32)))
+            .collect()
+    } else {
+        image
+            .to_rgb8()
+            .pixels()
+            .map(|p| Vector3::from(p.0.map(|c| c as f32 / u8::MAX as f32)))
+            .collect()
+    }
+}
+
+// Texture size storing the given number of texels.
+pub fn vat_texture_size(texel_count: u32) -> (u32, u32) {
+    let width = texel_count.clamp(1, VAT_TEXTURE_WIDTH);
+    (width, texel_count.div_ceil(width).max(1))
+}
+
+// Vertex animation uploaded in float textures.
+#[derive(Debug)]
+pub struct VertexAnimationTexture {
+    positions: gfx::TextureView,
+    normals: gfx::TextureView,
+    vertex_count: u32,
+    frame_count: u32,
+    frame_rate: f32,
+    width: u32,
+    has_normals: bool,
+}
+
+impl VertexAnimationTexture {
+    pub fn new(instance: &gfx::Instance, animation: &VertexAnimation) -> Self {
+        let texel_count = animation.vertex_count * animation.frame_count;
+        let (width, height) = vat_texture_size(texel_count);
+        let create = |data: Option<&[Vector3<f32>]>| {
+            // Missing normals use a single texel, never read.
+            let (width, height) = match data {
+                Some(_) => (width, height),
+                None => (1, 1),
+            };
+            let size = gfx::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            };
+            let texture = gfx::Texture::new(
+                instance,
+                &gfx::TextureDescriptor {
+                    label: None,
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: gfx::TextureDimension::D2,
+                    format: gfx::TextureFormat::Rgba32Float,
+                    usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+                },
+            );
+            let mut texels = vec![0f32; (width * height * 4) as usize];
+            for (i, v) in data.unwrap_or(&[]).iter().enumerate() {
+                texels[i * 4..i * 4 + 3].copy_from_slice(v.as_slice());
+            }
+            texture.write(
+                instance,
+                0,
+                gfx::Origin3d::ZERO,
+                bytemuck::cast_slice(&texels),
+                gfx::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: core::num::NonZeroU32::new(width * 16),
+                    rows_per_image: None,
+                },
+                size,
+            );
+            texture.create_view(&gfx::TextureViewDescriptor::default())
+        };
+        Self {
+            positions: create(Some(&animation.positions)),
+            normals: create(animation.normals.as_deref()),
+            vertex_count: animation.vertex_count,
+            frame_count: animation.frame_count,
+            frame_rate: animation.frame_rate,
+            width,
+            has_normals: animation.has_normals(),
+        }
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+}
+
+// Per instance transform and playback state.
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VatInstance {
+    pub model: [[f32; 4]; 4],
+    // Added to the scaled time, in seconds, so that instances don't move in sync.
+    pub time_offset: f32,
+    pub speed: f32,
+    // Range of the played frames, a frame count of 0 plays until the last frame.
+    pub first_frame: f32,
+    pub frame_count: f32,
+}
+
+impl VatInstance {
+    pub fn new(model: &HomogeneousMatrix3<f32>) -> Self {
+        Self {
+            model: (*model).into(),
+            time_offset: 0.,
+            speed: 1.,
+            first_frame: 0.,
+            frame_count: 0.,
+        }
+    }
+
+    pub fn with_time_offset(mut self, time_offset: f32) -> Self {
+        self.time_offset = time_offset;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_clip(mut self, first_frame: u32, frame_count: u32) -> Self {
+        self.first_frame = first_frame as f32;
+        self.frame_count = frame_count as f32;
+        self
+    }
+}
+
+unsafe impl bytemuck::Zeroable for VatInstance {}
+
+unsafe impl bytemuck::Pod for VatInstance {}
+
+#[derive(Debug)]
+pub struct VatInstanceBuffer {
+    buffer: gfx::Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl VatInstanceBuffer {
+    pub fn new(instance: &gfx::Instance, instances: &[VatInstance]) -> Self {
+        let mut buffer = Self {
+            buffer: Self::create_buffer(instance, 1),
+            capacity: 1,
+            len: 0,
+        };
+        buffer.update(instance, instances);
+        buffer
+    }
+
+    // The write is queued and applied before the next submitted commands.
+    pub fn update(&mut self, instance: &gfx::Instance, instances: &[VatInstance]) {
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.buffer = Self::create_buffer(instance, self.capacity);
+        }
+        self.len = instances.len();
+        if !instances.is_empty() {
+            instance.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn create_buffer(instance: &gfx::Instance, capacity: usize) -> gfx::Buffer {
+        gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (capacity * std::mem::size_of::<VatInstance>()) as gfx::BufferAddress,
+                usage: gfx::BufferUsage::VERTEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct AnimationBlock {
+    settings: [u32; 4],
+    parameters: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for AnimationBlock {}
+
+unsafe impl bytemuck::Pod for AnimationBlock {}
+
+impl AnimationBlock {
+    fn new(animation: &VertexAnimationTexture, time: f32) -> Self {
+        Self {
+            settings: [
+                animation.vertex_count,
+                animation.frame_count,
+                animation.width,
+                animation.has_normals as u32,
+            ],
+            parameters: [animation.frame_rate, time, 0., 0.],
+        }
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    let texture_entry = |binding| gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::VERTEX,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: false },
+            view_dimension: gfx::TextureViewDimension::D2,
+        },
+        count: None,
+    };
+    let mut entries = material_bind_group_layout_entries();
+    entries.extend_from_slice(&[
+        gfx::BindGroupLayoutEntry {
+            binding: MATERIAL_BINDING_COUNT,
+            visibility: gfx::ShaderStage::VERTEX,
+            ty: gfx::BindingType::Buffer {
+                ty: gfx::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        },
+        texture_entry(MATERIAL_BINDING_COUNT + 1),
+        texture_entry(MATERIAL_BINDING_COUNT + 2),
+        gfx::BindGroupLayoutEntry {
+            binding: MATERIAL_BINDING_COUNT + 3,
+            visibility: gfx::ShaderStage::VERTEX,
+            ty: gfx::BindingType::Sampler {
+                filtering: false,
+                comparison: false,
+            },
+            count: None,
+        },
+    ]);
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &entries,
+        },
+    )
+}
+
+// A PBR material with the vertex animation it deforms the mesh with.
+#[derive(Debug)]
+pub struct VatMaterialUniforms {
+    material: PbrMaterialUniforms,
+    animation_buffer: gfx::Buffer,
+    block: AnimationBlock,
+}
+
+impl VatMaterialUniforms {
+    pub fn new(
+        instance: &gfx::Instance,
+        material: &PbrMaterial,
+        textures: &PbrMaterialTextures,
+        defaults: &PbrDefaultTextures,
+        sampler: &gfx::Sampler,
+        animation: &VertexAnimationTexture,
+    ) -> Self {
+        let block = AnimationBlock::new(animation, 0.);
+        let animation_buffer = gfx::Buffer::init(
+            instance,
+            &gfx::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::bytes_of(&block),
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+            },
+        );
+        let animation_sampler = gfx::Sampler::new(instance, &gfx::SamplerDescriptor::default());
+        let material = PbrMaterialUniforms::with_layout(
+            instance,
+            material,
+            textures,
+            defaults,
+            sampler,
+            &bind_group_layout(instance),
+            &[
+                gfx::BindGroupEntry {
+                    binding: MATERIAL_BINDING_COUNT,
+                    resource: animation_buffer.as_entire_binding(),
+                },
+                gfx::BindGroupEntry {
+                    binding: MATERIAL_BINDING_COUNT + 1,
+                    resource: gfx::BindingResource::TextureView(&animation.positions),
+                },
+                gfx::BindGroupEntry {
+                    binding: MATERIAL_BINDING_COUNT + 2,
+                    resource: gfx::BindingResource::TextureView(&animation.normals),
+                },
+                gfx::BindGroupEntry {
+                    binding: MATERIAL_BINDING_COUNT + 3,
+                    resource: gfx::BindingResource::Sampler(&animation_sampler),
+                },
+            ],
+        );
+        Self {
+            material,
+            animation_buffer,
+            block,
+        }
+    }
+
+    pub fn update_material(&self, instance: &gfx::Instance, material: &PbrMaterial) {
+        self.material.update(instance, material);
+    }
+
+    // Playback time in seconds, shared by all instances before their offset and speed.
+    pub fn set_time(&mut self, instance: &gfx::Instance, time: f32) {
+        self.block.parameters[1] = time;
+        instance.write_buffer(&self.animation_buffer, 0, bytemuck::bytes_of(&self.block));
+    }
+}
+
+const INSTANCE_ATTRIBUTES: [gfx::VertexAttribute; 5] = [
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 0,
+        shader_location: 3,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 16,
+        shader_location: 4,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 32,
+        shader_location: 5,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 48,
+        shader_location: 6,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 64,
+        shader_location: 7,
+    },
+];
+
+// Instanced meshes deformed by a vertex animation texture, lit like the PBR meshes. The push
+// constants model matrix is applied after the instance ones.
+#[derive(Debug)]
+pub struct VatPipeline {
+    pipeline: PbrPipeline,
+}
+
+impl VatPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &PbrPipelineDescriptor) -> Self {
+        Self {
+            pipeline: PbrPipeline::with_shaders(
+                instance,
+                desc,
+                PbrPipelineShaders {
+                    vertex_shader: gfx::include_spirv!("shaders/gen/spirv/vat.vert.spv"),
+                    vertex_buffers: &[
+                        vertex_buffer_layout(),
+                        gfx::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<VatInstance>() as gfx::BufferAddress,
+                            step_mode: gfx::VertexStepMode::Instance,
+                            attributes: &INSTANCE_ATTRIBUTES,
+                        },
+                    ],
+                    material_bind_group_layout: bind_group_layout(instance),
+                    fragment_shader: gfx::include_spirv!("shaders/gen/spirv/pbr.frag.spv"),
+                },
+            ),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.pipeline.color_conversion()
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        self.pipeline.render_pass_requirements()
+    }
+}
+
+pub trait VatRenderer<'a> {
+    fn draw_vat_mesh(
+        &mut self,
+        pipeline: &'a VatPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a VatMaterialUniforms,
+        mesh: &'a Mesh,
+        instances: &'a VatInstanceBuffer,
+        push_constants: &'a PbrPushConstants,
+    );
+}
+
+impl<'a> VatRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_vat_mesh(
+        &mut self,
+        pipeline: &'a VatPipeline,
+        lighting: PbrLighting<'a>,
+        material: &'a VatMaterialUniforms,
+        mesh: &'a Mesh,
+        instances: &'a VatInstanceBuffer,
+        push_constants: &'a PbrPushConstants,
+    ) {
+        if instances.is_empty() {
+            return;
+        }
+        bind_lit_mesh(
+            self,
+            &pipeline.pipeline,
+            lighting,
+            material.material.bind_group(),
+            mesh,
+            push_constants,
+        );
+        self.set_vertex_buffer(1, instances.buffer.slice(..));
+        self.draw_indexed(0..mesh.index_count(), 0, 0..instances.len() as u32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    // Two vertices moving up by 1 each frame, for 4 frames.
+    fn rising() -> VertexAnimation {
+        let positions = (0..4)
+            .flat_map(|frame| (0..2).map(move |v| Vector3::new(v as f32, frame as f32, 0.)))
+            .collect();
+        VertexAnimation::new(2, 2., positions, None).unwrap()
+    }
+
+    #[test]
+    fn validation() {
+        expect_that!(matches!(
+            VertexAnimation::new(2, 30., vec![Vector3::zeros(); 3], None),
+            Err(VatError::InvalidData(_))
+        ));
+        expect_that!(matches!(
+            VertexAnimation::new(1, 30., vec![Vector3::zeros(); 3], Some(Vec::new())),
+            Err(VatError::InvalidData(_))
+        ));
+        expect_that!(matches!(
+            VertexAnimation::new(1, 0., vec![Vector3::zeros(); 3], None),
+            Err(VatError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn playback() {
+        let animation = rising();
+        expect_that!(&animation.frame_count(), eq(4));
+        expect_that!(&animation.duration(), close_to(2., 1e-6));
+        expect_that!(&animation.frames_at(0.75), eq((1, 2, 0.5)));
+        // Loops back to the first frame.
+        expect_that!(&animation.frames_at(1.75), eq((3, 0, 0.5)));
+        expect_that!(&animation.frames_at(-0.25), eq((3, 0, 0.5)));
+        let p = animation.sample_position(0.75, 1);
+        expect_that!(&p, eq(Vector3::new(1., 1.5, 0.)));
+    }
+
+    #[test]
+    fn images() {
+        let positions =
+            image::RgbImage::from_raw(2, 2, vec![0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255])
+                .unwrap();
+        let normals =
+            image::RgbImage::from_raw(2, 2, vec![255, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        let bounds = Aabb3::new(Vector3::new(-1., 0., 0.), Vector3::new(1., 4., 2.));
+        let animation = VertexAnimation::from_images(
+            &image::DynamicImage::ImageRgb8(positions),
+            Some(&image::DynamicImage::ImageRgb8(normals)),
+            &bounds,
+            30.,
+        )
+        .unwrap();
+        expect_that!(&animation.vertex_count(), eq(2));
+        expect_that!(&animation.frame_count(), eq(2));
+        expect_that!(&animation.position(0, 1), eq(Vector3::new(1., 0., 0.)));
+        expect_that!(&animation.position(1, 0), eq(Vector3::new(-1., 4., 0.)));
+        expect_that!(&animation.position(1, 1), eq(Vector3::new(-1., 0., 2.)));
+        let normal = animation.normal(0, 0).unwrap();
+        expect_that!((normal - Vector3::new(1., 0., -1.)).norm() < 0.01);
+    }
+
+    #[test]
+    fn texture_size() {
+        expect_that!(&vat_texture_size(100), eq((100, 1)));
+        expect_that!(
+            &vat_texture_size(VAT_TEXTURE_WIDTH * 2 + 1),
+            eq((VAT_TEXTURE_WIDTH, 3))
+        );
+        expect_that!(&vat_texture_size(0), eq((1, 1)));
+    }
+
+    #[test]
+    fn instance_layout() {
+        expect_that!(&std::mem::size_of::<VatInstance>(), eq(80));
+        let instance = VatInstance::new(&HomogeneousMatrix3::new_translation(&Vector3::new(
+            1., 2., 3.,
+        )))
+        .with_clip(4, 8);
+        let model = instance.model;
+        expect_that!(&model[3], eq([1., 2., 3., 1.]));
+        let (first_frame, frame_count) = (instance.first_frame, instance.frame_count);
+        expect_that!(&first_frame, eq(4.));
+        expect_that!(&frame_count, eq(8.));
+    }
+}
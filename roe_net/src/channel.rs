@@ -0,0 +1,178 @@
+use super::packet::sequence_greater_than;
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Channel {
+    // Messages may be lost, duplicated or delivered out of order.
+    Unreliable,
+    // Messages are resent until acknowledged, and delivered in order exactly once.
+    Reliable,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct PendingMessage {
+    id: u16,
+    payload: Vec<u8>,
+    last_sent: Option<Instant>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct ReliableSender {
+    next_id: u16,
+    pending: VecDeque<PendingMessage>,
+}
+
+impl ReliableSender {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, payload: Vec<u8>) {
+        self.pending.push_back(PendingMessage {
+            id: self.next_id,
+            payload,
+            last_sent: None,
+        });
+        self.next_id = self.next_id.wrapping_add(1);
+    }
+
+    pub fn acknowledge(&mut self, id: u16) {
+        self.pending.retain(|m| m.id != id);
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Returns the messages that were never sent, or whose last transmission is older than the
+    // resend interval, and marks them as sent.
+    pub fn collect_outgoing(
+        &mut self,
+        time: Instant,
+        resend_interval: Duration,
+    ) -> Vec<(u16, Vec<u8>)> {
+        let mut outgoing = Vec::new();
+        for message in self.pending.iter_mut() {
+            let due = match message.last_sent {
+                Some(last_sent) => time.saturating_duration_since(last_sent) >= resend_interval,
+                None => true,
+            };
+            if due {
+                message.last_sent = Some(time);
+                outgoing.push((message.id, message.payload.clone()));
+            }
+        }
+        outgoing
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct ReliableReceiver {
+    next_id: u16,
+    buffered: BTreeMap<u16, Vec<u8>>,
+}
+
+impl ReliableReceiver {
+    // Out of order messages further ahead than this are dropped. The sender will resend them.
+    const MAX_BUFFERED_DISTANCE: u16 = 1024;
+
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            buffered: BTreeMap::new(),
+        }
+    }
+
+    // Stores an incoming message. Returns true if the message must be acknowledged, which is
+    // also the case for duplicates of already delivered messages, since the previous ack may
+    // have been lost.
+    pub fn receive(&mut self, id: u16, payload: Vec<u8>) -> bool {
+        if id != self.next_id && !sequence_greater_than(id, self.next_id) {
+            return true;
+        }
+        if id.wrapping_sub(self.next_id) >= Self::MAX_BUFFERED_DISTANCE {
+            return false;
+        }
+        self.buffered.entry(id).or_insert(payload);
+        true
+    }
+
+    // Returns the messages that can be delivered in order.
+    pub fn drain_ready(&mut self) -> Vec<Vec<u8>> {
+        let mut ready = Vec::new();
+        while let Some(payload) = self.buffered.remove(&self.next_id) {
+            ready.push(payload);
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn sender_resends_unacknowledged_messages() {
+        let interval = Duration::from_millis(100);
+        let t0 = Instant::now();
+        let mut sender = ReliableSender::new();
+        sender.push(vec![1]);
+        sender.push(vec![2]);
+
+        expect_that!(
+            &sender.collect_outgoing(t0, interval),
+            eq(vec![(0, vec![1]), (1, vec![2])])
+        );
+        expect_that!(
+            &sender.collect_outgoing(t0 + Duration::from_millis(50), interval),
+            eq(Vec::new())
+        );
+
+        sender.acknowledge(0);
+        expect_that!(&sender.pending_count(), eq(1));
+        expect_that!(
+            &sender.collect_outgoing(t0 + Duration::from_millis(100), interval),
+            eq(vec![(1, vec![2])])
+        );
+
+        sender.acknowledge(1);
+        expect_that!(&sender.pending_count(), eq(0));
+    }
+
+    #[test]
+    fn receiver_delivers_in_order() {
+        let mut receiver = ReliableReceiver::new();
+        expect_that!(receiver.receive(1, vec![1]));
+        expect_that!(&receiver.drain_ready(), eq(Vec::<Vec<u8>>::new()));
+        expect_that!(receiver.receive(2, vec![2]));
+        expect_that!(receiver.receive(0, vec![0]));
+        expect_that!(&receiver.drain_ready(), eq(vec![vec![0], vec![1], vec![2]]));
+    }
+
+    #[test]
+    fn receiver_ignores_duplicates() {
+        let mut receiver = ReliableReceiver::new();
+        expect_that!(receiver.receive(0, vec![0]));
+        expect_that!(&receiver.drain_ready(), eq(vec![vec![0]]));
+        expect_that!(receiver.receive(0, vec![0]));
+        expect_that!(&receiver.drain_ready(), eq(Vec::<Vec<u8>>::new()));
+    }
+
+    #[test]
+    fn receiver_ids_wrap_around() {
+        let mut receiver = ReliableReceiver::new();
+        receiver.next_id = 65535;
+        expect_that!(receiver.receive(0, vec![1]));
+        expect_that!(receiver.receive(65535, vec![0]));
+        expect_that!(&receiver.drain_ready(), eq(vec![vec![0], vec![1]]));
+    }
+}
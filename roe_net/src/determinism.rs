@@ -0,0 +1,56 @@
+use super::Snapshot;
+
+use roe_app as app;
+
+pub trait SnapshotTickRecord {
+    // Adds a field for each entity of the snapshot, named after the entity id.
+    fn add_snapshot(&mut self, snapshot: &Snapshot);
+}
+
+impl SnapshotTickRecord for app::TickRecord {
+    fn add_snapshot(&mut self, snapshot: &Snapshot) {
+        for (entity, components) in snapshot.entity_components() {
+            let mut data = Vec::new();
+            for (component_id, component_data) in components.iter() {
+                data.extend_from_slice(&component_id.to_le_bytes());
+                data.extend_from_slice(&(component_data.len() as u64).to_le_bytes());
+                data.extend_from_slice(component_data);
+            }
+            self.add_bytes(format!("entity {}", entity.0), &data, || {
+                format!("{:?}", components)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EntityId;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn snapshots() {
+        let mut snapshot = Snapshot::new(0);
+        snapshot
+            .set_component(EntityId(1), 0, &(1f32, 2f32))
+            .unwrap();
+        snapshot
+            .set_component(EntityId(2), 0, &(3f32, 4f32))
+            .unwrap();
+        let mut expected = app::DeterminismLog::new();
+        let mut record = app::TickRecord::new(0, false);
+        record.add_snapshot(&snapshot);
+        expected.push(record);
+
+        snapshot.set_component(EntityId(2), 1, &10u32).unwrap();
+        let mut actual = app::DeterminismLog::new();
+        let mut record = app::TickRecord::new(0, false);
+        record.add_snapshot(&snapshot);
+        actual.push(record);
+
+        let divergence = expected.first_divergence(&actual).unwrap();
+        expect_that!(&divergence.fields.len(), eq(1));
+        expect_that!(&divergence.fields[0].name, eq(String::from("entity 2")));
+    }
+}
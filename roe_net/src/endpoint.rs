@@ -0,0 +1,825 @@
+use super::{
+    packet::{Packet, MAX_PACKET_SIZE, MAX_PAYLOAD_SIZE},
+    Channel, Error, ReliableReceiver, ReliableSender, Transport,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndpointDescriptor {
+    // Connection requests with a different protocol id are denied.
+    pub protocol_id: u64,
+    pub accept_connections: bool,
+    pub max_connections: usize,
+    pub timeout: Duration,
+    pub resend_interval: Duration,
+    pub keepalive_interval: Duration,
+}
+
+impl Default for EndpointDescriptor {
+    fn default() -> Self {
+        Self {
+            protocol_id: 0,
+            accept_connections: false,
+            max_connections: 32,
+            timeout: Duration::from_secs(5),
+            resend_interval: Duration::from_millis(100),
+            keepalive_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum DisconnectReason {
+    TimedOut,
+    Denied,
+    Remote,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum NetEvent {
+    Connected(SocketAddr),
+    Disconnected(SocketAddr, DisconnectReason),
+    Message {
+        address: SocketAddr,
+        channel: Channel,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum ConnectionState {
+    Connecting,
+    Connected,
+}
+
+#[derive(Debug)]
+struct Connection {
+    state: ConnectionState,
+    last_received: Option<Instant>,
+    last_sent: Option<Instant>,
+    reliable_sender: ReliableSender,
+    reliable_receiver: ReliableReceiver,
+    pending_acks: Vec<u16>,
+}
+
+impl Connection {
+    // Max number of ids in a single ack packet, so that it fits in a packet.
+    const MAX_ACKS_PER_PACKET: usize = 256;
+
+    fn new(state: ConnectionState, time: Option<Instant>) -> Self {
+        Self {
+            state,
+            last_received: time,
+            last_sent: None,
+            reliable_sender: ReliableSender::new(),
+            reliable_receiver: ReliableReceiver::new(),
+            pending_acks: Vec::new(),
+        }
+    }
+}
+
+// Connection oriented endpoint built on top of an unreliable transport. The same type is used for
+// clients and servers: a server accepts incoming connections, a client connects to a server.
+//
+// No data is received or sent, apart from unreliable messages, until pump is called. pump is
+// meant to be called once per fixed update (ApplicationState::on_fixed_update), after which
+// the received events can be retrieved through poll_event.
+pub struct Endpoint<T: Transport> {
+    transport: T,
+    desc: EndpointDescriptor,
+    connections: HashMap<SocketAddr, Connection>,
+    events: VecDeque<NetEvent>,
+}
+
+impl<T: Transport> Endpoint<T> {
+    pub fn new(transport: T, desc: EndpointDescriptor) -> Self {
+        Self {
+            transport,
+            desc,
+            connections: HashMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn descriptor(&self) -> &EndpointDescriptor {
+        &self.desc
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn local_address(&self) -> Result<SocketAddr, Error> {
+        self.transport.local_address()
+    }
+
+    pub fn is_connected(&self, address: SocketAddr) -> bool {
+        matches!(
+            self.connections.get(&address),
+            Some(Connection {
+                state: ConnectionState::Connected,
+                ..
+            })
+        )
+    }
+
+    pub fn connected_addresses(&self) -> Vec<SocketAddr> {
+        self.connections
+            .iter()
+            .filter(|(_, c)| c.state == ConnectionState::Connected)
+            .map(|(a, _)| *a)
+            .collect()
+    }
+
+    // Number of reliable messages not yet acknowledged by the remote endpoint.
+    pub fn unacknowledged_count(&self, address: SocketAddr) -> usize {
+        match self.connections.get(&address) {
+            Some(c) => c.reliable_sender.pending_count(),
+            None => 0,
+        }
+    }
+
+    // Starts the connection handshake. The connection request is sent on the next pump, and
+    // a Connected or Disconnected event is produced when the handshake completes.
+    pub fn connect(&mut self, address: SocketAddr) {
+        self.connections
+            .entry(address)
+            .or_insert_with(|| Connection::new(ConnectionState::Connecting, None));
+    }
+
+    pub fn disconnect(&mut self, address: SocketAddr) -> Result<(), Error> {
+        if self.connections.remove(&address).is_some() {
+            self.send_packet(address, &Packet::Disconnect)?;
+        }
+        Ok(())
+    }
+
+    pub fn send(
+        &mut self,
+        address: SocketAddr,
+        channel: Channel,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        if data.len() > MAX_PAYLOAD_SIZE {
+            return Err(Error::PayloadTooLarge(data.len()));
+        }
+        if !self.is_connected(address) {
+            return Err(Error::NotConnected(address));
+        }
+        match channel {
+            Channel::Unreliable => self.send_packet(
+                address,
+                &Packet::Unreliable {
+                    payload: data.to_vec(),
+                },
+            ),
+            Channel::Reliable => {
+                self.connections
+                    .get_mut(&address)
+                    .unwrap()
+                    .reliable_sender
+                    .push(data.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn send_message<M: Serialize>(
+        &mut self,
+        address: SocketAddr,
+        channel: Channel,
+        message: &M,
+    ) -> Result<(), Error> {
+        let data = bincode::serialize(message)?;
+        self.send(address, channel, &data)
+    }
+
+    pub fn broadcast(&mut self, channel: Channel, data: &[u8]) -> Result<(), Error> {
+        for address in self.connected_addresses() {
+            self.send(address, channel, data)?;
+        }
+        Ok(())
+    }
+
+    pub fn poll_event(&mut self) -> Option<NetEvent> {
+        self.events.pop_front()
+    }
+
+    // Receives all available packets, advances the connection handshakes, sends acks, pending
+    // reliable messages and keepalives, and closes the connections that timed out.
+    pub fn pump(&mut self, time: Instant) -> Result<(), Error> {
+        self.receive_packets(time)?;
+        self.send_packets(time)?;
+        self.check_timeouts(time);
+        Ok(())
+    }
+
+    fn receive_packets(&mut self, time: Instant) -> Result<(), Error> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        while let Some((size, address)) = self.transport.receive_from(&mut buffer)? {
+            // Malformed packets may come from anywhere, they are silently ignored.
+            if let Ok(packet) = Packet::deserialize(&buffer[..size]) {
+                self.handle_packet(address, packet, time)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_packet(
+        &mut self,
+        address: SocketAddr,
+        packet: Packet,
+        time: Instant,
+    ) -> Result<(), Error> {
+        if let Packet::ConnectionRequest { protocol_id } = packet {
+            return self.handle_connection_request(address, protocol_id, time);
+        }
+
+        let connection = match self.connections.get_mut(&address) {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        connection.last_received = Some(time);
+
+        match packet {
+            Packet::ConnectionAccepted => {
+                if connection.state == ConnectionState::Connecting {
+                    connection.state = ConnectionState::Connected;
+                    self.events.push_back(NetEvent::Connected(address));
+                }
+            }
+            Packet::ConnectionDenied => {
+                if connection.state == ConnectionState::Connecting {
+                    self.connections.remove(&address);
+                    self.events
+                        .push_back(NetEvent::Disconnected(address, DisconnectReason::Denied));
+                }
+            }
+            Packet::Disconnect => {
+                self.connections.remove(&address);
+                self.events
+                    .push_back(NetEvent::Disconnected(address, DisconnectReason::Remote));
+            }
+            Packet::Unreliable { payload } => {
+                if connection.state == ConnectionState::Connected {
+                    self.events.push_back(NetEvent::Message {
+                        address,
+                        channel: Channel::Unreliable,
+                        data: payload,
+                    });
+                }
+            }
+            Packet::Reliable { id, payload } => {
+                if connection.state == ConnectionState::Connected {
+                    if connection.reliable_receiver.receive(id, payload) {
+                        connection.pending_acks.push(id);
+                    }
+                    for data in connection.reliable_receiver.drain_ready() {
+                        self.events.push_back(NetEvent::Message {
+                            address,
+                            channel: Channel::Reliable,
+                            data,
+                        });
+                    }
+                }
+            }
+            Packet::Ack { ids } => {
+                for id in ids {
+                    connection.reliable_sender.acknowledge(id);
+                }
+            }
+            Packet::KeepAlive | Packet::ConnectionRequest { .. } => (),
+        }
+        Ok(())
+    }
+
+    fn handle_connection_request(
+        &mut self,
+        address: SocketAddr,
+        protocol_id: u64,
+        time: Instant,
+    ) -> Result<(), Error> {
+        // The acceptance packet might have been lost, the client is still waiting for it.
+        if let Some(connection) = self.connections.get_mut(&address) {
+            if connection.state == ConnectionState::Connected {
+                connection.last_received = Some(time);
+                return self.send_packet(address, &Packet::ConnectionAccepted);
+            }
+        }
+
+        let accepted = self.desc.accept_connections
+            && protocol_id == self.desc.protocol_id
+            && self.connections.len() < self.desc.max_connections;
+        if !accepted {
+            return self.send_packet(address, &Packet::ConnectionDenied);
+        }
+
+        self.connections.insert(
+            address,
+            Connection::new(ConnectionState::Connected, Some(time)),
+        );
+        self.events.push_back(NetEvent::Connected(address));
+        self.send_packet(address, &Packet::ConnectionAccepted)
+    }
+
+    fn send_packets(&mut self, time: Instant) -> Result<(), Error> {
+        let mut packets = Vec::new();
+        for (address, connection) in self.connections.iter_mut() {
+            if connection.last_received.is_none() {
+                connection.last_received = Some(time);
+            }
+
+            let mut connection_packets = Vec::new();
+            match connection.state {
+                ConnectionState::Connecting => {
+                    if is_due(connection.last_sent, time, self.desc.resend_interval) {
+                        connection_packets.push(Packet::ConnectionRequest {
+                            protocol_id: self.desc.protocol_id,
+                        });
+                    }
+                }
+                ConnectionState::Connected => {
+                    for ids in connection
+                        .pending_acks
+                        .chunks(Connection::MAX_ACKS_PER_PACKET)
+                    {
+                        connection_packets.push(Packet::Ack { ids: ids.to_vec() });
+                    }
+                    connection.pending_acks.clear();
+                    for (id, payload) in connection
+                        .reliable_sender
+                        .collect_outgoing(time, self.desc.resend_interval)
+                    {
+                        connection_packets.push(Packet::Reliable { id, payload });
+                    }
+                    if connection_packets.is_empty()
+                        && is_due(connection.last_sent, time, self.desc.keepalive_interval)
+                    {
+                        connection_packets.push(Packet::KeepAlive);
+                    }
+                }
+            }
+
+            if !connection_packets.is_empty() {
+                connection.last_sent = Some(time);
+            }
+            packets.extend(connection_packets.into_iter().map(|p| (*address, p)));
+        }
+
+        for (address, packet) in packets {
+            self.send_packet(address, &packet)?;
+        }
+        Ok(())
+    }
+
+    fn check_timeouts(&mut self, time: Instant) {
+        let timeout = self.desc.timeout;
+        let timed_out: Vec<SocketAddr> = self
+            .connections
+            .iter()
+            .filter(|(_, c)| match c.last_received {
+                Some(last_received) => time.saturating_duration_since(last_received) >= timeout,
+                None => false,
+            })
+            .map(|(a, _)| *a)
+            .collect();
+        for address in timed_out {
+            self.connections.remove(&address);
+            self.events
+                .push_back(NetEvent::Disconnected(address, DisconnectReason::TimedOut));
+        }
+    }
+
+    fn send_packet(&mut self, address: SocketAddr, packet: &Packet) -> Result<(), Error> {
+        let data = packet.serialize()?;
+        self.transport.send_to(&data, address)
+    }
+}
+
+impl<T: Transport + std::fmt::Debug> std::fmt::Debug for Endpoint<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("transport", &self.transport)
+            .field("desc", &self.desc)
+            .field("connections", &self.connections)
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+pub fn deserialize_message<M: DeserializeOwned>(data: &[u8]) -> Result<M, Error> {
+    Ok(bincode::deserialize(data)?)
+}
+
+fn is_due(last: Option<Instant>, time: Instant, interval: Duration) -> bool {
+    match last {
+        Some(last) => time.saturating_duration_since(last) >= interval,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UdpTransport;
+    use galvanic_assert::{matchers::*, *};
+    use std::{cell::RefCell, rc::Rc};
+
+    // In-memory network shared by all the test transports. Datagrams whose index is in
+    // drop_list are lost.
+    #[derive(Debug, Default)]
+    struct Network {
+        queues: HashMap<SocketAddr, VecDeque<(SocketAddr, Vec<u8>)>>,
+        sent_count: usize,
+        drop_list: Vec<usize>,
+    }
+
+    #[derive(Debug)]
+    struct MemoryTransport {
+        address: SocketAddr,
+        network: Rc<RefCell<Network>>,
+    }
+
+    impl MemoryTransport {
+        fn new(port: u16, network: &Rc<RefCell<Network>>) -> Self {
+            let address = SocketAddr::from(([127, 0, 0, 1], port));
+            network.borrow_mut().queues.insert(address, VecDeque::new());
+            Self {
+                address,
+                network: Rc::clone(network),
+            }
+        }
+    }
+
+    impl Transport for MemoryTransport {
+        fn send_to(&mut self, data: &[u8], address: SocketAddr) -> Result<(), Error> {
+            let mut network = self.network.borrow_mut();
+            let index = network.sent_count;
+            network.sent_count += 1;
+            if !network.drop_list.contains(&index) {
+                if let Some(queue) = network.queues.get_mut(&address) {
+                    queue.push_back((self.address, data.to_vec()));
+                }
+            }
+            Ok(())
+        }
+
+        fn receive_from(
+            &mut self,
+            buffer: &mut [u8],
+        ) -> Result<Option<(usize, SocketAddr)>, Error> {
+            let mut network = self.network.borrow_mut();
+            match network.queues.get_mut(&self.address).unwrap().pop_front() {
+                Some((address, data)) => {
+                    buffer[..data.len()].copy_from_slice(&data);
+                    Ok(Some((data.len(), address)))
+                }
+                None => Ok(None),
+            }
+        }
+
+        fn local_address(&self) -> Result<SocketAddr, Error> {
+            Ok(self.address)
+        }
+    }
+
+    fn server_desc() -> EndpointDescriptor {
+        EndpointDescriptor {
+            protocol_id: 7,
+            accept_connections: true,
+            ..EndpointDescriptor::default()
+        }
+    }
+
+    fn client_desc() -> EndpointDescriptor {
+        EndpointDescriptor {
+            protocol_id: 7,
+            ..EndpointDescriptor::default()
+        }
+    }
+
+    fn events<T: Transport>(endpoint: &mut Endpoint<T>) -> Vec<NetEvent> {
+        let mut events = Vec::new();
+        while let Some(event) = endpoint.poll_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    fn pump_all(
+        server: &mut Endpoint<MemoryTransport>,
+        client: &mut Endpoint<MemoryTransport>,
+        time: Instant,
+    ) {
+        client.pump(time).unwrap();
+        server.pump(time).unwrap();
+        client.pump(time).unwrap();
+    }
+
+    fn connected_pair(
+        network: &Rc<RefCell<Network>>,
+        time: Instant,
+    ) -> (Endpoint<MemoryTransport>, Endpoint<MemoryTransport>) {
+        let mut server = Endpoint::new(MemoryTransport::new(1, network), server_desc());
+        let mut client = Endpoint::new(MemoryTransport::new(2, network), client_desc());
+        client.connect(server.local_address().unwrap());
+        pump_all(&mut server, &mut client, time);
+        events(&mut server);
+        events(&mut client);
+        (server, client)
+    }
+
+    #[test]
+    fn handshake() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let mut server = Endpoint::new(MemoryTransport::new(1, &network), server_desc());
+        let mut client = Endpoint::new(MemoryTransport::new(2, &network), client_desc());
+        let server_address = server.local_address().unwrap();
+        let client_address = client.local_address().unwrap();
+
+        client.connect(server_address);
+        expect_that!(!client.is_connected(server_address));
+
+        pump_all(&mut server, &mut client, Instant::now());
+        expect_that!(
+            &events(&mut server),
+            eq(vec![NetEvent::Connected(client_address)])
+        );
+        expect_that!(
+            &events(&mut client),
+            eq(vec![NetEvent::Connected(server_address)])
+        );
+        expect_that!(server.is_connected(client_address));
+        expect_that!(client.is_connected(server_address));
+    }
+
+    #[test]
+    fn handshake_with_lost_request() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        network.borrow_mut().drop_list.push(0);
+        let mut server = Endpoint::new(MemoryTransport::new(1, &network), server_desc());
+        let mut client = Endpoint::new(MemoryTransport::new(2, &network), client_desc());
+        let server_address = server.local_address().unwrap();
+
+        let t0 = Instant::now();
+        client.connect(server_address);
+        pump_all(&mut server, &mut client, t0);
+        expect_that!(!client.is_connected(server_address));
+
+        pump_all(&mut server, &mut client, t0 + Duration::from_millis(100));
+        expect_that!(client.is_connected(server_address));
+    }
+
+    #[test]
+    fn handshake_denied() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let mut server = Endpoint::new(MemoryTransport::new(1, &network), server_desc());
+        let mut client = Endpoint::new(
+            MemoryTransport::new(2, &network),
+            EndpointDescriptor {
+                protocol_id: 8,
+                ..EndpointDescriptor::default()
+            },
+        );
+        let server_address = server.local_address().unwrap();
+
+        client.connect(server_address);
+        pump_all(&mut server, &mut client, Instant::now());
+        expect_that!(&events(&mut server), eq(Vec::new()));
+        expect_that!(
+            &events(&mut client),
+            eq(vec![NetEvent::Disconnected(
+                server_address,
+                DisconnectReason::Denied
+            )])
+        );
+    }
+
+    #[test]
+    fn send_requires_connection() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let mut client = Endpoint::new(MemoryTransport::new(2, &network), client_desc());
+        let address = SocketAddr::from(([127, 0, 0, 1], 1));
+        expect_that!(
+            &client.send(address, Channel::Unreliable, &[1]),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn payload_too_large() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let (mut server, _client) = connected_pair(&network, Instant::now());
+        let address = server.connected_addresses()[0];
+        expect_that!(
+            &server.send(address, Channel::Reliable, &vec![0; MAX_PAYLOAD_SIZE + 1]),
+            is_variant!(Result::Err)
+        );
+        expect_that!(
+            &server.send(address, Channel::Reliable, &vec![0; MAX_PAYLOAD_SIZE]),
+            is_variant!(Result::Ok)
+        );
+    }
+
+    #[test]
+    fn unreliable_messages() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let t0 = Instant::now();
+        let (mut server, mut client) = connected_pair(&network, t0);
+        let client_address = client.local_address().unwrap();
+        let server_address = server.local_address().unwrap();
+
+        client
+            .send(server_address, Channel::Unreliable, &[1, 2, 3])
+            .unwrap();
+        server.pump(t0).unwrap();
+        expect_that!(
+            &events(&mut server),
+            eq(vec![NetEvent::Message {
+                address: client_address,
+                channel: Channel::Unreliable,
+                data: vec![1, 2, 3],
+            }])
+        );
+    }
+
+    #[test]
+    fn reliable_messages_survive_packet_loss() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let t0 = Instant::now();
+        let (mut server, mut client) = connected_pair(&network, t0);
+        let client_address = client.local_address().unwrap();
+        let server_address = server.local_address().unwrap();
+
+        // Drop the first message.
+        let first = network.borrow().sent_count;
+        network.borrow_mut().drop_list.push(first);
+
+        client
+            .send(server_address, Channel::Reliable, &[1])
+            .unwrap();
+        client
+            .send(server_address, Channel::Reliable, &[2])
+            .unwrap();
+        pump_all(&mut server, &mut client, t0);
+        expect_that!(&events(&mut server), eq(Vec::new()));
+        expect_that!(&client.unacknowledged_count(server_address), eq(1));
+
+        let t1 = t0 + Duration::from_millis(100);
+        pump_all(&mut server, &mut client, t1);
+        expect_that!(
+            &events(&mut server),
+            eq(vec![
+                NetEvent::Message {
+                    address: client_address,
+                    channel: Channel::Reliable,
+                    data: vec![1],
+                },
+                NetEvent::Message {
+                    address: client_address,
+                    channel: Channel::Reliable,
+                    data: vec![2],
+                },
+            ])
+        );
+
+        // After the acks are received, nothing is resent.
+        server.pump(t1).unwrap();
+        client.pump(t1).unwrap();
+        expect_that!(&client.unacknowledged_count(server_address), eq(0));
+        let sent_count = network.borrow().sent_count;
+        client.pump(t0 + Duration::from_millis(200)).unwrap();
+        server.pump(t0 + Duration::from_millis(200)).unwrap();
+        expect_that!(&events(&mut server), eq(Vec::new()));
+        expect_that!(&network.borrow().sent_count, eq(sent_count));
+    }
+
+    #[test]
+    fn timeout() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let t0 = Instant::now();
+        let (mut server, client) = connected_pair(&network, t0);
+        let client_address = client.local_address().unwrap();
+
+        server.pump(t0 + Duration::from_secs(5)).unwrap();
+        expect_that!(
+            &events(&mut server),
+            eq(vec![NetEvent::Disconnected(
+                client_address,
+                DisconnectReason::TimedOut
+            )])
+        );
+        expect_that!(!server.is_connected(client_address));
+    }
+
+    #[test]
+    fn keepalive_prevents_timeout() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let t0 = Instant::now();
+        let (mut server, mut client) = connected_pair(&network, t0);
+        let client_address = client.local_address().unwrap();
+
+        for i in 1..20 {
+            pump_all(
+                &mut server,
+                &mut client,
+                t0 + Duration::from_millis(500 * i),
+            );
+        }
+        expect_that!(server.is_connected(client_address));
+        expect_that!(&events(&mut server), eq(Vec::new()));
+    }
+
+    #[test]
+    fn disconnect() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let t0 = Instant::now();
+        let (mut server, mut client) = connected_pair(&network, t0);
+        let client_address = client.local_address().unwrap();
+        let server_address = server.local_address().unwrap();
+
+        client.disconnect(server_address).unwrap();
+        expect_that!(!client.is_connected(server_address));
+        server.pump(t0).unwrap();
+        expect_that!(
+            &events(&mut server),
+            eq(vec![NetEvent::Disconnected(
+                client_address,
+                DisconnectReason::Remote
+            )])
+        );
+    }
+
+    #[test]
+    fn messages() {
+        let network = Rc::new(RefCell::new(Network::default()));
+        let t0 = Instant::now();
+        let (mut server, mut client) = connected_pair(&network, t0);
+        let server_address = server.local_address().unwrap();
+
+        client
+            .send_message(
+                server_address,
+                Channel::Reliable,
+                &(12u32, String::from("hi")),
+            )
+            .unwrap();
+        pump_all(&mut server, &mut client, t0);
+        match server.poll_event() {
+            Some(NetEvent::Message { data, .. }) => {
+                let message: (u32, String) = deserialize_message(&data).unwrap();
+                expect_that!(&message, eq((12, String::from("hi"))));
+            }
+            e => panic!("Unexpected event ({:?})", e),
+        }
+    }
+
+    #[test]
+    fn udp_loopback() {
+        let mut server = Endpoint::new(UdpTransport::bind("127.0.0.1:0").unwrap(), server_desc());
+        let mut client = Endpoint::new(UdpTransport::bind("127.0.0.1:0").unwrap(), client_desc());
+        let server_address = server.local_address().unwrap();
+
+        client.connect(server_address);
+        let start = Instant::now();
+        while !client.is_connected(server_address) {
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "Connection timed out"
+            );
+            let time = Instant::now();
+            client.pump(time).unwrap();
+            server.pump(time).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        client
+            .send(server_address, Channel::Reliable, &[42])
+            .unwrap();
+        let start = Instant::now();
+        loop {
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "Message not received"
+            );
+            let time = Instant::now();
+            client.pump(time).unwrap();
+            server.pump(time).unwrap();
+            if let Some(NetEvent::Message { data, .. }) = events(&mut server)
+                .into_iter()
+                .find(|e| matches!(e, NetEvent::Message { .. }))
+            {
+                expect_that!(&data, eq(vec![42]));
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
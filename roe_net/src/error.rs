@@ -0,0 +1,44 @@
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    SerializationError(bincode::Error),
+    PayloadTooLarge(usize),
+    NotConnected(std::net::SocketAddr),
+    #[cfg(feature = "websocket")]
+    WebSocketError(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::SerializationError(e) => write!(f, "Serialization error ({})", e),
+            Self::PayloadTooLarge(size) => write!(f, "Payload too large ({} bytes)", size),
+            Self::NotConnected(address) => write!(f, "Not connected ({})", address),
+            #[cfg(feature = "websocket")]
+            Self::WebSocketError(e) => write!(f, "WebSocket error ({})", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::SerializationError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Self {
+        Self::SerializationError(e)
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+pub trait Interpolate {
+    // Returns the value at t in [0, 1] between self and other.
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t as f32
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        std::array::from_fn(|i| self[i].interpolate(&other[i], t))
+    }
+}
+
+// Buffer of states received from the server, sampled in between ticks to render remote entities
+// smoothly. Sampling should happen with a delay of a few ticks behind the latest received state,
+// so that a state is usually available on both sides of the sampling point, even if some
+// snapshots are lost.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InterpolationBuffer<S: Interpolate + Clone> {
+    capacity: usize,
+    samples: VecDeque<(u32, S)>,
+}
+
+impl<S: Interpolate + Clone> InterpolationBuffer<S> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "The interpolation buffer capacity must be higher than 0"
+        );
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn latest_tick(&self) -> Option<u32> {
+        self.samples.back().map(|(tick, _)| *tick)
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    // Adds a state. States older than the latest one are inserted in order, duplicates are
+    // ignored.
+    pub fn push(&mut self, tick: u32, state: S) {
+        let index = self.samples.partition_point(|(t, _)| *t < tick);
+        if self.samples.get(index).map(|(t, _)| *t) == Some(tick) {
+            return;
+        }
+        self.samples.insert(index, (tick, state));
+        while self.samples.len() > self.capacity {
+            self.samples.pop_front();
+        }
+    }
+
+    // Returns the state at the given fractional tick. Outside of the buffered range the
+    // closest state is returned, no extrapolation is performed.
+    pub fn sample(&self, tick: f64) -> Option<S> {
+        let (first_tick, first_state) = self.samples.front()?;
+        if tick <= *first_tick as f64 {
+            return Some(first_state.clone());
+        }
+        let index = self.samples.partition_point(|(t, _)| (*t as f64) < tick);
+        match self.samples.get(index) {
+            Some((next_tick, next_state)) => {
+                let (prev_tick, prev_state) = &self.samples[index - 1];
+                let t = (tick - *prev_tick as f64) / (*next_tick - *prev_tick) as f64;
+                Some(prev_state.interpolate(next_state, t))
+            }
+            None => self.samples.back().map(|(_, s)| s.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn array_interpolation() {
+        expect_that!(&[0f32, 2.].interpolate(&[1., 4.], 0.5), eq([0.5, 3.]));
+    }
+
+    #[test]
+    fn sample() {
+        let mut buffer = InterpolationBuffer::new(4);
+        expect_that!(&buffer.sample(0.), eq(None));
+
+        buffer.push(10, 1.);
+        buffer.push(12, 3.);
+        expect_that!(&buffer.sample(9.), eq(Some(1.)));
+        expect_that!(&buffer.sample(10.), eq(Some(1.)));
+        expect_that!(&buffer.sample(11.), eq(Some(2.)));
+        expect_that!(&buffer.sample(11.5), eq(Some(2.5)));
+        expect_that!(&buffer.sample(12.), eq(Some(3.)));
+        expect_that!(&buffer.sample(15.), eq(Some(3.)));
+    }
+
+    #[test]
+    fn out_of_order_push() {
+        let mut buffer = InterpolationBuffer::new(4);
+        buffer.push(12, 3.);
+        buffer.push(10, 1.);
+        buffer.push(11, 5.);
+        buffer.push(11, 7.);
+        expect_that!(&buffer.len(), eq(3));
+        expect_that!(&buffer.latest_tick(), eq(Some(12)));
+        expect_that!(&buffer.sample(10.5), eq(Some(3.)));
+    }
+
+    #[test]
+    fn capacity() {
+        let mut buffer = InterpolationBuffer::new(2);
+        buffer.push(1, 1.);
+        buffer.push(2, 2.);
+        buffer.push(3, 3.);
+        expect_that!(&buffer.len(), eq(2));
+        expect_that!(&buffer.sample(0.), eq(Some(2.)));
+    }
+}
@@ -0,0 +1,33 @@
+mod error;
+pub use error::*;
+
+mod transport;
+pub use transport::*;
+
+#[cfg(feature = "websocket")]
+mod websocket_transport;
+#[cfg(feature = "websocket")]
+pub use websocket_transport::*;
+
+mod packet;
+
+mod channel;
+pub use channel::*;
+
+mod endpoint;
+pub use endpoint::*;
+
+mod snapshot;
+pub use snapshot::*;
+
+mod replication;
+pub use replication::*;
+
+mod interpolation;
+pub use interpolation::*;
+
+mod prediction;
+pub use prediction::*;
+
+mod determinism;
+pub use determinism::*;
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_PACKET_SIZE: usize = 1400;
+
+// Conservative bound on the size of the packet header, so that payloads can be checked before
+// serialization.
+pub const MAX_PACKET_HEADER_SIZE: usize = 16;
+
+pub const MAX_PAYLOAD_SIZE: usize = MAX_PACKET_SIZE - MAX_PACKET_HEADER_SIZE;
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum Packet {
+    ConnectionRequest { protocol_id: u64 },
+    ConnectionAccepted,
+    ConnectionDenied,
+    Disconnect,
+    KeepAlive,
+    Unreliable { payload: Vec<u8> },
+    Reliable { id: u16, payload: Vec<u8> },
+    Ack { ids: Vec<u16> },
+}
+
+impl Packet {
+    pub fn serialize(&self) -> Result<Vec<u8>, bincode::Error> {
+        bincode::serialize(self)
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, bincode::Error> {
+        bincode::deserialize(data)
+    }
+}
+
+// Returns true if sequence number a is more recent than b, taking wrap-around into account.
+pub fn sequence_greater_than(a: u16, b: u16) -> bool {
+    a != b && a.wrapping_sub(b) < 0x8000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn sequence_comparison() {
+        expect_that!(sequence_greater_than(1, 0));
+        expect_that!(!sequence_greater_than(0, 1));
+        expect_that!(!sequence_greater_than(5, 5));
+        expect_that!(sequence_greater_than(0, 65535));
+        expect_that!(sequence_greater_than(10, 65000));
+        expect_that!(!sequence_greater_than(65000, 10));
+    }
+
+    #[test]
+    fn serialization() {
+        let packet = Packet::Reliable {
+            id: 12,
+            payload: vec![1, 2, 3],
+        };
+        let data = packet.serialize().unwrap();
+        expect_that!(&Packet::deserialize(&data).unwrap(), eq(packet));
+    }
+
+    #[test]
+    fn header_size() {
+        let packet = Packet::Reliable {
+            id: 12,
+            payload: vec![0; MAX_PAYLOAD_SIZE],
+        };
+        expect_that!(packet.serialize().unwrap().len() <= MAX_PACKET_SIZE);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+// State simulated locally by the client ahead of the server.
+pub trait Predict {
+    type Input: Clone;
+
+    // Advances the state by one fixed update using the given input. Must match the server
+    // simulation, otherwise corrections are visible whenever the server state is received.
+    fn apply_input(&mut self, input: &Self::Input);
+}
+
+// Client-side prediction with server reconciliation. Inputs are applied immediately to the
+// predicted state and kept until the server confirms the tick they were applied on. When an
+// authoritative state is received, the inputs following it are applied again on top of it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientPrediction<S: Predict + Clone> {
+    state: S,
+    pending_inputs: VecDeque<(u32, S::Input)>,
+}
+
+impl<S: Predict + Clone> ClientPrediction<S> {
+    pub fn new(state: S) -> Self {
+        Self {
+            state,
+            pending_inputs: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn pending_inputs(&self) -> impl Iterator<Item = &(u32, S::Input)> {
+        self.pending_inputs.iter()
+    }
+
+    pub fn apply_input(&mut self, tick: u32, input: S::Input) {
+        if let Some((last_tick, _)) = self.pending_inputs.back() {
+            assert!(
+                tick > *last_tick,
+                "The input ticks must be strictly increasing"
+            );
+        }
+        self.state.apply_input(&input);
+        self.pending_inputs.push_back((tick, input));
+    }
+
+    // Replaces the predicted state with the server state for the given tick, including the
+    // input for that tick, and applies the inputs for the following ticks again.
+    pub fn reconcile(&mut self, tick: u32, state: S) {
+        while let Some((input_tick, _)) = self.pending_inputs.front() {
+            if *input_tick > tick {
+                break;
+            }
+            self.pending_inputs.pop_front();
+        }
+        self.state = state;
+        for (_, input) in self.pending_inputs.iter() {
+            self.state.apply_input(input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct Position(i32);
+
+    impl Predict for Position {
+        type Input = i32;
+
+        fn apply_input(&mut self, input: &Self::Input) {
+            self.0 += input;
+        }
+    }
+
+    #[test]
+    fn prediction() {
+        let mut prediction = ClientPrediction::new(Position(0));
+        prediction.apply_input(1, 1);
+        prediction.apply_input(2, 2);
+        prediction.apply_input(3, 3);
+        expect_that!(prediction.state(), eq(Position(6)));
+    }
+
+    #[test]
+    fn reconciliation() {
+        let mut prediction = ClientPrediction::new(Position(0));
+        prediction.apply_input(1, 1);
+        prediction.apply_input(2, 2);
+        prediction.apply_input(3, 3);
+
+        // The server processed the first two inputs, but the state diverged.
+        prediction.reconcile(2, Position(10));
+        expect_that!(prediction.state(), eq(Position(13)));
+        expect_that!(&prediction.pending_inputs().count(), eq(1));
+
+        prediction.reconcile(3, Position(13));
+        expect_that!(prediction.state(), eq(Position(13)));
+        expect_that!(&prediction.pending_inputs().count(), eq(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "The input ticks must be strictly increasing")]
+    fn input_ticks_must_increase() {
+        let mut prediction = ClientPrediction::new(Position(0));
+        prediction.apply_input(2, 1);
+        prediction.apply_input(2, 1);
+    }
+}
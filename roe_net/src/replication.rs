@@ -0,0 +1,294 @@
+use super::{Channel, Endpoint, Error, Snapshot, SnapshotDelta, Transport};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum ReplicationMessage {
+    // Sent by the server on the unreliable channel.
+    Snapshot(SnapshotDelta),
+    // Sent by the client to confirm that a snapshot was received, so that it can be used as
+    // baseline for the following deltas.
+    Ack(u32),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ReplicationDescriptor {
+    // Number of snapshots kept as potential delta baselines. If the last snapshot acknowledged
+    // by a client is older, a full snapshot is sent.
+    pub history_size: usize,
+}
+
+impl Default for ReplicationDescriptor {
+    fn default() -> Self {
+        Self { history_size: 32 }
+    }
+}
+
+// Server-authoritative replication. Each fixed update the server pushes the current snapshot of
+// the replicated entities, and sends to each client the delta relative to the last snapshot
+// the client acknowledged.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReplicationServer {
+    desc: ReplicationDescriptor,
+    history: VecDeque<Snapshot>,
+    acknowledged_ticks: HashMap<SocketAddr, u32>,
+}
+
+impl ReplicationServer {
+    pub fn new(desc: ReplicationDescriptor) -> Self {
+        assert!(
+            desc.history_size > 0,
+            "The replication history size must be higher than 0"
+        );
+        Self {
+            desc,
+            history: VecDeque::with_capacity(desc.history_size),
+            acknowledged_ticks: HashMap::new(),
+        }
+    }
+
+    pub fn descriptor(&self) -> &ReplicationDescriptor {
+        &self.desc
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&Snapshot> {
+        self.history.back()
+    }
+
+    pub fn push_snapshot(&mut self, snapshot: Snapshot) {
+        if let Some(latest) = self.history.back() {
+            assert!(
+                snapshot.tick() > latest.tick(),
+                "The snapshot ticks must be strictly increasing"
+            );
+        }
+        self.history.push_back(snapshot);
+        while self.history.len() > self.desc.history_size {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn acknowledge(&mut self, address: SocketAddr, tick: u32) {
+        let acknowledged_tick = self.acknowledged_ticks.entry(address).or_insert(tick);
+        *acknowledged_tick = std::cmp::max(*acknowledged_tick, tick);
+    }
+
+    pub fn handle_message(&mut self, address: SocketAddr, message: &ReplicationMessage) {
+        if let ReplicationMessage::Ack(tick) = message {
+            self.acknowledge(address, *tick);
+        }
+    }
+
+    // Must be called when a client disconnects.
+    pub fn remove_client(&mut self, address: SocketAddr) {
+        self.acknowledged_ticks.remove(&address);
+    }
+
+    // Delta between the latest snapshot and the baseline of the client, or None if no snapshot
+    // was pushed yet.
+    pub fn delta_for(&self, address: SocketAddr) -> Option<SnapshotDelta> {
+        let latest = self.history.back()?;
+        let baseline = self
+            .acknowledged_ticks
+            .get(&address)
+            .and_then(|tick| self.history.iter().find(|s| s.tick() == *tick));
+        Some(latest.delta(baseline))
+    }
+
+    pub fn send_snapshots<T: Transport>(&self, endpoint: &mut Endpoint<T>) -> Result<(), Error> {
+        for address in endpoint.connected_addresses() {
+            if let Some(delta) = self.delta_for(address) {
+                endpoint.send_message(
+                    address,
+                    Channel::Unreliable,
+                    &ReplicationMessage::Snapshot(delta),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Client side of the replication. Reconstructs the snapshots from the received deltas.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReplicationClient {
+    desc: ReplicationDescriptor,
+    history: VecDeque<Snapshot>,
+}
+
+impl ReplicationClient {
+    pub fn new(desc: ReplicationDescriptor) -> Self {
+        assert!(
+            desc.history_size > 0,
+            "The replication history size must be higher than 0"
+        );
+        Self {
+            desc,
+            history: VecDeque::with_capacity(desc.history_size),
+        }
+    }
+
+    pub fn descriptor(&self) -> &ReplicationDescriptor {
+        &self.desc
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&Snapshot> {
+        self.history.back()
+    }
+
+    // Returns the reconstructed snapshot, or None if the delta is older than the latest
+    // snapshot or its baseline is no longer available. The snapshot tick must be acknowledged
+    // to the server.
+    pub fn receive(&mut self, delta: &SnapshotDelta) -> Option<&Snapshot> {
+        if let Some(latest) = self.history.back() {
+            if delta.tick <= latest.tick() {
+                return None;
+            }
+        }
+        let baseline = match delta.baseline_tick {
+            Some(tick) => Some(self.history.iter().find(|s| s.tick() == tick)?),
+            None => None,
+        };
+        let snapshot = Snapshot::from_delta(baseline, delta);
+        self.history.push_back(snapshot);
+        while self.history.len() > self.desc.history_size {
+            self.history.pop_front();
+        }
+        self.history.back()
+    }
+
+    // Processes a snapshot message and sends the acknowledgement. Returns the reconstructed
+    // snapshot, if any.
+    pub fn handle_message<T: Transport>(
+        &mut self,
+        endpoint: &mut Endpoint<T>,
+        server_address: SocketAddr,
+        message: &ReplicationMessage,
+    ) -> Result<Option<&Snapshot>, Error> {
+        if let ReplicationMessage::Snapshot(delta) = message {
+            if let Some(snapshot) = self.receive(delta) {
+                endpoint.send_message(
+                    server_address,
+                    Channel::Unreliable,
+                    &ReplicationMessage::Ack(snapshot.tick()),
+                )?;
+                return Ok(self.history.back());
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EntityId;
+    use galvanic_assert::{matchers::*, *};
+
+    fn address() -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 1))
+    }
+
+    fn snapshot(tick: u32, positions: &[(u32, f32)]) -> Snapshot {
+        let mut snapshot = Snapshot::new(tick);
+        for (id, position) in positions {
+            snapshot.set_component(EntityId(*id), 0, position).unwrap();
+        }
+        snapshot
+    }
+
+    #[test]
+    fn full_snapshot_until_acknowledged() {
+        let mut server = ReplicationServer::new(ReplicationDescriptor::default());
+        expect_that!(&server.delta_for(address()), eq(None));
+
+        server.push_snapshot(snapshot(1, &[(0, 1.), (1, 1.)]));
+        server.push_snapshot(snapshot(2, &[(0, 2.), (1, 1.)]));
+        let delta = server.delta_for(address()).unwrap();
+        expect_that!(&delta.baseline_tick, eq(None));
+        expect_that!(&delta.changed_components.len(), eq(2));
+
+        server.acknowledge(address(), 1);
+        let delta = server.delta_for(address()).unwrap();
+        expect_that!(&delta.baseline_tick, eq(Some(1)));
+        expect_that!(&delta.changed_components.len(), eq(1));
+    }
+
+    #[test]
+    fn baseline_out_of_history() {
+        let mut server = ReplicationServer::new(ReplicationDescriptor { history_size: 2 });
+        server.push_snapshot(snapshot(1, &[]));
+        server.acknowledge(address(), 1);
+        server.push_snapshot(snapshot(2, &[]));
+        server.push_snapshot(snapshot(3, &[]));
+        expect_that!(
+            &server.delta_for(address()).unwrap().baseline_tick,
+            eq(None)
+        );
+    }
+
+    #[test]
+    fn old_acknowledgements_are_ignored() {
+        let mut server = ReplicationServer::new(ReplicationDescriptor::default());
+        server.push_snapshot(snapshot(1, &[]));
+        server.push_snapshot(snapshot(2, &[]));
+        server.push_snapshot(snapshot(3, &[]));
+        server.handle_message(address(), &ReplicationMessage::Ack(2));
+        server.handle_message(address(), &ReplicationMessage::Ack(1));
+        expect_that!(
+            &server.delta_for(address()).unwrap().baseline_tick,
+            eq(Some(2))
+        );
+        server.remove_client(address());
+        expect_that!(
+            &server.delta_for(address()).unwrap().baseline_tick,
+            eq(None)
+        );
+    }
+
+    #[test]
+    fn client_reconstructs_snapshots() {
+        let mut server = ReplicationServer::new(ReplicationDescriptor::default());
+        let mut client = ReplicationClient::new(ReplicationDescriptor::default());
+
+        let s1 = snapshot(1, &[(0, 1.), (1, 1.)]);
+        server.push_snapshot(s1.clone());
+        let d1 = server.delta_for(address()).unwrap();
+        expect_that!(&client.receive(&d1), eq(Some(&s1)));
+        server.acknowledge(address(), 1);
+
+        // Lost delta.
+        server.push_snapshot(snapshot(2, &[(0, 2.), (1, 1.)]));
+        let d2 = server.delta_for(address()).unwrap();
+
+        let s3 = snapshot(3, &[(0, 3.)]);
+        server.push_snapshot(s3.clone());
+        let d3 = server.delta_for(address()).unwrap();
+        expect_that!(&client.receive(&d3), eq(Some(&s3)));
+
+        // Late delta.
+        expect_that!(&client.receive(&d2), eq(None));
+        expect_that!(&client.latest_snapshot(), eq(Some(&s3)));
+    }
+
+    #[test]
+    fn client_drops_deltas_with_unknown_baseline() {
+        let mut client = ReplicationClient::new(ReplicationDescriptor::default());
+        let delta = snapshot(2, &[(0, 1.)]).delta(Some(&snapshot(1, &[])));
+        expect_that!(&client.receive(&delta), eq(None));
+        expect_that!(&client.latest_snapshot(), eq(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "The snapshot ticks must be strictly increasing")]
+    fn snapshot_ticks_must_increase() {
+        let mut server = ReplicationServer::new(ReplicationDescriptor::default());
+        server.push_snapshot(snapshot(2, &[]));
+        server.push_snapshot(snapshot(1, &[]));
+    }
+}
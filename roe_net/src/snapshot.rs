@@ -0,0 +1,309 @@
+use super::Error;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
+pub struct EntityId(pub u32);
+
+// Identifies a component type. The same ids must be used by the server and the clients.
+pub type ComponentId = u16;
+
+// State of the replicated entities at a given simulation tick. Components are stored in
+// serialized form, so that snapshots can be compared and delta compressed without knowing the
+// component types.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Snapshot {
+    tick: u32,
+    entities: BTreeMap<EntityId, BTreeMap<ComponentId, Vec<u8>>>,
+}
+
+impl Snapshot {
+    pub fn new(tick: u32) -> Self {
+        Self {
+            tick,
+            entities: BTreeMap::new(),
+        }
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.entities.keys().copied()
+    }
+
+    pub fn contains_entity(&self, entity: EntityId) -> bool {
+        self.entities.contains_key(&entity)
+    }
+
+    // Adds an entity without components. Entities are also implicitly added when setting their
+    // components.
+    pub fn insert_entity(&mut self, entity: EntityId) {
+        self.entities.entry(entity).or_default();
+    }
+
+    pub fn set_component<C: Serialize>(
+        &mut self,
+        entity: EntityId,
+        component_id: ComponentId,
+        component: &C,
+    ) -> Result<(), Error> {
+        let data = bincode::serialize(component)?;
+        self.entities
+            .entry(entity)
+            .or_default()
+            .insert(component_id, data);
+        Ok(())
+    }
+
+    // Returns None if the entity doesn't have the component.
+    pub fn component<C: DeserializeOwned>(
+        &self,
+        entity: EntityId,
+        component_id: ComponentId,
+    ) -> Option<Result<C, Error>> {
+        self.entities
+            .get(&entity)
+            .and_then(|components| components.get(&component_id))
+            .map(|data| Ok(bincode::deserialize(data)?))
+    }
+
+    pub(crate) fn entity_components(
+        &self,
+    ) -> impl Iterator<Item = (EntityId, &BTreeMap<ComponentId, Vec<u8>>)> {
+        self.entities
+            .iter()
+            .map(|(entity, components)| (*entity, components))
+    }
+
+    pub fn remove_component(&mut self, entity: EntityId, component_id: ComponentId) {
+        if let Some(components) = self.entities.get_mut(&entity) {
+            components.remove(&component_id);
+        }
+    }
+
+    pub fn remove_entity(&mut self, entity: EntityId) {
+        self.entities.remove(&entity);
+    }
+
+    // Computes the changes needed to produce this snapshot from the baseline. If no baseline is
+    // provided, the delta contains the full snapshot.
+    pub fn delta(&self, baseline: Option<&Snapshot>) -> SnapshotDelta {
+        let empty = BTreeMap::new();
+        let base_entities = match baseline {
+            Some(b) => &b.entities,
+            None => &empty,
+        };
+
+        let mut delta = SnapshotDelta {
+            tick: self.tick,
+            baseline_tick: baseline.map(|b| b.tick),
+            added_entities: Vec::new(),
+            changed_components: Vec::new(),
+            removed_components: Vec::new(),
+            removed_entities: Vec::new(),
+        };
+
+        for (entity, components) in self.entities.iter() {
+            let base_components = base_entities.get(entity);
+            if base_components.is_none() {
+                delta.added_entities.push(*entity);
+            }
+            for (component_id, data) in components.iter() {
+                let base_data = base_components.and_then(|c| c.get(component_id));
+                if base_data != Some(data) {
+                    delta
+                        .changed_components
+                        .push((*entity, *component_id, data.clone()));
+                }
+            }
+            if let Some(base_components) = base_components {
+                for component_id in base_components.keys() {
+                    if !components.contains_key(component_id) {
+                        delta.removed_components.push((*entity, *component_id));
+                    }
+                }
+            }
+        }
+
+        for entity in base_entities.keys() {
+            if !self.entities.contains_key(entity) {
+                delta.removed_entities.push(*entity);
+            }
+        }
+
+        delta
+    }
+
+    // Reconstructs a snapshot by applying a delta to its baseline. The baseline must match the
+    // one the delta was computed from.
+    pub fn from_delta(baseline: Option<&Snapshot>, delta: &SnapshotDelta) -> Self {
+        assert!(
+            baseline.map(|b| b.tick) == delta.baseline_tick,
+            "The baseline doesn't match the snapshot delta"
+        );
+        let mut entities = match baseline {
+            Some(b) => b.entities.clone(),
+            None => BTreeMap::new(),
+        };
+        for entity in delta.removed_entities.iter() {
+            entities.remove(entity);
+        }
+        for entity in delta.added_entities.iter() {
+            entities.entry(*entity).or_default();
+        }
+        for (entity, component_id) in delta.removed_components.iter() {
+            if let Some(components) = entities.get_mut(entity) {
+                components.remove(component_id);
+            }
+        }
+        for (entity, component_id, data) in delta.changed_components.iter() {
+            entities
+                .entry(*entity)
+                .or_default()
+                .insert(*component_id, data.clone());
+        }
+        Self {
+            tick: delta.tick,
+            entities,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SnapshotDelta {
+    pub tick: u32,
+    pub baseline_tick: Option<u32>,
+    pub added_entities: Vec<EntityId>,
+    pub changed_components: Vec<(EntityId, ComponentId, Vec<u8>)>,
+    pub removed_components: Vec<(EntityId, ComponentId)>,
+    pub removed_entities: Vec<EntityId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const POSITION: ComponentId = 0;
+    const HEALTH: ComponentId = 1;
+
+    type TestEntity = (u32, Option<(f32, f32)>, Option<u32>);
+
+    fn snapshot(tick: u32, entities: &[TestEntity]) -> Snapshot {
+        let mut snapshot = Snapshot::new(tick);
+        for (id, position, health) in entities {
+            snapshot.insert_entity(EntityId(*id));
+            if let Some(position) = position {
+                snapshot
+                    .set_component(EntityId(*id), POSITION, position)
+                    .unwrap();
+            }
+            if let Some(health) = health {
+                snapshot
+                    .set_component(EntityId(*id), HEALTH, health)
+                    .unwrap();
+            }
+        }
+        snapshot
+    }
+
+    #[test]
+    fn components() {
+        let snapshot = snapshot(3, &[(0, Some((1., 2.)), None)]);
+        expect_that!(&snapshot.tick(), eq(3));
+        expect_that!(
+            &snapshot
+                .component::<(f32, f32)>(EntityId(0), POSITION)
+                .unwrap()
+                .unwrap(),
+            eq((1., 2.))
+        );
+        expect_that!(snapshot.component::<u32>(EntityId(0), HEALTH).is_none());
+        expect_that!(snapshot.component::<u32>(EntityId(1), POSITION).is_none());
+    }
+
+    #[test]
+    fn full_delta() {
+        let s = snapshot(
+            1,
+            &[
+                (0, Some((1., 2.)), Some(10)),
+                (1, None, Some(5)),
+                (2, None, None),
+            ],
+        );
+        let delta = s.delta(None);
+        expect_that!(&delta.baseline_tick, eq(None));
+        expect_that!(&delta.changed_components.len(), eq(3));
+        expect_that!(&delta.added_entities.len(), eq(3));
+        expect_that!(&Snapshot::from_delta(None, &delta), eq(s));
+    }
+
+    #[test]
+    fn delta_contains_only_changes() {
+        let s0 = snapshot(
+            1,
+            &[
+                (0, Some((1., 2.)), Some(10)),
+                (1, None, Some(5)),
+                (2, Some((0., 0.)), None),
+            ],
+        );
+        let s1 = snapshot(
+            2,
+            &[
+                (0, Some((1., 3.)), Some(10)),
+                (2, None, None),
+                (3, None, Some(1)),
+            ],
+        );
+        let delta = s1.delta(Some(&s0));
+        expect_that!(&delta.baseline_tick, eq(Some(1)));
+        expect_that!(
+            &delta.changed_components,
+            eq(vec![
+                (
+                    EntityId(0),
+                    POSITION,
+                    bincode::serialize(&(1f32, 3f32)).unwrap()
+                ),
+                (EntityId(3), HEALTH, bincode::serialize(&1u32).unwrap()),
+            ])
+        );
+        expect_that!(&delta.removed_components, eq(vec![(EntityId(2), POSITION)]));
+        expect_that!(&delta.removed_entities, eq(vec![EntityId(1)]));
+        expect_that!(&delta.added_entities, eq(vec![EntityId(3)]));
+
+        // Entity 2 has no components left, but it is still alive.
+        expect_that!(&Snapshot::from_delta(Some(&s0), &delta), eq(s1));
+    }
+
+    #[test]
+    fn unchanged_snapshot_has_empty_delta() {
+        let s0 = snapshot(1, &[(0, Some((1., 2.)), Some(10))]);
+        let mut s1 = s0.clone();
+        s1.tick = 2;
+        let delta = s1.delta(Some(&s0));
+        expect_that!(delta.changed_components.is_empty());
+        expect_that!(delta.removed_components.is_empty());
+        expect_that!(delta.removed_entities.is_empty());
+        expect_that!(delta.added_entities.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "The baseline doesn't match the snapshot delta")]
+    fn from_delta_with_wrong_baseline() {
+        let s0 = snapshot(1, &[]);
+        let s1 = snapshot(2, &[]);
+        let delta = s1.delta(Some(&s0));
+        Snapshot::from_delta(None, &delta);
+    }
+}
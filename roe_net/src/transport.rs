@@ -0,0 +1,48 @@
+use super::Error;
+
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+// Unreliable, unordered datagram transport.
+pub trait Transport {
+    fn send_to(&mut self, data: &[u8], address: SocketAddr) -> Result<(), Error>;
+
+    // Non-blocking: returns None if no datagram is available.
+    fn receive_from(&mut self, buffer: &mut [u8]) -> Result<Option<(usize, SocketAddr)>, Error>;
+
+    fn local_address(&self) -> Result<SocketAddr, Error>;
+}
+
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<Self, Error> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_to(&mut self, data: &[u8], address: SocketAddr) -> Result<(), Error> {
+        self.socket.send_to(data, address)?;
+        Ok(())
+    }
+
+    fn receive_from(&mut self, buffer: &mut [u8]) -> Result<Option<(usize, SocketAddr)>, Error> {
+        match self.socket.recv_from(buffer) {
+            Ok(v) => Ok(Some(v)),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+            // On some platforms, an ICMP port unreachable message from a previous send is
+            // reported here. It shouldn't stop the processing of the other datagrams.
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => Ok(None),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    fn local_address(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.local_addr()?)
+    }
+}
@@ -0,0 +1,420 @@
+// WebSocket transport, allowing browser builds to connect to native servers. Each binary
+// WebSocket message carries one packet. The reliability of the underlying TCP connection isn't
+// relied upon: the endpoint protocol is the same as for UDP.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use crate::{Error, Transport};
+
+    use tungstenite::{
+        client::IntoClientRequest,
+        handshake::{
+            server::{NoCallback, ServerHandshake},
+            HandshakeError, MidHandshake,
+        },
+        Message, WebSocket,
+    };
+
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    };
+
+    type PendingHandshake = MidHandshake<ServerHandshake<TcpStream, NoCallback>>;
+
+    pub struct WebSocketTransport {
+        listener: Option<TcpListener>,
+        local_address: SocketAddr,
+        server_address: Option<SocketAddr>,
+        handshakes: Vec<(SocketAddr, PendingHandshake)>,
+        peers: HashMap<SocketAddr, WebSocket<TcpStream>>,
+    }
+
+    impl WebSocketTransport {
+        // Accepts WebSocket connections on the given address.
+        pub fn listen<A: ToSocketAddrs>(address: A) -> Result<Self, Error> {
+            let listener = TcpListener::bind(address)?;
+            listener.set_nonblocking(true)?;
+            let local_address = listener.local_addr()?;
+            Ok(Self {
+                listener: Some(listener),
+                local_address,
+                server_address: None,
+                handshakes: Vec::new(),
+                peers: HashMap::new(),
+            })
+        }
+
+        // Connects to a server, e.g. "ws://127.0.0.1:8080". Blocks until the WebSocket
+        // handshake completes. Secure WebSockets are not supported.
+        pub fn connect(url: &str) -> Result<Self, Error> {
+            let request = url.into_client_request()?;
+            let uri = request.uri();
+            if uri.scheme_str() != Some("ws") {
+                return Err(Error::WebSocketError(format!("Unsupported URL {}", url)));
+            }
+            let host = uri.host().unwrap_or_default().to_string();
+            let port = uri.port_u16().unwrap_or(80);
+
+            let stream = TcpStream::connect((host.as_str(), port))?;
+            let server_address = stream.peer_addr()?;
+            let local_address = stream.local_addr()?;
+            let (socket, _) = tungstenite::client(request, stream).map_err(|e| match e {
+                HandshakeError::Failure(e) => Error::from(e),
+                HandshakeError::Interrupted(_) => {
+                    Error::WebSocketError(String::from("Handshake interrupted"))
+                }
+            })?;
+            socket.get_ref().set_nonblocking(true)?;
+
+            let mut peers = HashMap::new();
+            peers.insert(server_address, socket);
+            Ok(Self {
+                listener: None,
+                local_address,
+                server_address: Some(server_address),
+                handshakes: Vec::new(),
+                peers,
+            })
+        }
+
+        // Address of the server, for transports created with connect.
+        pub fn server_address(&self) -> Option<SocketAddr> {
+            self.server_address
+        }
+
+        pub fn peer_count(&self) -> usize {
+            self.peers.len()
+        }
+
+        fn accept_connections(&mut self) -> Result<(), Error> {
+            let listener = match &self.listener {
+                Some(l) => l,
+                None => return Ok(()),
+            };
+            loop {
+                let (stream, address) = match listener.accept() {
+                    Ok(v) => v,
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+                    Err(e) => return Err(Error::from(e)),
+                };
+                stream.set_nonblocking(true)?;
+                match tungstenite::accept(stream) {
+                    Ok(socket) => {
+                        self.peers.insert(address, socket);
+                    }
+                    Err(HandshakeError::Interrupted(handshake)) => {
+                        self.handshakes.push((address, handshake))
+                    }
+                    Err(HandshakeError::Failure(_)) => (),
+                }
+            }
+        }
+
+        fn advance_handshakes(&mut self) {
+            for (address, handshake) in std::mem::take(&mut self.handshakes) {
+                match handshake.handshake() {
+                    Ok(socket) => {
+                        self.peers.insert(address, socket);
+                    }
+                    Err(HandshakeError::Interrupted(handshake)) => {
+                        self.handshakes.push((address, handshake))
+                    }
+                    Err(HandshakeError::Failure(_)) => (),
+                }
+            }
+        }
+    }
+
+    impl Transport for WebSocketTransport {
+        // Data sent to unknown or closed connections is silently dropped, as it would happen for
+        // datagrams. The endpoint detects the disconnection through its timeout.
+        fn send_to(&mut self, data: &[u8], address: SocketAddr) -> Result<(), Error> {
+            if let Some(socket) = self.peers.get_mut(&address) {
+                match socket.write_message(Message::Binary(data.to_vec())) {
+                    Ok(_) | Err(tungstenite::Error::SendQueueFull(_)) => (),
+                    Err(tungstenite::Error::Io(e))
+                        if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(_) => {
+                        self.peers.remove(&address);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn receive_from(
+            &mut self,
+            buffer: &mut [u8],
+        ) -> Result<Option<(usize, SocketAddr)>, Error> {
+            self.accept_connections()?;
+            self.advance_handshakes();
+
+            let mut received = None;
+            let mut closed = Vec::new();
+            for (address, socket) in self.peers.iter_mut() {
+                // Flushes the messages queued when the socket would have blocked. Errors are
+                // detected when reading.
+                let _ = socket.write_pending();
+                loop {
+                    match socket.read_message() {
+                        // Messages not fitting the buffer are dropped, as it would happen for
+                        // datagrams.
+                        Ok(Message::Binary(data)) if data.len() <= buffer.len() => {
+                            buffer[..data.len()].copy_from_slice(&data);
+                            received = Some((data.len(), *address));
+                            break;
+                        }
+                        // Control frames are handled by the socket.
+                        Ok(_) => (),
+                        Err(tungstenite::Error::Io(e))
+                            if e.kind() == std::io::ErrorKind::WouldBlock =>
+                        {
+                            break
+                        }
+                        Err(_) => {
+                            closed.push(*address);
+                            break;
+                        }
+                    }
+                }
+                if received.is_some() {
+                    break;
+                }
+            }
+            for address in closed {
+                self.peers.remove(&address);
+            }
+            Ok(received)
+        }
+
+        fn local_address(&self) -> Result<SocketAddr, Error> {
+            Ok(self.local_address)
+        }
+    }
+
+    impl From<tungstenite::Error> for Error {
+        fn from(e: tungstenite::Error) -> Self {
+            match e {
+                tungstenite::Error::Io(e) => Self::IoError(e),
+                e => Self::WebSocketError(e.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use crate::{Error, Transport};
+
+    use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+    use std::{
+        cell::RefCell,
+        collections::VecDeque,
+        net::{Ipv4Addr, SocketAddr},
+        rc::Rc,
+    };
+
+    // The browser doesn't expose socket addresses. The server is identified by this placeholder
+    // address instead.
+    fn placeholder_address() -> SocketAddr {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    }
+
+    pub struct WebSocketTransport {
+        socket: web_sys::WebSocket,
+        received: Rc<RefCell<VecDeque<Vec<u8>>>>,
+        pending: Vec<Vec<u8>>,
+        _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    }
+
+    impl WebSocketTransport {
+        // Connects to a server, e.g. "ws://127.0.0.1:8080". The connection is established
+        // asynchronously, data sent in the meantime is queued.
+        pub fn connect(url: &str) -> Result<Self, Error> {
+            let socket = web_sys::WebSocket::new(url).map_err(js_error)?;
+            socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+            let received = Rc::new(RefCell::new(VecDeque::new()));
+            let on_message = {
+                let received = Rc::clone(&received);
+                Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+                    if let Ok(data) = event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                        received
+                            .borrow_mut()
+                            .push_back(js_sys::Uint8Array::new(&data).to_vec());
+                    }
+                }) as Box<dyn FnMut(web_sys::MessageEvent)>)
+            };
+            socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+            Ok(Self {
+                socket,
+                received,
+                pending: Vec::new(),
+                _on_message: on_message,
+            })
+        }
+
+        pub fn server_address(&self) -> Option<SocketAddr> {
+            Some(placeholder_address())
+        }
+
+        pub fn peer_count(&self) -> usize {
+            match self.socket.ready_state() {
+                web_sys::WebSocket::OPEN => 1,
+                _ => 0,
+            }
+        }
+
+        fn flush_pending(&mut self) -> Result<(), Error> {
+            if self.socket.ready_state() == web_sys::WebSocket::OPEN {
+                for data in std::mem::take(&mut self.pending) {
+                    self.socket.send_with_u8_array(&data).map_err(js_error)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Transport for WebSocketTransport {
+        fn send_to(&mut self, data: &[u8], _address: SocketAddr) -> Result<(), Error> {
+            match self.socket.ready_state() {
+                web_sys::WebSocket::CONNECTING => self.pending.push(data.to_vec()),
+                web_sys::WebSocket::OPEN => {
+                    self.flush_pending()?;
+                    self.socket.send_with_u8_array(data).map_err(js_error)?;
+                }
+                // The connection is closed, the data is dropped as it would happen for datagrams.
+                _ => (),
+            }
+            Ok(())
+        }
+
+        fn receive_from(
+            &mut self,
+            buffer: &mut [u8],
+        ) -> Result<Option<(usize, SocketAddr)>, Error> {
+            self.flush_pending()?;
+            while let Some(data) = self.received.borrow_mut().pop_front() {
+                if data.len() <= buffer.len() {
+                    buffer[..data.len()].copy_from_slice(&data);
+                    return Ok(Some((data.len(), placeholder_address())));
+                }
+            }
+            Ok(None)
+        }
+
+        fn local_address(&self) -> Result<SocketAddr, Error> {
+            Ok(placeholder_address())
+        }
+    }
+
+    impl Drop for WebSocketTransport {
+        fn drop(&mut self) {
+            self.socket.set_onmessage(None);
+            let _ = self.socket.close();
+        }
+    }
+
+    fn js_error(e: JsValue) -> Error {
+        Error::WebSocketError(format!("{:?}", e))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::{Endpoint, EndpointDescriptor, NetEvent, Transport};
+    use galvanic_assert::{matchers::*, *};
+    use std::time::{Duration, Instant};
+
+    fn connect(server: &mut WebSocketTransport) -> WebSocketTransport {
+        let url = format!("ws://{}", server.local_address().unwrap());
+        let client = std::thread::spawn(move || WebSocketTransport::connect(&url).unwrap());
+        let mut buffer = [0; 16];
+        while !client.is_finished() {
+            server.receive_from(&mut buffer).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let client = client.join().unwrap();
+        while server.peer_count() == 0 {
+            server.receive_from(&mut buffer).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        client
+    }
+
+    #[test]
+    fn invalid_url() {
+        expect_that!(
+            &WebSocketTransport::connect("wss://127.0.0.1:1").is_err(),
+            eq(true)
+        );
+    }
+
+    #[test]
+    fn send_and_receive() {
+        let mut server = WebSocketTransport::listen("127.0.0.1:0").unwrap();
+        let mut client = connect(&mut server);
+        let server_address = client.server_address().unwrap();
+        let client_address = client.local_address().unwrap();
+        expect_that!(&server_address, eq(server.local_address().unwrap()));
+
+        client.send_to(&[1, 2, 3], server_address).unwrap();
+        let mut buffer = [0; 16];
+        let start = Instant::now();
+        let received = loop {
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "Message not received"
+            );
+            if let Some(v) = server.receive_from(&mut buffer).unwrap() {
+                break v;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        expect_that!(&received, eq((3, client_address)));
+        expect_that!(&buffer[..3].to_vec(), eq(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn endpoint_handshake() {
+        let mut server_transport = WebSocketTransport::listen("127.0.0.1:0").unwrap();
+        let client_transport = connect(&mut server_transport);
+        let server_address = client_transport.server_address().unwrap();
+
+        let mut server = Endpoint::new(
+            server_transport,
+            EndpointDescriptor {
+                accept_connections: true,
+                ..EndpointDescriptor::default()
+            },
+        );
+        let mut client = Endpoint::new(client_transport, EndpointDescriptor::default());
+        client.connect(server_address);
+
+        let start = Instant::now();
+        while !client.is_connected(server_address) {
+            assert!(
+                start.elapsed() < Duration::from_secs(2),
+                "Connection timed out"
+            );
+            let time = Instant::now();
+            client.pump(time).unwrap();
+            server.pump(time).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        expect_that!(
+            &server.poll_event(),
+            eq(Some(NetEvent::Connected(client.local_address().unwrap())))
+        );
+    }
+}
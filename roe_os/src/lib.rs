@@ -7,7 +7,8 @@ pub use winit::{
     event::{
         AxisId, ButtonId, DeviceEvent, DeviceId, ElementState, Event, Force as TouchForce,
         KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, ScanCode,
-        StartCause as EventLoopStartCause, TouchPhase, VirtualKeyCode as KeyCode, WindowEvent,
+        StartCause as EventLoopStartCause, Touch, TouchPhase, VirtualKeyCode as KeyCode,
+        WindowEvent,
     },
     event_loop::*,
     monitor::*,
@@ -0,0 +1,101 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+struct OpenNode<T> {
+    priority: f32,
+    cost: f32,
+    node: T,
+}
+
+impl<T> PartialEq for OpenNode<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for OpenNode<T> {}
+
+impl<T> PartialOrd for OpenNode<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for OpenNode<T> {
+    // The heap is a max-heap: lower priorities come first, ties are broken in favor of the node
+    // closer to the goal.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| self.cost.total_cmp(&other.cost))
+    }
+}
+
+// Generic A* search. The successors function receives the node being expanded and its parent,
+// and fills the output vector with the reachable nodes and the cost to reach them.
+pub(crate) fn astar<T, S, H>(
+    start: T,
+    goal: T,
+    mut successors: S,
+    heuristic: H,
+) -> Option<(Vec<T>, f32)>
+where
+    T: Copy + Eq + Hash,
+    S: FnMut(T, Option<T>, &mut Vec<(T, f32)>),
+    H: Fn(T) -> f32,
+{
+    let mut open = BinaryHeap::new();
+    let mut visited: HashMap<T, (f32, Option<T>)> = HashMap::new();
+    let mut next = Vec::new();
+
+    visited.insert(start, (0., None));
+    open.push(OpenNode {
+        priority: heuristic(start),
+        cost: 0.,
+        node: start,
+    });
+
+    while let Some(OpenNode { cost, node, .. }) = open.pop() {
+        let (best_cost, parent) = visited[&node];
+        if cost > best_cost {
+            continue;
+        }
+        if node == goal {
+            let mut path = vec![node];
+            let mut current = parent;
+            while let Some(n) = current {
+                path.push(n);
+                current = visited[&n].1;
+            }
+            path.reverse();
+            return Some((path, cost));
+        }
+
+        next.clear();
+        successors(node, parent, &mut next);
+        for &(successor, step_cost) in next.iter() {
+            let successor_cost = cost + step_cost;
+            match visited.entry(successor) {
+                Entry::Occupied(mut e) => {
+                    if successor_cost >= e.get().0 {
+                        continue;
+                    }
+                    e.insert((successor_cost, Some(node)));
+                }
+                Entry::Vacant(e) => {
+                    e.insert((successor_cost, Some(node)));
+                }
+            }
+            open.push(OpenNode {
+                priority: successor_cost + heuristic(successor),
+                cost: successor_cost,
+                node: successor,
+            });
+        }
+    }
+    None
+}
@@ -0,0 +1,571 @@
+use super::astar::astar;
+
+use roe_math::Vector2;
+
+use std::f32::consts::SQRT_2;
+
+pub trait GridMap {
+    fn width(&self) -> u32;
+
+    fn height(&self) -> u32;
+
+    // Cost of entering the cell, or None if the cell can't be walked on. Only called for
+    // positions inside the grid.
+    fn cost(&self, position: Vector2<i32>) -> Option<f32>;
+
+    // Lower bound of the cell costs, used to scale the search heuristic.
+    fn min_cost(&self) -> f32 {
+        1.
+    }
+
+    fn contains(&self, position: Vector2<i32>) -> bool {
+        position.x >= 0
+            && position.y >= 0
+            && (position.x as u32) < self.width()
+            && (position.y as u32) < self.height()
+    }
+
+    fn is_walkable(&self, position: Vector2<i32>) -> bool {
+        self.contains(position) && self.cost(position).is_some()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Grid {
+    width: u32,
+    height: u32,
+    costs: Vec<Option<f32>>,
+}
+
+impl Grid {
+    // Creates a grid where all cells are walkable with cost 1.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            costs: vec![Some(1.); width as usize * height as usize],
+        }
+    }
+
+    pub fn set_cost(&mut self, position: Vector2<i32>, cost: Option<f32>) {
+        if let Some(cost) = cost {
+            assert!(cost >= 1., "The cell cost must be at least 1");
+        }
+        let index = self.index(position);
+        self.costs[index] = cost;
+    }
+
+    pub fn set_walkable(&mut self, position: Vector2<i32>, walkable: bool) {
+        self.set_cost(position, if walkable { Some(1.) } else { None });
+    }
+
+    fn index(&self, position: Vector2<i32>) -> usize {
+        assert!(self.contains(position), "The position is outside the grid");
+        position.y as usize * self.width as usize + position.x as usize
+    }
+}
+
+impl GridMap for Grid {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn cost(&self, position: Vector2<i32>) -> Option<f32> {
+        self.costs[self.index(position)]
+    }
+}
+
+// Grid map whose costs are computed by a function, e.g. to make different kinds of agents
+// traverse the same terrain differently.
+pub struct FnGridMap<F> {
+    width: u32,
+    height: u32,
+    min_cost: f32,
+    cost_fn: F,
+}
+
+impl<F> FnGridMap<F>
+where
+    F: Fn(Vector2<i32>) -> Option<f32>,
+{
+    pub fn new(width: u32, height: u32, min_cost: f32, cost_fn: F) -> Self {
+        assert!(min_cost > 0., "The minimum cost must be higher than 0");
+        Self {
+            width,
+            height,
+            min_cost,
+            cost_fn,
+        }
+    }
+}
+
+impl<F> GridMap for FnGridMap<F>
+where
+    F: Fn(Vector2<i32>) -> Option<f32>,
+{
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn cost(&self, position: Vector2<i32>) -> Option<f32> {
+        (self.cost_fn)(position)
+    }
+
+    fn min_cost(&self) -> f32 {
+        self.min_cost
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GridPathDescriptor {
+    // Diagonal moves are only allowed when both adjacent orthogonal cells are walkable.
+    pub allow_diagonals: bool,
+}
+
+impl Default for GridPathDescriptor {
+    fn default() -> Self {
+        Self {
+            allow_diagonals: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GridPath {
+    // All cells from the start to the goal, both included.
+    pub points: Vec<Vector2<i32>>,
+    pub cost: f32,
+}
+
+const ORTHOGONAL_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+fn octile_distance(a: Vector2<i32>, b: Vector2<i32>) -> f32 {
+    let dx = (a.x - b.x).abs();
+    let dy = (a.y - b.y).abs();
+    (dx.max(dy) - dx.min(dy)) as f32 + SQRT_2 * dx.min(dy) as f32
+}
+
+fn manhattan_distance(a: Vector2<i32>, b: Vector2<i32>) -> f32 {
+    ((a.x - b.x).abs() + (a.y - b.y).abs()) as f32
+}
+
+// A* search on a grid. Moving to a cell costs the cell cost, multiplied by sqrt(2) for diagonal
+// moves.
+pub fn find_grid_path<G: GridMap + ?Sized>(
+    map: &G,
+    start: Vector2<i32>,
+    goal: Vector2<i32>,
+    desc: &GridPathDescriptor,
+) -> Option<GridPath> {
+    if !map.is_walkable(start) || !map.is_walkable(goal) {
+        return None;
+    }
+    let min_cost = map.min_cost();
+    let allow_diagonals = desc.allow_diagonals;
+    let (points, cost) = astar(
+        start,
+        goal,
+        |p, _, successors| {
+            for (dx, dy) in ORTHOGONAL_DIRECTIONS {
+                let n = Vector2::new(p.x + dx, p.y + dy);
+                if map.contains(n) {
+                    if let Some(cost) = map.cost(n) {
+                        successors.push((n, cost));
+                    }
+                }
+            }
+            if allow_diagonals {
+                for (dx, dy) in DIAGONAL_DIRECTIONS {
+                    let n = Vector2::new(p.x + dx, p.y + dy);
+                    if map.is_walkable(Vector2::new(p.x + dx, p.y))
+                        && map.is_walkable(Vector2::new(p.x, p.y + dy))
+                        && map.contains(n)
+                    {
+                        if let Some(cost) = map.cost(n) {
+                            successors.push((n, cost * SQRT_2));
+                        }
+                    }
+                }
+            }
+        },
+        |p| {
+            if allow_diagonals {
+                octile_distance(p, goal) * min_cost
+            } else {
+                manhattan_distance(p, goal) * min_cost
+            }
+        },
+    )?;
+    Some(GridPath { points, cost })
+}
+
+// Jump point search on a grid with diagonal movement. It is much faster than A* on large open
+// areas, but ignores the cell costs: all walkable cells are considered to have cost 1.
+pub fn find_grid_path_jps<G: GridMap + ?Sized>(
+    map: &G,
+    start: Vector2<i32>,
+    goal: Vector2<i32>,
+) -> Option<GridPath> {
+    if !map.is_walkable(start) || !map.is_walkable(goal) {
+        return None;
+    }
+    let (jump_points, cost) = astar(
+        start,
+        goal,
+        |p, parent, successors| {
+            for n in jps_neighbors(map, p, parent) {
+                if let Some(jump_point) = jump(map, n, n - p, goal) {
+                    successors.push((jump_point, octile_distance(p, jump_point)));
+                }
+            }
+        },
+        |p| octile_distance(p, goal),
+    )?;
+
+    let mut points = vec![start];
+    for pair in jump_points.windows(2) {
+        let direction = (pair[1] - pair[0]).map(i32::signum);
+        let mut p = pair[0];
+        while p != pair[1] {
+            p += direction;
+            points.push(p);
+        }
+    }
+    Some(GridPath { points, cost })
+}
+
+fn jps_neighbors<G: GridMap + ?Sized>(
+    map: &G,
+    p: Vector2<i32>,
+    parent: Option<Vector2<i32>>,
+) -> Vec<Vector2<i32>> {
+    let walkable = |x, y| map.is_walkable(Vector2::new(x, y));
+    let mut neighbors = Vec::new();
+    let (x, y) = (p.x, p.y);
+    match parent {
+        None => {
+            for (dx, dy) in ORTHOGONAL_DIRECTIONS {
+                if walkable(x + dx, y + dy) {
+                    neighbors.push(Vector2::new(x + dx, y + dy));
+                }
+            }
+            for (dx, dy) in DIAGONAL_DIRECTIONS {
+                if walkable(x + dx, y) && walkable(x, y + dy) && walkable(x + dx, y + dy) {
+                    neighbors.push(Vector2::new(x + dx, y + dy));
+                }
+            }
+        }
+        Some(parent) => {
+            let d = (p - parent).map(i32::signum);
+            let (dx, dy) = (d.x, d.y);
+            if dx != 0 && dy != 0 {
+                if walkable(x, y + dy) {
+                    neighbors.push(Vector2::new(x, y + dy));
+                }
+                if walkable(x + dx, y) {
+                    neighbors.push(Vector2::new(x + dx, y));
+                }
+                if walkable(x, y + dy) && walkable(x + dx, y) && walkable(x + dx, y + dy) {
+                    neighbors.push(Vector2::new(x + dx, y + dy));
+                }
+            } else if dx != 0 {
+                let next = walkable(x + dx, y);
+                let up = walkable(x, y + 1);
+                let down = walkable(x, y - 1);
+                if next {
+                    neighbors.push(Vector2::new(x + dx, y));
+                    if up && walkable(x + dx, y + 1) {
+                        neighbors.push(Vector2::new(x + dx, y + 1));
+                    }
+                    if down && walkable(x + dx, y - 1) {
+                        neighbors.push(Vector2::new(x + dx, y - 1));
+                    }
+                }
+                if up {
+                    neighbors.push(Vector2::new(x, y + 1));
+                }
+                if down {
+                    neighbors.push(Vector2::new(x, y - 1));
+                }
+            } else {
+                let next = walkable(x, y + dy);
+                let right = walkable(x + 1, y);
+                let left = walkable(x - 1, y);
+                if next {
+                    neighbors.push(Vector2::new(x, y + dy));
+                    if right && walkable(x + 1, y + dy) {
+                        neighbors.push(Vector2::new(x + 1, y + dy));
+                    }
+                    if left && walkable(x - 1, y + dy) {
+                        neighbors.push(Vector2::new(x - 1, y + dy));
+                    }
+                }
+                if right {
+                    neighbors.push(Vector2::new(x + 1, y));
+                }
+                if left {
+                    neighbors.push(Vector2::new(x - 1, y));
+                }
+            }
+        }
+    }
+    neighbors
+}
+
+// Moves from p in the given direction until reaching the goal, a cell with forced neighbors, or
+// an obstacle.
+fn jump<G: GridMap + ?Sized>(
+    map: &G,
+    mut p: Vector2<i32>,
+    direction: Vector2<i32>,
+    goal: Vector2<i32>,
+) -> Option<Vector2<i32>> {
+    let walkable = |x, y| map.is_walkable(Vector2::new(x, y));
+    let (dx, dy) = (direction.x, direction.y);
+    loop {
+        let (x, y) = (p.x, p.y);
+        if !walkable(x, y) {
+            return None;
+        }
+        if p == goal {
+            return Some(p);
+        }
+        if dx != 0 && dy != 0 {
+            if jump(map, Vector2::new(x + dx, y), Vector2::new(dx, 0), goal).is_some()
+                || jump(map, Vector2::new(x, y + dy), Vector2::new(0, dy), goal).is_some()
+            {
+                return Some(p);
+            }
+        } else if dx != 0 {
+            if (walkable(x, y - 1) && !walkable(x - dx, y - 1))
+                || (walkable(x, y + 1) && !walkable(x - dx, y + 1))
+            {
+                return Some(p);
+            }
+        } else if (walkable(x - 1, y) && !walkable(x - 1, y - dy))
+            || (walkable(x + 1, y) && !walkable(x + 1, y - dy))
+        {
+            return Some(p);
+        }
+        if walkable(x + dx, y) && walkable(x, y + dy) {
+            p += direction;
+        } else {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn grid_from_str(rows: &[&str]) -> Grid {
+        let mut grid = Grid::new(rows[0].len() as u32, rows.len() as u32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let p = Vector2::new(x as i32, y as i32);
+                match c {
+                    '#' => grid.set_walkable(p, false),
+                    '~' => grid.set_cost(p, Some(5.)),
+                    _ => (),
+                }
+            }
+        }
+        grid
+    }
+
+    fn is_valid_path<G: GridMap>(map: &G, path: &GridPath) -> bool {
+        path.points.iter().all(|p| map.is_walkable(*p))
+            && path.points.windows(2).all(|pair| {
+                let d = pair[1] - pair[0];
+                d.x.abs() <= 1
+                    && d.y.abs() <= 1
+                    && d != Vector2::zeros()
+                    && map.is_walkable(Vector2::new(pair[1].x, pair[0].y))
+                    && map.is_walkable(Vector2::new(pair[0].x, pair[1].y))
+            })
+    }
+
+    #[test]
+    fn straight_path() {
+        let grid = Grid::new(5, 1);
+        let path = find_grid_path(
+            &grid,
+            Vector2::new(0, 0),
+            Vector2::new(4, 0),
+            &GridPathDescriptor::default(),
+        )
+        .unwrap();
+        expect_that!(&path.points.len(), eq(5));
+        expect_that!(&path.cost, close_to(4., 1e-6));
+    }
+
+    #[test]
+    fn path_around_wall() {
+        let grid = grid_from_str(&[
+            ".....", //
+            ".###.", //
+            "...#.", //
+        ]);
+        let path = find_grid_path(
+            &grid,
+            Vector2::new(0, 2),
+            Vector2::new(4, 2),
+            &GridPathDescriptor {
+                allow_diagonals: false,
+            },
+        )
+        .unwrap();
+        expect_that!(is_valid_path(&grid, &path));
+        expect_that!(&path.points[0], eq(Vector2::new(0, 2)));
+        expect_that!(&path.points[path.points.len() - 1], eq(Vector2::new(4, 2)));
+        expect_that!(&path.cost, close_to(8., 1e-6));
+    }
+
+    #[test]
+    fn diagonal_path() {
+        let grid = Grid::new(4, 4);
+        let path = find_grid_path(
+            &grid,
+            Vector2::new(0, 0),
+            Vector2::new(3, 3),
+            &GridPathDescriptor::default(),
+        )
+        .unwrap();
+        expect_that!(&path.points.len(), eq(4));
+        expect_that!(&path.cost, close_to(3. * SQRT_2, 1e-5));
+    }
+
+    #[test]
+    fn no_corner_cutting() {
+        let grid = grid_from_str(&[
+            ".#", //
+            "..", //
+        ]);
+        let path = find_grid_path(
+            &grid,
+            Vector2::new(0, 0),
+            Vector2::new(1, 1),
+            &GridPathDescriptor::default(),
+        )
+        .unwrap();
+        expect_that!(&path.points.len(), eq(3));
+    }
+
+    #[test]
+    fn cell_costs() {
+        let grid = grid_from_str(&[
+            ".~.", //
+            "...", //
+        ]);
+        let path = find_grid_path(
+            &grid,
+            Vector2::new(0, 0),
+            Vector2::new(2, 0),
+            &GridPathDescriptor {
+                allow_diagonals: false,
+            },
+        )
+        .unwrap();
+        expect_that!(&path.points.len(), eq(5));
+        expect_that!(&path.cost, close_to(4., 1e-6));
+    }
+
+    #[test]
+    fn cost_function() {
+        // Cells in the middle row are cheap, the others expensive.
+        let map = FnGridMap::new(5, 3, 1., |p| Some(if p.y == 1 { 1. } else { 10. }));
+        let path = find_grid_path(
+            &map,
+            Vector2::new(0, 0),
+            Vector2::new(4, 0),
+            &GridPathDescriptor {
+                allow_diagonals: false,
+            },
+        )
+        .unwrap();
+        expect_that!(&path.points.len(), eq(7));
+        expect_that!(&path.cost, close_to(15., 1e-6));
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let grid = grid_from_str(&[
+            "..#..", //
+            "..#..", //
+        ]);
+        let desc = GridPathDescriptor::default();
+        expect_that!(
+            &find_grid_path(&grid, Vector2::new(0, 0), Vector2::new(4, 0), &desc),
+            eq(None)
+        );
+        expect_that!(
+            &find_grid_path_jps(&grid, Vector2::new(0, 0), Vector2::new(4, 0)),
+            eq(None)
+        );
+        expect_that!(
+            &find_grid_path(&grid, Vector2::new(0, 0), Vector2::new(2, 0), &desc),
+            eq(None)
+        );
+        expect_that!(
+            &find_grid_path(&grid, Vector2::new(0, 0), Vector2::new(9, 0), &desc),
+            eq(None)
+        );
+    }
+
+    #[test]
+    fn start_is_goal() {
+        let grid = Grid::new(2, 2);
+        let path = find_grid_path_jps(&grid, Vector2::new(1, 1), Vector2::new(1, 1)).unwrap();
+        expect_that!(&path.points, eq(vec![Vector2::new(1, 1)]));
+        expect_that!(&path.cost, eq(0.));
+    }
+
+    #[test]
+    fn jps_matches_astar() {
+        // Simple linear congruential generator, to have reproducible random grids.
+        let mut seed = 12345u32;
+        let mut random = move || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % 100
+        };
+
+        for _ in 0..50 {
+            let mut grid = Grid::new(20, 20);
+            for y in 0..20 {
+                for x in 0..20 {
+                    if random() < 30 {
+                        grid.set_walkable(Vector2::new(x, y), false);
+                    }
+                }
+            }
+            let start = Vector2::new(0, 0);
+            let goal = Vector2::new(19, 19);
+            grid.set_walkable(start, true);
+            grid.set_walkable(goal, true);
+
+            let astar_path = find_grid_path(&grid, start, goal, &GridPathDescriptor::default());
+            let jps_path = find_grid_path_jps(&grid, start, goal);
+            match (astar_path, jps_path) {
+                (Some(astar_path), Some(jps_path)) => {
+                    expect_that!(is_valid_path(&grid, &jps_path));
+                    expect_that!(&jps_path.cost, close_to(astar_path.cost, 1e-3));
+                    expect_that!(&jps_path.points.first(), eq(Some(&start)));
+                    expect_that!(&jps_path.points.last(), eq(Some(&goal)));
+                }
+                (None, None) => (),
+                _ => panic!("A* and JPS disagree on the existence of a path"),
+            }
+        }
+    }
+}
@@ -0,0 +1,10 @@
+mod astar;
+
+mod grid;
+pub use grid::*;
+
+mod navmesh;
+pub use navmesh::*;
+
+mod path_requests;
+pub use path_requests::*;
@@ -0,0 +1,431 @@
+use super::{astar::astar, GridMap};
+
+use roe_math::Vector2;
+
+const EPSILON: f32 = 1e-4;
+
+// Twice the signed area of the triangle abc, positive if c is on the left of the line from a to b.
+fn cross(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> f32 {
+    let ab = b - a;
+    let ac = c - a;
+    ab.x * ac.y - ab.y * ac.x
+}
+
+fn signed_area(polygon: &[Vector2<f32>]) -> f32 {
+    let mut area = 0.;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Link {
+    polygon: usize,
+    // Edges of the portal, as seen when leaving the polygon through it.
+    left: Vector2<f32>,
+    right: Vector2<f32>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct NavMesh {
+    polygons: Vec<Vec<Vector2<f32>>>,
+    centers: Vec<Vector2<f32>>,
+    links: Vec<Vec<Link>>,
+}
+
+impl NavMesh {
+    // Builds a navmesh from the convex polygons covering the walkable area. Polygons are
+    // connected where their edges overlap.
+    pub fn from_polygons(mut polygons: Vec<Vec<Vector2<f32>>>) -> Self {
+        for polygon in polygons.iter_mut() {
+            assert!(
+                polygon.len() >= 3,
+                "The navmesh polygons must have at least 3 vertices"
+            );
+            if signed_area(polygon) < 0. {
+                polygon.reverse();
+            }
+            for i in 0..polygon.len() {
+                let a = polygon[i];
+                let b = polygon[(i + 1) % polygon.len()];
+                let c = polygon[(i + 2) % polygon.len()];
+                assert!(
+                    cross(a, b, c) >= -EPSILON,
+                    "The navmesh polygons must be convex"
+                );
+            }
+        }
+
+        let centers = polygons
+            .iter()
+            .map(|polygon| polygon.iter().sum::<Vector2<f32>>() / polygon.len() as f32)
+            .collect();
+
+        let mut links = vec![Vec::new(); polygons.len()];
+        for i in 0..polygons.len() {
+            for j in (i + 1)..polygons.len() {
+                for (a0, a1) in edges(&polygons[i]) {
+                    for (b0, b1) in edges(&polygons[j]) {
+                        if let Some((s0, s1)) = edge_overlap(a0, a1, b0, b1) {
+                            links[i].push(Link {
+                                polygon: j,
+                                left: s1,
+                                right: s0,
+                            });
+                            links[j].push(Link {
+                                polygon: i,
+                                left: s0,
+                                right: s1,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            polygons,
+            centers,
+            links,
+        }
+    }
+
+    // Builds a navmesh from the walkable cells of a grid, merging them into rectangles. The cell
+    // at (x, y) covers the area from (x, y) * cell_size to (x + 1, y + 1) * cell_size.
+    pub fn from_grid<G: GridMap + ?Sized>(map: &G, cell_size: f32) -> Self {
+        assert!(cell_size > 0., "The cell size must be higher than 0");
+        let width = map.width() as i32;
+        let height = map.height() as i32;
+        let mut covered = vec![false; width as usize * height as usize];
+        let index = |x: i32, y: i32| (y * width + x) as usize;
+        let mut polygons = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                if covered[index(x, y)] || !map.is_walkable(Vector2::new(x, y)) {
+                    continue;
+                }
+                let free = |covered: &Vec<bool>, x, y| {
+                    !covered[index(x, y)] && map.is_walkable(Vector2::new(x, y))
+                };
+
+                let mut x_end = x + 1;
+                while x_end < width && free(&covered, x_end, y) {
+                    x_end += 1;
+                }
+                let mut y_end = y + 1;
+                while y_end < height && (x..x_end).all(|cx| free(&covered, cx, y_end)) {
+                    y_end += 1;
+                }
+                for cy in y..y_end {
+                    for cx in x..x_end {
+                        covered[index(cx, cy)] = true;
+                    }
+                }
+
+                let (x0, y0) = (x as f32 * cell_size, y as f32 * cell_size);
+                let (x1, y1) = (x_end as f32 * cell_size, y_end as f32 * cell_size);
+                polygons.push(vec![
+                    Vector2::new(x0, y0),
+                    Vector2::new(x1, y0),
+                    Vector2::new(x1, y1),
+                    Vector2::new(x0, y1),
+                ]);
+            }
+        }
+
+        Self::from_polygons(polygons)
+    }
+
+    pub fn polygons(&self) -> &[Vec<Vector2<f32>>] {
+        &self.polygons
+    }
+
+    pub fn neighbors(&self, polygon: usize) -> impl Iterator<Item = usize> + '_ {
+        self.links[polygon].iter().map(|link| link.polygon)
+    }
+
+    pub fn polygon_at(&self, point: Vector2<f32>) -> Option<usize> {
+        self.polygons
+            .iter()
+            .position(|polygon| edges(polygon).all(|(a, b)| cross(a, b, point) >= -EPSILON))
+    }
+
+    // Finds the sequence of polygons leading from start to goal, using A* on the polygon
+    // centers.
+    pub fn find_corridor(&self, start: Vector2<f32>, goal: Vector2<f32>) -> Option<Vec<usize>> {
+        let start_polygon = self.polygon_at(start)?;
+        let goal_polygon = self.polygon_at(goal)?;
+        let (corridor, _) = astar(
+            start_polygon,
+            goal_polygon,
+            |polygon, _, successors| {
+                for link in self.links[polygon].iter() {
+                    successors.push((
+                        link.polygon,
+                        (self.centers[link.polygon] - self.centers[polygon]).norm(),
+                    ));
+                }
+            },
+            |polygon| (self.centers[polygon] - self.centers[goal_polygon]).norm(),
+        )?;
+        Some(corridor)
+    }
+
+    // Finds the shortest path from start to goal inside the polygon corridor.
+    pub fn find_path(&self, start: Vector2<f32>, goal: Vector2<f32>) -> Option<Vec<Vector2<f32>>> {
+        let corridor = self.find_corridor(start, goal)?;
+        let portals: Vec<(Vector2<f32>, Vector2<f32>)> = corridor
+            .windows(2)
+            .map(|pair| {
+                let link = self.links[pair[0]]
+                    .iter()
+                    .filter(|link| link.polygon == pair[1])
+                    .max_by(|a, b| {
+                        (a.left - a.right)
+                            .norm_squared()
+                            .total_cmp(&(b.left - b.right).norm_squared())
+                    })
+                    .unwrap();
+                (link.left, link.right)
+            })
+            .collect();
+        Some(funnel(start, goal, &portals))
+    }
+}
+
+fn edges(polygon: &[Vector2<f32>]) -> impl Iterator<Item = (Vector2<f32>, Vector2<f32>)> + '_ {
+    (0..polygon.len()).map(move |i| (polygon[i], polygon[(i + 1) % polygon.len()]))
+}
+
+// Returns the overlapping part of two collinear edges going in opposite directions, ordered as
+// the first edge.
+fn edge_overlap(
+    a0: Vector2<f32>,
+    a1: Vector2<f32>,
+    b0: Vector2<f32>,
+    b1: Vector2<f32>,
+) -> Option<(Vector2<f32>, Vector2<f32>)> {
+    let length = (a1 - a0).norm();
+    if length < EPSILON {
+        return None;
+    }
+    let direction = (a1 - a0) / length;
+    if (b1 - b0).dot(&direction) >= 0.
+        || cross(a0, a1, b0).abs() > EPSILON * length
+        || cross(a0, a1, b1).abs() > EPSILON * length
+    {
+        return None;
+    }
+    let tb0 = (b0 - a0).dot(&direction);
+    let tb1 = (b1 - a0).dot(&direction);
+    let t_min = tb0.min(tb1).max(0.);
+    let t_max = tb0.max(tb1).min(length);
+    if t_max - t_min < EPSILON {
+        return None;
+    }
+    Some((a0 + direction * t_min, a0 + direction * t_max))
+}
+
+// Simple stupid funnel algorithm. Given the portals crossed to go from start to goal, as
+// (left, right) pairs, computes the shortest path through them.
+pub fn funnel(
+    start: Vector2<f32>,
+    goal: Vector2<f32>,
+    portals: &[(Vector2<f32>, Vector2<f32>)],
+) -> Vec<Vector2<f32>> {
+    let mut all_portals = Vec::with_capacity(portals.len() + 2);
+    all_portals.push((start, start));
+    all_portals.extend_from_slice(portals);
+    all_portals.push((goal, goal));
+
+    let mut path = vec![start];
+    let (mut apex, mut left, mut right) = (start, start, start);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut i = 1;
+    while i < all_portals.len() {
+        let (portal_left, portal_right) = all_portals[i];
+
+        if cross(apex, right, portal_right) >= 0. {
+            if apex == right || cross(apex, left, portal_right) < 0. {
+                right = portal_right;
+                right_index = i;
+            } else {
+                // The right side crossed the left one: the left vertex becomes the new apex.
+                apex = left;
+                path.push(apex);
+                right = apex;
+                right_index = left_index;
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        if cross(apex, left, portal_left) <= 0. {
+            if apex == left || cross(apex, right, portal_left) > 0. {
+                left = portal_left;
+                left_index = i;
+            } else {
+                apex = right;
+                path.push(apex);
+                left = apex;
+                left_index = right_index;
+                i = right_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    if path.last() != Some(&goal) {
+        path.push(goal);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Grid;
+    use galvanic_assert::{matchers::*, *};
+
+    fn rectangle(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<Vector2<f32>> {
+        vec![
+            Vector2::new(x0, y0),
+            Vector2::new(x1, y0),
+            Vector2::new(x1, y1),
+            Vector2::new(x0, y1),
+        ]
+    }
+
+    fn path_length(path: &[Vector2<f32>]) -> f32 {
+        path.windows(2).map(|pair| (pair[1] - pair[0]).norm()).sum()
+    }
+
+    #[test]
+    fn links() {
+        // Two squares sharing part of an edge, and a disconnected one. The second polygon is
+        // clockwise.
+        let navmesh = NavMesh::from_polygons(vec![
+            rectangle(0., 0., 2., 2.),
+            rectangle(2., 1., 4., 3.).into_iter().rev().collect(),
+            rectangle(10., 10., 11., 11.),
+        ]);
+        expect_that!(&navmesh.neighbors(0).collect::<Vec<_>>(), eq(vec![1]));
+        expect_that!(&navmesh.neighbors(1).collect::<Vec<_>>(), eq(vec![0]));
+        expect_that!(&navmesh.neighbors(2).count(), eq(0));
+        expect_that!(
+            &navmesh.links[0][0],
+            eq(Link {
+                polygon: 1,
+                left: Vector2::new(2., 2.),
+                right: Vector2::new(2., 1.),
+            })
+        );
+    }
+
+    #[test]
+    fn polygon_at() {
+        let navmesh =
+            NavMesh::from_polygons(vec![rectangle(0., 0., 2., 2.), rectangle(2., 0., 4., 2.)]);
+        expect_that!(&navmesh.polygon_at(Vector2::new(1., 1.)), eq(Some(0)));
+        expect_that!(&navmesh.polygon_at(Vector2::new(3., 0.5)), eq(Some(1)));
+        expect_that!(&navmesh.polygon_at(Vector2::new(5., 1.)), eq(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "The navmesh polygons must be convex")]
+    fn concave_polygon() {
+        NavMesh::from_polygons(vec![vec![
+            Vector2::new(0., 0.),
+            Vector2::new(2., 0.),
+            Vector2::new(1., 0.5),
+            Vector2::new(2., 2.),
+            Vector2::new(0., 2.),
+        ]]);
+    }
+
+    #[test]
+    fn path_in_same_polygon() {
+        let navmesh = NavMesh::from_polygons(vec![rectangle(0., 0., 2., 2.)]);
+        let path = navmesh
+            .find_path(Vector2::new(0.5, 0.5), Vector2::new(1.5, 1.5))
+            .unwrap();
+        expect_that!(
+            &path,
+            eq(vec![Vector2::new(0.5, 0.5), Vector2::new(1.5, 1.5)])
+        );
+    }
+
+    #[test]
+    fn path_around_corner() {
+        // L-shaped corridor: the shortest path touches the inner corner.
+        let navmesh =
+            NavMesh::from_polygons(vec![rectangle(0., 0., 4., 1.), rectangle(3., 1., 4., 4.)]);
+        let path = navmesh
+            .find_path(Vector2::new(0.5, 0.5), Vector2::new(3.5, 3.5))
+            .unwrap();
+        expect_that!(
+            &path,
+            eq(vec![
+                Vector2::new(0.5, 0.5),
+                Vector2::new(3., 1.),
+                Vector2::new(3.5, 3.5)
+            ])
+        );
+    }
+
+    #[test]
+    fn straight_path_through_portals() {
+        let navmesh = NavMesh::from_polygons(vec![
+            rectangle(0., 0., 1., 3.),
+            rectangle(1., 0., 2., 3.),
+            rectangle(2., 0., 3., 3.),
+        ]);
+        let path = navmesh
+            .find_path(Vector2::new(0.5, 0.5), Vector2::new(2.5, 2.5))
+            .unwrap();
+        expect_that!(
+            &path,
+            eq(vec![Vector2::new(0.5, 0.5), Vector2::new(2.5, 2.5)])
+        );
+    }
+
+    #[test]
+    fn path_from_grid() {
+        let mut grid = Grid::new(5, 5);
+        for y in 0..4 {
+            grid.set_walkable(Vector2::new(2, y), false);
+        }
+        let navmesh = NavMesh::from_grid(&grid, 2.);
+        let covered_area: f32 = navmesh.polygons().iter().map(|p| signed_area(p)).sum();
+        expect_that!(&covered_area, close_to(84., 1e-4));
+
+        let start = Vector2::new(1., 1.);
+        let goal = Vector2::new(9., 1.);
+        let path = navmesh.find_path(start, goal).unwrap();
+        expect_that!(&path.first(), eq(Some(&start)));
+        expect_that!(&path.last(), eq(Some(&goal)));
+        expect_that!(path.contains(&Vector2::new(4., 8.)));
+        expect_that!(path.contains(&Vector2::new(6., 8.)));
+        expect_that!(&path_length(&path), close_to(2. * 58f32.sqrt() + 2., 1e-4));
+    }
+
+    #[test]
+    fn no_path() {
+        let navmesh =
+            NavMesh::from_polygons(vec![rectangle(0., 0., 1., 1.), rectangle(2., 0., 3., 1.)]);
+        expect_that!(
+            &navmesh.find_path(Vector2::new(0.5, 0.5), Vector2::new(2.5, 0.5)),
+            eq(None)
+        );
+        expect_that!(
+            &navmesh.find_path(Vector2::new(0.5, 0.5), Vector2::new(5., 5.)),
+            eq(None)
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use super::{find_grid_path, find_grid_path_jps, GridMap, GridPath, GridPathDescriptor, NavMesh};
+
+use roe_math::Vector2;
+
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+pub trait PathFinder {
+    type Point: Send + 'static;
+    type Path: Send + 'static;
+
+    fn find_path(&self, start: Self::Point, goal: Self::Point) -> Option<Self::Path>;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GridSearch {
+    AStar(GridPathDescriptor),
+    JumpPoint,
+}
+
+impl Default for GridSearch {
+    fn default() -> Self {
+        Self::AStar(GridPathDescriptor::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GridPathFinder<G> {
+    pub map: G,
+    pub search: GridSearch,
+}
+
+impl<G: GridMap> PathFinder for GridPathFinder<G> {
+    type Point = Vector2<i32>;
+    type Path = GridPath;
+
+    fn find_path(&self, start: Self::Point, goal: Self::Point) -> Option<Self::Path> {
+        match &self.search {
+            GridSearch::AStar(desc) => find_grid_path(&self.map, start, goal, desc),
+            GridSearch::JumpPoint => find_grid_path_jps(&self.map, start, goal),
+        }
+    }
+}
+
+impl PathFinder for NavMesh {
+    type Point = Vector2<f32>;
+    type Path = Vec<Vector2<f32>>;
+
+    fn find_path(&self, start: Self::Point, goal: Self::Point) -> Option<Self::Path> {
+        NavMesh::find_path(self, start, goal)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct PathRequestId(u64);
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PathResult<P> {
+    pub id: PathRequestId,
+    pub path: Option<P>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PathRequestQueueDescriptor {
+    pub thread_count: usize,
+    // Number of requests processed by a worker thread in one go.
+    pub batch_size: usize,
+}
+
+impl Default for PathRequestQueueDescriptor {
+    fn default() -> Self {
+        Self {
+            thread_count: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            batch_size: 16,
+        }
+    }
+}
+
+type Request<F> = (
+    PathRequestId,
+    <F as PathFinder>::Point,
+    <F as PathFinder>::Point,
+);
+
+struct Batch<F: PathFinder> {
+    path_finder: Arc<F>,
+    requests: Vec<Request<F>>,
+}
+
+// Computes paths on worker threads, so that many agents can request paths without stalling the
+// main loop. Requests are sent to the workers in batches, either when a batch is full or when
+// flush is called. Results are returned in completion order.
+pub struct PathRequestQueue<F: PathFinder> {
+    path_finder: Arc<F>,
+    batch_size: usize,
+    pending: Vec<Request<F>>,
+    in_flight: usize,
+    next_id: u64,
+    batch_sender: Option<mpsc::Sender<Batch<F>>>,
+    result_receiver: mpsc::Receiver<PathResult<F::Path>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl<F> PathRequestQueue<F>
+where
+    F: PathFinder + Send + Sync + 'static,
+{
+    pub fn new(path_finder: F, desc: &PathRequestQueueDescriptor) -> Self {
+        assert!(
+            desc.thread_count > 0,
+            "The thread count must be higher than 0"
+        );
+        assert!(desc.batch_size > 0, "The batch size must be higher than 0");
+
+        let (batch_sender, batch_receiver) = mpsc::channel::<Batch<F>>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let batch_receiver = Arc::new(Mutex::new(batch_receiver));
+        let workers = (0..desc.thread_count)
+            .map(|_| {
+                let batch_receiver = Arc::clone(&batch_receiver);
+                let result_sender = result_sender.clone();
+                thread::spawn(move || loop {
+                    let batch = match batch_receiver.lock().unwrap().recv() {
+                        Ok(batch) => batch,
+                        Err(_) => break,
+                    };
+                    for (id, start, goal) in batch.requests {
+                        let path = batch.path_finder.find_path(start, goal);
+                        if result_sender.send(PathResult { id, path }).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            path_finder: Arc::new(path_finder),
+            batch_size: desc.batch_size,
+            pending: Vec::new(),
+            in_flight: 0,
+            next_id: 0,
+            batch_sender: Some(batch_sender),
+            result_receiver,
+            workers,
+        }
+    }
+
+    pub fn path_finder(&self) -> &F {
+        &self.path_finder
+    }
+
+    // Replaces the path finder, e.g. after the map changed. Requests already sent to the workers
+    // still use the previous one.
+    pub fn set_path_finder(&mut self, path_finder: F) {
+        self.flush();
+        self.path_finder = Arc::new(path_finder);
+    }
+
+    pub fn request(&mut self, start: F::Point, goal: F::Point) -> PathRequestId {
+        let id = PathRequestId(self.next_id);
+        self.next_id += 1;
+        self.pending.push((id, start, goal));
+        if self.pending.len() >= self.batch_size {
+            self.flush();
+        }
+        id
+    }
+
+    // Number of requests whose result hasn't been returned yet.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len() + self.in_flight
+    }
+
+    pub fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let requests = std::mem::take(&mut self.pending);
+        self.in_flight += requests.len();
+        self.batch_sender
+            .as_ref()
+            .unwrap()
+            .send(Batch {
+                path_finder: Arc::clone(&self.path_finder),
+                requests,
+            })
+            .expect("Failed to send the path requests to the worker threads");
+    }
+
+    // Returns a completed result if available, without blocking.
+    pub fn poll_result(&mut self) -> Option<PathResult<F::Path>> {
+        self.flush();
+        let result = self.result_receiver.try_recv().ok()?;
+        self.in_flight -= 1;
+        Some(result)
+    }
+
+    // Blocks until a result is available. Returns None if there are no pending requests.
+    pub fn wait_result(&mut self) -> Option<PathResult<F::Path>> {
+        self.flush();
+        if self.in_flight == 0 {
+            return None;
+        }
+        let result = self
+            .result_receiver
+            .recv()
+            .expect("The path worker threads terminated unexpectedly");
+        self.in_flight -= 1;
+        Some(result)
+    }
+}
+
+impl<F: PathFinder> Drop for PathRequestQueue<F> {
+    fn drop(&mut self) {
+        // Closing the channel makes the workers exit once the remaining batches are processed.
+        self.batch_sender.take();
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Grid;
+    use galvanic_assert::{matchers::*, *};
+
+    use std::collections::HashMap;
+
+    fn test_grid() -> Grid {
+        let mut grid = Grid::new(16, 16);
+        for y in 0..12 {
+            grid.set_walkable(Vector2::new(8, y), false);
+        }
+        grid
+    }
+
+    #[test]
+    fn batched_requests() {
+        let path_finder = GridPathFinder {
+            map: test_grid(),
+            search: GridSearch::default(),
+        };
+        let mut queue = PathRequestQueue::new(
+            path_finder.clone(),
+            &PathRequestQueueDescriptor {
+                thread_count: 4,
+                batch_size: 8,
+            },
+        );
+
+        let mut expected = HashMap::new();
+        for i in 0..50 {
+            let start = Vector2::new(i % 8, i % 16);
+            let goal = Vector2::new(15 - i % 7, (i * 3) % 16);
+            let id = queue.request(start, goal);
+            expected.insert(id, path_finder.find_path(start, goal));
+        }
+        expect_that!(&queue.pending_count(), eq(50));
+
+        let mut received = 0;
+        while let Some(result) = queue.wait_result() {
+            expect_that!(
+                &result.path.map(|p| p.cost),
+                eq(expected[&result.id].clone().map(|p| p.cost))
+            );
+            received += 1;
+        }
+        expect_that!(&received, eq(50));
+        expect_that!(&queue.pending_count(), eq(0));
+        expect_that!(&queue.poll_result(), eq(None));
+    }
+
+    #[test]
+    fn navmesh_requests() {
+        let navmesh = NavMesh::from_grid(&test_grid(), 1.);
+        let mut queue = PathRequestQueue::new(navmesh, &PathRequestQueueDescriptor::default());
+        let reachable = queue.request(Vector2::new(0.5, 0.5), Vector2::new(15.5, 0.5));
+        let unreachable = queue.request(Vector2::new(0.5, 0.5), Vector2::new(20., 0.5));
+
+        let mut results = HashMap::new();
+        while let Some(result) = queue.wait_result() {
+            results.insert(result.id, result.path);
+        }
+        expect_that!(&results[&reachable].as_ref().map(|p| p.len()), eq(Some(4)));
+        expect_that!(&results[&unreachable], eq(None));
+    }
+
+    #[test]
+    fn replace_path_finder() {
+        let mut queue = PathRequestQueue::new(
+            GridPathFinder {
+                map: Grid::new(4, 1),
+                search: GridSearch::JumpPoint,
+            },
+            &PathRequestQueueDescriptor::default(),
+        );
+        let before = queue.request(Vector2::new(0, 0), Vector2::new(3, 0));
+
+        let mut map = Grid::new(4, 1);
+        map.set_walkable(Vector2::new(2, 0), false);
+        queue.set_path_finder(GridPathFinder {
+            map,
+            search: GridSearch::JumpPoint,
+        });
+        let after = queue.request(Vector2::new(0, 0), Vector2::new(3, 0));
+
+        let mut results = HashMap::new();
+        while let Some(result) = queue.wait_result() {
+            results.insert(result.id, result.path);
+        }
+        expect_that!(results[&before].is_some());
+        expect_that!(&results[&after], eq(None));
+    }
+}
@@ -0,0 +1,42 @@
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    UnknownAchievement(String),
+    UnknownStat(String),
+    StatTypeMismatch(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::UnknownAchievement(name) => write!(f, "Unknown achievement ({})", name),
+            Self::UnknownStat(name) => write!(f, "Unknown stat ({})", name),
+            Self::StatTypeMismatch(name) => write!(f, "Stat type mismatch ({})", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
@@ -0,0 +1,11 @@
+mod error;
+pub use error::*;
+
+mod platform_services;
+pub use platform_services::*;
+
+mod local_platform_services;
+pub use local_platform_services::*;
+
+mod settings_store;
+pub use settings_store::*;
@@ -0,0 +1,300 @@
+use super::{Error, PlatformEvent, PlatformServices, PlatformServicesDescriptor, StatValue};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+// Persistent state of the local platform services.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct LocalPlatformData {
+    pub achievements: BTreeSet<String>,
+    pub stats: BTreeMap<String, StatValue>,
+}
+
+impl LocalPlatformData {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+}
+
+// Platform services without a platform, for development builds and stores without
+// achievements. The state is saved to a RON file, if set.
+#[derive(Debug)]
+pub struct LocalPlatformServices {
+    desc: PlatformServicesDescriptor,
+    path: Option<PathBuf>,
+    data: LocalPlatformData,
+    rich_presence: HashMap<String, String>,
+    events: VecDeque<PlatformEvent>,
+}
+
+impl LocalPlatformServices {
+    pub fn new(desc: &PlatformServicesDescriptor) -> Self {
+        Self {
+            desc: desc.clone(),
+            path: None,
+            data: LocalPlatformData::default(),
+            rich_presence: HashMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    // Loads the state from the file if it exists. Unknown achievements and stats in the file
+    // are ignored.
+    pub fn with_file<P: Into<PathBuf>>(
+        path: P,
+        desc: &PlatformServicesDescriptor,
+    ) -> Result<Self, Error> {
+        let path = path.into();
+        let mut services = Self::new(desc);
+        if path.exists() {
+            let data = LocalPlatformData::load(&path)?;
+            services.data.achievements = data
+                .achievements
+                .into_iter()
+                .filter(|name| services.desc.achievements.contains(name))
+                .collect();
+            for (name, value) in data.stats {
+                if services.check_stat(&name, &value).is_ok() {
+                    services.data.stats.insert(name, value);
+                }
+            }
+        }
+        services.path = Some(path);
+        Ok(services)
+    }
+
+    pub fn descriptor(&self) -> &PlatformServicesDescriptor {
+        &self.desc
+    }
+
+    pub fn data(&self) -> &LocalPlatformData {
+        &self.data
+    }
+
+    pub fn rich_presence(&self, key: &str) -> Option<&str> {
+        self.rich_presence.get(key).map(|value| value.as_str())
+    }
+
+    fn check_achievement(&self, name: &str) -> Result<(), Error> {
+        if self.desc.achievements.iter().any(|a| a == name) {
+            Ok(())
+        } else {
+            Err(Error::UnknownAchievement(String::from(name)))
+        }
+    }
+
+    fn check_stat(&self, name: &str, value: &StatValue) -> Result<(), Error> {
+        match self.desc.stats.iter().find(|s| s.name == name) {
+            Some(stat) if stat.default_value.is_same_type(value) => Ok(()),
+            Some(_) => Err(Error::StatTypeMismatch(String::from(name))),
+            None => Err(Error::UnknownStat(String::from(name))),
+        }
+    }
+}
+
+impl PlatformServices for LocalPlatformServices {
+    fn unlock_achievement(&mut self, name: &str) -> Result<(), Error> {
+        self.check_achievement(name)?;
+        if self.data.achievements.insert(String::from(name)) {
+            self.events
+                .push_back(PlatformEvent::AchievementUnlocked(String::from(name)));
+        }
+        Ok(())
+    }
+
+    fn is_achievement_unlocked(&self, name: &str) -> Result<bool, Error> {
+        self.check_achievement(name)?;
+        Ok(self.data.achievements.contains(name))
+    }
+
+    fn clear_achievement(&mut self, name: &str) -> Result<(), Error> {
+        self.check_achievement(name)?;
+        self.data.achievements.remove(name);
+        Ok(())
+    }
+
+    fn stat(&self, name: &str) -> Result<StatValue, Error> {
+        match self.data.stats.get(name) {
+            Some(value) => Ok(*value),
+            None => self
+                .desc
+                .stats
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| s.default_value)
+                .ok_or_else(|| Error::UnknownStat(String::from(name))),
+        }
+    }
+
+    fn set_stat(&mut self, name: &str, value: StatValue) -> Result<(), Error> {
+        self.check_stat(name, &value)?;
+        self.data.stats.insert(String::from(name), value);
+        Ok(())
+    }
+
+    fn set_rich_presence(&mut self, key: &str, value: Option<&str>) -> Result<(), Error> {
+        match value {
+            Some(value) => self
+                .rich_presence
+                .insert(String::from(key), String::from(value)),
+            None => self.rich_presence.remove(key),
+        };
+        Ok(())
+    }
+
+    fn store(&mut self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            self.data.save(path)?;
+        }
+        Ok(())
+    }
+
+    fn poll_event(&mut self) -> Option<PlatformEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StatDescriptor;
+    use galvanic_assert::{matchers::*, *};
+
+    fn descriptor() -> PlatformServicesDescriptor {
+        PlatformServicesDescriptor {
+            achievements: vec![String::from("first_blood"), String::from("pacifist")],
+            stats: vec![
+                StatDescriptor {
+                    name: String::from("kills"),
+                    default_value: StatValue::Int(0),
+                },
+                StatDescriptor {
+                    name: String::from("distance"),
+                    default_value: StatValue::Float(0.),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn achievements() {
+        let mut services = LocalPlatformServices::new(&descriptor());
+        expect_that!(
+            &services.is_achievement_unlocked("first_blood").unwrap(),
+            eq(false)
+        );
+        services.unlock_achievement("first_blood").unwrap();
+        services.unlock_achievement("first_blood").unwrap();
+        expect_that!(
+            &services.is_achievement_unlocked("first_blood").unwrap(),
+            eq(true)
+        );
+        expect_that!(
+            &services.poll_event(),
+            eq(Some(PlatformEvent::AchievementUnlocked(String::from(
+                "first_blood"
+            ))))
+        );
+        expect_that!(&services.poll_event(), eq(None));
+
+        services.clear_achievement("first_blood").unwrap();
+        expect_that!(
+            &services.is_achievement_unlocked("first_blood").unwrap(),
+            eq(false)
+        );
+        expect_that!(
+            &services.unlock_achievement("unknown"),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let mut services = LocalPlatformServices::new(&descriptor());
+        expect_that!(&services.stat("kills").unwrap(), eq(StatValue::Int(0)));
+        services.set_stat("kills", StatValue::Int(3)).unwrap();
+        expect_that!(
+            &services.add_to_stat("kills", StatValue::Int(2)).unwrap(),
+            eq(StatValue::Int(5))
+        );
+        expect_that!(
+            &services
+                .add_to_stat("distance", StatValue::Float(1.5))
+                .unwrap(),
+            eq(StatValue::Float(1.5))
+        );
+        expect_that!(
+            &services.set_stat("kills", StatValue::Float(1.)),
+            is_variant!(Result::Err)
+        );
+        expect_that!(
+            &services.add_to_stat("distance", StatValue::Int(1)),
+            is_variant!(Result::Err)
+        );
+        expect_that!(&services.stat("unknown"), is_variant!(Result::Err));
+        expect_that!(&services.stat("kills").unwrap(), eq(StatValue::Int(5)));
+    }
+
+    #[test]
+    fn rich_presence() {
+        let mut services = LocalPlatformServices::new(&descriptor());
+        services
+            .set_rich_presence("status", Some("In the forest"))
+            .unwrap();
+        expect_that!(&services.rich_presence("status"), eq(Some("In the forest")));
+        services.set_rich_presence("status", None).unwrap();
+        expect_that!(&services.rich_presence("status"), eq(None));
+    }
+
+    #[test]
+    fn persistence() {
+        let path = std::env::temp_dir().join(format!(
+            "roe_platform_local_test_{}.ron",
+            std::process::id()
+        ));
+        let mut services = LocalPlatformServices::with_file(&path, &descriptor()).unwrap();
+        services.unlock_achievement("pacifist").unwrap();
+        services
+            .set_stat("distance", StatValue::Float(12.5))
+            .unwrap();
+        services.store().unwrap();
+
+        let services = LocalPlatformServices::with_file(&path, &descriptor()).unwrap();
+        expect_that!(
+            &services.is_achievement_unlocked("pacifist").unwrap(),
+            eq(true)
+        );
+        expect_that!(
+            &services.stat("distance").unwrap(),
+            eq(StatValue::Float(12.5))
+        );
+
+        // Achievements removed from the game are dropped.
+        let mut desc = descriptor();
+        desc.achievements.clear();
+        let services = LocalPlatformServices::with_file(&path, &desc).unwrap();
+        expect_that!(services.data().achievements.is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
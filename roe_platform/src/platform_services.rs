@@ -0,0 +1,73 @@
+use super::Error;
+
+use serde::{Deserialize, Serialize};
+
+// Stats are either integer or floating point numbers, like on most platforms.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum StatValue {
+    Int(i32),
+    Float(f32),
+}
+
+impl StatValue {
+    pub fn is_same_type(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct StatDescriptor {
+    pub name: String,
+    // Also defines the type of the stat.
+    pub default_value: StatValue,
+}
+
+// Achievements and stats known to the game. They must match the ones configured on the
+// platform, e.g. in the Steamworks settings of the application.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct PlatformServicesDescriptor {
+    pub achievements: Vec<String>,
+    pub stats: Vec<StatDescriptor>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PlatformEvent {
+    // Only sent the first time an achievement is unlocked, e.g. to show a notification when
+    // the platform doesn't.
+    AchievementUnlocked(String),
+}
+
+// Achievements, stats and rich presence of the platform the game runs on. Games use this
+// trait instead of a specific platform SDK, so that the backend can be chosen when building
+// or at startup. Changes may be cached until store is called.
+pub trait PlatformServices {
+    fn unlock_achievement(&mut self, name: &str) -> Result<(), Error>;
+
+    fn is_achievement_unlocked(&self, name: &str) -> Result<bool, Error>;
+
+    // Mostly useful for testing.
+    fn clear_achievement(&mut self, name: &str) -> Result<(), Error>;
+
+    fn stat(&self, name: &str) -> Result<StatValue, Error>;
+
+    // The value must have the same type as the stat.
+    fn set_stat(&mut self, name: &str, value: StatValue) -> Result<(), Error>;
+
+    // Shown to friends, e.g. the current level. A None value removes the key.
+    fn set_rich_presence(&mut self, key: &str, value: Option<&str>) -> Result<(), Error>;
+
+    // Makes the changes persistent.
+    fn store(&mut self) -> Result<(), Error>;
+
+    fn poll_event(&mut self) -> Option<PlatformEvent>;
+
+    fn add_to_stat(&mut self, name: &str, amount: StatValue) -> Result<StatValue, Error> {
+        let value = match (self.stat(name)?, amount) {
+            (StatValue::Int(v), StatValue::Int(a)) => StatValue::Int(v.saturating_add(a)),
+            (StatValue::Float(v), StatValue::Float(a)) => StatValue::Float(v + a),
+            _ => return Err(Error::StatTypeMismatch(String::from(name))),
+        };
+        self.set_stat(name, value)?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,112 @@
+use super::Error;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use std::{collections::BTreeMap, path::Path};
+
+// User settings of the different subsystems, e.g. graphics or audio options, saved together to
+// a RON file. Each subsystem reads and writes its own named section. The sections are stored as
+// RON strings, since RON values don't preserve the names of enum variants.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct SettingsStore {
+    sections: BTreeMap<String, String>,
+}
+
+impl SettingsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+
+    pub fn has_section(&self, name: &str) -> bool {
+        self.sections.contains_key(name)
+    }
+
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(String::as_str)
+    }
+
+    // None if the section was never written.
+    pub fn section<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, Error> {
+        match self.sections.get(name) {
+            Some(s) => Ok(Some(ron::de::from_str(s)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_section<T: Serialize>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        self.sections
+            .insert(String::from(name), ron::ser::to_string(value)?);
+        Ok(())
+    }
+
+    // Returns false if there was no such section.
+    pub fn remove_section(&mut self, name: &str) -> bool {
+        self.sections.remove(name).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+    enum Quality {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+    struct AudioSettings {
+        volume: f32,
+        quality: Quality,
+    }
+
+    #[test]
+    fn sections() {
+        let mut store = SettingsStore::new();
+        expect_that!(&store.section::<AudioSettings>("audio").unwrap(), eq(None));
+
+        let audio = AudioSettings {
+            volume: 0.5,
+            quality: Quality::High,
+        };
+        store.set_section("audio", &audio).unwrap();
+        store.set_section("language", &"it").unwrap();
+        expect_that!(&store.section("audio").unwrap(), eq(Some(audio)));
+        expect_that!(
+            &store.section_names().collect::<Vec<_>>(),
+            eq(vec!["audio", "language"])
+        );
+        expect_that!(store.section::<Quality>("audio").is_err());
+
+        expect_that!(&store.remove_section("language"), eq(true));
+        expect_that!(&store.remove_section("language"), eq(false));
+        expect_that!(&store.has_section("language"), eq(false));
+
+        let s = store.to_ron_string().unwrap();
+        let loaded = SettingsStore::from_ron_str(&s).unwrap();
+        expect_that!(&loaded, eq(store));
+        expect_that!(&loaded.section("audio").unwrap(), eq(Some(audio)));
+    }
+}
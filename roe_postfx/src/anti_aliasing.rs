@@ -0,0 +1,729 @@
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix3, Vector2};
+
+fn texture_entry(binding: u32) -> gfx::BindGroupLayoutEntry {
+    gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: true },
+            view_dimension: gfx::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+// Texture bindings followed by the sampler.
+fn bind_group_layout(instance: &gfx::Instance, texture_count: u32) -> gfx::BindGroupLayout {
+    let mut entries: Vec<_> = (0..texture_count).map(texture_entry).collect();
+    entries.push(gfx::BindGroupLayoutEntry {
+        binding: texture_count,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Sampler {
+            filtering: true,
+            comparison: false,
+        },
+        count: None,
+    });
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &entries,
+        },
+    )
+}
+
+fn create_bind_group(
+    instance: &gfx::Instance,
+    textures: &[&gfx::TextureView],
+    sampler: &gfx::Sampler,
+) -> gfx::BindGroup {
+    let layout = bind_group_layout(instance, textures.len() as u32);
+    let mut entries: Vec<_> = textures
+        .iter()
+        .enumerate()
+        .map(|(i, texture)| gfx::BindGroupEntry {
+            binding: i as u32,
+            resource: gfx::BindingResource::TextureView(texture),
+        })
+        .collect();
+    entries.push(gfx::BindGroupEntry {
+        binding: textures.len() as u32,
+        resource: gfx::BindingResource::Sampler(sampler),
+    });
+    gfx::BindGroup::new(
+        instance,
+        &gfx::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &entries,
+        },
+    )
+}
+
+fn create_pipeline(
+    instance: &gfx::Instance,
+    label: Option<&str>,
+    bind_group_layout: &gfx::BindGroupLayout,
+    push_constants_size: u32,
+    fs_module: &gfx::ShaderModule,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    sample_count: gfx::SampleCount,
+) -> gfx::RenderPipeline {
+    let pipeline_layout = gfx::PipelineLayout::new(
+        instance,
+        &gfx::PipelineLayoutDescriptor {
+            label,
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[gfx::PushConstantRange {
+                stages: gfx::ShaderStage::FRAGMENT,
+                range: 0..push_constants_size,
+            }],
+        },
+    );
+    let vs_module = gfx::ShaderModule::new(
+        instance,
+        &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+    );
+    gfx::RenderPipeline::new(
+        instance,
+        &gfx::RenderPipelineDescriptor {
+            label,
+            layout: Some(&pipeline_layout),
+            vertex: gfx::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[],
+            },
+            primitive: gfx::PrimitiveState {
+                topology: gfx::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: gfx::FrontFace::Ccw,
+                cull_mode: None,
+                clamp_depth: false,
+                polygon_mode: gfx::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: gfx::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(gfx::FragmentState {
+                module: fs_module,
+                entry_point: "main",
+                targets: &[gfx::ColorTargetState {
+                    format: gfx::TextureFormat::from(color_buffer_format),
+                    blend: None,
+                    write_mask: gfx::ColorWrite::ALL,
+                }],
+            }),
+        },
+    )
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FxaaSettings {
+    // Minimum local contrast, relative to the brightest neighbor, for a pixel to be smoothed.
+    // Lower values smooth more edges, and blur more texture detail.
+    pub edge_threshold: f32,
+    // Minimum local contrast in dark areas.
+    pub edge_threshold_min: f32,
+    // Maximum length of the blur along the edges, in pixels.
+    pub span_max: f32,
+}
+
+impl Default for FxaaSettings {
+    fn default() -> Self {
+        Self {
+            edge_threshold: 0.125,
+            edge_threshold_min: 0.0625,
+            span_max: 8.,
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct FxaaPushConstants {
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+    span_max: f32,
+    color_conversion: u32,
+}
+
+unsafe impl bytemuck::Zeroable for FxaaPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            edge_threshold: 0.,
+            edge_threshold_min: 0.,
+            span_max: 0.,
+            color_conversion: 0,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for FxaaPushConstants {}
+
+// The final color buffer, sampled with a linear filter.
+#[derive(Debug)]
+pub struct FxaaUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl FxaaUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        Self {
+            bind_group: create_bind_group(instance, &[texture], sampler),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FxaaPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for FxaaPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+// Fast approximate anti-aliasing, drawn as a full screen pass over the final image, after tone
+// mapping. Cheaper than MSAA, and it also smooths the edges produced by post processing, at
+// the cost of some blur.
+#[derive(Debug)]
+pub struct FxaaPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl FxaaPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &FxaaPipelineDescriptor) -> Self {
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/fxaa.frag.spv"),
+        );
+        let pipeline = create_pipeline(
+            instance,
+            desc.label.as_deref(),
+            &bind_group_layout(instance, 1),
+            std::mem::size_of::<FxaaPushConstants>() as u32,
+            &fs_module,
+            desc.color_buffer_format,
+            desc.sample_count,
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait FxaaRenderer<'a> {
+    fn draw_fxaa(
+        &mut self,
+        pipeline: &'a FxaaPipeline,
+        uniform_constants: &'a FxaaUniformConstants,
+        settings: &FxaaSettings,
+    );
+}
+
+impl<'a> FxaaRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_fxaa(
+        &mut self,
+        pipeline: &'a FxaaPipeline,
+        uniform_constants: &'a FxaaUniformConstants,
+        settings: &FxaaSettings,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            bytemuck::bytes_of(&FxaaPushConstants {
+                edge_threshold: settings.edge_threshold,
+                edge_threshold_min: settings.edge_threshold_min,
+                span_max: settings.span_max,
+                color_conversion: pipeline.color_conversion as u32,
+            }),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.;
+    let mut result = 0.;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+// Sub-pixel offsets applied to the projection on successive frames, so that the TAA history
+// accumulates samples from different positions within each pixel. Follows the Halton (2, 3)
+// sequence.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TaaJitter {
+    sample_count: u32,
+    frame: u32,
+}
+
+impl TaaJitter {
+    pub fn new(sample_count: u32) -> Self {
+        Self {
+            sample_count: sample_count.max(1),
+            frame: 0,
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // In pixels, from -0.5 to 0.5.
+    pub fn offset(&self) -> Vector2<f32> {
+        // The sequence starts from 1, since all coordinates are 0 for index 0.
+        let index = self.frame % self.sample_count + 1;
+        Vector2::new(halton(index, 2) - 0.5, halton(index, 3) - 0.5)
+    }
+
+    pub fn advance(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    pub fn reset(&mut self) {
+        self.frame = 0;
+    }
+
+    // To be multiplied on the left of the projection, shifting the image by the current offset.
+    pub fn projection_offset(&self, canvas_size: &gfx::CanvasSize) -> HomogeneousMatrix3<f32> {
+        let offset = self.offset();
+        let mut out = HomogeneousMatrix3::identity();
+        out[(0, 3)] = offset.x * 2. / canvas_size.width() as f32;
+        out[(1, 3)] = -offset.y * 2. / canvas_size.height() as f32;
+        out
+    }
+}
+
+impl Default for TaaJitter {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+// Anti-aliasing pass selected by the graphics settings: none, FxaaRenderer::draw_fxaa, or the
+// TAA resolve with the jitter. The jitter restarts each time the mode changes, in which case the
+// TAA history must be invalidated.
+#[derive(Debug)]
+pub struct AntiAliasing {
+    mode: gfx::AntiAliasingMode,
+    jitter: TaaJitter,
+    graphics_settings: Option<gfx::GraphicsSettingsSubscriber>,
+}
+
+impl AntiAliasing {
+    pub fn new(mode: gfx::AntiAliasingMode, jitter: TaaJitter) -> Self {
+        Self {
+            mode,
+            jitter,
+            graphics_settings: None,
+        }
+    }
+
+    pub fn mode(&self) -> gfx::AntiAliasingMode {
+        self.mode
+    }
+
+    // Returns true if the mode changed.
+    pub fn set_mode(&mut self, mode: gfx::AntiAliasingMode) -> bool {
+        if mode == self.mode {
+            return false;
+        }
+        self.mode = mode;
+        self.jitter.reset();
+        true
+    }
+
+    pub fn jitter(&self) -> &TaaJitter {
+        &self.jitter
+    }
+
+    // Call once per frame, after the TAA resolve.
+    pub fn advance(&mut self) {
+        if self.mode == gfx::AntiAliasingMode::Taa {
+            self.jitter.advance();
+        }
+    }
+
+    // The jitter offset with TAA, the identity otherwise.
+    pub fn projection_offset(&self, canvas_size: &gfx::CanvasSize) -> HomogeneousMatrix3<f32> {
+        match self.mode {
+            gfx::AntiAliasingMode::Taa => self.jitter.projection_offset(canvas_size),
+            _ => HomogeneousMatrix3::identity(),
+        }
+    }
+
+    // The mode follows the anti-aliasing mode of the settings, starting from the current one.
+    // Returns true if the mode changed.
+    pub fn subscribe_graphics_settings(&mut self, settings: &gfx::RuntimeGraphicsSettings) -> bool {
+        let subscriber = settings.subscribe(gfx::GraphicsSettingsChanges::POST_EFFECTS);
+        let current = *subscriber.settings();
+        self.graphics_settings = Some(subscriber);
+        self.apply_graphics_settings(&current)
+    }
+
+    // Applies the settings changed since the last update, e.g. at the start of a frame. Returns
+    // true if the mode changed.
+    pub fn update_graphics_settings(&mut self) -> bool {
+        match self.graphics_settings.as_mut().and_then(|s| s.poll()) {
+            Some(update) => self.apply_graphics_settings(&update.settings),
+            None => false,
+        }
+    }
+
+    pub fn apply_graphics_settings(&mut self, settings: &gfx::GraphicsSettings) -> bool {
+        self.set_mode(settings.post_effects.anti_aliasing)
+    }
+}
+
+impl Default for AntiAliasing {
+    fn default() -> Self {
+        Self::new(gfx::AntiAliasingMode::default(), TaaJitter::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TaaSettings {
+    // Weight of the history in the resolved color. Higher values smooth more, and ghost more.
+    pub feedback: f32,
+}
+
+impl Default for TaaSettings {
+    fn default() -> Self {
+        Self { feedback: 0.9 }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TaaHistoryDescriptor {
+    pub label: Option<String>,
+    // Must match the TAA pipeline.
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+}
+
+impl Default for TaaHistoryDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::Rgba16Float,
+        }
+    }
+}
+
+// Pair of canvases the TAA resolve alternates between: each frame the resolve reads the
+// previous result and writes to the other canvas, which holds the anti-aliased image.
+#[derive(Debug)]
+pub struct TaaHistory {
+    desc: TaaHistoryDescriptor,
+    canvases: [gfx::CanvasTexture; 2],
+    target: usize,
+    valid: bool,
+}
+
+impl TaaHistory {
+    pub fn new(
+        instance: &gfx::Instance,
+        desc: &TaaHistoryDescriptor,
+        size: &gfx::CanvasSize,
+    ) -> Result<Self, gfx::CanvasBufferError> {
+        Ok(Self {
+            desc: desc.clone(),
+            canvases: [
+                Self::create_canvas(instance, desc, size)?,
+                Self::create_canvas(instance, desc, size)?,
+            ],
+            target: 0,
+            valid: false,
+        })
+    }
+
+    // Recreates the canvases, discarding the history.
+    pub fn resize(
+        &mut self,
+        instance: &gfx::Instance,
+        size: &gfx::CanvasSize,
+    ) -> Result<(), gfx::CanvasBufferError> {
+        *self = Self::new(instance, &self.desc, size)?;
+        Ok(())
+    }
+
+    pub fn size(&self) -> &gfx::CanvasSize {
+        gfx::Canvas::canvas_size(&self.canvases[0])
+    }
+
+    // False until the first resolve, or after invalidate. The resolve then uses the current
+    // frame only.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    // E.g. after a camera cut, when the previous frames are unrelated to the current one.
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    // Canvas the next resolve writes to.
+    pub fn target_mut(&mut self) -> &mut gfx::CanvasTexture {
+        &mut self.canvases[self.target]
+    }
+
+    // Result of the last resolve, read by the next one.
+    pub fn resolved_texture_view(&self) -> &gfx::TextureView {
+        self.canvases[1 - self.target].color_texture_view().unwrap()
+    }
+
+    // Must be called after submitting the resolve.
+    pub fn advance(&mut self) {
+        self.target = 1 - self.target;
+        self.valid = true;
+    }
+
+    fn create_canvas(
+        instance: &gfx::Instance,
+        desc: &TaaHistoryDescriptor,
+        size: &gfx::CanvasSize,
+    ) -> Result<gfx::CanvasTexture, gfx::CanvasBufferError> {
+        gfx::CanvasTexture::new(
+            instance,
+            &gfx::CanvasTextureDescriptor {
+                label: desc.label.clone(),
+                size: *size,
+                sample_count: 1,
+                color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
+                    format: desc.color_buffer_format,
+                    usage: gfx::CanvasColorBufferUsage::TEXTURE_BINDING,
+                    sample_count: None,
+                }),
+                depth_stencil_buffer_format: None,
+            },
+        )
+    }
+}
+
+// The jittered scene color and the history. Must be recreated every frame, since the history
+// alternates between two canvases.
+#[derive(Debug)]
+pub struct TaaUniformConstants {
+    bind_group: gfx::BindGroup,
+    history_valid: bool,
+}
+
+impl TaaUniformConstants {
+    // The scene color buffer must have the same size as the history.
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        history: &TaaHistory,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        Self {
+            bind_group: create_bind_group(
+                instance,
+                &[texture, history.resolved_texture_view()],
+                sampler,
+            ),
+            history_valid: history.is_valid(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TaaPipelineDescriptor {
+    pub label: Option<String>,
+    // Must match the history.
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+}
+
+impl Default for TaaPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: TaaHistoryDescriptor::default().color_buffer_format,
+        }
+    }
+}
+
+// Blends the jittered scene color with the history, clamped to the current neighborhood.
+// No color conversion is applied, since the result is read back as the next history: the
+// resolved image is meant to be drawn to the final target by another pass, e.g. the upscale
+// or the color grading one.
+#[derive(Debug)]
+pub struct TaaPipeline {
+    pipeline: gfx::RenderPipeline,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+}
+
+impl TaaPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &TaaPipelineDescriptor) -> Self {
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/taa.frag.spv"),
+        );
+        let pipeline = create_pipeline(
+            instance,
+            desc.label.as_deref(),
+            &bind_group_layout(instance, 2),
+            std::mem::size_of::<f32>() as u32,
+            &fs_module,
+            desc.color_buffer_format,
+            1,
+        );
+        Self {
+            pipeline,
+            color_buffer_format: desc.color_buffer_format,
+        }
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: 1,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait TaaRenderer<'a> {
+    // To be drawn to TaaHistory::target_mut.
+    fn draw_taa_resolve(
+        &mut self,
+        pipeline: &'a TaaPipeline,
+        uniform_constants: &'a TaaUniformConstants,
+        settings: &TaaSettings,
+    );
+}
+
+impl<'a> TaaRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_taa_resolve(
+        &mut self,
+        pipeline: &'a TaaPipeline,
+        uniform_constants: &'a TaaUniformConstants,
+        settings: &TaaSettings,
+    ) {
+        let feedback = if uniform_constants.history_valid {
+            settings.feedback.clamp(0., 1.)
+        } else {
+            0.
+        };
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(&feedback),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn jitter_sequence() {
+        let mut jitter = TaaJitter::new(4);
+        let mut offsets = Vec::new();
+        for _ in 0..5 {
+            offsets.push(jitter.offset());
+            jitter.advance();
+        }
+        expect_that!((offsets[0] - Vector2::new(0., -1. / 6.)).norm() < 1e-6);
+        expect_that!((offsets[1] - Vector2::new(-0.25, 1. / 6.)).norm() < 1e-6);
+        expect_that!(&offsets[4], eq(offsets[0]));
+        for offset in offsets.iter() {
+            expect_that!(offset.x.abs() <= 0.5 && offset.y.abs() <= 0.5);
+        }
+        jitter.reset();
+        expect_that!(&jitter.offset(), eq(offsets[0]));
+    }
+
+    #[test]
+    fn jitter_projection_offset() {
+        let mut jitter = TaaJitter::default();
+        jitter.advance();
+        let offset = jitter.projection_offset(&gfx::CanvasSize::new(100, 50));
+        expect_that!(&offset[(0, 3)], close_to(-0.005, 1e-6));
+        expect_that!(&offset[(1, 3)], close_to(-1. / 150., 1e-6));
+        expect_that!(&offset[(0, 0)], eq(1.));
+    }
+
+    #[test]
+    fn graphics_settings() {
+        let size = gfx::CanvasSize::new(100, 50);
+        let settings = gfx::RuntimeGraphicsSettings::new(gfx::GraphicsSettings::from_preset(
+            gfx::QualityPreset::Ultra,
+        ));
+        let mut anti_aliasing = AntiAliasing::default();
+        expect_that!(anti_aliasing.subscribe_graphics_settings(&settings));
+        expect_that!(&anti_aliasing.mode(), eq(gfx::AntiAliasingMode::Taa));
+        anti_aliasing.advance();
+        expect_that!(
+            &anti_aliasing.projection_offset(&size),
+            not(eq(HomogeneousMatrix3::identity()))
+        );
+
+        // Other post effects don't affect the mode.
+        settings.modify(|s| s.post_effects.bloom = false);
+        expect_that!(!anti_aliasing.update_graphics_settings());
+        settings.modify(|s| s.post_effects.anti_aliasing = gfx::AntiAliasingMode::Fxaa);
+        expect_that!(anti_aliasing.update_graphics_settings());
+        expect_that!(&anti_aliasing.mode(), eq(gfx::AntiAliasingMode::Fxaa));
+        expect_that!(
+            &anti_aliasing.jitter().offset(),
+            eq(TaaJitter::default().offset())
+        );
+        anti_aliasing.advance();
+        expect_that!(
+            &anti_aliasing.projection_offset(&size),
+            eq(HomogeneousMatrix3::identity())
+        );
+    }
+}
@@ -0,0 +1,496 @@
+use super::ColorFilterPushConstants;
+
+use roe_graphics as gfx;
+use roe_math::Matrix3;
+
+pub const HISTOGRAM_BIN_COUNT: usize = 256;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AutoExposureDescriptor {
+    // Log2 luminance range covered by the histogram. Darker and brighter pixels are counted in
+    // the first and last bins.
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    // Fractions of the pixels ignored at the dark and bright ends of the histogram, so that small
+    // highlights or shadows don't drive the exposure.
+    pub low_percentile: f32,
+    pub high_percentile: f32,
+    // Luminance the average luminance is mapped to.
+    pub key_value: f32,
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    // Adaptation speeds in stops per second, when the exposure increases (entering a dark area)
+    // and when it decreases.
+    pub brightening_speed: f32,
+    pub darkening_speed: f32,
+}
+
+impl Default for AutoExposureDescriptor {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -8.,
+            max_log_luminance: 4.,
+            low_percentile: 0.1,
+            high_percentile: 0.9,
+            key_value: 0.18,
+            min_exposure: 0.1,
+            max_exposure: 10.,
+            brightening_speed: 1.,
+            darkening_speed: 3.,
+        }
+    }
+}
+
+// Average log2 luminance of the pixels between the low and high percentiles. Bin 0 counts the
+// black pixels and is ignored. Returns None if the histogram has no lit pixels.
+pub fn average_log_luminance(histogram: &[u32], desc: &AutoExposureDescriptor) -> Option<f32> {
+    let lit_bins = histogram.get(1..).unwrap_or(&[]);
+    let total: u64 = lit_bins.iter().map(|count| *count as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let low = total as f32 * desc.low_percentile.clamp(0., 1.);
+    let high = total as f32 * desc.high_percentile.clamp(0., 1.).max(desc.low_percentile);
+    let range = desc.max_log_luminance - desc.min_log_luminance;
+    let step_count = (HISTOGRAM_BIN_COUNT - 2) as f32;
+
+    let mut counted = 0.;
+    let mut weighted_sum = 0.;
+    let mut weight = 0.;
+    for (i, count) in lit_bins.iter().enumerate() {
+        let start = counted;
+        counted += *count as f32;
+        // Part of the bin inside the percentile window.
+        let inside = (counted.min(high) - start.max(low)).max(0.);
+        if inside > 0. {
+            let t = ((i as f32 + 0.5) / step_count).min(1.);
+            weighted_sum += (desc.min_log_luminance + t * range) * inside;
+            weight += inside;
+        }
+    }
+    if weight > 0. {
+        Some(weighted_sum / weight)
+    } else {
+        None
+    }
+}
+
+// Exposure multiplier applied to the hdr image before tone mapping. Follows the average
+// luminance of the luminance histogram smoothly over time, unless a manual exposure is set. The
+// exposure can be applied with a ColorFilterPipeline using the push constants.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AutoExposure {
+    desc: AutoExposureDescriptor,
+    exposure: f32,
+    target_exposure: f32,
+    manual_exposure: Option<f32>,
+    push_constants: ColorFilterPushConstants,
+}
+
+impl AutoExposure {
+    pub fn new(desc: &AutoExposureDescriptor) -> Self {
+        assert!(
+            desc.min_log_luminance < desc.max_log_luminance,
+            "The luminance range must not be empty"
+        );
+        assert!(
+            desc.min_exposure > 0. && desc.min_exposure <= desc.max_exposure,
+            "Invalid exposure range"
+        );
+        let exposure = 1f32.clamp(desc.min_exposure, desc.max_exposure);
+        let mut auto_exposure = Self {
+            desc: *desc,
+            exposure,
+            target_exposure: exposure,
+            manual_exposure: None,
+            push_constants: ColorFilterPushConstants::default(),
+        };
+        auto_exposure.update_push_constants();
+        auto_exposure
+    }
+
+    pub fn descriptor(&self) -> &AutoExposureDescriptor {
+        &self.desc
+    }
+
+    // Current exposure, the manual one if set.
+    pub fn exposure(&self) -> f32 {
+        self.manual_exposure.unwrap_or(self.exposure)
+    }
+
+    // Exposure the automatic exposure is adapting to.
+    pub fn target_exposure(&self) -> f32 {
+        self.target_exposure
+    }
+
+    pub fn manual_exposure(&self) -> Option<f32> {
+        self.manual_exposure
+    }
+
+    // Overrides the automatic exposure, e.g. for cutscenes. The automatic exposure keeps
+    // adapting in the background, and is used again when the override is removed.
+    pub fn set_manual_exposure(&mut self, exposure: Option<f32>) {
+        if let Some(exposure) = exposure {
+            assert!(exposure > 0., "The exposure must be positive");
+        }
+        self.manual_exposure = exposure;
+        self.update_push_constants();
+    }
+
+    // Updates the target from the histogram of the last frame and adapts the exposure by dt
+    // seconds. Histograms without lit pixels keep the previous target.
+    pub fn update(&mut self, histogram: &[u32], dt: f32) {
+        if let Some(log_luminance) = average_log_luminance(histogram, &self.desc) {
+            self.target_exposure = (self.desc.key_value / log_luminance.exp2())
+                .clamp(self.desc.min_exposure, self.desc.max_exposure);
+        }
+        // Adapts in stops, so that the speed is perceptually uniform.
+        let current = self.exposure.log2();
+        let target = self.target_exposure.log2();
+        let speed = if target > current {
+            self.desc.brightening_speed
+        } else {
+            self.desc.darkening_speed
+        };
+        let factor = 1. - (-dt.max(0.) * speed).exp();
+        self.exposure = (current + (target - current) * factor).exp2();
+        self.update_push_constants();
+    }
+
+    // Jumps to the target exposure, e.g. after a camera cut.
+    pub fn snap_to_target(&mut self) {
+        self.exposure = self.target_exposure;
+        self.update_push_constants();
+    }
+
+    pub fn push_constants(&self) -> &ColorFilterPushConstants {
+        &self.push_constants
+    }
+
+    fn update_push_constants(&mut self) {
+        self.push_constants =
+            ColorFilterPushConstants::new(&(Matrix3::identity() * self.exposure()));
+    }
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self::new(&AutoExposureDescriptor::default())
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct HistogramPushConstants {
+    min_log_luminance: f32,
+    inverse_log_luminance_range: f32,
+}
+
+unsafe impl bytemuck::Zeroable for HistogramPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            min_log_luminance: 0.,
+            inverse_log_luminance_range: 0.,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for HistogramPushConstants {}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::COMPUTE,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Float { filterable: false },
+                        view_dimension: gfx::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::COMPUTE,
+                    ty: gfx::BindingType::Buffer {
+                        ty: gfx::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LuminanceHistogramPipelineDescriptor {
+    pub label: Option<String>,
+}
+
+// Compute pass counting the pixels of an hdr texture in log luminance bins.
+#[derive(Debug)]
+pub struct LuminanceHistogramPipeline {
+    pipeline: gfx::ComputePipeline,
+}
+
+impl LuminanceHistogramPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &LuminanceHistogramPipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::COMPUTE,
+                    range: 0..std::mem::size_of::<HistogramPushConstants>() as u32,
+                }],
+            },
+        );
+        let module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/luminance_histogram.comp.spv"),
+        );
+        let pipeline = gfx::ComputePipeline::new(
+            instance,
+            &gfx::ComputePipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: "main",
+            },
+        );
+        Self { pipeline }
+    }
+}
+
+const HISTOGRAM_BYTE_COUNT: gfx::BufferAddress =
+    (HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>()) as gfx::BufferAddress;
+
+// Histogram of one source texture, e.g. the hdr scene color buffer. The source must have a
+// float or unorm format and the TEXTURE_BINDING usage.
+#[derive(Debug)]
+pub struct LuminanceHistogram {
+    bind_group: gfx::BindGroup,
+    histogram_buffer: gfx::Buffer,
+    readback_buffer: gfx::Buffer,
+    source_size: (u32, u32),
+}
+
+impl LuminanceHistogram {
+    pub fn new(instance: &gfx::Instance, source: &gfx::Texture) -> Self {
+        let histogram_buffer = gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: HISTOGRAM_BYTE_COUNT,
+                usage: gfx::BufferUsage::STORAGE
+                    | gfx::BufferUsage::COPY_SRC
+                    | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let readback_buffer = gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: HISTOGRAM_BYTE_COUNT,
+                usage: gfx::BufferUsage::MAP_READ | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let source_view = source.create_view(&gfx::TextureViewDescriptor::default());
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &bind_group_layout(instance),
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(&source_view),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: histogram_buffer.as_entire_binding(),
+                    },
+                ],
+            },
+        );
+        Self {
+            bind_group,
+            histogram_buffer,
+            readback_buffer,
+            source_size: (source.size().width, source.size().height),
+        }
+    }
+
+    // Records the histogram computation into the command sequence. The histogram is cleared when
+    // the command sequence is submitted.
+    pub fn compute(
+        &self,
+        instance: &gfx::Instance,
+        cmd_sequence: &mut gfx::CommandSequence,
+        pipeline: &LuminanceHistogramPipeline,
+        desc: &AutoExposureDescriptor,
+    ) {
+        instance.write_buffer(
+            &self.histogram_buffer,
+            0,
+            &[0; HISTOGRAM_BYTE_COUNT as usize],
+        );
+        let push_constants = HistogramPushConstants {
+            min_log_luminance: desc.min_log_luminance,
+            inverse_log_luminance_range: 1. / (desc.max_log_luminance - desc.min_log_luminance),
+        };
+        {
+            let mut cpass = cmd_sequence.begin_compute_pass(None);
+            cpass.set_pipeline(&pipeline.pipeline);
+            cpass.set_bind_group(0, &self.bind_group, &[]);
+            cpass.set_push_constants(0, gfx::utility::as_slice(&push_constants));
+            let group_count = |size: u32| size.div_ceil(WORKGROUP_SIZE);
+            cpass.dispatch(
+                group_count(self.source_size.0),
+                group_count(self.source_size.1),
+                1,
+            );
+        }
+        cmd_sequence.copy_buffer(
+            &self.histogram_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            HISTOGRAM_BYTE_COUNT,
+        );
+    }
+
+    // Waits for the last computed histogram. Usually read after presenting the frame, to be used
+    // by AutoExposure::update in the next frame.
+    pub fn read(&self, instance: &gfx::Instance) -> Vec<u32> {
+        let buffer_slice = self.readback_buffer.slice(..);
+        let buffer_future = buffer_slice.map_async(gfx::MapMode::Read);
+        instance.poll(gfx::Maintain::Wait);
+        futures::executor::block_on(buffer_future).unwrap();
+        let histogram = bytemuck::cast_slice(&buffer_slice.get_mapped_range()).to_vec();
+        self.readback_buffer.unmap();
+        histogram
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn histogram_with(bins: &[(usize, u32)]) -> Vec<u32> {
+        let mut histogram = vec![0; HISTOGRAM_BIN_COUNT];
+        for (bin, count) in bins {
+            histogram[*bin] = *count;
+        }
+        histogram
+    }
+
+    fn bin_log_luminance(bin: usize, desc: &AutoExposureDescriptor) -> f32 {
+        let t = (bin as f32 - 0.5) / (HISTOGRAM_BIN_COUNT - 2) as f32;
+        desc.min_log_luminance + t * (desc.max_log_luminance - desc.min_log_luminance)
+    }
+
+    #[test]
+    fn average_luminance() {
+        let desc = AutoExposureDescriptor {
+            low_percentile: 0.,
+            high_percentile: 1.,
+            ..AutoExposureDescriptor::default()
+        };
+        expect_that!(
+            &average_log_luminance(&histogram_with(&[(0, 10)]), &desc),
+            eq(None)
+        );
+
+        let histogram = histogram_with(&[(0, 100), (100, 10), (200, 10)]);
+        let expected = (bin_log_luminance(100, &desc) + bin_log_luminance(200, &desc)) * 0.5;
+        expect_that!(
+            &average_log_luminance(&histogram, &desc).unwrap(),
+            close_to(expected, 1e-4)
+        );
+
+        // The outliers are ignored.
+        let desc = AutoExposureDescriptor {
+            low_percentile: 0.1,
+            high_percentile: 0.9,
+            ..AutoExposureDescriptor::default()
+        };
+        let histogram = histogram_with(&[(1, 1), (120, 8), (255, 1)]);
+        expect_that!(
+            &average_log_luminance(&histogram, &desc).unwrap(),
+            close_to(bin_log_luminance(120, &desc), 1e-4)
+        );
+    }
+
+    #[test]
+    fn adaptation() {
+        let desc = AutoExposureDescriptor {
+            low_percentile: 0.,
+            high_percentile: 1.,
+            min_exposure: 0.01,
+            max_exposure: 100.,
+            brightening_speed: 1.,
+            darkening_speed: 2.,
+            ..AutoExposureDescriptor::default()
+        };
+        let mut auto_exposure = AutoExposure::new(&desc);
+        expect_that!(&auto_exposure.exposure(), eq(1.));
+
+        // Dark scene: the exposure increases towards the target.
+        let dark = histogram_with(&[(50, 100)]);
+        auto_exposure.update(&dark, 0.5);
+        let target = desc.key_value / bin_log_luminance(50, &desc).exp2();
+        expect_that!(&auto_exposure.target_exposure(), close_to(target, 1e-3));
+        expect_that!(auto_exposure.exposure() > 1. && auto_exposure.exposure() < target);
+        for _ in 0..100 {
+            auto_exposure.update(&dark, 0.5);
+        }
+        expect_that!(&auto_exposure.exposure(), close_to(target, 1e-3));
+
+        // Black frames keep the previous target.
+        auto_exposure.update(&histogram_with(&[(0, 100)]), 0.5);
+        expect_that!(&auto_exposure.target_exposure(), close_to(target, 1e-3));
+    }
+
+    #[test]
+    fn exposure_clamps() {
+        let mut auto_exposure = AutoExposure::new(&AutoExposureDescriptor {
+            min_exposure: 0.5,
+            max_exposure: 2.,
+            ..AutoExposureDescriptor::default()
+        });
+        auto_exposure.update(&histogram_with(&[(1, 100)]), 0.);
+        expect_that!(&auto_exposure.target_exposure(), eq(2.));
+        auto_exposure.snap_to_target();
+        expect_that!(&auto_exposure.exposure(), eq(2.));
+        auto_exposure.update(&histogram_with(&[(255, 100)]), 0.);
+        expect_that!(&auto_exposure.target_exposure(), eq(0.5));
+    }
+
+    #[test]
+    fn manual_override() {
+        let mut auto_exposure = AutoExposure::default();
+        auto_exposure.set_manual_exposure(Some(4.));
+        auto_exposure.update(&histogram_with(&[(200, 100)]), 10.);
+        expect_that!(&auto_exposure.exposure(), eq(4.));
+        expect_that!(
+            auto_exposure.push_constants(),
+            eq(ColorFilterPushConstants::new(&(Matrix3::identity() * 4.)))
+        );
+        auto_exposure.set_manual_exposure(None);
+        expect_that!(auto_exposure.exposure() < 1.);
+    }
+}
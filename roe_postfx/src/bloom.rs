@@ -0,0 +1,596 @@
+use roe_graphics::{self as gfx, Canvas};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BloomSource {
+    // Every pixel brighter than the threshold glows.
+    #[default]
+    Luminance,
+    // Only the emissive surfaces glow, weighted by the alpha of the scene color buffer. All the
+    // pipelines drawing the scene must follow the emissive mask convention, see
+    // gfx::emissive_mask_alpha_blend.
+    EmissiveMask,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BloomSettings {
+    pub source: BloomSource,
+    // Brightness above which the luminance source glows.
+    pub threshold: f32,
+    // Width of the transition around the threshold, as a fraction of the threshold.
+    pub soft_knee: f32,
+    // Amount of bloom added to the scene.
+    pub intensity: f32,
+    // Spread of the blur, in texels of each mip.
+    pub radius: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            source: BloomSource::default(),
+            threshold: 0.8,
+            soft_knee: 0.5,
+            intensity: 0.6,
+            radius: 1.,
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct BloomPushConstants {
+    texel_size: [f32; 2],
+    threshold: f32,
+    soft_knee: f32,
+    intensity: f32,
+    radius: f32,
+    prefilter: u32,
+    color_conversion: u32,
+}
+
+impl BloomPushConstants {
+    fn new(settings: &BloomSettings, source_size: &gfx::CanvasSize) -> Self {
+        Self {
+            texel_size: [
+                1. / source_size.width() as f32,
+                1. / source_size.height() as f32,
+            ],
+            threshold: settings.threshold,
+            soft_knee: settings.soft_knee,
+            intensity: settings.intensity,
+            radius: settings.radius,
+            prefilter: 0,
+            color_conversion: 0,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for BloomPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            texel_size: [0., 0.],
+            threshold: 0.,
+            soft_knee: 0.,
+            intensity: 0.,
+            radius: 0.,
+            prefilter: 0,
+            color_conversion: 0,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for BloomPushConstants {}
+
+const PC_SIZE: u32 = std::mem::size_of::<BloomPushConstants>() as u32;
+
+// Sizes of the blurred mips, each half the previous one starting from half the scene size,
+// stopping early when a side reaches one pixel.
+pub fn bloom_mip_sizes(scene_size: &gfx::CanvasSize, mip_count: u32) -> Vec<gfx::CanvasSize> {
+    let mut sizes = Vec::with_capacity(mip_count as usize);
+    let (mut width, mut height) = (scene_size.width(), scene_size.height());
+    while (sizes.len() as u32) < mip_count && width > 1 && height > 1 {
+        width /= 2;
+        height /= 2;
+        sizes.push(gfx::CanvasSize::new(width, height));
+    }
+    sizes
+}
+
+fn bind_group_layout(instance: &gfx::Instance, texture_count: u32) -> gfx::BindGroupLayout {
+    let mut entries: Vec<_> = (0..texture_count)
+        .map(|binding| gfx::BindGroupLayoutEntry {
+            binding,
+            visibility: gfx::ShaderStage::FRAGMENT,
+            ty: gfx::BindingType::Texture {
+                multisampled: false,
+                sample_type: gfx::TextureSampleType::Float { filterable: true },
+                view_dimension: gfx::TextureViewDimension::D2,
+            },
+            count: None,
+        })
+        .collect();
+    entries.push(gfx::BindGroupLayoutEntry {
+        binding: texture_count,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Sampler {
+            filtering: true,
+            comparison: false,
+        },
+        count: None,
+    });
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &entries,
+        },
+    )
+}
+
+fn create_bind_group(
+    instance: &gfx::Instance,
+    textures: &[&gfx::TextureView],
+    sampler: &gfx::Sampler,
+) -> gfx::BindGroup {
+    let layout = bind_group_layout(instance, textures.len() as u32);
+    let mut entries: Vec<_> = textures
+        .iter()
+        .enumerate()
+        .map(|(i, texture)| gfx::BindGroupEntry {
+            binding: i as u32,
+            resource: gfx::BindingResource::TextureView(texture),
+        })
+        .collect();
+    entries.push(gfx::BindGroupEntry {
+        binding: textures.len() as u32,
+        resource: gfx::BindingResource::Sampler(sampler),
+    });
+    gfx::BindGroup::new(
+        instance,
+        &gfx::BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &entries,
+        },
+    )
+}
+
+// Format of the blurred mips.
+const MIP_FORMAT: gfx::CanvasColorBufferFormat = gfx::CanvasColorBufferFormat::Rgba16Float;
+
+fn create_pipeline(
+    instance: &gfx::Instance,
+    label: Option<&str>,
+    texture_count: u32,
+    fs_module: &gfx::ShaderModule,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    sample_count: gfx::SampleCount,
+    blend: Option<gfx::BlendState>,
+) -> gfx::RenderPipeline {
+    let pipeline_layout = gfx::PipelineLayout::new(
+        instance,
+        &gfx::PipelineLayoutDescriptor {
+            label,
+            bind_group_layouts: &[&bind_group_layout(instance, texture_count)],
+            push_constant_ranges: &[gfx::PushConstantRange {
+                stages: gfx::ShaderStage::FRAGMENT,
+                range: 0..PC_SIZE,
+            }],
+        },
+    );
+    let vs_module = gfx::ShaderModule::new(
+        instance,
+        &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+    );
+    gfx::RenderPipeline::new(
+        instance,
+        &gfx::RenderPipelineDescriptor {
+            label,
+            layout: Some(&pipeline_layout),
+            vertex: gfx::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[],
+            },
+            primitive: gfx::PrimitiveState {
+                topology: gfx::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: gfx::FrontFace::Ccw,
+                cull_mode: None,
+                clamp_depth: false,
+                polygon_mode: gfx::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: gfx::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(gfx::FragmentState {
+                module: fs_module,
+                entry_point: "main",
+                targets: &[gfx::ColorTargetState {
+                    format: gfx::TextureFormat::from(color_buffer_format),
+                    blend,
+                    write_mask: gfx::ColorWrite::ALL,
+                }],
+            }),
+        },
+    )
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BloomPipelineDescriptor {
+    pub label: Option<String>,
+    // Format of the canvas the scene and the bloom are composited to.
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for BloomPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+// Pipelines of the blur passes, recorded by Bloom::compute, and of the final composite pass,
+// drawn with BloomRenderer::draw_bloom.
+#[derive(Debug)]
+pub struct BloomPipeline {
+    downsample_pipeline: gfx::RenderPipeline,
+    upsample_pipeline: gfx::RenderPipeline,
+    composite_pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl BloomPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &BloomPipelineDescriptor) -> Self {
+        let label = desc.label.as_deref();
+        let downsample_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/bloom_downsample.frag.spv"),
+        );
+        let upsample_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/bloom_upsample.frag.spv"),
+        );
+        let composite_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/bloom_composite.frag.spv"),
+        );
+        let additive = gfx::BlendComponent {
+            src_factor: gfx::BlendFactor::One,
+            dst_factor: gfx::BlendFactor::One,
+            operation: gfx::BlendOperation::Add,
+        };
+        Self {
+            downsample_pipeline: create_pipeline(
+                instance,
+                label,
+                1,
+                &downsample_module,
+                MIP_FORMAT,
+                1,
+                None,
+            ),
+            upsample_pipeline: create_pipeline(
+                instance,
+                label,
+                1,
+                &upsample_module,
+                MIP_FORMAT,
+                1,
+                Some(gfx::BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+            ),
+            composite_pipeline: create_pipeline(
+                instance,
+                label,
+                2,
+                &composite_module,
+                desc.color_buffer_format,
+                desc.sample_count,
+                None,
+            ),
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    // Requirements of the composite pass.
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BloomDescriptor {
+    pub label: Option<String>,
+    // Number of blurred mips, more mips give a wider glow.
+    pub mip_count: u32,
+}
+
+impl Default for BloomDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            mip_count: 5,
+        }
+    }
+}
+
+// Blur chain of a scene color buffer: the bright or emissive parts of the scene are
+// downsampled into a chain of mips, which are then upsampled and accumulated back into the
+// first one. Must be recreated when the scene color buffer changes. When disabled, the blur
+// chain isn't rendered and the composite pass copies the scene.
+#[derive(Debug)]
+pub struct Bloom {
+    scene_size: gfx::CanvasSize,
+    mips: Vec<gfx::CanvasTexture>,
+    // The scene, then each mip, as the source of a pass.
+    source_bind_groups: Vec<gfx::BindGroup>,
+    composite_bind_group: gfx::BindGroup,
+    enabled: bool,
+    graphics_settings: Option<gfx::GraphicsSettingsSubscriber>,
+}
+
+impl Bloom {
+    pub fn new(
+        instance: &gfx::Instance,
+        scene: &gfx::TextureView,
+        scene_size: &gfx::CanvasSize,
+        desc: &BloomDescriptor,
+    ) -> Result<Self, gfx::CanvasBufferError> {
+        let mut mips = Vec::new();
+        for size in bloom_mip_sizes(scene_size, desc.mip_count.max(1)) {
+            mips.push(gfx::CanvasTexture::new(
+                instance,
+                &gfx::CanvasTextureDescriptor {
+                    label: desc.label.clone(),
+                    size,
+                    sample_count: 1,
+                    color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
+                        format: MIP_FORMAT,
+                        usage: gfx::CanvasColorBufferUsage::TEXTURE_BINDING,
+                        sample_count: None,
+                    }),
+                    depth_stencil_buffer_format: None,
+                },
+            )?);
+        }
+        let sampler = gfx::Sampler::new(
+            instance,
+            &gfx::SamplerDescriptor {
+                mag_filter: gfx::FilterMode::Linear,
+                min_filter: gfx::FilterMode::Linear,
+                ..gfx::SamplerDescriptor::default()
+            },
+        );
+        let mut source_bind_groups = vec![create_bind_group(instance, &[scene], &sampler)];
+        for mip in mips.iter() {
+            source_bind_groups.push(create_bind_group(
+                instance,
+                &[mip.color_texture_view().unwrap()],
+                &sampler,
+            ));
+        }
+        // A scene smaller than 2 pixels has no mips, and no bloom.
+        let bloom_texture = match mips.first() {
+            Some(mip) => mip.color_texture_view().unwrap(),
+            None => scene,
+        };
+        let composite_bind_group = create_bind_group(instance, &[scene, bloom_texture], &sampler);
+        Ok(Self {
+            scene_size: *scene_size,
+            mips,
+            source_bind_groups,
+            composite_bind_group,
+            enabled: true,
+            graphics_settings: None,
+        })
+    }
+
+    pub fn mip_count(&self) -> usize {
+        self.mips.len()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // The bloom is enabled according to the post effect settings, starting from the current
+    // ones.
+    pub fn subscribe_graphics_settings(&mut self, settings: &gfx::RuntimeGraphicsSettings) {
+        let subscriber = settings.subscribe(gfx::GraphicsSettingsChanges::POST_EFFECTS);
+        self.apply_graphics_settings(subscriber.settings());
+        self.graphics_settings = Some(subscriber);
+    }
+
+    // Applies the settings changed since the last update, e.g. at the start of a frame.
+    pub fn update_graphics_settings(&mut self) {
+        if let Some(update) = self.graphics_settings.as_mut().and_then(|s| s.poll()) {
+            self.apply_graphics_settings(&update.settings);
+        }
+    }
+
+    pub fn apply_graphics_settings(&mut self, settings: &gfx::GraphicsSettings) {
+        self.enabled = settings.post_effects.bloom;
+    }
+
+    // Renders the blur chain, to be called after drawing the scene and before the composite
+    // pass.
+    pub fn compute(
+        &mut self,
+        instance: &gfx::Instance,
+        pipeline: &BloomPipeline,
+        settings: &BloomSettings,
+    ) -> Result<(), gfx::SurfaceError> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let mip_sizes: Vec<_> = self.mips.iter().map(|mip| *mip.canvas_size()).collect();
+        let mut cmd_sequence = gfx::CommandSequence::new(instance);
+        let requirements = gfx::RenderPassRequirements {
+            sample_count: 1,
+            color_buffer_formats: vec![MIP_FORMAT],
+            depth_stencil_buffer_format: None,
+        };
+
+        for i in 0..self.mips.len() {
+            let source_size = if i == 0 {
+                self.scene_size
+            } else {
+                mip_sizes[i - 1]
+            };
+            let mut push_constants = BloomPushConstants::new(settings, &source_size);
+            if i == 0 {
+                push_constants.prefilter = settings.source as u32 + 1;
+            }
+            let frame = match self.mips[i].current_frame()? {
+                Some(frame) => frame,
+                None => return Ok(()),
+            };
+            {
+                let mut rpass = cmd_sequence.begin_render_pass(
+                    &frame,
+                    &requirements,
+                    &gfx::RenderPassOperations::default(),
+                );
+                rpass.set_pipeline(&pipeline.downsample_pipeline);
+                rpass.set_bind_group(0, &self.source_bind_groups[i], &[]);
+                rpass.set_push_constants(
+                    gfx::ShaderStage::FRAGMENT,
+                    0,
+                    gfx::utility::as_slice(&push_constants),
+                );
+                rpass.draw(0..3, 0..1);
+            }
+            frame.present();
+        }
+
+        for i in (1..self.mips.len()).rev() {
+            let push_constants = BloomPushConstants::new(settings, &mip_sizes[i]);
+            let frame = match self.mips[i - 1].current_frame()? {
+                Some(frame) => frame,
+                None => return Ok(()),
+            };
+            {
+                let mut rpass = cmd_sequence.begin_render_pass(
+                    &frame,
+                    &requirements,
+                    &gfx::RenderPassOperations {
+                        color_operations: vec![gfx::ColorOperations {
+                            load: gfx::LoadOp::Load,
+                            store: true,
+                        }],
+                        ..gfx::RenderPassOperations::default()
+                    },
+                );
+                rpass.set_pipeline(&pipeline.upsample_pipeline);
+                rpass.set_bind_group(0, &self.source_bind_groups[i + 1], &[]);
+                rpass.set_push_constants(
+                    gfx::ShaderStage::FRAGMENT,
+                    0,
+                    gfx::utility::as_slice(&push_constants),
+                );
+                rpass.draw(0..3, 0..1);
+            }
+            frame.present();
+        }
+
+        cmd_sequence.submit(instance);
+        Ok(())
+    }
+}
+
+pub trait BloomRenderer<'a> {
+    // Draws the scene with the bloom added, as a full screen pass.
+    fn draw_bloom(
+        &mut self,
+        pipeline: &'a BloomPipeline,
+        bloom: &'a Bloom,
+        settings: &BloomSettings,
+    );
+}
+
+impl<'a> BloomRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_bloom(
+        &mut self,
+        pipeline: &'a BloomPipeline,
+        bloom: &'a Bloom,
+        settings: &BloomSettings,
+    ) {
+        let mut push_constants = BloomPushConstants::new(settings, &bloom.scene_size);
+        if bloom.mips.is_empty() || !bloom.enabled {
+            push_constants.intensity = 0.;
+        }
+        push_constants.color_conversion = pipeline.color_conversion as u32;
+        self.set_pipeline(&pipeline.composite_pipeline);
+        self.set_bind_group(0, &bloom.composite_bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(&push_constants),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn mip_sizes() {
+        let sizes = bloom_mip_sizes(&gfx::CanvasSize::new(800, 600), 3);
+        expect_that!(
+            &sizes,
+            eq(vec![
+                gfx::CanvasSize::new(400, 300),
+                gfx::CanvasSize::new(200, 150),
+                gfx::CanvasSize::new(100, 75),
+            ])
+        );
+        let sizes = bloom_mip_sizes(&gfx::CanvasSize::new(10, 3), 5);
+        expect_that!(&sizes, eq(vec![gfx::CanvasSize::new(5, 1)]));
+        expect_that!(bloom_mip_sizes(&gfx::CanvasSize::new(1, 100), 5).is_empty());
+    }
+
+    #[test]
+    fn push_constants() {
+        let settings = BloomSettings {
+            source: BloomSource::EmissiveMask,
+            ..BloomSettings::default()
+        };
+        let pc = BloomPushConstants::new(&settings, &gfx::CanvasSize::new(200, 50));
+        let texel_size = pc.texel_size;
+        expect_that!(&texel_size, eq([0.005, 0.02]));
+        expect_that!(&(settings.source as u32 + 1), eq(2));
+        expect_that!(&PC_SIZE, eq(32));
+    }
+}
@@ -0,0 +1,252 @@
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix3, Matrix3};
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ColorFilterPushConstants {
+    color_matrix: HomogeneousMatrix3<f32>,
+}
+
+impl ColorFilterPushConstants {
+    // The matrix is applied to the rgb components of each pixel. Alpha is left unchanged.
+    pub fn new(color_matrix: &Matrix3<f32>) -> Self {
+        let mut m = HomogeneousMatrix3::identity();
+        for row in 0..3 {
+            for column in 0..3 {
+                m[(row, column)] = color_matrix[(row, column)];
+            }
+        }
+        Self { color_matrix: m }
+    }
+}
+
+impl Default for ColorFilterPushConstants {
+    fn default() -> Self {
+        Self::new(&Matrix3::identity())
+    }
+}
+
+unsafe impl bytemuck::Zeroable for ColorFilterPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            color_matrix: HomogeneousMatrix3::zeros(),
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for ColorFilterPushConstants {}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Float { filterable: true },
+                        view_dimension: gfx::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+// The source image of the filter, usually the texture the scene was rendered to.
+#[derive(Debug)]
+pub struct ColorFilterUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl ColorFilterUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        Self::new_with_label(instance, None, texture, sampler)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        let layout = bind_group_layout(instance);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label,
+                layout: &layout,
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(texture),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorFilterPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for ColorFilterPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<ColorFilterPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+// Full screen pass multiplying the colors of a texture by a matrix.
+#[derive(Debug)]
+pub struct ColorFilterPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl ColorFilterPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &ColorFilterPipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::FRAGMENT,
+                    range: 0..PC_SIZE,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_filter.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: None,
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait Renderer<'a> {
+    fn draw_color_filter(
+        &mut self,
+        pipeline: &'a ColorFilterPipeline,
+        uniform_constants: &'a ColorFilterUniformConstants,
+        push_constants: &ColorFilterPushConstants,
+    );
+}
+
+impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
+    fn draw_color_filter(
+        &mut self,
+        pipeline: &'a ColorFilterPipeline,
+        uniform_constants: &'a ColorFilterUniformConstants,
+        push_constants: &ColorFilterPushConstants,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,767 @@
+use roe_graphics as gfx;
+use roe_math::{Matrix3, Vector3};
+
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ColorLutError {
+    // The size must be at least 2.
+    InvalidSize(u32),
+    MissingSize,
+    // Line number and content.
+    InvalidLine(usize, String),
+    // Expected and found entry count.
+    EntryCountMismatch(usize, usize),
+    UnsupportedDomain,
+    // Width and height of the strip image.
+    InvalidStripSize(u32, u32),
+}
+
+impl std::fmt::Display for ColorLutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorLutError::InvalidSize(size) => write!(f, "Invalid LUT size ({})", size),
+            ColorLutError::MissingSize => write!(f, "Missing LUT size"),
+            ColorLutError::InvalidLine(number, line) => {
+                write!(f, "Invalid LUT line ({}: {})", number, line)
+            }
+            ColorLutError::EntryCountMismatch(expected, found) => write!(
+                f,
+                "LUT entry count mismatch (expected: {}, found: {})",
+                expected, found
+            ),
+            ColorLutError::UnsupportedDomain => write!(f, "Unsupported LUT domain"),
+            ColorLutError::InvalidStripSize(width, height) => {
+                write!(f, "Invalid LUT strip size ({}x{})", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColorLutError {}
+
+const MAX_LUT_SIZE: u32 = 256;
+
+fn check_size(size: u32) -> Result<(), ColorLutError> {
+    if (2..=MAX_LUT_SIZE).contains(&size) {
+        Ok(())
+    } else {
+        Err(ColorLutError::InvalidSize(size))
+    }
+}
+
+// 3D color lookup table: maps each input rgb color to an output color. Entries are stored with
+// red changing fastest, then green, then blue.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorLut {
+    size: u32,
+    entries: Vec<Vector3<f32>>,
+}
+
+impl ColorLut {
+    // Bakes the color transform into a LUT.
+    pub fn from_fn<F: FnMut(Vector3<f32>) -> Vector3<f32>>(
+        size: u32,
+        mut f: F,
+    ) -> Result<Self, ColorLutError> {
+        check_size(size)?;
+        let max = (size - 1) as f32;
+        let mut entries = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    entries.push(f(Vector3::new(r as f32, g as f32, b as f32) / max));
+                }
+            }
+        }
+        Ok(Self { size, entries })
+    }
+
+    // Maps each color to itself. Usually baked to a strip image and edited in an image editor
+    // together with a screenshot.
+    pub fn neutral(size: u32) -> Result<Self, ColorLutError> {
+        Self::from_fn(size, |color| color)
+    }
+
+    // Bakes a color matrix, e.g. of a ColorFilterPushConstants.
+    pub fn from_matrix(size: u32, matrix: &Matrix3<f32>) -> Result<Self, ColorLutError> {
+        Self::from_fn(size, |color| matrix * color)
+    }
+
+    // Parses the text of a .cube file. Only 3D LUTs with the default domain are supported.
+    pub fn from_cube(text: &str) -> Result<Self, ColorLutError> {
+        let mut size = None;
+        let mut entries = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let invalid_line = || ColorLutError::InvalidLine(index + 1, String::from(line));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            let keyword = tokens.next().unwrap();
+            match keyword {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    let value = tokens
+                        .next()
+                        .and_then(|t| t.parse::<u32>().ok())
+                        .ok_or_else(invalid_line)?;
+                    check_size(value)?;
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => return Err(ColorLutError::InvalidLine(index + 1, line.into())),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0. } else { 1. };
+                    let values = tokens
+                        .map(|t| t.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid_line())?;
+                    if values.len() != 3 || values.iter().any(|v| *v != expected) {
+                        return Err(ColorLutError::UnsupportedDomain);
+                    }
+                }
+                _ => {
+                    let values = line
+                        .split_whitespace()
+                        .map(|t| t.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid_line())?;
+                    if values.len() != 3 {
+                        return Err(invalid_line());
+                    }
+                    entries.push(Vector3::new(values[0], values[1], values[2]));
+                }
+            }
+        }
+        let size = size.ok_or(ColorLutError::MissingSize)?;
+        let expected = (size * size * size) as usize;
+        if entries.len() != expected {
+            return Err(ColorLutError::EntryCountMismatch(expected, entries.len()));
+        }
+        Ok(Self { size, entries })
+    }
+
+    // Reads a strip image, with the blue slices side by side: the image is size * size pixels
+    // wide and size pixels high, red increases to the right within each slice, and green
+    // increases downwards.
+    pub fn from_strip_image(image: &image::RgbaImage) -> Result<Self, ColorLutError> {
+        let (width, height) = image.dimensions();
+        if height < 2 || width != height * height {
+            return Err(ColorLutError::InvalidStripSize(width, height));
+        }
+        check_size(height)?;
+        let size = height;
+        let mut entries = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let pixel = image.get_pixel(b * size + r, g);
+                    entries.push(Vector3::new(
+                        pixel[0] as f32 / 255.,
+                        pixel[1] as f32 / 255.,
+                        pixel[2] as f32 / 255.,
+                    ));
+                }
+            }
+        }
+        Ok(Self { size, entries })
+    }
+
+    pub fn to_strip_image(&self) -> image::RgbaImage {
+        let size = self.size;
+        image::RgbaImage::from_fn(size * size, size, |x, y| {
+            let color = self.entry(x % size, y, x / size);
+            let channel = |v: f32| (v.clamp(0., 1.) * 255.).round() as u8;
+            image::Rgba([channel(color[0]), channel(color[1]), channel(color[2]), 255])
+        })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn entry(&self, r: u32, g: u32, b: u32) -> Vector3<f32> {
+        self.entries[(r + g * self.size + b * self.size * self.size) as usize]
+    }
+
+    // Trilinear interpolation of the entries, as done by the color grading pass.
+    pub fn sample(&self, color: &Vector3<f32>) -> Vector3<f32> {
+        let max = (self.size - 1) as f32;
+        let position = color.map(|v| v.clamp(0., 1.) * max);
+        let base = position.map(|v| (v.floor() as u32).min(self.size - 2));
+        let t = position - base.map(|v| v as f32);
+        let mut result = Vector3::zeros();
+        for corner in 0..8u32 {
+            let offset = Vector3::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = (0..3)
+                .map(|i| if offset[i] == 1 { t[i] } else { 1. - t[i] })
+                .product::<f32>();
+            let index = base + offset;
+            result += self.entry(index[0], index[1], index[2]) * weight;
+        }
+        result
+    }
+
+    // Blends two LUTs of the same size entry by entry.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        assert!(self.size == other.size, "The LUT sizes must match");
+        Self {
+            size: self.size,
+            entries: self
+                .entries
+                .iter()
+                .zip(other.entries.iter())
+                .map(|(a, b)| a + (b - a) * t)
+                .collect(),
+        }
+    }
+
+    fn to_rgba8(&self) -> Vec<u8> {
+        let channel = |v: f32| (v.clamp(0., 1.) * 255.).round() as u8;
+        self.entries
+            .iter()
+            .flat_map(|e| [channel(e[0]), channel(e[1]), channel(e[2]), 255])
+            .collect()
+    }
+}
+
+// 3D texture with the entries of a LUT, filtered linearly by the color grading pass.
+#[derive(Debug)]
+pub struct ColorLutTexture {
+    texture: gfx::Texture,
+    view: gfx::TextureView,
+}
+
+impl ColorLutTexture {
+    pub fn new(instance: &gfx::Instance, lut: &ColorLut) -> Self {
+        let size = gfx::Extent3d {
+            width: lut.size(),
+            height: lut.size(),
+            depth_or_array_layers: lut.size(),
+        };
+        let texture = gfx::Texture::new(
+            instance,
+            &gfx::TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: gfx::TextureDimension::D3,
+                format: gfx::TextureFormat::Rgba8Unorm,
+                usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+            },
+        );
+        let texture = Self {
+            view: texture.create_view(&gfx::TextureViewDescriptor::default()),
+            texture,
+        };
+        texture.write(instance, lut);
+        texture
+    }
+
+    // Replaces the entries, e.g. after editing the LUT at runtime. The size must not change.
+    pub fn write(&self, instance: &gfx::Instance, lut: &ColorLut) {
+        assert!(
+            lut.size() == self.size(),
+            "The LUT size must match the texture size"
+        );
+        self.texture.write(
+            instance,
+            0,
+            gfx::Origin3d::ZERO,
+            &lut.to_rgba8(),
+            gfx::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(lut.size() * 4),
+                rows_per_image: std::num::NonZeroU32::new(lut.size()),
+            },
+            *self.texture.size(),
+        );
+    }
+
+    pub fn size(&self) -> u32 {
+        self.texture.size().width
+    }
+
+    pub fn view(&self) -> &gfx::TextureView {
+        &self.view
+    }
+}
+
+fn texture_entry(
+    binding: u32,
+    view_dimension: gfx::TextureViewDimension,
+) -> gfx::BindGroupLayoutEntry {
+    gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: true },
+            view_dimension,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(binding: u32) -> gfx::BindGroupLayoutEntry {
+    gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Sampler {
+            filtering: true,
+            comparison: false,
+        },
+        count: None,
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                texture_entry(0, gfx::TextureViewDimension::D2),
+                sampler_entry(1),
+                texture_entry(2, gfx::TextureViewDimension::D3),
+                texture_entry(3, gfx::TextureViewDimension::D3),
+                sampler_entry(4),
+            ],
+        },
+    )
+}
+
+// The source image and the two LUTs blended by the pass.
+#[derive(Debug)]
+pub struct ColorGradingUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl ColorGradingUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+        lut_from: &ColorLutTexture,
+        lut_to: &ColorLutTexture,
+    ) -> Self {
+        Self::new_with_label(instance, None, texture, sampler, lut_from, lut_to)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+        lut_from: &ColorLutTexture,
+        lut_to: &ColorLutTexture,
+    ) -> Self {
+        let layout = bind_group_layout(instance);
+        let lut_sampler = gfx::Sampler::new(
+            instance,
+            &gfx::SamplerDescriptor {
+                mag_filter: gfx::FilterMode::Linear,
+                min_filter: gfx::FilterMode::Linear,
+                ..gfx::SamplerDescriptor::default()
+            },
+        );
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label,
+                layout: &layout,
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(texture),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 2,
+                        resource: gfx::BindingResource::TextureView(lut_from.view()),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 3,
+                        resource: gfx::BindingResource::TextureView(lut_to.view()),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 4,
+                        resource: gfx::BindingResource::Sampler(&lut_sampler),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ColorGradingPushConstants {
+    blend: f32,
+}
+
+impl ColorGradingPushConstants {
+    // Weight of the second LUT, 0 uses only the first one.
+    pub fn new(blend: f32) -> Self {
+        Self {
+            blend: blend.clamp(0., 1.),
+        }
+    }
+}
+
+impl Default for ColorGradingPushConstants {
+    fn default() -> Self {
+        Self::new(0.)
+    }
+}
+
+unsafe impl bytemuck::Zeroable for ColorGradingPushConstants {
+    fn zeroed() -> Self {
+        Self::new(0.)
+    }
+}
+
+unsafe impl bytemuck::Pod for ColorGradingPushConstants {}
+
+// Blend factor between the two LUTs of the pass, animated over time, e.g. from the outdoor LUT
+// to the cave LUT when the player enters a cave.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorGradingBlend {
+    blend: f32,
+    start: f32,
+    target: f32,
+    duration: Duration,
+    elapsed: Duration,
+    push_constants: ColorGradingPushConstants,
+}
+
+impl ColorGradingBlend {
+    pub fn new(blend: f32) -> Self {
+        let blend = blend.clamp(0., 1.);
+        Self {
+            blend,
+            start: blend,
+            target: blend,
+            duration: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            push_constants: ColorGradingPushConstants::new(blend),
+        }
+    }
+
+    pub fn blend(&self) -> f32 {
+        self.blend
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_blending(&self) -> bool {
+        self.blend != self.target
+    }
+
+    // Moves linearly from the current blend factor to the target over the duration.
+    pub fn blend_to(&mut self, target: f32, duration: Duration) {
+        self.start = self.blend;
+        self.target = target.clamp(0., 1.);
+        self.duration = duration;
+        self.elapsed = Duration::ZERO;
+        if duration == Duration::ZERO {
+            self.set_blend(self.target);
+        }
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        if !self.is_blending() {
+            return;
+        }
+        self.elapsed += dt;
+        if self.elapsed >= self.duration {
+            self.set_blend(self.target);
+        } else {
+            let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+            self.set_blend(self.start + (self.target - self.start) * t);
+        }
+    }
+
+    pub fn push_constants(&self) -> &ColorGradingPushConstants {
+        &self.push_constants
+    }
+
+    fn set_blend(&mut self, blend: f32) {
+        self.blend = blend;
+        self.push_constants = ColorGradingPushConstants::new(blend);
+    }
+}
+
+impl Default for ColorGradingBlend {
+    fn default() -> Self {
+        Self::new(0.)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorGradingPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for ColorGradingPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<ColorGradingPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+// Full screen pass mapping the colors of a texture through two LUTs blended together. The LUTs
+// are indexed with the colors as sampled from the texture.
+#[derive(Debug)]
+pub struct ColorGradingPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl ColorGradingPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &ColorGradingPipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::FRAGMENT,
+                    range: 0..PC_SIZE,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_grading.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: None,
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait ColorGradingRenderer<'a> {
+    fn draw_color_grading(
+        &mut self,
+        pipeline: &'a ColorGradingPipeline,
+        uniform_constants: &'a ColorGradingUniformConstants,
+        push_constants: &ColorGradingPushConstants,
+    );
+}
+
+impl<'a> ColorGradingRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_color_grading(
+        &mut self,
+        pipeline: &'a ColorGradingPipeline,
+        uniform_constants: &'a ColorGradingUniformConstants,
+        push_constants: &ColorGradingPushConstants,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn expect_close(a: &Vector3<f32>, b: &Vector3<f32>) {
+        for i in 0..3 {
+            expect_that!(&a[i], close_to(b[i], 1e-4));
+        }
+    }
+
+    #[test]
+    fn neutral_lut() {
+        let lut = ColorLut::neutral(4).unwrap();
+        expect_that!(&lut.size(), eq(4));
+        expect_close(&lut.entry(3, 0, 1), &Vector3::new(1., 0., 1. / 3.));
+        let color = Vector3::new(0.2, 0.55, 0.9);
+        expect_close(&lut.sample(&color), &color);
+        expect_that!(
+            &ColorLut::neutral(1).unwrap_err(),
+            eq(ColorLutError::InvalidSize(1))
+        );
+    }
+
+    #[test]
+    fn baked_matrix() {
+        let matrix = Matrix3::from_diagonal(&Vector3::new(0.5, 1., 0.25));
+        let lut = ColorLut::from_matrix(8, &matrix).unwrap();
+        let color = Vector3::new(0.3, 0.6, 0.8);
+        expect_close(&lut.sample(&color), &(matrix * color));
+    }
+
+    #[test]
+    fn strip_image() {
+        let lut = ColorLut::neutral(16).unwrap();
+        let image = lut.to_strip_image();
+        expect_that!(&image.dimensions(), eq((256, 16)));
+        expect_that!(
+            image.get_pixel(16 * 2 + 15, 0),
+            eq(image::Rgba([255, 0, 34, 255]))
+        );
+        let loaded = ColorLut::from_strip_image(&image).unwrap();
+        expect_close(&loaded.entry(5, 7, 9), &lut.entry(5, 7, 9));
+        expect_that!(
+            &ColorLut::from_strip_image(&image::RgbaImage::new(64, 16)).unwrap_err(),
+            eq(ColorLutError::InvalidStripSize(64, 16))
+        );
+    }
+
+    #[test]
+    fn cube_file() {
+        let mut text = String::from("# Warm\nTITLE \"Warm\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\n");
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    text += &format!("{} {} {}\n", r as f32, g as f32 * 0.5, b);
+                }
+            }
+        }
+        let lut = ColorLut::from_cube(&text).unwrap();
+        expect_close(&lut.entry(1, 1, 0), &Vector3::new(1., 0.5, 0.));
+        expect_close(
+            &lut.sample(&Vector3::new(0.5, 0.5, 0.5)),
+            &Vector3::new(0.5, 0.25, 0.5),
+        );
+
+        expect_that!(
+            &ColorLut::from_cube("LUT_3D_SIZE 2\n0 0 0\n").unwrap_err(),
+            eq(ColorLutError::EntryCountMismatch(8, 1))
+        );
+        expect_that!(
+            &ColorLut::from_cube("0 0 0\n").unwrap_err(),
+            eq(ColorLutError::MissingSize)
+        );
+        expect_that!(
+            &ColorLut::from_cube("LUT_3D_SIZE 2\n0 a 0\n").unwrap_err(),
+            eq(ColorLutError::InvalidLine(2, String::from("0 a 0")))
+        );
+        expect_that!(
+            &ColorLut::from_cube("DOMAIN_MAX 2 2 2\n").unwrap_err(),
+            eq(ColorLutError::UnsupportedDomain)
+        );
+    }
+
+    #[test]
+    fn lut_lerp() {
+        let neutral = ColorLut::neutral(4).unwrap();
+        let dark = ColorLut::from_fn(4, |color| color * 0.5).unwrap();
+        let color = Vector3::new(0.4, 0.8, 1.);
+        expect_close(&neutral.lerp(&dark, 0.5).sample(&color), &(color * 0.75));
+    }
+
+    #[test]
+    fn blend_over_time() {
+        let mut blend = ColorGradingBlend::default();
+        expect_that!(!blend.is_blending());
+        blend.blend_to(1., Duration::from_secs(2));
+        blend.update(Duration::from_millis(500));
+        expect_that!(&blend.blend(), close_to(0.25, 1e-6));
+        expect_that!(
+            blend.push_constants(),
+            eq(ColorGradingPushConstants::new(blend.blend()))
+        );
+
+        // Turning back midway starts from the current factor.
+        blend.blend_to(0., Duration::from_secs(1));
+        blend.update(Duration::from_millis(500));
+        expect_that!(&blend.blend(), close_to(0.125, 1e-6));
+        blend.update(Duration::from_secs(1));
+        expect_that!(&blend.blend(), eq(0.));
+        expect_that!(!blend.is_blending());
+
+        blend.blend_to(2., Duration::ZERO);
+        expect_that!(&blend.blend(), eq(1.));
+    }
+}
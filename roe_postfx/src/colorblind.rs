@@ -0,0 +1,204 @@
+use super::ColorFilterPushConstants;
+
+use roe_math::Matrix3;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ColorVisionDeficiency {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ColorblindFilterMode {
+    // Shows the image as perceived with the deficiency, to check the readability of the game.
+    Simulate,
+    // Shifts the colors that can't be told apart towards distinguishable ones.
+    Compensate,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ColorblindFilterDescriptor {
+    // No filtering if None.
+    pub deficiency: Option<ColorVisionDeficiency>,
+    pub mode: ColorblindFilterMode,
+    // Blends between the original colors (0) and the fully filtered ones (1).
+    pub strength: f32,
+}
+
+impl Default for ColorblindFilterDescriptor {
+    fn default() -> Self {
+        Self {
+            deficiency: None,
+            mode: ColorblindFilterMode::Compensate,
+            strength: 1.,
+        }
+    }
+}
+
+// Dichromacy simulation in linear rgb, from Machado, Oliveira and Fernandes (2009) at full
+// severity.
+pub fn simulation_matrix(deficiency: ColorVisionDeficiency) -> Matrix3<f32> {
+    match deficiency {
+        ColorVisionDeficiency::Protanopia => Matrix3::new(
+            0.152286, 1.052583, -0.204868, 0.114503, 0.786281, 0.099216, -0.003882, -0.048116,
+            1.051998,
+        ),
+        ColorVisionDeficiency::Deuteranopia => Matrix3::new(
+            0.367322, 0.860646, -0.227968, 0.280085, 0.672501, 0.047413, -0.011820, 0.042940,
+            0.968881,
+        ),
+        ColorVisionDeficiency::Tritanopia => Matrix3::new(
+            1.255528, -0.076749, -0.178779, -0.078411, 0.930809, 0.147602, 0.004733, 0.691367,
+            0.303900,
+        ),
+    }
+}
+
+// Daltonization: the difference between the original and the simulated colors is moved to the
+// channels the viewer can perceive.
+pub fn compensation_matrix(deficiency: ColorVisionDeficiency) -> Matrix3<f32> {
+    let error_shift = match deficiency {
+        ColorVisionDeficiency::Protanopia | ColorVisionDeficiency::Deuteranopia => {
+            Matrix3::new(0., 0., 0., 0.7, 1., 0., 0.7, 0., 1.)
+        }
+        ColorVisionDeficiency::Tritanopia => Matrix3::new(1., 0., 0.7, 0., 1., 0.7, 0., 0., 0.),
+    };
+    Matrix3::identity() + error_shift * (Matrix3::identity() - simulation_matrix(deficiency))
+}
+
+pub fn colorblind_matrix(desc: &ColorblindFilterDescriptor) -> Matrix3<f32> {
+    let deficiency = match desc.deficiency {
+        Some(deficiency) => deficiency,
+        None => return Matrix3::identity(),
+    };
+    let full = match desc.mode {
+        ColorblindFilterMode::Simulate => simulation_matrix(deficiency),
+        ColorblindFilterMode::Compensate => compensation_matrix(deficiency),
+    };
+    Matrix3::identity() + (full - Matrix3::identity()) * desc.strength.clamp(0., 1.)
+}
+
+// Runtime state of the colorblind filter, to be drawn with a ColorFilterPipeline. The
+// descriptor can be changed at any time, e.g. from the options menu.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorblindFilter {
+    desc: ColorblindFilterDescriptor,
+    push_constants: ColorFilterPushConstants,
+}
+
+impl ColorblindFilter {
+    pub fn new(desc: &ColorblindFilterDescriptor) -> Self {
+        Self {
+            desc: *desc,
+            push_constants: ColorFilterPushConstants::new(&colorblind_matrix(desc)),
+        }
+    }
+
+    pub fn descriptor(&self) -> &ColorblindFilterDescriptor {
+        &self.desc
+    }
+
+    pub fn set_descriptor(&mut self, desc: &ColorblindFilterDescriptor) {
+        *self = Self::new(desc);
+    }
+
+    // If false the pass can be skipped.
+    pub fn is_enabled(&self) -> bool {
+        self.desc.deficiency.is_some() && self.desc.strength > 0.
+    }
+
+    pub fn push_constants(&self) -> &ColorFilterPushConstants {
+        &self.push_constants
+    }
+}
+
+impl Default for ColorblindFilter {
+    fn default() -> Self {
+        Self::new(&ColorblindFilterDescriptor::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_math::Vector3;
+
+    const DEFICIENCIES: [ColorVisionDeficiency; 3] = [
+        ColorVisionDeficiency::Protanopia,
+        ColorVisionDeficiency::Deuteranopia,
+        ColorVisionDeficiency::Tritanopia,
+    ];
+
+    #[test]
+    fn grays_are_preserved() {
+        let gray = Vector3::new(0.5, 0.5, 0.5);
+        for deficiency in DEFICIENCIES {
+            for m in [
+                simulation_matrix(deficiency),
+                compensation_matrix(deficiency),
+            ] {
+                let out = m * gray;
+                for i in 0..3 {
+                    expect_that!(&out[i], close_to(0.5, 1e-3));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simulation() {
+        let red = Vector3::new(1., 0., 0.);
+        let green = Vector3::new(0., 1., 0.);
+        let blue = Vector3::new(0., 0., 1.);
+        let protanopia = simulation_matrix(ColorVisionDeficiency::Protanopia);
+        let tritanopia = simulation_matrix(ColorVisionDeficiency::Tritanopia);
+
+        // Red and green get confused with protanopia, but blue stays distinct.
+        let red_green = (protanopia * red - protanopia * green).norm();
+        let green_blue = (protanopia * green - protanopia * blue).norm();
+        expect_that!(red_green < (red - green).norm() && red_green < green_blue);
+        let green_blue = (tritanopia * green - tritanopia * blue).norm();
+        expect_that!(green_blue < (green - blue).norm());
+    }
+
+    #[test]
+    fn compensation() {
+        // After compensation, red and green are easier to tell apart for a protanope.
+        let red = Vector3::new(1., 0., 0.);
+        let green = Vector3::new(0., 1., 0.);
+        let simulation = simulation_matrix(ColorVisionDeficiency::Protanopia);
+        let compensation = compensation_matrix(ColorVisionDeficiency::Protanopia);
+        let before = (simulation * red - simulation * green).norm();
+        let after = (simulation * compensation * red - simulation * compensation * green).norm();
+        expect_that!(after > before);
+    }
+
+    #[test]
+    fn strength() {
+        let desc = ColorblindFilterDescriptor {
+            deficiency: Some(ColorVisionDeficiency::Deuteranopia),
+            mode: ColorblindFilterMode::Simulate,
+            strength: 0.5,
+        };
+        let full = simulation_matrix(ColorVisionDeficiency::Deuteranopia);
+        let half = colorblind_matrix(&desc);
+        expect_that!(&half[(0, 1)], close_to(full[(0, 1)] * 0.5, 1e-6));
+        expect_that!(&half[(0, 0)], close_to((1. + full[(0, 0)]) * 0.5, 1e-6));
+
+        let mut filter = ColorblindFilter::default();
+        expect_that!(!filter.is_enabled());
+        expect_that!(
+            filter.push_constants(),
+            eq(ColorFilterPushConstants::default())
+        );
+        filter.set_descriptor(&desc);
+        expect_that!(filter.is_enabled());
+        expect_that!(
+            filter.push_constants(),
+            eq(ColorFilterPushConstants::new(&half))
+        );
+    }
+}
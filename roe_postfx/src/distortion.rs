@@ -0,0 +1,325 @@
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+// Clear color of the displacement buffer, where nothing is distorted. The displacement buffer
+// must have an Rgba8Unorm color buffer, and the offsets must be written without color
+// conversion.
+pub const NEUTRAL_DISPLACEMENT: gfx::ColorF64 = gfx::ColorF64 {
+    r: 128. / 255.,
+    g: 128. / 255.,
+    b: 0.,
+    a: 0.,
+};
+
+// Color channels to write into the displacement buffer for the offset, in [-1, 1] per axis.
+// The offset is scaled by the strength of the pass.
+pub fn encode_displacement(offset: &Vector2<f32>) -> [f32; 2] {
+    let channel = |value: f32| (value.clamp(-1., 1.) * 127. + 128.) / 255.;
+    [channel(offset[0]), channel(offset[1])]
+}
+
+// Inverse of encode_displacement.
+pub fn decode_displacement(channels: [f32; 2]) -> Vector2<f32> {
+    let offset = |value: f32| ((value * 255. - 128.) / 127.).clamp(-1., 1.);
+    Vector2::new(offset(channels[0]), offset(channels[1]))
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DistortionPushConstants {
+    strength: [f32; 2],
+}
+
+impl DistortionPushConstants {
+    // Maximum offset of the scene texture coordinates per axis, for a displacement of 1.
+    pub fn new(strength: &Vector2<f32>) -> Self {
+        Self {
+            strength: [strength[0], strength[1]],
+        }
+    }
+
+    // Maximum offset in pixels of the render target, the same along both axes.
+    pub fn from_pixels(max_offset: f32, target_size: &gfx::CanvasSize) -> Self {
+        Self::new(&Vector2::new(
+            max_offset / target_size.width() as f32,
+            max_offset / target_size.height() as f32,
+        ))
+    }
+
+    pub fn strength(&self) -> Vector2<f32> {
+        let strength = self.strength;
+        Vector2::from(strength)
+    }
+}
+
+impl Default for DistortionPushConstants {
+    fn default() -> Self {
+        Self::new(&Vector2::new(0.01, 0.01))
+    }
+}
+
+unsafe impl bytemuck::Zeroable for DistortionPushConstants {
+    fn zeroed() -> Self {
+        Self::new(&Vector2::new(0., 0.))
+    }
+}
+
+unsafe impl bytemuck::Pod for DistortionPushConstants {}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    let texture_entry = |binding| gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: true },
+            view_dimension: gfx::TextureViewDimension::D2,
+        },
+        count: None,
+    };
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                texture_entry(0),
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+                texture_entry(2),
+            ],
+        },
+    )
+}
+
+// The scene texture and the displacement buffer, both sampled with the same sampler. A clamping
+// sampler avoids wrapping the scene around the screen edges.
+#[derive(Debug)]
+pub struct DistortionUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl DistortionUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+        displacement_texture: &gfx::TextureView,
+    ) -> Self {
+        Self::new_with_label(instance, None, texture, sampler, displacement_texture)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+        displacement_texture: &gfx::TextureView,
+    ) -> Self {
+        let layout = bind_group_layout(instance);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label,
+                layout: &layout,
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(texture),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 2,
+                        resource: gfx::BindingResource::TextureView(displacement_texture),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DistortionPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for DistortionPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<DistortionPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+// Full screen pass drawing the scene texture warped by the offsets of a displacement buffer,
+// e.g. for heat haze or refraction through water. The displacement buffer is cleared to
+// NEUTRAL_DISPLACEMENT and the distorting meshes render their offsets into it beforehand.
+#[derive(Debug)]
+pub struct DistortionPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl DistortionPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &DistortionPipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::FRAGMENT,
+                    range: 0..PC_SIZE,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/distortion.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: None,
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait DistortionRenderer<'a> {
+    fn draw_distortion(
+        &mut self,
+        pipeline: &'a DistortionPipeline,
+        uniform_constants: &'a DistortionUniformConstants,
+        push_constants: &DistortionPushConstants,
+    );
+}
+
+impl<'a> DistortionRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_distortion(
+        &mut self,
+        pipeline: &'a DistortionPipeline,
+        uniform_constants: &'a DistortionUniformConstants,
+        push_constants: &DistortionPushConstants,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn displacement_encoding() {
+        let neutral = [NEUTRAL_DISPLACEMENT.r as f32, NEUTRAL_DISPLACEMENT.g as f32];
+        expect_that!(&encode_displacement(&Vector2::new(0., 0.)), eq(neutral));
+        expect_that!(&decode_displacement(neutral), eq(Vector2::new(0., 0.)));
+        for offset in [
+            Vector2::new(1., -1.),
+            Vector2::new(0.5, -0.25),
+            Vector2::new(-0.75, 0.1),
+        ] {
+            let decoded = decode_displacement(encode_displacement(&offset));
+            expect_that!(&decoded[0], close_to(offset[0], 1e-5));
+            expect_that!(&decoded[1], close_to(offset[1], 1e-5));
+        }
+        expect_that!(
+            &decode_displacement(encode_displacement(&Vector2::new(3., -3.))),
+            eq(Vector2::new(1., -1.))
+        );
+    }
+
+    #[test]
+    fn strength_in_pixels() {
+        let pc = DistortionPushConstants::from_pixels(8., &gfx::CanvasSize::new(800, 400));
+        expect_that!(&pc.strength(), eq(Vector2::new(0.01, 0.02)));
+    }
+}
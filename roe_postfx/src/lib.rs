@@ -0,0 +1,32 @@
+mod anti_aliasing;
+pub use anti_aliasing::*;
+
+mod auto_exposure;
+pub use auto_exposure::*;
+
+mod bloom;
+pub use bloom::*;
+
+mod color_filter;
+pub use color_filter::*;
+
+mod color_grading;
+pub use color_grading::*;
+
+mod colorblind;
+pub use colorblind::*;
+
+mod distortion;
+pub use distortion::*;
+
+mod outline;
+pub use outline::*;
+
+mod resolution_scaling;
+pub use resolution_scaling::*;
+
+mod wetness;
+pub use wetness::*;
+
+mod water_reflection;
+pub use water_reflection::*;
@@ -0,0 +1,435 @@
+use roe_graphics as gfx;
+
+use std::collections::HashMap;
+
+pub const MAX_OUTLINE_GROUPS: usize = 4;
+
+// Object ids are stored in the rgb channels of the id buffer.
+pub const MAX_OBJECT_ID: u32 = 0x00FF_FFFF;
+
+// Color to write into the id buffer for the object. Id 0 is reserved for the background. The id
+// buffer must have an Rgba8Unorm color buffer, and the ids must be written without blending or
+// color conversion.
+pub fn object_id_color(id: u32, group: Option<usize>) -> gfx::ColorF32 {
+    assert!(id <= MAX_OBJECT_ID, "The object id is out of range");
+    let group = match group {
+        Some(group) => {
+            assert!(
+                group < MAX_OUTLINE_GROUPS,
+                "The selection group index is out of range"
+            );
+            group as u32 + 1
+        }
+        None => 0,
+    };
+    let channel = |value: u32| (value & 0xFF) as f32 / 255.;
+    gfx::ColorF32 {
+        r: channel(id),
+        g: channel(id >> 8),
+        b: channel(id >> 16),
+        a: channel(group),
+    }
+}
+
+// Inverse of object_id_color, e.g. for picking from an id buffer read back to the cpu.
+pub fn object_id_from_texel(texel: [u8; 4]) -> (u32, Option<usize>) {
+    let id = texel[0] as u32 | (texel[1] as u32) << 8 | (texel[2] as u32) << 16;
+    let group = match texel[3] as usize {
+        0 => None,
+        group => Some(group - 1),
+    };
+    (id, group)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct OutlineGroup {
+    pub color: gfx::ColorF32,
+    // Outline width in pixels, 0 disables the group.
+    pub width: u32,
+}
+
+impl Default for OutlineGroup {
+    fn default() -> Self {
+        Self {
+            color: gfx::ColorF32::YELLOW,
+            width: 2,
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct OutlinePushConstants {
+    group_colors: [gfx::ColorF32; MAX_OUTLINE_GROUPS],
+    group_widths: [u32; MAX_OUTLINE_GROUPS],
+}
+
+impl OutlinePushConstants {
+    pub fn new(groups: &[OutlineGroup; MAX_OUTLINE_GROUPS]) -> Self {
+        Self {
+            group_colors: groups.map(|group| group.color),
+            group_widths: groups.map(|group| group.width),
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for OutlinePushConstants {
+    fn zeroed() -> Self {
+        Self {
+            group_colors: [gfx::ColorF32::default(); MAX_OUTLINE_GROUPS],
+            group_widths: [0; MAX_OUTLINE_GROUPS],
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for OutlinePushConstants {}
+
+// Selected object ids and the appearance of each selection group, e.g. the player units in
+// green and the targeted enemy in red.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OutlineSelection {
+    groups: [OutlineGroup; MAX_OUTLINE_GROUPS],
+    selected: HashMap<u32, usize>,
+    push_constants: OutlinePushConstants,
+}
+
+impl OutlineSelection {
+    pub fn new() -> Self {
+        let groups = [OutlineGroup::default(); MAX_OUTLINE_GROUPS];
+        Self {
+            groups,
+            selected: HashMap::new(),
+            push_constants: OutlinePushConstants::new(&groups),
+        }
+    }
+
+    pub fn group(&self, index: usize) -> &OutlineGroup {
+        &self.groups[index]
+    }
+
+    pub fn set_group(&mut self, index: usize, group: OutlineGroup) {
+        assert!(
+            index < MAX_OUTLINE_GROUPS,
+            "The selection group index is out of range"
+        );
+        self.groups[index] = group;
+        self.push_constants = OutlinePushConstants::new(&self.groups);
+    }
+
+    // Moves the object to the group if already selected.
+    pub fn select(&mut self, id: u32, group: usize) {
+        assert!(
+            id != 0 && id <= MAX_OBJECT_ID,
+            "The object id is out of range"
+        );
+        assert!(
+            group < MAX_OUTLINE_GROUPS,
+            "The selection group index is out of range"
+        );
+        self.selected.insert(id, group);
+    }
+
+    pub fn deselect(&mut self, id: u32) -> bool {
+        self.selected.remove(&id).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.selected.clear();
+    }
+
+    pub fn group_of(&self, id: u32) -> Option<usize> {
+        self.selected.get(&id).copied()
+    }
+
+    pub fn selected_count(&self) -> usize {
+        self.selected.len()
+    }
+
+    // If false the pass can be skipped.
+    pub fn is_enabled(&self) -> bool {
+        !self.selected.is_empty()
+    }
+
+    // Color to write into the id buffer for the object, including its selection group.
+    pub fn id_color(&self, id: u32) -> gfx::ColorF32 {
+        object_id_color(id, self.group_of(id))
+    }
+
+    pub fn push_constants(&self) -> &OutlinePushConstants {
+        &self.push_constants
+    }
+}
+
+impl Default for OutlineSelection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[gfx::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: gfx::ShaderStage::FRAGMENT,
+                ty: gfx::BindingType::Texture {
+                    multisampled: false,
+                    sample_type: gfx::TextureSampleType::Float { filterable: false },
+                    view_dimension: gfx::TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        },
+    )
+}
+
+// The id buffer, with the same size as the render target of the pass. The texels are fetched
+// without filtering.
+#[derive(Debug)]
+pub struct OutlineUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl OutlineUniformConstants {
+    pub fn new(instance: &gfx::Instance, id_texture: &gfx::TextureView) -> Self {
+        Self::new_with_label(instance, None, id_texture)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        id_texture: &gfx::TextureView,
+    ) -> Self {
+        let layout = bind_group_layout(instance);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label,
+                layout: &layout,
+                entries: &[gfx::BindGroupEntry {
+                    binding: 0,
+                    resource: gfx::BindingResource::TextureView(id_texture),
+                }],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OutlinePipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for OutlinePipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<OutlinePushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+// Full screen pass blending colored edges over the scene around the selected objects of an id
+// buffer. The edges are drawn outside the objects, also where they overlap other objects.
+#[derive(Debug)]
+pub struct OutlinePipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl OutlinePipeline {
+    pub fn new(instance: &gfx::Instance, desc: &OutlinePipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::FRAGMENT,
+                    range: 0..PC_SIZE,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/outline.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: Some(gfx::BlendState::ALPHA_BLENDING),
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait OutlineRenderer<'a> {
+    fn draw_outline(
+        &mut self,
+        pipeline: &'a OutlinePipeline,
+        uniform_constants: &'a OutlineUniformConstants,
+        push_constants: &OutlinePushConstants,
+    );
+}
+
+impl<'a> OutlineRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_outline(
+        &mut self,
+        pipeline: &'a OutlinePipeline,
+        uniform_constants: &'a OutlineUniformConstants,
+        push_constants: &OutlinePushConstants,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn to_texel(color: gfx::ColorF32) -> [u8; 4] {
+        let channel = |value: f32| (value * 255.).round() as u8;
+        [
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            channel(color.a),
+        ]
+    }
+
+    #[test]
+    fn id_encoding() {
+        for (id, group) in [
+            (0, None),
+            (1, Some(0)),
+            (0x12_3456, Some(3)),
+            (MAX_OBJECT_ID, None),
+        ] {
+            let texel = to_texel(object_id_color(id, group));
+            expect_that!(&object_id_from_texel(texel), eq((id, group)));
+        }
+        expect_that!(
+            &to_texel(object_id_color(0x01_0203, Some(1))),
+            eq([3, 2, 1, 2])
+        );
+    }
+
+    #[test]
+    fn selection() {
+        let mut selection = OutlineSelection::new();
+        expect_that!(!selection.is_enabled());
+        selection.select(5, 1);
+        selection.select(7, 0);
+        selection.select(5, 2);
+        expect_that!(&selection.selected_count(), eq(2));
+        expect_that!(&selection.group_of(5), eq(Some(2)));
+        expect_that!(&selection.id_color(5), eq(object_id_color(5, Some(2))));
+        expect_that!(&selection.id_color(6), eq(object_id_color(6, None)));
+        expect_that!(selection.deselect(5));
+        expect_that!(!selection.deselect(5));
+        selection.clear();
+        expect_that!(!selection.is_enabled());
+    }
+
+    #[test]
+    fn groups() {
+        let mut selection = OutlineSelection::new();
+        let group = OutlineGroup {
+            color: gfx::ColorF32::RED,
+            width: 4,
+        };
+        selection.set_group(2, group);
+        expect_that!(selection.group(2), eq(group));
+        let mut groups = [OutlineGroup::default(); MAX_OUTLINE_GROUPS];
+        groups[2] = group;
+        expect_that!(
+            selection.push_constants(),
+            eq(OutlinePushConstants::new(&groups))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The object id is out of range")]
+    fn background_id_selection() {
+        OutlineSelection::new().select(0, 0);
+    }
+}
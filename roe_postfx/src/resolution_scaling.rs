@@ -0,0 +1,564 @@
+use roe_graphics as gfx;
+
+// Size of the internal canvas for the scale, at least one pixel per side.
+pub fn scaled_canvas_size(window_size: &gfx::CanvasSize, scale: f32) -> gfx::CanvasSize {
+    let scaled = |value: u32| ((value as f32 * scale).round() as u32).max(1);
+    gfx::CanvasSize::new(scaled(window_size.width()), scaled(window_size.height()))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScaledCanvasDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub depth_stencil_buffer_format: Option<gfx::CanvasDepthStencilBufferFormat>,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for ScaledCanvasDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            depth_stencil_buffer_format: None,
+            sample_count: 1,
+        }
+    }
+}
+
+// Canvas the scene is rendered to at a fraction of the window size, before being upscaled to the
+// window with UpscaleRenderer::draw_upscale.
+#[derive(Debug)]
+pub struct ScaledCanvas {
+    canvas: gfx::CanvasTexture,
+    desc: ScaledCanvasDescriptor,
+    window_size: gfx::CanvasSize,
+    scale: f32,
+    graphics_settings: Option<gfx::GraphicsSettingsSubscriber>,
+}
+
+impl ScaledCanvas {
+    pub fn new(
+        instance: &gfx::Instance,
+        desc: &ScaledCanvasDescriptor,
+        window_size: &gfx::CanvasSize,
+        scale: f32,
+    ) -> Result<Self, gfx::CanvasBufferError> {
+        Ok(Self {
+            canvas: Self::create_canvas(instance, desc, &scaled_canvas_size(window_size, scale))?,
+            desc: desc.clone(),
+            window_size: *window_size,
+            scale,
+            graphics_settings: None,
+        })
+    }
+
+    // The canvas is recreated only if its size changes, in which case true is returned and the
+    // uniform constants referencing the old color buffer must be recreated.
+    pub fn resize(
+        &mut self,
+        instance: &gfx::Instance,
+        window_size: &gfx::CanvasSize,
+        scale: f32,
+    ) -> Result<bool, gfx::CanvasBufferError> {
+        let size = scaled_canvas_size(window_size, scale);
+        self.window_size = *window_size;
+        self.scale = scale;
+        if size == *self.size() {
+            return Ok(false);
+        }
+        self.canvas = Self::create_canvas(instance, &self.desc, &size)?;
+        Ok(true)
+    }
+
+    // The scale and sample count follow the resolution scale and MSAA level of the settings,
+    // starting from the current ones. Not to be combined with an automatic DynamicResolution,
+    // which picks the scale itself. Returns true if the canvas was recreated.
+    pub fn subscribe_graphics_settings(
+        &mut self,
+        instance: &gfx::Instance,
+        settings: &gfx::RuntimeGraphicsSettings,
+    ) -> Result<bool, gfx::CanvasBufferError> {
+        let subscriber = settings.subscribe(
+            gfx::GraphicsSettingsChanges::MSAA | gfx::GraphicsSettingsChanges::RESOLUTION_SCALE,
+        );
+        let current = *subscriber.settings();
+        self.graphics_settings = Some(subscriber);
+        self.apply_graphics_settings(instance, &current)
+    }
+
+    // Applies the settings changed since the last update, e.g. at the start of a frame. Returns
+    // true if the canvas was recreated, see resize.
+    pub fn update_graphics_settings(
+        &mut self,
+        instance: &gfx::Instance,
+    ) -> Result<bool, gfx::CanvasBufferError> {
+        match self.graphics_settings.as_mut().and_then(|s| s.poll()) {
+            Some(update) => self.apply_graphics_settings(instance, &update.settings),
+            None => Ok(false),
+        }
+    }
+
+    // With a new sample count the pipelines rendering to the canvas must be recreated as well.
+    pub fn apply_graphics_settings(
+        &mut self,
+        instance: &gfx::Instance,
+        settings: &gfx::GraphicsSettings,
+    ) -> Result<bool, gfx::CanvasBufferError> {
+        let sample_count = settings.msaa.sample_count();
+        if sample_count == self.desc.sample_count {
+            let window_size = self.window_size;
+            return self.resize(instance, &window_size, settings.resolution_scale);
+        }
+        let size = scaled_canvas_size(&self.window_size, settings.resolution_scale);
+        let desc = ScaledCanvasDescriptor {
+            sample_count,
+            ..self.desc.clone()
+        };
+        self.canvas = Self::create_canvas(instance, &desc, &size)?;
+        self.desc = desc;
+        self.scale = settings.resolution_scale;
+        Ok(true)
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn sample_count(&self) -> gfx::SampleCount {
+        self.desc.sample_count
+    }
+
+    pub fn window_size(&self) -> &gfx::CanvasSize {
+        &self.window_size
+    }
+
+    pub fn size(&self) -> &gfx::CanvasSize {
+        gfx::Canvas::canvas_size(&self.canvas)
+    }
+
+    pub fn canvas(&self) -> &gfx::CanvasTexture {
+        &self.canvas
+    }
+
+    pub fn canvas_mut(&mut self) -> &mut gfx::CanvasTexture {
+        &mut self.canvas
+    }
+
+    pub fn color_texture_view(&self) -> &gfx::TextureView {
+        self.canvas.color_texture_view().unwrap()
+    }
+
+    fn create_canvas(
+        instance: &gfx::Instance,
+        desc: &ScaledCanvasDescriptor,
+        size: &gfx::CanvasSize,
+    ) -> Result<gfx::CanvasTexture, gfx::CanvasBufferError> {
+        gfx::CanvasTexture::new(
+            instance,
+            &gfx::CanvasTextureDescriptor {
+                label: desc.label.clone(),
+                size: *size,
+                sample_count: desc.sample_count,
+                color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
+                    format: desc.color_buffer_format,
+                    usage: gfx::CanvasColorBufferUsage::TEXTURE_BINDING,
+                    sample_count: None,
+                }),
+                depth_stencil_buffer_format: desc.depth_stencil_buffer_format,
+            },
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DynamicResolutionDescriptor {
+    // Frame time budget, in seconds.
+    pub target_frame_time: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    // Scales are multiples of the step, so that the canvas isn't recreated for small changes.
+    pub scale_step: f32,
+    // Fraction of the budget the frame time can differ from it before the scale changes.
+    pub tolerance: f32,
+    // Frames averaged before the scale can change again.
+    pub frames_between_changes: u32,
+}
+
+impl Default for DynamicResolutionDescriptor {
+    fn default() -> Self {
+        Self {
+            target_frame_time: 1. / 60.,
+            min_scale: 0.5,
+            max_scale: 1.,
+            scale_step: 0.05,
+            tolerance: 0.1,
+            frames_between_changes: 30,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ResolutionScaleMode {
+    Fixed(f32),
+    // Adjusted to the frame time, see DynamicResolution.
+    Automatic(DynamicResolutionDescriptor),
+}
+
+impl Default for ResolutionScaleMode {
+    fn default() -> Self {
+        Self::Fixed(1.)
+    }
+}
+
+// Picks the resolution scale keeping the frame time within the budget. The frame time is assumed
+// to be roughly proportional to the number of rendered pixels: when over budget the scale drops
+// to the estimated fitting value at once, when under budget it grows a step at a time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DynamicResolution {
+    mode: ResolutionScaleMode,
+    scale: f32,
+    frame_time_sum: f32,
+    frame_count: u32,
+}
+
+impl DynamicResolution {
+    pub fn new(mode: ResolutionScaleMode) -> Self {
+        let scale = match mode {
+            ResolutionScaleMode::Fixed(scale) => scale,
+            ResolutionScaleMode::Automatic(desc) => desc.max_scale,
+        };
+        Self {
+            mode,
+            scale,
+            frame_time_sum: 0.,
+            frame_count: 0,
+        }
+    }
+
+    pub fn mode(&self) -> &ResolutionScaleMode {
+        &self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ResolutionScaleMode) {
+        *self = Self::new(mode);
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // Call once per frame with the duration of the last frame, in seconds. Returns the scale for
+    // the next frame.
+    pub fn update(&mut self, frame_time: f32) -> f32 {
+        let desc = match self.mode {
+            ResolutionScaleMode::Fixed(scale) => {
+                self.scale = scale;
+                return scale;
+            }
+            ResolutionScaleMode::Automatic(desc) => desc,
+        };
+        self.frame_time_sum += frame_time.max(0.);
+        self.frame_count += 1;
+        if self.frame_count < desc.frames_between_changes.max(1) {
+            return self.scale;
+        }
+        let average = self.frame_time_sum / self.frame_count as f32;
+        self.frame_time_sum = 0.;
+        self.frame_count = 0;
+
+        let step = desc.scale_step.max(f32::EPSILON);
+        let ratio = desc.target_frame_time / average.max(f32::EPSILON);
+        let scale = if ratio < 1. - desc.tolerance {
+            // Rounded down, to get back within the budget.
+            let estimate = self.scale * ratio.sqrt();
+            (estimate / step).floor() * step
+        } else if ratio > 1. + desc.tolerance {
+            self.scale + step
+        } else {
+            self.scale
+        };
+        self.scale = scale.clamp(desc.min_scale, desc.max_scale);
+        self.scale
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Float { filterable: true },
+                        view_dimension: gfx::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+// The scaled canvas color buffer, sampled with a linear filter.
+#[derive(Debug)]
+pub struct UpscaleUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl UpscaleUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        let layout = bind_group_layout(instance);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &layout,
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(texture),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum UpscaleFilter {
+    #[default]
+    Bilinear,
+    // Bilinear followed by contrast adaptive sharpening, recovering some of the detail lost when
+    // rendering at a lower resolution. The sharpness goes from 0 to 1.
+    Sharpen(f32),
+}
+
+impl UpscaleFilter {
+    fn sharpness(&self) -> f32 {
+        match self {
+            Self::Bilinear => 0.,
+            Self::Sharpen(sharpness) => sharpness.clamp(f32::EPSILON, 1.),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpscalePipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for UpscalePipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<f32>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+// Full screen pass stretching a texture over the whole target.
+#[derive(Debug)]
+pub struct UpscalePipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl UpscalePipeline {
+    pub fn new(instance: &gfx::Instance, desc: &UpscalePipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::FRAGMENT,
+                    range: 0..PC_SIZE,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/color_filter.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/upscale.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: None,
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait UpscaleRenderer<'a> {
+    fn draw_upscale(
+        &mut self,
+        pipeline: &'a UpscalePipeline,
+        uniform_constants: &'a UpscaleUniformConstants,
+        filter: UpscaleFilter,
+    );
+}
+
+impl<'a> UpscaleRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_upscale(
+        &mut self,
+        pipeline: &'a UpscalePipeline,
+        uniform_constants: &'a UpscaleUniformConstants,
+        filter: UpscaleFilter,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            0,
+            gfx::utility::as_slice(&filter.sharpness()),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn canvas_size() {
+        let window = gfx::CanvasSize::new(1920, 1080);
+        expect_that!(
+            &scaled_canvas_size(&window, 0.5),
+            eq(gfx::CanvasSize::new(960, 540))
+        );
+        expect_that!(
+            &scaled_canvas_size(&window, 0.),
+            eq(gfx::CanvasSize::new(1, 1))
+        );
+    }
+
+    #[test]
+    fn fixed_scale() {
+        let mut resolution = DynamicResolution::new(ResolutionScaleMode::Fixed(0.75));
+        expect_that!(&resolution.update(1.), eq(0.75));
+        expect_that!(&resolution.scale(), eq(0.75));
+    }
+
+    #[test]
+    fn automatic_scale() {
+        let desc = DynamicResolutionDescriptor {
+            target_frame_time: 0.01,
+            frames_between_changes: 2,
+            ..DynamicResolutionDescriptor::default()
+        };
+        let mut resolution = DynamicResolution::new(ResolutionScaleMode::Automatic(desc));
+        expect_that!(&resolution.scale(), eq(1.));
+
+        // Twice over budget: about 70% of the pixels are kept, rounded down to the step.
+        expect_that!(&resolution.update(0.02), eq(1.));
+        expect_that!(&resolution.update(0.02), close_to(0.7, 1e-5));
+
+        // Within the tolerance.
+        resolution.update(0.0105);
+        expect_that!(&resolution.update(0.0105), close_to(0.7, 1e-5));
+
+        // Under budget: one step at a time, up to the maximum.
+        resolution.update(0.005);
+        expect_that!(&resolution.update(0.005), close_to(0.75, 1e-5));
+        for _ in 0..20 {
+            resolution.update(0.005);
+        }
+        expect_that!(&resolution.scale(), eq(1.));
+
+        // Never below the minimum.
+        resolution.update(1.);
+        expect_that!(&resolution.update(1.), eq(0.5));
+    }
+}
@@ -0,0 +1,28 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uSceneTex;
+layout(set = 0, binding = 1) uniform texture2D uBloomTex;
+layout(set = 0, binding = 2) uniform sampler uTexSampler;
+layout(push_constant) uniform PushConstant {
+    // Size of a texel of the source texture, in texture coordinates.
+    vec2 texelSize;
+    float threshold;
+    float softKnee;
+    float intensity;
+    float radius;
+    // BloomSource of the first downsample pass plus one, 0 for the other passes.
+    uint prefilter;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+    vec3 scene = texture(sampler2D(uSceneTex, uTexSampler), inTexCoords).rgb;
+    vec3 bloom = texture(sampler2D(uBloomTex, uTexSampler), inTexCoords).rgb;
+    vec3 color = scene + bloom * pushConstant.intensity;
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), 1.);
+}
@@ -0,0 +1,50 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uSourceTex;
+layout(set = 0, binding = 1) uniform sampler uSourceTexSampler;
+layout(push_constant) uniform PushConstant {
+    // Size of a texel of the source texture, in texture coordinates.
+    vec2 texelSize;
+    float threshold;
+    float softKnee;
+    float intensity;
+    float radius;
+    // BloomSource of the first downsample pass plus one, 0 for the other passes.
+    uint prefilter;
+    uint colorConversion;
+} pushConstant;
+
+vec3 sampleSource(vec2 texCoords) {
+    vec4 color = texture(sampler2D(uSourceTex, uSourceTexSampler), texCoords);
+    // Emissive mask: the alpha is the fraction of the color emitted by the surface.
+    if (pushConstant.prefilter == 2u) {
+        return color.rgb * clamp(color.a, 0., 1.);
+    }
+    return color.rgb;
+}
+
+// Keeps the part of the color above the threshold, with a quadratic transition.
+vec3 applyThreshold(vec3 color) {
+    float brightness = max(color.r, max(color.g, color.b));
+    float knee = pushConstant.threshold * pushConstant.softKnee;
+    float soft = clamp(brightness - pushConstant.threshold + knee, 0., 2. * knee);
+    soft = soft * soft / (4. * knee + 1e-5);
+    float contribution = max(soft, brightness - pushConstant.threshold) / max(brightness, 1e-5);
+    return color * contribution;
+}
+
+// Average of 4 bilinear samples, covering 4x4 source texels.
+void main() {
+    vec2 d = pushConstant.texelSize;
+    vec3 color = (sampleSource(inTexCoords + vec2(-d.x, -d.y))
+        + sampleSource(inTexCoords + vec2(d.x, -d.y))
+        + sampleSource(inTexCoords + vec2(-d.x, d.y))
+        + sampleSource(inTexCoords + vec2(d.x, d.y))) * 0.25;
+    if (pushConstant.prefilter == 1u) {
+        color = applyThreshold(color);
+    }
+    outColor = vec4(color, 1.);
+}
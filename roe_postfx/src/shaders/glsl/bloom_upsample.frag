@@ -0,0 +1,37 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uSourceTex;
+layout(set = 0, binding = 1) uniform sampler uSourceTexSampler;
+layout(push_constant) uniform PushConstant {
+    // Size of a texel of the source texture, in texture coordinates.
+    vec2 texelSize;
+    float threshold;
+    float softKnee;
+    float intensity;
+    float radius;
+    // BloomSource of the first downsample pass plus one, 0 for the other passes.
+    uint prefilter;
+    uint colorConversion;
+} pushConstant;
+
+vec3 sampleSource(vec2 texCoords) {
+    return texture(sampler2D(uSourceTex, uSourceTexSampler), texCoords).rgb;
+}
+
+// 3x3 tent filter, added to the larger mip by the blend state.
+void main() {
+    vec2 d = pushConstant.texelSize * pushConstant.radius;
+    vec3 color = sampleSource(inTexCoords) * 4.
+        + (sampleSource(inTexCoords + vec2(-d.x, 0.))
+            + sampleSource(inTexCoords + vec2(d.x, 0.))
+            + sampleSource(inTexCoords + vec2(0., -d.y))
+            + sampleSource(inTexCoords + vec2(0., d.y))) * 2.
+        + sampleSource(inTexCoords + vec2(-d.x, -d.y))
+        + sampleSource(inTexCoords + vec2(d.x, -d.y))
+        + sampleSource(inTexCoords + vec2(-d.x, d.y))
+        + sampleSource(inTexCoords + vec2(d.x, d.y));
+    outColor = vec4(color / 16., 1.);
+}
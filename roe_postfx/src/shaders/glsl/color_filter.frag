@@ -0,0 +1,19 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(push_constant) uniform PushConstant {
+    mat4 colorMatrix;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+    vec4 texColor = texture(sampler2D(uColorTex, uColorTexSampler), inTexCoords);
+    vec3 color = (pushConstant.colorMatrix * vec4(texColor.rgb, 0.)).rgb;
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), texColor.a);
+}
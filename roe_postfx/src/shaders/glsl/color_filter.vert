@@ -0,0 +1,11 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 outTexCoords;
+
+// Full screen triangle, generated from the vertex index.
+void main() {
+    vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(position * 2. - 1., 0., 1.);
+    outTexCoords = vec2(position.x, 1. - position.y);
+}
@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(set = 0, binding = 2) uniform texture3D uLutTex0;
+layout(set = 0, binding = 3) uniform texture3D uLutTex1;
+layout(set = 0, binding = 4) uniform sampler uLutSampler;
+layout(push_constant) uniform PushConstant {
+    float blend;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+// Maps the color to the centers of the first and last texels, so that the LUT entries are
+// interpolated linearly.
+vec3 lutCoords(vec3 color, float size) {
+    return clamp(color, 0., 1.) * ((size - 1.) / size) + 0.5 / size;
+}
+
+void main() {
+    vec4 texColor = texture(sampler2D(uColorTex, uColorTexSampler), inTexCoords);
+    vec3 coords0 = lutCoords(texColor.rgb, float(textureSize(uLutTex0, 0).x));
+    vec3 coords1 = lutCoords(texColor.rgb, float(textureSize(uLutTex1, 0).x));
+    vec3 graded0 = texture(sampler3D(uLutTex0, uLutSampler), coords0).rgb;
+    vec3 graded1 = texture(sampler3D(uLutTex1, uLutSampler), coords1).rgb;
+    vec3 color = mix(graded0, graded1, pushConstant.blend);
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), texColor.a);
+}
@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(set = 0, binding = 2) uniform texture2D uDisplacementTex;
+layout(push_constant) uniform PushConstant {
+    vec2 strength;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+// The offsets are stored in the rg channels, 128 being no offset.
+vec2 decodeDisplacement(vec2 encoded) {
+    return clamp((encoded * 255. - 128.) / 127., -1., 1.);
+}
+
+void main() {
+    vec2 encoded = texture(sampler2D(uDisplacementTex, uColorTexSampler), inTexCoords).rg;
+    vec2 offset = decodeDisplacement(encoded) * pushConstant.strength;
+    vec4 texColor = texture(sampler2D(uColorTex, uColorTexSampler), inTexCoords + offset);
+    outColor = vec4(convertColor(texColor.rgb, pushConstant.colorConversion), texColor.a);
+}
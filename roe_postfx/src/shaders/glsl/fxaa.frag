@@ -0,0 +1,60 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(push_constant) uniform PushConstant {
+    // Minimum local contrast, relative to the brightest neighbor, for a pixel to be smoothed.
+    float edgeThreshold;
+    // Minimum local contrast in dark areas.
+    float edgeThresholdMin;
+    // Maximum length of the blur along the edge, in pixels.
+    float spanMax;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+vec3 sampleColor(vec2 texCoords) {
+    return texture(sampler2D(uColorTex, uColorTexSampler), texCoords).rgb;
+}
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// The edge direction is estimated from the diagonal neighbors, and the color is blurred along
+// it. The wider blur is rejected if it brings in colors out of the local luma range.
+vec3 fxaa(vec4 center) {
+    vec2 texel = 1. / vec2(textureSize(sampler2D(uColorTex, uColorTexSampler), 0));
+    float lumaNW = luma(sampleColor(inTexCoords + vec2(-1., -1.) * texel));
+    float lumaNE = luma(sampleColor(inTexCoords + vec2(1., -1.) * texel));
+    float lumaSW = luma(sampleColor(inTexCoords + vec2(-1., 1.) * texel));
+    float lumaSE = luma(sampleColor(inTexCoords + vec2(1., 1.) * texel));
+    float lumaM = luma(center.rgb);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+    if (lumaMax - lumaMin
+        < max(pushConstant.edgeThresholdMin, lumaMax * pushConstant.edgeThreshold)) {
+        return center.rgb;
+    }
+
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * 0.125, 1. / 128.);
+    float rcpDirMin = 1. / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-pushConstant.spanMax), vec2(pushConstant.spanMax)) * texel;
+
+    vec3 colorA = 0.5 * (sampleColor(inTexCoords + dir * (1. / 3. - 0.5))
+        + sampleColor(inTexCoords + dir * (2. / 3. - 0.5)));
+    vec3 colorB = colorA * 0.5 + 0.25 * (sampleColor(inTexCoords + dir * -0.5)
+        + sampleColor(inTexCoords + dir * 0.5));
+    float lumaB = luma(colorB);
+    return (lumaB < lumaMin || lumaB > lumaMax) ? colorA : colorB;
+}
+
+void main() {
+    vec4 center = texture(sampler2D(uColorTex, uColorTexSampler), inTexCoords);
+    outColor = vec4(convertColor(fxaa(center), pushConstant.colorConversion), center.a);
+}
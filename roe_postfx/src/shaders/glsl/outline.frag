@@ -0,0 +1,57 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uIdTex;
+layout(push_constant) uniform PushConstant {
+    vec4 groupColors[4];
+    uvec4 groupWidths;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+// Object id in the rgb channels, selection group in the alpha channel (0 if not selected).
+uvec4 fetchId(ivec2 position) {
+    return uvec4(round(texelFetch(uIdTex, position, 0) * 255.));
+}
+
+uint objectId(uvec4 texel) {
+    return texel.r | (texel.g << 8) | (texel.b << 16);
+}
+
+void main() {
+    ivec2 size = textureSize(uIdTex, 0);
+    ivec2 center = ivec2(gl_FragCoord.xy);
+    uint centerId = objectId(fetchId(center));
+    uvec4 widths = pushConstant.groupWidths;
+    int maxWidth = int(max(max(widths.x, widths.y), max(widths.z, widths.w)));
+
+    // Nearest selected pixel of another object within the width of its group.
+    float bestDistance = float(maxWidth) + 1.;
+    uint bestGroup = 0u;
+    for (int y = -maxWidth; y <= maxWidth; ++y) {
+        for (int x = -maxWidth; x <= maxWidth; ++x) {
+            ivec2 position = center + ivec2(x, y);
+            if (any(lessThan(position, ivec2(0))) || any(greaterThanEqual(position, size))) {
+                continue;
+            }
+            uvec4 texel = fetchId(position);
+            uint group = texel.a;
+            if (group == 0u || group > 4u || objectId(texel) == centerId) {
+                continue;
+            }
+            float distance = length(vec2(x, y));
+            if (distance <= float(widths[group - 1u]) && distance < bestDistance) {
+                bestDistance = distance;
+                bestGroup = group;
+            }
+        }
+    }
+    if (bestGroup == 0u) {
+        discard;
+    }
+    vec4 color = pushConstant.groupColors[bestGroup - 1u];
+    outColor = vec4(convertColor(color.rgb, pushConstant.colorConversion), color.a);
+}
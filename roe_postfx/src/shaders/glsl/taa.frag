@@ -0,0 +1,33 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform texture2D uHistoryTex;
+layout(set = 0, binding = 2) uniform sampler uColorTexSampler;
+layout(push_constant) uniform PushConstant {
+    // Weight of the history, 0 if the history is invalid.
+    float feedback;
+} pushConstant;
+
+// The history is clamped to the range of the current neighborhood, which limits ghosting when
+// the image changes.
+void main() {
+    ivec2 size = textureSize(sampler2D(uColorTex, uColorTexSampler), 0);
+    ivec2 coords = ivec2(inTexCoords * vec2(size));
+    vec4 current = texelFetch(sampler2D(uColorTex, uColorTexSampler), coords, 0);
+    vec4 minColor = current;
+    vec4 maxColor = current;
+    for (int y = -1; y <= 1; ++y) {
+        for (int x = -1; x <= 1; ++x) {
+            ivec2 neighbor = clamp(coords + ivec2(x, y), ivec2(0), size - 1);
+            vec4 color = texelFetch(sampler2D(uColorTex, uColorTexSampler), neighbor, 0);
+            minColor = min(minColor, color);
+            maxColor = max(maxColor, color);
+        }
+    }
+    vec4 history = texture(sampler2D(uHistoryTex, uColorTexSampler), inTexCoords);
+    history = clamp(history, minColor, maxColor);
+    outColor = mix(current, history, pushConstant.feedback);
+}
@@ -0,0 +1,42 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(push_constant) uniform PushConstant {
+    // From 0 to 1, 0 disables sharpening.
+    float sharpness;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+vec3 sampleColor(vec2 texCoords) {
+    return texture(sampler2D(uColorTex, uColorTexSampler), texCoords).rgb;
+}
+
+// Contrast adaptive sharpening on the upsampled image: the cross of neighbors one source texel
+// away is subtracted, less where the local contrast is already high, to avoid halos.
+vec3 sharpen(vec3 center, float sharpness) {
+    vec2 texel = 1. / vec2(textureSize(sampler2D(uColorTex, uColorTexSampler), 0));
+    vec3 n = sampleColor(inTexCoords - vec2(0., texel.y));
+    vec3 s = sampleColor(inTexCoords + vec2(0., texel.y));
+    vec3 e = sampleColor(inTexCoords + vec2(texel.x, 0.));
+    vec3 w = sampleColor(inTexCoords - vec2(texel.x, 0.));
+    vec3 minColor = min(center, min(min(n, s), min(e, w)));
+    vec3 maxColor = max(center, max(max(n, s), max(e, w)));
+    vec3 amplitude = sqrt(clamp(min(minColor, 1. - maxColor) / max(maxColor, vec3(1e-4)), 0., 1.));
+    vec3 weight = amplitude * (-1. / mix(8., 5., sharpness));
+    return clamp((center + (n + s + e + w) * weight) / (1. + 4. * weight), 0., 1.);
+}
+
+void main() {
+    vec4 texColor = texture(sampler2D(uColorTex, uColorTexSampler), inTexCoords);
+    vec3 color = texColor.rgb;
+    if (pushConstant.sharpness > 0.) {
+        color = sharpen(color, clamp(pushConstant.sharpness, 0., 1.));
+    }
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), texColor.a);
+}
@@ -0,0 +1,43 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(push_constant) uniform PushConstant {
+    vec4 region;
+    vec4 tint;
+    float rippleAmplitude;
+    float rippleFrequency;
+    float ripplePhase;
+    float surfaceOpacity;
+    float bottomOpacity;
+    uint mirror;
+    uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+    vec4 region = pushConstant.region;
+    float depth = clamp((inTexCoords.y - region.y) / max(region.w - region.y, 1e-5), 0., 1.);
+
+    // The scene above the surface is mirrored, unless it was already rendered flipped into a
+    // reflection texture.
+    vec2 coords = inTexCoords;
+    if (pushConstant.mirror != 0u) {
+        coords.y = 2. * region.y - coords.y;
+    }
+
+    // Horizontal waves, growing stronger away from the surface.
+    float ripple = sin(inTexCoords.y * pushConstant.rippleFrequency + pushConstant.ripplePhase);
+    coords.x += ripple * pushConstant.rippleAmplitude * depth;
+
+    // Nothing to reflect outside of the texture.
+    float inside = step(0., coords.y) * step(coords.y, 1.);
+    vec3 color = texture(sampler2D(uColorTex, uColorTexSampler), coords).rgb;
+    color = mix(color, pushConstant.tint.rgb, pushConstant.tint.a);
+    float alpha = mix(pushConstant.surfaceOpacity, pushConstant.bottomOpacity, depth) * inside;
+    outColor = vec4(convertColor(color, pushConstant.colorConversion), alpha);
+}
@@ -0,0 +1,19 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 outTexCoords;
+layout(push_constant) uniform PushConstant {
+    vec4 region;
+} pushConstant;
+
+// Quad covering the water region, generated from the vertex index. The region is in texture
+// coordinates: left, top (the water surface), right, bottom.
+void main() {
+    const vec2 corners[6] = vec2[](
+        vec2(0., 0.), vec2(0., 1.), vec2(1., 0.),
+        vec2(1., 0.), vec2(0., 1.), vec2(1., 1.));
+    vec2 corner = corners[gl_VertexIndex];
+    vec2 coords = mix(pushConstant.region.xy, pushConstant.region.zw, corner);
+    gl_Position = vec4(coords.x * 2. - 1., 1. - coords.y * 2., 0., 1.);
+    outTexCoords = coords;
+}
@@ -0,0 +1,472 @@
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Vector2};
+
+use std::time::Duration;
+
+// Water area in pixels of the render target, y pointing down. The reflection is drawn between
+// the surface and the bottom.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WaterRegion {
+    pub left: f32,
+    pub right: f32,
+    pub surface: f32,
+    pub bottom: f32,
+}
+
+impl WaterRegion {
+    // Texture coordinates of the region: left, surface, right, bottom.
+    pub fn texture_coordinates(&self, target_size: &gfx::CanvasSize) -> [f32; 4] {
+        let width = target_size.width() as f32;
+        let height = target_size.height() as f32;
+        [
+            self.left / width,
+            self.surface / height,
+            self.right / width,
+            self.bottom / height,
+        ]
+    }
+
+    pub fn contains(&self, point: &Vector2<f32>) -> bool {
+        point[0] >= self.left
+            && point[0] <= self.right
+            && point[1] >= self.surface
+            && point[1] <= self.bottom
+    }
+}
+
+// Flips the scene around the water surface, in the same coordinates as the surface. Applied
+// after the view transform when re-rendering the scene into a reflection texture.
+pub fn water_mirror_transform(surface: f32) -> HomogeneousMatrix2<f32> {
+    HomogeneousMatrix2::new(1., 0., 0., 0., -1., 2. * surface, 0., 0., 1.)
+}
+
+// Where the reflected image comes from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WaterReflectionSource {
+    // The scene texture itself, mirrored by the pass. Objects outside the screen or hidden
+    // behind the water are not reflected.
+    SceneTexture,
+    // A texture where the scene was re-rendered with water_mirror_transform, with the same
+    // size as the render target.
+    ReflectionTexture,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WaterReflectionDescriptor {
+    pub source: WaterReflectionSource,
+    // Color mixed into the reflection, with the alpha as the amount.
+    pub tint: gfx::ColorF32,
+    // Reflection opacity at the surface and at the bottom of the region.
+    pub surface_opacity: f32,
+    pub bottom_opacity: f32,
+    // Horizontal ripple offset in pixels, reached at the bottom of the region.
+    pub ripple_amplitude: f32,
+    // In pixels.
+    pub ripple_wavelength: f32,
+    // Ripple cycles per second.
+    pub ripple_speed: f32,
+}
+
+impl Default for WaterReflectionDescriptor {
+    fn default() -> Self {
+        Self {
+            source: WaterReflectionSource::SceneTexture,
+            tint: gfx::ColorF32 {
+                r: 0.1,
+                g: 0.3,
+                b: 0.5,
+                a: 0.3,
+            },
+            surface_opacity: 0.8,
+            bottom_opacity: 0.2,
+            ripple_amplitude: 3.,
+            ripple_wavelength: 12.,
+            ripple_speed: 1.,
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WaterReflectionPushConstants {
+    region: [f32; 4],
+    tint: gfx::ColorF32,
+    ripple_amplitude: f32,
+    ripple_frequency: f32,
+    ripple_phase: f32,
+    surface_opacity: f32,
+    bottom_opacity: f32,
+    mirror: u32,
+}
+
+impl WaterReflectionPushConstants {
+    pub fn region(&self) -> [f32; 4] {
+        self.region
+    }
+}
+
+unsafe impl bytemuck::Zeroable for WaterReflectionPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            region: [0.; 4],
+            tint: gfx::ColorF32::default(),
+            ripple_amplitude: 0.,
+            ripple_frequency: 0.,
+            ripple_phase: 0.,
+            surface_opacity: 0.,
+            bottom_opacity: 0.,
+            mirror: 0,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for WaterReflectionPushConstants {}
+
+// Animation state of the water reflections, shared by all regions drawn with it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WaterReflection {
+    desc: WaterReflectionDescriptor,
+    phase: f32,
+}
+
+impl WaterReflection {
+    pub fn new(desc: &WaterReflectionDescriptor) -> Self {
+        Self {
+            desc: *desc,
+            phase: 0.,
+        }
+    }
+
+    pub fn descriptor(&self) -> &WaterReflectionDescriptor {
+        &self.desc
+    }
+
+    pub fn set_descriptor(&mut self, desc: &WaterReflectionDescriptor) {
+        self.desc = *desc;
+    }
+
+    // Ripple phase in radians, wrapped to a full cycle.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.phase = (self.phase
+            + std::f32::consts::TAU * self.desc.ripple_speed * dt.as_secs_f32())
+        .rem_euclid(std::f32::consts::TAU);
+    }
+
+    pub fn push_constants(
+        &self,
+        region: &WaterRegion,
+        target_size: &gfx::CanvasSize,
+    ) -> WaterReflectionPushConstants {
+        let height = target_size.height() as f32;
+        let ripple_frequency = if self.desc.ripple_wavelength > 0. {
+            std::f32::consts::TAU * height / self.desc.ripple_wavelength
+        } else {
+            0.
+        };
+        WaterReflectionPushConstants {
+            region: region.texture_coordinates(target_size),
+            tint: self.desc.tint,
+            ripple_amplitude: self.desc.ripple_amplitude / target_size.width() as f32,
+            ripple_frequency,
+            ripple_phase: self.phase,
+            surface_opacity: self.desc.surface_opacity,
+            bottom_opacity: self.desc.bottom_opacity,
+            mirror: match self.desc.source {
+                WaterReflectionSource::SceneTexture => 1,
+                WaterReflectionSource::ReflectionTexture => 0,
+            },
+        }
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                gfx::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: gfx::TextureSampleType::Float { filterable: true },
+                        view_dimension: gfx::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                gfx::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: gfx::ShaderStage::FRAGMENT,
+                    ty: gfx::BindingType::Sampler {
+                        filtering: true,
+                        comparison: false,
+                    },
+                    count: None,
+                },
+            ],
+        },
+    )
+}
+
+// The texture to reflect, either the scene or the re-rendered reflection depending on the
+// source. It must be a different texture than the render target of the pass.
+#[derive(Debug)]
+pub struct WaterReflectionUniformConstants {
+    bind_group: gfx::BindGroup,
+}
+
+impl WaterReflectionUniformConstants {
+    pub fn new(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        Self::new_with_label(instance, None, texture, sampler)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        let layout = bind_group_layout(instance);
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label,
+                layout: &layout,
+                entries: &[
+                    gfx::BindGroupEntry {
+                        binding: 0,
+                        resource: gfx::BindingResource::TextureView(texture),
+                    },
+                    gfx::BindGroupEntry {
+                        binding: 1,
+                        resource: gfx::BindingResource::Sampler(sampler),
+                    },
+                ],
+            },
+        );
+        Self { bind_group }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WaterReflectionPipelineDescriptor {
+    pub label: Option<String>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for WaterReflectionPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<WaterReflectionPushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
+// Blends the tinted and rippled reflection over the water regions. The frame is drawn in this
+// order:
+// - the scene, into a texture;
+// - with ReflectionTexture sources, the scene again with the mirror transform into a second
+//   texture;
+// - the scene texture into the render target, e.g. through other post passes;
+// - the water regions with this pipeline, in the same pass as the previous step.
+#[derive(Debug)]
+pub struct WaterReflectionPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl WaterReflectionPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &WaterReflectionPipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[gfx::PushConstantRange {
+                    stages: gfx::ShaderStage::VERTEX_FRAGMENT,
+                    range: 0..PC_SIZE,
+                }],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/water_reflection.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/water_reflection.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: None,
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: Some(gfx::BlendState::ALPHA_BLENDING),
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait WaterReflectionRenderer<'a> {
+    fn draw_water_reflection(
+        &mut self,
+        pipeline: &'a WaterReflectionPipeline,
+        uniform_constants: &'a WaterReflectionUniformConstants,
+        push_constants: &WaterReflectionPushConstants,
+    );
+}
+
+impl<'a> WaterReflectionRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_water_reflection(
+        &mut self,
+        pipeline: &'a WaterReflectionPipeline,
+        uniform_constants: &'a WaterReflectionUniformConstants,
+        push_constants: &WaterReflectionPushConstants,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX_FRAGMENT,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX_FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw(0..6, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use roe_math::HomogeneousVector2;
+
+    const REGION: WaterRegion = WaterRegion {
+        left: 100.,
+        right: 300.,
+        surface: 200.,
+        bottom: 300.,
+    };
+
+    #[test]
+    fn region() {
+        let size = gfx::CanvasSize::new(400, 400);
+        expect_that!(
+            &REGION.texture_coordinates(&size),
+            eq([0.25, 0.5, 0.75, 0.75])
+        );
+        expect_that!(REGION.contains(&Vector2::new(150., 250.)));
+        expect_that!(!REGION.contains(&Vector2::new(150., 150.)));
+    }
+
+    #[test]
+    fn mirror_transform() {
+        let transform = water_mirror_transform(REGION.surface);
+        let mirrored = transform * HomogeneousVector2::new(50., 150., 1.);
+        expect_that!(&mirrored, eq(HomogeneousVector2::new(50., 250., 1.)));
+        let on_surface = transform * HomogeneousVector2::new(10., REGION.surface, 1.);
+        expect_that!(&on_surface[1], eq(REGION.surface));
+    }
+
+    #[test]
+    fn ripple_animation() {
+        let mut reflection = WaterReflection::new(&WaterReflectionDescriptor {
+            ripple_speed: 0.5,
+            ..WaterReflectionDescriptor::default()
+        });
+        reflection.update(Duration::from_millis(500));
+        expect_that!(
+            &reflection.phase(),
+            close_to(std::f32::consts::FRAC_PI_2, 1e-5)
+        );
+        reflection.update(Duration::from_millis(1500));
+        expect_that!(&reflection.phase(), close_to(0., 1e-5));
+    }
+
+    #[test]
+    fn push_constants() {
+        let size = gfx::CanvasSize::new(400, 400);
+        let mut reflection = WaterReflection::new(&WaterReflectionDescriptor::default());
+        let pc = reflection.push_constants(&REGION, &size);
+        expect_that!(&pc.region(), eq([0.25, 0.5, 0.75, 0.75]));
+        let mirror = pc.mirror;
+        expect_that!(&mirror, eq(1));
+
+        reflection.set_descriptor(&WaterReflectionDescriptor {
+            source: WaterReflectionSource::ReflectionTexture,
+            ..WaterReflectionDescriptor::default()
+        });
+        let mirror = reflection.push_constants(&REGION, &size).mirror;
+        expect_that!(&mirror, eq(0));
+    }
+}
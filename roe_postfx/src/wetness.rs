@@ -0,0 +1,146 @@
+use super::ColorFilterPushConstants;
+
+use roe_math::{Matrix3, Vector3};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WetnessFilterDescriptor {
+    // Brightness multiplier at full wetness.
+    pub brightness: f32,
+    // Saturation multiplier at full wetness. Wet surfaces look more saturated.
+    pub saturation: f32,
+    // Per channel multiplier at full wetness, e.g. a slight blue tint.
+    pub tint: Vector3<f32>,
+}
+
+impl Default for WetnessFilterDescriptor {
+    fn default() -> Self {
+        Self {
+            brightness: 0.7,
+            saturation: 1.2,
+            tint: Vector3::new(0.95, 0.98, 1.05),
+        }
+    }
+}
+
+// Rec. 709 luminance weights, for linear rgb.
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+// Darkens and saturates the image as the wetness goes from 0 (unchanged) to 1.
+pub fn wetness_matrix(desc: &WetnessFilterDescriptor, wetness: f32) -> Matrix3<f32> {
+    let luminance = Vector3::from(LUMINANCE);
+    // Each row keeps the luminance and scales the difference from it.
+    let gray = Matrix3::from_rows(&[
+        luminance.transpose(),
+        luminance.transpose(),
+        luminance.transpose(),
+    ]);
+    let saturation = gray + (Matrix3::identity() - gray) * desc.saturation;
+    let full = Matrix3::from_diagonal(&(desc.tint * desc.brightness)) * saturation;
+    Matrix3::identity() + (full - Matrix3::identity()) * wetness.clamp(0., 1.)
+}
+
+// Runtime state of the wetness overlay, to be drawn with a ColorFilterPipeline. The wetness is
+// usually driven by a weather layer.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WetnessFilter {
+    desc: WetnessFilterDescriptor,
+    wetness: f32,
+    push_constants: ColorFilterPushConstants,
+}
+
+impl WetnessFilter {
+    pub fn new(desc: &WetnessFilterDescriptor) -> Self {
+        Self {
+            desc: *desc,
+            wetness: 0.,
+            push_constants: ColorFilterPushConstants::default(),
+        }
+    }
+
+    pub fn descriptor(&self) -> &WetnessFilterDescriptor {
+        &self.desc
+    }
+
+    pub fn set_descriptor(&mut self, desc: &WetnessFilterDescriptor) {
+        self.desc = *desc;
+        self.update_push_constants();
+    }
+
+    pub fn wetness(&self) -> f32 {
+        self.wetness
+    }
+
+    pub fn set_wetness(&mut self, wetness: f32) {
+        self.wetness = wetness.clamp(0., 1.);
+        self.update_push_constants();
+    }
+
+    // If false the pass can be skipped.
+    pub fn is_enabled(&self) -> bool {
+        self.wetness > 0.
+    }
+
+    pub fn push_constants(&self) -> &ColorFilterPushConstants {
+        &self.push_constants
+    }
+
+    fn update_push_constants(&mut self) {
+        self.push_constants =
+            ColorFilterPushConstants::new(&wetness_matrix(&self.desc, self.wetness));
+    }
+}
+
+impl Default for WetnessFilter {
+    fn default() -> Self {
+        Self::new(&WetnessFilterDescriptor::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn wetness() {
+        let desc = WetnessFilterDescriptor {
+            brightness: 0.5,
+            saturation: 2.,
+            tint: Vector3::new(1., 1., 1.),
+        };
+        expect_that!(&wetness_matrix(&desc, 0.), eq(Matrix3::identity()));
+
+        // Grays are only darkened.
+        let gray = wetness_matrix(&desc, 1.) * Vector3::new(0.5, 0.5, 0.5);
+        for i in 0..3 {
+            expect_that!(&gray[i], close_to(0.25, 1e-6));
+        }
+
+        // Colors get further from gray.
+        let color = Vector3::new(0.6, 0.4, 0.2);
+        let wet = wetness_matrix(&desc, 1.) * color;
+        expect_that!(wet[0] - wet[2] > (color[0] - color[2]) * 0.5);
+        let half = wetness_matrix(&desc, 0.5) * color;
+        expect_that!(half[0] < color[0] && half[0] > wet[0]);
+    }
+
+    #[test]
+    fn filter() {
+        let mut filter = WetnessFilter::default();
+        expect_that!(!filter.is_enabled());
+        expect_that!(
+            filter.push_constants(),
+            eq(ColorFilterPushConstants::default())
+        );
+        filter.set_wetness(2.);
+        expect_that!(filter.is_enabled());
+        expect_that!(&filter.wetness(), eq(1.));
+        expect_that!(
+            filter.push_constants(),
+            eq(ColorFilterPushConstants::new(&wetness_matrix(
+                &WetnessFilterDescriptor::default(),
+                1.
+            )))
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use super::Value;
+
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    UnknownType(String),
+    UnknownField { type_name: String, field: String },
+    InvalidValue { field: String, value: Value },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::UnknownType(type_name) => write!(f, "Unknown type ({})", type_name),
+            Self::UnknownField { type_name, field } => {
+                write!(f, "Unknown field ({}::{})", type_name, field)
+            }
+            Self::InvalidValue { field, value } => {
+                write!(f, "Invalid value for field {} ({:?})", field, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
@@ -0,0 +1,61 @@
+use super::{FieldMetadata, Reflect, Value};
+
+// Draws an editor for the fields of the object. Returns true if a field was changed.
+pub fn inspector_ui(ui: &mut egui::Ui, object: &mut dyn Reflect) -> bool {
+    let mut changed = false;
+    egui::Grid::new(object.type_name())
+        .num_columns(2)
+        .show(ui, |ui| {
+            for info in object.fields() {
+                let label = ui.label(info.name);
+                if let Some(tooltip) = info.metadata.tooltip {
+                    label.on_hover_text(tooltip);
+                }
+                let mut value = match object.field(info.name) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let value_changed = ui
+                    .scope(|ui| {
+                        ui.set_enabled(!info.metadata.read_only);
+                        value_ui(ui, &mut value, &info.metadata)
+                    })
+                    .inner;
+                if value_changed && object.set_field(info.name, value).is_ok() {
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+    changed
+}
+
+fn value_ui(ui: &mut egui::Ui, value: &mut Value, metadata: &FieldMetadata) -> bool {
+    match value {
+        Value::Bool(b) => ui.checkbox(b, "").changed(),
+        Value::Int(i) => ui.add(drag_value(i, metadata)).changed(),
+        Value::Float(f) => ui.add(drag_value(f, metadata).speed(0.1)).changed(),
+        Value::Text(s) => ui.text_edit_singleline(s).changed(),
+        Value::Vector(v) => {
+            ui.horizontal(|ui| {
+                let mut changed = false;
+                for x in v.iter_mut() {
+                    changed |= ui.add(drag_value(x, metadata).speed(0.1)).changed();
+                }
+                changed
+            })
+            .inner
+        }
+    }
+}
+
+fn drag_value<'a, N: egui::emath::Numeric>(
+    value: &'a mut N,
+    metadata: &FieldMetadata,
+) -> egui::DragValue<'a> {
+    let drag_value = egui::DragValue::new(value);
+    match metadata.range {
+        Some((min, max)) => drag_value.clamp_range(min..=max),
+        None => drag_value,
+    }
+}
@@ -0,0 +1,16 @@
+mod error;
+pub use error::*;
+
+mod value;
+pub use value::*;
+
+mod reflect;
+pub use reflect::*;
+
+mod registry;
+pub use registry::*;
+
+#[cfg(feature = "inspector")]
+mod inspector;
+#[cfg(feature = "inspector")]
+pub use inspector::*;
@@ -0,0 +1,188 @@
+use super::{Error, FieldKind, Value};
+
+use std::any::Any;
+
+// Hints used by the inspector to present a field.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FieldMetadata {
+    // Range the value is clamped to when edited, for numeric and vector fields.
+    pub range: Option<(f64, f64)>,
+    pub read_only: bool,
+    pub tooltip: Option<&'static str>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub kind: FieldKind,
+    pub metadata: FieldMetadata,
+}
+
+// Exposes the fields of a type by name. Usually implemented with the reflect macro.
+pub trait Reflect: Any {
+    fn type_name(&self) -> &'static str;
+
+    fn fields(&self) -> Vec<FieldInfo>;
+
+    fn field(&self, name: &str) -> Option<Value>;
+
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), Error>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn Reflect {
+    pub fn downcast_ref<T: Reflect>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Reflect>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
+}
+
+// Implements Reflect for a struct, exposing the listed fields. Each field can be followed by
+// its FieldMetadata:
+//
+// reflect!(Camera {
+//     position,
+//     zoom: FieldMetadata { range: Some((0.1, 10.)), ..Default::default() },
+// });
+#[macro_export]
+macro_rules! reflect {
+    ($type:ident { $($field:ident $(: $metadata:expr)?),* $(,)? }) => {
+        impl $crate::Reflect for $type {
+            fn type_name(&self) -> &'static str {
+                stringify!($type)
+            }
+
+            fn fields(&self) -> Vec<$crate::FieldInfo> {
+                vec![$(
+                    $crate::FieldInfo {
+                        name: stringify!($field),
+                        kind: $crate::ReflectValue::kind(&self.$field),
+                        metadata: $crate::reflect!(@metadata $($metadata)?),
+                    }
+                ),*]
+            }
+
+            fn field(&self, name: &str) -> Option<$crate::Value> {
+                match name {
+                    $(stringify!($field) => Some($crate::ReflectValue::to_value(&self.$field)),)*
+                    _ => None,
+                }
+            }
+
+            fn set_field(
+                &mut self,
+                name: &str,
+                value: $crate::Value,
+            ) -> Result<(), $crate::Error> {
+                match name {
+                    $(stringify!($field) => {
+                        self.$field = $crate::ReflectValue::from_value(value.clone()).ok_or(
+                            $crate::Error::InvalidValue {
+                                field: String::from(name),
+                                value,
+                            },
+                        )?;
+                        Ok(())
+                    })*
+                    _ => Err($crate::Error::UnknownField {
+                        type_name: String::from(stringify!($type)),
+                        field: String::from(name),
+                    }),
+                }
+            }
+
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+    };
+    (@metadata) => {
+        $crate::FieldMetadata::default()
+    };
+    (@metadata $metadata:expr) => {
+        $metadata
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_math::Vector2;
+
+    #[derive(Debug, PartialEq, Clone, Default)]
+    struct Camera {
+        position: Vector2<f32>,
+        zoom: f32,
+        name: String,
+        hidden: u32,
+    }
+
+    reflect!(Camera {
+        position,
+        zoom: FieldMetadata {
+            range: Some((0.1, 10.)),
+            tooltip: Some("Zoom factor"),
+            ..Default::default()
+        },
+        name,
+    });
+
+    #[test]
+    fn fields() {
+        let camera = Camera::default();
+        expect_that!(&camera.type_name(), eq("Camera"));
+        let fields = camera.fields();
+        expect_that!(
+            &fields.iter().map(|f| f.name).collect::<Vec<_>>(),
+            eq(vec!["position", "zoom", "name"])
+        );
+        expect_that!(&fields[0].kind, eq(FieldKind::Vector(2)));
+        expect_that!(&fields[0].metadata, eq(FieldMetadata::default()));
+        expect_that!(&fields[1].kind, eq(FieldKind::Float));
+        expect_that!(&fields[1].metadata.range, eq(Some((0.1, 10.))));
+        expect_that!(&fields[2].kind, eq(FieldKind::Text));
+    }
+
+    #[test]
+    fn get_and_set() {
+        let mut camera = Camera::default();
+        camera.set_field("zoom", Value::Float(2.)).unwrap();
+        camera
+            .set_field("position", Value::Vector(vec![1., 2.]))
+            .unwrap();
+        expect_that!(&camera.zoom, eq(2.));
+        expect_that!(&camera.position, eq(Vector2::new(1., 2.)));
+        expect_that!(&camera.field("zoom"), eq(Some(Value::Float(2.))));
+        expect_that!(&camera.field("hidden"), eq(None));
+
+        expect_that!(
+            &camera.set_field("zoom", Value::Text(String::from("a"))),
+            is_variant!(Result::Err)
+        );
+        expect_that!(
+            &camera.set_field("hidden", Value::Int(1)),
+            is_variant!(Result::Err)
+        );
+        expect_that!(&camera.zoom, eq(2.));
+        expect_that!(&camera.hidden, eq(0));
+    }
+
+    #[test]
+    fn downcast() {
+        let mut object: Box<dyn Reflect> = Box::new(Camera::default());
+        object.downcast_mut::<Camera>().unwrap().zoom = 3.;
+        expect_that!(&object.downcast_ref::<Camera>().unwrap().zoom, eq(3.));
+    }
+}
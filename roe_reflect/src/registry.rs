@@ -0,0 +1,233 @@
+use super::{Error, Reflect, Value};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+// Serializable form of a reflected object.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ReflectedObject {
+    pub type_name: String,
+    pub fields: BTreeMap<String, Value>,
+}
+
+impl ReflectedObject {
+    pub fn from_object(object: &dyn Reflect) -> Self {
+        let fields = object
+            .fields()
+            .into_iter()
+            .filter_map(|info| {
+                object
+                    .field(info.name)
+                    .map(|value| (String::from(info.name), value))
+            })
+            .collect();
+        Self {
+            type_name: String::from(object.type_name()),
+            fields,
+        }
+    }
+
+    // Sets the stored fields on the object. Fields that aren't stored keep their value.
+    pub fn apply_to(&self, object: &mut dyn Reflect) -> Result<(), Error> {
+        if self.type_name != object.type_name() {
+            return Err(Error::UnknownType(self.type_name.clone()));
+        }
+        for (name, value) in self.fields.iter() {
+            object.set_field(name, value.clone())?;
+        }
+        Ok(())
+    }
+}
+
+type Constructor = fn() -> Box<dyn Reflect>;
+
+// Maps type names to constructors, to instantiate the objects stored in level files.
+#[derive(Default)]
+pub struct TypeRegistry {
+    constructors: HashMap<&'static str, Constructor>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Reflect + Default>(&mut self) {
+        let type_name = T::default().type_name();
+        self.constructors
+            .insert(type_name, || Box::new(T::default()));
+    }
+
+    pub fn is_registered(&self, type_name: &str) -> bool {
+        self.constructors.contains_key(type_name)
+    }
+
+    pub fn type_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.constructors.keys().copied()
+    }
+
+    pub fn create(&self, type_name: &str) -> Result<Box<dyn Reflect>, Error> {
+        let constructor = self
+            .constructors
+            .get(type_name)
+            .ok_or_else(|| Error::UnknownType(String::from(type_name)))?;
+        Ok(constructor())
+    }
+
+    // Creates an object with default values, then sets the stored fields.
+    pub fn instantiate(&self, data: &ReflectedObject) -> Result<Box<dyn Reflect>, Error> {
+        let mut object = self.create(&data.type_name)?;
+        data.apply_to(object.as_mut())?;
+        Ok(object)
+    }
+}
+
+impl std::fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.constructors.keys()).finish()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct LevelFile {
+    pub objects: Vec<ReflectedObject>,
+}
+
+impl LevelFile {
+    pub fn from_objects<'a, I>(objects: I) -> Self
+    where
+        I: IntoIterator<Item = &'a dyn Reflect>,
+    {
+        Self {
+            objects: objects
+                .into_iter()
+                .map(ReflectedObject::from_object)
+                .collect(),
+        }
+    }
+
+    pub fn instantiate(&self, registry: &TypeRegistry) -> Result<Vec<Box<dyn Reflect>>, Error> {
+        self.objects
+            .iter()
+            .map(|object| registry.instantiate(object))
+            .collect()
+    }
+
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect;
+    use galvanic_assert::{matchers::*, *};
+
+    #[derive(Debug, PartialEq, Clone, Default)]
+    struct Light {
+        intensity: f32,
+        enabled: bool,
+    }
+
+    reflect!(Light { intensity, enabled });
+
+    #[derive(Debug, PartialEq, Clone, Default)]
+    struct Spawner {
+        prefab: String,
+        count: u32,
+        offset: [f32; 2],
+    }
+
+    reflect!(Spawner {
+        prefab,
+        count,
+        offset
+    });
+
+    fn registry() -> TypeRegistry {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Light>();
+        registry.register::<Spawner>();
+        registry
+    }
+
+    #[test]
+    fn registration() {
+        let registry = registry();
+        expect_that!(registry.is_registered("Light"));
+        expect_that!(!registry.is_registered("Camera"));
+        expect_that!(&registry.type_names().count(), eq(2));
+        expect_that!(&registry.create("Camera"), is_variant!(Result::Err));
+    }
+
+    #[test]
+    fn instantiate() {
+        let registry = registry();
+        let mut fields = BTreeMap::new();
+        fields.insert(String::from("count"), Value::Int(3));
+        let object = registry
+            .instantiate(&ReflectedObject {
+                type_name: String::from("Spawner"),
+                fields,
+            })
+            .unwrap();
+        expect_that!(
+            object.downcast_ref::<Spawner>().unwrap(),
+            eq(Spawner {
+                count: 3,
+                ..Spawner::default()
+            })
+        );
+
+        let mut fields = BTreeMap::new();
+        fields.insert(String::from("radius"), Value::Float(3.));
+        expect_that!(
+            &registry.instantiate(&ReflectedObject {
+                type_name: String::from("Light"),
+                fields,
+            }),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn level_round_trip() {
+        let light = Light {
+            intensity: 0.5,
+            enabled: true,
+        };
+        let spawner = Spawner {
+            prefab: String::from("enemy"),
+            count: 4,
+            offset: [1., -2.],
+        };
+        let level = LevelFile::from_objects([&light as &dyn Reflect, &spawner]);
+        let level = LevelFile::from_ron_str(&level.to_ron_string().unwrap()).unwrap();
+
+        let objects = level.instantiate(&registry()).unwrap();
+        expect_that!(&objects.len(), eq(2));
+        expect_that!(objects[0].downcast_ref::<Light>().unwrap(), eq(light));
+        expect_that!(objects[1].downcast_ref::<Spawner>().unwrap(), eq(spawner));
+    }
+}
@@ -0,0 +1,205 @@
+use roe_math::{Vector2, Vector3, Vector4};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum FieldKind {
+    Bool,
+    Int,
+    Float,
+    Text,
+    // Vector with the given number of components.
+    Vector(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Vector(Vec<f64>),
+}
+
+impl Value {
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::Bool(_) => FieldKind::Bool,
+            Self::Int(_) => FieldKind::Int,
+            Self::Float(_) => FieldKind::Float,
+            Self::Text(_) => FieldKind::Text,
+            Self::Vector(v) => FieldKind::Vector(v.len()),
+        }
+    }
+}
+
+// Conversion between field types and values.
+pub trait ReflectValue: Sized {
+    fn kind(&self) -> FieldKind;
+
+    fn to_value(&self) -> Value;
+
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+impl ReflectValue for bool {
+    fn kind(&self) -> FieldKind {
+        FieldKind::Bool
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_reflect_value_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl ReflectValue for $t {
+                fn kind(&self) -> FieldKind {
+                    FieldKind::Int
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::Int(*self as i64)
+                }
+
+                fn from_value(value: Value) -> Option<Self> {
+                    match value {
+                        Value::Int(i) => <$t>::try_from(i).ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_value_for_int!(i8, i16, i32, i64, u8, u16, u32, usize);
+
+macro_rules! impl_reflect_value_for_float {
+    ($($t:ty),*) => {
+        $(
+            impl ReflectValue for $t {
+                fn kind(&self) -> FieldKind {
+                    FieldKind::Float
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::Float(*self as f64)
+                }
+
+                fn from_value(value: Value) -> Option<Self> {
+                    match value {
+                        Value::Float(f) => Some(f as $t),
+                        Value::Int(i) => Some(i as $t),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_value_for_float!(f32, f64);
+
+impl ReflectValue for String {
+    fn kind(&self) -> FieldKind {
+        FieldKind::Text
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Text(self.clone())
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl<const N: usize> ReflectValue for [f32; N] {
+    fn kind(&self) -> FieldKind {
+        FieldKind::Vector(N)
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Vector(self.iter().map(|x| *x as f64).collect())
+    }
+
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Vector(v) if v.len() == N => {
+                let mut out = [0.; N];
+                for (o, x) in out.iter_mut().zip(v) {
+                    *o = x as f32;
+                }
+                Some(out)
+            }
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_reflect_value_for_vector {
+    ($($t:ident => $n:expr),*) => {
+        $(
+            impl ReflectValue for $t<f32> {
+                fn kind(&self) -> FieldKind {
+                    FieldKind::Vector($n)
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::Vector(self.iter().map(|x| *x as f64).collect())
+                }
+
+                fn from_value(value: Value) -> Option<Self> {
+                    <[f32; $n]>::from_value(value).map($t::from)
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_value_for_vector!(Vector2 => 2, Vector3 => 3, Vector4 => 4);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn round_trip() {
+        expect_that!(&bool::from_value(true.to_value()), eq(Some(true)));
+        expect_that!(&i32::from_value((-3i32).to_value()), eq(Some(-3)));
+        expect_that!(&f32::from_value(1.5f32.to_value()), eq(Some(1.5)));
+        expect_that!(
+            &String::from_value(String::from("a").to_value()),
+            eq(Some(String::from("a")))
+        );
+        let v = Vector3::new(1f32, 2., 3.);
+        expect_that!(&v.kind(), eq(FieldKind::Vector(3)));
+        expect_that!(&Vector3::<f32>::from_value(v.to_value()), eq(Some(v)));
+    }
+
+    #[test]
+    fn invalid_values() {
+        expect_that!(&bool::from_value(Value::Int(1)), eq(None));
+        expect_that!(&u8::from_value(Value::Int(300)), eq(None));
+        expect_that!(&u32::from_value(Value::Int(-1)), eq(None));
+        expect_that!(
+            &Vector2::<f32>::from_value(Value::Vector(vec![1., 2., 3.])),
+            eq(None)
+        );
+        expect_that!(&f32::from_value(Value::Int(2)), eq(Some(2.)));
+    }
+}
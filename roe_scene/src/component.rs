@@ -0,0 +1,150 @@
+use roe_assets::{SoundId, TextureId};
+use roe_math::Vector2;
+use roe_reflect::ReflectedObject;
+
+use serde::{Deserialize, Serialize};
+
+fn white() -> [f32; 4] {
+    [1., 1., 1., 1.]
+}
+
+fn one() -> f32 {
+    1.
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ShapeKind {
+    Rectangle { size: Vector2<f32> },
+    Circle { radius: f32 },
+    Polygon { points: Vec<Vector2<f32>> },
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Component {
+    Sprite {
+        // Asset path of the texture.
+        texture: TextureId,
+        size: Vector2<f32>,
+        // Normalized texture coordinates of the displayed region, as (left, top, right, bottom).
+        #[serde(default)]
+        region: Option<[f32; 4]>,
+        #[serde(default = "white")]
+        color: [f32; 4],
+    },
+    Shape {
+        shape: ShapeKind,
+        #[serde(default = "white")]
+        color: [f32; 4],
+    },
+    Audio {
+        // Asset path of the sound.
+        sound: SoundId,
+        #[serde(default = "one")]
+        volume: f32,
+        #[serde(default)]
+        looping: bool,
+        #[serde(default)]
+        autoplay: bool,
+    },
+    // Game specific component, to be instantiated through a roe_reflect::TypeRegistry.
+    Custom(ReflectedObject),
+}
+
+impl Component {
+    // Whether the two components are of the same kind. Custom components are compared by type
+    // name.
+    pub fn same_kind(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Custom(a), Self::Custom(b)) => a.type_name == b.type_name,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+
+    // Overrides the component with another of the same kind. Custom components are merged
+    // field by field, the other components are replaced.
+    pub fn apply_override(&mut self, other: &Self) {
+        assert!(
+            self.same_kind(other),
+            "Components can only be overridden by components of the same kind"
+        );
+        match (self, other) {
+            (Self::Custom(a), Self::Custom(b)) => {
+                for (name, value) in b.fields.iter() {
+                    a.fields.insert(name.clone(), value.clone());
+                }
+            }
+            (this, other) => *this = other.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_reflect::Value;
+
+    #[test]
+    fn deserialization_defaults() {
+        let component: Component =
+            ron::de::from_str(r#"Audio(sound: "sounds/wind.ogg", looping: true)"#).unwrap();
+        expect_that!(
+            &component,
+            eq(Component::Audio {
+                sound: SoundId::new("sounds/wind.ogg"),
+                volume: 1.,
+                looping: true,
+                autoplay: false,
+            })
+        );
+    }
+
+    #[test]
+    fn same_kind() {
+        let circle = Component::Shape {
+            shape: ShapeKind::Circle { radius: 1. },
+            color: white(),
+        };
+        let rectangle = Component::Shape {
+            shape: ShapeKind::Rectangle {
+                size: Vector2::new(1., 1.),
+            },
+            color: white(),
+        };
+        let health = Component::Custom(ReflectedObject {
+            type_name: String::from("Health"),
+            fields: Default::default(),
+        });
+        let speed = Component::Custom(ReflectedObject {
+            type_name: String::from("Speed"),
+            fields: Default::default(),
+        });
+        expect_that!(circle.same_kind(&rectangle));
+        expect_that!(!circle.same_kind(&health));
+        expect_that!(!health.same_kind(&speed));
+    }
+
+    #[test]
+    fn custom_override() {
+        let mut base = ReflectedObject {
+            type_name: String::from("Health"),
+            fields: Default::default(),
+        };
+        base.fields.insert(String::from("max"), Value::Int(10));
+        base.fields.insert(String::from("current"), Value::Int(10));
+        let mut over = base.clone();
+        over.fields.clear();
+        over.fields.insert(String::from("current"), Value::Int(5));
+
+        let mut component = Component::Custom(base);
+        component.apply_override(&Component::Custom(over));
+        match component {
+            Component::Custom(object) => {
+                expect_that!(&object.fields["max"], eq(Value::Int(10)));
+                expect_that!(&object.fields["current"], eq(Value::Int(5)));
+            }
+            _ => panic!("Unexpected component kind"),
+        }
+    }
+}
@@ -0,0 +1,17 @@
+mod error;
+pub use error::*;
+
+mod transform;
+pub use transform::*;
+
+mod component;
+pub use component::*;
+
+mod scene;
+pub use scene::*;
+
+mod prefab;
+pub use prefab::*;
+
+mod world_streamer;
+pub use world_streamer::*;
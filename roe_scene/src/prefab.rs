@@ -0,0 +1,386 @@
+use super::{Component, Error, NodeId, Scene, SceneNode, Transform2};
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct PrefabOverride {
+    // "/" separated path of the overridden entity, relative to the instancing entity.
+    pub entity: String,
+    #[serde(default)]
+    pub transform: Option<Transform2>,
+    // Replaces the components of the same kind, or adds them if missing. Custom components are
+    // merged field by field.
+    #[serde(default)]
+    pub components: Vec<Component>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct PrefabInstance {
+    pub prefab: String,
+    #[serde(default)]
+    pub overrides: Vec<PrefabOverride>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct EntityDescriptor {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub transform: Transform2,
+    #[serde(default)]
+    pub components: Vec<Component>,
+    #[serde(default)]
+    pub children: Vec<EntityDescriptor>,
+    // The entities of the prefab are spawned as children of this entity.
+    #[serde(default)]
+    pub instance: Option<PrefabInstance>,
+}
+
+// Describes a hierarchy of entities. Used both for reusable prefabs and for whole scenes.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Prefab {
+    pub entities: Vec<EntityDescriptor>,
+}
+
+impl Prefab {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, Error> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_ron_string()?)?;
+        Ok(())
+    }
+}
+
+// Stores the prefabs by name and instantiates them in scenes. If a root directory is set,
+// prefabs that weren't inserted are loaded from the file with the same name in it.
+#[derive(Debug, Default)]
+pub struct PrefabLibrary {
+    root: Option<PathBuf>,
+    prefabs: HashMap<String, Rc<Prefab>>,
+}
+
+impl PrefabLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: Some(root.into()),
+            prefabs: HashMap::new(),
+        }
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, name: S, prefab: Prefab) {
+        self.prefabs.insert(name.into(), Rc::new(prefab));
+    }
+
+    pub fn get(&mut self, name: &str) -> Result<Rc<Prefab>, Error> {
+        if let Some(prefab) = self.prefabs.get(name) {
+            return Ok(Rc::clone(prefab));
+        }
+        let root = self
+            .root
+            .as_ref()
+            .ok_or_else(|| Error::UnknownPrefab(String::from(name)))?;
+        let prefab = Rc::new(Prefab::load(root.join(name))?);
+        self.prefabs.insert(String::from(name), Rc::clone(&prefab));
+        Ok(prefab)
+    }
+
+    // Instantiates the named prefab under the parent node, returning the created top level
+    // nodes. On failure, no node is left in the scene.
+    pub fn spawn(
+        &mut self,
+        scene: &mut Scene,
+        name: &str,
+        parent: Option<NodeId>,
+    ) -> Result<Vec<NodeId>, Error> {
+        let prefab = self.get(name)?;
+        self.spawn_with_stack(scene, &prefab, parent, &mut vec![String::from(name)])
+    }
+
+    // Instantiates a prefab that isn't stored in the library, e.g. a scene file.
+    pub fn spawn_prefab(
+        &mut self,
+        scene: &mut Scene,
+        prefab: &Prefab,
+        parent: Option<NodeId>,
+    ) -> Result<Vec<NodeId>, Error> {
+        self.spawn_with_stack(scene, prefab, parent, &mut Vec::new())
+    }
+
+    fn spawn_with_stack(
+        &mut self,
+        scene: &mut Scene,
+        prefab: &Prefab,
+        parent: Option<NodeId>,
+        stack: &mut Vec<String>,
+    ) -> Result<Vec<NodeId>, Error> {
+        let mut spawned = Vec::new();
+        let result = self.spawn_entities(scene, &prefab.entities, parent, stack, &mut spawned);
+        if let Err(e) = result {
+            for id in spawned {
+                scene.remove_node(id);
+            }
+            return Err(e);
+        }
+        Ok(spawned)
+    }
+
+    fn spawn_entities(
+        &mut self,
+        scene: &mut Scene,
+        entities: &[EntityDescriptor],
+        parent: Option<NodeId>,
+        stack: &mut Vec<String>,
+        spawned: &mut Vec<NodeId>,
+    ) -> Result<(), Error> {
+        for entity in entities {
+            let mut node = SceneNode::new(entity.name.clone(), entity.transform);
+            node.components = entity.components.clone();
+            let id = scene.add_node(parent, node);
+            spawned.push(id);
+
+            if let Some(instance) = &entity.instance {
+                self.spawn_instance(scene, instance, id, stack)?;
+            }
+            self.spawn_entities(scene, &entity.children, Some(id), stack, &mut Vec::new())?;
+        }
+        Ok(())
+    }
+
+    fn spawn_instance(
+        &mut self,
+        scene: &mut Scene,
+        instance: &PrefabInstance,
+        id: NodeId,
+        stack: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        if stack.contains(&instance.prefab) {
+            return Err(Error::RecursivePrefab(instance.prefab.clone()));
+        }
+        let prefab = self.get(&instance.prefab)?;
+        stack.push(instance.prefab.clone());
+        self.spawn_entities(scene, &prefab.entities, Some(id), stack, &mut Vec::new())?;
+        stack.pop();
+
+        for o in instance.overrides.iter() {
+            let target = scene
+                .find(Some(id), &o.entity)
+                .ok_or_else(|| Error::UnknownEntity {
+                    prefab: instance.prefab.clone(),
+                    path: o.entity.clone(),
+                })?;
+            let node = scene.node_mut(target).unwrap();
+            if let Some(transform) = o.transform {
+                node.transform = transform;
+            }
+            for component in o.components.iter() {
+                match node.components.iter_mut().find(|c| c.same_kind(component)) {
+                    Some(existing) => existing.apply_override(component),
+                    None => node.components.push(component.clone()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShapeKind;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_math::Vector2;
+
+    const GUN: &str = r#"(
+        entities: [
+            (
+                name: "barrel",
+                transform: (position: [4., 0.]),
+                components: [Sprite(texture: "textures/barrel.png", size: [8., 2.])],
+            ),
+        ],
+    )"#;
+
+    const TANK: &str = r#"(
+        entities: [
+            (
+                name: "body",
+                components: [
+                    Shape(shape: Rectangle(size: [16., 10.]), color: (0., 0.5, 0., 1.)),
+                    Audio(sound: "sounds/engine.ogg", looping: true),
+                ],
+                children: [
+                    (
+                        name: "turret",
+                        transform: (rotation: 0.5),
+                        instance: Some((prefab: "gun")),
+                    ),
+                ],
+            ),
+        ],
+    )"#;
+
+    fn library() -> PrefabLibrary {
+        let mut library = PrefabLibrary::new();
+        library.insert("gun", Prefab::from_ron_str(GUN).unwrap());
+        library.insert("tank", Prefab::from_ron_str(TANK).unwrap());
+        library
+    }
+
+    #[test]
+    fn nested_prefabs() {
+        let mut library = library();
+        let mut scene = Scene::new();
+        let spawned = library.spawn(&mut scene, "tank", None).unwrap();
+        expect_that!(&spawned.len(), eq(1));
+        expect_that!(&scene.len(), eq(3));
+
+        let body = scene.node(spawned[0]).unwrap();
+        expect_that!(&body.name, eq(String::from("body")));
+        expect_that!(&body.components.len(), eq(2));
+
+        let turret = scene.find(None, "body/turret").unwrap();
+        expect_that!(&scene.node(turret).unwrap().transform.rotation, eq(0.5));
+        let barrel = scene.find(Some(turret), "barrel").unwrap();
+        expect_that!(
+            &scene.node(barrel).unwrap().transform.position,
+            eq(Vector2::new(4., 0.))
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let mut library = library();
+        let level = Prefab::from_ron_str(
+            r#"(
+                entities: [
+                    (
+                        name: "player",
+                        transform: (position: [100., 50.]),
+                        instance: Some((
+                            prefab: "tank",
+                            overrides: [
+                                (
+                                    entity: "body",
+                                    components: [
+                                        Shape(shape: Circle(radius: 8.)),
+                                        Custom((type_name: "Health", fields: {"max": Int(5)})),
+                                    ],
+                                ),
+                                (
+                                    entity: "body/turret/barrel",
+                                    transform: Some((position: [6., 0.])),
+                                ),
+                            ],
+                        )),
+                    ),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        let mut scene = Scene::new();
+        library.spawn_prefab(&mut scene, &level, None).unwrap();
+        expect_that!(&scene.len(), eq(4));
+
+        let body = scene
+            .node(scene.find(None, "player/body").unwrap())
+            .unwrap();
+        expect_that!(&body.components.len(), eq(3));
+        expect_that!(
+            &body.components[0],
+            eq(Component::Shape {
+                shape: ShapeKind::Circle { radius: 8. },
+                color: [1., 1., 1., 1.],
+            })
+        );
+        let barrel = scene.find(None, "player/body/turret/barrel").unwrap();
+        expect_that!(
+            &scene.node(barrel).unwrap().transform.position,
+            eq(Vector2::new(6., 0.))
+        );
+
+        // The prefab itself is unchanged.
+        let mut other_scene = Scene::new();
+        library.spawn(&mut other_scene, "tank", None).unwrap();
+        let body = other_scene
+            .node(other_scene.find(None, "body").unwrap())
+            .unwrap();
+        expect_that!(&body.components.len(), eq(2));
+    }
+
+    #[test]
+    fn errors() {
+        let mut library = library();
+        let mut scene = Scene::new();
+        expect_that!(
+            &library.spawn(&mut scene, "plane", None),
+            is_variant!(Result::Err)
+        );
+
+        library.insert(
+            "loop",
+            Prefab::from_ron_str(r#"(entities: [(instance: Some((prefab: "loop")))])"#).unwrap(),
+        );
+        expect_that!(
+            &library.spawn(&mut scene, "loop", None),
+            is_variant!(Result::Err)
+        );
+
+        let bad_override = Prefab::from_ron_str(
+            r#"(entities: [
+                (name: "a"),
+                (instance: Some((prefab: "gun", overrides: [(entity: "stock")]))),
+            ])"#,
+        )
+        .unwrap();
+        expect_that!(
+            &library.spawn_prefab(&mut scene, &bad_override, None),
+            is_variant!(Result::Err)
+        );
+        expect_that!(scene.is_empty());
+    }
+
+    #[test]
+    fn load_from_root() {
+        let root = std::env::temp_dir().join("roe_scene_prefab_test");
+        std::fs::create_dir_all(&root).unwrap();
+        Prefab::from_ron_str(GUN)
+            .unwrap()
+            .save(root.join("gun.ron"))
+            .unwrap();
+
+        let mut library = PrefabLibrary::with_root(&root);
+        let mut scene = Scene::new();
+        let spawned = library.spawn(&mut scene, "gun.ron", None).unwrap();
+        expect_that!(
+            &scene.node(spawned[0]).unwrap().name,
+            eq(String::from("barrel"))
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
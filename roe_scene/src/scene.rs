@@ -0,0 +1,341 @@
+use super::{Component, Transform2};
+
+use roe_jobs::JobSystem;
+use roe_math::HomogeneousMatrix2;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SceneNode {
+    pub name: String,
+    pub transform: Transform2,
+    pub components: Vec<Component>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl SceneNode {
+    pub fn new<S: Into<String>>(name: S, transform: Transform2) -> Self {
+        Self {
+            name: name.into(),
+            transform,
+            ..Self::default()
+        }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Slot {
+    generation: u32,
+    node: Option<SceneNode>,
+}
+
+// Hierarchy of named nodes, each with a transform relative to its parent and a list of
+// components.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Scene {
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, parent: Option<NodeId>, mut node: SceneNode) -> NodeId {
+        if let Some(parent) = parent {
+            assert!(self.contains(parent), "The parent node doesn't exist");
+        }
+        node.parent = parent;
+        node.children.clear();
+
+        let id = match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        match parent {
+            Some(parent) => self.node_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    // Removes the node and all its descendants.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<SceneNode> {
+        let node = self.take_node(id)?;
+        match node.parent {
+            Some(parent) => {
+                if let Some(parent) = self.node_mut(parent) {
+                    parent.children.retain(|child| *child != id);
+                }
+            }
+            None => self.roots.retain(|root| *root != id),
+        }
+        for child in node.children.iter() {
+            self.remove_subtree(*child);
+        }
+        Some(node)
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<&SceneNode> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut SceneNode> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &SceneNode)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.node.as_ref().map(|node| {
+                (
+                    NodeId {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    node,
+                )
+            })
+        })
+    }
+
+    // Finds a descendant from a "/" separated path of names. If parent is None, the first
+    // element of the path is looked up among the roots. An empty path returns the parent.
+    pub fn find(&self, parent: Option<NodeId>, path: &str) -> Option<NodeId> {
+        let mut current = parent;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let candidates = match current {
+                Some(id) => self.node(id)?.children.as_slice(),
+                None => self.roots.as_slice(),
+            };
+            current = Some(
+                *candidates
+                    .iter()
+                    .find(|id| self.node(**id).map(|node| node.name == name) == Some(true))?,
+            );
+        }
+        current
+    }
+
+    // Calls f on every node, in parallel on the threads of the job system. Each call only has
+    // access to its own node, e.g. for movement or animation updates not depending on other
+    // nodes.
+    pub fn par_for_each_mut<F>(&mut self, jobs: &JobSystem, f: F)
+    where
+        F: Fn(NodeId, &mut SceneNode) + Sync,
+    {
+        jobs.par_for_each_mut(&mut self.slots, |index, slot| {
+            let id = NodeId {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            if let Some(node) = slot.node.as_mut() {
+                f(id, node);
+            }
+        });
+    }
+
+    pub fn par_for_each<F>(&self, jobs: &JobSystem, f: F)
+    where
+        F: Fn(NodeId, &SceneNode) + Sync,
+    {
+        jobs.par_for_each(&self.slots, |index, slot| {
+            let id = NodeId {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            if let Some(node) = slot.node.as_ref() {
+                f(id, node);
+            }
+        });
+    }
+
+    pub fn world_transform(&self, id: NodeId) -> Option<HomogeneousMatrix2<f32>> {
+        let node = self.node(id)?;
+        let local = node.transform.to_matrix();
+        match node.parent {
+            Some(parent) => Some(self.world_transform(parent)? * local),
+            None => Some(local),
+        }
+    }
+
+    fn take_node(&mut self, id: NodeId) -> Option<SceneNode> {
+        let slot = self
+            .slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let node = slot.node.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.index);
+        Some(node)
+    }
+
+    fn remove_subtree(&mut self, id: NodeId) {
+        if let Some(node) = self.take_node(id) {
+            for child in node.children {
+                self.remove_subtree(child);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_math::{Vector2, Vector3};
+
+    fn at(x: f32, y: f32) -> Transform2 {
+        Transform2 {
+            position: Vector2::new(x, y),
+            ..Transform2::default()
+        }
+    }
+
+    #[test]
+    fn hierarchy() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, SceneNode::new("root", at(0., 0.)));
+        let body = scene.add_node(Some(root), SceneNode::new("body", at(0., 0.)));
+        let gun = scene.add_node(Some(body), SceneNode::new("gun", at(0., 0.)));
+
+        expect_that!(&scene.len(), eq(3));
+        expect_that!(&scene.roots().to_vec(), eq(vec![root]));
+        expect_that!(
+            &scene.node(body).unwrap().children().to_vec(),
+            eq(vec![gun])
+        );
+        expect_that!(&scene.node(gun).unwrap().parent(), eq(Some(body)));
+        expect_that!(&scene.find(None, "root/body/gun"), eq(Some(gun)));
+        expect_that!(&scene.find(Some(root), "body"), eq(Some(body)));
+        expect_that!(&scene.find(Some(root), ""), eq(Some(root)));
+        expect_that!(&scene.find(Some(root), "legs"), eq(None));
+    }
+
+    #[test]
+    fn removal() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, SceneNode::new("root", at(0., 0.)));
+        let child = scene.add_node(Some(root), SceneNode::new("child", at(0., 0.)));
+        let grandchild = scene.add_node(Some(child), SceneNode::new("grandchild", at(0., 0.)));
+        let other = scene.add_node(Some(root), SceneNode::new("other", at(0., 0.)));
+
+        expect_that!(
+            &scene.remove_node(child).map(|n| n.name),
+            eq(Some(String::from("child")))
+        );
+        expect_that!(!scene.contains(child));
+        expect_that!(!scene.contains(grandchild));
+        expect_that!(
+            &scene.node(root).unwrap().children().to_vec(),
+            eq(vec![other])
+        );
+        expect_that!(&scene.len(), eq(2));
+
+        // Reused slots don't make old ids valid again.
+        let new_node = scene.add_node(None, SceneNode::new("new", at(0., 0.)));
+        expect_that!(!scene.contains(child) && !scene.contains(grandchild));
+        expect_that!(scene.contains(new_node));
+        expect_that!(&scene.remove_node(child), eq(None));
+    }
+
+    #[test]
+    fn world_transform() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(
+            None,
+            SceneNode::new(
+                "parent",
+                Transform2 {
+                    position: Vector2::new(10., 0.),
+                    scale: Vector2::new(2., 2.),
+                    ..Transform2::default()
+                },
+            ),
+        );
+        let child = scene.add_node(Some(parent), SceneNode::new("child", at(1., 1.)));
+        let p = scene.world_transform(child).unwrap() * Vector3::new(0., 0., 1.);
+        expect_that!(&p.x, close_to(12., 1e-5));
+        expect_that!(&p.y, close_to(2., 1e-5));
+    }
+
+    #[test]
+    fn parallel_iteration() {
+        let jobs = JobSystem::new(&roe_jobs::JobSystemDescriptor {
+            thread_count: 2,
+            ..roe_jobs::JobSystemDescriptor::default()
+        });
+        let mut scene = Scene::new();
+        let ids: Vec<_> = (0..100)
+            .map(|i| scene.add_node(None, SceneNode::new(format!("{}", i), at(i as f32, 0.))))
+            .collect();
+        scene.remove_node(ids[50]);
+
+        scene.par_for_each_mut(&jobs, |_, node| node.transform.position.y += 1.);
+        let visited = std::sync::Mutex::new(Vec::new());
+        scene.par_for_each(&jobs, |id, node| {
+            visited.lock().unwrap().push((id, node.transform.position));
+        });
+        let mut visited = visited.into_inner().unwrap();
+        visited.sort_by_key(|(id, _)| *id);
+        expect_that!(&visited.len(), eq(99));
+        for (id, position) in visited {
+            expect_that!(&scene.node(id).unwrap().transform.position, eq(position));
+            expect_that!(&position.y, eq(1.));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use roe_math::{rotation2, scale2, translation2, HomogeneousMatrix2, Rotation2, Vector2};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform2 {
+    pub position: Vector2<f32>,
+    // Rotation angle in radians.
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+}
+
+impl Transform2 {
+    // Scale, then rotation, then translation.
+    pub fn to_matrix(&self) -> HomogeneousMatrix2<f32> {
+        translation2(&self.position)
+            * rotation2(&Rotation2::new(self.rotation))
+            * scale2(&self.scale)
+    }
+}
+
+impl Default for Transform2 {
+    fn default() -> Self {
+        Self {
+            position: Vector2::zeros(),
+            rotation: 0.,
+            scale: Vector2::new(1., 1.),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_math::Vector3;
+
+    #[test]
+    fn to_matrix() {
+        let transform = Transform2 {
+            position: Vector2::new(10., 0.),
+            rotation: std::f32::consts::FRAC_PI_2,
+            scale: Vector2::new(2., 2.),
+        };
+        let p = transform.to_matrix() * Vector3::new(1., 0., 1.);
+        expect_that!(&p.x, close_to(10., 1e-5));
+        expect_that!(&p.y, close_to(2., 1e-5));
+    }
+
+    #[test]
+    fn partial_deserialization() {
+        let transform: Transform2 = ron::de::from_str("(rotation: 1.5)").unwrap();
+        expect_that!(
+            &transform,
+            eq(Transform2 {
+                rotation: 1.5,
+                ..Transform2::default()
+            })
+        );
+    }
+}
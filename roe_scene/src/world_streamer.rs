@@ -0,0 +1,402 @@
+use roe_assets::{AssetLoader, Error as AssetError, LoadId, LoadPriority};
+use roe_math::Vector2;
+
+use std::{collections::HashMap, path::PathBuf};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl ChunkCoord {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    // Number of chunks to cross to reach the other chunk, diagonals included.
+    pub fn distance(&self, other: &Self) -> u32 {
+        std::cmp::max(self.x.abs_diff(other.x), self.y.abs_diff(other.y))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WorldStreamerDescriptor {
+    // Size of a chunk in world units. Chunk (0, 0) spans from the origin to the chunk size.
+    pub chunk_size: Vector2<f32>,
+    // Chunks up to this distance from the camera chunk are loaded.
+    pub load_radius: u32,
+    // Chunks up to this distance are requested ahead of time, so that their data is ready when
+    // the camera gets close enough. Can't be smaller than the load radius.
+    pub prefetch_radius: u32,
+    // Chunks are unloaded, and requests cancelled, only beyond this distance, so that moving
+    // back and forth across a chunk border doesn't reload the same chunks. Can't be smaller
+    // than the prefetch radius.
+    pub unload_radius: u32,
+    // Asset path of the chunks, where "{x}" and "{y}" are replaced by the chunk coordinates.
+    pub path_pattern: String,
+}
+
+impl Default for WorldStreamerDescriptor {
+    fn default() -> Self {
+        Self {
+            chunk_size: Vector2::new(1024., 1024.),
+            load_radius: 1,
+            prefetch_radius: 2,
+            unload_radius: 3,
+            path_pattern: String::from("world/chunk_{x}_{y}.ron"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChunkState {
+    Requested,
+    // The data is available, but the chunk isn't close enough to be loaded yet.
+    Ready,
+    Loaded,
+    Failed,
+}
+
+// Receives the chunks entering and leaving the loaded area. The chunk data is the content of the
+// chunk asset, e.g. a serialized tilemap section or prefab, to be instantiated by the handler.
+pub trait ChunkHandler {
+    fn on_chunk_loaded(&mut self, chunk: ChunkCoord, data: Vec<u8>);
+
+    fn on_chunk_unloaded(&mut self, chunk: ChunkCoord);
+
+    // Failed chunks aren't requested again until they leave the unload radius.
+    fn on_chunk_load_failed(&mut self, _chunk: ChunkCoord, _error: &AssetError) {}
+}
+
+#[derive(Debug)]
+enum Chunk {
+    Requested(LoadId),
+    Ready(Vec<u8>),
+    Loaded,
+    Failed,
+}
+
+// Streams the chunks of a world laid out on a grid around the camera. Chunk assets are requested
+// through the asset loader, with a higher priority for the chunks that must be loaded than for
+// the prefetched ones.
+#[derive(Debug)]
+pub struct WorldStreamer {
+    desc: WorldStreamerDescriptor,
+    loader: AssetLoader,
+    chunks: HashMap<ChunkCoord, Chunk>,
+    requests: HashMap<LoadId, ChunkCoord>,
+    camera_chunk: Option<ChunkCoord>,
+}
+
+impl WorldStreamer {
+    pub fn new(loader: AssetLoader, desc: &WorldStreamerDescriptor) -> Self {
+        assert!(
+            desc.chunk_size.x > 0. && desc.chunk_size.y > 0.,
+            "The chunk size must be higher than 0"
+        );
+        assert!(
+            desc.load_radius <= desc.prefetch_radius && desc.prefetch_radius <= desc.unload_radius,
+            "The radii must satisfy load radius <= prefetch radius <= unload radius"
+        );
+        Self {
+            desc: desc.clone(),
+            loader,
+            chunks: HashMap::new(),
+            requests: HashMap::new(),
+            camera_chunk: None,
+        }
+    }
+
+    pub fn descriptor(&self) -> &WorldStreamerDescriptor {
+        &self.desc
+    }
+
+    pub fn loader(&self) -> &AssetLoader {
+        &self.loader
+    }
+
+    pub fn chunk_at(&self, position: &Vector2<f32>) -> ChunkCoord {
+        ChunkCoord::new(
+            (position.x / self.desc.chunk_size.x).floor() as i32,
+            (position.y / self.desc.chunk_size.y).floor() as i32,
+        )
+    }
+
+    pub fn chunk_path(&self, chunk: ChunkCoord) -> PathBuf {
+        PathBuf::from(
+            self.desc
+                .path_pattern
+                .replace("{x}", &chunk.x.to_string())
+                .replace("{y}", &chunk.y.to_string()),
+        )
+    }
+
+    pub fn camera_chunk(&self) -> Option<ChunkCoord> {
+        self.camera_chunk
+    }
+
+    pub fn chunk_state(&self, chunk: ChunkCoord) -> Option<ChunkState> {
+        self.chunks.get(&chunk).map(|chunk| match chunk {
+            Chunk::Requested(_) => ChunkState::Requested,
+            Chunk::Ready(_) => ChunkState::Ready,
+            Chunk::Loaded => ChunkState::Loaded,
+            Chunk::Failed => ChunkState::Failed,
+        })
+    }
+
+    pub fn loaded_chunks(&self) -> Vec<ChunkCoord> {
+        let mut chunks: Vec<_> = self
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| matches!(chunk, Chunk::Loaded))
+            .map(|(coord, _)| *coord)
+            .collect();
+        chunks.sort();
+        chunks
+    }
+
+    // To be called every frame. Collects the completed loads, and fires the callbacks of the
+    // chunks entering or leaving the loaded area.
+    pub fn update(&mut self, camera: &Vector2<f32>, handler: &mut dyn ChunkHandler) {
+        let center = self.chunk_at(camera);
+        self.camera_chunk = Some(center);
+        self.unload_distant_chunks(center, handler);
+        self.request_chunks(center);
+        self.collect_results(handler);
+
+        let mut ready: Vec<_> = self
+            .chunks
+            .iter()
+            .filter(|(coord, chunk)| {
+                matches!(chunk, Chunk::Ready(_)) && coord.distance(&center) <= self.desc.load_radius
+            })
+            .map(|(coord, _)| *coord)
+            .collect();
+        ready.sort_by_key(|coord| (coord.distance(&center), *coord));
+        for coord in ready {
+            if let Some(Chunk::Ready(data)) = self.chunks.insert(coord, Chunk::Loaded) {
+                handler.on_chunk_loaded(coord, data);
+            }
+        }
+    }
+
+    // Unloads all chunks and cancels the pending requests.
+    pub fn clear(&mut self, handler: &mut dyn ChunkHandler) {
+        let mut loaded = self.loaded_chunks();
+        loaded.reverse();
+        for (id, _) in self.requests.drain() {
+            self.loader.cancel(id);
+        }
+        self.chunks.clear();
+        for coord in loaded {
+            handler.on_chunk_unloaded(coord);
+        }
+        self.camera_chunk = None;
+    }
+
+    fn unload_distant_chunks(&mut self, center: ChunkCoord, handler: &mut dyn ChunkHandler) {
+        let unload_radius = self.desc.unload_radius;
+        let mut distant: Vec<_> = self
+            .chunks
+            .keys()
+            .filter(|coord| coord.distance(&center) > unload_radius)
+            .copied()
+            .collect();
+        // Furthest first, the opposite of the load order.
+        distant.sort_by_key(|coord| (std::cmp::Reverse(coord.distance(&center)), *coord));
+        for coord in distant {
+            match self.chunks.remove(&coord) {
+                Some(Chunk::Requested(id)) => {
+                    self.loader.cancel(id);
+                    self.requests.remove(&id);
+                }
+                Some(Chunk::Loaded) => handler.on_chunk_unloaded(coord),
+                _ => {}
+            }
+        }
+    }
+
+    // Closest chunks first.
+    fn request_chunks(&mut self, center: ChunkCoord) {
+        let radius = self.desc.prefetch_radius as i32;
+        let mut missing = Vec::new();
+        for y in center.y - radius..=center.y + radius {
+            for x in center.x - radius..=center.x + radius {
+                let coord = ChunkCoord::new(x, y);
+                if !self.chunks.contains_key(&coord) {
+                    missing.push(coord);
+                }
+            }
+        }
+        missing.sort_by_key(|coord| (coord.distance(&center), *coord));
+        for coord in missing {
+            let priority = if coord.distance(&center) <= self.desc.load_radius {
+                LoadPriority::High
+            } else {
+                LoadPriority::Low
+            };
+            let id = self.loader.load(self.chunk_path(coord), priority);
+            self.chunks.insert(coord, Chunk::Requested(id));
+            self.requests.insert(id, coord);
+        }
+    }
+
+    fn collect_results(&mut self, handler: &mut dyn ChunkHandler) {
+        while let Some(result) = self.loader.poll_result() {
+            // Results of cancelled requests are no longer tracked.
+            let coord = match self.requests.remove(&result.id) {
+                Some(coord) => coord,
+                None => continue,
+            };
+            match result.data {
+                Ok(data) => {
+                    self.chunks.insert(coord, Chunk::Ready(data));
+                }
+                Err(e) => {
+                    self.chunks.insert(coord, Chunk::Failed);
+                    handler.on_chunk_load_failed(coord, &e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_assets::{AssetLoaderDescriptor, MemorySource, Vfs};
+    use std::sync::Arc;
+
+    #[derive(Debug, Default)]
+    struct Log {
+        entries: Vec<String>,
+    }
+
+    impl ChunkHandler for Log {
+        fn on_chunk_loaded(&mut self, chunk: ChunkCoord, data: Vec<u8>) {
+            self.entries.push(format!(
+                "load {} {} {}",
+                chunk.x,
+                chunk.y,
+                String::from_utf8(data).unwrap()
+            ));
+        }
+
+        fn on_chunk_unloaded(&mut self, chunk: ChunkCoord) {
+            self.entries.push(format!("unload {} {}", chunk.x, chunk.y));
+        }
+
+        fn on_chunk_load_failed(&mut self, chunk: ChunkCoord, _error: &AssetError) {
+            self.entries.push(format!("fail {} {}", chunk.x, chunk.y));
+        }
+    }
+
+    // A 10x7 world, without chunk (5, 0).
+    fn streamer() -> WorldStreamer {
+        let source = MemorySource::new();
+        for y in -3..=3 {
+            for x in (0..10).filter(|x| *x != 5 || y != 0) {
+                source.insert(format!("chunk_{}_{}", x, y), format!("{}", x).into_bytes());
+            }
+        }
+        let vfs = Vfs::new();
+        vfs.mount("world", "", 0, source);
+        let loader = AssetLoader::new(Arc::new(vfs), &AssetLoaderDescriptor::default());
+        WorldStreamer::new(
+            loader,
+            &WorldStreamerDescriptor {
+                chunk_size: Vector2::new(10., 10.),
+                load_radius: 0,
+                prefetch_radius: 1,
+                unload_radius: 2,
+                path_pattern: String::from("chunk_{x}_{y}"),
+            },
+        )
+    }
+
+    // Updates until all requests completed.
+    fn settle(streamer: &mut WorldStreamer, camera: Vector2<f32>, log: &mut Log) -> Vec<String> {
+        streamer.update(&camera, log);
+        while streamer.loader().pending_count() > 0 {
+            std::thread::yield_now();
+            streamer.update(&camera, log);
+        }
+        std::mem::take(&mut log.entries)
+    }
+
+    #[test]
+    fn chunk_coordinates() {
+        let streamer = streamer();
+        expect_that!(
+            &streamer.chunk_at(&Vector2::new(15., -0.5)),
+            eq(ChunkCoord::new(1, -1))
+        );
+        expect_that!(
+            &streamer.chunk_path(ChunkCoord::new(-2, 3)),
+            eq(PathBuf::from("chunk_-2_3"))
+        );
+        expect_that!(
+            &ChunkCoord::new(0, 0).distance(&ChunkCoord::new(-2, 1)),
+            eq(2)
+        );
+    }
+
+    #[test]
+    fn streaming() {
+        let mut streamer = streamer();
+        let mut log = Log::default();
+
+        let entries = settle(&mut streamer, Vector2::new(25., 5.), &mut log);
+        expect_that!(&entries, eq(vec![String::from("load 2 0 2")]));
+        expect_that!(&streamer.loaded_chunks(), eq(vec![ChunkCoord::new(2, 0)]));
+        // Prefetched.
+        expect_that!(
+            &streamer.chunk_state(ChunkCoord::new(3, 0)),
+            eq(Some(ChunkState::Ready))
+        );
+
+        // The prefetched chunk is loaded without waiting.
+        streamer.update(&Vector2::new(35., 5.), &mut log);
+        expect_that!(
+            &std::mem::take(&mut log.entries),
+            eq(vec![String::from("load 3 0 3")])
+        );
+
+        // Chunk 2 is kept within the unload radius. The missing chunk fails while prefetched.
+        let mut entries = settle(&mut streamer, Vector2::new(45., 5.), &mut log);
+        entries.sort();
+        expect_that!(
+            &entries,
+            eq(vec![String::from("fail 5 0"), String::from("load 4 0 4")])
+        );
+        expect_that!(
+            &streamer.loaded_chunks(),
+            eq(vec![
+                ChunkCoord::new(2, 0),
+                ChunkCoord::new(3, 0),
+                ChunkCoord::new(4, 0)
+            ])
+        );
+
+        // Going back and forth doesn't reload anything.
+        let entries = settle(&mut streamer, Vector2::new(35., 5.), &mut log);
+        expect_that!(&entries, eq(Vec::<String>::new()));
+
+        let entries = settle(&mut streamer, Vector2::new(55., 5.), &mut log);
+        expect_that!(&entries, eq(vec![String::from("unload 2 0")]));
+        expect_that!(
+            &streamer.chunk_state(ChunkCoord::new(5, 0)),
+            eq(Some(ChunkState::Failed))
+        );
+
+        streamer.clear(&mut log);
+        expect_that!(
+            &log.entries,
+            eq(vec![String::from("unload 4 0"), String::from("unload 3 0")])
+        );
+        expect_that!(&streamer.chunk_state(ChunkCoord::new(4, 0)), eq(None));
+    }
+}
@@ -0,0 +1,11 @@
+mod error;
+pub use error::*;
+
+mod script_command;
+pub use script_command::*;
+
+mod script_input;
+pub use script_input::*;
+
+mod script_engine;
+pub use script_engine::*;
@@ -0,0 +1,20 @@
+// Requests issued by scripts. Scripts don't access the engine directly: the application
+// retrieves the commands after each script callback and executes them, e.g. drawing the sprites
+// with its own renderer or mapping the scene commands to an application ControlFlow.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ScriptCommand {
+    DrawSprite {
+        sprite: String,
+        position: (f32, f32),
+        rotation: f32,
+        scale: (f32, f32),
+    },
+    PlaySound {
+        sound: String,
+        volume: f32,
+    },
+    PushScene(String),
+    PopScene,
+    ChangeScene(String),
+    Exit,
+}
@@ -0,0 +1,430 @@
+use super::{Error, ScriptCommand, ScriptInput};
+
+use std::{
+    cell::{RefCell, RefMut},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+#[derive(Debug, Default)]
+struct ScriptContext {
+    input: ScriptInput,
+    commands: Vec<ScriptCommand>,
+}
+
+#[derive(Debug)]
+struct ScriptFile {
+    path: PathBuf,
+    modified: SystemTime,
+}
+
+// Lua scripting environment. The engine API is exposed to the scripts through the global "roe"
+// table, and scripts implement the game logic by defining any of the following global
+// functions: on_start(), on_end(), on_fixed_update(dt), on_variable_update(dt), on_reload().
+//
+// When a script file is reloaded, it is executed again in the same Lua state. State that must
+// survive a reload should be initialized conditionally, e.g. "state = state or {}".
+pub struct ScriptEngine {
+    lua: mlua::Lua,
+    context: Rc<RefCell<ScriptContext>>,
+    file: Option<ScriptFile>,
+}
+
+impl ScriptEngine {
+    pub fn new() -> Result<Self, Error> {
+        let lua = mlua::Lua::new();
+        let context = Rc::new(RefCell::new(ScriptContext::default()));
+        register_api(&lua, &context)?;
+        Ok(Self {
+            lua,
+            context,
+            file: None,
+        })
+    }
+
+    // Gives access to the Lua state, e.g. to register additional bindings.
+    pub fn lua(&self) -> &mlua::Lua {
+        &self.lua
+    }
+
+    pub fn input_mut(&mut self) -> RefMut<'_, ScriptInput> {
+        RefMut::map(self.context.borrow_mut(), |c| &mut c.input)
+    }
+
+    // Returns the commands issued by the scripts since the last call.
+    pub fn take_commands(&mut self) -> Vec<ScriptCommand> {
+        std::mem::take(&mut self.context.borrow_mut().commands)
+    }
+
+    pub fn exec(&mut self, name: &str, source: &str) -> Result<(), Error> {
+        self.lua.load(source).set_name(name).exec()?;
+        Ok(())
+    }
+
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref().to_path_buf();
+        let modified = std::fs::metadata(&path)?.modified()?;
+        self.exec_file(&path)?;
+        self.file = Some(ScriptFile { path, modified });
+        Ok(())
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|f| f.path.as_path())
+    }
+
+    // Executes the script file again if it was modified since it was last loaded, then calls
+    // on_reload. Returns true if the file was reloaded. If the new script fails, the error is
+    // returned and the reload is attempted again on the next modification.
+    pub fn reload_if_modified(&mut self) -> Result<bool, Error> {
+        let (path, modified) = match &self.file {
+            Some(f) => {
+                let modified = std::fs::metadata(&f.path)?.modified()?;
+                if modified == f.modified {
+                    return Ok(false);
+                }
+                (f.path.clone(), modified)
+            }
+            None => return Ok(false),
+        };
+        if let Some(f) = self.file.as_mut() {
+            f.modified = modified;
+        }
+        self.exec_file(&path)?;
+        self.call_function("on_reload", ())?;
+        Ok(true)
+    }
+
+    // Calls a global function defined by the scripts. Returns false if the function isn't
+    // defined.
+    pub fn call_function<'lua, A: mlua::IntoLuaMulti<'lua>>(
+        &'lua self,
+        name: &str,
+        args: A,
+    ) -> Result<bool, Error> {
+        match self.lua.globals().get::<_, Option<mlua::Function>>(name)? {
+            Some(f) => {
+                f.call::<_, ()>(args)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn call_start(&mut self) -> Result<(), Error> {
+        self.call_function("on_start", ())?;
+        Ok(())
+    }
+
+    pub fn call_end(&mut self) -> Result<(), Error> {
+        self.call_function("on_end", ())?;
+        Ok(())
+    }
+
+    pub fn call_fixed_update(&mut self, dt: std::time::Duration) -> Result<(), Error> {
+        self.call_function("on_fixed_update", dt.as_secs_f64())?;
+        Ok(())
+    }
+
+    pub fn call_variable_update(&mut self, dt: std::time::Duration) -> Result<(), Error> {
+        self.call_function("on_variable_update", dt.as_secs_f64())?;
+        Ok(())
+    }
+
+    fn exec_file(&mut self, path: &Path) -> Result<(), Error> {
+        let source = std::fs::read_to_string(path)?;
+        self.exec(&path.to_string_lossy(), &source)
+    }
+}
+
+impl std::fmt::Debug for ScriptEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScriptEngine")
+            .field("context", &self.context)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+fn register_api(lua: &mlua::Lua, context: &Rc<RefCell<ScriptContext>>) -> Result<(), Error> {
+    let api = lua.create_table()?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "draw_sprite",
+        lua.create_function(
+            move |_,
+                  (sprite, x, y, rotation, scale_x, scale_y): (
+                String,
+                f32,
+                f32,
+                Option<f32>,
+                Option<f32>,
+                Option<f32>,
+            )| {
+                let scale_x = scale_x.unwrap_or(1.);
+                c.borrow_mut().commands.push(ScriptCommand::DrawSprite {
+                    sprite,
+                    position: (x, y),
+                    rotation: rotation.unwrap_or(0.),
+                    scale: (scale_x, scale_y.unwrap_or(scale_x)),
+                });
+                Ok(())
+            },
+        )?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "play_sound",
+        lua.create_function(move |_, (sound, volume): (String, Option<f32>)| {
+            c.borrow_mut().commands.push(ScriptCommand::PlaySound {
+                sound,
+                volume: volume.unwrap_or(1.),
+            });
+            Ok(())
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "is_key_pressed",
+        lua.create_function(move |_, name: String| {
+            Ok(c.borrow().input.is_key_pressed_by_name(&name))
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "is_mouse_button_pressed",
+        lua.create_function(move |_, name: String| {
+            Ok(c.borrow().input.is_mouse_button_pressed_by_name(&name))
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "cursor_position",
+        lua.create_function(move |_, ()| {
+            let position = c.borrow().input.cursor_position();
+            Ok((position.x, position.y))
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "push_scene",
+        lua.create_function(move |_, name: String| {
+            c.borrow_mut().commands.push(ScriptCommand::PushScene(name));
+            Ok(())
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "pop_scene",
+        lua.create_function(move |_, ()| {
+            c.borrow_mut().commands.push(ScriptCommand::PopScene);
+            Ok(())
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "change_scene",
+        lua.create_function(move |_, name: String| {
+            c.borrow_mut()
+                .commands
+                .push(ScriptCommand::ChangeScene(name));
+            Ok(())
+        })?,
+    )?;
+
+    let c = Rc::clone(context);
+    api.set(
+        "exit",
+        lua.create_function(move |_, ()| {
+            c.borrow_mut().commands.push(ScriptCommand::Exit);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set("roe", api)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use roe_os as os;
+
+    fn temp_script_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("roe_script_{}_{}.lua", name, std::process::id()))
+    }
+
+    #[test]
+    fn commands() {
+        let mut engine = ScriptEngine::new().unwrap();
+        engine
+            .exec(
+                "test",
+                r#"
+                roe.draw_sprite("player", 1, 2)
+                roe.draw_sprite("enemy", 3, 4, 0.5, 2)
+                roe.play_sound("jump")
+                roe.push_scene("pause")
+                roe.pop_scene()
+                roe.change_scene("menu")
+                roe.exit()
+                "#,
+            )
+            .unwrap();
+        expect_that!(
+            &engine.take_commands(),
+            eq(vec![
+                ScriptCommand::DrawSprite {
+                    sprite: String::from("player"),
+                    position: (1., 2.),
+                    rotation: 0.,
+                    scale: (1., 1.),
+                },
+                ScriptCommand::DrawSprite {
+                    sprite: String::from("enemy"),
+                    position: (3., 4.),
+                    rotation: 0.5,
+                    scale: (2., 2.),
+                },
+                ScriptCommand::PlaySound {
+                    sound: String::from("jump"),
+                    volume: 1.,
+                },
+                ScriptCommand::PushScene(String::from("pause")),
+                ScriptCommand::PopScene,
+                ScriptCommand::ChangeScene(String::from("menu")),
+                ScriptCommand::Exit,
+            ])
+        );
+        expect_that!(&engine.take_commands(), eq(Vec::new()));
+    }
+
+    #[test]
+    fn input() {
+        let mut engine = ScriptEngine::new().unwrap();
+        engine.input_mut().set_key_pressed(os::KeyCode::Space, true);
+        engine
+            .input_mut()
+            .set_cursor_position(os::PhysicalPosition::new(5., 6.));
+        engine
+            .exec(
+                "test",
+                r#"
+                if roe.is_key_pressed("Space") and not roe.is_key_pressed("A") then
+                    local x, y = roe.cursor_position()
+                    roe.draw_sprite("cursor", x, y)
+                end
+                "#,
+            )
+            .unwrap();
+        expect_that!(
+            &engine.take_commands(),
+            eq(vec![ScriptCommand::DrawSprite {
+                sprite: String::from("cursor"),
+                position: (5., 6.),
+                rotation: 0.,
+                scale: (1., 1.),
+            }])
+        );
+    }
+
+    #[test]
+    fn callbacks() {
+        let mut engine = ScriptEngine::new().unwrap();
+        engine
+            .exec(
+                "test",
+                r#"
+                elapsed = 0
+                function on_fixed_update(dt)
+                    elapsed = elapsed + dt
+                    if elapsed >= 1 then
+                        roe.exit()
+                    end
+                end
+                "#,
+            )
+            .unwrap();
+        engine.call_start().unwrap();
+        engine
+            .call_fixed_update(std::time::Duration::from_millis(500))
+            .unwrap();
+        expect_that!(&engine.take_commands(), eq(Vec::new()));
+        engine
+            .call_fixed_update(std::time::Duration::from_millis(500))
+            .unwrap();
+        expect_that!(&engine.take_commands(), eq(vec![ScriptCommand::Exit]));
+        expect_that!(!engine.call_function("undefined", ()).unwrap());
+    }
+
+    #[test]
+    fn script_error() {
+        let mut engine = ScriptEngine::new().unwrap();
+        engine
+            .exec("test", "function on_start() error('failure') end")
+            .unwrap();
+        expect_that!(&engine.call_start(), is_variant!(Result::Err));
+        expect_that!(
+            &engine.exec("test", "this is not lua"),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn hot_reload() {
+        let path = temp_script_path("hot_reload");
+        std::fs::write(
+            &path,
+            "function on_fixed_update(dt) roe.play_sound('a') end",
+        )
+        .unwrap();
+
+        let mut engine = ScriptEngine::new().unwrap();
+        engine.load_file(&path).unwrap();
+        expect_that!(&engine.file_path(), eq(Some(path.as_path())));
+        expect_that!(!engine.reload_if_modified().unwrap());
+
+        std::fs::write(
+            &path,
+            r#"
+            function on_fixed_update(dt) roe.play_sound('b') end
+            function on_reload() roe.play_sound('reloaded') end
+            "#,
+        )
+        .unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+
+        expect_that!(engine.reload_if_modified().unwrap());
+        engine
+            .call_fixed_update(std::time::Duration::from_millis(10))
+            .unwrap();
+        expect_that!(
+            &engine.take_commands(),
+            eq(vec![
+                ScriptCommand::PlaySound {
+                    sound: String::from("reloaded"),
+                    volume: 1.,
+                },
+                ScriptCommand::PlaySound {
+                    sound: String::from("b"),
+                    volume: 1.,
+                },
+            ])
+        );
+        expect_that!(!engine.reload_if_modified().unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use roe_os as os;
+
+use std::collections::HashSet;
+
+// Snapshot of the input state queried by the scripts. Must be kept up to date by the
+// application, typically from the ApplicationState input callbacks.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScriptInput {
+    pressed_keys: HashSet<os::KeyCode>,
+    pressed_mouse_buttons: HashSet<os::MouseButton>,
+    cursor_position: (f64, f64),
+}
+
+impl ScriptInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_key_pressed(&mut self, key_code: os::KeyCode, pressed: bool) {
+        if pressed {
+            self.pressed_keys.insert(key_code);
+        } else {
+            self.pressed_keys.remove(&key_code);
+        }
+    }
+
+    pub fn is_key_pressed(&self, key_code: os::KeyCode) -> bool {
+        self.pressed_keys.contains(&key_code)
+    }
+
+    // Keys are identified by the name of the corresponding KeyCode variant, e.g. "Space".
+    pub fn is_key_pressed_by_name(&self, name: &str) -> bool {
+        self.pressed_keys.iter().any(|k| format!("{:?}", k) == name)
+    }
+
+    pub fn set_mouse_button_pressed(&mut self, button: os::MouseButton, pressed: bool) {
+        if pressed {
+            self.pressed_mouse_buttons.insert(button);
+        } else {
+            self.pressed_mouse_buttons.remove(&button);
+        }
+    }
+
+    pub fn is_mouse_button_pressed(&self, button: os::MouseButton) -> bool {
+        self.pressed_mouse_buttons.contains(&button)
+    }
+
+    // Buttons are identified by the name of the corresponding MouseButton variant, e.g. "Left".
+    pub fn is_mouse_button_pressed_by_name(&self, name: &str) -> bool {
+        self.pressed_mouse_buttons
+            .iter()
+            .any(|b| format!("{:?}", b) == name)
+    }
+
+    pub fn set_cursor_position(&mut self, position: os::PhysicalPosition<f64>) {
+        self.cursor_position = (position.x, position.y);
+    }
+
+    pub fn cursor_position(&self) -> os::PhysicalPosition<f64> {
+        os::PhysicalPosition::new(self.cursor_position.0, self.cursor_position.1)
+    }
+
+    pub fn release_all(&mut self) {
+        self.pressed_keys.clear();
+        self.pressed_mouse_buttons.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn keys() {
+        let mut input = ScriptInput::new();
+        input.set_key_pressed(os::KeyCode::Space, true);
+        expect_that!(input.is_key_pressed(os::KeyCode::Space));
+        expect_that!(input.is_key_pressed_by_name("Space"));
+        expect_that!(!input.is_key_pressed_by_name("A"));
+        input.set_key_pressed(os::KeyCode::Space, false);
+        expect_that!(!input.is_key_pressed(os::KeyCode::Space));
+    }
+
+    #[test]
+    fn mouse() {
+        let mut input = ScriptInput::new();
+        input.set_mouse_button_pressed(os::MouseButton::Left, true);
+        input.set_cursor_position(os::PhysicalPosition::new(3., 4.));
+        expect_that!(input.is_mouse_button_pressed_by_name("Left"));
+        expect_that!(!input.is_mouse_button_pressed_by_name("Right"));
+        expect_that!(
+            &input.cursor_position(),
+            eq(os::PhysicalPosition::new(3., 4.))
+        );
+        input.release_all();
+        expect_that!(!input.is_mouse_button_pressed(os::MouseButton::Left));
+    }
+}
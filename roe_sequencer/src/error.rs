@@ -0,0 +1,53 @@
+#[derive(Debug)]
+pub enum Error {
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    YamlError(serde_yaml::Error),
+    UnsupportedFormat(std::path::PathBuf),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::YamlError(e) => write!(f, "YAML error ({})", e),
+            Self::UnsupportedFormat(path) => {
+                write!(f, "Unsupported sequence format ({})", path.display())
+            }
+            Self::DuplicateLabel(label) => write!(f, "Duplicate label ({})", label),
+            Self::UnknownLabel(label) => write!(f, "Unknown label ({})", label),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            Self::YamlError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for Error {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Self::YamlError(e)
+    }
+}
@@ -0,0 +1,8 @@
+mod error;
+pub use error::*;
+
+mod sequence;
+pub use sequence::*;
+
+mod sequencer;
+pub use sequencer::*;
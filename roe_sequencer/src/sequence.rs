@@ -0,0 +1,231 @@
+use super::Error;
+
+use roe_math::Easing;
+
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashMap, path::Path};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ChoiceOption {
+    pub text: String,
+    // Label the sequence jumps to when the option is chosen.
+    pub goto: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum SequenceCommand {
+    // Shows a line of text and waits for Sequencer::advance. If chars_per_second is set, the
+    // text is revealed progressively.
+    ShowText {
+        #[serde(default)]
+        speaker: Option<String>,
+        text: String,
+        #[serde(default)]
+        chars_per_second: Option<f32>,
+    },
+    // Moves a sprite along a Bézier curve defined by the control points, the first point being
+    // the start position. If wait is set, the sequence doesn't proceed until the movement ends.
+    MoveSprite {
+        sprite: String,
+        points: Vec<[f32; 2]>,
+        duration: f32,
+        #[serde(default)]
+        easing: Easing,
+        #[serde(default)]
+        wait: bool,
+    },
+    PlaySound {
+        sound: String,
+    },
+    // Waits for the given time in seconds.
+    Wait {
+        duration: f32,
+    },
+    // Waits for the moving sprites to reach their destination.
+    WaitForMovements,
+    // Presents a choice and waits for Sequencer::choose.
+    Choice {
+        #[serde(default)]
+        prompt: Option<String>,
+        options: Vec<ChoiceOption>,
+    },
+    Label(String),
+    Goto(String),
+    End,
+}
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Sequence {
+    pub commands: Vec<SequenceCommand>,
+}
+
+impl Sequence {
+    pub fn from_ron_str(s: &str) -> Result<Self, Error> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn from_yaml_str(s: &str) -> Result<Self, Error> {
+        Ok(serde_yaml::from_str(s)?)
+    }
+
+    // Loads a sequence from a ".ron", ".yaml" or ".yml" file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("ron") => Self::from_ron_str(&std::fs::read_to_string(path)?),
+            Some("yaml") | Some("yml") => Self::from_yaml_str(&std::fs::read_to_string(path)?),
+            _ => Err(Error::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    // Maps the labels to the index of the command following them, and checks that all jumps
+    // have a valid target.
+    pub fn labels(&self) -> Result<HashMap<String, usize>, Error> {
+        let mut labels = HashMap::new();
+        for (i, command) in self.commands.iter().enumerate() {
+            if let SequenceCommand::Label(label) = command {
+                if labels.insert(label.clone(), i + 1).is_some() {
+                    return Err(Error::DuplicateLabel(label.clone()));
+                }
+            }
+        }
+        for command in self.commands.iter() {
+            let targets: Vec<&String> = match command {
+                SequenceCommand::Goto(label) => vec![label],
+                SequenceCommand::Choice { options, .. } => {
+                    options.iter().map(|o| &o.goto).collect()
+                }
+                _ => Vec::new(),
+            };
+            for target in targets {
+                if !labels.contains_key(target) {
+                    return Err(Error::UnknownLabel(target.clone()));
+                }
+            }
+        }
+        Ok(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn ron() {
+        let sequence = Sequence::from_ron_str(
+            r#"(
+                commands: [
+                    ShowText(speaker: Some("Alice"), text: "Hello"),
+                    MoveSprite(
+                        sprite: "alice",
+                        points: [(0., 0.), (10., 0.)],
+                        duration: 1.,
+                        easing: QuadraticInOut,
+                    ),
+                    Wait(duration: 0.5),
+                    Label("end"),
+                ],
+            )"#,
+        )
+        .unwrap();
+        expect_that!(
+            &sequence.commands,
+            eq(vec![
+                SequenceCommand::ShowText {
+                    speaker: Some(String::from("Alice")),
+                    text: String::from("Hello"),
+                    chars_per_second: None,
+                },
+                SequenceCommand::MoveSprite {
+                    sprite: String::from("alice"),
+                    points: vec![[0., 0.], [10., 0.]],
+                    duration: 1.,
+                    easing: Easing::QuadraticInOut,
+                    wait: false,
+                },
+                SequenceCommand::Wait { duration: 0.5 },
+                SequenceCommand::Label(String::from("end")),
+            ])
+        );
+    }
+
+    #[test]
+    fn yaml() {
+        let sequence = Sequence::from_yaml_str(
+            r#"
+            commands:
+              - PlaySound:
+                  sound: door
+              - Choice:
+                  options:
+                    - text: Enter
+                      goto: inside
+              - Label: inside
+              - End
+            "#,
+        )
+        .unwrap();
+        expect_that!(
+            &sequence.commands,
+            eq(vec![
+                SequenceCommand::PlaySound {
+                    sound: String::from("door")
+                },
+                SequenceCommand::Choice {
+                    prompt: None,
+                    options: vec![ChoiceOption {
+                        text: String::from("Enter"),
+                        goto: String::from("inside"),
+                    }],
+                },
+                SequenceCommand::Label(String::from("inside")),
+                SequenceCommand::End,
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_sequence() {
+        expect_that!(
+            &Sequence::from_ron_str("(commands: [Unknown])"),
+            is_variant!(Result::Err)
+        );
+        expect_that!(&Sequence::load("sequence.txt"), is_variant!(Result::Err));
+    }
+
+    #[test]
+    fn labels() {
+        let sequence = Sequence {
+            commands: vec![
+                SequenceCommand::Label(String::from("a")),
+                SequenceCommand::Goto(String::from("b")),
+                SequenceCommand::Label(String::from("b")),
+            ],
+        };
+        let labels = sequence.labels().unwrap();
+        expect_that!(&labels["a"], eq(1));
+        expect_that!(&labels["b"], eq(3));
+    }
+
+    #[test]
+    fn invalid_labels() {
+        let duplicate = Sequence {
+            commands: vec![
+                SequenceCommand::Label(String::from("a")),
+                SequenceCommand::Label(String::from("a")),
+            ],
+        };
+        expect_that!(&duplicate.labels(), is_variant!(Result::Err));
+
+        let unknown = Sequence {
+            commands: vec![SequenceCommand::Goto(String::from("a"))],
+        };
+        expect_that!(&unknown.labels(), is_variant!(Result::Err));
+    }
+}
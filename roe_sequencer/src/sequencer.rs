@@ -0,0 +1,594 @@
+use super::{Error, Sequence, SequenceCommand};
+
+use roe_math::{Easing, Vector2};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SequenceEvent {
+    TextShown {
+        speaker: Option<String>,
+        text: String,
+    },
+    ChoiceShown {
+        prompt: Option<String>,
+        options: Vec<String>,
+    },
+    SoundPlayed(String),
+    SpriteMoved {
+        sprite: String,
+        position: Vector2<f32>,
+    },
+    Finished,
+}
+
+// Text currently displayed, as it should be rendered by the text renderer.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DisplayedText<'a> {
+    pub speaker: Option<&'a str>,
+    // Part of the text revealed so far.
+    pub text: &'a str,
+    pub complete: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum SequencerState {
+    Running,
+    Waiting(Duration),
+    WaitingForMovements,
+    ShowingText {
+        speaker: Option<String>,
+        text: String,
+        chars_per_second: Option<f32>,
+        elapsed: Duration,
+    },
+    WaitingForChoice(Vec<String>),
+    Finished,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Movement {
+    sprite: String,
+    points: Vec<Vector2<f32>>,
+    duration: Duration,
+    elapsed: Duration,
+    easing: Easing,
+    wait: bool,
+}
+
+impl Movement {
+    fn position(&self) -> Vector2<f32> {
+        let t = if self.duration.is_zero() {
+            1.
+        } else {
+            self.elapsed.as_secs_f32() / self.duration.as_secs_f32()
+        };
+        bezier_point(&self.points, self.easing.apply(t))
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+// Runs a sequence of commands, typically a dialogue or a cutscene. The sequencer must be updated
+// every fixed update, and produces events that the application handles by playing sounds, moving
+// sprites, or rendering the displayed text and choices.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sequencer {
+    sequence: Sequence,
+    labels: HashMap<String, usize>,
+    next_command: usize,
+    state: SequencerState,
+    movements: Vec<Movement>,
+    events: VecDeque<SequenceEvent>,
+}
+
+impl Sequencer {
+    pub fn new(sequence: Sequence) -> Result<Self, Error> {
+        let labels = sequence.labels()?;
+        let mut sequencer = Self {
+            sequence,
+            labels,
+            next_command: 0,
+            state: SequencerState::Running,
+            movements: Vec::new(),
+            events: VecDeque::new(),
+        };
+        sequencer.run();
+        Ok(sequencer)
+    }
+
+    pub fn sequence(&self) -> &Sequence {
+        &self.sequence
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state == SequencerState::Finished
+    }
+
+    pub fn is_waiting_for_choice(&self) -> bool {
+        matches!(self.state, SequencerState::WaitingForChoice(_))
+    }
+
+    pub fn displayed_text(&self) -> Option<DisplayedText<'_>> {
+        match &self.state {
+            SequencerState::ShowingText {
+                speaker,
+                text,
+                chars_per_second,
+                elapsed,
+            } => {
+                let char_count = text.chars().count();
+                let revealed = match chars_per_second {
+                    Some(cps) => std::cmp::min((elapsed.as_secs_f32() * cps) as usize, char_count),
+                    None => char_count,
+                };
+                let end = text
+                    .char_indices()
+                    .nth(revealed)
+                    .map(|(i, _)| i)
+                    .unwrap_or_else(|| text.len());
+                Some(DisplayedText {
+                    speaker: speaker.as_deref(),
+                    text: &text[..end],
+                    complete: revealed == char_count,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<SequenceEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.update_movements(dt);
+        match &mut self.state {
+            SequencerState::Waiting(remaining) => {
+                *remaining = remaining.saturating_sub(dt);
+                if !remaining.is_zero() {
+                    return;
+                }
+            }
+            SequencerState::WaitingForMovements => {
+                if self.movements.iter().any(|m| m.wait) {
+                    return;
+                }
+            }
+            SequencerState::ShowingText { elapsed, .. } => {
+                *elapsed = elapsed.saturating_add(dt);
+                return;
+            }
+            SequencerState::Running => (),
+            SequencerState::WaitingForChoice(_) | SequencerState::Finished => return,
+        }
+        self.state = SequencerState::Running;
+        self.run();
+    }
+
+    // Proceeds past the displayed text. If the text isn't completely revealed yet, it is
+    // revealed instantly instead.
+    pub fn advance(&mut self) {
+        if let Some(text) = self.displayed_text() {
+            if text.complete {
+                self.state = SequencerState::Running;
+                self.run();
+            } else if let SequencerState::ShowingText { elapsed, .. } = &mut self.state {
+                *elapsed = Duration::MAX;
+            }
+        }
+    }
+
+    pub fn choose(&mut self, option: usize) {
+        let goto = match &self.state {
+            SequencerState::WaitingForChoice(options) => {
+                assert!(option < options.len(), "Invalid choice option");
+                match &self.sequence.commands[self.next_command - 1] {
+                    SequenceCommand::Choice { options, .. } => options[option].goto.clone(),
+                    _ => unreachable!(),
+                }
+            }
+            _ => panic!("The sequencer isn't waiting for a choice"),
+        };
+        self.next_command = self.labels[&goto];
+        self.state = SequencerState::Running;
+        self.run();
+    }
+
+    fn update_movements(&mut self, dt: Duration) {
+        for movement in self.movements.iter_mut() {
+            movement.elapsed = std::cmp::min(movement.elapsed + dt, movement.duration);
+            self.events.push_back(SequenceEvent::SpriteMoved {
+                sprite: movement.sprite.clone(),
+                position: movement.position(),
+            });
+        }
+        self.movements.retain(|m| !m.is_finished());
+    }
+
+    // Executes commands until a blocking command is reached.
+    fn run(&mut self) {
+        // Without blocking commands in between, executing the same command twice means that the
+        // sequence would never stop.
+        let mut visited = vec![false; self.sequence.commands.len()];
+        while self.state == SequencerState::Running {
+            let index = self.next_command;
+            if index >= self.sequence.commands.len() {
+                self.finish();
+                return;
+            }
+            assert!(!visited[index], "Infinite loop in sequence");
+            visited[index] = true;
+            self.next_command += 1;
+            self.execute(self.sequence.commands[index].clone());
+        }
+    }
+
+    fn execute(&mut self, command: SequenceCommand) {
+        match command {
+            SequenceCommand::ShowText {
+                speaker,
+                text,
+                chars_per_second,
+            } => {
+                self.events.push_back(SequenceEvent::TextShown {
+                    speaker: speaker.clone(),
+                    text: text.clone(),
+                });
+                self.state = SequencerState::ShowingText {
+                    speaker,
+                    text,
+                    chars_per_second,
+                    elapsed: Duration::ZERO,
+                };
+            }
+            SequenceCommand::MoveSprite {
+                sprite,
+                points,
+                duration,
+                easing,
+                wait,
+            } => {
+                assert!(
+                    !points.is_empty(),
+                    "A sprite movement requires at least one point"
+                );
+                let movement = Movement {
+                    sprite,
+                    points: points.iter().map(|p| Vector2::new(p[0], p[1])).collect(),
+                    duration: Duration::from_secs_f32(duration.max(0.)),
+                    elapsed: Duration::ZERO,
+                    easing,
+                    wait,
+                };
+                self.events.push_back(SequenceEvent::SpriteMoved {
+                    sprite: movement.sprite.clone(),
+                    position: movement.position(),
+                });
+                self.movements.retain(|m| m.sprite != movement.sprite);
+                if !movement.is_finished() {
+                    self.movements.push(movement);
+                    if wait {
+                        self.state = SequencerState::WaitingForMovements;
+                    }
+                }
+            }
+            SequenceCommand::PlaySound { sound } => {
+                self.events.push_back(SequenceEvent::SoundPlayed(sound));
+            }
+            SequenceCommand::Wait { duration } => {
+                let duration = Duration::from_secs_f32(duration.max(0.));
+                if !duration.is_zero() {
+                    self.state = SequencerState::Waiting(duration);
+                }
+            }
+            SequenceCommand::WaitForMovements => {
+                if !self.movements.is_empty() {
+                    for movement in self.movements.iter_mut() {
+                        movement.wait = true;
+                    }
+                    self.state = SequencerState::WaitingForMovements;
+                }
+            }
+            SequenceCommand::Choice { prompt, options } => {
+                let options: Vec<String> = options.into_iter().map(|o| o.text).collect();
+                self.events.push_back(SequenceEvent::ChoiceShown {
+                    prompt,
+                    options: options.clone(),
+                });
+                self.state = SequencerState::WaitingForChoice(options);
+            }
+            SequenceCommand::Label(_) => (),
+            SequenceCommand::Goto(label) => {
+                self.next_command = self.labels[&label];
+            }
+            SequenceCommand::End => self.finish(),
+        }
+    }
+
+    fn finish(&mut self) {
+        self.state = SequencerState::Finished;
+        self.events.push_back(SequenceEvent::Finished);
+    }
+}
+
+fn bezier_point(points: &[Vector2<f32>], t: f32) -> Vector2<f32> {
+    let mut points = points.to_vec();
+    for n in (1..points.len()).rev() {
+        for i in 0..n {
+            points[i] = points[i] + (points[i + 1] - points[i]) * t;
+        }
+    }
+    points[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChoiceOption;
+    use galvanic_assert::{matchers::*, *};
+
+    fn events(sequencer: &mut Sequencer) -> Vec<SequenceEvent> {
+        let mut events = Vec::new();
+        while let Some(event) = sequencer.poll_event() {
+            events.push(event);
+        }
+        events
+    }
+
+    fn text(text: &str) -> SequenceCommand {
+        SequenceCommand::ShowText {
+            speaker: None,
+            text: String::from(text),
+            chars_per_second: None,
+        }
+    }
+
+    fn text_event(text: &str) -> SequenceEvent {
+        SequenceEvent::TextShown {
+            speaker: None,
+            text: String::from(text),
+        }
+    }
+
+    fn sound(sound: &str) -> SequenceCommand {
+        SequenceCommand::PlaySound {
+            sound: String::from(sound),
+        }
+    }
+
+    fn sequencer(commands: Vec<SequenceCommand>) -> Sequencer {
+        Sequencer::new(Sequence { commands }).unwrap()
+    }
+
+    #[test]
+    fn bezier() {
+        let points = [
+            Vector2::new(0., 0.),
+            Vector2::new(1., 2.),
+            Vector2::new(2., 0.),
+        ];
+        expect_that!(&bezier_point(&points, 0.), eq(Vector2::new(0., 0.)));
+        expect_that!(&bezier_point(&points, 0.5), eq(Vector2::new(1., 1.)));
+        expect_that!(&bezier_point(&points, 1.), eq(Vector2::new(2., 0.)));
+        expect_that!(
+            &bezier_point(&[Vector2::new(3., 4.)], 0.5),
+            eq(Vector2::new(3., 4.))
+        );
+    }
+
+    #[test]
+    fn empty_sequence() {
+        let mut sequencer = sequencer(Vec::new());
+        expect_that!(sequencer.is_finished());
+        expect_that!(&events(&mut sequencer), eq(vec![SequenceEvent::Finished]));
+    }
+
+    #[test]
+    fn text_waits_for_advance() {
+        let mut sequencer = sequencer(vec![text("a"), text("b")]);
+        expect_that!(&events(&mut sequencer), eq(vec![text_event("a")]));
+        sequencer.update(Duration::from_secs(10));
+        expect_that!(&events(&mut sequencer), eq(Vec::new()));
+
+        sequencer.advance();
+        expect_that!(&events(&mut sequencer), eq(vec![text_event("b")]));
+        sequencer.advance();
+        expect_that!(&events(&mut sequencer), eq(vec![SequenceEvent::Finished]));
+        expect_that!(sequencer.displayed_text().is_none());
+    }
+
+    #[test]
+    fn progressive_text() {
+        let mut sequencer = sequencer(vec![SequenceCommand::ShowText {
+            speaker: Some(String::from("Bob")),
+            text: String::from("Héllo"),
+            chars_per_second: Some(10.),
+        }]);
+        expect_that!(
+            &sequencer.displayed_text(),
+            eq(Some(DisplayedText {
+                speaker: Some("Bob"),
+                text: "",
+                complete: false,
+            }))
+        );
+        sequencer.update(Duration::from_millis(200));
+        expect_that!(&sequencer.displayed_text().unwrap().text, eq("Hé"));
+
+        // The first advance reveals the whole text.
+        sequencer.advance();
+        expect_that!(
+            &sequencer.displayed_text(),
+            eq(Some(DisplayedText {
+                speaker: Some("Bob"),
+                text: "Héllo",
+                complete: true,
+            }))
+        );
+        sequencer.advance();
+        expect_that!(sequencer.is_finished());
+    }
+
+    #[test]
+    fn wait() {
+        let mut sequencer = sequencer(vec![
+            sound("a"),
+            SequenceCommand::Wait { duration: 1. },
+            sound("b"),
+        ]);
+        expect_that!(
+            &events(&mut sequencer),
+            eq(vec![SequenceEvent::SoundPlayed(String::from("a"))])
+        );
+        sequencer.update(Duration::from_millis(600));
+        expect_that!(&events(&mut sequencer), eq(Vec::new()));
+        sequencer.update(Duration::from_millis(600));
+        expect_that!(
+            &events(&mut sequencer),
+            eq(vec![
+                SequenceEvent::SoundPlayed(String::from("b")),
+                SequenceEvent::Finished
+            ])
+        );
+    }
+
+    #[test]
+    fn movement() {
+        let mut sequencer = sequencer(vec![
+            SequenceCommand::MoveSprite {
+                sprite: String::from("s"),
+                points: vec![[0., 0.], [10., 0.]],
+                duration: 1.,
+                easing: Easing::Linear,
+                wait: true,
+            },
+            sound("a"),
+        ]);
+        let moved = |x: f32| SequenceEvent::SpriteMoved {
+            sprite: String::from("s"),
+            position: Vector2::new(x, 0.),
+        };
+        expect_that!(&events(&mut sequencer), eq(vec![moved(0.)]));
+        sequencer.update(Duration::from_millis(500));
+        expect_that!(&events(&mut sequencer), eq(vec![moved(5.)]));
+        sequencer.update(Duration::from_millis(700));
+        expect_that!(
+            &events(&mut sequencer),
+            eq(vec![
+                moved(10.),
+                SequenceEvent::SoundPlayed(String::from("a")),
+                SequenceEvent::Finished
+            ])
+        );
+    }
+
+    #[test]
+    fn wait_for_movements() {
+        let mut sequencer = sequencer(vec![
+            SequenceCommand::MoveSprite {
+                sprite: String::from("s"),
+                points: vec![[0., 0.], [10., 0.]],
+                duration: 1.,
+                easing: Easing::Linear,
+                wait: false,
+            },
+            sound("a"),
+            SequenceCommand::WaitForMovements,
+            sound("b"),
+        ]);
+        expect_that!(&events(&mut sequencer).len(), eq(2));
+        sequencer.update(Duration::from_millis(500));
+        expect_that!(&events(&mut sequencer).len(), eq(1));
+        sequencer.update(Duration::from_millis(500));
+        expect_that!(&events(&mut sequencer).len(), eq(3));
+        expect_that!(sequencer.is_finished());
+    }
+
+    #[test]
+    fn choice() {
+        let mut sequencer = sequencer(vec![
+            SequenceCommand::Choice {
+                prompt: Some(String::from("?")),
+                options: vec![
+                    ChoiceOption {
+                        text: String::from("yes"),
+                        goto: String::from("yes"),
+                    },
+                    ChoiceOption {
+                        text: String::from("no"),
+                        goto: String::from("no"),
+                    },
+                ],
+            },
+            SequenceCommand::Label(String::from("yes")),
+            sound("yes"),
+            SequenceCommand::End,
+            SequenceCommand::Label(String::from("no")),
+            sound("no"),
+        ]);
+        expect_that!(
+            &events(&mut sequencer),
+            eq(vec![SequenceEvent::ChoiceShown {
+                prompt: Some(String::from("?")),
+                options: vec![String::from("yes"), String::from("no")],
+            }])
+        );
+        expect_that!(sequencer.is_waiting_for_choice());
+        sequencer.update(Duration::from_secs(1));
+        expect_that!(sequencer.is_waiting_for_choice());
+
+        let mut other = sequencer.clone();
+        sequencer.choose(0);
+        expect_that!(
+            &events(&mut sequencer),
+            eq(vec![
+                SequenceEvent::SoundPlayed(String::from("yes")),
+                SequenceEvent::Finished
+            ])
+        );
+        other.choose(1);
+        expect_that!(
+            &events(&mut other),
+            eq(vec![
+                SequenceEvent::SoundPlayed(String::from("no")),
+                SequenceEvent::Finished
+            ])
+        );
+    }
+
+    #[test]
+    fn goto_loop() {
+        let mut sequencer = sequencer(vec![
+            SequenceCommand::Label(String::from("start")),
+            text("a"),
+            SequenceCommand::Goto(String::from("start")),
+        ]);
+        for _ in 0..3 {
+            expect_that!(&events(&mut sequencer), eq(vec![text_event("a")]));
+            sequencer.advance();
+        }
+        expect_that!(!sequencer.is_finished());
+    }
+
+    #[test]
+    #[should_panic(expected = "Infinite loop in sequence")]
+    fn infinite_loop() {
+        sequencer(vec![
+            SequenceCommand::Label(String::from("start")),
+            sound("a"),
+            SequenceCommand::Goto(String::from("start")),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The sequencer isn't waiting for a choice")]
+    fn choose_without_choice() {
+        sequencer(vec![text("a")]).choose(0);
+    }
+}
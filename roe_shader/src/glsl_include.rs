@@ -0,0 +1,44 @@
+use super::ShaderCompilationError;
+
+// Files shared by the engine GLSL shaders, included with #include <name>.
+pub const GLSL_INCLUDES: &[(&str, &str)] = &[(
+    "roe/color_conversion.glsl",
+    include_str!("shaders/include/color_conversion.glsl"),
+)];
+
+pub fn glsl_include(name: &str) -> Option<&'static str> {
+    GLSL_INCLUDES
+        .iter()
+        .find(|(include_name, _)| *include_name == name)
+        .map(|(_, code)| *code)
+}
+
+// Compile options resolving the shared includes.
+pub(crate) fn glsl_compile_options<'a>(
+) -> Result<shaderc::CompileOptions<'a>, ShaderCompilationError> {
+    let mut options = shaderc::CompileOptions::new()
+        .ok_or(ShaderCompilationError::CompilerInitializationFailed)?;
+    options.set_include_callback(|name, _, _, _| {
+        glsl_include(name)
+            .map(|code| shaderc::ResolvedInclude {
+                resolved_name: String::from(name),
+                content: String::from(code),
+            })
+            .ok_or_else(|| format!("Unknown include {}", name))
+    });
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn includes() {
+        expect_that!(glsl_include("roe/color_conversion.glsl")
+            .unwrap()
+            .contains("vec3 convertColor(vec3 color, uint conversion)"));
+        expect_that!(&glsl_include("roe/missing.glsl"), eq(None));
+    }
+}
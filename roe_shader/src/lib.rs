@@ -1,2 +1,8 @@
+mod glsl_include;
+pub use glsl_include::*;
+
 mod spirv;
 pub use spirv::*;
+
+mod shader_compiler;
+pub use shader_compiler::*;
@@ -0,0 +1,627 @@
+use super::{glsl_compile_options, ShaderCompilationError, GLSL_INCLUDES};
+
+use sha1::{Digest, Sha1};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+// Bump when the compilation process changes, invalidating the cached binaries.
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ShaderLanguage {
+    Glsl,
+    Wgsl,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ShaderKind {
+    Vertex,
+    Fragment,
+    Geometry,
+    Compute,
+}
+
+impl ShaderKind {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "vert" => Some(Self::Vertex),
+            "frag" => Some(Self::Fragment),
+            "geom" => Some(Self::Geometry),
+            "comp" => Some(Self::Compute),
+            _ => None,
+        }
+    }
+}
+
+// Defines are sorted so that the same set always produces the same cache key.
+pub type ShaderDefines = BTreeMap<String, String>;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShaderSource {
+    // Used in error messages.
+    pub name: String,
+    pub code: String,
+    pub language: ShaderLanguage,
+    pub kind: ShaderKind,
+    pub entry_point: String,
+}
+
+impl ShaderSource {
+    pub fn new(name: &str, code: &str, language: ShaderLanguage, kind: ShaderKind) -> Self {
+        Self {
+            name: String::from(name),
+            code: String::from(code),
+            language,
+            kind,
+            entry_point: String::from("main"),
+        }
+    }
+
+    // GLSL files are identified by the stage extension (e.g. "sprite.frag"), WGSL files by
+    // the stage extension followed by "wgsl" (e.g. "sprite.frag.wgsl").
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ShaderCompilationError> {
+        let path = path.as_ref();
+        let extension = |p: &Path| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .map(String::from)
+                .unwrap_or_default()
+        };
+        let (language, kind_extension) = match extension(path).as_str() {
+            "wgsl" => (
+                ShaderLanguage::Wgsl,
+                extension(Path::new(path.file_stem().unwrap_or_default())),
+            ),
+            e => (ShaderLanguage::Glsl, String::from(e)),
+        };
+        let kind = ShaderKind::from_extension(&kind_extension)
+            .ok_or(ShaderCompilationError::InvalidShaderExtension)?;
+        let code = std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            path.to_str().unwrap_or("unnamed_shader"),
+            &code,
+            language,
+            kind,
+        ))
+    }
+
+    // The name isn't part of the key, identical code compiled from different files shares the
+    // same binary.
+    pub fn cache_key(&self, defines: &ShaderDefines) -> String {
+        let mut hasher = Sha1::new();
+        hasher.update(CACHE_VERSION.to_le_bytes());
+        hasher.update(format!("{:?}\0{:?}\0", self.language, self.kind));
+        hasher.update(&self.entry_point);
+        hasher.update([0]);
+        hasher.update(&self.code);
+        hasher.update([0]);
+        if self.language == ShaderLanguage::Glsl {
+            for (name, code) in GLSL_INCLUDES {
+                hasher.update(name);
+                hasher.update([0]);
+                hasher.update(code);
+                hasher.update([0]);
+            }
+        }
+        for (name, value) in defines {
+            hasher.update(name);
+            hasher.update([0]);
+            hasher.update(value);
+            hasher.update([0]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Minimal preprocessor for languages without one. Supports #ifdef, #ifndef, #else and #endif,
+// and replaces the defined names with their values. Removed lines are left empty so that line
+// numbers in error messages still match the source.
+pub fn preprocess_shader(
+    code: &str,
+    defines: &ShaderDefines,
+) -> Result<String, ShaderCompilationError> {
+    let error = |line: usize, message: &str| {
+        ShaderCompilationError::PreprocessingFailed(format!("line {}: {}", line + 1, message))
+    };
+    // For each open block: whether the parent is active, whether the block is active.
+    let mut blocks: Vec<(bool, bool)> = Vec::new();
+    let mut out = String::with_capacity(code.len());
+    for (i, line) in code.lines().enumerate() {
+        let active = blocks.last().is_none_or(|b| b.1);
+        let trimmed = line.trim();
+        let mut words = trimmed.split_whitespace();
+        match words.next() {
+            Some(directive @ ("#ifdef" | "#ifndef")) => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| error(i, "Missing define name"))?;
+                let defined = defines.contains_key(name);
+                blocks.push((active, active && (defined == (directive == "#ifdef"))));
+            }
+            Some("#else") => {
+                let block = blocks
+                    .last_mut()
+                    .ok_or_else(|| error(i, "#else without #ifdef"))?;
+                block.1 = block.0 && !block.1;
+            }
+            Some("#endif") => {
+                blocks
+                    .pop()
+                    .ok_or_else(|| error(i, "#endif without #ifdef"))?;
+            }
+            _ if active => out.push_str(&replace_defines(line, defines)),
+            _ => (),
+        }
+        out.push('\n');
+    }
+    if !blocks.is_empty() {
+        return Err(error(code.lines().count(), "Unterminated #ifdef"));
+    }
+    Ok(out)
+}
+
+fn replace_defines(line: &str, defines: &ShaderDefines) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(is_identifier_char) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        match defines.get(word) {
+            Some(value) if !value.is_empty() => out.push_str(value),
+            _ => out.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ShaderCompilerDescriptor {
+    // Compiled binaries are stored here and reused across runs. Only the in-memory cache is
+    // used if None.
+    pub cache_dir: Option<PathBuf>,
+}
+
+// Compiles GLSL (through shaderc) and WGSL (through naga) into SPIR-V, caching the results by
+// source and defines. Usable from build scripts or at runtime, e.g. for custom materials.
+pub struct ShaderCompiler {
+    compiler: Option<shaderc::Compiler>,
+    cache_dir: Option<PathBuf>,
+    cache: HashMap<String, Vec<u32>>,
+}
+
+impl ShaderCompiler {
+    pub fn new(desc: &ShaderCompilerDescriptor) -> Self {
+        Self {
+            compiler: None,
+            cache_dir: desc.cache_dir.clone(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+
+    pub fn compile(
+        &mut self,
+        source: &ShaderSource,
+        defines: &ShaderDefines,
+    ) -> Result<Vec<u32>, ShaderCompilationError> {
+        let key = source.cache_key(defines);
+        if let Some(spirv) = self.cache.get(&key) {
+            return Ok(spirv.clone());
+        }
+        let cache_path = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.spv", key)));
+        // Unreadable or corrupted cache entries are recompiled.
+        let cached = cache_path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|bytes| spirv_from_bytes(&bytes));
+        let spirv = match cached {
+            Some(spirv) => spirv,
+            None => {
+                let spirv = match source.language {
+                    ShaderLanguage::Glsl => self.compile_glsl(source, defines)?,
+                    ShaderLanguage::Wgsl => compile_wgsl(source, defines)?,
+                };
+                if let Some(path) = &cache_path {
+                    if let Some(dir) = path.parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    std::fs::write(path, spirv_to_bytes(&spirv))?;
+                }
+                spirv
+            }
+        };
+        self.cache.insert(key, spirv.clone());
+        Ok(spirv)
+    }
+
+    pub fn compile_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        defines: &ShaderDefines,
+    ) -> Result<Vec<u32>, ShaderCompilationError> {
+        self.compile(&ShaderSource::from_file(path)?, defines)
+    }
+
+    // Clears the in-memory cache and removes the cached binaries.
+    pub fn clear_cache(&mut self) -> Result<(), ShaderCompilationError> {
+        self.cache.clear();
+        if let Some(dir) = &self.cache_dir {
+            if dir.exists() {
+                for entry in std::fs::read_dir(dir)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|e| e == "spv") {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_glsl(
+        &mut self,
+        source: &ShaderSource,
+        defines: &ShaderDefines,
+    ) -> Result<Vec<u32>, ShaderCompilationError> {
+        if self.compiler.is_none() {
+            self.compiler = Some(
+                shaderc::Compiler::new()
+                    .ok_or(ShaderCompilationError::CompilerInitializationFailed)?,
+            );
+        }
+        let compiler = self.compiler.as_mut().unwrap();
+        let mut options = glsl_compile_options()?;
+        for (name, value) in defines {
+            options.add_macro_definition(name, Some(value.as_str()).filter(|v| !v.is_empty()));
+        }
+        let shader_kind = match source.kind {
+            ShaderKind::Vertex => shaderc::ShaderKind::Vertex,
+            ShaderKind::Fragment => shaderc::ShaderKind::Fragment,
+            ShaderKind::Geometry => shaderc::ShaderKind::Geometry,
+            ShaderKind::Compute => shaderc::ShaderKind::Compute,
+        };
+        let artifact = compiler.compile_into_spirv(
+            &source.code,
+            shader_kind,
+            &source.name,
+            &source.entry_point,
+            Some(&options),
+        )?;
+        Ok(artifact.as_binary().to_vec())
+    }
+}
+
+impl std::fmt::Debug for ShaderCompiler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ShaderCompiler {{ cache_dir: {:?}, cached: {} }}",
+            self.cache_dir,
+            self.cache.len()
+        )
+    }
+}
+
+fn compile_wgsl(
+    source: &ShaderSource,
+    defines: &ShaderDefines,
+) -> Result<Vec<u32>, ShaderCompilationError> {
+    let wgsl_error = |e: String| {
+        ShaderCompilationError::WgslCompilationFailed(format!("{}: {}", source.name, e))
+    };
+    let stage = match source.kind {
+        ShaderKind::Vertex => naga::ShaderStage::Vertex,
+        ShaderKind::Fragment => naga::ShaderStage::Fragment,
+        ShaderKind::Compute => naga::ShaderStage::Compute,
+        ShaderKind::Geometry => {
+            return Err(wgsl_error(String::from(
+                "Geometry shaders aren't supported",
+            )))
+        }
+    };
+    let code = preprocess_shader(&source.code, defines)?;
+    let module =
+        naga::front::wgsl::parse_str(&code).map_err(|e| wgsl_error(e.emit_to_string(&code)))?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| wgsl_error(format!("{:?}", e)))?;
+    naga::back::spv::write_vec(
+        &module,
+        &info,
+        &naga::back::spv::Options::default(),
+        Some(&naga::back::spv::PipelineOptions {
+            shader_stage: stage,
+            entry_point: source.entry_point.clone(),
+        }),
+    )
+    .map_err(|e| wgsl_error(e.to_string()))
+}
+
+// Defines of the permutation with the given index: bit i of the index enables features[i].
+pub fn permutation_defines(features: &[&str], index: u32) -> ShaderDefines {
+    features
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| index & (1 << i) != 0)
+        .map(|(_, name)| (String::from(*name), String::new()))
+        .collect()
+}
+
+// Compiles every combination of the features. Outputs are named "<file name>.<index>.spv",
+// where the index is the feature mask as in permutation_defines.
+pub fn compile_shader_permutations<P, Q>(
+    compiler: &mut ShaderCompiler,
+    in_path: P,
+    out_dir: Q,
+    features: &[&str],
+) -> Result<(), ShaderCompilationError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let source = ShaderSource::from_file(&in_path)?;
+    let file_name = in_path
+        .as_ref()
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unnamed");
+    std::fs::create_dir_all(&out_dir)?;
+    for index in 0..(1u32 << features.len()) {
+        let spirv = compiler.compile(&source, &permutation_defines(features, index))?;
+        std::fs::write(
+            out_dir
+                .as_ref()
+                .join(format!("{}.{}.spv", file_name, index)),
+            spirv_to_bytes(&spirv),
+        )?;
+    }
+    Ok(())
+}
+
+fn spirv_to_bytes(spirv: &[u32]) -> Vec<u8> {
+    spirv.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+// Checks the SPIR-V magic number.
+fn spirv_from_bytes(bytes: &[u8]) -> Option<Vec<u32>> {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+    let spirv: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    if spirv[0] == 0x0723_0203 {
+        Some(spirv)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const WGSL_SHADER: &str = "
+[[stage(fragment)]]
+fn main() -> [[location(0)]] vec4<f32> {
+#ifdef RED
+    return vec4<f32>(1.0, 0.0, 0.0, ALPHA);
+#else
+    return vec4<f32>(0.0, 0.0, 1.0, ALPHA);
+#endif
+}
+";
+
+    fn defines(values: &[(&str, &str)]) -> ShaderDefines {
+        values
+            .iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn preprocessing() {
+        let code = "a\n#ifdef X\nb X\n#ifndef Y\nc\n#else\nd\n#endif\n#else\ne\n#endif\nf";
+        expect_that!(
+            &preprocess_shader(code, &defines(&[("X", "1")])).unwrap(),
+            eq(String::from("a\n\nb 1\n\nc\n\n\n\n\n\n\nf\n"))
+        );
+        expect_that!(
+            &preprocess_shader(code, &defines(&[("X", ""), ("Y", "")])).unwrap(),
+            eq(String::from("a\n\nb X\n\n\n\nd\n\n\n\n\nf\n"))
+        );
+        expect_that!(
+            &preprocess_shader(code, &defines(&[])).unwrap(),
+            eq(String::from("a\n\n\n\n\n\n\n\n\ne\n\nf\n"))
+        );
+        expect_that!(
+            &preprocess_shader("XY X_1 X", &defines(&[("X", "2")])).unwrap(),
+            eq(String::from("XY X_1 2\n"))
+        );
+        expect_that!(
+            &preprocess_shader("#ifdef X\n", &defines(&[])),
+            is_variant!(Result::Err)
+        );
+        expect_that!(
+            &preprocess_shader("#endif\n", &defines(&[])),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn permutations() {
+        let features = ["A", "B", "C"];
+        expect_that!(&permutation_defines(&features, 0), eq(defines(&[])));
+        expect_that!(
+            &permutation_defines(&features, 5),
+            eq(defines(&[("A", ""), ("C", "")]))
+        );
+
+        let dir = std::env::temp_dir().join("roe_shader_permutations");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.frag.wgsl"), WGSL_SHADER).unwrap();
+        let mut compiler = ShaderCompiler::new(&ShaderCompilerDescriptor::default());
+        compile_shader_permutations(
+            &mut compiler,
+            dir.join("a.frag.wgsl"),
+            dir.join("gen"),
+            &["RED", "ALPHA"],
+        )
+        .unwrap_err();
+        std::fs::write(dir.join("b.frag.wgsl"), WGSL_SHADER.replace("ALPHA", "1.0")).unwrap();
+        compile_shader_permutations(
+            &mut compiler,
+            dir.join("b.frag.wgsl"),
+            dir.join("gen"),
+            &["RED"],
+        )
+        .unwrap();
+        expect_that!(dir.join("gen/b.frag.wgsl.0.spv").exists());
+        expect_that!(dir.join("gen/b.frag.wgsl.1.spv").exists());
+        expect_that!(!dir.join("gen/b.frag.wgsl.2.spv").exists());
+    }
+
+    #[test]
+    fn cache_key() {
+        let source =
+            ShaderSource::new("a", WGSL_SHADER, ShaderLanguage::Wgsl, ShaderKind::Fragment);
+        let key = source.cache_key(&defines(&[("RED", "")]));
+        expect_that!(&key.len(), eq(40));
+        expect_that!(&source.cache_key(&defines(&[("RED", "")])), eq(key.clone()));
+        let mut renamed = source.clone();
+        renamed.name = String::from("b");
+        expect_that!(
+            &renamed.cache_key(&defines(&[("RED", "")])),
+            eq(key.clone())
+        );
+        expect_that!(source.cache_key(&defines(&[])) != key);
+        expect_that!(source.cache_key(&defines(&[("RED", "1")])) != key);
+        let mut vertex = source.clone();
+        vertex.kind = ShaderKind::Vertex;
+        expect_that!(vertex.cache_key(&defines(&[("RED", "")])) != key);
+    }
+
+    #[test]
+    fn source_from_file() {
+        let dir = std::env::temp_dir().join("roe_shader_source_from_file");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.frag.wgsl"), WGSL_SHADER).unwrap();
+        std::fs::write(dir.join("a.vert"), "void main() {}").unwrap();
+        std::fs::write(dir.join("a.wgsl"), WGSL_SHADER).unwrap();
+
+        let source = ShaderSource::from_file(dir.join("a.frag.wgsl")).unwrap();
+        expect_that!(&source.language, eq(ShaderLanguage::Wgsl));
+        expect_that!(&source.kind, eq(ShaderKind::Fragment));
+        let source = ShaderSource::from_file(dir.join("a.vert")).unwrap();
+        expect_that!(&source.language, eq(ShaderLanguage::Glsl));
+        expect_that!(&source.kind, eq(ShaderKind::Vertex));
+        expect_that!(
+            &ShaderSource::from_file(dir.join("a.wgsl")),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn glsl_includes() {
+        let code = "#version 450\n\
+            #include <roe/color_conversion.glsl>\n\
+            layout(location = 0) out vec4 outColor;\n\
+            void main() {\n\
+                outColor = vec4(convertColor(vec3(0.5), 1u), 1.);\n\
+            }\n";
+        let mut compiler = ShaderCompiler::new(&ShaderCompilerDescriptor::default());
+        let source = ShaderSource::new("a", code, ShaderLanguage::Glsl, ShaderKind::Fragment);
+        expect_that!(
+            &compiler.compile(&source, &defines(&[])).unwrap()[0],
+            eq(0x0723_0203)
+        );
+        let missing = ShaderSource::new(
+            "b",
+            &code.replace("color_conversion", "missing"),
+            ShaderLanguage::Glsl,
+            ShaderKind::Fragment,
+        );
+        expect_that!(
+            &compiler.compile(&missing, &defines(&[])),
+            is_variant!(Result::Err)
+        );
+    }
+
+    #[test]
+    fn wgsl_compilation_and_caching() {
+        let cache_dir = std::env::temp_dir().join("roe_shader_cache");
+        let mut compiler = ShaderCompiler::new(&ShaderCompilerDescriptor {
+            cache_dir: Some(cache_dir.clone()),
+        });
+        compiler.clear_cache().unwrap();
+        let source =
+            ShaderSource::new("a", WGSL_SHADER, ShaderLanguage::Wgsl, ShaderKind::Fragment);
+        let red = defines(&[("RED", ""), ("ALPHA", "1.0")]);
+        let blue = defines(&[("ALPHA", "0.5")]);
+
+        let red_spirv = compiler.compile(&source, &red).unwrap();
+        let blue_spirv = compiler.compile(&source, &blue).unwrap();
+        expect_that!(&red_spirv[0], eq(0x0723_0203));
+        expect_that!(red_spirv != blue_spirv);
+        expect_that!(cache_dir
+            .join(format!("{}.spv", source.cache_key(&red)))
+            .exists());
+
+        // A new compiler reads the binaries from the disk cache.
+        let mut compiler = ShaderCompiler::new(&ShaderCompilerDescriptor {
+            cache_dir: Some(cache_dir.clone()),
+        });
+        let mut broken = source.clone();
+        broken.code = String::from("not wgsl");
+        std::fs::write(
+            cache_dir.join(format!("{}.spv", broken.cache_key(&red))),
+            spirv_to_bytes(&red_spirv),
+        )
+        .unwrap();
+        expect_that!(
+            &compiler.compile(&broken, &red).unwrap(),
+            eq(red_spirv.clone())
+        );
+
+        // Corrupted entries are recompiled.
+        std::fs::write(
+            cache_dir.join(format!("{}.spv", source.cache_key(&blue))),
+            [1, 2, 3, 4],
+        )
+        .unwrap();
+        expect_that!(&compiler.compile(&source, &blue).unwrap(), eq(blue_spirv));
+
+        compiler.clear_cache().unwrap();
+        expect_that!(&compiler.compile(&broken, &red), is_variant!(Result::Err));
+        expect_that!(
+            &compiler.compile(&source, &defines(&[])),
+            is_variant!(Result::Err)
+        );
+    }
+}
@@ -0,0 +1,16 @@
+#ifndef ROE_COLOR_CONVERSION_GLSL
+#define ROE_COLOR_CONVERSION_GLSL
+
+// Values of ColorConversion.
+vec3 convertColor(vec3 color, uint conversion) {
+    if (conversion == 1u) {
+        return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92,
+            lessThanEqual(color, vec3(0.0031308)));
+    } else if (conversion == 2u) {
+        return mix(pow((color + 0.055) / 1.055, vec3(2.4)), color / 12.92,
+            lessThanEqual(color, vec3(0.04045)));
+    }
+    return color;
+}
+
+#endif
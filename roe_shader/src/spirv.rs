@@ -6,6 +6,8 @@ pub enum ShaderCompilationError {
     IoError(std::io::Error),
     CompilerInitializationFailed,
     CompilationFailed(shaderc::Error),
+    PreprocessingFailed(String),
+    WgslCompilationFailed(String),
 }
 
 impl std::fmt::Display for ShaderCompilationError {
@@ -17,6 +19,12 @@ impl std::fmt::Display for ShaderCompilationError {
                 write!(f, "Compiler initialization failed")
             }
             ShaderCompilationError::CompilationFailed(e) => write!(f, "Compilation failed ({})", e),
+            ShaderCompilationError::PreprocessingFailed(e) => {
+                write!(f, "Preprocessing failed ({})", e)
+            }
+            ShaderCompilationError::WgslCompilationFailed(e) => {
+                write!(f, "WGSL compilation failed ({})", e)
+            }
         }
     }
 }
@@ -67,12 +75,13 @@ pub fn compile_shader_into_spirv(
         None => return Err(ShaderCompilationError::CompilerInitializationFailed),
     };
 
+    let options = super::glsl_compile_options()?;
     let compiled_file = compiler.compile_into_spirv(
         &source,
         shader_kind,
         in_path.to_str().unwrap_or("unnamed_shader"),
         "main",
-        None,
+        Some(&options),
     )?;
 
     std::fs::write(&out_path, compiled_file.as_binary_u8())?;
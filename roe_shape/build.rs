@@ -9,6 +9,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
     let in_dir: std::path::PathBuf = [shader_folder, "glsl"].iter().collect();
     let out_dir: std::path::PathBuf = [shader_folder, "gen", "spirv"].iter().collect();
-    roe_shader::compile_shaders_into_spirv(in_dir, out_dir)?;
+    roe_shader::compile_shaders_into_spirv(in_dir.clone(), out_dir.clone())?;
+
+    // Fragment shader permutations, indexed by the bits of ShapeFeatures. The source is in a
+    // subfolder, so that it isn't also compiled without defines by the call above.
+    let mut compiler = roe_shader::ShaderCompiler::new(&roe_shader::ShaderCompilerDescriptor {
+        cache_dir: Some(std::path::PathBuf::from(std::env::var("OUT_DIR")?).join("shader_cache")),
+    });
+    roe_shader::compile_shader_permutations(
+        &mut compiler,
+        in_dir.join("permutations").join("shape2.frag"),
+        out_dir,
+        &["ALPHA_TEST"],
+    )?;
     Ok(())
 }
@@ -6,6 +6,12 @@ use roe_graphics as gfx;
 
 use roe_math::{HomogeneousMatrix2, HomogeneousMatrix3};
 
+mod stress;
+pub use stress::*;
+
+mod virtual_gamepad;
+pub use virtual_gamepad::*;
+
 #[repr(C, packed)]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vertex {
@@ -30,7 +36,7 @@ unsafe impl bytemuck::Pod for Vertex {}
 
 pub type MeshIndexRange = gfx::MeshIndexRange;
 pub type MeshIndex = gfx::MeshIndex;
-pub type Mesh = gfx::IndexedMesh<Vertex>;
+pub type Mesh<I = MeshIndex> = gfx::IndexedMesh<Vertex, I>;
 
 #[repr(C, packed)]
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -59,18 +65,39 @@ unsafe impl bytemuck::Zeroable for PushConstants {
 
 unsafe impl bytemuck::Pod for PushConstants {}
 
+bitflags::bitflags! {
+    // Optional shader features. Each combination uses a different shader permutation,
+    // generated at build time.
+    #[derive(Default)]
+    pub struct ShapeFeatures: u32 {
+        // Discards fragments with alpha lower than 0.5.
+        const ALPHA_TEST = 0b1;
+    }
+}
+
+fn fragment_shader(features: ShapeFeatures) -> gfx::ShaderModuleDescriptor<'static> {
+    match features.bits() {
+        0 => gfx::include_spirv!("shaders/gen/spirv/shape2.frag.0.spv"),
+        1 => gfx::include_spirv!("shaders/gen/spirv/shape2.frag.1.spv"),
+        bits => unreachable!("No shader variant for the feature bits {:#x}", bits),
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct RenderPipelineDescriptor {
+    pub label: Option<String>,
     pub color_blend: gfx::BlendComponent,
     pub alpha_blend: gfx::BlendComponent,
     pub write_mask: gfx::ColorWrite,
     pub color_buffer_format: gfx::CanvasColorBufferFormat,
     pub sample_count: gfx::SampleCount,
+    pub features: ShapeFeatures,
 }
 
 impl Default for RenderPipelineDescriptor {
     fn default() -> Self {
         Self {
+            label: None,
             color_blend: gfx::BlendComponent {
                 src_factor: gfx::BlendFactor::SrcAlpha,
                 dst_factor: gfx::BlendFactor::OneMinusSrcAlpha,
@@ -84,42 +111,54 @@ impl Default for RenderPipelineDescriptor {
             write_mask: gfx::ColorWrite::ALL,
             color_buffer_format: gfx::CanvasColorBufferFormat::default(),
             sample_count: 1,
+            features: ShapeFeatures::empty(),
         }
     }
 }
 
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<PushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
 #[derive(Debug)]
 pub struct RenderPipeline {
     pipeline: gfx::RenderPipeline,
     sample_count: gfx::SampleCount,
     color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+    features: ShapeFeatures,
 }
 
+// Pipelines for each descriptor, e.g. one per feature combination.
+pub type RenderPipelineCache = gfx::PipelineCache<RenderPipelineDescriptor, RenderPipeline>;
+
 impl RenderPipeline {
     pub fn new(instance: &gfx::Instance, desc: &RenderPipelineDescriptor) -> Self {
         let pipeline_layout = gfx::PipelineLayout::new(
             &instance,
             &gfx::PipelineLayoutDescriptor {
-                label: None,
+                label: desc.label.as_deref(),
                 bind_group_layouts: &[],
-                push_constant_ranges: &[gfx::PushConstantRange {
-                    stages: gfx::ShaderStage::VERTEX,
-                    range: 0..std::mem::size_of::<PushConstants>() as u32,
-                }],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_CONVERSION_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_CONVERSION_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
             },
         );
         let vs_module = gfx::ShaderModule::new(
             instance,
             &gfx::include_spirv!("shaders/gen/spirv/shape2.vert.spv"),
         );
-        let fs_module = gfx::ShaderModule::new(
-            instance,
-            &gfx::include_spirv!("shaders/gen/spirv/shape2.frag.spv"),
-        );
+        let fs_module = gfx::ShaderModule::new(instance, &fragment_shader(desc.features));
         let pipeline = gfx::RenderPipeline::new(
             &instance,
             &gfx::RenderPipelineDescriptor {
-                label: None,
+                label: desc.label.as_deref(),
                 layout: Some(&pipeline_layout),
                 vertex: gfx::VertexState {
                     module: &vs_module,
@@ -167,9 +206,21 @@ impl RenderPipeline {
             pipeline,
             sample_count: desc.sample_count,
             color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+            features: desc.features,
         }
     }
 
+    pub fn features(&self) -> ShapeFeatures {
+        self.features
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
     pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
         gfx::RenderPassRequirements {
             sample_count: self.sample_count,
@@ -180,34 +231,40 @@ impl RenderPipeline {
 }
 
 pub trait Renderer<'a> {
-    fn draw_shape2(
+    fn draw_shape2<I: gfx::MeshIndexType>(
         &mut self,
         pipeline: &'a RenderPipeline,
-        mesh: &'a Mesh,
+        mesh: &'a Mesh<I>,
         push_constants: &'a PushConstants,
         index_range: MeshIndexRange,
     );
 
-    fn draw_shape2_array<MeshIt, PcIt, RangeIt>(
+    fn draw_shape2_array<I, MeshIt, PcIt, RangeIt>(
         &mut self,
         pipeline: &'a RenderPipeline,
         draw_commands: MeshIt,
     ) where
-        MeshIt: IntoIterator<Item = (&'a Mesh, PcIt)>,
+        I: gfx::MeshIndexType,
+        MeshIt: IntoIterator<Item = (&'a Mesh<I>, PcIt)>,
         PcIt: IntoIterator<Item = (&'a PushConstants, RangeIt)>,
         RangeIt: IntoIterator<Item = gfx::MeshIndexRange>;
 }
 
 impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
-    fn draw_shape2(
+    fn draw_shape2<I: gfx::MeshIndexType>(
         &mut self,
         pipeline: &'a RenderPipeline,
-        mesh: &'a Mesh,
+        mesh: &'a Mesh<I>,
         push_constants: &'a PushConstants,
         index_range: MeshIndexRange,
     ) {
         self.set_pipeline(&pipeline.pipeline);
-        self.set_index_buffer(mesh.index_buffer().slice(..), gfx::IndexFormat::Uint16);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
         self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
         self.set_push_constants(
             gfx::ShaderStage::VERTEX,
@@ -217,18 +274,24 @@ impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
         self.draw_indexed(index_range, 0, 0..1);
     }
 
-    fn draw_shape2_array<MeshIt, PcIt, RangeIt>(
+    fn draw_shape2_array<I, MeshIt, PcIt, RangeIt>(
         &mut self,
         pipeline: &'a RenderPipeline,
         draw_commands: MeshIt,
     ) where
-        MeshIt: IntoIterator<Item = (&'a Mesh, PcIt)>,
+        I: gfx::MeshIndexType,
+        MeshIt: IntoIterator<Item = (&'a Mesh<I>, PcIt)>,
         PcIt: IntoIterator<Item = (&'a PushConstants, RangeIt)>,
         RangeIt: IntoIterator<Item = gfx::MeshIndexRange>,
     {
         self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
         for (mesh, pcs) in draw_commands.into_iter() {
-            self.set_index_buffer(mesh.index_buffer().slice(..), gfx::IndexFormat::Uint16);
+            self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
             self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
             for (pc, ranges) in pcs.into_iter() {
                 self.set_push_constants(gfx::ShaderStage::VERTEX, 0, gfx::utility::as_slice(pc));
@@ -247,6 +310,13 @@ mod tests {
     use gfx::Canvas;
     use roe_math::{Rotation2, Vector2};
 
+    #[test]
+    fn fragment_shader_variants() {
+        for bits in 0..=ShapeFeatures::all().bits() {
+            let _shader = fragment_shader(ShapeFeatures::from_bits(bits).unwrap());
+        }
+    }
+
     #[test]
     #[serial_test::serial]
     fn creation() {
@@ -254,6 +324,24 @@ mod tests {
         let _pipeline = RenderPipeline::new(&instance, &RenderPipelineDescriptor::default());
     }
 
+    #[test]
+    #[serial_test::serial]
+    fn pipeline_cache() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let mut cache = RenderPipelineCache::new();
+        let desc = RenderPipelineDescriptor {
+            features: ShapeFeatures::ALPHA_TEST,
+            ..RenderPipelineDescriptor::default()
+        };
+        let pipeline = cache.get_or_insert_with(&desc, |d| RenderPipeline::new(&instance, d));
+        expect_that!(&pipeline.features(), eq(ShapeFeatures::ALPHA_TEST));
+        cache.get_or_insert_with(&RenderPipelineDescriptor::default(), |d| {
+            RenderPipeline::new(&instance, d)
+        });
+        cache.get_or_insert_with(&desc, |d| RenderPipeline::new(&instance, d));
+        expect_that!(&cache.len(), eq(2));
+    }
+
     #[test]
     #[serial_test::serial]
     fn draw_shape2() {
@@ -261,15 +349,18 @@ mod tests {
         let mut canvas = gfx::CanvasTexture::new(
             &instance,
             &gfx::CanvasTextureDescriptor {
+                label: None,
                 size: gfx::CanvasSize::new(100, 100),
                 sample_count: 1,
                 color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
                     format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
                     usage: gfx::CanvasColorBufferUsage::COPY_SRC,
+                    sample_count: None,
                 }),
                 depth_stencil_buffer_format: None,
             },
-        );
+        )
+        .unwrap();
         let pipeline = RenderPipeline::new(
             &instance,
             &RenderPipelineDescriptor {
@@ -278,7 +369,7 @@ mod tests {
             },
         );
 
-        let mesh = Mesh::new(
+        let mesh: Mesh = Mesh::new(
             &instance,
             &[
                 Vertex::new([-50., 50.]),
@@ -336,20 +427,12 @@ mod tests {
             frame.present();
         }
 
-        let expected_image = image::load(
-            std::io::BufReader::new(std::fs::File::open("data/pictures/test_result.png").unwrap()),
-            image::ImageFormat::Png,
+        let result_image = canvas.color_texture().unwrap().to_image(&instance);
+        gfx::check_golden_image(
+            &result_image,
+            "data/pictures/test_result.png",
+            &gfx::GoldenImageDescriptor::default(),
         )
         .unwrap();
-        assert_that!(
-            &expected_image,
-            is_variant!(image::DynamicImage::ImageRgba8)
-        );
-
-        if let image::DynamicImage::ImageRgba8(expected_image) = expected_image {
-            let result_image = canvas.color_texture().unwrap().to_image(&instance);
-
-            expect_that!(&result_image, eq(expected_image));
-        }
     }
 }
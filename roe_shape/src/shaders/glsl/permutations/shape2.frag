@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#ifndef ALPHA_TEST_THRESHOLD
+#define ALPHA_TEST_THRESHOLD 0.5
+#endif
+
+layout(location = 0) in vec4 inColor;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 80) uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+#ifdef ALPHA_TEST
+    if(inColor.a < ALPHA_TEST_THRESHOLD) {
+        discard;
+    }
+#endif
+    outColor = vec4(convertColor(inColor.rgb, pushConstant.colorConversion), inColor.a);
+}
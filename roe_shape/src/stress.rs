@@ -0,0 +1,189 @@
+use super::{MeshIndex, PushConstants, Vertex};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Rotation2, Vector2};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StressShapeDescriptor {
+    pub count: usize,
+    // The shapes are placed and move inside the rectangle from the origin to the area size.
+    pub area: Vector2<f32>,
+    pub min_radius: f32,
+    pub max_radius: f32,
+    // Each shape is a regular polygon with a random number of sides in the range
+    // [3, max_side_count].
+    pub max_side_count: usize,
+    // In units per second. The shapes don't move if 0.
+    pub max_speed: f32,
+    // In radians per second.
+    pub max_angular_speed: f32,
+    // The same seed always generates the same shapes.
+    pub seed: u64,
+}
+
+impl Default for StressShapeDescriptor {
+    fn default() -> Self {
+        Self {
+            count: 1000,
+            area: Vector2::new(1920., 1080.),
+            min_radius: 4.,
+            max_radius: 32.,
+            max_side_count: 8,
+            max_speed: 100.,
+            max_angular_speed: 1.,
+            seed: 0,
+        }
+    }
+}
+
+// Randomly generated shape used to measure the renderer performance. The geometry is centered on
+// the origin, and placed by the transform.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StressShape {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<MeshIndex>,
+    pub color: gfx::ColorF32,
+    pub position: Vector2<f32>,
+    pub rotation: f32,
+    pub velocity: Vector2<f32>,
+    pub angular_velocity: f32,
+}
+
+impl StressShape {
+    pub fn transform(&self) -> HomogeneousMatrix2<f32> {
+        roe_math::translation2(&self.position)
+            * roe_math::rotation2(&Rotation2::from_angle(self.rotation))
+    }
+
+    pub fn push_constants(&self, projection_transform: &HomogeneousMatrix2<f32>) -> PushConstants {
+        PushConstants::new(&(projection_transform * self.transform()), self.color)
+    }
+
+    // Moves the shape, bouncing on the area borders.
+    pub fn advance(&mut self, dt: f32, area: &Vector2<f32>) {
+        self.position += self.velocity * dt;
+        self.rotation += self.angular_velocity * dt;
+        for i in 0..2 {
+            if self.position[i] < 0. {
+                self.position[i] = -self.position[i];
+                self.velocity[i] = self.velocity[i].abs();
+            } else if self.position[i] > area[i] {
+                self.position[i] = 2. * area[i] - self.position[i];
+                self.velocity[i] = -self.velocity[i].abs();
+            }
+        }
+    }
+}
+
+fn random_range(rng: &mut StdRng, min: f32, max: f32) -> f32 {
+    if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+// Triangle fan around the center.
+fn regular_polygon(radius: f32, side_count: usize) -> (Vec<Vertex>, Vec<MeshIndex>) {
+    let mut vertices = Vec::with_capacity(side_count + 1);
+    vertices.push(Vertex::new([0., 0.]));
+    for i in 0..side_count {
+        let angle = std::f32::consts::TAU * i as f32 / side_count as f32;
+        vertices.push(Vertex::new([radius * angle.cos(), radius * angle.sin()]));
+    }
+    let mut indices = Vec::with_capacity(side_count * 3);
+    for i in 0..side_count {
+        indices.extend([
+            0,
+            (i + 1) as MeshIndex,
+            ((i + 1) % side_count + 1) as MeshIndex,
+        ]);
+    }
+    (vertices, indices)
+}
+
+pub fn generate_stress_shapes(desc: &StressShapeDescriptor) -> Vec<StressShape> {
+    assert!(
+        desc.min_radius > 0. && desc.min_radius <= desc.max_radius,
+        "The minimum radius must be higher than 0 and not higher than the maximum radius"
+    );
+    assert!(
+        desc.max_side_count >= 3,
+        "The maximum side count must be at least 3"
+    );
+    let mut rng = StdRng::seed_from_u64(desc.seed);
+    (0..desc.count)
+        .map(|_| {
+            let radius = random_range(&mut rng, desc.min_radius, desc.max_radius);
+            let side_count = rng.gen_range(3..=desc.max_side_count);
+            let (vertices, indices) = regular_polygon(radius, side_count);
+            StressShape {
+                vertices,
+                indices,
+                color: gfx::ColorF32 {
+                    r: rng.gen(),
+                    g: rng.gen(),
+                    b: rng.gen(),
+                    a: 1.,
+                },
+                position: Vector2::new(
+                    random_range(&mut rng, 0., desc.area.x),
+                    random_range(&mut rng, 0., desc.area.y),
+                ),
+                rotation: random_range(&mut rng, 0., std::f32::consts::TAU),
+                velocity: Vector2::new(
+                    random_range(&mut rng, -desc.max_speed, desc.max_speed),
+                    random_range(&mut rng, -desc.max_speed, desc.max_speed),
+                ),
+                angular_velocity: random_range(
+                    &mut rng,
+                    -desc.max_angular_speed,
+                    desc.max_angular_speed,
+                ),
+            }
+        })
+        .collect()
+}
+
+// Moves all shapes, as a stress test would do every frame.
+pub fn advance_stress_shapes(shapes: &mut [StressShape], dt: f32, area: &Vector2<f32>) {
+    for shape in shapes.iter_mut() {
+        shape.advance(dt, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn generation() {
+        let desc = StressShapeDescriptor {
+            count: 50,
+            max_side_count: 5,
+            ..StressShapeDescriptor::default()
+        };
+        let shapes = generate_stress_shapes(&desc);
+        expect_that!(&shapes.len(), eq(50));
+        expect_that!(&shapes, eq(generate_stress_shapes(&desc)));
+        for s in shapes.iter() {
+            let side_count = s.vertices.len() - 1;
+            expect_that!((3..=5).contains(&side_count));
+            expect_that!(&s.indices.len(), eq(side_count * 3));
+            expect_that!(s.indices.iter().all(|i| (*i as usize) <= side_count));
+            expect_that!(s.position.x >= 0. && s.position.x <= desc.area.x);
+        }
+    }
+
+    #[test]
+    fn polygon() {
+        let (vertices, indices) = regular_polygon(2., 4);
+        expect_that!(&vertices.len(), eq(5));
+        let p = vertices[2].position;
+        expect_that!(&p[0], close_to(0., 1e-6));
+        expect_that!(&p[1], close_to(2., 1e-6));
+        expect_that!(&indices, eq(vec![0, 1, 2, 0, 2, 3, 0, 3, 4, 0, 4, 1]));
+    }
+}
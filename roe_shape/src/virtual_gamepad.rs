@@ -0,0 +1,232 @@
+use super::{Mesh, MeshIndex, PushConstants, RenderPipeline, Renderer, Vertex};
+
+use roe_app as app;
+
+use roe_graphics as gfx;
+
+use roe_math::{HomogeneousMatrix2, Vector2};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VirtualGamepadStyle {
+    pub joystick_color: gfx::ColorF32,
+    pub knob_color: gfx::ColorF32,
+    // Radius of the joystick knob, as a fraction of the joystick radius.
+    pub knob_radius: f32,
+    pub button_color: gfx::ColorF32,
+    pub pressed_button_color: gfx::ColorF32,
+}
+
+impl Default for VirtualGamepadStyle {
+    fn default() -> Self {
+        Self {
+            joystick_color: gfx::ColorF32 {
+                r: 1.,
+                g: 1.,
+                b: 1.,
+                a: 0.25,
+            },
+            knob_color: gfx::ColorF32 {
+                r: 1.,
+                g: 1.,
+                b: 1.,
+                a: 0.5,
+            },
+            knob_radius: 0.4,
+            button_color: gfx::ColorF32 {
+                r: 1.,
+                g: 1.,
+                b: 1.,
+                a: 0.25,
+            },
+            pressed_button_color: gfx::ColorF32 {
+                r: 1.,
+                g: 1.,
+                b: 1.,
+                a: 0.5,
+            },
+        }
+    }
+}
+
+// Draws the widgets of a virtual gamepad as circles, on top of the scene. The pipeline should
+// have alpha blending enabled for the default style.
+#[derive(Debug)]
+pub struct VirtualGamepadOverlay {
+    circle_mesh: Mesh,
+    style: VirtualGamepadStyle,
+    push_constants: Vec<PushConstants>,
+}
+
+impl VirtualGamepadOverlay {
+    const CIRCLE_SEGMENT_COUNT: MeshIndex = 32;
+
+    pub fn new(instance: &gfx::Instance, style: VirtualGamepadStyle) -> Self {
+        let mut vertices = vec![Vertex::new([0., 0.])];
+        let mut indices = Vec::new();
+        for i in 0..Self::CIRCLE_SEGMENT_COUNT {
+            let angle = std::f32::consts::PI * 2. * i as f32 / Self::CIRCLE_SEGMENT_COUNT as f32;
+            vertices.push(Vertex::new([angle.cos(), angle.sin()]));
+            indices.extend([0, i + 1, (i + 1) % Self::CIRCLE_SEGMENT_COUNT + 1]);
+        }
+        Self {
+            circle_mesh: Mesh::new(instance, &vertices, &indices),
+            style,
+            push_constants: Vec::new(),
+        }
+    }
+
+    pub fn style(&self) -> &VirtualGamepadStyle {
+        &self.style
+    }
+
+    pub fn set_style(&mut self, style: VirtualGamepadStyle) {
+        self.style = style;
+    }
+
+    // Updates the widgets from the gamepad state. The projection transform maps window
+    // coordinates, in physical pixels, to clip space.
+    pub fn update(
+        &mut self,
+        gamepad: &app::VirtualGamepad,
+        projection_transform: &HomogeneousMatrix2<f32>,
+    ) {
+        self.push_constants = widget_push_constants(gamepad, &self.style, projection_transform);
+    }
+}
+
+fn circle_push_constants(
+    projection_transform: &HomogeneousMatrix2<f32>,
+    center: (f64, f64),
+    radius: f32,
+    color: gfx::ColorF32,
+) -> PushConstants {
+    PushConstants::new(
+        &(projection_transform
+            * roe_math::translation2(&Vector2::new(center.0 as f32, center.1 as f32))
+            * roe_math::scale2(&Vector2::new(radius, radius))),
+        color,
+    )
+}
+
+fn widget_push_constants(
+    gamepad: &app::VirtualGamepad,
+    style: &VirtualGamepadStyle,
+    projection_transform: &HomogeneousMatrix2<f32>,
+) -> Vec<PushConstants> {
+    let mut push_constants = Vec::new();
+    for joystick in gamepad.joysticks() {
+        let desc = joystick.descriptor();
+        let radius = desc.radius as f32;
+        let knob_position = joystick.knob_position();
+        push_constants.push(circle_push_constants(
+            projection_transform,
+            (desc.center.x, desc.center.y),
+            radius,
+            style.joystick_color,
+        ));
+        push_constants.push(circle_push_constants(
+            projection_transform,
+            (knob_position.x, knob_position.y),
+            radius * style.knob_radius,
+            style.knob_color,
+        ));
+    }
+    for button in gamepad.buttons() {
+        let desc = button.descriptor();
+        let color = if button.is_pressed() {
+            style.pressed_button_color
+        } else {
+            style.button_color
+        };
+        push_constants.push(circle_push_constants(
+            projection_transform,
+            (desc.center.x, desc.center.y),
+            desc.radius as f32,
+            color,
+        ));
+    }
+    push_constants
+}
+
+pub trait VirtualGamepadRenderer<'a> {
+    // Draws the widgets as of the last VirtualGamepadOverlay::update call.
+    fn draw_virtual_gamepad(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        overlay: &'a VirtualGamepadOverlay,
+    );
+}
+
+impl<'a> VirtualGamepadRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_virtual_gamepad(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        overlay: &'a VirtualGamepadOverlay,
+    ) {
+        let index_range = 0..overlay.circle_mesh.index_count();
+        self.draw_shape2_array(
+            pipeline,
+            [(
+                &overlay.circle_mesh,
+                overlay
+                    .push_constants
+                    .iter()
+                    .map(|pc| (pc, [index_range.clone()])),
+            )],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use roe_os as os;
+
+    #[test]
+    fn widget_push_constants() {
+        let mut gamepad = app::VirtualGamepad::new();
+        gamepad.add_joystick(app::VirtualJoystickDescriptor {
+            center: os::PhysicalPosition::new(100., 100.),
+            radius: 50.,
+            dead_zone: 0.,
+            axes: None,
+        });
+        gamepad.add_button(app::VirtualButtonDescriptor {
+            center: os::PhysicalPosition::new(300., 100.),
+            radius: 20.,
+            key_code: None,
+        });
+        gamepad.on_touch(
+            os::TouchPhase::Started,
+            os::PhysicalPosition::new(120., 100.),
+            0,
+        );
+        gamepad.on_touch(
+            os::TouchPhase::Started,
+            os::PhysicalPosition::new(300., 100.),
+            1,
+        );
+
+        let style = VirtualGamepadStyle::default();
+        let projection_transform = roe_math::ortographic_projection2(0., 400., 200., 0.);
+        expect_that!(
+            &super::widget_push_constants(&gamepad, &style, &projection_transform),
+            eq(vec![
+                circle_push_constants(
+                    &projection_transform,
+                    (100., 100.),
+                    50.,
+                    style.joystick_color
+                ),
+                circle_push_constants(&projection_transform, (120., 100.), 20., style.knob_color),
+                circle_push_constants(
+                    &projection_transform,
+                    (300., 100.),
+                    20.,
+                    style.pressed_button_color
+                ),
+            ])
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use roe_sprite::{
+    advance_stress_sprites, generate_stress_sprites, ChunkedSpriteBatch,
+    ChunkedSpriteBatchDescriptor, StaticSpriteBatch, StressSprite, StressSpriteDescriptor,
+};
+
+const SPRITE_COUNTS: [usize; 3] = [1000, 10000, 100000];
+const TEXTURE_COUNT: usize = 16;
+const FRAME_TIME: f32 = 1. / 60.;
+
+fn sprites(count: usize) -> Vec<StressSprite> {
+    generate_stress_sprites(&StressSpriteDescriptor {
+        count,
+        texture_count: TEXTURE_COUNT,
+        ..StressSpriteDescriptor::default()
+    })
+}
+
+// Draw order grouping the sprites by texture, keeping the submission order within a texture.
+fn draw_order(sprites: &[StressSprite]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by_key(|i| sprites[*i].texture_index);
+    order
+}
+
+// One batch per texture, as drawn with one draw call each.
+fn build_batches(sprites: &[StressSprite], order: &[usize], batches: &mut [StaticSpriteBatch]) {
+    for batch in batches.iter_mut() {
+        batch.clear();
+    }
+    for i in order {
+        let sprite = &sprites[*i];
+        batches[sprite.texture_index].push(&sprite.to_static_sprite());
+    }
+}
+
+fn sorting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_by_texture");
+    for count in SPRITE_COUNTS {
+        let sprites = sprites(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &sprites, |b, s| {
+            b.iter(|| draw_order(s))
+        });
+    }
+    group.finish();
+}
+
+fn batching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("batching");
+    for count in SPRITE_COUNTS {
+        let sprites = sprites(count);
+        let order = draw_order(&sprites);
+        let mut batches = vec![StaticSpriteBatch::new(); TEXTURE_COUNT];
+        group.bench_with_input(BenchmarkId::from_parameter(count), &sprites, |b, s| {
+            b.iter(|| build_batches(s, &order, &mut batches))
+        });
+    }
+    group.finish();
+}
+
+fn mesh_update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunked_mesh_update");
+    for count in SPRITE_COUNTS {
+        let mut sprites = sprites(count);
+        let mut batch = ChunkedSpriteBatch::new(&ChunkedSpriteBatchDescriptor::default());
+        let ids: Vec<_> = sprites
+            .iter()
+            .map(|s| batch.insert(s.to_static_sprite()))
+            .collect();
+        batch.update_batches();
+        let area = StressSpriteDescriptor::default().area;
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter(|| {
+                advance_stress_sprites(&mut sprites, FRAME_TIME, &area);
+                for (id, s) in ids.iter().zip(sprites.iter()) {
+                    batch.set_sprite(*id, s.to_static_sprite());
+                }
+                batch.update_batches();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn particles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("particle_batching");
+    for count in SPRITE_COUNTS {
+        let desc = StressSpriteDescriptor::particles(count);
+        let particles = generate_stress_sprites(&desc);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &particles,
+            |b, particles| {
+                b.iter_batched(
+                    || particles.clone(),
+                    |mut particles| {
+                        advance_stress_sprites(&mut particles, FRAME_TIME, &desc.area);
+                        let mut batch = StaticSpriteBatch::new();
+                        for p in particles.iter() {
+                            batch.push(&p.to_static_sprite());
+                        }
+                        batch
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sorting, batching, mesh_update, particles);
+criterion_main!(benches);
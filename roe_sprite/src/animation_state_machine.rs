@@ -0,0 +1,698 @@
+use super::{AnimationClip, Skeleton, SkeletonPose};
+
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+#[derive(Debug)]
+pub enum AnimationStateMachineError {
+    IoError(std::io::Error),
+    RonError(ron::Error),
+    UnknownState(String),
+    UnknownClip(String),
+    // State name.
+    InvalidBlend(String),
+}
+
+impl std::fmt::Display for AnimationStateMachineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::RonError(e) => write!(f, "RON error ({})", e),
+            Self::UnknownState(state) => write!(f, "Unknown state ({})", state),
+            Self::UnknownClip(clip) => write!(f, "Unknown clip ({})", clip),
+            Self::InvalidBlend(state) => write!(
+                f,
+                "Blends need at least one clip with increasing thresholds ({})",
+                state
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AnimationStateMachineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::RonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AnimationStateMachineError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<ron::Error> for AnimationStateMachineError {
+    fn from(e: ron::Error) -> Self {
+        Self::RonError(e)
+    }
+}
+
+// Game values driving the transitions and blends, e.g. the speed of a character or whether the
+// jump input action was pressed this frame.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AnimationParameter {
+    Bool(bool),
+    Float(f32),
+    // Set for a single transition, then reset.
+    Trigger(bool),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AnimationParameters {
+    values: HashMap<String, AnimationParameter>,
+}
+
+impl AnimationParameters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.values
+            .insert(String::from(name), AnimationParameter::Bool(value));
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.values
+            .insert(String::from(name), AnimationParameter::Float(value));
+    }
+
+    pub fn set_trigger(&mut self, name: &str) {
+        self.values
+            .insert(String::from(name), AnimationParameter::Trigger(true));
+    }
+
+    pub fn get(&self, name: &str) -> Option<AnimationParameter> {
+        self.values.get(name).copied()
+    }
+
+    // Booleans and triggers count as 0 or 1, missing parameters as 0.
+    pub fn float(&self, name: &str) -> f32 {
+        match self.get(name) {
+            Some(AnimationParameter::Float(value)) => value,
+            Some(AnimationParameter::Bool(value)) | Some(AnimationParameter::Trigger(value)) => {
+                value as u32 as f32
+            }
+            None => 0.,
+        }
+    }
+
+    fn reset_trigger(&mut self, name: &str) {
+        if let Some(AnimationParameter::Trigger(value)) = self.values.get_mut(name) {
+            *value = false;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum AnimationConditionTest {
+    True,
+    False,
+    Greater(f32),
+    Less(f32),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AnimationCondition {
+    pub parameter: String,
+    pub test: AnimationConditionTest,
+}
+
+impl AnimationCondition {
+    pub fn is_met(&self, parameters: &AnimationParameters) -> bool {
+        let value = parameters.float(&self.parameter);
+        match self.test {
+            AnimationConditionTest::True => value != 0.,
+            AnimationConditionTest::False => value == 0.,
+            AnimationConditionTest::Greater(threshold) => value > threshold,
+            AnimationConditionTest::Less(threshold) => value < threshold,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum AnimationMotion {
+    Clip(String),
+    // Clips blended by a float parameter, e.g. idle, walk and run by speed. Each clip has a
+    // threshold, increasing along the list. The clips are synchronized by their phase.
+    Blend1D {
+        parameter: String,
+        clips: Vec<(f32, String)>,
+    },
+}
+
+impl AnimationMotion {
+    fn clips(&self) -> Vec<&str> {
+        match self {
+            Self::Clip(clip) => vec![clip.as_str()],
+            Self::Blend1D { clips, .. } => clips.iter().map(|(_, c)| c.as_str()).collect(),
+        }
+    }
+
+    fn weights(&self, parameters: &AnimationParameters) -> Vec<f32> {
+        match self {
+            Self::Clip(_) => vec![1.],
+            Self::Blend1D { parameter, clips } => {
+                let value = parameters.float(parameter);
+                let mut weights = vec![0.; clips.len()];
+                let next = clips.partition_point(|(threshold, _)| *threshold <= value);
+                if next == 0 {
+                    weights[0] = 1.;
+                } else if next == clips.len() {
+                    weights[next - 1] = 1.;
+                } else {
+                    let (low, high) = (clips[next - 1].0, clips[next].0);
+                    let t = (value - low) / (high - low);
+                    weights[next - 1] = 1. - t;
+                    weights[next] = t;
+                }
+                weights
+            }
+        }
+    }
+}
+
+fn default_speed() -> f32 {
+    1.
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+// Fired when playback crosses the phase, 0 being the start and 1 the end of the state motion.
+// For frame animations, the phase of frame i out of n is i / n.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AnimationEventDescriptor {
+    pub phase: f32,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AnimationStateDescriptor {
+    pub name: String,
+    pub motion: AnimationMotion,
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+    #[serde(default)]
+    pub events: Vec<AnimationEventDescriptor>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AnimationTransitionDescriptor {
+    // None for transitions from any other state.
+    #[serde(default)]
+    pub from: Option<String>,
+    pub to: String,
+    // All conditions must be met. Triggers used by the conditions are reset when the transition
+    // is taken.
+    #[serde(default)]
+    pub conditions: Vec<AnimationCondition>,
+    // Phase of the source state the transition waits for, e.g. 1 to finish an attack.
+    #[serde(default)]
+    pub exit_phase: Option<f32>,
+    // In seconds. The source state keeps playing while fading out.
+    #[serde(default)]
+    pub crossfade: f32,
+}
+
+// Transitions are checked in order, the first one whose conditions are met is taken.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AnimationStateMachineDescriptor {
+    pub initial_state: String,
+    pub states: Vec<AnimationStateDescriptor>,
+    #[serde(default)]
+    pub transitions: Vec<AnimationTransitionDescriptor>,
+}
+
+impl AnimationStateMachineDescriptor {
+    pub fn from_ron_str(s: &str) -> Result<Self, AnimationStateMachineError> {
+        Ok(ron::de::from_str(s)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, AnimationStateMachineError> {
+        Self::from_ron_str(&std::fs::read_to_string(path)?)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FiredAnimationEvent {
+    pub state: String,
+    pub name: String,
+}
+
+// Clip to display, with its weight in the current blend.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnimationSample<'a> {
+    pub clip: &'a str,
+    // In seconds.
+    pub time: f32,
+    pub weight: f32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct StatePlayback {
+    state: usize,
+    phase: f32,
+    weights: Vec<f32>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Crossfade {
+    from: StatePlayback,
+    elapsed: f32,
+    duration: f32,
+}
+
+struct Transition {
+    from: Option<usize>,
+    to: usize,
+    conditions: Vec<AnimationCondition>,
+    exit_phase: Option<f32>,
+    crossfade: f32,
+}
+
+impl std::fmt::Debug for Transition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Transition {{ from: {:?}, to: {} }}", self.from, self.to)
+    }
+}
+
+// Plays the states of a descriptor, switching between them when the transition conditions are
+// met. The output is a set of weighted clip samples, applied to skeletons with skeleton_pose or
+// to frame animations by showing the dominant sample.
+#[derive(Debug)]
+pub struct AnimationStateMachine {
+    states: Vec<AnimationStateDescriptor>,
+    transitions: Vec<Transition>,
+    clip_durations: HashMap<String, f32>,
+    current: StatePlayback,
+    crossfade: Option<Crossfade>,
+}
+
+impl AnimationStateMachine {
+    // The durations of the clips used by the states, in seconds.
+    pub fn new(
+        desc: &AnimationStateMachineDescriptor,
+        clip_durations: &HashMap<String, f32>,
+    ) -> Result<Self, AnimationStateMachineError> {
+        let state_index = |name: &str| {
+            desc.states
+                .iter()
+                .position(|s| s.name == name)
+                .ok_or_else(|| AnimationStateMachineError::UnknownState(String::from(name)))
+        };
+        let mut durations = HashMap::new();
+        for state in desc.states.iter() {
+            if let AnimationMotion::Blend1D { clips, .. } = &state.motion {
+                if clips.is_empty() || clips.windows(2).any(|c| c[0].0 >= c[1].0) {
+                    return Err(AnimationStateMachineError::InvalidBlend(state.name.clone()));
+                }
+            }
+            for clip in state.motion.clips() {
+                let duration = clip_durations
+                    .get(clip)
+                    .ok_or_else(|| AnimationStateMachineError::UnknownClip(String::from(clip)))?;
+                durations.insert(String::from(clip), *duration);
+            }
+        }
+        let transitions = desc
+            .transitions
+            .iter()
+            .map(|t| {
+                Ok(Transition {
+                    from: t.from.as_deref().map(state_index).transpose()?,
+                    to: state_index(&t.to)?,
+                    conditions: t.conditions.clone(),
+                    exit_phase: t.exit_phase,
+                    crossfade: t.crossfade,
+                })
+            })
+            .collect::<Result<Vec<_>, AnimationStateMachineError>>()?;
+        let initial = state_index(&desc.initial_state)?;
+        let weights = desc.states[initial]
+            .motion
+            .weights(&AnimationParameters::new());
+        Ok(Self {
+            states: desc.states.clone(),
+            transitions,
+            clip_durations: durations,
+            current: StatePlayback {
+                state: initial,
+                phase: 0.,
+                weights,
+            },
+            crossfade: None,
+        })
+    }
+
+    pub fn current_state(&self) -> &str {
+        &self.states[self.current.state].name
+    }
+
+    // Phase of the current state, in [0, 1).
+    pub fn phase(&self) -> f32 {
+        self.current.phase
+    }
+
+    pub fn is_crossfading(&self) -> bool {
+        self.crossfade.is_some()
+    }
+
+    // Starts a state immediately, e.g. when respawning.
+    pub fn play(&mut self, state: &str) -> Result<(), AnimationStateMachineError> {
+        let index = self
+            .states
+            .iter()
+            .position(|s| s.name == state)
+            .ok_or_else(|| AnimationStateMachineError::UnknownState(String::from(state)))?;
+        self.start_state(index, 0.);
+        Ok(())
+    }
+
+    // Advances the playback, then takes the first transition whose conditions are met. Returns
+    // the events of the current state crossed during the update.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        parameters: &mut AnimationParameters,
+    ) -> Vec<FiredAnimationEvent> {
+        let dt = dt.as_secs_f32();
+        let mut events = Vec::new();
+        let mut current = self.current.clone();
+        self.advance(&mut current, dt, parameters, Some(&mut events));
+        self.current = current;
+        if let Some(mut crossfade) = self.crossfade.take() {
+            self.advance(&mut crossfade.from, dt, parameters, None);
+            crossfade.elapsed += dt;
+            if crossfade.elapsed < crossfade.duration {
+                self.crossfade = Some(crossfade);
+            }
+        }
+
+        let taken = self.transitions.iter().position(|t| {
+            t.from.map_or(t.to != self.current.state, |from| {
+                from == self.current.state
+            }) && t.exit_phase.is_none_or(|phase| self.current.phase >= phase)
+                && t.conditions.iter().all(|c| c.is_met(parameters))
+        });
+        if let Some(taken) = taken {
+            for condition in self.transitions[taken].conditions.iter() {
+                parameters.reset_trigger(&condition.parameter);
+            }
+            let (to, crossfade) = (
+                self.transitions[taken].to,
+                self.transitions[taken].crossfade,
+            );
+            self.start_state(to, crossfade);
+            self.current.weights = self.states[to].motion.weights(parameters);
+        }
+        events
+    }
+
+    // Weighted samples of the current state and, while crossfading, of the previous one.
+    // Weights add up to 1.
+    pub fn samples(&self) -> Vec<AnimationSample<'_>> {
+        let mut samples = Vec::new();
+        let fade = self
+            .crossfade
+            .as_ref()
+            .map_or(1., |c| (c.elapsed / c.duration).clamp(0., 1.));
+        self.push_samples(&self.current, fade, &mut samples);
+        if let Some(crossfade) = &self.crossfade {
+            self.push_samples(&crossfade.from, 1. - fade, &mut samples);
+        }
+        samples
+    }
+
+    // Sample with the highest weight, for animations that can't be blended such as frame
+    // animations.
+    pub fn dominant_sample(&self) -> AnimationSample<'_> {
+        self.samples()
+            .into_iter()
+            .fold(None, |best: Option<AnimationSample>, sample| match best {
+                Some(best) if best.weight >= sample.weight => Some(best),
+                _ => Some(sample),
+            })
+            .unwrap()
+    }
+
+    // Blends the poses of the samples. Clips are looked up by name.
+    pub fn skeleton_pose(&self, skeleton: &Skeleton, clips: &[AnimationClip]) -> SkeletonPose {
+        let mut pose = skeleton.setup_pose();
+        let mut total_weight = 0.;
+        for sample in self.samples() {
+            if sample.weight <= 0. {
+                continue;
+            }
+            if let Some(clip) = clips.iter().find(|c| c.name == sample.clip) {
+                let sample_pose = clip.sample(skeleton, sample.time);
+                total_weight += sample.weight;
+                pose = pose.blend(&sample_pose, sample.weight / total_weight);
+            }
+        }
+        pose
+    }
+
+    fn start_state(&mut self, state: usize, crossfade: f32) {
+        let previous = std::mem::replace(
+            &mut self.current,
+            StatePlayback {
+                state,
+                phase: 0.,
+                weights: self.states[state]
+                    .motion
+                    .weights(&AnimationParameters::new()),
+            },
+        );
+        self.crossfade = if crossfade > 0. {
+            Some(Crossfade {
+                from: previous,
+                elapsed: 0.,
+                duration: crossfade,
+            })
+        } else {
+            None
+        };
+    }
+
+    fn push_samples<'a>(
+        &'a self,
+        playback: &StatePlayback,
+        weight: f32,
+        samples: &mut Vec<AnimationSample<'a>>,
+    ) {
+        let motion = &self.states[playback.state].motion;
+        for (clip, clip_weight) in motion.clips().into_iter().zip(playback.weights.iter()) {
+            let (clip, duration) = self.clip_durations.get_key_value(clip).unwrap();
+            samples.push(AnimationSample {
+                clip,
+                time: playback.phase * duration,
+                weight: weight * clip_weight,
+            });
+        }
+    }
+
+    fn advance(
+        &self,
+        playback: &mut StatePlayback,
+        dt: f32,
+        parameters: &AnimationParameters,
+        events: Option<&mut Vec<FiredAnimationEvent>>,
+    ) {
+        let state = &self.states[playback.state];
+        playback.weights = state.motion.weights(parameters);
+        let duration: f32 = state
+            .motion
+            .clips()
+            .into_iter()
+            .zip(playback.weights.iter())
+            .map(|(clip, weight)| self.clip_durations[clip] * weight)
+            .sum();
+        let start = playback.phase;
+        let mut end = if duration > 0. {
+            start + dt * state.speed / duration
+        } else {
+            1.
+        };
+        if !state.looping {
+            end = end.min(1.);
+        }
+        if let Some(events) = events {
+            for event in state.events.iter() {
+                let crossed = if state.looping {
+                    (end - event.phase).floor() > (start - event.phase).floor()
+                } else {
+                    start < event.phase && event.phase <= end
+                };
+                if crossed {
+                    events.push(FiredAnimationEvent {
+                        state: state.name.clone(),
+                        name: event.name.clone(),
+                    });
+                }
+            }
+        }
+        playback.phase = if state.looping {
+            end.rem_euclid(1.)
+        } else {
+            end
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const MACHINE: &str = r#"(
+        initial_state: "locomotion",
+        states: [
+            (
+                name: "locomotion",
+                motion: Blend1D(parameter: "speed", clips: [(0, "idle"), (1, "walk"), (3, "run")]),
+                events: [(phase: 0.5, name: "footstep")],
+            ),
+            (name: "attack", motion: Clip("attack"), looping: false),
+            (name: "hurt", motion: Clip("hurt"), looping: false),
+        ],
+        transitions: [
+            (
+                from: Some("locomotion"),
+                to: "attack",
+                conditions: [(parameter: "attack", test: True)],
+                crossfade: 0.5,
+            ),
+            (from: Some("attack"), to: "locomotion", exit_phase: Some(1)),
+            (to: "hurt", conditions: [(parameter: "health", test: Less(10))]),
+        ],
+    )"#;
+
+    fn machine() -> AnimationStateMachine {
+        let durations = [
+            ("idle", 2.),
+            ("walk", 1.),
+            ("run", 0.5),
+            ("attack", 1.),
+            ("hurt", 1.),
+        ]
+        .iter()
+        .map(|(name, duration)| (String::from(*name), *duration))
+        .collect();
+        AnimationStateMachine::new(
+            &AnimationStateMachineDescriptor::from_ron_str(MACHINE).unwrap(),
+            &durations,
+        )
+        .unwrap()
+    }
+
+    fn parameters() -> AnimationParameters {
+        let mut parameters = AnimationParameters::new();
+        parameters.set_float("health", 100.);
+        parameters
+    }
+
+    #[test]
+    fn blend_weights() {
+        let mut machine = machine();
+        let mut parameters = parameters();
+        parameters.set_float("speed", 2.);
+        machine.update(Duration::ZERO, &mut parameters);
+        let samples = machine.samples();
+        let weights: Vec<_> = samples.iter().map(|s| (s.clip, s.weight)).collect();
+        expect_that!(
+            &weights,
+            eq(vec![("idle", 0.), ("walk", 0.5), ("run", 0.5)])
+        );
+        expect_that!(&machine.dominant_sample().clip, eq("walk"));
+
+        // The blended duration is 0.75 seconds.
+        machine.update(Duration::from_secs_f32(0.375), &mut parameters);
+        expect_that!((machine.phase() - 0.5).abs() < 1e-5);
+        expect_that!((machine.samples()[2].time - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn footstep_events() {
+        let mut machine = machine();
+        let mut parameters = parameters();
+        parameters.set_float("speed", 1.);
+        let events = machine.update(Duration::from_secs_f32(0.4), &mut parameters);
+        expect_that!(events.is_empty());
+        let events = machine.update(Duration::from_secs_f32(0.2), &mut parameters);
+        expect_that!(
+            &events,
+            eq(vec![FiredAnimationEvent {
+                state: String::from("locomotion"),
+                name: String::from("footstep"),
+            }])
+        );
+        // Wrapping around the loop.
+        let events = machine.update(Duration::from_secs_f32(0.8), &mut parameters);
+        expect_that!(events.is_empty());
+        let events = machine.update(Duration::from_secs_f32(0.2), &mut parameters);
+        expect_that!(&events.len(), eq(1));
+    }
+
+    #[test]
+    fn triggered_transition_with_crossfade() {
+        let mut machine = machine();
+        let mut parameters = parameters();
+        parameters.set_trigger("attack");
+        machine.update(Duration::from_secs_f32(0.1), &mut parameters);
+        expect_that!(&machine.current_state(), eq("attack"));
+        expect_that!(
+            &parameters.get("attack"),
+            eq(Some(AnimationParameter::Trigger(false)))
+        );
+        expect_that!(machine.is_crossfading());
+
+        machine.update(Duration::from_secs_f32(0.25), &mut parameters);
+        let samples = machine.samples();
+        expect_that!(&samples[0].clip, eq("attack"));
+        expect_that!((samples[0].weight - 0.5).abs() < 1e-5);
+        let total: f32 = samples.iter().map(|s| s.weight).sum();
+        expect_that!((total - 1.).abs() < 1e-5);
+
+        machine.update(Duration::from_secs_f32(0.5), &mut parameters);
+        expect_that!(!machine.is_crossfading());
+        expect_that!(&machine.current_state(), eq("attack"));
+        // Back to locomotion once the attack finishes.
+        machine.update(Duration::from_secs_f32(0.5), &mut parameters);
+        expect_that!(&machine.current_state(), eq("locomotion"));
+    }
+
+    #[test]
+    fn any_state_transition() {
+        let mut machine = machine();
+        let mut parameters = parameters();
+        parameters.set_float("health", 5.);
+        machine.update(Duration::from_secs_f32(0.1), &mut parameters);
+        expect_that!(&machine.current_state(), eq("hurt"));
+        // Transitions from any state don't restart their target.
+        machine.update(Duration::from_secs_f32(0.1), &mut parameters);
+        expect_that!((machine.phase() - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn invalid_descriptors() {
+        let durations = HashMap::new();
+        let desc = AnimationStateMachineDescriptor::from_ron_str(MACHINE).unwrap();
+        expect_that!(matches!(
+            AnimationStateMachine::new(&desc, &durations),
+            Err(AnimationStateMachineError::UnknownClip(_))
+        ));
+        expect_that!(
+            &AnimationStateMachineDescriptor::from_ron_str("(states: [])"),
+            is_variant!(Result::Err)
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use super::{Skeleton, SkeletonPose};
+
+use roe_math::{HomogeneousMatrix2, Vector2, Vector3};
+
+use std::f32::consts::PI;
+
+// The solvers work in skeleton space and change the local rotations of the bones only. They
+// assume bones without negative or non-uniform scale. The weight blends the solution with the
+// pose passed in, usually the authored animation, 0 keeping the pose and 1 reaching the target.
+
+// Rotates a bone so that one of its local axes points at a target, e.g. a head or a gun.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LookAtIk {
+    pub bone: usize,
+    // Local direction pointing at the target.
+    pub forward: Vector2<f32>,
+    pub weight: f32,
+}
+
+impl LookAtIk {
+    pub fn new(bone: usize) -> Self {
+        Self {
+            bone,
+            forward: Vector2::new(1., 0.),
+            weight: 1.,
+        }
+    }
+
+    pub fn solve(&self, skeleton: &Skeleton, pose: &mut SkeletonPose, target: &Vector2<f32>) {
+        let world = skeleton.world_transforms(pose);
+        let origin = position(&world[self.bone]);
+        self.solve_direction(skeleton, pose, &(target - origin));
+    }
+
+    // Aligns the forward axis with a direction, e.g. a foot with the tangent of a slope.
+    pub fn solve_direction(
+        &self,
+        skeleton: &Skeleton,
+        pose: &mut SkeletonPose,
+        direction: &Vector2<f32>,
+    ) {
+        if direction.norm_squared() == 0. {
+            return;
+        }
+        let world = skeleton.world_transforms(pose);
+        let forward = transform_vector(&world[self.bone], &self.forward);
+        let delta = angle_between(&forward, direction);
+        pose.locals[self.bone].rotation += delta * self.weight;
+    }
+}
+
+// Analytic solver for a chain of two bones, e.g. arms and legs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TwoBoneIk {
+    pub upper: usize,
+    // Must be a child of the upper bone.
+    pub lower: usize,
+    // End of the chain in the lower bone space, e.g. the wrist or ankle.
+    pub end: Vector2<f32>,
+    // Whether the lower bone bends with a positive rotation relative to the upper one,
+    // clockwise on screen. Picks which of the two solutions is used.
+    pub bend_positive: bool,
+    pub weight: f32,
+}
+
+impl TwoBoneIk {
+    // Targets out of reach are approached with the chain fully extended.
+    pub fn solve(&self, skeleton: &Skeleton, pose: &mut SkeletonPose, target: &Vector2<f32>) {
+        assert!(
+            skeleton.bones()[self.lower].parent == Some(self.upper),
+            "The lower bone must be a child of the upper bone"
+        );
+        let authored = pose.clone();
+
+        let world = skeleton.world_transforms(pose);
+        let a = position(&world[self.upper]);
+        let b = position(&world[self.lower]);
+        let c = transform_point(&world[self.lower], &self.end);
+        let (l1, l2) = ((b - a).norm(), (c - b).norm());
+        let to_target = target - a;
+        if l1 > 0. && to_target.norm_squared() > 0. {
+            let min_distance = (l1 - l2).abs() + 1e-4;
+            let max_distance = l1 + l2;
+            let d = to_target
+                .norm()
+                .clamp(min_distance, max_distance.max(min_distance));
+            let cos_alpha = ((l1 * l1 + d * d - l2 * l2) / (2. * l1 * d)).clamp(-1., 1.);
+            let alpha = cos_alpha.acos();
+            let sign = if self.bend_positive { -1. } else { 1. };
+            let upper_direction = rotate(&to_target, sign * alpha);
+            pose.locals[self.upper].rotation += angle_between(&(b - a), &upper_direction);
+        }
+
+        let world = skeleton.world_transforms(pose);
+        let b = position(&world[self.lower]);
+        let c = transform_point(&world[self.lower], &self.end);
+        pose.locals[self.lower].rotation += angle_between(&(c - b), &(target - b));
+
+        blend_rotations(pose, &authored, &[self.upper, self.lower], self.weight);
+    }
+}
+
+// Iterative solver for chains of any length, e.g. tails and tentacles.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FabrikIk {
+    // From the root to the tip, each bone being the parent of the next.
+    pub chain: Vec<usize>,
+    // End of the chain in the tip bone space.
+    pub end: Vector2<f32>,
+    pub iterations: u32,
+    // Distance from the target at which the solver stops.
+    pub tolerance: f32,
+    pub weight: f32,
+}
+
+impl FabrikIk {
+    pub fn new(chain: Vec<usize>, end: Vector2<f32>) -> Self {
+        Self {
+            chain,
+            end,
+            iterations: 10,
+            tolerance: 0.01,
+            weight: 1.,
+        }
+    }
+
+    pub fn solve(&self, skeleton: &Skeleton, pose: &mut SkeletonPose, target: &Vector2<f32>) {
+        assert!(!self.chain.is_empty(), "The IK chain is empty");
+        for pair in self.chain.windows(2) {
+            assert!(
+                skeleton.bones()[pair[1]].parent == Some(pair[0]),
+                "Each bone of the IK chain must be the parent of the next"
+            );
+        }
+        let authored = pose.clone();
+
+        let world = skeleton.world_transforms(pose);
+        let tip = *self.chain.last().unwrap();
+        let mut points: Vec<Vector2<f32>> =
+            self.chain.iter().map(|b| position(&world[*b])).collect();
+        points.push(transform_point(&world[tip], &self.end));
+        let lengths: Vec<f32> = points.windows(2).map(|p| (p[1] - p[0]).norm()).collect();
+        let root = points[0];
+        let total_length: f32 = lengths.iter().sum();
+
+        if (target - root).norm() >= total_length {
+            // Out of reach: stretch towards the target.
+            let direction = (target - root).normalize();
+            for i in 0..lengths.len() {
+                points[i + 1] = points[i] + direction * lengths[i];
+            }
+        } else {
+            let last = points.len() - 1;
+            for _ in 0..self.iterations {
+                if (points[last] - target).norm() <= self.tolerance {
+                    break;
+                }
+                points[last] = *target;
+                for i in (0..last).rev() {
+                    points[i] = constrain(&points[i + 1], &points[i], lengths[i]);
+                }
+                points[0] = root;
+                for i in 0..last {
+                    points[i + 1] = constrain(&points[i], &points[i + 1], lengths[i]);
+                }
+            }
+        }
+
+        // Converts the points back to rotations, from the root down since each rotation moves
+        // the children.
+        for (i, bone) in self.chain.iter().enumerate() {
+            let world = skeleton.world_transforms(pose);
+            let start = position(&world[*bone]);
+            let current_end = match self.chain.get(i + 1) {
+                Some(next) => position(&world[*next]),
+                None => transform_point(&world[*bone], &self.end),
+            };
+            pose.locals[*bone].rotation +=
+                angle_between(&(current_end - start), &(points[i + 1] - start));
+        }
+
+        blend_rotations(pose, &authored, &self.chain, self.weight);
+    }
+}
+
+// Point at the given distance from the anchor, towards the point.
+fn constrain(anchor: &Vector2<f32>, point: &Vector2<f32>, distance: f32) -> Vector2<f32> {
+    let direction = point - anchor;
+    let length = direction.norm();
+    if length > 0. {
+        anchor + direction * (distance / length)
+    } else {
+        *anchor
+    }
+}
+
+fn blend_rotations(pose: &mut SkeletonPose, authored: &SkeletonPose, bones: &[usize], weight: f32) {
+    for bone in bones {
+        let from = authored.locals[*bone].rotation;
+        let delta = wrap_angle(pose.locals[*bone].rotation - from);
+        pose.locals[*bone].rotation = from + delta * weight.clamp(0., 1.);
+    }
+}
+
+// Signed angle rotating a onto b, in [-pi, pi].
+fn angle_between(a: &Vector2<f32>, b: &Vector2<f32>) -> f32 {
+    wrap_angle(b.y.atan2(b.x) - a.y.atan2(a.x))
+}
+
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2. * PI) - PI
+}
+
+fn rotate(v: &Vector2<f32>, angle: f32) -> Vector2<f32> {
+    let (sin, cos) = angle.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+fn position(matrix: &HomogeneousMatrix2<f32>) -> Vector2<f32> {
+    Vector2::new(matrix[(0, 2)], matrix[(1, 2)])
+}
+
+fn transform_point(matrix: &HomogeneousMatrix2<f32>, point: &Vector2<f32>) -> Vector2<f32> {
+    let p = matrix * Vector3::new(point.x, point.y, 1.);
+    Vector2::new(p.x, p.y)
+}
+
+fn transform_vector(matrix: &HomogeneousMatrix2<f32>, vector: &Vector2<f32>) -> Vector2<f32> {
+    let v = matrix * Vector3::new(vector.x, vector.y, 0.);
+    Vector2::new(v.x, v.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bone, BoneTransform};
+    use galvanic_assert::{matchers::*, *};
+
+    fn approx_eq(a: &Vector2<f32>, b: &Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-2
+    }
+
+    // A chain of bones of length 10 along the x axis.
+    fn chain(count: usize) -> Skeleton {
+        Skeleton::new(
+            (0..count)
+                .map(|i| Bone {
+                    name: format!("bone_{}", i),
+                    parent: if i == 0 { None } else { Some(i - 1) },
+                    setup: BoneTransform {
+                        translation: Vector2::new(if i == 0 { 0. } else { 10. }, 0.),
+                        ..BoneTransform::default()
+                    },
+                })
+                .collect(),
+        )
+    }
+
+    fn end_position(skeleton: &Skeleton, pose: &SkeletonPose) -> Vector2<f32> {
+        let world = skeleton.world_transforms(pose);
+        transform_point(world.last().unwrap(), &Vector2::new(10., 0.))
+    }
+
+    #[test]
+    fn look_at() {
+        let skeleton = chain(2);
+        let mut pose = skeleton.setup_pose();
+        LookAtIk::new(1).solve(&skeleton, &mut pose, &Vector2::new(10., 20.));
+        expect_that!(approx_eq(
+            &end_position(&skeleton, &pose),
+            &Vector2::new(10., 10.)
+        ));
+
+        // Half weight turns half way.
+        let mut pose = skeleton.setup_pose();
+        let look_at = LookAtIk {
+            weight: 0.5,
+            ..LookAtIk::new(1)
+        };
+        look_at.solve(&skeleton, &mut pose, &Vector2::new(10., 20.));
+        expect_that!((pose.locals[1].rotation - PI / 4.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn two_bone() {
+        let skeleton = chain(2);
+        let target = Vector2::new(10., 10.);
+        for bend_positive in [true, false] {
+            let mut pose = skeleton.setup_pose();
+            let ik = TwoBoneIk {
+                upper: 0,
+                lower: 1,
+                end: Vector2::new(10., 0.),
+                bend_positive,
+                weight: 1.,
+            };
+            ik.solve(&skeleton, &mut pose, &target);
+            expect_that!(approx_eq(&end_position(&skeleton, &pose), &target));
+            expect_that!(&(pose.locals[1].rotation > 0.), eq(bend_positive));
+        }
+
+        // Out of reach.
+        let mut pose = skeleton.setup_pose();
+        let ik = TwoBoneIk {
+            upper: 0,
+            lower: 1,
+            end: Vector2::new(10., 0.),
+            bend_positive: true,
+            weight: 1.,
+        };
+        ik.solve(&skeleton, &mut pose, &Vector2::new(0., 100.));
+        expect_that!(approx_eq(
+            &end_position(&skeleton, &pose),
+            &Vector2::new(0., 20.)
+        ));
+    }
+
+    #[test]
+    fn fabrik() {
+        let skeleton = chain(4);
+        let mut pose = skeleton.setup_pose();
+        let target = Vector2::new(15., 20.);
+        FabrikIk::new(vec![0, 1, 2, 3], Vector2::new(10., 0.)).solve(&skeleton, &mut pose, &target);
+        expect_that!(approx_eq(&end_position(&skeleton, &pose), &target));
+        // Bone lengths are kept.
+        let world = skeleton.world_transforms(&pose);
+        expect_that!(((position(&world[2]) - position(&world[1])).norm() - 10.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn foot_placement_on_slope() {
+        // Hip, knee and foot.
+        let skeleton = chain(3);
+        let mut pose = skeleton.setup_pose();
+        let ground = Vector2::new(8., 12.);
+        let leg = TwoBoneIk {
+            upper: 0,
+            lower: 1,
+            end: Vector2::new(10., 0.),
+            bend_positive: false,
+            weight: 1.,
+        };
+        leg.solve(&skeleton, &mut pose, &ground);
+        // Slope going up to the right.
+        let tangent = Vector2::new(1., -0.5);
+        LookAtIk::new(2).solve_direction(&skeleton, &mut pose, &tangent);
+
+        let world = skeleton.world_transforms(&pose);
+        expect_that!(approx_eq(&position(&world[2]), &ground));
+        let foot = transform_vector(&world[2], &Vector2::new(1., 0.));
+        expect_that!(angle_between(&foot, &tangent).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,374 @@
+use super::{DrawQueue, DrawQueueItem, RenderPipeline, SpriteDrawCommand};
+
+use roe_graphics as gfx;
+use roe_math::HomogeneousMatrix2;
+
+use std::collections::BTreeMap;
+
+// Set of draw queue layers, one bit per layer. Only layers in the 0..64 range can be selected.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct LayerMask(u64);
+
+impl LayerMask {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self(!0);
+
+    pub fn layer(layer: i32) -> Self {
+        Self::NONE.with(layer)
+    }
+
+    pub fn range(layers: std::ops::Range<i32>) -> Self {
+        layers.fold(Self::NONE, Self::with)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub fn with(self, layer: i32) -> Self {
+        Self(self.0 | Self::bit(layer))
+    }
+
+    pub fn without(self, layer: i32) -> Self {
+        Self(self.0 & !Self::bit(layer))
+    }
+
+    pub fn contains(&self, layer: i32) -> bool {
+        (0..64).contains(&layer) && self.0 & (1 << layer) != 0
+    }
+
+    fn bit(layer: i32) -> u64 {
+        assert!(
+            (0..64).contains(&layer),
+            "Layer masks only support layers in the 0..64 range"
+        );
+        1 << layer
+    }
+}
+
+impl Default for LayerMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl std::ops::BitOr for LayerMask {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+// Where a camera renders. Texture targets are identified by an application defined key, the
+// application begins the render pass on the matching texture.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Default)]
+pub enum CameraTarget {
+    #[default]
+    Surface,
+    Texture(u32),
+}
+
+// Region of the target, as fractions of its size, with the origin at the top left corner.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self {
+        x: 0.,
+        y: 0.,
+        width: 1.,
+        height: 1.,
+    };
+
+    // Region in pixels for a target of the given size, as x, y, width and height.
+    pub fn to_pixels(&self, target_size: &gfx::Size<u32>) -> [f32; 4] {
+        let (w, h) = (target_size.width() as f32, target_size.height() as f32);
+        [
+            (self.x * w).round(),
+            (self.y * h).round(),
+            (self.width * w).round(),
+            (self.height * h).round(),
+        ]
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Camera {
+    // Applied after the transform of each drawn item.
+    pub view_projection: HomogeneousMatrix2<f32>,
+    pub layers: LayerMask,
+    pub target: CameraTarget,
+    pub viewport: Viewport,
+    // Cameras sharing a target are drawn from the lowest order, e.g. the UI camera after the
+    // world camera.
+    pub order: i32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            view_projection: HomogeneousMatrix2::identity(),
+            layers: LayerMask::default(),
+            target: CameraTarget::default(),
+            viewport: Viewport::default(),
+            order: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct CameraId(u64);
+
+// Cameras of a scene, e.g. one per player for split-screen and one for the UI.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CameraSet {
+    cameras: BTreeMap<CameraId, Camera>,
+    next_id: u64,
+}
+
+impl CameraSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, camera: Camera) -> CameraId {
+        let id = CameraId(self.next_id);
+        self.next_id += 1;
+        self.cameras.insert(id, camera);
+        id
+    }
+
+    pub fn remove(&mut self, id: CameraId) -> Option<Camera> {
+        self.cameras.remove(&id)
+    }
+
+    pub fn get(&self, id: CameraId) -> Option<&Camera> {
+        self.cameras.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: CameraId) -> Option<&mut Camera> {
+        self.cameras.get_mut(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cameras.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cameras.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (CameraId, &Camera)> {
+        self.cameras.iter().map(|(id, camera)| (*id, camera))
+    }
+
+    // Targets used by at least one camera, each needing its own render pass.
+    pub fn targets(&self) -> Vec<CameraTarget> {
+        let mut targets: Vec<_> = self.cameras.values().map(|c| c.target).collect();
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    // Cameras rendering to the target, in drawing order. Ties keep the insertion order.
+    pub fn target_cameras(&self, target: CameraTarget) -> Vec<(CameraId, &Camera)> {
+        let mut cameras: Vec<_> = self.iter().filter(|(_, c)| c.target == target).collect();
+        cameras.sort_by_key(|(_, c)| c.order);
+        cameras
+    }
+}
+
+impl<T> DrawQueue<T> {
+    // Sorted items in the layers seen by the camera.
+    pub fn camera_items<'b>(
+        &'b self,
+        camera: &'b Camera,
+    ) -> impl Iterator<Item = &'b DrawQueueItem<T>> + 'b {
+        self.items()
+            .filter(move |item| camera.layers.contains(item.layer))
+    }
+}
+
+pub trait CameraRenderer<'a> {
+    // Draws the queue items seen by the camera inside its viewport. The render pass must target
+    // the camera target, whose size is given, and the item push constants must only contain the
+    // item transforms. The viewport is left set after the call. The queue must be sorted first.
+    fn draw_sprite_queue_for_camera<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        queue: &'a DrawQueue<SpriteDrawCommand<'a, I>>,
+        camera: &Camera,
+        target_size: &gfx::Size<u32>,
+    );
+}
+
+impl<'a> CameraRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_sprite_queue_for_camera<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        queue: &'a DrawQueue<SpriteDrawCommand<'a, I>>,
+        camera: &Camera,
+        target_size: &gfx::Size<u32>,
+    ) {
+        let [x, y, width, height] = camera.viewport.to_pixels(target_size);
+        if width <= 0. || height <= 0. {
+            return;
+        }
+        self.set_viewport(x, y, width, height, 0., 1.);
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            super::PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        for item in queue.camera_items(camera) {
+            let command = &item.value;
+            let push_constants = command
+                .push_constants
+                .with_view_projection(&camera.view_projection);
+            self.set_bind_group(0, &command.uniform_constants.bind_group, &[]);
+            self.set_index_buffer(
+                command.mesh.index_buffer().slice(..),
+                command.mesh.index_format(),
+            );
+            self.set_vertex_buffer(0, command.mesh.vertex_buffer().slice(..));
+            self.set_push_constants(
+                gfx::ShaderStage::VERTEX,
+                0,
+                gfx::utility::as_slice(&push_constants),
+            );
+            self.draw_indexed(command.index_range.clone(), 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PushConstants;
+    use galvanic_assert::{matchers::*, *};
+
+    use roe_math::Vector2;
+
+    #[test]
+    fn layer_mask() {
+        let mask = LayerMask::layer(0) | LayerMask::layer(3);
+        expect_that!(mask.contains(0));
+        expect_that!(mask.contains(3));
+        expect_that!(!mask.contains(1));
+        expect_that!(!mask.contains(-1));
+        expect_that!(!mask.without(3).contains(3));
+        expect_that!(LayerMask::ALL.contains(63));
+        expect_that!(!LayerMask::ALL.contains(64));
+        expect_that!(!LayerMask::NONE.contains(0));
+        expect_that!(&LayerMask::range(2..5).bits(), eq(0b11100));
+    }
+
+    #[test]
+    #[should_panic(expected = "Layer masks only support layers in the 0..64 range")]
+    fn layer_mask_out_of_range() {
+        let _ = LayerMask::layer(64);
+    }
+
+    #[test]
+    fn viewport_pixels() {
+        let left = Viewport {
+            width: 0.5,
+            ..Viewport::FULL
+        };
+        let right = Viewport { x: 0.5, ..left };
+        let size = gfx::Size::new(801, 600);
+        expect_that!(&left.to_pixels(&size), eq([0., 0., 401., 600.]));
+        expect_that!(&right.to_pixels(&size), eq([401., 0., 401., 600.]));
+    }
+
+    #[test]
+    fn target_cameras() {
+        let mut cameras = CameraSet::new();
+        let ui = cameras.insert(Camera {
+            layers: LayerMask::layer(2),
+            order: 1,
+            ..Camera::default()
+        });
+        let player_1 = cameras.insert(Camera::default());
+        let minimap = cameras.insert(Camera {
+            target: CameraTarget::Texture(0),
+            ..Camera::default()
+        });
+        let player_2 = cameras.insert(Camera::default());
+        expect_that!(
+            &cameras.targets(),
+            eq(vec![CameraTarget::Surface, CameraTarget::Texture(0)])
+        );
+        let ids = |target| {
+            cameras
+                .target_cameras(target)
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>()
+        };
+        expect_that!(
+            &ids(CameraTarget::Surface),
+            eq(vec![player_1, player_2, ui])
+        );
+        expect_that!(&ids(CameraTarget::Texture(0)), eq(vec![minimap]));
+
+        cameras.remove(minimap);
+        expect_that!(&cameras.targets(), eq(vec![CameraTarget::Surface]));
+        expect_that!(&cameras.len(), eq(3));
+    }
+
+    #[test]
+    fn camera_items() {
+        let mut queue = DrawQueue::new(crate::DrawSortMode::Submission);
+        queue.push(2, Vector2::zeros(), "hud");
+        queue.push(0, Vector2::zeros(), "ground");
+        queue.push(1, Vector2::zeros(), "player");
+        queue.sort();
+        let world = Camera {
+            layers: LayerMask::layer(0) | LayerMask::layer(1),
+            ..Camera::default()
+        };
+        let ui = Camera {
+            layers: LayerMask::layer(2),
+            ..Camera::default()
+        };
+        let values = |camera| {
+            queue
+                .camera_items(camera)
+                .map(|item| item.value)
+                .collect::<Vec<_>>()
+        };
+        expect_that!(&values(&world), eq(vec!["ground", "player"]));
+        expect_that!(&values(&ui), eq(vec!["hud"]));
+    }
+
+    #[test]
+    fn view_projection() {
+        let camera = Camera {
+            view_projection: roe_math::scale2(&Vector2::new(2., 2.)),
+            ..Camera::default()
+        };
+        let model = roe_math::translation2(&Vector2::new(1., 3.));
+        expect_that!(
+            &PushConstants::new(&model, gfx::ColorF32::WHITE)
+                .with_view_projection(&camera.view_projection),
+            eq(PushConstants::new(
+                &(camera.view_projection * model),
+                gfx::ColorF32::WHITE
+            ))
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use super::{Mesh, StaticSprite, StaticSpriteBatch};
+
+use std::collections::{BTreeSet, HashMap};
+
+use roe_graphics as gfx;
+use roe_math::{Aabb2, Vector2, Vector3};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChunkedSpriteBatchDescriptor {
+    // Size of the square regions sprites are grouped into, in the sprite coordinate space.
+    pub chunk_size: Vector2<f32>,
+}
+
+impl Default for ChunkedSpriteBatchDescriptor {
+    fn default() -> Self {
+        Self {
+            chunk_size: Vector2::new(512., 512.),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub struct ChunkedSpriteId(usize);
+
+pub type ChunkKey = (i32, i32);
+
+// Group of sprites merged into a single mesh.
+#[derive(Debug)]
+pub struct SpriteChunk {
+    key: ChunkKey,
+    sprites: BTreeSet<usize>,
+    batch: StaticSpriteBatch,
+    bounds: Option<Aabb2<f32>>,
+    mesh: Option<Mesh<u32>>,
+    dirty: bool,
+}
+
+impl SpriteChunk {
+    fn new(key: ChunkKey) -> Self {
+        Self {
+            key,
+            sprites: BTreeSet::new(),
+            batch: StaticSpriteBatch::new(),
+            bounds: None,
+            mesh: None,
+            dirty: true,
+        }
+    }
+
+    pub fn key(&self) -> ChunkKey {
+        self.key
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    // Bounds of the transformed sprites, which can exceed the chunk region.
+    pub fn bounds(&self) -> Option<&Aabb2<f32>> {
+        self.bounds.as_ref()
+    }
+
+    pub fn batch(&self) -> &StaticSpriteBatch {
+        &self.batch
+    }
+
+    // Available after update.
+    pub fn mesh(&self) -> Option<&Mesh<u32>> {
+        self.mesh.as_ref()
+    }
+}
+
+fn sprite_center(sprite: &StaticSprite) -> Vector2<f32> {
+    let p = sprite.transform * Vector3::new(sprite.size.x * 0.5, sprite.size.y * 0.5, 1.);
+    Vector2::new(p.x / p.z, p.y / p.z)
+}
+
+fn chunk_key(position: &Vector2<f32>, chunk_size: &Vector2<f32>) -> ChunkKey {
+    (
+        (position.x / chunk_size.x).floor() as i32,
+        (position.y / chunk_size.y).floor() as i32,
+    )
+}
+
+// Splits static sprites (e.g. tilemap tiles) into spatial chunks, each baked into its own mesh
+// with its own bounds, so that offscreen chunks can be skipped when drawing. Sprites are
+// assigned to a chunk based on their center. Changing a sprite only rebuilds the chunks
+// involved.
+#[derive(Debug)]
+pub struct ChunkedSpriteBatch {
+    chunk_size: Vector2<f32>,
+    sprites: Vec<Option<(StaticSprite, ChunkKey)>>,
+    free_ids: Vec<usize>,
+    chunks: HashMap<ChunkKey, SpriteChunk>,
+}
+
+impl ChunkedSpriteBatch {
+    pub fn new(desc: &ChunkedSpriteBatchDescriptor) -> Self {
+        Self::check_chunk_size(&desc.chunk_size);
+        Self {
+            chunk_size: desc.chunk_size,
+            sprites: Vec::new(),
+            free_ids: Vec::new(),
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, sprite: StaticSprite) -> ChunkedSpriteId {
+        let id = match self.free_ids.pop() {
+            Some(id) => id,
+            None => {
+                self.sprites.push(None);
+                self.sprites.len() - 1
+            }
+        };
+        let key = self.add_to_chunk(&sprite, id);
+        self.sprites[id] = Some((sprite, key));
+        ChunkedSpriteId(id)
+    }
+
+    pub fn remove(&mut self, id: ChunkedSpriteId) -> Option<StaticSprite> {
+        let (sprite, key) = self.sprites.get_mut(id.0)?.take()?;
+        self.remove_from_chunk(id.0, key);
+        self.free_ids.push(id.0);
+        Some(sprite)
+    }
+
+    pub fn sprite(&self, id: ChunkedSpriteId) -> Option<&StaticSprite> {
+        self.sprites
+            .get(id.0)
+            .and_then(|entry| entry.as_ref().map(|(sprite, _)| sprite))
+    }
+
+    pub fn set_sprite(&mut self, id: ChunkedSpriteId, sprite: StaticSprite) {
+        let old_key = match self.sprites.get(id.0) {
+            Some(Some((_, key))) => *key,
+            _ => panic!("Invalid sprite id"),
+        };
+        self.remove_from_chunk(id.0, old_key);
+        let key = self.add_to_chunk(&sprite, id.0);
+        self.sprites[id.0] = Some((sprite, key));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len() - self.free_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+        self.free_ids.clear();
+        self.chunks.clear();
+    }
+
+    pub fn chunk_size(&self) -> &Vector2<f32> {
+        &self.chunk_size
+    }
+
+    // Reassigns all sprites to the new chunks.
+    pub fn set_chunk_size(&mut self, chunk_size: Vector2<f32>) {
+        Self::check_chunk_size(&chunk_size);
+        self.chunk_size = chunk_size;
+        self.chunks.clear();
+        for id in 0..self.sprites.len() {
+            if let Some((sprite, _)) = self.sprites[id].take() {
+                let key = self.add_to_chunk(&sprite, id);
+                self.sprites[id] = Some((sprite, key));
+            }
+        }
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn chunk(&self, key: ChunkKey) -> Option<&SpriteChunk> {
+        self.chunks.get(&key)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &SpriteChunk> {
+        self.chunks.values()
+    }
+
+    // Chunks whose bounds intersect the given region, typically the area seen by the camera.
+    // Only the chunks updated since their last change are returned.
+    pub fn visible_chunks<'a>(
+        &'a self,
+        view: &'a Aabb2<f32>,
+    ) -> impl Iterator<Item = &'a SpriteChunk> {
+        self.chunks.values().filter(move |chunk| {
+            chunk
+                .bounds
+                .as_ref()
+                .is_some_and(|bounds| bounds.intersects(view))
+        })
+    }
+
+    // Rebuilds the vertex data and bounds of the changed chunks, without touching their meshes.
+    pub fn update_batches(&mut self) {
+        for chunk in self.chunks.values_mut().filter(|chunk| chunk.dirty) {
+            chunk.batch.clear();
+            for id in chunk.sprites.iter() {
+                let (sprite, _) = self.sprites[*id].as_ref().unwrap();
+                chunk.batch.push(sprite);
+            }
+            let positions: Vec<Vector2<f32>> = chunk
+                .batch
+                .vertices()
+                .iter()
+                .map(|v| {
+                    let p = v.position;
+                    Vector2::new(p[0], p[1])
+                })
+                .collect();
+            chunk.bounds = Aabb2::from_points(positions.iter());
+            chunk.mesh = None;
+            chunk.dirty = false;
+        }
+    }
+
+    // Rebuilds the changed chunks, including their meshes.
+    pub fn update(&mut self, instance: &gfx::Instance) {
+        self.update_batches();
+        for chunk in self.chunks.values_mut() {
+            if chunk.mesh.is_none() {
+                chunk.mesh = Some(chunk.batch.build(instance));
+            }
+        }
+    }
+
+    fn check_chunk_size(chunk_size: &Vector2<f32>) {
+        assert!(
+            chunk_size.x > 0. && chunk_size.y > 0.,
+            "The chunk size must be higher than 0"
+        );
+    }
+
+    fn add_to_chunk(&mut self, sprite: &StaticSprite, id: usize) -> ChunkKey {
+        let key = chunk_key(&sprite_center(sprite), &self.chunk_size);
+        let chunk = self
+            .chunks
+            .entry(key)
+            .or_insert_with(|| SpriteChunk::new(key));
+        chunk.sprites.insert(id);
+        chunk.bounds = None;
+        chunk.dirty = true;
+        key
+    }
+
+    fn remove_from_chunk(&mut self, id: usize, key: ChunkKey) {
+        let chunk = self.chunks.get_mut(&key).unwrap();
+        chunk.sprites.remove(&id);
+        chunk.bounds = None;
+        chunk.dirty = true;
+        if chunk.is_empty() {
+            self.chunks.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn tile(x: f32, y: f32) -> StaticSprite {
+        StaticSprite::new(
+            roe_math::translation2(&Vector2::new(x, y)),
+            Vector2::new(10., 10.),
+        )
+    }
+
+    fn bounds(x0: f32, y0: f32, x1: f32, y1: f32) -> Aabb2<f32> {
+        Aabb2::new(Vector2::new(x0, y0), Vector2::new(x1, y1))
+    }
+
+    #[test]
+    fn chunking() {
+        let mut batch = ChunkedSpriteBatch::new(&ChunkedSpriteBatchDescriptor {
+            chunk_size: Vector2::new(100., 100.),
+        });
+        let a = batch.insert(tile(0., 0.));
+        batch.insert(tile(80., 20.));
+        let c = batch.insert(tile(150., 0.));
+        batch.insert(tile(-20., -20.));
+        expect_that!(&batch.len(), eq(4));
+        expect_that!(&batch.chunk_count(), eq(3));
+
+        batch.update_batches();
+        let chunk = batch.chunk((0, 0)).unwrap();
+        expect_that!(&chunk.len(), eq(2));
+        expect_that!(&chunk.batch().len(), eq(2));
+        expect_that!(&chunk.bounds().cloned(), eq(Some(bounds(0., 0., 90., 30.))));
+        expect_that!(
+            &batch.chunk((-1, -1)).unwrap().bounds().cloned(),
+            eq(Some(bounds(-20., -20., -10., -10.)))
+        );
+
+        let mut visible: Vec<ChunkKey> = batch
+            .visible_chunks(&bounds(50., -50., 140., 50.))
+            .map(|chunk| chunk.key())
+            .collect();
+        visible.sort();
+        expect_that!(&visible, eq(vec![(0, 0)]));
+
+        // Changed chunks are rebuilt, emptied chunks are discarded.
+        batch.set_sprite(c, tile(40., 40.));
+        expect_that!(&batch.chunk_count(), eq(2));
+        expect_that!(
+            &batch.visible_chunks(&bounds(0., 0., 10., 10.)).count(),
+            eq(0)
+        );
+        batch.update_batches();
+        expect_that!(
+            &batch.chunk((0, 0)).unwrap().bounds().cloned(),
+            eq(Some(bounds(0., 0., 90., 50.)))
+        );
+
+        expect_that!(&batch.remove(a), eq(Some(tile(0., 0.))));
+        expect_that!(&batch.remove(a), eq(None));
+        expect_that!(&batch.len(), eq(3));
+        expect_that!(&batch.insert(tile(300., 300.)), eq(a));
+    }
+
+    #[test]
+    fn rechunking() {
+        let mut batch = ChunkedSpriteBatch::new(&ChunkedSpriteBatchDescriptor {
+            chunk_size: Vector2::new(100., 100.),
+        });
+        for i in 0..10 {
+            batch.insert(tile(i as f32 * 50., 0.));
+        }
+        expect_that!(&batch.chunk_count(), eq(5));
+        batch.set_chunk_size(Vector2::new(1000., 1000.));
+        expect_that!(&batch.chunk_count(), eq(1));
+        batch.update_batches();
+        expect_that!(&batch.chunk((0, 0)).unwrap().batch().len(), eq(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "The chunk size must be higher than 0")]
+    fn invalid_chunk_size() {
+        ChunkedSpriteBatch::new(&ChunkedSpriteBatchDescriptor {
+            chunk_size: Vector2::new(0., 100.),
+        });
+    }
+}
@@ -0,0 +1,436 @@
+use super::{
+    Mesh, MeshTemplates, PushConstants, RenderPipeline, Renderer, UniformConstants, Vertex,
+};
+
+use gfx::Canvas;
+use roe_graphics as gfx;
+use roe_math::{Rotation2, Vector2};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+// Identifier of a decal texture, registered in the decal layer.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct DecalTextureId(pub u32);
+
+// Sprite stamped into a decal layer, e.g. a bullet hole or a footprint.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Decal {
+    pub texture: DecalTextureId,
+    // Center of the decal, in layer pixels.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    pub rotation: f32,
+    pub color: gfx::ColorF32,
+    // The decal is removed once its lifetime elapses. Permanent decals are only removed when
+    // evicted.
+    pub lifetime: Option<Duration>,
+    // The decal fades out during the end of its lifetime.
+    pub fade_duration: Duration,
+}
+
+impl Default for Decal {
+    fn default() -> Self {
+        Self {
+            texture: DecalTextureId(0),
+            position: Vector2::zeros(),
+            size: Vector2::new(16., 16.),
+            rotation: 0.,
+            color: gfx::ColorF32::WHITE,
+            lifetime: None,
+            fade_duration: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct LiveDecal {
+    decal: Decal,
+    age: Duration,
+    // Quantized opacity, from 0 to the fade step count.
+    opacity_step: u32,
+}
+
+impl LiveDecal {
+    fn is_expired(&self) -> bool {
+        self.decal
+            .lifetime
+            .is_some_and(|lifetime| self.age >= lifetime)
+    }
+
+    fn opacity_step(&self, fade_step_count: u32) -> u32 {
+        let remaining = match self.decal.lifetime {
+            Some(lifetime) => lifetime.saturating_sub(self.age),
+            None => return fade_step_count,
+        };
+        if remaining >= self.decal.fade_duration || self.decal.fade_duration.is_zero() {
+            fade_step_count
+        } else {
+            let opacity = remaining.as_secs_f32() / self.decal.fade_duration.as_secs_f32();
+            (opacity * fade_step_count as f32).ceil() as u32
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DecalListDescriptor {
+    // When exceeded, the oldest decals are evicted.
+    pub max_decal_count: usize,
+    // Number of opacity levels used while fading. Each change of level requires redrawing the
+    // whole layer, so fewer steps mean fewer redraws.
+    pub fade_step_count: u32,
+}
+
+impl Default for DecalListDescriptor {
+    fn default() -> Self {
+        Self {
+            max_decal_count: 1024,
+            fade_step_count: 4,
+        }
+    }
+}
+
+// Tracks the decals of a decal layer and what needs to be drawn. New decals are drawn on top
+// of the existing content. Removing a decal or changing its opacity requires a full redraw.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DecalList {
+    desc: DecalListDescriptor,
+    // Oldest first.
+    decals: VecDeque<LiveDecal>,
+    drawn_count: usize,
+    redraw: bool,
+}
+
+impl DecalList {
+    pub fn new(desc: &DecalListDescriptor) -> Self {
+        assert!(
+            desc.max_decal_count > 0,
+            "The maximum decal count must be higher than 0"
+        );
+        assert!(
+            desc.fade_step_count > 0,
+            "The fade step count must be higher than 0"
+        );
+        Self {
+            desc: desc.clone(),
+            decals: VecDeque::new(),
+            drawn_count: 0,
+            redraw: false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.decals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decals.is_empty()
+    }
+
+    // Evicts the oldest decal if the budget is exceeded.
+    pub fn stamp(&mut self, decal: Decal) {
+        let mut live = LiveDecal {
+            decal,
+            age: Duration::ZERO,
+            opacity_step: 0,
+        };
+        live.opacity_step = live.opacity_step(self.desc.fade_step_count);
+        self.decals.push_back(live);
+        if self.decals.len() > self.desc.max_decal_count {
+            self.decals.pop_front();
+            self.drawn_count = self.drawn_count.saturating_sub(1);
+            self.redraw = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+        self.drawn_count = 0;
+        self.redraw = true;
+    }
+
+    // Ages the decals, removing the expired ones.
+    pub fn update(&mut self, dt: Duration) {
+        let fade_step_count = self.desc.fade_step_count;
+        let mut changed = false;
+        for live in self.decals.iter_mut() {
+            live.age += dt;
+            let opacity_step = live.opacity_step(fade_step_count);
+            changed |= opacity_step != live.opacity_step;
+            live.opacity_step = opacity_step;
+        }
+        let count = self.decals.len();
+        self.decals.retain(|live| !live.is_expired());
+        changed |= self.decals.len() != count;
+        if changed {
+            self.redraw = true;
+        }
+    }
+
+    // Whether the layer must be cleared and all decals drawn again.
+    pub fn needs_redraw(&self) -> bool {
+        self.redraw
+    }
+
+    // Decals to draw, with their opacity: all of them if a redraw is needed, otherwise the
+    // ones stamped since the last draw.
+    pub fn pending_decals(&self) -> impl Iterator<Item = (&Decal, f32)> {
+        let first = if self.redraw { 0 } else { self.drawn_count };
+        let fade_step_count = self.desc.fade_step_count as f32;
+        self.decals
+            .iter()
+            .skip(first)
+            .map(move |live| (&live.decal, live.opacity_step as f32 / fade_step_count))
+    }
+
+    pub fn mark_drawn(&mut self) {
+        self.drawn_count = self.decals.len();
+        self.redraw = false;
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DecalLayerDescriptor {
+    pub size: gfx::Size<u32>,
+    pub color_buffer_format: gfx::CanvasColorBufferFormat,
+    pub decal_list_descriptor: DecalListDescriptor,
+}
+
+impl Default for DecalLayerDescriptor {
+    fn default() -> Self {
+        Self {
+            size: gfx::Size::new(1024, 1024),
+            color_buffer_format: gfx::CanvasColorBufferFormat::default(),
+            decal_list_descriptor: DecalListDescriptor::default(),
+        }
+    }
+}
+
+// Persistent texture decals are stamped into. Decals are drawn once when stamped instead of
+// every frame, and the layer is drawn as a single sprite through its texture view. The layer
+// is only redrawn from scratch when decals fade, expire or are evicted.
+#[derive(Debug)]
+pub struct DecalLayer {
+    canvas: gfx::CanvasTexture,
+    quad: Mesh,
+    textures: HashMap<DecalTextureId, UniformConstants>,
+    decals: DecalList,
+    last_draw_count: usize,
+}
+
+impl DecalLayer {
+    pub fn new(
+        instance: &gfx::Instance,
+        desc: &DecalLayerDescriptor,
+    ) -> Result<Self, gfx::CanvasBufferError> {
+        let canvas = gfx::CanvasTexture::new(
+            instance,
+            &gfx::CanvasTextureDescriptor {
+                label: Some(String::from("decal_layer")),
+                size: desc.size,
+                sample_count: 1,
+                color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
+                    format: desc.color_buffer_format,
+                    usage: gfx::CanvasColorBufferUsage::TEXTURE_BINDING
+                        | gfx::CanvasColorBufferUsage::COPY_SRC,
+                    sample_count: None,
+                }),
+                depth_stencil_buffer_format: None,
+            },
+        )?;
+        let quad = Mesh::quad(
+            instance,
+            &Vertex::new([-0.5, -0.5], [0., 0.]),
+            &Vertex::new([0.5, 0.5], [1., 1.]),
+        );
+        Ok(Self {
+            canvas,
+            quad,
+            textures: HashMap::new(),
+            decals: DecalList::new(&desc.decal_list_descriptor),
+            last_draw_count: 0,
+        })
+    }
+
+    pub fn insert_texture(&mut self, id: DecalTextureId, texture: UniformConstants) {
+        self.textures.insert(id, texture);
+    }
+
+    pub fn decals(&self) -> &DecalList {
+        &self.decals
+    }
+
+    pub fn stamp(&mut self, decal: Decal) {
+        self.decals.stamp(decal);
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.decals.update(dt);
+    }
+
+    // Texture containing the decals, to be drawn over the scene.
+    pub fn texture_view(&self) -> &gfx::TextureView {
+        self.canvas.color_texture_view().unwrap()
+    }
+
+    // Number of decals drawn by the last call to render.
+    pub fn last_draw_count(&self) -> usize {
+        self.last_draw_count
+    }
+
+    // Draws the pending decals into the layer texture. Decals with unknown textures are
+    // skipped.
+    pub fn render(
+        &mut self,
+        instance: &gfx::Instance,
+        pipeline: &RenderPipeline,
+    ) -> Result<(), gfx::SurfaceError> {
+        self.last_draw_count = 0;
+        let redraw = self.decals.needs_redraw();
+        if !redraw && self.decals.pending_decals().next().is_none() {
+            return Ok(());
+        }
+        let size = *self.canvas.canvas_size();
+        let projection =
+            roe_math::ortographic_projection2(0., size.width() as f32, size.height() as f32, 0.);
+        let draws: Vec<_> = self
+            .decals
+            .pending_decals()
+            .filter_map(|(decal, opacity)| {
+                let texture = self.textures.get(&decal.texture)?;
+                let mut color = decal.color;
+                color.a *= opacity;
+                let transform = projection
+                    * roe_math::translation2(&decal.position)
+                    * roe_math::rotation2(&Rotation2::new(decal.rotation))
+                    * roe_math::scale2(&decal.size);
+                Some((texture, PushConstants::new(&transform, color)))
+            })
+            .collect();
+
+        let frame = match self.canvas.current_frame()? {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        let load = if redraw {
+            gfx::LoadOp::Clear(gfx::ColorF64::TRANSPARENT)
+        } else {
+            gfx::LoadOp::Load
+        };
+        let mut cmd_sequence = gfx::CommandSequence::new(instance);
+        {
+            let mut rpass = cmd_sequence.begin_render_pass(
+                &frame,
+                &pipeline.render_pass_requirements(),
+                &gfx::RenderPassOperations {
+                    color_operations: vec![gfx::ColorOperations { load, store: true }],
+                    ..gfx::RenderPassOperations::default()
+                },
+            );
+            for (texture, push_constants) in draws.iter() {
+                rpass.draw_sprite(
+                    pipeline,
+                    texture,
+                    &self.quad,
+                    push_constants,
+                    0..self.quad.index_count(),
+                );
+            }
+        }
+        cmd_sequence.submit(instance);
+        frame.present();
+
+        self.last_draw_count = draws.len();
+        self.decals.mark_drawn();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn decal(x: f32, lifetime: Option<u64>) -> Decal {
+        Decal {
+            texture: DecalTextureId(1),
+            position: Vector2::new(x, 0.),
+            lifetime: lifetime.map(Duration::from_secs),
+            fade_duration: Duration::from_secs(2),
+            ..Decal::default()
+        }
+    }
+
+    fn pending(list: &DecalList) -> Vec<(f32, f32)> {
+        list.pending_decals()
+            .map(|(decal, opacity)| (decal.position.x, opacity))
+            .collect()
+    }
+
+    #[test]
+    fn incremental_stamping() {
+        let mut list = DecalList::new(&DecalListDescriptor::default());
+        list.stamp(decal(1., None));
+        list.stamp(decal(2., None));
+        expect_that!(!list.needs_redraw());
+        expect_that!(&pending(&list), eq(vec![(1., 1.), (2., 1.)]));
+        list.mark_drawn();
+        expect_that!(&pending(&list), eq(Vec::new()));
+
+        list.stamp(decal(3., None));
+        list.update(Duration::from_secs(100));
+        expect_that!(!list.needs_redraw());
+        expect_that!(&pending(&list), eq(vec![(3., 1.)]));
+    }
+
+    #[test]
+    fn eviction() {
+        let mut list = DecalList::new(&DecalListDescriptor {
+            max_decal_count: 2,
+            ..DecalListDescriptor::default()
+        });
+        list.stamp(decal(1., None));
+        list.stamp(decal(2., None));
+        list.mark_drawn();
+        list.stamp(decal(3., None));
+        expect_that!(&list.len(), eq(2));
+        expect_that!(list.needs_redraw());
+        expect_that!(&pending(&list), eq(vec![(2., 1.), (3., 1.)]));
+        list.mark_drawn();
+        expect_that!(!list.needs_redraw());
+    }
+
+    #[test]
+    fn fading() {
+        let mut list = DecalList::new(&DecalListDescriptor {
+            fade_step_count: 4,
+            ..DecalListDescriptor::default()
+        });
+        list.stamp(decal(1., Some(10)));
+        list.stamp(decal(2., None));
+        list.mark_drawn();
+
+        // Fading starts 2 seconds before the end of the lifetime.
+        list.update(Duration::from_secs(8));
+        expect_that!(!list.needs_redraw());
+        list.update(Duration::from_millis(600));
+        expect_that!(list.needs_redraw());
+        expect_that!(&pending(&list), eq(vec![(1., 0.75), (2., 1.)]));
+        list.mark_drawn();
+
+        // Same opacity step.
+        list.update(Duration::from_millis(100));
+        expect_that!(!list.needs_redraw());
+
+        list.update(Duration::from_secs(2));
+        expect_that!(&list.len(), eq(1));
+        expect_that!(list.needs_redraw());
+        expect_that!(&pending(&list), eq(vec![(2., 1.)]));
+    }
+}
@@ -0,0 +1,401 @@
+use super::{bind_group_layout, Mesh, MeshIndexRange, PushConstants, UniformConstants, Vertex};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+use std::time::Duration;
+
+// Tileable value noise, with independent patterns in the red and green channels. The pattern
+// has cell_count random values per axis, interpolated smoothly.
+pub fn distortion_noise_image(size: u32, cell_count: u32, seed: u64) -> image::RgbaImage {
+    assert!(
+        size > 0 && cell_count > 0,
+        "The noise size and cell count must be positive"
+    );
+    let mut rng = StdRng::seed_from_u64(seed);
+    let lattice_size = (cell_count * cell_count) as usize;
+    let lattices: [Vec<f32>; 2] = [
+        (0..lattice_size).map(|_| rng.gen::<f32>()).collect(),
+        (0..lattice_size).map(|_| rng.gen::<f32>()).collect(),
+    ];
+    let value = |lattice: &[f32], x: u32, y: u32| {
+        lattice[((y % cell_count) * cell_count + x % cell_count) as usize]
+    };
+    let smooth = |t: f32| t * t * (3. - 2. * t);
+    image::RgbaImage::from_fn(size, size, |x, y| {
+        let fx = x as f32 * cell_count as f32 / size as f32;
+        let fy = y as f32 * cell_count as f32 / size as f32;
+        let (cx, cy) = (fx.floor() as u32, fy.floor() as u32);
+        let (tx, ty) = (smooth(fx.fract()), smooth(fy.fract()));
+        let channel = |lattice: &[f32]| {
+            let top = value(lattice, cx, cy) * (1. - tx) + value(lattice, cx + 1, cy) * tx;
+            let bottom =
+                value(lattice, cx, cy + 1) * (1. - tx) + value(lattice, cx + 1, cy + 1) * tx;
+            ((top * (1. - ty) + bottom * ty) * 255.).round() as u8
+        };
+        image::Rgba([channel(&lattices[0]), channel(&lattices[1]), 0, 255])
+    })
+}
+
+// Uploads the noise without color space conversion. Sample it with a repeating sampler.
+pub fn distortion_noise_texture(instance: &gfx::Instance, img: &image::RgbaImage) -> gfx::Texture {
+    let size = gfx::Extent3d {
+        width: img.width(),
+        height: img.height(),
+        depth_or_array_layers: 1,
+    };
+    let texture = gfx::Texture::new(
+        instance,
+        &gfx::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: gfx::TextureDimension::D2,
+            format: gfx::TextureFormat::Rgba8Unorm,
+            usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+        },
+    );
+    texture.write(
+        instance,
+        0,
+        gfx::Origin3d::ZERO,
+        img.as_flat_samples().as_slice(),
+        gfx::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(4 * size.width),
+            rows_per_image: None,
+        },
+        size,
+    );
+    texture
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DistortionMaterialDescriptor {
+    // Noise texture repetitions across the sprite.
+    pub noise_scale: f32,
+    // In noise texture coordinates per second.
+    pub scroll_velocity: Vector2<f32>,
+    // Displacement for the strongest noise values, 1 being the full strength of the distortion
+    // pass.
+    pub strength: f32,
+    // Width of the fade towards the sprite edges, in sprite texture coordinates.
+    pub edge_fade: f32,
+}
+
+impl DistortionMaterialDescriptor {
+    // Fast upwards shimmer, e.g. above fires.
+    pub fn heat_haze() -> Self {
+        Self {
+            noise_scale: 2.,
+            scroll_velocity: Vector2::new(0., 0.8),
+            strength: 0.6,
+            edge_fade: 0.2,
+        }
+    }
+
+    // Slow sideways ripples, e.g. seen through a water surface.
+    pub fn water() -> Self {
+        Self {
+            noise_scale: 1.,
+            scroll_velocity: Vector2::new(0.1, 0.03),
+            strength: 1.,
+            edge_fade: 0.05,
+        }
+    }
+}
+
+impl Default for DistortionMaterialDescriptor {
+    fn default() -> Self {
+        Self::heat_haze()
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DistortionMaterialPushConstants {
+    scroll_offsets: [[f32; 2]; 2],
+    noise_scale: f32,
+    strength: f32,
+    edge_fade: f32,
+}
+
+unsafe impl bytemuck::Zeroable for DistortionMaterialPushConstants {
+    fn zeroed() -> Self {
+        Self {
+            scroll_offsets: [[0., 0.]; 2],
+            noise_scale: 0.,
+            strength: 0.,
+            edge_fade: 0.,
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for DistortionMaterialPushConstants {}
+
+// The second noise layer scrolls backwards and slower than the first one.
+const SECOND_LAYER_VELOCITY_FACTOR: f32 = -0.6;
+
+// Animation state of a distortion material. The scroll offsets wrap around the noise texture,
+// so that the precision doesn't degrade over time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DistortionMaterial {
+    desc: DistortionMaterialDescriptor,
+    scroll_offsets: [Vector2<f32>; 2],
+}
+
+impl DistortionMaterial {
+    pub fn new(desc: &DistortionMaterialDescriptor) -> Self {
+        Self {
+            desc: *desc,
+            scroll_offsets: [Vector2::new(0., 0.), Vector2::new(0.37, 0.71)],
+        }
+    }
+
+    pub fn descriptor(&self) -> &DistortionMaterialDescriptor {
+        &self.desc
+    }
+
+    pub fn set_descriptor(&mut self, desc: &DistortionMaterialDescriptor) {
+        self.desc = *desc;
+    }
+
+    pub fn scroll_offsets(&self) -> &[Vector2<f32>; 2] {
+        &self.scroll_offsets
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let distance = self.desc.scroll_velocity * dt.as_secs_f32();
+        let wrap = |v: Vector2<f32>| v.map(|c| c.rem_euclid(1.));
+        self.scroll_offsets[0] = wrap(self.scroll_offsets[0] + distance);
+        self.scroll_offsets[1] =
+            wrap(self.scroll_offsets[1] + distance * SECOND_LAYER_VELOCITY_FACTOR);
+    }
+
+    pub fn push_constants(&self) -> DistortionMaterialPushConstants {
+        DistortionMaterialPushConstants {
+            scroll_offsets: self.scroll_offsets.map(|v| [v[0], v[1]]),
+            noise_scale: self.desc.noise_scale,
+            strength: self.desc.strength,
+            edge_fade: self.desc.edge_fade,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DistortionMaterialPipelineDescriptor {
+    pub label: Option<String>,
+    pub sample_count: gfx::SampleCount,
+}
+
+impl Default for DistortionMaterialPipelineDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            sample_count: 1,
+        }
+    }
+}
+
+const PC_MATERIAL_MEM_OFFSET: u32 = std::mem::size_of::<PushConstants>() as u32;
+const PC_SIZE: u32 =
+    PC_MATERIAL_MEM_OFFSET + std::mem::size_of::<DistortionMaterialPushConstants>() as u32;
+
+// Draws sprite meshes into a displacement buffer with an Rgba8Unorm color buffer, writing
+// animated noise offsets. The uniform constants hold the noise texture instead of the sprite
+// texture, and the alpha of the sprite color scales the distortion.
+#[derive(Debug)]
+pub struct DistortionMaterialPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+}
+
+impl DistortionMaterialPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &DistortionMaterialPipelineDescriptor) -> Self {
+        let bind_group_layout = bind_group_layout(instance, false);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_MATERIAL_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_MATERIAL_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/sprite.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/distortion.frag.spv"),
+        );
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[gfx::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as gfx::BufferAddress,
+                        step_mode: gfx::VertexStepMode::Vertex,
+                        attributes: &[
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x2,
+                                offset: 8,
+                                shader_location: 1,
+                            },
+                        ],
+                    }],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: Some(gfx::Face::Back),
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::Rgba8Unorm,
+                        blend: Some(gfx::BlendState::ALPHA_BLENDING),
+                        write_mask: gfx::ColorWrite::ALL,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+        }
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![gfx::CanvasColorBufferFormat::Rgba8Unorm],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait DistortionMaterialRenderer<'a> {
+    fn draw_distortion_sprite<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a DistortionMaterialPipeline,
+        uniform_constants: &'a UniformConstants,
+        mesh: &'a Mesh<I>,
+        push_constants: &'a PushConstants,
+        material: &DistortionMaterialPushConstants,
+        index_range: MeshIndexRange,
+    );
+}
+
+impl<'a> DistortionMaterialRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_distortion_sprite<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a DistortionMaterialPipeline,
+        uniform_constants: &'a UniformConstants,
+        mesh: &'a Mesh<I>,
+        push_constants: &'a PushConstants,
+        material: &DistortionMaterialPushConstants,
+        index_range: MeshIndexRange,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
+        self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_MATERIAL_MEM_OFFSET,
+            gfx::utility::as_slice(material),
+        );
+        self.draw_indexed(index_range, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn noise_is_tileable() {
+        let img = distortion_noise_image(64, 4, 3);
+        expect_that!(&img.dimensions(), eq((64, 64)));
+        // Neighbouring texels across the wrap differ as little as inner neighbours do.
+        for i in 0..64 {
+            for (a, b) in [
+                (img.get_pixel(63, i), img.get_pixel(0, i)),
+                (img.get_pixel(i, 63), img.get_pixel(i, 0)),
+            ] {
+                expect_that!(&(a[0] as i32 - b[0] as i32).abs(), leq(32));
+                expect_that!(&(a[1] as i32 - b[1] as i32).abs(), leq(32));
+            }
+        }
+        expect_that!(&img, eq(distortion_noise_image(64, 4, 3)));
+        expect_that!(img != distortion_noise_image(64, 4, 4));
+        expect_that!(img.pixels().any(|p| p[0] != p[1]));
+    }
+
+    #[test]
+    fn scroll_offsets_wrap() {
+        let mut material = DistortionMaterial::new(&DistortionMaterialDescriptor {
+            scroll_velocity: Vector2::new(0.5, -0.25),
+            ..DistortionMaterialDescriptor::default()
+        });
+        let start = *material.scroll_offsets();
+        material.update(Duration::from_secs(1));
+        expect_that!(&material.scroll_offsets()[0], eq(Vector2::new(0.5, 0.75)));
+        for _ in 0..4 {
+            material.update(Duration::from_secs(1));
+        }
+        let offsets = material.scroll_offsets();
+        expect_that!(&offsets[0][0], close_to(0.5, 1e-5));
+        expect_that!(&offsets[0][1], close_to(0.75, 1e-5));
+        // 5 seconds move the second layer by (-1.5, 0.75).
+        expect_that!(
+            &offsets[1][0],
+            close_to((start[1][0] - 1.5).rem_euclid(1.), 1e-5)
+        );
+        expect_that!(
+            &offsets[1][1],
+            close_to((start[1][1] + 0.75).rem_euclid(1.), 1e-5)
+        );
+        for offset in offsets.iter() {
+            expect_that!(offset.iter().all(|c| (0. ..1.).contains(c)));
+        }
+    }
+}
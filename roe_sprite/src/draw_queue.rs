@@ -0,0 +1,360 @@
+use super::{Mesh, MeshIndexRange, PushConstants, RenderPipeline, UniformConstants};
+
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DrawSortMode {
+    // Items are drawn in the order they were pushed.
+    #[default]
+    Submission,
+    // Items with a lower sort origin y are drawn first, so that sprites lower on the screen
+    // overlap the ones behind them, e.g. characters in top-down games. Ties keep the
+    // submission order.
+    YSort,
+    // Items with a lower depth are drawn first, e.g. opaque 3D meshes, so that hidden fragments
+    // fail the depth test early.
+    FrontToBack,
+    // Items with a higher depth are drawn first, e.g. blended 3D meshes.
+    BackToFront,
+}
+
+// Items drawn as a unit, e.g. the body, clothes and weapon of a character. The group is sorted
+// with the rest of the layer through the sort origin and depth of its items, which should be
+// the same for the whole group, and its items are drawn by increasing order.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct SortingGroup {
+    pub id: u32,
+    pub order: i32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DrawQueueItem<T> {
+    // Lower layers are always drawn first, whatever the sort mode, see RenderLayer.
+    pub layer: i32,
+    // Lower orders are drawn first inside a layer, before the sort mode applies.
+    pub order: i32,
+    pub sorting_group: Option<SortingGroup>,
+    // See SpriteTransform::sort_origin.
+    pub sort_origin: Vector2<f32>,
+    // Distance from the camera, used by the 3D sort modes.
+    pub depth: f32,
+    pub value: T,
+}
+
+// Collects the draws of a frame and sorts them before drawing.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DrawQueue<T> {
+    sort_mode: DrawSortMode,
+    // Overrides of the sort mode, so that e.g. 2D and 3D layers can share a queue.
+    layer_sort_modes: Vec<(i32, DrawSortMode)>,
+    items: Vec<DrawQueueItem<T>>,
+    sorted: bool,
+}
+
+impl<T> DrawQueue<T> {
+    pub fn new(sort_mode: DrawSortMode) -> Self {
+        Self {
+            sort_mode,
+            layer_sort_modes: Vec::new(),
+            items: Vec::new(),
+            sorted: true,
+        }
+    }
+
+    pub fn sort_mode(&self) -> DrawSortMode {
+        self.sort_mode
+    }
+
+    pub fn set_sort_mode(&mut self, sort_mode: DrawSortMode) {
+        self.sort_mode = sort_mode;
+        self.sorted = false;
+    }
+
+    pub fn layer_sort_mode<L: Into<i32>>(&self, layer: L) -> DrawSortMode {
+        let layer = layer.into();
+        self.layer_sort_modes
+            .iter()
+            .find(|(l, _)| *l == layer)
+            .map(|(_, mode)| *mode)
+            .unwrap_or(self.sort_mode)
+    }
+
+    // None restores the queue sort mode for the layer.
+    pub fn set_layer_sort_mode<L: Into<i32>>(&mut self, layer: L, sort_mode: Option<DrawSortMode>) {
+        let layer = layer.into();
+        self.layer_sort_modes.retain(|(l, _)| *l != layer);
+        if let Some(sort_mode) = sort_mode {
+            self.layer_sort_modes.push((layer, sort_mode));
+        }
+        self.sorted = false;
+    }
+
+    pub fn push<L: Into<i32>>(&mut self, layer: L, sort_origin: Vector2<f32>, value: T) {
+        self.push_item(DrawQueueItem {
+            layer: layer.into(),
+            order: 0,
+            sorting_group: None,
+            sort_origin,
+            depth: 0.,
+            value,
+        });
+    }
+
+    // For 3D draws, see FrontToBack and BackToFront.
+    pub fn push_with_depth<L: Into<i32>>(&mut self, layer: L, depth: f32, value: T) {
+        self.push_item(DrawQueueItem {
+            layer: layer.into(),
+            order: 0,
+            sorting_group: None,
+            sort_origin: Vector2::zeros(),
+            depth,
+            value,
+        });
+    }
+
+    // The sort origin is the one of the whole group.
+    pub fn push_grouped<L: Into<i32>>(
+        &mut self,
+        layer: L,
+        sort_origin: Vector2<f32>,
+        sorting_group: SortingGroup,
+        value: T,
+    ) {
+        self.push_item(DrawQueueItem {
+            layer: layer.into(),
+            order: 0,
+            sorting_group: Some(sorting_group),
+            sort_origin,
+            depth: 0.,
+            value,
+        });
+    }
+
+    pub fn push_item(&mut self, item: DrawQueueItem<T>) {
+        self.items.push(item);
+        self.sorted = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Usually called at the start of each frame.
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.sorted = true;
+    }
+
+    // Items are sorted by layer, order, sort mode key and then sorting group, ungrouped items
+    // first and groups by id, each group by item order. Sorting is stable, so the submission
+    // order is kept where all keys are equal. Submission order can't be recovered after sorting
+    // in the other modes.
+    pub fn sort(&mut self) {
+        if self.sorted {
+            return;
+        }
+        let sort_mode = self.sort_mode;
+        let layer_sort_modes = &self.layer_sort_modes;
+        let mode = |layer: i32| {
+            layer_sort_modes
+                .iter()
+                .find(|(l, _)| *l == layer)
+                .map(|(_, mode)| *mode)
+                .unwrap_or(sort_mode)
+        };
+        let key = |a: f32, b: f32| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal);
+        let group = |item: &DrawQueueItem<T>| item.sorting_group.map(|g| (g.id, g.order));
+        self.items.sort_by(|a, b| {
+            a.layer
+                .cmp(&b.layer)
+                .then(a.order.cmp(&b.order))
+                .then_with(|| match mode(a.layer) {
+                    DrawSortMode::Submission => std::cmp::Ordering::Equal,
+                    DrawSortMode::YSort => key(a.sort_origin.y, b.sort_origin.y),
+                    DrawSortMode::FrontToBack => key(a.depth, b.depth),
+                    DrawSortMode::BackToFront => key(b.depth, a.depth),
+                })
+                .then_with(|| group(a).cmp(&group(b)))
+        });
+        self.sorted = true;
+    }
+
+    // Items in drawing order.
+    pub fn sorted_items(&mut self) -> &[DrawQueueItem<T>] {
+        self.sort();
+        &self.items
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &DrawQueueItem<T>> {
+        assert!(self.sorted, "The draw queue must be sorted first");
+        self.items.iter()
+    }
+
+    // Sorted items of a layer, e.g. to draw them with a layer specific pipeline.
+    pub fn layer_items<L: Into<i32>>(&self, layer: L) -> &[DrawQueueItem<T>] {
+        let layer = layer.into();
+        assert!(self.sorted, "The draw queue must be sorted first");
+        let begin = self.items.partition_point(|item| item.layer < layer);
+        let end = self.items.partition_point(|item| item.layer <= layer);
+        &self.items[begin..end]
+    }
+}
+
+impl<T> Default for DrawQueue<T> {
+    fn default() -> Self {
+        Self::new(DrawSortMode::default())
+    }
+}
+
+#[derive(Debug)]
+pub struct SpriteDrawCommand<'a, I: gfx::MeshIndexType = gfx::MeshIndex> {
+    pub uniform_constants: &'a UniformConstants,
+    pub mesh: &'a Mesh<I>,
+    pub push_constants: PushConstants,
+    pub index_range: MeshIndexRange,
+}
+
+pub trait DrawQueueRenderer<'a> {
+    // The queue must be sorted first.
+    fn draw_sprite_queue<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        queue: &'a DrawQueue<SpriteDrawCommand<'a, I>>,
+    );
+}
+
+impl<'a> DrawQueueRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_sprite_queue<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        queue: &'a DrawQueue<SpriteDrawCommand<'a, I>>,
+    ) {
+        use super::Renderer;
+        for item in queue.items() {
+            let command = &item.value;
+            self.draw_sprite(
+                pipeline,
+                command.uniform_constants,
+                command.mesh,
+                &command.push_constants,
+                command.index_range.clone(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn order(queue: &mut DrawQueue<&'static str>) -> Vec<&'static str> {
+        queue.sorted_items().iter().map(|item| item.value).collect()
+    }
+
+    #[test]
+    fn submission_order() {
+        let mut queue = DrawQueue::new(DrawSortMode::Submission);
+        queue.push(0, Vector2::new(0., 30.), "a");
+        queue.push(0, Vector2::new(0., 10.), "b");
+        queue.push(-1, Vector2::new(0., 50.), "background");
+        expect_that!(&order(&mut queue), eq(vec!["background", "a", "b"]));
+    }
+
+    #[test]
+    fn y_sort() {
+        let mut queue = DrawQueue::new(DrawSortMode::YSort);
+        queue.push(0, Vector2::new(0., 30.), "front");
+        queue.push(0, Vector2::new(5., 10.), "back");
+        queue.push(1, Vector2::new(0., 0.), "overlay");
+        queue.push(0, Vector2::new(8., 10.), "back_2");
+        expect_that!(
+            &order(&mut queue),
+            eq(vec!["back", "back_2", "front", "overlay"])
+        );
+
+        queue.clear();
+        expect_that!(queue.is_empty());
+        expect_that!(&queue.items().count(), eq(0));
+    }
+
+    #[test]
+    fn layer_sort_modes() {
+        let mut queue = DrawQueue::new(DrawSortMode::Submission);
+        queue.set_layer_sort_mode(0, Some(DrawSortMode::FrontToBack));
+        queue.set_layer_sort_mode(1, Some(DrawSortMode::BackToFront));
+        queue.push_with_depth(1, 2., "glass_near");
+        queue.push_with_depth(0, 8., "wall_far");
+        queue.push(2, Vector2::new(0., 0.), "hud_b");
+        queue.push_with_depth(1, 6., "glass_far");
+        queue.push_with_depth(0, 3., "wall_near");
+        queue.push(2, Vector2::new(0., -5.), "hud_a");
+        expect_that!(&queue.layer_sort_mode(2), eq(DrawSortMode::Submission));
+        expect_that!(
+            &order(&mut queue),
+            eq(vec![
+                "wall_near",
+                "wall_far",
+                "glass_far",
+                "glass_near",
+                "hud_b",
+                "hud_a"
+            ])
+        );
+
+        queue.set_layer_sort_mode(0, None);
+        expect_that!(&queue.layer_sort_mode(0), eq(DrawSortMode::Submission));
+    }
+
+    #[test]
+    fn explicit_order() {
+        let mut queue = DrawQueue::new(DrawSortMode::YSort);
+        queue.push(0, Vector2::new(0., 0.), "tree");
+        queue.push_item(DrawQueueItem {
+            layer: 0,
+            order: -1,
+            sorting_group: None,
+            sort_origin: Vector2::new(0., 50.),
+            depth: 0.,
+            value: "shadow",
+        });
+        queue.push(0, Vector2::new(0., 10.), "rock");
+        expect_that!(&order(&mut queue), eq(vec!["shadow", "tree", "rock"]));
+    }
+
+    #[test]
+    fn sorting_groups() {
+        let mut queue = DrawQueue::new(DrawSortMode::YSort);
+        let group = |id, order| SortingGroup { id, order };
+        queue.push_grouped(0, Vector2::new(0., 20.), group(1, 1), "knight_sword");
+        queue.push_grouped(0, Vector2::new(0., 20.), group(2, 1), "archer_bow");
+        queue.push(0, Vector2::new(0., 20.), "barrel");
+        queue.push_grouped(0, Vector2::new(0., 20.), group(1, 0), "knight_body");
+        queue.push_grouped(0, Vector2::new(0., 5.), group(3, 0), "guard_body");
+        queue.push_grouped(0, Vector2::new(0., 20.), group(2, 0), "archer_body");
+        expect_that!(
+            &order(&mut queue),
+            eq(vec![
+                "guard_body",
+                "barrel",
+                "knight_body",
+                "knight_sword",
+                "archer_body",
+                "archer_bow"
+            ])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The draw queue must be sorted first")]
+    fn unsorted_items() {
+        let mut queue = DrawQueue::new(DrawSortMode::YSort);
+        queue.push(0, Vector2::new(0., 0.), "a");
+        let _ = queue.items().count();
+    }
+}
@@ -0,0 +1,548 @@
+use super::{
+    Mesh, MeshIndexRange, MeshTemplates, PushConstants, RenderPipeline, Renderer, UniformConstants,
+};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Vector2};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum FogCellState {
+    // Never seen, fully covered.
+    #[default]
+    Unexplored,
+    // Seen before but not currently in sight, partially covered.
+    Explored,
+    Visible,
+}
+
+// Area revealed by a viewer. Coordinates are in cells for FogGrid and in world units for
+// FogOfWar.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RevealBrush {
+    Circle {
+        center: Vector2<f32>,
+        radius: f32,
+    },
+    // Direction and half angle in radians, e.g. a torch or a guard's view.
+    Cone {
+        center: Vector2<f32>,
+        radius: f32,
+        direction: f32,
+        half_angle: f32,
+    },
+}
+
+impl RevealBrush {
+    pub fn center(&self) -> &Vector2<f32> {
+        match self {
+            Self::Circle { center, .. } | Self::Cone { center, .. } => center,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            Self::Circle { radius, .. } | Self::Cone { radius, .. } => *radius,
+        }
+    }
+
+    pub fn contains(&self, p: &Vector2<f32>) -> bool {
+        let offset = p - self.center();
+        let distance = offset.norm();
+        if distance > self.radius() {
+            return false;
+        }
+        match self {
+            Self::Circle { .. } => true,
+            Self::Cone {
+                direction,
+                half_angle,
+                ..
+            } => {
+                if distance <= f32::EPSILON {
+                    return true;
+                }
+                let angle = offset.y.atan2(offset.x) - direction;
+                let angle = (angle + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                angle.abs() <= *half_angle
+            }
+        }
+    }
+
+    fn scaled(&self, origin: &Vector2<f32>, cell_size: f32) -> Self {
+        let to_cells = |center: &Vector2<f32>| (center - origin) / cell_size;
+        match *self {
+            Self::Circle { center, radius } => Self::Circle {
+                center: to_cells(&center),
+                radius: radius / cell_size,
+            },
+            Self::Cone {
+                center,
+                radius,
+                direction,
+                half_angle,
+            } => Self::Cone {
+                center: to_cells(&center),
+                radius: radius / cell_size,
+                direction,
+                half_angle,
+            },
+        }
+    }
+}
+
+// Visibility state of a grid of cells. Viewers reveal cells each frame, cells revealed in the
+// previous frame and not in the current one become explored. Occluder cells block the line of
+// sight, but are revealed themselves so that walls are visible.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FogGrid {
+    width: u32,
+    height: u32,
+    states: Vec<FogCellState>,
+    occluders: Vec<bool>,
+    visible: Vec<usize>,
+    // Cells whose state changed since the last call to take_changes, possibly repeated.
+    changes: Vec<usize>,
+}
+
+impl FogGrid {
+    pub fn new(width: u32, height: u32) -> Self {
+        let count = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            states: vec![FogCellState::Unexplored; count],
+            occluders: vec![false; count],
+            visible: Vec::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "Cell out of bounds");
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn state(&self, x: u32, y: u32) -> FogCellState {
+        self.states[self.index(x, y)]
+    }
+
+    pub fn is_occluder(&self, x: u32, y: u32) -> bool {
+        self.occluders[self.index(x, y)]
+    }
+
+    pub fn set_occluder(&mut self, x: u32, y: u32, occluder: bool) {
+        let i = self.index(x, y);
+        self.occluders[i] = occluder;
+    }
+
+    // Cells outside of the grid never block the line of sight.
+    fn blocks(&self, x: i64, y: i64) -> bool {
+        x >= 0
+            && y >= 0
+            && x < self.width as i64
+            && y < self.height as i64
+            && self.occluders[y as usize * self.width as usize + x as usize]
+    }
+
+    // Whether no occluder lies strictly between the two cells, along a Bresenham line.
+    pub fn line_of_sight(&self, from: (i64, i64), to: (i64, i64)) -> bool {
+        let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+        let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+        let (mut x, mut y) = from;
+        let mut error = dx + dy;
+        loop {
+            if (x, y) == to {
+                return true;
+            }
+            if (x, y) != from && self.blocks(x, y) {
+                return false;
+            }
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // Turns the currently visible cells into explored cells. Called once per frame before
+    // revealing.
+    pub fn begin_frame(&mut self) {
+        for i in self.visible.drain(..) {
+            self.states[i] = FogCellState::Explored;
+            self.changes.push(i);
+        }
+    }
+
+    pub fn reveal(&mut self, brush: &RevealBrush) {
+        let center = brush.center();
+        let radius = brush.radius();
+        let origin = (center.x.floor() as i64, center.y.floor() as i64);
+        let min_x = ((center.x - radius).floor() as i64).max(0);
+        let min_y = ((center.y - radius).floor() as i64).max(0);
+        let max_x = ((center.x + radius).ceil() as i64).min(self.width as i64);
+        let max_y = ((center.y + radius).ceil() as i64).min(self.height as i64);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let cell_center = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if brush.contains(&cell_center) && self.line_of_sight(origin, (x, y)) {
+                    self.set_visible(x as u32, y as u32);
+                }
+            }
+        }
+    }
+
+    fn set_visible(&mut self, x: u32, y: u32) {
+        let i = self.index(x, y);
+        if self.states[i] != FogCellState::Visible {
+            self.states[i] = FogCellState::Visible;
+            self.visible.push(i);
+            self.changes.push(i);
+        }
+    }
+
+    // Covers the whole grid again, e.g. when loading a new level.
+    pub fn reset(&mut self) {
+        self.visible.clear();
+        for (i, state) in self.states.iter_mut().enumerate() {
+            if *state != FogCellState::Unexplored {
+                *state = FogCellState::Unexplored;
+                self.changes.push(i);
+            }
+        }
+    }
+
+    // Cells whose state may have changed, each listed once.
+    pub fn take_changes(&mut self) -> Vec<(u32, u32)> {
+        let mut changes = std::mem::take(&mut self.changes);
+        changes.sort_unstable();
+        changes.dedup();
+        changes
+            .into_iter()
+            .map(|i| {
+                (
+                    (i % self.width as usize) as u32,
+                    (i / self.width as usize) as u32,
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FogOfWarDescriptor {
+    // Size of the grid, in cells.
+    pub size: gfx::Size<u32>,
+    // World position of the top left corner of the grid.
+    pub origin: Vector2<f32>,
+    pub cell_size: f32,
+    pub color: gfx::Color,
+    pub explored_opacity: f32,
+    pub unexplored_opacity: f32,
+}
+
+impl Default for FogOfWarDescriptor {
+    fn default() -> Self {
+        Self {
+            size: gfx::Size::new(64, 64),
+            origin: Vector2::zeros(),
+            cell_size: 32.,
+            color: gfx::Color::BLACK,
+            explored_opacity: 0.6,
+            unexplored_opacity: 1.,
+        }
+    }
+}
+
+// Fog texel of a cell state: the fog color, with the opacity of the state as alpha.
+fn fog_texel(desc: &FogOfWarDescriptor, state: FogCellState) -> image::Rgba<u8> {
+    let opacity = match state {
+        FogCellState::Unexplored => desc.unexplored_opacity,
+        FogCellState::Explored => desc.explored_opacity,
+        FogCellState::Visible => 0.,
+    };
+    let alpha = (opacity.clamp(0., 1.) * 255.).round() as u8;
+    image::Rgba([desc.color.r, desc.color.g, desc.color.b, alpha])
+}
+
+// Fog grid with a mask texture holding a texel per cell. The mask is drawn over the world with
+// the sprite pipeline and linear filtering, darkening the hidden areas with soft edges.
+#[derive(Debug)]
+pub struct FogOfWar {
+    desc: FogOfWarDescriptor,
+    grid: FogGrid,
+    mask: gfx::EditableTexture,
+    uniform_constants: UniformConstants,
+    quad: Mesh,
+    push_constants: PushConstants,
+}
+
+impl FogOfWar {
+    pub fn new(instance: &gfx::Instance, desc: &FogOfWarDescriptor) -> Self {
+        let (width, height) = (desc.size.width(), desc.size.height());
+        let mask = gfx::EditableTexture::new(
+            instance,
+            image::RgbaImage::from_pixel(width, height, fog_texel(desc, FogCellState::Unexplored)),
+            gfx::TextureUsage::TEXTURE_BINDING,
+        );
+        let view = mask
+            .texture()
+            .create_view(&gfx::TextureViewDescriptor::default());
+        let sampler = gfx::Sampler::new(
+            instance,
+            &gfx::SamplerDescriptor {
+                mag_filter: gfx::FilterMode::Linear,
+                min_filter: gfx::FilterMode::Linear,
+                ..gfx::SamplerDescriptor::default()
+            },
+        );
+        let uniform_constants =
+            UniformConstants::new_with_label(instance, Some("fog_of_war"), &view, &sampler);
+        let mut fog = Self {
+            desc: desc.clone(),
+            grid: FogGrid::new(width, height),
+            mask,
+            uniform_constants,
+            quad: Mesh::rectangle(instance, 1., 1.),
+            push_constants: PushConstants::new(
+                &HomogeneousMatrix2::identity(),
+                gfx::ColorF32::WHITE,
+            ),
+        };
+        fog.set_view_projection(&HomogeneousMatrix2::identity());
+        fog
+    }
+
+    pub fn descriptor(&self) -> &FogOfWarDescriptor {
+        &self.desc
+    }
+
+    pub fn grid(&self) -> &FogGrid {
+        &self.grid
+    }
+
+    // E.g. to set the occluders.
+    pub fn grid_mut(&mut self) -> &mut FogGrid {
+        &mut self.grid
+    }
+
+    pub fn mask(&self) -> &gfx::EditableTexture {
+        &self.mask
+    }
+
+    // Cell containing a world position, if any.
+    pub fn cell_at(&self, position: &Vector2<f32>) -> Option<(u32, u32)> {
+        let cell = (position - self.desc.origin) / self.desc.cell_size;
+        if cell.x < 0. || cell.y < 0. {
+            return None;
+        }
+        let (x, y) = (cell.x as u32, cell.y as u32);
+        if x < self.grid.width() && y < self.grid.height() {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.grid.begin_frame();
+    }
+
+    // The brush is in world units.
+    pub fn reveal(&mut self, brush: &RevealBrush) {
+        self.grid
+            .reveal(&brush.scaled(&self.desc.origin, self.desc.cell_size));
+    }
+
+    pub fn reset(&mut self) {
+        self.grid.reset();
+    }
+
+    // Writes the changed cells into the mask and uploads them.
+    pub fn update(&mut self, instance: &gfx::Instance) {
+        for (x, y) in self.grid.take_changes() {
+            let texel = fog_texel(&self.desc, self.grid.state(x, y));
+            if self.mask.pixel(x, y) != texel {
+                self.mask.set_pixel(x, y, texel);
+            }
+        }
+        self.mask.upload(instance);
+    }
+
+    // Projection from world coordinates, e.g. the camera view projection.
+    pub fn set_view_projection(&mut self, view_projection: &HomogeneousMatrix2<f32>) {
+        let size = Vector2::new(
+            self.grid.width() as f32 * self.desc.cell_size,
+            self.grid.height() as f32 * self.desc.cell_size,
+        );
+        let transform =
+            view_projection * roe_math::translation2(&self.desc.origin) * roe_math::scale2(&size);
+        self.push_constants = PushConstants::new(&transform, gfx::ColorF32::WHITE);
+    }
+}
+
+pub trait FogOfWarRenderer<'a> {
+    // The pipeline must use alpha blending, e.g. the default one.
+    fn draw_fog_of_war(&mut self, pipeline: &'a RenderPipeline, fog: &'a FogOfWar);
+}
+
+impl<'a> FogOfWarRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_fog_of_war(&mut self, pipeline: &'a RenderPipeline, fog: &'a FogOfWar) {
+        let index_range: MeshIndexRange = 0..fog.quad.index_count();
+        self.draw_sprite(
+            pipeline,
+            &fog.uniform_constants,
+            &fog.quad,
+            &fog.push_constants,
+            index_range,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn states(grid: &FogGrid, y: u32) -> Vec<FogCellState> {
+        (0..grid.width()).map(|x| grid.state(x, y)).collect()
+    }
+
+    #[test]
+    fn circle_reveal() {
+        let mut grid = FogGrid::new(8, 8);
+        grid.begin_frame();
+        grid.reveal(&RevealBrush::Circle {
+            center: Vector2::new(4., 4.),
+            radius: 2.,
+        });
+        expect_that!(&grid.state(4, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(2, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(5, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(5, 5), eq(FogCellState::Unexplored));
+        expect_that!(&grid.state(1, 4), eq(FogCellState::Unexplored));
+        expect_that!(&grid.state(2, 2), eq(FogCellState::Unexplored));
+        expect_that!(&grid.take_changes().len(), eq(12));
+        expect_that!(&grid.take_changes(), eq(Vec::new()));
+    }
+
+    #[test]
+    fn explored_cells() {
+        let mut grid = FogGrid::new(8, 1);
+        let brush = |x: f32| RevealBrush::Circle {
+            center: Vector2::new(x, 0.5),
+            radius: 1.,
+        };
+        grid.reveal(&brush(1.5));
+        grid.take_changes();
+
+        grid.begin_frame();
+        grid.reveal(&brush(2.5));
+        use FogCellState::*;
+        expect_that!(
+            &states(&grid, 0),
+            eq(vec![
+                Explored, Visible, Visible, Visible, Unexplored, Unexplored, Unexplored, Unexplored
+            ])
+        );
+        expect_that!(
+            &grid.take_changes(),
+            eq(vec![(0, 0), (1, 0), (2, 0), (3, 0)])
+        );
+
+        grid.reset();
+        expect_that!(&states(&grid, 0), eq(vec![Unexplored; 8]));
+        grid.begin_frame();
+        expect_that!(&states(&grid, 0), eq(vec![Unexplored; 8]));
+    }
+
+    #[test]
+    fn cone_reveal() {
+        let mut grid = FogGrid::new(9, 9);
+        grid.reveal(&RevealBrush::Cone {
+            center: Vector2::new(4.5, 4.5),
+            radius: 4.,
+            direction: 0.,
+            half_angle: std::f32::consts::FRAC_PI_4,
+        });
+        expect_that!(&grid.state(4, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(8, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(7, 5), eq(FogCellState::Visible));
+        expect_that!(&grid.state(6, 7), eq(FogCellState::Unexplored));
+        expect_that!(&grid.state(2, 4), eq(FogCellState::Unexplored));
+        expect_that!(&grid.state(4, 7), eq(FogCellState::Unexplored));
+    }
+
+    #[test]
+    fn occluders() {
+        let mut grid = FogGrid::new(9, 9);
+        grid.set_occluder(6, 4, true);
+        grid.reveal(&RevealBrush::Circle {
+            center: Vector2::new(4.5, 4.5),
+            radius: 4.,
+        });
+        // The wall is visible, the cells behind it are not.
+        expect_that!(&grid.state(5, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(6, 4), eq(FogCellState::Visible));
+        expect_that!(&grid.state(7, 4), eq(FogCellState::Unexplored));
+        expect_that!(&grid.state(8, 4), eq(FogCellState::Unexplored));
+        expect_that!(&grid.state(7, 2), eq(FogCellState::Visible));
+        expect_that!(grid.line_of_sight((4, 4), (6, 4)));
+        expect_that!(!grid.line_of_sight((4, 4), (7, 4)));
+        expect_that!(grid.line_of_sight((-3, 4), (2, 4)));
+    }
+
+    #[test]
+    fn world_brush() {
+        let brush = RevealBrush::Circle {
+            center: Vector2::new(100., 60.),
+            radius: 64.,
+        };
+        expect_that!(
+            &brush.scaled(&Vector2::new(20., -4.), 32.),
+            eq(RevealBrush::Circle {
+                center: Vector2::new(2.5, 2.),
+                radius: 2.
+            })
+        );
+    }
+
+    #[test]
+    fn texels() {
+        let desc = FogOfWarDescriptor {
+            color: gfx::Color {
+                r: 10,
+                g: 20,
+                b: 30,
+                a: 255,
+            },
+            explored_opacity: 0.5,
+            ..FogOfWarDescriptor::default()
+        };
+        expect_that!(
+            &fog_texel(&desc, FogCellState::Unexplored),
+            eq(image::Rgba([10, 20, 30, 255]))
+        );
+        expect_that!(
+            &fog_texel(&desc, FogCellState::Explored),
+            eq(image::Rgba([10, 20, 30, 128]))
+        );
+        expect_that!(
+            &fog_texel(&desc, FogCellState::Visible),
+            eq(image::Rgba([10, 20, 30, 0]))
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use super::{
+    bind_group_layout, fragment_shader, MeshIndex, MeshIndexRange, PushConstants,
+    RenderPipelineDescriptor, Skeleton, SkinnedMesh, SpriteFeatures, UniformConstants,
+    PC_CONVERSION_MEM_OFFSET, PC_SIZE,
+};
+
+use roe_graphics as gfx;
+use roe_math::HomogeneousMatrix2;
+
+// Bones a GPU skinned mesh can use. Must match MAX_BONES in skinned_sprite.vert.
+pub const MAX_SKINNING_BONES: usize = 128;
+
+// Vertices are skinned by up to 4 bones.
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SkinnedSpriteVertex {
+    // In the setup pose, in skeleton space.
+    pub position: [f32; 2],
+    pub texture_coordinates: [f32; 2],
+    pub bone_indices: [u32; 4],
+    // Add up to 1.
+    pub bone_weights: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for SkinnedSpriteVertex {
+    fn zeroed() -> Self {
+        Self {
+            position: [0., 0.],
+            texture_coordinates: [0., 0.],
+            bone_indices: [0; 4],
+            bone_weights: [0.; 4],
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for SkinnedSpriteVertex {}
+
+pub type SkinnedSpriteMesh<I = MeshIndex> = gfx::IndexedMesh<SkinnedSpriteVertex, I>;
+
+impl SkinnedMesh {
+    // Vertices for GPU skinning. Only the 4 most influential bones of each vertex are kept,
+    // with their weights normalized.
+    pub fn gpu_vertices(&self, skeleton: &Skeleton) -> Vec<SkinnedSpriteVertex> {
+        let setup = self.deform(&skeleton.world_transforms(&skeleton.setup_pose()));
+        self.vertices
+            .iter()
+            .zip(setup.iter())
+            .map(|(vertex, setup)| {
+                let mut influences = vertex.influences.clone();
+                influences.sort_by(|a, b| b.weight.partial_cmp(&a.weight).unwrap());
+                influences.truncate(4);
+                let total: f32 = influences.iter().map(|i| i.weight).sum();
+                let mut bone_indices = [0; 4];
+                let mut bone_weights = [0.; 4];
+                for (i, influence) in influences.iter().enumerate() {
+                    assert!(
+                        influence.bone < MAX_SKINNING_BONES,
+                        "Too many bones for GPU skinning"
+                    );
+                    bone_indices[i] = influence.bone as u32;
+                    bone_weights[i] = if total > 0. {
+                        influence.weight / total
+                    } else {
+                        0.
+                    };
+                }
+                SkinnedSpriteVertex {
+                    position: setup.position,
+                    texture_coordinates: [
+                        vertex.texture_coordinates.x,
+                        vertex.texture_coordinates.y,
+                    ],
+                    bone_indices,
+                    bone_weights,
+                }
+            })
+            .collect()
+    }
+
+    pub fn create_gpu_mesh(
+        &self,
+        instance: &gfx::Instance,
+        skeleton: &Skeleton,
+    ) -> SkinnedSpriteMesh {
+        SkinnedSpriteMesh::new(instance, &self.gpu_vertices(skeleton), &self.indices)
+    }
+}
+
+// Rows of the bone transforms relative to the setup pose, as laid out in the palette buffer.
+pub fn bone_palette_rows(
+    world_transforms: &[HomogeneousMatrix2<f32>],
+    inverse_setup_transforms: &[HomogeneousMatrix2<f32>],
+) -> Vec<[f32; 4]> {
+    assert!(
+        world_transforms.len() == inverse_setup_transforms.len(),
+        "The transforms belong to different skeletons"
+    );
+    assert!(
+        world_transforms.len() <= MAX_SKINNING_BONES,
+        "Too many bones for GPU skinning"
+    );
+    let mut rows = Vec::with_capacity(world_transforms.len() * 2);
+    for (world, inverse_setup) in world_transforms.iter().zip(inverse_setup_transforms) {
+        let m = world * inverse_setup;
+        rows.push([m[(0, 0)], m[(0, 1)], m[(0, 2)], 0.]);
+        rows.push([m[(1, 0)], m[(1, 1)], m[(1, 2)], 0.]);
+    }
+    rows
+}
+
+const PALETTE_BYTE_COUNT: gfx::BufferAddress =
+    (MAX_SKINNING_BONES * 2 * std::mem::size_of::<[f32; 4]>()) as gfx::BufferAddress;
+
+fn bone_palette_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+    gfx::BindGroupLayout::new(
+        instance,
+        &gfx::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[gfx::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: gfx::ShaderStage::VERTEX,
+                ty: gfx::BindingType::Buffer {
+                    ty: gfx::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        },
+    )
+}
+
+// Bone transforms of a skinned mesh instance, rewritten each frame. Each animated character
+// needs its own palette.
+#[derive(Debug)]
+pub struct BonePalette {
+    buffer: gfx::Buffer,
+    bind_group: gfx::BindGroup,
+}
+
+impl BonePalette {
+    pub fn new(instance: &gfx::Instance) -> Self {
+        let buffer = gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: PALETTE_BYTE_COUNT,
+                usage: gfx::BufferUsage::UNIFORM | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let bind_group = gfx::BindGroup::new(
+            instance,
+            &gfx::BindGroupDescriptor {
+                label: None,
+                layout: &bone_palette_layout(instance),
+                entries: &[gfx::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            },
+        );
+        Self { buffer, bind_group }
+    }
+
+    // The inverse setup transforms are usually computed once per skeleton.
+    pub fn update(
+        &self,
+        instance: &gfx::Instance,
+        world_transforms: &[HomogeneousMatrix2<f32>],
+        inverse_setup_transforms: &[HomogeneousMatrix2<f32>],
+    ) {
+        let rows = bone_palette_rows(world_transforms, inverse_setup_transforms);
+        instance.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&rows));
+    }
+}
+
+// Sprite pipeline deforming the meshes in the vertex shader, so that the vertex buffers are
+// never rewritten. Uses the same uniform constants and push constants as RenderPipeline.
+#[derive(Debug)]
+pub struct SkinnedSpritePipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl SkinnedSpritePipeline {
+    pub fn new(instance: &gfx::Instance, desc: &RenderPipelineDescriptor) -> Self {
+        let bind_group_layout =
+            bind_group_layout(instance, desc.features.contains(SpriteFeatures::PALETTE));
+        let palette_layout = bone_palette_layout(instance);
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout, &palette_layout],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_CONVERSION_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_CONVERSION_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/skinned_sprite.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(instance, &fragment_shader(desc.features));
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[gfx::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<SkinnedSpriteVertex>()
+                            as gfx::BufferAddress,
+                        step_mode: gfx::VertexStepMode::Vertex,
+                        attributes: &[
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x2,
+                                offset: 0,
+                                shader_location: 0,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x2,
+                                offset: 8,
+                                shader_location: 1,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Uint32x4,
+                                offset: 16,
+                                shader_location: 2,
+                            },
+                            gfx::VertexAttribute {
+                                format: gfx::VertexFormat::Float32x4,
+                                offset: 32,
+                                shader_location: 3,
+                            },
+                        ],
+                    }],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: Some(gfx::Face::Back),
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: Some(gfx::BlendState {
+                            color: desc.color_blend,
+                            alpha: desc.alpha_blend,
+                        }),
+                        write_mask: desc.write_mask,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait SkinnedSpriteRenderer<'a> {
+    fn draw_skinned_sprite<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a SkinnedSpritePipeline,
+        uniform_constants: &'a UniformConstants,
+        bone_palette: &'a BonePalette,
+        mesh: &'a SkinnedSpriteMesh<I>,
+        push_constants: &'a PushConstants,
+        index_range: MeshIndexRange,
+    );
+}
+
+impl<'a> SkinnedSpriteRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_skinned_sprite<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a SkinnedSpritePipeline,
+        uniform_constants: &'a UniformConstants,
+        bone_palette: &'a BonePalette,
+        mesh: &'a SkinnedSpriteMesh<I>,
+        push_constants: &'a PushConstants,
+        index_range: MeshIndexRange,
+    ) {
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_bind_group(1, &bone_palette.bind_group, &[]);
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
+        self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.draw_indexed(index_range, 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bone, BoneTransform, Vertex};
+    use galvanic_assert::{matchers::*, *};
+    use roe_math::Vector2;
+
+    // Same computation as skinned_sprite.vert.
+    fn skin(vertex: &SkinnedSpriteVertex, rows: &[[f32; 4]]) -> Vector2<f32> {
+        let position = vertex.position;
+        let (indices, weights) = (vertex.bone_indices, vertex.bone_weights);
+        let mut out = Vector2::new(0., 0.);
+        for i in 0..4 {
+            let (r0, r1) = (
+                rows[indices[i] as usize * 2],
+                rows[indices[i] as usize * 2 + 1],
+            );
+            out += Vector2::new(
+                r0[0] * position[0] + r0[1] * position[1] + r0[2],
+                r1[0] * position[0] + r1[1] * position[1] + r1[2],
+            ) * weights[i];
+        }
+        out
+    }
+
+    #[test]
+    fn matches_cpu_skinning() {
+        let skeleton = Skeleton::new(vec![
+            Bone {
+                name: String::from("root"),
+                parent: None,
+                setup: BoneTransform {
+                    translation: Vector2::new(5., 5.),
+                    ..BoneTransform::default()
+                },
+            },
+            Bone {
+                name: String::from("child"),
+                parent: Some(0),
+                setup: BoneTransform {
+                    translation: Vector2::new(10., 0.),
+                    rotation: 0.3,
+                    ..BoneTransform::default()
+                },
+            },
+        ]);
+        let mesh = SkinnedMesh::bind(
+            &skeleton,
+            &[
+                Vertex::new([5., 5.], [0., 0.]),
+                Vertex::new([15., 5.], [0.5, 0.]),
+                Vertex::new([25., 8.], [1., 1.]),
+            ],
+            &[vec![(0, 1.)], vec![(0, 0.25), (1, 0.75)], vec![(1, 1.)]],
+            vec![0, 1, 2],
+        );
+        let vertices = mesh.gpu_vertices(&skeleton);
+        let weights = vertices[1].bone_weights;
+        expect_that!(&weights, eq([0.75, 0.25, 0., 0.]));
+
+        let mut pose = skeleton.setup_pose();
+        pose.locals[0].rotation = 0.5;
+        pose.locals[1].rotation = -0.4;
+        pose.locals[1].scale = Vector2::new(1.5, 1.5);
+        let world = skeleton.world_transforms(&pose);
+        let rows = bone_palette_rows(&world, &skeleton.inverse_setup_transforms());
+        expect_that!(&rows.len(), eq(4));
+        for (gpu, cpu) in vertices.iter().zip(mesh.deform(&world).iter()) {
+            let expected = cpu.position;
+            expect_that!((skin(gpu, &rows) - Vector2::from(expected)).norm() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn influences_are_limited() {
+        let skeleton = Skeleton::new(
+            (0..5)
+                .map(|i| Bone {
+                    name: format!("bone_{}", i),
+                    parent: None,
+                    setup: BoneTransform::default(),
+                })
+                .collect(),
+        );
+        let mesh = SkinnedMesh::bind(
+            &skeleton,
+            &[Vertex::new([0., 0.], [0., 0.])],
+            &[vec![(0, 0.1), (1, 0.2), (2, 0.3), (3, 0.2), (4, 0.2)]],
+            vec![0],
+        );
+        let vertex = mesh.gpu_vertices(&skeleton)[0];
+        let (indices, weights) = (vertex.bone_indices, vertex.bone_weights);
+        expect_that!(!indices.contains(&0));
+        expect_that!((weights.iter().sum::<f32>() - 1.).abs() < 1e-6);
+    }
+}
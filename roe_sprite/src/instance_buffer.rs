@@ -0,0 +1,161 @@
+use num_traits::Zero;
+
+use roe_math::{HomogeneousMatrix2, HomogeneousMatrix3};
+
+use roe_graphics as gfx;
+
+// Transform and color of a sprite drawn with Renderer::draw_sprite_instanced.
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SpriteInstance {
+    transform: HomogeneousMatrix3<f32>,
+    color: gfx::ColorF32,
+}
+
+impl SpriteInstance {
+    pub fn new(transform: &HomogeneousMatrix2<f32>, color: gfx::ColorF32) -> Self {
+        Self {
+            transform: roe_math::transform2_to_transform3(transform),
+            color,
+        }
+    }
+}
+
+unsafe impl bytemuck::Zeroable for SpriteInstance {
+    fn zeroed() -> Self {
+        Self {
+            transform: HomogeneousMatrix3::zero(),
+            color: gfx::ColorF32::default(),
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for SpriteInstance {}
+
+pub(crate) const INSTANCE_ATTRIBUTES: [gfx::VertexAttribute; 5] = [
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 0,
+        shader_location: 2,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 16,
+        shader_location: 3,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 32,
+        shader_location: 4,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 48,
+        shader_location: 5,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 64,
+        shader_location: 6,
+    },
+];
+
+// GPU buffer of sprite instances. It grows when updated with more instances than it can hold,
+// and is never shrunk.
+#[derive(Debug)]
+pub struct InstanceBuffer {
+    buffer: gfx::Buffer,
+    capacity: usize,
+    len: usize,
+}
+
+impl InstanceBuffer {
+    pub fn new(instance: &gfx::Instance, instances: &[SpriteInstance]) -> Self {
+        let mut buffer = Self::with_capacity(instance, instances.len());
+        buffer.update(instance, instances);
+        buffer
+    }
+
+    pub fn with_capacity(instance: &gfx::Instance, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            buffer: Self::create_buffer(instance, capacity),
+            capacity,
+            len: 0,
+        }
+    }
+
+    // The write is queued and applied before the next submitted commands.
+    pub fn update(&mut self, instance: &gfx::Instance, instances: &[SpriteInstance]) {
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.buffer = Self::create_buffer(instance, self.capacity);
+        }
+        self.len = instances.len();
+        if !instances.is_empty() {
+            instance.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn buffer(&self) -> &gfx::Buffer {
+        &self.buffer
+    }
+
+    fn create_buffer(instance: &gfx::Instance, capacity: usize) -> gfx::Buffer {
+        gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (capacity * std::mem::size_of::<SpriteInstance>()) as gfx::BufferAddress,
+                usage: gfx::BufferUsage::VERTEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+    use roe_math::Vector2;
+
+    #[test]
+    fn instance_layout() {
+        expect_that!(&std::mem::size_of::<SpriteInstance>(), eq(80));
+        let instance = SpriteInstance::new(
+            &roe_math::translation2(&Vector2::new(3., 4.)),
+            gfx::ColorF32::RED,
+        );
+        let floats: [f32; 20] = bytemuck::cast(instance);
+        expect_that!(&floats[12..14].to_vec(), eq(vec![3., 4.]));
+        expect_that!(&floats[16..20].to_vec(), eq(vec![1., 0., 0., 1.]));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn growth() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let sprite = SpriteInstance::new(&HomogeneousMatrix2::identity(), gfx::ColorF32::WHITE);
+        let mut buffer = InstanceBuffer::new(&instance, &[sprite; 3]);
+        expect_that!(&buffer.len(), eq(3));
+        expect_that!(&buffer.capacity(), eq(3));
+        buffer.update(&instance, &[sprite; 5]);
+        expect_that!(&buffer.capacity(), eq(8));
+        buffer.update(&instance, &[]);
+        expect_that!(buffer.is_empty());
+        expect_that!(&buffer.capacity(), eq(8));
+    }
+}
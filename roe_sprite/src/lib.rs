@@ -6,6 +6,75 @@ use roe_math::{HomogeneousMatrix2, HomogeneousMatrix3};
 
 use roe_graphics as gfx;
 
+mod animation_state_machine;
+pub use animation_state_machine::*;
+
+mod bone_ik;
+pub use bone_ik::*;
+
+mod camera;
+pub use camera::*;
+
+mod chunked_batch;
+pub use chunked_batch::*;
+
+mod decal_layer;
+pub use decal_layer::*;
+
+mod distortion_material;
+pub use distortion_material::*;
+
+mod draw_queue;
+pub use draw_queue::*;
+
+mod fog_of_war;
+pub use fog_of_war::*;
+
+mod gpu_skinning;
+pub use gpu_skinning::*;
+
+mod instance_buffer;
+pub use instance_buffer::*;
+
+mod light_probes;
+pub use light_probes::*;
+
+mod pivot;
+pub use pivot::*;
+
+mod render_layer;
+pub use render_layer::*;
+
+mod skeletal_animation;
+pub use skeletal_animation::*;
+
+mod spine;
+pub use spine::*;
+
+mod sprite_animation;
+pub use sprite_animation::*;
+
+mod sprite_batch;
+pub use sprite_batch::*;
+
+mod static_batch;
+pub use static_batch::*;
+
+mod stress;
+pub use stress::*;
+
+mod texture_atlas;
+pub use texture_atlas::*;
+
+mod time_of_day;
+pub use time_of_day::*;
+
+mod verlet_rope;
+pub use verlet_rope::*;
+
+mod weather;
+pub use weather::*;
+
 #[repr(C, packed)]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Vertex {
@@ -32,7 +101,7 @@ unsafe impl bytemuck::Pod for Vertex {}
 
 pub type MeshIndexRange = gfx::MeshIndexRange;
 pub type MeshIndex = gfx::MeshIndex;
-pub type Mesh = gfx::IndexedMesh<Vertex>;
+pub type Mesh<I = MeshIndex> = gfx::IndexedMesh<Vertex, I>;
 
 pub trait MeshTemplates {
     fn rectangle(instance: &gfx::Instance, width: f32, height: f32) -> Self;
@@ -112,6 +181,15 @@ impl PushConstants {
             color,
         }
     }
+
+    // Applies a view projection after the transform, e.g. the one of a camera.
+    pub fn with_view_projection(&self, view_projection: &HomogeneousMatrix2<f32>) -> Self {
+        let transform = self.transform;
+        Self {
+            transform: roe_math::transform2_to_transform3(view_projection) * transform,
+            color: self.color,
+        }
+    }
 }
 
 unsafe impl bytemuck::Zeroable for PushConstants {
@@ -125,32 +203,64 @@ unsafe impl bytemuck::Zeroable for PushConstants {
 
 unsafe impl bytemuck::Pod for PushConstants {}
 
-fn bind_group_layout(instance: &gfx::Instance) -> gfx::BindGroupLayout {
+bitflags::bitflags! {
+    // Optional shader features. Each combination uses a different shader permutation,
+    // generated at build time.
+    #[derive(Default)]
+    pub struct SpriteFeatures: u32 {
+        // Discards fragments with alpha lower than 0.5, e.g. for cutout sprites drawn without
+        // blending.
+        const ALPHA_TEST = 0b01;
+        // The red channel of the sprite texture is an index into a palette texture, 256x1
+        // texels. Requires uniform constants created with UniformConstants::with_palette.
+        const PALETTE = 0b10;
+    }
+}
+
+fn fragment_shader(features: SpriteFeatures) -> gfx::ShaderModuleDescriptor<'static> {
+    match features.bits() {
+        0 => gfx::include_spirv!("shaders/gen/spirv/sprite.frag.0.spv"),
+        1 => gfx::include_spirv!("shaders/gen/spirv/sprite.frag.1.spv"),
+        2 => gfx::include_spirv!("shaders/gen/spirv/sprite.frag.2.spv"),
+        3 => gfx::include_spirv!("shaders/gen/spirv/sprite.frag.3.spv"),
+        bits => unreachable!("No shader variant for the feature bits {:#x}", bits),
+    }
+}
+
+fn texture_entry(binding: u32) -> gfx::BindGroupLayoutEntry {
+    gfx::BindGroupLayoutEntry {
+        binding,
+        visibility: gfx::ShaderStage::FRAGMENT,
+        ty: gfx::BindingType::Texture {
+            multisampled: false,
+            sample_type: gfx::TextureSampleType::Float { filterable: true },
+            view_dimension: gfx::TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+fn bind_group_layout(instance: &gfx::Instance, palette: bool) -> gfx::BindGroupLayout {
+    let mut entries = vec![
+        texture_entry(0),
+        gfx::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: gfx::ShaderStage::FRAGMENT,
+            ty: gfx::BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            },
+            count: None,
+        },
+    ];
+    if palette {
+        entries.push(texture_entry(2));
+    }
     gfx::BindGroupLayout::new(
         instance,
         &gfx::BindGroupLayoutDescriptor {
             label: None,
-            entries: &[
-                gfx::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: gfx::ShaderStage::FRAGMENT,
-                    ty: gfx::BindingType::Texture {
-                        multisampled: false,
-                        sample_type: gfx::TextureSampleType::Float { filterable: true },
-                        view_dimension: gfx::TextureViewDimension::D2,
-                    },
-                    count: None,
-                },
-                gfx::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: gfx::ShaderStage::FRAGMENT,
-                    ty: gfx::BindingType::Sampler {
-                        filtering: true,
-                        comparison: false,
-                    },
-                    count: None,
-                },
-            ],
+            entries: &entries,
         },
     )
 }
@@ -166,22 +276,58 @@ impl UniformConstants {
         texture: &gfx::TextureView,
         sampler: &gfx::Sampler,
     ) -> Self {
-        let layout = bind_group_layout(instance);
+        Self::create(instance, None, texture, sampler, None)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+    ) -> Self {
+        Self::create(instance, label, texture, sampler, None)
+    }
+
+    // For pipelines with the PALETTE feature. The palette is fetched without filtering.
+    pub fn with_palette(
+        instance: &gfx::Instance,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+        palette: &gfx::TextureView,
+    ) -> Self {
+        Self::create(instance, None, texture, sampler, Some(palette))
+    }
+
+    fn create(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
+        palette: Option<&gfx::TextureView>,
+    ) -> Self {
+        let layout = bind_group_layout(instance, palette.is_some());
+        let mut entries = vec![
+            gfx::BindGroupEntry {
+                binding: 0,
+                resource: gfx::BindingResource::TextureView(texture),
+            },
+            gfx::BindGroupEntry {
+                binding: 1,
+                resource: gfx::BindingResource::Sampler(sampler),
+            },
+        ];
+        if let Some(palette) = palette {
+            entries.push(gfx::BindGroupEntry {
+                binding: 2,
+                resource: gfx::BindingResource::TextureView(palette),
+            });
+        }
         let bind_group = gfx::BindGroup::new(
             instance,
             &gfx::BindGroupDescriptor {
-                label: None,
+                label,
                 layout: &layout,
-                entries: &[
-                    gfx::BindGroupEntry {
-                        binding: 0,
-                        resource: gfx::BindingResource::TextureView(texture),
-                    },
-                    gfx::BindGroupEntry {
-                        binding: 1,
-                        resource: gfx::BindingResource::Sampler(sampler),
-                    },
-                ],
+                entries: &entries,
             },
         );
         Self { bind_group }
@@ -190,16 +336,23 @@ impl UniformConstants {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct RenderPipelineDescriptor {
+    pub label: Option<String>,
     pub color_blend: gfx::BlendComponent,
+    // See gfx::emissive_mask_alpha_blend to restrict the bloom to emissive sprites.
     pub alpha_blend: gfx::BlendComponent,
     pub write_mask: gfx::ColorWrite,
     pub color_buffer_format: gfx::CanvasColorBufferFormat,
     pub sample_count: gfx::SampleCount,
+    pub features: SpriteFeatures,
+    // Reads per-instance transforms and colors from an instance buffer, see
+    // Renderer::draw_sprite_instanced.
+    pub instanced: bool,
 }
 
 impl Default for RenderPipelineDescriptor {
     fn default() -> Self {
         Self {
+            label: None,
             color_blend: gfx::BlendComponent {
                 src_factor: gfx::BlendFactor::SrcAlpha,
                 dst_factor: gfx::BlendFactor::OneMinusSrcAlpha,
@@ -213,64 +366,96 @@ impl Default for RenderPipelineDescriptor {
             write_mask: gfx::ColorWrite::ALL,
             color_buffer_format: gfx::CanvasColorBufferFormat::default(),
             sample_count: 1,
+            features: SpriteFeatures::empty(),
+            instanced: false,
         }
     }
 }
 
+const PC_CONVERSION_MEM_OFFSET: u32 = std::mem::size_of::<PushConstants>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + std::mem::size_of::<u32>() as u32;
+
 #[derive(Debug)]
 pub struct RenderPipeline {
     pipeline: gfx::RenderPipeline,
     bind_group_layout: gfx::BindGroupLayout,
     sample_count: gfx::SampleCount,
     color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+    features: SpriteFeatures,
+    instanced: bool,
 }
 
+const VERTEX_ATTRIBUTES: [gfx::VertexAttribute; 2] = [
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x2,
+        offset: 0,
+        shader_location: 0,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x2,
+        offset: 8,
+        shader_location: 1,
+    },
+];
+
+// Pipelines for each descriptor, e.g. one per feature combination.
+pub type RenderPipelineCache = gfx::PipelineCache<RenderPipelineDescriptor, RenderPipeline>;
+
 impl RenderPipeline {
     pub fn new(instance: &gfx::Instance, desc: &RenderPipelineDescriptor) -> Self {
-        let bind_group_layout = bind_group_layout(instance);
+        let bind_group_layout =
+            bind_group_layout(instance, desc.features.contains(SpriteFeatures::PALETTE));
         let pipeline_layout = gfx::PipelineLayout::new(
             instance,
             &gfx::PipelineLayoutDescriptor {
-                label: None,
+                label: desc.label.as_deref(),
                 bind_group_layouts: &[&bind_group_layout],
-                push_constant_ranges: &[gfx::PushConstantRange {
-                    stages: gfx::ShaderStage::VERTEX,
-                    range: 0..std::mem::size_of::<PushConstants>() as u32,
-                }],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_CONVERSION_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_CONVERSION_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
             },
         );
         let vs_module = gfx::ShaderModule::new(
             instance,
-            &gfx::include_spirv!("shaders/gen/spirv/sprite.vert.spv"),
-        );
-        let fs_module = gfx::ShaderModule::new(
-            instance,
-            &gfx::include_spirv!("shaders/gen/spirv/sprite.frag.spv"),
+            &if desc.instanced {
+                gfx::include_spirv!("shaders/gen/spirv/sprite_instanced.vert.spv")
+            } else {
+                gfx::include_spirv!("shaders/gen/spirv/sprite.vert.spv")
+            },
         );
+        let fs_module = gfx::ShaderModule::new(instance, &fragment_shader(desc.features));
+        let vertex_buffer = gfx::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as gfx::BufferAddress,
+            step_mode: gfx::VertexStepMode::Vertex,
+            attributes: &VERTEX_ATTRIBUTES,
+        };
+        let instance_buffer = gfx::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteInstance>() as gfx::BufferAddress,
+            step_mode: gfx::VertexStepMode::Instance,
+            attributes: &INSTANCE_ATTRIBUTES,
+        };
+        let vertex_buffers = if desc.instanced {
+            vec![vertex_buffer, instance_buffer]
+        } else {
+            vec![vertex_buffer]
+        };
         let pipeline = gfx::RenderPipeline::new(
             instance,
             &gfx::RenderPipelineDescriptor {
-                label: None,
+                label: desc.label.as_deref(),
                 layout: Some(&pipeline_layout),
                 vertex: gfx::VertexState {
                     module: &vs_module,
                     entry_point: "main",
-                    buffers: &[gfx::VertexBufferLayout {
-                        array_stride: std::mem::size_of::<Vertex>() as gfx::BufferAddress,
-                        step_mode: gfx::VertexStepMode::Vertex,
-                        attributes: &[
-                            gfx::VertexAttribute {
-                                format: gfx::VertexFormat::Float32x2,
-                                offset: 0,
-                                shader_location: 0,
-                            },
-                            gfx::VertexAttribute {
-                                format: gfx::VertexFormat::Float32x2,
-                                offset: 8,
-                                shader_location: 1,
-                            },
-                        ],
-                    }],
+                    buffers: &vertex_buffers,
                 },
                 primitive: gfx::PrimitiveState {
                     topology: gfx::PrimitiveTopology::TriangleList,
@@ -306,9 +491,26 @@ impl RenderPipeline {
             bind_group_layout,
             sample_count: desc.sample_count,
             color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+            features: desc.features,
+            instanced: desc.instanced,
         }
     }
 
+    pub fn features(&self) -> SpriteFeatures {
+        self.features
+    }
+
+    pub fn is_instanced(&self) -> bool {
+        self.instanced
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
     pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
         gfx::RenderPassRequirements {
             sample_count: self.sample_count,
@@ -319,38 +521,56 @@ impl RenderPipeline {
 }
 
 pub trait Renderer<'a> {
-    fn draw_sprite(
+    fn draw_sprite<I: gfx::MeshIndexType>(
         &mut self,
         pipeline: &'a RenderPipeline,
         uniform_constants: &'a UniformConstants,
-        mesh: &'a Mesh,
+        mesh: &'a Mesh<I>,
         push_constants: &'a PushConstants,
         index_range: MeshIndexRange,
     );
 
-    fn draw_sprite_array<UcIt, MeshIt, PcIt, RangeIt>(
+    fn draw_sprite_array<I, UcIt, MeshIt, PcIt, RangeIt>(
         &mut self,
         pipeline: &'a RenderPipeline,
         draw_commands: UcIt,
     ) where
         UcIt: IntoIterator<Item = (&'a UniformConstants, MeshIt)>,
-        MeshIt: IntoIterator<Item = (&'a Mesh, PcIt)>,
+        I: gfx::MeshIndexType,
+        MeshIt: IntoIterator<Item = (&'a Mesh<I>, PcIt)>,
         PcIt: IntoIterator<Item = (&'a PushConstants, RangeIt)>,
         RangeIt: IntoIterator<Item = gfx::MeshIndexRange>;
+
+    // Draws the mesh once per instance, in a single draw call. Requires an instanced pipeline.
+    // The push constants transform and color are applied after the instance ones.
+    fn draw_sprite_instanced<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        uniform_constants: &'a UniformConstants,
+        mesh: &'a Mesh<I>,
+        push_constants: &'a PushConstants,
+        instances: &'a InstanceBuffer,
+        index_range: MeshIndexRange,
+    );
 }
 
 impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
-    fn draw_sprite(
+    fn draw_sprite<I: gfx::MeshIndexType>(
         &mut self,
         pipeline: &'a RenderPipeline,
         uniform_constants: &'a UniformConstants,
-        mesh: &'a Mesh,
+        mesh: &'a Mesh<I>,
         push_constants: &'a PushConstants,
         index_range: MeshIndexRange,
     ) {
         self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
         self.set_bind_group(0, &uniform_constants.bind_group, &[]);
-        self.set_index_buffer(mesh.index_buffer().slice(..), gfx::IndexFormat::Uint16);
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
         self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
         self.set_push_constants(
             gfx::ShaderStage::VERTEX,
@@ -360,21 +580,27 @@ impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
         self.draw_indexed(index_range, 0, 0..1);
     }
 
-    fn draw_sprite_array<UcIt, MeshIt, PcIt, RangeIt>(
+    fn draw_sprite_array<I, UcIt, MeshIt, PcIt, RangeIt>(
         &mut self,
         pipeline: &'a RenderPipeline,
         draw_commands: UcIt,
     ) where
         UcIt: IntoIterator<Item = (&'a UniformConstants, MeshIt)>,
-        MeshIt: IntoIterator<Item = (&'a Mesh, PcIt)>,
+        I: gfx::MeshIndexType,
+        MeshIt: IntoIterator<Item = (&'a Mesh<I>, PcIt)>,
         PcIt: IntoIterator<Item = (&'a PushConstants, RangeIt)>,
         RangeIt: IntoIterator<Item = gfx::MeshIndexRange>,
     {
         self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
         for (uc, meshes) in draw_commands.into_iter() {
             self.set_bind_group(0, &uc.bind_group, &[]);
             for (mesh, pcs) in meshes.into_iter() {
-                self.set_index_buffer(mesh.index_buffer().slice(..), gfx::IndexFormat::Uint16);
+                self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
                 self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
                 for (pc, ranges) in pcs.into_iter() {
                     self.set_push_constants(
@@ -389,6 +615,40 @@ impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
             }
         }
     }
+
+    fn draw_sprite_instanced<I: gfx::MeshIndexType>(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        uniform_constants: &'a UniformConstants,
+        mesh: &'a Mesh<I>,
+        push_constants: &'a PushConstants,
+        instances: &'a InstanceBuffer,
+        index_range: MeshIndexRange,
+    ) {
+        assert!(
+            pipeline.instanced,
+            "Instanced sprites require an instanced pipeline"
+        );
+        if instances.is_empty() {
+            return;
+        }
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.set_bind_group(0, &uniform_constants.bind_group, &[]);
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
+        self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+        self.set_vertex_buffer(1, instances.buffer().slice(..));
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        self.draw_indexed(index_range, 0, 0..instances.len() as u32);
+    }
 }
 
 #[cfg(test)]
@@ -398,6 +658,13 @@ mod tests {
     use gfx::Canvas;
     use roe_math::{Rotation2, Vector2};
 
+    #[test]
+    fn fragment_shader_variants() {
+        for bits in 0..=SpriteFeatures::all().bits() {
+            let _shader = fragment_shader(SpriteFeatures::from_bits(bits).unwrap());
+        }
+    }
+
     #[test]
     #[serial_test::serial]
     fn creation() {
@@ -405,6 +672,27 @@ mod tests {
         let _pipeline = RenderPipeline::new(&instance, &RenderPipelineDescriptor::default());
     }
 
+    #[test]
+    #[serial_test::serial]
+    fn creation_with_features() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let mut cache = RenderPipelineCache::new();
+        for features in [
+            SpriteFeatures::empty(),
+            SpriteFeatures::ALPHA_TEST,
+            SpriteFeatures::PALETTE,
+            SpriteFeatures::all(),
+        ] {
+            let desc = RenderPipelineDescriptor {
+                features,
+                ..RenderPipelineDescriptor::default()
+            };
+            let pipeline = cache.get_or_insert_with(&desc, |d| RenderPipeline::new(&instance, d));
+            expect_that!(&pipeline.features(), eq(features));
+        }
+        expect_that!(&cache.len(), eq(4));
+    }
+
     #[test]
     #[serial_test::serial]
     fn draw_sprite() {
@@ -412,15 +700,18 @@ mod tests {
         let mut canvas = gfx::CanvasTexture::new(
             &instance,
             &gfx::CanvasTextureDescriptor {
+                label: None,
                 size: gfx::CanvasSize::new(100, 100),
                 sample_count: 1,
                 color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
                     format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
                     usage: gfx::CanvasColorBufferUsage::COPY_SRC,
+                    sample_count: None,
                 }),
                 depth_stencil_buffer_format: None,
             },
-        );
+        )
+        .unwrap();
         let pipeline = RenderPipeline::new(
             &instance,
             &RenderPipelineDescriptor {
@@ -547,20 +838,130 @@ mod tests {
             frame.present();
         }
 
-        let expected_image = image::load(
-            std::io::BufReader::new(std::fs::File::open("data/pictures/test_result.png").unwrap()),
-            image::ImageFormat::Png,
+        let result_image = canvas.color_texture().unwrap().to_image(&instance);
+        gfx::check_golden_image(
+            &result_image,
+            "data/pictures/test_result.png",
+            &gfx::GoldenImageDescriptor::default(),
         )
         .unwrap();
-        assert_that!(
-            &expected_image,
-            is_variant!(image::DynamicImage::ImageRgba8)
-        );
+    }
 
-        if let image::DynamicImage::ImageRgba8(expected_image) = expected_image {
-            let result_image = canvas.color_texture().unwrap().to_image(&instance);
+    #[test]
+    #[serial_test::serial]
+    fn draw_sprite_instanced() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let create_canvas = || {
+            gfx::CanvasTexture::new(
+                &instance,
+                &gfx::CanvasTextureDescriptor {
+                    label: None,
+                    size: gfx::CanvasSize::new(100, 100),
+                    sample_count: 1,
+                    color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
+                        format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
+                        usage: gfx::CanvasColorBufferUsage::COPY_SRC,
+                        sample_count: None,
+                    }),
+                    depth_stencil_buffer_format: None,
+                },
+            )
+            .unwrap()
+        };
+        let desc = RenderPipelineDescriptor {
+            color_buffer_format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
+            ..RenderPipelineDescriptor::default()
+        };
+        let pipeline = RenderPipeline::new(&instance, &desc);
+        let instanced_pipeline = RenderPipeline::new(
+            &instance,
+            &RenderPipelineDescriptor {
+                instanced: true,
+                ..desc
+            },
+        );
+        expect_that!(instanced_pipeline.is_instanced());
+        let texture = gfx::Texture::from_image(
+            &instance,
+            &image::open("data/pictures/gioconda.jpg")
+                .unwrap()
+                .into_rgba8(),
+            gfx::TextureUsage::TEXTURE_BINDING,
+        )
+        .create_view(&gfx::TextureViewDescriptor::default());
+        let uniform_constants = UniformConstants::new(
+            &instance,
+            &texture,
+            &gfx::Sampler::new(&instance, &gfx::SamplerDescriptor::default()),
+        );
+        let mesh = Mesh::quad(
+            &instance,
+            &Vertex::new([0., 0.], [0., 0.]),
+            &Vertex::new([10., 10.], [1., 1.]),
+        );
 
-            expect_that!(&result_image, eq(expected_image));
+        let projection_transform = roe_math::ortographic_projection2(0., 100., 100., 0.);
+        let transforms: Vec<_> = (0..20)
+            .map(|i| {
+                roe_math::translation2(&Vector2::new((i % 5) as f32 * 20., (i / 5) as f32 * 25.))
+                    * roe_math::rotation2(&Rotation2::new(i as f32 * 0.3))
+            })
+            .collect();
+        let color = |i: usize| gfx::ColorF32 {
+            r: 1.,
+            g: i as f32 / 20.,
+            b: 0.5,
+            a: 1.,
+        };
+        let push_constants: Vec<_> = transforms
+            .iter()
+            .enumerate()
+            .map(|(i, t)| PushConstants::new(&(projection_transform * t), color(i)))
+            .collect();
+        let instances: Vec<_> = transforms
+            .iter()
+            .enumerate()
+            .map(|(i, t)| SpriteInstance::new(t, color(i)))
+            .collect();
+        let instance_buffer = InstanceBuffer::new(&instance, &instances);
+        let global_push_constants = PushConstants::new(&projection_transform, gfx::ColorF32::WHITE);
+
+        let mut images = Vec::new();
+        for instanced in [false, true] {
+            let mut canvas = create_canvas();
+            let frame = canvas.current_frame().unwrap().unwrap();
+            let mut cmd_sequence = gfx::CommandSequence::new(&instance);
+            {
+                let mut rpass = cmd_sequence.begin_render_pass(
+                    &frame,
+                    &pipeline.render_pass_requirements(),
+                    &gfx::RenderPassOperations::default(),
+                );
+                if instanced {
+                    rpass.draw_sprite_instanced(
+                        &instanced_pipeline,
+                        &uniform_constants,
+                        &mesh,
+                        &global_push_constants,
+                        &instance_buffer,
+                        0..mesh.index_count(),
+                    );
+                } else {
+                    for pc in &push_constants {
+                        rpass.draw_sprite(
+                            &pipeline,
+                            &uniform_constants,
+                            &mesh,
+                            pc,
+                            0..mesh.index_count(),
+                        );
+                    }
+                }
+            }
+            cmd_sequence.submit(&instance);
+            frame.present();
+            images.push(canvas.color_texture().unwrap().to_image(&instance));
         }
+        expect_that!(&images[1], eq(images[0].clone()));
     }
 }
@@ -0,0 +1,196 @@
+use super::AmbientLight;
+
+use roe_graphics::ColorF32;
+use roe_math::Vector2;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LightProbeGrid2Descriptor {
+    // Position of the first probe, in world units.
+    pub origin: Vector2<f32>,
+    // Distance between neighboring probes.
+    pub spacing: Vector2<f32>,
+    // Number of probes along each axis.
+    pub size: Vector2<u32>,
+    // Initial value of all probes.
+    pub light: AmbientLight,
+}
+
+impl Default for LightProbeGrid2Descriptor {
+    fn default() -> Self {
+        Self {
+            origin: Vector2::zeros(),
+            spacing: Vector2::new(64., 64.),
+            size: Vector2::new(16, 16),
+            light: AmbientLight::default(),
+        }
+    }
+}
+
+// Grid of ambient light values, sampled by position so that sprites pick up the local lighting,
+// e.g. darker in caves or tinted near lava, without evaluating every light per sprite. The
+// probes can be baked once or updated at runtime. Positions outside the grid take the value of
+// the closest edge.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LightProbeGrid2 {
+    origin: Vector2<f32>,
+    spacing: Vector2<f32>,
+    size: Vector2<u32>,
+    // Row by row, starting from the origin.
+    probes: Vec<AmbientLight>,
+}
+
+impl LightProbeGrid2 {
+    pub fn new(desc: &LightProbeGrid2Descriptor) -> Self {
+        let size = Vector2::new(desc.size.x.max(1), desc.size.y.max(1));
+        Self {
+            origin: desc.origin,
+            spacing: desc.spacing,
+            size,
+            probes: vec![desc.light; (size.x * size.y) as usize],
+        }
+    }
+
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    pub fn probe_position(&self, x: u32, y: u32) -> Vector2<f32> {
+        self.origin
+            + self
+                .spacing
+                .component_mul(&Vector2::new(x as f32, y as f32))
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        if x < self.size.x && y < self.size.y {
+            Some((y * self.size.x + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn probe(&self, x: u32, y: u32) -> Option<&AmbientLight> {
+        self.index(x, y).map(|i| &self.probes[i])
+    }
+
+    // Returns false if the probe is outside the grid.
+    pub fn set_probe(&mut self, x: u32, y: u32, light: AmbientLight) -> bool {
+        match self.index(x, y) {
+            Some(i) => {
+                self.probes[i] = light;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Sets every probe from its position, e.g. by accumulating the static lights in range.
+    pub fn bake<F: FnMut(&Vector2<f32>) -> AmbientLight>(&mut self, mut f: F) {
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let light = f(&self.probe_position(x, y));
+                self.probes[(y * self.size.x + x) as usize] = light;
+            }
+        }
+    }
+
+    // Bilinear interpolation of the 4 probes around the position.
+    pub fn sample(&self, position: &Vector2<f32>) -> AmbientLight {
+        let cell = |p: f32, origin: f32, spacing: f32, size: u32| {
+            let t = if spacing > 0. {
+                ((p - origin) / spacing).clamp(0., (size - 1) as f32)
+            } else {
+                0.
+            };
+            let i = (t.floor() as u32).min(size.saturating_sub(2));
+            (i, (i + 1).min(size - 1), t - i as f32)
+        };
+        let (x0, x1, tx) = cell(position.x, self.origin.x, self.spacing.x, self.size.x);
+        let (y0, y1, ty) = cell(position.y, self.origin.y, self.spacing.y, self.size.y);
+        let probe = |x, y| &self.probes[(y * self.size.x + x) as usize];
+        let bottom = probe(x0, y0).lerp(probe(x1, y0), tx);
+        let top = probe(x0, y1).lerp(probe(x1, y1), tx);
+        bottom.lerp(&top, ty)
+    }
+
+    // Color multiplied by the local ambient light, e.g. for the sprite push constants.
+    pub fn lit_color(&self, color: &ColorF32, position: &Vector2<f32>) -> ColorF32 {
+        let light = self.sample(position).scaled_color();
+        ColorF32 {
+            r: color.r * light.r,
+            g: color.g * light.g,
+            b: color.b * light.b,
+            a: color.a * light.a,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn grid() -> LightProbeGrid2 {
+        let mut grid = LightProbeGrid2::new(&LightProbeGrid2Descriptor {
+            origin: Vector2::new(10., 0.),
+            spacing: Vector2::new(10., 20.),
+            size: Vector2::new(3, 2),
+            light: AmbientLight::new(ColorF32::WHITE, 0.),
+        });
+        grid.bake(|p| AmbientLight::new(ColorF32::WHITE, p.x / 10. + p.y / 20.));
+        grid
+    }
+
+    #[test]
+    fn probes() {
+        let mut grid = grid();
+        expect_that!(&grid.size(), eq(Vector2::new(3, 2)));
+        expect_that!(&grid.probe_position(2, 1), eq(Vector2::new(30., 20.)));
+        expect_that!(&grid.probe(2, 1).unwrap().intensity, close_to(4., 1e-6));
+        expect_that!(grid.probe(3, 0).is_none());
+        expect_that!(grid.set_probe(0, 0, AmbientLight::new(ColorF32::BLACK, 1.)));
+        expect_that!(!grid.set_probe(0, 2, AmbientLight::default()));
+        expect_that!(&grid.probe(0, 0).unwrap().color, eq(ColorF32::BLACK));
+    }
+
+    #[test]
+    fn sampling() {
+        let grid = grid();
+        // The intensity is linear in the position, so it is interpolated exactly.
+        let intensity = |x, y| grid.sample(&Vector2::new(x, y)).intensity;
+        expect_that!(&intensity(10., 0.), close_to(1., 1e-5));
+        expect_that!(&intensity(25., 5.), close_to(2.75, 1e-5));
+        expect_that!(&intensity(30., 20.), close_to(4., 1e-5));
+        // Clamped outside the grid.
+        expect_that!(&intensity(-100., -100.), close_to(1., 1e-5));
+        expect_that!(&intensity(100., 100.), close_to(4., 1e-5));
+
+        let color = grid.lit_color(
+            &ColorF32 {
+                r: 1.,
+                g: 0.5,
+                b: 0.,
+                a: 0.5,
+            },
+            &Vector2::new(15., 0.),
+        );
+        expect_that!(&color.r, close_to(1.5, 1e-5));
+        expect_that!(&color.g, close_to(0.75, 1e-5));
+        expect_that!(&color.b, close_to(0., 1e-5));
+        expect_that!(&color.a, close_to(0.5, 1e-5));
+    }
+
+    #[test]
+    fn single_probe() {
+        let grid = LightProbeGrid2::new(&LightProbeGrid2Descriptor {
+            size: Vector2::new(1, 0),
+            light: AmbientLight::new(ColorF32::WHITE, 0.5),
+            ..LightProbeGrid2Descriptor::default()
+        });
+        expect_that!(&grid.size(), eq(Vector2::new(1, 1)));
+        expect_that!(
+            &grid.sample(&Vector2::new(100., -3.)).intensity,
+            close_to(0.5, 1e-6)
+        );
+    }
+}
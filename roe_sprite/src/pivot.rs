@@ -0,0 +1,143 @@
+use roe_math::{HomogeneousMatrix2, Rotation2, Vector2, Vector3};
+
+// Point of the sprite rectangle the sprite is positioned, rotated and scaled about.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum SpriteAnchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    // Usually the feet of characters in top-down games.
+    Bottom,
+    BottomRight,
+    // Relative to the sprite size, (0, 0) being the top left corner and (1, 1) the bottom right
+    // corner.
+    Relative(Vector2<f32>),
+    // In pixels from the top left corner.
+    Absolute(Vector2<f32>),
+}
+
+impl SpriteAnchor {
+    // Pivot position in the sprite rectangle, which goes from the origin to the size.
+    pub fn pivot(&self, size: &Vector2<f32>) -> Vector2<f32> {
+        let relative = match self {
+            Self::TopLeft => Vector2::new(0., 0.),
+            Self::Top => Vector2::new(0.5, 0.),
+            Self::TopRight => Vector2::new(1., 0.),
+            Self::Left => Vector2::new(0., 0.5),
+            Self::Center => Vector2::new(0.5, 0.5),
+            Self::Right => Vector2::new(1., 0.5),
+            Self::BottomLeft => Vector2::new(0., 1.),
+            Self::Bottom => Vector2::new(0.5, 1.),
+            Self::BottomRight => Vector2::new(1., 1.),
+            Self::Relative(relative) => *relative,
+            Self::Absolute(pivot) => return *pivot,
+        };
+        relative.component_mul(size)
+    }
+}
+
+// Placement of a sprite of the given size. The pivot ends up at the position, and the sprite
+// is rotated and scaled about it rather than about the mesh origin.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SpriteTransform {
+    pub position: Vector2<f32>,
+    pub rotation: Rotation2<f32>,
+    pub scale: Vector2<f32>,
+    pub anchor: SpriteAnchor,
+    pub size: Vector2<f32>,
+}
+
+impl SpriteTransform {
+    pub fn new(position: Vector2<f32>, size: Vector2<f32>, anchor: SpriteAnchor) -> Self {
+        Self {
+            position,
+            rotation: Rotation2::identity(),
+            scale: Vector2::new(1., 1.),
+            anchor,
+            size,
+        }
+    }
+
+    pub fn pivot(&self) -> Vector2<f32> {
+        self.anchor.pivot(&self.size)
+    }
+
+    // Model transform for meshes spanning the sprite rectangle, e.g. MeshTemplates::rectangle
+    // or StaticSprite.
+    pub fn matrix(&self) -> HomogeneousMatrix2<f32> {
+        roe_math::translation2(&self.position)
+            * roe_math::rotation2(&self.rotation)
+            * roe_math::scale2(&self.scale)
+            * roe_math::translation2(&-self.pivot())
+    }
+
+    // World position of a point of the sprite rectangle.
+    pub fn transform_point(&self, point: &Vector2<f32>) -> Vector2<f32> {
+        let p = self.matrix() * Vector3::new(point.x, point.y, 1.);
+        Vector2::new(p.x / p.z, p.y / p.z)
+    }
+
+    // Origin used for Y sorting: the point of the sprite rectangle at the given anchor, in
+    // world coordinates. The pivot itself is usually fine, but e.g. a character anchored at its
+    // center can be sorted by its feet with SpriteAnchor::Bottom.
+    pub fn sort_origin(&self, anchor: &SpriteAnchor) -> Vector2<f32> {
+        self.transform_point(&anchor.pivot(&self.size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn approx_eq(a: &Vector2<f32>, b: &Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    #[test]
+    fn anchor_pivot() {
+        let size = Vector2::new(32., 48.);
+        expect_that!(
+            &SpriteAnchor::Bottom.pivot(&size),
+            eq(Vector2::new(16., 48.))
+        );
+        expect_that!(
+            &SpriteAnchor::Relative(Vector2::new(0.25, 0.5)).pivot(&size),
+            eq(Vector2::new(8., 24.))
+        );
+        expect_that!(
+            &SpriteAnchor::Absolute(Vector2::new(3., 4.)).pivot(&size),
+            eq(Vector2::new(3., 4.))
+        );
+    }
+
+    #[test]
+    fn rotation_about_pivot() {
+        let mut transform = SpriteTransform::new(
+            Vector2::new(100., 50.),
+            Vector2::new(20., 10.),
+            SpriteAnchor::Center,
+        );
+        transform.rotation = Rotation2::new(std::f32::consts::PI * 0.5);
+        transform.scale = Vector2::new(2., 2.);
+        // The pivot stays in place.
+        expect_that!(approx_eq(
+            &transform.transform_point(&Vector2::new(10., 5.)),
+            &Vector2::new(100., 50.)
+        ));
+        // The right edge center is rotated down, at twice the distance.
+        expect_that!(approx_eq(
+            &transform.transform_point(&Vector2::new(20., 5.)),
+            &Vector2::new(100., 70.)
+        ));
+        expect_that!(approx_eq(
+            &transform.sort_origin(&SpriteAnchor::Bottom),
+            &Vector2::new(90., 50.)
+        ));
+    }
+}
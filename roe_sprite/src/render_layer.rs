@@ -0,0 +1,118 @@
+use super::LayerMask;
+
+use std::ops::Range;
+
+// Named draw queue layers, drawn in declaration order. Each one spans a range of layer indices,
+// so that finer layers can be defined relative to it, e.g. RenderLayer::World.index() + 1 for
+// characters drawn over the terrain.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum RenderLayer {
+    Background,
+    World,
+    Fx,
+    Ui,
+}
+
+impl RenderLayer {
+    pub const ALL: [Self; 4] = [Self::Background, Self::World, Self::Fx, Self::Ui];
+
+    const SPAN: i32 = 16;
+
+    // Lowest layer index of the range.
+    pub fn index(&self) -> i32 {
+        *self as i32 * Self::SPAN
+    }
+
+    pub fn range(&self) -> Range<i32> {
+        self.index()..self.index() + Self::SPAN
+    }
+
+    // Named layer whose range contains the layer index.
+    pub fn containing(layer: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.range().contains(&layer))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Background => "background",
+            Self::World => "world",
+            Self::Fx => "fx",
+            Self::Ui => "ui",
+        }
+    }
+
+    // E.g. for layers specified in data files.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.name() == name)
+    }
+
+    // Every layer index of the range, e.g. for the mask of a camera.
+    pub fn mask(&self) -> LayerMask {
+        LayerMask::range(self.range())
+    }
+}
+
+impl From<RenderLayer> for i32 {
+    fn from(layer: RenderLayer) -> Self {
+        layer.index()
+    }
+}
+
+impl From<RenderLayer> for LayerMask {
+    fn from(layer: RenderLayer) -> Self {
+        layer.mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use crate::{DrawQueue, DrawSortMode};
+    use roe_math::Vector2;
+
+    #[test]
+    fn ranges() {
+        expect_that!(&RenderLayer::Background.index(), eq(0));
+        expect_that!(&RenderLayer::Ui.range(), eq(48..64));
+        expect_that!(
+            &RenderLayer::containing(RenderLayer::World.index() + 3),
+            eq(Some(RenderLayer::World))
+        );
+        expect_that!(&RenderLayer::containing(-1), eq(None));
+        expect_that!(&RenderLayer::containing(64), eq(None));
+    }
+
+    #[test]
+    fn names() {
+        for layer in RenderLayer::ALL {
+            expect_that!(&RenderLayer::from_name(layer.name()), eq(Some(layer)));
+        }
+        expect_that!(&RenderLayer::from_name("foreground"), eq(None));
+    }
+
+    #[test]
+    fn masks() {
+        let mask = LayerMask::from(RenderLayer::World) | RenderLayer::Fx.into();
+        expect_that!(mask.contains(RenderLayer::World.index()));
+        expect_that!(mask.contains(RenderLayer::Fx.index() + 15));
+        expect_that!(!mask.contains(RenderLayer::Background.index()));
+        expect_that!(!mask.contains(RenderLayer::Ui.index()));
+    }
+
+    #[test]
+    fn draw_queue_layers() {
+        let mut queue = DrawQueue::new(DrawSortMode::Submission);
+        queue.push(RenderLayer::Ui, Vector2::zeros(), "hud");
+        queue.push(RenderLayer::World.index() + 1, Vector2::zeros(), "player");
+        queue.push(RenderLayer::Background, Vector2::zeros(), "sky");
+        queue.push(RenderLayer::World, Vector2::zeros(), "terrain");
+        queue.sort();
+        expect_that!(
+            &queue.items().map(|item| item.value).collect::<Vec<_>>(),
+            eq(vec!["sky", "terrain", "player", "hud"])
+        );
+        expect_that!(&queue.layer_items(RenderLayer::Ui).len(), eq(1));
+    }
+}
@@ -0,0 +1,35 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 inColor;
+layout(location = 1) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uNoiseTex;
+layout(set = 0, binding = 1) uniform sampler uNoiseTexSampler;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 80) vec2 scrollOffset0;
+    vec2 scrollOffset1;
+    float noiseScale;
+    float strength;
+    float edgeFade;
+} pushConstant;
+
+void main() {
+    // Two noise layers scrolling in different directions, so that the pattern changes over time
+    // instead of just moving.
+    vec2 coords = inTexCoords * pushConstant.noiseScale;
+    vec2 noise0 = texture(sampler2D(uNoiseTex, uNoiseTexSampler),
+        coords + pushConstant.scrollOffset0).rg;
+    vec2 noise1 = texture(sampler2D(uNoiseTex, uNoiseTexSampler),
+        coords * 1.7 + pushConstant.scrollOffset1).rg;
+    vec2 offset = clamp((noise0 + noise1 - 1.) * pushConstant.strength, -1., 1.);
+
+    // Fades out towards the sprite edges, so that the distorted area has no visible seams.
+    vec2 edge = min(inTexCoords, 1. - inTexCoords);
+    float fade = pushConstant.edgeFade > 0.
+        ? smoothstep(0., pushConstant.edgeFade, min(edge.x, edge.y))
+        : 1.;
+
+    // Encoded as expected by the distortion pass, 128 being no offset.
+    outColor = vec4((offset * 127. + 128.) / 255., 0., inColor.a * fade);
+}
@@ -0,0 +1,35 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#ifndef ALPHA_TEST_THRESHOLD
+#define ALPHA_TEST_THRESHOLD 0.5
+#endif
+
+layout(location = 0) in vec4 inColor;
+layout(location = 1) in vec2 inTexCoords;
+layout(location = 0) out vec4 outColor;
+layout(set = 0, binding = 0) uniform texture2D uColorTex;
+layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+#ifdef ENABLE_PALETTE
+layout(set = 0, binding = 2) uniform texture2D uPaletteTex;
+#endif
+layout(push_constant) uniform PushConstant {
+    layout(offset = 80) uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
+
+void main() {
+    vec4 texColor = texture(sampler2D(uColorTex, uColorTexSampler), inTexCoords);
+#ifdef ENABLE_PALETTE
+    int paletteIndex = int(texColor.r * 255. + 0.5);
+    texColor = texelFetch(sampler2D(uPaletteTex, uColorTexSampler), ivec2(paletteIndex, 0), 0);
+#endif
+    outColor = inColor * texColor;
+#ifdef ALPHA_TEST
+    if (outColor.a < ALPHA_TEST_THRESHOLD) {
+        discard;
+    }
+#endif
+    outColor.rgb = convertColor(outColor.rgb, pushConstant.colorConversion);
+}
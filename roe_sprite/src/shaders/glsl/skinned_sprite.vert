@@ -0,0 +1,35 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// Must match MAX_SKINNING_BONES.
+#define MAX_BONES 128
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inTexCoords;
+layout(location = 2) in uvec4 inBoneIndices;
+layout(location = 3) in vec4 inBoneWeights;
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec2 outTexCoords;
+// Two rows of a 2D affine transform per bone.
+layout(set = 1, binding = 0) uniform BonePalette {
+    vec4 rows[2 * MAX_BONES];
+} uBones;
+layout(push_constant) uniform PushConstant {
+    mat4 transform;
+    vec4 color;
+} pushConstant;
+
+void main() {
+    vec3 bindPosition = vec3(inPosition, 1.);
+    vec2 position = vec2(0.);
+    for (int i = 0; i < 4; ++i) {
+        uint bone = inBoneIndices[i];
+        vec2 bonePosition = vec2(
+            dot(uBones.rows[2 * bone].xyz, bindPosition),
+            dot(uBones.rows[2 * bone + 1].xyz, bindPosition));
+        position += inBoneWeights[i] * bonePosition;
+    }
+    gl_Position = pushConstant.transform * vec4(position, 0., 1.);
+    outColor = pushConstant.color;
+    outTexCoords = inTexCoords;
+}
@@ -0,0 +1,25 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inTexCoords;
+// Columns of the instance transform.
+layout(location = 2) in vec4 inTransform0;
+layout(location = 3) in vec4 inTransform1;
+layout(location = 4) in vec4 inTransform2;
+layout(location = 5) in vec4 inTransform3;
+layout(location = 6) in vec4 inColor;
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec2 outTexCoords;
+layout(push_constant) uniform PushConstant {
+    mat4 transform;
+    vec4 color;
+} pushConstant;
+
+void main() {
+    mat4 transform = pushConstant.transform
+        * mat4(inTransform0, inTransform1, inTransform2, inTransform3);
+    gl_Position = transform * vec4(inPosition.x, inPosition.y, 0., 1.);
+    outColor = pushConstant.color * inColor;
+    outTexCoords = inTexCoords;
+}
@@ -0,0 +1,592 @@
+use super::{MeshIndex, Vertex};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Rotation2, Vector2, Vector3};
+
+use std::time::Duration;
+
+// Local transform of a bone relative to its parent. Rotations are in radians, clockwise on
+// screen since y points down.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoneTransform {
+    pub translation: Vector2<f32>,
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+}
+
+impl BoneTransform {
+    pub fn matrix(&self) -> HomogeneousMatrix2<f32> {
+        roe_math::translation2(&self.translation)
+            * roe_math::rotation2(&Rotation2::new(self.rotation))
+            * roe_math::scale2(&self.scale)
+    }
+}
+
+impl Default for BoneTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vector2::new(0., 0.),
+            rotation: 0.,
+            scale: Vector2::new(1., 1.),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    // Transform in the setup pose, the one the mesh weights are bound in.
+    pub setup: BoneTransform,
+}
+
+// Bone hierarchy. Parents always come before their children.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Skeleton {
+    bones: Vec<Bone>,
+}
+
+impl Skeleton {
+    pub fn new(bones: Vec<Bone>) -> Self {
+        for (index, bone) in bones.iter().enumerate() {
+            if let Some(parent) = bone.parent {
+                assert!(
+                    parent < index,
+                    "Bone parents must come before their children"
+                );
+            }
+        }
+        Self { bones }
+    }
+
+    pub fn bones(&self) -> &[Bone] {
+        &self.bones
+    }
+
+    pub fn bone_index(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|b| b.name == name)
+    }
+
+    pub fn setup_pose(&self) -> SkeletonPose {
+        SkeletonPose {
+            locals: self.bones.iter().map(|b| b.setup).collect(),
+        }
+    }
+
+    // Bone to skeleton space transforms of the pose.
+    pub fn world_transforms(&self, pose: &SkeletonPose) -> Vec<HomogeneousMatrix2<f32>> {
+        assert!(
+            pose.locals.len() == self.bones.len(),
+            "The pose doesn't match the skeleton"
+        );
+        let mut world: Vec<HomogeneousMatrix2<f32>> = Vec::with_capacity(self.bones.len());
+        for (bone, local) in self.bones.iter().zip(pose.locals.iter()) {
+            let matrix = match bone.parent {
+                Some(parent) => world[parent] * local.matrix(),
+                None => local.matrix(),
+            };
+            world.push(matrix);
+        }
+        world
+    }
+
+    // Skeleton to bone space transforms of the setup pose, also known as inverse bind
+    // matrices.
+    pub fn inverse_setup_transforms(&self) -> Vec<HomogeneousMatrix2<f32>> {
+        self.world_transforms(&self.setup_pose())
+            .iter()
+            .map(|m| {
+                m.try_inverse()
+                    .expect("Setup bone transforms must be invertible")
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkeletonPose {
+    // Indexed like the skeleton bones.
+    pub locals: Vec<BoneTransform>,
+}
+
+impl SkeletonPose {
+    // Interpolates the local transforms, t being the weight of the other pose.
+    pub fn blend(&self, other: &Self, t: f32) -> Self {
+        assert!(
+            self.locals.len() == other.locals.len(),
+            "The poses belong to different skeletons"
+        );
+        Self {
+            locals: self
+                .locals
+                .iter()
+                .zip(other.locals.iter())
+                .map(|(a, b)| BoneTransform {
+                    translation: a.translation.lerp(&b.translation, t),
+                    rotation: a.rotation + (b.rotation - a.rotation) * t,
+                    scale: a.scale.lerp(&b.scale, t),
+                })
+                .collect(),
+        }
+    }
+}
+
+pub trait CurveValue: Copy {
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl CurveValue for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl CurveValue for Vector2<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+// Interpolation from a keyframe to the next one.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CurveInterpolation {
+    Linear,
+    // Keeps the keyframe value until the next keyframe.
+    Stepped,
+    // Cubic bezier from (0, 0) to (1, 1) with the given control points, x being the time and y
+    // the progress, as in CSS and most animation tools.
+    Bezier([f32; 4]),
+}
+
+impl CurveInterpolation {
+    pub fn progress(&self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::Stepped => 0.,
+            Self::Bezier([x1, y1, x2, y2]) => {
+                let bezier = |a: f32, b: f32, s: f32| {
+                    let r = 1. - s;
+                    3. * r * r * s * a + 3. * r * s * s * b + s * s * s
+                };
+                // x is monotonic in s for control points in [0, 1].
+                let (mut low, mut high) = (0f32, 1f32);
+                for _ in 0..24 {
+                    let mid = (low + high) * 0.5;
+                    if bezier(*x1, *x2, mid) < t {
+                        low = mid;
+                    } else {
+                        high = mid;
+                    }
+                }
+                bezier(*y1, *y2, (low + high) * 0.5)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Keyframe<T> {
+    // In seconds.
+    pub time: f32,
+    pub value: T,
+    pub interpolation: CurveInterpolation,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnimationCurve<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: CurveValue> AnimationCurve<T> {
+    pub fn new(mut keyframes: Vec<Keyframe<T>>) -> Self {
+        assert!(!keyframes.is_empty(), "Curves need at least one keyframe");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        Self { keyframes }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().unwrap().time
+    }
+
+    // Values before the first and after the last keyframe are held.
+    pub fn sample(&self, time: f32) -> T {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0].value;
+        }
+        let current = &self.keyframes[next - 1];
+        match self.keyframes.get(next) {
+            Some(next) => {
+                let t = (time - current.time) / (next.time - current.time);
+                current
+                    .value
+                    .lerp(&next.value, current.interpolation.progress(t))
+            }
+            None => current.value,
+        }
+    }
+}
+
+// Offsets from the setup pose of a bone: rotation and translation are added, scale is
+// multiplied.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BoneTimeline {
+    pub bone: usize,
+    pub rotation: Option<AnimationCurve<f32>>,
+    pub translation: Option<AnimationCurve<Vector2<f32>>>,
+    pub scale: Option<AnimationCurve<Vector2<f32>>>,
+}
+
+impl BoneTimeline {
+    pub fn new(bone: usize) -> Self {
+        Self {
+            bone,
+            rotation: None,
+            translation: None,
+            scale: None,
+        }
+    }
+
+    fn duration(&self) -> f32 {
+        let durations = [
+            self.rotation.as_ref().map(|c| c.duration()),
+            self.translation.as_ref().map(|c| c.duration()),
+            self.scale.as_ref().map(|c| c.duration()),
+        ];
+        durations.iter().flatten().fold(0., |a, b| f32::max(a, *b))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    pub timelines: Vec<BoneTimeline>,
+}
+
+impl AnimationClip {
+    // Time of the last keyframe, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.timelines
+            .iter()
+            .fold(0., |a, timeline| f32::max(a, timeline.duration()))
+    }
+
+    // Bones without a timeline keep the setup pose.
+    pub fn sample(&self, skeleton: &Skeleton, time: f32) -> SkeletonPose {
+        let mut pose = skeleton.setup_pose();
+        for timeline in self.timelines.iter() {
+            let local = &mut pose.locals[timeline.bone];
+            if let Some(rotation) = &timeline.rotation {
+                local.rotation += rotation.sample(time);
+            }
+            if let Some(translation) = &timeline.translation {
+                local.translation += translation.sample(time);
+            }
+            if let Some(scale) = &timeline.scale {
+                local.scale.component_mul_assign(&scale.sample(time));
+            }
+        }
+        pose
+    }
+}
+
+// Playback state of a clip.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkeletalAnimationPlayer {
+    pub speed: f32,
+    pub looping: bool,
+    time: f32,
+}
+
+impl SkeletalAnimationPlayer {
+    pub fn new(looping: bool) -> Self {
+        Self {
+            speed: 1.,
+            looping,
+            time: 0.,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.;
+    }
+
+    pub fn is_finished(&self, clip: &AnimationClip) -> bool {
+        !self.looping && self.time >= clip.duration()
+    }
+
+    pub fn update(&mut self, dt: Duration, clip: &AnimationClip) {
+        let duration = clip.duration();
+        self.time += dt.as_secs_f32() * self.speed;
+        if duration <= 0. {
+            self.time = 0.;
+        } else if self.looping {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.clamp(0., duration);
+        }
+    }
+
+    pub fn pose(&self, skeleton: &Skeleton, clip: &AnimationClip) -> SkeletonPose {
+        clip.sample(skeleton, self.time)
+    }
+}
+
+// Bone affecting a vertex, with the vertex position in the bone space.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BoneInfluence {
+    pub bone: usize,
+    pub weight: f32,
+    pub position: Vector2<f32>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkinnedVertex {
+    pub texture_coordinates: Vector2<f32>,
+    // Weights should add up to 1.
+    pub influences: Vec<BoneInfluence>,
+}
+
+// Sprite mesh deformed by the bones of a skeleton.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkinnedMesh {
+    pub vertices: Vec<SkinnedVertex>,
+    pub indices: Vec<MeshIndex>,
+}
+
+impl SkinnedMesh {
+    // Binds a mesh laid out in skeleton space to the skeleton setup pose. Each vertex has a list
+    // of bone and weight pairs.
+    pub fn bind(
+        skeleton: &Skeleton,
+        vertices: &[Vertex],
+        weights: &[Vec<(usize, f32)>],
+        indices: Vec<MeshIndex>,
+    ) -> Self {
+        assert!(
+            vertices.len() == weights.len(),
+            "Each vertex needs a list of weights"
+        );
+        let inverse_setup = skeleton.inverse_setup_transforms();
+        let vertices = vertices
+            .iter()
+            .zip(weights.iter())
+            .map(|(vertex, weights)| {
+                let position = vertex.position;
+                let texture_coordinates = vertex.texture_coordinates;
+                SkinnedVertex {
+                    texture_coordinates: Vector2::from(texture_coordinates),
+                    influences: weights
+                        .iter()
+                        .map(|(bone, weight)| BoneInfluence {
+                            bone: *bone,
+                            weight: *weight,
+                            position: transform_point(
+                                &inverse_setup[*bone],
+                                &Vector2::from(position),
+                            ),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Self { vertices, indices }
+    }
+
+    // Vertices in skeleton space for the given bone transforms.
+    pub fn deform(&self, world_transforms: &[HomogeneousMatrix2<f32>]) -> Vec<Vertex> {
+        self.vertices
+            .iter()
+            .map(|vertex| {
+                let position =
+                    vertex
+                        .influences
+                        .iter()
+                        .fold(Vector2::new(0., 0.), |acc, influence| {
+                            acc + transform_point(
+                                &world_transforms[influence.bone],
+                                &influence.position,
+                            ) * influence.weight
+                        });
+                Vertex::new(position, vertex.texture_coordinates)
+            })
+            .collect()
+    }
+
+    // Mesh in the setup pose, to be updated with update_dynamic_mesh.
+    pub fn create_dynamic_mesh(
+        &self,
+        instance: &gfx::Instance,
+        skeleton: &Skeleton,
+    ) -> gfx::DynamicMesh<Vertex> {
+        let world = skeleton.world_transforms(&skeleton.setup_pose());
+        gfx::DynamicMesh::new(instance, &self.deform(&world), &self.indices)
+    }
+
+    // Usually called once per frame, after updating the pose.
+    pub fn update_dynamic_mesh(
+        &self,
+        instance: &gfx::Instance,
+        mesh: &mut gfx::DynamicMesh<Vertex>,
+        world_transforms: &[HomogeneousMatrix2<f32>],
+    ) {
+        mesh.update_vertices(instance, &self.deform(world_transforms));
+    }
+}
+
+fn transform_point(matrix: &HomogeneousMatrix2<f32>, point: &Vector2<f32>) -> Vector2<f32> {
+    let p = matrix * Vector3::new(point.x, point.y, 1.);
+    Vector2::new(p.x, p.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    use std::f32::consts::FRAC_PI_2;
+
+    fn approx_eq(a: &Vector2<f32>, b: &Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    // An arm with an upper bone at the origin and a lower bone 10 units to the right.
+    fn arm() -> Skeleton {
+        Skeleton::new(vec![
+            Bone {
+                name: String::from("upper"),
+                parent: None,
+                setup: BoneTransform::default(),
+            },
+            Bone {
+                name: String::from("lower"),
+                parent: Some(0),
+                setup: BoneTransform {
+                    translation: Vector2::new(10., 0.),
+                    ..BoneTransform::default()
+                },
+            },
+        ])
+    }
+
+    #[test]
+    fn world_transforms() {
+        let skeleton = arm();
+        let mut pose = skeleton.setup_pose();
+        pose.locals[0].rotation = FRAC_PI_2;
+        let world = skeleton.world_transforms(&pose);
+        // The lower bone rotates with its parent.
+        expect_that!(approx_eq(
+            &transform_point(&world[1], &Vector2::new(5., 0.)),
+            &Vector2::new(0., 15.)
+        ));
+        expect_that!(&skeleton.bone_index("lower"), eq(Some(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bone parents must come before their children")]
+    fn invalid_bone_order() {
+        Skeleton::new(vec![Bone {
+            name: String::from("root"),
+            parent: Some(0),
+            setup: BoneTransform::default(),
+        }]);
+    }
+
+    #[test]
+    fn curves() {
+        let curve = AnimationCurve::new(vec![
+            Keyframe {
+                time: 1.,
+                value: 10.,
+                interpolation: CurveInterpolation::Stepped,
+            },
+            Keyframe {
+                time: 0.,
+                value: 0.,
+                interpolation: CurveInterpolation::Linear,
+            },
+            Keyframe {
+                time: 2.,
+                value: 20.,
+                interpolation: CurveInterpolation::Linear,
+            },
+        ]);
+        expect_that!(&curve.sample(-1.), eq(0.));
+        expect_that!(&curve.sample(0.5), eq(5.));
+        expect_that!(&curve.sample(1.5), eq(10.));
+        expect_that!(&curve.sample(3.), eq(20.));
+        expect_that!(&curve.duration(), eq(2.));
+
+        let ease = CurveInterpolation::Bezier([0.42, 0., 0.58, 1.]);
+        expect_that!((ease.progress(0.5) - 0.5).abs() < 1e-3);
+        expect_that!(ease.progress(0.25) < 0.25);
+    }
+
+    #[test]
+    fn clip_playback() {
+        let skeleton = arm();
+        let mut timeline = BoneTimeline::new(1);
+        timeline.rotation = Some(AnimationCurve::new(vec![
+            Keyframe {
+                time: 0.,
+                value: 0.,
+                interpolation: CurveInterpolation::Linear,
+            },
+            Keyframe {
+                time: 2.,
+                value: FRAC_PI_2,
+                interpolation: CurveInterpolation::Linear,
+            },
+        ]));
+        let clip = AnimationClip {
+            name: String::from("bend"),
+            timelines: vec![timeline],
+        };
+        let mut player = SkeletalAnimationPlayer::new(true);
+        player.update(Duration::from_secs_f32(2.5), &clip);
+        expect_that!((player.time() - 0.5).abs() < 1e-4);
+
+        let mut player = SkeletalAnimationPlayer::new(false);
+        player.update(Duration::from_secs(3), &clip);
+        expect_that!(player.is_finished(&clip));
+        let pose = player.pose(&skeleton, &clip);
+        expect_that!(&pose.locals[1].rotation, eq(FRAC_PI_2));
+        expect_that!(&pose.locals[1].translation, eq(Vector2::new(10., 0.)));
+    }
+
+    #[test]
+    fn deformation() {
+        let skeleton = arm();
+        let vertices = [
+            Vertex::new([5., 0.], [0., 0.]),
+            Vertex::new([10., 0.], [0.5, 0.]),
+            Vertex::new([15., 0.], [1., 0.]),
+        ];
+        let weights = vec![vec![(0, 1.)], vec![(0, 0.5), (1, 0.5)], vec![(1, 1.)]];
+        let mesh = SkinnedMesh::bind(&skeleton, &vertices, &weights, vec![0, 1, 2]);
+
+        // The setup pose gives back the original vertices.
+        let setup = mesh.deform(&skeleton.world_transforms(&skeleton.setup_pose()));
+        expect_that!(&setup, eq(vertices.to_vec()));
+
+        let mut pose = skeleton.setup_pose();
+        pose.locals[1].rotation = FRAC_PI_2;
+        let deformed = mesh.deform(&skeleton.world_transforms(&pose));
+        let position = |v: &Vertex| {
+            let p = v.position;
+            Vector2::from(p)
+        };
+        expect_that!(approx_eq(&position(&deformed[0]), &Vector2::new(5., 0.)));
+        expect_that!(approx_eq(&position(&deformed[1]), &Vector2::new(10., 0.)));
+        expect_that!(approx_eq(&position(&deformed[2]), &Vector2::new(10., 5.)));
+    }
+}
@@ -0,0 +1,559 @@
+use super::{
+    AnimationClip, AnimationCurve, Bone, BoneInfluence, BoneTimeline, BoneTransform,
+    CurveInterpolation, Keyframe, MeshIndex, Skeleton, SkinnedMesh, SkinnedVertex,
+};
+
+use roe_math::{HomogeneousMatrix2, Vector2, Vector3};
+
+use serde::Deserialize;
+
+use std::{collections::HashMap, path::Path};
+
+#[derive(Debug)]
+pub enum SpineError {
+    IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    UnknownBone(String),
+    // Slot and attachment names, and description.
+    InvalidAttachment(String, String, String),
+}
+
+impl std::fmt::Display for SpineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::JsonError(e) => write!(f, "JSON error ({})", e),
+            Self::UnknownBone(name) => write!(f, "Unknown bone {}", name),
+            Self::InvalidAttachment(slot, attachment, description) => write!(
+                f,
+                "Invalid attachment {} in slot {} ({})",
+                attachment, slot, description
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::JsonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SpineError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<serde_json::Error> for SpineError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::JsonError(e)
+    }
+}
+
+// Subset of the Spine 3.8 JSON format read by the importer.
+#[derive(Deserialize)]
+struct RawSkeletonData {
+    bones: Vec<RawBone>,
+    #[serde(default)]
+    slots: Vec<RawSlot>,
+    #[serde(default)]
+    skins: Vec<RawSkin>,
+    #[serde(default)]
+    animations: HashMap<String, RawAnimation>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBone {
+    name: String,
+    parent: Option<String>,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "default_scale")]
+    scale_x: f32,
+    #[serde(default = "default_scale")]
+    scale_y: f32,
+}
+
+fn default_scale() -> f32 {
+    1.
+}
+
+#[derive(Deserialize)]
+struct RawSlot {
+    name: String,
+    bone: String,
+    attachment: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawSkin {
+    name: String,
+    #[serde(default)]
+    attachments: HashMap<String, HashMap<String, RawAttachment>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAttachment {
+    #[serde(rename = "type", default = "default_attachment_type")]
+    attachment_type: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "default_scale")]
+    scale_x: f32,
+    #[serde(default = "default_scale")]
+    scale_y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    uvs: Vec<f32>,
+    #[serde(default)]
+    triangles: Vec<u32>,
+    #[serde(default)]
+    vertices: Vec<f32>,
+}
+
+fn default_attachment_type() -> String {
+    String::from("region")
+}
+
+#[derive(Deserialize)]
+struct RawAnimation {
+    #[serde(default)]
+    bones: HashMap<String, RawBoneTimelines>,
+}
+
+#[derive(Deserialize)]
+struct RawBoneTimelines {
+    #[serde(default)]
+    rotate: Vec<RawKeyframe>,
+    #[serde(default)]
+    translate: Vec<RawKeyframe>,
+    #[serde(default)]
+    scale: Vec<RawKeyframe>,
+}
+
+#[derive(Deserialize)]
+struct RawKeyframe {
+    #[serde(default)]
+    time: f32,
+    #[serde(default)]
+    angle: f32,
+    x: Option<f32>,
+    y: Option<f32>,
+    // "stepped", or the first bezier control point coordinate followed by c2, c3 and c4.
+    curve: Option<serde_json::Value>,
+    #[serde(default)]
+    c2: f32,
+    #[serde(default = "default_scale")]
+    c3: f32,
+    #[serde(default = "default_scale")]
+    c4: f32,
+}
+
+impl RawKeyframe {
+    fn interpolation(&self) -> CurveInterpolation {
+        match &self.curve {
+            Some(serde_json::Value::String(s)) if s == "stepped" => CurveInterpolation::Stepped,
+            Some(serde_json::Value::Number(c1)) => CurveInterpolation::Bezier([
+                c1.as_f64().unwrap_or(0.) as f32,
+                self.c2,
+                self.c3,
+                self.c4,
+            ]),
+            _ => CurveInterpolation::Linear,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpineAttachment {
+    pub slot: String,
+    pub name: String,
+    pub mesh: SkinnedMesh,
+}
+
+// Skeleton, default skin meshes and animations of a Spine export. Coordinates are converted to
+// y pointing down, which mirrors rotations. Shearing, constraints, deform timelines and slot
+// timelines are not imported.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpineSkeletonData {
+    pub skeleton: Skeleton,
+    // The setup attachment of each slot, in drawing order. Texture coordinates are relative to
+    // the attachment region in the atlas.
+    pub attachments: Vec<SpineAttachment>,
+    pub clips: Vec<AnimationClip>,
+}
+
+impl SpineSkeletonData {
+    pub fn from_json_str(s: &str) -> Result<Self, SpineError> {
+        let raw: RawSkeletonData = serde_json::from_str(s)?;
+        let skeleton = import_skeleton(&raw.bones)?;
+        let attachments = import_attachments(&raw, &skeleton)?;
+        let mut clips = raw
+            .animations
+            .iter()
+            .map(|(name, animation)| import_clip(name, animation, &skeleton))
+            .collect::<Result<Vec<_>, _>>()?;
+        clips.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self {
+            skeleton,
+            attachments,
+            clips,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SpineError> {
+        Self::from_json_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.iter().find(|c| c.name == name)
+    }
+}
+
+fn bone_index(skeleton: &Skeleton, name: &str) -> Result<usize, SpineError> {
+    skeleton
+        .bone_index(name)
+        .ok_or_else(|| SpineError::UnknownBone(String::from(name)))
+}
+
+fn import_skeleton(raw_bones: &[RawBone]) -> Result<Skeleton, SpineError> {
+    let mut bones: Vec<Bone> = Vec::with_capacity(raw_bones.len());
+    for raw in raw_bones {
+        let parent = match &raw.parent {
+            Some(parent) => Some(
+                bones
+                    .iter()
+                    .position(|b| &b.name == parent)
+                    .ok_or_else(|| SpineError::UnknownBone(parent.clone()))?,
+            ),
+            None => None,
+        };
+        bones.push(Bone {
+            name: raw.name.clone(),
+            parent,
+            setup: BoneTransform {
+                translation: Vector2::new(raw.x, -raw.y),
+                rotation: -raw.rotation.to_radians(),
+                scale: Vector2::new(raw.scale_x, raw.scale_y),
+            },
+        });
+    }
+    Ok(Skeleton::new(bones))
+}
+
+fn import_attachments(
+    raw: &RawSkeletonData,
+    skeleton: &Skeleton,
+) -> Result<Vec<SpineAttachment>, SpineError> {
+    let skin = match raw.skins.iter().find(|s| s.name == "default") {
+        Some(skin) => skin,
+        None => return Ok(Vec::new()),
+    };
+    let mut attachments = Vec::new();
+    for slot in raw.slots.iter() {
+        let name = match &slot.attachment {
+            Some(name) => name,
+            None => continue,
+        };
+        let attachment = match skin.attachments.get(&slot.name).and_then(|a| a.get(name)) {
+            Some(attachment) => attachment,
+            None => continue,
+        };
+        let bone = bone_index(skeleton, &slot.bone)?;
+        let invalid = |description: &str| {
+            SpineError::InvalidAttachment(
+                slot.name.clone(),
+                name.clone(),
+                String::from(description),
+            )
+        };
+        let mesh = match attachment.attachment_type.as_str() {
+            "region" => import_region(attachment, bone),
+            "mesh" => import_mesh(attachment, bone, skeleton.bones().len()).map_err(invalid)?,
+            _ => continue,
+        };
+        attachments.push(SpineAttachment {
+            slot: slot.name.clone(),
+            name: name.clone(),
+            mesh,
+        });
+    }
+    Ok(attachments)
+}
+
+// Rectangle centered on the attachment position.
+fn import_region(raw: &RawAttachment, bone: usize) -> SkinnedMesh {
+    let transform = BoneTransform {
+        translation: Vector2::new(raw.x, -raw.y),
+        rotation: -raw.rotation.to_radians(),
+        scale: Vector2::new(raw.scale_x, raw.scale_y),
+    }
+    .matrix();
+    let (hw, hh) = (raw.width * 0.5, raw.height * 0.5);
+    let corners = [
+        (Vector2::new(-hw, -hh), Vector2::new(0., 0.)),
+        (Vector2::new(-hw, hh), Vector2::new(0., 1.)),
+        (Vector2::new(hw, hh), Vector2::new(1., 1.)),
+        (Vector2::new(hw, -hh), Vector2::new(1., 0.)),
+    ];
+    SkinnedMesh {
+        vertices: corners
+            .iter()
+            .map(|(position, texture_coordinates)| SkinnedVertex {
+                texture_coordinates: *texture_coordinates,
+                influences: vec![BoneInfluence {
+                    bone,
+                    weight: 1.,
+                    position: transform_point(&transform, position),
+                }],
+            })
+            .collect(),
+        indices: vec![0, 1, 3, 3, 1, 2],
+    }
+}
+
+// Spine stores unweighted meshes as positions in the slot bone space, and weighted meshes as
+// a bone count per vertex followed by bone index, x, y and weight for each bone.
+fn import_mesh(
+    raw: &RawAttachment,
+    slot_bone: usize,
+    bone_count: usize,
+) -> Result<SkinnedMesh, &'static str> {
+    if !raw.uvs.len().is_multiple_of(2) {
+        return Err("odd uv count");
+    }
+    let vertex_count = raw.uvs.len() / 2;
+    let mut influences = Vec::with_capacity(vertex_count);
+    if raw.vertices.len() == raw.uvs.len() {
+        for position in raw.vertices.chunks(2) {
+            influences.push(vec![BoneInfluence {
+                bone: slot_bone,
+                weight: 1.,
+                position: Vector2::new(position[0], -position[1]),
+            }]);
+        }
+    } else {
+        let mut values = raw.vertices.iter();
+        for _ in 0..vertex_count {
+            let count = *values.next().ok_or("missing vertex weights")? as usize;
+            let mut vertex = Vec::with_capacity(count);
+            for _ in 0..count {
+                let mut next = || values.next().copied().ok_or("missing vertex weights");
+                let (bone, x, y, weight) = (next()? as usize, next()?, next()?, next()?);
+                if bone >= bone_count {
+                    return Err("invalid bone index");
+                }
+                vertex.push(BoneInfluence {
+                    bone,
+                    weight,
+                    position: Vector2::new(x, -y),
+                });
+            }
+            influences.push(vertex);
+        }
+        if values.next().is_some() {
+            return Err("too many vertex weights");
+        }
+    }
+    if raw.triangles.iter().any(|i| *i as usize >= vertex_count) {
+        return Err("invalid triangle index");
+    }
+    // Mirroring y flips the winding, so triangles are reversed.
+    let indices = raw
+        .triangles
+        .chunks(3)
+        .flat_map(|t| t.iter().rev().map(|i| *i as MeshIndex))
+        .collect();
+    Ok(SkinnedMesh {
+        vertices: raw
+            .uvs
+            .chunks(2)
+            .zip(influences)
+            .map(|(uv, influences)| SkinnedVertex {
+                texture_coordinates: Vector2::new(uv[0], uv[1]),
+                influences,
+            })
+            .collect(),
+        indices,
+    })
+}
+
+fn import_clip(
+    name: &str,
+    raw: &RawAnimation,
+    skeleton: &Skeleton,
+) -> Result<AnimationClip, SpineError> {
+    let mut timelines = Vec::new();
+    for (bone_name, raw_timelines) in raw.bones.iter() {
+        let mut timeline = BoneTimeline::new(bone_index(skeleton, bone_name)?);
+        timeline.rotation = curve(&raw_timelines.rotate, |k| -k.angle.to_radians());
+        timeline.translation = curve(&raw_timelines.translate, |k| {
+            Vector2::new(k.x.unwrap_or(0.), -k.y.unwrap_or(0.))
+        });
+        timeline.scale = curve(&raw_timelines.scale, |k| {
+            Vector2::new(k.x.unwrap_or(1.), k.y.unwrap_or(1.))
+        });
+        timelines.push(timeline);
+    }
+    timelines.sort_by_key(|t| t.bone);
+    Ok(AnimationClip {
+        name: String::from(name),
+        timelines,
+    })
+}
+
+fn curve<T, F>(keyframes: &[RawKeyframe], value: F) -> Option<AnimationCurve<T>>
+where
+    T: super::CurveValue,
+    F: Fn(&RawKeyframe) -> T,
+{
+    if keyframes.is_empty() {
+        return None;
+    }
+    Some(AnimationCurve::new(
+        keyframes
+            .iter()
+            .map(|k| Keyframe {
+                time: k.time,
+                value: value(k),
+                interpolation: k.interpolation(),
+            })
+            .collect(),
+    ))
+}
+
+fn transform_point(matrix: &HomogeneousMatrix2<f32>, point: &Vector2<f32>) -> Vector2<f32> {
+    let p = matrix * Vector3::new(point.x, point.y, 1.);
+    Vector2::new(p.x, p.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const SKELETON: &str = r#"{
+        "skeleton": {"spine": "3.8.99"},
+        "bones": [
+            {"name": "root"},
+            {"name": "arm", "parent": "root", "x": 10, "y": 20, "rotation": 90}
+        ],
+        "slots": [
+            {"name": "body", "bone": "root", "attachment": "body"},
+            {"name": "sleeve", "bone": "arm", "attachment": "sleeve"},
+            {"name": "hidden", "bone": "root"}
+        ],
+        "skins": [
+            {
+                "name": "default",
+                "attachments": {
+                    "body": {"body": {"width": 20, "height": 40}},
+                    "sleeve": {
+                        "sleeve": {
+                            "type": "mesh",
+                            "uvs": [0, 0, 1, 0, 1, 1],
+                            "triangles": [0, 1, 2],
+                            "vertices": [1, 1, 0, 2, 1, 2, 0, 5, 0, 0.5, 1, 5, 0, 0.5, 1, 1, 4, 4, 1]
+                        }
+                    }
+                }
+            }
+        ],
+        "animations": {
+            "wave": {
+                "bones": {
+                    "arm": {
+                        "rotate": [
+                            {"curve": 0.25, "c3": 0.75},
+                            {"time": 0.5, "angle": 45, "curve": "stepped"},
+                            {"time": 1, "angle": 0}
+                        ],
+                        "translate": [{"time": 0, "x": 2, "y": 3}]
+                    }
+                }
+            }
+        }
+    }"#;
+
+    fn approx_eq(a: &Vector2<f32>, b: &Vector2<f32>) -> bool {
+        (a - b).norm() < 1e-4
+    }
+
+    #[test]
+    fn skeleton() {
+        let data = SpineSkeletonData::from_json_str(SKELETON).unwrap();
+        let arm = &data.skeleton.bones()[1];
+        expect_that!(&arm.parent, eq(Some(0)));
+        expect_that!(&arm.setup.translation, eq(Vector2::new(10., -20.)));
+        expect_that!((arm.setup.rotation + std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn attachments() {
+        let data = SpineSkeletonData::from_json_str(SKELETON).unwrap();
+        let names: Vec<_> = data.attachments.iter().map(|a| a.name.as_str()).collect();
+        expect_that!(&names, eq(vec!["body", "sleeve"]));
+
+        let world = data.skeleton.world_transforms(&data.skeleton.setup_pose());
+        let body = data.attachments[0].mesh.deform(&world);
+        let corner = body[0].position;
+        expect_that!(&Vector2::from(corner), eq(Vector2::new(-10., -20.)));
+
+        let sleeve = &data.attachments[1].mesh;
+        expect_that!(&sleeve.vertices[1].influences.len(), eq(2));
+        expect_that!(&sleeve.indices, eq(vec![2, 1, 0]));
+        // The first vertex is at (0, 2) in the arm space, with the arm rotated by 90 degrees
+        // counterclockwise in Spine coordinates.
+        let deformed = sleeve.deform(&world);
+        let position = deformed[0].position;
+        expect_that!(approx_eq(&Vector2::from(position), &Vector2::new(8., -20.)));
+    }
+
+    #[test]
+    fn animations() {
+        let data = SpineSkeletonData::from_json_str(SKELETON).unwrap();
+        let clip = data.clip("wave").unwrap();
+        expect_that!(&clip.duration(), eq(1.));
+        let timeline = &clip.timelines[0];
+        let rotation = timeline.rotation.as_ref().unwrap();
+        expect_that!(
+            &rotation.keyframes()[0].interpolation,
+            eq(CurveInterpolation::Bezier([0.25, 0., 0.75, 1.]))
+        );
+        expect_that!((rotation.sample(0.75) + std::f32::consts::FRAC_PI_4).abs() < 1e-6);
+        let pose = clip.sample(&data.skeleton, 0.);
+        expect_that!(&pose.locals[1].translation, eq(Vector2::new(12., -23.)));
+    }
+
+    #[test]
+    fn invalid_data() {
+        let unknown_parent = SKELETON.replace("\"parent\": \"root\"", "\"parent\": \"spine\"");
+        expect_that!(
+            &SpineSkeletonData::from_json_str(&unknown_parent),
+            is_variant!(Result::Err)
+        );
+        let bad_triangles = SKELETON.replace("[0, 1, 2]", "[0, 1, 7]");
+        expect_that!(
+            &SpineSkeletonData::from_json_str(&bad_triangles),
+            is_variant!(Result::Err)
+        );
+    }
+}
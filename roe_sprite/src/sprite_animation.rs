@@ -0,0 +1,283 @@
+use super::{Mesh, MeshIndexRange, TextureAtlasLayout, Vertex};
+
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpriteAnimationFrame {
+    // Name of the region in the atlas.
+    pub region: String,
+    pub duration: Duration,
+}
+
+// Sequence of atlas regions, e.g. the frames of a walk cycle.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpriteAnimationClip {
+    pub name: String,
+    pub frames: Vec<SpriteAnimationFrame>,
+}
+
+fn numeric_suffix(name: &str) -> Option<u64> {
+    let prefix_len = name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    name[prefix_len..].parse().ok()
+}
+
+impl SpriteAnimationClip {
+    pub fn new<S: Into<String>>(name: S, frames: Vec<SpriteAnimationFrame>) -> Self {
+        Self {
+            name: name.into(),
+            frames,
+        }
+    }
+
+    // The regions whose name starts with the prefix, e.g. "player_idle_" for "player_idle_0",
+    // "player_idle_1" and so on, ordered by their numeric suffix, all with the same duration.
+    pub fn from_atlas_prefix<S: Into<String>>(
+        name: S,
+        layout: &TextureAtlasLayout,
+        prefix: &str,
+        frame_duration: Duration,
+    ) -> Self {
+        let mut regions: Vec<_> = layout
+            .region_names()
+            .into_iter()
+            .filter(|region| region.starts_with(prefix))
+            .collect();
+        regions.sort_by_key(|region| (numeric_suffix(region), *region));
+        Self::new(
+            name,
+            regions
+                .into_iter()
+                .map(|region| SpriteAnimationFrame {
+                    region: String::from(region),
+                    duration: frame_duration,
+                })
+                .collect(),
+        )
+    }
+
+    // In seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames
+            .iter()
+            .map(|frame| frame.duration.as_secs_f32())
+            .sum()
+    }
+
+    // Index of the frame displayed at the given time, from the start of the clip.
+    pub fn frame_at(&self, time: f32) -> usize {
+        let mut end = 0.;
+        for (i, frame) in self.frames.iter().enumerate() {
+            end += frame.duration.as_secs_f32();
+            if time < end {
+                return i;
+            }
+        }
+        self.frames.len().saturating_sub(1)
+    }
+
+    // Mesh with a rectangle per frame, in order. Draw the current frame with
+    // SpriteAnimationPlayer::index_range. Frames missing from the atlas are empty.
+    pub fn build_mesh(&self, instance: &gfx::Instance, layout: &TextureAtlasLayout) -> Mesh {
+        let mut vertices = Vec::with_capacity(self.frames.len() * 4);
+        let mut indices = Vec::with_capacity(self.frames.len() * 6);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let first = i as u16 * 4;
+            vertices.extend_from_slice(
+                &layout
+                    .vertices(&frame.region)
+                    .unwrap_or([Vertex::new([0., 0.], [0., 0.]); 4]),
+            );
+            indices.extend([0, 1, 3, 3, 1, 2].iter().map(|i| first + i));
+        }
+        Mesh::new(instance, &vertices, &indices)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SpriteAnimationMode {
+    #[default]
+    Loop,
+    // Plays forward then backward. The first and last frames are shown twice as long, once in
+    // each direction.
+    PingPong,
+    // Stops on the last frame.
+    Once,
+}
+
+// Playback state of a sprite animation clip.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpriteAnimationPlayer {
+    pub speed: f32,
+    pub mode: SpriteAnimationMode,
+    // In the ping pong mode, goes up to twice the clip duration.
+    time: f32,
+    frame: usize,
+}
+
+impl SpriteAnimationPlayer {
+    pub fn new(mode: SpriteAnimationMode) -> Self {
+        Self {
+            speed: 1.,
+            mode,
+            time: 0.,
+            frame: 0,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.;
+        self.frame = 0;
+    }
+
+    pub fn is_finished(&self, clip: &SpriteAnimationClip) -> bool {
+        self.mode == SpriteAnimationMode::Once && self.time >= clip.duration()
+    }
+
+    // Returns true if the displayed frame changed.
+    pub fn update(&mut self, dt: Duration, clip: &SpriteAnimationClip) -> bool {
+        let duration = clip.duration();
+        self.time += dt.as_secs_f32() * self.speed;
+        let clip_time = if duration <= 0. {
+            self.time = 0.;
+            0.
+        } else {
+            match self.mode {
+                SpriteAnimationMode::Loop => {
+                    self.time = self.time.rem_euclid(duration);
+                    self.time
+                }
+                SpriteAnimationMode::PingPong => {
+                    self.time = self.time.rem_euclid(2. * duration);
+                    if self.time < duration {
+                        self.time
+                    } else {
+                        2. * duration - self.time
+                    }
+                }
+                SpriteAnimationMode::Once => {
+                    self.time = self.time.clamp(0., duration);
+                    self.time
+                }
+            }
+        };
+        let frame = clip.frame_at(clip_time);
+        let changed = frame != self.frame;
+        self.frame = frame;
+        changed
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    pub fn frame<'a>(&self, clip: &'a SpriteAnimationClip) -> Option<&'a SpriteAnimationFrame> {
+        clip.frames.get(self.frame)
+    }
+
+    // Indices of the current frame in the mesh built with SpriteAnimationClip::build_mesh.
+    pub fn index_range(&self) -> MeshIndexRange {
+        let first = self.frame as u32 * 6;
+        first..first + 6
+    }
+
+    // Texture coordinates of the current frame corners, for custom meshes. See
+    // AtlasRegion::texture_coordinates.
+    pub fn texture_coordinates(
+        &self,
+        clip: &SpriteAnimationClip,
+        layout: &TextureAtlasLayout,
+    ) -> Option<[Vector2<f32>; 4]> {
+        self.frame(clip)
+            .and_then(|frame| layout.texture_coordinates(&frame.region))
+    }
+}
+
+impl Default for SpriteAnimationPlayer {
+    fn default() -> Self {
+        Self::new(SpriteAnimationMode::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AtlasRegion;
+    use galvanic_assert::{matchers::*, *};
+
+    fn clip() -> SpriteAnimationClip {
+        let mut layout = TextureAtlasLayout::new(Vector2::new(64, 16));
+        for i in [0, 1, 2, 10] {
+            layout
+                .insert(format!("walk_{}", i), AtlasRegion::new(i * 4, 0, 4, 16))
+                .unwrap();
+        }
+        layout
+            .insert("idle", AtlasRegion::new(60, 0, 4, 16))
+            .unwrap();
+        SpriteAnimationClip::from_atlas_prefix("walk", &layout, "walk_", Duration::from_millis(100))
+    }
+
+    fn frames(player: &mut SpriteAnimationPlayer, clip: &SpriteAnimationClip) -> Vec<usize> {
+        (0..10)
+            .map(|_| {
+                player.update(Duration::from_millis(100), clip);
+                player.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clip_from_atlas() {
+        let clip = clip();
+        let regions: Vec<_> = clip.frames.iter().map(|f| f.region.as_str()).collect();
+        expect_that!(&regions, eq(vec!["walk_0", "walk_1", "walk_2", "walk_10"]));
+        expect_that!(&clip.duration(), close_to(0.4, 1e-6));
+        expect_that!(&clip.frame_at(0.25), eq(2));
+        expect_that!(&clip.frame_at(10.), eq(3));
+    }
+
+    #[test]
+    fn playback_modes() {
+        let clip = clip();
+        // Halfway through the frames, to avoid rounding at the boundaries.
+        let start = |mode| {
+            let mut player = SpriteAnimationPlayer::new(mode);
+            player.update(Duration::from_millis(50), &clip);
+            player
+        };
+
+        let mut player = start(SpriteAnimationMode::Loop);
+        expect_that!(
+            &frames(&mut player, &clip),
+            eq(vec![1, 2, 3, 0, 1, 2, 3, 0, 1, 2])
+        );
+        expect_that!(!player.is_finished(&clip));
+
+        let mut player = start(SpriteAnimationMode::PingPong);
+        expect_that!(
+            &frames(&mut player, &clip),
+            eq(vec![1, 2, 3, 3, 2, 1, 0, 0, 1, 2])
+        );
+
+        let mut player = start(SpriteAnimationMode::Once);
+        expect_that!(
+            &frames(&mut player, &clip),
+            eq(vec![1, 2, 3, 3, 3, 3, 3, 3, 3, 3])
+        );
+        expect_that!(player.is_finished(&clip));
+        expect_that!(&player.index_range(), eq(18..24));
+        expect_that!(&player.frame(&clip).unwrap().region.as_str(), eq("walk_10"));
+
+        player.restart();
+        expect_that!(&player.frame_index(), eq(0));
+        expect_that!(!player.update(Duration::from_millis(50), &clip));
+        expect_that!(player.update(Duration::from_millis(100), &clip));
+    }
+}
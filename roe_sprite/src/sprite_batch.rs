@@ -0,0 +1,640 @@
+use super::{
+    bind_group_layout, fragment_shader, PushConstants, RenderPipelineDescriptor, SpriteFeatures,
+    UniformConstants, PC_CONVERSION_MEM_OFFSET, PC_SIZE,
+};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Vector2, Vector3};
+
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SpriteBatchVertex {
+    pub position: [f32; 2],
+    pub texture_coordinates: [f32; 2],
+    pub color: gfx::ColorF32,
+}
+
+unsafe impl bytemuck::Zeroable for SpriteBatchVertex {
+    fn zeroed() -> Self {
+        Self {
+            position: [0., 0.],
+            texture_coordinates: [0., 0.],
+            color: gfx::ColorF32::default(),
+        }
+    }
+}
+
+unsafe impl bytemuck::Pod for SpriteBatchVertex {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BatchedSprite {
+    // Top left corner of the sprite rectangle, before the transform.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    // Texture coordinates of the top left and bottom right corners.
+    pub texture_coordinates: [Vector2<f32>; 2],
+    pub color: gfx::ColorF32,
+    pub transform: HomogeneousMatrix2<f32>,
+}
+
+impl BatchedSprite {
+    pub fn new(position: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Self {
+            position,
+            size,
+            texture_coordinates: [Vector2::new(0., 0.), Vector2::new(1., 1.)],
+            color: gfx::ColorF32::WHITE,
+            transform: HomogeneousMatrix2::identity(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SpriteBatchOrder {
+    // Sprites are drawn in the order they were pushed. Consecutive sprites with the same texture
+    // share a draw call.
+    #[default]
+    Submission,
+    // Sprites are grouped by texture, with a single draw call per texture. Sprites with the
+    // same texture keep the submission order. Only suitable when sprites with different
+    // textures don't overlap, or aren't blended.
+    Texture,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpriteBatchDescriptor {
+    pub order: SpriteBatchOrder,
+    // Sprites the GPU buffers can hold before having to grow.
+    pub capacity: usize,
+}
+
+impl Default for SpriteBatchDescriptor {
+    fn default() -> Self {
+        Self {
+            order: SpriteBatchOrder::default(),
+            capacity: 1024,
+        }
+    }
+}
+
+// Sprites drawn with the same texture, in a single draw call.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SpriteBatchDraw {
+    // Index into the uniform constants passed to SpriteBatchRenderer::draw_sprite_batch.
+    pub texture: usize,
+    pub index_range: gfx::MeshIndexRange,
+}
+
+#[derive(Debug)]
+struct SpriteBatchBuffers {
+    vertex_buffer: gfx::Buffer,
+    index_buffer: gfx::Buffer,
+}
+
+// Collects the sprites of a frame on the CPU, with their transforms applied to the vertices,
+// and uploads them into a single vertex and index buffer, drawn with as few draw calls as the
+// textures allow. Meant to be cleared and refilled every frame, e.g. for particles or dynamic
+// tilemaps. Static sprites are better baked once with StaticSpriteBatch.
+//
+// The buffers grow when uploading more sprites than they can hold, and are never shrunk.
+#[derive(Debug)]
+pub struct SpriteBatch {
+    order: SpriteBatchOrder,
+    textures: Vec<usize>,
+    vertices: Vec<SpriteBatchVertex>,
+    sorted_vertices: Vec<SpriteBatchVertex>,
+    draws: Vec<SpriteBatchDraw>,
+    buffers: Option<SpriteBatchBuffers>,
+    capacity: usize,
+    uploaded: bool,
+}
+
+impl SpriteBatch {
+    pub fn new(desc: &SpriteBatchDescriptor) -> Self {
+        Self {
+            order: desc.order,
+            textures: Vec::new(),
+            vertices: Vec::new(),
+            sorted_vertices: Vec::new(),
+            draws: Vec::new(),
+            buffers: None,
+            capacity: desc.capacity.max(1),
+            uploaded: false,
+        }
+    }
+
+    pub fn order(&self) -> SpriteBatchOrder {
+        self.order
+    }
+
+    pub fn set_order(&mut self, order: SpriteBatchOrder) {
+        self.order = order;
+        self.uploaded = false;
+    }
+
+    // The texture is an index into the uniform constants passed when drawing.
+    pub fn push(&mut self, texture: usize, sprite: &BatchedSprite) {
+        let [t0, t1] = sprite.texture_coordinates;
+        let (p0, p1) = (sprite.position, sprite.position + sprite.size);
+        let corners = [
+            (Vector2::new(p0.x, p0.y), [t0.x, t0.y]),
+            (Vector2::new(p0.x, p1.y), [t0.x, t1.y]),
+            (Vector2::new(p1.x, p1.y), [t1.x, t1.y]),
+            (Vector2::new(p1.x, p0.y), [t1.x, t0.y]),
+        ];
+        for (position, texture_coordinates) in corners.iter() {
+            let p = sprite.transform * Vector3::new(position.x, position.y, 1.);
+            self.vertices.push(SpriteBatchVertex {
+                position: [p.x / p.z, p.y / p.z],
+                texture_coordinates: *texture_coordinates,
+                color: sprite.color,
+            });
+        }
+        self.textures.push(texture);
+        self.uploaded = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.textures.clear();
+        self.vertices.clear();
+        self.uploaded = false;
+    }
+
+    // Sprites the GPU buffers can currently hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Available after upload.
+    pub fn draws(&self) -> &[SpriteBatchDraw] {
+        &self.draws
+    }
+
+    // Must be called after the last change and before drawing. The write is queued and applied
+    // before the next submitted commands. The sorting scratch data is taken from the frame arena.
+    pub fn upload(&mut self, instance: &gfx::Instance, arena: &gfx::FrameArena) {
+        self.prepare(arena);
+        if self.len() > self.capacity {
+            self.capacity = self.len().next_power_of_two();
+            self.buffers = None;
+        }
+        let capacity = self.capacity;
+        let buffers = self
+            .buffers
+            .get_or_insert_with(|| Self::create_buffers(instance, capacity));
+        let vertices = match self.order {
+            SpriteBatchOrder::Submission => &self.vertices,
+            SpriteBatchOrder::Texture => &self.sorted_vertices,
+        };
+        if !vertices.is_empty() {
+            instance.write_buffer(&buffers.vertex_buffer, 0, bytemuck::cast_slice(vertices));
+        }
+        self.uploaded = true;
+    }
+
+    // Sorts the vertices if needed and merges sprites into draws.
+    fn prepare(&mut self, arena: &gfx::FrameArena) {
+        self.draws.clear();
+        let mut push_draw = |texture: usize, sprite: usize| {
+            let start = sprite as u32 * 6;
+            match self.draws.last_mut() {
+                Some(draw) if draw.texture == texture => draw.index_range.end = start + 6,
+                _ => self.draws.push(SpriteBatchDraw {
+                    texture,
+                    index_range: start..start + 6,
+                }),
+            }
+        };
+        match self.order {
+            SpriteBatchOrder::Submission => {
+                for (i, texture) in self.textures.iter().enumerate() {
+                    push_draw(*texture, i);
+                }
+            }
+            SpriteBatchOrder::Texture => {
+                let mut order = arena.vec_from_iter(0..self.textures.len());
+                order.sort_by_key(|i| self.textures[*i]);
+                self.sorted_vertices.clear();
+                for (i, sprite) in order.into_iter().enumerate() {
+                    self.sorted_vertices
+                        .extend_from_slice(&self.vertices[sprite * 4..sprite * 4 + 4]);
+                    push_draw(self.textures[sprite], i);
+                }
+            }
+        }
+    }
+
+    fn create_buffers(instance: &gfx::Instance, capacity: usize) -> SpriteBatchBuffers {
+        let vertex_buffer = gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (capacity * 4 * std::mem::size_of::<SpriteBatchVertex>())
+                    as gfx::BufferAddress,
+                usage: gfx::BufferUsage::VERTEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        // The indices only depend on the number of sprites, so they are written once.
+        let indices: Vec<u32> = (0..capacity as u32)
+            .flat_map(|sprite| [0, 1, 3, 3, 1, 2].map(|i| sprite * 4 + i))
+            .collect();
+        let index_buffer = gfx::Buffer::new(
+            instance,
+            &gfx::BufferDescriptor {
+                label: None,
+                size: (indices.len() * std::mem::size_of::<u32>()) as gfx::BufferAddress,
+                usage: gfx::BufferUsage::INDEX | gfx::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        instance.write_buffer(&index_buffer, 0, bytemuck::cast_slice(&indices));
+        SpriteBatchBuffers {
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+}
+
+const VERTEX_ATTRIBUTES: [gfx::VertexAttribute; 3] = [
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x2,
+        offset: 0,
+        shader_location: 0,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x2,
+        offset: 8,
+        shader_location: 1,
+    },
+    gfx::VertexAttribute {
+        format: gfx::VertexFormat::Float32x4,
+        offset: 16,
+        shader_location: 2,
+    },
+];
+
+// Same as RenderPipeline, with the color read from the vertices of a SpriteBatch. The instanced
+// flag of the descriptor is ignored.
+#[derive(Debug)]
+pub struct SpriteBatchPipeline {
+    pipeline: gfx::RenderPipeline,
+    sample_count: gfx::SampleCount,
+    color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
+}
+
+impl SpriteBatchPipeline {
+    pub fn new(instance: &gfx::Instance, desc: &RenderPipelineDescriptor) -> Self {
+        let bind_group_layout =
+            bind_group_layout(instance, desc.features.contains(SpriteFeatures::PALETTE));
+        let pipeline_layout = gfx::PipelineLayout::new(
+            instance,
+            &gfx::PipelineLayoutDescriptor {
+                label: desc.label.as_deref(),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_CONVERSION_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_CONVERSION_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
+            },
+        );
+        let vs_module = gfx::ShaderModule::new(
+            instance,
+            &gfx::include_spirv!("shaders/gen/spirv/sprite_batch.vert.spv"),
+        );
+        let fs_module = gfx::ShaderModule::new(instance, &fragment_shader(desc.features));
+        let pipeline = gfx::RenderPipeline::new(
+            instance,
+            &gfx::RenderPipelineDescriptor {
+                label: desc.label.as_deref(),
+                layout: Some(&pipeline_layout),
+                vertex: gfx::VertexState {
+                    module: &vs_module,
+                    entry_point: "main",
+                    buffers: &[gfx::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<SpriteBatchVertex>()
+                            as gfx::BufferAddress,
+                        step_mode: gfx::VertexStepMode::Vertex,
+                        attributes: &VERTEX_ATTRIBUTES,
+                    }],
+                },
+                primitive: gfx::PrimitiveState {
+                    topology: gfx::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: gfx::FrontFace::Ccw,
+                    cull_mode: Some(gfx::Face::Back),
+                    clamp_depth: false,
+                    polygon_mode: gfx::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: gfx::MultisampleState {
+                    count: desc.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(gfx::FragmentState {
+                    module: &fs_module,
+                    entry_point: "main",
+                    targets: &[gfx::ColorTargetState {
+                        format: gfx::TextureFormat::from(desc.color_buffer_format),
+                        blend: Some(gfx::BlendState {
+                            color: desc.color_blend,
+                            alpha: desc.alpha_blend,
+                        }),
+                        write_mask: desc.write_mask,
+                    }],
+                }),
+            },
+        );
+        Self {
+            pipeline,
+            sample_count: desc.sample_count,
+            color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
+        }
+    }
+
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
+    pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
+        gfx::RenderPassRequirements {
+            sample_count: self.sample_count,
+            color_buffer_formats: vec![self.color_buffer_format],
+            depth_stencil_buffer_format: None,
+        }
+    }
+}
+
+pub trait SpriteBatchRenderer<'a> {
+    // The push constants transform and color are applied to all sprites, e.g. the camera
+    // projection. Each draw of the batch uses the uniform constants at its texture index.
+    fn draw_sprite_batch(
+        &mut self,
+        pipeline: &'a SpriteBatchPipeline,
+        batch: &'a SpriteBatch,
+        uniform_constants: &[&'a UniformConstants],
+        push_constants: &'a PushConstants,
+    );
+}
+
+impl<'a> SpriteBatchRenderer<'a> for gfx::RenderPass<'a> {
+    fn draw_sprite_batch(
+        &mut self,
+        pipeline: &'a SpriteBatchPipeline,
+        batch: &'a SpriteBatch,
+        uniform_constants: &[&'a UniformConstants],
+        push_constants: &'a PushConstants,
+    ) {
+        assert!(
+            batch.uploaded,
+            "The sprite batch must be uploaded after the last change"
+        );
+        let buffers = match &batch.buffers {
+            Some(buffers) if !batch.is_empty() => buffers,
+            _ => return,
+        };
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.set_index_buffer(buffers.index_buffer.slice(..), gfx::IndexFormat::Uint32);
+        self.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+        self.set_push_constants(
+            gfx::ShaderStage::VERTEX,
+            0,
+            gfx::utility::as_slice(push_constants),
+        );
+        for draw in batch.draws.iter() {
+            self.set_bind_group(0, &uniform_constants[draw.texture].bind_group, &[]);
+            self.draw_indexed(draw.index_range.clone(), 0, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mesh, MeshTemplates, RenderPipeline, Renderer, Vertex};
+    use galvanic_assert::{matchers::*, *};
+    use gfx::Canvas;
+
+    fn draws(batch: &SpriteBatch) -> Vec<(usize, gfx::MeshIndexRange)> {
+        batch
+            .draws()
+            .iter()
+            .map(|draw| (draw.texture, draw.index_range.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn vertices() {
+        let mut batch = SpriteBatch::new(&SpriteBatchDescriptor::default());
+        expect_that!(batch.is_empty());
+        batch.push(
+            0,
+            &BatchedSprite {
+                position: Vector2::new(-5., 0.),
+                size: Vector2::new(10., 20.),
+                texture_coordinates: [Vector2::new(0.5, 0.), Vector2::new(1., 0.25)],
+                color: gfx::ColorF32::RED,
+                transform: roe_math::translation2(&Vector2::new(100., 50.))
+                    * roe_math::scale2(&Vector2::new(2., 2.)),
+            },
+        );
+        expect_that!(&batch.len(), eq(1));
+        let color = gfx::ColorF32::RED;
+        expect_that!(
+            &batch.vertices,
+            eq(vec![
+                SpriteBatchVertex {
+                    position: [90., 50.],
+                    texture_coordinates: [0.5, 0.],
+                    color,
+                },
+                SpriteBatchVertex {
+                    position: [90., 90.],
+                    texture_coordinates: [0.5, 0.25],
+                    color,
+                },
+                SpriteBatchVertex {
+                    position: [110., 90.],
+                    texture_coordinates: [1., 0.25],
+                    color,
+                },
+                SpriteBatchVertex {
+                    position: [110., 50.],
+                    texture_coordinates: [1., 0.],
+                    color,
+                },
+            ])
+        );
+        batch.clear();
+        expect_that!(batch.is_empty());
+    }
+
+    #[test]
+    fn draw_grouping() {
+        let arena = gfx::FrameArena::new();
+        let mut batch = SpriteBatch::new(&SpriteBatchDescriptor::default());
+        for (i, texture) in [1, 1, 0, 0, 0, 1].iter().enumerate() {
+            batch.push(
+                *texture,
+                &BatchedSprite::new(Vector2::new(i as f32, 0.), Vector2::new(1., 1.)),
+            );
+        }
+        batch.prepare(&arena);
+        expect_that!(
+            &draws(&batch),
+            eq(vec![(1, 0..12), (0, 12..30), (1, 30..36)])
+        );
+
+        batch.set_order(SpriteBatchOrder::Texture);
+        batch.prepare(&arena);
+        expect_that!(&draws(&batch), eq(vec![(0, 0..18), (1, 18..36)]));
+        // Sprites with the same texture keep their order.
+        let x: Vec<f32> = batch
+            .sorted_vertices
+            .iter()
+            .step_by(4)
+            .map(|v| v.position[0])
+            .collect();
+        expect_that!(&x, eq(vec![2., 3., 4., 0., 1., 5.]));
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn draw_sprite_batch() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let create_canvas = || {
+            gfx::CanvasTexture::new(
+                &instance,
+                &gfx::CanvasTextureDescriptor {
+                    label: None,
+                    size: gfx::CanvasSize::new(100, 100),
+                    sample_count: 1,
+                    color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
+                        format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
+                        usage: gfx::CanvasColorBufferUsage::COPY_SRC,
+                        sample_count: None,
+                    }),
+                    depth_stencil_buffer_format: None,
+                },
+            )
+            .unwrap()
+        };
+        let desc = RenderPipelineDescriptor {
+            color_buffer_format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
+            ..RenderPipelineDescriptor::default()
+        };
+        let pipeline = RenderPipeline::new(&instance, &desc);
+        let batch_pipeline = SpriteBatchPipeline::new(&instance, &desc);
+        let texture = gfx::Texture::from_image(
+            &instance,
+            &image::open("data/pictures/gioconda.jpg")
+                .unwrap()
+                .into_rgba8(),
+            gfx::TextureUsage::TEXTURE_BINDING,
+        )
+        .create_view(&gfx::TextureViewDescriptor::default());
+        let uniform_constants = UniformConstants::new(
+            &instance,
+            &texture,
+            &gfx::Sampler::new(&instance, &gfx::SamplerDescriptor::default()),
+        );
+        let mesh = Mesh::quad(
+            &instance,
+            &Vertex::new([0., 0.], [0., 0.]),
+            &Vertex::new([10., 10.], [1., 1.]),
+        );
+
+        let projection_transform = roe_math::ortographic_projection2(0., 100., 100., 0.);
+        let sprites: Vec<_> = (0..20)
+            .map(|i| BatchedSprite {
+                color: gfx::ColorF32 {
+                    r: 1.,
+                    g: i as f32 / 20.,
+                    b: 0.5,
+                    a: 1.,
+                },
+                transform: roe_math::translation2(&Vector2::new(
+                    (i % 5) as f32 * 20.,
+                    (i / 5) as f32 * 25.,
+                )),
+                ..BatchedSprite::new(Vector2::new(0., 0.), Vector2::new(10., 10.))
+            })
+            .collect();
+        let push_constants: Vec<_> = sprites
+            .iter()
+            .map(|s| PushConstants::new(&(projection_transform * s.transform), s.color))
+            .collect();
+        let mut batch = SpriteBatch::new(&SpriteBatchDescriptor {
+            capacity: 4,
+            ..SpriteBatchDescriptor::default()
+        });
+        for sprite in sprites.iter() {
+            batch.push(0, sprite);
+        }
+        batch.upload(&instance, &gfx::FrameArena::new());
+        expect_that!(&batch.capacity(), eq(32));
+        expect_that!(&batch.draws().len(), eq(1));
+        let global_push_constants = PushConstants::new(&projection_transform, gfx::ColorF32::WHITE);
+
+        let mut images = Vec::new();
+        for batched in [false, true] {
+            let mut canvas = create_canvas();
+            let frame = canvas.current_frame().unwrap().unwrap();
+            let mut cmd_sequence = gfx::CommandSequence::new(&instance);
+            {
+                let mut rpass = cmd_sequence.begin_render_pass(
+                    &frame,
+                    &pipeline.render_pass_requirements(),
+                    &gfx::RenderPassOperations::default(),
+                );
+                if batched {
+                    rpass.draw_sprite_batch(
+                        &batch_pipeline,
+                        &batch,
+                        &[&uniform_constants],
+                        &global_push_constants,
+                    );
+                } else {
+                    for pc in &push_constants {
+                        rpass.draw_sprite(
+                            &pipeline,
+                            &uniform_constants,
+                            &mesh,
+                            pc,
+                            0..mesh.index_count(),
+                        );
+                    }
+                }
+            }
+            cmd_sequence.submit(&instance);
+            frame.present();
+            images.push(canvas.color_texture().unwrap().to_image(&instance));
+        }
+        expect_that!(&images[1], eq(images[0].clone()));
+    }
+}
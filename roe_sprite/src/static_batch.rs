@@ -0,0 +1,126 @@
+use super::{Mesh, Vertex};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Vector2, Vector3};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StaticSprite {
+    // Applied to the sprite rectangle, which goes from the origin to the size.
+    pub transform: HomogeneousMatrix2<f32>,
+    pub size: Vector2<f32>,
+    // Texture coordinates of the top left and bottom right corners.
+    pub texture_coordinates: [Vector2<f32>; 2],
+}
+
+impl StaticSprite {
+    pub fn new(transform: HomogeneousMatrix2<f32>, size: Vector2<f32>) -> Self {
+        Self {
+            transform,
+            size,
+            texture_coordinates: [Vector2::new(0., 0.), Vector2::new(1., 1.)],
+        }
+    }
+}
+
+// Bakes many static sprites sharing a texture into a single mesh, with the transforms applied
+// to the vertices, so that they can be drawn with a single draw call. 32 bit indices are used,
+// so the number of sprites isn't limited.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct StaticSpriteBatch {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl StaticSpriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sprite: &StaticSprite) {
+        let first = self.vertices.len() as u32;
+        let [t0, t1] = sprite.texture_coordinates;
+        let corners = [
+            (Vector2::new(0., 0.), Vector2::new(t0.x, t0.y)),
+            (Vector2::new(0., sprite.size.y), Vector2::new(t0.x, t1.y)),
+            (sprite.size, Vector2::new(t1.x, t1.y)),
+            (Vector2::new(sprite.size.x, 0.), Vector2::new(t1.x, t0.y)),
+        ];
+        for (position, texture_coordinates) in corners.iter() {
+            let p = sprite.transform * Vector3::new(position.x, position.y, 1.);
+            self.vertices.push(Vertex::new(
+                [p.x / p.z, p.y / p.z],
+                [texture_coordinates.x, texture_coordinates.y],
+            ));
+        }
+        // Same winding as MeshTemplates::rectangle.
+        self.indices
+            .extend([0, 1, 3, 3, 1, 2].iter().map(|i| first + i));
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    // Draw the whole mesh with the 0..index_count range and the push constants of the batch,
+    // without an additional transform.
+    pub fn build(&self, instance: &gfx::Instance) -> Mesh<u32> {
+        Mesh::new(instance, &self.vertices, &self.indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn merge() {
+        let mut batch = StaticSpriteBatch::new();
+        expect_that!(batch.is_empty());
+        batch.push(&StaticSprite::new(
+            HomogeneousMatrix2::identity(),
+            Vector2::new(10., 20.),
+        ));
+        batch.push(&StaticSprite {
+            transform: roe_math::translation2(&Vector2::new(100., 50.))
+                * roe_math::scale2(&Vector2::new(2., 2.)),
+            size: Vector2::new(10., 10.),
+            texture_coordinates: [Vector2::new(0.5, 0.), Vector2::new(1., 0.25)],
+        });
+        expect_that!(&batch.len(), eq(2));
+        expect_that!(&batch.vertices()[2], eq(Vertex::new([10., 20.], [1., 1.])));
+        expect_that!(
+            &batch.vertices()[4..].to_vec(),
+            eq(vec![
+                Vertex::new([100., 50.], [0.5, 0.]),
+                Vertex::new([100., 70.], [0.5, 0.25]),
+                Vertex::new([120., 70.], [1., 0.25]),
+                Vertex::new([120., 50.], [1., 0.]),
+            ])
+        );
+        expect_that!(
+            &batch.indices().to_vec(),
+            eq(vec![0, 1, 3, 3, 1, 2, 4, 5, 7, 7, 5, 6])
+        );
+
+        batch.clear();
+        expect_that!(&batch.len(), eq(0));
+    }
+}
@@ -0,0 +1,197 @@
+use super::StaticSprite;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use roe_math::{HomogeneousMatrix2, Rotation2, Vector2};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StressSpriteDescriptor {
+    pub count: usize,
+    // Sprites are evenly assigned to texture indices in the range [0, texture_count).
+    pub texture_count: usize,
+    // The sprites are placed and move inside the rectangle from the origin to the area size.
+    pub area: Vector2<f32>,
+    pub min_size: f32,
+    pub max_size: f32,
+    // In units per second. The sprites don't move if 0.
+    pub max_speed: f32,
+    // In radians per second.
+    pub max_angular_speed: f32,
+    // The same seed always generates the same sprites.
+    pub seed: u64,
+}
+
+impl Default for StressSpriteDescriptor {
+    fn default() -> Self {
+        Self {
+            count: 1000,
+            texture_count: 1,
+            area: Vector2::new(1920., 1080.),
+            min_size: 8.,
+            max_size: 64.,
+            max_speed: 100.,
+            max_angular_speed: 1.,
+            seed: 0,
+        }
+    }
+}
+
+impl StressSpriteDescriptor {
+    // Many small fast sprites, as produced by a particle system.
+    pub fn particles(count: usize) -> Self {
+        Self {
+            count,
+            min_size: 1.,
+            max_size: 4.,
+            max_speed: 400.,
+            max_angular_speed: 0.,
+            ..Self::default()
+        }
+    }
+}
+
+// Randomly generated sprite used to measure the renderer performance.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StressSprite {
+    // Position of the sprite center.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    pub rotation: f32,
+    pub velocity: Vector2<f32>,
+    pub angular_velocity: f32,
+    pub texture_index: usize,
+}
+
+impl StressSprite {
+    pub fn transform(&self) -> HomogeneousMatrix2<f32> {
+        roe_math::translation2(&self.position)
+            * roe_math::rotation2(&Rotation2::from_angle(self.rotation))
+            * roe_math::translation2(&(self.size * -0.5))
+    }
+
+    pub fn to_static_sprite(&self) -> StaticSprite {
+        StaticSprite::new(self.transform(), self.size)
+    }
+
+    // Moves the sprite, bouncing on the area borders.
+    pub fn advance(&mut self, dt: f32, area: &Vector2<f32>) {
+        self.position += self.velocity * dt;
+        self.rotation += self.angular_velocity * dt;
+        for i in 0..2 {
+            if self.position[i] < 0. {
+                self.position[i] = -self.position[i];
+                self.velocity[i] = self.velocity[i].abs();
+            } else if self.position[i] > area[i] {
+                self.position[i] = 2. * area[i] - self.position[i];
+                self.velocity[i] = -self.velocity[i].abs();
+            }
+        }
+    }
+}
+
+fn random_range(rng: &mut StdRng, min: f32, max: f32) -> f32 {
+    if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    }
+}
+
+pub fn generate_stress_sprites(desc: &StressSpriteDescriptor) -> Vec<StressSprite> {
+    assert!(
+        desc.texture_count > 0,
+        "The texture count must be higher than 0"
+    );
+    assert!(
+        desc.min_size > 0. && desc.min_size <= desc.max_size,
+        "The minimum size must be higher than 0 and not higher than the maximum size"
+    );
+    let mut rng = StdRng::seed_from_u64(desc.seed);
+    (0..desc.count)
+        .map(|i| StressSprite {
+            position: Vector2::new(
+                random_range(&mut rng, 0., desc.area.x),
+                random_range(&mut rng, 0., desc.area.y),
+            ),
+            size: Vector2::new(
+                random_range(&mut rng, desc.min_size, desc.max_size),
+                random_range(&mut rng, desc.min_size, desc.max_size),
+            ),
+            rotation: random_range(&mut rng, 0., std::f32::consts::TAU),
+            velocity: Vector2::new(
+                random_range(&mut rng, -desc.max_speed, desc.max_speed),
+                random_range(&mut rng, -desc.max_speed, desc.max_speed),
+            ),
+            angular_velocity: random_range(
+                &mut rng,
+                -desc.max_angular_speed,
+                desc.max_angular_speed,
+            ),
+            texture_index: i % desc.texture_count,
+        })
+        .collect()
+}
+
+// Moves all sprites, as a stress test would do every frame.
+pub fn advance_stress_sprites(sprites: &mut [StressSprite], dt: f32, area: &Vector2<f32>) {
+    for sprite in sprites.iter_mut() {
+        sprite.advance(dt, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn generation() {
+        let desc = StressSpriteDescriptor {
+            count: 100,
+            texture_count: 3,
+            ..StressSpriteDescriptor::default()
+        };
+        let sprites = generate_stress_sprites(&desc);
+        expect_that!(&sprites.len(), eq(100));
+        expect_that!(&sprites, eq(generate_stress_sprites(&desc)));
+        expect_that!(
+            &sprites.iter().filter(|s| s.texture_index == 2).count(),
+            eq(33)
+        );
+        for s in sprites.iter() {
+            expect_that!(s.position.x >= 0. && s.position.x <= desc.area.x);
+            expect_that!(s.position.y >= 0. && s.position.y <= desc.area.y);
+            expect_that!(s.size.x >= desc.min_size && s.size.x <= desc.max_size);
+        }
+
+        let other = generate_stress_sprites(&StressSpriteDescriptor {
+            seed: 1,
+            ..desc.clone()
+        });
+        expect_that!(sprites != other);
+    }
+
+    #[test]
+    fn movement() {
+        let area = Vector2::new(100., 100.);
+        let mut sprite = StressSprite {
+            position: Vector2::new(90., 50.),
+            size: Vector2::new(10., 10.),
+            rotation: 0.,
+            velocity: Vector2::new(20., -10.),
+            angular_velocity: 1.,
+            texture_index: 0,
+        };
+        sprite.advance(1., &area);
+        expect_that!(&sprite.position, eq(Vector2::new(90., 40.)));
+        expect_that!(&sprite.velocity, eq(Vector2::new(-20., -10.)));
+        expect_that!(&sprite.rotation, eq(1.));
+
+        let static_sprite = StressSprite {
+            rotation: 0.,
+            ..sprite
+        }
+        .to_static_sprite();
+        let center = static_sprite.transform * roe_math::Vector3::new(5., 5., 1.);
+        expect_that!(&center, eq(roe_math::Vector3::new(90., 40., 1.)));
+    }
+}
@@ -0,0 +1,493 @@
+use super::{Mesh, Vertex};
+
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+use serde::Deserialize;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum TextureAtlasError {
+    IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    ImageError(image::ImageError),
+    // Region name and description.
+    InvalidRegion(String, String),
+    MissingImage,
+}
+
+impl std::fmt::Display for TextureAtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::JsonError(e) => write!(f, "JSON error ({})", e),
+            Self::ImageError(e) => write!(f, "Image error ({})", e),
+            Self::InvalidRegion(name, description) => {
+                write!(f, "Invalid region {} ({})", name, description)
+            }
+            Self::MissingImage => write!(f, "The atlas description doesn't specify an image"),
+        }
+    }
+}
+
+impl std::error::Error for TextureAtlasError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::JsonError(e) => Some(e),
+            Self::ImageError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TextureAtlasError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<serde_json::Error> for TextureAtlasError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::JsonError(e)
+    }
+}
+
+impl From<image::ImageError> for TextureAtlasError {
+    fn from(e: image::ImageError) -> Self {
+        Self::ImageError(e)
+    }
+}
+
+// Sub-image of an atlas, in pixels.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    // Size of the sub-image, before the rotation.
+    pub width: u32,
+    pub height: u32,
+    // Stored rotated 90 degrees clockwise, so that it covers height x width pixels in the
+    // atlas. Packing tools do this to fit more sprites.
+    pub rotated: bool,
+    // Position of the sub-image within the original image, when transparent borders were
+    // trimmed when packing.
+    pub offset: Vector2<u32>,
+    // Size of the original image.
+    pub source_size: Vector2<u32>,
+}
+
+impl AtlasRegion {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            rotated: false,
+            offset: Vector2::new(0, 0),
+            source_size: Vector2::new(width, height),
+        }
+    }
+
+    // Size of the area covered in the atlas.
+    pub fn atlas_size(&self) -> Vector2<u32> {
+        if self.rotated {
+            Vector2::new(self.height, self.width)
+        } else {
+            Vector2::new(self.width, self.height)
+        }
+    }
+
+    // Texture coordinates of the top left, bottom left, bottom right and top right corners of
+    // the sub-image, the same order as the MeshTemplates::rectangle vertices.
+    pub fn texture_coordinates(&self, atlas_size: &Vector2<u32>) -> [Vector2<f32>; 4] {
+        let size = self.atlas_size();
+        let u0 = self.x as f32 / atlas_size.x as f32;
+        let v0 = self.y as f32 / atlas_size.y as f32;
+        let u1 = (self.x + size.x) as f32 / atlas_size.x as f32;
+        let v1 = (self.y + size.y) as f32 / atlas_size.y as f32;
+        if self.rotated {
+            [
+                Vector2::new(u1, v0),
+                Vector2::new(u0, v0),
+                Vector2::new(u0, v1),
+                Vector2::new(u1, v1),
+            ]
+        } else {
+            [
+                Vector2::new(u0, v0),
+                Vector2::new(u0, v1),
+                Vector2::new(u1, v1),
+                Vector2::new(u1, v0),
+            ]
+        }
+    }
+
+    // Rectangle of the sub-image, placed at its offset within the original image, so that
+    // trimmed sprites keep their pivot.
+    pub fn vertices(&self, atlas_size: &Vector2<u32>) -> [Vertex; 4] {
+        let texture_coordinates = self.texture_coordinates(atlas_size);
+        let (x0, y0) = (self.offset.x as f32, self.offset.y as f32);
+        let (x1, y1) = (x0 + self.width as f32, y0 + self.height as f32);
+        let positions = [[x0, y0], [x0, y1], [x1, y1], [x1, y0]];
+        let vertex = |i: usize| {
+            Vertex::new(
+                positions[i],
+                [texture_coordinates[i].x, texture_coordinates[i].y],
+            )
+        };
+        [vertex(0), vertex(1), vertex(2), vertex(3)]
+    }
+}
+
+// Subset of the TexturePacker JSON format, with frames either in a hash or in an array.
+#[derive(Deserialize)]
+struct RawRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct RawSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFrame {
+    #[serde(default)]
+    filename: Option<String>,
+    frame: RawRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    sprite_source_size: Option<RawRect>,
+    #[serde(default)]
+    source_size: Option<RawSize>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFrames {
+    Hash(HashMap<String, RawFrame>),
+    Array(Vec<RawFrame>),
+}
+
+#[derive(Deserialize, Default)]
+struct RawMeta {
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    size: Option<RawSize>,
+}
+
+#[derive(Deserialize)]
+struct RawAtlas {
+    frames: RawFrames,
+    #[serde(default)]
+    meta: RawMeta,
+}
+
+impl RawFrame {
+    fn region(&self) -> AtlasRegion {
+        let (width, height) = match &self.sprite_source_size {
+            Some(rect) => (rect.w, rect.h),
+            None if self.rotated => (self.frame.h, self.frame.w),
+            None => (self.frame.w, self.frame.h),
+        };
+        AtlasRegion {
+            x: self.frame.x,
+            y: self.frame.y,
+            width,
+            height,
+            rotated: self.rotated,
+            offset: match &self.sprite_source_size {
+                Some(rect) => Vector2::new(rect.x, rect.y),
+                None => Vector2::new(0, 0),
+            },
+            source_size: match &self.source_size {
+                Some(size) => Vector2::new(size.w, size.h),
+                None => Vector2::new(width, height),
+            },
+        }
+    }
+}
+
+// Named regions of an atlas image, independent of the GPU texture.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextureAtlasLayout {
+    size: Vector2<u32>,
+    // Path of the atlas image, relative to the description file.
+    image: Option<String>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlasLayout {
+    pub fn new(size: Vector2<u32>) -> Self {
+        Self {
+            size,
+            image: None,
+            regions: HashMap::new(),
+        }
+    }
+
+    // Reads a TexturePacker JSON description, either in the hash or in the array format. If
+    // the atlas size isn't specified, the smallest size containing all regions is used.
+    pub fn from_json_str(s: &str) -> Result<Self, TextureAtlasError> {
+        let raw: RawAtlas = serde_json::from_str(s)?;
+        let frames: Vec<(String, AtlasRegion)> = match &raw.frames {
+            RawFrames::Hash(frames) => frames
+                .iter()
+                .map(|(name, frame)| (name.clone(), frame.region()))
+                .collect(),
+            RawFrames::Array(frames) => frames
+                .iter()
+                .enumerate()
+                .map(|(i, frame)| match &frame.filename {
+                    Some(name) => Ok((name.clone(), frame.region())),
+                    None => Err(TextureAtlasError::InvalidRegion(
+                        format!("#{}", i),
+                        String::from("missing file name"),
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        let size = match &raw.meta.size {
+            Some(size) => Vector2::new(size.w, size.h),
+            None => frames.iter().fold(Vector2::new(0, 0), |size, (_, region)| {
+                let end = Vector2::new(region.x, region.y) + region.atlas_size();
+                Vector2::new(size.x.max(end.x), size.y.max(end.y))
+            }),
+        };
+        let mut layout = Self::new(size);
+        layout.image = raw.meta.image.clone();
+        for (name, region) in frames {
+            layout.insert(name, region)?;
+        }
+        Ok(layout)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TextureAtlasError> {
+        Self::from_json_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn size(&self) -> &Vector2<u32> {
+        &self.size
+    }
+
+    pub fn image(&self) -> Option<&str> {
+        self.image.as_deref()
+    }
+
+    // Fails if the region exceeds the atlas. Replaces any region with the same name.
+    pub fn insert<S: Into<String>>(
+        &mut self,
+        name: S,
+        region: AtlasRegion,
+    ) -> Result<(), TextureAtlasError> {
+        let name = name.into();
+        let end = Vector2::new(region.x, region.y) + region.atlas_size();
+        if end.x > self.size.x || end.y > self.size.y {
+            return Err(TextureAtlasError::InvalidRegion(
+                name,
+                format!("exceeds the atlas size {}x{}", self.size.x, self.size.y),
+            ));
+        }
+        self.regions.insert(name, region);
+        Ok(())
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+
+    pub fn regions(&self) -> impl Iterator<Item = (&str, &AtlasRegion)> {
+        self.regions
+            .iter()
+            .map(|(name, region)| (name.as_str(), region))
+    }
+
+    // Region names, sorted, e.g. to collect the frames of an animation.
+    pub fn region_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.regions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn texture_coordinates(&self, name: &str) -> Option<[Vector2<f32>; 4]> {
+        self.region(name)
+            .map(|region| region.texture_coordinates(&self.size))
+    }
+
+    pub fn vertices(&self, name: &str) -> Option<[Vertex; 4]> {
+        self.region(name).map(|region| region.vertices(&self.size))
+    }
+}
+
+// Texture containing many sprites, each one identified by the name of its region.
+#[derive(Debug)]
+pub struct TextureAtlas {
+    texture: gfx::Texture,
+    layout: TextureAtlasLayout,
+}
+
+impl TextureAtlas {
+    pub fn new(texture: gfx::Texture, layout: TextureAtlasLayout) -> Self {
+        Self { texture, layout }
+    }
+
+    pub fn from_image(
+        instance: &gfx::Instance,
+        img: &image::RgbaImage,
+        layout: TextureAtlasLayout,
+    ) -> Self {
+        Self::new(
+            gfx::Texture::from_image(instance, img, gfx::TextureUsage::TEXTURE_BINDING),
+            layout,
+        )
+    }
+
+    // Loads a TexturePacker JSON description, and the image it references, relative to the
+    // description file.
+    pub fn load<P: AsRef<Path>>(
+        instance: &gfx::Instance,
+        path: P,
+    ) -> Result<Self, TextureAtlasError> {
+        let path = path.as_ref();
+        let layout = TextureAtlasLayout::load(path)?;
+        let image_path: PathBuf = match (path.parent(), layout.image()) {
+            (_, None) => return Err(TextureAtlasError::MissingImage),
+            (Some(dir), Some(image)) => dir.join(image),
+            (None, Some(image)) => PathBuf::from(image),
+        };
+        let img = image::open(image_path)?.into_rgba8();
+        Ok(Self::from_image(instance, &img, layout))
+    }
+
+    pub fn texture(&self) -> &gfx::Texture {
+        &self.texture
+    }
+
+    pub fn layout(&self) -> &TextureAtlasLayout {
+        &self.layout
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.layout.region(name)
+    }
+
+    pub fn create_view(&self) -> gfx::TextureView {
+        self.texture
+            .create_view(&gfx::TextureViewDescriptor::default())
+    }
+
+    // Rectangle with the size of the region, textured with it. Draw it with the uniform
+    // constants of the atlas texture.
+    pub fn mesh_for(&self, instance: &gfx::Instance, name: &str) -> Option<Mesh> {
+        self.layout
+            .vertices(name)
+            .map(|vertices| Mesh::new(instance, &vertices, &[0, 1, 3, 3, 1, 2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn texture_packer_hash() {
+        let layout = TextureAtlasLayout::from_json_str(
+            r#"{
+                "frames": {
+                    "player_idle_0": {
+                        "frame": {"x": 0, "y": 0, "w": 32, "h": 64},
+                        "rotated": false,
+                        "trimmed": false,
+                        "spriteSourceSize": {"x": 0, "y": 0, "w": 32, "h": 64},
+                        "sourceSize": {"w": 32, "h": 64}
+                    },
+                    "player_idle_1": {
+                        "frame": {"x": 32, "y": 0, "w": 20, "h": 40},
+                        "trimmed": true,
+                        "spriteSourceSize": {"x": 6, "y": 24, "w": 20, "h": 40},
+                        "sourceSize": {"w": 32, "h": 64}
+                    }
+                },
+                "meta": {"image": "player.png", "size": {"w": 128, "h": 64}}
+            }"#,
+        )
+        .unwrap();
+        expect_that!(layout.size(), eq(Vector2::new(128, 64)));
+        expect_that!(&layout.image(), eq(Some("player.png")));
+        expect_that!(
+            &layout.region_names(),
+            eq(vec!["player_idle_0", "player_idle_1"])
+        );
+        expect_that!(
+            &layout.texture_coordinates("player_idle_0").unwrap()[2],
+            eq(Vector2::new(0.25, 1.))
+        );
+        let vertices = layout.vertices("player_idle_1").unwrap();
+        expect_that!(&vertices[0], eq(Vertex::new([6., 24.], [0.25, 0.])));
+        expect_that!(&vertices[2], eq(Vertex::new([26., 64.], [0.40625, 0.625])));
+        expect_that!(layout.region("missing").is_none());
+    }
+
+    #[test]
+    fn texture_packer_array() {
+        let layout = TextureAtlasLayout::from_json_str(
+            r#"{
+                "frames": [
+                    {"filename": "a", "frame": {"x": 0, "y": 0, "w": 10, "h": 20}},
+                    {"filename": "b", "frame": {"x": 10, "y": 0, "w": 20, "h": 10},
+                        "rotated": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+        // Without a size, the atlas covers all regions.
+        expect_that!(layout.size(), eq(Vector2::new(30, 20)));
+        let b = layout.region("b").unwrap();
+        expect_that!(&(b.width, b.height), eq((10, 20)));
+        expect_that!(&b.atlas_size(), eq(Vector2::new(20, 10)));
+        let uv = |x: f32, y: f32| Vector2::new(x / 30., y / 20.);
+        // The left edge of the sprite is the top edge of the region.
+        expect_that!(
+            &layout.texture_coordinates("b").unwrap(),
+            eq([uv(30., 0.), uv(10., 0.), uv(10., 10.), uv(30., 10.)])
+        );
+
+        let missing_name = TextureAtlasLayout::from_json_str(
+            r#"{"frames": [{"frame": {"x": 0, "y": 0, "w": 1, "h": 1}}]}"#,
+        );
+        expect_that!(matches!(
+            missing_name,
+            Err(TextureAtlasError::InvalidRegion(..))
+        ));
+    }
+
+    #[test]
+    fn region_bounds() {
+        let mut layout = TextureAtlasLayout::new(Vector2::new(64, 64));
+        expect_that!(layout.insert("a", AtlasRegion::new(32, 32, 32, 32)).is_ok());
+        expect_that!(layout.insert("b", AtlasRegion::new(40, 0, 32, 32)).is_err());
+        expect_that!(&layout.len(), eq(1));
+    }
+}
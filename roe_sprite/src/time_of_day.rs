@@ -0,0 +1,401 @@
+use roe_app::EventBus;
+use roe_graphics::ColorF32;
+
+// Ambient light applied to the whole scene. The lit color is the base color multiplied by the
+// ambient color scaled by the intensity.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AmbientLight {
+    pub color: ColorF32,
+    pub intensity: f32,
+}
+
+impl AmbientLight {
+    pub fn new(color: ColorF32, intensity: f32) -> Self {
+        Self { color, intensity }
+    }
+
+    // Ambient color premultiplied by the intensity, alpha excluded.
+    pub fn scaled_color(&self) -> ColorF32 {
+        ColorF32 {
+            r: self.color.r * self.intensity,
+            g: self.color.g * self.intensity,
+            b: self.color.b * self.intensity,
+            a: self.color.a,
+        }
+    }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            color: ColorF32 {
+                r: mix(self.color.r, other.color.r),
+                g: mix(self.color.g, other.color.g),
+                b: mix(self.color.b, other.color.b),
+                a: mix(self.color.a, other.color.a),
+            },
+            intensity: mix(self.intensity, other.intensity),
+        }
+    }
+}
+
+impl Default for AmbientLight {
+    fn default() -> Self {
+        Self::new(ColorF32::WHITE, 1.)
+    }
+}
+
+// Ambient light at a given time of the day, as a fraction of the day in [0, 1).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AmbientGradientStop {
+    pub time: f32,
+    pub light: AmbientLight,
+}
+
+// Named time of the day, e.g. dawn or dusk, as a fraction of the day in [0, 1).
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeOfDayKey {
+    pub name: String,
+    pub time: f32,
+}
+
+impl TimeOfDayKey {
+    pub fn new<S: Into<String>>(name: S, time: f32) -> Self {
+        Self {
+            name: name.into(),
+            time,
+        }
+    }
+}
+
+// Published to the event bus when the clock goes past a key time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeOfDayEvent {
+    pub name: String,
+    pub day: u64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeOfDayDescriptor {
+    // Length of a full day in seconds.
+    pub day_duration: f32,
+    // Fraction of the day at start.
+    pub start_time: f32,
+    // The light is interpolated linearly between stops, wrapping around midnight.
+    pub gradient: Vec<AmbientGradientStop>,
+    pub keys: Vec<TimeOfDayKey>,
+}
+
+impl Default for TimeOfDayDescriptor {
+    fn default() -> Self {
+        let night = AmbientLight::new(
+            ColorF32 {
+                r: 0.25,
+                g: 0.3,
+                b: 0.55,
+                a: 1.,
+            },
+            0.35,
+        );
+        let twilight = AmbientLight::new(
+            ColorF32 {
+                r: 1.,
+                g: 0.6,
+                b: 0.45,
+                a: 1.,
+            },
+            0.7,
+        );
+        let day = AmbientLight::new(ColorF32::WHITE, 1.);
+        Self {
+            day_duration: 600.,
+            start_time: 0.3,
+            gradient: vec![
+                AmbientGradientStop {
+                    time: 0.2,
+                    light: night,
+                },
+                AmbientGradientStop {
+                    time: 0.25,
+                    light: twilight,
+                },
+                AmbientGradientStop {
+                    time: 0.32,
+                    light: day,
+                },
+                AmbientGradientStop {
+                    time: 0.7,
+                    light: day,
+                },
+                AmbientGradientStop {
+                    time: 0.77,
+                    light: twilight,
+                },
+                AmbientGradientStop {
+                    time: 0.82,
+                    light: night,
+                },
+            ],
+            keys: vec![
+                TimeOfDayKey::new("dawn", 0.25),
+                TimeOfDayKey::new("dusk", 0.77),
+            ],
+        }
+    }
+}
+
+// Advances a day clock and computes the ambient light for the current time. The light can be
+// passed to the lighting pass, and key times are published as TimeOfDayEvent on the event bus,
+// so gameplay systems can e.g. open shops at dawn or spawn enemies at dusk.
+#[derive(Debug, Clone)]
+pub struct TimeOfDay {
+    desc: TimeOfDayDescriptor,
+    time: f32,
+    day: u64,
+    speed: f32,
+    paused: bool,
+    ambient_light: AmbientLight,
+}
+
+impl TimeOfDay {
+    pub fn new(desc: &TimeOfDayDescriptor) -> Self {
+        assert!(desc.day_duration > 0., "The day duration must be positive");
+        assert!(
+            !desc.gradient.is_empty(),
+            "The ambient gradient must have at least one stop"
+        );
+        let mut desc = desc.clone();
+        desc.gradient
+            .sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        let time = desc.start_time.rem_euclid(1.);
+        let ambient_light = sample_gradient(&desc.gradient, time);
+        Self {
+            desc,
+            time,
+            day: 0,
+            speed: 1.,
+            paused: false,
+            ambient_light,
+        }
+    }
+
+    pub fn descriptor(&self) -> &TimeOfDayDescriptor {
+        &self.desc
+    }
+
+    // Fraction of the current day in [0, 1).
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    // Jumps to the given time of the current day, without publishing events for the skipped
+    // keys.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(1.);
+        self.update_ambient_light();
+    }
+
+    // Number of midnights passed since start.
+    pub fn day(&self) -> u64 {
+        self.day
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        assert!(speed >= 0., "The speed must not be negative");
+        self.speed = speed;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn ambient_light(&self) -> &AmbientLight {
+        &self.ambient_light
+    }
+
+    // Advances the clock by dt seconds. Keys in (previous time, new time] are published in
+    // order, also when the update covers more than a day.
+    pub fn update(&mut self, dt: f32, event_bus: &EventBus) {
+        if self.paused || dt <= 0. {
+            return;
+        }
+        let mut remaining = dt * self.speed / self.desc.day_duration;
+        while remaining > 0. {
+            let end = self.time + remaining;
+            let day_end = end.min(1.);
+            let mut crossed: Vec<&TimeOfDayKey> = self
+                .desc
+                .keys
+                .iter()
+                .filter(|key| key.time > self.time && key.time <= day_end)
+                .collect();
+            crossed.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+            for key in crossed {
+                event_bus.publish(TimeOfDayEvent {
+                    name: key.name.clone(),
+                    day: self.day,
+                });
+            }
+            remaining -= day_end - self.time;
+            if end >= 1. {
+                self.day += 1;
+                self.time = 0.;
+                // Keys at midnight belong to the new day.
+                for key in self.desc.keys.iter().filter(|key| key.time == 0.) {
+                    event_bus.publish(TimeOfDayEvent {
+                        name: key.name.clone(),
+                        day: self.day,
+                    });
+                }
+            } else {
+                self.time = end;
+                remaining = 0.;
+            }
+        }
+        self.update_ambient_light();
+    }
+
+    fn update_ambient_light(&mut self) {
+        self.ambient_light = sample_gradient(&self.desc.gradient, self.time);
+    }
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self::new(&TimeOfDayDescriptor::default())
+    }
+}
+
+// The stops must be sorted by time.
+fn sample_gradient(stops: &[AmbientGradientStop], time: f32) -> AmbientLight {
+    let first = stops.first().unwrap();
+    let last = stops.last().unwrap();
+    let next_index = stops.iter().position(|stop| stop.time > time);
+    let (from, to, span, offset) = match next_index {
+        Some(0) | None => {
+            // Between the last stop and the first one of the next day.
+            let span = first.time + 1. - last.time;
+            let offset = (time - last.time).rem_euclid(1.);
+            (last, first, span, offset)
+        }
+        Some(i) => {
+            let from = &stops[i - 1];
+            let to = &stops[i];
+            (from, to, to.time - from.time, time - from.time)
+        }
+    };
+    if span <= 0. {
+        return to.light;
+    }
+    from.light.lerp(&to.light, offset / span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn gray(value: f32) -> AmbientLight {
+        AmbientLight::new(
+            ColorF32 {
+                r: value,
+                g: value,
+                b: value,
+                a: 1.,
+            },
+            value,
+        )
+    }
+
+    fn descriptor() -> TimeOfDayDescriptor {
+        TimeOfDayDescriptor {
+            day_duration: 100.,
+            start_time: 0.,
+            gradient: vec![
+                AmbientGradientStop {
+                    time: 0.75,
+                    light: gray(0.2),
+                },
+                AmbientGradientStop {
+                    time: 0.25,
+                    light: gray(1.),
+                },
+            ],
+            keys: vec![
+                TimeOfDayKey::new("dusk", 0.75),
+                TimeOfDayKey::new("dawn", 0.25),
+                TimeOfDayKey::new("midnight", 0.),
+            ],
+        }
+    }
+
+    fn names(events: Vec<TimeOfDayEvent>) -> Vec<(String, u64)> {
+        events
+            .into_iter()
+            .map(|event| (event.name, event.day))
+            .collect()
+    }
+
+    #[test]
+    fn ambient_gradient() {
+        let mut clock = TimeOfDay::new(&descriptor());
+        // Half way between dusk and dawn across midnight.
+        expect_that!(&clock.ambient_light().intensity, close_to(0.6, 1e-6));
+        clock.set_time(0.5);
+        expect_that!(&clock.ambient_light().intensity, close_to(0.6, 1e-6));
+        clock.set_time(0.25);
+        expect_that!(clock.ambient_light(), eq(gray(1.)));
+        clock.set_time(0.375);
+        expect_that!(&clock.ambient_light().color.r, close_to(0.8, 1e-6));
+        clock.set_time(0.8);
+        expect_that!(&clock.ambient_light().intensity, close_to(0.28, 1e-6));
+        expect_that!(
+            &clock.ambient_light().scaled_color().g,
+            close_to(0.28 * 0.28, 1e-6)
+        );
+    }
+
+    #[test]
+    fn key_events() {
+        let bus = EventBus::new();
+        let mut events = bus.subscribe::<TimeOfDayEvent>();
+        let mut clock = TimeOfDay::new(&descriptor());
+
+        clock.update(20., &bus);
+        bus.deliver();
+        expect_that!(&events.read(), eq(Vec::new()));
+
+        clock.update(5., &bus);
+        bus.deliver();
+        expect_that!(&names(events.read()), eq(vec![(String::from("dawn"), 0)]));
+        expect_that!(&clock.time(), close_to(0.25, 1e-6));
+
+        // More than a day at once.
+        clock.set_speed(2.);
+        clock.update(70., &bus);
+        bus.deliver();
+        expect_that!(
+            &names(events.read()),
+            eq(vec![
+                (String::from("dusk"), 0),
+                (String::from("midnight"), 1),
+                (String::from("dawn"), 1)
+            ])
+        );
+        expect_that!(&clock.day(), eq(1));
+        expect_that!(&clock.time(), close_to(0.65, 1e-5));
+
+        clock.set_paused(true);
+        clock.update(100., &bus);
+        bus.deliver();
+        expect_that!(&events.read(), eq(Vec::new()));
+        expect_that!(&clock.time(), close_to(0.65, 1e-5));
+    }
+}
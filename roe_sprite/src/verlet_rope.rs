@@ -0,0 +1,428 @@
+use super::{Mesh, Vertex};
+
+use roe_graphics as gfx;
+use roe_math::{Aabb2, Vector2};
+
+// Static obstacle the rope points are pushed out of.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RopeCollider {
+    Aabb(Aabb2<f32>),
+    Circle { center: Vector2<f32>, radius: f32 },
+}
+
+impl RopeCollider {
+    fn resolve(&self, p: &mut Vector2<f32>) {
+        match self {
+            Self::Aabb(aabb) => {
+                if !aabb.contains_point(p) {
+                    return;
+                }
+                // Out through the closest edge.
+                let exits = [
+                    (p.x - aabb.min.x, Vector2::new(aabb.min.x, p.y)),
+                    (aabb.max.x - p.x, Vector2::new(aabb.max.x, p.y)),
+                    (p.y - aabb.min.y, Vector2::new(p.x, aabb.min.y)),
+                    (aabb.max.y - p.y, Vector2::new(p.x, aabb.max.y)),
+                ];
+                let (_, exit) = exits
+                    .iter()
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                    .unwrap();
+                *p = *exit;
+            }
+            Self::Circle { center, radius } => {
+                let offset = *p - center;
+                let distance = offset.norm();
+                if distance >= *radius {
+                    return;
+                }
+                // A point exactly at the center is pushed up.
+                let direction = if distance > 0. {
+                    offset / distance
+                } else {
+                    Vector2::new(0., -1.)
+                };
+                *p = center + direction * *radius;
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerletRopeDescriptor {
+    // In units per second squared, y pointing down.
+    pub gravity: Vector2<f32>,
+    // Fraction of the velocity kept at each update.
+    pub damping: f32,
+    // More iterations make the rope stiffer.
+    pub iteration_count: usize,
+}
+
+impl Default for VerletRopeDescriptor {
+    fn default() -> Self {
+        Self {
+            gravity: Vector2::new(0., 980.),
+            damping: 0.99,
+            iteration_count: 8,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct RopePoint {
+    position: Vector2<f32>,
+    previous_position: Vector2<f32>,
+    pinned: bool,
+}
+
+// Chain of points simulated with verlet integration, kept together by distance constraints,
+// e.g. for chains, cables and hair. Pinned points don't move on their own, and can be attached
+// to other objects by setting their position every frame.
+#[derive(Debug, PartialEq, Clone)]
+pub struct VerletRope {
+    desc: VerletRopeDescriptor,
+    points: Vec<RopePoint>,
+    // Rest length of the segment between each point and the next.
+    segment_lengths: Vec<f32>,
+}
+
+impl VerletRope {
+    // The rest lengths of the segments are the initial distances between the points.
+    pub fn new(points: &[Vector2<f32>], desc: &VerletRopeDescriptor) -> Self {
+        assert!(points.len() >= 2, "The rope must have at least 2 points");
+        let segment_lengths = points.windows(2).map(|w| (w[1] - w[0]).norm()).collect();
+        let points = points
+            .iter()
+            .map(|p| RopePoint {
+                position: *p,
+                previous_position: *p,
+                pinned: false,
+            })
+            .collect();
+        Self {
+            desc: desc.clone(),
+            points,
+            segment_lengths,
+        }
+    }
+
+    // Straight rope with the given number of segments.
+    pub fn line(
+        start: Vector2<f32>,
+        end: Vector2<f32>,
+        segment_count: usize,
+        desc: &VerletRopeDescriptor,
+    ) -> Self {
+        assert!(segment_count > 0, "The segment count must be higher than 0");
+        let points: Vec<_> = (0..=segment_count)
+            .map(|i| start + (end - start) * (i as f32 / segment_count as f32))
+            .collect();
+        Self::new(&points, desc)
+    }
+
+    pub fn descriptor(&self) -> &VerletRopeDescriptor {
+        &self.desc
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn position(&self, index: usize) -> &Vector2<f32> {
+        &self.points[index].position
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = &Vector2<f32>> {
+        self.points.iter().map(|p| &p.position)
+    }
+
+    // Moves the point without giving it any velocity.
+    pub fn set_position(&mut self, index: usize, position: Vector2<f32>) {
+        let point = &mut self.points[index];
+        point.position = position;
+        point.previous_position = position;
+    }
+
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.points[index].pinned
+    }
+
+    pub fn set_pinned(&mut self, index: usize, pinned: bool) {
+        self.points[index].pinned = pinned;
+    }
+
+    // Sum of the rest lengths of the segments.
+    pub fn rest_length(&self) -> f32 {
+        self.segment_lengths.iter().sum()
+    }
+
+    // Current length, longer than the rest length when the rope is stretched.
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|w| (w[1].position - w[0].position).norm())
+            .sum()
+    }
+
+    pub fn update(&mut self, dt: f32, colliders: &[RopeCollider]) {
+        let acceleration = self.desc.gravity * dt * dt;
+        for point in self.points.iter_mut().filter(|p| !p.pinned) {
+            let velocity = (point.position - point.previous_position) * self.desc.damping;
+            point.previous_position = point.position;
+            point.position += velocity + acceleration;
+        }
+
+        for _ in 0..self.desc.iteration_count {
+            self.solve_constraints();
+            for point in self.points.iter_mut().filter(|p| !p.pinned) {
+                for collider in colliders {
+                    collider.resolve(&mut point.position);
+                }
+            }
+        }
+    }
+
+    fn solve_constraints(&mut self) {
+        for (i, rest_length) in self.segment_lengths.iter().enumerate() {
+            let (head, tail) = self.points.split_at_mut(i + 1);
+            let (a, b) = (&mut head[i], &mut tail[0]);
+            let delta = b.position - a.position;
+            let distance = delta.norm();
+            if distance == 0. {
+                continue;
+            }
+            let wa = if a.pinned { 0. } else { 1. };
+            let wb = if b.pinned { 0. } else { 1. };
+            let total_weight = wa + wb;
+            if total_weight == 0. {
+                continue;
+            }
+            let correction = delta * ((distance - rest_length) / distance / total_weight);
+            a.position += correction * wa;
+            b.position -= correction * wb;
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RopeRibbonDescriptor {
+    pub width: f32,
+    // Rope length covered by the texture. The texture is repeated along the rope, and requires
+    // a sampler with repeat address mode. If None, the texture is stretched on the whole rope.
+    pub texture_length: Option<f32>,
+}
+
+impl Default for RopeRibbonDescriptor {
+    fn default() -> Self {
+        Self {
+            width: 4.,
+            texture_length: None,
+        }
+    }
+}
+
+// Textured strip following a rope. The texture u coordinate goes along the rope, the v
+// coordinate across it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RopeRibbon {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl RopeRibbon {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Rebuilds the vertices from the current rope positions.
+    pub fn update(&mut self, rope: &VerletRope, desc: &RopeRibbonDescriptor) {
+        self.vertices.clear();
+        self.indices.clear();
+
+        let positions: Vec<_> = rope.positions().copied().collect();
+        let u_scale = match desc.texture_length {
+            Some(texture_length) => 1. / texture_length,
+            None => {
+                let length = rope.length();
+                if length > 0. {
+                    1. / length
+                } else {
+                    0.
+                }
+            }
+        };
+        let half_width = desc.width * 0.5;
+        let mut arc_length = 0.;
+        for (i, p) in positions.iter().enumerate() {
+            if i > 0 {
+                arc_length += (p - positions[i - 1]).norm();
+            }
+            // At inner points, perpendicular to the average direction of the two segments.
+            let previous = positions[i.saturating_sub(1)];
+            let next = positions[std::cmp::min(i + 1, positions.len() - 1)];
+            let tangent = (next - previous)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| Vector2::new(1., 0.));
+            let normal = Vector2::new(-tangent.y, tangent.x);
+            let u = arc_length * u_scale;
+            let top = p - normal * half_width;
+            let bottom = p + normal * half_width;
+            self.vertices.push(Vertex::new([top.x, top.y], [u, 0.]));
+            self.vertices
+                .push(Vertex::new([bottom.x, bottom.y], [u, 1.]));
+        }
+
+        // Same winding as MeshTemplates::rectangle.
+        for i in 0..positions.len() as u32 - 1 {
+            let (top, bottom) = (i * 2, i * 2 + 1);
+            let (next_top, next_bottom) = (top + 2, bottom + 2);
+            self.indices
+                .extend([top, bottom, next_top, next_top, bottom, next_bottom]);
+        }
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn build(&self, instance: &gfx::Instance) -> Mesh<u32> {
+        Mesh::new(instance, &self.vertices, &self.indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn hanging_rope() -> VerletRope {
+        let mut rope = VerletRope::line(
+            Vector2::new(0., 0.),
+            Vector2::new(100., 0.),
+            10,
+            &VerletRopeDescriptor {
+                iteration_count: 20,
+                ..VerletRopeDescriptor::default()
+            },
+        );
+        rope.set_pinned(0, true);
+        rope
+    }
+
+    #[test]
+    fn creation() {
+        let rope = hanging_rope();
+        expect_that!(&rope.len(), eq(11));
+        expect_that!(&rope.position(5), eq(Vector2::new(50., 0.)));
+        expect_that!(&rope.rest_length(), eq(100.));
+        expect_that!(&rope.length(), eq(100.));
+        expect_that!(rope.is_pinned(0));
+        expect_that!(!rope.is_pinned(10));
+    }
+
+    #[test]
+    fn simulation() {
+        let mut rope = hanging_rope();
+        for _ in 0..600 {
+            rope.update(1. / 60., &[]);
+        }
+        // Hanging down from the pinned point, close to its rest length.
+        expect_that!(&rope.position(0), eq(Vector2::new(0., 0.)));
+        let end = rope.position(10);
+        expect_that!(end.x.abs() < 5.);
+        expect_that!(end.y > 95. && end.y < 102.);
+        expect_that!((rope.length() - rope.rest_length()).abs() < 2.);
+
+        // Pinned points follow their attachment.
+        rope.set_position(0, Vector2::new(20., 0.));
+        for _ in 0..600 {
+            rope.update(1. / 60., &[]);
+        }
+        expect_that!((rope.position(10).x - 20.).abs() < 5.);
+    }
+
+    #[test]
+    fn collision() {
+        let colliders = [
+            RopeCollider::Circle {
+                center: Vector2::new(0., 60.),
+                radius: 10.,
+            },
+            RopeCollider::Aabb(Aabb2::new(
+                Vector2::new(-50., 120.),
+                Vector2::new(50., 140.),
+            )),
+        ];
+        let mut rope = VerletRope::line(
+            Vector2::new(0., 0.),
+            Vector2::new(0., 200.),
+            40,
+            &VerletRopeDescriptor::default(),
+        );
+        rope.set_pinned(0, true);
+        for _ in 0..120 {
+            rope.update(1. / 60., &colliders);
+        }
+        for p in rope.positions() {
+            expect_that!((p - Vector2::new(0., 60.)).norm() >= 10. - 1e-3);
+            expect_that!(p.x <= -50. || p.x >= 50. || p.y <= 120. + 1e-3 || p.y >= 140. - 1e-3);
+        }
+
+        let mut p = Vector2::new(45., 122.);
+        colliders[1].resolve(&mut p);
+        expect_that!(&p, eq(Vector2::new(45., 120.)));
+        let mut p = Vector2::new(0., 60.);
+        colliders[0].resolve(&mut p);
+        expect_that!(&p, eq(Vector2::new(0., 50.)));
+    }
+
+    #[test]
+    fn ribbon() {
+        let rope = VerletRope::new(
+            &[
+                Vector2::new(0., 0.),
+                Vector2::new(10., 0.),
+                Vector2::new(30., 0.),
+            ],
+            &VerletRopeDescriptor::default(),
+        );
+        let mut ribbon = RopeRibbon::new();
+        ribbon.update(
+            &rope,
+            &RopeRibbonDescriptor {
+                width: 4.,
+                texture_length: None,
+            },
+        );
+        expect_that!(&ribbon.vertices().len(), eq(6));
+        expect_that!(
+            &ribbon.vertices()[2],
+            eq(Vertex::new([10., -2.], [1. / 3., 0.]))
+        );
+        expect_that!(&ribbon.vertices()[5], eq(Vertex::new([30., 2.], [1., 1.])));
+        expect_that!(
+            &ribbon.indices(),
+            eq(&[0, 1, 2, 2, 1, 3, 2, 3, 4, 4, 3, 5][..])
+        );
+
+        ribbon.update(
+            &rope,
+            &RopeRibbonDescriptor {
+                width: 4.,
+                texture_length: Some(20.),
+            },
+        );
+        expect_that!(
+            &ribbon.vertices()[4],
+            eq(Vertex::new([30., -2.], [1.5, 0.]))
+        );
+    }
+}
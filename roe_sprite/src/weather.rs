@@ -0,0 +1,469 @@
+use super::{StaticSprite, StaticSpriteBatch};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use roe_math::{Rotation2, Vector2};
+
+// Particles reaching the plane are removed, e.g. rain drops hitting the ground. The plane is
+// in world coordinates, and particles on the side the normal points away from are removed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WeatherKillPlane {
+    pub point: Vector2<f32>,
+    pub normal: Vector2<f32>,
+}
+
+impl WeatherKillPlane {
+    // Horizontal ground at the given height, y pointing down.
+    pub fn ground(y: f32) -> Self {
+        Self {
+            point: Vector2::new(0., y),
+            normal: Vector2::new(0., -1.),
+        }
+    }
+
+    fn is_behind(&self, p: &Vector2<f32>) -> bool {
+        (p - self.point).dot(&self.normal) < 0.
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WeatherLayerDescriptor {
+    // Particles per second at full intensity, for a screen of the reference size.
+    pub spawn_rate: f32,
+    pub reference_screen_size: Vector2<f32>,
+    pub max_particle_count: usize,
+    // In pixels per second, y pointing down.
+    pub fall_velocity: Vector2<f32>,
+    // Each particle has a random speed factor in [1 - variance, 1 + variance].
+    pub speed_variance: f32,
+    // Fraction of the wind velocity added to the particles.
+    pub wind_influence: f32,
+    // Horizontal oscillation, e.g. for snow flakes.
+    pub sway_amplitude: f32,
+    pub sway_frequency: f32,
+    pub min_size: f32,
+    pub max_size: f32,
+    // Particles are stretched along their velocity by the distance covered in this time, e.g.
+    // for rain streaks.
+    pub streak_duration: f32,
+    // How much the particles move with the camera: 1 for particles at the depth of the scene,
+    // lower values for particles closer to the viewer.
+    pub parallax: f32,
+    // Screen wetness gained per second at full intensity. Lost at the drying rate when the
+    // intensity is 0.
+    pub wetting_rate: f32,
+    pub drying_rate: f32,
+    // The same seed always generates the same particles.
+    pub seed: u64,
+}
+
+impl WeatherLayerDescriptor {
+    pub fn rain() -> Self {
+        Self {
+            spawn_rate: 600.,
+            reference_screen_size: Vector2::new(1920., 1080.),
+            max_particle_count: 2000,
+            fall_velocity: Vector2::new(0., 1400.),
+            speed_variance: 0.2,
+            wind_influence: 0.3,
+            sway_amplitude: 0.,
+            sway_frequency: 0.,
+            min_size: 1.,
+            max_size: 2.,
+            streak_duration: 0.02,
+            parallax: 1.,
+            wetting_rate: 0.1,
+            drying_rate: 0.02,
+            seed: 0,
+        }
+    }
+
+    pub fn snow() -> Self {
+        Self {
+            spawn_rate: 150.,
+            reference_screen_size: Vector2::new(1920., 1080.),
+            max_particle_count: 1500,
+            fall_velocity: Vector2::new(0., 80.),
+            speed_variance: 0.4,
+            wind_influence: 1.,
+            sway_amplitude: 20.,
+            sway_frequency: 0.5,
+            min_size: 2.,
+            max_size: 6.,
+            streak_duration: 0.,
+            parallax: 1.,
+            wetting_rate: 0.,
+            drying_rate: 0.,
+            seed: 0,
+        }
+    }
+}
+
+impl Default for WeatherLayerDescriptor {
+    fn default() -> Self {
+        Self::rain()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct WeatherParticle {
+    // In screen coordinates.
+    position: Vector2<f32>,
+    speed_factor: f32,
+    size: f32,
+    sway_phase: f32,
+}
+
+// Screen space rain or snow emitter. Particles are spawned above the screen and fall through
+// it. When the camera moves, the particles move the other way scaled by the parallax, and wrap
+// around the screen, so that they always cover it. Particles can be drawn as a static sprite
+// batch in screen coordinates, with a single texture.
+#[derive(Debug, Clone)]
+pub struct WeatherLayer {
+    desc: WeatherLayerDescriptor,
+    rng: StdRng,
+    particles: Vec<WeatherParticle>,
+    intensity: f32,
+    wind: Vector2<f32>,
+    wetness: f32,
+    time: f32,
+    spawn_accumulator: f32,
+    screen_size: Vector2<f32>,
+    camera_position: Option<Vector2<f32>>,
+    impacts: Vec<Vector2<f32>>,
+    kill_planes: Vec<WeatherKillPlane>,
+}
+
+impl WeatherLayer {
+    pub fn new(desc: &WeatherLayerDescriptor) -> Self {
+        assert!(
+            desc.min_size > 0. && desc.min_size <= desc.max_size,
+            "The minimum size must be higher than 0 and not higher than the maximum size"
+        );
+        Self {
+            desc: desc.clone(),
+            rng: StdRng::seed_from_u64(desc.seed),
+            particles: Vec::new(),
+            intensity: 1.,
+            wind: Vector2::zeros(),
+            wetness: 0.,
+            time: 0.,
+            spawn_accumulator: 0.,
+            screen_size: desc.reference_screen_size,
+            camera_position: None,
+            impacts: Vec::new(),
+            kill_planes: Vec::new(),
+        }
+    }
+
+    pub fn descriptor(&self) -> &WeatherLayerDescriptor {
+        &self.desc
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    // From 0 (no new particles) to 1. Existing particles keep falling.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.clamp(0., 1.);
+    }
+
+    pub fn wind(&self) -> &Vector2<f32> {
+        &self.wind
+    }
+
+    // In pixels per second.
+    pub fn set_wind(&mut self, wind: Vector2<f32>) {
+        self.wind = wind;
+    }
+
+    // From 0 (dry) to 1, to drive a wetness post effect.
+    pub fn wetness(&self) -> f32 {
+        self.wetness
+    }
+
+    pub fn kill_planes(&self) -> &[WeatherKillPlane] {
+        &self.kill_planes
+    }
+
+    pub fn set_kill_planes(&mut self, kill_planes: Vec<WeatherKillPlane>) {
+        self.kill_planes = kill_planes;
+    }
+
+    // World positions where particles hit a kill plane in the last update, e.g. to spawn
+    // splashes.
+    pub fn impacts(&self) -> &[Vector2<f32>] {
+        &self.impacts
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.impacts.clear();
+        self.spawn_accumulator = 0.;
+    }
+
+    // The camera position is the world position of the top left corner of the screen.
+    pub fn update(&mut self, dt: f32, camera_position: Vector2<f32>, screen_size: Vector2<f32>) {
+        self.time += dt;
+        self.screen_size = screen_size;
+        let camera_delta = match self.camera_position {
+            Some(previous) => camera_position - previous,
+            None => Vector2::zeros(),
+        };
+        self.camera_position = Some(camera_position);
+
+        self.update_wetness(dt);
+        self.move_particles(dt, &camera_delta);
+        self.kill_particles(&camera_position);
+        self.spawn_particles(dt);
+    }
+
+    fn update_wetness(&mut self, dt: f32) {
+        let change = if self.intensity > 0. {
+            self.desc.wetting_rate * self.intensity
+        } else {
+            -self.desc.drying_rate
+        };
+        self.wetness = (self.wetness + change * dt).clamp(0., 1.);
+    }
+
+    // Margin around the screen where particles are kept, so that streaks and large flakes
+    // don't pop at the borders.
+    fn margin(&self) -> f32 {
+        self.desc.max_size + self.max_speed() * self.desc.streak_duration
+    }
+
+    fn max_speed(&self) -> f32 {
+        (self.desc.fall_velocity + self.wind * self.desc.wind_influence).norm()
+            * (1. + self.desc.speed_variance)
+    }
+
+    fn particle_velocity(&self, particle: &WeatherParticle) -> Vector2<f32> {
+        let sway = if self.desc.sway_amplitude > 0. {
+            let angle = std::f32::consts::TAU * self.desc.sway_frequency;
+            self.desc.sway_amplitude * angle * (angle * self.time + particle.sway_phase).cos()
+        } else {
+            0.
+        };
+        self.desc.fall_velocity * particle.speed_factor
+            + self.wind * self.desc.wind_influence
+            + Vector2::new(sway, 0.)
+    }
+
+    fn move_particles(&mut self, dt: f32, camera_delta: &Vector2<f32>) {
+        let margin = self.margin();
+        let min = Vector2::new(-margin, -margin);
+        let extent = self.screen_size + Vector2::new(margin, margin) * 2.;
+        let camera_shift = camera_delta * self.desc.parallax;
+        for i in 0..self.particles.len() {
+            let velocity = self.particle_velocity(&self.particles[i]);
+            let particle = &mut self.particles[i];
+            particle.position -= camera_shift;
+            for axis in 0..2 {
+                let offset = (particle.position[axis] - min[axis]).rem_euclid(extent[axis]);
+                particle.position[axis] = min[axis] + offset;
+            }
+            particle.position += velocity * dt;
+        }
+    }
+
+    fn kill_particles(&mut self, camera_position: &Vector2<f32>) {
+        self.impacts.clear();
+        let bottom = self.screen_size.y + self.margin();
+        let kill_planes = &self.kill_planes;
+        let impacts = &mut self.impacts;
+        self.particles.retain(|particle| {
+            let world_position = camera_position + particle.position;
+            if kill_planes
+                .iter()
+                .any(|plane| plane.is_behind(&world_position))
+            {
+                impacts.push(world_position);
+                return false;
+            }
+            particle.position.y <= bottom
+        });
+    }
+
+    fn spawn_particles(&mut self, dt: f32) {
+        let reference_area = self.desc.reference_screen_size.x * self.desc.reference_screen_size.y;
+        let area_scale = if reference_area > 0. {
+            self.screen_size.x * self.screen_size.y / reference_area
+        } else {
+            1.
+        };
+        self.spawn_accumulator += self.desc.spawn_rate * self.intensity * area_scale * dt;
+        let margin = self.margin();
+        // Upwind particles are spawned further to the side, to cover the screen when falling
+        // diagonally.
+        let drift = self.wind.x * self.desc.wind_influence / self.desc.fall_velocity.y.max(1.)
+            * self.screen_size.y;
+        let (min_x, max_x) = if drift > 0. {
+            (-margin - drift, self.screen_size.x + margin)
+        } else {
+            (-margin, self.screen_size.x + margin - drift)
+        };
+        while self.spawn_accumulator >= 1. {
+            self.spawn_accumulator -= 1.;
+            if self.particles.len() >= self.desc.max_particle_count {
+                continue;
+            }
+            let particle = WeatherParticle {
+                position: Vector2::new(self.rng.gen_range(min_x..max_x), -margin),
+                speed_factor: 1.
+                    + self
+                        .rng
+                        .gen_range(-self.desc.speed_variance..=self.desc.speed_variance),
+                size: self.rng.gen_range(self.desc.min_size..=self.desc.max_size),
+                sway_phase: self.rng.gen_range(0. ..std::f32::consts::TAU),
+            };
+            self.particles.push(particle);
+        }
+    }
+
+    // Adds a sprite for each particle, in screen coordinates.
+    pub fn fill_batch(&self, batch: &mut StaticSpriteBatch) {
+        for particle in self.particles.iter() {
+            let velocity = self.particle_velocity(particle);
+            let length = particle
+                .size
+                .max(velocity.norm() * self.desc.streak_duration);
+            let angle = if self.desc.streak_duration > 0. && velocity.norm() > 0. {
+                velocity.y.atan2(velocity.x) - std::f32::consts::FRAC_PI_2
+            } else {
+                0.
+            };
+            let size = Vector2::new(particle.size, length);
+            let transform = roe_math::translation2(&particle.position)
+                * roe_math::rotation2(&Rotation2::new(angle))
+                * roe_math::translation2(&(size * -0.5));
+            batch.push(&StaticSprite::new(transform, size));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const SCREEN: Vector2<f32> = Vector2::new(800., 600.);
+
+    fn positions(layer: &WeatherLayer) -> Vec<Vector2<f32>> {
+        layer.particles.iter().map(|p| p.position).collect()
+    }
+
+    #[test]
+    fn spawning() {
+        let mut layer = WeatherLayer::new(&WeatherLayerDescriptor {
+            spawn_rate: 100.,
+            reference_screen_size: SCREEN,
+            max_particle_count: 150,
+            fall_velocity: Vector2::new(0., 400.),
+            ..WeatherLayerDescriptor::rain()
+        });
+        layer.update(0.5, Vector2::zeros(), SCREEN);
+        expect_that!(&layer.particle_count(), eq(50));
+
+        layer.set_intensity(0.5);
+        layer.update(0.5, Vector2::zeros(), SCREEN);
+        expect_that!(&layer.particle_count(), eq(75));
+
+        // Particles fall out of the screen.
+        layer.set_intensity(0.);
+        for _ in 0..30 {
+            layer.update(0.1, Vector2::zeros(), SCREEN);
+        }
+        expect_that!(&layer.particle_count(), eq(0));
+
+        layer.set_intensity(1.);
+        layer.update(5., Vector2::zeros(), SCREEN);
+        expect_that!(&layer.particle_count(), eq(150));
+
+        let mut batch = StaticSpriteBatch::new();
+        layer.fill_batch(&mut batch);
+        expect_that!(&batch.len(), eq(150));
+    }
+
+    #[test]
+    fn determinism() {
+        let desc = WeatherLayerDescriptor::snow();
+        let mut a = WeatherLayer::new(&desc);
+        let mut b = WeatherLayer::new(&desc);
+        for _ in 0..10 {
+            a.update(0.1, Vector2::zeros(), SCREEN);
+            b.update(0.1, Vector2::zeros(), SCREEN);
+        }
+        expect_that!(a.particle_count() > 0);
+        expect_that!(&positions(&a), eq(positions(&b)));
+    }
+
+    #[test]
+    fn wind_and_camera() {
+        let mut layer = WeatherLayer::new(&WeatherLayerDescriptor {
+            speed_variance: 0.,
+            ..WeatherLayerDescriptor::rain()
+        });
+        layer.update(0.05, Vector2::zeros(), SCREEN);
+        layer.set_intensity(0.);
+        let before = positions(&layer);
+
+        layer.set_wind(Vector2::new(200., 0.));
+        layer.update(0.01, Vector2::zeros(), SCREEN);
+        for (b, a) in before.iter().zip(positions(&layer).iter()) {
+            expect_that!(((a - b) - Vector2::new(0.6, 14.)).norm() < 1e-3);
+        }
+
+        // Moving the camera to the right moves the particles to the left, except the ones
+        // wrapping around the left border.
+        let before = positions(&layer);
+        layer.set_wind(Vector2::zeros());
+        layer.update(0., Vector2::new(10., 0.), SCREEN);
+        for (b, a) in before.iter().zip(positions(&layer).iter()) {
+            expect_that!((a.x - (b.x - 10.)).abs() < 1e-3 || a.x > SCREEN.x);
+            expect_that!(&a.y, eq(b.y));
+        }
+    }
+
+    #[test]
+    fn kill_planes() {
+        let mut layer = WeatherLayer::new(&WeatherLayerDescriptor::rain());
+        layer.set_kill_planes(vec![WeatherKillPlane::ground(1200.)]);
+        // The ground is 200 pixels below the top of the screen.
+        let camera = Vector2::new(0., 1000.);
+        layer.update(0.05, camera, SCREEN);
+        let count = layer.particle_count();
+        expect_that!(count > 0);
+        layer.set_intensity(0.);
+        let mut impacts = Vec::new();
+        for _ in 0..10 {
+            layer.update(0.05, camera, SCREEN);
+            impacts.extend_from_slice(layer.impacts());
+        }
+        expect_that!(&layer.particle_count(), eq(0));
+        expect_that!(&impacts.len(), eq(count));
+        for impact in impacts.iter() {
+            expect_that!(impact.y > 1200.);
+        }
+    }
+
+    #[test]
+    fn wetness() {
+        let mut layer = WeatherLayer::new(&WeatherLayerDescriptor {
+            wetting_rate: 0.5,
+            drying_rate: 0.25,
+            ..WeatherLayerDescriptor::rain()
+        });
+        layer.update(1., Vector2::zeros(), SCREEN);
+        expect_that!(&layer.wetness(), close_to(0.5, 1e-6));
+        layer.update(2., Vector2::zeros(), SCREEN);
+        expect_that!(&layer.wetness(), eq(1.));
+        layer.set_intensity(0.);
+        layer.update(1., Vector2::zeros(), SCREEN);
+        expect_that!(&layer.wetness(), close_to(0.75, 1e-6));
+    }
+}
@@ -59,7 +59,7 @@ impl Debug for FontLibrary {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Face {
     ft_face: ft::Face,
     hb_face: hb::Shared<hb::Face<'static>>,
@@ -78,12 +78,31 @@ impl Face {
         Ok(Self { ft_face, hb_face })
     }
 
+    // The size is in points, the resolution in dots per inch. With a resolution of 72 the size
+    // is in pixels.
+    pub fn set_char_size(
+        &self,
+        size: FontSize,
+        resolution: FontResolution,
+    ) -> Result<(), FontError> {
+        self.ft_face
+            .set_char_size(0, fsize_to_i26dot6(size) as isize, 0, resolution)?;
+        Ok(())
+    }
+
     pub fn load_char(&self, c: char) -> Result<&ft::GlyphSlot, FontError> {
         self.ft_face
             .load_char(c as usize, ft::face::LoadFlag::RENDER)?;
         Ok(self.ft_face.glyph())
     }
 
+    // Loads a glyph by index, e.g. a glyph from the text shaping output.
+    pub fn load_glyph(&self, char_index: CharIndex) -> Result<&ft::GlyphSlot, FontError> {
+        self.ft_face
+            .load_glyph(char_index, ft::face::LoadFlag::RENDER)?;
+        Ok(self.ft_face.glyph())
+    }
+
     pub fn char_index(&self, c: char) -> CharIndex {
         self.ft_face.get_char_index(c as usize)
     }
@@ -128,8 +147,7 @@ impl GlyphSet {
         size: FontSize,
         resolution: FontResolution,
     ) -> Result<Self, FontError> {
-        face.ft_face
-            .set_char_size(0, fsize_to_i26dot6(size) as isize, 0, resolution)?;
+        face.set_char_size(size, resolution)?;
         let mut glyphs = Vec::with_capacity(characters.len());
         for c in characters {
             glyphs.push(Glyph::new(face, *c)?);
@@ -161,7 +179,7 @@ pub struct Font {
 }
 
 impl Font {
-    const RESOLUTION: FontResolution = 300;
+    pub(crate) const RESOLUTION: FontResolution = 300;
 
     pub fn new(
         instance: &gfx::Instance,
@@ -176,8 +194,12 @@ impl Font {
         let glyph_set = GlyphSet::new(face, characters, size, Self::RESOLUTION)?;
         let glyph_atlas_texture = Self::create_glyph_atlas_texture(instance, &glyph_set);
         let glyph_atlas_sampler = gfx::Sampler::new(instance, &gfx::SamplerDescriptor::default());
-        let glyph_atlas_uniform =
-            UniformConstants::new(instance, &glyph_atlas_texture, &glyph_atlas_sampler);
+        let glyph_atlas_uniform = UniformConstants::new_with_label(
+            instance,
+            Some("glyph_atlas"),
+            &glyph_atlas_texture,
+            &glyph_atlas_sampler,
+        );
         let glyph_atlas_mesh = Self::create_glyph_atlas_mesh(instance, &glyph_set);
         let glyph_atlas_map = Self::create_glyph_atlas_map(&glyph_set);
 
@@ -192,7 +214,7 @@ impl Font {
         })
     }
 
-    fn create_shaper(face: &Face, size: FontSize) -> hb::Owned<hb::Font<'static>> {
+    pub(crate) fn create_shaper(face: &Face, size: FontSize) -> hb::Owned<hb::Font<'static>> {
         let mut hb_font = hb::Font::new(face.hb_face.clone());
         let size_ppem = fsize_to_ppem(size, Self::RESOLUTION);
         hb_font.set_scale(size_ppem, size_ppem);
@@ -225,7 +247,7 @@ impl Font {
         let glyph_atlas_texture = gfx::Texture::new(
             instance,
             &gfx::TextureDescriptor {
-                label: None,
+                label: Some("glyph_atlas"),
                 size: glyph_set.extent,
                 mip_level_count: 1,
                 sample_count: 1,
@@ -275,7 +297,12 @@ impl Font {
                 vertices_begin + 2,
             ]);
         }
-        Mesh::new(instance, &glyph_atlas_vertices, &glyph_atlas_indices)
+        Mesh::new_with_label(
+            instance,
+            Some("glyph_atlas"),
+            &glyph_atlas_vertices,
+            &glyph_atlas_indices,
+        )
     }
 
     fn create_glyph_atlas_map(glyph_set: &GlyphSet) -> HashMap<CharIndex, GlyphRenderingInfo> {
@@ -311,6 +338,10 @@ impl Font {
         self.glyph_atlas_mesh.index_buffer()
     }
 
+    pub fn index_format(&self) -> gfx::IndexFormat {
+        self.glyph_atlas_mesh.index_format()
+    }
+
     pub fn vertex_buffer(&self) -> &gfx::Buffer {
         self.glyph_atlas_mesh.vertex_buffer()
     }
@@ -324,6 +355,7 @@ impl Font {
 pub enum FontError {
     FontCreationFailed(ft::Error),
     ShaperCreationFailed(std::io::Error),
+    GlyphAtlasFull,
 }
 
 impl std::fmt::Display for FontError {
@@ -335,6 +367,7 @@ impl std::fmt::Display for FontError {
             FontError::ShaperCreationFailed(e) => {
                 write!(f, "Shaper creation failed ({})", e)
             }
+            FontError::GlyphAtlasFull => write!(f, "Glyph atlas full"),
         }
     }
 }
@@ -344,6 +377,7 @@ impl std::error::Error for FontError {
         match self {
             FontError::FontCreationFailed(e) => Some(e),
             FontError::ShaperCreationFailed(e) => Some(e),
+            FontError::GlyphAtlasFull => None,
         }
     }
 }
@@ -0,0 +1,419 @@
+extern crate harfbuzz_rs as hb;
+
+use std::collections::HashMap;
+
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+use super::{
+    i26dot6_to_fsize, CharIndex, Face, Font, FontError, FontSize, Mesh, MeshIndex, TextShapingInfo,
+    UniformConstants, Vertex,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GlyphAtlasDescriptor {
+    pub label: Option<String>,
+    // Width and height of each page in pixels.
+    pub page_size: u32,
+    // Layers of the atlas texture. When all pages are full, new glyphs can't be added until the
+    // atlas is cleared.
+    pub page_count: u32,
+}
+
+impl Default for GlyphAtlasDescriptor {
+    fn default() -> Self {
+        Self {
+            label: None,
+            page_size: 512,
+            page_count: 1,
+        }
+    }
+}
+
+// Empty pixels around each glyph, so that filtering doesn't bleed into the neighbors.
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    // First free column.
+    x: u32,
+}
+
+// Packs rectangles in rows as tall as the first rectangle placed in them. Glyphs of the same
+// font have similar heights, so little space is wasted.
+#[derive(Debug, PartialEq, Clone)]
+struct ShelfAllocator {
+    page_size: u32,
+    page_count: u32,
+    page: u32,
+    // Shelves of the current page, previous pages are full.
+    shelves: Vec<Shelf>,
+    next_shelf_y: u32,
+}
+
+impl ShelfAllocator {
+    fn new(page_size: u32, page_count: u32) -> Self {
+        Self {
+            page_size,
+            page_count,
+            page: 0,
+            shelves: Vec::new(),
+            next_shelf_y: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.page = 0;
+        self.shelves.clear();
+        self.next_shelf_y = 0;
+    }
+
+    // Page and position of the top left corner, None if there is no space left.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32, u32)> {
+        if width > self.page_size || height > self.page_size {
+            return None;
+        }
+        while self.page < self.page_count {
+            let page_size = self.page_size;
+            let best_shelf = self
+                .shelves
+                .iter_mut()
+                .filter(|shelf| shelf.height >= height && shelf.x + width <= page_size)
+                .min_by_key(|shelf| shelf.height - height);
+            if let Some(shelf) = best_shelf {
+                let x = shelf.x;
+                shelf.x += width;
+                return Some((self.page, x, shelf.y));
+            }
+            if self.next_shelf_y + height <= self.page_size {
+                let y = self.next_shelf_y;
+                self.shelves.push(Shelf {
+                    y,
+                    height,
+                    x: width,
+                });
+                self.next_shelf_y += height;
+                return Some((self.page, 0, y));
+            }
+            self.page += 1;
+            self.shelves.clear();
+            self.next_shelf_y = 0;
+        }
+        None
+    }
+}
+
+// Location of a rasterized glyph in the atlas.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GlyphAtlasEntry {
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // Offset of the top left corner of the bitmap from the pen position.
+    pub bearing: Vector2<f32>,
+}
+
+impl GlyphAtlasEntry {
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+// Font whose glyphs are rasterized into the atlas texture the first time they are needed,
+// unlike Font which rasterizes a fixed character set upfront. Suited to large character sets,
+// e.g. CJK text or user input, of which only a few glyphs are displayed at once.
+#[derive(Debug)]
+pub struct GlyphAtlas {
+    face: Face,
+    size: FontSize,
+    hb_font: hb::Owned<hb::Font<'static>>,
+    page_size: u32,
+    texture: gfx::Texture,
+    _sampler: gfx::Sampler,
+    uniform_constants: UniformConstants,
+    allocator: ShelfAllocator,
+    glyphs: HashMap<CharIndex, GlyphAtlasEntry>,
+}
+
+impl GlyphAtlas {
+    pub fn new(
+        instance: &gfx::Instance,
+        face: &Face,
+        size: FontSize,
+        desc: &GlyphAtlasDescriptor,
+    ) -> Self {
+        assert!(size > 0.);
+        let page_size = desc.page_size.max(1);
+        let page_count = desc.page_count.max(1);
+        let texture = gfx::Texture::new(
+            instance,
+            &gfx::TextureDescriptor {
+                label: desc.label.as_deref(),
+                size: gfx::Extent3d {
+                    width: page_size,
+                    height: page_size,
+                    depth_or_array_layers: page_count,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: gfx::TextureDimension::D2,
+                format: gfx::TextureFormat::R8Unorm,
+                usage: gfx::TextureUsage::TEXTURE_BINDING | gfx::TextureUsage::COPY_DST,
+            },
+        );
+        let view = texture.create_view(&gfx::TextureViewDescriptor {
+            dimension: Some(gfx::TextureViewDimension::D2Array),
+            ..gfx::TextureViewDescriptor::default()
+        });
+        let sampler = gfx::Sampler::new(instance, &gfx::SamplerDescriptor::default());
+        let uniform_constants =
+            UniformConstants::new_with_label(instance, desc.label.as_deref(), &view, &sampler);
+        Self {
+            face: face.clone(),
+            size,
+            hb_font: Font::create_shaper(face, size),
+            page_size,
+            texture,
+            _sampler: sampler,
+            uniform_constants,
+            allocator: ShelfAllocator::new(page_size, page_count),
+            glyphs: HashMap::new(),
+        }
+    }
+
+    pub fn size(&self) -> FontSize {
+        self.size
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    // Number of glyphs rasterized so far.
+    pub fn glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn contains(&self, char_index: CharIndex) -> bool {
+        self.glyphs.contains_key(&char_index)
+    }
+
+    // Forgets all glyphs, they are rasterized again when needed. Meshes created before are
+    // invalidated.
+    pub fn clear(&mut self) {
+        self.allocator.clear();
+        self.glyphs.clear();
+    }
+
+    pub fn shape_text(&self, text: &str) -> TextShapingInfo {
+        let buffer = hb::UnicodeBuffer::new().add_str(text);
+        hb::shape(&self.hb_font, buffer, &[])
+    }
+
+    // Rasterizes the glyph if it isn't in the atlas yet.
+    pub fn glyph(
+        &mut self,
+        instance: &gfx::Instance,
+        char_index: CharIndex,
+    ) -> Result<GlyphAtlasEntry, FontError> {
+        if let Some(entry) = self.glyphs.get(&char_index) {
+            return Ok(*entry);
+        }
+
+        self.face.set_char_size(self.size, Font::RESOLUTION)?;
+        let glyph = self.face.load_glyph(char_index)?;
+        let bitmap = glyph.bitmap();
+        let (width, height) = (bitmap.width().max(0) as u32, bitmap.rows().max(0) as u32);
+        let bearing = Vector2::new(glyph.bitmap_left() as f32, -glyph.bitmap_top() as f32);
+        if width == 0 || height == 0 {
+            let entry = GlyphAtlasEntry {
+                page: 0,
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+                bearing,
+            };
+            self.glyphs.insert(char_index, entry);
+            return Ok(entry);
+        }
+
+        let (page, x, y) = self
+            .allocator
+            .allocate(width + 2 * GLYPH_PADDING, height + 2 * GLYPH_PADDING)
+            .ok_or(FontError::GlyphAtlasFull)?;
+        let pitch = bitmap.pitch().unsigned_abs() as usize;
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in bitmap.buffer().chunks(pitch).take(height as usize) {
+            pixels.extend_from_slice(&row[..width as usize]);
+        }
+        self.texture.write(
+            instance,
+            0,
+            gfx::Origin3d {
+                x: x + GLYPH_PADDING,
+                y: y + GLYPH_PADDING,
+                z: page,
+            },
+            pixels.as_slice(),
+            gfx::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: core::num::NonZeroU32::new(width),
+                rows_per_image: core::num::NonZeroU32::new(height),
+            },
+            gfx::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let entry = GlyphAtlasEntry {
+            page,
+            x: x + GLYPH_PADDING,
+            y: y + GLYPH_PADDING,
+            width,
+            height,
+            bearing,
+        };
+        self.glyphs.insert(char_index, entry);
+        Ok(entry)
+    }
+
+    // Mesh of the shaped text, with the pen starting at the origin, rasterizing the missing
+    // glyphs. Draw it with Renderer::draw_text_mesh. The vertices are laid out in the frame
+    // arena before being uploaded.
+    pub fn text_mesh(
+        &mut self,
+        instance: &gfx::Instance,
+        text: &str,
+        arena: &gfx::FrameArena,
+    ) -> Result<Mesh, FontError> {
+        let shaping_output = self.shape_text(text);
+        let positions = shaping_output.get_glyph_positions();
+        let infos = shaping_output.get_glyph_infos();
+        let page_size = self.page_size as f32;
+
+        let mut vertices = arena.vec_with_capacity(infos.len() * 4);
+        let mut indices = arena.vec_with_capacity(infos.len() * 6);
+        let mut cursor_pos = Vector2::<f32>::zeros();
+        for (position, info) in positions.iter().zip(infos) {
+            let entry = self.glyph(instance, info.codepoint)?;
+            if !entry.is_empty() {
+                let x = cursor_pos.x + entry.bearing.x + i26dot6_to_fsize(position.x_offset);
+                let y = cursor_pos.y + entry.bearing.y + i26dot6_to_fsize(position.y_offset);
+                let (w, h) = (entry.width as f32, entry.height as f32);
+                let (u0, v0) = (entry.x as f32 / page_size, entry.y as f32 / page_size);
+                let (u1, v1) = (u0 + w / page_size, v0 + h / page_size);
+                let layer = entry.page as f32;
+
+                let vertices_begin = vertices.len() as MeshIndex;
+                vertices.extend_from_slice(&[
+                    Vertex::new([x, y], [u0, v0, layer]),
+                    Vertex::new([x, y + h], [u0, v1, layer]),
+                    Vertex::new([x + w, y + h], [u1, v1, layer]),
+                    Vertex::new([x + w, y], [u1, v0, layer]),
+                ]);
+                indices.extend_from_slice(&[
+                    vertices_begin,
+                    vertices_begin + 1,
+                    vertices_begin + 3,
+                    vertices_begin + 3,
+                    vertices_begin + 1,
+                    vertices_begin + 2,
+                ]);
+            }
+            cursor_pos.x += i26dot6_to_fsize(position.x_advance);
+            cursor_pos.y += i26dot6_to_fsize(position.y_advance);
+        }
+        Ok(Mesh::new(instance, &vertices, &indices))
+    }
+
+    pub fn uniform_constants(&self) -> &UniformConstants {
+        &self.uniform_constants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::FontLibrary, *};
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn shelf_allocation() {
+        let mut allocator = ShelfAllocator::new(16, 2);
+        expect_that!(&allocator.allocate(10, 6), eq(Some((0, 0, 0))));
+        // Beside the first rectangle, in the shortest shelf that fits.
+        expect_that!(&allocator.allocate(6, 4), eq(Some((0, 10, 0))));
+        expect_that!(&allocator.allocate(8, 6), eq(Some((0, 0, 6))));
+        expect_that!(&allocator.allocate(8, 3), eq(Some((0, 8, 6))));
+        // No space left in the first page.
+        expect_that!(&allocator.allocate(4, 5), eq(Some((1, 0, 0))));
+        expect_that!(&allocator.allocate(16, 11), eq(Some((1, 0, 5))));
+        expect_that!(&allocator.allocate(4, 1), eq(Some((1, 4, 0))));
+        expect_that!(&allocator.allocate(1, 1), eq(Some((1, 8, 0))));
+        expect_that!(&allocator.allocate(16, 1), eq(None));
+        expect_that!(&allocator.allocate(17, 1), eq(None));
+
+        allocator.clear();
+        expect_that!(&allocator.allocate(16, 16), eq(Some((0, 0, 0))));
+    }
+
+    #[test]
+    fn glyph_caching() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let lib = FontLibrary::new().unwrap();
+        let face = Face::from_file(&lib, "data/fonts/Roboto-Regular.ttf", 0).unwrap();
+        let mut atlas = GlyphAtlas::new(&instance, &face, 12., &GlyphAtlasDescriptor::default());
+        expect_that!(&atlas.glyph_count(), eq(0));
+
+        let mesh = atlas
+            .text_mesh(&instance, "Hello world", &gfx::FrameArena::new())
+            .unwrap();
+        // The space has no bitmap.
+        expect_that!(&atlas.glyph_count(), eq(8));
+        expect_that!(&mesh.index_count(), eq(60));
+        expect_that!(atlas.contains(face.char_index('w')));
+        expect_that!(atlas
+            .glyph(&instance, face.char_index(' '))
+            .unwrap()
+            .is_empty());
+
+        let entry = atlas.glyph(&instance, face.char_index('l')).unwrap();
+        expect_that!(&entry.page, eq(0));
+        expect_that!(entry.width > 0 && entry.height > 0);
+        atlas
+            .text_mesh(&instance, "low", &gfx::FrameArena::new())
+            .unwrap();
+        expect_that!(&atlas.glyph_count(), eq(8));
+
+        atlas.clear();
+        expect_that!(&atlas.glyph_count(), eq(0));
+    }
+
+    #[test]
+    fn full_atlas() {
+        let instance = gfx::Instance::new(&gfx::InstanceDescriptor::default()).unwrap();
+        let lib = FontLibrary::new().unwrap();
+        let face = Face::from_file(&lib, "data/fonts/Roboto-Regular.ttf", 0).unwrap();
+        let mut atlas = GlyphAtlas::new(
+            &instance,
+            &face,
+            12.,
+            &GlyphAtlasDescriptor {
+                page_size: 64,
+                ..GlyphAtlasDescriptor::default()
+            },
+        );
+        let error = atlas.text_mesh(
+            &instance,
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            &gfx::FrameArena::new(),
+        );
+        expect_that!(matches!(error, Err(FontError::GlyphAtlasFull)));
+    }
+}
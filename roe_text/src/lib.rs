@@ -1,5 +1,8 @@
 mod font;
 pub use font::*;
 
+mod glyph_atlas;
+pub use glyph_atlas::*;
+
 mod text_renderer;
 pub use text_renderer::*;
@@ -6,9 +6,15 @@ layout(location = 1) in vec3 inTexCoords;
 layout(location = 0) out vec4 outColor;
 layout(set = 0, binding = 0) uniform texture2DArray uColorTex;
 layout(set = 0, binding = 1) uniform sampler uColorTexSampler;
+layout(push_constant) uniform PushConstant {
+    layout(offset = 96) uint colorConversion;
+} pushConstant;
+
+#include <roe/color_conversion.glsl>
 
 void main() {
     float alpha = texture(sampler2DArray(uColorTex, uColorTexSampler), inTexCoords).r;
     vec4 texColor = vec4(1., 1., 1., alpha);
     outColor = inColor * texColor;
-}
\ No newline at end of file
+    outColor.rgb = convertColor(outColor.rgb, pushConstant.colorConversion);
+}
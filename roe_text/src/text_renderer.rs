@@ -10,7 +10,7 @@ use roe_graphics as gfx;
 
 use roe_math::{HomogeneousMatrix2, HomogeneousMatrix3, HomogeneousVector2, HomogeneousVector3};
 
-use super::{i26dot6_to_fsize, Font, GlyphRenderingInfo};
+use super::{i26dot6_to_fsize, Font, GlyphAtlas, GlyphRenderingInfo};
 
 #[repr(C, packed)]
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -78,12 +78,21 @@ impl UniformConstants {
         instance: &gfx::Instance,
         texture: &gfx::TextureView,
         sampler: &gfx::Sampler,
+    ) -> Self {
+        Self::new_with_label(instance, None, texture, sampler)
+    }
+
+    pub fn new_with_label(
+        instance: &gfx::Instance,
+        label: Option<&str>,
+        texture: &gfx::TextureView,
+        sampler: &gfx::Sampler,
     ) -> Self {
         let layout = bind_group_layout(instance);
         let bind_group = gfx::BindGroup::new(
             instance,
             &gfx::BindGroupDescriptor {
-                label: None,
+                label,
                 layout: &layout,
                 entries: &[
                     gfx::BindGroupEntry {
@@ -103,6 +112,7 @@ impl UniformConstants {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct RenderPipelineDescriptor {
+    pub label: Option<String>,
     pub color_blend: gfx::BlendComponent,
     pub alpha_blend: gfx::BlendComponent,
     pub write_mask: gfx::ColorWrite,
@@ -113,6 +123,7 @@ pub struct RenderPipelineDescriptor {
 impl Default for RenderPipelineDescriptor {
     fn default() -> Self {
         Self {
+            label: None,
             color_blend: gfx::BlendComponent {
                 src_factor: gfx::BlendFactor::SrcAlpha,
                 dst_factor: gfx::BlendFactor::OneMinusSrcAlpha,
@@ -135,7 +146,8 @@ const PC_GLYPH_OFFSET_MEM_OFFSET: u32 =
     PC_TRANSFORM_MEM_OFFSET + size_of::<HomogeneousMatrix3<f32>>() as u32;
 const PC_COLOR_MEM_OFFSET: u32 =
     PC_GLYPH_OFFSET_MEM_OFFSET + size_of::<HomogeneousVector3<f32>>() as u32;
-const PC_SIZE: u32 = PC_COLOR_MEM_OFFSET + size_of::<gfx::ColorF32>() as u32;
+const PC_CONVERSION_MEM_OFFSET: u32 = PC_COLOR_MEM_OFFSET + size_of::<gfx::ColorF32>() as u32;
+const PC_SIZE: u32 = PC_CONVERSION_MEM_OFFSET + size_of::<u32>() as u32;
 
 #[derive(Debug)]
 pub struct RenderPipeline {
@@ -143,6 +155,7 @@ pub struct RenderPipeline {
     bind_group_layout: gfx::BindGroupLayout,
     sample_count: gfx::SampleCount,
     color_buffer_format: gfx::CanvasColorBufferFormat,
+    color_conversion: gfx::ColorConversion,
 }
 
 impl RenderPipeline {
@@ -151,12 +164,18 @@ impl RenderPipeline {
         let pipeline_layout = gfx::PipelineLayout::new(
             instance,
             &gfx::PipelineLayoutDescriptor {
-                label: None,
+                label: desc.label.as_deref(),
                 bind_group_layouts: &[&bind_group_layout],
-                push_constant_ranges: &[gfx::PushConstantRange {
-                    stages: gfx::ShaderStage::VERTEX,
-                    range: 0..PC_SIZE,
-                }],
+                push_constant_ranges: &[
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::VERTEX,
+                        range: 0..PC_CONVERSION_MEM_OFFSET,
+                    },
+                    gfx::PushConstantRange {
+                        stages: gfx::ShaderStage::FRAGMENT,
+                        range: PC_CONVERSION_MEM_OFFSET..PC_SIZE,
+                    },
+                ],
             },
         );
         let vs_module = gfx::ShaderModule::new(
@@ -170,7 +189,7 @@ impl RenderPipeline {
         let pipeline = gfx::RenderPipeline::new(
             instance,
             &gfx::RenderPipelineDescriptor {
-                label: None,
+                label: desc.label.as_deref(),
                 layout: Some(&pipeline_layout),
                 vertex: gfx::VertexState {
                     module: &vs_module,
@@ -226,9 +245,16 @@ impl RenderPipeline {
             bind_group_layout,
             sample_count: desc.sample_count,
             color_buffer_format: desc.color_buffer_format,
+            color_conversion: instance
+                .color_workflow()
+                .output_conversion(desc.color_buffer_format),
         }
     }
 
+    pub fn color_conversion(&self) -> gfx::ColorConversion {
+        self.color_conversion
+    }
+
     pub fn render_pass_requirements(&self) -> gfx::RenderPassRequirements {
         gfx::RenderPassRequirements {
             sample_count: self.sample_count,
@@ -247,6 +273,16 @@ pub trait Renderer<'a> {
         transform: &HomogeneousMatrix2<f32>,
         color: &gfx::ColorF32,
     );
+
+    // Draws a mesh created with GlyphAtlas::text_mesh.
+    fn draw_text_mesh(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        atlas: &'a GlyphAtlas,
+        mesh: &'a Mesh,
+        transform: &HomogeneousMatrix2<f32>,
+        color: &gfx::ColorF32,
+    );
 }
 
 impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
@@ -264,7 +300,7 @@ impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
 
         self.set_pipeline(&pipeline.pipeline);
         self.set_bind_group(0, &font.uniform_constants().bind_group, &[]);
-        self.set_index_buffer(font.index_buffer().slice(..), gfx::IndexFormat::Uint16);
+        self.set_index_buffer(font.index_buffer().slice(..), font.index_format());
         self.set_vertex_buffer(0, font.vertex_buffer().slice(..));
 
         let pc = (
@@ -273,6 +309,11 @@ impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
             color.clone(),
         );
         self.set_push_constants(gfx::ShaderStage::VERTEX, 0, gfx::utility::as_slice(&pc));
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
 
         let mut cursor_pos = HomogeneousVector2::<f32>::zero();
         for (position, info) in positions.iter().zip(infos) {
@@ -296,6 +337,36 @@ impl<'a> Renderer<'a> for gfx::RenderPass<'a> {
             cursor_pos.y = cursor_pos.y + i26dot6_to_fsize(position.y_advance);
         }
     }
+
+    fn draw_text_mesh(
+        &mut self,
+        pipeline: &'a RenderPipeline,
+        atlas: &'a GlyphAtlas,
+        mesh: &'a Mesh,
+        transform: &HomogeneousMatrix2<f32>,
+        color: &gfx::ColorF32,
+    ) {
+        if mesh.index_count() == 0 {
+            return;
+        }
+        self.set_pipeline(&pipeline.pipeline);
+        self.set_bind_group(0, &atlas.uniform_constants().bind_group, &[]);
+        self.set_index_buffer(mesh.index_buffer().slice(..), mesh.index_format());
+        self.set_vertex_buffer(0, mesh.vertex_buffer().slice(..));
+
+        let pc = (
+            roe_math::transform2_to_transform3(transform),
+            HomogeneousVector3::<f32>::zero(),
+            color,
+        );
+        self.set_push_constants(gfx::ShaderStage::VERTEX, 0, gfx::utility::as_slice(&pc));
+        self.set_push_constants(
+            gfx::ShaderStage::FRAGMENT,
+            PC_CONVERSION_MEM_OFFSET,
+            gfx::utility::as_slice(&(pipeline.color_conversion as u32)),
+        );
+        self.draw_indexed(0..mesh.index_count(), 0, 0..1);
+    }
 }
 
 #[cfg(test)]
@@ -304,7 +375,6 @@ mod tests {
         super::{character_set, Face, Font, FontLibrary},
         *,
     };
-    use galvanic_assert::{matchers::*, *};
     use gfx::Canvas;
     use roe_math::Vector2;
 
@@ -322,15 +392,18 @@ mod tests {
         let mut canvas = gfx::CanvasTexture::new(
             &instance,
             &gfx::CanvasTextureDescriptor {
+                label: None,
                 size: gfx::CanvasSize::new(300, 300),
                 sample_count: 1,
                 color_buffer_descriptor: Some(gfx::CanvasTextureColorBufferDescriptor {
                     format: gfx::CanvasColorBufferFormat::Rgba8Unorm,
                     usage: gfx::CanvasColorBufferUsage::COPY_SRC,
+                    sample_count: None,
                 }),
                 depth_stencil_buffer_format: None,
             },
-        );
+        )
+        .unwrap();
         let pipeline = RenderPipeline::new(
             &instance,
             &RenderPipelineDescriptor {
@@ -372,20 +445,12 @@ mod tests {
             cmd_sequence.submit(&instance);
             frame.present();
         }
-        let expected_image = image::load(
-            std::io::BufReader::new(std::fs::File::open("data/pictures/test_result.png").unwrap()),
-            image::ImageFormat::Png,
+        let result_image = canvas.color_texture().unwrap().to_image(&instance);
+        gfx::check_golden_image(
+            &result_image,
+            "data/pictures/test_result.png",
+            &gfx::GoldenImageDescriptor::default(),
         )
         .unwrap();
-        assert_that!(
-            &expected_image,
-            is_variant!(image::DynamicImage::ImageRgba8)
-        );
-
-        if let image::DynamicImage::ImageRgba8(expected_image) = expected_image {
-            let result_image = canvas.color_texture().unwrap().to_image(&instance);
-
-            expect_that!(&result_image, eq(expected_image));
-        }
     }
 }
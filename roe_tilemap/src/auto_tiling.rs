@@ -0,0 +1,343 @@
+use super::{TileId, Tilemap};
+
+use roe_math::Vector2;
+
+use std::collections::HashMap;
+
+// Cells covered by a terrain, e.g. grass or water.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TerrainMask {
+    width: u32,
+    height: u32,
+    cells: Vec<bool>,
+}
+
+impl TerrainMask {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![false; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn contains(&self, cell: &Vector2<i32>) -> bool {
+        cell[0] >= 0
+            && cell[1] >= 0
+            && (cell[0] as u32) < self.width
+            && (cell[1] as u32) < self.height
+    }
+
+    // Cells outside the mask are None.
+    pub fn get(&self, cell: &Vector2<i32>) -> Option<bool> {
+        if self.contains(cell) {
+            Some(self.cells[self.index(cell)])
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, cell: &Vector2<i32>, value: bool) {
+        assert!(self.contains(cell), "The cell is outside the terrain mask");
+        let index = self.index(cell);
+        self.cells[index] = value;
+    }
+
+    fn index(&self, cell: &Vector2<i32>) -> usize {
+        cell[1] as usize * self.width as usize + cell[0] as usize
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AdjacencyRule {
+    // Edge neighbors only, 16 variants. Bits: north 1, east 2, south 4, west 8.
+    FourBit,
+    // Edge and corner neighbors, 47 distinct variants ("blob" tiles). Bits clockwise from
+    // north: 1, 2, 4, ..., 128. A corner only counts if both edges next to it are set.
+    EightBit,
+}
+
+const NORTH: u8 = 1;
+const EAST: u8 = 1 << 2;
+const SOUTH: u8 = 1 << 4;
+const WEST: u8 = 1 << 6;
+
+// Offsets of the eight neighbors, clockwise from north, y pointing down.
+const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+// Clears the corner bits of an eight bit mask without both adjacent edges.
+pub fn normalize_blob_mask(mask: u8) -> u8 {
+    let mut normalized = mask & (NORTH | EAST | SOUTH | WEST);
+    for (corner, edges) in [
+        (1 << 1, NORTH | EAST),
+        (1 << 3, EAST | SOUTH),
+        (1 << 5, SOUTH | WEST),
+        (1 << 7, WEST | NORTH),
+    ] {
+        if mask & corner != 0 && mask & edges == edges {
+            normalized |= corner;
+        }
+    }
+    normalized
+}
+
+// The 47 distinct normalized eight bit masks, in increasing order. Tile sheets for blob tiles
+// are commonly laid out in this order.
+pub fn blob_masks() -> Vec<u8> {
+    let mut masks: Vec<u8> = (0..=255u8).map(normalize_blob_mask).collect();
+    masks.sort_unstable();
+    masks.dedup();
+    masks
+}
+
+// Maps adjacency masks to tile variants.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AutoTileRules {
+    rule: AdjacencyRule,
+    variants: HashMap<u8, TileId>,
+    // Used for masks without a variant.
+    pub fallback: Option<TileId>,
+    // Whether cells outside the mask count as terrain, e.g. so that terrain touching the map
+    // border doesn't show an edge there.
+    pub border_is_terrain: bool,
+}
+
+impl AutoTileRules {
+    pub fn new(rule: AdjacencyRule) -> Self {
+        Self {
+            rule,
+            variants: HashMap::new(),
+            fallback: None,
+            border_is_terrain: false,
+        }
+    }
+
+    // Tile sheet with the 16 variants in mask order, starting from the given tile.
+    pub fn four_bit(first: TileId) -> Self {
+        let mut rules = Self::new(AdjacencyRule::FourBit);
+        for mask in 0..16 {
+            rules.set_variant(mask, TileId(first.0 + mask as u32));
+        }
+        rules
+    }
+
+    // Tile sheet with the 47 variants in the order of blob_masks, starting from the given tile.
+    pub fn blob(first: TileId) -> Self {
+        let mut rules = Self::new(AdjacencyRule::EightBit);
+        for (index, mask) in blob_masks().into_iter().enumerate() {
+            rules.set_variant(mask, TileId(first.0 + index as u32));
+        }
+        rules
+    }
+
+    pub fn rule(&self) -> AdjacencyRule {
+        self.rule
+    }
+
+    // Eight bit masks are normalized first.
+    pub fn set_variant(&mut self, mask: u8, tile: TileId) {
+        let mask = match self.rule {
+            AdjacencyRule::FourBit => {
+                assert!(mask < 16, "Four bit masks must be lower than 16");
+                mask
+            }
+            AdjacencyRule::EightBit => normalize_blob_mask(mask),
+        };
+        self.variants.insert(mask, tile);
+    }
+
+    pub fn variant(&self, mask: u8) -> Option<TileId> {
+        self.variants.get(&mask).copied().or(self.fallback)
+    }
+
+    // Adjacency mask of the cell, normalized for eight bit rules.
+    pub fn mask(&self, terrain: &TerrainMask, cell: &Vector2<i32>) -> u8 {
+        let is_terrain = |index: usize| {
+            let (x, y) = NEIGHBOR_OFFSETS[index];
+            terrain
+                .get(&(cell + Vector2::new(x, y)))
+                .unwrap_or(self.border_is_terrain)
+        };
+        match self.rule {
+            AdjacencyRule::FourBit => (0..4)
+                .filter(|i| is_terrain(i * 2))
+                .fold(0, |mask, i| mask | 1 << i),
+            AdjacencyRule::EightBit => normalize_blob_mask(
+                (0..8)
+                    .filter(|i| is_terrain(*i))
+                    .fold(0, |mask, i| mask | 1 << i),
+            ),
+        }
+    }
+
+    // Tile for the cell, None where there is no terrain.
+    pub fn select(&self, terrain: &TerrainMask, cell: &Vector2<i32>) -> Option<TileId> {
+        if terrain.get(cell) == Some(true) {
+            self.variant(self.mask(terrain, cell))
+        } else {
+            None
+        }
+    }
+}
+
+// Keeps the tiles of a terrain layer in sync with its mask. Works with square and isometric
+// tilemaps, where the adjacency is the same in tile coordinates.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AutoTiler {
+    terrain: TerrainMask,
+    rules: AutoTileRules,
+}
+
+impl AutoTiler {
+    pub fn new(terrain: TerrainMask, rules: AutoTileRules) -> Self {
+        Self { terrain, rules }
+    }
+
+    pub fn terrain(&self) -> &TerrainMask {
+        &self.terrain
+    }
+
+    pub fn rules(&self) -> &AutoTileRules {
+        &self.rules
+    }
+
+    // Selects the tiles of all cells, e.g. after loading the mask.
+    pub fn apply(&self, tilemap: &mut Tilemap) {
+        self.assert_same_size(tilemap);
+        for y in 0..self.terrain.height() as i32 {
+            for x in 0..self.terrain.width() as i32 {
+                let cell = Vector2::new(x, y);
+                tilemap.set_tile(&cell, self.rules.select(&self.terrain, &cell));
+            }
+        }
+    }
+
+    // Changes the terrain of a cell at runtime, e.g. when digging, and updates the tiles of the
+    // cell and its neighbors. Returns the cells whose tile changed.
+    pub fn paint(
+        &mut self,
+        tilemap: &mut Tilemap,
+        cell: &Vector2<i32>,
+        value: bool,
+    ) -> Vec<Vector2<i32>> {
+        self.assert_same_size(tilemap);
+        self.terrain.set(cell, value);
+        let mut changed = Vec::new();
+        let cells = std::iter::once(*cell).chain(
+            NEIGHBOR_OFFSETS
+                .iter()
+                .map(|(x, y)| cell + Vector2::new(*x, *y)),
+        );
+        for cell in cells.filter(|c| self.terrain.contains(c)) {
+            let tile = self.rules.select(&self.terrain, &cell);
+            if tilemap.tile(&cell) != tile {
+                tilemap.set_tile(&cell, tile);
+                changed.push(cell);
+            }
+        }
+        changed
+    }
+
+    fn assert_same_size(&self, tilemap: &Tilemap) {
+        assert!(
+            tilemap.width() == self.terrain.width() && tilemap.height() == self.terrain.height(),
+            "The tilemap and the terrain mask must have the same size"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GridProjection;
+    use galvanic_assert::{matchers::*, *};
+
+    fn terrain(rows: &[&str]) -> TerrainMask {
+        let mut terrain = TerrainMask::new(rows[0].len() as u32, rows.len() as u32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                terrain.set(&Vector2::new(x as i32, y as i32), c == '#');
+            }
+        }
+        terrain
+    }
+
+    #[test]
+    fn blob_mask_count() {
+        let masks = blob_masks();
+        expect_that!(&masks.len(), eq(47));
+        expect_that!(&masks[0], eq(0));
+        expect_that!(&masks[46], eq(255));
+        expect_that!(&normalize_blob_mask(0b0000_0010), eq(0));
+        expect_that!(&normalize_blob_mask(0b0000_0111), eq(0b0000_0111));
+    }
+
+    #[test]
+    fn four_bit_masks() {
+        let terrain = terrain(&["...", "##.", ".#."]);
+        let rules = AutoTileRules::four_bit(TileId(100));
+        // South and west neighbors.
+        expect_that!(&rules.mask(&terrain, &Vector2::new(1, 1)), eq(4 | 8));
+        expect_that!(
+            &rules.select(&terrain, &Vector2::new(1, 1)),
+            eq(Some(TileId(100 + 4 + 8)))
+        );
+        expect_that!(
+            &rules.select(&terrain, &Vector2::new(1, 2)),
+            eq(Some(TileId(101)))
+        );
+        expect_that!(&rules.select(&terrain, &Vector2::new(2, 2)), eq(None));
+    }
+
+    #[test]
+    fn eight_bit_masks() {
+        let terrain = terrain(&["##.", "##.", "..#"]);
+        let rules = AutoTileRules::blob(TileId(0));
+        // The diagonal neighbor at (2, 2) doesn't count without the edges next to it.
+        expect_that!(
+            &rules.mask(&terrain, &Vector2::new(1, 1)),
+            eq(NORTH | WEST | 1 << 7)
+        );
+        let mut rules = rules;
+        rules.border_is_terrain = true;
+        expect_that!(&rules.mask(&terrain, &Vector2::new(0, 0)), eq(255));
+    }
+
+    #[test]
+    fn paint_updates_neighbors() {
+        let mut tiler = AutoTiler::new(
+            terrain(&["...", ".#.", "..."]),
+            AutoTileRules::four_bit(TileId(0)),
+        );
+        let mut tilemap = Tilemap::new(3, 3, GridProjection::square(16.));
+        tiler.apply(&mut tilemap);
+        expect_that!(&tilemap.tile(&Vector2::new(1, 1)), eq(Some(TileId(0))));
+
+        let changed = tiler.paint(&mut tilemap, &Vector2::new(2, 1), true);
+        expect_that!(&changed, eq(vec![Vector2::new(2, 1), Vector2::new(1, 1)]));
+        expect_that!(&tilemap.tile(&Vector2::new(1, 1)), eq(Some(TileId(2))));
+        expect_that!(&tilemap.tile(&Vector2::new(2, 1)), eq(Some(TileId(8))));
+
+        let changed = tiler.paint(&mut tilemap, &Vector2::new(1, 1), false);
+        expect_that!(&changed.len(), eq(2));
+        expect_that!(&tilemap.tile(&Vector2::new(1, 1)), eq(None));
+        expect_that!(&tilemap.tile(&Vector2::new(2, 1)), eq(Some(TileId(0))));
+    }
+}
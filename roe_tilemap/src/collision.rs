@@ -0,0 +1,408 @@
+use super::{TileId, TileShape, Tilemap};
+
+use roe_math::{Aabb2, Vector2};
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// Collision geometry in world coordinates, to be turned into colliders of the physics engine in
+// use. Polygons wind clockwise on screen (y pointing down) around solid areas and
+// counterclockwise around holes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum CollisionShape {
+    Rectangle(Aabb2<f32>),
+    Polygon(Vec<Vector2<f32>>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CollisionMode {
+    // Solid cells merged greedily into few rectangles, e.g. for box colliders.
+    Rectangles,
+    // The outlines of the solid areas, e.g. for chain or polyline colliders.
+    Outlines,
+}
+
+// Covers the solid cells in [begin, end) with non-overlapping rectangles, returned as the first
+// and past-the-last cell. Rows are extended first, then the rows below.
+pub fn merge_solid_cells<F: Fn(&Vector2<i32>) -> bool>(
+    begin: Vector2<i32>,
+    end: Vector2<i32>,
+    is_solid: F,
+) -> Vec<(Vector2<i32>, Vector2<i32>)> {
+    let mut covered = HashSet::new();
+    let free = |cell: &Vector2<i32>, covered: &HashSet<Vector2<i32>>| {
+        is_solid(cell) && !covered.contains(cell)
+    };
+    let mut rectangles = Vec::new();
+    for y in begin[1]..end[1] {
+        for x in begin[0]..end[0] {
+            if !free(&Vector2::new(x, y), &covered) {
+                continue;
+            }
+            let mut right = x + 1;
+            while right < end[0] && free(&Vector2::new(right, y), &covered) {
+                right += 1;
+            }
+            let mut bottom = y + 1;
+            while bottom < end[1] && (x..right).all(|cx| free(&Vector2::new(cx, bottom), &covered))
+            {
+                bottom += 1;
+            }
+            for cy in y..bottom {
+                for cx in x..right {
+                    covered.insert(Vector2::new(cx, cy));
+                }
+            }
+            rectangles.push((Vector2::new(x, y), Vector2::new(right, bottom)));
+        }
+    }
+    rectangles
+}
+
+// Boundaries of the solid cells in [begin, end), as loops of cell corners without collinear
+// points. Cell (x, y) has corners (x, y) and (x + 1, y + 1). Cells outside the range are
+// considered empty. Solid cells touching only diagonally get separate loops.
+pub fn trace_solid_outlines<F: Fn(&Vector2<i32>) -> bool>(
+    begin: Vector2<i32>,
+    end: Vector2<i32>,
+    is_solid: F,
+) -> Vec<Vec<Vector2<i32>>> {
+    let inside = |cell: &Vector2<i32>| {
+        cell[0] >= begin[0]
+            && cell[1] >= begin[1]
+            && cell[0] < end[0]
+            && cell[1] < end[1]
+            && is_solid(cell)
+    };
+
+    // Directed edges with the solid cell on their right, keyed by their start corner.
+    let mut edges: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+    for y in begin[1]..end[1] {
+        for x in begin[0]..end[0] {
+            if !inside(&Vector2::new(x, y)) {
+                continue;
+            }
+            let sides = [
+                ((0, -1), (x, y), (x + 1, y)),
+                ((1, 0), (x + 1, y), (x + 1, y + 1)),
+                ((0, 1), (x + 1, y + 1), (x, y + 1)),
+                ((-1, 0), (x, y + 1), (x, y)),
+            ];
+            for ((dx, dy), from, to) in sides {
+                if !inside(&Vector2::new(x + dx, y + dy)) {
+                    edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+    }
+
+    let mut loops = Vec::new();
+    // Loops start from their top-left corner, in row order.
+    while let Some(&start) = edges.keys().min_by_key(|(x, y)| (*y, *x)) {
+        let mut corners = vec![start];
+        let mut current = start;
+        let mut direction = (0, 0);
+        loop {
+            let targets = edges.get_mut(&current).unwrap();
+            // Turning right first keeps diagonally touching cells apart.
+            let right = (-direction.1, direction.0);
+            let straight = direction;
+            let index = [right, straight]
+                .iter()
+                .find_map(|d| {
+                    targets
+                        .iter()
+                        .position(|t| (t.0 - current.0, t.1 - current.1) == *d)
+                })
+                .unwrap_or(0);
+            let next = targets.swap_remove(index);
+            if targets.is_empty() {
+                edges.remove(&current);
+            }
+            direction = (next.0 - current.0, next.1 - current.1);
+            current = next;
+            if current == start {
+                break;
+            }
+            corners.push(current);
+        }
+        loops.push(remove_collinear(corners));
+    }
+    loops
+}
+
+fn remove_collinear(corners: Vec<(i32, i32)>) -> Vec<Vector2<i32>> {
+    let count = corners.len();
+    (0..count)
+        .filter(|i| {
+            let prev = corners[(i + count - 1) % count];
+            let curr = corners[*i];
+            let next = corners[(i + 1) % count];
+            (curr.0 - prev.0) * (next.1 - curr.1) != (curr.1 - prev.1) * (next.0 - curr.0)
+        })
+        .map(|i| Vector2::new(corners[i].0, corners[i].1))
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileCollisionDescriptor {
+    pub mode: CollisionMode,
+    // Shapes don't extend across chunks, so that a tile change only regenerates its chunk.
+    pub chunk_size: u32,
+    pub solid_tiles: HashSet<TileId>,
+}
+
+impl Default for TileCollisionDescriptor {
+    fn default() -> Self {
+        Self {
+            mode: CollisionMode::Rectangles,
+            chunk_size: 16,
+            solid_tiles: HashSet::new(),
+        }
+    }
+}
+
+// Regenerated shapes of a chunk. The previous shapes of the chunk must be replaced.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CollisionChunkUpdate {
+    pub chunk: Vector2<i32>,
+    pub shapes: Vec<CollisionShape>,
+}
+
+// Collision geometry of the solid tiles of a square tilemap, kept per chunk and regenerated
+// only for chunks with changed tiles.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileCollisionLayer {
+    desc: TileCollisionDescriptor,
+    chunks: HashMap<Vector2<i32>, Vec<CollisionShape>>,
+    dirty_chunks: BTreeSet<(i32, i32)>,
+}
+
+impl TileCollisionLayer {
+    pub fn new(desc: TileCollisionDescriptor, tilemap: &Tilemap) -> Self {
+        assert!(desc.chunk_size > 0, "The chunk size must be positive");
+        assert!(
+            tilemap.projection().shape == TileShape::Square,
+            "Collision extraction requires a square tilemap"
+        );
+        let mut layer = Self {
+            desc,
+            chunks: HashMap::new(),
+            dirty_chunks: BTreeSet::new(),
+        };
+        layer.mark_all_dirty(tilemap);
+        layer.update(tilemap);
+        layer
+    }
+
+    pub fn descriptor(&self) -> &TileCollisionDescriptor {
+        &self.desc
+    }
+
+    pub fn chunk_of(&self, tile: &Vector2<i32>) -> Vector2<i32> {
+        let size = self.desc.chunk_size as i32;
+        Vector2::new(tile[0].div_euclid(size), tile[1].div_euclid(size))
+    }
+
+    // Call after changing the tile, e.g. with the cells returned by AutoTiler::paint.
+    pub fn mark_dirty(&mut self, tile: &Vector2<i32>) {
+        let chunk = self.chunk_of(tile);
+        self.dirty_chunks.insert((chunk[0], chunk[1]));
+    }
+
+    pub fn mark_all_dirty(&mut self, tilemap: &Tilemap) {
+        let size = self.desc.chunk_size;
+        for y in 0..tilemap.height().div_ceil(size) {
+            for x in 0..tilemap.width().div_ceil(size) {
+                self.dirty_chunks.insert((x as i32, y as i32));
+            }
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty_chunks.is_empty()
+    }
+
+    // Regenerates the dirty chunks.
+    pub fn update(&mut self, tilemap: &Tilemap) -> Vec<CollisionChunkUpdate> {
+        let dirty_chunks = std::mem::take(&mut self.dirty_chunks);
+        let mut updates = Vec::with_capacity(dirty_chunks.len());
+        for (x, y) in dirty_chunks {
+            let chunk = Vector2::new(x, y);
+            let shapes = self.generate_chunk_shapes(tilemap, &chunk);
+            if shapes.is_empty() {
+                self.chunks.remove(&chunk);
+            } else {
+                self.chunks.insert(chunk, shapes.clone());
+            }
+            updates.push(CollisionChunkUpdate { chunk, shapes });
+        }
+        updates
+    }
+
+    pub fn chunk_shapes(&self, chunk: &Vector2<i32>) -> &[CollisionShape] {
+        self.chunks.get(chunk).map(|s| s.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn shapes(&self) -> impl Iterator<Item = &CollisionShape> {
+        self.chunks.values().flatten()
+    }
+
+    fn generate_chunk_shapes(
+        &self,
+        tilemap: &Tilemap,
+        chunk: &Vector2<i32>,
+    ) -> Vec<CollisionShape> {
+        let size = self.desc.chunk_size as i32;
+        let begin = chunk * size;
+        let end = Vector2::new(
+            (begin[0] + size).min(tilemap.width() as i32),
+            (begin[1] + size).min(tilemap.height() as i32),
+        );
+        let is_solid = |cell: &Vector2<i32>| match tilemap.tile(cell) {
+            Some(id) => self.desc.solid_tiles.contains(&id),
+            None => false,
+        };
+        // Tile centers are at the world origin, so cell corners are offset by half a tile.
+        let tile_size = tilemap.projection().tile_size;
+        let to_world = |corner: &Vector2<i32>| {
+            Vector2::new(
+                (corner[0] as f32 - 0.5) * tile_size[0],
+                (corner[1] as f32 - 0.5) * tile_size[1],
+            )
+        };
+        match self.desc.mode {
+            CollisionMode::Rectangles => merge_solid_cells(begin, end, is_solid)
+                .into_iter()
+                .map(|(min, max)| {
+                    CollisionShape::Rectangle(Aabb2::new(to_world(&min), to_world(&max)))
+                })
+                .collect(),
+            CollisionMode::Outlines => trace_solid_outlines(begin, end, is_solid)
+                .into_iter()
+                .map(|corners| CollisionShape::Polygon(corners.iter().map(to_world).collect()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GridProjection;
+    use galvanic_assert::{matchers::*, *};
+
+    fn cells(rows: &[&str]) -> impl Fn(&Vector2<i32>) -> bool {
+        let rows: Vec<Vec<bool>> = rows
+            .iter()
+            .map(|r| r.chars().map(|c| c == '#').collect())
+            .collect();
+        move |cell: &Vector2<i32>| rows[cell[1] as usize][cell[0] as usize]
+    }
+
+    fn tilemap(rows: &[&str]) -> Tilemap {
+        let mut tilemap = Tilemap::new(
+            rows[0].len() as u32,
+            rows.len() as u32,
+            GridProjection::square(10.),
+        );
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let id = if c == '#' { Some(TileId(1)) } else { None };
+                tilemap.set_tile(&Vector2::new(x as i32, y as i32), id);
+            }
+        }
+        tilemap
+    }
+
+    fn v(x: i32, y: i32) -> Vector2<i32> {
+        Vector2::new(x, y)
+    }
+
+    #[test]
+    fn greedy_rectangles() {
+        let is_solid = cells(&["###.", "###.", "#..#"]);
+        let rectangles = merge_solid_cells(v(0, 0), v(4, 3), is_solid);
+        expect_that!(
+            &rectangles,
+            eq(vec![
+                (v(0, 0), v(3, 2)),
+                (v(0, 2), v(1, 3)),
+                (v(3, 2), v(4, 3))
+            ])
+        );
+    }
+
+    #[test]
+    fn outlines() {
+        let is_solid = cells(&["###", "#.#", "###", "...", "#.."]);
+        let loops = trace_solid_outlines(v(0, 0), v(3, 5), is_solid);
+        expect_that!(&loops.len(), eq(3));
+        expect_that!(&loops[0], eq(vec![v(0, 0), v(3, 0), v(3, 3), v(0, 3)]));
+        // The hole winds the other way.
+        expect_that!(&loops[1], eq(vec![v(1, 1), v(1, 2), v(2, 2), v(2, 1)]));
+        expect_that!(&loops[2], eq(vec![v(0, 4), v(1, 4), v(1, 5), v(0, 5)]));
+    }
+
+    #[test]
+    fn diagonal_cells_are_separate() {
+        let is_solid = cells(&["#.", ".#"]);
+        let loops = trace_solid_outlines(v(0, 0), v(2, 2), is_solid);
+        expect_that!(&loops.len(), eq(2));
+        expect_that!(loops.iter().all(|l| l.len() == 4));
+    }
+
+    #[test]
+    fn incremental_update() {
+        let mut tilemap = tilemap(&["##..", "##..", "....", "...#"]);
+        let mut layer = TileCollisionLayer::new(
+            TileCollisionDescriptor {
+                chunk_size: 2,
+                solid_tiles: [TileId(1)].into_iter().collect(),
+                ..TileCollisionDescriptor::default()
+            },
+            &tilemap,
+        );
+        expect_that!(&layer.shapes().count(), eq(2));
+        let expected = [CollisionShape::Rectangle(Aabb2::new(
+            Vector2::new(-5., -5.),
+            Vector2::new(15., 15.),
+        ))];
+        expect_that!(&layer.chunk_shapes(&v(0, 0)), eq(&expected[..]));
+        expect_that!(!layer.is_dirty());
+
+        tilemap.set_tile(&v(3, 3), None);
+        layer.mark_dirty(&v(3, 3));
+        let updates = layer.update(&tilemap);
+        expect_that!(
+            &updates,
+            eq(vec![CollisionChunkUpdate {
+                chunk: v(1, 1),
+                shapes: Vec::new()
+            }])
+        );
+        expect_that!(&layer.shapes().count(), eq(1));
+    }
+
+    #[test]
+    fn outline_polygons_in_world_coordinates() {
+        let tilemap = tilemap(&["#"]);
+        let layer = TileCollisionLayer::new(
+            TileCollisionDescriptor {
+                mode: CollisionMode::Outlines,
+                solid_tiles: [TileId(1)].into_iter().collect(),
+                ..TileCollisionDescriptor::default()
+            },
+            &tilemap,
+        );
+        let shapes: Vec<_> = layer.shapes().cloned().collect();
+        expect_that!(
+            &shapes,
+            eq(vec![CollisionShape::Polygon(vec![
+                Vector2::new(-5., -5.),
+                Vector2::new(5., -5.),
+                Vector2::new(5., 5.),
+                Vector2::new(-5., 5.),
+            ])])
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use roe_math::Vector2;
+
+const SQRT_3: f32 = 1.732_050_8;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HexOrientation {
+    // Rows of hexagons with a vertex pointing up. Odd rows are shifted right by half a tile.
+    PointyTop,
+    // Columns of hexagons with a flat edge on top. Odd columns are shifted down by half a tile.
+    FlatTop,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TileShape {
+    Square,
+    // Diamonds, with the tile x axis going right-down and the tile y axis going left-down.
+    Isometric,
+    Hex(HexOrientation),
+}
+
+// Maps tile coordinates to world coordinates and back, y pointing down. The center of tile
+// (0, 0) is at the world origin. The tile size is the bounding box of a single tile, e.g. the
+// width and height of the diamond for isometric tiles.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GridProjection {
+    pub shape: TileShape,
+    pub tile_size: Vector2<f32>,
+}
+
+impl GridProjection {
+    pub fn new(shape: TileShape, tile_size: Vector2<f32>) -> Self {
+        assert!(
+            tile_size[0] > 0. && tile_size[1] > 0.,
+            "The tile size must be positive"
+        );
+        Self { shape, tile_size }
+    }
+
+    pub fn square(tile_size: f32) -> Self {
+        Self::new(TileShape::Square, Vector2::new(tile_size, tile_size))
+    }
+
+    // Regular hexagons with the given distance from the center to the vertices.
+    pub fn regular_hex(orientation: HexOrientation, radius: f32) -> Self {
+        let tile_size = match orientation {
+            HexOrientation::PointyTop => Vector2::new(SQRT_3 * radius, 2. * radius),
+            HexOrientation::FlatTop => Vector2::new(2. * radius, SQRT_3 * radius),
+        };
+        Self::new(TileShape::Hex(orientation), tile_size)
+    }
+
+    pub fn tile_to_world(&self, tile: &Vector2<i32>) -> Vector2<f32> {
+        let (x, y) = (tile[0] as f32, tile[1] as f32);
+        let (w, h) = (self.tile_size[0], self.tile_size[1]);
+        match self.shape {
+            TileShape::Square => Vector2::new(x * w, y * h),
+            TileShape::Isometric => Vector2::new((x - y) * w * 0.5, (x + y) * h * 0.5),
+            TileShape::Hex(HexOrientation::PointyTop) => {
+                let shift = (tile[1] & 1) as f32 * 0.5;
+                Vector2::new((x + shift) * w, y * h * 0.75)
+            }
+            TileShape::Hex(HexOrientation::FlatTop) => {
+                let shift = (tile[0] & 1) as f32 * 0.5;
+                Vector2::new(x * w * 0.75, (y + shift) * h)
+            }
+        }
+    }
+
+    // Tile containing the world position, exact also along the diagonal and hexagonal edges.
+    pub fn world_to_tile(&self, position: &Vector2<f32>) -> Vector2<i32> {
+        let (w, h) = (self.tile_size[0], self.tile_size[1]);
+        match self.shape {
+            TileShape::Square => Vector2::new(
+                (position[0] / w).round() as i32,
+                (position[1] / h).round() as i32,
+            ),
+            TileShape::Isometric => {
+                let u = position[0] / (w * 0.5);
+                let v = position[1] / (h * 0.5);
+                Vector2::new(
+                    ((u + v) * 0.5).round() as i32,
+                    ((v - u) * 0.5).round() as i32,
+                )
+            }
+            TileShape::Hex(HexOrientation::PointyTop) => {
+                // Scaled to a regular hexagon with unit radius.
+                let x = position[0] / w * SQRT_3;
+                let y = position[1] / h * 2.;
+                let (q, r) = round_axial(SQRT_3 / 3. * x - y / 3., y * 2. / 3.);
+                Vector2::new(q + (r - (r & 1)) / 2, r)
+            }
+            TileShape::Hex(HexOrientation::FlatTop) => {
+                let x = position[0] / w * 2.;
+                let y = position[1] / h * SQRT_3;
+                let (q, r) = round_axial(x * 2. / 3., -x / 3. + SQRT_3 / 3. * y);
+                Vector2::new(q, r + (q - (q & 1)) / 2)
+            }
+        }
+    }
+
+    // Outline of the tile in world coordinates, clockwise on screen.
+    pub fn tile_corners(&self, tile: &Vector2<i32>) -> Vec<Vector2<f32>> {
+        let center = self.tile_to_world(tile);
+        let (hw, hh) = (self.tile_size[0] * 0.5, self.tile_size[1] * 0.5);
+        let offsets = match self.shape {
+            TileShape::Square => vec![(-hw, -hh), (hw, -hh), (hw, hh), (-hw, hh)],
+            TileShape::Isometric => vec![(0., -hh), (hw, 0.), (0., hh), (-hw, 0.)],
+            TileShape::Hex(HexOrientation::PointyTop) => vec![
+                (0., -hh),
+                (hw, -hh * 0.5),
+                (hw, hh * 0.5),
+                (0., hh),
+                (-hw, hh * 0.5),
+                (-hw, -hh * 0.5),
+            ],
+            TileShape::Hex(HexOrientation::FlatTop) => vec![
+                (-hw * 0.5, -hh),
+                (hw * 0.5, -hh),
+                (hw, 0.),
+                (hw * 0.5, hh),
+                (-hw * 0.5, hh),
+                (-hw, 0.),
+            ],
+        };
+        offsets
+            .into_iter()
+            .map(|(x, y)| center + Vector2::new(x, y))
+            .collect()
+    }
+
+    // Tiles sharing an edge with the tile.
+    pub fn neighbors(&self, tile: &Vector2<i32>) -> Vec<Vector2<i32>> {
+        let offsets: &[(i32, i32)] = match self.shape {
+            TileShape::Square | TileShape::Isometric => &[(1, 0), (0, 1), (-1, 0), (0, -1)],
+            TileShape::Hex(HexOrientation::PointyTop) => {
+                if tile[1] & 1 == 0 {
+                    &[(1, 0), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1)]
+                } else {
+                    &[(1, 0), (1, 1), (0, 1), (-1, 0), (0, -1), (1, -1)]
+                }
+            }
+            TileShape::Hex(HexOrientation::FlatTop) => {
+                if tile[0] & 1 == 0 {
+                    &[(1, -1), (1, 0), (0, 1), (-1, 0), (-1, -1), (0, -1)]
+                } else {
+                    &[(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (0, -1)]
+                }
+            }
+        };
+        offsets
+            .iter()
+            .map(|(x, y)| tile + Vector2::new(*x, *y))
+            .collect()
+    }
+
+    // Tiles are drawn back to front with increasing keys, so that tall tiles and objects standing
+    // on them overlap the tiles behind them.
+    pub fn draw_order_key(&self, tile: &Vector2<i32>) -> (i32, i32) {
+        match self.shape {
+            TileShape::Square => (tile[1], tile[0]),
+            TileShape::Isometric => (tile[0] + tile[1], tile[0]),
+            TileShape::Hex(HexOrientation::PointyTop) => (tile[1], tile[0]),
+            // Odd columns are lower than even columns in the same row.
+            TileShape::Hex(HexOrientation::FlatTop) => (tile[1] * 2 + (tile[0] & 1), tile[0]),
+        }
+    }
+}
+
+// Rounds fractional axial hex coordinates to the nearest hexagon.
+fn round_axial(q: f32, r: f32) -> (i32, i32) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as i32, rr as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn projections() -> Vec<GridProjection> {
+        vec![
+            GridProjection::square(32.),
+            GridProjection::new(TileShape::Isometric, Vector2::new(64., 32.)),
+            GridProjection::regular_hex(HexOrientation::PointyTop, 20.),
+            GridProjection::regular_hex(HexOrientation::FlatTop, 20.),
+        ]
+    }
+
+    fn tiles() -> Vec<Vector2<i32>> {
+        let mut tiles = Vec::new();
+        for y in -3..4 {
+            for x in -3..4 {
+                tiles.push(Vector2::new(x, y));
+            }
+        }
+        tiles
+    }
+
+    #[test]
+    fn round_trip() {
+        for projection in projections() {
+            for tile in tiles() {
+                let center = projection.tile_to_world(&tile);
+                expect_that!(&projection.world_to_tile(&center), eq(tile));
+                // Points slightly inside the outline still belong to the tile.
+                for corner in projection.tile_corners(&tile) {
+                    let point = center + (corner - center) * 0.95;
+                    expect_that!(&projection.world_to_tile(&point), eq(tile));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn isometric() {
+        let projection = GridProjection::new(TileShape::Isometric, Vector2::new(64., 32.));
+        expect_that!(
+            &projection.tile_to_world(&Vector2::new(1, 0)),
+            eq(Vector2::new(32., 16.))
+        );
+        expect_that!(
+            &projection.tile_to_world(&Vector2::new(0, 1)),
+            eq(Vector2::new(-32., 16.))
+        );
+        // The right corner of tile (0, 0) borders tiles (1, 0) and (0, -1).
+        expect_that!(
+            &projection.world_to_tile(&Vector2::new(31., -1.)),
+            eq(Vector2::new(0, -1))
+        );
+        expect_that!(
+            &projection.world_to_tile(&Vector2::new(31., 1.)),
+            eq(Vector2::new(1, 0))
+        );
+    }
+
+    #[test]
+    fn hex_neighbors_are_adjacent() {
+        for projection in projections() {
+            for tile in tiles() {
+                let center = projection.tile_to_world(&tile);
+                let neighbors = projection.neighbors(&tile);
+                let expected_count = match projection.shape {
+                    TileShape::Hex(_) => 6,
+                    _ => 4,
+                };
+                expect_that!(&neighbors.len(), eq(expected_count));
+                // The midpoint between the centers is on the shared edge.
+                for neighbor in neighbors {
+                    let other = projection.tile_to_world(&neighbor);
+                    let midpoint = (center + other) * 0.5;
+                    let towards_tile = midpoint + (center - other) * 0.01;
+                    expect_that!(&projection.world_to_tile(&towards_tile), eq(tile));
+                    expect_that!(projection.neighbors(&neighbor).contains(&tile));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn draw_order_is_back_to_front() {
+        for projection in projections() {
+            let mut tiles = tiles();
+            tiles.sort_by_key(|tile| projection.draw_order_key(tile));
+            for pair in tiles.windows(2) {
+                let (a, b) = (
+                    projection.tile_to_world(&pair[0]),
+                    projection.tile_to_world(&pair[1]),
+                );
+                expect_that!(&a[1], leq(b[1]));
+            }
+        }
+    }
+}
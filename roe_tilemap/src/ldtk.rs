@@ -0,0 +1,692 @@
+use super::{GridProjection, TerrainMask, TileId, Tilemap};
+
+use roe_math::Vector2;
+use roe_reflect::{ReflectedObject, Value};
+use roe_scene::{Component, EntityDescriptor, Prefab, PrefabInstance, Transform2};
+
+use serde::Deserialize;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum LdtkError {
+    IoError(std::io::Error),
+    JsonError(serde_json::Error),
+    // Layer identifier and description.
+    InvalidLayer(String, String),
+}
+
+impl std::fmt::Display for LdtkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error ({})", e),
+            Self::JsonError(e) => write!(f, "JSON error ({})", e),
+            Self::InvalidLayer(layer, description) => {
+                write!(f, "Invalid layer {} ({})", layer, description)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LdtkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::JsonError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for LdtkError {
+    fn from(e: std::io::Error) -> Self {
+        Self::IoError(e)
+    }
+}
+
+impl From<serde_json::Error> for LdtkError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::JsonError(e)
+    }
+}
+
+// Subset of the LDtk project format read by the importer.
+#[derive(Deserialize)]
+struct RawProject {
+    levels: Vec<RawLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawLevel {
+    identifier: String,
+    iid: String,
+    world_x: i64,
+    world_y: i64,
+    px_wid: i64,
+    px_hei: i64,
+    #[serde(default)]
+    layer_instances: Option<Vec<RawLayer>>,
+    #[serde(rename = "__neighbours", default)]
+    neighbours: Vec<RawNeighbour>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawNeighbour {
+    level_iid: String,
+    dir: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawLayer {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__type")]
+    layer_type: String,
+    #[serde(rename = "__cWid")]
+    width: u32,
+    #[serde(rename = "__cHei")]
+    height: u32,
+    #[serde(rename = "__gridSize")]
+    grid_size: u32,
+    #[serde(rename = "__tilesetRelPath", default)]
+    tileset_path: Option<String>,
+    #[serde(default)]
+    px_offset_x: i64,
+    #[serde(default)]
+    px_offset_y: i64,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default)]
+    int_grid_csv: Vec<i64>,
+    #[serde(default)]
+    grid_tiles: Vec<RawTile>,
+    #[serde(default)]
+    auto_layer_tiles: Vec<RawTile>,
+    #[serde(default)]
+    entity_instances: Vec<RawEntity>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct RawTile {
+    px: [i64; 2],
+    // Bit 0: flipped horizontally, bit 1: flipped vertically.
+    #[serde(default)]
+    f: u8,
+    t: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawEntity {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    iid: String,
+    px: [i64; 2],
+    #[serde(default)]
+    width: i64,
+    #[serde(default)]
+    height: i64,
+    #[serde(default)]
+    field_instances: Vec<RawField>,
+}
+
+#[derive(Deserialize)]
+struct RawField {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: serde_json::Value,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LdtkDirection {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+    // Overlapping level in the same world depth.
+    Overlap,
+    // Level in the depth above or below.
+    Above,
+    Below,
+    Other(String),
+}
+
+impl LdtkDirection {
+    fn parse(dir: &str) -> Self {
+        match dir {
+            "n" => Self::North,
+            "ne" => Self::NorthEast,
+            "e" => Self::East,
+            "se" => Self::SouthEast,
+            "s" => Self::South,
+            "sw" => Self::SouthWest,
+            "w" => Self::West,
+            "nw" => Self::NorthWest,
+            "o" => Self::Overlap,
+            ">" => Self::Above,
+            "<" => Self::Below,
+            other => Self::Other(String::from(other)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LdtkNeighbor {
+    pub level_iid: String,
+    pub direction: LdtkDirection,
+}
+
+// Tiles of a Tiles layer, or of the rules of an IntGrid or AutoLayer layer. Only the top tile
+// of stacked tiles is kept.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LdtkTileLayer {
+    pub identifier: String,
+    // Tileset image, relative to the project file. The tile ids index its tiles row by row.
+    pub tileset_path: Option<String>,
+    // World position of the center of tile (0, 0).
+    pub origin: Vector2<f32>,
+    pub tilemap: Tilemap,
+    // Horizontal and vertical flips of the flipped tiles.
+    pub flips: HashMap<Vector2<i32>, (bool, bool)>,
+    pub visible: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LdtkIntGridLayer {
+    pub identifier: String,
+    pub width: u32,
+    pub height: u32,
+    pub grid_size: u32,
+    // Row by row, 0 for empty cells.
+    pub values: Vec<i64>,
+}
+
+impl LdtkIntGridLayer {
+    pub fn value(&self, cell: &Vector2<i32>) -> Option<i64> {
+        if cell[0] >= 0
+            && cell[1] >= 0
+            && (cell[0] as u32) < self.width
+            && (cell[1] as u32) < self.height
+        {
+            Some(self.values[(cell[1] as u32 * self.width + cell[0] as u32) as usize])
+        } else {
+            None
+        }
+    }
+
+    // Cells with the given values, e.g. for auto-tiling or collision extraction.
+    pub fn terrain_mask(&self, values: &[i64]) -> TerrainMask {
+        let mut mask = TerrainMask::new(self.width, self.height);
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let cell = Vector2::new(x, y);
+                mask.set(&cell, values.contains(&self.value(&cell).unwrap()));
+            }
+        }
+        mask
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LdtkEntity {
+    pub identifier: String,
+    pub iid: String,
+    // World position of the entity pivot.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    // Fields without a value are skipped.
+    pub fields: BTreeMap<String, Value>,
+}
+
+impl LdtkEntity {
+    // Instance of the prefab named after the entity identifier. The fields are added as a
+    // custom component named after the identifier as well, to be instantiated through a
+    // roe_reflect::TypeRegistry.
+    pub fn to_entity_descriptor(&self) -> EntityDescriptor {
+        EntityDescriptor {
+            name: self.iid.clone(),
+            transform: Transform2 {
+                position: self.position,
+                ..Transform2::default()
+            },
+            components: vec![Component::Custom(ReflectedObject {
+                type_name: self.identifier.clone(),
+                fields: self.fields.clone(),
+            })],
+            children: Vec::new(),
+            instance: Some(PrefabInstance {
+                prefab: self.identifier.clone(),
+                overrides: Vec::new(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LdtkLevel {
+    pub identifier: String,
+    pub iid: String,
+    // World position of the top left corner, in pixels.
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    // Bottom to top, in drawing order.
+    pub tile_layers: Vec<LdtkTileLayer>,
+    pub int_grid_layers: Vec<LdtkIntGridLayer>,
+    pub entities: Vec<LdtkEntity>,
+    pub neighbors: Vec<LdtkNeighbor>,
+}
+
+impl LdtkLevel {
+    // The entities of the level, to be spawned with a roe_scene::PrefabLibrary.
+    pub fn to_prefab(&self) -> Prefab {
+        Prefab {
+            entities: self
+                .entities
+                .iter()
+                .map(LdtkEntity::to_entity_descriptor)
+                .collect(),
+        }
+    }
+
+    pub fn tile_layer(&self, identifier: &str) -> Option<&LdtkTileLayer> {
+        self.tile_layers.iter().find(|l| l.identifier == identifier)
+    }
+
+    pub fn int_grid_layer(&self, identifier: &str) -> Option<&LdtkIntGridLayer> {
+        self.int_grid_layers
+            .iter()
+            .find(|l| l.identifier == identifier)
+    }
+}
+
+// Levels of an LDtk project. Levels saved in separate files are not loaded.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LdtkProject {
+    pub levels: Vec<LdtkLevel>,
+}
+
+impl LdtkProject {
+    pub fn from_json_str(s: &str) -> Result<Self, LdtkError> {
+        let raw: RawProject = serde_json::from_str(s)?;
+        let levels = raw
+            .levels
+            .into_iter()
+            .map(import_level)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { levels })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LdtkError> {
+        Self::from_json_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn level(&self, identifier: &str) -> Option<&LdtkLevel> {
+        self.levels.iter().find(|l| l.identifier == identifier)
+    }
+
+    pub fn level_by_iid(&self, iid: &str) -> Option<&LdtkLevel> {
+        self.levels.iter().find(|l| l.iid == iid)
+    }
+}
+
+fn import_level(raw: RawLevel) -> Result<LdtkLevel, LdtkError> {
+    let position = Vector2::new(raw.world_x as f32, raw.world_y as f32);
+    let mut level = LdtkLevel {
+        identifier: raw.identifier,
+        iid: raw.iid,
+        position,
+        size: Vector2::new(raw.px_wid as f32, raw.px_hei as f32),
+        tile_layers: Vec::new(),
+        int_grid_layers: Vec::new(),
+        entities: Vec::new(),
+        neighbors: raw
+            .neighbours
+            .into_iter()
+            .map(|n| LdtkNeighbor {
+                level_iid: n.level_iid,
+                direction: LdtkDirection::parse(&n.dir),
+            })
+            .collect(),
+    };
+
+    // LDtk lists the layers from top to bottom.
+    for layer in raw.layer_instances.unwrap_or_default().into_iter().rev() {
+        let offset = position + Vector2::new(layer.px_offset_x as f32, layer.px_offset_y as f32);
+        if layer.grid_size == 0 {
+            return Err(LdtkError::InvalidLayer(
+                layer.identifier,
+                String::from("zero grid size"),
+            ));
+        }
+        match layer.layer_type.as_str() {
+            "Entities" => {
+                for entity in layer.entity_instances.iter() {
+                    level.entities.push(import_entity(entity, &offset));
+                }
+            }
+            "IntGrid" | "AutoLayer" | "Tiles" => {
+                if layer.layer_type == "IntGrid" {
+                    let cell_count = (layer.width * layer.height) as usize;
+                    if layer.int_grid_csv.len() != cell_count {
+                        return Err(LdtkError::InvalidLayer(
+                            layer.identifier,
+                            String::from("wrong IntGrid value count"),
+                        ));
+                    }
+                    level.int_grid_layers.push(LdtkIntGridLayer {
+                        identifier: layer.identifier.clone(),
+                        width: layer.width,
+                        height: layer.height,
+                        grid_size: layer.grid_size,
+                        values: layer.int_grid_csv.clone(),
+                    });
+                }
+                let tiles = if layer.layer_type == "Tiles" {
+                    &layer.grid_tiles
+                } else {
+                    &layer.auto_layer_tiles
+                };
+                if !tiles.is_empty() {
+                    level
+                        .tile_layers
+                        .push(import_tile_layer(&layer, tiles, &offset)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(level)
+}
+
+fn import_tile_layer(
+    layer: &RawLayer,
+    tiles: &[RawTile],
+    offset: &Vector2<f32>,
+) -> Result<LdtkTileLayer, LdtkError> {
+    let grid_size = layer.grid_size as i64;
+    let half_tile = layer.grid_size as f32 * 0.5;
+    let mut tilemap = Tilemap::new(
+        layer.width,
+        layer.height,
+        GridProjection::square(layer.grid_size as f32),
+    );
+    let mut flips = HashMap::new();
+    // Later tiles are drawn above the earlier ones.
+    for tile in tiles {
+        let cell = Vector2::new(
+            tile.px[0].div_euclid(grid_size) as i32,
+            tile.px[1].div_euclid(grid_size) as i32,
+        );
+        if !tilemap.contains(&cell) {
+            return Err(LdtkError::InvalidLayer(
+                layer.identifier.clone(),
+                format!("tile outside the layer ({}, {})", tile.px[0], tile.px[1]),
+            ));
+        }
+        tilemap.set_tile(&cell, Some(TileId(tile.t)));
+        if tile.f != 0 {
+            flips.insert(cell, (tile.f & 1 != 0, tile.f & 2 != 0));
+        } else {
+            flips.remove(&cell);
+        }
+    }
+    Ok(LdtkTileLayer {
+        identifier: layer.identifier.clone(),
+        tileset_path: layer.tileset_path.clone(),
+        origin: offset + Vector2::new(half_tile, half_tile),
+        tilemap,
+        flips,
+        visible: layer.visible,
+    })
+}
+
+fn import_entity(raw: &RawEntity, offset: &Vector2<f32>) -> LdtkEntity {
+    let fields = raw
+        .field_instances
+        .iter()
+        .filter_map(|f| import_field_value(&f.value).map(|v| (f.identifier.clone(), v)))
+        .collect();
+    LdtkEntity {
+        identifier: raw.identifier.clone(),
+        iid: raw.iid.clone(),
+        position: offset + Vector2::new(raw.px[0] as f32, raw.px[1] as f32),
+        size: Vector2::new(raw.width as f32, raw.height as f32),
+        fields,
+    }
+}
+
+// Numbers, booleans and strings (including enums, colors and file paths) map directly. Points
+// become grid cell vectors, and arrays of numbers or points become flat vectors.
+fn import_field_value(value: &serde_json::Value) -> Option<Value> {
+    use serde_json::Value as Json;
+    let point = |object: &serde_json::Map<String, Json>| {
+        Some([object.get("cx")?.as_f64()?, object.get("cy")?.as_f64()?])
+    };
+    match value {
+        Json::Bool(b) => Some(Value::Bool(*b)),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => Some(Value::Int(i)),
+            None => n.as_f64().map(Value::Float),
+        },
+        Json::String(s) => Some(Value::Text(s.clone())),
+        Json::Object(object) => point(object).map(|p| Value::Vector(p.to_vec())),
+        Json::Array(items) => {
+            let mut values = Vec::new();
+            for item in items {
+                match item {
+                    Json::Number(n) => values.push(n.as_f64()?),
+                    Json::Object(object) => values.extend_from_slice(&point(object)?),
+                    _ => return None,
+                }
+            }
+            Some(Value::Vector(values))
+        }
+        Json::Null => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    const PROJECT: &str = r##"{
+        "jsonVersion": "1.1.3",
+        "levels": [
+            {
+                "identifier": "Level_0",
+                "iid": "a1",
+                "uid": 0,
+                "worldX": 0,
+                "worldY": 0,
+                "pxWid": 32,
+                "pxHei": 16,
+                "__neighbours": [{"levelIid": "b2", "dir": "e"}],
+                "layerInstances": [
+                    {
+                        "__identifier": "Entities",
+                        "__type": "Entities",
+                        "__cWid": 4,
+                        "__cHei": 2,
+                        "__gridSize": 8,
+                        "entityInstances": [
+                            {
+                                "__identifier": "Chest",
+                                "iid": "e1",
+                                "__grid": [1, 1],
+                                "px": [12, 16],
+                                "width": 8,
+                                "height": 8,
+                                "fieldInstances": [
+                                    {"__identifier": "gold", "__type": "Int", "__value": 25},
+                                    {"__identifier": "locked", "__type": "Bool", "__value": true},
+                                    {"__identifier": "key", "__type": "String", "__value": null},
+                                    {"__identifier": "tint", "__type": "Color", "__value": "#FF0000"},
+                                    {"__identifier": "patrol", "__type": "Array<Point>",
+                                        "__value": [{"cx": 1, "cy": 0}, {"cx": 3, "cy": 1}]}
+                                ]
+                            }
+                        ]
+                    },
+                    {
+                        "__identifier": "Walls",
+                        "__type": "IntGrid",
+                        "__cWid": 4,
+                        "__cHei": 2,
+                        "__gridSize": 8,
+                        "__tilesetRelPath": "tiles.png",
+                        "intGridCsv": [1, 1, 0, 0, 1, 0, 0, 2],
+                        "autoLayerTiles": [
+                            {"px": [0, 0], "src": [0, 0], "f": 0, "t": 3},
+                            {"px": [8, 0], "src": [8, 0], "f": 1, "t": 4},
+                            {"px": [0, 8], "src": [0, 8], "f": 0, "t": 5}
+                        ]
+                    },
+                    {
+                        "__identifier": "Background",
+                        "__type": "Tiles",
+                        "__cWid": 4,
+                        "__cHei": 2,
+                        "__gridSize": 8,
+                        "pxOffsetX": 2,
+                        "visible": false,
+                        "gridTiles": [{"px": [24, 8], "src": [0, 0], "f": 2, "t": 9}]
+                    }
+                ]
+            },
+            {
+                "identifier": "Level_1",
+                "iid": "b2",
+                "worldX": 32,
+                "worldY": 0,
+                "pxWid": 32,
+                "pxHei": 16,
+                "layerInstances": null
+            }
+        ]
+    }"##;
+
+    #[test]
+    fn levels() {
+        let project = LdtkProject::from_json_str(PROJECT).unwrap();
+        expect_that!(&project.levels.len(), eq(2));
+        let level = project.level("Level_0").unwrap();
+        expect_that!(
+            &level.neighbors,
+            eq(vec![LdtkNeighbor {
+                level_iid: String::from("b2"),
+                direction: LdtkDirection::East,
+            }])
+        );
+        let neighbor = project.level_by_iid(&level.neighbors[0].level_iid).unwrap();
+        expect_that!(&neighbor.position, eq(Vector2::new(32., 0.)));
+        expect_that!(neighbor.tile_layers.is_empty());
+    }
+
+    #[test]
+    fn tile_layers() {
+        let project = LdtkProject::from_json_str(PROJECT).unwrap();
+        let level = project.level("Level_0").unwrap();
+        let names: Vec<_> = level
+            .tile_layers
+            .iter()
+            .map(|l| l.identifier.as_str())
+            .collect();
+        expect_that!(&names, eq(vec!["Background", "Walls"]));
+
+        let background = level.tile_layer("Background").unwrap();
+        expect_that!(!background.visible);
+        expect_that!(&background.origin, eq(Vector2::new(6., 4.)));
+        expect_that!(
+            &background.tilemap.tile(&Vector2::new(3, 1)),
+            eq(Some(TileId(9)))
+        );
+        expect_that!(
+            &background.flips.get(&Vector2::new(3, 1)),
+            eq(Some(&(false, true)))
+        );
+
+        let walls = level.tile_layer("Walls").unwrap();
+        expect_that!(&walls.tileset_path, eq(Some(String::from("tiles.png"))));
+        expect_that!(&walls.tilemap.draw_order().len(), eq(3));
+        expect_that!(
+            &walls.flips.get(&Vector2::new(1, 0)),
+            eq(Some(&(true, false)))
+        );
+    }
+
+    #[test]
+    fn int_grid_layers() {
+        let project = LdtkProject::from_json_str(PROJECT).unwrap();
+        let walls = project
+            .level("Level_0")
+            .unwrap()
+            .int_grid_layer("Walls")
+            .unwrap();
+        expect_that!(&walls.value(&Vector2::new(3, 1)), eq(Some(2)));
+        expect_that!(&walls.value(&Vector2::new(4, 1)), eq(None));
+        let mask = walls.terrain_mask(&[1]);
+        expect_that!(&mask.get(&Vector2::new(0, 1)), eq(Some(true)));
+        expect_that!(&mask.get(&Vector2::new(3, 1)), eq(Some(false)));
+    }
+
+    #[test]
+    fn entities() {
+        let project = LdtkProject::from_json_str(PROJECT).unwrap();
+        let level = project.level("Level_0").unwrap();
+        let chest = &level.entities[0];
+        expect_that!(&chest.position, eq(Vector2::new(12., 16.)));
+        expect_that!(&chest.fields.len(), eq(4));
+        expect_that!(&chest.fields["gold"], eq(Value::Int(25)));
+        expect_that!(
+            &chest.fields["patrol"],
+            eq(Value::Vector(vec![1., 0., 3., 1.]))
+        );
+
+        let prefab = level.to_prefab();
+        let entity = &prefab.entities[0];
+        expect_that!(&entity.name, eq(String::from("e1")));
+        expect_that!(
+            &entity.instance.as_ref().unwrap().prefab,
+            eq(String::from("Chest"))
+        );
+        expect_that!(
+            &entity.components[0],
+            eq(Component::Custom(ReflectedObject {
+                type_name: String::from("Chest"),
+                fields: chest.fields.clone(),
+            }))
+        );
+    }
+
+    #[test]
+    fn invalid_layers() {
+        let project = PROJECT.replace(
+            "\"intGridCsv\": [1, 1, 0, 0, 1, 0, 0, 2]",
+            "\"intGridCsv\": [1]",
+        );
+        expect_that!(
+            &LdtkProject::from_json_str(&project),
+            is_variant!(Result::Err)
+        );
+        expect_that!(
+            &LdtkProject::from_json_str("{\"levels\": 3}"),
+            is_variant!(Result::Err)
+        );
+    }
+}
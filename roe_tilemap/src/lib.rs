@@ -0,0 +1,14 @@
+mod auto_tiling;
+pub use auto_tiling::*;
+
+mod collision;
+pub use collision::*;
+
+mod grid_projection;
+pub use grid_projection::*;
+
+mod ldtk;
+pub use ldtk::*;
+
+mod tilemap;
+pub use tilemap::*;
@@ -0,0 +1,156 @@
+use super::GridProjection;
+
+use roe_math::Vector2;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct TileId(pub u32);
+
+// Rectangular grid of tiles, laid out in the world by its projection. Tiles outside the grid
+// and empty cells have no tile.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tilemap {
+    width: u32,
+    height: u32,
+    projection: GridProjection,
+    tiles: Vec<Option<TileId>>,
+}
+
+impl Tilemap {
+    pub fn new(width: u32, height: u32, projection: GridProjection) -> Self {
+        Self {
+            width,
+            height,
+            projection,
+            tiles: vec![None; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn projection(&self) -> &GridProjection {
+        &self.projection
+    }
+
+    pub fn contains(&self, tile: &Vector2<i32>) -> bool {
+        tile[0] >= 0
+            && tile[1] >= 0
+            && (tile[0] as u32) < self.width
+            && (tile[1] as u32) < self.height
+    }
+
+    pub fn tile(&self, tile: &Vector2<i32>) -> Option<TileId> {
+        if self.contains(tile) {
+            self.tiles[self.index(tile)]
+        } else {
+            None
+        }
+    }
+
+    pub fn set_tile(&mut self, tile: &Vector2<i32>, id: Option<TileId>) {
+        assert!(self.contains(tile), "The tile is outside the tilemap");
+        let index = self.index(tile);
+        self.tiles[index] = id;
+    }
+
+    pub fn fill(&mut self, id: Option<TileId>) {
+        self.tiles.iter_mut().for_each(|t| *t = id);
+    }
+
+    // World position of the tile center.
+    pub fn tile_center(&self, tile: &Vector2<i32>) -> Vector2<f32> {
+        self.projection.tile_to_world(tile)
+    }
+
+    // Tile under the world position, e.g. under the mouse cursor.
+    pub fn pick(&self, position: &Vector2<f32>) -> Option<Vector2<i32>> {
+        let tile = self.projection.world_to_tile(position);
+        if self.contains(&tile) {
+            Some(tile)
+        } else {
+            None
+        }
+    }
+
+    // Non-empty tiles in the order they must be drawn.
+    pub fn draw_order(&self) -> Vec<(Vector2<i32>, TileId)> {
+        let mut tiles = Vec::new();
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                let tile = Vector2::new(x, y);
+                if let Some(id) = self.tile(&tile) {
+                    tiles.push((tile, id));
+                }
+            }
+        }
+        tiles.sort_by_key(|(tile, _)| self.projection.draw_order_key(tile));
+        tiles
+    }
+
+    fn index(&self, tile: &Vector2<i32>) -> usize {
+        tile[1] as usize * self.width as usize + tile[0] as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HexOrientation, TileShape};
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn tiles() {
+        let mut tilemap = Tilemap::new(4, 3, GridProjection::square(16.));
+        tilemap.set_tile(&Vector2::new(3, 2), Some(TileId(7)));
+        expect_that!(&tilemap.tile(&Vector2::new(3, 2)), eq(Some(TileId(7))));
+        expect_that!(&tilemap.tile(&Vector2::new(0, 0)), eq(None));
+        expect_that!(&tilemap.tile(&Vector2::new(4, 0)), eq(None));
+        tilemap.fill(Some(TileId(1)));
+        expect_that!(&tilemap.draw_order().len(), eq(12));
+    }
+
+    #[test]
+    #[should_panic(expected = "The tile is outside the tilemap")]
+    fn set_tile_outside() {
+        let mut tilemap = Tilemap::new(4, 3, GridProjection::square(16.));
+        tilemap.set_tile(&Vector2::new(-1, 0), Some(TileId(0)));
+    }
+
+    #[test]
+    fn picking() {
+        let mut tilemap = Tilemap::new(
+            5,
+            5,
+            GridProjection::regular_hex(HexOrientation::PointyTop, 10.),
+        );
+        tilemap.fill(Some(TileId(0)));
+        let center = tilemap.tile_center(&Vector2::new(2, 3));
+        expect_that!(&tilemap.pick(&center), eq(Some(Vector2::new(2, 3))));
+        expect_that!(&tilemap.pick(&Vector2::new(-20., -20.)), eq(None));
+    }
+
+    #[test]
+    fn isometric_draw_order() {
+        let mut tilemap = Tilemap::new(
+            2,
+            2,
+            GridProjection::new(TileShape::Isometric, Vector2::new(32., 16.)),
+        );
+        tilemap.fill(Some(TileId(0)));
+        let order: Vec<_> = tilemap.draw_order().into_iter().map(|(t, _)| t).collect();
+        expect_that!(
+            &order,
+            eq(vec![
+                Vector2::new(0, 0),
+                Vector2::new(0, 1),
+                Vector2::new(1, 0),
+                Vector2::new(1, 1),
+            ])
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use super::{FontId, Quad, TextureId};
+
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DrawCommand<'a> {
+    // Quads sharing the same texture and color, drawn with a single call.
+    Sprites {
+        texture: TextureId,
+        color: gfx::ColorF32,
+        vertices: gfx::ArenaVec<'a, roe_sprite::Vertex>,
+        indices: gfx::ArenaVec<'a, roe_sprite::MeshIndex>,
+    },
+    Text {
+        font: FontId,
+        text: &'a str,
+        // Top left corner of the text box.
+        position: Vector2<f32>,
+        color: gfx::ColorF32,
+    },
+}
+
+// Ordered list of draw commands. Consecutive quads with the same texture and color are merged
+// into the same batch. The commands are allocated from a frame arena, and are only valid until
+// the end of the frame.
+#[derive(Debug)]
+pub struct DrawList<'a> {
+    arena: &'a gfx::FrameArena,
+    commands: gfx::ArenaVec<'a, DrawCommand<'a>>,
+}
+
+impl<'a> DrawList<'a> {
+    pub fn new(arena: &'a gfx::FrameArena) -> Self {
+        Self {
+            arena,
+            commands: arena.vec(),
+        }
+    }
+
+    pub fn arena(&self) -> &'a gfx::FrameArena {
+        self.arena
+    }
+
+    pub fn commands(&self) -> &[DrawCommand<'a>] {
+        &self.commands
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn push_quad(&mut self, texture: TextureId, color: gfx::ColorF32, quad: &Quad) {
+        let can_merge = match self.commands.last() {
+            Some(DrawCommand::Sprites {
+                texture: t,
+                color: c,
+                vertices,
+                ..
+            }) => {
+                *t == texture
+                    && *c == color
+                    && vertices.len() + 4 <= roe_sprite::MeshIndex::MAX as usize + 1
+            }
+            _ => false,
+        };
+        if !can_merge {
+            self.commands.push(DrawCommand::Sprites {
+                texture,
+                color,
+                vertices: self.arena.vec(),
+                indices: self.arena.vec(),
+            });
+        }
+        let (vertices, indices) = match self.commands.last_mut() {
+            Some(DrawCommand::Sprites {
+                vertices, indices, ..
+            }) => (vertices, indices),
+            _ => unreachable!(),
+        };
+
+        let first = vertices.len() as roe_sprite::MeshIndex;
+        let min = quad.rect.min();
+        let max = quad.rect.max();
+        let uv_min = quad.texture_coordinates.min();
+        let uv_max = quad.texture_coordinates.max();
+        vertices.extend_from_slice(&[
+            roe_sprite::Vertex::new([min.x, min.y], [uv_min.x, uv_min.y]),
+            roe_sprite::Vertex::new([min.x, max.y], [uv_min.x, uv_max.y]),
+            roe_sprite::Vertex::new([max.x, max.y], [uv_max.x, uv_max.y]),
+            roe_sprite::Vertex::new([max.x, min.y], [uv_max.x, uv_min.y]),
+        ]);
+        indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    pub fn push_text(
+        &mut self,
+        font: FontId,
+        text: &str,
+        position: Vector2<f32>,
+        color: gfx::ColorF32,
+    ) {
+        self.commands.push(DrawCommand::Text {
+            font,
+            text: self.arena.alloc_str(text),
+            position,
+            color,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rect;
+    use galvanic_assert::{matchers::*, *};
+
+    fn quad(x: f32) -> Quad {
+        Quad {
+            rect: Rect::new(x, 0., 10., 10.),
+            texture_coordinates: Rect::new(0., 0., 1., 1.),
+        }
+    }
+
+    fn batch_sizes(list: &DrawList) -> Vec<usize> {
+        list.commands()
+            .iter()
+            .map(|command| match command {
+                DrawCommand::Sprites { indices, .. } => indices.len() / 6,
+                DrawCommand::Text { .. } => 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batching() {
+        let arena = gfx::FrameArena::new();
+        let mut list = DrawList::new(&arena);
+        list.push_quad(TextureId(0), gfx::ColorF32::WHITE, &quad(0.));
+        list.push_quad(TextureId(0), gfx::ColorF32::WHITE, &quad(10.));
+        list.push_quad(TextureId(1), gfx::ColorF32::WHITE, &quad(20.));
+        list.push_quad(TextureId(1), gfx::ColorF32::RED, &quad(30.));
+        list.push_text(FontId(0), "Start", Vector2::zeros(), gfx::ColorF32::WHITE);
+        list.push_quad(TextureId(1), gfx::ColorF32::RED, &quad(40.));
+        expect_that!(&batch_sizes(&list), eq(vec![2, 1, 1, 0, 1]));
+
+        match &list.commands()[0] {
+            DrawCommand::Sprites {
+                vertices, indices, ..
+            } => {
+                expect_that!(&vertices.len(), eq(8));
+                expect_that!(&indices[6..].to_vec(), eq(vec![4, 5, 6, 4, 6, 7]));
+                expect_that!(
+                    &vertices[6],
+                    eq(roe_sprite::Vertex::new([20., 10.], [1., 1.]))
+                );
+            }
+            _ => panic!("Unexpected draw command"),
+        }
+    }
+
+    #[test]
+    fn batch_index_limit() {
+        let arena = gfx::FrameArena::new();
+        let mut list = DrawList::new(&arena);
+        for i in 0..16385 {
+            list.push_quad(TextureId(0), gfx::ColorF32::WHITE, &quad(i as f32));
+        }
+        expect_that!(&batch_sizes(&list), eq(vec![16384, 1]));
+    }
+}
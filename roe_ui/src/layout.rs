@@ -0,0 +1,291 @@
+use super::Rect;
+
+use roe_math::Vector2;
+
+// Position of the corners of a widget relative to its parent, as fractions of the parent size.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Anchors {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl Anchors {
+    // Both corners anchored to the same point: the widget keeps the size given by its offsets.
+    pub fn point(x: f32, y: f32) -> Self {
+        Self {
+            min: Vector2::new(x, y),
+            max: Vector2::new(x, y),
+        }
+    }
+
+    // The widget covers the whole parent, minus its offsets.
+    pub fn fill() -> Self {
+        Self {
+            min: Vector2::new(0., 0.),
+            max: Vector2::new(1., 1.),
+        }
+    }
+}
+
+impl Default for Anchors {
+    fn default() -> Self {
+        Self::fill()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FlexDirection {
+    Row,
+    Column,
+}
+
+impl FlexDirection {
+    fn main_axis(&self) -> usize {
+        match self {
+            FlexDirection::Row => 0,
+            FlexDirection::Column => 1,
+        }
+    }
+}
+
+// Placement of the children along the cross axis of a flex container.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FlexAlign {
+    Start,
+    Center,
+    End,
+    Stretch,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FlexDescriptor {
+    pub direction: FlexDirection,
+    pub spacing: f32,
+    pub align: FlexAlign,
+}
+
+impl Default for FlexDescriptor {
+    fn default() -> Self {
+        Self {
+            direction: FlexDirection::Column,
+            spacing: 0.,
+            align: FlexAlign::Stretch,
+        }
+    }
+}
+
+// How a widget places its children.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ChildrenLayout {
+    // Each child is placed through its anchors and offsets.
+    #[default]
+    Anchored,
+    // Children are stacked one after the other, ignoring their anchors.
+    Flex(FlexDescriptor),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LayoutDescriptor {
+    pub anchors: Anchors,
+    // Pixel offsets added to the anchored corners.
+    pub offset_min: Vector2<f32>,
+    pub offset_max: Vector2<f32>,
+    // Size inside flex containers. Zero components are replaced by the size of the content.
+    pub size: Vector2<f32>,
+    // Share of the free space along the main axis of a flex container.
+    pub grow: f32,
+    // Space between the borders of the widget and its content or children.
+    pub padding: f32,
+    pub children: ChildrenLayout,
+}
+
+impl LayoutDescriptor {
+    // A rectangle of the given size, with its top left corner at the given offset from the
+    // anchor point.
+    pub fn anchored(anchor: Vector2<f32>, offset: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Self {
+            anchors: Anchors::point(anchor.x, anchor.y),
+            offset_min: offset,
+            offset_max: offset + size,
+            size,
+            ..Self::default()
+        }
+    }
+
+    // A fixed size item for flex containers.
+    pub fn sized(width: f32, height: f32) -> Self {
+        Self {
+            size: Vector2::new(width, height),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for LayoutDescriptor {
+    fn default() -> Self {
+        Self {
+            anchors: Anchors::fill(),
+            offset_min: Vector2::zeros(),
+            offset_max: Vector2::zeros(),
+            size: Vector2::zeros(),
+            grow: 0.,
+            padding: 0.,
+            children: ChildrenLayout::Anchored,
+        }
+    }
+}
+
+pub fn anchored_rect(parent: &Rect, layout: &LayoutDescriptor) -> Rect {
+    let min = parent.position + parent.size.component_mul(&layout.anchors.min) + layout.offset_min;
+    let max = parent.position + parent.size.component_mul(&layout.anchors.max) + layout.offset_max;
+    Rect::from_corners(min, max)
+}
+
+// Places items with the given preferred sizes and grow factors inside the area.
+pub fn flex_rects(area: &Rect, desc: &FlexDescriptor, items: &[(Vector2<f32>, f32)]) -> Vec<Rect> {
+    flex_rects_iter(area, desc, items).collect()
+}
+
+// Same as flex_rects, without collecting the rectangles.
+pub fn flex_rects_iter<'a>(
+    area: &'a Rect,
+    desc: &'a FlexDescriptor,
+    items: &'a [(Vector2<f32>, f32)],
+) -> impl ExactSizeIterator<Item = Rect> + 'a {
+    let main = desc.direction.main_axis();
+    let cross = 1 - main;
+
+    let spacing = desc.spacing * items.len().saturating_sub(1) as f32;
+    let used: f32 = items.iter().map(|(size, _)| size[main]).sum::<f32>() + spacing;
+    let free = (area.size[main] - used).max(0.);
+    let total_grow: f32 = items.iter().map(|(_, grow)| grow.max(0.)).sum();
+
+    let mut cursor = area.position[main];
+    items.iter().map(move |(size, grow)| {
+        let mut rect = Rect::default();
+        rect.position[main] = cursor;
+        rect.size[main] = size[main];
+        if total_grow > 0. {
+            rect.size[main] += free * grow.max(0.) / total_grow;
+        }
+        cursor += rect.size[main] + desc.spacing;
+
+        let (position, length) = match desc.align {
+            FlexAlign::Start => (0., size[cross]),
+            FlexAlign::Center => ((area.size[cross] - size[cross]) * 0.5, size[cross]),
+            FlexAlign::End => (area.size[cross] - size[cross], size[cross]),
+            FlexAlign::Stretch => (0., area.size[cross]),
+        };
+        rect.position[cross] = area.position[cross] + position;
+        rect.size[cross] = length;
+        rect
+    })
+}
+
+// Preferred size of a flex container with the given items, including its padding.
+pub fn flex_content_size(
+    desc: &FlexDescriptor,
+    items: &[Vector2<f32>],
+    padding: f32,
+) -> Vector2<f32> {
+    let main = desc.direction.main_axis();
+    let cross = 1 - main;
+    let mut size = Vector2::zeros();
+    size[main] = items.iter().map(|item| item[main]).sum::<f32>()
+        + desc.spacing * items.len().saturating_sub(1) as f32;
+    size[cross] = items.iter().map(|item| item[cross]).fold(0., f32::max);
+    size + Vector2::new(padding * 2., padding * 2.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    #[test]
+    fn anchored() {
+        let parent = Rect::new(10., 20., 200., 100.);
+        let layout = LayoutDescriptor::anchored(
+            Vector2::new(1., 0.5),
+            Vector2::new(-50., -10.),
+            Vector2::new(40., 20.),
+        );
+        expect_that!(
+            &anchored_rect(&parent, &layout),
+            eq(Rect::new(160., 60., 40., 20.))
+        );
+
+        let layout = LayoutDescriptor {
+            offset_min: Vector2::new(5., 5.),
+            offset_max: Vector2::new(-5., -5.),
+            ..LayoutDescriptor::default()
+        };
+        expect_that!(
+            &anchored_rect(&parent, &layout),
+            eq(Rect::new(15., 25., 190., 90.))
+        );
+    }
+
+    #[test]
+    fn flex_grow() {
+        let area = Rect::new(0., 0., 100., 30.);
+        let desc = FlexDescriptor {
+            direction: FlexDirection::Row,
+            spacing: 10.,
+            align: FlexAlign::Stretch,
+        };
+        let rects = flex_rects(
+            &area,
+            &desc,
+            &[
+                (Vector2::new(20., 10.), 0.),
+                (Vector2::new(10., 10.), 1.),
+                (Vector2::new(10., 10.), 3.),
+            ],
+        );
+        expect_that!(
+            &rects,
+            eq(vec![
+                Rect::new(0., 0., 20., 30.),
+                Rect::new(30., 0., 20., 30.),
+                Rect::new(60., 0., 40., 30.),
+            ])
+        );
+    }
+
+    #[test]
+    fn flex_align() {
+        let area = Rect::new(0., 0., 100., 100.);
+        let items = [(Vector2::new(20., 10.), 0.), (Vector2::new(40., 10.), 0.)];
+        let rects = |align| {
+            flex_rects(
+                &area,
+                &FlexDescriptor {
+                    align,
+                    ..FlexDescriptor::default()
+                },
+                &items,
+            )
+        };
+        expect_that!(
+            &rects(FlexAlign::Start)[1],
+            eq(Rect::new(0., 10., 40., 10.))
+        );
+        expect_that!(
+            &rects(FlexAlign::Center)[1],
+            eq(Rect::new(30., 10., 40., 10.))
+        );
+        expect_that!(&rects(FlexAlign::End)[0], eq(Rect::new(80., 0., 20., 10.)));
+        expect_that!(
+            &flex_content_size(
+                &FlexDescriptor {
+                    spacing: 5.,
+                    ..FlexDescriptor::default()
+                },
+                &[Vector2::new(20., 10.), Vector2::new(40., 10.)],
+                2.
+            ),
+            eq(Vector2::new(44., 29.))
+        );
+    }
+}
@@ -0,0 +1,23 @@
+mod rect;
+pub use rect::*;
+
+mod layout;
+pub use layout::*;
+
+mod nine_patch;
+pub use nine_patch::*;
+
+mod widget;
+pub use widget::*;
+
+mod navigation;
+pub use navigation::*;
+
+mod draw_list;
+pub use draw_list::*;
+
+mod ui;
+pub use ui::*;
+
+mod renderer;
+pub use renderer::*;
@@ -0,0 +1,210 @@
+use super::{NinePatch, Rect};
+
+use roe_math::Vector2;
+
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum NavigationDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl NavigationDirection {
+    // Unit vector in ui coordinates, with the y axis pointing down.
+    pub fn vector(&self) -> Vector2<f32> {
+        match self {
+            NavigationDirection::Up => Vector2::new(0., -1.),
+            NavigationDirection::Down => Vector2::new(0., 1.),
+            NavigationDirection::Left => Vector2::new(-1., 0.),
+            NavigationDirection::Right => Vector2::new(1., 0.),
+        }
+    }
+}
+
+// Set on a widget to keep directional navigation inside its descendants, e.g. for dialogs.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct FocusScope {
+    // If no widget is found in the navigation direction, the focus moves to the widget on the
+    // opposite side of the scope.
+    pub wrap: bool,
+}
+
+// Nine patch drawn over the focused widget, extending beyond its borders by the margin.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FocusHighlight {
+    pub patch: NinePatch,
+    pub margin: f32,
+}
+
+// Picks the candidate closest to the given rectangle in the given direction. Distances across
+// the direction weigh more, so that aligned widgets are preferred.
+pub fn find_in_direction<T: Copy>(
+    from: &Rect,
+    candidates: &[(T, Rect)],
+    direction: NavigationDirection,
+    wrap: bool,
+) -> Option<T> {
+    const CROSS_WEIGHT: f32 = 2.;
+    let forward = direction.vector();
+    let cross = Vector2::new(forward.y, forward.x);
+    let origin = from.center();
+
+    let score = |rect: &Rect| {
+        let offset = rect.center() - origin;
+        (offset.dot(&forward), offset.dot(&cross).abs())
+    };
+
+    let best = candidates
+        .iter()
+        .map(|(id, rect)| (*id, score(rect)))
+        .filter(|(_, (distance, _))| *distance > 0.)
+        .min_by(|(_, a), (_, b)| (a.0 + a.1 * CROSS_WEIGHT).total_cmp(&(b.0 + b.1 * CROSS_WEIGHT)))
+        .map(|(id, _)| id);
+    if best.is_some() || !wrap {
+        return best;
+    }
+
+    // Wrapping around: the farthest candidate in the opposite direction.
+    candidates
+        .iter()
+        .map(|(id, rect)| (*id, score(rect)))
+        .filter(|(_, (distance, _))| *distance < 0.)
+        .min_by(|(_, a), (_, b)| (a.0 + a.1 * CROSS_WEIGHT).total_cmp(&(b.0 + b.1 * CROSS_WEIGHT)))
+        .map(|(id, _)| id)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NavigationRepeaterDescriptor {
+    // Stick deflection below which the stick is considered at rest.
+    pub dead_zone: f32,
+    // Time the stick must be held before the direction repeats.
+    pub repeat_delay: Duration,
+    pub repeat_interval: Duration,
+}
+
+impl Default for NavigationRepeaterDescriptor {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.5,
+            repeat_delay: Duration::from_millis(400),
+            repeat_interval: Duration::from_millis(150),
+        }
+    }
+}
+
+// Turns the position of an analog stick into discrete navigation steps: one when the stick is
+// pushed in a direction, then repeated while it's held.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NavigationRepeater {
+    desc: NavigationRepeaterDescriptor,
+    direction: Option<NavigationDirection>,
+    next_repeat: Duration,
+}
+
+impl NavigationRepeater {
+    pub fn new(desc: &NavigationRepeaterDescriptor) -> Self {
+        assert!(
+            desc.repeat_interval > Duration::ZERO,
+            "The navigation repeat interval must be higher than 0"
+        );
+        Self {
+            desc: *desc,
+            direction: None,
+            next_repeat: Duration::ZERO,
+        }
+    }
+
+    // The stick position is in ui coordinates, with the y axis pointing down.
+    pub fn update(&mut self, stick: &Vector2<f32>, dt: Duration) -> Option<NavigationDirection> {
+        let direction = if stick.norm() < self.desc.dead_zone {
+            None
+        } else if stick.x.abs() > stick.y.abs() {
+            Some(if stick.x > 0. {
+                NavigationDirection::Right
+            } else {
+                NavigationDirection::Left
+            })
+        } else {
+            Some(if stick.y > 0. {
+                NavigationDirection::Down
+            } else {
+                NavigationDirection::Up
+            })
+        };
+
+        if direction != self.direction {
+            self.direction = direction;
+            self.next_repeat = self.desc.repeat_delay;
+            return direction;
+        }
+        let direction = direction?;
+        if dt < self.next_repeat {
+            self.next_repeat -= dt;
+            None
+        } else {
+            self.next_repeat = self
+                .desc
+                .repeat_interval
+                .saturating_sub(dt - self.next_repeat);
+            Some(direction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    // A 3x2 grid of 10x10 cells with 10 pixels gaps, missing the bottom right cell.
+    fn grid() -> Vec<(usize, Rect)> {
+        (0..5)
+            .map(|i| {
+                (
+                    i,
+                    Rect::new((i % 3) as f32 * 20., (i / 3) as f32 * 20., 10., 10.),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn direction() {
+        let grid = grid();
+        let find =
+            |from: usize, direction, wrap| find_in_direction(&grid[from].1, &grid, direction, wrap);
+        expect_that!(&find(0, NavigationDirection::Right, false), eq(Some(1)));
+        expect_that!(&find(1, NavigationDirection::Down, false), eq(Some(4)));
+        expect_that!(&find(4, NavigationDirection::Up, false), eq(Some(1)));
+        expect_that!(&find(2, NavigationDirection::Down, false), eq(Some(4)));
+        expect_that!(&find(2, NavigationDirection::Right, false), eq(None));
+        expect_that!(&find(2, NavigationDirection::Right, true), eq(Some(0)));
+        expect_that!(&find(3, NavigationDirection::Down, true), eq(Some(0)));
+    }
+
+    #[test]
+    fn repeater() {
+        let mut repeater = NavigationRepeater::new(&NavigationRepeaterDescriptor::default());
+        let step = Duration::from_millis(100);
+        let down = Vector2::new(0.1, 0.9);
+        expect_that!(
+            &repeater.update(&down, step),
+            eq(Some(NavigationDirection::Down))
+        );
+        // Waits for the repeat delay, then repeats at the repeat interval.
+        let steps: Vec<_> = (0..8).map(|_| repeater.update(&down, step)).collect();
+        expect_that!(&steps.iter().filter(|d| d.is_some()).count(), eq(3));
+        expect_that!(&steps[3], eq(Some(NavigationDirection::Down)));
+        expect_that!(&steps[5], eq(Some(NavigationDirection::Down)));
+        expect_that!(&steps[6], eq(Some(NavigationDirection::Down)));
+
+        expect_that!(&repeater.update(&Vector2::new(0.2, 0.), step), eq(None));
+        expect_that!(
+            &repeater.update(&Vector2::new(-0.8, 0.), step),
+            eq(Some(NavigationDirection::Left))
+        );
+    }
+}
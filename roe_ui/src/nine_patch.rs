@@ -0,0 +1,214 @@
+use super::Rect;
+
+use roe_graphics as gfx;
+use roe_math::Vector2;
+
+// Identifiers of the textures and fonts used by the widgets. The renderer maps them to the
+// actual gpu resources.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct TextureId(pub u32);
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct FontId(pub u32);
+
+// Region of a texture, in texture pixels.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ImageRegion {
+    pub texture: TextureId,
+    pub texture_size: Vector2<f32>,
+    pub region: Rect,
+}
+
+impl ImageRegion {
+    pub fn whole(texture: TextureId, texture_size: Vector2<f32>) -> Self {
+        Self {
+            texture,
+            texture_size,
+            region: Rect {
+                position: Vector2::zeros(),
+                size: texture_size,
+            },
+        }
+    }
+
+    pub fn texture_coordinates(&self) -> Rect {
+        Rect {
+            position: self.region.position.component_div(&self.texture_size),
+            size: self.region.size.component_div(&self.texture_size),
+        }
+    }
+}
+
+// Textured rectangle, the unit of ui drawing.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quad {
+    pub rect: Rect,
+    pub texture_coordinates: Rect,
+}
+
+// Image split in a 3x3 grid by its borders. When stretched, the corners keep their size, the
+// edges are stretched along one axis and the center along both.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NinePatch {
+    pub image: ImageRegion,
+    // Border widths in pixels, as (left, top, right, bottom).
+    pub borders: [f32; 4],
+}
+
+impl NinePatch {
+    pub fn new(image: ImageRegion, borders: [f32; 4]) -> Self {
+        assert!(
+            borders[0] + borders[2] <= image.region.size.x
+                && borders[1] + borders[3] <= image.region.size.y,
+            "The nine patch borders must fit in the image region"
+        );
+        Self { image, borders }
+    }
+
+    // Quads covering the rectangle. If the rectangle is smaller than the borders, the borders
+    // are shrunk proportionally.
+    pub fn quads(&self, rect: &Rect) -> Vec<Quad> {
+        let mut quads = Vec::with_capacity(9);
+        self.extend_quads(rect, &mut quads);
+        quads
+    }
+
+    // Same as quads, allocating the quads from the frame arena.
+    pub fn quads_in<'a>(&self, rect: &Rect, arena: &'a gfx::FrameArena) -> gfx::ArenaVec<'a, Quad> {
+        let mut quads = arena.vec_with_capacity(9);
+        self.extend_quads(rect, &mut quads);
+        quads
+    }
+
+    fn extend_quads<E: Extend<Quad>>(&self, rect: &Rect, quads: &mut E) {
+        let [left, top, right, bottom] = self.borders;
+        let x_scale = border_scale(left + right, rect.size.x);
+        let y_scale = border_scale(top + bottom, rect.size.y);
+
+        let min = rect.min();
+        let max = rect.max();
+        let xs = [
+            min.x,
+            min.x + left * x_scale,
+            max.x - right * x_scale,
+            max.x,
+        ];
+        let ys = [
+            min.y,
+            min.y + top * y_scale,
+            max.y - bottom * y_scale,
+            max.y,
+        ];
+
+        let uv = self.image.texture_coordinates();
+        let uv_min = uv.min();
+        let uv_max = uv.max();
+        let texture_size = self.image.texture_size;
+        let us = [
+            uv_min.x,
+            uv_min.x + left / texture_size.x,
+            uv_max.x - right / texture_size.x,
+            uv_max.x,
+        ];
+        let vs = [
+            uv_min.y,
+            uv_min.y + top / texture_size.y,
+            uv_max.y - bottom / texture_size.y,
+            uv_max.y,
+        ];
+
+        for row in 0..3 {
+            for column in 0..3 {
+                if xs[column + 1] <= xs[column] || ys[row + 1] <= ys[row] {
+                    continue;
+                }
+                quads.extend([Quad {
+                    rect: Rect::from_corners(
+                        Vector2::new(xs[column], ys[row]),
+                        Vector2::new(xs[column + 1], ys[row + 1]),
+                    ),
+                    texture_coordinates: Rect::from_corners(
+                        Vector2::new(us[column], vs[row]),
+                        Vector2::new(us[column + 1], vs[row + 1]),
+                    ),
+                }]);
+            }
+        }
+    }
+}
+
+fn border_scale(borders: f32, size: f32) -> f32 {
+    if borders > size && borders > 0. {
+        size / borders
+    } else {
+        1.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use galvanic_assert::{matchers::*, *};
+
+    fn patch() -> NinePatch {
+        NinePatch::new(
+            ImageRegion {
+                texture: TextureId(0),
+                texture_size: Vector2::new(64., 32.),
+                region: Rect::new(32., 0., 32., 32.),
+            },
+            [8., 4., 8., 4.],
+        )
+    }
+
+    #[test]
+    fn stretched() {
+        let quads = patch().quads(&Rect::new(10., 10., 100., 50.));
+        expect_that!(&quads.len(), eq(9));
+        expect_that!(
+            &quads[0],
+            eq(Quad {
+                rect: Rect::new(10., 10., 8., 4.),
+                texture_coordinates: Rect::new(0.5, 0., 0.125, 0.125),
+            })
+        );
+        expect_that!(
+            &quads[4],
+            eq(Quad {
+                rect: Rect::new(18., 14., 84., 42.),
+                texture_coordinates: Rect::new(0.625, 0.125, 0.25, 0.75),
+            })
+        );
+        expect_that!(&quads[8].rect, eq(Rect::new(102., 56., 8., 4.)));
+        expect_that!(
+            &quads[8].texture_coordinates.max(),
+            eq(Vector2::new(1., 1.))
+        );
+    }
+
+    #[test]
+    fn arena_quads() {
+        let rect = Rect::new(10., 10., 100., 50.);
+        let arena = gfx::FrameArena::new();
+        let quads = patch().quads(&rect);
+        expect_that!(&&patch().quads_in(&rect, &arena)[..], eq(&quads[..]));
+    }
+
+    #[test]
+    fn smaller_than_borders() {
+        let quads = patch().quads(&Rect::new(0., 0., 8., 40.));
+        // The center column collapses and the borders are halved.
+        expect_that!(&quads.len(), eq(6));
+        expect_that!(&quads[0].rect, eq(Rect::new(0., 0., 4., 4.)));
+        expect_that!(&quads[1].rect, eq(Rect::new(4., 0., 4., 4.)));
+    }
+
+    #[test]
+    #[should_panic(expected = "The nine patch borders must fit in the image region")]
+    fn oversized_borders() {
+        NinePatch::new(
+            ImageRegion::whole(TextureId(0), Vector2::new(16., 16.)),
+            [10., 0., 10., 0.],
+        );
+    }
+}
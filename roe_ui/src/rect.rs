@@ -0,0 +1,64 @@
+use roe_math::Vector2;
+
+// Axis aligned rectangle in ui coordinates: pixels, origin in the top left corner and y axis
+// pointing down.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde-serialize",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Rect {
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            position: Vector2::new(x, y),
+            size: Vector2::new(width, height),
+        }
+    }
+
+    pub fn from_corners(min: Vector2<f32>, max: Vector2<f32>) -> Self {
+        Self {
+            position: min,
+            size: max - min,
+        }
+    }
+
+    pub fn min(&self) -> Vector2<f32> {
+        self.position
+    }
+
+    pub fn max(&self) -> Vector2<f32> {
+        self.position + self.size
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        self.position + self.size * 0.5
+    }
+
+    pub fn contains(&self, point: &Vector2<f32>) -> bool {
+        let max = self.max();
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < max.x
+            && point.y < max.y
+    }
+
+    // Moves all sides inwards. The size is clamped to 0.
+    pub fn shrink(&self, amount: f32) -> Self {
+        let size = self.size - Vector2::new(amount * 2., amount * 2.);
+        Self {
+            position: self.position + Vector2::new(amount, amount),
+            size: Vector2::new(size.x.max(0.), size.y.max(0.)),
+        }
+    }
+}
+
+impl Default for Rect {
+    fn default() -> Self {
+        Self::new(0., 0., 0., 0.)
+    }
+}
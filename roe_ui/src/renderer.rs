@@ -0,0 +1,149 @@
+use super::{DrawCommand, DrawList, FontId, TextMeasure, TextureId};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Vector2};
+use roe_sprite::Renderer as SpriteRenderer;
+use roe_text::Renderer as TextRenderer;
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+enum Batch {
+    Sprites {
+        texture: TextureId,
+        mesh: roe_sprite::Mesh,
+        push_constants: roe_sprite::PushConstants,
+        index_count: u32,
+    },
+    Text {
+        font: FontId,
+        text: String,
+        transform: HomogeneousMatrix2<f32>,
+        color: gfx::ColorF32,
+    },
+}
+
+// Draws ui draw lists through the sprite and text pipelines. Textures and fonts are registered
+// under the ids used by the widgets.
+#[derive(Debug, Default)]
+pub struct UiRenderer {
+    textures: HashMap<TextureId, roe_sprite::UniformConstants>,
+    fonts: HashMap<FontId, roe_text::Font>,
+    batches: Vec<Batch>,
+}
+
+impl UiRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_texture(&mut self, id: TextureId, texture: roe_sprite::UniformConstants) {
+        self.textures.insert(id, texture);
+    }
+
+    pub fn insert_font(&mut self, id: FontId, font: roe_text::Font) {
+        self.fonts.insert(id, font);
+    }
+
+    pub fn font(&self, id: FontId) -> Option<&roe_text::Font> {
+        self.fonts.get(&id)
+    }
+
+    // Uploads the draw list, replacing the previous one. The projection maps ui coordinates
+    // to clip space, usually Ui::projection, which also applies the ui scale to text and
+    // images.
+    pub fn prepare(
+        &mut self,
+        instance: &gfx::Instance,
+        draw_list: &DrawList,
+        projection: &HomogeneousMatrix2<f32>,
+    ) {
+        self.batches.clear();
+        for command in draw_list.commands() {
+            match command {
+                DrawCommand::Sprites {
+                    texture,
+                    color,
+                    vertices,
+                    indices,
+                } => self.batches.push(Batch::Sprites {
+                    texture: *texture,
+                    mesh: roe_sprite::Mesh::new(instance, vertices, indices),
+                    push_constants: roe_sprite::PushConstants::new(projection, *color),
+                    index_count: indices.len() as u32,
+                }),
+                DrawCommand::Text {
+                    font,
+                    text,
+                    position,
+                    color,
+                } => {
+                    // Text is drawn from the baseline, roughly one font size below the top.
+                    let size = self.fonts.get(font).map(|f| f.size()).unwrap_or(0.);
+                    let baseline = position + Vector2::new(0., size * 0.8);
+                    self.batches.push(Batch::Text {
+                        font: *font,
+                        text: String::from(*text),
+                        transform: projection * roe_math::translation2(&baseline),
+                        color: *color,
+                    });
+                }
+            }
+        }
+    }
+
+    // Draws the prepared draw list. Batches referencing unknown textures or fonts are skipped.
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut gfx::RenderPass<'a>,
+        sprite_pipeline: &'a roe_sprite::RenderPipeline,
+        text_pipeline: &'a roe_text::RenderPipeline,
+    ) {
+        for batch in self.batches.iter() {
+            match batch {
+                Batch::Sprites {
+                    texture,
+                    mesh,
+                    push_constants,
+                    index_count,
+                } => {
+                    if let Some(uniform_constants) = self.textures.get(texture) {
+                        pass.draw_sprite(
+                            sprite_pipeline,
+                            uniform_constants,
+                            mesh,
+                            push_constants,
+                            0..*index_count,
+                        );
+                    }
+                }
+                Batch::Text {
+                    font,
+                    text,
+                    transform,
+                    color,
+                } => {
+                    if let Some(font) = self.fonts.get(font) {
+                        pass.draw_text(text_pipeline, font, text, transform, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl TextMeasure for UiRenderer {
+    fn measure_text(&self, font: FontId, text: &str) -> Vector2<f32> {
+        let font = match self.fonts.get(&font) {
+            Some(font) => font,
+            None => return Vector2::zeros(),
+        };
+        let shaping_output = font.shape_text(text);
+        let width = shaping_output
+            .get_glyph_positions()
+            .iter()
+            .map(|position| roe_text::i26dot6_to_fsize(position.x_advance))
+            .sum();
+        Vector2::new(width, font.size())
+    }
+}
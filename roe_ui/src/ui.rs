@@ -0,0 +1,1226 @@
+use super::{
+    anchored_rect, find_in_direction, flex_content_size, flex_rects_iter, ChildrenLayout, DrawList,
+    FocusHighlight, FocusScope, FontId, NavigationDirection, NinePatch, Quad, Rect, Widget,
+    WidgetKind, WidgetState,
+};
+
+use roe_graphics as gfx;
+use roe_math::{HomogeneousMatrix2, Vector2};
+
+use std::collections::VecDeque;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct WidgetId {
+    index: u32,
+    generation: u32,
+}
+
+// Provides the size of text drawn with the fonts referenced by the widgets.
+pub trait TextMeasure {
+    fn measure_text(&self, font: FontId, text: &str) -> Vector2<f32>;
+}
+
+// Pointer input in screen pixels and focus navigation input. The application forwards its
+// cursor or touch events and its d-pad or stick events here.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UiInput {
+    PointerMoved(Vector2<f32>),
+    PointerPressed,
+    PointerReleased,
+    PointerLeft,
+    Navigate(NavigationDirection),
+    ActivatePressed,
+    ActivateReleased,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum UiEvent {
+    Clicked(WidgetId),
+    ValueChanged { widget: WidgetId, value: f32 },
+    FocusChanged(Option<WidgetId>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Slot {
+    generation: u32,
+    widget: Option<Widget>,
+}
+
+// Retained tree of widgets. Widgets are placed by layout, react to input and are turned into
+// a draw list each frame.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Ui {
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    roots: Vec<WidgetId>,
+    size: Vector2<f32>,
+    scale: f32,
+    pointer: Option<Vector2<f32>>,
+    hovered: Option<WidgetId>,
+    pressed: Option<WidgetId>,
+    // Whether the pressed widget was pressed through the activate input.
+    activating: bool,
+    focused: Option<WidgetId>,
+    root_focus_scope: FocusScope,
+    focus_highlight: Option<FocusHighlight>,
+    events: VecDeque<UiEvent>,
+}
+
+impl Ui {
+    pub fn new(size: Vector2<f32>) -> Self {
+        Self {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            roots: Vec::new(),
+            size,
+            scale: 1.,
+            pointer: None,
+            hovered: None,
+            pressed: None,
+            activating: false,
+            focused: None,
+            root_focus_scope: FocusScope::default(),
+            focus_highlight: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn size(&self) -> &Vector2<f32> {
+        &self.size
+    }
+
+    pub fn set_size(&mut self, size: Vector2<f32>) {
+        self.size = size;
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // Widgets are laid out in logical units, each covering scale screen pixels. Requires a new
+    // layout pass.
+    pub fn set_scale(&mut self, scale: f32) {
+        assert!(scale > 0., "The ui scale must be higher than 0");
+        self.scale = scale;
+    }
+
+    // Size of the ui in logical units.
+    pub fn logical_size(&self) -> Vector2<f32> {
+        self.size / self.scale
+    }
+
+    // Maps the logical coordinates of the draw list to clip space.
+    pub fn projection(&self) -> HomogeneousMatrix2<f32> {
+        let size = self.logical_size();
+        roe_math::ortographic_projection2(0., size.x, size.y, 0.)
+    }
+
+    pub fn add(&mut self, parent: Option<WidgetId>, mut widget: Widget) -> WidgetId {
+        if let Some(parent) = parent {
+            assert!(self.contains(parent), "The parent widget doesn't exist");
+        }
+        widget.parent = parent;
+        widget.children.clear();
+
+        let id = match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.widget = Some(widget);
+                WidgetId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    widget: Some(widget),
+                });
+                WidgetId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        match parent {
+            Some(parent) => self.widget_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    // Removes the widget and all its descendants.
+    pub fn remove(&mut self, id: WidgetId) -> Option<Widget> {
+        let widget = self.take_widget(id)?;
+        match widget.parent {
+            Some(parent) => {
+                if let Some(parent) = self.widget_mut(parent) {
+                    parent.children.retain(|child| *child != id);
+                }
+            }
+            None => self.roots.retain(|root| *root != id),
+        }
+        for child in widget.children.iter() {
+            self.remove_subtree(*child);
+        }
+        if self.hovered.map(|id| self.contains(id)) == Some(false) {
+            self.hovered = None;
+        }
+        if self.pressed.map(|id| self.contains(id)) == Some(false) {
+            self.pressed = None;
+            self.activating = false;
+        }
+        if self.focused.map(|id| self.contains(id)) == Some(false) {
+            self.set_focus(None);
+        }
+        Some(widget)
+    }
+
+    pub fn contains(&self, id: WidgetId) -> bool {
+        self.widget(id).is_some()
+    }
+
+    pub fn widget(&self, id: WidgetId) -> Option<&Widget> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.widget.as_ref())
+    }
+
+    pub fn widget_mut(&mut self, id: WidgetId) -> Option<&mut Widget> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.widget.as_mut())
+    }
+
+    pub fn roots(&self) -> &[WidgetId] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn hovered(&self) -> Option<WidgetId> {
+        self.hovered
+    }
+
+    pub fn pressed(&self) -> Option<WidgetId> {
+        self.pressed
+    }
+
+    pub fn focused(&self) -> Option<WidgetId> {
+        self.focused
+    }
+
+    pub fn set_focus(&mut self, focus: Option<WidgetId>) {
+        if focus == self.focused {
+            return;
+        }
+        if self.activating && self.pressed != focus {
+            self.pressed = None;
+            self.activating = false;
+        }
+        self.focused = focus;
+        self.events.push_back(UiEvent::FocusChanged(focus));
+    }
+
+    // Navigation rules outside of any widget with a focus scope.
+    pub fn set_root_focus_scope(&mut self, scope: FocusScope) {
+        self.root_focus_scope = scope;
+    }
+
+    // Drawn over the focused widget. Widget skins can also react to the focused state.
+    pub fn set_focus_highlight(&mut self, highlight: Option<FocusHighlight>) {
+        self.focus_highlight = highlight;
+    }
+
+    // Whether the widget can receive focus: it must be interactive, enabled and visible
+    // together with all its ancestors.
+    pub fn is_focusable(&self, id: WidgetId) -> bool {
+        let widget = match self.widget(id) {
+            Some(widget) => widget,
+            None => return false,
+        };
+        if !widget.enabled || !widget.kind.is_interactive() {
+            return false;
+        }
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let widget = self.widget(id).unwrap();
+            if !widget.visible {
+                return false;
+            }
+            current = widget.parent;
+        }
+        true
+    }
+
+    pub fn widget_state(&self, id: WidgetId) -> WidgetState {
+        match self.widget(id) {
+            Some(widget) if !widget.enabled => WidgetState::Disabled,
+            _ if self.pressed == Some(id) => WidgetState::Pressed,
+            _ if self.focused == Some(id) => WidgetState::Focused,
+            _ if self.hovered == Some(id) => WidgetState::Hovered,
+            _ => WidgetState::Normal,
+        }
+    }
+
+    // Computes the rectangles of all visible widgets. Root widgets are placed relative to the
+    // whole ui. The temporary data is taken from the frame arena.
+    pub fn layout_in(&mut self, measure: &dyn TextMeasure, arena: &gfx::FrameArena) {
+        let area = Rect {
+            position: Vector2::zeros(),
+            size: self.logical_size(),
+        };
+        let roots = arena.alloc_slice_copy(&self.roots);
+        self.layout_children(&area, &ChildrenLayout::Anchored, roots, measure, arena);
+    }
+
+    // Replaces the texts of the labels and buttons having a translation key with the
+    // translated ones, e.g. after switching locale. A layout pass is needed afterwards, since
+    // the text sizes may change.
+    pub fn translate_texts(&mut self, translate: &dyn Fn(&str) -> String) {
+        for widget in self
+            .slots
+            .iter_mut()
+            .filter_map(|slot| slot.widget.as_mut())
+        {
+            let key = match &widget.text_key {
+                Some(key) => key,
+                None => continue,
+            };
+            match &mut widget.kind {
+                WidgetKind::Label { text, .. } | WidgetKind::Button { text, .. } => {
+                    *text = translate(key)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Updates the widget states from the input. Returns true if the input is captured by the
+    // ui, in which case the game shouldn't handle it: pointer input is captured when the
+    // pointer is over a visible widget or is dragging one, navigation input when a widget has
+    // focus.
+    pub fn handle_input(&mut self, input: UiInput) -> bool {
+        match input {
+            UiInput::PointerMoved(position) => {
+                let position = position / self.scale;
+                self.pointer = Some(position);
+                self.hovered = self.hit_test(&position, true);
+                if let Some(id) = self.pressed {
+                    self.drag(id, &position);
+                }
+            }
+            UiInput::PointerPressed => {
+                if !self.activating {
+                    self.pressed = self.hovered;
+                    if let (Some(id), Some(position)) = (self.pressed, self.pointer) {
+                        self.drag(id, &position);
+                    }
+                }
+            }
+            UiInput::PointerReleased => {
+                if !self.activating {
+                    let hovered = self.hovered;
+                    self.release(hovered);
+                }
+            }
+            UiInput::PointerLeft => {
+                self.pointer = None;
+                self.hovered = None;
+            }
+            UiInput::Navigate(direction) => {
+                self.navigate(direction);
+                return self.focused.is_some();
+            }
+            UiInput::ActivatePressed => {
+                if self.pressed.is_none() {
+                    if let Some(id) = self.focused.filter(|id| self.is_focusable(*id)) {
+                        self.pressed = Some(id);
+                        self.activating = true;
+                    }
+                }
+                return self.focused.is_some();
+            }
+            UiInput::ActivateReleased => {
+                if self.activating {
+                    self.activating = false;
+                    let focused = self.focused;
+                    self.release(focused);
+                }
+                return self.focused.is_some();
+            }
+        }
+        self.pressed.is_some()
+            || self
+                .pointer
+                .map(|position| self.hit_test(&position, false).is_some())
+                == Some(true)
+    }
+
+    pub fn poll_event(&mut self) -> Option<UiEvent> {
+        self.events.pop_front()
+    }
+
+    // Generates the draw commands of the visible widgets, parents before children. The commands
+    // are allocated from the frame arena.
+    pub fn draw_list_in<'a>(
+        &self,
+        measure: &dyn TextMeasure,
+        arena: &'a gfx::FrameArena,
+    ) -> DrawList<'a> {
+        let mut list = DrawList::new(arena);
+        for root in self.roots.iter() {
+            self.draw_widget(*root, &mut list, measure);
+        }
+        list
+    }
+
+    // Releases the pressed widget. Buttons are clicked if released over the given widget.
+    fn release(&mut self, over: Option<WidgetId>) {
+        if let Some(id) = self.pressed.take() {
+            let is_button = matches!(
+                self.widget(id).map(|w| &w.kind),
+                Some(WidgetKind::Button { .. })
+            );
+            if is_button && over == Some(id) {
+                self.events.push_back(UiEvent::Clicked(id));
+            }
+        }
+    }
+
+    fn navigate(&mut self, direction: NavigationDirection) {
+        let focused = match self.focused.filter(|id| self.is_focusable(*id)) {
+            Some(id) => id,
+            None => {
+                let first = self.focusable_descendants(&self.roots).first().copied();
+                self.set_focus(first);
+                return;
+            }
+        };
+
+        // Focused sliders use the horizontal directions to change their value.
+        if let Some(&WidgetKind::Slider {
+            value,
+            min,
+            max,
+            step,
+            ..
+        }) = self.widget(focused).map(|w| &w.kind)
+        {
+            let sign = match direction {
+                NavigationDirection::Left => Some(-1.),
+                NavigationDirection::Right => Some(1.),
+                _ => None,
+            };
+            if let Some(sign) = sign {
+                let increment = step.unwrap_or((max - min) * 0.1);
+                let new_value = (value + increment * sign).clamp(min, max.max(min));
+                self.set_slider_value(focused, new_value);
+                return;
+            }
+        }
+
+        let (children, scope) = match self.enclosing_focus_scope(focused) {
+            Some((id, scope)) => (self.widget(id).unwrap().children.clone(), scope),
+            None => (self.roots.clone(), self.root_focus_scope),
+        };
+        let candidates: Vec<_> = self
+            .focusable_descendants(&children)
+            .into_iter()
+            .filter(|id| *id != focused)
+            .map(|id| (id, self.widget(id).unwrap().rect))
+            .collect();
+        let from = self.widget(focused).unwrap().rect;
+        if let Some(next) = find_in_direction(&from, &candidates, direction, scope.wrap) {
+            self.set_focus(Some(next));
+        }
+    }
+
+    // Nearest ancestor with a focus scope.
+    fn enclosing_focus_scope(&self, id: WidgetId) -> Option<(WidgetId, FocusScope)> {
+        let mut current = self.widget(id)?.parent;
+        while let Some(id) = current {
+            let widget = self.widget(id)?;
+            if let Some(scope) = widget.focus_scope {
+                return Some((id, scope));
+            }
+            current = widget.parent;
+        }
+        None
+    }
+
+    // Focusable widgets among the given ones and their descendants, in tree order.
+    fn focusable_descendants(&self, children: &[WidgetId]) -> Vec<WidgetId> {
+        let mut out = Vec::new();
+        for id in self.visible_children(children) {
+            let widget = self.widget(id).unwrap();
+            if widget.enabled && widget.kind.is_interactive() {
+                out.push(id);
+            }
+            out.extend(self.focusable_descendants(&widget.children));
+        }
+        out
+    }
+
+    fn take_widget(&mut self, id: WidgetId) -> Option<Widget> {
+        let slot = self
+            .slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let widget = slot.widget.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.index);
+        Some(widget)
+    }
+
+    fn remove_subtree(&mut self, id: WidgetId) {
+        if let Some(widget) = self.take_widget(id) {
+            for child in widget.children {
+                self.remove_subtree(child);
+            }
+        }
+    }
+
+    fn visible_children<'a>(
+        &'a self,
+        children: &'a [WidgetId],
+    ) -> impl Iterator<Item = WidgetId> + 'a {
+        children
+            .iter()
+            .copied()
+            .filter(|child| self.widget(*child).map(|w| w.visible) == Some(true))
+    }
+
+    fn layout_children(
+        &mut self,
+        area: &Rect,
+        layout: &ChildrenLayout,
+        children: &[WidgetId],
+        measure: &dyn TextMeasure,
+        arena: &gfx::FrameArena,
+    ) {
+        let children = arena.vec_from_iter(self.visible_children(children));
+        let rects = match layout {
+            ChildrenLayout::Anchored => arena.vec_from_iter(
+                children
+                    .iter()
+                    .map(|child| anchored_rect(area, &self.widget(*child).unwrap().layout)),
+            ),
+            ChildrenLayout::Flex(desc) => {
+                let items = arena.vec_from_iter(children.iter().map(|child| {
+                    (
+                        self.preferred_size(*child, measure, arena),
+                        self.widget(*child).unwrap().layout.grow,
+                    )
+                }));
+                arena.vec_from_iter(flex_rects_iter(area, desc, &items))
+            }
+        };
+        for (child, rect) in children.iter().zip(rects) {
+            let widget = self.widget_mut(*child).unwrap();
+            widget.rect = rect;
+            let area = rect.shrink(widget.layout.padding);
+            let layout = widget.layout.children;
+            let grandchildren = arena.alloc_slice_copy(&widget.children);
+            self.layout_children(&area, &layout, grandchildren, measure, arena);
+        }
+    }
+
+    // Size of the widget inside flex containers: the size in its layout, with zero components
+    // replaced by the size of its content.
+    fn preferred_size(
+        &self,
+        id: WidgetId,
+        measure: &dyn TextMeasure,
+        arena: &gfx::FrameArena,
+    ) -> Vector2<f32> {
+        let widget = self.widget(id).unwrap();
+        let mut size = widget.layout.size;
+        if size.x <= 0. || size.y <= 0. {
+            let content = self.content_size(widget, measure, arena);
+            if size.x <= 0. {
+                size.x = content.x;
+            }
+            if size.y <= 0. {
+                size.y = content.y;
+            }
+        }
+        size
+    }
+
+    fn content_size(
+        &self,
+        widget: &Widget,
+        measure: &dyn TextMeasure,
+        arena: &gfx::FrameArena,
+    ) -> Vector2<f32> {
+        let padding = widget.layout.padding;
+        let padding_size = Vector2::new(padding * 2., padding * 2.);
+        match &widget.kind {
+            WidgetKind::Label { text, style } | WidgetKind::Button { text, style, .. } => {
+                measure.measure_text(style.font, text) + padding_size
+            }
+            WidgetKind::Image { image, .. } => image.region.size + padding_size,
+            WidgetKind::Slider { skin, .. } => skin.thumb.region.size + padding_size,
+            WidgetKind::Panel { .. } => match &widget.layout.children {
+                ChildrenLayout::Flex(desc) => {
+                    let items = arena.vec_from_iter(
+                        self.visible_children(&widget.children)
+                            .map(|child| self.preferred_size(child, measure, arena)),
+                    );
+                    flex_content_size(desc, &items, padding)
+                }
+                ChildrenLayout::Anchored => padding_size,
+            },
+        }
+    }
+
+    // Topmost visible widget under the point. Children are above their parents, and later
+    // siblings above earlier ones.
+    fn hit_test(&self, point: &Vector2<f32>, interactive: bool) -> Option<WidgetId> {
+        self.hit_test_children(&self.roots, point, interactive)
+    }
+
+    fn hit_test_children(
+        &self,
+        children: &[WidgetId],
+        point: &Vector2<f32>,
+        interactive: bool,
+    ) -> Option<WidgetId> {
+        for id in children.iter().rev() {
+            let widget = match self.widget(*id) {
+                Some(widget) if widget.visible => widget,
+                _ => continue,
+            };
+            if let Some(hit) = self.hit_test_children(&widget.children, point, interactive) {
+                return Some(hit);
+            }
+            let accepted = !interactive || (widget.enabled && widget.kind.is_interactive());
+            if accepted && widget.rect.contains(point) {
+                return Some(*id);
+            }
+        }
+        None
+    }
+
+    fn drag(&mut self, id: WidgetId, position: &Vector2<f32>) {
+        let widget = match self.widget_mut(id) {
+            Some(widget) => widget,
+            None => return,
+        };
+        let rect = widget.rect;
+        if let &WidgetKind::Slider {
+            min,
+            max,
+            step,
+            skin,
+            ..
+        } = &widget.kind
+        {
+            let thumb_width = thumb_width(&skin.thumb.region.size, &rect);
+            let track = rect.size.x - thumb_width;
+            let t = if track > 0. {
+                ((position.x - rect.position.x - thumb_width * 0.5) / track).clamp(0., 1.)
+            } else {
+                0.
+            };
+            let mut new_value = min + t * (max - min);
+            if let Some(step) = step.filter(|step| *step > 0.) {
+                new_value = (min + ((new_value - min) / step).round() * step).min(max);
+            }
+            self.set_slider_value(id, new_value);
+        }
+    }
+
+    fn set_slider_value(&mut self, id: WidgetId, new_value: f32) {
+        if let Some(WidgetKind::Slider { value, .. }) = self.widget_mut(id).map(|w| &mut w.kind) {
+            if new_value != *value {
+                *value = new_value;
+                self.events.push_back(UiEvent::ValueChanged {
+                    widget: id,
+                    value: new_value,
+                });
+            }
+        }
+    }
+
+    fn draw_widget(&self, id: WidgetId, list: &mut DrawList, measure: &dyn TextMeasure) {
+        let widget = match self.widget(id) {
+            Some(widget) if widget.visible => widget,
+            _ => return,
+        };
+        let rect = &widget.rect;
+        match &widget.kind {
+            WidgetKind::Panel { background } => {
+                if let Some(background) = background {
+                    push_nine_patch(list, background, rect);
+                }
+            }
+            WidgetKind::Label { text, style } => {
+                let area = rect.shrink(widget.layout.padding);
+                let size = measure.measure_text(style.font, text);
+                let position = Vector2::new(area.position.x, area.center().y - size.y * 0.5);
+                list.push_text(style.font, text, position, style.color);
+            }
+            WidgetKind::Button { text, style, skin } => {
+                push_nine_patch(list, skin.background(self.widget_state(id)), rect);
+                let size = measure.measure_text(style.font, text);
+                list.push_text(style.font, text, rect.center() - size * 0.5, style.color);
+            }
+            WidgetKind::Slider {
+                value,
+                min,
+                max,
+                skin,
+                ..
+            } => {
+                push_nine_patch(list, &skin.track, rect);
+                let thumb_width = thumb_width(&skin.thumb.region.size, rect);
+                let t = if max > min {
+                    ((value - min) / (max - min)).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                let x = rect.position.x + t * (rect.size.x - thumb_width).max(0.);
+                list.push_quad(
+                    skin.thumb.texture,
+                    gfx::ColorF32::WHITE,
+                    &Quad {
+                        rect: Rect::new(x, rect.position.y, thumb_width, rect.size.y),
+                        texture_coordinates: skin.thumb.texture_coordinates(),
+                    },
+                );
+            }
+            WidgetKind::Image { image, color } => {
+                list.push_quad(
+                    image.texture,
+                    *color,
+                    &Quad {
+                        rect: *rect,
+                        texture_coordinates: image.texture_coordinates(),
+                    },
+                );
+            }
+        }
+        for child in widget.children.iter() {
+            self.draw_widget(*child, list, measure);
+        }
+        if self.focused == Some(id) {
+            if let Some(highlight) = &self.focus_highlight {
+                // A negative shrink grows the rectangle by the margin.
+                push_nine_patch(list, &highlight.patch, &rect.shrink(-highlight.margin));
+            }
+        }
+    }
+}
+
+// Width of the slider thumb scaled to the height of the slider.
+fn thumb_width(thumb_size: &Vector2<f32>, rect: &Rect) -> f32 {
+    if thumb_size.y > 0. {
+        thumb_size.x * rect.size.y / thumb_size.y
+    } else {
+        0.
+    }
+}
+
+fn push_nine_patch(list: &mut DrawList, patch: &NinePatch, rect: &Rect) {
+    for quad in patch.quads_in(rect, list.arena()) {
+        list.push_quad(patch.image.texture, gfx::ColorF32::WHITE, &quad);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ButtonSkin, DrawCommand, FlexAlign, FlexDescriptor, FlexDirection, ImageRegion,
+        LayoutDescriptor, SliderSkin, TextStyle, TextureId,
+    };
+    use galvanic_assert::{matchers::*, *};
+
+    // Monospace font with 8x16 pixels glyphs.
+    struct Monospace;
+
+    impl TextMeasure for Monospace {
+        fn measure_text(&self, _font: FontId, text: &str) -> Vector2<f32> {
+            Vector2::new(text.chars().count() as f32 * 8., 16.)
+        }
+    }
+
+    fn style() -> TextStyle {
+        TextStyle {
+            font: FontId(0),
+            color: gfx::ColorF32::WHITE,
+        }
+    }
+
+    fn patch() -> NinePatch {
+        NinePatch::new(
+            ImageRegion::whole(TextureId(0), Vector2::new(16., 16.)),
+            [4., 4., 4., 4.],
+        )
+    }
+
+    fn button(text: &str, layout: LayoutDescriptor) -> Widget {
+        Widget::new(
+            WidgetKind::Button {
+                text: String::from(text),
+                style: style(),
+                skin: ButtonSkin::uniform(patch()),
+            },
+            layout,
+        )
+    }
+
+    fn slider(step: Option<f32>) -> Widget {
+        Widget::new(
+            WidgetKind::Slider {
+                value: 0.,
+                min: 0.,
+                max: 10.,
+                step,
+                skin: SliderSkin {
+                    track: patch(),
+                    thumb: ImageRegion::whole(TextureId(1), Vector2::new(8., 8.)),
+                },
+            },
+            LayoutDescriptor::anchored(
+                Vector2::zeros(),
+                Vector2::new(0., 100.),
+                Vector2::new(120., 20.),
+            ),
+        )
+    }
+
+    fn menu() -> (Ui, WidgetId, Vec<WidgetId>) {
+        let mut ui = Ui::new(Vector2::new(800., 600.));
+        let panel = ui.add(
+            None,
+            Widget::new(
+                WidgetKind::Panel {
+                    background: Some(patch()),
+                },
+                LayoutDescriptor {
+                    padding: 10.,
+                    children: ChildrenLayout::Flex(FlexDescriptor {
+                        direction: FlexDirection::Column,
+                        spacing: 5.,
+                        align: FlexAlign::Center,
+                    }),
+                    ..LayoutDescriptor::anchored(
+                        Vector2::new(0.5, 0.5),
+                        Vector2::new(-100., -100.),
+                        Vector2::new(200., 200.),
+                    )
+                },
+            ),
+        );
+        let buttons = vec![
+            ui.add(
+                Some(panel),
+                button(
+                    "Play",
+                    LayoutDescriptor {
+                        padding: 4.,
+                        ..LayoutDescriptor::default()
+                    },
+                ),
+            ),
+            ui.add(
+                Some(panel),
+                button(
+                    "Options",
+                    LayoutDescriptor {
+                        grow: 1.,
+                        ..LayoutDescriptor::sized(150., 0.)
+                    },
+                ),
+            ),
+            ui.add(
+                Some(panel),
+                button("Quit", LayoutDescriptor::sized(100., 30.)),
+            ),
+        ];
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+        (ui, panel, buttons)
+    }
+
+    #[test]
+    fn layout() {
+        let (ui, panel, buttons) = menu();
+        expect_that!(
+            ui.widget(panel).unwrap().rect(),
+            eq(Rect::new(300., 200., 200., 200.))
+        );
+        expect_that!(
+            ui.widget(buttons[0]).unwrap().rect(),
+            eq(Rect::new(380., 210., 40., 24.))
+        );
+        // Takes the space left: 180 - 24 - 30 - 2 * 5.
+        expect_that!(
+            ui.widget(buttons[1]).unwrap().rect(),
+            eq(Rect::new(325., 239., 150., 116.))
+        );
+        expect_that!(
+            ui.widget(buttons[2]).unwrap().rect(),
+            eq(Rect::new(350., 360., 100., 30.))
+        );
+    }
+
+    #[test]
+    fn hidden_widgets() {
+        let (mut ui, _, buttons) = menu();
+        ui.widget_mut(buttons[1]).unwrap().visible = false;
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+        expect_that!(
+            ui.widget(buttons[2]).unwrap().rect(),
+            eq(Rect::new(350., 239., 100., 30.))
+        );
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(400., 300.)));
+        expect_that!(&ui.hovered(), eq(None));
+    }
+
+    #[test]
+    fn click() {
+        let (mut ui, _, buttons) = menu();
+        expect_that!(ui.handle_input(UiInput::PointerMoved(Vector2::new(400., 220.))));
+        expect_that!(&ui.hovered(), eq(Some(buttons[0])));
+        ui.handle_input(UiInput::PointerPressed);
+        expect_that!(&ui.widget_state(buttons[0]), eq(WidgetState::Pressed));
+        ui.handle_input(UiInput::PointerReleased);
+        expect_that!(&ui.poll_event(), eq(Some(UiEvent::Clicked(buttons[0]))));
+        expect_that!(&ui.poll_event(), eq(None));
+
+        // Releasing outside of the button cancels the click.
+        ui.handle_input(UiInput::PointerPressed);
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(10., 10.)));
+        expect_that!(&ui.widget_state(buttons[0]), eq(WidgetState::Pressed));
+        ui.handle_input(UiInput::PointerReleased);
+        expect_that!(&ui.poll_event(), eq(None));
+        expect_that!(!ui.handle_input(UiInput::PointerMoved(Vector2::new(10., 10.))));
+
+        // The panel captures the input but doesn't react to it.
+        expect_that!(ui.handle_input(UiInput::PointerMoved(Vector2::new(305., 205.))));
+        expect_that!(&ui.hovered(), eq(None));
+
+        ui.widget_mut(buttons[2]).unwrap().enabled = false;
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(400., 370.)));
+        ui.handle_input(UiInput::PointerPressed);
+        ui.handle_input(UiInput::PointerReleased);
+        expect_that!(&ui.widget_state(buttons[2]), eq(WidgetState::Disabled));
+        expect_that!(&ui.poll_event(), eq(None));
+    }
+
+    #[test]
+    fn slider_drag() {
+        let mut ui = Ui::new(Vector2::new(800., 600.));
+        let id = ui.add(None, slider(Some(2.5)));
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+
+        // The thumb is 20 pixels wide, so the track spans from 10 to 110.
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(60., 110.)));
+        ui.handle_input(UiInput::PointerPressed);
+        expect_that!(
+            &ui.poll_event(),
+            eq(Some(UiEvent::ValueChanged {
+                widget: id,
+                value: 5.
+            }))
+        );
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(68., 200.)));
+        expect_that!(&ui.poll_event(), eq(None));
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(500., 200.)));
+        expect_that!(
+            &ui.poll_event(),
+            eq(Some(UiEvent::ValueChanged {
+                widget: id,
+                value: 10.
+            }))
+        );
+        ui.handle_input(UiInput::PointerReleased);
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(0., 200.)));
+        expect_that!(&ui.poll_event(), eq(None));
+    }
+
+    #[test]
+    fn removal() {
+        let (mut ui, panel, buttons) = menu();
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(400., 220.)));
+        expect_that!(ui.remove(panel).is_some());
+        expect_that!(ui.is_empty());
+        expect_that!(!ui.contains(buttons[0]));
+        expect_that!(&ui.hovered(), eq(None));
+    }
+
+    #[test]
+    fn draw_list() {
+        let (mut ui, _, _) = menu();
+        ui.add(None, slider(None));
+        let arena = gfx::FrameArena::new();
+        ui.layout_in(&Monospace, &arena);
+        let list = ui.draw_list_in(&Monospace, &arena);
+
+        // The panel and the buttons share the same texture, but the texts break the batches.
+        let kinds: Vec<_> = list
+            .commands()
+            .iter()
+            .map(|command| match command {
+                DrawCommand::Sprites {
+                    texture, indices, ..
+                } => (texture.0, indices.len() / 6),
+                DrawCommand::Text { .. } => (100, 0),
+            })
+            .collect();
+        expect_that!(
+            &kinds,
+            eq(vec![
+                (0, 18),
+                (100, 0),
+                (0, 9),
+                (100, 0),
+                (0, 9),
+                (100, 0),
+                (0, 9),
+                (1, 1),
+            ])
+        );
+        match &list.commands()[1] {
+            DrawCommand::Text { text, position, .. } => {
+                expect_that!(text, eq("Play"));
+                expect_that!(&position.x, close_to(384., 1e-5));
+                expect_that!(&position.y, close_to(214., 1e-5));
+            }
+            _ => panic!("Unexpected draw command"),
+        }
+    }
+
+    fn drain_events(ui: &mut Ui) -> Vec<UiEvent> {
+        std::iter::from_fn(|| ui.poll_event()).collect()
+    }
+
+    #[test]
+    fn focus_navigation() {
+        let (mut ui, _, buttons) = menu();
+        expect_that!(ui.handle_input(UiInput::Navigate(NavigationDirection::Down)));
+        expect_that!(
+            &drain_events(&mut ui),
+            eq(vec![UiEvent::FocusChanged(Some(buttons[0]))])
+        );
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Down));
+        expect_that!(&ui.focused(), eq(Some(buttons[1])));
+        expect_that!(&ui.widget_state(buttons[1]), eq(WidgetState::Focused));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Down));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Down));
+        expect_that!(&ui.focused(), eq(Some(buttons[2])));
+        expect_that!(&drain_events(&mut ui).len(), eq(2));
+
+        ui.set_root_focus_scope(FocusScope { wrap: true });
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Down));
+        expect_that!(&ui.focused(), eq(Some(buttons[0])));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Up));
+        expect_that!(&ui.focused(), eq(Some(buttons[2])));
+
+        // Disabled and hidden widgets are skipped.
+        ui.widget_mut(buttons[1]).unwrap().enabled = false;
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Up));
+        expect_that!(&ui.focused(), eq(Some(buttons[0])));
+        ui.widget_mut(buttons[0]).unwrap().visible = false;
+        expect_that!(!ui.is_focusable(buttons[0]));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Left));
+        expect_that!(&ui.focused(), eq(Some(buttons[2])));
+    }
+
+    #[test]
+    fn focus_scope() {
+        let (mut ui, _, buttons) = menu();
+        let dialog = ui.add(
+            None,
+            Widget {
+                focus_scope: Some(FocusScope::default()),
+                ..Widget::new(
+                    WidgetKind::Panel { background: None },
+                    LayoutDescriptor {
+                        children: ChildrenLayout::Flex(FlexDescriptor {
+                            direction: FlexDirection::Row,
+                            ..FlexDescriptor::default()
+                        }),
+                        ..LayoutDescriptor::anchored(
+                            Vector2::zeros(),
+                            Vector2::zeros(),
+                            Vector2::new(200., 50.),
+                        )
+                    },
+                )
+            },
+        );
+        let yes = ui.add(
+            Some(dialog),
+            button("Yes", LayoutDescriptor::sized(100., 0.)),
+        );
+        let no = ui.add(
+            Some(dialog),
+            button("No", LayoutDescriptor::sized(100., 0.)),
+        );
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+
+        ui.set_focus(Some(yes));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Right));
+        expect_that!(&ui.focused(), eq(Some(no)));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Right));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Down));
+        expect_that!(&ui.focused(), eq(Some(no)));
+
+        // Outside of the dialog the whole ui is reachable.
+        ui.set_focus(Some(buttons[0]));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Up));
+        expect_that!(&ui.focused(), eq(Some(no)));
+
+        ui.remove(dialog);
+        expect_that!(&ui.focused(), eq(None));
+    }
+
+    #[test]
+    fn activation() {
+        let (mut ui, _, buttons) = menu();
+        expect_that!(!ui.handle_input(UiInput::ActivatePressed));
+        ui.handle_input(UiInput::ActivateReleased);
+
+        ui.set_focus(Some(buttons[1]));
+        drain_events(&mut ui);
+        expect_that!(ui.handle_input(UiInput::ActivatePressed));
+        expect_that!(&ui.widget_state(buttons[1]), eq(WidgetState::Pressed));
+        ui.handle_input(UiInput::ActivateReleased);
+        expect_that!(
+            &drain_events(&mut ui),
+            eq(vec![UiEvent::Clicked(buttons[1])])
+        );
+
+        // Moving the focus cancels the activation.
+        ui.handle_input(UiInput::ActivatePressed);
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Down));
+        ui.handle_input(UiInput::ActivateReleased);
+        expect_that!(
+            &drain_events(&mut ui),
+            eq(vec![UiEvent::FocusChanged(Some(buttons[2]))])
+        );
+        expect_that!(&ui.widget_state(buttons[1]), eq(WidgetState::Normal));
+    }
+
+    #[test]
+    fn slider_navigation() {
+        let mut ui = Ui::new(Vector2::new(800., 600.));
+        let id = ui.add(None, slider(Some(2.5)));
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+        ui.set_focus(Some(id));
+        drain_events(&mut ui);
+
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Right));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Left));
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Left));
+        expect_that!(
+            &drain_events(&mut ui),
+            eq(vec![
+                UiEvent::ValueChanged {
+                    widget: id,
+                    value: 2.5
+                },
+                UiEvent::ValueChanged {
+                    widget: id,
+                    value: 0.
+                },
+            ])
+        );
+        ui.handle_input(UiInput::Navigate(NavigationDirection::Up));
+        expect_that!(&ui.focused(), eq(Some(id)));
+    }
+
+    #[test]
+    fn focus_highlight() {
+        let (mut ui, _, buttons) = menu();
+        ui.set_focus_highlight(Some(FocusHighlight {
+            patch: NinePatch::new(
+                ImageRegion::whole(TextureId(2), Vector2::new(16., 16.)),
+                [4., 4., 4., 4.],
+            ),
+            margin: 2.,
+        }));
+        ui.set_focus(Some(buttons[0]));
+        let arena = gfx::FrameArena::new();
+        let list = ui.draw_list_in(&Monospace, &arena);
+        expect_that!(&list.commands().len(), eq(7));
+        match &list.commands()[2] {
+            DrawCommand::Sprites {
+                texture, vertices, ..
+            } => {
+                expect_that!(texture, eq(TextureId(2)));
+                expect_that!(&vertices.len(), eq(36));
+                expect_that!(
+                    &vertices[0],
+                    eq(roe_sprite::Vertex::new([378., 208.], [0., 0.]))
+                );
+            }
+            _ => panic!("Unexpected draw command"),
+        }
+    }
+
+    #[test]
+    fn scale() {
+        let (mut ui, panel, buttons) = menu();
+        ui.set_scale(2.);
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+        expect_that!(&ui.logical_size(), eq(Vector2::new(400., 300.)));
+        expect_that!(
+            ui.widget(panel).unwrap().rect(),
+            eq(Rect::new(100., 50., 200., 200.))
+        );
+
+        // Pointer positions are in screen pixels.
+        ui.handle_input(UiInput::PointerMoved(Vector2::new(400., 140.)));
+        expect_that!(&ui.hovered(), eq(Some(buttons[0])));
+
+        let corner = ui.projection() * roe_math::Vector3::new(400., 300., 1.);
+        expect_that!(&corner.x, close_to(1., 1e-6));
+        expect_that!(&corner.y, close_to(-1., 1e-6));
+    }
+
+    #[test]
+    #[should_panic(expected = "The ui scale must be higher than 0")]
+    fn invalid_scale() {
+        Ui::new(Vector2::new(800., 600.)).set_scale(0.);
+    }
+
+    #[test]
+    fn translate_texts() {
+        let (mut ui, panel, buttons) = menu();
+        ui.widget_mut(buttons[0]).unwrap().text_key = Some(String::from("menu.play"));
+        ui.widget_mut(panel).unwrap().text_key = Some(String::from("menu.panel"));
+        ui.translate_texts(&|key| match key {
+            "menu.play" => String::from("Giocare"),
+            _ => String::from(key),
+        });
+        ui.layout_in(&Monospace, &gfx::FrameArena::new());
+        match &ui.widget(buttons[0]).unwrap().kind {
+            WidgetKind::Button { text, .. } => {
+                expect_that!(text, eq(String::from("Giocare")));
+            }
+            _ => panic!("Unexpected widget kind"),
+        }
+        // The button grows to fit the longer text.
+        expect_that!(&ui.widget(buttons[0]).unwrap().rect().size.x, eq(64.));
+        match &ui.widget(buttons[1]).unwrap().kind {
+            WidgetKind::Button { text, .. } => {
+                expect_that!(text, eq(String::from("Options")));
+            }
+            _ => panic!("Unexpected widget kind"),
+        }
+    }
+}
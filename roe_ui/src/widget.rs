@@ -0,0 +1,138 @@
+use super::{FocusScope, FontId, ImageRegion, LayoutDescriptor, NinePatch, Rect, WidgetId};
+
+use roe_graphics as gfx;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TextStyle {
+    pub font: FontId,
+    pub color: gfx::ColorF32,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum WidgetState {
+    Normal,
+    Hovered,
+    Focused,
+    Pressed,
+    Disabled,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ButtonSkin {
+    pub normal: NinePatch,
+    pub hovered: NinePatch,
+    pub focused: NinePatch,
+    pub pressed: NinePatch,
+    pub disabled: NinePatch,
+}
+
+impl ButtonSkin {
+    // Same background for every state.
+    pub fn uniform(background: NinePatch) -> Self {
+        Self {
+            normal: background,
+            hovered: background,
+            focused: background,
+            pressed: background,
+            disabled: background,
+        }
+    }
+
+    pub fn background(&self, state: WidgetState) -> &NinePatch {
+        match state {
+            WidgetState::Normal => &self.normal,
+            WidgetState::Hovered => &self.hovered,
+            WidgetState::Focused => &self.focused,
+            WidgetState::Pressed => &self.pressed,
+            WidgetState::Disabled => &self.disabled,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SliderSkin {
+    pub track: NinePatch,
+    // Scaled to the height of the slider, keeping its aspect ratio.
+    pub thumb: ImageRegion,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum WidgetKind {
+    Panel {
+        background: Option<NinePatch>,
+    },
+    Label {
+        text: String,
+        style: TextStyle,
+    },
+    Button {
+        text: String,
+        style: TextStyle,
+        skin: ButtonSkin,
+    },
+    Slider {
+        value: f32,
+        min: f32,
+        max: f32,
+        // If set, the value snaps to multiples of the step from min.
+        step: Option<f32>,
+        skin: SliderSkin,
+    },
+    Image {
+        image: ImageRegion,
+        color: gfx::ColorF32,
+    },
+}
+
+impl WidgetKind {
+    // Whether the widget reacts to pointer input.
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, WidgetKind::Button { .. } | WidgetKind::Slider { .. })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Widget {
+    pub kind: WidgetKind,
+    pub layout: LayoutDescriptor,
+    // Hidden widgets and their children aren't drawn and don't receive input.
+    pub visible: bool,
+    // Disabled widgets are drawn but don't react to input.
+    pub enabled: bool,
+    // Keeps focus navigation inside the descendants of the widget.
+    pub focus_scope: Option<FocusScope>,
+    // Translation key of the label or button text, see Ui::translate_texts.
+    pub text_key: Option<String>,
+    pub(crate) parent: Option<WidgetId>,
+    pub(crate) children: Vec<WidgetId>,
+    pub(crate) rect: Rect,
+}
+
+impl Widget {
+    pub fn new(kind: WidgetKind, layout: LayoutDescriptor) -> Self {
+        Self {
+            kind,
+            layout,
+            visible: true,
+            enabled: true,
+            focus_scope: None,
+            text_key: None,
+            parent: None,
+            children: Vec::new(),
+            rect: Rect::default(),
+        }
+    }
+
+    pub fn parent(&self) -> Option<WidgetId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[WidgetId] {
+        &self.children
+    }
+
+    // Rectangle computed by the last layout pass.
+    pub fn rect(&self) -> &Rect {
+        &self.rect
+    }
+}